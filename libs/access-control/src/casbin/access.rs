@@ -1,5 +1,6 @@
 use super::adapter::PgAdapter;
 use super::enforcer::AFEnforcer;
+use super::reload::{spawn_policy_version_follower, PolicyReloader};
 use crate::act::{Action, Acts};
 use crate::entity::{ObjectType, SubjectType};
 use crate::metrics::{tick_metric, AccessControlMetrics};
@@ -9,6 +10,7 @@ use app_error::AppError;
 use casbin::function_map::OperatorFunction;
 use casbin::rhai::{Dynamic, ImmutableString};
 use casbin::{CoreApi, DefaultModel, Enforcer, MgmtApi};
+use database::access_policy::select_access_policy_version;
 use database_entity::dto::{AFAccessLevel, AFRole};
 
 use sqlx::PgPool;
@@ -31,6 +33,8 @@ use tracing::trace;
 #[derive(Clone)]
 pub struct AccessControl {
   enforcer: Arc<AFEnforcer>,
+  /// `None` when the policies aren't loaded from Postgres.
+  reloader: Option<Arc<PolicyReloader>>,
   #[allow(dead_code)]
   access_control_metrics: Arc<AccessControlMetrics>,
 }
@@ -40,20 +44,22 @@ impl AccessControl {
     pg_pool: PgPool,
     access_control_metrics: Arc<AccessControlMetrics>,
   ) -> Result<Self, AppError> {
-    let model = casbin_model().await?;
-    let adapter = PgAdapter::new(pg_pool.clone(), access_control_metrics.clone());
-    let mut enforcer = casbin::Enforcer::new(model, adapter).await.map_err(|e| {
-      AppError::Internal(anyhow!("Failed to create access control enforcer: {}", e))
-    })?;
-    enforcer.add_function("cmpRoleOrLevel", OperatorFunction::Arg2(cmp_role_or_level));
-
-    let enforcer = Arc::new(AFEnforcer::new(enforcer).await?);
+    let version = select_access_policy_version(&pg_pool).await?;
+    let enforcer = create_enforcer(pg_pool.clone(), access_control_metrics.clone()).await?;
+    let enforcer = Arc::new(AFEnforcer::new_at_version(enforcer, version).await?);
     tick_metric(
       enforcer.metrics_state.clone(),
       access_control_metrics.clone(),
     );
+    let reloader = Arc::new(PolicyReloader::new(
+      pg_pool.clone(),
+      access_control_metrics.clone(),
+      enforcer.clone(),
+    ));
+    spawn_policy_version_follower(Arc::downgrade(&reloader), pg_pool);
     Ok(Self {
       enforcer,
+      reloader: Some(reloader),
       access_control_metrics,
     })
   }
@@ -63,10 +69,35 @@ impl AccessControl {
     let access_control_metrics = Arc::new(AccessControlMetrics::init());
     Self {
       enforcer: Arc::new(enforcer),
+      reloader: None,
       access_control_metrics,
     }
   }
 
+  /// Version of the enforced policies.
+  pub fn policy_version(&self) -> i64 {
+    self.enforcer.version()
+  }
+
+  /// Makes sure the enforced policies include the changes of `version`, loading them first when
+  /// this server is behind. Returns AppError::InvalidRequest when the version doesn't exist yet.
+  pub async fn require_policy_version(&self, version: i64) -> Result<(), AppError> {
+    if self.enforcer.version() >= version {
+      return Ok(());
+    }
+    if let Some(reloader) = &self.reloader {
+      reloader.reload_if_outdated().await?;
+    }
+    if self.enforcer.version() >= version {
+      Ok(())
+    } else {
+      Err(AppError::InvalidRequest(format!(
+        "access policy version {} doesn't exist",
+        version
+      )))
+    }
+  }
+
   pub async fn update_policy<T>(
     &self,
     sub: SubjectType,
@@ -164,6 +195,20 @@ e = some(where (p.eft == allow))
 m = r.sub == p.sub && p.obj == r.obj && (g(p.act, r.act) || cmpRoleOrLevel(r.act, p.act))
 "###;
 
+/// Creates an enforcer holding all the policies stored in Postgres.
+pub(crate) async fn create_enforcer(
+  pg_pool: PgPool,
+  access_control_metrics: Arc<AccessControlMetrics>,
+) -> Result<Enforcer, AppError> {
+  let model = casbin_model().await?;
+  let adapter = PgAdapter::new(pg_pool, access_control_metrics);
+  let mut enforcer = casbin::Enforcer::new(model, adapter)
+    .await
+    .map_err(|e| AppError::Internal(anyhow!("Failed to create access control enforcer: {}", e)))?;
+  enforcer.add_function("cmpRoleOrLevel", OperatorFunction::Arg2(cmp_role_or_level));
+  Ok(enforcer)
+}
+
 pub async fn casbin_model() -> Result<DefaultModel, AppError> {
   let model = casbin::DefaultModel::from_str(MODEL_CONF)
     .await
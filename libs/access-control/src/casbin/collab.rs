@@ -165,6 +165,10 @@ impl RealtimeAccessControl for RealtimeCollabAccessControlImpl {
       .can_perform_action(workspace_id, uid, oid, Action::Read)
       .await
   }
+
+  fn policy_version(&self) -> i64 {
+    self.access_control.policy_version()
+  }
 }
 
 #[cfg(test)]
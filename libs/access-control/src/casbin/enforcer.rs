@@ -6,24 +6,50 @@ use crate::request::PolicyRequest;
 use anyhow::anyhow;
 use app_error::AppError;
 use casbin::{CoreApi, Enforcer, MgmtApi};
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicI64, Ordering};
 use tokio::sync::RwLock;
 use tracing::{event, instrument, trace};
 
 pub struct AFEnforcer {
   enforcer: RwLock<Enforcer>,
+  /// Version of the policies loaded from Postgres. The policies updated in memory afterwards are
+  /// only counted once they are loaded again.
+  version: AtomicI64,
   pub(crate) metrics_state: MetricsCalState,
 }
 
 impl AFEnforcer {
-  pub async fn new(mut enforcer: Enforcer) -> Result<Self, AppError> {
+  pub async fn new(enforcer: Enforcer) -> Result<Self, AppError> {
+    Self::new_at_version(enforcer, 0).await
+  }
+
+  pub async fn new_at_version(mut enforcer: Enforcer, version: i64) -> Result<Self, AppError> {
     load_group_policies(&mut enforcer).await?;
     Ok(Self {
       enforcer: RwLock::new(enforcer),
+      version: AtomicI64::new(version),
       metrics_state: MetricsCalState::new(),
     })
   }
 
+  pub fn version(&self) -> i64 {
+    self.version.load(Ordering::Acquire)
+  }
+
+  /// Replaces all the policies with the ones of `enforcer`, loaded at `version`. A request is
+  /// enforced either with the previous policies or with the new ones, never with a mix of both.
+  /// Returns Ok(false) when policies of a later version are already loaded.
+  pub async fn replace(&self, mut enforcer: Enforcer, version: i64) -> Result<bool, AppError> {
+    load_group_policies(&mut enforcer).await?;
+    let mut current = self.enforcer.write().await;
+    if version < self.version() {
+      return Ok(false);
+    }
+    *current = enforcer;
+    self.version.store(version, Ordering::Release);
+    Ok(true)
+  }
+
   /// Update policy for a user.
   /// If the policy is already exist, then it will return Ok(false).
   ///
@@ -152,7 +178,7 @@ async fn policies_for_subject_with_given_object(
 #[cfg(test)]
 pub(crate) mod tests {
   use crate::{
-    act::Action,
+    act::{Action, Acts},
     casbin::access::{casbin_model, cmp_role_or_level},
     entity::{ObjectType, SubjectType},
   };
@@ -161,14 +187,56 @@ pub(crate) mod tests {
 
   use super::AFEnforcer;
 
-  pub async fn test_enforcer() -> AFEnforcer {
+  async fn memory_enforcer() -> Enforcer {
     let model = casbin_model().await.unwrap();
     let mut enforcer = casbin::Enforcer::new(model, MemoryAdapter::default())
       .await
       .unwrap();
 
     enforcer.add_function("cmpRoleOrLevel", OperatorFunction::Arg2(cmp_role_or_level));
-    AFEnforcer::new(enforcer).await.unwrap()
+    enforcer
+  }
+
+  pub async fn test_enforcer() -> AFEnforcer {
+    AFEnforcer::new(memory_enforcer().await).await.unwrap()
+  }
+
+  #[tokio::test]
+  async fn replace_policies_test() {
+    let enforcer = test_enforcer().await;
+    let workspace = ObjectType::Workspace("w1".to_string());
+    enforcer
+      .update_policy(SubjectType::User(1), workspace.clone(), AFRole::Owner)
+      .await
+      .unwrap();
+
+    let mut loaded = memory_enforcer().await;
+    loaded
+      .add_policy(vec![
+        "2".to_string(),
+        workspace.policy_object(),
+        AFRole::Member.to_enforce_act(),
+      ])
+      .await
+      .unwrap();
+    assert!(enforcer.replace(loaded, 2).await.unwrap());
+    assert_eq!(enforcer.version(), 2);
+    // the policies updated in memory are replaced by the loaded ones
+    assert!(!enforcer
+      .enforce_policy(&1, workspace.clone(), Action::Read)
+      .await
+      .unwrap());
+    assert!(enforcer
+      .enforce_policy(&2, workspace.clone(), Action::Write)
+      .await
+      .unwrap());
+
+    // policies of an earlier version never replace the loaded ones
+    assert!(!enforcer.replace(memory_enforcer().await, 1).await.unwrap());
+    assert!(enforcer
+      .enforce_policy(&2, workspace, Action::Read)
+      .await
+      .unwrap());
   }
 
   #[tokio::test]
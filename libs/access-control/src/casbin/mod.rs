@@ -2,4 +2,5 @@ pub mod access;
 mod adapter;
 pub mod collab;
 mod enforcer;
+mod reload;
 pub mod workspace;
@@ -0,0 +1,113 @@
+//! Keeps the policies enforced by every server at the same version.
+//!
+//! Each change of the workspace members bumps the version of the policies in Postgres, which is
+//! announced on [POLICY_VERSION_CHANNEL]. Each server then loads all the policies again and swaps
+//! them at once, see [AFEnforcer::replace]. The version is also polled, since announcements are
+//! missed while the connection to Postgres is lost.
+
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use app_error::AppError;
+use database::access_policy::select_access_policy_version;
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use super::access::create_enforcer;
+use super::enforcer::AFEnforcer;
+use crate::metrics::AccessControlMetrics;
+
+pub const POLICY_VERSION_CHANNEL: &str = "af_access_policy_version_channel";
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+pub(crate) struct PolicyReloader {
+  pg_pool: PgPool,
+  access_control_metrics: Arc<AccessControlMetrics>,
+  enforcer: Arc<AFEnforcer>,
+  reloading: Mutex<()>,
+}
+
+impl PolicyReloader {
+  pub(crate) fn new(
+    pg_pool: PgPool,
+    access_control_metrics: Arc<AccessControlMetrics>,
+    enforcer: Arc<AFEnforcer>,
+  ) -> Self {
+    Self {
+      pg_pool,
+      access_control_metrics,
+      enforcer,
+      reloading: Mutex::new(()),
+    }
+  }
+
+  /// Loads the policies again when a later version is stored in Postgres. Returns the version
+  /// of the enforced policies.
+  pub(crate) async fn reload_if_outdated(&self) -> Result<i64, AppError> {
+    // Concurrent reloads would all load the same policies.
+    let _reloading = self.reloading.lock().await;
+    // The version is read before the policies, so the loaded policies include at least the
+    // changes of that version.
+    let version = select_access_policy_version(&self.pg_pool).await?;
+    if version <= self.enforcer.version() {
+      return Ok(self.enforcer.version());
+    }
+    let enforcer =
+      create_enforcer(self.pg_pool.clone(), self.access_control_metrics.clone()).await?;
+    if self.enforcer.replace(enforcer, version).await? {
+      info!("[access control]: reloaded policies of version {}", version);
+    }
+    Ok(self.enforcer.version())
+  }
+}
+
+/// Reloads the policies each time a new version is announced, and every [POLL_INTERVAL].
+pub(crate) fn spawn_policy_version_follower(reloader: Weak<PolicyReloader>, pg_pool: PgPool) {
+  tokio::spawn(async move {
+    let mut listener = loop {
+      match listen_policy_version(&pg_pool).await {
+        Ok(listener) => break listener,
+        Err(err) => {
+          warn!("failed to listen to the access policy version: {}", err);
+          tokio::time::sleep(RECONNECT_INTERVAL).await;
+        },
+      }
+    };
+    let mut poll = tokio::time::interval(POLL_INTERVAL);
+    loop {
+      let announced = tokio::select! {
+        notification = listener.try_recv() => match notification {
+          Ok(Some(notification)) => notification.payload().parse::<i64>().ok(),
+          // The connection was lost and is opened again by the next call
+          Ok(None) => None,
+          Err(err) => {
+            warn!("failed to receive the access policy version: {}", err);
+            tokio::time::sleep(RECONNECT_INTERVAL).await;
+            None
+          },
+        },
+        _ = poll.tick() => None,
+      };
+
+      let reloader = match reloader.upgrade() {
+        Some(reloader) => reloader,
+        None => return,
+      };
+      if matches!(announced, Some(version) if version <= reloader.enforcer.version()) {
+        continue;
+      }
+      if let Err(err) = reloader.reload_if_outdated().await {
+        error!("failed to reload the access control policies: {}", err);
+      }
+    }
+  });
+}
+
+async fn listen_policy_version(pg_pool: &PgPool) -> Result<PgListener, sqlx::Error> {
+  let mut listener = PgListener::connect_with(pg_pool).await?;
+  listener.listen(POLICY_VERSION_CHANNEL).await?;
+  Ok(listener)
+}
@@ -62,4 +62,10 @@ pub trait RealtimeAccessControl: Sync + Send + 'static {
     uid: &i64,
     oid: &str,
   ) -> Result<bool, AppError>;
+
+  /// Version of the enforced policies, which changes with the role of any member. The access
+  /// negotiated by the realtime subscriptions is checked again when it changes.
+  fn policy_version(&self) -> i64 {
+    0
+  }
 }
@@ -73,6 +73,12 @@ pub enum AppError {
   #[error("s3 response error:{0}")]
   S3ResponseError(String),
 
+  #[error("s3 access denied:{0}")]
+  S3AccessDenied(String),
+
+  #[error("s3 request rate exceeded:{0}")]
+  S3SlowDown(String),
+
   #[error("Storage space not enough")]
   StorageSpaceNotEnough,
 
@@ -156,6 +162,68 @@ pub enum AppError {
   #[error("{0}")]
   TooManyImportTask(String),
 
+  #[error("{0}")]
+  ExportTaskAlreadyRunning(String),
+
+  /// The workspaces can't be merged right now, for instance because one of them has an import
+  /// or another merge in progress.
+  #[error("{0}")]
+  WorkspaceMergeBlocked(String),
+
+  #[error("{0}")]
+  StaleDryRun(String),
+
+  #[error("{0}")]
+  EditingLocked(String),
+
+  #[error("{0}")]
+  TooManyRequests(String),
+
+  /// The query was cancelled by Postgres for running longer than its statement timeout. The
+  /// request can be retried.
+  #[error("Statement timeout: {0}")]
+  StatementTimeout(String),
+
+  /// The view was taken down by a moderator and can't be published again unless an admin lifts
+  /// the takedown.
+  #[error("{0}")]
+  PublishTakenDown(String),
+
+  /// The collab is end-to-end encrypted, the server can't read it to run the requested feature.
+  #[error("Not available for encrypted objects: {0}")]
+  EncryptedCollab(String),
+
+  /// The AI feature was turned off in the AI settings of the workspace.
+  #[error("{0}")]
+  AIFeatureDisabled(String),
+
+  /// The invitation is past its expiry, the invitee has to ask the workspace owner to send it
+  /// again.
+  #[error("The invitation has expired, ask the workspace owner to send it again: {0}")]
+  InvitationExpired(String),
+
+  /// The pagination cursor wasn't issued by the list endpoint it was sent to, or was altered.
+  #[error("Invalid cursor: {0}")]
+  InvalidCursor(String),
+
+  /// The file is larger than the subscription plan of the workspace allows.
+  #[error(
+    "The file is {size} bytes, but the plan of the workspace allows files up to {limit} bytes"
+  )]
+  FileTooLarge { size: u64, limit: u64 },
+
+  /// An update or the encoded state of a collab is larger than the server accepts.
+  #[error("The collab is {actual} bytes, which exceeds the limit of {limit} bytes")]
+  CollabSizeExceeded { limit: usize, actual: usize },
+
+  /// The collab doesn't have the structure of its type, ie. it was written under the object id of
+  /// a collab of another type.
+  #[error("The {collab_type} collab is missing {}", missing.join(", "))]
+  InvalidCollabStructure {
+    collab_type: String,
+    missing: Vec<String>,
+  },
+
   #[error("There is existing access request for workspace {workspace_id} and view {view_id}")]
   AccessRequestAlreadyExists { workspace_id: Uuid, view_id: Uuid },
 
@@ -237,6 +305,8 @@ impl AppError {
       #[cfg(feature = "validation_error")]
       AppError::ValidatorError(_) => ErrorCode::InvalidRequest,
       AppError::S3ResponseError(_) => ErrorCode::S3ResponseError,
+      AppError::S3AccessDenied(_) => ErrorCode::S3AccessDenied,
+      AppError::S3SlowDown(_) => ErrorCode::S3SlowDown,
       AppError::UrlError(_) => ErrorCode::InvalidUrl,
       AppError::SerdeError(_) => ErrorCode::SerdeError,
       AppError::Connect(_) => ErrorCode::NetworkError,
@@ -259,6 +329,20 @@ impl AppError {
       AppError::MissingView(_) => ErrorCode::MissingView,
       AppError::AccessRequestAlreadyExists { .. } => ErrorCode::AccessRequestAlreadyExists,
       AppError::TooManyImportTask(_) => ErrorCode::TooManyImportTask,
+      AppError::ExportTaskAlreadyRunning(_) => ErrorCode::ExportTaskAlreadyRunning,
+      AppError::WorkspaceMergeBlocked(_) => ErrorCode::WorkspaceMergeBlocked,
+      AppError::StaleDryRun(_) => ErrorCode::StaleDryRun,
+      AppError::EditingLocked(_) => ErrorCode::EditingLocked,
+      AppError::TooManyRequests(_) => ErrorCode::TooManyRequests,
+      AppError::StatementTimeout(_) => ErrorCode::StatementTimeout,
+      AppError::PublishTakenDown(_) => ErrorCode::PublishTakenDown,
+      AppError::EncryptedCollab(_) => ErrorCode::EncryptedCollab,
+      AppError::AIFeatureDisabled(_) => ErrorCode::AIFeatureDisabled,
+      AppError::InvitationExpired(_) => ErrorCode::InvitationExpired,
+      AppError::InvalidCursor(_) => ErrorCode::InvalidCursor,
+      AppError::FileTooLarge { .. } => ErrorCode::FileTooLarge,
+      AppError::CollabSizeExceeded { .. } => ErrorCode::CollabSizeExceeded,
+      AppError::InvalidCollabStructure { .. } => ErrorCode::InvalidCollabStructure,
       AppError::PublishNameAlreadyExists { .. } => ErrorCode::PublishNameAlreadyExists,
       AppError::PublishNameInvalidCharacter { .. } => ErrorCode::PublishNameInvalidCharacter,
       AppError::PublishNameTooLong { .. } => ErrorCode::PublishNameTooLong,
@@ -323,6 +407,10 @@ impl From<sqlx::Error> for AppError {
         AppError::RecordNotFound(format!("Record not exist in db. {})", msg))
       },
       sqlx::Error::PoolTimedOut => AppError::ActionTimeout(value.to_string()),
+      // query_canceled, raised when a statement exceeds the statement_timeout
+      sqlx::Error::Database(ref err) if err.code().as_deref() == Some("57014") => {
+        AppError::StatementTimeout(msg)
+      },
       _ => AppError::SqlxError(msg),
     }
   }
@@ -438,12 +526,40 @@ pub enum ErrorCode {
   AIMaxRequired = 1061,
   InvalidPageData = 1062,
   MemberNotFound = 1063,
+  ExportTaskAlreadyRunning = 1064,
+  StaleDryRun = 1065,
+  S3AccessDenied = 1066,
+  S3SlowDown = 1067,
+  EditingLocked = 1068,
+  TooManyRequests = 1069,
+  StatementTimeout = 1070,
+  PublishTakenDown = 1071,
+  WorkspaceMergeBlocked = 1072,
+  EncryptedCollab = 1073,
+  AIFeatureDisabled = 1074,
+  InvitationExpired = 1075,
+  InvalidCursor = 1076,
+  FileTooLarge = 1077,
+  CollabSizeExceeded = 1078,
+  InvalidCollabStructure = 1079,
 }
 
 impl ErrorCode {
   pub fn value(&self) -> i32 {
     *self as i32
   }
+
+  /// Errors are answered with 200 and described by their code, except for the ones the client is
+  /// expected to retry, which are answered with 503 so that proxies and retry middlewares
+  /// recognize them, and the invalid cursors, answered with 400 so that a client following the
+  /// cursors never takes them for an empty page.
+  pub fn http_status(&self) -> u16 {
+    match self {
+      ErrorCode::StatementTimeout => 503,
+      ErrorCode::InvalidCursor => 400,
+      _ => 200,
+    }
+  }
 }
 
 #[derive(Serialize)]
@@ -461,14 +577,25 @@ impl From<&AppError> for AppErrorSerde {
   }
 }
 
+/// Seconds a client is asked to wait before retrying a request which hit a statement timeout.
+pub const STATEMENT_TIMEOUT_RETRY_AFTER_SECS: u64 = 5;
+
 #[cfg(feature = "actix_web_error")]
 impl actix_web::error::ResponseError for AppError {
   fn status_code(&self) -> actix_web::http::StatusCode {
-    actix_web::http::StatusCode::OK
+    actix_web::http::StatusCode::from_u16(self.code().http_status())
+      .unwrap_or(actix_web::http::StatusCode::OK)
   }
 
   fn error_response(&self) -> actix_web::HttpResponse {
-    actix_web::HttpResponse::Ok().json(AppErrorSerde::from(self))
+    let mut builder = actix_web::HttpResponse::build(self.status_code());
+    if self.status_code() == actix_web::http::StatusCode::SERVICE_UNAVAILABLE {
+      builder.insert_header((
+        actix_web::http::header::RETRY_AFTER,
+        STATEMENT_TIMEOUT_RETRY_AFTER_SECS,
+      ));
+    }
+    builder.json(AppErrorSerde::from(self))
   }
 }
 
@@ -482,6 +609,7 @@ impl From<AIError> for AppError {
       AIError::InvalidRequest(err) => AppError::InvalidRequest(err),
       AIError::SerdeError(err) => AppError::SerdeError(err),
       AIError::ServiceUnavailable(err) => AppError::AIServiceUnavailable(err),
+      AIError::RateLimited(err) => AppError::ServiceTemporaryUnavailable(err),
     }
   }
 }
@@ -6,6 +6,8 @@ use crate::dto::{
 };
 use crate::error::AIError;
 
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use bytes::Bytes;
 use futures::{Stream, StreamExt};
 use reqwest;
@@ -18,11 +20,14 @@ use std::time::Duration;
 use tracing::{info, trace};
 
 const AI_MODEL_HEADER_KEY: &str = "ai-model";
+/// The system prompt of the requests, base64 encoded since it may span several lines.
+const AI_SYSTEM_PROMPT_HEADER_KEY: &str = "ai-system-prompt";
 
 #[derive(Clone, Debug)]
 pub struct AppFlowyAIClient {
   async_client: reqwest::Client,
   url: String,
+  system_prompt: Option<String>,
 }
 
 impl AppFlowyAIClient {
@@ -30,7 +35,18 @@ impl AppFlowyAIClient {
     info!("Creating AppFlowyAIClient with url: {}", url);
     let url = url.to_string();
     let async_client = reqwest::Client::new();
-    Self { async_client, url }
+    Self {
+      async_client,
+      url,
+      system_prompt: None,
+    }
+  }
+
+  /// Send the given system prompt along with every request of the returned client, in place of
+  /// the default one of the AI service.
+  pub fn with_system_prompt(mut self, system_prompt: Option<String>) -> Self {
+    self.system_prompt = system_prompt.map(|prompt| STANDARD.encode(prompt));
+    self
   }
 
   pub async fn health_check(&self) -> Result<(), AIError> {
@@ -309,7 +325,10 @@ impl AppFlowyAIClient {
   }
 
   fn async_http_client(&self, method: Method, url: &str) -> Result<RequestBuilder, AIError> {
-    let request_builder = self.async_client.request(method, url);
+    let mut request_builder = self.async_client.request(method, url);
+    if let Some(system_prompt) = &self.system_prompt {
+      request_builder = request_builder.header(AI_SYSTEM_PROMPT_HEADER_KEY, system_prompt);
+    }
     Ok(request_builder)
   }
 }
@@ -327,11 +346,20 @@ impl<T> AIResponse<T>
 where
   T: DeserializeOwned + 'static,
 {
-  pub async fn from_reqwest_response(resp: reqwest::Response) -> Result<Self, anyhow::Error> {
+  pub async fn from_reqwest_response(resp: reqwest::Response) -> Result<Self, AIError> {
     let status_code = resp.status();
+    if status_code == StatusCode::TOO_MANY_REQUESTS {
+      let body = resp.text().await?;
+      return Err(AIError::RateLimited(body));
+    }
+
     if !status_code.is_success() {
       let body = resp.text().await?;
-      anyhow::bail!("error code: {}, {}", status_code, body)
+      return Err(AIError::Internal(anyhow::anyhow!(
+        "error code: {}, {}",
+        status_code,
+        body
+      )));
     }
 
     let bytes = resp.bytes().await?;
@@ -361,6 +389,11 @@ where
     resp: reqwest::Response,
   ) -> Result<impl Stream<Item = Result<Bytes, AIError>>, AIError> {
     let status_code = resp.status();
+    if status_code == StatusCode::TOO_MANY_REQUESTS {
+      let body = resp.text().await?;
+      return Err(AIError::RateLimited(body));
+    }
+
     if status_code.is_server_error() {
       let body = resp.text().await?;
       return Err(AIError::ServiceUnavailable(body));
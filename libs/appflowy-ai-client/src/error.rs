@@ -17,4 +17,13 @@ pub enum AIError {
 
   #[error("Service unavailable:{0}")]
   ServiceUnavailable(String),
+
+  #[error("Rate limited:{0}")]
+  RateLimited(String),
+}
+
+impl AIError {
+  pub fn is_rate_limited(&self) -> bool {
+    matches!(self, AIError::RateLimited(_))
+  }
 }
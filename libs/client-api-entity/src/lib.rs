@@ -4,6 +4,7 @@ pub use collab_entity::*;
 pub use collab_rt_entity::user::*;
 pub use database_entity::dto::*;
 pub use database_entity::file_dto::*;
+pub use database_entity::timestamp;
 pub use gotrue_entity::dto::*;
 pub use shared_entity::dto::*;
 
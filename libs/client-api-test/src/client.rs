@@ -58,13 +58,18 @@ pub fn localhost_client() -> Client {
 }
 
 pub fn localhost_client_with_device_id(device_id: &str) -> Client {
+  localhost_client_with_version(device_id, "0.7.0")
+}
+
+/// Same as [localhost_client_with_device_id], for a client reporting the given app version.
+pub fn localhost_client_with_version(device_id: &str, client_version: &str) -> Client {
   Client::new(
     &LOCALHOST_URL,
     &LOCALHOST_WS,
     &LOCALHOST_GOTRUE,
     device_id,
     ClientConfiguration::default(),
-    "0.7.0",
+    client_version,
   )
 }
 
@@ -53,7 +53,7 @@ use shared_entity::response::AppResponseError;
 
 use crate::database_util::TestDatabaseCollabService;
 use crate::user::{generate_unique_registered_user, User};
-use crate::{load_env, localhost_client_with_device_id, setup_log};
+use crate::{load_env, localhost_client_with_version, setup_log};
 
 pub type CollabRef = Arc<RwLock<dyn BorrowMut<Collab> + Send + Sync + 'static>>;
 
@@ -97,9 +97,20 @@ impl TestClient {
     device_id: &str,
     registered_user: User,
     start_ws_conn: bool,
+  ) -> Self {
+    Self::new_with_client_version(device_id, "0.7.0", registered_user, start_ws_conn).await
+  }
+
+  /// Same as [Self::new_with_device_id], for a client reporting the given app version, which
+  /// decides the messages the realtime server sends it.
+  pub async fn new_with_client_version(
+    device_id: &str,
+    client_version: &str,
+    registered_user: User,
+    start_ws_conn: bool,
   ) -> Self {
     setup_log();
-    let api_client = localhost_client_with_device_id(device_id);
+    let api_client = localhost_client_with_version(device_id, client_version);
     api_client
       .sign_in_password(&registered_user.email, &registered_user.password)
       .await
@@ -136,6 +147,13 @@ impl TestClient {
     this
   }
 
+  pub async fn new_user_with_client_version(client_version: &str) -> Self {
+    load_env();
+    let registered_user = generate_unique_registered_user().await;
+    let device_id = Uuid::new_v4().to_string();
+    Self::new_with_client_version(&device_id, client_version, registered_user, true).await
+  }
+
   pub async fn new_user_without_ws_conn() -> Self {
     let registered_user = generate_unique_registered_user().await;
     Self::new(registered_user, false).await
@@ -1092,6 +1110,7 @@ impl TestClient {
         &PublishedDuplicate {
           published_view_id: src_view_id.to_string(),
           dest_view_id: dest_view_id.to_string(),
+          nonce: None,
         },
       )
       .await
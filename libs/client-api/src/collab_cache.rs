@@ -0,0 +1,115 @@
+use client_api_entity::EncodedCollab;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A collab fetched from the server, together with the hash of its state vector as computed by the
+/// server. The hash is sent back on the next fetch so that the server can skip unchanged collabs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedCollab {
+  pub encode_collab: EncodedCollab,
+  pub state_vector_hash: String,
+}
+
+/// Storage used by the [crate::Client] to keep the collabs it fetched, keyed by object id.
+///
+/// Implementations should not fail: a cache that can not be read or written behaves as if it was
+/// empty.
+pub trait CollabCache: Send + Sync {
+  fn get(&self, object_id: &str) -> Option<CachedCollab>;
+  fn put(&self, object_id: &str, collab: CachedCollab);
+  /// Called when the cached copy is known to be outdated, for example when a realtime update
+  /// arrives for the object.
+  fn invalidate(&self, object_id: &str);
+}
+
+#[derive(Default)]
+pub struct InMemoryCollabCache {
+  collabs: RwLock<HashMap<String, CachedCollab>>,
+}
+
+impl InMemoryCollabCache {
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
+
+impl CollabCache for InMemoryCollabCache {
+  fn get(&self, object_id: &str) -> Option<CachedCollab> {
+    self.collabs.read().get(object_id).cloned()
+  }
+
+  fn put(&self, object_id: &str, collab: CachedCollab) {
+    self.collabs.write().insert(object_id.to_string(), collab);
+  }
+
+  fn invalidate(&self, object_id: &str) {
+    self.collabs.write().remove(object_id);
+  }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use file_cache::FileCollabCache;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod file_cache {
+  use super::{CachedCollab, CollabCache};
+  use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+  use std::fs;
+  use std::path::PathBuf;
+  use tracing::warn;
+
+  /// Keeps one file per collab in the given directory.
+  pub struct FileCollabCache {
+    dir: PathBuf,
+  }
+
+  impl FileCollabCache {
+    pub fn new(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+      let dir = dir.into();
+      fs::create_dir_all(&dir)?;
+      Ok(Self { dir })
+    }
+
+    fn path(&self, object_id: &str) -> PathBuf {
+      self
+        .dir
+        .join(utf8_percent_encode(object_id, NON_ALPHANUMERIC).to_string())
+    }
+  }
+
+  impl CollabCache for FileCollabCache {
+    fn get(&self, object_id: &str) -> Option<CachedCollab> {
+      let data = fs::read(self.path(object_id)).ok()?;
+      match bincode::deserialize(&data) {
+        Ok(collab) => Some(collab),
+        Err(err) => {
+          warn!("remove corrupted cached collab {}: {}", object_id, err);
+          self.invalidate(object_id);
+          None
+        },
+      }
+    }
+
+    fn put(&self, object_id: &str, collab: CachedCollab) {
+      let data = match bincode::serialize(&collab) {
+        Ok(data) => data,
+        Err(err) => {
+          warn!("failed to serialize cached collab {}: {}", object_id, err);
+          return;
+        },
+      };
+      // Write to a temporary file first so that a reader never sees a partially written file.
+      let path = self.path(object_id);
+      let tmp_path = path.with_extension("tmp");
+      if let Err(err) = fs::write(&tmp_path, data).and_then(|_| fs::rename(&tmp_path, &path)) {
+        warn!("failed to write cached collab {}: {}", object_id, err);
+        let _ = fs::remove_file(&tmp_path);
+      }
+    }
+
+    fn invalidate(&self, object_id: &str) {
+      let _ = fs::remove_file(self.path(object_id));
+    }
+  }
+}
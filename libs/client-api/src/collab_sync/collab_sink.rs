@@ -19,6 +19,8 @@ use collab_rt_entity::{ClientCollabMessage, MsgId, ServerCollabMessage, SinkMess
 
 pub(crate) const SEND_INTERVAL: Duration = Duration::from_secs(8);
 pub const COLLAB_SINK_DELAY_MILLIS: u64 = 500;
+/// How long a message the server asked to retry waits before being sent again.
+pub const COLLAB_SINK_RETRY_DELAY_MILLIS: u64 = 2000;
 
 pub struct CollabSink<Sink> {
   #[allow(dead_code)]
@@ -155,6 +157,20 @@ where
     let _ = self.notifier.send(SinkSignal::Proceed);
   }
 
+  /// Queues the message asking for the broadcasts missed while the network was down. Unlike
+  /// [Self::queue_init_sync], the pending messages are kept, and the ones being sent are sent
+  /// again since their acks were lost with the connection.
+  pub fn queue_replay_sync(&self, f: impl FnOnce(MsgId) -> ClientCollabMessage) {
+    let _ = self.sync_state_tx.send(CollabSyncState::Syncing);
+    self.sending_messages.lock().clear();
+
+    let mut msg_queue = self.message_queue.lock();
+    let msg_id = self.state.id_counter.next();
+    let replay_sync = f(msg_id);
+    msg_queue.push_msg(msg_id, replay_sync);
+    let _ = self.notifier.send(SinkSignal::Proceed);
+  }
+
   pub fn did_queue_init_sync(&self) -> bool {
     self.state.did_queue_int_sync.load(Ordering::SeqCst)
   }
@@ -172,6 +188,15 @@ where
     true
   }
 
+  /// Sends the message again later. It stays in the queue, so it can be merged with the updates
+  /// queued in the meantime.
+  pub fn retry_msg(&self, msg_id: MsgId) {
+    self.sending_messages.lock().remove(&msg_id);
+    let _ = self.notifier.send(SinkSignal::ProcessAfterMillis(
+      COLLAB_SINK_RETRY_DELAY_MILLIS,
+    ));
+  }
+
   pub fn clear(&self) {
     self.message_queue.lock().clear();
     self.sending_messages.lock().clear();
@@ -1,6 +1,6 @@
 use std::borrow::BorrowMut;
 use std::marker::PhantomData;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::{Arc, Weak};
 use std::time::Duration;
 
@@ -20,7 +20,8 @@ use yrs::ReadTxn;
 use client_api_entity::{validate_data_for_folder, CollabType};
 use collab_rt_entity::{AckCode, ClientCollabMessage, ServerCollabMessage, ServerInit, UpdateSync};
 use collab_rt_protocol::{
-  ClientSyncProtocol, CollabSyncProtocol, Message, MessageReader, SyncMessage,
+  ClientSyncProtocol, CollabSizeExceeded, CollabSyncProtocol, EditingLockMeta, Message,
+  MessageReader, SyncMessage,
 };
 
 use crate::collab_sync::{
@@ -37,8 +38,7 @@ pub struct ObserveCollab<Sink, Stream> {
   phantom_sink: PhantomData<Sink>,
   phantom_stream: PhantomData<Stream>,
   // Use sequence number to check if the received updates/broadcasts are continuous.
-  #[allow(dead_code)]
-  seq_num_counter: Arc<SeqNumCounter>,
+  pub(crate) seq_num_counter: Arc<SeqNumCounter>,
 }
 
 impl<Sink, Stream> Drop for ObserveCollab<Sink, Stream> {
@@ -225,6 +225,33 @@ where
             error!("Error while processing message: {}", error);
             break;
           },
+          SyncError::CollabReset => {
+            // Syncing the local copy would bring back the history removed by the server. The
+            // owner of the collab is notified through [crate::ws::WSClient::subscribe_collab_reset]
+            // and is expected to load the collab again.
+            warn!("{} was reset by the server, stop syncing", object.object_id);
+            break;
+          },
+          SyncError::EditingLocked(lock) => {
+            // The owner of the collab is notified through
+            // [crate::ws::WSClient::subscribe_editing_lock] and is expected to make it read-only.
+            warn!(
+              "{} is locked by {}, local updates were rejected",
+              object.object_id, lock.name
+            );
+          },
+          SyncError::ReadOnly => {
+            warn!(
+              "{} is read-only until its repair is confirmed, local updates were rejected",
+              object.object_id
+            );
+          },
+          SyncError::CollabSizeExceeded(size) => {
+            error!(
+              "{} exceeds the size limit of {} bytes with {} bytes, local updates were rejected",
+              object.object_id, size.limit, size.actual
+            );
+          },
           _ => {
             error!("Error while processing message: {}", error);
           },
@@ -252,6 +279,41 @@ where
         return Err(SyncError::CannotApplyUpdate);
       }
 
+      if ack_code == AckCode::Reset {
+        return Err(SyncError::CollabReset);
+      }
+
+      if ack_code == AckCode::EditingLocked {
+        // The pending updates would be rejected as well, so they are dropped.
+        sink.clear();
+        let lock = EditingLockMeta::from_vec(&ack.payload)?;
+        return Err(SyncError::EditingLocked(lock));
+      }
+
+      if ack_code == AckCode::ReadOnly {
+        sink.clear();
+        return Err(SyncError::ReadOnly);
+      }
+
+      if ack_code == AckCode::CollabSizeExceeded {
+        // The local copy holds the rejected update, so the following ones would be rejected too.
+        sink.clear();
+        let size = CollabSizeExceeded::from_vec(&ack.payload)?;
+        return Err(SyncError::CollabSizeExceeded(size));
+      }
+
+      if ack_code == AckCode::ReplayUnavailable {
+        // the broadcasts missed while disconnected are gone, the whole collab is synced instead
+        seq_num_counter.reset_stream_seq();
+        return Err(SyncError::CannotApplyUpdate);
+      }
+
+      if ack_code == AckCode::Retry {
+        // the server rejected the message, for example because it's overloaded
+        sink.retry_msg(ack.msg_id);
+        return Ok(());
+      }
+
       if ack_code == AckCode::MissUpdate {
         // if the ack code is MissUpdate, it means the server has missed some updates. Client need to
         // use the payload of the current message to calculate missing update. So any existing pending
@@ -272,9 +334,18 @@ where
         Self::process_message_follow_protocol(object, &msg, collab, sink).await?;
         sink.notify_next();
 
-        if let ServerCollabMessage::ServerBroadcast(ref data) = msg {
-          seq_num_counter.check_broadcast_contiguous(&object.object_id, data.seq_num)?;
-          seq_num_counter.store_broadcast_seq_num(data.seq_num);
+        match msg {
+          ServerCollabMessage::ServerBroadcast(ref data) => {
+            seq_num_counter.check_broadcast_contiguous(&object.object_id, data.seq_num)?;
+            seq_num_counter.store_broadcast_seq_num(data.seq_num);
+          },
+          ServerCollabMessage::SequencedBroadcast(ref data) => {
+            seq_num_counter
+              .check_broadcast_contiguous(&object.object_id, data.broadcast.seq_num)?;
+            seq_num_counter.store_broadcast_seq_num(data.broadcast.seq_num);
+            seq_num_counter.store_stream_seq(data.stream_seq);
+          },
+          _ => {},
         }
         Ok(())
       },
@@ -391,9 +462,35 @@ pub struct SeqNumCounter {
   /// prompting an initialization sync to rectify missing updates.
   pub ack_seq_counter: AtomicU32,
   pub miss_update_counter: AtomicU32,
+  /// The number of the last update received in a [collab_rt_entity::SequencedBroadcast], which
+  /// the client asks the server to replay the updates after when the network resumes. 0 until
+  /// the server sent one.
+  pub stream_seq: AtomicU64,
 }
 
 impl SeqNumCounter {
+  pub fn store_stream_seq(&self, stream_seq: u64) {
+    // the replayed updates may arrive after the broadcasts which followed them
+    self.stream_seq.fetch_max(stream_seq, Ordering::SeqCst);
+  }
+
+  pub fn stream_seq(&self) -> u64 {
+    self.stream_seq.load(Ordering::SeqCst)
+  }
+
+  pub fn reset_stream_seq(&self) {
+    self.stream_seq.store(0, Ordering::SeqCst);
+  }
+
+  /// Forgets the broadcast and ack sequence numbers of the group the client was subscribed to
+  /// before reconnecting. They're counted by each group, so the ones of the group it asks for a
+  /// replay may have moved on, or started again.
+  pub fn reset_broadcast_seq(&self) {
+    self.broadcast_seq_counter.store(0, Ordering::SeqCst);
+    self.ack_seq_counter.store(0, Ordering::SeqCst);
+    self.miss_update_counter.store(0, Ordering::SeqCst);
+  }
+
   pub fn store_ack_seq_num(&self, seq_num: u32) -> u32 {
     // If the broadcast sequence counter is 0, set it to the current sequence number.
     if self.broadcast_seq_counter.load(Ordering::SeqCst) == 0 {
@@ -1,4 +1,4 @@
-use collab_rt_protocol::RTProtocolError;
+use collab_rt_protocol::{CollabSizeExceeded, EditingLockMeta, RTProtocolError};
 use std::fmt::Display;
 
 #[derive(Debug, thiserror::Error)]
@@ -39,6 +39,26 @@ pub enum SyncError {
   #[error("{0}")]
   OverrideWithIncorrectData(String),
 
+  /// The server replaced the collab by a compacted copy. The local copy can not be synced anymore
+  /// and must be loaded again from the server.
+  #[error("Collab was reset by the server")]
+  CollabReset,
+
+  /// The collab is locked for editing by another member. Local updates are rejected by the server
+  /// until the lock is released.
+  #[error("Collab is locked by {}", .0.name)]
+  EditingLocked(EditingLockMeta),
+
+  /// The collab was recovered from a snapshot on the server. Local updates are rejected until an
+  /// admin confirms the repair.
+  #[error("Collab is read-only until its repair is confirmed")]
+  ReadOnly,
+
+  /// The server rejected a local update because of its size, or the size of the collab it would
+  /// lead to.
+  #[error("Collab of {} bytes exceeds the limit of {} bytes", .0.actual, .0.limit)]
+  CollabSizeExceeded(CollabSizeExceeded),
+
   #[error(transparent)]
   Internal(#[from] anyhow::Error),
 }
@@ -91,6 +111,10 @@ impl From<RTProtocolError> for SyncError {
       RTProtocolError::YAwareness(e) => Self::YAwareness(e),
       RTProtocolError::YrsApplyUpdate(e) => Self::YrsApplyUpdate(e),
       RTProtocolError::Internal(e) => Self::Internal(e),
+      RTProtocolError::CollabReset => Self::CollabReset,
+      RTProtocolError::EditingLocked(lock) => Self::EditingLocked(lock),
+      RTProtocolError::ReadOnly => Self::ReadOnly,
+      RTProtocolError::CollabSizeExceeded(size) => Self::CollabSizeExceeded(size),
       _ => Self::YSync(value),
     }
   }
@@ -13,7 +13,9 @@ use yrs::updates::decoder::Decode;
 use yrs::updates::encoder::{Encode, Encoder, EncoderV1};
 use yrs::{ReadTxn, StateVector};
 
-use collab_rt_entity::{ClientCollabMessage, InitSync, ServerCollabMessage, UpdateSync};
+use collab_rt_entity::{
+  ClientCollabMessage, InitSync, ReplaySync, ServerCollabMessage, UpdateSync,
+};
 use collab_rt_protocol::{ClientSyncProtocol, CollabSyncProtocol, Message, SyncMessage};
 
 use crate::collab_sync::collab_stream::{CollabRef, ObserveCollab};
@@ -33,7 +35,6 @@ pub struct SyncControl<Sink, Stream> {
   sink: Arc<CollabSink<Sink>>,
   /// The [ObserveCollab] will be spawned in a separate task It continuously receive
   /// the updates from the remote.
-  #[allow(dead_code)]
   observe_collab: ObserveCollab<Sink, Stream>,
   sync_state_tx: broadcast::Sender<CollabSyncState>,
 }
@@ -122,6 +123,29 @@ where
     collab: &collab::preclude::Collab,
     reason: SyncReason,
   ) -> Result<bool, SyncError> {
+    // after a short disconnection, the server replays the broadcasts the client missed rather
+    // than syncing the whole collab. It acks with ReplayUnavailable when it can't.
+    if matches!(reason, SyncReason::NetworkResume) {
+      let seq_num_counter = &self.observe_collab.seq_num_counter;
+      let last_seen_seq = seq_num_counter.stream_seq();
+      if last_seen_seq > 0 {
+        tracing::debug!(
+          "🔥{} resume network, replay after: {}",
+          &self.object.object_id,
+          last_seen_seq
+        );
+        seq_num_counter.reset_broadcast_seq();
+        self.sink.queue_replay_sync(|msg_id| {
+          ClientCollabMessage::new_replay_sync(ReplaySync {
+            origin: self.origin.clone(),
+            object_id: self.object.object_id.clone(),
+            msg_id,
+            last_seen_seq: Some(last_seen_seq),
+          })
+        });
+        return Ok(true);
+      }
+    }
     start_sync(
       self.origin.clone(),
       &self.object,
@@ -166,6 +190,28 @@ fn gen_sync_state<P: CollabSyncProtocol>(
   Ok(encoder.to_vec())
 }
 
+/// Opts into the [collab_rt_entity::SequencedBroadcast]s of the collab after its init sync, so
+/// that the client can ask for the broadcasts it missed when the network resumes.
+fn queue_replay_opt_in<E, Sink>(
+  origin: CollabOrigin,
+  sync_object: &SyncObject,
+  sink: &Arc<CollabSink<Sink>>,
+) where
+  E: Into<anyhow::Error> + Send + Sync + 'static,
+  Sink: SinkExt<Vec<ClientCollabMessage>, Error = E> + Send + Sync + Unpin + 'static,
+{
+  sink.queue_msg(|msg_id| {
+    ClientCollabMessage::new_replay_sync(ReplaySync {
+      origin,
+      object_id: sync_object.object_id.clone(),
+      msg_id,
+      last_seen_seq: None,
+    })
+  });
+  // the init sync is sent right away, rather than after the delay of the queued message
+  sink.notify_next();
+}
+
 fn gen_missing_updates(collab: &Collab, sv: StateVector) -> Result<Vec<u8>, SyncError> {
   let update = {
     let txn = collab.transact();
@@ -208,7 +254,7 @@ where
       let payload = gen_sync_state(awareness, &ClientSyncProtocol)?;
       sink.queue_init_sync(|msg_id| {
         let init_sync = InitSync::new(
-          origin,
+          origin.clone(),
           sync_object.object_id.clone(),
           sync_object.collab_type.clone(),
           sync_object.workspace_id.clone(),
@@ -217,6 +263,7 @@ where
         );
         ClientCollabMessage::new_init_sync(init_sync)
       });
+      queue_replay_opt_in(origin, sync_object, sink);
     },
     SyncReason::ServerMissUpdates {
       state_vector_v1,
@@ -249,7 +296,7 @@ where
       let payload = gen_sync_state(awareness, &ClientSyncProtocol)?;
       sink.queue_init_sync(|msg_id| {
         let init_sync = InitSync::new(
-          origin,
+          origin.clone(),
           sync_object.object_id.clone(),
           sync_object.collab_type.clone(),
           sync_object.workspace_id.clone(),
@@ -258,6 +305,7 @@ where
         );
         ClientCollabMessage::new_init_sync(init_sync)
       });
+      queue_replay_opt_in(origin, sync_object, sink);
     },
   };
 
@@ -2,7 +2,9 @@ use crate::notify::{ClientToken, TokenStateReceiver};
 use app_error::AppError;
 use app_error::ErrorCode;
 use client_api_entity::auth_dto::DeleteUserQuery;
+use client_api_entity::page_dto::PAGE_API_VERSION;
 use client_api_entity::server_info_dto::ServerInfoResponseItem;
+use client_api_entity::timestamp::X_API_VERSION;
 use client_api_entity::workspace_dto::FavoriteSectionItems;
 use client_api_entity::workspace_dto::RecentSectionItems;
 use client_api_entity::workspace_dto::TrashSectionItems;
@@ -25,15 +27,16 @@ use reqwest::RequestBuilder;
 
 use anyhow::anyhow;
 use client_api_entity::{
-  AFSnapshotMeta, AFSnapshotMetas, AFUserProfile, AFUserWorkspaceInfo, AFWorkspace,
-  QuerySnapshotParams, SnapshotData,
+  AFCollabVersion, AFSnapshotMeta, AFSnapshotMetas, AFUserProfile, AFUserWorkspaceInfo,
+  AFWorkspace, QuerySnapshotParams, SnapshotData,
 };
 use semver::Version;
 use shared_entity::dto::auth_dto::SignInTokenResponse;
 use shared_entity::dto::auth_dto::UpdateUserParams;
-use shared_entity::dto::workspace_dto::WorkspaceSpaceUsage;
+use shared_entity::dto::policy_version_dto::{X_MIN_POLICY_VERSION, X_POLICY_VERSION};
+use shared_entity::dto::workspace_dto::{WorkspaceSpaceUsage, WorkspaceStats};
 use shared_entity::response::{AppResponse, AppResponseError};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio_retry::strategy::FixedInterval;
@@ -41,6 +44,7 @@ use tokio_retry::RetryIf;
 use tracing::{debug, error, event, info, instrument, trace, warn};
 use url::Url;
 
+use crate::collab_cache::CollabCache;
 use crate::retry::{RefreshTokenAction, RefreshTokenRetryCondition};
 use crate::ws::ConnectInfo;
 use client_api_entity::SignUpResponse::{Authenticated, NotAuthenticated};
@@ -59,6 +63,9 @@ pub struct ClientConfiguration {
   /// A larger buffer size means more data is compressed in a single operation, which can lead to better compression ratios
   /// since Brotli has more data to analyze for patterns and repetitions.
   pub(crate) compression_buffer_size: usize,
+  /// When set, collabs fetched with [Client::get_collab] are kept in this cache and only fetched
+  /// again when they changed on the server.
+  pub(crate) collab_cache: Option<Arc<dyn CollabCache>>,
 }
 
 impl ClientConfiguration {
@@ -76,6 +83,11 @@ impl ClientConfiguration {
     };
     self
   }
+
+  pub fn with_collab_cache(mut self, collab_cache: Arc<dyn CollabCache>) -> Self {
+    self.collab_cache = Some(collab_cache);
+    self
+  }
 }
 
 impl Default for ClientConfiguration {
@@ -83,6 +95,7 @@ impl Default for ClientConfiguration {
     Self {
       compression_quality: 8,
       compression_buffer_size: 10240,
+      collab_cache: None,
     }
   }
 }
@@ -112,10 +125,36 @@ pub struct Client {
   pub(crate) refresh_ret_txs: Arc<RwLock<Vec<RefreshTokenSender>>>,
   pub(crate) config: ClientConfiguration,
   pub(crate) ai_model: Arc<RwLock<String>>,
+  /// Version of the access control policies returned after the last permission change, sent
+  /// with the following requests so that they are enforced with the changed permissions.
+  pub(crate) policy_version: Arc<AtomicI64>,
 }
 
 pub(crate) type RefreshTokenSender = tokio::sync::oneshot::Sender<Result<(), AppResponseError>>;
 
+/// The refresh of the token being sent. Once it's dropped, the calls waiting for the refresh get
+/// its result, or an error if it was cancelled, and the next call refreshes the token again.
+struct InFlightRefresh<'a> {
+  is_refreshing_token: &'a AtomicBool,
+  refresh_ret_txs: &'a RwLock<Vec<RefreshTokenSender>>,
+  result: Option<Result<(), AppResponseError>>,
+}
+
+impl Drop for InFlightRefresh<'_> {
+  fn drop(&mut self) {
+    let txs = {
+      let mut txs = self.refresh_ret_txs.write();
+      self.is_refreshing_token.store(false, Ordering::SeqCst);
+      std::mem::take(&mut *txs)
+    };
+    if let Some(result) = self.result.take() {
+      for tx in txs {
+        let _ = tx.send(result.clone());
+      }
+    }
+  }
+}
+
 /// Hardcoded schema in the frontend application. Do not change this value.
 const DESKTOP_CALLBACK_URL: &str = "appflowy-flutter://login-callback";
 
@@ -189,6 +228,7 @@ impl Client {
       device_id: device_id.to_string(),
       client_version,
       ai_model,
+      policy_version: Default::default(),
     }
   }
 
@@ -204,11 +244,30 @@ impl Client {
     &self.gotrue_client.base_url
   }
 
+  /// The collab cache configured with [ClientConfiguration::with_collab_cache]. Pass it to
+  /// [crate::ws::WSClient::set_collab_cache] so that realtime updates invalidate cached collabs.
+  pub fn collab_cache(&self) -> Option<Arc<dyn CollabCache>> {
+    self.config.collab_cache.clone()
+  }
+
   pub fn set_ai_model(&self, model: String) {
     info!("using ai model: {:?}", model);
     *self.ai_model.write() = model;
   }
 
+  /// Keeps the version of the access control policies returned by a request changing
+  /// permissions.
+  pub(crate) fn record_policy_version(&self, resp: &reqwest::Response) {
+    let version = resp
+      .headers()
+      .get(X_POLICY_VERSION)
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| value.parse::<i64>().ok());
+    if let Some(version) = version {
+      self.policy_version.fetch_max(version, Ordering::AcqRel);
+    }
+  }
+
   #[instrument(level = "debug", skip_all, err)]
   pub fn restore_token(&self, token: &str) -> Result<(), AppResponseError> {
     match serde_json::from_str::<GotrueTokenResponse>(token) {
@@ -939,6 +998,52 @@ impl Client {
       .into_data()
   }
 
+  /// Returns the version history of the collab, newest first.
+  pub async fn get_snapshots(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<Vec<AFCollabVersion>, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/{}/snapshot/history",
+      self.base_url, workspace_id, object_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<Vec<AFCollabVersion>>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Restores the content of the collab to the one of the snapshot. Returns the snapshot taken
+  /// of the content before the restore, which can be restored to undo it.
+  pub async fn restore_snapshot(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    snapshot_id: i64,
+    collab_type: CollabType,
+  ) -> Result<AFSnapshotMeta, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/{}/snapshot/{}/restore",
+      self.base_url, workspace_id, object_id, snapshot_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&collab_type)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<AFSnapshotMeta>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
   pub async fn ws_connect_info(&self, auto_refresh: bool) -> Result<ConnectInfo, AppResponseError> {
     if auto_refresh {
       self
@@ -973,6 +1078,43 @@ impl Client {
       .into_data()
   }
 
+  /// Object counts per type, folder depths and their changes over a week
+  #[instrument(level = "info", skip_all)]
+  pub async fn get_workspace_stats(
+    &self,
+    workspace_id: &str,
+  ) -> Result<WorkspaceStats, AppResponseError> {
+    let url = format!("{}/api/workspace/{}/stats", self.base_url, workspace_id);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<WorkspaceStats>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Recomputes the stats of the workspace from scratch. Only the owner of the workspace can.
+  #[instrument(level = "info", skip_all)]
+  pub async fn recompute_workspace_stats(
+    &self,
+    workspace_id: &str,
+  ) -> Result<(), AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/stats/recompute",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
   #[instrument(level = "info", skip_all)]
   pub async fn get_server_info(&self) -> Result<ServerInfoResponseItem, AppResponseError> {
     let url = format!("{}/api/server", self.base_url);
@@ -998,21 +1140,28 @@ impl Client {
   /// This function attempts to refresh the access token by sending a request to the authentication server
   /// using the stored refresh token. If successful, it updates the stored access token with the new one
   /// received from the server.
+  ///
+  /// The refresh token is single use, a new one being issued by every refresh, so the concurrent
+  /// calls share a single refresh: the first one sends the request and the others wait for its
+  /// result. Refreshing again with a used refresh token would sign the session out.
   #[instrument(level = "debug", skip_all, err)]
   pub async fn refresh_token(&self, reason: &str) -> Result<(), AppResponseError> {
     let (tx, rx) = tokio::sync::oneshot::channel();
-    self.refresh_ret_txs.write().push(tx);
-
-    if !self.is_refreshing_token.load(Ordering::SeqCst) {
-      self.is_refreshing_token.store(true, Ordering::SeqCst);
+    let is_first = {
+      let mut txs = self.refresh_ret_txs.write();
+      txs.push(tx);
+      !self.is_refreshing_token.swap(true, Ordering::SeqCst)
+    };
 
+    if is_first {
       info!("refresh token reason:{}", reason);
-      let result = self.inner_refresh_token().await;
-      let txs = std::mem::take(&mut *self.refresh_ret_txs.write());
-      for tx in txs {
-        let _ = tx.send(result.clone());
-      }
-      self.is_refreshing_token.store(false, Ordering::SeqCst);
+      // Wakes up the waiting calls even if this one is dropped before the refresh completes.
+      let mut in_flight = InFlightRefresh {
+        is_refreshing_token: &self.is_refreshing_token,
+        refresh_ret_txs: &self.refresh_ret_txs,
+        result: None,
+      };
+      in_flight.result = Some(self.inner_refresh_token().await);
     } else {
       debug!("refresh token is already in progress");
     }
@@ -1021,10 +1170,7 @@ impl Client {
     match tokio::time::timeout(Duration::from_secs(60), rx).await {
       Ok(Ok(result)) => result,
       Ok(Err(err)) => Err(AppError::Internal(anyhow!("refresh token error: {}", err)).into()),
-      Err(_) => {
-        self.is_refreshing_token.store(false, Ordering::SeqCst);
-        Err(AppError::RequestTimeout("refresh token timeout".to_string()).into())
-      },
+      Err(_) => Err(AppError::RequestTimeout("refresh token timeout".to_string()).into()),
     }
   }
 
@@ -1087,6 +1233,7 @@ impl Client {
       ("client-timestamp", ts_now.to_string()),
       ("device-id", self.device_id.clone()),
       ("ai-model", self.ai_model.read().clone()),
+      (X_API_VERSION, PAGE_API_VERSION.to_string()),
     ];
     trace!(
       "start request: {}, method: {}, headers: {:?}",
@@ -1103,6 +1250,10 @@ impl Client {
     for header in headers {
       request_builder = request_builder.header(header.0, header.1);
     }
+    let policy_version = self.policy_version.load(Ordering::Acquire);
+    if policy_version > 0 {
+      request_builder = request_builder.header(X_MIN_POLICY_VERSION, policy_version.to_string());
+    }
     Ok(request_builder)
   }
 
@@ -54,6 +54,7 @@ impl Client {
       .json(&ApproveAccessRequestParams { is_approved: true })
       .send()
       .await?;
+    self.record_policy_version(&resp);
     AppResponse::<()>::from_response(resp).await?.into_error()
   }
 
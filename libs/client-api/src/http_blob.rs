@@ -1,16 +1,23 @@
 use crate::http::log_request_id;
-use crate::Client;
+use crate::{paginate, Client};
 
 use app_error::AppError;
 use bytes::Bytes;
-use futures_util::TryStreamExt;
+use chrono::{DateTime, Utc};
+use client_api_entity::PutBlobQuery;
+use futures_util::{Stream, TryStreamExt};
 use mime::Mime;
 use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
 use reqwest::{header, Method, StatusCode};
-use shared_entity::dto::workspace_dto::{BlobMetadata, RepeatedBlobMetaData};
+use shared_entity::dto::page_dto::{Cursor, Page, PageQuery};
+use shared_entity::dto::workspace_dto::{
+  BlobMetadata, BlobMetadataExportFormat, RepeatedBlobMetaData,
+};
 use shared_entity::response::{AppResponse, AppResponseError};
 
-use shared_entity::dto::file_dto::PutFileResponse;
+use shared_entity::dto::file_dto::{
+  BlobFileIds, PutFileResponse, RepeatedBlobVersion, UploadImageResponse,
+};
 use tracing::instrument;
 use url::Url;
 
@@ -53,6 +60,43 @@ impl Client {
     parent_dir: &str,
     data: T,
     mime: &Mime,
+  ) -> Result<PutFileResponse, AppResponseError> {
+    self
+      .put_blob_v1_with_query(
+        workspace_id,
+        parent_dir,
+        data,
+        mime,
+        &PutBlobQuery::default(),
+      )
+      .await
+  }
+
+  /// Upload a blob which is deleted by the server after `expires_at`.
+  #[instrument(level = "info", skip_all)]
+  pub async fn put_temporary_blob_v1<T: Into<Bytes>>(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    data: T,
+    mime: &Mime,
+    expires_at: DateTime<Utc>,
+  ) -> Result<PutFileResponse, AppResponseError> {
+    let query = PutBlobQuery {
+      expires_at: Some(expires_at),
+    };
+    self
+      .put_blob_v1_with_query(workspace_id, parent_dir, data, mime, &query)
+      .await
+  }
+
+  async fn put_blob_v1_with_query<T: Into<Bytes>>(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    data: T,
+    mime: &Mime,
+    query: &PutBlobQuery,
   ) -> Result<PutFileResponse, AppResponseError> {
     let url = format!(
       "{}/api/file_storage/{}/v1/blob/{}",
@@ -62,6 +106,7 @@ impl Client {
     let resp = self
       .http_client_with_auth(Method::PUT, &url)
       .await?
+      .query(query)
       .header(header::CONTENT_TYPE, mime.to_string())
       .body(data)
       .send()
@@ -165,6 +210,33 @@ impl Client {
     AppResponse::<()>::from_response(resp).await?.into_error()
   }
 
+  /// Deletes the blobs of the parent dir with the given file ids. Returns the file ids of the
+  /// blobs which were deleted.
+  #[instrument(level = "info", skip_all)]
+  pub async fn delete_blobs_v1(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_ids: Vec<String>,
+  ) -> Result<Vec<String>, AppResponseError> {
+    let parent_dir = utf8_percent_encode(parent_dir, NON_ALPHANUMERIC).to_string();
+    let url = format!(
+      "{}/api/file_storage/{workspace_id}/v1/blob/{parent_dir}",
+      self.base_url
+    );
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .json(&BlobFileIds { file_ids })
+      .send()
+      .await?;
+    log_request_id(&resp);
+    let deleted = AppResponse::<BlobFileIds>::from_response(resp)
+      .await?
+      .into_data()?;
+    Ok(deleted.file_ids)
+  }
+
   #[instrument(level = "info", skip_all)]
   pub async fn get_blob_v1_metadata(
     &self,
@@ -189,6 +261,54 @@ impl Client {
       .into_data()
   }
 
+  /// List the archived versions of a blob, newest first. Versions are only recorded when blob
+  /// versioning is enabled on the server.
+  #[instrument(level = "info", skip_all)]
+  pub async fn list_blob_versions_v1(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+  ) -> Result<RepeatedBlobVersion, AppResponseError> {
+    let parent_dir = utf8_percent_encode(parent_dir, NON_ALPHANUMERIC).to_string();
+    let url = format!(
+      "{}/api/file_storage/{workspace_id}/v1/versions/{parent_dir}/{file_id}",
+      self.base_url
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<RepeatedBlobVersion>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Replace the current content of a blob with one of its archived versions.
+  #[instrument(level = "info", skip_all)]
+  pub async fn restore_blob_version_v1(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    version: i64,
+  ) -> Result<(), AppResponseError> {
+    let parent_dir = utf8_percent_encode(parent_dir, NON_ALPHANUMERIC).to_string();
+    let url = format!(
+      "{}/api/file_storage/{workspace_id}/v1/versions/{parent_dir}/{file_id}/{version}/restore",
+      self.base_url
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
   /// Get the file with the given url. The url should be in the format of
   /// `https://appflowy.io/api/file_storage/<workspace_id>/<file_id>`.
   #[instrument(level = "info", skip_all)]
@@ -260,18 +380,139 @@ impl Client {
     AppResponse::<()>::from_response(resp).await?.into_error()
   }
 
+  /// The metadata of every blob of the workspace, fetched page by page.
   pub async fn get_workspace_all_blob_metadata(
     &self,
     workspace_id: &str,
   ) -> Result<RepeatedBlobMetaData, AppResponseError> {
+    let blobs = self
+      .stream_workspace_blob_metadata(workspace_id)
+      .try_collect::<Vec<_>>()
+      .await?;
+    Ok(RepeatedBlobMetaData(blobs))
+  }
+
+  /// The metadata of the blobs of the workspace, following the cursors of the pages.
+  pub fn stream_workspace_blob_metadata<'a>(
+    &'a self,
+    workspace_id: &'a str,
+  ) -> impl Stream<Item = Result<BlobMetadata, AppResponseError>> + 'a {
+    paginate(move |cursor| self.get_workspace_blob_metadata_page(workspace_id, cursor, None))
+  }
+
+  pub async fn get_workspace_blob_metadata_page(
+    &self,
+    workspace_id: &str,
+    cursor: Option<Cursor>,
+    limit: Option<u32>,
+  ) -> Result<Page<BlobMetadata>, AppResponseError> {
     let url = format!("{}/api/file_storage/{}/blobs", self.base_url, workspace_id);
     let resp = self
       .http_client_with_auth(Method::GET, &url)
       .await?
+      .query(&PageQuery { cursor, limit })
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<Page<BlobMetadata>>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// The export of the metadata of every blob of the workspace, served to its owners.
+  #[instrument(level = "info", skip_all, err)]
+  pub async fn export_workspace_blob_metadata(
+    &self,
+    workspace_id: &str,
+    format: BlobMetadataExportFormat,
+  ) -> Result<String, AppResponseError> {
+    let url = format!(
+      "{}/api/file_storage/{}/blobs/export",
+      self.base_url, workspace_id
+    );
+    let accept = match format {
+      BlobMetadataExportFormat::Ndjson => "application/x-ndjson",
+      BlobMetadataExportFormat::Csv => "text/csv",
+    };
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .header(header::ACCEPT, accept)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    if resp.status().is_success() {
+      Ok(resp.text().await?)
+    } else {
+      AppResponse::from_response(resp).await?.into_data()
+    }
+  }
+
+  /// Upload an image to be used as the workspace icon. The server downscales it and returns the
+  /// url that the workspace icon now points to.
+  #[instrument(level = "info", skip_all)]
+  pub async fn upload_workspace_icon<T: Into<Bytes>>(
+    &self,
+    workspace_id: &str,
+    data: T,
+    mime: &Mime,
+  ) -> Result<UploadImageResponse, AppResponseError> {
+    let url = format!("{}/api/workspace/{}/icon", self.base_url, workspace_id);
+    self.put_image(&url, data, mime).await
+  }
+
+  /// Upload an image to be used as the icon of the page view.
+  #[instrument(level = "info", skip_all)]
+  pub async fn upload_page_view_icon<T: Into<Bytes>>(
+    &self,
+    workspace_id: &str,
+    view_id: &str,
+    data: T,
+    mime: &Mime,
+  ) -> Result<UploadImageResponse, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/page-view/{}/icon",
+      self.base_url, workspace_id, view_id
+    );
+    self.put_image(&url, data, mime).await
+  }
+
+  /// Upload an image to be used as the cover of the page view.
+  #[instrument(level = "info", skip_all)]
+  pub async fn upload_page_view_cover<T: Into<Bytes>>(
+    &self,
+    workspace_id: &str,
+    view_id: &str,
+    data: T,
+    mime: &Mime,
+  ) -> Result<UploadImageResponse, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/page-view/{}/cover",
+      self.base_url, workspace_id, view_id
+    );
+    self.put_image(&url, data, mime).await
+  }
+
+  async fn put_image<T: Into<Bytes>>(
+    &self,
+    url: &str,
+    data: T,
+    mime: &Mime,
+  ) -> Result<UploadImageResponse, AppResponseError> {
+    let resp = self
+      .http_client_with_auth(Method::PUT, url)
+      .await?
+      .header(header::CONTENT_TYPE, mime.to_string())
+      .body(data.into())
       .send()
       .await?;
     log_request_id(&resp);
-    AppResponse::<RepeatedBlobMetaData>::from_response(resp)
+    if resp.status() == StatusCode::PAYLOAD_TOO_LARGE {
+      return Err(AppResponseError::from(AppError::PayloadTooLarge(
+        StatusCode::PAYLOAD_TOO_LARGE.to_string(),
+      )));
+    }
+    AppResponse::<UploadImageResponse>::from_response(resp)
       .await?
       .into_data()
   }
@@ -14,7 +14,9 @@ use shared_entity::dto::ai_dto::{
   CalculateSimilarityParams, ChatQuestionQuery, RepeatedRelatedQuestion, SimilarityResponse,
   STREAM_ANSWER_KEY, STREAM_IMAGE_KEY, STREAM_KEEP_ALIVE_KEY, STREAM_METADATA_KEY,
 };
-use shared_entity::dto::chat_dto::{ChatSettings, UpdateChatParams};
+use shared_entity::dto::chat_dto::{
+  ChatSettings, RepeatedSharedChat, ShareChatParams, UpdateChatParams,
+};
 use shared_entity::response::{AppResponse, AppResponseError};
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -78,6 +80,57 @@ impl Client {
       .into_data()
   }
 
+  /// Share the chat with the whole workspace or with some of its members. Only the owner of the
+  /// chat can share it.
+  pub async fn share_chat(
+    &self,
+    workspace_id: &str,
+    chat_id: &str,
+    params: ShareChatParams,
+  ) -> Result<(), AppResponseError> {
+    let url = format!("{}/api/chat/{workspace_id}/{chat_id}/share", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::PUT, &url)
+      .await?
+      .json(&params)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  pub async fn unshare_chat(
+    &self,
+    workspace_id: &str,
+    chat_id: &str,
+  ) -> Result<(), AppResponseError> {
+    let url = format!("{}/api/chat/{workspace_id}/{chat_id}/share", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// List the chats of the workspace shared with the user, or shared by the user
+  pub async fn list_shared_chats(
+    &self,
+    workspace_id: &str,
+  ) -> Result<RepeatedSharedChat, AppResponseError> {
+    let url = format!("{}/api/chat/{workspace_id}/shared", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<RepeatedSharedChat>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
   /// Delete a chat for given chat_id
   pub async fn delete_chat(
     &self,
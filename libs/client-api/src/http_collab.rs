@@ -1,3 +1,4 @@
+use crate::collab_cache::{CachedCollab, CollabCache};
 use crate::entity::CollabType;
 use crate::http::log_request_id;
 use crate::{blocking_brotli_compress, brotli_compress, Client};
@@ -6,14 +7,15 @@ use app_error::AppError;
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use client_api_entity::workspace_dto::{
-  AFDatabase, AFDatabaseField, AFDatabaseRow, AFDatabaseRowDetail, AFInsertDatabaseField,
-  AddDatatabaseRow, DatabaseRowUpdatedItem, ListDatabaseRowDetailParam,
-  ListDatabaseRowUpdatedParam, UpsertDatatabaseRow,
+  AFDatabase, AFDatabaseField, AFDatabaseRow, AFDatabaseRowAccess, AFDatabaseRowDetail,
+  AFFieldConversionTask, AFInsertDatabaseField, AddDatatabaseRow, BatchAddDatabaseRows,
+  DatabaseRowUpdatedItem, ListDatabaseRowDetailParam, ListDatabaseRowUpdatedParam,
+  UpdateDatabaseFieldType, UpdateDatabaseRow, UpsertDatabaseRowAccess, UpsertDatatabaseRow,
 };
 use client_api_entity::{
-  AFCollabEmbedInfo, BatchQueryCollabParams, BatchQueryCollabResult, CollabParams,
+  AFCollabEmbedInfo, BatchQueryCollabParams, BatchQueryCollabResult, CollabMode, CollabParams,
   CreateCollabParams, DeleteCollabParams, PublishCollabItem, QueryCollab, QueryCollabParams,
-  RepeatedAFCollabEmbedInfo, UpdateCollabWebParams,
+  RepeatedAFCollabEmbedInfo, UpdateCollabModeParams, UpdateCollabWebParams,
 };
 use collab_rt_entity::collab_proto::{CollabDocStateParams, PayloadCompressionType};
 use collab_rt_entity::HttpRealtimeMessage;
@@ -23,7 +25,13 @@ use prost::Message;
 use rayon::prelude::*;
 use reqwest::{Body, Method};
 use serde::Serialize;
-use shared_entity::dto::workspace_dto::{CollabResponse, CollabTypeParam, EmbeddedCollabQuery};
+use shared_entity::dto::workspace_dto::{
+  AFCollabPresence, AFEditingLock, BatchGetCollabItem, BatchGetCollabResponse,
+  BulkCreateCollabItem, BulkCreateCollabParams, BulkCreateCollabResponse, CollabDiffParams,
+  CollabDiffResponse, CollabResponse, CollabTypeParam, DeletedCollabs, DocumentDiff,
+  DocumentFindResult, DocumentOutline, DocumentSnapshotDiffQuery, EmbeddedCollabQuery,
+  FindInDocumentQuery, ReleaseEditingLockQuery,
+};
 use shared_entity::response::{AppResponse, AppResponseError};
 use std::collections::HashMap;
 use std::future::Future;
@@ -127,6 +135,56 @@ impl Client {
       .await
   }
 
+  /// Fetch up to 50 collabs of the workspace with one request. Each collab gets its own
+  /// result, by object id: the collabs which are missing or can't be read by the user get a
+  /// [BatchGetCollabItem::Failed] item.
+  #[instrument(level = "info", skip_all, err)]
+  pub async fn batch_get_collabs(
+    &self,
+    workspace_id: &str,
+    params: Vec<QueryCollab>,
+  ) -> Result<HashMap<String, BatchGetCollabItem>, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/collab/batch",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&BatchQueryCollabParams(params))
+      .send()
+      .await?;
+    log_request_id(&resp);
+    let resp = AppResponse::<BatchGetCollabResponse>::from_response(resp)
+      .await?
+      .into_data()?;
+    Ok(resp.items)
+  }
+
+  /// Create up to 500 collabs of the workspace in a single transaction. When any of them is
+  /// invalid none is created, see [BulkCreateCollabResponse::is_created].
+  #[instrument(level = "info", skip_all, err)]
+  pub async fn bulk_create_collabs(
+    &self,
+    workspace_id: &str,
+    items: Vec<BulkCreateCollabItem>,
+  ) -> Result<BulkCreateCollabResponse, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/collab/bulk",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&BulkCreateCollabParams { items })
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<BulkCreateCollabResponse>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
   async fn send_batch_collab_request(
     &self,
     method: Method,
@@ -166,6 +224,66 @@ impl Client {
     AppResponse::<()>::from_response(resp).await?.into_error()
   }
 
+  /// Returns the deleted collabs of the workspace, which can be restored until they're purged.
+  #[instrument(level = "info", skip_all, err)]
+  pub async fn list_deleted_collabs(
+    &self,
+    workspace_id: &str,
+  ) -> Result<DeletedCollabs, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/trash/collabs",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<DeletedCollabs>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  #[instrument(level = "info", skip_all, err)]
+  pub async fn restore_collab(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<(), AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/trash/collabs/{}/restore",
+      self.base_url, workspace_id, object_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Permanently deletes a collab of the trash.
+  #[instrument(level = "info", skip_all, err)]
+  pub async fn purge_collab(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<(), AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/trash/collabs/{}",
+      self.base_url, workspace_id, object_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
   #[instrument(level = "info", skip_all, err)]
   pub async fn list_databases(
     &self,
@@ -239,6 +357,106 @@ impl Client {
     AppResponse::from_response(resp).await?.into_data()
   }
 
+  /// Changes the type of a database field. The existing cells are converted in the background,
+  /// poll [Client::get_database_field_conversion] for the progress of the conversion.
+  pub async fn update_database_field_type(
+    &self,
+    workspace_id: &str,
+    database_id: &str,
+    field_id: &str,
+    params: &UpdateDatabaseFieldType,
+  ) -> Result<AFFieldConversionTask, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/database/{}/fields/{}/type",
+      self.base_url, workspace_id, database_id, field_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::PUT, &url)
+      .await?
+      .json(params)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::from_response(resp).await?.into_data()
+  }
+
+  pub async fn get_database_field_conversion(
+    &self,
+    workspace_id: &str,
+    database_id: &str,
+    task_id: &str,
+  ) -> Result<AFFieldConversionTask, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/database/{}/fields/conversion/{}",
+      self.base_url, workspace_id, database_id, task_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::from_response(resp).await?.into_data()
+  }
+
+  pub async fn get_database_row_access(
+    &self,
+    workspace_id: &str,
+    database_id: &str,
+  ) -> Result<AFDatabaseRowAccess, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/database/{}/row-access",
+      self.base_url, workspace_id, database_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::from_response(resp).await?.into_data()
+  }
+
+  /// Only the rows whose `field_id` cell names the member, by email or name, are visible to the
+  /// members of the workspace when `restricted_rows` is set. Only the workspace owner can set it.
+  pub async fn upsert_database_row_access(
+    &self,
+    workspace_id: &str,
+    database_id: &str,
+    params: &UpsertDatabaseRowAccess,
+  ) -> Result<AFDatabaseRowAccess, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/database/{}/row-access",
+      self.base_url, workspace_id, database_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::PUT, &url)
+      .await?
+      .json(params)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::from_response(resp).await?.into_data()
+  }
+
+  pub async fn delete_database_row_access(
+    &self,
+    workspace_id: &str,
+    database_id: &str,
+  ) -> Result<(), AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/database/{}/row-access",
+      self.base_url, workspace_id, database_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
   pub async fn list_database_row_ids_updated(
     &self,
     workspace_id: &str,
@@ -339,6 +557,81 @@ impl Client {
     AppResponse::from_response(resp).await?.into_data()
   }
 
+  /// Like [add_database_item], but inserts up to 100 rows at once. Unlike [add_database_item],
+  /// the request is rejected if a cell refers to a field which does not exist, or if its value
+  /// does not match the type of the field.
+  /// Upon success, returns the ids of the new rows, in the order of the given rows.
+  pub async fn batch_add_database_items(
+    &self,
+    workspace_id: &str,
+    database_id: &str,
+    rows: Vec<AddDatatabaseRow>,
+    create_select_options: bool,
+  ) -> Result<Vec<String>, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/database/{}/row/batch",
+      self.base_url, workspace_id, database_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&BatchAddDatabaseRows {
+        rows,
+        create_select_options,
+      })
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::from_response(resp).await?.into_data()
+  }
+
+  /// Updates the cells of an existing row, validating them like [batch_add_database_items].
+  pub async fn update_database_item(
+    &self,
+    workspace_id: &str,
+    database_id: &str,
+    row_id: &str,
+    cells_by_id: HashMap<String, serde_json::Value>,
+    row_doc_content: Option<String>,
+    create_select_options: bool,
+  ) -> Result<(), AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/database/{}/row/{}",
+      self.base_url, workspace_id, database_id, row_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::PATCH, &url)
+      .await?
+      .json(&UpdateDatabaseRow {
+        cells: cells_by_id,
+        document: row_doc_content,
+        create_select_options,
+      })
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  pub async fn delete_database_item(
+    &self,
+    workspace_id: &str,
+    database_id: &str,
+    row_id: &str,
+  ) -> Result<(), AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/database/{}/row/{}",
+      self.base_url, workspace_id, database_id, row_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
   #[instrument(level = "debug", skip_all, err)]
   pub async fn post_realtime_msg(
     &self,
@@ -419,12 +712,309 @@ impl Client {
     &self,
     params: QueryCollabParams,
   ) -> Result<CollabResponse, AppResponseError> {
+    if let Some(collab_cache) = self.config.collab_cache.clone() {
+      return self
+        .get_collab_with_cache(collab_cache.as_ref(), params)
+        .await;
+    }
+
     // 2 seconds, 4 seconds, 8 seconds
     let retry_strategy = ExponentialBackoff::from_millis(2).factor(1000).take(3);
     let action = GetCollabAction::new(self.clone(), params);
     RetryIf::spawn(retry_strategy, action, RetryGetCollabCondition).await
   }
 
+  /// Fetch the collab unless the copy held by the client, identified by
+  /// [CollabDiffParams::state_vector_hash], is up to date.
+  pub async fn get_collab_diff(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    params: CollabDiffParams,
+  ) -> Result<CollabDiffResponse, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/v1/{}/collab/{}/diff",
+      self.base_url, workspace_id, object_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&params)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<CollabDiffResponse>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Returns the headings of the document, without the rest of its content.
+  pub async fn get_document_outline(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<DocumentOutline, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/document/{}/outline",
+      self.base_url, workspace_id, object_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<DocumentOutline>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Finds the query in the blocks of the document, without downloading it.
+  pub async fn find_in_document(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    query: &FindInDocumentQuery,
+  ) -> Result<DocumentFindResult, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/document/{}/find",
+      self.base_url, workspace_id, object_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .query(query)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<DocumentFindResult>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Returns the blocks changed between two snapshots of the document, or between a snapshot and
+  /// its current version when `query.to` is `None`.
+  pub async fn get_document_snapshot_diff(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    query: &DocumentSnapshotDiffQuery,
+  ) -> Result<DocumentDiff, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/document/{}/snapshot/diff",
+      self.base_url, workspace_id, object_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .query(query)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<DocumentDiff>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Returns the editing lock of the collab, or `None` if no member holds it.
+  pub async fn get_editing_lock(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<Option<AFEditingLock>, AppResponseError> {
+    let url = self.editing_lock_url(workspace_id, object_id);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<Option<AFEditingLock>>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Locks the collab for editing. While the lock is held, the updates of other members are
+  /// rejected. The lock expires unless it's renewed with [Client::heartbeat_editing_lock].
+  pub async fn acquire_editing_lock(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<AFEditingLock, AppResponseError> {
+    let url = self.editing_lock_url(workspace_id, object_id);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<AFEditingLock>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  pub async fn heartbeat_editing_lock(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<AFEditingLock, AppResponseError> {
+    let url = format!(
+      "{}/heartbeat",
+      self.editing_lock_url(workspace_id, object_id)
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<AFEditingLock>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Releases the editing lock held by the current user. Workspace owners can release the lock
+  /// held by another member with `force`.
+  pub async fn release_editing_lock(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    force: bool,
+  ) -> Result<(), AppResponseError> {
+    let url = self.editing_lock_url(workspace_id, object_id);
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .query(&ReleaseEditingLockQuery { force })
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Returns the clients currently connected to the collab, as announced by their awareness.
+  pub async fn get_collab_presence(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<Vec<AFCollabPresence>, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/collab/{}/presence",
+      self.base_url, workspace_id, object_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<Vec<AFCollabPresence>>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  pub async fn get_collab_mode(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<CollabMode, AppResponseError> {
+    let url = self.collab_mode_url(workspace_id, object_id);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<CollabMode>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Sets the mode of a collab which isn't created yet. The updates of an opaque collab are
+  /// stored and relayed by the server without being decoded, so they can be encrypted.
+  pub async fn set_collab_mode(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    mode: CollabMode,
+  ) -> Result<(), AppResponseError> {
+    let url = self.collab_mode_url(workspace_id, object_id);
+    let resp = self
+      .http_client_with_auth(Method::PUT, &url)
+      .await?
+      .json(&UpdateCollabModeParams { mode })
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  fn collab_mode_url(&self, workspace_id: &str, object_id: &str) -> String {
+    format!(
+      "{}/api/workspace/{}/collab/{}/mode",
+      self.base_url, workspace_id, object_id
+    )
+  }
+
+  fn editing_lock_url(&self, workspace_id: &str, object_id: &str) -> String {
+    format!(
+      "{}/api/workspace/{}/collab/{}/editing-lock",
+      self.base_url, workspace_id, object_id
+    )
+  }
+
+  async fn get_collab_with_cache(
+    &self,
+    collab_cache: &dyn CollabCache,
+    params: QueryCollabParams,
+  ) -> Result<CollabResponse, AppResponseError> {
+    let cached = collab_cache.get(&params.object_id);
+    let diff = self
+      .get_collab_diff(
+        &params.workspace_id,
+        &params.object_id,
+        CollabDiffParams {
+          collab_type: params.collab_type.clone(),
+          state_vector_hash: cached.as_ref().map(|c| c.state_vector_hash.clone()),
+          state_vector: None,
+        },
+      )
+      .await?;
+
+    let encode_collab = match (diff.encode_collab, cached) {
+      (Some(encode_collab), _) => {
+        collab_cache.put(
+          &params.object_id,
+          CachedCollab {
+            encode_collab: encode_collab.clone(),
+            state_vector_hash: diff.state_vector_hash,
+          },
+        );
+        encode_collab
+      },
+      (None, Some(cached)) => {
+        event!(
+          tracing::Level::TRACE,
+          "collab {} not modified, use cached copy",
+          params.object_id
+        );
+        cached.encode_collab
+      },
+      (None, None) => {
+        return Err(AppResponseError::from(AppError::Internal(anyhow!(
+          "collab {} is not modified but there is no cached copy",
+          params.object_id
+        ))))
+      },
+    };
+
+    Ok(CollabResponse {
+      encode_collab,
+      object_id: params.object_id.clone(),
+      recovered_from: diff.recovered_from,
+    })
+  }
+
   pub async fn publish_collabs<Metadata, Data>(
     &self,
     workspace_id: &str,
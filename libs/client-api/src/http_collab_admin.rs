@@ -0,0 +1,74 @@
+use reqwest::Method;
+use shared_entity::dto::collab_compaction_dto::{CollabCompactionResult, CompactCollabQuery};
+use shared_entity::dto::collab_recovery_dto::{
+  CollabVerificationReport, ListCollabVerificationReportsQuery,
+};
+use shared_entity::response::{AppResponse, AppResponseError};
+
+use crate::http::log_request_id;
+use crate::Client;
+
+impl Client {
+  /// Lists the collabs whose stored state could not be decoded. Only available to the admins of
+  /// the instance.
+  pub async fn list_collab_verification_reports(
+    &self,
+    query: &ListCollabVerificationReportsQuery,
+  ) -> Result<Vec<CollabVerificationReport>, AppResponseError> {
+    let url = format!("{}/api/admin/collab/verification-reports", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .query(query)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<Vec<CollabVerificationReport>>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Restores the snapshot a corrupted collab was recovered from and makes it editable again.
+  pub async fn confirm_collab_repair(
+    &self,
+    report_id: i64,
+  ) -> Result<CollabVerificationReport, AppResponseError> {
+    let url = format!(
+      "{}/api/admin/collab/verification-reports/{}/repair",
+      self.base_url, report_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<CollabVerificationReport>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Replaces the stored state of the collab by a compacted copy holding only its current
+  /// content. The previous state is kept as a snapshot of the collab.
+  pub async fn compact_collab(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    query: &CompactCollabQuery,
+  ) -> Result<CollabCompactionResult, AppResponseError> {
+    let url = format!(
+      "{}/api/admin/collab/{}/{}/compact",
+      self.base_url, workspace_id, object_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .query(query)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<CollabCompactionResult>::from_response(resp)
+      .await?
+      .into_data()
+  }
+}
@@ -0,0 +1,164 @@
+use futures_util::Stream;
+use reqwest::Method;
+use shared_entity::dto::export_dto::{
+  ListNotificationSubscriptionsQuery, ListUserNotificationsQuery, MarkNotificationsReadParams,
+  NotificationSettings, NotificationSubscriptionLevel, NotificationSubscriptions,
+  SetNotificationSubscriptionParams, UserNotification, WorkspaceExportTask,
+};
+use shared_entity::dto::page_dto::{Cursor, Page};
+use shared_entity::response::{AppResponse, AppResponseError};
+use uuid::Uuid;
+
+use crate::{paginate, Client};
+
+// Workspace export API
+impl Client {
+  /// Request an export of the whole workspace. The archive is built in the background, use
+  /// [Client::get_workspace_export] to follow the progress.
+  pub async fn create_workspace_export(
+    &self,
+    workspace_id: &Uuid,
+  ) -> Result<WorkspaceExportTask, AppResponseError> {
+    let url = format!("{}/api/workspace/{}/export", self.base_url, workspace_id);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<WorkspaceExportTask>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  pub async fn get_workspace_export(
+    &self,
+    workspace_id: &Uuid,
+    task_id: &Uuid,
+  ) -> Result<WorkspaceExportTask, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/export/{}",
+      self.base_url, workspace_id, task_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<WorkspaceExportTask>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// A page of the notifications of the user, the most recent first.
+  pub async fn list_user_notifications(
+    &self,
+    only_unread: bool,
+    cursor: Option<Cursor>,
+    limit: Option<i64>,
+  ) -> Result<Page<UserNotification>, AppResponseError> {
+    let url = format!("{}/api/user/notification", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .query(&ListUserNotificationsQuery {
+        only_unread,
+        cursor,
+        limit,
+      })
+      .send()
+      .await?;
+    AppResponse::<Page<UserNotification>>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// The notifications of the user, following the cursors of the pages.
+  pub fn stream_user_notifications(
+    &self,
+    only_unread: bool,
+  ) -> impl Stream<Item = Result<UserNotification, AppResponseError>> + '_ {
+    paginate(move |cursor| self.list_user_notifications(only_unread, cursor, None))
+  }
+
+  pub async fn mark_user_notifications_read(
+    &self,
+    notification_ids: Vec<Uuid>,
+  ) -> Result<(), AppResponseError> {
+    let url = format!("{}/api/user/notification/read", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&MarkNotificationsReadParams { notification_ids })
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  pub async fn list_notification_subscriptions(
+    &self,
+    workspace_id: &Uuid,
+  ) -> Result<NotificationSubscriptions, AppResponseError> {
+    let url = format!("{}/api/user/notification/subscription", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .query(&ListNotificationSubscriptionsQuery {
+        workspace_id: *workspace_id,
+      })
+      .send()
+      .await?;
+    AppResponse::<NotificationSubscriptions>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Set whether the user is notified about the activity of the object. Setting it back to
+  /// [NotificationSubscriptionLevel::Default] removes the preference.
+  pub async fn set_notification_subscription(
+    &self,
+    workspace_id: &Uuid,
+    object_id: &Uuid,
+    level: NotificationSubscriptionLevel,
+  ) -> Result<(), AppResponseError> {
+    let url = format!(
+      "{}/api/user/notification/subscription/{}",
+      self.base_url, object_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::PUT, &url)
+      .await?
+      .json(&SetNotificationSubscriptionParams {
+        workspace_id: *workspace_id,
+        level,
+      })
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  pub async fn get_notification_settings(&self) -> Result<NotificationSettings, AppResponseError> {
+    let url = format!("{}/api/user/notification/setting", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<NotificationSettings>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  pub async fn update_notification_settings(
+    &self,
+    settings: &NotificationSettings,
+  ) -> Result<(), AppResponseError> {
+    let url = format!("{}/api/user/notification/setting", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::PUT, &url)
+      .await?
+      .json(settings)
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+}
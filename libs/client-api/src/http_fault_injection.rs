@@ -0,0 +1,54 @@
+use reqwest::Method;
+use shared_entity::dto::fault_injection_dto::FaultInjectionParams;
+use shared_entity::response::{AppResponse, AppResponseError};
+
+use crate::http::log_request_id;
+use crate::Client;
+
+impl Client {
+  /// Returns the faults injected by the server. Only available to the admins of an instance
+  /// started with `APPFLOWY_FAULT_INJECTION_ENABLED`.
+  pub async fn get_fault_injection_params(&self) -> Result<FaultInjectionParams, AppResponseError> {
+    let url = format!("{}/api/admin/fault-injection", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<FaultInjectionParams>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Replaces the faults injected by the server. The responses of the requests a fault was
+  /// injected into carry the `x-fault-injected` header.
+  pub async fn set_fault_injection_params(
+    &self,
+    params: &FaultInjectionParams,
+  ) -> Result<FaultInjectionParams, AppResponseError> {
+    let url = format!("{}/api/admin/fault-injection", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::PUT, &url)
+      .await?
+      .json(params)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<FaultInjectionParams>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Stops injecting faults.
+  pub async fn clear_fault_injection(&self) -> Result<(), AppResponseError> {
+    let url = format!("{}/api/admin/fault-injection", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+}
@@ -0,0 +1,44 @@
+use reqwest::Method;
+use shared_entity::dto::feature_flag_dto::{
+  FeatureFlag, FeatureFlagValue, RepeatedFeatureFlag, UpdateFeatureFlagParams,
+};
+use shared_entity::response::{AppResponse, AppResponseError};
+
+use crate::http::log_request_id;
+use crate::Client;
+
+impl Client {
+  /// Returns every feature flag of the deployment with its current value. Only available to the
+  /// admins of the instance.
+  pub async fn list_feature_flags(&self) -> Result<RepeatedFeatureFlag, AppResponseError> {
+    let url = format!("{}/api/admin/feature-flags", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<RepeatedFeatureFlag>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Sets the value of a feature flag. The value must be of the kind the flag was defined with.
+  pub async fn update_feature_flag(
+    &self,
+    name: &str,
+    value: FeatureFlagValue,
+  ) -> Result<FeatureFlag, AppResponseError> {
+    let url = format!("{}/api/admin/feature-flags/{}", self.base_url, name);
+    let resp = self
+      .http_client_with_auth(Method::PUT, &url)
+      .await?
+      .json(&UpdateFeatureFlagParams { value })
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<FeatureFlag>::from_response(resp)
+      .await?
+      .into_data()
+  }
+}
@@ -7,9 +7,10 @@ use async_trait::async_trait;
 use std::fs::metadata;
 
 use client_api_entity::{
-  CompleteUploadRequest, CreateUploadRequest, CreateUploadResponse, UploadPartResponse,
+  CompletePresignedUploadRequest, CompleteUploadRequest, CreateUploadRequest, CreateUploadResponse,
+  ListUploadPartsResponse, PresignedUploadRequest, PresignedUploadResponse, UploadPartResponse,
 };
-use client_api_entity::{CreateImportTask, CreateImportTaskResponse};
+use client_api_entity::{CreateImportTask, CreateImportTaskResponse, ImportSource};
 
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use reqwest::{multipart, Body, Method};
@@ -18,7 +19,7 @@ use std::path::Path;
 
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
-use shared_entity::dto::import_dto::UserImportTask;
+use shared_entity::dto::import_dto::{ImportReport, ImportTaskDetail, UserImportTask};
 use tokio::fs::File;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio_util::codec::{BytesCodec, FramedRead};
@@ -84,6 +85,31 @@ impl Client {
       .into_data()
   }
 
+  /// List the parts already uploaded for an upload, to resume it after an interruption by
+  /// uploading the missing parts only. The parts are sorted by part number.
+  pub async fn list_upload_parts(
+    &self,
+    workspace_id: &str,
+    parent_dir: &str,
+    file_id: &str,
+    upload_id: &str,
+  ) -> Result<ListUploadPartsResponse, AppResponseError> {
+    let parent_dir = utf8_percent_encode(parent_dir, NON_ALPHANUMERIC).to_string();
+    let url = format!(
+      "{}/api/file_storage/{workspace_id}/upload_part/{parent_dir}/{file_id}/{upload_id}",
+      self.base_url
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<ListUploadPartsResponse>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
   pub async fn complete_upload(
     &self,
     workspace_id: &str,
@@ -103,6 +129,79 @@ impl Client {
     AppResponse::<()>::from_response(resp).await?.into_error()
   }
 
+  /// Request a url to upload a file straight to the storage bucket, for the files too large to go
+  /// through the server. Upload the file with [Self::upload_presigned_file], then record it with
+  /// [Self::complete_presigned_upload] before the url expires.
+  pub async fn create_presigned_upload(
+    &self,
+    workspace_id: &str,
+    req: PresignedUploadRequest,
+  ) -> Result<PresignedUploadResponse, AppResponseError> {
+    let url = format!(
+      "{}/api/file_storage/{workspace_id}/presigned_upload",
+      self.base_url
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&req)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<PresignedUploadResponse>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Stream the file to the url returned by [Self::create_presigned_upload]. The content type
+  /// must be the one the url was requested with.
+  pub async fn upload_presigned_file(
+    &self,
+    file_path: &Path,
+    url: &str,
+    content_type: &str,
+  ) -> Result<(), AppResponseError> {
+    let file_size = metadata(file_path)?.len();
+    let file = File::open(file_path).await?;
+    let stream_body = Body::wrap_stream(FramedRead::new(file, BytesCodec::new()));
+    trace!("start upload file to s3: {}", url);
+
+    let upload_resp = reqwest::Client::new()
+      .put(url)
+      .header("Content-Length", file_size)
+      .header("Content-Type", content_type)
+      .body(stream_body)
+      .send()
+      .await?;
+
+    if !upload_resp.status().is_success() {
+      error!("File upload failed: {:?}", upload_resp);
+      return Err(AppError::S3ResponseError("Cannot upload file to S3".to_string()).into());
+    }
+    Ok(())
+  }
+
+  /// Record a file uploaded with a presigned url. Fails when the uploaded file is missing or
+  /// doesn't have the declared size.
+  pub async fn complete_presigned_upload(
+    &self,
+    workspace_id: &str,
+    req: CompletePresignedUploadRequest,
+  ) -> Result<(), AppResponseError> {
+    let url = format!(
+      "{}/api/file_storage/{}/presigned_upload/complete",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::PUT, &url)
+      .await?
+      .json(&req)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
   /// Sends a POST request to import a file to the server.
   ///
   /// This function streams the contents of a file located at the provided `file_path`
@@ -177,6 +276,15 @@ impl Client {
   pub async fn create_import(
     &self,
     file_path: &Path,
+  ) -> Result<CreateImportTaskResponse, AppResponseError> {
+    self.create_import_from_source(file_path, None).await
+  }
+
+  /// Same as [Self::create_import], for a file exported from `source`.
+  pub async fn create_import_from_source(
+    &self,
+    file_path: &Path,
+    source: Option<ImportSource>,
   ) -> Result<CreateImportTaskResponse, AppResponseError> {
     let url = format!("{}/api/import/create", self.base_url);
     let file_name = file_path
@@ -188,6 +296,7 @@ impl Client {
     let params = CreateImportTask {
       workspace_name: file_name.clone(),
       content_length,
+      source,
     };
     let resp = self
       .http_client_with_auth(Method::POST, &url)
@@ -252,6 +361,21 @@ impl Client {
       .await?
       .into_data()
   }
+
+  /// Returns the report of an import task, available once the import ran. See
+  /// [ImportTaskDetail::has_report].
+  pub async fn get_import_report(&self, task_id: &str) -> Result<ImportReport, AppResponseError> {
+    let url = format!("{}/api/import/{}/report", self.base_url, task_id);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+
+    AppResponse::<ImportReport>::from_response(resp)
+      .await?
+      .into_data()
+  }
 }
 
 #[async_trait]
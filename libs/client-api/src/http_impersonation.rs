@@ -0,0 +1,103 @@
+use reqwest::Method;
+use shared_entity::dto::impersonation_dto::{
+  CreateImpersonationParams, GrantImpersonationWriteParams, Impersonation, ImpersonationRequests,
+  ImpersonationToken, Impersonations,
+};
+use shared_entity::response::{AppResponse, AppResponseError};
+use uuid::Uuid;
+
+use crate::http::log_request_id;
+use crate::Client;
+
+// The impersonations are only available to the admins of the instance.
+impl Client {
+  /// Returns an access token to act as the user, read-only until writes are granted with
+  /// [Client::admin_grant_impersonation_write]. The user is notified.
+  pub async fn admin_create_impersonation(
+    &self,
+    params: &CreateImpersonationParams,
+  ) -> Result<ImpersonationToken, AppResponseError> {
+    let url = format!("{}/api/admin/impersonations", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(params)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<ImpersonationToken>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  pub async fn admin_list_impersonations(&self) -> Result<Impersonations, AppResponseError> {
+    let url = format!("{}/api/admin/impersonations", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<Impersonations>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  pub async fn admin_revoke_impersonation(
+    &self,
+    impersonation_id: &Uuid,
+  ) -> Result<(), AppResponseError> {
+    let url = format!(
+      "{}/api/admin/impersonations/{}",
+      self.base_url, impersonation_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  pub async fn admin_grant_impersonation_write(
+    &self,
+    impersonation_id: &Uuid,
+    params: &GrantImpersonationWriteParams,
+  ) -> Result<Impersonation, AppResponseError> {
+    let url = format!(
+      "{}/api/admin/impersonations/{}/write",
+      self.base_url, impersonation_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(params)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<Impersonation>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Returns the requests made under the impersonation, the most recent first.
+  pub async fn admin_list_impersonation_requests(
+    &self,
+    impersonation_id: &Uuid,
+  ) -> Result<ImpersonationRequests, AppResponseError> {
+    let url = format!(
+      "{}/api/admin/impersonations/{}/requests",
+      self.base_url, impersonation_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<ImpersonationRequests>::from_response(resp)
+      .await?
+      .into_data()
+  }
+}
@@ -0,0 +1,89 @@
+use reqwest::Method;
+use shared_entity::dto::inbound_email_dto::{InboundEmailAddress, SetInboundEmailParams};
+use shared_entity::response::{AppResponse, AppResponseError};
+use uuid::Uuid;
+
+use crate::http::log_request_id;
+use crate::Client;
+
+impl Client {
+  /// Returns the address whose emails are appended to a page of the workspace.
+  pub async fn get_inbound_email_address(
+    &self,
+    workspace_id: &Uuid,
+  ) -> Result<InboundEmailAddress, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/inbound-email",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<InboundEmailAddress>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Sets the page the emails sent to the inbound address are appended to. The address is created
+  /// if the workspace doesn't have one yet.
+  pub async fn set_inbound_email_address(
+    &self,
+    workspace_id: &Uuid,
+    params: &SetInboundEmailParams,
+  ) -> Result<InboundEmailAddress, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/inbound-email",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::PUT, &url)
+      .await?
+      .json(params)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<InboundEmailAddress>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Replaces the inbound address, the previous one stops accepting emails.
+  pub async fn rotate_inbound_email_address(
+    &self,
+    workspace_id: &Uuid,
+  ) -> Result<InboundEmailAddress, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/inbound-email/rotate",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<InboundEmailAddress>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  pub async fn delete_inbound_email_address(
+    &self,
+    workspace_id: &Uuid,
+  ) -> Result<(), AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/inbound-email",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+}
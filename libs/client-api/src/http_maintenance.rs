@@ -0,0 +1,56 @@
+use reqwest::Method;
+use shared_entity::dto::maintenance_dto::{MaintenanceJob, MaintenanceJobReport};
+use shared_entity::response::{AppResponse, AppResponseError};
+use uuid::Uuid;
+
+use crate::Client;
+
+// Workspace maintenance API
+impl Client {
+  /// Run a destructive maintenance job on the workspace. With `dry_run` set nothing is deleted
+  /// and the returned report can be executed with [Client::execute_last_maintenance_dry_run].
+  pub async fn run_maintenance_job(
+    &self,
+    workspace_id: &Uuid,
+    job: MaintenanceJob,
+    dry_run: bool,
+  ) -> Result<MaintenanceJobReport, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/maintenance/{}",
+      self.base_url,
+      workspace_id,
+      job.as_str()
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .query(&[("dry_run", dry_run)])
+      .send()
+      .await?;
+    AppResponse::<MaintenanceJobReport>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Delete exactly what the last dry run of the job selected
+  pub async fn execute_last_maintenance_dry_run(
+    &self,
+    workspace_id: &Uuid,
+    job: MaintenanceJob,
+  ) -> Result<MaintenanceJobReport, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/maintenance/{}/execute-last-dry-run",
+      self.base_url,
+      workspace_id,
+      job.as_str()
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<MaintenanceJobReport>::from_response(resp)
+      .await?
+      .into_data()
+  }
+}
@@ -1,11 +1,15 @@
 use crate::http::log_request_id;
-use crate::Client;
+use crate::{paginate, Client};
 use client_api_entity::{
-  AFWorkspaceInvitation, AFWorkspaceInvitationStatus, AFWorkspaceMember, QueryWorkspaceMember,
+  AFWorkspaceInvitation, AFWorkspaceInvitationStatus, AFWorkspaceMember,
+  AFWorkspacePendingInvitation, QueryWorkspaceMember, WorkspaceMemberFilter,
 };
+use futures_util::{Stream, TryStreamExt};
 use reqwest::Method;
+use shared_entity::dto::page_dto::{Cursor, Page, PageQuery};
 use shared_entity::dto::workspace_dto::{
-  CreateWorkspaceMembers, WorkspaceMemberChangeset, WorkspaceMemberInvitation, WorkspaceMembers,
+  CreateWorkspaceMembers, ExtendWorkspaceInvitationParams, WorkspaceMemberChangeset,
+  WorkspaceMemberInvitation, WorkspaceMembers,
 };
 use shared_entity::response::{AppResponse, AppResponseError};
 use tracing::instrument;
@@ -21,26 +25,68 @@ impl Client {
       .send()
       .await?;
     log_request_id(&resp);
+    self.record_policy_version(&resp);
     AppResponse::<()>::from_response(resp).await?.into_error()
   }
 
+  /// Every member of the workspace, fetched page by page.
   #[instrument(level = "info", skip_all, err)]
   pub async fn get_workspace_members<W: AsRef<str>>(
     &self,
     workspace_id: W,
   ) -> Result<Vec<AFWorkspaceMember>, AppResponseError> {
-    let url = format!(
-      "{}/api/workspace/{}/member",
-      self.base_url,
-      workspace_id.as_ref()
-    );
+    self
+      .stream_workspace_members(workspace_id.as_ref())
+      .try_collect()
+      .await
+  }
+
+  /// The members of the workspace, following the cursors of the pages.
+  pub fn stream_workspace_members<'a>(
+    &'a self,
+    workspace_id: &'a str,
+  ) -> impl Stream<Item = Result<AFWorkspaceMember, AppResponseError>> + 'a {
+    paginate(move |cursor| self.get_workspace_member_page(workspace_id, cursor, None))
+  }
+
+  /// A page of the members of the workspace, the earliest to join first.
+  pub async fn get_workspace_member_page(
+    &self,
+    workspace_id: &str,
+    cursor: Option<Cursor>,
+    limit: Option<u32>,
+  ) -> Result<Page<AFWorkspaceMember>, AppResponseError> {
+    self
+      .get_workspace_members_paged(
+        workspace_id,
+        &WorkspaceMemberFilter::default(),
+        cursor,
+        limit,
+      )
+      .await
+  }
+
+  /// A page of the members of the workspace matching the filter, in its order. The total of the
+  /// page counts the members matching the filter. The cursor of the next page must be sent along
+  /// with the same sort order.
+  #[instrument(level = "info", skip_all, err)]
+  pub async fn get_workspace_members_paged(
+    &self,
+    workspace_id: &str,
+    filter: &WorkspaceMemberFilter,
+    cursor: Option<Cursor>,
+    limit: Option<u32>,
+  ) -> Result<Page<AFWorkspaceMember>, AppResponseError> {
+    let url = format!("{}/api/workspace/{}/member", self.base_url, workspace_id);
     let resp = self
       .http_client_with_auth(Method::GET, &url)
       .await?
+      .query(&PageQuery { cursor, limit })
+      .query(filter)
       .send()
       .await?;
     log_request_id(&resp);
-    AppResponse::<Vec<AFWorkspaceMember>>::from_response(resp)
+    AppResponse::<Page<AFWorkspaceMember>>::from_response(resp)
       .await?
       .into_data()
   }
@@ -63,6 +109,48 @@ impl Client {
     Ok(())
   }
 
+  /// The pending invitations of a workspace, only listed to its owners.
+  #[instrument(level = "info", skip_all, err)]
+  pub async fn list_workspace_pending_invitations(
+    &self,
+    workspace_id: &str,
+  ) -> Result<Vec<AFWorkspacePendingInvitation>, AppResponseError> {
+    let url = format!("{}/api/workspace/{}/invite", self.base_url, workspace_id);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<Vec<AFWorkspacePendingInvitation>>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Resets the expiry of a pending invitation, and sends its email again when asked to.
+  #[instrument(level = "info", skip_all, err)]
+  pub async fn extend_workspace_invitation(
+    &self,
+    workspace_id: &str,
+    invite_id: &str,
+    params: &ExtendWorkspaceInvitationParams,
+  ) -> Result<AFWorkspacePendingInvitation, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/invite/{}/extend",
+      self.base_url, workspace_id, invite_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(params)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<AFWorkspacePendingInvitation>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
   pub async fn list_workspace_invitations(
     &self,
     status: Option<AFWorkspaceInvitationStatus>,
@@ -108,6 +196,7 @@ impl Client {
       .send()
       .await?;
     log_request_id(&resp);
+    self.record_policy_version(&resp);
     AppResponse::<()>::from_response(resp).await?.into_error()?;
     Ok(())
   }
@@ -132,6 +221,7 @@ impl Client {
       .send()
       .await?;
     log_request_id(&resp);
+    self.record_policy_version(&resp);
     AppResponse::<()>::from_response(resp).await?.into_error()?;
     Ok(())
   }
@@ -154,6 +244,7 @@ impl Client {
       .send()
       .await?;
     log_request_id(&resp);
+    self.record_policy_version(&resp);
     AppResponse::<()>::from_response(resp).await?.into_error()?;
     Ok(())
   }
@@ -177,6 +268,7 @@ impl Client {
       .send()
       .await?;
     log_request_id(&resp);
+    self.record_policy_version(&resp);
     AppResponse::<()>::from_response(resp).await?.into_error()?;
     Ok(())
   }
@@ -200,4 +292,29 @@ impl Client {
       .await?
       .into_data()
   }
+
+  /// The members of the workspace matching `filter`, as the CSV file served to the owner.
+  #[instrument(level = "info", skip_all, err)]
+  pub async fn export_workspace_members_csv(
+    &self,
+    workspace_id: &str,
+    filter: &WorkspaceMemberFilter,
+  ) -> Result<String, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/member/export",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .query(filter)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    if resp.status().is_success() {
+      Ok(resp.text().await?)
+    } else {
+      AppResponse::from_response(resp).await?.into_data()
+    }
+  }
 }
@@ -0,0 +1,50 @@
+use reqwest::Method;
+use shared_entity::dto::merge_dto::{MergeWorkspaceParams, WorkspaceMergeTask};
+use shared_entity::response::{AppResponse, AppResponseError};
+use uuid::Uuid;
+
+use crate::Client;
+
+// Workspace merge API
+impl Client {
+  /// Merge the source workspace into the workspace, the user must own both. The members are
+  /// merged right away while the content is moved in the background, use
+  /// [Client::get_workspace_merge] to follow the progress.
+  pub async fn merge_workspace(
+    &self,
+    workspace_id: &Uuid,
+    source_workspace_id: &Uuid,
+  ) -> Result<WorkspaceMergeTask, AppResponseError> {
+    let url = format!("{}/api/workspace/{}/merge", self.base_url, workspace_id);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&MergeWorkspaceParams {
+        source_workspace_id: *source_workspace_id,
+      })
+      .send()
+      .await?;
+    AppResponse::<WorkspaceMergeTask>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  pub async fn get_workspace_merge(
+    &self,
+    workspace_id: &Uuid,
+    task_id: &Uuid,
+  ) -> Result<WorkspaceMergeTask, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/merge/{}",
+      self.base_url, workspace_id, task_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<WorkspaceMergeTask>::from_response(resp)
+      .await?
+      .into_data()
+  }
+}
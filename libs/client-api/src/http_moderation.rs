@@ -0,0 +1,101 @@
+use reqwest::Method;
+use shared_entity::dto::moderation_dto::{
+  ListPublishedViewReportsQuery, PublishNamespaceRisk, PublishedViewReport,
+  ReportPublishedViewParams, ResolvePublishedViewReportParams,
+};
+use shared_entity::response::{AppResponse, AppResponseError};
+use uuid::Uuid;
+
+use crate::http::log_request_id;
+use crate::Client;
+
+impl Client {
+  /// Reports the view published under the given namespace and name. It doesn't require the user
+  /// to be signed in.
+  pub async fn report_published_view(
+    &self,
+    publish_namespace: &str,
+    publish_name: &str,
+    params: &ReportPublishedViewParams,
+  ) -> Result<(), AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/published/{}/{}/report",
+      self.base_url, publish_namespace, publish_name
+    );
+    let resp = self.cloud_client.post(&url).json(params).send().await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  pub async fn list_published_view_reports(
+    &self,
+    query: &ListPublishedViewReportsQuery,
+  ) -> Result<Vec<PublishedViewReport>, AppResponseError> {
+    let url = format!("{}/api/admin/moderation/report", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .query(query)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<Vec<PublishedViewReport>>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  pub async fn resolve_published_view_report(
+    &self,
+    report_id: &Uuid,
+    params: &ResolvePublishedViewReportParams,
+  ) -> Result<PublishedViewReport, AppResponseError> {
+    let url = format!(
+      "{}/api/admin/moderation/report/{}/resolve",
+      self.base_url, report_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(params)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<PublishedViewReport>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  pub async fn get_publish_namespace_risk(
+    &self,
+    publish_namespace: &str,
+  ) -> Result<PublishNamespaceRisk, AppResponseError> {
+    let url = format!(
+      "{}/api/admin/moderation/namespace/{}/risk",
+      self.base_url, publish_namespace
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<PublishNamespaceRisk>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Allows publishing the view again after it was taken down.
+  pub async fn lift_published_view_takedown(&self, view_id: &Uuid) -> Result<(), AppResponseError> {
+    let url = format!(
+      "{}/api/admin/moderation/takedown/{}",
+      self.base_url, view_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+}
@@ -0,0 +1,47 @@
+use reqwest::Method;
+use shared_entity::dto::provisioning_dto::{ProvisionWorkspaceParams, WorkspaceProvisioning};
+use shared_entity::response::{AppResponse, AppResponseError};
+
+use crate::http::log_request_id;
+use crate::Client;
+
+impl Client {
+  /// Provisions a workspace from a spec. Only available to the service accounts of the instance.
+  /// The workspace is created before returning, its members and template are added in the
+  /// background: poll [Client::get_workspace_provisioning] until the provisioning completes.
+  pub async fn provision_workspace(
+    &self,
+    params: ProvisionWorkspaceParams,
+  ) -> Result<WorkspaceProvisioning, AppResponseError> {
+    let url = format!("{}/api/admin/workspace-provisioning", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(&params)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<WorkspaceProvisioning>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  pub async fn get_workspace_provisioning(
+    &self,
+    external_id: &str,
+  ) -> Result<WorkspaceProvisioning, AppResponseError> {
+    let url = format!(
+      "{}/api/admin/workspace-provisioning/{}",
+      self.base_url, external_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<WorkspaceProvisioning>::from_response(resp)
+      .await?
+      .into_data()
+  }
+}
@@ -1,13 +1,13 @@
 use bytes::Bytes;
 use client_api_entity::publish_dto::DuplicatePublishedPageResponse;
-use client_api_entity::workspace_dto::{PublishInfoView, PublishedView};
+use client_api_entity::workspace_dto::{DocumentOutline, PublishInfoView, PublishedView};
 use client_api_entity::{workspace_dto::PublishedDuplicate, PublishInfo, UpdatePublishNamespace};
 use client_api_entity::{
   CreateGlobalCommentParams, CreateReactionParams, DeleteGlobalCommentParams, DeleteReactionParams,
   GetReactionQueryParams, GlobalComments, PatchPublishedCollab, PublishInfoMeta, Reactions,
   UpdateDefaultPublishView,
 };
-use reqwest::Method;
+use reqwest::{Method, StatusCode};
 use shared_entity::response::{AppResponse, AppResponseError};
 use tracing::instrument;
 
@@ -407,6 +407,80 @@ impl Client {
     Ok(bytes)
   }
 
+  /// Returns the published blob with its ETag, or `None` when `if_none_match` is still the ETag
+  /// of the published revision.
+  pub async fn get_published_collab_blob_if_modified(
+    &self,
+    publish_namespace: &str,
+    publish_name: &str,
+    if_none_match: Option<&str>,
+  ) -> Result<Option<(String, Bytes)>, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/published/{}/{}/blob",
+      self.base_url, publish_namespace, publish_name
+    );
+    let mut req = self.cloud_client.get(&url);
+    if let Some(etag) = if_none_match {
+      req = req.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+    let resp = req.send().await?;
+    log_request_id(&resp);
+    if resp.status() == StatusCode::NOT_MODIFIED {
+      return Ok(None);
+    }
+    let resp = resp.error_for_status()?;
+    let etag = resp
+      .headers()
+      .get(reqwest::header::ETAG)
+      .and_then(|value| value.to_str().ok())
+      .unwrap_or_default()
+      .to_string();
+    let bytes = resp.bytes().await?;
+    if let Ok(app_err) = serde_json::from_slice::<AppResponseError>(&bytes) {
+      return Err(app_err);
+    }
+    Ok(Some((etag, bytes)))
+  }
+
+  /// Returns the blob of the given revision of a published view. `revision` is the ETag of the
+  /// revision, without quotes.
+  pub async fn get_published_collab_blob_at_revision(
+    &self,
+    publish_namespace: &str,
+    publish_name: &str,
+    revision: &str,
+  ) -> Result<Bytes, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/published/{}/{}/blob/{}",
+      self.base_url, publish_namespace, publish_name, revision
+    );
+    let resp = self.cloud_client.get(&url).send().await?;
+    log_request_id(&resp);
+    let bytes = resp.error_for_status()?.bytes().await?;
+    if let Ok(app_err) = serde_json::from_slice::<AppResponseError>(&bytes) {
+      return Err(app_err);
+    }
+    Ok(bytes)
+  }
+
+  /// Returns the headings of a published revision of a document.
+  pub async fn get_published_document_outline(
+    &self,
+    publish_namespace: &str,
+    publish_name: &str,
+    revision: &str,
+  ) -> Result<DocumentOutline, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/published/{}/{}/outline/{}",
+      self.base_url, publish_namespace, publish_name, revision
+    );
+    let resp = self.cloud_client.get(&url).send().await?;
+    log_request_id(&resp);
+    AppResponse::<DocumentOutline>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
   pub async fn duplicate_published_to_workspace(
     &self,
     workspace_id: &str,
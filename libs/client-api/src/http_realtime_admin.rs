@@ -0,0 +1,30 @@
+use reqwest::Method;
+use shared_entity::dto::realtime_dto::{TopWorkspacesByBandwidthQuery, WorkspaceBandwidth};
+use shared_entity::response::{AppResponse, AppResponseError};
+
+use crate::http::log_request_id;
+use crate::Client;
+
+impl Client {
+  /// Returns the workspaces which exchanged the most bytes with the realtime clients. Only
+  /// available to the admins of the instance.
+  pub async fn get_top_workspaces_by_realtime_bandwidth(
+    &self,
+    query: &TopWorkspacesByBandwidthQuery,
+  ) -> Result<Vec<WorkspaceBandwidth>, AppResponseError> {
+    let url = format!(
+      "{}/api/admin/realtime/bandwidth/top-workspaces",
+      self.base_url
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .query(query)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<Vec<WorkspaceBandwidth>>::from_response(resp)
+      .await?
+      .into_data()
+  }
+}
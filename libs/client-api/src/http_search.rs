@@ -1,5 +1,6 @@
 use app_error::ErrorCode;
 use reqwest::Method;
+use shared_entity::dto::page_dto::{Cursor, Page};
 use shared_entity::dto::search_dto::SearchDocumentResponseItem;
 use shared_entity::response::{AppResponse, AppResponseError};
 
@@ -7,6 +8,7 @@ use crate::http::log_request_id;
 use crate::Client;
 
 impl Client {
+  /// The first page of the results, the most relevant first.
   pub async fn search_documents(
     &self,
     workspace_id: &str,
@@ -14,12 +16,34 @@ impl Client {
     limit: u32,
     preview_size: u32,
   ) -> Result<Vec<SearchDocumentResponseItem>, AppResponseError> {
-    let query = serde_urlencoded::to_string([
+    let page = self
+      .search_documents_page(workspace_id, query, limit, preview_size, None)
+      .await?;
+    Ok(page.items)
+  }
+
+  /// A page of the results, pass the `next_cursor` of a page along with the same query to get
+  /// the next one.
+  pub async fn search_documents_page(
+    &self,
+    workspace_id: &str,
+    query: &str,
+    limit: u32,
+    preview_size: u32,
+    cursor: Option<Cursor>,
+  ) -> Result<Page<SearchDocumentResponseItem>, AppResponseError> {
+    let limit = limit.to_string();
+    let preview_size = preview_size.to_string();
+    let mut params = vec![
       ("query", query),
-      ("limit", &limit.to_string()),
-      ("preview_size", &preview_size.to_string()),
-    ])
-    .map_err(|err| AppResponseError::new(ErrorCode::InvalidRequest, err.to_string()))?;
+      ("limit", &limit),
+      ("preview_size", &preview_size),
+    ];
+    if let Some(cursor) = &cursor {
+      params.push(("cursor", cursor.as_str()));
+    }
+    let query = serde_urlencoded::to_string(params)
+      .map_err(|err| AppResponseError::new(ErrorCode::InvalidRequest, err.to_string()))?;
     let url = format!("{}/api/search/{workspace_id}?{query}", self.base_url);
     let resp = self
       .http_client_with_auth(Method::GET, &url)
@@ -27,7 +51,7 @@ impl Client {
       .send()
       .await?;
     log_request_id(&resp);
-    AppResponse::<Vec<SearchDocumentResponseItem>>::from_response(resp)
+    AppResponse::<Page<SearchDocumentResponseItem>>::from_response(resp)
       .await?
       .into_data()
   }
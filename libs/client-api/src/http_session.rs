@@ -0,0 +1,109 @@
+use reqwest::Method;
+use shared_entity::dto::session_dto::{RevokedSessions, SessionRevocations, UserSessions};
+use shared_entity::response::{AppResponse, AppResponseError};
+use uuid::Uuid;
+
+use crate::http::log_request_id;
+use crate::Client;
+
+impl Client {
+  /// Returns the signed in devices of the user.
+  pub async fn list_user_sessions(&self) -> Result<UserSessions, AppResponseError> {
+    let url = format!("{}/api/user/session", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<UserSessions>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Signs the device of the session out. Its token can't be refreshed anymore and its realtime
+  /// connection is closed.
+  pub async fn revoke_user_session(
+    &self,
+    session_id: &Uuid,
+  ) -> Result<RevokedSessions, AppResponseError> {
+    let url = format!("{}/api/user/session/{}", self.base_url, session_id);
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<RevokedSessions>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Signs out every device of the user but this one.
+  pub async fn revoke_other_user_sessions(&self) -> Result<RevokedSessions, AppResponseError> {
+    let url = format!("{}/api/user/session", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<RevokedSessions>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Returns the signed in devices of any user. Only available to the admins of the instance.
+  pub async fn admin_list_user_sessions(
+    &self,
+    user_uuid: &Uuid,
+  ) -> Result<UserSessions, AppResponseError> {
+    let url = format!("{}/api/admin/users/{}/sessions", self.base_url, user_uuid);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<UserSessions>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Signs any user out of all their devices. Only available to the admins of the instance.
+  pub async fn admin_revoke_user_sessions(
+    &self,
+    user_uuid: &Uuid,
+  ) -> Result<RevokedSessions, AppResponseError> {
+    let url = format!("{}/api/admin/users/{}/sessions", self.base_url, user_uuid);
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<RevokedSessions>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Returns who revoked the sessions of any user. Only available to the admins of the instance.
+  pub async fn admin_list_user_session_revocations(
+    &self,
+    user_uuid: &Uuid,
+  ) -> Result<SessionRevocations, AppResponseError> {
+    let url = format!(
+      "{}/api/admin/users/{}/sessions/revocations",
+      self.base_url, user_uuid
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<SessionRevocations>::from_response(resp)
+      .await?
+      .into_data()
+  }
+}
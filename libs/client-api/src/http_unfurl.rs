@@ -0,0 +1,26 @@
+use app_error::ErrorCode;
+use reqwest::Method;
+use shared_entity::dto::unfurl_dto::UnfurlMetadata;
+use shared_entity::response::{AppResponse, AppResponseError};
+
+use crate::http::log_request_id;
+use crate::Client;
+
+impl Client {
+  /// Returns the metadata of the page at the given url, fetched by the server. If the page can
+  /// not be fetched, only the normalized url is returned.
+  pub async fn unfurl_url(&self, url: &str) -> Result<UnfurlMetadata, AppResponseError> {
+    let query = serde_urlencoded::to_string([("url", url)])
+      .map_err(|err| AppResponseError::new(ErrorCode::InvalidRequest, err.to_string()))?;
+    let url = format!("{}/api/unfurl?{query}", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<UnfurlMetadata>::from_response(resp)
+      .await?
+      .into_data()
+  }
+}
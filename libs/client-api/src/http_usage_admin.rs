@@ -0,0 +1,87 @@
+use reqwest::Method;
+use shared_entity::dto::billing_dto::{
+  RecomputedWorkspaceUsage, RollupWorkspaceUsageParams, WorkspaceUsageDaily,
+  WorkspaceUsageDailyQuery,
+};
+use shared_entity::response::{AppResponse, AppResponseError};
+use uuid::Uuid;
+
+use crate::http::log_request_id;
+use crate::Client;
+
+impl Client {
+  /// Returns the usage of the workspaces rolled up per day. Only available to the admins of the
+  /// instance.
+  pub async fn get_workspace_usage_daily(
+    &self,
+    query: &WorkspaceUsageDailyQuery,
+  ) -> Result<Vec<WorkspaceUsageDaily>, AppResponseError> {
+    let url = format!("{}/api/admin/usage/daily", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .query(query)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<Vec<WorkspaceUsageDaily>>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Same usage as [Client::get_workspace_usage_daily], as a CSV file.
+  pub async fn export_workspace_usage_daily_csv(
+    &self,
+    query: &WorkspaceUsageDailyQuery,
+  ) -> Result<String, AppResponseError> {
+    let url = format!("{}/api/admin/usage/daily/export", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .query(query)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    if resp.status().is_success() {
+      Ok(resp.text().await?)
+    } else {
+      AppResponse::from_response(resp).await?.into_data()
+    }
+  }
+
+  /// Rolls up the usage of the days again.
+  pub async fn rollup_workspace_usage_daily(
+    &self,
+    params: &RollupWorkspaceUsageParams,
+  ) -> Result<(), AppResponseError> {
+    let url = format!("{}/api/admin/usage/daily/rollup", self.base_url);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(params)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  /// Recomputes the usage counter of the workspace from its blobs, when it drifted.
+  pub async fn recompute_workspace_usage(
+    &self,
+    workspace_id: &Uuid,
+  ) -> Result<RecomputedWorkspaceUsage, AppResponseError> {
+    let url = format!(
+      "{}/api/admin/usage/{}/recompute",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<RecomputedWorkspaceUsage>::from_response(resp)
+      .await?
+      .into_data()
+  }
+}
@@ -1,9 +1,12 @@
 use client_api_entity::workspace_dto::{
-  CreatePageParams, CreateSpaceParams, MovePageParams, Page, PageCollab, PublishPageParams, Space,
-  UpdatePageParams, UpdateSpaceParams,
+  CreatePageParams, CreateSpaceParams, MovePageParams, Page, PageCollab, PublishPageParams,
+  PublishRevision, RepublishPageParams, Space, UpdatePageParams, UpdateSpaceParams,
 };
 use reqwest::Method;
 use serde_json::json;
+use shared_entity::dto::similar_page_dto::{
+  SimilarPageClusters, SimilarPagesQuery, TrashSimilarPagesParams, TrashedSimilarPages,
+};
 use shared_entity::response::{AppResponse, AppResponseError};
 use uuid::Uuid;
 
@@ -206,6 +209,44 @@ impl Client {
     AppResponse::<()>::from_response(resp).await?.into_error()
   }
 
+  pub async fn republish_page(
+    &self,
+    workspace_id: Uuid,
+    view_id: &str,
+    params: &RepublishPageParams,
+  ) -> Result<(), AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/page-view/{}/republish",
+      self.base_url, workspace_id, view_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(params)
+      .send()
+      .await?;
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+
+  pub async fn get_publish_revision(
+    &self,
+    workspace_id: Uuid,
+    view_id: &str,
+  ) -> Result<PublishRevision, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/page-view/{}/publish-revision",
+      self.base_url, workspace_id, view_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    AppResponse::<PublishRevision>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
   pub async fn create_space(
     &self,
     workspace_id: Uuid,
@@ -239,4 +280,47 @@ impl Client {
       .await?;
     AppResponse::<()>::from_response(resp).await?.into_error()
   }
+
+  /// Returns the clusters of pages of the workspace whose content is nearly the same.
+  pub async fn list_similar_pages(
+    &self,
+    workspace_id: Uuid,
+    threshold: Option<f32>,
+  ) -> Result<SimilarPageClusters, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/similar-pages",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .query(&SimilarPagesQuery { threshold })
+      .send()
+      .await?;
+    AppResponse::<SimilarPageClusters>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Moves the pages to the trash, skipping the published and recently edited ones unless the
+  /// params say otherwise.
+  pub async fn move_similar_pages_to_trash(
+    &self,
+    workspace_id: Uuid,
+    params: &TrashSimilarPagesParams,
+  ) -> Result<TrashedSimilarPages, AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/similar-pages/move-to-trash",
+      self.base_url, workspace_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(params)
+      .send()
+      .await?;
+    AppResponse::<TrashedSimilarPages>::from_response(resp)
+      .await?
+      .into_data()
+  }
 }
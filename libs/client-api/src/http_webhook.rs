@@ -0,0 +1,65 @@
+use reqwest::Method;
+use shared_entity::dto::webhook_dto::{
+  CreateWorkspaceWebhookParams, CreatedWorkspaceWebhook, WorkspaceWebhook,
+};
+use shared_entity::response::{AppResponse, AppResponseError};
+use uuid::Uuid;
+
+use crate::http::log_request_id;
+use crate::Client;
+
+impl Client {
+  pub async fn list_workspace_webhooks(
+    &self,
+    workspace_id: &Uuid,
+  ) -> Result<Vec<WorkspaceWebhook>, AppResponseError> {
+    let url = format!("{}/api/workspace/{}/webhook", self.base_url, workspace_id);
+    let resp = self
+      .http_client_with_auth(Method::GET, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<Vec<WorkspaceWebhook>>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  /// Subscribes an endpoint to the membership events of the workspace. The returned secret signs
+  /// the deliveries and can't be retrieved afterwards.
+  pub async fn create_workspace_webhook(
+    &self,
+    workspace_id: &Uuid,
+    params: &CreateWorkspaceWebhookParams,
+  ) -> Result<CreatedWorkspaceWebhook, AppResponseError> {
+    let url = format!("{}/api/workspace/{}/webhook", self.base_url, workspace_id);
+    let resp = self
+      .http_client_with_auth(Method::POST, &url)
+      .await?
+      .json(params)
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<CreatedWorkspaceWebhook>::from_response(resp)
+      .await?
+      .into_data()
+  }
+
+  pub async fn delete_workspace_webhook(
+    &self,
+    workspace_id: &Uuid,
+    webhook_id: &Uuid,
+  ) -> Result<(), AppResponseError> {
+    let url = format!(
+      "{}/api/workspace/{}/webhook/{}",
+      self.base_url, workspace_id, webhook_id
+    );
+    let resp = self
+      .http_client_with_auth(Method::DELETE, &url)
+      .await?
+      .send()
+      .await?;
+    log_request_id(&resp);
+    AppResponse::<()>::from_response(resp).await?.into_error()
+  }
+}
@@ -5,14 +5,32 @@ mod http_billing;
 mod http_access_request;
 mod http_blob;
 mod http_collab;
+mod http_collab_admin;
+mod http_export;
+mod http_fault_injection;
+mod http_feature_flag;
+mod http_impersonation;
+mod http_inbound_email;
+mod http_maintenance;
 mod http_member;
+mod http_merge;
+mod http_moderation;
+mod http_provisioning;
 mod http_publish;
 mod http_quick_note;
+mod http_realtime_admin;
 mod http_search;
+mod http_session;
 mod http_template;
+mod http_unfurl;
+mod http_usage_admin;
 mod http_view;
+mod http_webhook;
+mod pagination;
 pub use http::*;
+pub use pagination::paginate;
 
+pub mod collab_cache;
 #[cfg(feature = "collab-sync")]
 pub mod collab_sync;
 
@@ -0,0 +1,44 @@
+use std::future::Future;
+
+use futures::stream::{self, Stream, StreamExt};
+use shared_entity::dto::page_dto::{Cursor, Page};
+use shared_entity::response::{AppResponseError, ErrorCode};
+
+/// Follows the cursors of a list endpoint, yielding the items of every page until the last one.
+/// `fetch_page` is called with the cursor of the page to fetch, `None` for the first page. The
+/// stream ends after the first error, and when a page points back at itself.
+pub fn paginate<T, F, Fut>(fetch_page: F) -> impl Stream<Item = Result<T, AppResponseError>>
+where
+  F: FnMut(Option<Cursor>) -> Fut,
+  Fut: Future<Output = Result<Page<T>, AppResponseError>>,
+{
+  stream::unfold(
+    (fetch_page, Some(None::<Cursor>)),
+    |(mut fetch_page, cursor)| async move {
+      // `None` once the last page was fetched
+      let cursor = cursor?;
+      match fetch_page(cursor.clone()).await {
+        Ok(page) => {
+          let next = match page.next_cursor {
+            Some(next_cursor) if Some(&next_cursor) == cursor.as_ref() => {
+              let err = AppResponseError::new(
+                ErrorCode::Internal,
+                format!(
+                  "the page after cursor {} points back at itself",
+                  next_cursor
+                ),
+              );
+              return Some((vec![Err(err)], (fetch_page, None)));
+            },
+            Some(next_cursor) => Some(Some(next_cursor)),
+            None => None,
+          };
+          let items = page.items.into_iter().map(Ok).collect::<Vec<_>>();
+          Some((items, (fetch_page, next)))
+        },
+        Err(err) => Some((vec![Err(err)], (fetch_page, None))),
+      }
+    },
+  )
+  .flat_map(stream::iter)
+}
@@ -43,26 +43,42 @@ impl Action for RefreshTokenAction {
         (weak_token.upgrade(), weak_gotrue_client.upgrade())
       {
         let (refresh_token, provider_access_token, provider_refresh_token) = {
-          let mut token_write = token.write();
-          let gotrue_resp_token = token_write.as_mut().ok_or(GoTrueError::NotLoggedIn(
+          let token_read = token.read();
+          let gotrue_resp_token = token_read.as_ref().ok_or(GoTrueError::NotLoggedIn(
             "fail to refresh user token".to_owned(),
           ))?;
-          let refresh_token = gotrue_resp_token.refresh_token.as_str().to_owned();
-          let provider_access_token = gotrue_resp_token.provider_access_token.take();
-          let provider_refresh_token = gotrue_resp_token.provider_refresh_token.take();
-          (refresh_token, provider_access_token, provider_refresh_token)
+          (
+            gotrue_resp_token.refresh_token.clone(),
+            gotrue_resp_token.provider_access_token.clone(),
+            gotrue_resp_token.provider_refresh_token.clone(),
+          )
         };
 
-        let mut access_token_resp = gotrue_client
-          .token(&Grant::RefreshToken(RefreshTokenGrant { refresh_token }))
-          .await?;
+        let result = gotrue_client
+          .token(&Grant::RefreshToken(RefreshTokenGrant {
+            refresh_token: refresh_token.clone(),
+          }))
+          .await;
+
+        let mut token_write = token.write();
+        // Each refresh rotates the refresh token. When the token was replaced meanwhile, by
+        // another window of the app sharing the session, the one sent here was already used:
+        // the replacing token is kept, whether the server accepted the old one or not.
+        let current_refresh_token = token_write
+          .as_ref()
+          .map(|token| token.refresh_token.as_str());
+        if current_refresh_token != Some(refresh_token.as_str()) {
+          debug!("token was refreshed elsewhere, keeping it");
+          return Ok(());
+        }
+        let mut access_token_resp = result?;
 
         // refresh does not preserve provider token and refresh token
         // so we need to set it manually to preserve this information
         access_token_resp.provider_access_token = provider_access_token;
         access_token_resp.provider_refresh_token = provider_refresh_token;
 
-        token.write().set(access_token_resp);
+        token_write.set(access_token_resp);
       }
       Ok(())
     })
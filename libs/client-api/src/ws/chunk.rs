@@ -0,0 +1,105 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{anyhow, Error};
+use bytes::Bytes;
+use collab_rt_entity::{MessageChunk, MAXIMUM_REALTIME_MESSAGE_SIZE};
+
+static NEXT_CHUNKED_MESSAGE_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Splits an encoded realtime message into the binary frames sent over the websocket. A message
+/// of at most `chunk_size` bytes is sent as is, a larger one is split into [MessageChunk]s, which
+/// the server reassembles before handling the message.
+pub(crate) fn split_into_frames(data: Vec<u8>, chunk_size: usize) -> Result<Vec<Vec<u8>>, Error> {
+  if data.len() <= chunk_size {
+    return Ok(vec![data]);
+  }
+  if data.len() as u64 > MAXIMUM_REALTIME_MESSAGE_SIZE {
+    return Err(anyhow!(
+      "The realtime message of {} bytes exceeds the maximum size of {} bytes",
+      data.len(),
+      MAXIMUM_REALTIME_MESSAGE_SIZE
+    ));
+  }
+
+  let message_id = NEXT_CHUNKED_MESSAGE_ID.fetch_add(1, Ordering::Relaxed);
+  let data = Bytes::from(data);
+  let count = data.len().div_ceil(chunk_size) as u32;
+  (0..count)
+    .map(|index| {
+      let start = index as usize * chunk_size;
+      let end = (start + chunk_size).min(data.len());
+      MessageChunk {
+        message_id,
+        index,
+        count,
+        data: data.slice(start..end),
+      }
+      .encode()
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const CHUNK_SIZE: usize = 1024;
+
+  fn reassemble(frames: &[Vec<u8>]) -> Vec<u8> {
+    frames
+      .iter()
+      .flat_map(|frame| MessageChunk::decode(frame).unwrap().data.to_vec())
+      .collect()
+  }
+
+  #[test]
+  fn message_fitting_in_one_frame_is_not_chunked_test() {
+    for size in [0, 1, CHUNK_SIZE - 1, CHUNK_SIZE] {
+      let data = vec![7u8; size];
+      let frames = split_into_frames(data.clone(), CHUNK_SIZE).unwrap();
+      assert_eq!(frames, vec![data]);
+    }
+  }
+
+  #[test]
+  fn oversized_message_is_chunked_test() {
+    for (size, expected_count) in [
+      (CHUNK_SIZE + 1, 2),
+      (2 * CHUNK_SIZE, 2),
+      (2 * CHUNK_SIZE + 1, 3),
+      (10 * CHUNK_SIZE - 1, 10),
+    ] {
+      let data = (0..size).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+      let frames = split_into_frames(data.clone(), CHUNK_SIZE).unwrap();
+      assert_eq!(frames.len(), expected_count, "size {}", size);
+
+      let chunks = frames
+        .iter()
+        .map(|frame| MessageChunk::decode(frame).unwrap())
+        .collect::<Vec<_>>();
+      for (index, chunk) in chunks.iter().enumerate() {
+        assert_eq!(chunk.index as usize, index);
+        assert_eq!(chunk.count as usize, expected_count);
+        assert_eq!(chunk.message_id, chunks[0].message_id);
+        assert!(chunk.data.len() <= CHUNK_SIZE);
+      }
+      assert_eq!(reassemble(&frames), data);
+    }
+  }
+
+  #[test]
+  fn chunked_messages_have_distinct_ids_test() {
+    let first = split_into_frames(vec![0u8; CHUNK_SIZE + 1], CHUNK_SIZE).unwrap();
+    let second = split_into_frames(vec![0u8; CHUNK_SIZE + 1], CHUNK_SIZE).unwrap();
+    assert_ne!(
+      MessageChunk::decode(&first[0]).unwrap().message_id,
+      MessageChunk::decode(&second[0]).unwrap().message_id
+    );
+  }
+
+  #[test]
+  fn message_above_maximum_size_is_rejected_test() {
+    let data = vec![0u8; MAXIMUM_REALTIME_MESSAGE_SIZE as usize + 1];
+    assert!(split_into_frames(data, CHUNK_SIZE).is_err());
+  }
+}
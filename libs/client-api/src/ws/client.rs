@@ -1,5 +1,5 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
 use std::sync::{Arc, Weak};
 use std::time::Duration;
@@ -16,15 +16,19 @@ use tokio_tungstenite::tungstenite::http::header::AUTHORIZATION;
 use tokio_tungstenite::tungstenite::http::{HeaderMap, HeaderValue};
 use tracing::{error, info, trace, warn};
 
+use crate::collab_cache::CollabCache;
 use crate::ping::ServerFixIntervalPing;
 use crate::retry::retry_connect;
 use crate::ws::msg_queue::{AggregateMessageQueue, AggregateMessagesReceiver};
 use crate::ws::{ConnectState, ConnectStateNotify, WSError, WebSocketChannel};
 use client_websocket::{CloseCode, CloseFrame, Message, WebSocketStream};
 use collab_rt_entity::user::UserMessage;
-use collab_rt_entity::ClientCollabMessage;
 use collab_rt_entity::ServerCollabMessage;
-use collab_rt_entity::{RealtimeMessage, SystemMessage};
+use collab_rt_entity::{AckCode, ClientCollabMessage};
+use collab_rt_entity::{
+  CollabAccess, CollabDeleted, PermissionDenied, RealtimeMessage, SystemMessage, UpdateRateLimited,
+};
+use collab_rt_protocol::EditingLockMeta;
 
 pub struct WSClientConfig {
   /// specifies the number of messages that the channel can hold at any given
@@ -80,6 +84,15 @@ pub struct WSClient {
   ping: Arc<Mutex<Option<ServerFixIntervalPing>>>,
   stop_ws_msg_loop_tx: Mutex<Option<oneshot::Sender<()>>>,
   aggregate_queue: Arc<AggregateMessageQueue>,
+  collab_cache: Arc<RwLock<Option<Arc<dyn CollabCache>>>>,
+  collab_reset_channel: Arc<Sender<String>>,
+  editing_lock_channel: Arc<Sender<EditingLockChanged>>,
+  rate_limited_channel: Arc<Sender<UpdateRateLimited>>,
+  collab_access_channel: Arc<Sender<CollabAccess>>,
+  permission_denied_channel: Arc<Sender<PermissionDenied>>,
+  collab_deleted_channel: Arc<Sender<CollabDeleted>>,
+  /// Object id of the opened collabs which the user can only read.
+  read_only_collabs: Arc<RwLock<HashSet<String>>>,
 
   #[cfg(debug_assertions)]
   skip_realtime_message: Arc<std::sync::atomic::AtomicBool>,
@@ -97,6 +110,12 @@ impl WSClient {
     let ping = Arc::new(Mutex::from(None));
     let http_sender = Arc::new(http_sender);
     let (user_channel, _) = channel(1);
+    let (collab_reset_channel, _) = channel(100);
+    let (editing_lock_channel, _) = channel(100);
+    let (rate_limited_channel, _) = channel(100);
+    let (collab_access_channel, _) = channel(100);
+    let (permission_denied_channel, _) = channel(100);
+    let (collab_deleted_channel, _) = channel(100);
     let (rt_msg_sender, _) = channel(config.buffer_capacity);
     let connect_provider = Arc::new(connect_provider);
     let aggregate_queue = Arc::new(AggregateMessageQueue::new(MAXIMUM_BATCH_MESSAGE_SIZE));
@@ -111,6 +130,14 @@ impl WSClient {
       ping,
       stop_ws_msg_loop_tx: Mutex::from(None),
       aggregate_queue,
+      collab_cache: Default::default(),
+      collab_reset_channel: Arc::new(collab_reset_channel),
+      editing_lock_channel: Arc::new(editing_lock_channel),
+      rate_limited_channel: Arc::new(rate_limited_channel),
+      collab_access_channel: Arc::new(collab_access_channel),
+      permission_denied_channel: Arc::new(permission_denied_channel),
+      collab_deleted_channel: Arc::new(collab_deleted_channel),
+      read_only_collabs: Default::default(),
 
       #[cfg(debug_assertions)]
       skip_realtime_message: Default::default(),
@@ -266,6 +293,14 @@ impl WSClient {
     #[cfg(debug_assertions)]
     let cloned_skip_realtime_message = self.skip_realtime_message.clone();
     let user_message_tx = self.user_channel.as_ref().clone();
+    let collab_cache = self.collab_cache.clone();
+    let collab_reset_tx = self.collab_reset_channel.as_ref().clone();
+    let editing_lock_tx = self.editing_lock_channel.as_ref().clone();
+    let rate_limited_tx = self.rate_limited_channel.as_ref().clone();
+    let collab_access_tx = self.collab_access_channel.as_ref().clone();
+    let permission_denied_tx = self.permission_denied_channel.as_ref().clone();
+    let collab_deleted_tx = self.collab_deleted_channel.as_ref().clone();
+    let read_only_collabs = self.read_only_collabs.clone();
     tokio::spawn(async move {
       while let Some(Ok(ws_msg)) = stream.next().await {
         match ws_msg {
@@ -281,7 +316,13 @@ impl WSClient {
                 RealtimeMessage::Collab(collab_msg) => {
                   match ServerCollabMessage::try_from(collab_msg) {
                     Ok(collab_message) => {
-                      handle_collab_message(&weak_collab_channels, vec![collab_message]);
+                      handle_collab_message(
+                        &weak_collab_channels,
+                        &collab_cache,
+                        &collab_reset_tx,
+                        &editing_lock_tx,
+                        vec![collab_message],
+                      );
                     },
                     Err(err) => {
                       error!("parser ServerCollabMessage failed: {:?}", err);
@@ -300,9 +341,44 @@ impl WSClient {
                     trace!("detect same ws connect from this device, closing the connection");
                     break;
                   },
+                  SystemMessage::UpdateRateLimited(rate_limited) => {
+                    warn!(
+                      "updates are rate limited by the server, retry after {}ms",
+                      rate_limited.retry_after_ms
+                    );
+                    let _ = rate_limited_tx.send(rate_limited);
+                  },
+                  SystemMessage::CollabAccess(access) => {
+                    if access.read_only {
+                      read_only_collabs.write().insert(access.object_id.clone());
+                    } else {
+                      read_only_collabs.write().remove(&access.object_id);
+                    }
+                    let _ = collab_access_tx.send(access);
+                  },
+                  SystemMessage::PermissionDenied(denied) => {
+                    warn!(
+                      "update {} of collab {} was rejected, the collab is read-only",
+                      denied.msg_id, denied.object_id
+                    );
+                    let _ = permission_denied_tx.send(denied);
+                  },
+                  SystemMessage::CollabDeleted(deleted) => {
+                    warn!(
+                      "collab {} is deleted, it can be restored from the trash",
+                      deleted.object_id
+                    );
+                    let _ = collab_deleted_tx.send(deleted);
+                  },
                 },
                 RealtimeMessage::ServerCollabV1(collab_messages) => {
-                  handle_collab_message(&weak_collab_channels, collab_messages);
+                  handle_collab_message(
+                    &weak_collab_channels,
+                    &collab_cache,
+                    &collab_reset_tx,
+                    &editing_lock_tx,
+                    collab_messages,
+                  );
                 },
                 RealtimeMessage::ClientCollabV2(_) | RealtimeMessage::ClientCollabV1(_) => {
                   // The message from server should not be collab message.
@@ -366,6 +442,52 @@ impl WSClient {
     Ok(channel)
   }
 
+  /// Cached collabs are invalidated when the server sends an update for them. Usually the cache
+  /// returned by [crate::Client::collab_cache].
+  pub fn set_collab_cache(&self, collab_cache: Arc<dyn CollabCache>) {
+    *self.collab_cache.write() = Some(collab_cache);
+  }
+
+  /// Receives the object id of collabs that were replaced by a compacted copy on the server. The
+  /// local copy of such a collab stops syncing, so it must be dropped and loaded again.
+  pub fn subscribe_collab_reset(&self) -> Receiver<String> {
+    self.collab_reset_channel.subscribe()
+  }
+
+  /// Receives the changes of the editing lock of the collabs opened by this client. A collab
+  /// locked by another member should be made read-only, since the server rejects its updates.
+  pub fn subscribe_editing_lock(&self) -> Receiver<EditingLockChanged> {
+    self.editing_lock_channel.subscribe()
+  }
+
+  /// Receives the notices sent by the server when this client sends collab updates faster than
+  /// its rate limit. The updates sent until `retry_after_ms` passed are dropped.
+  pub fn subscribe_rate_limited(&self) -> Receiver<UpdateRateLimited> {
+    self.rate_limited_channel.subscribe()
+  }
+
+  /// Receives whether the user can edit the collabs opened by this client, sent when a collab
+  /// is opened and when the role of the user changes.
+  pub fn subscribe_collab_access(&self) -> Receiver<CollabAccess> {
+    self.collab_access_channel.subscribe()
+  }
+
+  /// Receives the updates the server rejected because the user can only read their collab.
+  pub fn subscribe_permission_denied(&self) -> Receiver<PermissionDenied> {
+    self.permission_denied_channel.subscribe()
+  }
+
+  /// Receives the collabs the server refused to sync because they were deleted.
+  pub fn subscribe_collab_deleted(&self) -> Receiver<CollabDeleted> {
+    self.collab_deleted_channel.subscribe()
+  }
+
+  /// Whether the server told this client that it can only read the collab. Editing should be
+  /// disabled for such a collab, since its updates are rejected.
+  pub fn is_collab_read_only(&self, object_id: &str) -> bool {
+    self.read_only_collabs.read().contains(object_id)
+  }
+
   pub fn subscribe_user_changed(&self) -> Receiver<UserMessage> {
     self.user_channel.subscribe()
   }
@@ -415,11 +537,58 @@ impl WSClient {
   }
 }
 
+/// Change of the editing lock of a collab. `lock` is `None` when the lock was released.
+#[derive(Debug, Clone)]
+pub struct EditingLockChanged {
+  pub object_id: String,
+  pub lock: Option<EditingLockMeta>,
+}
+
 #[inline]
 fn handle_collab_message(
   weak_collab_channels: &Weak<RwLock<ChannelByObjectId>>,
+  collab_cache: &RwLock<Option<Arc<dyn CollabCache>>>,
+  collab_reset_tx: &Sender<String>,
+  editing_lock_tx: &Sender<EditingLockChanged>,
   collab_messages: Vec<ServerCollabMessage>,
 ) {
+  if let Some(collab_cache) = collab_cache.read().as_ref() {
+    for collab_msg in &collab_messages {
+      // Awareness updates do not change the content of the collab.
+      if !matches!(collab_msg, ServerCollabMessage::AwarenessSync(_)) {
+        collab_cache.invalidate(collab_msg.object_id());
+      }
+    }
+  }
+
+  for collab_msg in &collab_messages {
+    match collab_msg {
+      ServerCollabMessage::ClientAck(ack) => match ack.get_code() {
+        AckCode::Reset => {
+          let _ = collab_reset_tx.send(ack.object_id.clone());
+        },
+        AckCode::EditingLocked => {
+          if let Ok(lock) = EditingLockMeta::from_vec(&ack.payload) {
+            let _ = editing_lock_tx.send(EditingLockChanged {
+              object_id: ack.object_id.clone(),
+              lock: Some(lock),
+            });
+          }
+        },
+        _ => {},
+      },
+      ServerCollabMessage::ServerBroadcast(_) => {
+        if let Some(lock) = EditingLockMeta::from_message_payload(collab_msg.payload()) {
+          let _ = editing_lock_tx.send(EditingLockChanged {
+            object_id: collab_msg.object_id().to_string(),
+            lock,
+          });
+        }
+      },
+      _ => {},
+    }
+  }
+
   if let Some(collab_channels) = weak_collab_channels.upgrade() {
     for collab_msg in collab_messages {
       let object_id = collab_msg.object_id().to_owned();
@@ -1,3 +1,4 @@
+mod chunk;
 mod client;
 mod error;
 mod handler;
@@ -9,7 +9,9 @@ use tracing::{error, trace};
 
 use client_websocket::Message;
 use collab_rt_entity::{ClientCollabMessage, MsgId};
-use collab_rt_entity::{MessageByObjectId, RealtimeMessage};
+use collab_rt_entity::{MessageByObjectId, RealtimeMessage, MAXIMUM_CHUNK_DATA_SIZE};
+
+use crate::ws::chunk::split_into_frames;
 
 pub type AggregateMessagesSender = mpsc::Sender<Message>;
 pub type AggregateMessagesReceiver = mpsc::Receiver<Message>;
@@ -115,10 +117,16 @@ async fn send_batch_message(
   sender: &AggregateMessagesSender,
   messages_map: HashMap<String, Vec<ClientCollabMessage>>,
 ) {
-  match RealtimeMessage::ClientCollabV2(MessageByObjectId(messages_map)).encode() {
-    Ok(data) => {
-      if let Err(e) = sender.send(Message::Binary(data)).await {
-        trace!("websocket channel close:{}, stop sending messages", e);
+  let frames = RealtimeMessage::ClientCollabV2(MessageByObjectId(messages_map))
+    .encode()
+    .and_then(|data| split_into_frames(data, MAXIMUM_CHUNK_DATA_SIZE));
+  match frames {
+    Ok(frames) => {
+      for frame in frames {
+        if let Err(e) = sender.send(Message::Binary(frame)).await {
+          trace!("websocket channel close:{}, stop sending messages", e);
+          return;
+        }
       }
     },
     Err(err) => {
@@ -0,0 +1,95 @@
+use anyhow::{anyhow, Error};
+use bincode::{DefaultOptions, Options};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::MAXIMUM_REALTIME_MESSAGE_SIZE;
+
+/// Binary frames starting with this prefix hold a [MessageChunk] instead of a realtime message.
+pub const CHUNK_PREFIX: &[u8] = b"CHUNK:1";
+
+/// Largest number of bytes of an encoded realtime message carried by one chunk. A chunk fits in a
+/// single websocket frame accepted by the server and by the proxies in front of it.
+pub const MAXIMUM_CHUNK_DATA_SIZE: usize = 64 * 1024;
+
+/// The chunks of a message add up to at most [MAXIMUM_REALTIME_MESSAGE_SIZE].
+pub const MAXIMUM_CHUNK_COUNT: u32 =
+  (MAXIMUM_REALTIME_MESSAGE_SIZE as usize).div_ceil(MAXIMUM_CHUNK_DATA_SIZE) as u32;
+
+/// A part of an encoded realtime message too large to be sent in a single websocket frame.
+///
+/// The chunks of a message share its `message_id`, which is unique within a connection. They are
+/// sent in order, and the receiver only decodes the message once its `count` chunks arrived.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageChunk {
+  pub message_id: u64,
+  /// Position of the chunk within the message, starting at 0.
+  pub index: u32,
+  /// Number of chunks of the message.
+  pub count: u32,
+  pub data: Bytes,
+}
+
+impl MessageChunk {
+  pub fn is_chunk(frame: &[u8]) -> bool {
+    frame.starts_with(CHUNK_PREFIX)
+  }
+
+  pub fn encode(&self) -> Result<Vec<u8>, Error> {
+    let mut frame = CHUNK_PREFIX.to_vec();
+    DefaultOptions::new()
+      .with_fixint_encoding()
+      .allow_trailing_bytes()
+      .serialize_into(&mut frame, self)
+      .map_err(|e| anyhow!("Failed to encode message chunk: {}", e))?;
+    Ok(frame)
+  }
+
+  pub fn decode(frame: &[u8]) -> Result<Self, Error> {
+    let data = frame
+      .strip_prefix(CHUNK_PREFIX)
+      .ok_or_else(|| anyhow!("The frame is not a message chunk"))?;
+    let chunk: MessageChunk = DefaultOptions::new()
+      .with_fixint_encoding()
+      .allow_trailing_bytes()
+      .with_limit(2 * MAXIMUM_CHUNK_DATA_SIZE as u64)
+      .deserialize(data)?;
+    if chunk.count == 0 || chunk.index >= chunk.count {
+      return Err(anyhow!(
+        "Invalid chunk {} of {} of message {}",
+        chunk.index,
+        chunk.count,
+        chunk.message_id
+      ));
+    }
+    if chunk.count > MAXIMUM_CHUNK_COUNT {
+      return Err(anyhow!(
+        "Message {} is split in too many chunks: {}",
+        chunk.message_id,
+        chunk.count
+      ));
+    }
+    Ok(chunk)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn chunk_encode_decode_test() {
+    let chunk = MessageChunk {
+      message_id: 7,
+      index: 1,
+      count: 3,
+      data: Bytes::from(vec![1u8; 100]),
+    };
+    let frame = chunk.encode().unwrap();
+    assert!(MessageChunk::is_chunk(&frame));
+    assert_eq!(MessageChunk::decode(&frame).unwrap(), chunk);
+
+    let invalid = MessageChunk { index: 3, ..chunk };
+    assert!(MessageChunk::decode(&invalid.encode().unwrap()).is_err());
+  }
+}
@@ -27,11 +27,20 @@ pub trait SinkMessage: Clone + Send + Sync + 'static + Ord + Display {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ClientCollabMessage {
-  ClientInitSync { data: InitSync },
-  ClientUpdateSync { data: UpdateSync },
+  ClientInitSync {
+    data: InitSync,
+  },
+  ClientUpdateSync {
+    data: UpdateSync,
+  },
   ServerInitSync(ServerInit),
   ClientAwarenessSync(UpdateSync),
   ClientCollabStateCheck(CollabStateCheck),
+  /// Selects the events the sender receives for the object. It can be sent along with the init
+  /// sync, or at any time to change the selection.
+  ClientSubscriptionFilter(SubscriptionFilterUpdate),
+  /// Asks for the broadcasts missed since the given one, see [ReplaySync].
+  ClientReplaySync(ReplaySync),
 }
 
 impl ClientCollabMessage {
@@ -51,6 +60,14 @@ impl ClientCollabMessage {
     Self::ClientAwarenessSync(data)
   }
 
+  pub fn new_subscription_filter(data: SubscriptionFilterUpdate) -> Self {
+    Self::ClientSubscriptionFilter(data)
+  }
+
+  pub fn new_replay_sync(data: ReplaySync) -> Self {
+    Self::ClientReplaySync(data)
+  }
+
   pub fn size(&self) -> usize {
     match self {
       ClientCollabMessage::ClientInitSync { data, .. } => data.payload.len(),
@@ -58,6 +75,8 @@ impl ClientCollabMessage {
       ClientCollabMessage::ServerInitSync(msg) => msg.payload.len(),
       ClientCollabMessage::ClientAwarenessSync(data) => data.payload.len(),
       ClientCollabMessage::ClientCollabStateCheck(_) => 0,
+      ClientCollabMessage::ClientSubscriptionFilter(_) => 0,
+      ClientCollabMessage::ClientReplaySync(_) => 0,
     }
   }
   pub fn object_id(&self) -> &str {
@@ -67,6 +86,8 @@ impl ClientCollabMessage {
       ClientCollabMessage::ServerInitSync(msg) => &msg.object_id,
       ClientCollabMessage::ClientAwarenessSync(data) => &data.object_id,
       ClientCollabMessage::ClientCollabStateCheck(data) => &data.object_id,
+      ClientCollabMessage::ClientSubscriptionFilter(data) => &data.object_id,
+      ClientCollabMessage::ClientReplaySync(data) => &data.object_id,
     }
   }
 
@@ -77,6 +98,8 @@ impl ClientCollabMessage {
       ClientCollabMessage::ServerInitSync(msg) => &msg.origin,
       ClientCollabMessage::ClientAwarenessSync(data) => &data.origin,
       ClientCollabMessage::ClientCollabStateCheck(data) => &data.origin,
+      ClientCollabMessage::ClientSubscriptionFilter(data) => &data.origin,
+      ClientCollabMessage::ClientReplaySync(data) => &data.origin,
     }
   }
   pub fn payload(&self) -> &Bytes {
@@ -87,6 +110,8 @@ impl ClientCollabMessage {
       ClientCollabMessage::ServerInitSync(msg) => &msg.payload,
       ClientCollabMessage::ClientAwarenessSync(data) => &data.payload,
       ClientCollabMessage::ClientCollabStateCheck(_data) => &EMPTY_BYTES,
+      ClientCollabMessage::ClientSubscriptionFilter(_data) => &EMPTY_BYTES,
+      ClientCollabMessage::ClientReplaySync(_data) => &EMPTY_BYTES,
     }
   }
   pub fn device_id(&self) -> Option<String> {
@@ -103,6 +128,8 @@ impl ClientCollabMessage {
       ClientCollabMessage::ServerInitSync(value) => value.msg_id,
       ClientCollabMessage::ClientAwarenessSync(data) => data.msg_id,
       ClientCollabMessage::ClientCollabStateCheck(data) => data.msg_id,
+      ClientCollabMessage::ClientSubscriptionFilter(data) => data.msg_id,
+      ClientCollabMessage::ClientReplaySync(data) => data.msg_id,
     }
   }
 
@@ -125,6 +152,8 @@ impl Display for ClientCollabMessage {
         data.payload.len(),
       )),
       ClientCollabMessage::ClientCollabStateCheck(data) => Display::fmt(data, f),
+      ClientCollabMessage::ClientSubscriptionFilter(data) => Display::fmt(data, f),
+      ClientCollabMessage::ClientReplaySync(data) => Display::fmt(data, f),
     }
   }
 }
@@ -385,3 +414,179 @@ impl Display for CollabStateCheck {
     ))
   }
 }
+
+/// The classes of events a subscriber of an object receives. Subscribers receive all of them
+/// until they send a [SubscriptionFilterUpdate].
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, Hash)]
+pub struct SubscriptionFilter {
+  /// The updates of the collab made by the other subscribers.
+  pub updates: bool,
+  /// The awareness updates of the other subscribers, such as their cursors.
+  pub awareness: bool,
+  /// The notifications about the collab itself, such as its editing lock or its reset.
+  pub control: bool,
+}
+
+impl SubscriptionFilter {
+  const UPDATES: u8 = 1;
+  const AWARENESS: u8 = 1 << 1;
+  const CONTROL: u8 = 1 << 2;
+
+  pub fn updates_only() -> Self {
+    Self {
+      updates: true,
+      awareness: false,
+      control: true,
+    }
+  }
+
+  pub fn awareness_only() -> Self {
+    Self {
+      updates: false,
+      awareness: true,
+      control: false,
+    }
+  }
+
+  /// Packs the filter into a byte, so that it can be stored in an atomic.
+  pub fn to_bits(self) -> u8 {
+    let mut bits = 0;
+    if self.updates {
+      bits |= Self::UPDATES;
+    }
+    if self.awareness {
+      bits |= Self::AWARENESS;
+    }
+    if self.control {
+      bits |= Self::CONTROL;
+    }
+    bits
+  }
+
+  pub fn from_bits(bits: u8) -> Self {
+    Self {
+      updates: bits & Self::UPDATES != 0,
+      awareness: bits & Self::AWARENESS != 0,
+      control: bits & Self::CONTROL != 0,
+    }
+  }
+}
+
+impl Default for SubscriptionFilter {
+  fn default() -> Self {
+    Self {
+      updates: true,
+      awareness: true,
+      control: true,
+    }
+  }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, Hash)]
+pub struct SubscriptionFilterUpdate {
+  pub origin: CollabOrigin,
+  pub object_id: String,
+  pub msg_id: MsgId,
+  pub filter: SubscriptionFilter,
+}
+
+impl Display for SubscriptionFilterUpdate {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_fmt(format_args!(
+      "subscription filter: [uid:{}|oid:{}|msg_id:{:?}|updates:{}|awareness:{}|control:{}]",
+      self.origin.client_user_id().unwrap_or(0),
+      self.object_id,
+      self.msg_id,
+      self.filter.updates,
+      self.filter.awareness,
+      self.filter.control,
+    ))
+  }
+}
+
+/// Opts the sender into [crate::SequencedBroadcast]s of the object, which carry the number of
+/// each update in the replay buffer of the server. With `last_seen_seq`, the number of the last
+/// one the sender received before reconnecting, the server also sends the updates it missed
+/// since, or acks with [crate::AckCode::ReplayUnavailable] when they are no longer buffered.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, Hash)]
+pub struct ReplaySync {
+  pub origin: CollabOrigin,
+  pub object_id: String,
+  pub msg_id: MsgId,
+  pub last_seen_seq: Option<u64>,
+}
+
+impl Display for ReplaySync {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_fmt(format_args!(
+      "replay: [uid:{}|oid:{}|msg_id:{:?}|last_seen_seq:{:?}]",
+      self.origin.client_user_id().unwrap_or(0),
+      self.object_id,
+      self.msg_id,
+      self.last_seen_seq,
+    ))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn subscription_filter_bits_round_trip_test() {
+    for filter in [
+      SubscriptionFilter::default(),
+      SubscriptionFilter::updates_only(),
+      SubscriptionFilter::awareness_only(),
+      SubscriptionFilter {
+        updates: false,
+        awareness: false,
+        control: false,
+      },
+    ] {
+      assert_eq!(SubscriptionFilter::from_bits(filter.to_bits()), filter);
+    }
+  }
+
+  #[test]
+  fn subscription_filter_message_round_trip_test() {
+    let message = ClientCollabMessage::new_subscription_filter(SubscriptionFilterUpdate {
+      origin: CollabOrigin::Empty,
+      object_id: "object".to_string(),
+      msg_id: 1,
+      filter: SubscriptionFilter::updates_only(),
+    });
+    let encoded = RealtimeMessage::from(message).encode().unwrap();
+    let decoded = RealtimeMessage::decode(&encoded)
+      .unwrap()
+      .split_messages_by_object_id()
+      .unwrap();
+    match &decoded["object"][..] {
+      [ClientCollabMessage::ClientSubscriptionFilter(update)] => {
+        assert_eq!(update.filter, SubscriptionFilter::updates_only())
+      },
+      other => panic!("unexpected messages: {:?}", other),
+    }
+  }
+
+  #[test]
+  fn replay_sync_message_round_trip_test() {
+    let message = ClientCollabMessage::new_replay_sync(ReplaySync {
+      origin: CollabOrigin::Empty,
+      object_id: "object".to_string(),
+      msg_id: 1,
+      last_seen_seq: Some(42),
+    });
+    let encoded = RealtimeMessage::from(message).encode().unwrap();
+    let decoded = RealtimeMessage::decode(&encoded)
+      .unwrap()
+      .split_messages_by_object_id()
+      .unwrap();
+    match &decoded["object"][..] {
+      [ClientCollabMessage::ClientReplaySync(replay)] => {
+        assert_eq!(replay.last_seen_seq, Some(42))
+      },
+      other => panic!("unexpected messages: {:?}", other),
+    }
+  }
+}
@@ -1,3 +1,4 @@
+mod chunk;
 mod message;
 pub mod user;
 
@@ -12,6 +13,7 @@ pub mod collab_proto;
 pub mod realtime_proto;
 mod server_message;
 
+pub use chunk::*;
 pub use client_message::*;
 pub use message::*;
 pub use realtime_proto::*;
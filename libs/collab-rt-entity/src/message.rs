@@ -5,7 +5,9 @@ use std::collections::HashMap;
 use crate::client_message::ClientCollabMessage;
 use crate::server_message::ServerCollabMessage;
 use crate::user::UserMessage;
-use crate::{AwarenessSync, BroadcastSync, CollabAck, InitSync, ServerInit, UpdateSync};
+use crate::{
+  AwarenessSync, BroadcastSync, CollabAck, InitSync, SequencedBroadcast, ServerInit, UpdateSync,
+};
 #[cfg(feature = "rt_compress")]
 use brotli::{CompressorReader, Decompressor};
 use bytes::Bytes;
@@ -199,6 +201,44 @@ pub enum SystemMessage {
   RateLimit(u32),
   KickOff,
   DuplicateConnection,
+  UpdateRateLimited(UpdateRateLimited),
+  CollabAccess(CollabAccess),
+  PermissionDenied(PermissionDenied),
+  CollabDeleted(CollabDeleted),
+}
+
+/// Sent when a connection sends collab updates faster than its rate limit. The updates received
+/// while it's limited are dropped without being acknowledged, and should be sent again once
+/// `retry_after_ms` passed. A connection which keeps exceeding the limit is kicked off.
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
+pub struct UpdateRateLimited {
+  /// The collab whose update was dropped first.
+  pub object_id: String,
+  pub retry_after_ms: u64,
+}
+
+/// Sent when a connection subscribes to a collab, and again when the role of the user changes.
+/// The server syncs a `read_only` collab, but rejects its updates with [PermissionDenied].
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
+pub struct CollabAccess {
+  pub object_id: String,
+  pub read_only: bool,
+}
+
+/// Answers an update sent to a collab the user can only read. The update isn't applied, so the
+/// local copy of the sender no longer matches the one of the server.
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
+pub struct PermissionDenied {
+  pub object_id: String,
+  /// Id of the rejected message.
+  pub msg_id: MsgId,
+}
+
+/// Answers the sync of a collab which was deleted: the connection isn't subscribed to it. The
+/// collab can be restored from the trash of its workspace.
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
+pub struct CollabDeleted {
+  pub object_id: String,
 }
 
 pub type MsgId = u64;
@@ -210,6 +250,7 @@ pub enum CollabMessage {
   ServerInitSync(ServerInit),
   AwarenessSync(AwarenessSync),
   ServerBroadcast(BroadcastSync),
+  SequencedBroadcast(SequencedBroadcast),
 }
 
 impl CollabMessage {
@@ -220,6 +261,7 @@ impl CollabMessage {
       CollabMessage::ClientAck(value) => Some(value.msg_id),
       CollabMessage::ServerInitSync(value) => Some(value.msg_id),
       CollabMessage::ServerBroadcast(_) => None,
+      CollabMessage::SequencedBroadcast(_) => None,
       CollabMessage::AwarenessSync(_) => None,
     }
   }
@@ -234,6 +276,7 @@ impl CollabMessage {
       CollabMessage::ClientAck(value) => &value.payload,
       CollabMessage::ServerInitSync(value) => &value.payload,
       CollabMessage::ServerBroadcast(value) => &value.payload,
+      CollabMessage::SequencedBroadcast(value) => &value.broadcast.payload,
       CollabMessage::AwarenessSync(value) => &value.payload,
     }
   }
@@ -247,6 +290,7 @@ impl CollabMessage {
       CollabMessage::ClientAck(value) => &value.origin,
       CollabMessage::ServerInitSync(value) => &value.origin,
       CollabMessage::ServerBroadcast(value) => &value.origin,
+      CollabMessage::SequencedBroadcast(value) => &value.broadcast.origin,
       CollabMessage::AwarenessSync(value) => &value.origin,
     }
   }
@@ -262,6 +306,7 @@ impl CollabMessage {
       CollabMessage::ClientAck(value) => &value.object_id,
       CollabMessage::ServerInitSync(value) => &value.object_id,
       CollabMessage::ServerBroadcast(value) => &value.object_id,
+      CollabMessage::SequencedBroadcast(value) => &value.broadcast.object_id,
       CollabMessage::AwarenessSync(value) => &value.object_id,
     }
   }
@@ -275,6 +320,7 @@ impl Display for CollabMessage {
       CollabMessage::ClientAck(value) => Display::fmt(&value, f),
       CollabMessage::ServerInitSync(value) => Display::fmt(&value, f),
       CollabMessage::ServerBroadcast(value) => Display::fmt(&value, f),
+      CollabMessage::SequencedBroadcast(value) => Display::fmt(&value, f),
       CollabMessage::AwarenessSync(value) => Display::fmt(&value, f),
     }
   }
@@ -292,6 +338,12 @@ impl From<BroadcastSync> for CollabMessage {
   }
 }
 
+impl From<SequencedBroadcast> for CollabMessage {
+  fn from(value: SequencedBroadcast) -> Self {
+    CollabMessage::SequencedBroadcast(value)
+  }
+}
+
 impl From<InitSync> for CollabMessage {
   fn from(value: InitSync) -> Self {
     CollabMessage::ClientInitSync(value)
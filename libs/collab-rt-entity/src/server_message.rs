@@ -13,6 +13,8 @@ pub enum ServerCollabMessage {
   ServerInitSync(ServerInit),
   AwarenessSync(AwarenessSync),
   ServerBroadcast(BroadcastSync),
+  /// A [BroadcastSync] sent to the subscribers which asked for a [crate::ReplaySync].
+  SequencedBroadcast(SequencedBroadcast),
 }
 
 impl ServerCollabMessage {
@@ -22,6 +24,7 @@ impl ServerCollabMessage {
       ServerCollabMessage::ServerInitSync(value) => &value.object_id,
       ServerCollabMessage::AwarenessSync(value) => &value.object_id,
       ServerCollabMessage::ServerBroadcast(value) => &value.object_id,
+      ServerCollabMessage::SequencedBroadcast(value) => &value.broadcast.object_id,
     }
   }
 
@@ -31,6 +34,7 @@ impl ServerCollabMessage {
       ServerCollabMessage::ServerInitSync(value) => Some(value.msg_id),
       ServerCollabMessage::AwarenessSync(_) => None,
       ServerCollabMessage::ServerBroadcast(_) => None,
+      ServerCollabMessage::SequencedBroadcast(_) => None,
     }
   }
 
@@ -40,6 +44,7 @@ impl ServerCollabMessage {
       ServerCollabMessage::ServerInitSync(value) => &value.payload,
       ServerCollabMessage::AwarenessSync(value) => &value.payload,
       ServerCollabMessage::ServerBroadcast(value) => &value.payload,
+      ServerCollabMessage::SequencedBroadcast(value) => &value.broadcast.payload,
     }
   }
 
@@ -49,6 +54,7 @@ impl ServerCollabMessage {
       ServerCollabMessage::ServerInitSync(msg) => msg.payload.len(),
       ServerCollabMessage::AwarenessSync(msg) => msg.payload.len(),
       ServerCollabMessage::ServerBroadcast(msg) => msg.payload.len(),
+      ServerCollabMessage::SequencedBroadcast(msg) => msg.broadcast.payload.len(),
     }
   }
 
@@ -58,6 +64,7 @@ impl ServerCollabMessage {
       ServerCollabMessage::ServerInitSync(value) => &value.origin,
       ServerCollabMessage::AwarenessSync(value) => &value.origin,
       ServerCollabMessage::ServerBroadcast(value) => &value.origin,
+      ServerCollabMessage::SequencedBroadcast(value) => &value.broadcast.origin,
     }
   }
 }
@@ -69,6 +76,7 @@ impl Display for ServerCollabMessage {
       ServerCollabMessage::ServerInitSync(value) => Display::fmt(&value, f),
       ServerCollabMessage::AwarenessSync(value) => Display::fmt(&value, f),
       ServerCollabMessage::ServerBroadcast(value) => Display::fmt(&value, f),
+      ServerCollabMessage::SequencedBroadcast(value) => Display::fmt(&value, f),
     }
   }
 }
@@ -82,6 +90,7 @@ impl TryFrom<CollabMessage> for ServerCollabMessage {
       CollabMessage::ServerInitSync(msg) => Ok(ServerCollabMessage::ServerInitSync(msg)),
       CollabMessage::AwarenessSync(msg) => Ok(ServerCollabMessage::AwarenessSync(msg)),
       CollabMessage::ServerBroadcast(msg) => Ok(ServerCollabMessage::ServerBroadcast(msg)),
+      CollabMessage::SequencedBroadcast(msg) => Ok(ServerCollabMessage::SequencedBroadcast(msg)),
       _ => Err(anyhow!("Invalid collab message type.")),
     }
   }
@@ -211,6 +220,21 @@ pub enum AckCode {
   Internal = 3,
   EncodeStateAsUpdateFail = 4,
   MissUpdate = 5,
+  /// The collab was replaced by a compacted copy. The receiver must discard its local copy and
+  /// load the collab again.
+  Reset = 6,
+  /// The collab is locked for editing by another member. The payload holds the encoded
+  /// [collab_rt_protocol::EditingLockMeta] of the holder.
+  EditingLocked = 7,
+  /// The collab was recovered from a snapshot because its stored state could not be decoded. It's
+  /// read-only until an admin confirms the repair.
+  ReadOnly = 8,
+  /// The update was rejected because of its size, or the size of the collab it would lead to. The
+  /// payload holds the encoded [collab_rt_protocol::CollabSizeExceeded].
+  CollabSizeExceeded = 9,
+  /// The updates asked for by a [crate::ReplaySync] are no longer buffered. The receiver must
+  /// sync the whole collab instead.
+  ReplayUnavailable = 10,
 }
 
 impl From<u8> for AckCode {
@@ -222,6 +246,11 @@ impl From<u8> for AckCode {
       3 => AckCode::Internal,
       4 => AckCode::EncodeStateAsUpdateFail,
       5 => AckCode::MissUpdate,
+      6 => AckCode::Reset,
+      7 => AckCode::EditingLocked,
+      8 => AckCode::ReadOnly,
+      9 => AckCode::CollabSizeExceeded,
+      10 => AckCode::ReplayUnavailable,
       _ => AckCode::Internal,
     }
   }
@@ -295,6 +324,28 @@ impl Display for BroadcastSync {
   }
 }
 
+/// A broadcast along with the number of its update in the replay buffer of the collab. Unlike
+/// [BroadcastSync::seq_num], which is counted by each group, the number is shared by all the
+/// realtime servers, so that a client reconnecting to another one can ask for the updates it
+/// missed, see [crate::ReplaySync].
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, Hash)]
+pub struct SequencedBroadcast {
+  pub broadcast: BroadcastSync,
+  pub stream_seq: u64,
+}
+
+impl Display for SequencedBroadcast {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_fmt(format_args!(
+      "sequenced broadcast: [oid:{}|len:{}|seq_num:{}|stream_seq:{}]",
+      self.broadcast.object_id,
+      self.broadcast.payload.len(),
+      self.broadcast.seq_num,
+      self.stream_seq
+    ))
+  }
+}
+
 ///  ⚠️ ⚠️ ⚠️Compatibility Warning:
 ///
 /// The structure of this struct is integral to maintaining compatibility with existing messages.
@@ -1,7 +1,9 @@
 mod data_validation;
 mod message;
+mod opaque;
 mod protocol;
 
 pub use data_validation::*;
 pub use message::*;
+pub use opaque::*;
 pub use protocol::*;
@@ -95,16 +95,22 @@ impl Display for Message {
 
 /// Tag id for [CustomMessage::MSG_CUSTOM_START_SYNC].
 pub const MSG_CUSTOM_START_SYNC: u8 = 0;
+/// Tag id for [CustomMessage::EditingLock].
+pub const MSG_CUSTOM_EDITING_LOCK: u8 = 1;
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum CustomMessage {
   SyncCheck(SyncMeta),
+  /// Sent by the server when the editing lock of the collab was acquired (`Some`) or released
+  /// (`None`).
+  EditingLock(Option<EditingLockMeta>),
 }
 
 impl Display for CustomMessage {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
     match self {
       CustomMessage::SyncCheck(_) => f.write_str("SyncCheck"),
+      CustomMessage::EditingLock(_) => f.write_str("EditingLock"),
     }
   }
 }
@@ -115,6 +121,10 @@ impl Encode for CustomMessage {
         encoder.write_var(MSG_CUSTOM_START_SYNC);
         encoder.write_buf(msg.to_vec());
       },
+      CustomMessage::EditingLock(lock) => {
+        encoder.write_var(MSG_CUSTOM_EDITING_LOCK);
+        encoder.write_buf(bincode::serialize(lock).unwrap());
+      },
     }
   }
 }
@@ -128,6 +138,12 @@ impl Decode for CustomMessage {
         let meta = SyncMeta::from_vec(buf)?;
         Ok(CustomMessage::SyncCheck(meta))
       },
+      MSG_CUSTOM_EDITING_LOCK => {
+        let buf = decoder.read_buf()?;
+        let lock =
+          bincode::deserialize(buf).map_err(|_| yrs::encoding::read::Error::UnexpectedValue)?;
+        Ok(CustomMessage::EditingLock(lock))
+      },
       _ => Err(yrs::encoding::read::Error::UnexpectedValue),
     }
   }
@@ -150,12 +166,69 @@ impl SyncMeta {
   }
 }
 
+/// The member holding the editing lock of a collab. While the lock is held, updates from other
+/// members are rejected by the server.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct EditingLockMeta {
+  pub uid: i64,
+  pub name: String,
+  /// Timestamp in milliseconds after which the lock expires unless the holder renews it.
+  pub expires_at: i64,
+}
+
+impl EditingLockMeta {
+  pub fn to_vec(&self) -> Vec<u8> {
+    bincode::serialize(self).unwrap()
+  }
+
+  pub fn from_vec(data: &[u8]) -> Result<Self, yrs::encoding::read::Error> {
+    let meta =
+      bincode::deserialize(data).map_err(|_| yrs::encoding::read::Error::UnexpectedValue)?;
+    Ok(meta)
+  }
+
+  /// Returns the lock carried by a payload encoded with [Message::encode_v1], or `None` if the
+  /// payload is not a [CustomMessage::EditingLock]. The first byte is checked before decoding, so
+  /// that regular updates are not decoded.
+  pub fn from_message_payload(payload: &[u8]) -> Option<Option<Self>> {
+    if payload.first() != Some(&MSG_CUSTOM) {
+      return None;
+    }
+    match Message::decode_v1(payload) {
+      Ok(Message::Custom(CustomMessage::EditingLock(lock))) => Some(lock),
+      _ => None,
+    }
+  }
+}
+
+/// The size limit an update or a doc state went over. The server rejects the update, and the
+/// sender's copy of the collab can't be synced anymore.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CollabSizeExceeded {
+  pub limit: u64,
+  pub actual: u64,
+}
+
+impl CollabSizeExceeded {
+  pub fn to_vec(&self) -> Vec<u8> {
+    bincode::serialize(self).unwrap()
+  }
+
+  pub fn from_vec(data: &[u8]) -> Result<Self, yrs::encoding::read::Error> {
+    let size =
+      bincode::deserialize(data).map_err(|_| yrs::encoding::read::Error::UnexpectedValue)?;
+    Ok(size)
+  }
+}
+
 /// Tag id for [SyncMessage::SyncStep1].
 pub const MSG_SYNC_STEP_1: u8 = 0;
 /// Tag id for [SyncMessage::SyncStep2].
 pub const MSG_SYNC_STEP_2: u8 = 1;
 /// Tag id for [SyncMessage::Update].
 pub const MSG_SYNC_UPDATE: u8 = 2;
+/// Tag id for [SyncMessage::FullState].
+pub const MSG_SYNC_FULL_STATE: u8 = 3;
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum SyncMessage {
@@ -165,6 +238,11 @@ pub enum SyncMessage {
   SyncStep2(Vec<u8>),
   /// Update message contains the encoded [yrs::Update] from the remote side
   Update(Vec<u8>),
+  /// The full doc state of the remote side, sent in place of a [SyncMessage::SyncStep2] when the
+  /// missing updates would be larger. It doesn't depend on the state of the receiver, which
+  /// takes it as its new state: nothing of the remote side can be missing once it's applied, and
+  /// the changes the remote side doesn't have yet are sent back in reply to its sync step 1.
+  FullState(Vec<u8>),
 }
 
 impl Display for SyncMessage {
@@ -179,6 +257,9 @@ impl Display for SyncMessage {
       SyncMessage::Update(data) => {
         write!(f, "Update({})", data.len())
       },
+      SyncMessage::FullState(data) => {
+        write!(f, "FullState({})", data.len())
+      },
     }
   }
 }
@@ -198,6 +279,10 @@ impl Encode for SyncMessage {
         encoder.write_var(MSG_SYNC_UPDATE);
         encoder.write_buf(u);
       },
+      SyncMessage::FullState(u) => {
+        encoder.write_var(MSG_SYNC_FULL_STATE);
+        encoder.write_buf(u);
+      },
     }
   }
 }
@@ -219,6 +304,10 @@ impl Decode for SyncMessage {
         let buf = decoder.read_buf()?;
         Ok(SyncMessage::Update(buf.into()))
       },
+      MSG_SYNC_FULL_STATE => {
+        let buf = decoder.read_buf()?;
+        Ok(SyncMessage::FullState(buf.into()))
+      },
       _ => Err(yrs::encoding::read::Error::UnexpectedValue),
     }
   }
@@ -267,6 +356,29 @@ pub enum RTProtocolError {
     reason: String,
   },
 
+  /// The collab was replaced by a compacted copy after the sender loaded it. The sender must
+  /// discard its local copy and load the collab again instead of syncing it.
+  #[error("collab was reset")]
+  CollabReset,
+
+  /// The collab is locked for editing by another member. Updates from the sender were dropped.
+  #[error("collab is locked by {}", .0.name)]
+  EditingLocked(EditingLockMeta),
+
+  /// The collab is served from the snapshot it was recovered from until an admin confirms the
+  /// repair. Updates from the sender were dropped.
+  #[error("collab is read-only until its repair is confirmed")]
+  ReadOnly,
+
+  /// The update, or the doc state it would grow the collab to, is larger than the server accepts.
+  /// The update was dropped.
+  #[error("collab of {} bytes exceeds the limit of {} bytes", .0.actual, .0.limit)]
+  CollabSizeExceeded(CollabSizeExceeded),
+
+  /// The server is overloaded and rejected the message. The sender can send it again later.
+  #[error("server is overloaded")]
+  Overloaded,
+
   #[error(transparent)]
   Internal(#[from] anyhow::Error),
 }
@@ -0,0 +1,63 @@
+//! The state of an opaque collab, whose updates are encrypted by the clients. The server can't
+//! merge them, so the state is the sequence of the updates it received, each one stored as a
+//! length-prefixed frame.
+use yrs::encoding::read::{Cursor, Read};
+use yrs::encoding::write::Write;
+
+/// Encodes the updates as the doc state of an opaque collab.
+pub fn encode_opaque_frames<I, F>(frames: I) -> Vec<u8>
+where
+  I: IntoIterator<Item = F>,
+  F: AsRef<[u8]>,
+{
+  let mut buf = vec![];
+  for frame in frames {
+    buf.write_buf(frame.as_ref());
+  }
+  buf
+}
+
+/// Appends the updates to the doc state of an opaque collab.
+pub fn append_opaque_frames<I, F>(doc_state: &[u8], frames: I) -> Vec<u8>
+where
+  I: IntoIterator<Item = F>,
+  F: AsRef<[u8]>,
+{
+  let mut doc_state = doc_state.to_vec();
+  doc_state.extend(encode_opaque_frames(frames));
+  doc_state
+}
+
+/// Returns the updates held by the doc state of an opaque collab, in the order they were received.
+pub fn decode_opaque_frames(doc_state: &[u8]) -> Result<Vec<Vec<u8>>, yrs::encoding::read::Error> {
+  let mut cursor = Cursor::new(doc_state);
+  let mut frames = vec![];
+  while cursor.has_content() {
+    frames.push(cursor.read_buf()?.to_vec());
+  }
+  Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn opaque_frames_round_trip_test() {
+    let frames = vec![vec![1u8, 2, 3], vec![], vec![0xff; 300]];
+    let doc_state = encode_opaque_frames(&frames);
+    assert_eq!(decode_opaque_frames(&doc_state).unwrap(), frames);
+
+    let appended = append_opaque_frames(&doc_state, [vec![9u8]]);
+    let decoded = decode_opaque_frames(&appended).unwrap();
+    assert_eq!(decoded.len(), 4);
+    assert_eq!(decoded[3], vec![9u8]);
+    assert!(decode_opaque_frames(&[]).unwrap().is_empty());
+  }
+
+  #[test]
+  fn truncated_frames_are_rejected_test() {
+    let doc_state = encode_opaque_frames([vec![1u8; 10]]);
+    assert!(decode_opaque_frames(&doc_state[..5]).is_err());
+  }
+}
@@ -69,6 +69,30 @@ impl CollabSyncProtocol for ClientSyncProtocol {
       None => Ok(None),
     }
   }
+
+  /// The full state doesn't depend on the local state, so once it's applied nothing of the
+  /// sender can be missing: the pending updates are not checked as for a sync step 2.
+  async fn handle_full_state(
+    &self,
+    origin: &CollabOrigin,
+    collab: &CollabRef,
+    state: Vec<u8>,
+  ) -> Result<Option<Vec<u8>>, RTProtocolError> {
+    let update = decode_update(state).await?;
+    let mut lock = collab.write().await;
+    let collab = (*lock).borrow_mut();
+    let mut txn = collab
+      .get_awareness()
+      .doc()
+      .try_transact_mut_with(origin.clone())
+      .map_err(|err| {
+        RTProtocolError::YrsTransaction(format!("full state transaction acquire: {}", err))
+      })?;
+    txn.try_apply_update(update).map_err(|err| {
+      RTProtocolError::YrsApplyUpdate(format!("full state apply update: {} ", err))
+    })?;
+    Ok(None)
+  }
 }
 
 pub type CollabRef = Arc<RwLock<dyn BorrowMut<Collab> + Send + Sync + 'static>>;
@@ -89,6 +113,9 @@ pub trait CollabSyncProtocol {
           self.handle_sync_step2(message_origin, collab, update).await
         },
         SyncMessage::Update(update) => self.handle_update(message_origin, collab, update).await,
+        SyncMessage::FullState(state) => {
+          self.handle_full_state(message_origin, collab, state).await
+        },
       },
       Message::Auth(reason) => self.handle_auth(collab, reason).await,
       //FIXME: where is the QueryAwareness protocol?
@@ -175,6 +202,17 @@ pub trait CollabSyncProtocol {
     update: Vec<u8>,
   ) -> Result<Option<Vec<u8>>, RTProtocolError>;
 
+  /// Handle the full doc state sent in place of a sync-step-2 reply, see
+  /// [SyncMessage::FullState]. By default it's applied as a sync step 2.
+  async fn handle_full_state(
+    &self,
+    origin: &CollabOrigin,
+    collab: &CollabRef,
+    state: Vec<u8>,
+  ) -> Result<Option<Vec<u8>>, RTProtocolError> {
+    self.handle_sync_step2(origin, collab, state).await
+  }
+
   /// Handle continuous update send from the client. By default just apply an update to a current
   /// `awareness` document instance.
   async fn handle_update(
@@ -223,6 +261,49 @@ pub trait CollabSyncProtocol {
 
 pub const LARGE_UPDATE_THRESHOLD: usize = 1024 * 1024; // 1MB
 
+/// When the reply to a sync step 1 is the full doc state instead of the missing updates, see
+/// [SyncMessage::FullState].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FullStateThreshold {
+  /// Missing updates smaller than this are sent as they are, without encoding the full doc state
+  /// to compare them with.
+  pub min_delta_len: usize,
+  /// The full doc state is sent when the missing updates are larger than this ratio of its size.
+  /// At `1.0`, the smaller of the two is sent.
+  pub ratio: f64,
+}
+
+impl Default for FullStateThreshold {
+  fn default() -> Self {
+    Self {
+      min_delta_len: 64 * 1024,
+      ratio: 1.0,
+    }
+  }
+}
+
+/// Encodes the updates missing from `remote_sv`, as a sync step 2, or the full doc state when
+/// the missing updates are over the `threshold`. Without a threshold, the receiver doesn't
+/// support [SyncMessage::FullState] and always gets the missing updates.
+pub fn encode_missing_updates<T: ReadTxn>(
+  txn: &T,
+  remote_sv: &StateVector,
+  threshold: Option<&FullStateThreshold>,
+) -> SyncMessage {
+  let delta = txn.encode_state_as_update_v1(remote_sv);
+  let threshold = match threshold {
+    // from an empty state vector, the missing updates are the full state
+    Some(threshold) if !remote_sv.is_empty() && delta.len() >= threshold.min_delta_len => threshold,
+    _ => return SyncMessage::SyncStep2(delta),
+  };
+  let full_state = txn.encode_state_as_update_v1(&StateVector::default());
+  if delta.len() as f64 > full_state.len() as f64 * threshold.ratio {
+    SyncMessage::FullState(full_state)
+  } else {
+    SyncMessage::SyncStep2(delta)
+  }
+}
+
 #[inline]
 pub async fn decode_update(update: Vec<u8>) -> Result<Update, yrs::encoding::read::Error> {
   let update = if update.len() > LARGE_UPDATE_THRESHOLD {
@@ -234,3 +315,119 @@ pub async fn decode_update(update: Vec<u8>) -> Result<Update, yrs::encoding::rea
   }?;
   Ok(update)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use yrs::{Doc, GetString, Text};
+
+  const THRESHOLD: FullStateThreshold = FullStateThreshold {
+    min_delta_len: 0,
+    ratio: 0.5,
+  };
+
+  /// A doc with `edits` inserts of ten characters by the same client.
+  fn server_doc(edits: usize) -> Doc {
+    let doc = Doc::with_client_id(1);
+    let text = doc.get_or_insert_text("text");
+    for i in 0..edits {
+      let mut txn = doc.transact_mut();
+      let len = text.len(&txn);
+      text.insert(&mut txn, len, &i.to_string().repeat(10)[..10]);
+    }
+    doc
+  }
+
+  fn copy_of(doc: &Doc, client_id: u64) -> Doc {
+    let copy = Doc::with_client_id(client_id);
+    let update = doc
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+    copy
+      .transact_mut()
+      .apply_update(Update::decode_v1(&update).unwrap())
+      .unwrap();
+    copy
+  }
+
+  fn apply(doc: &Doc, payload: &[u8]) {
+    doc
+      .transact_mut()
+      .apply_update(Update::decode_v1(payload).unwrap())
+      .unwrap();
+  }
+
+  fn text_of(doc: &Doc) -> String {
+    let text = doc.get_or_insert_text("text");
+    let txn = doc.transact();
+    text.get_string(&txn)
+  }
+
+  #[test]
+  fn empty_state_vector_gets_sync_step2_test() {
+    let server = server_doc(100);
+    let txn = server.transact();
+    let full_state = txn.encode_state_as_update_v1(&StateVector::default());
+    let msg = encode_missing_updates(&txn, &StateVector::default(), Some(&THRESHOLD));
+    assert_eq!(msg, SyncMessage::SyncStep2(full_state));
+  }
+
+  #[test]
+  fn slightly_behind_client_gets_missing_updates_test() {
+    let client = copy_of(&server_doc(99), 2);
+    let server = server_doc(100);
+    let client_sv = client.transact().state_vector();
+    let msg = encode_missing_updates(&server.transact(), &client_sv, Some(&THRESHOLD));
+    match msg {
+      SyncMessage::SyncStep2(delta) => {
+        apply(&client, &delta);
+        assert_eq!(text_of(&client), text_of(&server));
+      },
+      other => panic!("expected the missing updates, got {}", other),
+    }
+  }
+
+  #[test]
+  fn divergent_client_gets_full_state_test() {
+    // the client only saw the first edit, then edited the collab on its own
+    let client = copy_of(&server_doc(1), 2);
+    {
+      let text = client.get_or_insert_text("text");
+      let mut txn = client.transact_mut();
+      text.insert(&mut txn, 0, "offline ");
+    }
+    let server = server_doc(100);
+    let client_sv = client.transact().state_vector();
+    let server_txn = server.transact();
+
+    let msg = encode_missing_updates(&server_txn, &client_sv, Some(&THRESHOLD));
+    let full_state = match msg {
+      SyncMessage::FullState(full_state) => full_state,
+      other => panic!("expected the full state, got {}", other),
+    };
+    assert_eq!(
+      full_state,
+      server_txn.encode_state_as_update_v1(&StateVector::default())
+    );
+    apply(&client, &full_state);
+    // the changes of the client are kept, to be sent back to the server
+    assert_eq!(text_of(&client), format!("offline {}", text_of(&server)));
+
+    // without a threshold, the receiver gets the missing updates
+    let msg = encode_missing_updates(&server_txn, &client_sv, None);
+    assert!(matches!(msg, SyncMessage::SyncStep2(_)));
+  }
+
+  #[test]
+  fn small_delta_is_not_compared_test() {
+    let client = copy_of(&server_doc(1), 2);
+    let server = server_doc(2);
+    let client_sv = client.transact().state_vector();
+    let threshold = FullStateThreshold {
+      min_delta_len: 1024,
+      ratio: 0.0,
+    };
+    let msg = encode_missing_updates(&server.transact(), &client_sv, Some(&threshold));
+    assert!(matches!(msg, SyncMessage::SyncStep2(_)));
+  }
+}
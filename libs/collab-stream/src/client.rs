@@ -1,18 +1,38 @@
 use crate::collab_update_sink::{AwarenessUpdateSink, CollabUpdateSink};
+use crate::edit_counter::EditCounterStore;
+use crate::editing_lock::EditingLockStore;
 use crate::error::{internal, StreamError};
 use crate::lease::{Lease, LeaseAcquisition};
 use crate::metrics::CollabStreamMetrics;
-use crate::model::{AwarenessStreamUpdate, CollabStreamUpdate, MessageId};
+use crate::model::{AwarenessStreamUpdate, CollabStreamUpdate, MessageId, UpdateFlags};
+use crate::presence::PresenceStore;
+use crate::replay_buffer::ReplayBuffer;
 use crate::stream_group::{StreamConfig, StreamGroup};
 use crate::stream_router::{StreamRouter, StreamRouterOptions};
+use collab::core::origin::CollabOrigin;
+use collab::preclude::updates::encoder::Encode;
+use collab::preclude::Update;
 use futures::Stream;
 use redis::aio::ConnectionManager;
-use redis::streams::StreamReadReply;
+use redis::streams::{StreamRangeReply, StreamReadReply};
 use redis::{AsyncCommands, FromRedisValue};
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::error;
 
+/// Appends the reset marker unless an update was appended after ARGV[1], then drops the updates
+/// before the marker and the replay buffer. Returns the id of the marker.
+const RESET_SCRIPT: &str = r#"
+local after = redis.call("XRANGE", KEYS[1], "(" .. ARGV[1], "+", "COUNT", 1)
+if #after > 0 then
+  return false
+end
+redis.call("DEL", KEYS[2])
+local id = redis.call("XADD", KEYS[1], "*", "flags", ARGV[2], "sender", ARGV[3], "data", ARGV[4])
+redis.call("XTRIM", KEYS[1], "MINID", id)
+return id
+"#;
+
 #[derive(Clone)]
 pub struct CollabRedisStream {
   connection_manager: ConnectionManager,
@@ -108,8 +128,23 @@ impl CollabRedisStream {
   }
 
   pub fn collab_update_sink(&self, workspace_id: &str, object_id: &str) -> CollabUpdateSink {
-    let stream_key = CollabStreamUpdate::stream_key(workspace_id, object_id);
-    CollabUpdateSink::new(self.connection_manager.clone(), stream_key)
+    CollabUpdateSink::new(self.connection_manager.clone(), workspace_id, object_id)
+  }
+
+  pub fn replay_buffer(&self) -> ReplayBuffer {
+    ReplayBuffer::new(self.connection_manager.clone())
+  }
+
+  pub fn editing_locks(&self) -> EditingLockStore {
+    EditingLockStore::new(self.connection_manager.clone())
+  }
+
+  pub fn edit_counters(&self) -> EditCounterStore {
+    EditCounterStore::new(self.connection_manager.clone())
+  }
+
+  pub fn presence(&self) -> PresenceStore {
+    PresenceStore::new(self.connection_manager.clone())
   }
 
   pub fn awareness_update_sink(&self, workspace_id: &str, object_id: &str) -> AwarenessUpdateSink {
@@ -142,6 +177,29 @@ impl CollabRedisStream {
     Ok(result)
   }
 
+  /// Reads the collab updates for a given `workspace_id`:`object_id` entry between two message
+  /// ids, both included. Missing bounds stand for the start and the end of the stream.
+  pub async fn collab_updates_range(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    start: Option<MessageId>,
+    end: Option<MessageId>,
+  ) -> Result<Vec<(MessageId, CollabStreamUpdate)>, StreamError> {
+    let stream_key = CollabStreamUpdate::stream_key(workspace_id, object_id);
+    let start = start.map_or_else(|| "-".to_string(), |id| id.to_string());
+    let end = end.map_or_else(|| "+".to_string(), |id| id.to_string());
+    let mut conn = self.connection_manager.clone();
+    let reply: StreamRangeReply = conn.xrange(&stream_key, start, end).await?;
+    let mut result = Vec::with_capacity(reply.ids.len());
+    for stream_id in reply.ids {
+      let message_id = MessageId::try_from(stream_id.id)?;
+      let stream_update = CollabStreamUpdate::try_from(stream_id.map)?;
+      result.push((message_id, stream_update));
+    }
+    Ok(result)
+  }
+
   /// Reads all collab updates for a given `workspace_id`:`object_id` entry, starting
   /// from a given message id. This stream will be kept alive and pass over all future messages
   /// coming from corresponding Redis stream until explicitly closed.
@@ -209,6 +267,36 @@ impl CollabRedisStream {
     Ok(count)
   }
 
+  /// Tells every group of the collab that its history was replaced, see [UpdateFlags::IS_RESET],
+  /// and drops the updates of the old history. Nothing is done when updates were appended after
+  /// `message_id`, since they weren't part of the new history: checking it and appending the
+  /// marker is atomic, so no update can slip in between.
+  ///
+  /// Returns the id of the marker, or `None` when updates were appended after `message_id`.
+  pub async fn reset_update_stream(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    message_id: MessageId,
+  ) -> Result<Option<MessageId>, StreamError> {
+    let reset = CollabStreamUpdate::new(
+      Update::default().encode_v1(),
+      CollabOrigin::Server,
+      UpdateFlags::IS_RESET,
+    );
+    let mut conn = self.connection_manager.clone();
+    let reset_id: Option<MessageId> = redis::Script::new(RESET_SCRIPT)
+      .key(CollabStreamUpdate::stream_key(workspace_id, object_id))
+      .key(ReplayBuffer::buffer_key(workspace_id, object_id))
+      .arg(message_id.to_string())
+      .arg(reset.flags)
+      .arg(reset.sender.to_string())
+      .arg(&*reset.data)
+      .invoke_async(&mut conn)
+      .await?;
+    Ok(reset_id)
+  }
+
   pub async fn prune_awareness_stream(&self, stream_key: &str) -> Result<(), StreamError> {
     let mut conn = self.connection_manager.clone();
     let value = conn
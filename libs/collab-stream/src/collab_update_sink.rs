@@ -1,5 +1,6 @@
 use crate::error::StreamError;
 use crate::model::{AwarenessStreamUpdate, CollabStreamUpdate, MessageId};
+use crate::replay_buffer::{ReplayBuffer, APPEND_SCRIPT};
 use redis::aio::ConnectionManager;
 use redis::cmd;
 use tokio::sync::Mutex;
@@ -7,20 +8,57 @@ use tokio::sync::Mutex;
 pub struct CollabUpdateSink {
   conn: Mutex<ConnectionManager>,
   stream_key: String,
+  replay_key: String,
+  replay_seq_key: String,
 }
 
 impl CollabUpdateSink {
-  pub fn new(conn: ConnectionManager, stream_key: String) -> Self {
+  /// The update stream is trimmed once its updates are persisted, see
+  /// [crate::client::CollabRedisStream::prune_update_stream]. This cap only bounds the stream of a
+  /// collab whose updates no group persists, and is far above what a group leaves unpersisted.
+  pub const MAX_STREAM_LEN: usize = 100_000;
+
+  pub fn new(conn: ConnectionManager, workspace_id: &str, object_id: &str) -> Self {
     CollabUpdateSink {
       conn: conn.into(),
-      stream_key,
+      stream_key: CollabStreamUpdate::stream_key(workspace_id, object_id),
+      replay_key: ReplayBuffer::buffer_key(workspace_id, object_id),
+      replay_seq_key: ReplayBuffer::seq_key(workspace_id, object_id),
     }
   }
 
+  /// Appends the update to the update stream. The updates with data are also numbered and kept
+  /// in the [ReplayBuffer] of the collab, while a reset drops the buffer, since the updates it
+  /// holds no longer apply to the collab.
   pub async fn send(&self, msg: &CollabStreamUpdate) -> Result<MessageId, StreamError> {
     let mut lock = self.conn.lock().await;
+    if !msg.flags.is_reset() && !msg.flags.is_lock_changed() {
+      let msg_id: MessageId = redis::Script::new(APPEND_SCRIPT)
+        .key(&self.stream_key)
+        .key(&self.replay_seq_key)
+        .key(&self.replay_key)
+        .arg(Self::MAX_STREAM_LEN)
+        .arg(ReplayBuffer::CAPACITY)
+        .arg(ReplayBuffer::TTL.as_millis() as u64)
+        .arg(ReplayBuffer::initial_seq())
+        .arg(msg.flags)
+        .arg(msg.sender.to_string())
+        .arg(&*msg.data)
+        .invoke_async(&mut *lock)
+        .await?;
+      return Ok(msg_id);
+    }
+    if msg.flags.is_reset() {
+      cmd("DEL")
+        .arg(&self.replay_key)
+        .query_async::<_, ()>(&mut *lock)
+        .await?;
+    }
     let msg_id: MessageId = cmd("XADD")
       .arg(&self.stream_key)
+      .arg("MAXLEN")
+      .arg("~")
+      .arg(Self::MAX_STREAM_LEN)
       .arg("*")
       .arg("flags")
       .arg(msg.flags)
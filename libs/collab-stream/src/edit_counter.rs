@@ -0,0 +1,203 @@
+use crate::error::StreamError;
+use crate::model::MessageId;
+use redis::aio::ConnectionManager;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Adds the updates past the last counted one to the counter. The updates stay in the Redis
+/// stream for a grace period after being persisted, so the same update may be read again, by
+/// this server or by another one. Arguments: the current time in milliseconds, the ttl of the
+/// counter in milliseconds, then the timestamp, sequence number and length of each update.
+const RECORD_SCRIPT: &str = r#"
+local last_ts = tonumber(redis.call("HGET", KEYS[1], "last_ts") or "-1")
+local last_seq = tonumber(redis.call("HGET", KEYS[1], "last_seq") or "-1")
+local updates = 0
+local bytes = 0
+for i = 3, #ARGV, 3 do
+  local ts = tonumber(ARGV[i])
+  local seq = tonumber(ARGV[i + 1])
+  if ts > last_ts or (ts == last_ts and seq > last_seq) then
+    updates = updates + 1
+    bytes = bytes + tonumber(ARGV[i + 2])
+    last_ts = ts
+    last_seq = seq
+  end
+end
+if updates > 0 then
+  redis.call("HSETNX", KEYS[1], "since", ARGV[1])
+  redis.call("HINCRBY", KEYS[1], "updates", updates)
+  redis.call("HINCRBY", KEYS[1], "bytes", bytes)
+  redis.call("HSET", KEYS[1], "last_ts", last_ts, "last_seq", last_seq)
+end
+if redis.call("EXISTS", KEYS[1]) == 1 then
+  redis.call("PEXPIRE", KEYS[1], ARGV[2])
+end
+return redis.call("HGETALL", KEYS[1])
+"#;
+
+/// Resets the counter unless it was reset since it was read with the given generation.
+/// Arguments: the generation, then the current time in milliseconds.
+const RESET_SCRIPT: &str = r#"
+local generation = tonumber(redis.call("HGET", KEYS[1], "generation") or "0")
+if generation ~= tonumber(ARGV[1]) then
+  return 0
+end
+redis.call("HSET", KEYS[1], "updates", 0, "bytes", 0, "since", ARGV[2], "generation", generation + 1)
+return 1
+"#;
+
+/// The updates applied to a collab since its counter was last reset.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EditCount {
+  pub updates: u64,
+  pub bytes: u64,
+  /// Timestamp in milliseconds of the last reset, or of the first counted update.
+  pub since: Option<i64>,
+  /// Incremented by each reset, see [EditCounterStore::reset].
+  pub generation: u64,
+}
+
+impl EditCount {
+  fn from_fields(fields: HashMap<String, String>) -> Result<Self, StreamError> {
+    let get = |key: &str| {
+      fields
+        .get(key)
+        .map(|value| value.parse::<i64>())
+        .transpose()
+    };
+    Ok(Self {
+      updates: get("updates")?.unwrap_or_default() as u64,
+      bytes: get("bytes")?.unwrap_or_default() as u64,
+      since: get("since")?,
+      generation: get("generation")?.unwrap_or_default() as u64,
+    })
+  }
+}
+
+/// Counts in Redis the updates applied to each collab, so that all realtime servers see the
+/// same counts whichever of them persists the collab.
+#[derive(Clone)]
+pub struct EditCounterStore {
+  conn: ConnectionManager,
+}
+
+impl EditCounterStore {
+  /// The counter of a collab which isn't edited for this long is dropped.
+  pub const TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+  pub fn new(conn: ConnectionManager) -> Self {
+    Self { conn }
+  }
+
+  pub fn counter_key(workspace_id: &str, object_id: &str) -> String {
+    format!("af:{}:{}:edit_count", workspace_id, object_id)
+  }
+
+  /// Counts the given updates, skipping the ones already counted. Returns the updated count.
+  pub async fn record(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    updates: &[(MessageId, usize)],
+  ) -> Result<EditCount, StreamError> {
+    let script = redis::Script::new(RECORD_SCRIPT);
+    let mut invocation = script.key(Self::counter_key(workspace_id, object_id));
+    invocation
+      .arg(chrono::Utc::now().timestamp_millis())
+      .arg(Self::TTL.as_millis() as u64);
+    for (message_id, len) in updates {
+      invocation
+        .arg(message_id.timestamp_ms)
+        .arg(message_id.sequence_number)
+        .arg(*len);
+    }
+    let mut conn = self.conn.clone();
+    let fields: HashMap<String, String> = invocation.invoke_async(&mut conn).await?;
+    EditCount::from_fields(fields)
+  }
+
+  pub async fn get(&self, workspace_id: &str, object_id: &str) -> Result<EditCount, StreamError> {
+    let mut conn = self.conn.clone();
+    let fields: HashMap<String, String> = redis::cmd("HGETALL")
+      .arg(Self::counter_key(workspace_id, object_id))
+      .query_async(&mut conn)
+      .await?;
+    EditCount::from_fields(fields)
+  }
+
+  /// Resets the counter read as `count`, once a snapshot is taken from it. Returns `false` when
+  /// the counter was reset since it was read, by another server which is taking the snapshot.
+  pub async fn reset(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    count: &EditCount,
+  ) -> Result<bool, StreamError> {
+    let mut conn = self.conn.clone();
+    let reset: i32 = redis::Script::new(RESET_SCRIPT)
+      .key(Self::counter_key(workspace_id, object_id))
+      .arg(count.generation)
+      .arg(chrono::Utc::now().timestamp_millis())
+      .invoke_async(&mut conn)
+      .await?;
+    Ok(reset == 1)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::edit_counter::EditCounterStore;
+  use crate::model::MessageId;
+  use redis::Client;
+
+  fn message_id(timestamp_ms: u64) -> MessageId {
+    MessageId {
+      timestamp_ms,
+      sequence_number: 0,
+    }
+  }
+
+  #[tokio::test]
+  async fn replayed_updates_are_counted_once() {
+    let redis_client = Client::open("redis://localhost:6379").unwrap();
+    let conn = redis_client.get_connection_manager().await.unwrap();
+    let store = EditCounterStore::new(conn);
+    let object_id = format!("edit_count_{}", rand::random::<u64>());
+
+    let updates: Vec<_> = (1..=3).map(|ts| (message_id(ts), 10)).collect();
+    let count = store.record("w1", &object_id, &updates).await.unwrap();
+    assert_eq!((count.updates, count.bytes), (3, 30));
+    assert!(count.since.is_some());
+
+    // the next save reads the updates still kept in the stream, from any server
+    let updates: Vec<_> = (1..=4).map(|ts| (message_id(ts), 10)).collect();
+    let count = store.record("w1", &object_id, &updates).await.unwrap();
+    assert_eq!((count.updates, count.bytes), (4, 40));
+    assert_eq!(store.get("w1", &object_id).await.unwrap(), count);
+  }
+
+  #[tokio::test]
+  async fn counter_is_reset_once() {
+    let redis_client = Client::open("redis://localhost:6379").unwrap();
+    let conn = redis_client.get_connection_manager().await.unwrap();
+    let store = EditCounterStore::new(conn);
+    let object_id = format!("edit_count_{}", rand::random::<u64>());
+
+    let count = store
+      .record("w1", &object_id, &[(message_id(1), 10)])
+      .await
+      .unwrap();
+    // two servers read the same count, only the first one takes the snapshot
+    assert!(store.reset("w1", &object_id, &count).await.unwrap());
+    assert!(!store.reset("w1", &object_id, &count).await.unwrap());
+
+    let count = store.get("w1", &object_id).await.unwrap();
+    assert_eq!((count.updates, count.bytes, count.generation), (0, 0, 1));
+    // the updates counted before the reset aren't counted again
+    let count = store
+      .record("w1", &object_id, &[(message_id(1), 10), (message_id(2), 5)])
+      .await
+      .unwrap();
+    assert_eq!((count.updates, count.bytes), (1, 5));
+  }
+}
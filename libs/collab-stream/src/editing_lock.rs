@@ -0,0 +1,341 @@
+use crate::collab_update_sink::CollabUpdateSink;
+use crate::error::{internal, StreamError};
+use crate::model::{CollabStreamUpdate, UpdateFlags};
+use collab::core::origin::CollabOrigin;
+use collab::preclude::updates::encoder::Encode;
+use collab::preclude::Update;
+use redis::aio::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Sets the lock unless it is held by another user. When the same user acquires the lock again
+/// (ie. from another device) the lock is taken over but keeps its original acquisition time.
+const ACQUIRE_SCRIPT: &str = r#"
+local holder = redis.call("HGET", KEYS[1], "uid")
+if holder and holder ~= ARGV[1] then
+  return 0
+end
+if not holder then
+  redis.call("HSET", KEYS[1], "acquired_at", ARGV[4])
+end
+redis.call("HSET", KEYS[1], "uid", ARGV[1], "name", ARGV[2], "device_id", ARGV[3], "expires_at", ARGV[5])
+redis.call("PEXPIRE", KEYS[1], ARGV[6])
+return 1
+"#;
+
+const HEARTBEAT_SCRIPT: &str = r#"
+if redis.call("HGET", KEYS[1], "uid") == ARGV[1] then
+  redis.call("HSET", KEYS[1], "expires_at", ARGV[2])
+  redis.call("PEXPIRE", KEYS[1], ARGV[3])
+  return 1
+end
+return 0
+"#;
+
+/// Removes the lock if it's held by the given user and, when a device id is given, by the given
+/// device. An empty uid removes the lock regardless of its holder.
+const RELEASE_SCRIPT: &str = r#"
+if ARGV[1] ~= "" then
+  if redis.call("HGET", KEYS[1], "uid") ~= ARGV[1] then
+    return 0
+  end
+  if ARGV[2] ~= "" and redis.call("HGET", KEYS[1], "device_id") ~= ARGV[2] then
+    return 0
+  end
+end
+return redis.call("DEL", KEYS[1])
+"#;
+
+/// Exclusive editing lock of a collab. While the lock is held, only its holder can modify the
+/// collab. It expires on its own unless the holder renews it with a heartbeat.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct EditingLock {
+  pub uid: i64,
+  pub name: String,
+  pub device_id: String,
+  /// Timestamp in milliseconds.
+  pub acquired_at: i64,
+  /// Timestamp in milliseconds.
+  pub expires_at: i64,
+}
+
+impl EditingLock {
+  pub fn is_expired(&self) -> bool {
+    self.expires_at <= chrono::Utc::now().timestamp_millis()
+  }
+
+  fn from_fields(mut fields: HashMap<String, String>) -> Result<Option<Self>, StreamError> {
+    if fields.is_empty() {
+      return Ok(None);
+    }
+    let mut take = |key: &str| {
+      fields
+        .remove(key)
+        .ok_or_else(|| internal(format!("editing lock is missing field `{}`", key)))
+    };
+    Ok(Some(EditingLock {
+      uid: take("uid")?.parse()?,
+      name: take("name")?,
+      device_id: take("device_id")?,
+      acquired_at: take("acquired_at")?.parse()?,
+      expires_at: take("expires_at")?.parse()?,
+    }))
+  }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum EditingLockAcquisition {
+  Acquired(EditingLock),
+  /// The lock is held by another user.
+  Locked(EditingLock),
+}
+
+/// Stores the editing locks in Redis. Every change of a lock holder is announced on the update
+/// stream of the collab with the [UpdateFlags::IS_LOCK_CHANGED] flag, so that the collab groups
+/// of all realtime servers pick it up. Heartbeats only extend the lock and are not announced.
+#[derive(Clone)]
+pub struct EditingLockStore {
+  conn: ConnectionManager,
+}
+
+impl EditingLockStore {
+  pub const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+  pub fn new(conn: ConnectionManager) -> Self {
+    Self { conn }
+  }
+
+  pub fn lock_key(workspace_id: &str, object_id: &str) -> String {
+    format!("af:{}:{}:editing_lock", workspace_id, object_id)
+  }
+
+  pub async fn get(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<Option<EditingLock>, StreamError> {
+    let mut conn = self.conn.clone();
+    let fields: HashMap<String, String> = redis::cmd("HGETALL")
+      .arg(Self::lock_key(workspace_id, object_id))
+      .query_async(&mut conn)
+      .await?;
+    EditingLock::from_fields(fields)
+  }
+
+  pub async fn acquire(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    uid: i64,
+    name: &str,
+    device_id: &str,
+    ttl: Duration,
+  ) -> Result<EditingLockAcquisition, StreamError> {
+    let key = Self::lock_key(workspace_id, object_id);
+    // the lock may expire between a failed attempt and reading its holder, in which case we try
+    // again
+    for _ in 0..2 {
+      let now = chrono::Utc::now().timestamp_millis();
+      let mut conn = self.conn.clone();
+      let acquired: i32 = redis::Script::new(ACQUIRE_SCRIPT)
+        .key(&key)
+        .arg(uid)
+        .arg(name)
+        .arg(device_id)
+        .arg(now)
+        .arg(now + ttl.as_millis() as i64)
+        .arg(ttl.as_millis() as u64)
+        .invoke_async(&mut conn)
+        .await?;
+
+      let lock = self.get(workspace_id, object_id).await?;
+      match lock {
+        Some(lock) if acquired == 1 => {
+          self.notify(workspace_id, object_id).await?;
+          return Ok(EditingLockAcquisition::Acquired(lock));
+        },
+        Some(lock) if lock.uid != uid => return Ok(EditingLockAcquisition::Locked(lock)),
+        _ => continue,
+      }
+    }
+    Err(internal(format!("failed to acquire editing lock of {}", object_id)).into())
+  }
+
+  /// Extends the lock held by the given user. Returns `None` if the user doesn't hold the lock.
+  pub async fn heartbeat(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    uid: i64,
+    ttl: Duration,
+  ) -> Result<Option<EditingLock>, StreamError> {
+    let expires_at = chrono::Utc::now().timestamp_millis() + ttl.as_millis() as i64;
+    let mut conn = self.conn.clone();
+    let renewed: i32 = redis::Script::new(HEARTBEAT_SCRIPT)
+      .key(Self::lock_key(workspace_id, object_id))
+      .arg(uid)
+      .arg(expires_at)
+      .arg(ttl.as_millis() as u64)
+      .invoke_async(&mut conn)
+      .await?;
+    if renewed == 1 {
+      self.get(workspace_id, object_id).await
+    } else {
+      Ok(None)
+    }
+  }
+
+  /// Releases the lock held by the given user. If `device_id` is set, the lock is only released
+  /// when it was acquired from that device. Returns `true` if the lock was released.
+  pub async fn release(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    uid: i64,
+    device_id: Option<&str>,
+  ) -> Result<bool, StreamError> {
+    self
+      .release_internal(
+        workspace_id,
+        object_id,
+        uid.to_string(),
+        device_id.unwrap_or_default(),
+      )
+      .await
+  }
+
+  /// Releases the lock regardless of its holder.
+  pub async fn force_release(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<bool, StreamError> {
+    self
+      .release_internal(workspace_id, object_id, String::new(), "")
+      .await
+  }
+
+  async fn release_internal(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    uid: String,
+    device_id: &str,
+  ) -> Result<bool, StreamError> {
+    let mut conn = self.conn.clone();
+    let released: i32 = redis::Script::new(RELEASE_SCRIPT)
+      .key(Self::lock_key(workspace_id, object_id))
+      .arg(uid)
+      .arg(device_id)
+      .invoke_async(&mut conn)
+      .await?;
+    if released == 1 {
+      self.notify(workspace_id, object_id).await?;
+    }
+    Ok(released == 1)
+  }
+
+  async fn notify(&self, workspace_id: &str, object_id: &str) -> Result<(), StreamError> {
+    let sink = CollabUpdateSink::new(self.conn.clone(), workspace_id, object_id);
+    let update = CollabStreamUpdate::new(
+      Update::default().encode_v1(),
+      CollabOrigin::Server,
+      UpdateFlags::IS_LOCK_CHANGED,
+    );
+    sink.send(&update).await?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::editing_lock::{EditingLockAcquisition, EditingLockStore};
+  use redis::Client;
+  use std::time::Duration;
+
+  #[tokio::test]
+  async fn editing_lock_acquisition() {
+    let redis_client = Client::open("redis://localhost:6379").unwrap();
+    let conn = redis_client.get_connection_manager().await.unwrap();
+    let store = EditingLockStore::new(conn);
+    let object_id = random_object_id();
+    let ttl = Duration::from_secs(5);
+
+    let lock = store
+      .acquire("w1", &object_id, 1, "alice", "d1", ttl)
+      .await
+      .unwrap();
+    assert!(matches!(lock, EditingLockAcquisition::Acquired(ref lock) if lock.uid == 1));
+
+    let lock = store
+      .acquire("w1", &object_id, 2, "bob", "d2", ttl)
+      .await
+      .unwrap();
+    assert!(
+      matches!(lock, EditingLockAcquisition::Locked(ref lock) if lock.name == "alice"),
+      "should fail to acquire lock held by another user"
+    );
+
+    assert!(store
+      .heartbeat("w1", &object_id, 1, ttl)
+      .await
+      .unwrap()
+      .is_some());
+    assert!(store
+      .heartbeat("w1", &object_id, 2, ttl)
+      .await
+      .unwrap()
+      .is_none());
+
+    // the holder disconnected from another device
+    assert!(!store
+      .release("w1", &object_id, 1, Some("d3"))
+      .await
+      .unwrap());
+    assert!(store
+      .release("w1", &object_id, 1, Some("d1"))
+      .await
+      .unwrap());
+    assert!(store.get("w1", &object_id).await.unwrap().is_none());
+
+    store
+      .acquire("w1", &object_id, 2, "bob", "d2", ttl)
+      .await
+      .unwrap();
+    assert!(store.force_release("w1", &object_id).await.unwrap());
+    assert!(store.get("w1", &object_id).await.unwrap().is_none());
+  }
+
+  #[tokio::test]
+  async fn editing_lock_expiration() {
+    let redis_client = Client::open("redis://localhost:6379").unwrap();
+    let conn = redis_client.get_connection_manager().await.unwrap();
+    let store = EditingLockStore::new(conn);
+    let object_id = random_object_id();
+
+    store
+      .acquire(
+        "w1",
+        &object_id,
+        1,
+        "alice",
+        "d1",
+        Duration::from_millis(200),
+      )
+      .await
+      .unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let lock = store
+      .acquire("w1", &object_id, 2, "bob", "d2", Duration::from_secs(5))
+      .await
+      .unwrap();
+    assert!(matches!(lock, EditingLockAcquisition::Acquired(ref lock) if lock.uid == 2));
+    store.force_release("w1", &object_id).await.unwrap();
+  }
+
+  fn random_object_id() -> String {
+    format!("editing_lock_{}", rand::random::<u64>())
+  }
+}
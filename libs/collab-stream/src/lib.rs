@@ -1,9 +1,13 @@
 pub mod client;
 pub mod collab_update_sink;
+pub mod edit_counter;
+pub mod editing_lock;
 pub mod error;
 pub mod lease;
 pub mod metrics;
 pub mod model;
+pub mod presence;
 pub mod pubsub;
+pub mod replay_buffer;
 pub mod stream_group;
 pub mod stream_router;
@@ -370,6 +370,10 @@ pub struct CollabStreamUpdate {
   pub data: Vec<u8>, // yrs::Update::encode_v1
   pub sender: CollabOrigin,
   pub flags: UpdateFlags,
+  /// Number of the update in the [crate::replay_buffer::ReplayBuffer] of the collab, assigned
+  /// when it's sent. Missing for the empty updates marked by a flag, and for the updates sent by
+  /// older servers.
+  pub seq: Option<u64>,
 }
 
 impl CollabStreamUpdate {
@@ -382,6 +386,7 @@ impl CollabStreamUpdate {
       data: data.into(),
       sender,
       flags: flags.into(),
+      seq: None,
     }
   }
 
@@ -425,10 +430,15 @@ impl TryFrom<HashMap<String, redis::Value>> for CollabStreamUpdate {
       .get("data")
       .ok_or_else(|| internal("expecting field `data`"))?;
     let data: Vec<u8> = FromRedisValue::from_redis_value(data_raw)?;
+    let seq = match fields.get("seq") {
+      None => None,
+      Some(seq) => Some(u64::from_redis_value(seq)?),
+    };
     Ok(CollabStreamUpdate {
       data,
       sender,
       flags,
+      seq,
     })
   }
 }
@@ -465,7 +475,7 @@ impl TryFrom<HashMap<String, redis::Value>> for AwarenessStreamUpdate {
 }
 
 //FIXME: this should be `impl FromStr for CollabOrigin`
-fn collab_origin_from_str(value: &str) -> RedisResult<CollabOrigin> {
+pub(crate) fn collab_origin_from_str(value: &str) -> RedisResult<CollabOrigin> {
   match value {
     "" => Ok(CollabOrigin::Empty),
     "server" => Ok(CollabOrigin::Server),
@@ -500,6 +510,13 @@ impl UpdateFlags {
   pub const IS_V2_ENCODED: u8 = 0b0000_0001;
   /// Flag bit to mark if update is compressed.
   pub const IS_COMPRESSED: u8 = 0b0000_0010;
+  /// Flag bit to mark that the collab was replaced by a compacted copy. Updates sent before this
+  /// one belong to the previous copy, so subscribers must reload the collab instead of applying
+  /// them. The update itself is empty.
+  pub const IS_RESET: u8 = 0b0000_0100;
+  /// Flag bit to mark that the editing lock of the collab was acquired or released. Subscribers
+  /// reload the lock from [crate::editing_lock::EditingLockStore]. The update itself is empty.
+  pub const IS_LOCK_CHANGED: u8 = 0b0000_1000;
 
   #[inline]
   pub fn is_v2_encoded(&self) -> bool {
@@ -515,6 +532,16 @@ impl UpdateFlags {
   pub fn is_compressed(&self) -> bool {
     self.0 & Self::IS_COMPRESSED != 0
   }
+
+  #[inline]
+  pub fn is_reset(&self) -> bool {
+    self.0 & Self::IS_RESET != 0
+  }
+
+  #[inline]
+  pub fn is_lock_changed(&self) -> bool {
+    self.0 & Self::IS_LOCK_CHANGED != 0
+  }
 }
 
 impl ToRedisArgs for UpdateFlags {
@@ -534,6 +561,13 @@ impl From<u8> for UpdateFlags {
   }
 }
 
+impl From<UpdateFlags> for u8 {
+  #[inline]
+  fn from(value: UpdateFlags) -> Self {
+    value.0
+  }
+}
+
 impl Display for UpdateFlags {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
     if !self.is_v2_encoded() {
@@ -546,6 +580,14 @@ impl Display for UpdateFlags {
       write!(f, ".zstd")?;
     }
 
+    if self.is_reset() {
+      write!(f, ".reset")?;
+    }
+
+    if self.is_lock_changed() {
+      write!(f, ".lock")?;
+    }
+
     Ok(())
   }
 }
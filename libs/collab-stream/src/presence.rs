@@ -0,0 +1,161 @@
+use crate::error::StreamError;
+use crate::model::{AwarenessStreamUpdate, MessageId};
+use collab::core::awareness::AwarenessUpdate;
+use collab::core::origin::CollabOrigin;
+use collab::preclude::updates::decoder::Decode;
+use redis::aio::ConnectionManager;
+use redis::streams::StreamRangeReply;
+use redis::AsyncCommands;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::warn;
+
+/// The awareness state of a client connected to a collab, as last announced by the client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Presence {
+  /// Id of the awareness client, one per opened copy of the collab.
+  pub client_id: u64,
+  pub uid: i64,
+  pub device_id: String,
+  pub clock: u32,
+  pub state: serde_json::Value,
+  /// Timestamp in milliseconds of the last awareness update of the client.
+  pub last_seen: u64,
+}
+
+/// Reads who's connected to a collab from its awareness stream. The collab groups of all
+/// realtime servers write the awareness updates of their subscribers to that stream, so it
+/// holds the presence of a collab however many servers it's opened on.
+#[derive(Clone)]
+pub struct PresenceStore {
+  conn: ConnectionManager,
+}
+
+impl PresenceStore {
+  /// Clients renew their awareness state every 15 seconds, and the clients which didn't renew it
+  /// for this long are considered gone, the same as in the awareness of the clients.
+  pub const AWARENESS_TIMEOUT: Duration = Duration::from_secs(30);
+
+  pub fn new(conn: ConnectionManager) -> Self {
+    Self { conn }
+  }
+
+  /// Returns the clients which announced their awareness state within the awareness timeout,
+  /// ordered by the time they last did so.
+  pub async fn get(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+  ) -> Result<Vec<Presence>, StreamError> {
+    let stream_key = AwarenessStreamUpdate::stream_key(workspace_id, object_id);
+    let since = (chrono::Utc::now().timestamp_millis() as u64)
+      .saturating_sub(Self::AWARENESS_TIMEOUT.as_millis() as u64);
+    let mut conn = self.conn.clone();
+    let reply: StreamRangeReply = conn.xrange(&stream_key, since, "+").await?;
+    let mut updates = Vec::with_capacity(reply.ids.len());
+    for stream_id in reply.ids {
+      let message_id = MessageId::try_from(stream_id.id)?;
+      match AwarenessStreamUpdate::try_from(stream_id.map) {
+        Ok(update) => updates.push((message_id, update)),
+        Err(err) => warn!("skip invalid awareness update {}: {}", message_id, err),
+      }
+    }
+    Ok(current_presences(updates))
+  }
+}
+
+/// Folds the awareness updates, in stream order, into the last state of each client. The clients
+/// which removed their state, when they closed the collab, are left out.
+fn current_presences(updates: Vec<(MessageId, AwarenessStreamUpdate)>) -> Vec<Presence> {
+  let mut presences: HashMap<u64, Option<Presence>> = HashMap::new();
+  for (message_id, update) in updates {
+    let device = match update.sender {
+      CollabOrigin::Client(client) => (client.uid, client.device_id),
+      // awareness is only announced by the clients
+      _ => continue,
+    };
+    let awareness = match AwarenessUpdate::decode_v1(&update.data) {
+      Ok(awareness) => awareness,
+      Err(err) => {
+        warn!("skip undecodable awareness update {}: {}", message_id, err);
+        continue;
+      },
+    };
+    for (client_id, entry) in awareness.clients {
+      let outdated = presences
+        .get(&client_id)
+        .and_then(|presence| presence.as_ref())
+        .is_some_and(|presence| presence.clock > entry.clock);
+      if outdated {
+        continue;
+      }
+      let state = match serde_json::from_str::<serde_json::Value>(&entry.json) {
+        Ok(serde_json::Value::Null) | Err(_) => None,
+        Ok(state) => Some(Presence {
+          client_id,
+          uid: device.0,
+          device_id: device.1.clone(),
+          clock: entry.clock,
+          state,
+          last_seen: message_id.timestamp_ms,
+        }),
+      };
+      presences.insert(client_id, state);
+    }
+  }
+  let mut presences: Vec<_> = presences.into_values().flatten().collect();
+  presences.sort_by_key(|presence| presence.last_seen);
+  presences
+}
+
+#[cfg(test)]
+mod test {
+  use super::current_presences;
+  use crate::model::{AwarenessStreamUpdate, MessageId};
+  use collab::core::awareness::{AwarenessUpdate, AwarenessUpdateEntry};
+  use collab::core::origin::{CollabClient, CollabOrigin};
+  use collab::preclude::updates::encoder::Encode;
+
+  fn awareness(uid: i64, client_id: u64, clock: u32, json: &str) -> AwarenessStreamUpdate {
+    let update = AwarenessUpdate {
+      clients: [(
+        client_id,
+        AwarenessUpdateEntry {
+          clock,
+          json: json.into(),
+        },
+      )]
+      .into_iter()
+      .collect(),
+    };
+    AwarenessStreamUpdate {
+      data: update.encode_v1(),
+      sender: CollabOrigin::Client(CollabClient {
+        uid,
+        device_id: "device".to_string(),
+      }),
+    }
+  }
+
+  #[test]
+  fn last_state_of_each_client_is_kept() {
+    let updates = vec![
+      (MessageId::new(1, 0), awareness(1, 10, 1, r#"{"uid":1}"#)),
+      (MessageId::new(2, 0), awareness(2, 20, 1, r#"{"uid":2}"#)),
+      (
+        MessageId::new(3, 0),
+        awareness(1, 10, 2, r#"{"uid":1,"selection":{"start":1}}"#),
+      ),
+      // the second client closed the collab
+      (MessageId::new(4, 0), awareness(2, 20, 2, "null")),
+      (MessageId::new(5, 0), awareness(3, 30, 1, r#"{"uid":3}"#)),
+    ];
+    let presences = current_presences(updates);
+    let clients: Vec<_> = presences
+      .iter()
+      .map(|presence| (presence.uid, presence.last_seen))
+      .collect();
+    assert_eq!(clients, vec![(1, 3), (3, 5)]);
+    assert_eq!(presences[0].state["selection"]["start"], 1);
+  }
+}
@@ -0,0 +1,219 @@
+use crate::error::StreamError;
+use crate::model::collab_origin_from_str;
+use collab::core::origin::CollabOrigin;
+use redis::aio::ConnectionManager;
+use std::time::Duration;
+
+/// Numbers an update and appends it to both the update stream and the replay buffer of the
+/// collab, so that the stream order and the sequence order are the same. The counter starts from
+/// the given initial value, derived from the current time, so that it keeps increasing after it
+/// expired along with the buffer. Keys: the update stream, the sequence counter and the buffer.
+/// Arguments: the max length of the stream, the capacity and ttl in milliseconds of the buffer,
+/// the initial value of the counter, then the flags, sender and data of the update.
+pub(crate) const APPEND_SCRIPT: &str = r#"
+if redis.call("EXISTS", KEYS[2]) == 0 then
+  redis.call("SET", KEYS[2], ARGV[4])
+end
+local seq = redis.call("INCR", KEYS[2])
+local id = redis.call("XADD", KEYS[1], "MAXLEN", "~", ARGV[1], "*",
+  "flags", ARGV[5], "sender", ARGV[6], "data", ARGV[7], "seq", seq)
+redis.call("ZADD", KEYS[3], seq, seq .. ":" .. string.len(ARGV[6]) .. ":" .. ARGV[6] .. ARGV[7])
+local excess = redis.call("ZCARD", KEYS[3]) - tonumber(ARGV[2])
+if excess > 0 then
+  redis.call("ZREMRANGEBYRANK", KEYS[3], 0, excess - 1)
+end
+redis.call("PEXPIRE", KEYS[2], ARGV[3])
+redis.call("PEXPIRE", KEYS[3], ARGV[3])
+return id
+"#;
+
+/// An update of the replay buffer, as it was broadcast to the subscribers of the collab.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayEntry {
+  pub seq: u64,
+  pub sender: CollabOrigin,
+  /// yrs::Update::encode_v1, as in [crate::model::CollabStreamUpdate].
+  pub data: Vec<u8>,
+}
+
+impl ReplayEntry {
+  /// Parses a member of the buffer, written by [APPEND_SCRIPT] as `seq:sender_len:sender` followed
+  /// by the data.
+  fn decode(member: &[u8]) -> Result<Self, StreamError> {
+    let (seq, rest) = split_field(member)?;
+    let (sender_len, rest) = split_field(rest)?;
+    let sender_len: usize = sender_len.parse()?;
+    if rest.len() < sender_len {
+      return Err(StreamError::InvalidFormat);
+    }
+    let (sender, data) = rest.split_at(sender_len);
+    Ok(Self {
+      seq: seq.parse()?,
+      sender: collab_origin_from_str(std::str::from_utf8(sender)?)?,
+      data: data.to_vec(),
+    })
+  }
+}
+
+fn split_field(value: &[u8]) -> Result<(&str, &[u8]), StreamError> {
+  let pos = value
+    .iter()
+    .position(|b| *b == b':')
+    .ok_or(StreamError::InvalidFormat)?;
+  Ok((std::str::from_utf8(&value[..pos])?, &value[pos + 1..]))
+}
+
+/// The broadcasts a client missed since the last one it received.
+#[derive(Debug, PartialEq)]
+pub enum Replay {
+  /// The missed updates, in sequence order. Empty when the client didn't miss any.
+  Updates(Vec<ReplayEntry>),
+  /// Some of the missed updates are no longer buffered, the client must sync the whole collab.
+  Unavailable,
+}
+
+/// Keeps the last updates broadcast to the subscribers of each collab, numbered by a sequence
+/// shared by all realtime servers. A client which reconnects after a short disconnection gets
+/// the updates it missed from the buffer, instead of syncing the whole collab.
+#[derive(Clone)]
+pub struct ReplayBuffer {
+  conn: ConnectionManager,
+}
+
+impl ReplayBuffer {
+  /// Number of updates kept for each collab.
+  pub const CAPACITY: usize = 1000;
+  /// The buffer of a collab which isn't updated for this long is dropped.
+  pub const TTL: Duration = Duration::from_secs(60 * 60);
+
+  pub fn new(conn: ConnectionManager) -> Self {
+    Self { conn }
+  }
+
+  pub fn buffer_key(workspace_id: &str, object_id: &str) -> String {
+    format!("af:{}:{}:replay", workspace_id, object_id)
+  }
+
+  pub fn seq_key(workspace_id: &str, object_id: &str) -> String {
+    format!("af:{}:{}:replay_seq", workspace_id, object_id)
+  }
+
+  /// Initial value of the sequence counter of a collab, see [APPEND_SCRIPT]. A thousand updates
+  /// per millisecond would be needed for a new counter to start below an expired one.
+  pub(crate) fn initial_seq() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64 * 1000
+  }
+
+  /// Returns the updates numbered after `last_seen`, the sequence number of the last update
+  /// broadcast to the client.
+  pub async fn since(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    last_seen: u64,
+  ) -> Result<Replay, StreamError> {
+    let buffer_key = Self::buffer_key(workspace_id, object_id);
+    let mut conn = self.conn.clone();
+    let (current, oldest, members): (Option<u64>, Vec<Vec<u8>>, Vec<Vec<u8>>) = redis::pipe()
+      .atomic()
+      .cmd("GET")
+      .arg(Self::seq_key(workspace_id, object_id))
+      .cmd("ZRANGE")
+      .arg(&buffer_key)
+      .arg(0)
+      .arg(0)
+      .cmd("ZRANGEBYSCORE")
+      .arg(&buffer_key)
+      .arg(format!("({}", last_seen))
+      .arg("+inf")
+      .query_async(&mut conn)
+      .await?;
+    let oldest = match oldest.first() {
+      Some(member) => Some(ReplayEntry::decode(member)?.seq),
+      None => None,
+    };
+    let entries = members
+      .iter()
+      .map(|member| ReplayEntry::decode(member))
+      .collect::<Result<Vec<_>, _>>()?;
+    Ok(replay_since(
+      last_seen,
+      current.unwrap_or_default(),
+      oldest,
+      entries,
+    ))
+  }
+}
+
+/// The updates after `last_seen`, given the current value of the counter and the sequence number
+/// of the oldest buffered update. Every number of the counter goes to an update of the buffer,
+/// so the missed updates are all buffered when the next one after `last_seen` still is.
+fn replay_since(
+  last_seen: u64,
+  current: u64,
+  oldest: Option<u64>,
+  entries: Vec<ReplayEntry>,
+) -> Replay {
+  if last_seen == current {
+    return Replay::Updates(vec![]);
+  }
+  // the client saw a number the counter didn't reach, it was reset since
+  if last_seen > current {
+    return Replay::Unavailable;
+  }
+  match oldest {
+    Some(oldest) if oldest <= last_seen + 1 => Replay::Updates(entries),
+    _ => Replay::Unavailable,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{replay_since, Replay, ReplayEntry};
+  use collab::core::origin::{CollabClient, CollabOrigin};
+
+  fn entry(seq: u64) -> ReplayEntry {
+    ReplayEntry {
+      seq,
+      sender: CollabOrigin::Client(CollabClient {
+        uid: 1,
+        device_id: "device".to_string(),
+      }),
+      data: vec![1, 2, 3],
+    }
+  }
+
+  #[test]
+  fn entry_roundtrip_test() {
+    let sender = entry(0).sender.to_string();
+    let mut member = format!("42:{}:{}", sender.len(), sender).into_bytes();
+    member.extend([1, 2, 3]);
+    assert_eq!(ReplayEntry::decode(&member).unwrap(), entry(42));
+  }
+
+  #[test]
+  fn replay_falls_back_when_trimmed_test() {
+    // nothing was missed
+    assert_eq!(
+      replay_since(10, 10, Some(5), vec![]),
+      Replay::Updates(vec![])
+    );
+    // the missed updates are still buffered
+    assert_eq!(
+      replay_since(10, 12, Some(5), vec![entry(11), entry(12)]),
+      Replay::Updates(vec![entry(11), entry(12)])
+    );
+    assert_eq!(
+      replay_since(10, 11, Some(11), vec![entry(11)]),
+      Replay::Updates(vec![entry(11)])
+    );
+    // the next update after the last seen one was trimmed
+    assert_eq!(
+      replay_since(10, 20, Some(12), vec![entry(12)]),
+      Replay::Unavailable
+    );
+    // the buffer expired, or the counter started again
+    assert_eq!(replay_since(10, 20, None, vec![]), Replay::Unavailable);
+    assert_eq!(replay_since(10, 3, Some(1), vec![]), Replay::Unavailable);
+  }
+}
@@ -1,3 +1,4 @@
+mod reset_test;
 mod stream_group_test;
 mod stream_test;
 mod test_util;
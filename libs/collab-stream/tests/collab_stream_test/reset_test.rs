@@ -0,0 +1,41 @@
+use crate::collab_stream_test::test_util::{random_i64, stream_client};
+use collab::core::origin::CollabOrigin;
+use collab_stream::model::{CollabStreamUpdate, UpdateFlags};
+
+#[tokio::test]
+async fn reset_update_stream_test() {
+  let workspace_id = "w1";
+  let oid = format!("o{}", random_i64());
+  let client = stream_client().await;
+  let sink = client.collab_update_sink(workspace_id, &oid);
+  let update = CollabStreamUpdate::new(vec![1, 2, 3], CollabOrigin::Empty, UpdateFlags::default());
+  let first = sink.send(&update).await.unwrap();
+  let late = sink.send(&update).await.unwrap();
+
+  // an update arrived after the compacted state: the stream is left as it is
+  let reset = client
+    .reset_update_stream(workspace_id, &oid, first)
+    .await
+    .unwrap();
+  assert!(reset.is_none());
+  let updates = client
+    .current_collab_updates(workspace_id, &oid, None)
+    .await
+    .unwrap();
+  assert_eq!(updates.len(), 2);
+  assert!(updates.iter().all(|(_, update)| !update.flags.is_reset()));
+
+  // once the late update is part of it, the old history is replaced by the marker
+  let reset = client
+    .reset_update_stream(workspace_id, &oid, late)
+    .await
+    .unwrap()
+    .unwrap();
+  let updates = client
+    .current_collab_updates(workspace_id, &oid, None)
+    .await
+    .unwrap();
+  assert_eq!(updates.len(), 1);
+  assert_eq!(updates[0].0, reset);
+  assert!(updates[0].1.flags.is_reset());
+}
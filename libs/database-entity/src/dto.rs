@@ -221,6 +221,8 @@ pub struct InsertSnapshotParams {
   #[validate(custom(function = "validate_not_empty_str"))]
   pub workspace_id: String,
   pub collab_type: CollabType,
+  /// The user who took the snapshot, `None` for the snapshots taken by the server.
+  pub created_by: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -323,6 +325,19 @@ pub struct AFSnapshotMeta {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AFSnapshotMetas(pub Vec<AFSnapshotMeta>);
 
+/// A snapshot listed in the version history of a collab. The size and the author are unknown
+/// for the snapshots taken before they were recorded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AFCollabVersion {
+  pub snapshot_id: i64,
+  pub object_id: String,
+  pub created_at: DateTime<Utc>,
+  /// Size in bytes of the doc state of the snapshot.
+  pub len: Option<i64>,
+  /// The user who took the snapshot, `None` for the snapshots taken by the server.
+  pub created_by: Option<i64>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct QueryObjectSnapshotParams {
   pub object_id: String,
@@ -348,6 +363,39 @@ pub enum QueryCollabResult {
 #[derive(Serialize, Deserialize)]
 pub struct BatchQueryCollabResult(pub HashMap<String, QueryCollabResult>);
 
+/// How the server handles the content of a collab.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+#[repr(i16)]
+pub enum CollabMode {
+  /// A Yrs document the server can read.
+  #[default]
+  Plain = 0,
+  /// Encrypted by the clients. The server stores and relays its updates without decoding them,
+  /// its doc state being the sequence of these updates.
+  Opaque = 1,
+}
+
+impl CollabMode {
+  pub fn is_opaque(&self) -> bool {
+    matches!(self, CollabMode::Opaque)
+  }
+}
+
+impl From<i16> for CollabMode {
+  fn from(value: i16) -> Self {
+    match value {
+      1 => CollabMode::Opaque,
+      _ => CollabMode::Plain,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UpdateCollabModeParams {
+  pub mode: CollabMode,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct WorkspaceUsage {
   pub total_document_size: i64,
@@ -458,6 +506,28 @@ pub enum AFRole {
   Guest = 3,
 }
 
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkspaceMemberSortBy {
+  #[default]
+  JoinedAt,
+  Name,
+  Email,
+  Role,
+}
+
+/// Which members of a workspace to list, and in which order.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorkspaceMemberFilter {
+  pub role: Option<AFRole>,
+  /// Only the members whose name or email contains it, ignoring the case.
+  pub search: Option<String>,
+  #[serde(default)]
+  pub sort_by: WorkspaceMemberSortBy,
+  #[serde(default)]
+  pub descending: bool,
+}
+
 impl AFRole {
   /// The user can create a [Collab] if the user is [AFRole::Owner] or [AFRole::Member] of the workspace.
   pub fn can_create_collab(&self) -> bool {
@@ -626,6 +696,7 @@ pub struct AFUserProfile {
   pub metadata: Option<serde_json::Value>,
   pub encryption_sign: Option<String>,
   pub latest_workspace_id: Uuid,
+  #[serde(with = "crate::timestamp::epoch_seconds")]
   pub updated_at: i64,
 }
 
@@ -653,6 +724,19 @@ pub struct AFWorkspaceSettings {
 
   #[serde(default)]
   pub ai_model: String,
+
+  /// When enabled, members with write access can lock a collab for editing, so that other members
+  /// can only read it until the lock is released.
+  #[serde(default)]
+  pub enable_editing_lock: bool,
+
+  #[serde(default)]
+  pub ai: AFWorkspaceAISettings,
+
+  /// The content types the files uploaded to the workspace can have, such as `application/pdf`
+  /// or `image/*`. Empty to allow the types the server allows by default.
+  #[serde(default)]
+  pub allowed_blob_content_types: Vec<String>,
 }
 
 impl Default for AFWorkspaceSettings {
@@ -660,16 +744,113 @@ impl Default for AFWorkspaceSettings {
     Self {
       disable_search_indexing: false,
       ai_model: "".to_string(),
+      enable_editing_lock: false,
+      ai: AFWorkspaceAISettings::default(),
+      allowed_blob_content_types: vec![],
+    }
+  }
+}
+
+/// How the AI assistant answers the members of a workspace.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct AFWorkspaceAISettings {
+  /// Guidelines added to the system prompt of every AI request of the workspace, such as the brand
+  /// voice of the team. They never take precedence over the safety instructions of the server.
+  #[serde(default)]
+  pub system_prompt: String,
+  /// Set by the server when the system prompt looks like it tries to override the instructions
+  /// of the assistant. The prompt is saved anyway, the warnings are meant to be shown next to it.
+  #[serde(default)]
+  pub system_prompt_warnings: Vec<String>,
+  #[serde(default)]
+  pub tone: AIResponseTone,
+  #[serde(default)]
+  pub length: AIResponseLength,
+  #[serde(default)]
+  pub features: AIFeatureToggles,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AIResponseTone {
+  #[default]
+  Default,
+  Professional,
+  Friendly,
+  Casual,
+  Formal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AIResponseLength {
+  #[default]
+  Default,
+  Concise,
+  Detailed,
+}
+
+/// The AI features of a workspace, which the clients hide when they're turned off.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AIFeature {
+  Chat,
+  Completion,
+  SummarizeRow,
+  TranslateRow,
+}
+
+/// Which AI features are enabled in a workspace. Every feature is enabled unless turned off.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AIFeatureToggles {
+  #[serde(default = "default_true")]
+  pub chat: bool,
+  #[serde(default = "default_true")]
+  pub completion: bool,
+  #[serde(default = "default_true")]
+  pub summarize_row: bool,
+  #[serde(default = "default_true")]
+  pub translate_row: bool,
+}
+
+impl AIFeatureToggles {
+  pub fn is_enabled(&self, feature: AIFeature) -> bool {
+    match feature {
+      AIFeature::Chat => self.chat,
+      AIFeature::Completion => self.completion,
+      AIFeature::SummarizeRow => self.summarize_row,
+      AIFeature::TranslateRow => self.translate_row,
     }
   }
 }
 
+impl Default for AIFeatureToggles {
+  fn default() -> Self {
+    Self {
+      chat: true,
+      completion: true,
+      summarize_row: true,
+      translate_row: true,
+    }
+  }
+}
+
+fn default_true() -> bool {
+  true
+}
+
 #[derive(Default, Serialize, Deserialize, Debug)]
 pub struct AFWorkspaceSettingsChange {
   #[serde(skip_serializing_if = "Option::is_none")]
   pub disable_search_indexing: Option<bool>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub ai_model: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub enable_editing_lock: Option<bool>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub ai: Option<AFWorkspaceAISettings>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub allowed_blob_content_types: Option<Vec<String>>,
 }
 
 impl AFWorkspaceSettingsChange {
@@ -677,6 +858,9 @@ impl AFWorkspaceSettingsChange {
     Self {
       disable_search_indexing: None,
       ai_model: None,
+      enable_editing_lock: None,
+      ai: None,
+      allowed_blob_content_types: None,
     }
   }
   pub fn disable_search_indexing(mut self, disable_search_indexing: bool) -> Self {
@@ -687,6 +871,18 @@ impl AFWorkspaceSettingsChange {
     self.ai_model = Some(ai_model);
     self
   }
+  pub fn enable_editing_lock(mut self, enable_editing_lock: bool) -> Self {
+    self.enable_editing_lock = Some(enable_editing_lock);
+    self
+  }
+  pub fn ai(mut self, ai: AFWorkspaceAISettings) -> Self {
+    self.ai = Some(ai);
+    self
+  }
+  pub fn allowed_blob_content_types(mut self, allowed_blob_content_types: Vec<String>) -> Self {
+    self.allowed_blob_content_types = Some(allowed_blob_content_types);
+    self
+  }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -716,6 +912,30 @@ pub struct AFWorkspaceInvitation {
   pub inviter_icon: Option<String>,
   pub workspace_icon: String,
   pub member_count: Option<i64>, // use unwrap_or(0) to get the value
+  /// The invitation can no longer be accepted past this time.
+  #[serde(default, with = "crate::timestamp::option_rfc3339")]
+  pub expires_at: Option<DateTime<Utc>>,
+  /// Seconds left before the invitation expires, 0 once it expired.
+  #[serde(default)]
+  pub expires_in_secs: Option<i64>,
+}
+
+/// A pending invitation of a workspace, as listed to its owners.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AFWorkspacePendingInvitation {
+  pub invite_id: Uuid,
+  pub invitee_email: String,
+  pub role: AFRole,
+  pub inviter_email: Option<String>,
+  #[serde(with = "crate::timestamp::rfc3339")]
+  pub created_at: DateTime<Utc>,
+  #[serde(with = "crate::timestamp::rfc3339")]
+  pub expires_at: DateTime<Utc>,
+  /// Seconds left before the invitation expires, 0 once it expired.
+  pub expires_in_secs: i64,
+  /// When the invitation email was last queued by extending the invitation.
+  #[serde(default, with = "crate::timestamp::option_rfc3339")]
+  pub email_queued_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone)]
@@ -1157,6 +1377,16 @@ pub struct CreateImportTask {
   #[validate(custom(function = "validate_not_empty_str"))]
   pub workspace_name: String,
   pub content_length: u64,
+  /// The app the file was exported from. Detected from the content of the file when not set.
+  #[serde(default)]
+  pub source: Option<ImportSource>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportSource {
+  Notion,
+  Obsidian,
 }
 
 /// Create a import task
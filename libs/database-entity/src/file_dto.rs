@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
@@ -53,6 +54,22 @@ pub struct UploadPartResponse {
   pub part_num: i32,
 }
 
+/// The parts uploaded so far for a multipart upload, by part number, for a client to resume an
+/// interrupted upload from.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListUploadPartsResponse {
+  pub file_id: String,
+  pub upload_id: String,
+  pub parts: Vec<UploadedPart>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UploadedPart {
+  pub part_number: i32,
+  pub e_tag: String,
+  pub size: u64,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct CompleteUploadRequest {
   pub file_id: String,
@@ -85,3 +102,67 @@ pub struct CompleteUploadResponse {
   pub upload_id: String,
   pub parts: Vec<CompletedPartRequest>,
 }
+
+/// Request a url to upload a blob straight to the bucket, for the files too large to go through
+/// the server. The upload must send the given content type and length.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PresignedUploadRequest {
+  pub file_id: String,
+  pub parent_dir: String,
+  pub content_type: String,
+  pub content_length: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PresignedUploadResponse {
+  pub file_id: String,
+  /// The url to upload the content to with a PUT request
+  pub presigned_url: String,
+  /// Unix timestamp, in seconds, after which the url can no longer be used
+  pub expires_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CompletePresignedUploadRequest {
+  pub file_id: String,
+  pub parent_dir: String,
+}
+
+/// A downscaled rendition of an image blob, requested with the `size` query param of the blob
+/// GET endpoints. The blob itself is returned while its variant isn't generated.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum BlobVariant {
+  Thumb,
+  Medium,
+}
+
+impl BlobVariant {
+  pub const ALL: [BlobVariant; 2] = [BlobVariant::Thumb, BlobVariant::Medium];
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      BlobVariant::Thumb => "thumb",
+      BlobVariant::Medium => "medium",
+    }
+  }
+
+  /// Bound of the longest side of the variant, in pixels.
+  pub fn max_dimension(&self) -> u32 {
+    match self {
+      BlobVariant::Thumb => 256,
+      BlobVariant::Medium => 1024,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct BlobVariantQuery {
+  pub size: Option<BlobVariant>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PutBlobQuery {
+  /// The blob is deleted after this time, for the temporary files such as the generated exports.
+  pub expires_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,311 @@
+//! Serde helpers for the timestamps of the API. Responses carry RFC3339 timestamps in UTC, and
+//! requests may use either RFC3339 or the number of seconds since the epoch.
+//!
+//! Legacy clients expect the timestamps that used to be serialized as epoch seconds to stay
+//! numbers. The server serializes its responses to them within [with_legacy_format].
+//!
+//! Formats that aren't human readable, such as bincode, keep the epoch seconds as numbers.
+use std::cell::Cell;
+
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use serde::de::{self, Visitor};
+use serde::{Deserializer, Serializer};
+
+/// Requests sending this header with a version of at least [RFC3339_API_VERSION] get RFC3339
+/// timestamps only.
+pub const X_API_VERSION: &str = "x-api-version";
+pub const RFC3339_API_VERSION: u32 = 2;
+
+thread_local! {
+  static LEGACY_FORMAT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f` with the timestamps serialized in the legacy format or not.
+pub fn with_legacy_format<R>(legacy: bool, f: impl FnOnce() -> R) -> R {
+  struct Restore(bool);
+  impl Drop for Restore {
+    fn drop(&mut self) {
+      LEGACY_FORMAT.with(|cell| cell.set(self.0));
+    }
+  }
+  let _restore = Restore(LEGACY_FORMAT.with(|cell| cell.replace(legacy)));
+  f()
+}
+
+pub fn is_legacy_format() -> bool {
+  LEGACY_FORMAT.with(|cell| cell.get())
+}
+
+pub fn to_rfc3339(timestamp: &DateTime<Utc>) -> String {
+  timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// Parses an RFC3339 timestamp with any offset, a datetime without offset taken as UTC, or a
+/// number of seconds since the epoch.
+pub fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+  let value = value.trim();
+  if let Ok(seconds) = value.parse::<i64>() {
+    return Utc.timestamp_opt(seconds, 0).single();
+  }
+  if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+    return Some(timestamp.with_timezone(&Utc));
+  }
+  ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    .map(|naive| naive.and_utc())
+}
+
+struct TimestampVisitor;
+
+impl Visitor<'_> for TimestampVisitor {
+  type Value = DateTime<Utc>;
+
+  fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    formatter.write_str("an RFC3339 timestamp or a number of seconds since the epoch")
+  }
+
+  fn visit_i64<E: de::Error>(self, seconds: i64) -> Result<Self::Value, E> {
+    Utc
+      .timestamp_opt(seconds, 0)
+      .single()
+      .ok_or_else(|| E::custom(format!("timestamp {} is out of range", seconds)))
+  }
+
+  fn visit_u64<E: de::Error>(self, seconds: u64) -> Result<Self::Value, E> {
+    let seconds = i64::try_from(seconds)
+      .map_err(|_| E::custom(format!("timestamp {} is out of range", seconds)))?;
+    self.visit_i64(seconds)
+  }
+
+  fn visit_f64<E: de::Error>(self, seconds: f64) -> Result<Self::Value, E> {
+    self.visit_i64(seconds.trunc() as i64)
+  }
+
+  fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+    parse_timestamp(value).ok_or_else(|| E::custom(format!("invalid timestamp: {}", value)))
+  }
+}
+
+/// A [DateTime] field, serialized as RFC3339 in UTC in every format.
+pub mod rfc3339 {
+  use super::*;
+
+  pub fn serialize<S: Serializer>(
+    timestamp: &DateTime<Utc>,
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&to_rfc3339(timestamp))
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<DateTime<Utc>, D::Error> {
+    if deserializer.is_human_readable() {
+      deserializer.deserialize_any(TimestampVisitor)
+    } else {
+      deserializer.deserialize_str(TimestampVisitor)
+    }
+  }
+}
+
+pub mod option_rfc3339 {
+  use super::*;
+  use serde::{Deserialize, Serialize};
+
+  pub fn serialize<S: Serializer>(
+    timestamp: &Option<DateTime<Utc>>,
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    timestamp.as_ref().map(to_rfc3339).serialize(serializer)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Option<DateTime<Utc>>, D::Error> {
+    #[derive(Deserialize)]
+    struct Wrapper(#[serde(deserialize_with = "super::rfc3339::deserialize")] DateTime<Utc>);
+    Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(timestamp)| timestamp))
+  }
+}
+
+/// An `i64` field holding seconds since the epoch. It's serialized as RFC3339 in UTC, or kept a
+/// number for the legacy clients.
+pub mod epoch_seconds {
+  use super::*;
+
+  pub fn serialize<S: Serializer>(seconds: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+    if !serializer.is_human_readable() || is_legacy_format() {
+      return serializer.serialize_i64(*seconds);
+    }
+    match Utc.timestamp_opt(*seconds, 0).single() {
+      Some(timestamp) => serializer.serialize_str(&to_rfc3339(&timestamp)),
+      None => serializer.serialize_i64(*seconds),
+    }
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    let timestamp = if deserializer.is_human_readable() {
+      deserializer.deserialize_any(TimestampVisitor)?
+    } else {
+      deserializer.deserialize_i64(TimestampVisitor)?
+    };
+    Ok(timestamp.timestamp())
+  }
+}
+
+pub mod option_epoch_seconds {
+  use super::*;
+  use serde::{Deserialize, Serialize};
+
+  #[derive(Serialize, Deserialize)]
+  struct Wrapper(#[serde(with = "super::epoch_seconds")] i64);
+
+  pub fn serialize<S: Serializer>(seconds: &Option<i64>, serializer: S) -> Result<S::Ok, S::Error> {
+    seconds.map(Wrapper).serialize(serializer)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<i64>, D::Error> {
+    Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(seconds)| seconds))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde::{Deserialize, Serialize};
+  use serde_json::json;
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  struct Timestamps {
+    #[serde(with = "rfc3339")]
+    at: DateTime<Utc>,
+    #[serde(with = "option_rfc3339")]
+    maybe_at: Option<DateTime<Utc>>,
+    #[serde(with = "epoch_seconds")]
+    epoch: i64,
+    #[serde(with = "option_epoch_seconds")]
+    maybe_epoch: Option<i64>,
+  }
+
+  fn timestamps() -> Timestamps {
+    Timestamps {
+      at: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+      maybe_at: None,
+      epoch: 1_700_000_000,
+      maybe_epoch: Some(1_700_000_060),
+    }
+  }
+
+  #[test]
+  fn timestamps_serialize_as_rfc3339_test() {
+    let value = serde_json::to_value(timestamps()).unwrap();
+    assert_eq!(
+      value,
+      json!({
+        "at": "2023-11-14T22:13:20Z",
+        "maybe_at": null,
+        "epoch": "2023-11-14T22:13:20Z",
+        "maybe_epoch": "2023-11-14T22:14:20Z",
+      })
+    );
+    let round_trip: Timestamps = serde_json::from_value(value).unwrap();
+    assert_eq!(round_trip, timestamps());
+  }
+
+  #[test]
+  fn legacy_format_keeps_epoch_seconds_test() {
+    let value = with_legacy_format(true, || serde_json::to_value(timestamps()).unwrap());
+    assert_eq!(value["epoch"], json!(1_700_000_000));
+    assert_eq!(value["maybe_epoch"], json!(1_700_000_060));
+    assert_eq!(value["at"], json!("2023-11-14T22:13:20Z"));
+    assert!(!is_legacy_format());
+
+    let round_trip: Timestamps = serde_json::from_value(value).unwrap();
+    assert_eq!(round_trip, timestamps());
+  }
+
+  #[test]
+  fn timestamps_accept_every_input_format_test() {
+    let expected = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+    for input in [
+      json!(1_700_000_000),
+      json!("1700000000"),
+      json!("2023-11-14T22:13:20Z"),
+      json!("2023-11-15T00:13:20+02:00"),
+      json!("2023-11-14T22:13:20"),
+      json!("2023-11-14 22:13:20.000"),
+    ] {
+      let value = json!({ "at": input, "maybe_at": input, "epoch": input, "maybe_epoch": input });
+      let parsed: Timestamps = serde_json::from_value(value).unwrap();
+      assert_eq!(parsed.at, expected, "{}", input);
+      assert_eq!(parsed.maybe_at, Some(expected));
+      assert_eq!(parsed.epoch, expected.timestamp());
+      assert_eq!(parsed.maybe_epoch, Some(expected.timestamp()));
+    }
+    assert!(serde_json::from_value::<Timestamps>(json!({
+      "at": "yesterday", "maybe_at": null, "epoch": 0, "maybe_epoch": null
+    }))
+    .is_err());
+  }
+
+  #[test]
+  fn binary_formats_keep_epoch_seconds_test() {
+    let bytes = bincode::serialize(&timestamps()).unwrap();
+    let round_trip: Timestamps = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(round_trip, timestamps());
+  }
+
+  /// The timestamps of the API types carry their timezone. The ones kept as epoch seconds must be
+  /// serialized with [epoch_seconds]. The published view info is written by the clients and stored
+  /// as is, so it keeps its numbers.
+  #[test]
+  fn api_types_use_consistent_timestamps_test() {
+    const ALLOWED: [&str; 2] = [
+      "publish_dto.rs: pub last_edited_time: i64,",
+      "publish_dto.rs: pub created_at: i64,",
+    ];
+    let manifest_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dirs = [
+      manifest_dir.join("src"),
+      manifest_dir.join("../shared-entity/src/dto"),
+    ];
+    let mut violations = vec![];
+    for dir in dirs {
+      for entry in std::fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().map_or(true, |ext| ext != "rs") || path.ends_with("timestamp.rs") {
+          continue;
+        }
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        let source = std::fs::read_to_string(&path).unwrap();
+        let lines = source.lines().map(str::trim).collect::<Vec<_>>();
+        for (i, line) in lines.iter().enumerate() {
+          let (name, ty) = match line
+            .strip_prefix("pub ")
+            .and_then(|rest| rest.split_once(": "))
+          {
+            Some((name, ty)) if !name.contains(' ') => (name, ty.trim_end_matches(',')),
+            _ => continue,
+          };
+          let finding = format!("{}: {}", file_name, line);
+          if ty.contains("NaiveDateTime") {
+            violations.push(finding);
+            continue;
+          }
+          let is_epoch = matches!(ty, "i64" | "Option<i64>")
+            && (name.ends_with("_at") || name.ends_with("_time") || name.ends_with("_end"));
+          let has_serde_with = i > 0 && lines[i - 1].contains("timestamp::");
+          if is_epoch && !has_serde_with && !ALLOWED.contains(&finding.as_str()) {
+            violations.push(finding);
+          }
+        }
+      }
+    }
+    assert!(
+      violations.is_empty(),
+      "timestamps without a timezone or serialized as numbers: {:#?}",
+      violations
+    );
+  }
+}
@@ -0,0 +1,14 @@
+use app_error::AppError;
+use sqlx::{Executor, Postgres};
+
+/// The current version of the access control policies, bumped by each change of the workspace
+/// members.
+pub async fn select_access_policy_version<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+) -> Result<i64, AppError> {
+  let version =
+    sqlx::query_scalar::<_, i64>(r#"SELECT version FROM af_access_policy_version WHERE id"#)
+      .fetch_optional(executor)
+      .await?;
+  Ok(version.unwrap_or(0))
+}
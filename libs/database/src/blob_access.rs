@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use app_error::AppError;
+use chrono::{DateTime, Utc};
+use redis::aio::ConnectionManager;
+use redis::{pipe, AsyncCommands, RedisResult};
+use sqlx::{Executor, PgPool, Postgres};
+use tracing::{instrument, warn};
+use uuid::Uuid;
+
+use crate::pg_row::AFBlobMetadataRow;
+
+/// The downloads of the blobs since the last flush, the field `<workspace_id>/<file_id>` holding
+/// the epoch seconds of the last download.
+const BLOB_ACCESS_BUFFER_KEY: &str = "af:blob_access";
+/// The buffer is renamed to a key of its own before being flushed, so that the downloads
+/// recorded meanwhile go to a new buffer. It expires in case the flush never completes.
+const BLOB_ACCESS_FLUSHING_KEY_TTL_SECS: i64 = 3600;
+/// Number of blobs updated per statement when flushing the buffer.
+const BLOB_ACCESS_FLUSH_BATCH_SIZE: usize = 1000;
+
+/// Renames the buffer when it exists. Returns whether it was renamed.
+const TAKE_BUFFER_SCRIPT: &str = r#"
+if redis.call('EXISTS', KEYS[1]) == 0 then
+  return 0
+end
+redis.call('RENAME', KEYS[1], KEYS[2])
+redis.call('EXPIRE', KEYS[2], ARGV[1])
+return 1
+"#;
+
+fn blob_access_field(workspace_id: &Uuid, file_id: &str) -> String {
+  format!("{}/{}", workspace_id, file_id)
+}
+
+fn parse_blob_access_field(field: &str) -> Option<(Uuid, String)> {
+  let (workspace_id, file_id) = field.split_once('/')?;
+  let workspace_id = Uuid::parse_str(workspace_id).ok()?;
+  Some((workspace_id, file_id.to_string()))
+}
+
+/// Records the download of a blob in the Redis buffer, which is written to its `last_accessed_at`
+/// by [flush_blob_access]. The download isn't recorded when Redis can't be reached.
+pub async fn record_blob_access(redis: &ConnectionManager, workspace_id: &Uuid, file_id: &str) {
+  let result: RedisResult<()> = redis
+    .clone()
+    .hset(
+      BLOB_ACCESS_BUFFER_KEY,
+      blob_access_field(workspace_id, file_id),
+      Utc::now().timestamp(),
+    )
+    .await;
+  if let Err(err) = result {
+    warn!(
+      "failed to record the access of blob {}/{}: {}",
+      workspace_id, file_id, err
+    );
+  }
+}
+
+/// Writes the downloads recorded in the Redis buffer to the `last_accessed_at` of their blobs,
+/// and returns the number of blobs downloaded since the last flush. Several instances can flush
+/// the buffer at the same time, each download is only taken by one of them. The downloads which
+/// couldn't be written are put back in the buffer, unless the blob was downloaded again since.
+pub async fn flush_blob_access(
+  redis: &ConnectionManager,
+  pg_pool: &PgPool,
+) -> Result<usize, AppError> {
+  let mut conn = redis.clone();
+  let flushing_key = format!("{}:flushing:{}", BLOB_ACCESS_BUFFER_KEY, Uuid::new_v4());
+  let taken: i64 = redis::Script::new(TAKE_BUFFER_SCRIPT)
+    .key(BLOB_ACCESS_BUFFER_KEY)
+    .key(&flushing_key)
+    .arg(BLOB_ACCESS_FLUSHING_KEY_TTL_SECS)
+    .invoke_async(&mut conn)
+    .await
+    .map_err(|err| AppError::Internal(err.into()))?;
+  if taken == 0 {
+    return Ok(0);
+  }
+  let accesses: HashMap<String, i64> = conn
+    .hgetall(&flushing_key)
+    .await
+    .map_err(|err| AppError::Internal(err.into()))?;
+
+  let mut workspace_ids = Vec::with_capacity(accesses.len());
+  let mut file_ids = Vec::with_capacity(accesses.len());
+  let mut accessed_at = Vec::with_capacity(accesses.len());
+  for (field, timestamp) in &accesses {
+    let (Some((workspace_id, file_id)), Some(timestamp)) = (
+      parse_blob_access_field(field),
+      DateTime::from_timestamp(*timestamp, 0),
+    ) else {
+      continue;
+    };
+    workspace_ids.push(workspace_id);
+    file_ids.push(file_id);
+    accessed_at.push(timestamp);
+  }
+
+  let mut result = Ok(());
+  for start in (0..workspace_ids.len()).step_by(BLOB_ACCESS_FLUSH_BATCH_SIZE) {
+    let end = (start + BLOB_ACCESS_FLUSH_BATCH_SIZE).min(workspace_ids.len());
+    result = update_blob_last_accessed_at(
+      pg_pool,
+      &workspace_ids[start..end],
+      &file_ids[start..end],
+      &accessed_at[start..end],
+    )
+    .await
+    .map(|_| ());
+    if result.is_err() {
+      break;
+    }
+  }
+
+  if result.is_err() {
+    // an update only moves `last_accessed_at` forward, so the batches written already can be
+    // written again with the next flush
+    let mut pipeline = pipe();
+    for (field, timestamp) in &accesses {
+      pipeline
+        .hset_nx(BLOB_ACCESS_BUFFER_KEY, field, *timestamp)
+        .ignore();
+    }
+    let restored: RedisResult<()> = pipeline.query_async(&mut conn).await;
+    if let Err(err) = restored {
+      warn!("failed to restore the blob accesses to flush: {}", err);
+    }
+  }
+  let deleted: RedisResult<()> = conn.del(&flushing_key).await;
+  if let Err(err) = deleted {
+    // the key is left to expire
+    warn!("failed to delete the flushed blob accesses: {}", err);
+  }
+  result?;
+  Ok(accesses.len())
+}
+
+/// Moves the `last_accessed_at` of the given blobs forward to the given times. The blobs which
+/// were deleted are ignored.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn update_blob_last_accessed_at<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_ids: &[Uuid],
+  file_ids: &[String],
+  accessed_at: &[DateTime<Utc>],
+) -> Result<u64, AppError> {
+  let result = sqlx::query(
+    r#"
+      UPDATE af_blob_metadata AS m
+      SET last_accessed_at = a.accessed_at
+      FROM UNNEST($1::UUID[], $2::TEXT[], $3::TIMESTAMPTZ[])
+        AS a(workspace_id, file_id, accessed_at)
+      WHERE m.workspace_id = a.workspace_id
+        AND m.file_id = a.file_id
+        AND m.last_accessed_at < a.accessed_at
+    "#,
+  )
+  .bind(workspace_ids)
+  .bind(file_ids)
+  .bind(accessed_at)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected())
+}
+
+/// The blobs of the workspace still in the default storage class which weren't downloaded since
+/// `older_than`, least recently downloaded first. A blob sharing its object with a blob
+/// downloaded since then isn't returned.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn get_cold_blobs(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  older_than: DateTime<Utc>,
+) -> Result<Vec<AFBlobMetadataRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFBlobMetadataRow>(
+    r#"
+      SELECT m.* FROM af_blob_metadata AS m
+      WHERE m.workspace_id = $1
+        AND m.storage_class IS NULL
+        AND m.last_accessed_at < $2
+        AND (m.object_key IS NULL OR NOT EXISTS (
+          SELECT 1 FROM af_blob_metadata AS other
+          WHERE other.workspace_id = m.workspace_id
+            AND other.object_key = m.object_key
+            AND other.last_accessed_at >= $2
+        ))
+      ORDER BY m.last_accessed_at, m.file_id
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(older_than)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(rows)
+}
+
+/// Records the storage class the object of a blob was transitioned to, on every blob sharing the
+/// object. Returns the number of blobs updated.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn update_blob_storage_class<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  file_id: &str,
+  object_key: Option<&str>,
+  storage_class: &str,
+) -> Result<u64, AppError> {
+  let result = sqlx::query(
+    r#"
+      UPDATE af_blob_metadata
+      SET storage_class = $4, storage_class_changed_at = CURRENT_TIMESTAMP
+      WHERE workspace_id = $1
+        AND (file_id = $2 OR ($3::TEXT IS NOT NULL AND object_key = $3))
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .bind(object_key)
+  .bind(storage_class)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected())
+}
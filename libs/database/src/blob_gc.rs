@@ -0,0 +1,95 @@
+use app_error::AppError;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Progress of the collector of orphaned blobs, see af_blob_gc_cursor.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct BlobGcCursor {
+  pub last_workspace_id: Option<Uuid>,
+  pub pass_finished_at: Option<DateTime<Utc>>,
+}
+
+/// Lock the cursor of the collector of orphaned blobs for the rest of the transaction. Returns
+/// `None` when another worker holds the lock.
+pub async fn select_blob_gc_cursor_for_update<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+) -> Result<Option<BlobGcCursor>, AppError> {
+  let cursor = sqlx::query_as::<_, BlobGcCursor>(
+    r#"
+      SELECT last_workspace_id, pass_finished_at FROM af_blob_gc_cursor
+      FOR UPDATE SKIP LOCKED
+    "#,
+  )
+  .fetch_optional(executor)
+  .await?;
+  Ok(cursor)
+}
+
+/// Move the cursor after the given workspace. A `None` workspace ends the current pass.
+pub async fn update_blob_gc_cursor<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  last_workspace_id: Option<&Uuid>,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_blob_gc_cursor
+      SET last_workspace_id = $1,
+        pass_finished_at = CASE WHEN $1::UUID IS NULL THEN NOW() ELSE pass_finished_at END,
+        updated_at = NOW()
+    "#,
+  )
+  .bind(last_workspace_id)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+/// Return up to `limit` workspace ids following `after`, in order.
+pub async fn select_workspace_ids_after<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  after: Option<&Uuid>,
+  limit: i64,
+) -> Result<Vec<Uuid>, AppError> {
+  let workspace_ids: Vec<(Uuid,)> = sqlx::query_as(
+    r#"
+      SELECT workspace_id FROM af_workspace
+      WHERE $1::UUID IS NULL OR workspace_id > $1
+      ORDER BY workspace_id
+      LIMIT $2
+    "#,
+  )
+  .bind(after)
+  .bind(limit)
+  .fetch_all(executor)
+  .await?;
+  Ok(workspace_ids.into_iter().map(|(id,)| id).collect())
+}
+
+/// Return the given object ids which still exist in the workspace: a collab, even in the trash
+/// since it's purged along with its blobs, or a chat that isn't deleted, or that was deleted after
+/// `deleted_after`, so it can still be restored along with its blobs.
+pub async fn select_blob_referencing_object_ids<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  object_ids: &[String],
+  deleted_after: DateTime<Utc>,
+) -> Result<HashSet<String>, AppError> {
+  let object_ids: Vec<(String,)> = sqlx::query_as(
+    r#"
+      SELECT oid FROM af_collab
+      WHERE workspace_id = $1 AND oid = ANY($2)
+      UNION
+      SELECT chat_id::TEXT FROM af_chat
+      WHERE workspace_id = $1 AND chat_id::TEXT = ANY($2)
+        AND (deleted_at IS NULL OR deleted_at > $3)
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(object_ids)
+  .bind(deleted_after)
+  .fetch_all(executor)
+  .await?;
+  Ok(object_ids.into_iter().map(|(id,)| id).collect())
+}
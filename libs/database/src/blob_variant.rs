@@ -0,0 +1,174 @@
+use std::ops::DerefMut;
+
+use app_error::AppError;
+use chrono::{DateTime, Utc};
+use database_entity::file_dto::BlobVariant;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::pg_row::{AFBlobVariantRow, AFBlobVariantTaskRow};
+
+/// The object key of a variant of the blob stored under `object_key`.
+pub fn blob_variant_object_key(object_key: &str, variant: BlobVariant) -> String {
+  format!("{}@{}", object_key, variant.as_str())
+}
+
+/// The variant of the blob, unless it wasn't generated yet or the blob was uploaded again since.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_blob_variant<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  file_id: &str,
+  variant: BlobVariant,
+) -> Result<Option<AFBlobVariantRow>, AppError> {
+  let row = sqlx::query_as::<_, AFBlobVariantRow>(
+    r#"
+      SELECT workspace_id, file_id, variant, object_key, file_type, file_size, stale, created_at
+      FROM af_blob_variant
+      WHERE workspace_id = $1 AND file_id = $2 AND variant = $3 AND NOT stale
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .bind(variant.as_str())
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Record a generated variant of the blob, replacing the previous one.
+#[instrument(level = "trace", skip_all, err)]
+#[allow(clippy::too_many_arguments)]
+pub async fn upsert_blob_variant(
+  tx: &mut Transaction<'_, Postgres>,
+  workspace_id: &Uuid,
+  file_id: &str,
+  variant: BlobVariant,
+  object_key: &str,
+  file_type: &str,
+  file_size: i64,
+  stale: bool,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      INSERT INTO af_blob_variant
+        (workspace_id, file_id, variant, object_key, file_type, file_size, stale)
+      VALUES ($1, $2, $3, $4, $5, $6, $7)
+      ON CONFLICT (workspace_id, file_id, variant) DO UPDATE SET
+        object_key = $4,
+        file_type = $5,
+        file_size = $6,
+        stale = $7,
+        created_at = CURRENT_TIMESTAMP
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .bind(variant.as_str())
+  .bind(object_key)
+  .bind(file_type)
+  .bind(file_size)
+  .bind(stale)
+  .execute(tx.deref_mut())
+  .await?;
+  Ok(())
+}
+
+/// Delete the variants of the given blobs. Returns the object keys of the deleted variants, so
+/// that the caller can delete them from the bucket once the transaction is committed.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn delete_blob_variants_of_files(
+  tx: &mut Transaction<'_, Postgres>,
+  workspace_id: &Uuid,
+  file_ids: &[String],
+) -> Result<Vec<String>, AppError> {
+  let object_keys: Vec<(String,)> = sqlx::query_as(
+    r#"
+      DELETE FROM af_blob_variant
+      WHERE workspace_id = $1 AND file_id = ANY($2)
+      RETURNING object_key
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_ids)
+  .fetch_all(tx.deref_mut())
+  .await?;
+  Ok(object_keys.into_iter().map(|(key,)| key).collect())
+}
+
+/// Return the object keys of all variants of the blobs of the workspace, stale ones included:
+/// they are replaced in place when regenerated.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_workspace_blob_variant_keys(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+) -> Result<Vec<String>, AppError> {
+  let object_keys: Vec<(String,)> = sqlx::query_as(
+    r#"
+      SELECT object_key FROM af_blob_variant
+      WHERE workspace_id = $1
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(object_keys.into_iter().map(|(key,)| key).collect())
+}
+
+/// Claim up to `limit` tasks which are due, oldest first. A claimed task isn't handed out again
+/// for `lease_secs`, after which it's retried if it wasn't completed meanwhile. The tasks locked
+/// by other workers are skipped.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn claim_blob_variant_tasks(
+  pg_pool: &PgPool,
+  limit: i64,
+  lease_secs: i64,
+) -> Result<Vec<AFBlobVariantTaskRow>, AppError> {
+  let tasks = sqlx::query_as::<_, AFBlobVariantTaskRow>(
+    r#"
+      WITH due AS (
+        SELECT workspace_id, file_id
+        FROM af_blob_variant_task
+        WHERE next_attempt_at <= CURRENT_TIMESTAMP
+        ORDER BY next_attempt_at
+        LIMIT $1
+        FOR UPDATE SKIP LOCKED
+      )
+      UPDATE af_blob_variant_task AS t
+      SET attempts = t.attempts + 1,
+          next_attempt_at = CURRENT_TIMESTAMP + make_interval(secs => $2)
+      FROM due
+      WHERE t.workspace_id = due.workspace_id AND t.file_id = due.file_id
+      RETURNING t.workspace_id, t.file_id, t.attempts, t.enqueued_at
+    "#,
+  )
+  .bind(limit)
+  .bind(lease_secs as f64)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(tasks)
+}
+
+/// Delete the task unless the blob was uploaded again since it was claimed. Returns whether it
+/// was deleted: when it wasn't, the variants generated from the previous upload are stale.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn complete_blob_variant_task<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  file_id: &str,
+  enqueued_at: DateTime<Utc>,
+) -> Result<bool, AppError> {
+  let result = sqlx::query(
+    r#"
+      DELETE FROM af_blob_variant_task
+      WHERE workspace_id = $1 AND file_id = $2 AND enqueued_at = $3
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .bind(enqueued_at)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,224 @@
+use app_error::AppError;
+use sqlx::{Executor, Postgres, Transaction};
+use std::ops::DerefMut;
+use uuid::Uuid;
+
+use crate::pg_row::{AFChatAccessRow, AFSharedChatRow, AFSharedChatSearchRow};
+
+pub async fn update_chat_owner<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  chat_id: &Uuid,
+  owner_uid: i64,
+) -> Result<(), AppError> {
+  sqlx::query(r#"UPDATE af_chat SET owner_uid = $2 WHERE chat_id = $1"#)
+    .bind(chat_id)
+    .bind(owner_uid)
+    .execute(executor)
+    .await?;
+  Ok(())
+}
+
+/// Returns the sharing of the chat, along with whether the user is a member of its workspace and
+/// one of the members it's shared with.
+pub async fn select_chat_access<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  chat_id: &Uuid,
+  uid: i64,
+) -> Result<Option<AFChatAccessRow>, AppError> {
+  let row = sqlx::query_as::<_, AFChatAccessRow>(
+    r#"
+      SELECT
+        c.workspace_id,
+        c.owner_uid,
+        c.share_scope,
+        c.collaborative,
+        EXISTS (
+          SELECT 1 FROM af_workspace_member m
+          WHERE m.workspace_id = c.workspace_id AND m.uid = $2
+        ) AS is_workspace_member,
+        EXISTS (
+          SELECT 1 FROM af_chat_share_member s
+          WHERE s.chat_id = c.chat_id AND s.uid = $2
+        ) AS is_share_member
+      FROM af_chat c
+      WHERE c.chat_id = $1 AND c.deleted_at IS NULL
+    "#,
+  )
+  .bind(chat_id)
+  .bind(uid)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Replaces the sharing of the chat. `member_emails` are resolved among the members of the
+/// workspace of the chat, the number of members the chat is shared with is returned.
+pub async fn update_chat_share(
+  txn: &mut Transaction<'_, Postgres>,
+  chat_id: &Uuid,
+  share_scope: i16,
+  collaborative: bool,
+  member_emails: &[String],
+) -> Result<u64, AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_chat
+      SET share_scope = $2,
+          collaborative = $3,
+          shared_at = CASE WHEN $2 = 0 THEN NULL ELSE COALESCE(shared_at, NOW()) END
+      WHERE chat_id = $1
+    "#,
+  )
+  .bind(chat_id)
+  .bind(share_scope)
+  .bind(collaborative)
+  .execute(txn.deref_mut())
+  .await?;
+
+  sqlx::query(r#"DELETE FROM af_chat_share_member WHERE chat_id = $1"#)
+    .bind(chat_id)
+    .execute(txn.deref_mut())
+    .await?;
+  if member_emails.is_empty() {
+    return Ok(0);
+  }
+
+  let member_emails = member_emails
+    .iter()
+    .map(|email| email.to_lowercase())
+    .collect::<Vec<_>>();
+  let result = sqlx::query(
+    r#"
+      INSERT INTO af_chat_share_member (chat_id, uid)
+      SELECT c.chat_id, u.uid
+      FROM af_chat c
+      JOIN af_workspace_member m ON m.workspace_id = c.workspace_id
+      JOIN af_user u ON u.uid = m.uid
+      WHERE c.chat_id = $1 AND LOWER(u.email) = ANY($2)
+      ON CONFLICT DO NOTHING
+    "#,
+  )
+  .bind(chat_id)
+  .bind(member_emails)
+  .execute(txn.deref_mut())
+  .await?;
+  Ok(result.rows_affected())
+}
+
+/// The shared chats of the workspace visible to the user: the ones shared with the whole
+/// workspace, the ones shared with the user, and the ones the user shared.
+pub async fn select_shared_chats<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  uid: i64,
+) -> Result<Vec<AFSharedChatRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFSharedChatRow>(
+    r#"
+      SELECT c.chat_id, c.name, c.owner_uid, u.name AS owner_name, c.share_scope, c.collaborative,
+        c.shared_at
+      FROM af_chat c
+      LEFT JOIN af_user u ON u.uid = c.owner_uid
+      WHERE c.workspace_id = $1
+        AND c.deleted_at IS NULL
+        AND c.share_scope <> 0
+        AND (
+          c.share_scope = 1
+          OR c.owner_uid = $2
+          OR EXISTS (
+            SELECT 1 FROM af_chat_share_member s WHERE s.chat_id = c.chat_id AND s.uid = $2
+          )
+        )
+      ORDER BY c.shared_at DESC
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(uid)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+/// Full text search over the questions and answers of the shared chats visible to the user, see
+/// [select_shared_chats]. Returns the best matching message of each chat, best chats first.
+pub async fn search_shared_chat_messages<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  uid: i64,
+  query: &str,
+  limit: i32,
+  preview: i32,
+) -> Result<Vec<AFSharedChatSearchRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFSharedChatSearchRow>(
+    r#"
+      SELECT chat_id, workspace_id, preview, rank, owner_name, created_at
+      FROM (
+        SELECT DISTINCT ON (c.chat_id)
+          c.chat_id,
+          c.workspace_id,
+          LEFT(m.content, $5) AS preview,
+          ts_rank(to_tsvector('simple', m.content), websearch_to_tsquery('simple', $3)) AS rank,
+          u.name AS owner_name,
+          c.created_at
+        FROM af_chat c
+        JOIN af_chat_messages m ON m.chat_id = c.chat_id AND m.deleted_at IS NULL
+        LEFT JOIN af_user u ON u.uid = c.owner_uid
+        WHERE c.workspace_id = $1
+          AND c.deleted_at IS NULL
+          AND c.share_scope <> 0
+          AND (
+            c.share_scope = 1
+            OR c.owner_uid = $2
+            OR EXISTS (
+              SELECT 1 FROM af_chat_share_member s WHERE s.chat_id = c.chat_id AND s.uid = $2
+            )
+          )
+          AND to_tsvector('simple', m.content) @@ websearch_to_tsquery('simple', $3)
+        ORDER BY c.chat_id, rank DESC
+      ) matches
+      ORDER BY rank DESC
+      LIMIT $4
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(uid)
+  .bind(query)
+  .bind(limit)
+  .bind(preview)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+/// Called before the account of the user is deleted. The shared chats the user owns in the
+/// workspaces of other owners are handed over to the owner of the workspace, so that the members
+/// they were shared with keep them. The private ones are deleted.
+pub async fn transfer_chats_of_deleted_user(
+  txn: &mut Transaction<'_, Postgres>,
+  uid: i64,
+) -> Result<u64, AppError> {
+  let transferred = sqlx::query(
+    r#"
+      UPDATE af_chat c
+      SET owner_uid = w.owner_uid
+      FROM af_workspace w
+      WHERE c.workspace_id = w.workspace_id
+        AND c.owner_uid = $1
+        AND c.share_scope <> 0
+        AND w.owner_uid <> $1
+    "#,
+  )
+  .bind(uid)
+  .execute(txn.deref_mut())
+  .await?;
+
+  sqlx::query(
+    r#"
+      UPDATE af_chat SET deleted_at = NOW()
+      WHERE owner_uid = $1 AND share_scope = 0 AND deleted_at IS NULL
+    "#,
+  )
+  .bind(uid)
+  .execute(txn.deref_mut())
+  .await?;
+  Ok(transferred.rows_affected())
+}
@@ -1 +1,2 @@
 pub mod chat_ops;
+pub mod chat_share;
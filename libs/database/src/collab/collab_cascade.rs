@@ -0,0 +1,125 @@
+use std::ops::DerefMut;
+
+use app_error::AppError;
+use sqlx::{Executor, Postgres, Transaction};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::blob_variant::delete_blob_variants_of_files;
+use crate::index::delete_collab_fingerprint;
+use crate::notification::delete_notification_subscriptions_for_objects;
+use crate::resource_usage::{
+  delete_blob_metadata_bulk, delete_blob_versions_of_files, deleted_object_keys,
+  release_blob_contents, select_blob_file_ids_by_prefix,
+};
+
+/// Key of the doc state of a collab kept in S3 rather than in af_collab.
+pub fn collab_object_key(workspace_id: &str, object_id: &str) -> String {
+  format!(
+    "collabs/{}/{}/encoded_collab.v1.zstd",
+    workspace_id, object_id
+  )
+}
+
+/// Key of a snapshot of a collab kept in S3. The snapshot id is a timestamp in milliseconds,
+/// inverted so that the latest snapshot is listed first.
+pub fn collab_snapshot_object_key(workspace_id: &str, object_id: &str, snapshot_id: i64) -> String {
+  let snapshot_id = u64::MAX - snapshot_id as u64;
+  format!(
+    "collabs/{}/{}/snapshot_{:16x}.v1.zstd",
+    workspace_id, object_id, snapshot_id
+  )
+}
+
+/// The object ids of all the collabs of the workspace, including the deleted ones.
+pub async fn select_workspace_collab_oids<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<Vec<String>, AppError> {
+  let object_ids = sqlx::query_scalar("SELECT oid FROM af_collab WHERE workspace_id = $1")
+    .bind(workspace_id)
+    .fetch_all(executor)
+    .await?;
+  Ok(object_ids)
+}
+
+/// Permanently deletes a collab along with everything kept for it: its snapshots, embeddings,
+/// fingerprint, notification subscriptions, and the files attached to it, whose file id starts
+/// with `{object_id}_` as written by [crate::resource_usage::insert_blob_metadata_bulk].
+///
+/// Returns the S3 keys of the objects which belonged to the collab. The caller deletes them once
+/// the transaction is committed: the doc state and snapshots of the collab, whether or not they
+/// were offloaded to S3, and the objects of its files which no other file shares.
+#[instrument(level = "trace", skip(tx), err)]
+pub async fn delete_collab_cascade(
+  tx: &mut Transaction<'_, Postgres>,
+  workspace_id: &Uuid,
+  object_id: &str,
+) -> Result<Vec<String>, AppError> {
+  let workspace_id_str = workspace_id.to_string();
+  let mut object_keys = vec![collab_object_key(&workspace_id_str, object_id)];
+
+  let snapshot_ids: Vec<i64> = sqlx::query_scalar(
+    r#"
+      DELETE FROM af_collab_snapshot_info
+      WHERE workspace_id = $1 AND oid = $2
+      RETURNING snapshot_id
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(object_id)
+  .fetch_all(tx.deref_mut())
+  .await?;
+  object_keys.extend(
+    snapshot_ids
+      .into_iter()
+      .map(|snapshot_id| collab_snapshot_object_key(&workspace_id_str, object_id, snapshot_id)),
+  );
+  for query in [
+    "DELETE FROM af_collab_snapshot WHERE workspace_id = $1 AND oid = $2",
+    "DELETE FROM af_snapshot_meta WHERE workspace_id = $1 AND oid = $2",
+    "DELETE FROM af_snapshot_state WHERE workspace_id = $1 AND oid = $2",
+  ] {
+    sqlx::query(query)
+      .bind(workspace_id)
+      .bind(object_id)
+      .execute(tx.deref_mut())
+      .await?;
+  }
+
+  sqlx::query("DELETE FROM af_collab_embeddings WHERE oid = $1")
+    .bind(object_id)
+    .execute(tx.deref_mut())
+    .await?;
+  sqlx::query("DELETE FROM af_collab WHERE workspace_id = $1 AND oid = $2")
+    .bind(workspace_id)
+    .bind(object_id)
+    .execute(tx.deref_mut())
+    .await?;
+  delete_collab_fingerprint(tx.deref_mut(), object_id).await?;
+  if let Ok(oid) = Uuid::parse_str(object_id) {
+    delete_notification_subscriptions_for_objects(tx.deref_mut(), &[oid]).await?;
+  }
+
+  let file_ids = select_blob_file_ids_by_prefix(tx, workspace_id, object_id).await?;
+  if !file_ids.is_empty() {
+    let released = release_blob_contents(tx, workspace_id, &file_ids).await?;
+    let file_ids = delete_blob_metadata_bulk(tx, workspace_id, &file_ids).await?;
+    let version_keys = delete_blob_versions_of_files(tx, workspace_id, &file_ids).await?;
+    let variant_keys = delete_blob_variants_of_files(tx, workspace_id, &file_ids).await?;
+    let prefix = format!("{}_", object_id);
+    let blob_keys = file_ids.iter().filter_map(|file_id| {
+      let object_key = format!(
+        "{}/{}/{}",
+        workspace_id,
+        object_id,
+        file_id.strip_prefix(&prefix)?
+      );
+      Some((file_id.clone(), object_key))
+    });
+    object_keys.extend(deleted_object_keys(&released, blob_keys));
+    object_keys.extend(version_keys);
+    object_keys.extend(variant_keys);
+  }
+  Ok(object_keys)
+}
@@ -8,6 +8,7 @@ use shared_entity::dto::workspace_dto::{DatabaseRowUpdatedItem, EmbeddedCollabQu
 
 use crate::collab::{partition_key_from_collab_type, SNAPSHOT_PER_HOUR};
 use crate::pg_row::AFCollabRowMeta;
+use crate::pg_row::AFCollabSnapshotInfoRow;
 use crate::pg_row::AFSnapshotRow;
 use app_error::AppError;
 use chrono::{DateTime, Duration, Utc};
@@ -201,6 +202,66 @@ pub async fn insert_into_af_collab_bulk_for_user(
   Ok(())
 }
 
+/// Inserts new collabs of a workspace with [insert_into_af_collab_bulk_for_user], rejecting the
+/// batch instead of skipping collabs silently: when an object id appears more than once, or when
+/// a collab already belongs to another workspace. Nothing is written when it fails, and the
+/// caller's transaction is rolled back when an insert fails.
+#[instrument(level = "trace", skip_all, fields(uid=%uid, workspace_id=%workspace_id), err)]
+pub async fn insert_new_collabs_bulk_for_user(
+  tx: &mut Transaction<'_, Postgres>,
+  uid: &i64,
+  workspace_id: &str,
+  collab_params_list: &[CollabParams],
+) -> Result<(), AppError> {
+  let duplicates = duplicate_object_ids(
+    collab_params_list
+      .iter()
+      .map(|params| params.object_id.as_str()),
+  );
+  if !duplicates.is_empty() {
+    return Err(AppError::InvalidRequest(format!(
+      "duplicate object ids in the batch: {}",
+      duplicates.join(", ")
+    )));
+  }
+
+  let workspace_uuid = Uuid::from_str(workspace_id)?;
+  let object_ids = collab_params_list
+    .iter()
+    .map(|params| params.object_id.as_str())
+    .collect::<Vec<_>>();
+  let taken: Vec<String> = sqlx::query_scalar(
+    r#"
+      SELECT oid FROM af_collab
+      WHERE oid = ANY($1) AND workspace_id <> $2
+    "#,
+  )
+  .bind(&object_ids)
+  .bind(workspace_uuid)
+  .fetch_all(tx.deref_mut())
+  .await?;
+  if !taken.is_empty() {
+    return Err(AppError::RecordAlreadyExists(format!(
+      "collabs belong to another workspace: {}",
+      taken.join(", ")
+    )));
+  }
+
+  insert_into_af_collab_bulk_for_user(tx, uid, workspace_id, collab_params_list).await
+}
+
+/// The object ids which appear more than once, in the order of their first repetition.
+pub fn duplicate_object_ids<'a>(object_ids: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+  let mut visited = HashSet::new();
+  let mut duplicates = Vec::new();
+  for object_id in object_ids {
+    if !visited.insert(object_id) && !duplicates.iter().any(|dup| dup == object_id) {
+      duplicates.push(object_id.to_string());
+    }
+  }
+  duplicates
+}
+
 #[inline]
 pub async fn select_blob_from_af_collab<'a, E>(
   conn: E,
@@ -486,6 +547,63 @@ pub async fn get_all_collab_snapshot_meta(
   Ok(AFSnapshotMetas(snapshots))
 }
 
+pub async fn insert_collab_snapshot_info<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  object_id: &str,
+  snapshot_id: i64,
+  len: i64,
+  created_by: Option<i64>,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      INSERT INTO af_collab_snapshot_info (workspace_id, oid, snapshot_id, len, created_by)
+      VALUES ($1, $2, $3, $4, $5)
+      ON CONFLICT (oid, snapshot_id) DO NOTHING
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(object_id)
+  .bind(snapshot_id)
+  .bind(len)
+  .bind(created_by)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+pub async fn select_collab_snapshot_infos<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  object_id: &str,
+) -> Result<Vec<AFCollabSnapshotInfoRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFCollabSnapshotInfoRow>(
+    r#"
+      SELECT snapshot_id, len, created_by
+      FROM af_collab_snapshot_info
+      WHERE workspace_id = $1 AND oid = $2
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(object_id)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+pub async fn delete_collab_snapshot_infos<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  object_id: &str,
+  snapshot_ids: &[i64],
+) -> Result<(), AppError> {
+  sqlx::query("DELETE FROM af_collab_snapshot_info WHERE oid = $1 AND snapshot_id = ANY($2)")
+    .bind(object_id)
+    .bind(snapshot_ids)
+    .execute(executor)
+    .await?;
+  Ok(())
+}
+
 #[inline]
 fn transform_record_not_found_error(
   result: Result<Option<bool>, sqlx::Error>,
@@ -2,8 +2,8 @@ use app_error::AppError;
 use async_trait::async_trait;
 
 use database_entity::dto::{
-  AFAccessLevel, AFSnapshotMeta, AFSnapshotMetas, CollabParams, InsertSnapshotParams, QueryCollab,
-  QueryCollabParams, QueryCollabResult, SnapshotData,
+  AFAccessLevel, AFCollabVersion, AFSnapshotMeta, AFSnapshotMetas, CollabMode, CollabParams,
+  InsertSnapshotParams, QueryCollab, QueryCollabParams, QueryCollabResult, SnapshotData,
 };
 
 use crate::collab::CollabType;
@@ -101,6 +101,17 @@ pub trait CollabStorage: Send + Sync + 'static {
     action_description: &str,
   ) -> AppResult<()>;
 
+  /// Insert new collaborations in the storage within the given transaction. Either all of them
+  /// are inserted when the transaction is committed, or none, see
+  /// [crate::collab::insert_new_collabs_bulk_for_user].
+  async fn batch_insert_new_collab_with_transaction(
+    &self,
+    workspace_id: &str,
+    uid: &i64,
+    params_list: Vec<CollabParams>,
+    transaction: &mut Transaction<'_, sqlx::Postgres>,
+  ) -> AppResult<()>;
+
   /// Retrieves a collaboration from the storage.
   ///
   /// # Arguments
@@ -125,7 +136,8 @@ pub trait CollabStorage: Send + Sync + 'static {
     from_editing_collab: bool,
   ) -> HashMap<String, QueryCollabResult>;
 
-  /// Deletes a collaboration from the storage.
+  /// Deletes a collaboration from the storage. It's kept in the trash of its workspace, from which
+  /// it can be restored until it's purged.
   ///
   /// # Arguments
   ///
@@ -161,6 +173,21 @@ pub trait CollabStorage: Send + Sync + 'static {
     workspace_id: &str,
     oid: &str,
   ) -> AppResult<AFSnapshotMetas>;
+
+  /// Returns the version history of the collab: its snapshots in descending order of creation
+  /// time, with their size and author when known.
+  async fn get_collab_versions(
+    &self,
+    workspace_id: &str,
+    oid: &str,
+  ) -> AppResult<Vec<AFCollabVersion>>;
+
+  /// Returns whether the server can read the content of the collab, see [CollabMode].
+  async fn collab_mode(&self, object_id: &str) -> AppResult<CollabMode>;
+
+  /// Whether the collab was deleted and is in the trash of its workspace. The collabs which don't
+  /// exist aren't deleted.
+  async fn is_collab_deleted(&self, object_id: &str) -> AppResult<bool>;
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
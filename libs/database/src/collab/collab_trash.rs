@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+
+use app_error::AppError;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::AFDeletedCollabRow;
+
+/// The soft-deleted collabs of the workspace, most recently deleted first. They stay in the
+/// trash until they are restored or purged with [crate::collab::delete_collab_cascade].
+pub async fn select_deleted_collabs<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<Vec<AFDeletedCollabRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFDeletedCollabRow>(
+    r#"
+      SELECT oid, workspace_id, partition_key, deleted_at
+      FROM af_collab
+      WHERE workspace_id = $1 AND deleted_at IS NOT NULL
+      ORDER BY deleted_at DESC
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+/// Returns the collabs deleted before `deleted_before`, oldest first, across all workspaces. The
+/// returned rows are locked, the ones locked by another transaction are skipped.
+pub async fn select_collabs_deleted_before<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  deleted_before: DateTime<Utc>,
+  limit: i64,
+) -> Result<Vec<AFDeletedCollabRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFDeletedCollabRow>(
+    r#"
+      SELECT oid, workspace_id, partition_key, deleted_at
+      FROM af_collab
+      WHERE deleted_at IS NOT NULL AND deleted_at < $1
+      ORDER BY deleted_at
+      LIMIT $2
+      FOR UPDATE SKIP LOCKED
+    "#,
+  )
+  .bind(deleted_before)
+  .bind(limit)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+/// Takes the collab out of the trash. Returns false when it isn't in the trash of the workspace.
+pub async fn restore_deleted_collab<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  object_id: &str,
+) -> Result<bool, AppError> {
+  let result = sqlx::query(
+    r#"
+      UPDATE af_collab SET deleted_at = NULL
+      WHERE workspace_id = $1 AND oid = $2 AND deleted_at IS NOT NULL
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(object_id)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected() > 0)
+}
+
+/// Whether the collab is in the trash of the workspace.
+pub async fn is_collab_in_trash<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  object_id: &str,
+) -> Result<bool, AppError> {
+  let in_trash = sqlx::query_scalar(
+    r#"
+      SELECT EXISTS (
+        SELECT 1 FROM af_collab
+        WHERE workspace_id = $1 AND oid = $2 AND deleted_at IS NOT NULL
+      )
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(object_id)
+  .fetch_one(executor)
+  .await?;
+  Ok(in_trash)
+}
+
+/// Returns the given object ids whose collab is soft-deleted. The ids without a collab aren't
+/// returned, so that a collab which is yet to be created isn't taken for a deleted one.
+pub async fn select_deleted_collab_oids<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  object_ids: &[String],
+) -> Result<HashSet<String>, AppError> {
+  let object_ids: Vec<String> =
+    sqlx::query_scalar("SELECT oid FROM af_collab WHERE oid = ANY($1) AND deleted_at IS NOT NULL")
+      .bind(object_ids)
+      .fetch_all(executor)
+      .await?;
+  Ok(object_ids.into_iter().collect())
+}
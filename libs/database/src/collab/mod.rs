@@ -1,9 +1,13 @@
+mod collab_cascade;
 mod collab_db_ops;
 mod collab_storage;
+mod collab_trash;
 
+pub use collab_cascade::*;
 pub use collab_db_ops::*;
 use collab_entity::CollabType;
 pub use collab_storage::*;
+pub use collab_trash::*;
 
 pub(crate) fn partition_key_from_collab_type(collab_type: &CollabType) -> i32 {
   match collab_type {
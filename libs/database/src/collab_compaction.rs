@@ -0,0 +1,57 @@
+use app_error::AppError;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::AFCollabCompactionCandidateRow;
+
+/// Returns the collab `object_id` of the workspace, or `None` if it's not stored.
+pub async fn select_collab_compaction_candidate<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  object_id: &str,
+) -> Result<Option<AFCollabCompactionCandidateRow>, AppError> {
+  let row = sqlx::query_as::<_, AFCollabCompactionCandidateRow>(
+    r#"
+      SELECT oid, workspace_id, partition_key, owner_uid, len
+      FROM af_collab
+      WHERE oid = $1 AND workspace_id = $2 AND deleted_at IS NULL
+    "#,
+  )
+  .bind(object_id)
+  .bind(workspace_id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Returns up to `limit` collabs whose encoded state is larger than `min_len` bytes and which were
+/// written since `updated_since`, ordered by object id and starting after `after_oid`, so that the
+/// whole table can be walked in batches.
+pub async fn select_collabs_over_len<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  min_len: i32,
+  updated_since: Option<DateTime<Utc>>,
+  after_oid: Option<&str>,
+  limit: i64,
+) -> Result<Vec<AFCollabCompactionCandidateRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFCollabCompactionCandidateRow>(
+    r#"
+      SELECT oid, workspace_id, partition_key, owner_uid, len
+      FROM af_collab
+      WHERE len > $1
+        AND deleted_at IS NULL
+        AND ($2::TIMESTAMPTZ IS NULL OR updated_at >= $2)
+        AND ($3::TEXT IS NULL OR oid > $3)
+      ORDER BY oid
+      LIMIT $4
+    "#,
+  )
+  .bind(min_len)
+  .bind(updated_since)
+  .bind(after_oid)
+  .bind(limit)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
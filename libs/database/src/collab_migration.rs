@@ -0,0 +1,85 @@
+use app_error::AppError;
+use collab_entity::CollabType;
+use sqlx::{Executor, Postgres};
+
+use crate::collab::partition_key_from_collab_type;
+use crate::pg_row::AFCollabMigrationCandidateRow;
+
+/// Returns the version of the last collab migration applied to the collab, or `None` if the
+/// collab is not stored yet.
+pub async fn select_collab_migration_version<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  object_id: &str,
+  collab_type: &CollabType,
+) -> Result<Option<i32>, AppError> {
+  let version = sqlx::query_scalar::<_, i32>(
+    r#"
+      SELECT migration_version FROM af_collab
+      WHERE oid = $1 AND partition_key = $2 AND deleted_at IS NULL
+    "#,
+  )
+  .bind(object_id)
+  .bind(partition_key_from_collab_type(collab_type))
+  .fetch_optional(executor)
+  .await?;
+  Ok(version)
+}
+
+/// Moves the collab from version `from` to version `to`. Returns false when the collab is not at
+/// version `from` anymore, which means that it was migrated concurrently by another server.
+pub async fn update_collab_migration_version<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  object_id: &str,
+  collab_type: &CollabType,
+  from: i32,
+  to: i32,
+) -> Result<bool, AppError> {
+  let result = sqlx::query(
+    r#"
+      UPDATE af_collab SET migration_version = $4
+      WHERE oid = $1 AND partition_key = $2 AND migration_version = $3
+    "#,
+  )
+  .bind(object_id)
+  .bind(partition_key_from_collab_type(collab_type))
+  .bind(from)
+  .bind(to)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected() > 0)
+}
+
+/// Returns up to `limit` collabs of the given types whose migration version is below `version`,
+/// ordered by object id and starting after `after_oid`, so that the whole table can be walked in
+/// batches.
+pub async fn select_collabs_behind_migration_version<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  collab_types: &[CollabType],
+  version: i32,
+  after_oid: Option<&str>,
+  limit: i64,
+) -> Result<Vec<AFCollabMigrationCandidateRow>, AppError> {
+  let partition_keys = collab_types
+    .iter()
+    .map(partition_key_from_collab_type)
+    .collect::<Vec<_>>();
+  let rows = sqlx::query_as::<_, AFCollabMigrationCandidateRow>(
+    r#"
+      SELECT oid, workspace_id, partition_key, migration_version
+      FROM af_collab
+      WHERE partition_key = ANY($1)
+        AND migration_version < $2
+        AND deleted_at IS NULL
+        AND ($3::TEXT IS NULL OR oid > $3)
+      ORDER BY oid
+      LIMIT $4
+    "#,
+  )
+  .bind(partition_keys)
+  .bind(version)
+  .bind(after_oid)
+  .bind(limit)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
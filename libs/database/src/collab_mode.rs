@@ -0,0 +1,80 @@
+use std::collections::HashSet;
+
+use app_error::AppError;
+use database_entity::dto::CollabMode;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+/// Records the mode of the collab. Only the opaque collabs have a row, so setting a collab back to
+/// [CollabMode::Plain] deletes it.
+pub async fn upsert_collab_mode<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  oid: &str,
+  workspace_id: &Uuid,
+  mode: CollabMode,
+  created_by: i64,
+) -> Result<(), AppError> {
+  match mode {
+    CollabMode::Plain => {
+      sqlx::query(
+        r#"
+          DELETE FROM af_collab_mode
+          WHERE oid = $1
+        "#,
+      )
+      .bind(oid)
+      .execute(executor)
+      .await?;
+    },
+    CollabMode::Opaque => {
+      sqlx::query(
+        r#"
+          INSERT INTO af_collab_mode (oid, workspace_id, mode, created_by)
+          VALUES ($1, $2, $3, $4)
+          ON CONFLICT (oid) DO UPDATE SET mode = EXCLUDED.mode
+        "#,
+      )
+      .bind(oid)
+      .bind(workspace_id)
+      .bind(mode as i16)
+      .bind(created_by)
+      .execute(executor)
+      .await?;
+    },
+  }
+  Ok(())
+}
+
+pub async fn select_collab_mode<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  oid: &str,
+) -> Result<CollabMode, AppError> {
+  let mode: Option<i16> = sqlx::query_scalar(
+    r#"
+      SELECT mode FROM af_collab_mode
+      WHERE oid = $1
+    "#,
+  )
+  .bind(oid)
+  .fetch_optional(executor)
+  .await?;
+  Ok(mode.map(CollabMode::from).unwrap_or_default())
+}
+
+/// The opaque collabs among `oids`.
+pub async fn select_opaque_collab_oids<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  oids: &[String],
+) -> Result<HashSet<String>, AppError> {
+  let rows: Vec<String> = sqlx::query_scalar(
+    r#"
+      SELECT oid FROM af_collab_mode
+      WHERE oid = ANY($1) AND mode = $2
+    "#,
+  )
+  .bind(oids)
+  .bind(CollabMode::Opaque as i16)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows.into_iter().collect())
+}
@@ -0,0 +1,149 @@
+use app_error::AppError;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::AFCollabVerificationReportRow;
+
+/// Records that the stored state of a collab could not be decoded. A collab has at most one report
+/// waiting for repair: detecting the corruption again only updates the snapshot it was recovered
+/// from and the detection count.
+#[allow(clippy::too_many_arguments)]
+pub async fn upsert_collab_verification_report<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  object_id: &str,
+  collab_type: i32,
+  detected_on: &str,
+  error: &str,
+  recovered_snapshot_id: Option<i64>,
+  recovered_snapshot_at: Option<DateTime<Utc>>,
+) -> Result<AFCollabVerificationReportRow, AppError> {
+  let row = sqlx::query_as::<_, AFCollabVerificationReportRow>(
+    r#"
+      INSERT INTO af_collab_verification_report (
+        workspace_id, object_id, collab_type, detected_on, error, recovered_snapshot_id,
+        recovered_snapshot_at
+      )
+      VALUES ($1, $2, $3, $4, $5, $6, $7)
+      ON CONFLICT (object_id) WHERE repaired_at IS NULL DO UPDATE SET
+        error = EXCLUDED.error,
+        recovered_snapshot_id = EXCLUDED.recovered_snapshot_id,
+        recovered_snapshot_at = EXCLUDED.recovered_snapshot_at,
+        detection_count = af_collab_verification_report.detection_count + 1,
+        last_detected_at = NOW()
+      RETURNING
+        report_id, workspace_id, object_id, collab_type, detected_on, error,
+        recovered_snapshot_id, recovered_snapshot_at, detection_count, created_at,
+        last_detected_at, repaired_at
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(object_id)
+  .bind(collab_type)
+  .bind(detected_on)
+  .bind(error)
+  .bind(recovered_snapshot_id)
+  .bind(recovered_snapshot_at)
+  .fetch_one(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Returns the report of the collab which is waiting for repair, if any.
+pub async fn select_unrepaired_collab_verification_report<
+  'a,
+  E: Executor<'a, Database = Postgres>,
+>(
+  executor: E,
+  object_id: &str,
+) -> Result<Option<AFCollabVerificationReportRow>, AppError> {
+  let row = sqlx::query_as::<_, AFCollabVerificationReportRow>(
+    r#"
+      SELECT
+        report_id, workspace_id, object_id, collab_type, detected_on, error,
+        recovered_snapshot_id, recovered_snapshot_at, detection_count, created_at,
+        last_detected_at, repaired_at
+      FROM af_collab_verification_report
+      WHERE object_id = $1 AND repaired_at IS NULL
+    "#,
+  )
+  .bind(object_id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+pub async fn select_collab_verification_report<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  report_id: i64,
+) -> Result<Option<AFCollabVerificationReportRow>, AppError> {
+  let row = sqlx::query_as::<_, AFCollabVerificationReportRow>(
+    r#"
+      SELECT
+        report_id, workspace_id, object_id, collab_type, detected_on, error,
+        recovered_snapshot_id, recovered_snapshot_at, detection_count, created_at,
+        last_detected_at, repaired_at
+      FROM af_collab_verification_report
+      WHERE report_id = $1
+    "#,
+  )
+  .bind(report_id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+pub async fn select_collab_verification_reports<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  include_repaired: bool,
+  offset: i64,
+  limit: i64,
+) -> Result<Vec<AFCollabVerificationReportRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFCollabVerificationReportRow>(
+    r#"
+      SELECT
+        report_id, workspace_id, object_id, collab_type, detected_on, error,
+        recovered_snapshot_id, recovered_snapshot_at, detection_count, created_at,
+        last_detected_at, repaired_at
+      FROM af_collab_verification_report
+      WHERE $1 OR repaired_at IS NULL
+      ORDER BY created_at DESC
+      OFFSET $2
+      LIMIT $3
+    "#,
+  )
+  .bind(include_repaired)
+  .bind(offset)
+  .bind(limit)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+/// Marks the report as repaired if it's still waiting for repair. Returns the repaired report.
+pub async fn update_collab_verification_report_repaired<
+  'a,
+  E: Executor<'a, Database = Postgres>,
+>(
+  executor: E,
+  report_id: i64,
+  repaired_by: i64,
+) -> Result<Option<AFCollabVerificationReportRow>, AppError> {
+  let row = sqlx::query_as::<_, AFCollabVerificationReportRow>(
+    r#"
+      UPDATE af_collab_verification_report
+      SET repaired_at = NOW(), repaired_by = $2
+      WHERE report_id = $1 AND repaired_at IS NULL
+      RETURNING
+        report_id, workspace_id, object_id, collab_type, detected_on, error,
+        recovered_snapshot_id, recovered_snapshot_at, detection_count, created_at,
+        last_detected_at, repaired_at
+    "#,
+  )
+  .bind(report_id)
+  .bind(repaired_by)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
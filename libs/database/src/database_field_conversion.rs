@@ -0,0 +1,130 @@
+use app_error::AppError;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::AFDatabaseFieldConversionRow;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FieldConversionState {
+  Pending = 0,
+  Running = 1,
+  Completed = 2,
+  Failed = 3,
+}
+
+impl From<i16> for FieldConversionState {
+  fn from(val: i16) -> Self {
+    match val {
+      1 => FieldConversionState::Running,
+      2 => FieldConversionState::Completed,
+      3 => FieldConversionState::Failed,
+      _ => FieldConversionState::Pending,
+    }
+  }
+}
+
+/// Insert a pending conversion task. Returns [AppError::InvalidRequest] if the field is already
+/// being converted.
+#[allow(clippy::too_many_arguments)]
+pub async fn insert_field_conversion_task<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  task_id: &Uuid,
+  workspace_id: &Uuid,
+  database_id: &Uuid,
+  field_id: &str,
+  from_field_type: i64,
+  to_field_type: i64,
+  uid: i64,
+) -> Result<AFDatabaseFieldConversionRow, AppError> {
+  let result = sqlx::query_as::<_, AFDatabaseFieldConversionRow>(
+    r#"
+      INSERT INTO af_database_field_conversion
+        (task_id, workspace_id, database_id, field_id, from_field_type, to_field_type, created_by)
+      VALUES ($1, $2, $3, $4, $5, $6, $7)
+      RETURNING *
+    "#,
+  )
+  .bind(task_id)
+  .bind(workspace_id)
+  .bind(database_id)
+  .bind(field_id)
+  .bind(from_field_type)
+  .bind(to_field_type)
+  .bind(uid)
+  .fetch_one(executor)
+  .await;
+
+  match result {
+    Ok(row) => Ok(row),
+    Err(sqlx::Error::Database(err)) if err.is_unique_violation() => Err(AppError::InvalidRequest(
+      format!("field {} is already being converted", field_id),
+    )),
+    Err(err) => Err(err.into()),
+  }
+}
+
+pub async fn select_field_conversion_task<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  database_id: &Uuid,
+  task_id: &Uuid,
+) -> Result<AFDatabaseFieldConversionRow, AppError> {
+  let row = sqlx::query_as::<_, AFDatabaseFieldConversionRow>(
+    r#"
+      SELECT * FROM af_database_field_conversion
+      WHERE workspace_id = $1 AND database_id = $2 AND task_id = $3
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(database_id)
+  .bind(task_id)
+  .fetch_one(executor)
+  .await?;
+  Ok(row)
+}
+
+pub async fn update_field_conversion_progress<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  task_id: &Uuid,
+  status: FieldConversionState,
+  total_rows: i64,
+  converted_rows: i64,
+  report: &serde_json::Value,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_database_field_conversion
+      SET status = $2, total_rows = $3, converted_rows = $4, report = $5,
+          updated_at = CURRENT_TIMESTAMP
+      WHERE task_id = $1
+    "#,
+  )
+  .bind(task_id)
+  .bind(status as i16)
+  .bind(total_rows)
+  .bind(converted_rows)
+  .bind(report)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+pub async fn update_field_conversion_failed<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  task_id: &Uuid,
+  error: &str,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_database_field_conversion
+      SET status = $2, error = $3, updated_at = CURRENT_TIMESTAMP
+      WHERE task_id = $1
+    "#,
+  )
+  .bind(task_id)
+  .bind(FieldConversionState::Failed as i16)
+  .bind(error)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
@@ -0,0 +1,90 @@
+use app_error::AppError;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::AFDatabaseRowAccessRow;
+
+pub async fn upsert_database_row_access<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  database_id: &Uuid,
+  field_id: &str,
+  restricted_rows: bool,
+  uid: i64,
+) -> Result<AFDatabaseRowAccessRow, AppError> {
+  let row = sqlx::query_as::<_, AFDatabaseRowAccessRow>(
+    r#"
+      INSERT INTO af_database_row_access
+        (database_id, workspace_id, field_id, restricted_rows, created_by)
+      VALUES ($1, $2, $3, $4, $5)
+      ON CONFLICT (database_id) DO UPDATE
+        SET field_id = EXCLUDED.field_id,
+            restricted_rows = EXCLUDED.restricted_rows,
+            created_by = EXCLUDED.created_by,
+            updated_at = CURRENT_TIMESTAMP
+      RETURNING *
+    "#,
+  )
+  .bind(database_id)
+  .bind(workspace_id)
+  .bind(field_id)
+  .bind(restricted_rows)
+  .bind(uid)
+  .fetch_one(executor)
+  .await?;
+  Ok(row)
+}
+
+pub async fn select_database_row_access<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  database_id: &Uuid,
+) -> Result<Option<AFDatabaseRowAccessRow>, AppError> {
+  let row = sqlx::query_as::<_, AFDatabaseRowAccessRow>(
+    r#"
+      SELECT * FROM af_database_row_access
+      WHERE workspace_id = $1 AND database_id = $2
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(database_id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Returns the rules of the databases of the workspace whose rows are restricted.
+pub async fn select_restricted_database_row_access<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<Vec<AFDatabaseRowAccessRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFDatabaseRowAccessRow>(
+    r#"
+      SELECT * FROM af_database_row_access
+      WHERE workspace_id = $1 AND restricted_rows
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+/// Returns false when the database has no rule.
+pub async fn delete_database_row_access<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  database_id: &Uuid,
+) -> Result<bool, AppError> {
+  let result = sqlx::query(
+    r#"
+      DELETE FROM af_database_row_access
+      WHERE workspace_id = $1 AND database_id = $2
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(database_id)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected() > 0)
+}
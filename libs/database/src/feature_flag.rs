@@ -0,0 +1,36 @@
+use app_error::AppError;
+use sqlx::{Executor, Postgres};
+
+use crate::pg_row::AFFeatureFlagRow;
+
+pub async fn select_feature_flags<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+) -> Result<Vec<AFFeatureFlagRow>, AppError> {
+  let rows =
+    sqlx::query_as::<_, AFFeatureFlagRow>(r#"SELECT name, value, updated_at FROM af_feature_flag"#)
+      .fetch_all(executor)
+      .await?;
+  Ok(rows)
+}
+
+pub async fn upsert_feature_flag<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  name: &str,
+  value: serde_json::Value,
+  updated_by: i64,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      INSERT INTO af_feature_flag (name, value, updated_by, updated_at)
+      VALUES ($1, $2, $3, NOW())
+      ON CONFLICT (name) DO UPDATE
+      SET value = EXCLUDED.value, updated_by = EXCLUDED.updated_by, updated_at = NOW()
+    "#,
+  )
+  .bind(name)
+  .bind(value)
+  .bind(updated_by)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
@@ -1,19 +1,47 @@
-use crate::pg_row::AFBlobMetadataRow;
+use crate::blob_variant::{delete_blob_variants_of_files, select_blob_variant};
+use crate::pg_row::{
+  AFBlobMetadataRow, AFBlobMultipartUploadRow, AFBlobVariantRow, AFBlobVersionRow,
+};
 use crate::resource_usage::{
-  delete_blob_metadata, get_blob_metadata, insert_blob_metadata, is_blob_metadata_exists,
+  acquire_blob_content, blob_content_hash, check_workspace_storage_limit, delete_all_blob_versions,
+  delete_blob_metadata, delete_blob_metadata_bulk, delete_blob_versions_of_files,
+  delete_expired_blob_versions, delete_multipart_upload, delete_pending_blob_metadata,
+  deleted_object_keys, find_blob_by_content_hash, get_blob_metadata, insert_blob_content,
+  insert_blob_content_metadata, insert_blob_metadata, insert_blob_metadata_bulk,
+  insert_blob_metadata_with_limit, insert_blob_version, insert_multipart_upload,
+  insert_pending_blob_metadata, invalidate_workspace_usage_size_cache, is_blob_metadata_exists,
+  max_blob_size_for_workspace, release_blob_contents, select_blob_file_ids_by_prefix,
+  select_blob_metadata_by_file_ids, select_blob_metadata_for_update, select_blob_version,
+  select_blob_versions, select_multipart_upload, select_multipart_upload_parts,
+  select_next_blob_version, select_pending_blob_metadata, update_blob_metadata,
+  update_workspace_usage_size_cache, upsert_multipart_upload_part, BlobAttributes, BulkInsertMeta,
 };
+use anyhow::anyhow;
 use app_error::AppError;
 use async_trait::async_trait;
 use aws_sdk_s3::primitives::ByteStream;
+use chrono::{DateTime, Utc};
 use database_entity::file_dto::{
-  CompleteUploadRequest, CreateUploadRequest, CreateUploadResponse, UploadPartData,
-  UploadPartResponse,
+  BlobVariant, CompleteUploadRequest, CreateUploadRequest, CreateUploadResponse,
+  ListUploadPartsResponse, PresignedUploadRequest, PresignedUploadResponse, UploadPartData,
+  UploadPartResponse, UploadedPart,
 };
+use redis::aio::ConnectionManager;
 use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::ops::DerefMut;
+use std::slice;
+use std::time::Duration;
 
-use tracing::{info, instrument, warn};
+use tracing::{error, info, instrument, trace, warn};
 use uuid::Uuid;
 
+/// Attempts to delete the objects of deleted blobs before giving up on them.
+const DELETE_OBJECTS_ATTEMPTS: u32 = 3;
+
+/// How long the urls to upload a blob straight to the bucket stay valid.
+const PRESIGNED_UPLOAD_EXPIRATION: Duration = Duration::from_secs(60 * 60);
+
 pub trait ResponseBlob {
   fn to_blob(self) -> Vec<u8>;
   fn content_type(&self) -> Option<String>;
@@ -37,12 +65,41 @@ pub trait BucketClient {
     content_type: &str,
   ) -> Result<(), AppError>;
 
+  async fn copy_blob(&self, from_object_key: &str, to_object_key: &str) -> Result<(), AppError>;
+
   async fn delete_blob(&self, object_key: &str) -> Result<Self::ResponseData, AppError>;
 
   async fn delete_blobs(&self, object_key: Vec<String>) -> Result<(), AppError>;
 
+  /// Delete the objects with DeleteObjects requests of up to 1000 keys. Returns the keys which
+  /// weren't deleted: the ones the bucket reported an error for, and every key of a request which
+  /// failed.
+  async fn delete_objects_batch(&self, object_keys: &[String]) -> Result<Vec<String>, AppError>;
+
   async fn get_blob(&self, object_key: &str) -> Result<Self::ResponseData, AppError>;
 
+  /// Read the bytes of an object from `start` to `end`, both included.
+  async fn get_blob_range(
+    &self,
+    object_key: &str,
+    start: u64,
+    end: u64,
+  ) -> Result<Self::ResponseData, AppError>;
+
+  /// Return the key, size and modification time of an object without reading its content. Fails
+  /// with [AppError::RecordNotFound] when there is no such object.
+  async fn head_blob(&self, object_key: &str) -> Result<BucketObject, AppError>;
+
+  /// Generate a url the clients can upload the content of an object to with a PUT request,
+  /// without going through the server. The request must have the given content type and length.
+  async fn presign_put_blob(
+    &self,
+    object_key: &str,
+    content_type: &str,
+    content_length: u64,
+    expires_in: Duration,
+  ) -> Result<String, AppError>;
+
   async fn create_upload(
     &self,
     object_key: &str,
@@ -62,6 +119,16 @@ pub trait BucketClient {
   async fn remove_dir(&self, dir: &str) -> Result<(), AppError>;
 
   async fn list_dir(&self, dir: &str, limit: usize) -> Result<Vec<String>, AppError>;
+
+  /// List every object whose key starts with `prefix`
+  async fn list_objects(&self, prefix: &str) -> Result<Vec<BucketObject>, AppError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct BucketObject {
+  pub key: String,
+  pub size: i64,
+  pub last_modified: Option<DateTime<Utc>>,
 }
 
 pub trait BlobKey: Send + Sync {
@@ -71,9 +138,35 @@ pub trait BlobKey: Send + Sync {
   fn e_tag(&self) -> &str;
 }
 
+/// Controls how previous revisions of a blob are retained when its file id is overwritten.
+#[derive(Debug, Clone)]
+pub struct BlobVersionPolicy {
+  /// Maximum number of archived versions kept per file. Older versions are pruned.
+  pub max_versions: i64,
+  /// Archived versions older than this are pruned. `None` keeps them until `max_versions` is hit.
+  pub max_age_days: Option<i64>,
+}
+
+/// File id of the copy of a blob made by [BucketStorage::copy_blobs]: the blob of `parent_dir`
+/// whose name in the parent is the file id of the copied blob.
+pub fn copied_blob_file_id(parent_dir: &str, file_id: &str) -> String {
+  format!("{}_{}", parent_dir, file_id)
+}
+
+/// A fresh object key for the new content of an existing blob, see
+/// [BucketStorage::overwrite_blob]. The previous object is kept as an archived version. The key
+/// keeps the original object key as its prefix, so removing the parent directory also removes
+/// every content the blob had.
+pub fn blob_revision_object_key(object_key: &str) -> String {
+  format!("{}@r{}", object_key, Uuid::new_v4().simple())
+}
+
 pub struct BucketStorage<C> {
   client: C,
   pg_pool: PgPool,
+  version_policy: Option<BlobVersionPolicy>,
+  storage_limit: Option<u64>,
+  usage_cache: Option<ConnectionManager>,
 }
 
 impl<C> BucketStorage<C>
@@ -81,7 +174,66 @@ where
   C: BucketClient,
 {
   pub fn new(client: C, pg_pool: PgPool) -> Self {
-    Self { client, pg_pool }
+    Self {
+      client,
+      pg_pool,
+      version_policy: None,
+      storage_limit: None,
+      usage_cache: None,
+    }
+  }
+
+  /// Keep the usage of the workspaces cached in Redis up to date with the blobs written and
+  /// deleted, see [crate::resource_usage::get_workspace_usage_size_cached].
+  pub fn with_usage_cache(mut self, redis: ConnectionManager) -> Self {
+    self.usage_cache = Some(redis);
+    self
+  }
+
+  /// Limit the total size of the blobs of each workspace, archived versions included. The
+  /// uploads which would go over it fail with [AppError::StorageSpaceNotEnough].
+  pub fn with_storage_limit(mut self, limit_bytes: Option<u64>) -> Self {
+    self.storage_limit = limit_bytes;
+    self
+  }
+
+  /// Enable blob versioning. When set, overwriting an existing file id keeps the previous
+  /// content as an archived version instead of discarding it.
+  pub fn with_version_policy(mut self, version_policy: Option<BlobVersionPolicy>) -> Self {
+    self.version_policy = version_policy;
+    self
+  }
+
+  pub fn is_versioning_enabled(&self) -> bool {
+    self.version_policy.is_some()
+  }
+
+  pub fn version_policy(&self) -> Option<&BlobVersionPolicy> {
+    self.version_policy.as_ref()
+  }
+
+  async fn update_usage_cache(&self, workspace_id: &Uuid, delta: i64) {
+    if let Some(redis) = &self.usage_cache {
+      update_workspace_usage_size_cache(redis, workspace_id, delta).await;
+    }
+  }
+
+  async fn invalidate_usage_cache(&self, workspace_id: &Uuid) {
+    if let Some(redis) = &self.usage_cache {
+      invalidate_workspace_usage_size_cache(redis, workspace_id).await;
+    }
+  }
+
+  pub async fn list_objects(&self, prefix: &str) -> Result<Vec<BucketObject>, AppError> {
+    self.client.list_objects(prefix).await
+  }
+
+  /// Delete objects by key without touching the blob metadata
+  pub async fn delete_objects(&self, object_keys: Vec<String>) -> Result<(), AppError> {
+    if object_keys.is_empty() {
+      return Ok(());
+    }
+    self.client.delete_blobs(object_keys).await
   }
 
   pub async fn remove_dir(&self, dir: &str) -> Result<(), AppError> {
@@ -98,8 +250,22 @@ where
     file_stream: ByteStream,
     file_type: String,
     file_size: usize,
+    attributes: BlobAttributes,
   ) -> Result<(), AppError> {
     if is_blob_metadata_exists(&self.pg_pool, key.workspace_id(), &key.blob_metadata_key()).await? {
+      if self.is_versioning_enabled() {
+        let object_key = blob_revision_object_key(&key.object_key());
+        return self
+          .overwrite_blob(&key, object_key, attributes, |object_key| async move {
+            self
+              .client
+              .put_blob(&object_key, file_stream, Some(&file_type))
+              .await?;
+            Ok((file_size, file_type))
+          })
+          .await;
+      }
+
       warn!(
         "file already exists, workspace_id: {}, blob_metadata_key: {}",
         key.workspace_id(),
@@ -108,36 +274,685 @@ where
       return Ok(());
     }
 
+    let Some(limit_bytes) = self.storage_limit else {
+      self
+        .client
+        .put_blob(&key.object_key(), file_stream, Some(&file_type))
+        .await?;
+      let delta = insert_blob_metadata(
+        &self.pg_pool,
+        &key.blob_metadata_key(),
+        key.workspace_id(),
+        &file_type,
+        file_size,
+        None,
+        attributes,
+      )
+      .await?;
+      self.update_usage_cache(key.workspace_id(), delta).await;
+      return Ok(());
+    };
+
+    // The space is reserved before the upload, so that an upload over the limit never reaches
+    // the bucket, and released if the upload fails.
     self
+      .insert_blob_metadata_with_limit(&key, &file_type, file_size, limit_bytes, attributes)
+      .await?;
+    if let Err(err) = self
       .client
       .put_blob(&key.object_key(), file_stream, Some(&file_type))
+      .await
+    {
+      self.release_blob_metadata(&key).await;
+      return Err(err);
+    }
+    Ok(())
+  }
+
+  /// Upload a blob whose whole content is known, storing each content once per workspace: when
+  /// a blob of the workspace already has the same content, the new blob only gets a metadata
+  /// row pointing at its object, and the upload to the bucket is skipped. While versioning is
+  /// enabled, the blobs aren't deduplicated, since overwriting the object of a blob would
+  /// change the content of every blob sharing it.
+  #[instrument(skip_all, err)]
+  pub async fn put_blob_content<K: BlobKey>(
+    &self,
+    key: K,
+    content: Vec<u8>,
+    file_type: String,
+    attributes: BlobAttributes,
+  ) -> Result<(), AppError> {
+    let file_size = content.len();
+    if self.is_versioning_enabled()
+      || is_blob_metadata_exists(&self.pg_pool, key.workspace_id(), &key.blob_metadata_key())
+        .await?
+    {
+      return self
+        .put_blob_with_content_type(
+          key,
+          ByteStream::from(content),
+          file_type,
+          file_size,
+          attributes,
+        )
+        .await;
+    }
+
+    let content_hash = blob_content_hash(&content);
+    if let Some(limit_bytes) = self.storage_limit {
+      // a blob sharing its content still counts toward the usage of the workspace
+      self
+        .insert_blob_metadata_with_limit(&key, &file_type, file_size, limit_bytes, attributes)
+        .await?;
+    }
+    let result = self
+      .put_blob_content_once(&key, content, &file_type, &content_hash, attributes)
+      .await;
+    if result.is_err() && self.storage_limit.is_some() {
+      self.release_blob_metadata(&key).await;
+    }
+    result
+  }
+
+  async fn put_blob_content_once(
+    &self,
+    key: &impl BlobKey,
+    content: Vec<u8>,
+    file_type: &str,
+    content_hash: &str,
+    attributes: BlobAttributes,
+  ) -> Result<(), AppError> {
+    let workspace_id = key.workspace_id();
+    let file_id = key.blob_metadata_key();
+    let file_size = content.len();
+    if find_blob_by_content_hash(&self.pg_pool, workspace_id, content_hash)
+      .await?
+      .is_some()
+    {
+      let mut tx = self.pg_pool.begin().await?;
+      if let Some(object_key) = acquire_blob_content(&mut tx, workspace_id, content_hash).await? {
+        let delta = insert_blob_content_metadata(
+          &mut tx,
+          workspace_id,
+          &file_id,
+          file_type,
+          file_size,
+          content_hash,
+          &object_key,
+          attributes,
+        )
+        .await?;
+        tx.commit().await?;
+        self.update_usage_cache(workspace_id, delta).await;
+        trace!(
+          "blob {}/{} shares the content of object {}",
+          workspace_id,
+          file_id,
+          object_key
+        );
+        return Ok(());
+      }
+      // the last blob with this content was deleted meanwhile, upload it again
+    }
+
+    let uploaded_key = key.object_key();
+    self
+      .client
+      .put_blob(&uploaded_key, ByteStream::from(content), Some(file_type))
       .await?;
-    insert_blob_metadata(
-      &self.pg_pool,
-      &key.blob_metadata_key(),
+    let mut tx = self.pg_pool.begin().await?;
+    let object_key =
+      insert_blob_content(&mut tx, workspace_id, content_hash, &uploaded_key).await?;
+    let delta = insert_blob_content_metadata(
+      &mut tx,
+      workspace_id,
+      &file_id,
+      file_type,
+      file_size,
+      content_hash,
+      &object_key,
+      attributes,
+    )
+    .await?;
+    tx.commit().await?;
+    self.update_usage_cache(workspace_id, delta).await;
+    if object_key != uploaded_key {
+      // the same content was uploaded concurrently and recorded first
+      self.delete_objects_with_retry(vec![uploaded_key]).await;
+    }
+    Ok(())
+  }
+
+  async fn insert_blob_metadata_with_limit(
+    &self,
+    key: &impl BlobKey,
+    file_type: &str,
+    file_size: usize,
+    limit_bytes: u64,
+    attributes: BlobAttributes,
+  ) -> Result<(), AppError> {
+    let mut tx = self.pg_pool.begin().await?;
+    let delta = insert_blob_metadata_with_limit(
+      &mut tx,
       key.workspace_id(),
-      &file_type,
+      &key.blob_metadata_key(),
+      file_type,
       file_size,
+      limit_bytes,
+      attributes,
     )
     .await?;
+    tx.commit().await?;
+    self.update_usage_cache(key.workspace_id(), delta).await;
     Ok(())
   }
 
+  /// Delete the metadata inserted for an upload which didn't complete.
+  async fn release_blob_metadata(&self, key: &impl BlobKey) {
+    let result = async {
+      let mut tx = self.pg_pool.begin().await?;
+      let freed =
+        delete_blob_metadata(&mut tx, key.workspace_id(), &key.blob_metadata_key()).await?;
+      tx.commit().await?;
+      Ok::<_, AppError>(freed)
+    }
+    .await;
+    match result {
+      Ok(freed) => self.update_usage_cache(key.workspace_id(), -freed).await,
+      Err(err) => error!(
+        "failed to release the metadata of blob {}/{}: {}",
+        key.workspace_id(),
+        key.blob_metadata_key(),
+        err
+      ),
+    }
+  }
+
+  /// Copy blobs of a workspace into another one, as blobs of `dst_parent_dir`, using server-side
+  /// copies of their objects. A blob keeps its file id under its new parent, see
+  /// [copied_blob_file_id]. Returns the new file id of every blob copied, by its file id in the
+  /// source workspace, so that the urls pointing at them can be rewritten. Left out are the file
+  /// ids without metadata, the blobs larger than the plan of the destination allows and the
+  /// objects which couldn't be copied. The blobs copied before into the same parent are returned
+  /// without being copied again.
+  ///
+  /// Fails with [AppError::StorageSpaceNotEnough] before anything is copied when the blobs would
+  /// take the destination over its storage limit. No metadata is inserted for the objects which
+  /// weren't copied, and the copied objects are deleted when their metadata can't be inserted.
+  pub async fn copy_blobs(
+    &self,
+    src_workspace_id: &Uuid,
+    dst_workspace_id: &Uuid,
+    dst_parent_dir: &str,
+    file_ids: &[String],
+    uploaded_by: Option<i64>,
+  ) -> Result<HashMap<String, String>, AppError> {
+    let mut copied_file_ids = HashMap::new();
+    let rows = select_blob_metadata_by_file_ids(&self.pg_pool, src_workspace_id, file_ids).await?;
+    if rows.is_empty() {
+      return Ok(copied_file_ids);
+    }
+    let new_file_ids = rows
+      .iter()
+      .map(|row| copied_blob_file_id(dst_parent_dir, &row.file_id))
+      .collect::<Vec<_>>();
+    let existing_file_ids =
+      select_blob_metadata_by_file_ids(&self.pg_pool, dst_workspace_id, &new_file_ids)
+        .await?
+        .into_iter()
+        .map(|row| row.file_id)
+        .collect::<HashSet<_>>();
+    let max_blob_size = max_blob_size_for_workspace(&self.pg_pool, dst_workspace_id).await?;
+
+    let mut to_copy = Vec::with_capacity(rows.len());
+    for (row, new_file_id) in rows.into_iter().zip(new_file_ids) {
+      if existing_file_ids.contains(&new_file_id) {
+        copied_file_ids.insert(row.file_id, new_file_id);
+      } else if max_blob_size.is_some_and(|max_blob_size| row.file_size > max_blob_size) {
+        warn!(
+          "blob {}/{} is too large to be copied to workspace {}",
+          src_workspace_id, row.file_id, dst_workspace_id
+        );
+      } else {
+        to_copy.push(row);
+      }
+    }
+    if to_copy.is_empty() {
+      return Ok(copied_file_ids);
+    }
+    if let Some(limit_bytes) = self.storage_limit {
+      let total_size = to_copy.iter().map(|row| row.file_size.max(0) as u64).sum();
+      // no blob of the destination is replaced by the copies
+      let mut tx = self.pg_pool.begin().await?;
+      check_workspace_storage_limit(&mut tx, dst_workspace_id, "", total_size, limit_bytes).await?;
+    }
+
+    let mut copied = Vec::with_capacity(to_copy.len());
+    for row in to_copy {
+      let src_object_key = match self.blob_object_key(src_workspace_id, &row).await {
+        Ok(object_key) => object_key,
+        Err(err) => {
+          warn!(
+            "failed to find the object of blob {}/{}: {}",
+            src_workspace_id, row.file_id, err
+          );
+          continue;
+        },
+      };
+      let dst_object_key = format!("{}/{}/{}", dst_workspace_id, dst_parent_dir, row.file_id);
+      match self
+        .client
+        .copy_blob(&src_object_key, &dst_object_key)
+        .await
+      {
+        Ok(()) => copied.push((row, dst_object_key)),
+        Err(err) => warn!(
+          "failed to copy blob {}/{} to workspace {}: {}",
+          src_workspace_id, row.file_id, dst_workspace_id, err
+        ),
+      }
+    }
+    if copied.is_empty() {
+      return Ok(copied_file_ids);
+    }
+
+    let copied_size = copied
+      .iter()
+      .map(|(row, _)| row.file_size.max(0) as u64)
+      .sum();
+    let metas = copied
+      .iter()
+      .map(|(row, _)| BulkInsertMeta {
+        object_id: dst_parent_dir.to_string(),
+        file_id: row.file_id.clone(),
+        file_type: row.file_type.clone(),
+        file_size: row.file_size,
+        // the copies are stored under their own object, the content isn't shared with the blobs
+        // of the destination
+        content_hash: None,
+      })
+      .collect();
+    let inserted = async {
+      let mut tx = self.pg_pool.begin().await?;
+      if let Some(limit_bytes) = self.storage_limit {
+        // the usage of the destination may have grown while the objects were copied
+        check_workspace_storage_limit(&mut tx, dst_workspace_id, "", copied_size, limit_bytes)
+          .await?;
+      }
+      let inserted =
+        insert_blob_metadata_bulk(tx.deref_mut(), dst_workspace_id, metas, uploaded_by).await?;
+      tx.commit().await?;
+      Ok::<_, AppError>(inserted)
+    }
+    .await;
+    match inserted {
+      Ok(inserted) => {
+        self
+          .update_usage_cache(dst_workspace_id, inserted.inserted_bytes)
+          .await;
+        copied_file_ids.extend(copied.into_iter().map(|(row, _)| {
+          let new_file_id = copied_blob_file_id(dst_parent_dir, &row.file_id);
+          (row.file_id, new_file_id)
+        }));
+        Ok(copied_file_ids)
+      },
+      Err(err) => {
+        let dst_object_keys = copied
+          .into_iter()
+          .map(|(_, object_key)| object_key)
+          .collect();
+        self.delete_objects_with_retry(dst_object_keys).await;
+        Err(err)
+      },
+    }
+  }
+
+  /// The object storing the content of a blob. Files uploaded with the v1 api are stored under
+  /// `{workspace_id}/{parent_dir}/{file_id}` and recorded as `{parent_dir}_{file_id}`, while older
+  /// files are stored as `{workspace_id}/{file_id}`.
+  async fn blob_object_key(
+    &self,
+    workspace_id: &Uuid,
+    row: &AFBlobMetadataRow,
+  ) -> Result<String, AppError> {
+    if let Some(object_key) = &row.object_key {
+      return Ok(object_key.clone());
+    }
+    if let Some((parent_dir, file_id)) = row.file_id.split_once('_') {
+      let object_key = format!("{}/{}/{}", workspace_id, parent_dir, file_id);
+      match self.client.head_blob(&object_key).await {
+        Ok(_) => return Ok(object_key),
+        Err(AppError::RecordNotFound(_)) => {},
+        Err(err) => return Err(err),
+      }
+    }
+    Ok(format!("{}/{}", workspace_id, row.file_id))
+  }
+
+  /// Delete the blob along with its versions and variants. The object of a blob sharing its content is only
+  /// deleted with the last blob referencing it.
   pub async fn delete_blob(&self, key: impl BlobKey) -> Result<(), AppError> {
-    self.client.delete_blob(&key.object_key()).await?;
+    let file_id = key.blob_metadata_key();
+    let mut tx = self.pg_pool.begin().await?;
+    let released =
+      release_blob_contents(&mut tx, key.workspace_id(), slice::from_ref(&file_id)).await?;
+    delete_blob_metadata(&mut tx, key.workspace_id(), &file_id).await?;
+    let version_keys = delete_all_blob_versions(&mut tx, key.workspace_id(), &file_id).await?;
+    let variant_keys =
+      delete_blob_variants_of_files(&mut tx, key.workspace_id(), slice::from_ref(&file_id)).await?;
+    tx.commit().await?;
+    // the size of the deleted versions isn't known
+    self.invalidate_usage_cache(key.workspace_id()).await;
+
+    let object_keys = deleted_object_keys(&released, [(file_id, key.object_key())])
+      .chain(version_keys)
+      .chain(variant_keys)
+      .collect::<Vec<_>>();
+    if !object_keys.is_empty() {
+      self.client.delete_blobs(object_keys).await?;
+    }
+    Ok(())
+  }
+
+  /// Delete every blob attached to the object, along with their versions and variants, in one
+  /// statement and one batch of bucket requests. Returns the file ids of the deleted blobs.
+  pub async fn delete_object_blobs(
+    &self,
+    workspace_id: &Uuid,
+    object_id: &str,
+  ) -> Result<Vec<String>, AppError> {
+    let mut tx = self.pg_pool.begin().await?;
+    let file_ids = select_blob_file_ids_by_prefix(&mut tx, workspace_id, object_id).await?;
+    if file_ids.is_empty() {
+      return Ok(file_ids);
+    }
+    let released = release_blob_contents(&mut tx, workspace_id, &file_ids).await?;
+    let file_ids = delete_blob_metadata_bulk(&mut tx, workspace_id, &file_ids).await?;
+    let version_keys = delete_blob_versions_of_files(&mut tx, workspace_id, &file_ids).await?;
+    let variant_keys = delete_blob_variants_of_files(&mut tx, workspace_id, &file_ids).await?;
+    tx.commit().await?;
+    self.invalidate_usage_cache(workspace_id).await;
+
+    let prefix = format!("{}_", object_id);
+    let blob_keys = file_ids.iter().filter_map(|file_id| {
+      let object_key = format!(
+        "{}/{}/{}",
+        workspace_id,
+        object_id,
+        file_id.strip_prefix(&prefix)?
+      );
+      Some((file_id.clone(), object_key))
+    });
+    let object_keys = deleted_object_keys(&released, blob_keys)
+      .chain(version_keys)
+      .chain(variant_keys)
+      .collect();
+    self.delete_objects_with_retry(object_keys).await;
+    Ok(file_ids)
+  }
 
+  /// Delete the blobs stored under `parent_dir` with the given file ids, along with their
+  /// versions and variants: their metadata in one statement, then their objects in DeleteObjects
+  /// batches. Only the objects of the blobs whose metadata was deleted are removed from the
+  /// bucket, so the metadata never points to a deleted object. Returns the file ids of the deleted blobs.
+  pub async fn delete_blobs_bulk(
+    &self,
+    workspace_id: &Uuid,
+    parent_dir: &str,
+    file_ids: &[String],
+  ) -> Result<Vec<String>, AppError> {
+    let prefix = format!("{}_", parent_dir);
+    let metadata_keys = file_ids
+      .iter()
+      .map(|file_id| format!("{}{}", prefix, file_id))
+      .collect::<Vec<_>>();
     let mut tx = self.pg_pool.begin().await?;
-    delete_blob_metadata(&mut tx, key.workspace_id(), &key.blob_metadata_key()).await?;
+    let released = release_blob_contents(&mut tx, workspace_id, &metadata_keys).await?;
+    let deleted = delete_blob_metadata_bulk(&mut tx, workspace_id, &metadata_keys).await?;
+    if deleted.is_empty() {
+      return Ok(vec![]);
+    }
+    let version_keys = delete_blob_versions_of_files(&mut tx, workspace_id, &deleted).await?;
+    let variant_keys = delete_blob_variants_of_files(&mut tx, workspace_id, &deleted).await?;
     tx.commit().await?;
+    self.invalidate_usage_cache(workspace_id).await;
+
+    let deleted_file_ids = deleted
+      .iter()
+      .filter_map(|key| key.strip_prefix(&prefix).map(str::to_string))
+      .collect::<Vec<_>>();
+    let blob_keys = deleted_file_ids.iter().map(|file_id| {
+      (
+        format!("{}{}", prefix, file_id),
+        format!("{}/{}/{}", workspace_id, parent_dir, file_id),
+      )
+    });
+    let object_keys = deleted_object_keys(&released, blob_keys)
+      .chain(version_keys)
+      .chain(variant_keys)
+      .collect();
+    self.delete_objects_with_retry(object_keys).await;
+    Ok(deleted_file_ids)
+  }
+
+  /// Delete the objects returned by [crate::collab::delete_collab_cascade], once its transaction
+  /// is committed.
+  pub async fn delete_cascaded_objects(&self, workspace_id: &Uuid, object_keys: Vec<String>) {
+    self.invalidate_usage_cache(workspace_id).await;
+    self.delete_objects_with_retry(object_keys).await;
+  }
+
+  /// Delete the objects whose metadata was deleted. The keys the bucket fails to delete are
+  /// retried, and the ones still failing after the last attempt are logged: they're left to the
+  /// orphan blob collection, since their metadata is already gone.
+  async fn delete_objects_with_retry(&self, object_keys: Vec<String>) {
+    let mut pending = object_keys;
+    for attempt in 1..=DELETE_OBJECTS_ATTEMPTS {
+      if pending.is_empty() {
+        return;
+      }
+      match self.client.delete_objects_batch(&pending).await {
+        Ok(failed) => pending = failed,
+        Err(err) => warn!("failed to delete {} objects: {}", pending.len(), err),
+      }
+      if !pending.is_empty() && attempt < DELETE_OBJECTS_ATTEMPTS {
+        warn!(
+          "retrying the deletion of {} objects, attempt {}",
+          pending.len(),
+          attempt + 1
+        );
+        tokio::time::sleep(Duration::from_millis(200 * attempt as u64)).await;
+      }
+    }
+    if !pending.is_empty() {
+      error!(
+        "failed to delete {} objects after {} attempts: {:?}",
+        pending.len(),
+        DELETE_OBJECTS_ATTEMPTS,
+        pending
+      );
+    }
+  }
+
+  pub async fn list_blob_versions(
+    &self,
+    key: &impl BlobKey,
+  ) -> Result<Vec<AFBlobVersionRow>, AppError> {
+    select_blob_versions(&self.pg_pool, key.workspace_id(), &key.blob_metadata_key()).await
+  }
+
+  /// Replace the current content of the blob with the given archived version. The content being
+  /// replaced is archived as a new version, so a restore can itself be undone.
+  #[instrument(skip_all, err)]
+  pub async fn restore_blob_version(
+    &self,
+    key: &impl BlobKey,
+    version: i64,
+    uploaded_by: Option<i64>,
+  ) -> Result<(), AppError> {
+    if !self.is_versioning_enabled() {
+      return Err(AppError::InvalidRequest(
+        "blob versioning is not enabled".to_string(),
+      ));
+    }
+    let target = select_blob_version(
+      &self.pg_pool,
+      key.workspace_id(),
+      &key.blob_metadata_key(),
+      version,
+    )
+    .await?;
+
+    // the version keeps its own object, which is pruned along with it
+    self
+      .overwrite_blob(
+        key,
+        blob_revision_object_key(&key.object_key()),
+        BlobAttributes {
+          uploaded_by,
+          expires_at: None,
+        },
+        |object_key| async move {
+          self
+            .client
+            .copy_blob(&target.object_key, &object_key)
+            .await?;
+          Ok((target.file_size as usize, target.file_type))
+        },
+      )
+      .await
+  }
+
+  /// Write the new content of an existing blob with `write` under `object_key`, a fresh key,
+  /// then point the blob metadata at it in a short transaction, which archives the previous
+  /// object as a version. No object is replaced in the bucket, so a failure leaves the blob as it
+  /// was, and nothing slow runs while its metadata is locked. The storage limit is checked in
+  /// that transaction: the new object is deleted when it doesn't fit. Versions outside the
+  /// retention policy are pruned afterwards.
+  async fn overwrite_blob<K, F, Fut>(
+    &self,
+    key: &K,
+    object_key: String,
+    attributes: BlobAttributes,
+    write: F,
+  ) -> Result<(), AppError>
+  where
+    K: BlobKey,
+    F: FnOnce(String) -> Fut,
+    Fut: std::future::Future<Output = Result<(usize, String), AppError>>,
+  {
+    let policy = match &self.version_policy {
+      None => {
+        return Err(AppError::Internal(anyhow!(
+          "blob versioning is not enabled"
+        )))
+      },
+      Some(policy) => policy,
+    };
+    let workspace_id = key.workspace_id();
+    let file_id = key.blob_metadata_key();
+
+    let (file_size, file_type) = write(object_key.clone()).await?;
+    let swapped = async {
+      let mut tx = self.pg_pool.begin().await?;
+      let current = select_blob_metadata_for_update(&mut tx, workspace_id, &file_id).await?;
+      if current
+        .as_ref()
+        .is_some_and(|current| current.content_hash.is_some())
+      {
+        return Err(AppError::InvalidRequest(format!(
+          "blob {} shares its content with other blobs and can't be overwritten",
+          file_id
+        )));
+      }
+      if let Some(limit_bytes) = self.storage_limit {
+        // the previous content is archived, so it keeps counting toward the usage
+        let archived_size = current
+          .as_ref()
+          .map_or(0, |current| current.file_size as u64);
+        check_workspace_storage_limit(
+          &mut tx,
+          workspace_id,
+          &file_id,
+          file_size as u64 + archived_size,
+          limit_bytes,
+        )
+        .await?;
+      }
+      if let Some(current) = current {
+        let version = select_next_blob_version(&mut tx, workspace_id, &file_id).await?;
+        let version_key = current.object_key.unwrap_or_else(|| key.object_key());
+        insert_blob_version(
+          &mut tx,
+          workspace_id,
+          &file_id,
+          version,
+          &version_key,
+          &current.file_type,
+          current.file_size,
+        )
+        .await?;
+      }
+      update_blob_metadata(
+        &mut tx,
+        workspace_id,
+        &file_id,
+        &file_type,
+        file_size as i64,
+        &object_key,
+        attributes,
+      )
+      .await?;
+      let expired_keys = delete_expired_blob_versions(
+        &mut tx,
+        workspace_id,
+        &file_id,
+        policy.max_versions,
+        policy.max_age_days,
+      )
+      .await?;
+      tx.commit().await?;
+      Ok(expired_keys)
+    }
+    .await;
+    let expired_keys = match swapped {
+      Ok(expired_keys) => expired_keys,
+      Err(err) => {
+        self.delete_objects_with_retry(vec![object_key]).await;
+        return Err(err);
+      },
+    };
+    self.invalidate_usage_cache(workspace_id).await;
+
+    if !expired_keys.is_empty() {
+      info!(
+        "pruned {} versions of blob: {}/{}",
+        expired_keys.len(),
+        workspace_id,
+        file_id
+      );
+      self.client.delete_blobs(expired_keys).await?;
+    }
     Ok(())
   }
 
+  /// The metadata of a blob which can be served. The expired blobs are missing, even before the
+  /// worker deletes them.
   pub async fn get_blob_metadata(
     &self,
     workspace_id: &Uuid,
     metadata_key: &str,
   ) -> Result<AFBlobMetadataRow, AppError> {
     let metadata = get_blob_metadata(&self.pg_pool, workspace_id, metadata_key).await?;
+    if metadata
+      .expires_at
+      .is_some_and(|expires_at| expires_at <= Utc::now())
+    {
+      return Err(AppError::RecordNotFound(format!(
+        "blob {} expired",
+        metadata_key
+      )));
+    }
     Ok(metadata)
   }
 
@@ -146,12 +961,86 @@ where
     Ok(blob)
   }
 
+  /// Read the content of a blob from the object its metadata points at, which is the object of
+  /// another blob when the content is shared.
+  pub async fn get_blob_with_metadata(
+    &self,
+    key: &impl BlobKey,
+    metadata: &AFBlobMetadataRow,
+  ) -> Result<Vec<u8>, AppError> {
+    let object_key = metadata
+      .object_key
+      .clone()
+      .unwrap_or_else(|| key.object_key());
+    let blob = self.client.get_blob(&object_key).await?.to_blob();
+    Ok(blob)
+  }
+
+  /// The variant of an image blob along with its content. `None` while the variant isn't
+  /// generated, or is stale.
+  pub async fn get_blob_variant(
+    &self,
+    workspace_id: &Uuid,
+    file_id: &str,
+    variant: BlobVariant,
+  ) -> Result<Option<(AFBlobVariantRow, Vec<u8>)>, AppError> {
+    let Some(row) = select_blob_variant(&self.pg_pool, workspace_id, file_id, variant).await?
+    else {
+      return Ok(None);
+    };
+    let blob = self.client.get_blob(&row.object_key).await?.to_blob();
+    Ok(Some((row, blob)))
+  }
+
+  /// Read the bytes of a blob from `start` to `end`, both included, as
+  /// [Self::get_blob_with_metadata] reads the whole content.
+  pub async fn get_blob_range_with_metadata(
+    &self,
+    key: &impl BlobKey,
+    metadata: &AFBlobMetadataRow,
+    start: u64,
+    end: u64,
+  ) -> Result<Vec<u8>, AppError> {
+    let object_key = metadata
+      .object_key
+      .clone()
+      .unwrap_or_else(|| key.object_key());
+    let blob = self
+      .client
+      .get_blob_range(&object_key, start, end)
+      .await?
+      .to_blob();
+    Ok(blob)
+  }
+
+  /// Start a multipart upload of a blob. Its parts are recorded as they are uploaded, so that the
+  /// client can resume the upload with [Self::list_upload_parts] after an interruption.
   pub async fn create_upload(
     &self,
     key: impl BlobKey,
     req: CreateUploadRequest,
   ) -> Result<CreateUploadResponse, AppError> {
-    self.client.create_upload(&key.object_key(), req).await
+    let file_type = req.content_type.clone();
+    // the new content of an existing blob is uploaded next to it, see [Self::overwrite_blob]
+    let object_key = if self.is_versioning_enabled()
+      && is_blob_metadata_exists(&self.pg_pool, key.workspace_id(), &key.blob_metadata_key())
+        .await?
+    {
+      blob_revision_object_key(&key.object_key())
+    } else {
+      key.object_key()
+    };
+    let resp = self.client.create_upload(&object_key, req).await?;
+    insert_multipart_upload(
+      &self.pg_pool,
+      &resp.upload_id,
+      key.workspace_id(),
+      &key.blob_metadata_key(),
+      &object_key,
+      &file_type,
+    )
+    .await?;
+    Ok(resp)
   }
 
   pub async fn upload_part(
@@ -159,15 +1048,102 @@ where
     key: impl BlobKey,
     req: UploadPartData,
   ) -> Result<UploadPartResponse, AppError> {
-    self.client.upload_part(&key.object_key(), req).await
+    let upload = self.get_multipart_upload(&key, &req.upload_id).await?;
+    let part_size = req.body.len();
+    let resp = self.client.upload_part(&upload.object_key, req).await?;
+    upsert_multipart_upload_part(
+      &self.pg_pool,
+      &upload.upload_id,
+      resp.part_num,
+      &resp.e_tag,
+      part_size,
+    )
+    .await?;
+    Ok(resp)
+  }
+
+  /// Return the parts uploaded so far for a multipart upload of the blob.
+  pub async fn list_upload_parts(
+    &self,
+    key: impl BlobKey,
+    upload_id: &str,
+  ) -> Result<ListUploadPartsResponse, AppError> {
+    let upload = self.get_multipart_upload(&key, upload_id).await?;
+    let parts = select_multipart_upload_parts(&self.pg_pool, &upload.upload_id)
+      .await?
+      .into_iter()
+      .map(|part| UploadedPart {
+        part_number: part.part_number,
+        e_tag: part.e_tag,
+        size: part.part_size as u64,
+      })
+      .collect();
+    Ok(ListUploadPartsResponse {
+      file_id: key.blob_metadata_key(),
+      upload_id: upload.upload_id,
+      parts,
+    })
   }
 
+  async fn get_multipart_upload(
+    &self,
+    key: &impl BlobKey,
+    upload_id: &str,
+  ) -> Result<AFBlobMultipartUploadRow, AppError> {
+    select_multipart_upload(
+      &self.pg_pool,
+      key.workspace_id(),
+      &key.blob_metadata_key(),
+      upload_id,
+    )
+    .await?
+    .ok_or_else(|| {
+      AppError::RecordNotFound(format!(
+        "no multipart upload {} for blob {}",
+        upload_id,
+        key.blob_metadata_key()
+      ))
+    })
+  }
+
+  /// Complete a multipart upload, giving the blob its metadata with the size of the uploaded
+  /// object. The storage limit of the workspace is enforced then, since the size of the blob is
+  /// only known once all its parts are uploaded: an upload which goes over it is discarded.
   pub async fn complete_upload(
     &self,
     key: impl BlobKey,
     req: CompleteUploadRequest,
+    attributes: BlobAttributes,
   ) -> Result<(), AppError> {
+    let upload = self.get_multipart_upload(&key, &req.upload_id).await?;
+    if upload.object_key != key.object_key() {
+      // the upload was started over an existing blob, see [Self::create_upload]
+      self
+        .overwrite_blob(
+          &key,
+          upload.object_key.clone(),
+          attributes,
+          |object_key| async move { self.client.complete_upload(&object_key, req).await },
+        )
+        .await?;
+      delete_multipart_upload(&self.pg_pool, &upload.upload_id).await?;
+      return Ok(());
+    }
+    if self.is_versioning_enabled()
+      && is_blob_metadata_exists(&self.pg_pool, key.workspace_id(), &key.blob_metadata_key())
+        .await?
+    {
+      // The blob was created after the upload started. Completing the upload would replace the
+      // object of the blob instead of archiving it, so the client has to upload it again. The
+      // upload is left to expire, the worker aborts it along with its parts.
+      return Err(AppError::RecordAlreadyExists(format!(
+        "blob {} was created during its upload",
+        key.blob_metadata_key()
+      )));
+    }
+
     if is_blob_metadata_exists(&self.pg_pool, key.workspace_id(), &key.object_key()).await? {
+      // the upload is left to expire, the worker aborts it along with its parts
       warn!(
         "file already exists, workspace_id: {}, request: {}",
         key.workspace_id(),
@@ -177,15 +1153,164 @@ where
     }
 
     let (content_length, content_type) =
-      self.client.complete_upload(&key.object_key(), req).await?;
-    insert_blob_metadata(
-      &self.pg_pool,
-      &key.blob_metadata_key(),
-      key.workspace_id(),
-      &content_type,
-      content_length,
+      self.client.complete_upload(&upload.object_key, req).await?;
+    // the parts are merged into the object by now, the upload can no longer be resumed
+    delete_multipart_upload(&self.pg_pool, &upload.upload_id).await?;
+    match self.storage_limit {
+      Some(limit_bytes) => {
+        let inserted = self
+          .insert_blob_metadata_with_limit(
+            &key,
+            &content_type,
+            content_length,
+            limit_bytes,
+            attributes,
+          )
+          .await;
+        if let Err(err) = inserted {
+          self.delete_objects_with_retry(vec![key.object_key()]).await;
+          return Err(err);
+        }
+      },
+      None => {
+        let delta = insert_blob_metadata(
+          &self.pg_pool,
+          &key.blob_metadata_key(),
+          key.workspace_id(),
+          &content_type,
+          content_length,
+          None,
+          attributes,
+        )
+        .await?;
+        self.update_usage_cache(key.workspace_id(), delta).await;
+      },
+    }
+    Ok(())
+  }
+
+  /// Reserve the declared size of a blob which the client uploads straight to the bucket, and
+  /// return the presigned url to upload it to. The blob only gets its metadata once the upload is
+  /// completed with [Self::complete_presigned_upload], until then its declared size counts toward
+  /// the storage limit of the workspace.
+  #[instrument(skip_all, err)]
+  pub async fn create_presigned_upload(
+    &self,
+    key: impl BlobKey,
+    req: PresignedUploadRequest,
+  ) -> Result<PresignedUploadResponse, AppError> {
+    let workspace_id = key.workspace_id();
+    let file_id = key.blob_metadata_key();
+    if is_blob_metadata_exists(&self.pg_pool, workspace_id, &file_id).await? {
+      return Err(AppError::RecordAlreadyExists(format!(
+        "blob {} already exists",
+        file_id
+      )));
+    }
+
+    let object_key = key.object_key();
+    let mut tx = self.pg_pool.begin().await?;
+    if let Some(limit_bytes) = self.storage_limit {
+      check_workspace_storage_limit(
+        &mut tx,
+        workspace_id,
+        &file_id,
+        req.content_length,
+        limit_bytes,
+      )
+      .await?;
+    }
+    insert_pending_blob_metadata(
+      &mut tx,
+      workspace_id,
+      &file_id,
+      &object_key,
+      &req.content_type,
+      req.content_length,
+    )
+    .await?;
+    let presigned_url = self
+      .client
+      .presign_put_blob(
+        &object_key,
+        &req.content_type,
+        req.content_length,
+        PRESIGNED_UPLOAD_EXPIRATION,
+      )
+      .await?;
+    tx.commit().await?;
+    Ok(PresignedUploadResponse {
+      file_id: req.file_id,
+      presigned_url,
+      expires_at: Utc::now().timestamp() + PRESIGNED_UPLOAD_EXPIRATION.as_secs() as i64,
+    })
+  }
+
+  /// Give its metadata to a blob uploaded with a presigned url, once its object is in the bucket
+  /// with the declared size. The storage limit is checked again, since it may have been lowered
+  /// since the url was issued: an upload which no longer fits is discarded.
+  #[instrument(skip_all, err)]
+  pub async fn complete_presigned_upload(
+    &self,
+    key: impl BlobKey,
+    attributes: BlobAttributes,
+  ) -> Result<(), AppError> {
+    let workspace_id = key.workspace_id();
+    let file_id = key.blob_metadata_key();
+    let pending = select_pending_blob_metadata(&self.pg_pool, workspace_id, &file_id)
+      .await?
+      .ok_or_else(|| AppError::RecordNotFound(format!("no pending upload for blob {}", file_id)))?;
+    let object = self.client.head_blob(&pending.object_key).await?;
+    if object.size != pending.file_size {
+      return Err(AppError::InvalidRequest(format!(
+        "blob {} was declared with {} bytes, but {} bytes were uploaded",
+        file_id, pending.file_size, object.size
+      )));
+    }
+
+    let mut tx = self.pg_pool.begin().await?;
+    if let Some(limit_bytes) = self.storage_limit {
+      let checked = check_workspace_storage_limit(
+        &mut tx,
+        workspace_id,
+        &file_id,
+        object.size as u64,
+        limit_bytes,
+      )
+      .await;
+      if let Err(err) = checked {
+        if matches!(err, AppError::StorageSpaceNotEnough) {
+          delete_pending_blob_metadata(&mut tx, workspace_id, &file_id).await?;
+          tx.commit().await?;
+          self
+            .delete_objects_with_retry(vec![pending.object_key])
+            .await;
+        }
+        return Err(err);
+      }
+    }
+    // the upload may have expired, or been issued again with another size, in the meantime
+    match delete_pending_blob_metadata(&mut tx, workspace_id, &file_id).await? {
+      Some(row) if row.file_size == object.size => {},
+      _ => {
+        return Err(AppError::RecordNotFound(format!(
+          "no pending upload for blob {}",
+          file_id
+        )))
+      },
+    }
+    let delta = insert_blob_metadata(
+      tx.deref_mut(),
+      &file_id,
+      workspace_id,
+      &pending.file_type,
+      object.size as usize,
+      None,
+      attributes,
     )
     .await?;
+    tx.commit().await?;
+    self.update_usage_cache(workspace_id, delta).await;
     Ok(())
   }
 }
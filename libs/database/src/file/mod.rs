@@ -1,5 +1,6 @@
 mod file_storage;
 pub mod s3_client_impl;
+pub mod s3_retry;
 mod utils;
 
 pub use file_storage::*;
@@ -1,15 +1,18 @@
-use crate::file::{BucketClient, BucketStorage, ResponseBlob};
+use crate::file::s3_retry::{CircuitBreakerConfig, RetryPolicy, S3Metrics, S3RequestExecutor};
+use crate::file::{BucketClient, BucketObject, BucketStorage, ResponseBlob};
+use crate::operation_delay::OperationDelay;
 use anyhow::anyhow;
 use app_error::AppError;
 use async_trait::async_trait;
 use aws_sdk_s3::operation::delete_object::DeleteObjectOutput;
+use bytes::Bytes;
+use chrono::DateTime;
 
 use std::ops::Deref;
+use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
-use aws_sdk_s3::error::SdkError;
 use aws_sdk_s3::operation::delete_objects::DeleteObjectsOutput;
-use aws_sdk_s3::operation::get_object::GetObjectError;
 
 use aws_sdk_s3::presigning::PresigningConfig;
 use aws_sdk_s3::primitives::ByteStream;
@@ -19,11 +22,30 @@ use database_entity::file_dto::{
   CompleteUploadRequest, CreateUploadRequest, CreateUploadResponse, UploadPartData,
   UploadPartResponse,
 };
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 
 use tracing::{error, trace};
 
 pub type S3BucketStorage = BucketStorage<AwsS3BucketClientImpl>;
 
+/// Characters of an object key left as they are in a copy source, every other one is percent
+/// encoded. The `/` separating the key segments must stay unencoded.
+const COPY_SOURCE_KEY: &AsciiSet = &NON_ALPHANUMERIC
+  .remove(b'/')
+  .remove(b'-')
+  .remove(b'.')
+  .remove(b'_')
+  .remove(b'~');
+
+/// Returns the `x-amz-copy-source` of the object, which S3 expects URL encoded.
+pub fn copy_source(bucket: &str, object_key: &str) -> String {
+  format!(
+    "{}/{}",
+    bucket,
+    utf8_percent_encode(object_key, COPY_SOURCE_KEY)
+  )
+}
+
 impl S3BucketStorage {
   pub fn from_bucket_impl(client: AwsS3BucketClientImpl, pg_pool: sqlx::PgPool) -> Self {
     Self::new(client, pg_pool)
@@ -36,6 +58,7 @@ pub struct AwsS3BucketClientImpl {
   bucket: String,
   endpoint: String,
   presigned_url_endpoint: Option<String>,
+  executor: Arc<S3RequestExecutor>,
 }
 
 impl AwsS3BucketClientImpl {
@@ -51,7 +74,75 @@ impl AwsS3BucketClientImpl {
       bucket,
       endpoint,
       presigned_url_endpoint,
+      executor: Arc::new(S3RequestExecutor::new(
+        CircuitBreakerConfig::default(),
+        Arc::new(S3Metrics::default()),
+      )),
+    }
+  }
+
+  /// Report retries and circuit breaker transitions to the given metrics.
+  pub fn with_metrics(mut self, metrics: Arc<S3Metrics>) -> Self {
+    let operation_delay = self.executor.operation_delay().cloned();
+    self.executor = Arc::new(
+      S3RequestExecutor::new(CircuitBreakerConfig::default(), metrics)
+        .with_operation_delay(operation_delay),
+    );
+    self
+  }
+
+  /// Waits for the given [OperationDelay] before each request is sent to S3.
+  pub fn with_operation_delay(mut self, operation_delay: Arc<dyn OperationDelay>) -> Self {
+    let metrics = self.executor.metrics().clone();
+    self.executor = Arc::new(
+      S3RequestExecutor::new(CircuitBreakerConfig::default(), metrics)
+        .with_operation_delay(Some(operation_delay)),
+    );
+    self
+  }
+
+  /// Uploads `content` with a single PUT. The request is only retried when the content is already
+  /// in memory, since a stream can not be read twice.
+  async fn put_object(
+    &self,
+    object_key: &str,
+    content: ByteStream,
+    content_type: &str,
+  ) -> Result<(), AppError> {
+    match content.bytes().map(Bytes::copy_from_slice) {
+      Some(data) => {
+        self
+          .executor
+          .execute("put_object", RetryPolicy::IDEMPOTENT, || {
+            self
+              .client
+              .put_object()
+              .bucket(&self.bucket)
+              .key(object_key)
+              .body(ByteStream::from(data.clone()))
+              .content_type(content_type)
+              .send()
+          })
+          .await?;
+      },
+      None => {
+        let mut content = Some(content);
+        self
+          .executor
+          .execute("put_object", RetryPolicy::NO_RETRY, || {
+            self
+              .client
+              .put_object()
+              .bucket(&self.bucket)
+              .key(object_key)
+              .body(content.take().unwrap_or_default())
+              .content_type(content_type)
+              .send()
+          })
+          .await?;
+      },
     }
+    Ok(())
   }
 
   pub async fn gen_presigned_url(
@@ -60,7 +151,23 @@ impl AwsS3BucketClientImpl {
     content_length: u64,
     expires_in_secs: u64,
   ) -> Result<String, AppError> {
-    let expires_in = Duration::from_secs(expires_in_secs);
+    self
+      .gen_presigned_put_url(
+        s3_key,
+        "application/zip",
+        content_length,
+        Duration::from_secs(expires_in_secs),
+      )
+      .await
+  }
+
+  async fn gen_presigned_put_url(
+    &self,
+    s3_key: &str,
+    content_type: &str,
+    content_length: u64,
+    expires_in: Duration,
+  ) -> Result<String, AppError> {
     let config = PresigningConfig::builder()
       .start_time(SystemTime::now())
       .expires_in(expires_in)
@@ -77,7 +184,7 @@ impl AwsS3BucketClientImpl {
       .put_object()
       .bucket(&self.bucket)
       .key(s3_key)
-      .content_type("application/zip")
+      .content_type(content_type)
       .content_length(content_length as i64)
       .presigned(config)
       .await
@@ -108,25 +215,31 @@ impl AwsS3BucketClientImpl {
   ) -> Result<(usize, String), AppError> {
     // Complete the multipart upload
     let _ = self
-      .client
-      .complete_multipart_upload()
-      .bucket(&self.bucket)
-      .key(object_key)
-      .upload_id(upload_id)
-      .multipart_upload(completed_multipart_upload)
-      .send()
-      .await
-      .map_err(|e| AppError::Internal(anyhow::anyhow!(e)))?;
+      .executor
+      .execute("complete_multipart_upload", RetryPolicy::NO_RETRY, || {
+        self
+          .client
+          .complete_multipart_upload()
+          .bucket(&self.bucket)
+          .key(object_key)
+          .upload_id(upload_id)
+          .multipart_upload(completed_multipart_upload.clone())
+          .send()
+      })
+      .await?;
 
     // Retrieve the object metadata using head_object
     let head_object_result = self
-      .client
-      .head_object()
-      .bucket(&self.bucket)
-      .key(object_key)
-      .send()
-      .await
-      .map_err(|e| AppError::Internal(anyhow::anyhow!(e)))?;
+      .executor
+      .execute("head_object", RetryPolicy::IDEMPOTENT, || {
+        self
+          .client
+          .head_object()
+          .bucket(&self.bucket)
+          .key(object_key)
+          .send()
+      })
+      .await?;
 
     let content_len = head_object_result
       .content_length()
@@ -144,6 +257,46 @@ impl AwsS3BucketClientImpl {
 
     Ok((content_len as usize, content_type))
   }
+
+  /// Read an object, or the bytes of it given by a `bytes=<start>-<end>` range.
+  async fn get_object(
+    &self,
+    object_key: &str,
+    range: Option<String>,
+  ) -> Result<S3ResponseData, AppError> {
+    let output = self
+      .executor
+      .execute("get_object", RetryPolicy::IDEMPOTENT, || {
+        self
+          .client
+          .get_object()
+          .bucket(&self.bucket)
+          .key(object_key)
+          .set_range(range.clone())
+          .send()
+      })
+      .await
+      .map_err(|err| match err {
+        AppError::RecordNotFound(_) => {
+          AppError::RecordNotFound(format!("blob not found for key:{object_key}"))
+        },
+        err => err,
+      })?;
+
+    match output.body.collect().await {
+      Ok(body) => {
+        let data = body.into_bytes().to_vec();
+
+        trace!("get object from S3: {} ({} bytes)", object_key, data.len());
+
+        Ok(S3ResponseData::new_with_data(data, output.content_type))
+      },
+      Err(err) => Err(AppError::ServiceTemporaryUnavailable(format!(
+        "Failed to collect body: {}",
+        err
+      ))),
+    }
+  }
 }
 
 #[async_trait]
@@ -157,20 +310,12 @@ impl BucketClient for AwsS3BucketClientImpl {
     content_type: Option<&str>,
   ) -> Result<(), AppError> {
     self
-      .client
-      .put_object()
-      .bucket(&self.bucket)
-      .key(object_key)
-      .body(content)
-      .content_type(content_type.unwrap_or("application/octet-stream"))
-      .send()
-      .await
-      .map_err(|err| match err {
-        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ServiceError(_) => {
-          AppError::ServiceTemporaryUnavailable(format!("Failed to upload object to S3: {}", err))
-        },
-        _ => AppError::Internal(anyhow!("Failed to upload object to S3: {}", err)),
-      })?;
+      .put_object(
+        object_key,
+        content,
+        content_type.unwrap_or("application/octet-stream"),
+      )
+      .await?;
 
     trace!("put object to S3: {}", object_key);
 
@@ -183,36 +328,47 @@ impl BucketClient for AwsS3BucketClientImpl {
     stream: ByteStream,
     content_type: &str,
   ) -> Result<(), AppError> {
-    self
-      .client
-      .put_object()
-      .bucket(&self.bucket)
-      .key(object_key)
-      .body(stream)
-      .content_type(content_type)
-      .send()
-      .await
-      .map_err(|err| match err {
-        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ServiceError(_) => {
-          AppError::ServiceTemporaryUnavailable(format!("Failed to upload object to S3: {}", err))
-        },
-        _ => AppError::Internal(anyhow!("Failed to upload object to S3: {}", err)),
-      })?;
+    self.put_object(object_key, stream, content_type).await?;
 
     trace!("put object to S3: {} ({})", object_key, content_type);
 
     Ok(())
   }
 
+  async fn copy_blob(&self, from_object_key: &str, to_object_key: &str) -> Result<(), AppError> {
+    self
+      .executor
+      .execute("copy_object", RetryPolicy::IDEMPOTENT, || {
+        self
+          .client
+          .copy_object()
+          .bucket(&self.bucket)
+          .copy_source(copy_source(&self.bucket, from_object_key))
+          .key(to_object_key)
+          .send()
+      })
+      .await?;
+
+    trace!(
+      "copied object in S3: {} -> {}",
+      from_object_key,
+      to_object_key
+    );
+    Ok(())
+  }
+
   async fn delete_blob(&self, object_key: &str) -> Result<Self::ResponseData, AppError> {
     let output = self
-      .client
-      .delete_object()
-      .bucket(&self.bucket)
-      .key(object_key)
-      .send()
-      .await
-      .map_err(|err| anyhow!("Failed to delete object to S3: {}", err))?;
+      .executor
+      .execute("delete_object", RetryPolicy::IDEMPOTENT, || {
+        self
+          .client
+          .delete_object()
+          .bucket(&self.bucket)
+          .key(object_key)
+          .send()
+      })
+      .await?;
 
     trace!("deleted object from S3: {}", object_key);
 
@@ -234,19 +390,22 @@ impl BucketClient for AwsS3BucketClientImpl {
         delete_object_ids.push(obj_id);
       }
       let len = delete_object_ids.len();
+      let delete = Delete::builder()
+        .set_objects(Some(delete_object_ids))
+        .build()
+        .map_err(|err| {
+          AppError::Internal(anyhow!("Failed to create delete object request: {}", err))
+        })?;
       let res = self
-        .client
-        .delete_objects()
-        .bucket(&self.bucket)
-        .delete(
-          Delete::builder()
-            .set_objects(Some(delete_object_ids))
-            .build()
-            .map_err(|err| {
-              AppError::Internal(anyhow!("Failed to create delete object request: {}", err))
-            })?,
-        )
-        .send()
+        .executor
+        .execute("delete_objects", RetryPolicy::IDEMPOTENT, || {
+          self
+            .client
+            .delete_objects()
+            .bucket(&self.bucket)
+            .delete(delete.clone())
+            .send()
+        })
         .await;
 
       match res {
@@ -262,39 +421,117 @@ impl BucketClient for AwsS3BucketClientImpl {
     Ok(())
   }
 
+  async fn delete_objects_batch(&self, object_keys: &[String]) -> Result<Vec<String>, AppError> {
+    const BATCH_SIZE: usize = 1000;
+    let mut failed = vec![];
+    for chunk in object_keys.chunks(BATCH_SIZE) {
+      let objects = chunk
+        .iter()
+        .map(|key| {
+          ObjectIdentifier::builder().key(key).build().map_err(|err| {
+            AppError::Internal(anyhow!("Failed to create object identifier: {}", err))
+          })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+      let delete = Delete::builder()
+        .set_objects(Some(objects))
+        .quiet(true)
+        .build()
+        .map_err(|err| {
+          AppError::Internal(anyhow!("Failed to create delete object request: {}", err))
+        })?;
+      let res = self
+        .executor
+        .execute("delete_objects", RetryPolicy::IDEMPOTENT, || {
+          self
+            .client
+            .delete_objects()
+            .bucket(&self.bucket)
+            .delete(delete.clone())
+            .send()
+        })
+        .await;
+
+      match res {
+        Ok(output) => {
+          for error in output.errors.unwrap_or_default() {
+            tracing::warn!(
+              "failed to delete object {:?}: {:?} {:?}",
+              error.key(),
+              error.code(),
+              error.message()
+            );
+            if let Some(key) = error.key {
+              failed.push(key);
+            }
+          }
+        },
+        Err(err) => {
+          tracing::warn!("failed to delete {} objects: {}", chunk.len(), err);
+          failed.extend(chunk.iter().cloned());
+        },
+      }
+    }
+    trace!(
+      "deleted {} objects from S3",
+      object_keys.len().saturating_sub(failed.len())
+    );
+    Ok(failed)
+  }
+
   async fn get_blob(&self, object_key: &str) -> Result<Self::ResponseData, AppError> {
-    match self
-      .client
-      .get_object()
-      .bucket(&self.bucket)
-      .key(object_key)
-      .send()
-      .await
-    {
-      Ok(output) => match output.body.collect().await {
-        Ok(body) => {
-          let data = body.into_bytes().to_vec();
+    self.get_object(object_key, None).await
+  }
 
-          trace!("get object from S3: {} ({} bytes)", object_key, data.len());
+  async fn get_blob_range(
+    &self,
+    object_key: &str,
+    start: u64,
+    end: u64,
+  ) -> Result<Self::ResponseData, AppError> {
+    self
+      .get_object(object_key, Some(format!("bytes={}-{}", start, end)))
+      .await
+  }
 
-          Ok(S3ResponseData::new_with_data(data, output.content_type))
+  async fn head_blob(&self, object_key: &str) -> Result<BucketObject, AppError> {
+    let output = self
+      .executor
+      .execute("head_object", RetryPolicy::IDEMPOTENT, || {
+        self
+          .client
+          .head_object()
+          .bucket(&self.bucket)
+          .key(object_key)
+          .send()
+      })
+      .await
+      .map_err(|err| match err {
+        AppError::RecordNotFound(_) => {
+          AppError::RecordNotFound(format!("blob not found for key:{object_key}"))
         },
-        Err(err) => Err(AppError::from(anyhow!("Failed to collect body: {}", err))),
-      },
-      Err(SdkError::ServiceError(service_err)) => match service_err.err() {
-        GetObjectError::NoSuchKey(_) => Err(AppError::RecordNotFound(format!(
-          "blob not found for key:{object_key}"
-        ))),
-        _ => Err(AppError::from(anyhow!(
-          "Failed to get object from S3: {:?}",
-          service_err
-        ))),
-      },
-      Err(err) => Err(AppError::from(anyhow!(
-        "Failed to get object from S3: {}",
-        err
-      ))),
-    }
+        err => err,
+      })?;
+
+    Ok(BucketObject {
+      key: object_key.to_string(),
+      size: output.content_length.unwrap_or(0),
+      last_modified: output
+        .last_modified
+        .and_then(|t| DateTime::from_timestamp(t.secs(), t.subsec_nanos())),
+    })
+  }
+
+  async fn presign_put_blob(
+    &self,
+    object_key: &str,
+    content_type: &str,
+    content_length: u64,
+    expires_in: Duration,
+  ) -> Result<String, AppError> {
+    self
+      .gen_presigned_put_url(object_key, content_type, content_length, expires_in)
+      .await
   }
 
   /// Create a new upload session
@@ -307,14 +544,17 @@ impl BucketClient for AwsS3BucketClientImpl {
     trace!("creating multi-part upload to S3: {} - {}", object_key, req);
 
     let multipart_upload_res = self
-      .client
-      .create_multipart_upload()
-      .bucket(&self.bucket)
-      .key(object_key)
-      .content_type(req.content_type)
-      .send()
-      .await
-      .map_err(|err| anyhow!(format!("Failed to create upload: {:?}", err)))?;
+      .executor
+      .execute("create_multipart_upload", RetryPolicy::NO_RETRY, || {
+        self
+          .client
+          .create_multipart_upload()
+          .bucket(&self.bucket)
+          .key(object_key)
+          .content_type(&req.content_type)
+          .send()
+      })
+      .await?;
 
     match multipart_upload_res.upload_id {
       None => Err(anyhow!("Failed to create upload: upload_id is None").into()),
@@ -334,18 +574,21 @@ impl BucketClient for AwsS3BucketClientImpl {
       return Err(AppError::InvalidRequest("body is empty".to_string()));
     }
     trace!("multi-part upload to s3: {} - {}", object_key, req,);
-    let body = ByteStream::from(req.body);
+    let body = Bytes::from(req.body);
     let upload_part_res = self
-      .client
-      .upload_part()
-      .bucket(&self.bucket)
-      .key(object_key)
-      .upload_id(&req.upload_id)
-      .part_number(req.part_number)
-      .body(body)
-      .send()
-      .await
-      .map_err(|err| anyhow!(format!("Failed to upload part: {:?}", err)))?;
+      .executor
+      .execute("upload_part", RetryPolicy::UPLOAD_PART, || {
+        self
+          .client
+          .upload_part()
+          .bucket(&self.bucket)
+          .key(object_key)
+          .upload_id(&req.upload_id)
+          .part_number(req.part_number)
+          .body(ByteStream::from(body.clone()))
+          .send()
+      })
+      .await?;
 
     match upload_part_res.e_tag {
       None => Err(anyhow!("Failed to upload part: e_tag is None").into()),
@@ -385,14 +628,17 @@ impl BucketClient for AwsS3BucketClientImpl {
     let mut continuation_token = None;
     loop {
       let list_objects = self
-        .client
-        .list_objects_v2()
-        .bucket(&self.bucket)
-        .prefix(parent_dir)
-        .set_continuation_token(continuation_token.clone())
-        .send()
-        .await
-        .map_err(|err| anyhow!("Failed to list object: {}", err))?;
+        .executor
+        .execute("list_objects_v2", RetryPolicy::IDEMPOTENT, || {
+          self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(parent_dir)
+            .set_continuation_token(continuation_token.clone())
+            .send()
+        })
+        .await?;
 
       let mut objects_to_delete: Vec<ObjectIdentifier> = list_objects
         .contents
@@ -436,13 +682,16 @@ impl BucketClient for AwsS3BucketClientImpl {
           .map_err(|err| anyhow!("Failed to build delete object: {}", err))?;
 
         let delete_objects_output: DeleteObjectsOutput = self
-          .client
-          .delete_objects()
-          .bucket(&self.bucket)
-          .delete(delete)
-          .send()
-          .await
-          .map_err(|err| anyhow!("Failed to delete delete object: {}", err))?;
+          .executor
+          .execute("delete_objects", RetryPolicy::IDEMPOTENT, || {
+            self
+              .client
+              .delete_objects()
+              .bucket(&self.bucket)
+              .delete(delete.clone())
+              .send()
+          })
+          .await?;
 
         if let Some(errors) = delete_objects_output.errors {
           for error in errors {
@@ -471,14 +720,17 @@ impl BucketClient for AwsS3BucketClientImpl {
 
   async fn list_dir(&self, dir: &str, limit: usize) -> Result<Vec<String>, AppError> {
     let list_objects = self
-      .client
-      .list_objects_v2()
-      .bucket(&self.bucket)
-      .prefix(dir)
-      .max_keys(limit as i32)
-      .send()
-      .await
-      .map_err(|err| anyhow!("Failed to list object: {}", err))?;
+      .executor
+      .execute("list_objects_v2", RetryPolicy::IDEMPOTENT, || {
+        self
+          .client
+          .list_objects_v2()
+          .bucket(&self.bucket)
+          .prefix(dir)
+          .max_keys(limit as i32)
+          .send()
+      })
+      .await?;
 
     Ok(
       list_objects
@@ -489,6 +741,47 @@ impl BucketClient for AwsS3BucketClientImpl {
         .collect(),
     )
   }
+
+  async fn list_objects(&self, prefix: &str) -> Result<Vec<BucketObject>, AppError> {
+    let mut objects = vec![];
+    let mut continuation_token = None;
+    loop {
+      let output = self
+        .executor
+        .execute("list_objects_v2", RetryPolicy::IDEMPOTENT, || {
+          self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(prefix)
+            .set_continuation_token(continuation_token.clone())
+            .send()
+        })
+        .await?;
+
+      objects.extend(
+        output
+          .contents
+          .unwrap_or_default()
+          .into_iter()
+          .filter_map(|o| {
+            Some(BucketObject {
+              key: o.key?,
+              size: o.size.unwrap_or(0),
+              last_modified: o
+                .last_modified
+                .and_then(|t| DateTime::from_timestamp(t.secs(), t.subsec_nanos())),
+            })
+          }),
+      );
+
+      match output.next_continuation_token {
+        Some(token) if output.is_truncated.unwrap_or(false) => continuation_token = Some(token),
+        _ => break,
+      }
+    }
+    Ok(objects)
+  }
 }
 
 #[derive(Debug)]
@@ -0,0 +1,437 @@
+use crate::operation_delay::{DelayedDependency, OperationDelay};
+use app_error::AppError;
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::registry::Registry;
+use std::error::Error;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{trace, warn};
+
+#[derive(Default)]
+pub struct S3Metrics {
+  /// Incremented each time a failed S3 request is sent again.
+  pub retry_count: Counter,
+  /// Incremented each time a request is rejected without being sent because the breaker is open.
+  pub rejected_count: Counter,
+  pub breaker_opened_count: Counter,
+  pub breaker_half_opened_count: Counter,
+  pub breaker_closed_count: Counter,
+  /// Current state of the breaker: 0 when closed, 1 when half open and 2 when open.
+  pub breaker_state: Gauge,
+}
+
+impl S3Metrics {
+  pub fn register(registry: &mut Registry) -> Self {
+    let metrics = Self::default();
+    let s3_registry = registry.sub_registry_with_prefix("s3");
+    s3_registry.register(
+      "retry_count",
+      "Number of failed S3 requests that were sent again",
+      metrics.retry_count.clone(),
+    );
+    s3_registry.register(
+      "rejected_count",
+      "Number of S3 requests rejected because the circuit breaker is open",
+      metrics.rejected_count.clone(),
+    );
+    s3_registry.register(
+      "breaker_opened_count",
+      "Number of times the S3 circuit breaker opened",
+      metrics.breaker_opened_count.clone(),
+    );
+    s3_registry.register(
+      "breaker_half_opened_count",
+      "Number of times the S3 circuit breaker let a probe request through",
+      metrics.breaker_half_opened_count.clone(),
+    );
+    s3_registry.register(
+      "breaker_closed_count",
+      "Number of times the S3 circuit breaker closed after a successful probe",
+      metrics.breaker_closed_count.clone(),
+    );
+    s3_registry.register(
+      "breaker_state",
+      "State of the S3 circuit breaker: 0 closed, 1 half open, 2 open",
+      metrics.breaker_state.clone(),
+    );
+    metrics
+  }
+}
+
+/// How many times, and how fast, a failed S3 request is sent again.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+  /// Total number of attempts, including the first one.
+  pub max_attempts: u32,
+  pub base_delay: Duration,
+  pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+  /// For GET, HEAD, DELETE and other requests that can be sent any number of times.
+  pub const IDEMPOTENT: RetryPolicy = RetryPolicy {
+    max_attempts: 5,
+    base_delay: Duration::from_millis(100),
+    max_delay: Duration::from_secs(3),
+  };
+
+  /// For a single part of a multipart upload. The part is small and the whole upload fails if
+  /// the part fails, so it is retried a bit less aggressively than a GET.
+  pub const UPLOAD_PART: RetryPolicy = RetryPolicy {
+    max_attempts: 3,
+    base_delay: Duration::from_millis(200),
+    max_delay: Duration::from_secs(2),
+  };
+
+  /// For requests whose body is a stream that can only be read once.
+  pub const NO_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 1,
+    base_delay: Duration::ZERO,
+    max_delay: Duration::ZERO,
+  };
+
+  /// Exponential backoff with jitter. S3 asks clients to slow down with a `SlowDown` error, which
+  /// gets a longer delay than other transient errors.
+  fn delay(&self, attempt: u32, kind: S3ErrorKind) -> Duration {
+    let factor = if kind == S3ErrorKind::SlowDown { 4 } else { 1 };
+    let delay = self
+      .base_delay
+      .saturating_mul(factor * 2u32.saturating_pow(attempt.saturating_sub(1)))
+      .min(self.max_delay);
+    let nanos = SystemTime::UNIX_EPOCH
+      .elapsed()
+      .map(|d| d.subsec_nanos())
+      .unwrap_or_default();
+    // wait between 50% and 100% of the delay
+    delay / 2 + delay.mul_f64((nanos % 1000) as f64 / 2000.0)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum S3ErrorKind {
+  NotFound,
+  AccessDenied,
+  SlowDown,
+  /// Timeouts, connection failures and 5xx responses.
+  Transient,
+  Other,
+}
+
+impl S3ErrorKind {
+  pub fn from_sdk_error<E>(err: &SdkError<E, HttpResponse>) -> Self
+  where
+    E: ProvideErrorMetadata,
+  {
+    match err {
+      SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError(_) => {
+        S3ErrorKind::Transient
+      },
+      SdkError::ServiceError(service_err) => {
+        match service_err.err().code() {
+          Some("NoSuchKey") | Some("NoSuchUpload") | Some("NotFound") => {
+            return S3ErrorKind::NotFound
+          },
+          Some("AccessDenied") => return S3ErrorKind::AccessDenied,
+          Some("SlowDown") => return S3ErrorKind::SlowDown,
+          _ => {},
+        }
+        // HEAD responses have no body, so the error code is missing
+        match service_err.raw().status().as_u16() {
+          404 => S3ErrorKind::NotFound,
+          403 => S3ErrorKind::AccessDenied,
+          503 => S3ErrorKind::SlowDown,
+          status if status >= 500 => S3ErrorKind::Transient,
+          _ => S3ErrorKind::Other,
+        }
+      },
+      _ => S3ErrorKind::Other,
+    }
+  }
+
+  fn is_retryable(&self) -> bool {
+    matches!(self, S3ErrorKind::SlowDown | S3ErrorKind::Transient)
+  }
+
+  fn into_app_error(self, msg: String) -> AppError {
+    match self {
+      S3ErrorKind::NotFound => AppError::RecordNotFound(msg),
+      S3ErrorKind::AccessDenied => AppError::S3AccessDenied(msg),
+      S3ErrorKind::SlowDown => AppError::S3SlowDown(msg),
+      S3ErrorKind::Transient => AppError::ServiceTemporaryUnavailable(msg),
+      S3ErrorKind::Other => AppError::S3ResponseError(msg),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerConfig {
+  /// Length of the window over which the error rate is computed.
+  pub window: Duration,
+  /// The breaker never opens if fewer requests were sent during the window.
+  pub min_requests: u32,
+  /// The breaker opens when the ratio of failed requests during the window reaches this value.
+  pub failure_rate: f64,
+  /// How long the breaker stays open before letting a probe request through.
+  pub open_duration: Duration,
+  /// Another probe is let through if the outcome of the previous one is not known after this
+  /// delay, for example because the request was cancelled.
+  pub probe_timeout: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+  fn default() -> Self {
+    Self {
+      window: Duration::from_secs(10),
+      min_requests: 20,
+      failure_rate: 0.5,
+      open_duration: Duration::from_secs(30),
+      probe_timeout: Duration::from_secs(60),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BreakerMode {
+  Closed,
+  Open {
+    until: Instant,
+  },
+  /// A single probe request is in flight. Its outcome decides if the breaker closes again.
+  HalfOpen {
+    since: Instant,
+  },
+}
+
+struct BreakerState {
+  mode: BreakerMode,
+  window_start: Instant,
+  requests: u32,
+  failures: u32,
+}
+
+/// Stops sending requests to S3 for a while when most of the recent requests failed, so that
+/// callers fail fast instead of holding connections while S3 is unavailable.
+///
+/// Only transient errors count as failures: a missing key means S3 is healthy.
+pub struct CircuitBreaker {
+  config: CircuitBreakerConfig,
+  state: Mutex<BreakerState>,
+  metrics: Arc<S3Metrics>,
+}
+
+impl CircuitBreaker {
+  pub fn new(config: CircuitBreakerConfig, metrics: Arc<S3Metrics>) -> Self {
+    Self {
+      config,
+      state: Mutex::new(BreakerState {
+        mode: BreakerMode::Closed,
+        window_start: Instant::now(),
+        requests: 0,
+        failures: 0,
+      }),
+      metrics,
+    }
+  }
+
+  /// Returns `false` if the request must not be sent.
+  pub fn try_acquire(&self) -> bool {
+    let mut state = self.state.lock().unwrap();
+    match state.mode {
+      BreakerMode::Closed => true,
+      BreakerMode::HalfOpen { since } => {
+        if since.elapsed() < self.config.probe_timeout {
+          return false;
+        }
+        self.transition(
+          &mut state,
+          BreakerMode::HalfOpen {
+            since: Instant::now(),
+          },
+        );
+        true
+      },
+      BreakerMode::Open { until } => {
+        if Instant::now() < until {
+          return false;
+        }
+        self.transition(
+          &mut state,
+          BreakerMode::HalfOpen {
+            since: Instant::now(),
+          },
+        );
+        true
+      },
+    }
+  }
+
+  pub fn record(&self, success: bool) {
+    let mut state = self.state.lock().unwrap();
+    let now = Instant::now();
+    match state.mode {
+      BreakerMode::HalfOpen { .. } => {
+        let mode = if success {
+          BreakerMode::Closed
+        } else {
+          BreakerMode::Open {
+            until: now + self.config.open_duration,
+          }
+        };
+        self.transition(&mut state, mode);
+      },
+      BreakerMode::Open { .. } => {},
+      BreakerMode::Closed => {
+        if now.duration_since(state.window_start) > self.config.window {
+          state.window_start = now;
+          state.requests = 0;
+          state.failures = 0;
+        }
+        state.requests += 1;
+        if !success {
+          state.failures += 1;
+        }
+        if state.requests >= self.config.min_requests
+          && state.failures as f64 >= state.requests as f64 * self.config.failure_rate
+        {
+          warn!(
+            "S3 circuit breaker opened: {}/{} requests failed",
+            state.failures, state.requests
+          );
+          self.transition(
+            &mut state,
+            BreakerMode::Open {
+              until: now + self.config.open_duration,
+            },
+          );
+        }
+      },
+    }
+  }
+
+  pub fn is_open(&self) -> bool {
+    matches!(self.state.lock().unwrap().mode, BreakerMode::Open { .. })
+  }
+
+  fn transition(&self, state: &mut BreakerState, mode: BreakerMode) {
+    state.mode = mode;
+    state.window_start = Instant::now();
+    state.requests = 0;
+    state.failures = 0;
+    match mode {
+      BreakerMode::Closed => {
+        self.metrics.breaker_closed_count.inc();
+        self.metrics.breaker_state.set(0);
+      },
+      BreakerMode::HalfOpen { .. } => {
+        self.metrics.breaker_half_opened_count.inc();
+        self.metrics.breaker_state.set(1);
+      },
+      BreakerMode::Open { .. } => {
+        self.metrics.breaker_opened_count.inc();
+        self.metrics.breaker_state.set(2);
+      },
+    }
+  }
+}
+
+/// Sends S3 requests with the given [RetryPolicy], through a [CircuitBreaker] shared by all
+/// requests of the client.
+pub struct S3RequestExecutor {
+  breaker: CircuitBreaker,
+  metrics: Arc<S3Metrics>,
+  operation_delay: Option<Arc<dyn OperationDelay>>,
+}
+
+impl S3RequestExecutor {
+  pub fn new(config: CircuitBreakerConfig, metrics: Arc<S3Metrics>) -> Self {
+    Self {
+      breaker: CircuitBreaker::new(config, metrics.clone()),
+      metrics,
+      operation_delay: None,
+    }
+  }
+
+  /// Waits for the [OperationDelay] before each operation is sent.
+  pub fn with_operation_delay(mut self, operation_delay: Option<Arc<dyn OperationDelay>>) -> Self {
+    self.operation_delay = operation_delay;
+    self
+  }
+
+  pub fn metrics(&self) -> &Arc<S3Metrics> {
+    &self.metrics
+  }
+
+  pub fn operation_delay(&self) -> Option<&Arc<dyn OperationDelay>> {
+    self.operation_delay.as_ref()
+  }
+
+  pub fn breaker(&self) -> &CircuitBreaker {
+    &self.breaker
+  }
+
+  /// Runs `request` until it succeeds, fails with an error that can not be retried, or runs out of
+  /// attempts. The error is mapped to an [AppError] according to the S3 error code.
+  pub async fn execute<T, E, F, Fut>(
+    &self,
+    operation: &str,
+    policy: RetryPolicy,
+    mut request: F,
+  ) -> Result<T, AppError>
+  where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, SdkError<E, HttpResponse>>>,
+    E: ProvideErrorMetadata + Error + Send + Sync + 'static,
+  {
+    if let Some(operation_delay) = &self.operation_delay {
+      operation_delay
+        .delay(DelayedDependency::S3, operation)
+        .await;
+    }
+    let mut attempt = 0;
+    loop {
+      attempt += 1;
+      if !self.breaker.try_acquire() {
+        self.metrics.rejected_count.inc();
+        return Err(AppError::ServiceTemporaryUnavailable(format!(
+          "S3 is unavailable, {} rejected",
+          operation
+        )));
+      }
+
+      match request().await {
+        Ok(output) => {
+          self.breaker.record(true);
+          return Ok(output);
+        },
+        Err(err) => {
+          let kind = S3ErrorKind::from_sdk_error(&err);
+          self.breaker.record(!kind.is_retryable());
+          if !kind.is_retryable() || attempt >= policy.max_attempts {
+            return Err(kind.into_app_error(format!(
+              "{} failed: {}",
+              operation,
+              DisplayErrorContext(&err)
+            )));
+          }
+
+          let delay = policy.delay(attempt, kind);
+          trace!(
+            "retry {} in {:?} after {:?} error (attempt {}/{}): {}",
+            operation,
+            delay,
+            kind,
+            attempt,
+            policy.max_attempts,
+            DisplayErrorContext(&err)
+          );
+          self.metrics.retry_count.inc();
+          tokio::time::sleep(delay).await;
+        },
+      }
+    }
+  }
+}
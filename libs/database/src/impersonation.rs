@@ -0,0 +1,155 @@
+use app_error::AppError;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::{AFImpersonationRequestRow, AFImpersonationRow};
+
+pub async fn insert_impersonation<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  impersonation_id: &Uuid,
+  admin_uid: i64,
+  uid: i64,
+  reason: &str,
+  expires_at: DateTime<Utc>,
+) -> Result<AFImpersonationRow, AppError> {
+  let row = sqlx::query_as::<_, AFImpersonationRow>(
+    r#"
+      INSERT INTO af_impersonation (impersonation_id, admin_uid, uid, reason, expires_at)
+      VALUES ($1, $2, $3, $4, $5)
+      RETURNING *
+    "#,
+  )
+  .bind(impersonation_id)
+  .bind(admin_uid)
+  .bind(uid)
+  .bind(reason)
+  .bind(expires_at)
+  .fetch_one(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Returns the impersonation if it's neither expired nor revoked.
+pub async fn select_active_impersonation<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  impersonation_id: &Uuid,
+) -> Result<Option<AFImpersonationRow>, AppError> {
+  let row = sqlx::query_as::<_, AFImpersonationRow>(
+    r#"
+      SELECT * FROM af_impersonation
+      WHERE impersonation_id = $1
+        AND revoked_at IS NULL
+        AND expires_at > CURRENT_TIMESTAMP
+    "#,
+  )
+  .bind(impersonation_id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Returns the impersonations which are neither expired nor revoked, the most recent first.
+pub async fn select_active_impersonations<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+) -> Result<Vec<AFImpersonationRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFImpersonationRow>(
+    r#"
+      SELECT * FROM af_impersonation
+      WHERE revoked_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+      ORDER BY created_at DESC
+    "#,
+  )
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+/// Allows the writes under an active impersonation. Returns `None` if the impersonation is
+/// expired or revoked.
+pub async fn update_impersonation_write_grant<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  impersonation_id: &Uuid,
+  write_reason: &str,
+) -> Result<Option<AFImpersonationRow>, AppError> {
+  let row = sqlx::query_as::<_, AFImpersonationRow>(
+    r#"
+      UPDATE af_impersonation
+      SET write_reason = $2, write_granted_at = CURRENT_TIMESTAMP
+      WHERE impersonation_id = $1
+        AND revoked_at IS NULL
+        AND expires_at > CURRENT_TIMESTAMP
+      RETURNING *
+    "#,
+  )
+  .bind(impersonation_id)
+  .bind(write_reason)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Returns true if the impersonation was active.
+pub async fn revoke_impersonation<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  impersonation_id: &Uuid,
+  revoked_by: i64,
+) -> Result<bool, AppError> {
+  let result = sqlx::query(
+    r#"
+      UPDATE af_impersonation
+      SET revoked_at = CURRENT_TIMESTAMP, revoked_by = $2
+      WHERE impersonation_id = $1
+        AND revoked_at IS NULL
+        AND expires_at > CURRENT_TIMESTAMP
+    "#,
+  )
+  .bind(impersonation_id)
+  .bind(revoked_by)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected() > 0)
+}
+
+pub async fn insert_impersonation_request<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  impersonation_id: &Uuid,
+  method: &str,
+  path: &str,
+  status: i16,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      INSERT INTO af_impersonation_request (impersonation_id, method, path, status)
+      VALUES ($1, $2, $3, $4)
+    "#,
+  )
+  .bind(impersonation_id)
+  .bind(method)
+  .bind(path)
+  .bind(status)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+/// Returns the requests made under the impersonation, the most recent first.
+pub async fn select_impersonation_requests<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  impersonation_id: &Uuid,
+  limit: i64,
+) -> Result<Vec<AFImpersonationRequestRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFImpersonationRequestRow>(
+    r#"
+      SELECT * FROM af_impersonation_request
+      WHERE impersonation_id = $1
+      ORDER BY created_at DESC
+      LIMIT $2
+    "#,
+  )
+  .bind(impersonation_id)
+  .bind(limit)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
@@ -0,0 +1,106 @@
+use app_error::AppError;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::AFWorkspaceInboundEmailRow;
+
+/// Sets the page the emails sent to the inbound address of the workspace are appended to. The
+/// token is only used when the workspace has no inbound address yet, the existing address is kept
+/// otherwise.
+pub async fn upsert_workspace_inbound_email<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  view_id: &Uuid,
+  token: &str,
+  created_by: i64,
+) -> Result<AFWorkspaceInboundEmailRow, AppError> {
+  let row = sqlx::query_as::<_, AFWorkspaceInboundEmailRow>(
+    r#"
+      INSERT INTO af_workspace_inbound_email (workspace_id, view_id, token, created_by)
+      VALUES ($1, $2, $3, $4)
+      ON CONFLICT (workspace_id) DO UPDATE SET
+        view_id = EXCLUDED.view_id,
+        created_by = EXCLUDED.created_by
+      RETURNING workspace_id, view_id, token, created_by, created_at, rotated_at
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(view_id)
+  .bind(token)
+  .bind(created_by)
+  .fetch_one(executor)
+  .await?;
+  Ok(row)
+}
+
+pub async fn select_workspace_inbound_email<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<Option<AFWorkspaceInboundEmailRow>, AppError> {
+  let row = sqlx::query_as::<_, AFWorkspaceInboundEmailRow>(
+    r#"
+      SELECT workspace_id, view_id, token, created_by, created_at, rotated_at
+      FROM af_workspace_inbound_email
+      WHERE workspace_id = $1
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+pub async fn select_workspace_inbound_email_by_token<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  token: &str,
+) -> Result<Option<AFWorkspaceInboundEmailRow>, AppError> {
+  let row = sqlx::query_as::<_, AFWorkspaceInboundEmailRow>(
+    r#"
+      SELECT workspace_id, view_id, token, created_by, created_at, rotated_at
+      FROM af_workspace_inbound_email
+      WHERE token = $1
+    "#,
+  )
+  .bind(token)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Replaces the token of the inbound address. Returns `None` if the workspace has no inbound
+/// address.
+pub async fn update_workspace_inbound_email_token<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  token: &str,
+) -> Result<Option<AFWorkspaceInboundEmailRow>, AppError> {
+  let row = sqlx::query_as::<_, AFWorkspaceInboundEmailRow>(
+    r#"
+      UPDATE af_workspace_inbound_email
+      SET token = $2, rotated_at = NOW()
+      WHERE workspace_id = $1
+      RETURNING workspace_id, view_id, token, created_by, created_at, rotated_at
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(token)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+pub async fn delete_workspace_inbound_email<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      DELETE FROM af_workspace_inbound_email
+      WHERE workspace_id = $1
+    "#,
+  )
+  .bind(workspace_id)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
@@ -0,0 +1,69 @@
+use sqlx::{Error, Executor, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::AFCollabFingerprintRow;
+
+pub async fn upsert_collab_fingerprint<'a, E>(
+  tx: E,
+  workspace_id: &Uuid,
+  object_id: &str,
+  simhash: i64,
+  text_len: i32,
+) -> Result<(), Error>
+where
+  E: Executor<'a, Database = Postgres>,
+{
+  sqlx::query(
+    r#"
+      INSERT INTO af_collab_fingerprint (oid, workspace_id, simhash, text_len)
+      VALUES ($1, $2, $3, $4)
+      ON CONFLICT (oid) DO UPDATE
+      SET simhash = EXCLUDED.simhash,
+          text_len = EXCLUDED.text_len,
+          updated_at = CURRENT_TIMESTAMP
+    "#,
+  )
+  .bind(object_id)
+  .bind(workspace_id)
+  .bind(simhash)
+  .bind(text_len)
+  .execute(tx)
+  .await?;
+  Ok(())
+}
+
+/// Removes the fingerprint of a document which has no text anymore.
+pub async fn delete_collab_fingerprint<'a, E>(tx: E, object_id: &str) -> Result<(), Error>
+where
+  E: Executor<'a, Database = Postgres>,
+{
+  sqlx::query(r#"DELETE FROM af_collab_fingerprint WHERE oid = $1"#)
+    .bind(object_id)
+    .execute(tx)
+    .await?;
+  Ok(())
+}
+
+/// Returns the fingerprints of the documents of the workspace, the largest documents first.
+pub async fn select_workspace_collab_fingerprints<'a, E>(
+  tx: E,
+  workspace_id: &Uuid,
+  limit: i64,
+) -> Result<Vec<AFCollabFingerprintRow>, Error>
+where
+  E: Executor<'a, Database = Postgres>,
+{
+  sqlx::query_as::<_, AFCollabFingerprintRow>(
+    r#"
+      SELECT oid, simhash, text_len, updated_at
+      FROM af_collab_fingerprint
+      WHERE workspace_id = $1
+      ORDER BY text_len DESC
+      LIMIT $2
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(limit)
+  .fetch_all(tx)
+  .await
+}
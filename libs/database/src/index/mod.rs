@@ -1,5 +1,7 @@
 mod collab_embeddings_ops;
+mod collab_fingerprint_ops;
 mod search_ops;
 
 pub use collab_embeddings_ops::*;
+pub use collab_fingerprint_ops::*;
 pub use search_ops::*;
@@ -1,14 +1,42 @@
+pub mod access_policy;
 pub mod access_request;
+pub mod blob_access;
+pub mod blob_gc;
+pub mod blob_variant;
 pub mod chat;
 pub mod collab;
+pub mod collab_compaction;
+pub mod collab_migration;
+pub mod collab_mode;
+pub mod collab_verification;
+pub mod database_field_conversion;
+pub mod database_row_access;
+pub mod feature_flag;
 pub mod file;
 pub mod history;
+pub mod impersonation;
+pub mod inbound_email;
 pub mod index;
 pub mod listener;
+pub mod maintenance;
+pub mod moderation;
+pub mod notification;
+pub mod operation_delay;
+pub mod outbox;
 pub mod pg_row;
 pub mod publish;
 pub mod quick_note;
+pub mod realtime_bandwidth;
 pub mod resource_usage;
+pub mod statement_timeout;
 pub mod template;
 pub mod user;
+pub mod user_session;
 pub mod workspace;
+pub mod workspace_audit_log;
+pub mod workspace_export;
+pub mod workspace_merge;
+pub mod workspace_provisioning;
+pub mod workspace_stats;
+pub mod workspace_usage;
+pub mod workspace_webhook;
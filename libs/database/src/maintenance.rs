@@ -0,0 +1,79 @@
+use app_error::AppError;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::AFMaintenanceJobReportRow;
+
+/// Persist the report of a dry run together with the selection it was computed from.
+pub async fn insert_maintenance_job_report<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  job: &str,
+  uid: i64,
+  selection_hash: &str,
+  report: &serde_json::Value,
+  selection: &serde_json::Value,
+) -> Result<AFMaintenanceJobReportRow, AppError> {
+  let row = sqlx::query_as::<_, AFMaintenanceJobReportRow>(
+    r#"
+      INSERT INTO af_maintenance_job_report
+        (workspace_id, job, created_by, selection_hash, report, selection)
+      VALUES ($1, $2, $3, $4, $5, $6)
+      RETURNING *
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(job)
+  .bind(uid)
+  .bind(selection_hash)
+  .bind(report)
+  .bind(selection)
+  .fetch_one(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Returns the most recent dry run of the job in the workspace. The row is locked so that the
+/// same dry run can not be executed twice concurrently.
+pub async fn select_last_maintenance_job_report_for_update<
+  'a,
+  E: Executor<'a, Database = Postgres>,
+>(
+  executor: E,
+  workspace_id: &Uuid,
+  job: &str,
+) -> Result<Option<AFMaintenanceJobReportRow>, AppError> {
+  let row = sqlx::query_as::<_, AFMaintenanceJobReportRow>(
+    r#"
+      SELECT * FROM af_maintenance_job_report
+      WHERE workspace_id = $1 AND job = $2
+      ORDER BY created_at DESC
+      LIMIT 1
+      FOR UPDATE
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(job)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+pub async fn mark_maintenance_job_report_executed<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  report_id: &Uuid,
+  report: &serde_json::Value,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_maintenance_job_report
+      SET executed_at = NOW(), report = $2
+      WHERE report_id = $1
+    "#,
+  )
+  .bind(report_id)
+  .bind(report)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
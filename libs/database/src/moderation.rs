@@ -0,0 +1,226 @@
+use app_error::AppError;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::{AFPublishNamespaceReportCountRow, AFPublishedViewReportRow};
+
+/// Records a report against the view published under the given namespace and name. Returns
+/// `None` when no view is published there.
+pub async fn insert_published_view_report<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  publish_namespace: &str,
+  publish_name: &str,
+  reason: i16,
+  details: Option<&str>,
+  reporter_email: Option<&str>,
+) -> Result<Option<Uuid>, AppError> {
+  let report_id = sqlx::query_scalar::<_, Uuid>(
+    r#"
+      INSERT INTO af_published_view_report
+        (workspace_id, view_id, publish_namespace, publish_name, reason, details, reporter_email)
+      SELECT apc.workspace_id, apc.view_id, $1, apc.publish_name, $3, $4, $5
+      FROM af_published_collab apc
+      WHERE apc.workspace_id = (SELECT workspace_id FROM af_workspace_namespace WHERE namespace = $1)
+        AND apc.publish_name = $2
+        AND apc.unpublished_at IS NULL
+      RETURNING report_id
+    "#,
+  )
+  .bind(publish_namespace)
+  .bind(publish_name)
+  .bind(reason)
+  .bind(details)
+  .bind(reporter_email)
+  .fetch_optional(executor)
+  .await?;
+  Ok(report_id)
+}
+
+pub async fn select_published_view_report<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  report_id: &Uuid,
+) -> Result<Option<AFPublishedViewReportRow>, AppError> {
+  let row = sqlx::query_as::<_, AFPublishedViewReportRow>(
+    r#"
+      SELECT
+        report_id, workspace_id, view_id, publish_namespace, publish_name, reason, details,
+        reporter_email, status, created_at, resolved_at, resolution_note
+      FROM af_published_view_report
+      WHERE report_id = $1
+    "#,
+  )
+  .bind(report_id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Lists the reports, oldest first so that the moderation queue is handled in order.
+pub async fn select_published_view_reports<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  status: Option<i16>,
+  offset: i64,
+  limit: i64,
+) -> Result<Vec<AFPublishedViewReportRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFPublishedViewReportRow>(
+    r#"
+      SELECT
+        report_id, workspace_id, view_id, publish_namespace, publish_name, reason, details,
+        reporter_email, status, created_at, resolved_at, resolution_note
+      FROM af_published_view_report
+      WHERE $1::SMALLINT IS NULL OR status = $1
+      ORDER BY created_at ASC
+      OFFSET $2
+      LIMIT $3
+    "#,
+  )
+  .bind(status)
+  .bind(offset)
+  .bind(limit)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+/// Resolves the report if it's still pending. Returns the resolved report.
+pub async fn resolve_published_view_report<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  report_id: &Uuid,
+  status: i16,
+  resolved_by: i64,
+  resolution_note: Option<&str>,
+) -> Result<Option<AFPublishedViewReportRow>, AppError> {
+  let row = sqlx::query_as::<_, AFPublishedViewReportRow>(
+    r#"
+      UPDATE af_published_view_report
+      SET status = $2, resolved_at = NOW(), resolved_by = $3, resolution_note = $4
+      WHERE report_id = $1 AND status = 0
+      RETURNING
+        report_id, workspace_id, view_id, publish_namespace, publish_name, reason, details,
+        reporter_email, status, created_at, resolved_at, resolution_note
+    "#,
+  )
+  .bind(report_id)
+  .bind(status)
+  .bind(resolved_by)
+  .bind(resolution_note)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Resolves all the pending reports of the view. Returns the resolved reports.
+pub async fn resolve_pending_published_view_reports<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  view_id: &Uuid,
+  status: i16,
+  resolved_by: i64,
+  resolution_note: Option<&str>,
+) -> Result<Vec<AFPublishedViewReportRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFPublishedViewReportRow>(
+    r#"
+      UPDATE af_published_view_report
+      SET status = $2, resolved_at = NOW(), resolved_by = $3, resolution_note = $4
+      WHERE view_id = $1 AND status = 0
+      RETURNING
+        report_id, workspace_id, view_id, publish_namespace, publish_name, reason, details,
+        reporter_email, status, created_at, resolved_at, resolution_note
+    "#,
+  )
+  .bind(view_id)
+  .bind(status)
+  .bind(resolved_by)
+  .bind(resolution_note)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+/// Counts the reports against each namespace created during the last `window_days` days, by
+/// status.
+pub async fn select_publish_namespace_report_counts<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  publish_namespaces: &[String],
+  window_days: i64,
+) -> Result<Vec<AFPublishNamespaceReportCountRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFPublishNamespaceReportCountRow>(
+    r#"
+      SELECT
+        publish_namespace,
+        COUNT(*) FILTER (WHERE status = 0) AS pending,
+        COUNT(*) FILTER (WHERE status = 1) AS dismissed,
+        COUNT(*) FILTER (WHERE status = 2) AS taken_down
+      FROM af_published_view_report
+      WHERE publish_namespace = ANY($1)
+        AND created_at > NOW() - make_interval(days => $2::INT)
+      GROUP BY publish_namespace
+    "#,
+  )
+  .bind(publish_namespaces)
+  .bind(window_days as i32)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+#[inline]
+pub async fn insert_published_view_takedown<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  view_id: &Uuid,
+  report_id: &Uuid,
+  taken_down_by: i64,
+  note: Option<&str>,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      INSERT INTO af_published_view_takedown (view_id, workspace_id, report_id, taken_down_by, note)
+      VALUES ($1, $2, $3, $4, $5)
+      ON CONFLICT (view_id) DO NOTHING
+    "#,
+  )
+  .bind(view_id)
+  .bind(workspace_id)
+  .bind(report_id)
+  .bind(taken_down_by)
+  .bind(note)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+/// Lifts the takedown of the view. Returns false if the view was not taken down.
+#[inline]
+pub async fn delete_published_view_takedown<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  view_id: &Uuid,
+) -> Result<bool, AppError> {
+  let res = sqlx::query(
+    r#"
+      DELETE FROM af_published_view_takedown
+      WHERE view_id = $1
+    "#,
+  )
+  .bind(view_id)
+  .execute(executor)
+  .await?;
+  Ok(res.rows_affected() > 0)
+}
+
+#[inline]
+pub async fn select_taken_down_view_ids<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  view_ids: &[Uuid],
+) -> Result<Vec<Uuid>, AppError> {
+  let view_ids = sqlx::query_scalar::<_, Uuid>(
+    r#"
+      SELECT view_id
+      FROM af_published_view_takedown
+      WHERE view_id = ANY($1)
+    "#,
+  )
+  .bind(view_ids)
+  .fetch_all(executor)
+  .await?;
+  Ok(view_ids)
+}
@@ -0,0 +1,228 @@
+use app_error::AppError;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::{AFNotificationSubscriptionRow, AFUserNotificationRow};
+
+pub async fn insert_user_notification<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+  workspace_id: Option<&Uuid>,
+  kind: &str,
+  payload: &serde_json::Value,
+) -> Result<Uuid, AppError> {
+  let (notification_id,): (Uuid,) = sqlx::query_as(
+    r#"
+      INSERT INTO af_user_notification (uid, workspace_id, kind, payload)
+      VALUES ($1, $2, $3, $4)
+      RETURNING notification_id
+    "#,
+  )
+  .bind(uid)
+  .bind(workspace_id)
+  .bind(kind)
+  .bind(payload)
+  .fetch_one(executor)
+  .await?;
+  Ok(notification_id)
+}
+
+/// Return the most recent notifications of a user preceding `before`, the
+/// `(created_at, notification_id)` of the last notification of the previous page, newest first
+pub async fn select_user_notifications<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+  only_unread: bool,
+  before: Option<(DateTime<Utc>, Uuid)>,
+  limit: i64,
+) -> Result<Vec<AFUserNotificationRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFUserNotificationRow>(
+    r#"
+      SELECT * FROM af_user_notification
+      WHERE uid = $1 AND (NOT $2 OR is_read = FALSE)
+        AND ($3::TIMESTAMPTZ IS NULL OR (created_at, notification_id) < ($3, $4))
+      ORDER BY created_at DESC, notification_id DESC
+      LIMIT $5
+    "#,
+  )
+  .bind(uid)
+  .bind(only_unread)
+  .bind(before.map(|(created_at, _)| created_at))
+  .bind(before.map(|(_, notification_id)| notification_id))
+  .bind(limit)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+pub async fn mark_user_notifications_read<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+  notification_ids: &[Uuid],
+) -> Result<u64, AppError> {
+  let result = sqlx::query(
+    r#"
+      UPDATE af_user_notification
+      SET is_read = TRUE
+      WHERE uid = $1 AND notification_id = ANY($2)
+    "#,
+  )
+  .bind(uid)
+  .bind(notification_ids)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected())
+}
+
+pub async fn upsert_notification_subscription<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+  workspace_id: &Uuid,
+  object_id: &Uuid,
+  level: i16,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      INSERT INTO af_notification_subscription (uid, workspace_id, object_id, level)
+      VALUES ($1, $2, $3, $4)
+      ON CONFLICT (uid, object_id)
+      DO UPDATE SET level = EXCLUDED.level, updated_at = NOW()
+    "#,
+  )
+  .bind(uid)
+  .bind(workspace_id)
+  .bind(object_id)
+  .bind(level)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+pub async fn delete_notification_subscription<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+  object_id: &Uuid,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      DELETE FROM af_notification_subscription
+      WHERE uid = $1 AND object_id = $2
+    "#,
+  )
+  .bind(uid)
+  .bind(object_id)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+/// Remove the preferences of all users for the given objects, called once the objects are deleted
+pub async fn delete_notification_subscriptions_for_objects<
+  'a,
+  E: Executor<'a, Database = Postgres>,
+>(
+  executor: E,
+  object_ids: &[Uuid],
+) -> Result<u64, AppError> {
+  let result = sqlx::query(
+    r#"
+      DELETE FROM af_notification_subscription
+      WHERE object_id = ANY($1)
+    "#,
+  )
+  .bind(object_ids)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected())
+}
+
+pub async fn select_user_notification_subscriptions<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+  workspace_id: &Uuid,
+) -> Result<Vec<AFNotificationSubscriptionRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFNotificationSubscriptionRow>(
+    r#"
+      SELECT * FROM af_notification_subscription
+      WHERE uid = $1 AND workspace_id = $2
+      ORDER BY updated_at DESC
+    "#,
+  )
+  .bind(uid)
+  .bind(workspace_id)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+/// Return the preferences set on the object by the users who are still members of its workspace
+pub async fn select_object_notification_subscriptions<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  object_id: &Uuid,
+) -> Result<Vec<AFNotificationSubscriptionRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFNotificationSubscriptionRow>(
+    r#"
+      SELECT s.* FROM af_notification_subscription s
+      JOIN af_workspace_member m ON m.uid = s.uid AND m.workspace_id = s.workspace_id
+      WHERE s.object_id = $1
+    "#,
+  )
+  .bind(object_id)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+/// Return the users, among the given ones, who also mute the mentions in the objects they muted
+pub async fn select_uids_muting_mentions<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uids: &[i64],
+) -> Result<Vec<i64>, AppError> {
+  let uids = sqlx::query_scalar(
+    r#"
+      SELECT uid FROM af_user_notification_setting
+      WHERE uid = ANY($1) AND mute_mentions
+    "#,
+  )
+  .bind(uids)
+  .fetch_all(executor)
+  .await?;
+  Ok(uids)
+}
+
+pub async fn select_user_mute_mentions<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+) -> Result<bool, AppError> {
+  let mute_mentions: Option<bool> = sqlx::query_scalar(
+    r#"
+      SELECT mute_mentions FROM af_user_notification_setting
+      WHERE uid = $1
+    "#,
+  )
+  .bind(uid)
+  .fetch_optional(executor)
+  .await?;
+  Ok(mute_mentions.unwrap_or(false))
+}
+
+pub async fn upsert_user_mute_mentions<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+  mute_mentions: bool,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      INSERT INTO af_user_notification_setting (uid, mute_mentions)
+      VALUES ($1, $2)
+      ON CONFLICT (uid)
+      DO UPDATE SET mute_mentions = EXCLUDED.mute_mentions, updated_at = NOW()
+    "#,
+  )
+  .bind(uid)
+  .bind(mute_mentions)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
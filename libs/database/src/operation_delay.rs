@@ -0,0 +1,26 @@
+use async_trait::async_trait;
+
+/// The dependency a delayed operation is sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelayedDependency {
+  Redis,
+  S3,
+  Postgres,
+}
+
+impl DelayedDependency {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      DelayedDependency::Redis => "redis",
+      DelayedDependency::S3 => "s3",
+      DelayedDependency::Postgres => "postgres",
+    }
+  }
+}
+
+/// Waits before an operation is sent to a dependency, to reproduce a slow network or a slow
+/// service. Only the fault injection of staging deployments sets one.
+#[async_trait]
+pub trait OperationDelay: Send + Sync {
+  async fn delay(&self, dependency: DelayedDependency, operation: &str);
+}
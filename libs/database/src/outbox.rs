@@ -0,0 +1,224 @@
+use std::ops::DerefMut;
+
+use app_error::AppError;
+use serde::{Deserialize, Serialize};
+use shared_entity::dto::webhook_dto::MembershipEvent;
+use sqlx::{Executor, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::pg_row::AFOutboxEntryRow;
+
+/// The subsystem an outbox entry is dispatched to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutboxTopic {
+  /// A [CdnPurgeIntent] sent to the CDN purge webhook.
+  CdnPurge,
+  /// An indexer task, pushed to the queue of the background indexer.
+  IndexCollab,
+  /// An [InvitationEmailIntent], sent to the invitee of a workspace invitation.
+  InvitationEmail,
+  /// A workspace export task, pushed to the stream of the export worker, see
+  /// [crate::workspace_export::EXPORT_TASK_STREAM].
+  WorkspaceExport,
+  /// A [NotificationIntent], inserted into the inbox of its recipients.
+  Notification,
+  /// A [WebhookDeliveryIntent], posted to the endpoint of a workspace webhook.
+  WorkspaceWebhook,
+}
+
+impl OutboxTopic {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      OutboxTopic::CdnPurge => "cdn_purge",
+      OutboxTopic::IndexCollab => "index_collab",
+      OutboxTopic::InvitationEmail => "invitation_email",
+      OutboxTopic::WorkspaceExport => "workspace_export",
+      OutboxTopic::Notification => "notification",
+      OutboxTopic::WorkspaceWebhook => "workspace_webhook",
+    }
+  }
+
+  pub fn parse(topic: &str) -> Option<Self> {
+    match topic {
+      "cdn_purge" => Some(OutboxTopic::CdnPurge),
+      "index_collab" => Some(OutboxTopic::IndexCollab),
+      "invitation_email" => Some(OutboxTopic::InvitationEmail),
+      "workspace_export" => Some(OutboxTopic::WorkspaceExport),
+      "notification" => Some(OutboxTopic::Notification),
+      "workspace_webhook" => Some(OutboxTopic::WorkspaceWebhook),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PublishCdnEvent {
+  Publish,
+  Unpublish,
+}
+
+/// The entry points of the published views have to be purged from the CDN.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CdnPurgeIntent {
+  pub event: PublishCdnEvent,
+  pub workspace_id: Uuid,
+  pub view_ids: Vec<Uuid>,
+}
+
+/// The email of a workspace invitation, rendered from the `workspace_invite` template. It's
+/// dropped when the invitation can no longer be accepted, or when a later email was queued for
+/// the invitation since, `email_seq` being the sequence of the invitation when this one was.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvitationEmailIntent {
+  pub invite_id: Uuid,
+  pub email_seq: i32,
+  pub recipient: String,
+  pub sender_name: Option<String>,
+  pub subject: String,
+  pub param: serde_json::Value,
+}
+
+/// An inbox entry of the given kind for each recipient.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationIntent {
+  pub recipients: Vec<i64>,
+  pub workspace_id: Option<Uuid>,
+  pub kind: String,
+  pub payload: serde_json::Value,
+}
+
+/// Membership events to deliver to a webhook. The webhook is looked up on dispatch, the events of
+/// a webhook deleted in the meantime are dropped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDeliveryIntent {
+  pub webhook_id: Uuid,
+  pub events: Vec<MembershipEvent>,
+}
+
+/// Records a side effect of the changes made in the transaction. It's only dispatched once the
+/// transaction is committed, and dispatched again until it succeeds, so the consumers must be
+/// idempotent.
+pub async fn insert_outbox_entry<T: Serialize>(
+  txn: &mut Transaction<'_, Postgres>,
+  topic: OutboxTopic,
+  payload: &T,
+) -> Result<i64, AppError> {
+  let payload = serde_json::to_value(payload)?;
+  let id = sqlx::query_scalar::<_, i64>(
+    r#"
+      INSERT INTO af_outbox (topic, payload)
+      VALUES ($1, $2)
+      RETURNING id
+    "#,
+  )
+  .bind(topic.as_str())
+  .bind(payload)
+  .fetch_one(txn.deref_mut())
+  .await?;
+  Ok(id)
+}
+
+/// Claims the entries due for dispatch, oldest first. A claimed entry isn't due again before the
+/// lease expires, so that an entry whose relay died is picked up by another one.
+pub async fn claim_outbox_entries<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  limit: i64,
+  lease_secs: i64,
+) -> Result<Vec<AFOutboxEntryRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFOutboxEntryRow>(
+    r#"
+      UPDATE af_outbox
+      SET attempts = attempts + 1,
+          next_attempt_at = NOW() + make_interval(secs => $2)
+      WHERE id IN (
+        SELECT id FROM af_outbox
+        WHERE processed_at IS NULL AND next_attempt_at <= NOW()
+        ORDER BY id
+        LIMIT $1
+        FOR UPDATE SKIP LOCKED
+      )
+      RETURNING id, topic, payload, attempts, created_at
+    "#,
+  )
+  .bind(limit)
+  .bind(lease_secs as f64)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+pub async fn complete_outbox_entry<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  id: i64,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_outbox
+      SET processed_at = NOW(), last_error = NULL
+      WHERE id = $1
+    "#,
+  )
+  .bind(id)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+/// Schedules the next attempt of an entry which couldn't be dispatched.
+pub async fn fail_outbox_entry<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  id: i64,
+  error: &str,
+  retry_in_secs: i64,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_outbox
+      SET last_error = $2,
+          next_attempt_at = NOW() + make_interval(secs => $3)
+      WHERE id = $1
+    "#,
+  )
+  .bind(id)
+  .bind(error)
+  .bind(retry_in_secs as f64)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+/// Deletes the entries dispatched more than `retention_secs` ago.
+pub async fn delete_processed_outbox_entries<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  retention_secs: i64,
+) -> Result<u64, AppError> {
+  let result = sqlx::query(
+    r#"
+      DELETE FROM af_outbox
+      WHERE processed_at < NOW() - make_interval(secs => $1)
+    "#,
+  )
+  .bind(retention_secs as f64)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected())
+}
+
+/// Number of entries still not dispatched `min_age_secs` after they were recorded.
+pub async fn select_stuck_outbox_entry_count<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  min_age_secs: i64,
+) -> Result<i64, AppError> {
+  let count = sqlx::query_scalar::<_, i64>(
+    r#"
+      SELECT COUNT(*) FROM af_outbox
+      WHERE processed_at IS NULL
+        AND created_at < NOW() - make_interval(secs => $1)
+    "#,
+  )
+  .bind(min_age_secs as f64)
+  .fetch_one(executor)
+  .await?;
+  Ok(count)
+}
@@ -1,13 +1,13 @@
 use anyhow::anyhow;
 use app_error::AppError;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 
 use database_entity::dto::{
-  AFAccessLevel, AFRole, AFUserProfile, AFWebUser, AFWorkspace, AFWorkspaceInvitationStatus,
-  AccessRequestMinimal, AccessRequestStatus, AccessRequestWithViewId, AccessRequesterInfo,
-  AccountLink, GlobalComment, QuickNote, Reaction, Template, TemplateCategory,
-  TemplateCategoryMinimal, TemplateCategoryType, TemplateCreator, TemplateCreatorMinimal,
-  TemplateGroup, TemplateMinimal,
+  AFAccessLevel, AFRole, AFUserProfile, AFWebUser, AFWorkspace, AFWorkspaceInvitation,
+  AFWorkspaceInvitationStatus, AFWorkspacePendingInvitation, AccessRequestMinimal,
+  AccessRequestStatus, AccessRequestWithViewId, AccessRequesterInfo, AccountLink, GlobalComment,
+  QuickNote, Reaction, Template, TemplateCategory, TemplateCategoryMinimal, TemplateCategoryType,
+  TemplateCreator, TemplateCreatorMinimal, TemplateGroup, TemplateMinimal,
 };
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
@@ -184,6 +184,30 @@ pub struct AFWorkspaceMemberRow {
   pub role: AFRole,
 }
 
+/// A member of a workspace, as listed page by page.
+#[derive(Debug, Clone, FromRow)]
+pub struct AFWorkspaceMemberPageRow {
+  pub uid: i64,
+  pub name: String,
+  pub email: String,
+  pub role_id: i32,
+  pub joined_at: DateTime<Utc>,
+}
+
+/// A member of a workspace, as exported for the audits of the workspace.
+#[derive(Debug, Clone, FromRow)]
+pub struct AFWorkspaceMemberExportRow {
+  pub uid: i64,
+  pub name: String,
+  pub email: String,
+  pub role_id: i32,
+  pub joined_at: DateTime<Utc>,
+  /// When any session of the member was last seen.
+  pub last_active: Option<DateTime<Utc>>,
+  /// Whether the member has a verified second factor, `None` when GoTrue doesn't record them.
+  pub mfa_enabled: Option<bool>,
+}
+
 #[derive(FromRow)]
 pub struct AFCollabMemberAccessLevelRow {
   pub uid: i64,
@@ -224,6 +248,115 @@ pub struct AFBlobMetadataRow {
   pub modified_at: DateTime<Utc>,
   #[serde(default)]
   pub status: i16,
+  /// The sha256 of the content, hex encoded. `None` for the blobs whose content wasn't hashed,
+  /// such as the multipart uploads.
+  #[serde(default)]
+  pub content_hash: Option<String>,
+  /// The object storing the content. With a `content_hash`, it's tracked in af_blob_content and
+  /// possibly uploaded by another blob of the workspace, otherwise it's the object the blob was
+  /// moved to when overwritten. `None` for a blob stored under its own object key only.
+  #[serde(default)]
+  pub object_key: Option<String>,
+  /// Approximately when the blob was last downloaded, the downloads are written in batches.
+  #[serde(default = "Utc::now")]
+  pub last_accessed_at: DateTime<Utc>,
+  /// The storage class the object was moved to. `None` while it's in the default storage class
+  /// of the bucket.
+  #[serde(default)]
+  pub storage_class: Option<String>,
+  #[serde(default)]
+  pub storage_class_changed_at: Option<DateTime<Utc>>,
+  /// When the blob was first uploaded. `None` for the blobs uploaded before it was recorded.
+  #[serde(default)]
+  pub created_at: Option<DateTime<Utc>>,
+  /// The uid of the user who last uploaded the blob. `None` for the blobs uploaded before it was
+  /// recorded and for those of a deleted user.
+  #[serde(default)]
+  pub uploaded_by: Option<i64>,
+  /// When the blob expires, see [crate::resource_usage::BlobAttributes].
+  #[serde(default)]
+  pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Represent the row of the af_blob_content table: an object whose content is shared by
+/// `ref_count` blobs of the workspace.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct AFBlobContentRow {
+  pub workspace_id: Uuid,
+  pub content_hash: String,
+  pub object_key: String,
+  pub ref_count: i64,
+  pub created_at: DateTime<Utc>,
+}
+
+/// Represent the row of the af_blob_pending_upload table: a blob being uploaded to the bucket
+/// with a presigned url, whose declared size is reserved until the upload is completed.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct AFBlobPendingUploadRow {
+  pub workspace_id: Uuid,
+  pub file_id: String,
+  pub object_key: String,
+  pub file_type: String,
+  pub file_size: i64,
+  pub created_at: DateTime<Utc>,
+}
+
+/// Represent the row of the af_blob_multipart_upload table: a blob being uploaded to the bucket
+/// in parts, which the client can resume until the upload is completed or aborted.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct AFBlobMultipartUploadRow {
+  pub upload_id: String,
+  pub workspace_id: Uuid,
+  pub file_id: String,
+  pub object_key: String,
+  pub file_type: String,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+/// Represent the row of the af_blob_multipart_upload_part table
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct AFBlobMultipartUploadPartRow {
+  pub upload_id: String,
+  pub part_number: i32,
+  pub e_tag: String,
+  pub part_size: i64,
+  pub uploaded_at: DateTime<Utc>,
+}
+
+/// Represent the row of the af_blob_version table
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct AFBlobVersionRow {
+  pub workspace_id: Uuid,
+  pub file_id: String,
+  pub version: i64,
+  pub object_key: String,
+  pub file_type: String,
+  pub file_size: i64,
+  pub created_at: DateTime<Utc>,
+}
+
+/// Represent the row of the af_blob_variant table: a downscaled rendition of an image blob.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct AFBlobVariantRow {
+  pub workspace_id: Uuid,
+  pub file_id: String,
+  pub variant: String,
+  pub object_key: String,
+  pub file_type: String,
+  pub file_size: i64,
+  pub stale: bool,
+  pub created_at: DateTime<Utc>,
+}
+
+/// Represent the row of the af_blob_variant_task table: a blob whose variants are to be
+/// generated.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct AFBlobVariantTaskRow {
+  pub workspace_id: Uuid,
+  pub file_id: String,
+  pub attempts: i32,
+  pub enqueued_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -251,6 +384,13 @@ pub struct AFSnapshotRow {
   pub workspace_id: Uuid,
 }
 
+#[derive(Debug, FromRow, Clone)]
+pub struct AFCollabSnapshotInfoRow {
+  pub snapshot_id: i64,
+  pub len: i64,
+  pub created_by: Option<i64>,
+}
+
 #[derive(Debug, FromRow, Deserialize, Serialize)]
 pub struct AFWorkspaceInvitationMinimal {
   pub workspace_id: Uuid,
@@ -260,6 +400,70 @@ pub struct AFWorkspaceInvitationMinimal {
   pub role: AFRole,
 }
 
+/// An invitation of the user, along with the workspace it's for and its inviter.
+#[derive(Debug, Clone, FromRow)]
+pub struct AFWorkspaceInvitationRow {
+  pub invite_id: Uuid,
+  pub workspace_id: Uuid,
+  pub workspace_name: Option<String>,
+  pub inviter_email: Option<String>,
+  pub inviter_name: Option<String>,
+  pub status: i16,
+  pub updated_at: DateTime<Utc>,
+  pub inviter_icon: Option<String>,
+  pub workspace_icon: String,
+  pub member_count: Option<i64>,
+  pub expires_at: DateTime<Utc>,
+  pub expires_in_secs: i64,
+}
+
+impl From<AFWorkspaceInvitationRow> for AFWorkspaceInvitation {
+  fn from(value: AFWorkspaceInvitationRow) -> Self {
+    Self {
+      invite_id: value.invite_id,
+      workspace_id: value.workspace_id,
+      workspace_name: value.workspace_name,
+      inviter_email: value.inviter_email,
+      inviter_name: value.inviter_name,
+      status: AFWorkspaceInvitationStatus::from(value.status),
+      updated_at: value.updated_at,
+      inviter_icon: value.inviter_icon,
+      workspace_icon: value.workspace_icon,
+      member_count: value.member_count,
+      expires_at: Some(value.expires_at),
+      expires_in_secs: Some(value.expires_in_secs),
+    }
+  }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct AFWorkspacePendingInvitationRow {
+  pub invite_id: Uuid,
+  pub workspace_id: Uuid,
+  pub invitee_email: String,
+  pub role_id: i32,
+  pub inviter_email: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub expires_at: DateTime<Utc>,
+  pub expires_in_secs: i64,
+  pub email_queued_at: Option<DateTime<Utc>>,
+}
+
+impl From<AFWorkspacePendingInvitationRow> for AFWorkspacePendingInvitation {
+  fn from(value: AFWorkspacePendingInvitationRow) -> Self {
+    Self {
+      invite_id: value.invite_id,
+      invitee_email: value.invitee_email,
+      role: AFRole::from(value.role_id),
+      inviter_email: value.inviter_email,
+      created_at: value.created_at,
+      expires_at: value.expires_at,
+      expires_in_secs: value.expires_in_secs,
+      email_queued_at: value.email_queued_at,
+    }
+  }
+}
+
 #[derive(FromRow, Clone, Debug)]
 pub struct AFCollabRowMeta {
   pub oid: String,
@@ -278,6 +482,10 @@ pub struct AFChatRow {
   pub rag_ids: serde_json::Value,
   pub workspace_id: Uuid,
   pub meta_data: serde_json::Value,
+  pub owner_uid: Option<i64>,
+  pub share_scope: i16,
+  pub collaborative: bool,
+  pub shared_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
@@ -584,7 +792,124 @@ pub struct AFImportTask {
   pub created_at: DateTime<Utc>,
   #[serde(default)]
   pub file_url: Option<String>,
+  /// The [shared_entity::dto::import_dto::ImportReport] of the import, once it ran
+  #[serde(default)]
+  pub report: Option<serde_json::Value>,
+}
+/// Represent the row of the af_workspace_export table
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFWorkspaceExportRow {
+  pub task_id: Uuid,
+  pub workspace_id: Uuid,
+  pub created_by: i64,
+  pub status: i16,
+  pub stage: i16,
+  pub bytes_written: i64,
+  pub s3_key: Option<String>,
+  pub download_url: Option<String>,
+  pub error: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+  pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Represent the row of the af_user_session table
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFUserSessionRow {
+  pub session_id: Uuid,
+  pub uid: i64,
+  pub device_id: Option<String>,
+  pub client_version: Option<String>,
+  pub user_agent: Option<String>,
+  pub ip_address: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub last_seen_at: DateTime<Utc>,
+  pub revoked_at: Option<DateTime<Utc>>,
+  /// When the last access token seen for the session was issued.
+  pub token_issued_at: Option<DateTime<Utc>>,
+  /// How many times the session was seen refreshed.
+  pub refresh_count: i64,
+}
+
+/// Payload of the notifications sent on the af_user_session_channel when a session is revoked.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AFUserSessionRevokedNotification {
+  pub session_id: Uuid,
+  pub uid: i64,
+}
+
+/// Represent the row of the af_workspace_merge table
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFWorkspaceMergeRow {
+  pub task_id: Uuid,
+  pub target_workspace_id: Uuid,
+  pub source_workspace_id: Uuid,
+  pub created_by: i64,
+  pub status: i16,
+  pub stage: i16,
+  pub moved_collabs: i64,
+  pub copied_blobs: i64,
+  pub report: serde_json::Value,
+  pub error: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+/// Represent the row of the af_database_field_conversion table
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFDatabaseFieldConversionRow {
+  pub task_id: Uuid,
+  pub workspace_id: Uuid,
+  pub database_id: Uuid,
+  pub field_id: String,
+  pub from_field_type: i64,
+  pub to_field_type: i64,
+  pub created_by: i64,
+  pub status: i16,
+  pub total_rows: i64,
+  pub converted_rows: i64,
+  pub report: serde_json::Value,
+  pub error: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+/// Represent the row of the af_maintenance_job_report table
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFMaintenanceJobReportRow {
+  pub report_id: Uuid,
+  pub workspace_id: Uuid,
+  pub job: String,
+  pub created_by: i64,
+  pub selection_hash: String,
+  pub report: serde_json::Value,
+  pub selection: serde_json::Value,
+  pub created_at: DateTime<Utc>,
+  pub executed_at: Option<DateTime<Utc>>,
 }
+
+/// Represent the row of the af_user_notification table
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFUserNotificationRow {
+  pub notification_id: Uuid,
+  pub uid: i64,
+  pub workspace_id: Option<Uuid>,
+  pub kind: String,
+  pub payload: serde_json::Value,
+  pub is_read: bool,
+  pub created_at: DateTime<Utc>,
+}
+
+/// Represent the row of the af_notification_subscription table
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFNotificationSubscriptionRow {
+  pub uid: i64,
+  pub workspace_id: Uuid,
+  pub object_id: Uuid,
+  pub level: i16,
+  pub updated_at: DateTime<Utc>,
+}
+
 #[derive(sqlx::Type, Serialize, Deserialize, Debug)]
 #[repr(i32)]
 pub enum AFAccessRequestStatusColumn {
@@ -695,3 +1020,250 @@ pub struct AFPublishViewWithPublishInfo {
   pub comments_enabled: bool,
   pub duplicate_enabled: bool,
 }
+
+#[derive(Debug, FromRow)]
+pub struct AFPublishedRevisionRow {
+  pub publish_name: String,
+  pub comments_enabled: bool,
+  pub duplicate_enabled: bool,
+  pub revision_etag: String,
+  pub source_fingerprint: Option<String>,
+  pub revision_created_at: DateTime<Utc>,
+  pub scheduled_publish_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct AFPublishedViewReportRow {
+  pub report_id: Uuid,
+  pub workspace_id: Uuid,
+  pub view_id: Uuid,
+  pub publish_namespace: String,
+  pub publish_name: String,
+  pub reason: i16,
+  pub details: Option<String>,
+  pub reporter_email: Option<String>,
+  pub status: i16,
+  pub created_at: DateTime<Utc>,
+  pub resolved_at: Option<DateTime<Utc>>,
+  pub resolution_note: Option<String>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct AFPublishNamespaceReportCountRow {
+  pub publish_namespace: String,
+  pub pending: i64,
+  pub dismissed: i64,
+  pub taken_down: i64,
+}
+
+#[derive(Debug, FromRow)]
+pub struct AFWorkspaceBandwidthRow {
+  pub workspace_id: Uuid,
+  pub workspace_name: Option<String>,
+  pub bytes_in: i64,
+  pub bytes_out: i64,
+  pub updates_out: i64,
+  pub awareness_out: i64,
+  pub control_out: i64,
+}
+
+#[derive(Debug, FromRow)]
+pub struct AFCollabVerificationReportRow {
+  pub report_id: i64,
+  pub workspace_id: Uuid,
+  pub object_id: String,
+  pub collab_type: i32,
+  pub detected_on: String,
+  pub error: String,
+  pub recovered_snapshot_id: Option<i64>,
+  pub recovered_snapshot_at: Option<DateTime<Utc>>,
+  pub detection_count: i32,
+  pub created_at: DateTime<Utc>,
+  pub last_detected_at: DateTime<Utc>,
+  pub repaired_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct AFWorkspaceInboundEmailRow {
+  pub workspace_id: Uuid,
+  pub view_id: Uuid,
+  pub token: String,
+  pub created_by: i64,
+  pub created_at: DateTime<Utc>,
+  pub rotated_at: Option<DateTime<Utc>>,
+}
+
+/// Represent the row of the af_impersonation table
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFImpersonationRow {
+  pub impersonation_id: Uuid,
+  pub admin_uid: i64,
+  pub uid: i64,
+  pub reason: String,
+  pub write_reason: Option<String>,
+  pub write_granted_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+  pub expires_at: DateTime<Utc>,
+  pub revoked_at: Option<DateTime<Utc>>,
+  pub revoked_by: Option<i64>,
+}
+
+/// Represent the row of the af_impersonation_request table
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFImpersonationRequestRow {
+  pub id: i64,
+  pub impersonation_id: Uuid,
+  pub method: String,
+  pub path: String,
+  pub status: i16,
+  pub created_at: DateTime<Utc>,
+}
+
+/// Represent the row of the af_collab_fingerprint table
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFCollabFingerprintRow {
+  pub oid: String,
+  pub simhash: i64,
+  pub text_len: i32,
+  pub updated_at: DateTime<Utc>,
+}
+
+/// Represent the row of the af_database_row_access table
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFDatabaseRowAccessRow {
+  pub database_id: Uuid,
+  pub workspace_id: Uuid,
+  pub field_id: String,
+  pub restricted_rows: bool,
+  pub created_by: i64,
+  pub updated_at: DateTime<Utc>,
+}
+
+/// A collab which has not been brought to the latest collab migration version yet
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFCollabMigrationCandidateRow {
+  pub oid: String,
+  pub workspace_id: Uuid,
+  pub partition_key: i32,
+  pub migration_version: i32,
+}
+
+/// A soft-deleted collab, kept in the trash of its workspace
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFDeletedCollabRow {
+  pub oid: String,
+  pub workspace_id: Uuid,
+  pub partition_key: i32,
+  pub deleted_at: DateTime<Utc>,
+}
+
+/// A stored collab considered for the compaction of its doc state
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFCollabCompactionCandidateRow {
+  pub oid: String,
+  pub workspace_id: Uuid,
+  pub partition_key: i32,
+  pub owner_uid: i64,
+  /// Bytes of the encoded collab, `None` for the collabs written before it was recorded.
+  pub len: Option<i32>,
+}
+
+/// The sharing of a chat, seen from a given user
+#[derive(Debug, Clone, FromRow)]
+pub struct AFChatAccessRow {
+  pub workspace_id: Uuid,
+  pub owner_uid: Option<i64>,
+  pub share_scope: i16,
+  pub collaborative: bool,
+  pub is_workspace_member: bool,
+  pub is_share_member: bool,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFSharedChatRow {
+  pub chat_id: Uuid,
+  pub name: String,
+  pub owner_uid: Option<i64>,
+  pub owner_name: Option<String>,
+  pub share_scope: i16,
+  pub collaborative: bool,
+  pub shared_at: Option<DateTime<Utc>>,
+}
+
+/// A message of a shared chat matching a search query
+#[derive(Debug, Clone, FromRow)]
+pub struct AFSharedChatSearchRow {
+  pub chat_id: Uuid,
+  pub workspace_id: Uuid,
+  pub preview: String,
+  pub rank: f32,
+  pub owner_name: Option<String>,
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct AFFeatureFlagRow {
+  pub name: String,
+  pub value: serde_json::Value,
+  pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct AFWorkspaceStatsRow {
+  pub partition_key: i32,
+  pub object_count: i64,
+  pub total_bytes: i64,
+}
+
+/// Represent the row of the af_workspace_provisioning table
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AFWorkspaceProvisioningRow {
+  pub created_by: i64,
+  pub external_id: String,
+  pub workspace_id: Option<Uuid>,
+  pub spec: serde_json::Value,
+  pub status: i16,
+  pub members: serde_json::Value,
+  pub template_view_id: Option<String>,
+  pub error: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+/// Represent the row of the af_outbox table
+#[derive(Debug, Clone, FromRow)]
+pub struct AFOutboxEntryRow {
+  pub id: i64,
+  pub topic: String,
+  pub payload: serde_json::Value,
+  pub attempts: i32,
+  pub created_at: DateTime<Utc>,
+}
+
+/// Represent the row of the af_workspace_webhook table
+#[derive(Debug, Clone, FromRow)]
+pub struct AFWorkspaceWebhookRow {
+  pub id: Uuid,
+  pub workspace_id: Uuid,
+  pub url: String,
+  pub secret: String,
+  pub event_types: Vec<String>,
+  pub batch: bool,
+  pub created_by: i64,
+  pub created_at: DateTime<Utc>,
+}
+
+/// Represent the row of the af_workspace_usage_daily table
+#[derive(Debug, Clone, FromRow)]
+pub struct AFWorkspaceUsageDailyRow {
+  pub workspace_id: Uuid,
+  pub workspace_name: Option<String>,
+  pub day: NaiveDate,
+  pub storage_bytes: i64,
+  pub ai_search_requests: i64,
+  pub ai_tokens: i64,
+  pub active_members: i64,
+  pub bandwidth_in: i64,
+  pub bandwidth_out: i64,
+  pub rolled_up_at: DateTime<Utc>,
+}
@@ -1,11 +1,12 @@
 use app_error::AppError;
+use chrono::{DateTime, Utc};
 use database_entity::dto::{
   PatchPublishedCollab, PublishCollabItem, PublishCollabKey, PublishInfo, WorkspaceNamespace,
 };
-use sqlx::{Executor, PgPool, Postgres, QueryBuilder};
+use sqlx::{Executor, PgPool, Postgres, QueryBuilder, Transaction};
 use uuid::Uuid;
 
-use crate::pg_row::AFPublishViewWithPublishInfo;
+use crate::pg_row::{AFPublishViewWithPublishInfo, AFPublishedRevisionRow};
 
 pub async fn select_user_is_collab_publisher_for_all_views(
   pg_pool: &PgPool,
@@ -243,7 +244,7 @@ async fn delete_published_collabs(
 
 #[inline]
 pub async fn insert_or_replace_publish_collabs(
-  pg_pool: &PgPool,
+  txn: &mut Transaction<'_, Postgres>,
   workspace_id: &Uuid,
   publisher_uuid: &Uuid,
   publish_items: Vec<PublishCollabItem<serde_json::Value, Vec<u8>>>,
@@ -264,10 +265,9 @@ pub async fn insert_or_replace_publish_collabs(
     duplicate_enabled_list.push(item.duplicate_enabled);
   });
 
-  let mut txn = pg_pool.begin().await?;
-  delete_published_collabs(&mut txn, workspace_id, &publish_names).await?;
+  delete_published_collabs(txn, workspace_id, &publish_names).await?;
 
-  let res = sqlx::query!(
+  let res = sqlx::query(
     r#"
       INSERT INTO af_published_collab (workspace_id, view_id, publish_name, published_by, metadata, blob, comments_enabled, duplicate_enabled)
       SELECT * FROM UNNEST(
@@ -284,18 +284,20 @@ pub async fn insert_or_replace_publish_collabs(
       SET metadata = EXCLUDED.metadata,
           blob = EXCLUDED.blob,
           published_by = EXCLUDED.published_by,
-          publish_name = EXCLUDED.publish_name
+          publish_name = EXCLUDED.publish_name,
+          source_fingerprint = NULL,
+          revision_created_at = NOW()
     "#,
-    workspace_id,
-    &view_ids,
-    &publish_names,
-    publisher_uuid,
-    &metadatas,
-    &blobs,
-    &comments_enabled_list,
-    &duplicate_enabled_list,
-    item_count as i32,
   )
+  .bind(workspace_id)
+  .bind(&view_ids)
+  .bind(&publish_names)
+  .bind(publisher_uuid)
+  .bind(&metadatas)
+  .bind(&blobs)
+  .bind(&comments_enabled_list)
+  .bind(&duplicate_enabled_list)
+  .bind(item_count as i32)
   .execute(txn.as_mut())
   .await?;
 
@@ -305,8 +307,6 @@ pub async fn insert_or_replace_publish_collabs(
       workspace_id, publisher_uuid, res.rows_affected(), item_count
     );
   }
-
-  txn.commit().await?;
   Ok(())
 }
 
@@ -734,3 +734,266 @@ pub async fn select_published_view_ids_with_publish_info_for_workspace<
 
   Ok(res)
 }
+
+/// Records the digest of the collabs the published revision of the view was generated from.
+#[inline]
+pub async fn update_published_collab_source_fingerprint<
+  'a,
+  E: Executor<'a, Database = Postgres>,
+>(
+  executor: E,
+  workspace_id: &Uuid,
+  view_id: &Uuid,
+  source_fingerprint: &str,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_published_collab
+      SET source_fingerprint = $3
+      WHERE workspace_id = $1
+        AND view_id = $2
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(view_id)
+  .bind(source_fingerprint)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+#[inline]
+pub async fn select_published_collab_etag<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  publish_namespace: &str,
+  publish_name: &str,
+) -> Result<Option<String>, AppError> {
+  let res = sqlx::query_scalar::<_, Option<String>>(
+    r#"
+      SELECT revision_etag
+      FROM af_published_collab
+      WHERE workspace_id = (SELECT workspace_id FROM af_workspace_namespace WHERE namespace = $1)
+        AND unpublished_at IS NULL
+        AND publish_name = $2
+    "#,
+  )
+  .bind(publish_namespace)
+  .bind(publish_name)
+  .fetch_optional(executor)
+  .await?;
+  Ok(res.flatten())
+}
+
+/// Returns the blob of the given revision of a published view, provided the revision is the
+/// published one or was replaced less than `grace_period_secs` ago.
+pub async fn select_published_collab_blob_at_revision<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  publish_namespace: &str,
+  publish_name: &str,
+  revision_etag: &str,
+  grace_period_secs: i64,
+) -> Result<Option<Vec<u8>>, AppError> {
+  let res = sqlx::query_scalar::<_, Vec<u8>>(
+    r#"
+      WITH published AS (
+        SELECT view_id, revision_etag, blob
+        FROM af_published_collab
+        WHERE workspace_id = (SELECT workspace_id FROM af_workspace_namespace WHERE namespace = $1)
+          AND unpublished_at IS NULL
+          AND publish_name = $2
+      )
+      SELECT blob FROM published WHERE revision_etag = $3
+      UNION ALL
+      SELECT apcr.blob
+      FROM af_published_collab_revision apcr
+      JOIN published ON published.view_id = apcr.view_id
+      WHERE apcr.revision_etag = $3
+        AND apcr.superseded_at > NOW() - make_interval(secs => $4)
+      LIMIT 1
+    "#,
+  )
+  .bind(publish_namespace)
+  .bind(publish_name)
+  .bind(revision_etag)
+  .bind(grace_period_secs as f64)
+  .fetch_optional(executor)
+  .await?;
+  Ok(res)
+}
+
+/// Deletes the replaced revisions of published views which are past their grace period.
+pub async fn delete_expired_published_collab_revisions<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  grace_period_secs: i64,
+) -> Result<u64, AppError> {
+  let res = sqlx::query(
+    r#"
+      DELETE FROM af_published_collab_revision
+      WHERE superseded_at <= NOW() - make_interval(secs => $1)
+    "#,
+  )
+  .bind(grace_period_secs as f64)
+  .execute(executor)
+  .await?;
+  Ok(res.rows_affected())
+}
+
+/// Returns every namespace and publish name the views are reachable at, whether they are still
+/// published or not.
+pub async fn select_published_collab_addresses<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  view_ids: &[Uuid],
+) -> Result<Vec<(String, String)>, AppError> {
+  let res = sqlx::query_as::<_, (String, String)>(
+    r#"
+      SELECT awn.namespace, apc.publish_name
+      FROM af_published_collab apc
+      JOIN af_workspace_namespace awn ON awn.workspace_id = apc.workspace_id
+      WHERE apc.workspace_id = $1
+        AND apc.view_id = ANY($2)
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(view_ids)
+  .fetch_all(executor)
+  .await?;
+  Ok(res)
+}
+
+pub async fn select_published_collab_revision<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  view_id: &Uuid,
+) -> Result<Option<AFPublishedRevisionRow>, AppError> {
+  let res = sqlx::query_as::<_, AFPublishedRevisionRow>(
+    r#"
+      SELECT
+        apc.publish_name,
+        apc.comments_enabled,
+        apc.duplicate_enabled,
+        apc.revision_etag,
+        apc.source_fingerprint,
+        apc.revision_created_at,
+        asp.publish_at AS scheduled_publish_at
+      FROM af_published_collab apc
+      LEFT JOIN af_scheduled_publish asp
+        ON asp.workspace_id = apc.workspace_id AND asp.view_id = apc.view_id
+      WHERE apc.workspace_id = $1
+        AND apc.view_id = $2
+        AND apc.unpublished_at IS NULL
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(view_id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(res)
+}
+
+/// Schedules `blob` to replace the published revision of the view at `publish_at`, replacing the
+/// revision scheduled before if any.
+#[allow(clippy::too_many_arguments)]
+pub async fn upsert_scheduled_publish<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  view_id: &Uuid,
+  scheduled_by: i64,
+  metadata: &serde_json::Value,
+  blob: &[u8],
+  source_fingerprint: &str,
+  publish_at: &DateTime<Utc>,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      INSERT INTO af_scheduled_publish
+        (workspace_id, view_id, scheduled_by, metadata, blob, source_fingerprint, publish_at)
+      VALUES ($1, $2, $3, $4, $5, $6, $7)
+      ON CONFLICT (workspace_id, view_id) DO UPDATE
+      SET scheduled_by = EXCLUDED.scheduled_by,
+          metadata = EXCLUDED.metadata,
+          blob = EXCLUDED.blob,
+          source_fingerprint = EXCLUDED.source_fingerprint,
+          publish_at = EXCLUDED.publish_at,
+          created_at = NOW()
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(view_id)
+  .bind(scheduled_by)
+  .bind(metadata)
+  .bind(blob)
+  .bind(source_fingerprint)
+  .bind(publish_at)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+#[inline]
+pub async fn delete_scheduled_publish<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  view_id: &Uuid,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      DELETE FROM af_scheduled_publish
+      WHERE workspace_id = $1
+        AND view_id = $2
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(view_id)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+/// Replaces the published revision of the views by their scheduled revision, for at most `limit`
+/// revisions which are due. Scheduled revisions of views which were unpublished in the meantime
+/// are dropped. Returns the views whose published revision was replaced.
+pub async fn apply_due_scheduled_publishes<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  limit: i64,
+) -> Result<Vec<PublishCollabKey>, AppError> {
+  let res = sqlx::query_as::<_, (Uuid, Uuid)>(
+    r#"
+      WITH due AS (
+        DELETE FROM af_scheduled_publish
+        WHERE (workspace_id, view_id) IN (
+          SELECT workspace_id, view_id
+          FROM af_scheduled_publish
+          WHERE publish_at <= NOW()
+          ORDER BY publish_at
+          LIMIT $1
+          FOR UPDATE SKIP LOCKED
+        )
+        RETURNING workspace_id, view_id, scheduled_by, metadata, blob, source_fingerprint
+      )
+      UPDATE af_published_collab apc
+      SET blob = due.blob,
+          metadata = due.metadata,
+          published_by = due.scheduled_by,
+          source_fingerprint = due.source_fingerprint,
+          revision_created_at = NOW()
+      FROM due
+      WHERE apc.workspace_id = due.workspace_id
+        AND apc.view_id = due.view_id
+        AND apc.unpublished_at IS NULL
+      RETURNING apc.workspace_id, apc.view_id
+    "#,
+  )
+  .bind(limit)
+  .fetch_all(executor)
+  .await?;
+  Ok(
+    res
+      .into_iter()
+      .map(|(workspace_id, view_id)| PublishCollabKey {
+        workspace_id,
+        view_id,
+      })
+      .collect(),
+  )
+}
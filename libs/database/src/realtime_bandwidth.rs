@@ -0,0 +1,88 @@
+use app_error::AppError;
+use chrono::NaiveDate;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::AFWorkspaceBandwidthRow;
+
+/// Stores the bandwidth used by the given workspaces on a day. The totals are read from the
+/// counters every instance increments, so they only grow: storing the same totals again, or older
+/// ones, leaves the row as is. Workspaces which were deleted in the meantime are skipped.
+#[allow(clippy::too_many_arguments)]
+pub async fn upsert_workspace_realtime_bandwidth<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  day: NaiveDate,
+  workspace_ids: &[Uuid],
+  bytes_in: &[i64],
+  bytes_out: &[i64],
+  updates_out: &[i64],
+  awareness_out: &[i64],
+  control_out: &[i64],
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      INSERT INTO af_workspace_realtime_bandwidth
+        (workspace_id, day, bytes_in, bytes_out, updates_out, awareness_out, control_out)
+      SELECT t.workspace_id, $1, t.bytes_in, t.bytes_out, t.updates_out, t.awareness_out,
+        t.control_out
+      FROM UNNEST($2::uuid[], $3::bigint[], $4::bigint[], $5::bigint[], $6::bigint[], $7::bigint[])
+        AS t(workspace_id, bytes_in, bytes_out, updates_out, awareness_out, control_out)
+      WHERE EXISTS (SELECT 1 FROM af_workspace w WHERE w.workspace_id = t.workspace_id)
+      ON CONFLICT (workspace_id, day) DO UPDATE SET
+        bytes_in = GREATEST(af_workspace_realtime_bandwidth.bytes_in, EXCLUDED.bytes_in),
+        bytes_out = GREATEST(af_workspace_realtime_bandwidth.bytes_out, EXCLUDED.bytes_out),
+        updates_out = GREATEST(af_workspace_realtime_bandwidth.updates_out, EXCLUDED.updates_out),
+        awareness_out =
+          GREATEST(af_workspace_realtime_bandwidth.awareness_out, EXCLUDED.awareness_out),
+        control_out = GREATEST(af_workspace_realtime_bandwidth.control_out, EXCLUDED.control_out),
+        updated_at = NOW()
+    "#,
+  )
+  .bind(day)
+  .bind(workspace_ids)
+  .bind(bytes_in)
+  .bind(bytes_out)
+  .bind(updates_out)
+  .bind(awareness_out)
+  .bind(control_out)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+/// Returns the workspaces which exchanged the most bytes with the realtime clients between the
+/// two days, both included.
+pub async fn select_top_workspaces_by_realtime_bandwidth<
+  'a,
+  E: Executor<'a, Database = Postgres>,
+>(
+  executor: E,
+  from: NaiveDate,
+  to: NaiveDate,
+  limit: i64,
+) -> Result<Vec<AFWorkspaceBandwidthRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFWorkspaceBandwidthRow>(
+    r#"
+      SELECT
+        b.workspace_id,
+        w.workspace_name,
+        SUM(b.bytes_in)::BIGINT AS bytes_in,
+        SUM(b.bytes_out)::BIGINT AS bytes_out,
+        SUM(b.updates_out)::BIGINT AS updates_out,
+        SUM(b.awareness_out)::BIGINT AS awareness_out,
+        SUM(b.control_out)::BIGINT AS control_out
+      FROM af_workspace_realtime_bandwidth b
+      JOIN af_workspace w ON w.workspace_id = b.workspace_id
+      WHERE b.day BETWEEN $1 AND $2
+      GROUP BY b.workspace_id, w.workspace_name
+      ORDER BY SUM(b.bytes_in + b.bytes_out) DESC
+      LIMIT $3
+    "#,
+  )
+  .bind(from)
+  .bind(to)
+  .bind(limit)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
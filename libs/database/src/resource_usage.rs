@@ -1,11 +1,22 @@
-use crate::pg_row::AFBlobMetadataRow;
+use crate::pg_row::{
+  AFBlobContentRow, AFBlobMetadataRow, AFBlobMultipartUploadPartRow, AFBlobMultipartUploadRow,
+  AFBlobPendingUploadRow, AFBlobVersionRow,
+};
 use app_error::AppError;
+use chrono::{DateTime, Utc};
+use futures_util::stream::BoxStream;
 use rust_decimal::prelude::ToPrimitive;
+use sha2::{Digest, Sha256};
+use shared_entity::dto::billing_dto::RecomputedWorkspaceUsage;
+use shared_entity::dto::workspace_dto::{FileTypeCategory, FileTypeUsage, MemberBlobUsage};
 use sqlx::types::Decimal;
 use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::collections::{HashMap, HashSet};
 use std::ops::DerefMut;
 
-use tracing::instrument;
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, RedisResult};
+use tracing::{error, instrument, warn};
 use uuid::Uuid;
 
 #[instrument(level = "trace", skip_all)]
@@ -32,184 +43,1662 @@ pub async fn is_blob_metadata_exists(
   Ok(exists.0)
 }
 
+/// Recorded along with the metadata of an uploaded blob.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlobAttributes {
+  /// The uid of the user who uploaded the blob, if known.
+  pub uploaded_by: Option<i64>,
+  /// When the blob expires: it's no longer served from then on, and deleted by the appflowy
+  /// worker. `None` keeps the blob until it's deleted. The file ids being derived from the
+  /// content, uploading a blob again never shortens its life: a permanent blob stays permanent
+  /// and a temporary one keeps the later of the two expiries.
+  pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl BlobAttributes {
+  pub fn uploaded_by(uid: i64) -> Self {
+    Self {
+      uploaded_by: Some(uid),
+      expires_at: None,
+    }
+  }
+
+  pub fn with_expires_at(mut self, expires_at: Option<DateTime<Utc>>) -> Self {
+    self.expires_at = expires_at;
+    self
+  }
+}
+
+/// Insert or replace the metadata of a blob, with its [BlobAttributes]. A replaced blob keeps its
+/// uploader when the new one isn't known, and gets the expiry of the new one. Returns the change
+/// of the usage of the workspace in bytes: the size of the blob, minus the size of the blob it
+/// replaced.
 #[instrument(level = "trace", skip_all, err)]
-pub async fn insert_blob_metadata(
-  pg_pool: &PgPool,
+pub async fn insert_blob_metadata<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
   file_id: &str,
   workspace_id: &Uuid,
   file_type: &str,
   file_size: usize,
-) -> Result<(), AppError> {
-  let res = sqlx::query!(
+  content_hash: Option<&str>,
+  attributes: BlobAttributes,
+) -> Result<i64, AppError> {
+  let (delta,): (i64,) = sqlx::query_as(
     r#"
+        WITH replaced AS (
+            SELECT file_size FROM af_blob_metadata
+            WHERE workspace_id = $1 AND file_id = $2
+            FOR UPDATE
+        )
         INSERT INTO af_blob_metadata
-        (workspace_id, file_id, file_type, file_size)
-        VALUES ($1, $2, $3, $4)
+        (workspace_id, file_id, file_type, file_size, content_hash, uploaded_by, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
         ON CONFLICT (workspace_id, file_id) DO UPDATE SET
             file_type = $3,
-            file_size = $4
+            file_size = $4,
+            content_hash = $5,
+            object_key = NULL,
+            uploaded_by = COALESCE($6, af_blob_metadata.uploaded_by),
+            expires_at = CASE
+              WHEN af_blob_metadata.expires_at IS NULL OR $7 IS NULL THEN NULL
+              ELSE GREATEST(af_blob_metadata.expires_at, $7)
+            END
+        RETURNING $4 - COALESCE((SELECT file_size FROM replaced), 0)
         "#,
-    workspace_id,
-    file_id,
-    file_type,
-    file_size as i64,
   )
+  .bind(workspace_id)
+  .bind(file_id)
+  .bind(file_type)
+  .bind(file_size as i64)
+  .bind(content_hash)
+  .bind(attributes.uploaded_by)
+  .bind(attributes.expires_at)
+  .fetch_one(executor)
+  .await?;
+  Ok(delta)
+}
+
+/// Insert or replace the metadata of a blob, unless the usage of the workspace would then exceed
+/// `limit_bytes`, see [check_workspace_storage_limit]. Returns the change of the usage of the
+/// workspace in bytes, as [insert_blob_metadata].
+#[instrument(level = "trace", skip_all, err)]
+pub async fn insert_blob_metadata_with_limit(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  file_id: &str,
+  file_type: &str,
+  file_size: usize,
+  limit_bytes: u64,
+  attributes: BlobAttributes,
+) -> Result<i64, AppError> {
+  check_workspace_storage_limit(tx, workspace_id, file_id, file_size as u64, limit_bytes).await?;
+  let (delta,): (i64,) = sqlx::query_as(
+    r#"
+    WITH replaced AS (
+        SELECT file_size FROM af_blob_metadata
+        WHERE workspace_id = $1 AND file_id = $2
+        FOR UPDATE
+    )
+    INSERT INTO af_blob_metadata
+    (workspace_id, file_id, file_type, file_size, uploaded_by, expires_at)
+    VALUES ($1, $2, $3, $4, $5, $6)
+    ON CONFLICT (workspace_id, file_id) DO UPDATE SET
+        file_type = $3,
+        file_size = $4,
+        uploaded_by = COALESCE($5, af_blob_metadata.uploaded_by),
+        expires_at = CASE
+          WHEN af_blob_metadata.expires_at IS NULL OR $6 IS NULL THEN NULL
+          ELSE GREATEST(af_blob_metadata.expires_at, $6)
+        END
+    RETURNING $4 - COALESCE((SELECT file_size FROM replaced), 0)
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .bind(file_type)
+  .bind(file_size as i64)
+  .bind(attributes.uploaded_by)
+  .bind(attributes.expires_at)
+  .fetch_one(tx.deref_mut())
+  .await?;
+  Ok(delta)
+}
+
+/// Fail with [AppError::StorageSpaceNotEnough] when storing `file_size` bytes under `file_id`
+/// would take the usage of the workspace over `limit_bytes`. The pending uploads count toward the
+/// usage, and the blob or pending upload being replaced doesn't. The workspace row is locked until
+/// the end of the transaction, so the concurrent uploads of a workspace are checked one after the
+/// other and can't go over the limit together.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn check_workspace_storage_limit(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  file_id: &str,
+  file_size: u64,
+  limit_bytes: u64,
+) -> Result<(), AppError> {
+  let locked: Option<(Uuid,)> = sqlx::query_as(
+    r#"
+    SELECT workspace_id FROM af_workspace
+    WHERE workspace_id = $1
+    FOR NO KEY UPDATE
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_optional(tx.deref_mut())
+  .await?;
+  if locked.is_none() {
+    return Err(AppError::RecordNotFound(format!(
+      "workspace {} not found",
+      workspace_id
+    )));
+  }
+
+  let row: (Option<Decimal>,) = sqlx::query_as(
+    r#"
+    SELECT
+      COALESCE((SELECT usage_bytes FROM af_workspace_usage WHERE workspace_id = $1), 0)
+      - COALESCE((
+        SELECT file_size FROM af_blob_metadata
+        WHERE workspace_id = $1 AND file_id = $2
+      ), 0)
+      + COALESCE((
+        SELECT SUM(file_size) FROM af_blob_pending_upload
+        WHERE workspace_id = $1 AND file_id <> $2
+      ), 0);
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .fetch_one(tx.deref_mut())
+  .await?;
+  let usage = row.0.and_then(|decimal| decimal.to_u64()).unwrap_or(0);
+  if usage.saturating_add(file_size) > limit_bytes {
+    return Err(AppError::StorageSpaceNotEnough);
+  }
+  Ok(())
+}
+
+/// Insert or replace the pending upload of a blob, for which the client was given a presigned
+/// url. The blob only gets its metadata once the upload is completed, with
+/// [insert_blob_metadata].
+#[instrument(level = "trace", skip_all, err)]
+pub async fn insert_pending_blob_metadata(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  file_id: &str,
+  object_key: &str,
+  file_type: &str,
+  file_size: u64,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      INSERT INTO af_blob_pending_upload
+      (workspace_id, file_id, object_key, file_type, file_size)
+      VALUES ($1, $2, $3, $4, $5)
+      ON CONFLICT (workspace_id, file_id) DO UPDATE SET
+          object_key = $3,
+          file_type = $4,
+          file_size = $5,
+          created_at = CURRENT_TIMESTAMP
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .bind(object_key)
+  .bind(file_type)
+  .bind(file_size as i64)
+  .execute(tx.deref_mut())
+  .await?;
+  Ok(())
+}
+
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_pending_blob_metadata(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  file_id: &str,
+) -> Result<Option<AFBlobPendingUploadRow>, AppError> {
+  let row = sqlx::query_as::<_, AFBlobPendingUploadRow>(
+    r#"
+      SELECT * FROM af_blob_pending_upload
+      WHERE workspace_id = $1 AND file_id = $2
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .fetch_optional(pg_pool)
+  .await?;
+  Ok(row)
+}
+
+/// Delete the pending upload of a blob and return it, `None` when there is none, such as when it
+/// expired and was deleted by the worker.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn delete_pending_blob_metadata(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  file_id: &str,
+) -> Result<Option<AFBlobPendingUploadRow>, AppError> {
+  let row = sqlx::query_as::<_, AFBlobPendingUploadRow>(
+    r#"
+      DELETE FROM af_blob_pending_upload
+      WHERE workspace_id = $1 AND file_id = $2
+      RETURNING *
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .fetch_optional(tx.deref_mut())
+  .await?;
+  Ok(row)
+}
+
+/// Delete up to `limit` pending uploads created before `created_before`, oldest first. Returns
+/// the object keys to delete from the bucket along with them, leaving out the ones of the blobs
+/// which got their metadata through another upload meanwhile. The uploads being completed are
+/// skipped.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn delete_expired_pending_blob_metadata(
+  pg_pool: &PgPool,
+  created_before: DateTime<Utc>,
+  limit: i64,
+) -> Result<Vec<String>, AppError> {
+  let rows: Vec<(String, bool)> = sqlx::query_as(
+    r#"
+      DELETE FROM af_blob_pending_upload AS pending
+      WHERE (pending.workspace_id, pending.file_id) IN (
+        SELECT workspace_id, file_id FROM af_blob_pending_upload
+        WHERE created_at < $1
+        ORDER BY created_at
+        LIMIT $2
+        FOR UPDATE SKIP LOCKED
+      )
+      RETURNING pending.object_key, EXISTS (
+        SELECT 1 FROM af_blob_metadata
+        WHERE workspace_id = pending.workspace_id AND file_id = pending.file_id
+      )
+    "#,
+  )
+  .bind(created_before)
+  .bind(limit)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(
+    rows
+      .into_iter()
+      .filter(|(_, committed)| !committed)
+      .map(|(object_key, _)| object_key)
+      .collect(),
+  )
+}
+
+#[instrument(level = "trace", skip_all, err)]
+pub async fn insert_multipart_upload(
+  pg_pool: &PgPool,
+  upload_id: &str,
+  workspace_id: &Uuid,
+  file_id: &str,
+  object_key: &str,
+  file_type: &str,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      INSERT INTO af_blob_multipart_upload
+      (upload_id, workspace_id, file_id, object_key, file_type)
+      VALUES ($1, $2, $3, $4, $5)
+    "#,
+  )
+  .bind(upload_id)
+  .bind(workspace_id)
+  .bind(file_id)
+  .bind(object_key)
+  .bind(file_type)
   .execute(pg_pool)
   .await?;
-  let n = res.rows_affected();
-  if n != 1 {
-    tracing::error!("insert_blob_metadata: rows_affected: {}", n);
+  Ok(())
+}
+
+/// Return the multipart upload of the blob, `None` when there is none, such as when it was
+/// completed or aborted.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_multipart_upload(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  file_id: &str,
+  upload_id: &str,
+) -> Result<Option<AFBlobMultipartUploadRow>, AppError> {
+  let row = sqlx::query_as::<_, AFBlobMultipartUploadRow>(
+    r#"
+      SELECT * FROM af_blob_multipart_upload
+      WHERE upload_id = $1 AND workspace_id = $2 AND file_id = $3
+    "#,
+  )
+  .bind(upload_id)
+  .bind(workspace_id)
+  .bind(file_id)
+  .fetch_optional(pg_pool)
+  .await?;
+  Ok(row)
+}
+
+/// Record a part of a multipart upload, replacing the part uploaded before with the same number,
+/// and push back the expiration of the upload.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn upsert_multipart_upload_part(
+  pg_pool: &PgPool,
+  upload_id: &str,
+  part_number: i32,
+  e_tag: &str,
+  part_size: usize,
+) -> Result<(), AppError> {
+  let mut tx = pg_pool.begin().await?;
+  let updated = sqlx::query(
+    r#"
+      UPDATE af_blob_multipart_upload
+      SET updated_at = CURRENT_TIMESTAMP
+      WHERE upload_id = $1
+    "#,
+  )
+  .bind(upload_id)
+  .execute(tx.deref_mut())
+  .await?;
+  if updated.rows_affected() == 0 {
+    return Err(AppError::RecordNotFound(format!(
+      "no multipart upload with id {}",
+      upload_id
+    )));
   }
+  sqlx::query(
+    r#"
+      INSERT INTO af_blob_multipart_upload_part (upload_id, part_number, e_tag, part_size)
+      VALUES ($1, $2, $3, $4)
+      ON CONFLICT (upload_id, part_number) DO UPDATE SET
+          e_tag = $3,
+          part_size = $4,
+          uploaded_at = CURRENT_TIMESTAMP
+    "#,
+  )
+  .bind(upload_id)
+  .bind(part_number)
+  .bind(e_tag)
+  .bind(part_size as i64)
+  .execute(tx.deref_mut())
+  .await?;
+  tx.commit().await?;
+  Ok(())
+}
+
+/// Return the parts uploaded so far for a multipart upload, by part number.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_multipart_upload_parts(
+  pg_pool: &PgPool,
+  upload_id: &str,
+) -> Result<Vec<AFBlobMultipartUploadPartRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFBlobMultipartUploadPartRow>(
+    r#"
+      SELECT * FROM af_blob_multipart_upload_part
+      WHERE upload_id = $1
+      ORDER BY part_number
+    "#,
+  )
+  .bind(upload_id)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(rows)
+}
+
+/// Delete a multipart upload along with its parts, once it's completed.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn delete_multipart_upload(pg_pool: &PgPool, upload_id: &str) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      DELETE FROM af_blob_multipart_upload
+      WHERE upload_id = $1
+    "#,
+  )
+  .bind(upload_id)
+  .execute(pg_pool)
+  .await?;
   Ok(())
 }
 
+/// Delete up to `limit` multipart uploads without any part uploaded since `updated_before`, the
+/// least recently active first. Returns them, so that they can be aborted in the bucket.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn delete_expired_multipart_uploads(
+  pg_pool: &PgPool,
+  updated_before: DateTime<Utc>,
+  limit: i64,
+) -> Result<Vec<AFBlobMultipartUploadRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFBlobMultipartUploadRow>(
+    r#"
+      DELETE FROM af_blob_multipart_upload
+      WHERE upload_id IN (
+        SELECT upload_id FROM af_blob_multipart_upload
+        WHERE updated_at < $1
+        ORDER BY updated_at
+        LIMIT $2
+        FOR UPDATE SKIP LOCKED
+      )
+      RETURNING *
+    "#,
+  )
+  .bind(updated_before)
+  .bind(limit)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(rows)
+}
+
 #[derive(Debug, Clone)]
 pub struct BulkInsertMeta {
   pub object_id: String,
   pub file_id: String,
   pub file_type: String,
   pub file_size: i64,
+  /// The sha256 of the content, hex encoded, see [blob_content_hash]
+  pub content_hash: Option<String>,
+}
+
+/// The metadata inserted by [insert_blob_metadata_bulk].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BulkInsertedMeta {
+  pub rows_affected: u64,
+  /// The total size of the inserted blobs, by which the usage of the workspace grew.
+  pub inserted_bytes: i64,
+  /// Number of blobs skipped for being larger than [max_blob_size_for_workspace].
+  pub too_large: u64,
 }
 
+/// The largest blob the workspace can upload, as set for the subscription plan recorded by the
+/// billing service. `None` when the workspace has no recorded plan, such as on a self-hosted
+/// instance, or when no limit is set for the plan.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn max_blob_size_for_workspace<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<Option<i64>, AppError> {
+  let limit = sqlx::query_scalar::<_, Option<i64>>(
+    r#"
+      SELECT l.max_blob_size
+      FROM af_workspace w
+      LEFT JOIN af_workspace_subscription_plan s ON s.workspace_id = w.workspace_id
+      LEFT JOIN af_plan_blob_size_limit l ON l.plan = s.plan
+      WHERE w.workspace_id = $1
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_optional(executor)
+  .await?
+  .ok_or_else(|| AppError::RecordNotFound(format!("workspace {} does not exist", workspace_id)))?;
+  Ok(limit)
+}
+
+/// Insert the metadata of the blobs which don't have any yet, the others are skipped, as are the
+/// blobs larger than the limit of the plan of the workspace, see [max_blob_size_for_workspace].
+/// The inserted blobs are attributed to the user `uploaded_by`, such as the user who imported
+/// them.
 #[instrument(level = "trace", skip_all, err)]
 pub async fn insert_blob_metadata_bulk<'a, E: Executor<'a, Database = Postgres>>(
   executor: E,
   workspace_id: &Uuid,
   metadata: Vec<BulkInsertMeta>,
-) -> Result<u64, sqlx::Error> {
+  uploaded_by: Option<i64>,
+) -> Result<BulkInsertedMeta, sqlx::Error> {
   let mut file_ids = Vec::with_capacity(metadata.len());
   let mut file_types = Vec::with_capacity(metadata.len());
   let mut file_sizes = Vec::with_capacity(metadata.len());
+  let mut content_hashes = Vec::with_capacity(metadata.len());
 
   for BulkInsertMeta {
     object_id,
     file_id,
     file_type,
     file_size,
+    content_hash,
   } in metadata
   {
     // we use BlobPathV1 to generate file_id
     file_ids.push(format!("{}_{}", object_id, file_id));
     file_types.push(file_type);
     file_sizes.push(file_size);
+    content_hashes.push(content_hash);
   }
   let query = r#"
-        INSERT INTO af_blob_metadata (workspace_id, file_id, file_type, file_size)
-        SELECT $1, unnest($2::text[]), unnest($3::text[]), unnest($4::int8[])
-        ON CONFLICT DO NOTHING
+        WITH size_limit AS (
+            SELECT l.max_blob_size
+            FROM af_workspace w
+            LEFT JOIN af_workspace_subscription_plan s ON s.workspace_id = w.workspace_id
+            LEFT JOIN af_plan_blob_size_limit l ON l.plan = s.plan
+            WHERE w.workspace_id = $1
+        ),
+        meta AS (
+            SELECT *
+            FROM unnest($2::text[], $3::text[], $4::int8[], $5::text[])
+              AS m(file_id, file_type, file_size, content_hash)
+        ),
+        inserted AS (
+            INSERT INTO af_blob_metadata
+              (workspace_id, file_id, file_type, file_size, content_hash, uploaded_by)
+            SELECT $1, m.file_id, m.file_type, m.file_size, m.content_hash, $6
+            FROM meta m
+            WHERE m.file_size <= COALESCE((SELECT max_blob_size FROM size_limit), m.file_size)
+            ON CONFLICT DO NOTHING
+            RETURNING file_size
+        )
+        SELECT
+            (SELECT COUNT(*) FROM inserted),
+            (SELECT COALESCE(SUM(file_size), 0)::BIGINT FROM inserted),
+            (SELECT COUNT(*) FROM meta
+             WHERE meta.file_size > COALESCE((SELECT max_blob_size FROM size_limit), meta.file_size))
     "#;
 
-  let result = sqlx::query(query)
+  let (rows_affected, inserted_bytes, too_large): (i64, i64, i64) = sqlx::query_as(query)
     .bind(workspace_id)
     .bind(file_ids)
     .bind(file_types)
     .bind(file_sizes)
-    .execute(executor)
+    .bind(content_hashes)
+    .bind(uploaded_by)
+    .fetch_one(executor)
     .await?;
 
-  Ok(result.rows_affected())
+  Ok(BulkInsertedMeta {
+    rows_affected: rows_affected as u64,
+    inserted_bytes,
+    too_large: too_large as u64,
+  })
 }
+/// The sha256 of the content of a blob, hex encoded
+pub fn blob_content_hash(content: &[u8]) -> String {
+  format!("{:x}", Sha256::digest(content))
+}
+
+/// Return the object storing the given content in the workspace, if a blob was uploaded with it
 #[instrument(level = "trace", skip_all, err)]
-#[inline]
-pub async fn delete_blob_metadata(
-  tx: &mut Transaction<'_, sqlx::Postgres>,
+pub async fn find_blob_by_content_hash(
+  pg_pool: &PgPool,
   workspace_id: &Uuid,
-  file_id: &str,
-) -> Result<(), AppError> {
-  let result = sqlx::query!(
+  content_hash: &str,
+) -> Result<Option<AFBlobContentRow>, AppError> {
+  let row = sqlx::query_as::<_, AFBlobContentRow>(
     r#"
-        DELETE FROM af_blob_metadata
-        WHERE workspace_id = $1 AND file_id = $2
-        "#,
-    workspace_id,
-    file_id,
+      SELECT * FROM af_blob_content
+      WHERE workspace_id = $1 AND content_hash = $2
+    "#,
   )
-  .execute(tx.deref_mut())
+  .bind(workspace_id)
+  .bind(content_hash)
+  .fetch_optional(pg_pool)
   .await?;
-  let n = result.rows_affected();
-  tracing::info!("delete_blob_metadata: rows_affected: {}", n);
-  Ok(())
+  Ok(row)
 }
 
+/// Add a reference to the object storing the given content. Returns its object key, or `None`
+/// when the last reference to the content was released in the meantime.
 #[instrument(level = "trace", skip_all, err)]
-pub async fn get_blob_metadata(
-  pg_pool: &PgPool,
+pub async fn acquire_blob_content(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
   workspace_id: &Uuid,
-  metadata_key: &str,
-) -> Result<AFBlobMetadataRow, AppError> {
-  tracing::trace!(
-    "get_blob_metadata: workspace_id: {}, metadata_key: {}",
-    workspace_id,
-    metadata_key
-  );
-  // file_id is the BlobPath's blob_metadata_key
-  let metadata = sqlx::query_as!(
-    AFBlobMetadataRow,
+  content_hash: &str,
+) -> Result<Option<String>, AppError> {
+  let object_key: Option<(String,)> = sqlx::query_as(
     r#"
-        SELECT * FROM af_blob_metadata
-        WHERE workspace_id = $1 AND file_id = $2
-        "#,
-    workspace_id,
-    metadata_key,
+      UPDATE af_blob_content SET ref_count = ref_count + 1
+      WHERE workspace_id = $1 AND content_hash = $2
+      RETURNING object_key
+    "#,
   )
-  .fetch_one(pg_pool)
+  .bind(workspace_id)
+  .bind(content_hash)
+  .fetch_optional(tx.deref_mut())
   .await?;
-  Ok(metadata)
+  Ok(object_key.map(|(key,)| key))
 }
 
-/// Return all blob metadata of a workspace
+/// Record the object just uploaded with the given content, with one reference. When the same
+/// content was uploaded concurrently, a reference to the object recorded first is added
+/// instead. Returns the object key of the content.
 #[instrument(level = "trace", skip_all, err)]
-#[inline]
-pub async fn get_all_workspace_blob_metadata(
-  pg_pool: &PgPool,
+pub async fn insert_blob_content(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
   workspace_id: &Uuid,
-) -> Result<Vec<AFBlobMetadataRow>, AppError> {
-  let all_metadata = sqlx::query_as!(
-    AFBlobMetadataRow,
+  content_hash: &str,
+  object_key: &str,
+) -> Result<String, AppError> {
+  let (object_key,): (String,) = sqlx::query_as(
     r#"
-        SELECT * FROM af_blob_metadata
-        WHERE workspace_id = $1
-        "#,
-    workspace_id,
+      INSERT INTO af_blob_content (workspace_id, content_hash, object_key)
+      VALUES ($1, $2, $3)
+      ON CONFLICT (workspace_id, content_hash) DO UPDATE SET
+          ref_count = af_blob_content.ref_count + 1
+      RETURNING object_key
+    "#,
   )
-  .fetch_all(pg_pool)
+  .bind(workspace_id)
+  .bind(content_hash)
+  .bind(object_key)
+  .fetch_one(tx.deref_mut())
   .await?;
-  Ok(all_metadata)
+  Ok(object_key)
 }
 
-/// Return all blob ids of a workspace
+/// Insert or replace the metadata of a blob whose content is stored by the object `object_key`
+/// of af_blob_content, once a reference to it was taken. The attributes are recorded as by
+/// [insert_blob_metadata].
 #[instrument(level = "trace", skip_all, err)]
-#[inline]
-pub async fn get_all_workspace_blob_ids(
-  pg_pool: &PgPool,
+#[allow(clippy::too_many_arguments)]
+pub async fn insert_blob_content_metadata(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
   workspace_id: &Uuid,
-) -> Result<Vec<String>, AppError> {
-  let file_ids = sqlx::query!(
+  file_id: &str,
+  file_type: &str,
+  file_size: usize,
+  content_hash: &str,
+  object_key: &str,
+  attributes: BlobAttributes,
+) -> Result<i64, AppError> {
+  let (delta,): (i64,) = sqlx::query_as(
     r#"
-    SELECT file_id FROM af_blob_metadata
-    WHERE workspace_id = $1
+      WITH replaced AS (
+          SELECT file_size FROM af_blob_metadata
+          WHERE workspace_id = $1 AND file_id = $2
+          FOR UPDATE
+      )
+      INSERT INTO af_blob_metadata
+      (workspace_id, file_id, file_type, file_size, content_hash, object_key, uploaded_by,
+        expires_at)
+      VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+      ON CONFLICT (workspace_id, file_id) DO UPDATE SET
+          file_type = $3,
+          file_size = $4,
+          content_hash = $5,
+          object_key = $6,
+          uploaded_by = COALESCE($7, af_blob_metadata.uploaded_by),
+          expires_at = CASE
+            WHEN af_blob_metadata.expires_at IS NULL OR $8 IS NULL THEN NULL
+            ELSE GREATEST(af_blob_metadata.expires_at, $8)
+          END
+      RETURNING $4 - COALESCE((SELECT file_size FROM replaced), 0)
     "#,
-    workspace_id
   )
-  .fetch_all(pg_pool)
-  .await?
-  .into_iter()
-  .map(|record| record.file_id)
-  .collect();
-  Ok(file_ids)
-}
+  .bind(workspace_id)
+  .bind(file_id)
+  .bind(file_type)
+  .bind(file_size as i64)
+  .bind(content_hash)
+  .bind(object_key)
+  .bind(attributes.uploaded_by)
+  .bind(attributes.expires_at)
+  .fetch_one(tx.deref_mut())
+  .await?;
+  Ok(delta)
+}
+
+/// The objects to keep and to delete along with the metadata of some blobs, see
+/// [release_blob_contents].
+#[derive(Debug, Default)]
+pub struct ReleasedBlobContents {
+  /// The blobs whose content is tracked in af_blob_content. Their object key may be used by
+  /// other blobs, so it's never deleted with them.
+  pub shared_file_ids: HashSet<String>,
+  /// The objects of the contents which lost their last reference, and the objects the blobs
+  /// were moved to when overwritten, which no other blob uses.
+  pub orphaned_object_keys: Vec<String>,
+}
+
+/// The objects to delete along with the given blobs, as pairs of their file id and object key:
+/// the objects of the blobs which don't share their content, and the shared objects without
+/// references left.
+pub fn deleted_object_keys<'a>(
+  released: &'a ReleasedBlobContents,
+  blobs: impl IntoIterator<Item = (String, String)> + 'a,
+) -> impl Iterator<Item = String> + 'a {
+  blobs
+    .into_iter()
+    .filter(|(file_id, _)| !released.shared_file_ids.contains(file_id))
+    .map(|(_, object_key)| object_key)
+    .chain(released.orphaned_object_keys.iter().cloned())
+}
+
+/// Release the references the given blobs hold on their content, before their metadata is
+/// deleted in the same transaction. The metadata rows are locked, so that a blob deleted twice
+/// concurrently only releases its reference once, and the contents without references left are
+/// removed from af_blob_content. The objects the overwritten blobs were moved to are released too.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn release_blob_contents(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  file_ids: &[String],
+) -> Result<ReleasedBlobContents, AppError> {
+  let rows: Vec<(String, Option<String>, Option<String>)> = sqlx::query_as(
+    r#"
+      SELECT file_id, content_hash, object_key FROM af_blob_metadata
+      WHERE workspace_id = $1 AND file_id = ANY($2)
+        AND (content_hash IS NOT NULL OR object_key IS NOT NULL)
+      FOR UPDATE
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_ids)
+  .fetch_all(tx.deref_mut())
+  .await?;
+  let mut hashed = Vec::with_capacity(rows.len());
+  let mut moved_object_keys = vec![];
+  for (file_id, content_hash, object_key) in rows {
+    match (content_hash, object_key) {
+      (Some(content_hash), _) => hashed.push((file_id, content_hash)),
+      (None, Some(object_key)) => moved_object_keys.push(object_key),
+      (None, None) => {},
+    }
+  }
+  if hashed.is_empty() {
+    return Ok(ReleasedBlobContents {
+      orphaned_object_keys: moved_object_keys,
+      ..Default::default()
+    });
+  }
+
+  let mut references = HashMap::<&str, i64>::new();
+  for (_, content_hash) in &hashed {
+    *references.entry(content_hash.as_str()).or_default() += 1;
+  }
+  let (content_hashes, counts): (Vec<&str>, Vec<i64>) = references.into_iter().unzip();
+  let released: Vec<(String, String, i64)> = sqlx::query_as(
+    r#"
+      UPDATE af_blob_content SET ref_count = af_blob_content.ref_count - refs.count
+      FROM unnest($2::text[], $3::int8[]) AS refs(content_hash, count)
+      WHERE af_blob_content.workspace_id = $1
+        AND af_blob_content.content_hash = refs.content_hash
+      RETURNING af_blob_content.content_hash, af_blob_content.object_key,
+        af_blob_content.ref_count
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(&content_hashes)
+  .bind(&counts)
+  .fetch_all(tx.deref_mut())
+  .await?;
+
+  let tracked_hashes = released
+    .iter()
+    .map(|(content_hash, _, _)| content_hash.as_str())
+    .collect::<HashSet<_>>();
+  let shared_file_ids = hashed
+    .iter()
+    .filter(|(_, content_hash)| tracked_hashes.contains(content_hash.as_str()))
+    .map(|(file_id, _)| file_id.clone())
+    .collect();
+  let (orphaned_hashes, orphaned_object_keys): (Vec<String>, Vec<String>) = released
+    .into_iter()
+    .filter(|(_, _, ref_count)| *ref_count <= 0)
+    .map(|(content_hash, object_key, _)| (content_hash, object_key))
+    .unzip();
+  if !orphaned_hashes.is_empty() {
+    sqlx::query(
+      r#"
+        DELETE FROM af_blob_content
+        WHERE workspace_id = $1 AND content_hash = ANY($2)
+      "#,
+    )
+    .bind(workspace_id)
+    .bind(&orphaned_hashes)
+    .execute(tx.deref_mut())
+    .await?;
+  }
+  Ok(ReleasedBlobContents {
+    shared_file_ids,
+    orphaned_object_keys: orphaned_object_keys
+      .into_iter()
+      .chain(moved_object_keys)
+      .collect(),
+  })
+}
+
+/// Return the file ids of the blobs attached to the object, whose file ids are prefixed with the
+/// object id as in [BulkInsertMeta], locking their metadata until the end of the transaction.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_blob_file_ids_by_prefix(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  object_id: &str,
+) -> Result<Vec<String>, AppError> {
+  let file_ids: Vec<(String,)> = sqlx::query_as(
+    r#"
+      SELECT file_id FROM af_blob_metadata
+      WHERE workspace_id = $1 AND file_id LIKE $2 ESCAPE '\'
+      FOR UPDATE
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(format!("{}\\_%", escape_like_pattern(object_id)))
+  .fetch_all(tx.deref_mut())
+  .await?;
+  Ok(file_ids.into_iter().map(|(file_id,)| file_id).collect())
+}
+
+/// Delete the metadata of a blob. Returns the size of the deleted blob, by which the usage of the
+/// workspace shrank, 0 when the blob had no metadata.
+#[instrument(level = "trace", skip_all, err)]
+#[inline]
+pub async fn delete_blob_metadata(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  file_id: &str,
+) -> Result<i64, AppError> {
+  let deleted: Option<(i64,)> = sqlx::query_as(
+    r#"
+        DELETE FROM af_blob_metadata
+        WHERE workspace_id = $1 AND file_id = $2
+        RETURNING file_size
+        "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .fetch_optional(tx.deref_mut())
+  .await?;
+  tracing::info!("delete_blob_metadata: deleted: {}", deleted.is_some());
+  Ok(deleted.map(|(file_size,)| file_size).unwrap_or(0))
+}
+
+/// Lock up to `limit` blobs which expired before `expired_before`, the oldest first, so that they
+/// can be deleted in the same transaction. The blobs locked by another transaction are skipped.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_expired_blob_metadata_for_update(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  expired_before: DateTime<Utc>,
+  limit: i64,
+) -> Result<Vec<AFBlobMetadataRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFBlobMetadataRow>(
+    r#"
+      SELECT * FROM af_blob_metadata
+      WHERE expires_at <= $1
+      ORDER BY expires_at
+      LIMIT $2
+      FOR UPDATE SKIP LOCKED
+    "#,
+  )
+  .bind(expired_before)
+  .bind(limit)
+  .fetch_all(tx.deref_mut())
+  .await?;
+  Ok(rows)
+}
+
+/// Delete the metadata of the given blobs in one statement. Returns the file ids which were
+/// deleted, the ones without metadata are skipped.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn delete_blob_metadata_bulk(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  file_ids: &[String],
+) -> Result<Vec<String>, AppError> {
+  let file_ids: Vec<(String,)> = sqlx::query_as(
+    r#"
+      DELETE FROM af_blob_metadata
+      WHERE workspace_id = $1 AND file_id = ANY($2)
+      RETURNING file_id
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_ids)
+  .fetch_all(tx.deref_mut())
+  .await?;
+  Ok(file_ids.into_iter().map(|(file_id,)| file_id).collect())
+}
+
+/// Delete the metadata of every blob attached to the object, whose file ids are prefixed with the
+/// object id as in [BulkInsertMeta]. Returns the deleted file ids.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn delete_blob_metadata_by_prefix(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  object_id: &str,
+) -> Result<Vec<String>, AppError> {
+  let file_ids: Vec<(String,)> = sqlx::query_as(
+    r#"
+      DELETE FROM af_blob_metadata
+      WHERE workspace_id = $1 AND file_id LIKE $2 ESCAPE '\'
+      RETURNING file_id
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(format!("{}\\_%", escape_like_pattern(object_id)))
+  .fetch_all(tx.deref_mut())
+  .await?;
+  Ok(file_ids.into_iter().map(|(file_id,)| file_id).collect())
+}
+
+/// Delete all versions of the given files and return their object keys
+#[instrument(level = "trace", skip_all, err)]
+pub async fn delete_blob_versions_of_files(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  file_ids: &[String],
+) -> Result<Vec<String>, AppError> {
+  let object_keys: Vec<(String,)> = sqlx::query_as(
+    r#"
+      DELETE FROM af_blob_version
+      WHERE workspace_id = $1 AND file_id = ANY($2)
+      RETURNING object_key
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_ids)
+  .fetch_all(tx.deref_mut())
+  .await?;
+  Ok(object_keys.into_iter().map(|(key,)| key).collect())
+}
+
+/// Escape the characters that have a special meaning in a `LIKE` pattern, using `\` as the escape
+/// character.
+pub(crate) fn escape_like_pattern(value: &str) -> String {
+  let mut escaped = String::with_capacity(value.len());
+  for c in value.chars() {
+    if matches!(c, '\\' | '%' | '_') {
+      escaped.push('\\');
+    }
+    escaped.push(c);
+  }
+  escaped
+}
+
+#[instrument(level = "trace", skip_all, err)]
+pub async fn get_blob_metadata(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  metadata_key: &str,
+) -> Result<AFBlobMetadataRow, AppError> {
+  tracing::trace!(
+    "get_blob_metadata: workspace_id: {}, metadata_key: {}",
+    workspace_id,
+    metadata_key
+  );
+  // file_id is the BlobPath's blob_metadata_key
+  let metadata = sqlx::query_as::<_, AFBlobMetadataRow>(
+    r#"
+        SELECT * FROM af_blob_metadata
+        WHERE workspace_id = $1 AND file_id = $2
+        "#,
+  )
+  .bind(workspace_id)
+  .bind(metadata_key)
+  .fetch_one(pg_pool)
+  .await?;
+  Ok(metadata)
+}
+
+/// Return the metadata of the given blobs of a workspace, the file ids without metadata are
+/// skipped.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_blob_metadata_by_file_ids(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  file_ids: &[String],
+) -> Result<Vec<AFBlobMetadataRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFBlobMetadataRow>(
+    r#"
+        SELECT * FROM af_blob_metadata
+        WHERE workspace_id = $1 AND file_id = ANY($2)
+        "#,
+  )
+  .bind(workspace_id)
+  .bind(file_ids)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(rows)
+}
 
-/// Return the total size of a workspace in bytes
+/// Return all blob metadata of a workspace
 #[instrument(level = "trace", skip_all, err)]
 #[inline]
-pub async fn get_workspace_usage_size(pool: &PgPool, workspace_id: &Uuid) -> Result<u64, AppError> {
-  let row: (Option<Decimal>,) =
-    sqlx::query_as(r#"SELECT SUM(file_size) FROM af_blob_metadata WHERE workspace_id = $1;"#)
-      .bind(workspace_id)
-      .fetch_one(pool)
-      .await?;
-  match row.0 {
-    Some(decimal) => Ok(decimal.to_u64().unwrap_or(0)),
-    None => Ok(0),
+pub async fn get_all_workspace_blob_metadata(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+) -> Result<Vec<AFBlobMetadataRow>, AppError> {
+  let all_metadata = sqlx::query_as::<_, AFBlobMetadataRow>(
+    r#"
+        SELECT * FROM af_blob_metadata
+        WHERE workspace_id = $1
+        "#,
+  )
+  .bind(workspace_id)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(all_metadata)
+}
+
+/// Streams all blob metadata of a workspace, ordered by file id, as
+/// [get_all_workspace_blob_metadata] returns it at once. The rows are read as the stream is
+/// polled, so that a workspace with many blobs isn't held in memory.
+pub fn get_all_workspace_blob_metadata_stream(
+  pg_pool: &PgPool,
+  workspace_id: Uuid,
+) -> BoxStream<'_, sqlx::Result<AFBlobMetadataRow>> {
+  sqlx::query_as::<_, AFBlobMetadataRow>(
+    r#"
+      SELECT * FROM af_blob_metadata
+      WHERE workspace_id = $1
+      ORDER BY file_id
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch(pg_pool)
+}
+
+/// Return up to `limit` blob metadata rows of a workspace following `after`, the
+/// `(modified_at, file_id)` of the last row of the previous page. The rows are ordered by
+/// `modified_at, file_id`, so new uploads land on the last pages and a position stays valid after
+/// its row is deleted.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn get_workspace_blob_metadata_page(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  after: Option<(DateTime<Utc>, &str)>,
+  limit: i64,
+) -> Result<Vec<AFBlobMetadataRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFBlobMetadataRow>(
+    r#"
+      SELECT * FROM af_blob_metadata
+      WHERE workspace_id = $1
+        AND ($2::TIMESTAMPTZ IS NULL OR (modified_at, file_id) > ($2, $3))
+      ORDER BY modified_at, file_id
+      LIMIT $4
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(after.map(|(modified_at, _)| modified_at))
+  .bind(after.map(|(_, file_id)| file_id))
+  .bind(limit)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(rows)
+}
+
+/// Return all blob ids of a workspace
+#[instrument(level = "trace", skip_all, err)]
+#[inline]
+pub async fn get_all_workspace_blob_ids(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+) -> Result<Vec<String>, AppError> {
+  let file_ids = sqlx::query!(
+    r#"
+    SELECT file_id FROM af_blob_metadata
+    WHERE workspace_id = $1
+    "#,
+    workspace_id
+  )
+  .fetch_all(pg_pool)
+  .await?
+  .into_iter()
+  .map(|record| record.file_id)
+  .collect();
+  Ok(file_ids)
+}
+
+/// Return the total size of a workspace in bytes. Archived blob versions count toward the usage.
+/// The size is read from the counter kept up to date by the writes of the blobs, see
+/// [recompute_workspace_usage] to fix it when it drifted.
+#[instrument(level = "trace", skip_all, err)]
+#[inline]
+pub async fn get_workspace_usage_size<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<u64, AppError> {
+  let usage: Option<i64> = sqlx::query_scalar(
+    r#"
+    SELECT usage_bytes FROM af_workspace_usage WHERE workspace_id = $1
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(usage.unwrap_or(0).max(0) as u64)
+}
+
+/// Replace the usage counter of the workspace with the sum of the sizes of its blobs and of their
+/// archived versions. The counter is locked before the sizes are summed, so the blobs written
+/// meanwhile are either in the sum or added to the counter after it's replaced.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn recompute_workspace_usage(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+) -> Result<RecomputedWorkspaceUsage, AppError> {
+  let mut tx = pg_pool.begin().await?;
+  sqlx::query(
+    r#"
+    INSERT INTO af_workspace_usage (workspace_id)
+    SELECT workspace_id FROM af_workspace WHERE workspace_id = $1
+    ON CONFLICT (workspace_id) DO NOTHING
+    "#,
+  )
+  .bind(workspace_id)
+  .execute(tx.deref_mut())
+  .await?;
+  let previous: Option<i64> = sqlx::query_scalar(
+    r#"
+    SELECT usage_bytes FROM af_workspace_usage WHERE workspace_id = $1 FOR UPDATE
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_optional(tx.deref_mut())
+  .await?;
+  let previous = previous
+    .ok_or_else(|| AppError::RecordNotFound(format!("workspace {} not found", workspace_id)))?;
+
+  let usage: i64 = sqlx::query_scalar(
+    r#"
+    UPDATE af_workspace_usage
+    SET usage_bytes = (
+          COALESCE((SELECT SUM(file_size) FROM af_blob_metadata WHERE workspace_id = $1), 0)
+          + COALESCE((SELECT SUM(file_size) FROM af_blob_version WHERE workspace_id = $1), 0)
+        )::BIGINT,
+        updated_at = CURRENT_TIMESTAMP
+    WHERE workspace_id = $1
+    RETURNING usage_bytes
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_one(tx.deref_mut())
+  .await?;
+  tx.commit().await?;
+
+  if usage != previous {
+    warn!(
+      "usage of workspace {} drifted by {} bytes, recomputed to {}",
+      workspace_id,
+      previous - usage,
+      usage
+    );
+  }
+  Ok(RecomputedWorkspaceUsage {
+    workspace_id: *workspace_id,
+    previous_bytes: previous,
+    usage_bytes: usage,
+  })
+}
+
+/// How long the cached usage of a workspace is kept. The counter is only updated by the writes
+/// which know the size they add or remove, the others invalidate it, and it expires so that a
+/// write missed in between, such as an update racing with the population of the counter, doesn't
+/// skew the usage for longer than this.
+const WORKSPACE_USAGE_SIZE_CACHE_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Increment the counter only when it exists: a counter created by an increment would hold the
+/// change only, short of the usage when the change was made.
+const INCR_USAGE_SIZE_SCRIPT: &str = r#"
+if redis.call('EXISTS', KEYS[1]) == 1 then
+  return redis.call('INCRBY', KEYS[1], ARGV[1])
+end
+return nil
+"#;
+
+fn workspace_usage_size_cache_key(workspace_id: &Uuid) -> String {
+  format!("af:workspace_usage_size:{}", workspace_id)
+}
+
+/// Return the total size of a workspace in bytes as [get_workspace_usage_size], from the counter
+/// kept in Redis. A missing counter is populated with the usage read from the database, which is
+/// also returned when Redis can't be reached.
+pub async fn get_workspace_usage_size_cached(
+  pg_pool: &PgPool,
+  redis: &ConnectionManager,
+  workspace_id: &Uuid,
+) -> Result<u64, AppError> {
+  let key = workspace_usage_size_cache_key(workspace_id);
+  let mut conn = redis.clone();
+  match conn.get::<_, Option<i64>>(&key).await {
+    Ok(Some(size)) => return Ok(size.max(0) as u64),
+    Ok(None) => {},
+    Err(err) => {
+      warn!(
+        "failed to read the cached usage of {}: {}",
+        workspace_id, err
+      );
+      return get_workspace_usage_size(pg_pool, workspace_id).await;
+    },
+  }
+
+  let size = get_workspace_usage_size(pg_pool, workspace_id).await?;
+  // a counter populated in the meantime is kept, it's as recent as this sum
+  let result: RedisResult<()> = redis::cmd("SET")
+    .arg(&key)
+    .arg(size)
+    .arg("NX")
+    .arg("EX")
+    .arg(WORKSPACE_USAGE_SIZE_CACHE_TTL_SECS)
+    .query_async(&mut conn)
+    .await;
+  if let Err(err) = result {
+    warn!("failed to cache the usage of {}: {}", workspace_id, err);
+  }
+  Ok(size)
+}
+
+/// Apply the change of the usage of a workspace, in bytes, to its cached counter once the write
+/// making it is committed. The counter is invalidated when it can't be updated, so that the next
+/// read recomputes the usage rather than returning a counter missing the change.
+pub async fn update_workspace_usage_size_cache(
+  redis: &ConnectionManager,
+  workspace_id: &Uuid,
+  delta: i64,
+) {
+  if delta == 0 {
+    return;
+  }
+  let result: RedisResult<Option<i64>> = redis::Script::new(INCR_USAGE_SIZE_SCRIPT)
+    .key(workspace_usage_size_cache_key(workspace_id))
+    .arg(delta)
+    .invoke_async(&mut redis.clone())
+    .await;
+  if let Err(err) = result {
+    warn!(
+      "failed to update the cached usage of {} by {} bytes: {}",
+      workspace_id, delta, err
+    );
+    invalidate_workspace_usage_size_cache(redis, workspace_id).await;
+  }
+}
+
+/// Drop the cached usage of a workspace, after a write whose change of the usage isn't known,
+/// such as the deletion of blobs along with their versions.
+pub async fn invalidate_workspace_usage_size_cache(redis: &ConnectionManager, workspace_id: &Uuid) {
+  let result: RedisResult<()> = redis
+    .clone()
+    .del(workspace_usage_size_cache_key(workspace_id))
+    .await;
+  if let Err(err) = result {
+    // the counter is left to expire
+    error!(
+      "failed to invalidate the cached usage of {}: {}",
+      workspace_id, err
+    );
   }
 }
+
+/// Return the number and total size of the files of a workspace per category of their mime type.
+/// The parameters of the mime type and its case are ignored, and the files whose type is unknown
+/// or empty are counted as [FileTypeCategory::Other]. The categories without any file are
+/// omitted.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn get_workspace_usage_breakdown<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<Vec<FileTypeUsage>, AppError> {
+  let rows: Vec<(String, i64, Option<Decimal>)> = sqlx::query_as(
+    r#"
+    SELECT category, COUNT(*), SUM(file_size)
+    FROM (
+      SELECT
+        CASE
+          WHEN mime_type LIKE 'image/%' THEN 'image'
+          WHEN mime_type LIKE 'video/%' THEN 'video'
+          WHEN mime_type LIKE 'audio/%' THEN 'audio'
+          WHEN mime_type = 'application/pdf' THEN 'pdf'
+          ELSE 'other'
+        END AS category,
+        file_size
+      FROM (
+        SELECT lower(btrim(split_part(file_type, ';', 1))) AS mime_type, file_size
+        FROM af_blob_metadata
+        WHERE workspace_id = $1
+      ) AS blob
+    ) AS categorized
+    GROUP BY category
+    ORDER BY category
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_all(executor)
+  .await?;
+  Ok(
+    rows
+      .into_iter()
+      .map(|(category, file_count, total_size)| FileTypeUsage {
+        category: FileTypeCategory::from_name(&category),
+        file_count: file_count.max(0) as u64,
+        total_size: total_size.and_then(|size| size.to_u64()).unwrap_or(0),
+      })
+      .collect(),
+  )
+}
+
+/// Return the number and total size of the files of a workspace per uploader, largest first. The
+/// files whose uploader isn't known are counted together, with no uid. Archived blob versions
+/// aren't counted.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn get_blob_usage_by_member<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<Vec<MemberBlobUsage>, AppError> {
+  let rows: Vec<(Option<i64>, i64, Option<Decimal>)> = sqlx::query_as(
+    r#"
+    SELECT uploaded_by, COUNT(*), SUM(file_size)
+    FROM af_blob_metadata
+    WHERE workspace_id = $1
+    GROUP BY uploaded_by
+    ORDER BY SUM(file_size) DESC, uploaded_by NULLS LAST
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_all(executor)
+  .await?;
+  Ok(
+    rows
+      .into_iter()
+      .map(|(uid, file_count, total_size)| MemberBlobUsage {
+        uid,
+        file_count: file_count.max(0) as u64,
+        total_size: total_size.and_then(|size| size.to_u64()).unwrap_or(0),
+      })
+      .collect(),
+  )
+}
+
+/// Update the type and size of an existing blob, point it at `object_key` and bump its modified
+/// time. Unlike [insert_blob_metadata], this is used when the content behind a file id is
+/// replaced by a new object. The attributes are recorded as by [insert_blob_metadata].
+#[instrument(level = "trace", skip_all, err)]
+pub async fn update_blob_metadata(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  file_id: &str,
+  file_type: &str,
+  file_size: i64,
+  object_key: &str,
+  attributes: BlobAttributes,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      INSERT INTO af_blob_metadata
+      (workspace_id, file_id, file_type, file_size, uploaded_by, expires_at, object_key)
+      VALUES ($1, $2, $3, $4, $5, $6, $7)
+      ON CONFLICT (workspace_id, file_id) DO UPDATE SET
+        file_type = $3,
+        file_size = $4,
+        modified_at = CURRENT_TIMESTAMP,
+        uploaded_by = COALESCE($5, af_blob_metadata.uploaded_by),
+        expires_at = CASE
+          WHEN af_blob_metadata.expires_at IS NULL OR $6 IS NULL THEN NULL
+          ELSE GREATEST(af_blob_metadata.expires_at, $6)
+        END,
+        object_key = $7
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .bind(file_type)
+  .bind(file_size)
+  .bind(attributes.uploaded_by)
+  .bind(attributes.expires_at)
+  .bind(object_key)
+  .execute(tx.deref_mut())
+  .await?;
+  Ok(())
+}
+
+/// Lock the blob metadata row of the given file and return it, if any. Used to serialize
+/// concurrent overwrites of the same file id while its previous revision is being archived.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_blob_metadata_for_update(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  file_id: &str,
+) -> Result<Option<AFBlobMetadataRow>, AppError> {
+  let metadata = sqlx::query_as::<_, AFBlobMetadataRow>(
+    r#"
+      SELECT * FROM af_blob_metadata
+      WHERE workspace_id = $1 AND file_id = $2
+      FOR UPDATE
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .fetch_optional(tx.deref_mut())
+  .await?;
+  Ok(metadata)
+}
+
+/// Return the version number that the next archived revision of the file should use.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_next_blob_version(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  file_id: &str,
+) -> Result<i64, AppError> {
+  let row: (Option<i64>,) = sqlx::query_as(
+    r#"
+      SELECT MAX(version) FROM af_blob_version
+      WHERE workspace_id = $1 AND file_id = $2
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .fetch_one(tx.deref_mut())
+  .await?;
+  Ok(row.0.unwrap_or(0) + 1)
+}
+
+#[instrument(level = "trace", skip_all, err)]
+pub async fn insert_blob_version(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  file_id: &str,
+  version: i64,
+  object_key: &str,
+  file_type: &str,
+  file_size: i64,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      INSERT INTO af_blob_version
+      (workspace_id, file_id, version, object_key, file_type, file_size)
+      VALUES ($1, $2, $3, $4, $5, $6)
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .bind(version)
+  .bind(object_key)
+  .bind(file_type)
+  .bind(file_size)
+  .execute(tx.deref_mut())
+  .await?;
+  Ok(())
+}
+
+/// Return all archived versions of a file, newest first
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_blob_versions(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  file_id: &str,
+) -> Result<Vec<AFBlobVersionRow>, AppError> {
+  let versions = sqlx::query_as::<_, AFBlobVersionRow>(
+    r#"
+      SELECT * FROM af_blob_version
+      WHERE workspace_id = $1 AND file_id = $2
+      ORDER BY version DESC
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(versions)
+}
+
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_blob_version(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  file_id: &str,
+  version: i64,
+) -> Result<AFBlobVersionRow, AppError> {
+  let version = sqlx::query_as::<_, AFBlobVersionRow>(
+    r#"
+      SELECT * FROM af_blob_version
+      WHERE workspace_id = $1 AND file_id = $2 AND version = $3
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .bind(version)
+  .fetch_one(pg_pool)
+  .await?;
+  Ok(version)
+}
+
+/// Delete the versions of a file that fall outside the retention policy: only the newest
+/// `max_versions` are kept, and if `max_age_days` is set, versions older than that are removed
+/// too. Returns the object keys of the deleted versions so the caller can remove them from the
+/// bucket.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn delete_expired_blob_versions(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  file_id: &str,
+  max_versions: i64,
+  max_age_days: Option<i64>,
+) -> Result<Vec<String>, AppError> {
+  let object_keys: Vec<(String,)> = sqlx::query_as(
+    r#"
+      DELETE FROM af_blob_version
+      WHERE workspace_id = $1 AND file_id = $2
+        AND (
+          version NOT IN (
+            SELECT version FROM af_blob_version
+            WHERE workspace_id = $1 AND file_id = $2
+            ORDER BY version DESC
+            LIMIT $3
+          )
+          OR ($4::BIGINT IS NOT NULL AND created_at < NOW() - make_interval(days => $4::INT))
+        )
+      RETURNING object_key
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .bind(max_versions)
+  .bind(max_age_days)
+  .fetch_all(tx.deref_mut())
+  .await?;
+  Ok(object_keys.into_iter().map(|(key,)| key).collect())
+}
+
+/// Delete all versions of a file and return their object keys
+#[instrument(level = "trace", skip_all, err)]
+pub async fn delete_all_blob_versions(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  file_id: &str,
+) -> Result<Vec<String>, AppError> {
+  let object_keys: Vec<(String,)> = sqlx::query_as(
+    r#"
+      DELETE FROM af_blob_version
+      WHERE workspace_id = $1 AND file_id = $2
+      RETURNING object_key
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_id)
+  .fetch_all(tx.deref_mut())
+  .await?;
+  Ok(object_keys.into_iter().map(|(key,)| key).collect())
+}
+
+/// Return the archived versions of every file in the workspace that fall outside the retention
+/// policy, using the same rules as [delete_expired_blob_versions]. Nothing is deleted.
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_workspace_expired_blob_versions(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  max_versions: i64,
+  max_age_days: Option<i64>,
+) -> Result<Vec<AFBlobVersionRow>, AppError> {
+  let versions = sqlx::query_as::<_, AFBlobVersionRow>(
+    r#"
+      SELECT workspace_id, file_id, version, object_key, file_type, file_size, created_at
+      FROM (
+        SELECT *, ROW_NUMBER() OVER (PARTITION BY file_id ORDER BY version DESC) AS rank
+        FROM af_blob_version
+        WHERE workspace_id = $1
+      ) AS versions
+      WHERE rank > $2
+        OR ($3::BIGINT IS NOT NULL AND created_at < NOW() - make_interval(days => $3::INT))
+      ORDER BY file_id, version
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(max_versions)
+  .bind(max_age_days)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(versions)
+}
+
+/// Delete the given `(file_id, version)` pairs and return the object keys of the deleted versions
+#[instrument(level = "trace", skip_all, err)]
+pub async fn delete_blob_versions(
+  tx: &mut Transaction<'_, sqlx::Postgres>,
+  workspace_id: &Uuid,
+  versions: &[(String, i64)],
+) -> Result<Vec<String>, AppError> {
+  let (file_ids, versions): (Vec<String>, Vec<i64>) = versions.iter().cloned().unzip();
+  let object_keys: Vec<(String,)> = sqlx::query_as(
+    r#"
+      DELETE FROM af_blob_version
+      WHERE workspace_id = $1
+        AND (file_id, version) IN (SELECT * FROM UNNEST($2::TEXT[], $3::BIGINT[]))
+      RETURNING object_key
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(file_ids)
+  .bind(versions)
+  .fetch_all(tx.deref_mut())
+  .await?;
+  Ok(object_keys.into_iter().map(|(key,)| key).collect())
+}
+
+/// Return the object keys of all archived blob versions of the workspace
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_workspace_blob_version_keys(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+) -> Result<Vec<String>, AppError> {
+  let object_keys: Vec<(String,)> = sqlx::query_as(
+    r#"
+      SELECT object_key FROM af_blob_version
+      WHERE workspace_id = $1
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(object_keys.into_iter().map(|(key,)| key).collect())
+}
+
+/// Return the object keys of all contents shared by blobs of the workspace, and of the objects
+/// the overwritten blobs were moved to
+#[instrument(level = "trace", skip_all, err)]
+pub async fn select_workspace_blob_content_keys(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+) -> Result<Vec<String>, AppError> {
+  let object_keys: Vec<(String,)> = sqlx::query_as(
+    r#"
+      SELECT object_key FROM af_blob_content
+      WHERE workspace_id = $1
+      UNION
+      SELECT object_key FROM af_blob_metadata
+      WHERE workspace_id = $1 AND content_hash IS NULL AND object_key IS NOT NULL
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(object_keys.into_iter().map(|(key,)| key).collect())
+}
@@ -0,0 +1,61 @@
+use std::ops::DerefMut;
+use std::time::Duration;
+
+use sqlx::{PgPool, Postgres, Transaction};
+
+/// SQLSTATE raised by Postgres when a statement is cancelled, which includes the statements
+/// running longer than the `statement_timeout`.
+const QUERY_CANCELED: &str = "57014";
+
+/// Begin a transaction whose statements are cancelled once they run longer than `timeout`. The
+/// timeout is local to the transaction, the connection is back to the default when it's returned
+/// to the pool.
+pub async fn begin_with_statement_timeout(
+  pool: &PgPool,
+  timeout: Duration,
+) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+  let mut txn = pool.begin().await?;
+  sqlx::query("SELECT set_config('statement_timeout', $1, true)")
+    .bind(format!("{}ms", timeout.as_millis()))
+    .execute(txn.deref_mut())
+    .await?;
+  Ok(txn)
+}
+
+pub fn is_statement_timeout(err: &sqlx::Error) -> bool {
+  match err {
+    sqlx::Error::Database(err) => err.code().as_deref() == Some(QUERY_CANCELED),
+    _ => false,
+  }
+}
+
+/// Page size of a query that is read page by page. It's halved every time a page hits the
+/// statement timeout, so that a large workspace is read in smaller pages rather than failing.
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptivePageSize {
+  size: i64,
+  min: i64,
+}
+
+impl AdaptivePageSize {
+  pub fn new(size: i64, min: i64) -> Self {
+    Self {
+      size: size.max(min),
+      min,
+    }
+  }
+
+  pub fn get(&self) -> i64 {
+    self.size
+  }
+
+  /// Halve the page size. Returns false when it's already at its minimum, in which case the
+  /// query should fail.
+  pub fn shrink(&mut self) -> bool {
+    if self.size <= self.min {
+      return false;
+    }
+    self.size = (self.size / 2).max(self.min);
+    true
+  }
+}
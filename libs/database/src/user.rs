@@ -273,3 +273,18 @@ pub async fn select_web_user_from_uid(
 
   Ok(row)
 }
+
+pub async fn select_uuid_and_email_from_uid<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+) -> Result<(Uuid, String), AppError> {
+  let row = sqlx::query_as::<_, (Uuid, String)>(
+    r#"
+      SELECT uuid, email FROM af_user WHERE uid = $1
+    "#,
+  )
+  .bind(uid)
+  .fetch_one(executor)
+  .await?;
+  Ok(row)
+}
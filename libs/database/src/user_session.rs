@@ -0,0 +1,196 @@
+use app_error::AppError;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres, Transaction};
+use std::collections::BTreeSet;
+use std::ops::DerefMut;
+use uuid::Uuid;
+
+use crate::pg_row::AFUserSessionRow;
+
+/// Records that the session was seen with an access token issued at `token_issued_at`. A token
+/// issued after the last one seen counts as a refresh of the session. Returns `None` if the user
+/// doesn't exist.
+#[allow(clippy::too_many_arguments)]
+pub async fn upsert_user_session<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  session_id: &Uuid,
+  user_uuid: &Uuid,
+  device_id: Option<&str>,
+  client_version: Option<&str>,
+  user_agent: Option<&str>,
+  ip_address: Option<&str>,
+  token_issued_at: Option<DateTime<Utc>>,
+) -> Result<Option<AFUserSessionRow>, AppError> {
+  let row = sqlx::query_as::<_, AFUserSessionRow>(
+    r#"
+      INSERT INTO af_user_session
+        (session_id, uid, device_id, client_version, user_agent, ip_address, token_issued_at)
+      SELECT $1, uid, $3, $4, $5, $6, $7 FROM af_user WHERE uuid = $2
+      ON CONFLICT (session_id) DO UPDATE
+      SET last_seen_at = CURRENT_TIMESTAMP,
+          device_id = COALESCE(EXCLUDED.device_id, af_user_session.device_id),
+          client_version = COALESCE(EXCLUDED.client_version, af_user_session.client_version),
+          user_agent = COALESCE(EXCLUDED.user_agent, af_user_session.user_agent),
+          ip_address = COALESCE(EXCLUDED.ip_address, af_user_session.ip_address),
+          refresh_count = af_user_session.refresh_count
+            + CASE WHEN EXCLUDED.token_issued_at > af_user_session.token_issued_at THEN 1 ELSE 0 END,
+          token_issued_at = GREATEST(EXCLUDED.token_issued_at, af_user_session.token_issued_at)
+      RETURNING *
+    "#,
+  )
+  .bind(session_id)
+  .bind(user_uuid)
+  .bind(device_id)
+  .bind(client_version)
+  .bind(user_agent)
+  .bind(ip_address)
+  .bind(token_issued_at)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Returns the sessions of the user which were not revoked, the most recently seen first.
+pub async fn select_user_sessions<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+) -> Result<Vec<AFUserSessionRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFUserSessionRow>(
+    r#"
+      SELECT * FROM af_user_session
+      WHERE uid = $1 AND revoked_at IS NULL
+      ORDER BY last_seen_at DESC
+    "#,
+  )
+  .bind(uid)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+/// Whether GoTrue revoked every refresh token of the session. It revokes the whole family of
+/// refresh tokens of a session when one that was already rotated is used again, past the reuse
+/// interval: either the client or someone who stole its token is using an old token.
+pub async fn is_refresh_token_family_revoked<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  session_id: &Uuid,
+) -> Result<bool, AppError> {
+  let revoked = sqlx::query_scalar::<_, bool>(
+    r#"
+      SELECT EXISTS (SELECT 1 FROM auth.refresh_tokens WHERE session_id = $1)
+        AND NOT EXISTS (
+          SELECT 1 FROM auth.refresh_tokens WHERE session_id = $1 AND NOT revoked
+        )
+    "#,
+  )
+  .bind(session_id)
+  .fetch_one(executor)
+  .await?;
+  Ok(revoked)
+}
+
+/// Which sessions of a user to revoke.
+pub enum SessionRevocation<'a> {
+  One(&'a Uuid),
+  AllExcept(&'a Uuid),
+  All,
+}
+
+/// Revokes sessions of the user and records who revoked them, and why when neither the user nor
+/// an admin asked for it. The GoTrue sessions are deleted along with their refresh tokens, so
+/// that they can't be refreshed anymore, including the ones the server never saw. Returns the ids
+/// of the revoked sessions.
+pub async fn revoke_user_sessions(
+  txn: &mut Transaction<'_, Postgres>,
+  uid: i64,
+  revocation: SessionRevocation<'_>,
+  revoked_by: i64,
+  revoked_by_admin: bool,
+  reason: Option<&str>,
+) -> Result<Vec<Uuid>, AppError> {
+  let (only, except) = match revocation {
+    SessionRevocation::One(session_id) => (Some(*session_id), None),
+    SessionRevocation::AllExcept(session_id) => (None, Some(*session_id)),
+    SessionRevocation::All => (None, None),
+  };
+  let revoked = sqlx::query_scalar::<_, Uuid>(
+    r#"
+      UPDATE af_user_session
+      SET revoked_at = CURRENT_TIMESTAMP
+      WHERE uid = $1
+        AND revoked_at IS NULL
+        AND ($2::uuid IS NULL OR session_id = $2)
+        AND ($3::uuid IS NULL OR session_id <> $3)
+      RETURNING session_id
+    "#,
+  )
+  .bind(uid)
+  .bind(only)
+  .bind(except)
+  .fetch_all(txn.deref_mut())
+  .await?;
+  let deleted = sqlx::query_scalar::<_, Uuid>(
+    r#"
+      DELETE FROM auth.sessions
+      WHERE user_id = (SELECT uuid FROM af_user WHERE uid = $1)
+        AND ($2::uuid IS NULL OR id = $2)
+        AND ($3::uuid IS NULL OR id <> $3)
+      RETURNING id
+    "#,
+  )
+  .bind(uid)
+  .bind(only)
+  .bind(except)
+  .fetch_all(txn.deref_mut())
+  .await?;
+
+  let session_ids = revoked
+    .into_iter()
+    .chain(deleted)
+    .collect::<BTreeSet<_>>()
+    .into_iter()
+    .collect::<Vec<_>>();
+  if !session_ids.is_empty() {
+    sqlx::query(
+      r#"
+        INSERT INTO af_user_session_revocation
+          (session_id, uid, revoked_by, revoked_by_admin, reason)
+        SELECT session_id, $2, $3, $4, $5 FROM UNNEST($1::uuid[]) AS session_id
+      "#,
+    )
+    .bind(&session_ids)
+    .bind(uid)
+    .bind(revoked_by)
+    .bind(revoked_by_admin)
+    .bind(reason)
+    .execute(txn.deref_mut())
+    .await?;
+  }
+  Ok(session_ids)
+}
+
+/// A revocation of a session: the session id, who revoked it, whether they're an admin, when and
+/// why.
+pub type SessionRevocationRecord = (Uuid, i64, bool, DateTime<Utc>, Option<String>);
+
+/// Returns the revocations of the sessions of the user, the most recent first.
+pub async fn select_user_session_revocations<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+  limit: i64,
+) -> Result<Vec<SessionRevocationRecord>, AppError> {
+  let rows = sqlx::query_as::<_, SessionRevocationRecord>(
+    r#"
+      SELECT session_id, revoked_by, revoked_by_admin, created_at, reason
+      FROM af_user_session_revocation
+      WHERE uid = $1
+      ORDER BY created_at DESC
+      LIMIT $2
+    "#,
+  )
+  .bind(uid)
+  .bind(limit)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
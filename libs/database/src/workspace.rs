@@ -1,7 +1,7 @@
 use chrono::{DateTime, Utc};
 use database_entity::dto::{
   AFRole, AFWorkspaceInvitation, AFWorkspaceInvitationStatus, AFWorkspaceSettings, GlobalComment,
-  Reaction,
+  Reaction, WorkspaceMemberFilter, WorkspaceMemberSortBy,
 };
 use futures_util::stream::BoxStream;
 use sqlx::{types::uuid, Executor, PgPool, Postgres, Transaction};
@@ -11,11 +11,13 @@ use uuid::Uuid;
 
 use crate::pg_row::{
   AFGlobalCommentRow, AFImportTask, AFPermissionRow, AFReactionRow, AFUserProfileRow,
-  AFWebUserColumn, AFWorkspaceInvitationMinimal, AFWorkspaceMemberPermRow, AFWorkspaceMemberRow,
-  AFWorkspaceRow,
+  AFWebUserColumn, AFWorkspaceInvitationMinimal, AFWorkspaceInvitationRow,
+  AFWorkspaceMemberExportRow, AFWorkspaceMemberPageRow, AFWorkspaceMemberPermRow,
+  AFWorkspaceMemberRow, AFWorkspacePendingInvitationRow, AFWorkspaceRow,
 };
-use crate::user::select_uid_from_email;
+use crate::resource_usage::escape_like_pattern;
 use app_error::AppError;
+use shared_entity::dto::import_dto::ImportReport;
 
 #[inline]
 pub async fn delete_from_workspace(pg_pool: &PgPool, workspace_id: &Uuid) -> Result<(), AppError> {
@@ -246,31 +248,35 @@ pub async fn insert_workspace_invitation(
   inviter_uuid: &Uuid,
   invitee_email: &str,
   invitee_role: &AFRole,
+  expires_in_secs: i64,
 ) -> Result<(), AppError> {
   let role_id: i32 = invitee_role.into();
-  sqlx::query!(
+  sqlx::query(
     r#"
       INSERT INTO public.af_workspace_invitation (
           id,
           workspace_id,
           inviter,
           invitee_email,
-          role_id
+          role_id,
+          expires_at
       )
       VALUES (
         $1,
         $2,
         (SELECT uid FROM public.af_user WHERE uuid = $3),
         $4,
-        $5
+        $5,
+        NOW() + make_interval(secs => $6)
       )
     "#,
-    invite_id,
-    workspace_id,
-    inviter_uuid,
-    invitee_email,
-    role_id
   )
+  .bind(invite_id)
+  .bind(workspace_id)
+  .bind(inviter_uuid)
+  .bind(invitee_email)
+  .bind(role_id)
+  .bind(expires_in_secs as f64)
   .execute(txn.deref_mut())
   .await?;
 
@@ -336,41 +342,45 @@ pub async fn get_invitation_by_id(
   Ok(res)
 }
 
+const SELECT_WORKSPACE_INVITATION_FOR_USER: &str = r#"
+  SELECT
+    i.id AS invite_id,
+    i.workspace_id,
+    w.workspace_name,
+    u_inviter.email AS inviter_email,
+    u_inviter.name AS inviter_name,
+    i.status,
+    i.updated_at,
+    u_inviter.metadata->>'icon_url' AS inviter_icon,
+    w.icon AS workspace_icon,
+    (SELECT COUNT(*) FROM public.af_workspace_member m WHERE m.workspace_id = i.workspace_id) AS member_count,
+    i.expires_at,
+    GREATEST(CEIL(EXTRACT(EPOCH FROM i.expires_at - NOW())), 0)::BIGINT AS expires_in_secs
+  FROM
+    public.af_workspace_invitation i
+    JOIN public.af_workspace w ON i.workspace_id = w.workspace_id
+    JOIN public.af_user u_inviter ON i.inviter = u_inviter.uid
+    JOIN public.af_user u_invitee ON u_invitee.uuid = $1
+  WHERE
+    LOWER(i.invitee_email) = LOWER(u_invitee.email)
+"#;
+
 #[inline]
 pub async fn select_workspace_invitations_for_user(
   pg_pool: &PgPool,
   invitee_uuid: &Uuid,
   status_filter: Option<AFWorkspaceInvitationStatus>,
 ) -> Result<Vec<AFWorkspaceInvitation>, AppError> {
-  let res = sqlx::query_as!(
-    AFWorkspaceInvitation,
-    r#"
-      SELECT
-        i.id AS invite_id,
-        i.workspace_id,
-        w.workspace_name,
-        u_inviter.email AS inviter_email,
-        u_inviter.name AS inviter_name,
-        i.status,
-        i.updated_at,
-        u_inviter.metadata->>'icon_url' AS inviter_icon,
-        w.icon AS workspace_icon,
-        (SELECT COUNT(*) FROM public.af_workspace_member m WHERE m.workspace_id = i.workspace_id) AS member_count
-      FROM
-        public.af_workspace_invitation i
-        JOIN public.af_workspace w ON i.workspace_id = w.workspace_id
-        JOIN public.af_user u_inviter ON i.inviter = u_inviter.uid
-        JOIN public.af_user u_invitee ON u_invitee.uuid = $1
-      WHERE
-        LOWER(i.invitee_email) = LOWER(u_invitee.email)
-        AND ($2::SMALLINT IS NULL OR i.status = $2);
-    "#,
-    invitee_uuid,
-    status_filter.map(|s| s as i16)
-  )
-  .fetch_all(pg_pool)
-  .await?;
-  Ok(res)
+  let query = format!(
+    "{} AND ($2::SMALLINT IS NULL OR i.status = $2)",
+    SELECT_WORKSPACE_INVITATION_FOR_USER
+  );
+  let rows = sqlx::query_as::<_, AFWorkspaceInvitationRow>(&query)
+    .bind(invitee_uuid)
+    .bind(status_filter.map(|s| s as i16))
+    .fetch_all(pg_pool)
+    .await?;
+  Ok(rows.into_iter().map(AFWorkspaceInvitation::from).collect())
 }
 
 #[inline]
@@ -379,50 +389,195 @@ pub async fn select_workspace_invitation_for_user(
   invitee_uuid: &Uuid,
   invite_id: &Uuid,
 ) -> Result<AFWorkspaceInvitation, AppError> {
-  let res = sqlx::query_as!(
-    AFWorkspaceInvitation,
+  let query = format!("{} AND i.id = $2", SELECT_WORKSPACE_INVITATION_FOR_USER);
+  let row = sqlx::query_as::<_, AFWorkspaceInvitationRow>(&query)
+    .bind(invitee_uuid)
+    .bind(invite_id)
+    .fetch_one(pg_pool)
+    .await?;
+  Ok(row.into())
+}
+
+/// Locks the invitation until the end of the transaction and returns its expiry.
+pub async fn select_workspace_invitation_expiry_for_update(
+  txn: &mut Transaction<'_, sqlx::Postgres>,
+  invite_id: &Uuid,
+) -> Result<DateTime<Utc>, AppError> {
+  let expires_at = sqlx::query_scalar::<_, DateTime<Utc>>(
     r#"
-      SELECT
-        i.id AS invite_id,
-        i.workspace_id,
-        w.workspace_name,
-        u_inviter.email AS inviter_email,
-        u_inviter.name AS inviter_name,
-        i.status,
-        i.updated_at,
-        u_inviter.metadata->>'icon_url' AS inviter_icon,
-        w.icon AS workspace_icon,
-        (SELECT COUNT(*) FROM public.af_workspace_member m WHERE m.workspace_id = i.workspace_id) AS member_count
-      FROM
-        public.af_workspace_invitation i
-        JOIN public.af_workspace w ON i.workspace_id = w.workspace_id
-        JOIN public.af_user u_inviter ON i.inviter = u_inviter.uid
-        JOIN public.af_user u_invitee ON u_invitee.uuid = $1
-      WHERE
-        LOWER(i.invitee_email) = LOWER(u_invitee.email)
-        AND i.id = $2;
+      SELECT expires_at FROM public.af_workspace_invitation
+      WHERE id = $1
+      FOR UPDATE
     "#,
-    invitee_uuid,
-    invite_id,
   )
-  .fetch_one(pg_pool)
+  .bind(invite_id)
+  .fetch_one(txn.deref_mut())
   .await?;
-  Ok(res)
+  Ok(expires_at)
+}
+
+const SELECT_WORKSPACE_PENDING_INVITATION: &str = r#"
+  SELECT
+    i.id AS invite_id,
+    i.workspace_id,
+    i.invitee_email,
+    i.role_id,
+    u_inviter.email AS inviter_email,
+    i.created_at,
+    i.expires_at,
+    GREATEST(CEIL(EXTRACT(EPOCH FROM i.expires_at - NOW())), 0)::BIGINT AS expires_in_secs,
+    i.email_queued_at
+  FROM public.af_workspace_invitation i
+  LEFT JOIN public.af_user u_inviter ON i.inviter = u_inviter.uid
+  WHERE i.workspace_id = $1 AND i.status = 0
+"#;
+
+/// The pending invitations of the workspace, the expired ones included until they're pruned,
+/// soonest to expire first.
+pub async fn select_workspace_pending_invitation_list<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<Vec<AFWorkspacePendingInvitationRow>, AppError> {
+  let query = format!(
+    "{} ORDER BY i.expires_at, i.id",
+    SELECT_WORKSPACE_PENDING_INVITATION
+  );
+  let rows = sqlx::query_as::<_, AFWorkspacePendingInvitationRow>(&query)
+    .bind(workspace_id)
+    .fetch_all(executor)
+    .await?;
+  Ok(rows)
+}
+
+pub async fn select_workspace_pending_invitation<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  invite_id: &Uuid,
+) -> Result<AFWorkspacePendingInvitationRow, AppError> {
+  let query = format!("{} AND i.id = $2", SELECT_WORKSPACE_PENDING_INVITATION);
+  let row = sqlx::query_as::<_, AFWorkspacePendingInvitationRow>(&query)
+    .bind(workspace_id)
+    .bind(invite_id)
+    .fetch_optional(executor)
+    .await?
+    .ok_or_else(|| {
+      AppError::RecordNotFound(format!(
+        "No pending invitation {} in workspace {}",
+        invite_id, workspace_id
+      ))
+    })?;
+  Ok(row)
+}
+
+/// Resets the expiry of a pending invitation, whether it already expired or not.
+pub async fn update_workspace_invitation_expiry<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  invite_id: &Uuid,
+  expires_in_secs: i64,
+) -> Result<(), AppError> {
+  let res = sqlx::query(
+    r#"
+      UPDATE public.af_workspace_invitation
+      SET expires_at = NOW() + make_interval(secs => $3)
+      WHERE workspace_id = $1 AND id = $2 AND status = 0
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(invite_id)
+  .bind(expires_in_secs as f64)
+  .execute(executor)
+  .await?;
+  if res.rows_affected() == 0 {
+    return Err(AppError::RecordNotFound(format!(
+      "No pending invitation {} in workspace {}",
+      invite_id, workspace_id
+    )));
+  }
+  Ok(())
+}
+
+/// Bumps the email sequence of a pending invitation before its email is queued, and returns the
+/// new sequence. Returns `None` when an email was already queued less than `cooldown_secs` ago,
+/// in which case no other email should be queued.
+pub async fn update_workspace_invitation_email_seq<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  invite_id: &Uuid,
+  cooldown_secs: i64,
+) -> Result<Option<i32>, AppError> {
+  let seq = sqlx::query_scalar::<_, i32>(
+    r#"
+      UPDATE public.af_workspace_invitation
+      SET email_seq = email_seq + 1, email_queued_at = NOW()
+      WHERE id = $1
+        AND status = 0
+        AND (email_queued_at IS NULL OR email_queued_at <= NOW() - make_interval(secs => $2))
+      RETURNING email_seq
+    "#,
+  )
+  .bind(invite_id)
+  .bind(cooldown_secs as f64)
+  .fetch_optional(executor)
+  .await?;
+  Ok(seq)
+}
+
+/// The email sequence of an invitation which can still be accepted, `None` once the invitation
+/// was accepted, rejected, deleted or expired.
+pub async fn select_workspace_invitation_email_seq<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  invite_id: &Uuid,
+) -> Result<Option<i32>, AppError> {
+  let seq = sqlx::query_scalar::<_, i32>(
+    r#"
+      SELECT email_seq FROM public.af_workspace_invitation
+      WHERE id = $1 AND status = 0 AND expires_at > NOW()
+    "#,
+  )
+  .bind(invite_id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(seq)
+}
+
+/// Deletes up to `limit` pending invitations which expired more than `grace_period_secs` ago.
+/// Until then, accepting them fails with [AppError::InvitationExpired] rather than not found.
+pub async fn delete_expired_workspace_invitations<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  grace_period_secs: i64,
+  limit: i64,
+) -> Result<u64, AppError> {
+  let res = sqlx::query(
+    r#"
+      DELETE FROM public.af_workspace_invitation
+      WHERE id IN (
+        SELECT id FROM public.af_workspace_invitation
+        WHERE status = 0 AND expires_at < NOW() - make_interval(secs => $1)
+        LIMIT $2
+        FOR UPDATE SKIP LOCKED
+      )
+    "#,
+  )
+  .bind(grace_period_secs as f64)
+  .bind(limit)
+  .execute(executor)
+  .await?;
+  Ok(res.rows_affected())
 }
 
 #[inline]
-#[instrument(level = "trace", skip(pool, email, role), err)]
-pub async fn upsert_workspace_member(
-  pool: &PgPool,
+#[instrument(level = "trace", skip(executor, email, role), err)]
+pub async fn upsert_workspace_member<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
   workspace_id: &Uuid,
   email: &str,
   role: AFRole,
 ) -> Result<(), sqlx::Error> {
   event!(
     tracing::Level::TRACE,
-    "update workspace member: workspace_id:{}, uid {:?}, role:{:?}",
+    "update workspace member: workspace_id:{}, email {}, role:{:?}",
     workspace_id,
-    select_uid_from_email(pool, email).await,
+    email,
     role
   );
 
@@ -440,7 +595,7 @@ pub async fn upsert_workspace_member(
     workspace_id,
     email
   )
-  .execute(pool)
+  .execute(executor)
   .await?;
 
   Ok(())
@@ -532,6 +687,180 @@ pub async fn select_workspace_member_list(
   Ok(members)
 }
 
+/// Position of a member in a list of [select_workspace_member_page]: the value the list is sorted
+/// on, see [WorkspaceMemberSortBy], followed by the uid of the member.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkspaceMemberPosition {
+  JoinedAt(DateTime<Utc>, i64),
+  /// The name or the email of the member.
+  Text(String, i64),
+  Role(i32, i64),
+}
+
+/// Return up to `limit` members of the workspace matching the filter, following `after`, the
+/// position of the last member of the previous page, in the order of the filter.
+pub async fn select_workspace_member_page(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  filter: &WorkspaceMemberFilter,
+  after: Option<WorkspaceMemberPosition>,
+  limit: i64,
+) -> Result<Vec<AFWorkspaceMemberPageRow>, AppError> {
+  let (sort_key, after_key) = match filter.sort_by {
+    WorkspaceMemberSortBy::JoinedAt => ("joined_at", "$5::TIMESTAMPTZ"),
+    WorkspaceMemberSortBy::Name => ("lower(name)", "lower($5::TEXT)"),
+    WorkspaceMemberSortBy::Email => ("lower(email)", "lower($5::TEXT)"),
+    WorkspaceMemberSortBy::Role => ("role_id", "$5::INT"),
+  };
+  let (direction, comparison) = if filter.descending {
+    ("DESC", "<")
+  } else {
+    ("ASC", ">")
+  };
+  let sql = format!(
+    r#"
+      SELECT uid, name, email, role_id, joined_at FROM (
+        SELECT af_user.uid, af_user.name, af_user.email, af_workspace_member.role_id,
+          COALESCE(af_workspace_member.created_at, 'epoch'::TIMESTAMPTZ) AS joined_at
+        FROM public.af_workspace_member
+          JOIN public.af_user ON af_workspace_member.uid = af_user.uid
+        WHERE af_workspace_member.workspace_id = $1
+          AND ($2::INT IS NULL OR af_workspace_member.role_id = $2)
+          AND ($3::TEXT IS NULL
+            OR af_user.name ILIKE '%' || $3 || '%' ESCAPE '\'
+            OR af_user.email ILIKE '%' || $3 || '%' ESCAPE '\')
+      ) AS member
+      WHERE $6::BIGINT IS NULL OR ({sort_key}, uid) {comparison} ({after_key}, $6)
+      ORDER BY {sort_key} {direction}, uid {direction}
+      LIMIT $4
+    "#
+  );
+  let query = sqlx::query_as::<_, AFWorkspaceMemberPageRow>(&sql)
+    .bind(workspace_id)
+    .bind(filter.role.clone().map(|role| role as i32))
+    .bind(member_search_pattern(filter))
+    .bind(limit);
+  let query = match after {
+    None => query.bind(None::<String>).bind(None::<i64>),
+    Some(WorkspaceMemberPosition::JoinedAt(joined_at, uid)) => query.bind(joined_at).bind(uid),
+    Some(WorkspaceMemberPosition::Text(text, uid)) => query.bind(text).bind(uid),
+    Some(WorkspaceMemberPosition::Role(role_id, uid)) => query.bind(role_id).bind(uid),
+  };
+  let members = query.fetch_all(pg_pool).await?;
+  Ok(members)
+}
+
+/// Number of members of the workspace matching the role and the search of the filter.
+pub async fn select_workspace_member_match_count(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  filter: &WorkspaceMemberFilter,
+) -> Result<i64, AppError> {
+  let count = sqlx::query_scalar::<_, i64>(
+    r#"
+      SELECT COUNT(*)
+      FROM public.af_workspace_member
+        JOIN public.af_user ON af_workspace_member.uid = af_user.uid
+      WHERE af_workspace_member.workspace_id = $1
+        AND ($2::INT IS NULL OR af_workspace_member.role_id = $2)
+        AND ($3::TEXT IS NULL
+          OR af_user.name ILIKE '%' || $3 || '%' ESCAPE '\'
+          OR af_user.email ILIKE '%' || $3 || '%' ESCAPE '\')
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(filter.role.clone().map(|role| role as i32))
+  .bind(member_search_pattern(filter))
+  .fetch_one(pg_pool)
+  .await?;
+  Ok(count)
+}
+
+/// The `ILIKE` pattern of the search of the filter, `None` when it doesn't search.
+fn member_search_pattern(filter: &WorkspaceMemberFilter) -> Option<String> {
+  filter
+    .search
+    .as_deref()
+    .map(str::trim)
+    .filter(|search| !search.is_empty())
+    .map(escape_like_pattern)
+}
+
+macro_rules! workspace_member_export_query {
+  ($mfa_enabled:literal) => {
+    concat!(
+      r#"
+      SELECT af_user.uid, af_user.name, af_user.email, af_workspace_member.role_id,
+        af_workspace_member.created_at AS joined_at,
+        (
+          SELECT MAX(af_user_session.last_seen_at) FROM af_user_session
+          WHERE af_user_session.uid = af_user.uid
+        ) AS last_active,
+      "#,
+      $mfa_enabled,
+      r#" AS mfa_enabled
+      FROM af_workspace_member
+        JOIN af_user ON af_workspace_member.uid = af_user.uid
+      WHERE af_workspace_member.workspace_id = $1
+        AND ($2::INT IS NULL OR af_workspace_member.role_id = $2)
+        AND ($3::TEXT IS NULL
+          OR af_user.name ILIKE '%' || $3 || '%' ESCAPE '\'
+          OR af_user.email ILIKE '%' || $3 || '%' ESCAPE '\')
+      ORDER BY
+        CASE WHEN NOT $5 AND $4 = 'name' THEN lower(af_user.name)
+          WHEN NOT $5 AND $4 = 'email' THEN lower(af_user.email) END ASC,
+        CASE WHEN $5 AND $4 = 'name' THEN lower(af_user.name)
+          WHEN $5 AND $4 = 'email' THEN lower(af_user.email) END DESC,
+        CASE WHEN NOT $5 AND $4 = 'role' THEN af_workspace_member.role_id END ASC,
+        CASE WHEN $5 AND $4 = 'role' THEN af_workspace_member.role_id END DESC,
+        CASE WHEN NOT $5 THEN af_workspace_member.created_at END ASC,
+        CASE WHEN $5 THEN af_workspace_member.created_at END DESC,
+        af_user.uid
+      "#
+    )
+  };
+}
+
+/// Whether GoTrue records the second factors of the users, which depends on its version.
+pub async fn select_mfa_factors_available(pg_pool: &PgPool) -> Result<bool, AppError> {
+  let available =
+    sqlx::query_scalar::<_, bool>("SELECT to_regclass('auth.mfa_factors') IS NOT NULL")
+      .fetch_one(pg_pool)
+      .await?;
+  Ok(available)
+}
+
+/// Streams the members of the workspace matching the filter, in its order. The members are
+/// joined with their last session and, when `with_mfa` is set, with their verified second
+/// factors, which requires [select_mfa_factors_available].
+pub fn select_workspace_member_export_stream<'a>(
+  pg_pool: &'a PgPool,
+  workspace_id: Uuid,
+  filter: &WorkspaceMemberFilter,
+  with_mfa: bool,
+) -> BoxStream<'a, sqlx::Result<AFWorkspaceMemberExportRow>> {
+  let sql = if with_mfa {
+    workspace_member_export_query!(
+      "EXISTS (SELECT 1 FROM auth.mfa_factors WHERE auth.mfa_factors.user_id = af_user.uuid AND auth.mfa_factors.status = 'verified')"
+    )
+  } else {
+    workspace_member_export_query!("NULL::BOOLEAN")
+  };
+  let sort_by = match filter.sort_by {
+    WorkspaceMemberSortBy::JoinedAt => "joined_at",
+    WorkspaceMemberSortBy::Name => "name",
+    WorkspaceMemberSortBy::Email => "email",
+    WorkspaceMemberSortBy::Role => "role",
+  };
+  sqlx::query_as::<_, AFWorkspaceMemberExportRow>(sql)
+    .bind(workspace_id)
+    .bind(filter.role.clone().map(|role| role as i32))
+    .bind(member_search_pattern(filter))
+    .bind(sort_by)
+    .bind(filter.descending)
+    .fetch(pg_pool)
+}
+
 #[inline]
 pub async fn select_workspace_member<'a, E: Executor<'a, Database = Postgres>>(
   executor: E,
@@ -1422,6 +1751,42 @@ pub async fn update_import_task_metadata(
   Ok(())
 }
 
+/// Store the report of the import with its task, replacing the previous one.
+pub async fn update_import_task_report<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  task_id: &Uuid,
+  report: &ImportReport,
+) -> Result<(), AppError> {
+  sqlx::query("UPDATE af_import_task SET report = $1 WHERE task_id = $2")
+    .bind(sqlx::types::Json(report))
+    .bind(task_id)
+    .execute(executor)
+    .await?;
+  Ok(())
+}
+
+/// Return the report of an import task created by the user. Fails with
+/// [AppError::RecordNotFound] when the task doesn't exist, belongs to another user or has no
+/// report yet.
+pub async fn select_import_task_report(
+  pg_pool: &PgPool,
+  task_id: &Uuid,
+  uid: i64,
+) -> Result<ImportReport, AppError> {
+  let report: Option<(Option<sqlx::types::Json<ImportReport>>,)> =
+    sqlx::query_as("SELECT report FROM af_import_task WHERE task_id = $1 AND created_by = $2")
+      .bind(task_id)
+      .bind(uid)
+      .fetch_optional(pg_pool)
+      .await?;
+  report
+    .and_then(|(report,)| report)
+    .map(|report| report.0)
+    .ok_or_else(|| {
+      AppError::RecordNotFound(format!("No report found for the import task {}", task_id))
+    })
+}
+
 #[inline]
 pub async fn select_publish_name_exists(
   pg_pool: &PgPool,
@@ -1469,3 +1834,78 @@ pub async fn select_view_id_from_publish_name(
 
   Ok(res)
 }
+
+/// Returns the subscription plan recorded for the workspace, if any.
+pub async fn select_workspace_ai_tier<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<Option<i16>, AppError> {
+  let plan = sqlx::query_scalar::<_, i16>(
+    r#"
+      SELECT plan FROM af_workspace_ai_tier
+      WHERE workspace_id = $1
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(plan)
+}
+
+/// Returns the subscription plan recorded for the workspace by the billing service, if any. Unlike
+/// [select_workspace_ai_tier], the AI add-ons don't replace the plan.
+pub async fn select_workspace_subscription_plan<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<Option<i16>, AppError> {
+  let plan = sqlx::query_scalar::<_, i16>(
+    r#"
+      SELECT plan FROM af_workspace_subscription_plan
+      WHERE workspace_id = $1
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(plan)
+}
+
+/// Records the subscription plan of the workspace, overriding the one set by the billing service.
+pub async fn upsert_workspace_subscription_plan<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  plan: i16,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      INSERT INTO af_workspace_subscription_plan (workspace_id, plan, updated_at)
+      VALUES ($1, $2, NOW())
+      ON CONFLICT (workspace_id) DO UPDATE SET plan = EXCLUDED.plan, updated_at = NOW()
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(plan)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+/// Records the subscription plan of the workspace, overriding the one set by the billing service.
+pub async fn upsert_workspace_ai_tier<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  plan: i16,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      INSERT INTO af_workspace_ai_tier (workspace_id, plan, updated_at)
+      VALUES ($1, $2, NOW())
+      ON CONFLICT (workspace_id) DO UPDATE SET plan = EXCLUDED.plan, updated_at = NOW()
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(plan)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
@@ -0,0 +1,25 @@
+use app_error::AppError;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+pub async fn insert_workspace_audit_log<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  uid: i64,
+  action: &str,
+  details: &serde_json::Value,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      INSERT INTO af_workspace_audit_log (workspace_id, uid, action, details)
+      VALUES ($1, $2, $3, $4)
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(uid)
+  .bind(action)
+  .bind(details)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
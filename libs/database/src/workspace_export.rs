@@ -0,0 +1,243 @@
+use app_error::AppError;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::AFWorkspaceExportRow;
+
+/// The stream the export worker reads the export tasks from.
+pub const EXPORT_TASK_STREAM: &str = "export_task_stream";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExportTaskState {
+  Pending = 0,
+  Running = 1,
+  Completed = 2,
+  Failed = 3,
+  Expired = 4,
+}
+
+impl From<i16> for ExportTaskState {
+  fn from(val: i16) -> Self {
+    match val {
+      1 => ExportTaskState::Running,
+      2 => ExportTaskState::Completed,
+      3 => ExportTaskState::Failed,
+      4 => ExportTaskState::Expired,
+      _ => ExportTaskState::Pending,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExportStage {
+  Queued = 0,
+  Collabs = 1,
+  Blobs = 2,
+  Uploading = 3,
+  Done = 4,
+}
+
+impl From<i16> for ExportStage {
+  fn from(val: i16) -> Self {
+    match val {
+      1 => ExportStage::Collabs,
+      2 => ExportStage::Blobs,
+      3 => ExportStage::Uploading,
+      4 => ExportStage::Done,
+      _ => ExportStage::Queued,
+    }
+  }
+}
+
+/// Insert a pending export task. Returns [AppError::ExportTaskAlreadyRunning] if the workspace
+/// already has an export that is pending or running.
+pub async fn insert_workspace_export_task<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  task_id: &Uuid,
+  workspace_id: &Uuid,
+  uid: i64,
+) -> Result<AFWorkspaceExportRow, AppError> {
+  let result = sqlx::query_as::<_, AFWorkspaceExportRow>(
+    r#"
+      INSERT INTO af_workspace_export (task_id, workspace_id, created_by)
+      VALUES ($1, $2, $3)
+      RETURNING *
+    "#,
+  )
+  .bind(task_id)
+  .bind(workspace_id)
+  .bind(uid)
+  .fetch_one(executor)
+  .await;
+
+  match result {
+    Ok(row) => Ok(row),
+    Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+      Err(AppError::ExportTaskAlreadyRunning(format!(
+        "workspace {} already has an export in progress",
+        workspace_id
+      )))
+    },
+    Err(err) => Err(err.into()),
+  }
+}
+
+pub async fn select_workspace_export_task<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  task_id: &Uuid,
+) -> Result<AFWorkspaceExportRow, AppError> {
+  let row = sqlx::query_as::<_, AFWorkspaceExportRow>(
+    r#"
+      SELECT * FROM af_workspace_export
+      WHERE workspace_id = $1 AND task_id = $2
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(task_id)
+  .fetch_one(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Move a pending export task to running and return it. A running task whose progress wasn't
+/// updated for `lease_secs` is claimed again, its worker being assumed to have stopped. Returns
+/// `None` if the task is being processed by another worker, or is finished.
+pub async fn claim_workspace_export_task<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  task_id: &Uuid,
+  lease_secs: i64,
+) -> Result<Option<AFWorkspaceExportRow>, AppError> {
+  let row = sqlx::query_as::<_, AFWorkspaceExportRow>(
+    r#"
+      UPDATE af_workspace_export
+      SET status = $2, updated_at = CURRENT_TIMESTAMP
+      WHERE task_id = $1
+        AND (status = $3
+          OR (status = $2 AND updated_at < CURRENT_TIMESTAMP - make_interval(secs => $4)))
+      RETURNING *
+    "#,
+  )
+  .bind(task_id)
+  .bind(ExportTaskState::Running as i16)
+  .bind(ExportTaskState::Pending as i16)
+  .bind(lease_secs as f64)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+pub async fn update_workspace_export_progress<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  task_id: &Uuid,
+  status: ExportTaskState,
+  stage: ExportStage,
+  bytes_written: i64,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_workspace_export
+      SET status = $2, stage = $3, bytes_written = $4, updated_at = CURRENT_TIMESTAMP
+      WHERE task_id = $1
+    "#,
+  )
+  .bind(task_id)
+  .bind(status as i16)
+  .bind(stage as i16)
+  .bind(bytes_written)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+pub async fn complete_workspace_export_task<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  task_id: &Uuid,
+  s3_key: &str,
+  download_url: &str,
+  bytes_written: i64,
+  expires_at: DateTime<Utc>,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_workspace_export
+      SET status = $2, stage = $3, s3_key = $4, download_url = $5, bytes_written = $6,
+          expires_at = $7, updated_at = CURRENT_TIMESTAMP
+      WHERE task_id = $1
+    "#,
+  )
+  .bind(task_id)
+  .bind(ExportTaskState::Completed as i16)
+  .bind(ExportStage::Done as i16)
+  .bind(s3_key)
+  .bind(download_url)
+  .bind(bytes_written)
+  .bind(expires_at)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+pub async fn fail_workspace_export_task<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  task_id: &Uuid,
+  error: &str,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_workspace_export
+      SET status = $2, error = $3, updated_at = CURRENT_TIMESTAMP
+      WHERE task_id = $1
+    "#,
+  )
+  .bind(task_id)
+  .bind(ExportTaskState::Failed as i16)
+  .bind(error)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+/// Mark the running exports whose progress wasn't updated for `lease_secs` as failed, their
+/// worker being assumed to have stopped, so that the workspace can be exported again.
+pub async fn fail_stale_workspace_export_tasks(
+  pg_pool: &PgPool,
+  lease_secs: i64,
+) -> Result<Vec<AFWorkspaceExportRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFWorkspaceExportRow>(
+    r#"
+      UPDATE af_workspace_export
+      SET status = $1, error = $2, updated_at = CURRENT_TIMESTAMP
+      WHERE status = $3 AND updated_at < CURRENT_TIMESTAMP - make_interval(secs => $4)
+      RETURNING *
+    "#,
+  )
+  .bind(ExportTaskState::Failed as i16)
+  .bind("the export was interrupted")
+  .bind(ExportTaskState::Running as i16)
+  .bind(lease_secs as f64)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(rows)
+}
+
+/// Mark completed exports whose download link has expired as expired, and return them so that
+/// the caller can delete the archives from the bucket.
+pub async fn expire_workspace_export_tasks(
+  pg_pool: &PgPool,
+) -> Result<Vec<AFWorkspaceExportRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFWorkspaceExportRow>(
+    r#"
+      UPDATE af_workspace_export
+      SET status = $1, download_url = NULL, updated_at = CURRENT_TIMESTAMP
+      WHERE status = $2 AND expires_at < CURRENT_TIMESTAMP
+      RETURNING *
+    "#,
+  )
+  .bind(ExportTaskState::Expired as i16)
+  .bind(ExportTaskState::Completed as i16)
+  .fetch_all(pg_pool)
+  .await?;
+  Ok(rows)
+}
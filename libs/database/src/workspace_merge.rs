@@ -0,0 +1,333 @@
+use app_error::AppError;
+use chrono::{DateTime, Utc};
+use database_entity::dto::AFRole;
+use sqlx::{Executor, Postgres, Transaction};
+use std::ops::DerefMut;
+use uuid::Uuid;
+
+use crate::pg_row::AFWorkspaceMergeRow;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MergeTaskState {
+  Pending = 0,
+  Running = 1,
+  Completed = 2,
+  Failed = 3,
+}
+
+impl From<i16> for MergeTaskState {
+  fn from(val: i16) -> Self {
+    match val {
+      1 => MergeTaskState::Running,
+      2 => MergeTaskState::Completed,
+      3 => MergeTaskState::Failed,
+      _ => MergeTaskState::Pending,
+    }
+  }
+}
+
+/// The stages of a merge run in order. The stage of a task is the one it's currently running, so
+/// a task which is picked up again after a restart resumes from it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum MergeStage {
+  Queued = 0,
+  Collabs = 1,
+  Folder = 2,
+  Blobs = 3,
+  Archive = 4,
+  Done = 5,
+}
+
+impl From<i16> for MergeStage {
+  fn from(val: i16) -> Self {
+    match val {
+      1 => MergeStage::Collabs,
+      2 => MergeStage::Folder,
+      3 => MergeStage::Blobs,
+      4 => MergeStage::Archive,
+      5 => MergeStage::Done,
+      _ => MergeStage::Queued,
+    }
+  }
+}
+
+/// Insert a pending merge task. Returns [AppError::WorkspaceMergeBlocked] if one of the
+/// workspaces already takes part in a merge that is pending or running.
+pub async fn insert_workspace_merge_task<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  task_id: &Uuid,
+  target_workspace_id: &Uuid,
+  source_workspace_id: &Uuid,
+  uid: i64,
+  report: &serde_json::Value,
+) -> Result<AFWorkspaceMergeRow, AppError> {
+  let result = sqlx::query_as::<_, AFWorkspaceMergeRow>(
+    r#"
+      INSERT INTO af_workspace_merge
+        (task_id, target_workspace_id, source_workspace_id, created_by, report)
+      VALUES ($1, $2, $3, $4, $5)
+      RETURNING *
+    "#,
+  )
+  .bind(task_id)
+  .bind(target_workspace_id)
+  .bind(source_workspace_id)
+  .bind(uid)
+  .bind(report)
+  .fetch_one(executor)
+  .await;
+
+  match result {
+    Ok(row) => Ok(row),
+    Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+      Err(AppError::WorkspaceMergeBlocked(format!(
+        "workspace {} or {} already has a merge in progress",
+        target_workspace_id, source_workspace_id
+      )))
+    },
+    Err(err) => Err(err.into()),
+  }
+}
+
+pub async fn select_workspace_merge_task<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  target_workspace_id: &Uuid,
+  task_id: &Uuid,
+) -> Result<AFWorkspaceMergeRow, AppError> {
+  let row = sqlx::query_as::<_, AFWorkspaceMergeRow>(
+    r#"
+      SELECT * FROM af_workspace_merge
+      WHERE target_workspace_id = $1 AND task_id = $2
+    "#,
+  )
+  .bind(target_workspace_id)
+  .bind(task_id)
+  .fetch_one(executor)
+  .await?;
+  Ok(row)
+}
+
+pub async fn select_workspace_merge_task_by_id<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  task_id: &Uuid,
+) -> Result<AFWorkspaceMergeRow, AppError> {
+  let row = sqlx::query_as::<_, AFWorkspaceMergeRow>(
+    r#"
+      SELECT * FROM af_workspace_merge
+      WHERE task_id = $1
+    "#,
+  )
+  .bind(task_id)
+  .fetch_one(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Returns true if one of the workspaces is the source or the target of a merge that is pending
+/// or running.
+pub async fn is_workspace_merge_active<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_ids: &[Uuid],
+) -> Result<bool, AppError> {
+  let exists = sqlx::query_scalar::<_, bool>(
+    r#"
+      SELECT EXISTS (
+        SELECT 1 FROM af_workspace_merge
+        WHERE status IN (0, 1)
+          AND (target_workspace_id = ANY($1) OR source_workspace_id = ANY($1))
+      )
+    "#,
+  )
+  .bind(workspace_ids)
+  .fetch_one(executor)
+  .await?;
+  Ok(exists)
+}
+
+/// Returns true if one of the workspaces has an import that is still waiting to be processed.
+pub async fn has_pending_import_task<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_ids: &[Uuid],
+) -> Result<bool, AppError> {
+  let workspace_ids = workspace_ids
+    .iter()
+    .map(|id| id.to_string())
+    .collect::<Vec<_>>();
+  let exists = sqlx::query_scalar::<_, bool>(
+    r#"
+      SELECT EXISTS (
+        SELECT 1 FROM af_import_task
+        WHERE workspace_id = ANY($1) AND status = 0
+      )
+    "#,
+  )
+  .bind(&workspace_ids)
+  .fetch_one(executor)
+  .await?;
+  Ok(exists)
+}
+
+pub async fn update_workspace_merge_progress<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  task_id: &Uuid,
+  status: MergeTaskState,
+  stage: MergeStage,
+  moved_collabs: i64,
+  copied_blobs: i64,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_workspace_merge
+      SET status = $2, stage = $3, moved_collabs = $4, copied_blobs = $5,
+          updated_at = CURRENT_TIMESTAMP
+      WHERE task_id = $1
+    "#,
+  )
+  .bind(task_id)
+  .bind(status as i16)
+  .bind(stage as i16)
+  .bind(moved_collabs)
+  .bind(copied_blobs)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+pub async fn update_workspace_merge_report<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  task_id: &Uuid,
+  report: &serde_json::Value,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_workspace_merge
+      SET report = $2, updated_at = CURRENT_TIMESTAMP
+      WHERE task_id = $1
+    "#,
+  )
+  .bind(task_id)
+  .bind(report)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+pub async fn fail_workspace_merge_task<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  task_id: &Uuid,
+  error: &str,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_workspace_merge
+      SET status = $2, error = $3, updated_at = CURRENT_TIMESTAMP
+      WHERE task_id = $1
+    "#,
+  )
+  .bind(task_id)
+  .bind(MergeTaskState::Failed as i16)
+  .bind(error)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+/// Move the collabs of the source workspace to the target workspace, along with their snapshots.
+/// Returns the number of moved collabs.
+///
+/// The ids are kept as they are: a collab is identified by its id and its partition key, the
+/// workspace isn't part of it, so an id can only be used by one workspace and the collabs of the
+/// source workspace can't collide with the ones of the target workspace. A collab moved by a
+/// previous run is no longer part of the source workspace and is skipped.
+pub async fn move_merged_collabs(
+  txn: &mut Transaction<'_, Postgres>,
+  source_workspace_id: &Uuid,
+  target_workspace_id: &Uuid,
+  object_ids: &[String],
+) -> Result<u64, AppError> {
+  let moved = sqlx::query(
+    r#"
+      UPDATE af_collab SET workspace_id = $2
+      WHERE workspace_id = $1 AND oid = ANY($3)
+    "#,
+  )
+  .bind(source_workspace_id)
+  .bind(target_workspace_id)
+  .bind(object_ids)
+  .execute(txn.deref_mut())
+  .await?
+  .rows_affected();
+  sqlx::query(
+    r#"
+      UPDATE af_collab_snapshot SET workspace_id = $2
+      WHERE workspace_id = $1 AND oid = ANY($3)
+    "#,
+  )
+  .bind(source_workspace_id)
+  .bind(target_workspace_id)
+  .bind(object_ids)
+  .execute(txn.deref_mut())
+  .await?;
+  Ok(moved)
+}
+
+/// Archive the source workspace of a merge, pointing at the workspace its content was moved to.
+pub async fn archive_merged_workspace<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  source_workspace_id: &Uuid,
+  target_workspace_id: &Uuid,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_workspace
+      SET merged_into = $2, archived_at = COALESCE(archived_at, CURRENT_TIMESTAMP)
+      WHERE workspace_id = $1
+    "#,
+  )
+  .bind(source_workspace_id)
+  .bind(target_workspace_id)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+/// Returns the time the workspace was archived at, and the workspace it was merged into.
+pub async fn select_workspace_archive<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<Option<(DateTime<Utc>, Option<Uuid>)>, AppError> {
+  let row = sqlx::query_as::<_, (Option<DateTime<Utc>>, Option<Uuid>)>(
+    r#"
+      SELECT archived_at, merged_into FROM af_workspace
+      WHERE workspace_id = $1
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row.and_then(|(archived_at, merged_into)| archived_at.map(|at| (at, merged_into))))
+}
+
+/// Add the member to the workspace, or give them the role if it's higher than the one they have.
+pub async fn upsert_merged_workspace_member(
+  txn: &mut Transaction<'_, Postgres>,
+  workspace_id: &Uuid,
+  uid: i64,
+  role: AFRole,
+) -> Result<(), AppError> {
+  let role_id: i32 = role.into();
+  sqlx::query(
+    r#"
+      INSERT INTO af_workspace_member (workspace_id, uid, role_id)
+      VALUES ($1, $2, $3)
+      ON CONFLICT (workspace_id, uid)
+      DO UPDATE SET role_id = LEAST(af_workspace_member.role_id, excluded.role_id)
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(uid)
+  .bind(role_id)
+  .execute(txn.deref_mut())
+  .await?;
+  Ok(())
+}
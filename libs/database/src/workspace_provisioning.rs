@@ -0,0 +1,162 @@
+use app_error::AppError;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::AFWorkspaceProvisioningRow;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ProvisioningState {
+  Pending = 0,
+  Running = 1,
+  Completed = 2,
+  Failed = 3,
+}
+
+impl From<i16> for ProvisioningState {
+  fn from(val: i16) -> Self {
+    match val {
+      1 => ProvisioningState::Running,
+      2 => ProvisioningState::Completed,
+      3 => ProvisioningState::Failed,
+      _ => ProvisioningState::Pending,
+    }
+  }
+}
+
+/// Insert a pending provisioning. Returns `None` when the caller already started a provisioning
+/// with the same external id.
+pub async fn insert_workspace_provisioning<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+  external_id: &str,
+  spec: &serde_json::Value,
+) -> Result<Option<AFWorkspaceProvisioningRow>, AppError> {
+  let row = sqlx::query_as::<_, AFWorkspaceProvisioningRow>(
+    r#"
+      INSERT INTO af_workspace_provisioning (created_by, external_id, spec)
+      VALUES ($1, $2, $3)
+      ON CONFLICT (created_by, external_id) DO NOTHING
+      RETURNING *
+    "#,
+  )
+  .bind(uid)
+  .bind(external_id)
+  .bind(spec)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+pub async fn select_workspace_provisioning<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+  external_id: &str,
+) -> Result<AFWorkspaceProvisioningRow, AppError> {
+  sqlx::query_as::<_, AFWorkspaceProvisioningRow>(
+    r#"
+      SELECT * FROM af_workspace_provisioning
+      WHERE created_by = $1 AND external_id = $2
+    "#,
+  )
+  .bind(uid)
+  .bind(external_id)
+  .fetch_optional(executor)
+  .await?
+  .ok_or_else(|| AppError::RecordNotFound(format!("provisioning {} not found", external_id)))
+}
+
+/// Sets the workspace of the provisioning unless another request already set it. Returns the
+/// workspace of the provisioning.
+pub async fn update_workspace_provisioning_workspace<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+  external_id: &str,
+  workspace_id: &Uuid,
+) -> Result<Uuid, AppError> {
+  let workspace_id = sqlx::query_scalar::<_, Uuid>(
+    r#"
+      UPDATE af_workspace_provisioning
+      SET workspace_id = COALESCE(workspace_id, $3), updated_at = NOW()
+      WHERE created_by = $1 AND external_id = $2
+      RETURNING workspace_id
+    "#,
+  )
+  .bind(uid)
+  .bind(external_id)
+  .bind(workspace_id)
+  .fetch_one(executor)
+  .await?;
+  Ok(workspace_id)
+}
+
+/// Takes over the provisioning to run it. Only a pending or failed provisioning, or one whose run
+/// stopped updating it for `stale_after_secs`, can be taken over. Returns false when it's running
+/// or completed.
+pub async fn start_workspace_provisioning<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+  external_id: &str,
+  stale_after_secs: i64,
+) -> Result<bool, AppError> {
+  let result = sqlx::query(
+    r#"
+      UPDATE af_workspace_provisioning
+      SET status = 1, error = NULL, updated_at = NOW()
+      WHERE created_by = $1 AND external_id = $2
+        AND (status IN (0, 3)
+          OR (status = 1 AND updated_at < NOW() - make_interval(secs => $3)))
+    "#,
+  )
+  .bind(uid)
+  .bind(external_id)
+  .bind(stale_after_secs as f64)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected() > 0)
+}
+
+pub async fn update_workspace_provisioning_progress<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+  external_id: &str,
+  members: &serde_json::Value,
+  template_view_id: Option<&str>,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_workspace_provisioning
+      SET members = $3, template_view_id = COALESCE($4, template_view_id), updated_at = NOW()
+      WHERE created_by = $1 AND external_id = $2
+    "#,
+  )
+  .bind(uid)
+  .bind(external_id)
+  .bind(members)
+  .bind(template_view_id)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+pub async fn finish_workspace_provisioning<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  uid: i64,
+  external_id: &str,
+  state: ProvisioningState,
+  error: Option<&str>,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      UPDATE af_workspace_provisioning
+      SET status = $3, error = $4, updated_at = NOW()
+      WHERE created_by = $1 AND external_id = $2
+    "#,
+  )
+  .bind(uid)
+  .bind(external_id)
+  .bind(state as i16)
+  .bind(error)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
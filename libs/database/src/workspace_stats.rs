@@ -0,0 +1,189 @@
+use app_error::AppError;
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::AFWorkspaceStatsRow;
+
+/// Returns when the stats of the workspace were last fully computed, `None` when they never were.
+pub async fn select_workspace_stats_computed_at<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<Option<DateTime<Utc>>, AppError> {
+  let computed_at = sqlx::query_scalar::<_, DateTime<Utc>>(
+    r#"SELECT computed_at FROM af_workspace_stats_state WHERE workspace_id = $1"#,
+  )
+  .bind(workspace_id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(computed_at)
+}
+
+/// The stats of the workspace, including the changes not folded yet.
+pub async fn select_workspace_stats<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<Vec<AFWorkspaceStatsRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFWorkspaceStatsRow>(
+    r#"
+      SELECT partition_key, SUM(object_count)::BIGINT AS object_count,
+        SUM(total_bytes)::BIGINT AS total_bytes
+      FROM (
+        SELECT partition_key, object_count, total_bytes
+        FROM af_workspace_stats
+        WHERE workspace_id = $1
+        UNION ALL
+        SELECT partition_key, object_delta, bytes_delta
+        FROM af_workspace_stats_delta
+        WHERE workspace_id = $1
+      ) stats
+      GROUP BY partition_key
+      ORDER BY partition_key
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+/// Recomputes the stats of the workspace from the metadata of its collabs, discarding the pending
+/// changes. The changes are discarded in the same statement the collabs are counted, so that a
+/// change is either counted or kept for the next fold, never both.
+pub async fn recompute_workspace_stats<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<(), AppError> {
+  sqlx::query(
+    r#"
+      WITH discarded AS (
+        DELETE FROM af_workspace_stats_delta WHERE workspace_id = $1
+      ),
+      totals AS (
+        SELECT key AS partition_key,
+          COUNT(c.oid) AS object_count,
+          COALESCE(SUM(c.len), 0)::BIGINT AS total_bytes
+        FROM (
+          SELECT partition_key AS key FROM af_workspace_stats WHERE workspace_id = $1
+          UNION
+          SELECT DISTINCT partition_key FROM af_collab WHERE workspace_id = $1
+        ) keys
+        LEFT JOIN af_collab c
+          ON c.workspace_id = $1 AND c.partition_key = keys.key AND c.deleted_at IS NULL
+        GROUP BY key
+      ),
+      state AS (
+        INSERT INTO af_workspace_stats_state (workspace_id, computed_at)
+        VALUES ($1, NOW())
+        ON CONFLICT (workspace_id) DO UPDATE SET computed_at = NOW()
+      )
+      INSERT INTO af_workspace_stats (workspace_id, partition_key, object_count, total_bytes)
+      SELECT $1, partition_key, object_count, total_bytes FROM totals
+      ON CONFLICT (workspace_id, partition_key) DO UPDATE
+      SET object_count = EXCLUDED.object_count, total_bytes = EXCLUDED.total_bytes
+    "#,
+  )
+  .bind(workspace_id)
+  .execute(executor)
+  .await?;
+  Ok(())
+}
+
+/// Folds at most `limit` pending changes into the stats of their workspace. The changes of the
+/// workspaces whose stats were never computed are dropped, they are counted when the stats are
+/// computed. Returns the number of changes folded.
+pub async fn fold_workspace_stats_deltas<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  limit: i64,
+) -> Result<u64, AppError> {
+  let folded = sqlx::query_scalar::<_, i64>(
+    r#"
+      WITH folded AS (
+        DELETE FROM af_workspace_stats_delta
+        WHERE id IN (
+          SELECT id FROM af_workspace_stats_delta ORDER BY id LIMIT $1 FOR UPDATE SKIP LOCKED
+        )
+        RETURNING workspace_id, partition_key, object_delta, bytes_delta
+      ),
+      sums AS (
+        SELECT workspace_id, partition_key, SUM(object_delta)::BIGINT AS object_delta,
+          SUM(bytes_delta)::BIGINT AS bytes_delta, COUNT(*) AS changes
+        FROM folded
+        GROUP BY workspace_id, partition_key
+      ),
+      applied AS (
+        INSERT INTO af_workspace_stats (workspace_id, partition_key, object_count, total_bytes)
+        SELECT s.workspace_id, s.partition_key, s.object_delta, s.bytes_delta
+        FROM sums s
+        JOIN af_workspace_stats_state st ON st.workspace_id = s.workspace_id
+        ON CONFLICT (workspace_id, partition_key) DO UPDATE
+        SET object_count = af_workspace_stats.object_count + EXCLUDED.object_count,
+            total_bytes = af_workspace_stats.total_bytes + EXCLUDED.total_bytes
+      )
+      SELECT COALESCE(SUM(changes), 0)::BIGINT FROM sums
+    "#,
+  )
+  .bind(limit)
+  .fetch_one(executor)
+  .await?;
+  Ok(folded as u64)
+}
+
+/// Copies the stats of every computed workspace as the snapshot of `date`. Does nothing for the
+/// workspaces which already have one for that date.
+pub async fn insert_workspace_stats_snapshots<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  date: NaiveDate,
+) -> Result<u64, AppError> {
+  let result = sqlx::query(
+    r#"
+      INSERT INTO af_workspace_stats_snapshot
+        (workspace_id, snapshot_date, partition_key, object_count, total_bytes)
+      SELECT workspace_id, $1, partition_key, object_count, total_bytes
+      FROM af_workspace_stats
+      ON CONFLICT DO NOTHING
+    "#,
+  )
+  .bind(date)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected())
+}
+
+/// The latest snapshot of the workspace taken on or before `date`.
+pub async fn select_workspace_stats_snapshot<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  date: NaiveDate,
+) -> Result<Option<(NaiveDate, Vec<AFWorkspaceStatsRow>)>, AppError> {
+  let rows = sqlx::query_as::<_, (NaiveDate, i32, i64, i64)>(
+    r#"
+      SELECT snapshot_date, partition_key, object_count, total_bytes
+      FROM af_workspace_stats_snapshot
+      WHERE workspace_id = $1 AND snapshot_date = (
+        SELECT MAX(snapshot_date) FROM af_workspace_stats_snapshot
+        WHERE workspace_id = $1 AND snapshot_date <= $2
+      )
+      ORDER BY partition_key
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(date)
+  .fetch_all(executor)
+  .await?;
+  let snapshot_date = match rows.first() {
+    None => return Ok(None),
+    Some((snapshot_date, ..)) => *snapshot_date,
+  };
+  let rows = rows
+    .into_iter()
+    .map(
+      |(_, partition_key, object_count, total_bytes)| AFWorkspaceStatsRow {
+        partition_key,
+        object_count,
+        total_bytes,
+      },
+    )
+    .collect();
+  Ok(Some((snapshot_date, rows)))
+}
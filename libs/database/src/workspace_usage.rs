@@ -0,0 +1,108 @@
+use app_error::AppError;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::stream::BoxStream;
+use sqlx::{Executor, PgPool, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::AFWorkspaceUsageDailyRow;
+
+/// Rolls up the usage of every workspace which existed on `day`, between `start` and `end`, the
+/// bounds of the day. Each source is aggregated again from scratch and replaces the row of a
+/// previous rollup, so rolling up a day twice doesn't count its usage twice, and a rollup run
+/// after late data, such as a delayed flush of the bandwidth counters, updates the row.
+///
+/// The storage is the bytes of the blobs uploaded by the end of the day which are still stored.
+/// The active members are the members with a session seen during the day.
+pub async fn upsert_workspace_usage_daily<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  day: NaiveDate,
+  start: DateTime<Utc>,
+  end: DateTime<Utc>,
+) -> Result<u64, AppError> {
+  let result = sqlx::query(
+    r#"
+      INSERT INTO af_workspace_usage_daily
+        (workspace_id, day, storage_bytes, ai_search_requests, ai_tokens, active_members,
+          bandwidth_in, bandwidth_out)
+      SELECT
+        w.workspace_id,
+        $1,
+        COALESCE((
+          SELECT SUM(b.file_size) FROM af_blob_metadata b
+          WHERE b.workspace_id = w.workspace_id AND b.modified_at < $3
+        ), 0)::BIGINT,
+        COALESCE(ai.search_requests, 0)::BIGINT,
+        (COALESCE(ai.search_tokens_consumed, 0) + COALESCE(ai.index_tokens_consumed, 0))::BIGINT,
+        (
+          SELECT COUNT(DISTINCT m.uid) FROM af_workspace_member m
+          JOIN af_user_session s ON s.uid = m.uid
+          WHERE m.workspace_id = w.workspace_id
+            AND s.created_at < $3 AND s.last_seen_at >= $2
+        ),
+        COALESCE(bw.bytes_in, 0),
+        COALESCE(bw.bytes_out, 0)
+      FROM af_workspace w
+      LEFT JOIN af_workspace_ai_usage ai
+        ON ai.workspace_id = w.workspace_id AND ai.created_at = $1
+      LEFT JOIN af_workspace_realtime_bandwidth bw
+        ON bw.workspace_id = w.workspace_id AND bw.day = $1
+      WHERE COALESCE(w.created_at, $2) < $3
+        AND (w.deleted_at IS NULL OR w.deleted_at >= $2)
+      ON CONFLICT (workspace_id, day) DO UPDATE SET
+        storage_bytes = EXCLUDED.storage_bytes,
+        ai_search_requests = EXCLUDED.ai_search_requests,
+        ai_tokens = EXCLUDED.ai_tokens,
+        active_members = EXCLUDED.active_members,
+        bandwidth_in = EXCLUDED.bandwidth_in,
+        bandwidth_out = EXCLUDED.bandwidth_out,
+        rolled_up_at = NOW()
+    "#,
+  )
+  .bind(day)
+  .bind(start)
+  .bind(end)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected())
+}
+
+const SELECT_WORKSPACE_USAGE_DAILY: &str = r#"
+  SELECT u.workspace_id, w.workspace_name, u.day, u.storage_bytes, u.ai_search_requests,
+    u.ai_tokens, u.active_members, u.bandwidth_in, u.bandwidth_out, u.rolled_up_at
+  FROM af_workspace_usage_daily u
+  JOIN af_workspace w ON w.workspace_id = u.workspace_id
+  WHERE u.day BETWEEN $1 AND $2
+    AND ($3::UUID IS NULL OR u.workspace_id = $3)
+  ORDER BY u.day, u.workspace_id
+"#;
+
+/// The rolled up usage between the two days, both included, of every workspace or of the given
+/// one.
+pub async fn select_workspace_usage_daily<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  from: NaiveDate,
+  to: NaiveDate,
+  workspace_id: Option<Uuid>,
+) -> Result<Vec<AFWorkspaceUsageDailyRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFWorkspaceUsageDailyRow>(SELECT_WORKSPACE_USAGE_DAILY)
+    .bind(from)
+    .bind(to)
+    .bind(workspace_id)
+    .fetch_all(executor)
+    .await?;
+  Ok(rows)
+}
+
+/// Same rows as [select_workspace_usage_daily], streamed as they're read.
+pub fn select_workspace_usage_daily_stream(
+  pg_pool: &PgPool,
+  from: NaiveDate,
+  to: NaiveDate,
+  workspace_id: Option<Uuid>,
+) -> BoxStream<'_, sqlx::Result<AFWorkspaceUsageDailyRow>> {
+  sqlx::query_as::<_, AFWorkspaceUsageDailyRow>(SELECT_WORKSPACE_USAGE_DAILY)
+    .bind(from)
+    .bind(to)
+    .bind(workspace_id)
+    .fetch(pg_pool)
+}
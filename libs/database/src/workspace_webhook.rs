@@ -0,0 +1,89 @@
+use app_error::AppError;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::pg_row::AFWorkspaceWebhookRow;
+
+#[allow(clippy::too_many_arguments)]
+pub async fn insert_workspace_webhook<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  id: &Uuid,
+  workspace_id: &Uuid,
+  url: &str,
+  secret: &str,
+  event_types: &[String],
+  batch: bool,
+  created_by: i64,
+) -> Result<AFWorkspaceWebhookRow, AppError> {
+  let row = sqlx::query_as::<_, AFWorkspaceWebhookRow>(
+    r#"
+      INSERT INTO af_workspace_webhook (id, workspace_id, url, secret, event_types, batch, created_by)
+      VALUES ($1, $2, $3, $4, $5, $6, $7)
+      RETURNING id, workspace_id, url, secret, event_types, batch, created_by, created_at
+    "#,
+  )
+  .bind(id)
+  .bind(workspace_id)
+  .bind(url)
+  .bind(secret)
+  .bind(event_types)
+  .bind(batch)
+  .bind(created_by)
+  .fetch_one(executor)
+  .await?;
+  Ok(row)
+}
+
+pub async fn select_workspace_webhooks<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+) -> Result<Vec<AFWorkspaceWebhookRow>, AppError> {
+  let rows = sqlx::query_as::<_, AFWorkspaceWebhookRow>(
+    r#"
+      SELECT id, workspace_id, url, secret, event_types, batch, created_by, created_at
+      FROM af_workspace_webhook
+      WHERE workspace_id = $1
+      ORDER BY created_at
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_all(executor)
+  .await?;
+  Ok(rows)
+}
+
+pub async fn select_workspace_webhook<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  id: &Uuid,
+) -> Result<Option<AFWorkspaceWebhookRow>, AppError> {
+  let row = sqlx::query_as::<_, AFWorkspaceWebhookRow>(
+    r#"
+      SELECT id, workspace_id, url, secret, event_types, batch, created_by, created_at
+      FROM af_workspace_webhook
+      WHERE id = $1
+    "#,
+  )
+  .bind(id)
+  .fetch_optional(executor)
+  .await?;
+  Ok(row)
+}
+
+/// Returns whether the workspace had a webhook with this id.
+pub async fn delete_workspace_webhook<'a, E: Executor<'a, Database = Postgres>>(
+  executor: E,
+  workspace_id: &Uuid,
+  id: &Uuid,
+) -> Result<bool, AppError> {
+  let result = sqlx::query(
+    r#"
+      DELETE FROM af_workspace_webhook
+      WHERE workspace_id = $1 AND id = $2
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(id)
+  .execute(executor)
+  .await?;
+  Ok(result.rows_affected() > 0)
+}
@@ -1,4 +1,4 @@
-use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 
@@ -48,4 +48,13 @@ impl GoTrueJWTClaims {
     let token_data = decode::<Self>(token, &DecodingKey::from_secret(secret), &VALIDATION)?;
     Ok(token_data.claims)
   }
+
+  /// Sign the claims the same way GoTrue signs its access tokens.
+  pub fn encode(&self, secret: &[u8]) -> Result<String, jsonwebtoken::errors::Error> {
+    encode(
+      &Header::new(Algorithm::HS256),
+      self,
+      &EncodingKey::from_secret(secret),
+    )
+  }
 }
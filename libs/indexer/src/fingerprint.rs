@@ -0,0 +1,91 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Number of consecutive words hashed together. Single words would make any two documents
+/// written in the same language look alike.
+const SHINGLE_SIZE: usize = 3;
+
+/// A 64 bits simhash of the text of a document: the more two documents have in common, the fewer
+/// bits their fingerprints differ by. The fingerprints are stored, so the hash must not change
+/// between releases, which rules out the hasher of the standard library.
+pub fn simhash(text: &str) -> Option<i64> {
+  let words = text
+    .unicode_words()
+    .map(|word| word.to_lowercase())
+    .collect::<Vec<_>>();
+  if words.is_empty() {
+    return None;
+  }
+
+  let mut weights = [0i32; 64];
+  let shingle_size = SHINGLE_SIZE.min(words.len());
+  for shingle in words.windows(shingle_size) {
+    let hash = fnv1a(shingle);
+    for (bit, weight) in weights.iter_mut().enumerate() {
+      if hash & (1 << bit) != 0 {
+        *weight += 1;
+      } else {
+        *weight -= 1;
+      }
+    }
+  }
+  let fingerprint = weights
+    .iter()
+    .enumerate()
+    .filter(|(_, weight)| **weight > 0)
+    .fold(0u64, |fingerprint, (bit, _)| fingerprint | (1 << bit));
+  Some(fingerprint as i64)
+}
+
+/// The share of the bits two fingerprints have in common, from 0.0 to 1.0.
+pub fn similarity(a: i64, b: i64) -> f32 {
+  1.0 - (a ^ b).count_ones() as f32 / 64.0
+}
+
+fn fnv1a(words: &[String]) -> u64 {
+  const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+  const PRIME: u64 = 0x100000001b3;
+  let mut hash = OFFSET_BASIS;
+  for (index, word) in words.iter().enumerate() {
+    if index > 0 {
+      hash ^= b' ' as u64;
+      hash = hash.wrapping_mul(PRIME);
+    }
+    for byte in word.as_bytes() {
+      hash ^= *byte as u64;
+      hash = hash.wrapping_mul(PRIME);
+    }
+  }
+  hash
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const TEXT: &str = "Meeting notes for the weekly sync. We discussed the roadmap for the next \
+    quarter, the hiring plan for the design team and the migration of the billing service to the \
+    new infrastructure. Action items are listed at the end of the page.";
+
+  #[test]
+  fn near_duplicates_are_more_similar_than_unrelated_texts_test() {
+    let original = simhash(TEXT).unwrap();
+    assert_eq!(simhash(&TEXT.to_uppercase()), Some(original));
+
+    let edited = simhash(&TEXT.replace("design team", "support team")).unwrap();
+    let unrelated = simhash(
+      "Recipe: mix the flour with two eggs and a glass of milk, let the batter rest for an hour \
+      and cook thin pancakes in a hot pan with a little butter.",
+    )
+    .unwrap();
+    assert!(similarity(original, edited) >= 0.85);
+    assert!(similarity(original, edited) > similarity(original, unrelated));
+    assert_eq!(similarity(original, original), 1.0);
+  }
+
+  #[test]
+  fn text_without_words_has_no_fingerprint_test() {
+    assert_eq!(simhash(""), None);
+    assert_eq!(simhash("  \n  ... "), None);
+    assert!(simhash("hello").is_some());
+  }
+}
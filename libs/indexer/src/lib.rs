@@ -1,6 +1,7 @@
 pub mod collab_indexer;
 pub mod entity;
 pub mod error;
+pub mod fingerprint;
 pub mod metrics;
 pub mod queue;
 pub mod scheduler;
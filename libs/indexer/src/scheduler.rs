@@ -1,6 +1,7 @@
 use crate::collab_indexer::{Indexer, IndexerProvider};
 use crate::entity::EmbeddingRecord;
 use crate::error::IndexerError;
+use crate::fingerprint::simhash;
 use crate::metrics::EmbeddingMetrics;
 use crate::queue::add_background_embed_task;
 use crate::thread_pool::{ThreadPoolNoAbort, ThreadPoolNoAbortBuilder};
@@ -9,10 +10,12 @@ use crate::vector::open_ai;
 use app_error::AppError;
 use appflowy_ai_client::dto::{EmbeddingRequest, OpenAIEmbeddingResponse};
 use collab::preclude::Collab;
-use collab_document::document::DocumentBody;
 use collab_entity::CollabType;
 use database::collab::CollabStorage;
-use database::index::{update_collab_indexed_at, upsert_collab_embeddings};
+use database::index::{
+  delete_collab_fingerprint, update_collab_indexed_at, upsert_collab_embeddings,
+  upsert_collab_fingerprint,
+};
 use database::workspace::select_workspace_settings;
 use database_entity::dto::AFCollabEmbeddedChunk;
 use infra::env_util::get_env_var;
@@ -33,6 +36,7 @@ use tokio::sync::RwLock as TokioRwLock;
 use tokio::time::timeout;
 use tracing::{debug, error, info, instrument, trace, warn};
 use uuid::Uuid;
+use workspace_template::collab_registry::collab_type_spec;
 
 pub struct IndexerScheduler {
   pub(crate) indexer_provider: Arc<IndexerProvider>,
@@ -254,27 +258,16 @@ impl IndexerScheduler {
       return Ok(());
     }
 
-    match collab_type {
-      CollabType::Document => {
-        let txn = collab.transact();
-        let text = DocumentBody::from_collab(collab)
-          .and_then(|body| body.to_plain_text(txn, false, true).ok());
-
-        if let Some(text) = text {
-          if !text.is_empty() {
-            let pending = UnindexedCollabTask::new(
-              Uuid::parse_str(workspace_id)?,
-              object_id.to_string(),
-              collab_type.clone(),
-              UnindexedData::Text(text),
-            );
-            self.embed_immediately(pending)?;
-          }
-        }
-      },
-      _ => {
-        // TODO(nathan): support other collab types
-      },
+    if let Some(text) = collab_type_spec(collab_type).extract_text(collab) {
+      if !text.is_empty() {
+        let pending = UnindexedCollabTask::new(
+          Uuid::parse_str(workspace_id)?,
+          object_id.to_string(),
+          collab_type.clone(),
+          UnindexedData::Text(text),
+        );
+        self.embed_immediately(pending)?;
+      }
     }
 
     Ok(())
@@ -461,6 +454,9 @@ pub(crate) async fn batch_insert_records(
     )
     .await?;
 
+    if record.collab_type == CollabType::Document {
+      write_document_fingerprint(txn.deref_mut(), &record).await?;
+    }
     upsert_collab_embeddings(
       &mut txn,
       &record.workspace_id,
@@ -479,6 +475,35 @@ pub(crate) async fn batch_insert_records(
   Ok(())
 }
 
+/// Stores the fingerprint of the text the document was indexed with, which is the text of its
+/// chunks, so that the near duplicates of the document can be found.
+async fn write_document_fingerprint(
+  conn: &mut sqlx::PgConnection,
+  record: &EmbeddingRecord,
+) -> Result<(), AppError> {
+  let text = record
+    .contents
+    .iter()
+    .map(|chunk| chunk.content.as_str())
+    .collect::<Vec<_>>()
+    .join(" ");
+  match simhash(&text) {
+    Some(simhash) => {
+      let text_len = text.chars().count().min(i32::MAX as usize) as i32;
+      upsert_collab_fingerprint(
+        conn,
+        &record.workspace_id,
+        &record.object_id,
+        simhash,
+        text_len,
+      )
+      .await?
+    },
+    None => delete_collab_fingerprint(conn, &record.object_id).await?,
+  }
+  Ok(())
+}
+
 /// This function must be called within the rayon thread pool.
 fn process_collab(
   embedder: &Embedder,
@@ -2,6 +2,9 @@ pub mod env_util;
 
 #[cfg(feature = "file_util")]
 pub mod file_util;
+#[cfg(feature = "net_util")]
+pub mod net_util;
 #[cfg(feature = "request_util")]
 pub mod reqwest;
+pub mod text_offset;
 pub mod validate;
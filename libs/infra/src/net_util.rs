@@ -0,0 +1,105 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use anyhow::bail;
+
+/// Resolves the host and returns the first of its addresses, unless any of them is not
+/// [is_public_ip]. Connecting to the returned address rather than to the host keeps it from being
+/// rebound to a private address after the check.
+pub async fn resolve_public_addr(host: &str, port: u16) -> Result<SocketAddr, anyhow::Error> {
+  let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+  if addrs.is_empty() {
+    bail!("{} can not be resolved", host);
+  }
+  if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+    bail!("{} resolves to a non public address {}", host, addr.ip());
+  }
+  Ok(addrs[0])
+}
+
+/// Returns false for the addresses which are not reachable on the public internet, such as
+/// private, loopback, link-local and shared address ranges.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+  match ip {
+    IpAddr::V4(ip) => is_public_ipv4(ip),
+    IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+      Some(ip) => is_public_ipv4(ip),
+      None => is_public_ipv6(ip),
+    },
+  }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+  let [a, b, c, _] = ip.octets();
+  !(ip.is_unspecified()
+    || ip.is_private()
+    || ip.is_loopback()
+    || ip.is_link_local()
+    || ip.is_broadcast()
+    || ip.is_documentation()
+    || ip.is_multicast()
+    || a == 0
+    // shared address space (carrier-grade NAT)
+    || (a == 100 && (b & 0xc0) == 64)
+    // IETF protocol assignments
+    || (a == 192 && b == 0 && c == 0)
+    // benchmarking
+    || (a == 198 && (b & 0xfe) == 18)
+    // reserved
+    || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+  let segments = ip.segments();
+  !(ip.is_unspecified()
+    || ip.is_loopback()
+    || ip.is_multicast()
+    // unique local
+    || (segments[0] & 0xfe00) == 0xfc00
+    // link-local
+    || (segments[0] & 0xffc0) == 0xfe80
+    // documentation
+    || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+    // NAT64, which may translate to a private IPv4 address
+    || (segments[0] == 0x0064 && segments[1] == 0xff9b)
+    // IPv4-compatible, deprecated
+    || segments[..6].iter().all(|segment| *segment == 0))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn non_public_ip_test() {
+    for ip in [
+      "127.0.0.1",
+      "10.0.0.1",
+      "172.16.0.1",
+      "192.168.1.1",
+      "169.254.169.254",
+      "100.64.0.1",
+      "0.0.0.0",
+      "255.255.255.255",
+      "::1",
+      "::",
+      "fc00::1",
+      "fe80::1",
+      "::ffff:127.0.0.1",
+      "::ffff:10.0.0.1",
+      "64:ff9b::a00:1",
+    ] {
+      assert!(
+        !is_public_ip(ip.parse().unwrap()),
+        "{} should be denied",
+        ip
+      );
+    }
+    for ip in ["93.184.216.34", "8.8.8.8", "2606:4700:4700::1111"] {
+      assert!(
+        is_public_ip(ip.parse().unwrap()),
+        "{} should be allowed",
+        ip
+      );
+    }
+  }
+}
@@ -0,0 +1,247 @@
+//! Offsets into a text, in the unit they are counted in.
+//!
+//! Rust strings are indexed by bytes, the text of a collab is indexed by UTF-16 code units and
+//! the clients usually count characters. Every offset the server uses to mutate a text is wrapped
+//! in one of [ByteOffset], [Utf16Offset] or [CharOffset], and converted with the functions of this
+//! module against the text it points into, so that the units can't be mixed up.
+
+use std::fmt::{Display, Formatter};
+
+/// An offset in bytes of the UTF-8 encoding of a text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ByteOffset(pub usize);
+
+/// An offset in UTF-16 code units, the unit used by the text of a collab.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Utf16Offset(pub u32);
+
+/// An offset in unicode scalar values, which is what [str::chars] iterates over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct CharOffset(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OffsetError {
+  /// The offset is past the end of the text.
+  OutOfBounds,
+  /// The offset points inside the encoding of a character.
+  NotCharBoundary,
+}
+
+impl Display for OffsetError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      OffsetError::OutOfBounds => write!(f, "offset is out of the bounds of the text"),
+      OffsetError::NotCharBoundary => write!(f, "offset is not on a character boundary"),
+    }
+  }
+}
+
+impl std::error::Error for OffsetError {}
+
+impl ByteOffset {
+  pub fn to_utf16(self, text: &str) -> Result<Utf16Offset, OffsetError> {
+    let prefix = byte_prefix(text, self)?;
+    Ok(Utf16Offset(prefix.encode_utf16().count() as u32))
+  }
+
+  pub fn to_char(self, text: &str) -> Result<CharOffset, OffsetError> {
+    let prefix = byte_prefix(text, self)?;
+    Ok(CharOffset(prefix.chars().count()))
+  }
+}
+
+impl Utf16Offset {
+  pub fn to_byte(self, text: &str) -> Result<ByteOffset, OffsetError> {
+    let target = self.0 as usize;
+    let mut utf16 = 0;
+    for (index, c) in text.char_indices() {
+      if utf16 == target {
+        return Ok(ByteOffset(index));
+      }
+      utf16 += c.len_utf16();
+      if utf16 > target {
+        // The offset falls between the two halves of a surrogate pair.
+        return Err(OffsetError::NotCharBoundary);
+      }
+    }
+    if utf16 == target {
+      Ok(ByteOffset(text.len()))
+    } else {
+      Err(OffsetError::OutOfBounds)
+    }
+  }
+
+  pub fn to_char(self, text: &str) -> Result<CharOffset, OffsetError> {
+    let byte = self.to_byte(text)?;
+    Ok(CharOffset(text[..byte.0].chars().count()))
+  }
+}
+
+impl CharOffset {
+  pub fn to_byte(self, text: &str) -> Result<ByteOffset, OffsetError> {
+    match text.char_indices().nth(self.0) {
+      Some((index, _)) => Ok(ByteOffset(index)),
+      None if text.chars().count() == self.0 => Ok(ByteOffset(text.len())),
+      None => Err(OffsetError::OutOfBounds),
+    }
+  }
+
+  pub fn to_utf16(self, text: &str) -> Result<Utf16Offset, OffsetError> {
+    self.to_byte(text)?.to_utf16(text)
+  }
+}
+
+/// The length of the text in UTF-16 code units.
+pub fn utf16_len(text: &str) -> Utf16Offset {
+  Utf16Offset(text.encode_utf16().count() as u32)
+}
+
+/// Converts the range of `len` bytes starting at `start` into the UTF-16 offset and length the
+/// text of a collab expects for a deletion.
+pub fn byte_range_to_utf16(
+  text: &str,
+  start: ByteOffset,
+  len: usize,
+) -> Result<(Utf16Offset, u32), OffsetError> {
+  let end = ByteOffset(start.0.checked_add(len).ok_or(OffsetError::OutOfBounds)?);
+  let start = start.to_utf16(text)?;
+  let end = end.to_utf16(text)?;
+  Ok((start, end.0 - start.0))
+}
+
+/// Truncates the text to at most `max_len` characters. Returns true if it was truncated.
+pub fn truncate_chars(text: &mut String, max_len: CharOffset) -> bool {
+  match max_len.to_byte(text) {
+    Ok(ByteOffset(index)) if index < text.len() => {
+      text.truncate(index);
+      true
+    },
+    _ => false,
+  }
+}
+
+fn byte_prefix(text: &str, offset: ByteOffset) -> Result<&str, OffsetError> {
+  if offset.0 > text.len() {
+    return Err(OffsetError::OutOfBounds);
+  }
+  text.get(..offset.0).ok_or(OffsetError::NotCharBoundary)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use proptest::prelude::*;
+
+  /// Characters which are encoded differently in UTF-8 and UTF-16: ASCII, two and three bytes
+  /// characters, combining marks, emoji outside the basic plane and multi-codepoint emoji.
+  const PIECES: &[&str] = &[
+    "a",
+    " ",
+    "é",
+    "e\u{301}",
+    "中",
+    "😀",
+    "👍🏽",
+    "👨‍👩‍👧",
+    "🇫🇷",
+    "\u{10348}",
+    "\n",
+  ];
+
+  fn tricky_text() -> impl Strategy<Value = String> {
+    prop::collection::vec(prop::sample::select(PIECES), 0..16).prop_map(|pieces| pieces.concat())
+  }
+
+  #[test]
+  fn surrogate_pair_halves_are_rejected_test() {
+    let text = "a😀b";
+    assert_eq!(Utf16Offset(1).to_byte(text), Ok(ByteOffset(1)));
+    assert_eq!(
+      Utf16Offset(2).to_byte(text),
+      Err(OffsetError::NotCharBoundary)
+    );
+    assert_eq!(Utf16Offset(3).to_byte(text), Ok(ByteOffset(5)));
+    assert_eq!(Utf16Offset(4).to_byte(text), Ok(ByteOffset(6)));
+    assert_eq!(Utf16Offset(5).to_byte(text), Err(OffsetError::OutOfBounds));
+    assert_eq!(
+      ByteOffset(2).to_utf16(text),
+      Err(OffsetError::NotCharBoundary)
+    );
+  }
+
+  #[test]
+  fn combining_marks_count_as_characters_test() {
+    let text = "e\u{301}x";
+    assert_eq!(CharOffset(1).to_byte(text), Ok(ByteOffset(1)));
+    assert_eq!(CharOffset(2).to_utf16(text), Ok(Utf16Offset(2)));
+    let mut truncated = text.to_string();
+    assert!(truncate_chars(&mut truncated, CharOffset(2)));
+    assert_eq!(truncated, "e\u{301}");
+    assert!(!truncate_chars(&mut truncated, CharOffset(2)));
+  }
+
+  proptest! {
+    #[test]
+    fn every_char_boundary_round_trips_test(text in tricky_text()) {
+      for (char_index, (byte_index, _)) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+        .enumerate()
+      {
+        let byte = ByteOffset(byte_index);
+        let utf16 = byte.to_utf16(&text).unwrap();
+        let char_offset = byte.to_char(&text).unwrap();
+        prop_assert_eq!(char_offset, CharOffset(char_index));
+        prop_assert_eq!(utf16.to_byte(&text), Ok(byte));
+        prop_assert_eq!(utf16.to_char(&text), Ok(char_offset));
+        prop_assert_eq!(char_offset.to_byte(&text), Ok(byte));
+        prop_assert_eq!(char_offset.to_utf16(&text), Ok(utf16));
+      }
+      prop_assert_eq!(
+        ByteOffset(text.len()).to_utf16(&text),
+        Ok(utf16_len(&text))
+      );
+    }
+
+    #[test]
+    fn offsets_inside_characters_are_rejected_test(text in tricky_text()) {
+      for byte_index in 0..=text.len() + 1 {
+        let result = ByteOffset(byte_index).to_utf16(&text);
+        if text.is_char_boundary(byte_index) {
+          prop_assert!(result.is_ok());
+        } else {
+          prop_assert!(result.is_err());
+        }
+      }
+      let utf16: Vec<u16> = text.encode_utf16().collect();
+      for utf16_index in 0..=utf16.len() {
+        let result = Utf16Offset(utf16_index as u32).to_byte(&text);
+        let splits_pair = utf16
+          .get(utf16_index)
+          .map(|unit| (0xDC00..0xE000).contains(unit))
+          .unwrap_or(false);
+        prop_assert_eq!(result.is_err(), splits_pair);
+      }
+    }
+
+    #[test]
+    fn utf16_splice_lands_on_intended_characters_test(
+      prefix in tricky_text(),
+      removed in tricky_text(),
+      suffix in tricky_text(),
+      inserted in tricky_text(),
+    ) {
+      let text = format!("{}{}{}", prefix, removed, suffix);
+      let (at, len) =
+        byte_range_to_utf16(&text, ByteOffset(prefix.len()), removed.len()).unwrap();
+      prop_assert_eq!(len, utf16_len(&removed).0);
+
+      // Apply the edit in UTF-16 code units, the way the text of a collab does.
+      let mut units: Vec<u16> = text.encode_utf16().collect();
+      let start = at.0 as usize;
+      units.splice(start..start + len as usize, inserted.encode_utf16());
+      let edited = String::from_utf16(&units).unwrap();
+      prop_assert_eq!(edited, format!("{}{}{}", prefix, inserted, suffix));
+    }
+  }
+}
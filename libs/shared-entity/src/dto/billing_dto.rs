@@ -1,6 +1,8 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use database_entity::timestamp;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
@@ -104,7 +106,9 @@ pub struct WorkspaceSubscriptionStatus {
   pub recurring_interval: RecurringInterval,
   pub subscription_status: SubscriptionStatus,
   pub subscription_quantity: u64,
+  #[serde(default, with = "timestamp::option_epoch_seconds")]
   pub cancel_at: Option<i64>,
+  #[serde(with = "timestamp::epoch_seconds")]
   pub current_period_end: i64,
 }
 
@@ -228,3 +232,51 @@ impl TryFrom<&str> for LicensedProductType {
 pub struct LicenseProductSubscriptionLinkQuery {
   pub product_type: LicensedProductType,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceUsageDailyQuery {
+  /// First day (UTC), included.
+  pub from: NaiveDate,
+  /// Last day (UTC), included.
+  pub to: NaiveDate,
+  /// Only the usage of this workspace, the usage of every workspace when unset.
+  pub workspace_id: Option<Uuid>,
+}
+
+/// Usage of a workspace on a day (UTC), as rolled up for billing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceUsageDaily {
+  pub workspace_id: Uuid,
+  pub workspace_name: Option<String>,
+  pub day: NaiveDate,
+  /// Bytes of the blobs stored by the workspace.
+  pub storage_bytes: i64,
+  pub ai_search_requests: i64,
+  /// Tokens consumed by the AI searches and by the indexing of the documents.
+  pub ai_tokens: i64,
+  /// Members with a session seen during the day.
+  pub active_members: i64,
+  /// Bytes received from the realtime clients.
+  pub bandwidth_in: i64,
+  /// Bytes sent to the realtime clients.
+  pub bandwidth_out: i64,
+  /// When the day was last rolled up. A day is rolled up again when late data arrives.
+  pub rolled_up_at: DateTime<Utc>,
+}
+
+/// The usage counter of a workspace, before and after it was recomputed from its blobs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecomputedWorkspaceUsage {
+  pub workspace_id: Uuid,
+  /// Bytes held by the counter before it was recomputed.
+  pub previous_bytes: i64,
+  /// Bytes of the blobs and of their archived versions.
+  pub usage_bytes: i64,
+}
+
+/// Days to roll up again, for instance after a failed rollup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollupWorkspaceUsageParams {
+  pub from: NaiveDate,
+  pub to: NaiveDate,
+}
@@ -26,6 +26,56 @@ pub struct UpdateChatParams {
   pub rag_ids: Option<Vec<String>>,
 }
 
+/// Who a chat is visible to, besides its owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(i16)]
+pub enum ChatShareScope {
+  Private = 0,
+  /// Every member of the workspace.
+  Workspace = 1,
+  /// The members listed when sharing the chat.
+  Members = 2,
+}
+
+impl From<i16> for ChatShareScope {
+  fn from(value: i16) -> Self {
+    match value {
+      1 => ChatShareScope::Workspace,
+      2 => ChatShareScope::Members,
+      _ => ChatShareScope::Private,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareChatParams {
+  pub scope: ChatShareScope,
+  /// Emails of the workspace members the chat is shared with, when the scope is
+  /// [ChatShareScope::Members].
+  #[serde(default)]
+  pub member_emails: Vec<String>,
+  /// Whether the members the chat is shared with can ask questions. Otherwise they can only read
+  /// the conversation.
+  #[serde(default)]
+  pub collaborative: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedChat {
+  pub chat_id: String,
+  pub name: String,
+  pub owner_uid: Option<i64>,
+  pub owner_name: Option<String>,
+  pub scope: ChatShareScope,
+  pub collaborative: bool,
+  pub shared_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepeatedSharedChat {
+  pub items: Vec<SharedChat>,
+}
+
 #[derive(Debug, Clone, Validate, Serialize, Deserialize)]
 pub struct CreateChatMessageParams {
   #[validate(custom(function = "validate_not_empty_str"))]
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompactCollabQuery {
+  /// Leave the collab alone when clients are connected to it. By default they're made to load
+  /// the compacted collab again.
+  pub skip_connected: Option<bool>,
+}
+
+/// The outcome of the compaction of a collab, which replaces its stored state by a copy holding
+/// only its current content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollabCompactionResult {
+  pub compacted: bool,
+  /// Why the collab was left untouched: `connected`, `lease_held`, `no_gain` or `edited`.
+  pub skipped_reason: Option<String>,
+  /// The snapshot holding the collab as it was before the compaction.
+  pub snapshot_id: Option<i64>,
+  /// Bytes of the doc state before and after the compaction.
+  pub doc_state_len: usize,
+  pub compacted_len: usize,
+}
@@ -0,0 +1,39 @@
+use chrono::{DateTime, Utc};
+use collab_entity::CollabType;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// The snapshot a collab was served from because its stored state could not be decoded.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RecoveredCollabSnapshot {
+  pub snapshot_id: i64,
+  /// When the snapshot was taken. The edits made after this time are not part of the content.
+  pub created_at: DateTime<Utc>,
+}
+
+/// A collab whose stored state could not be decoded. Until the report is repaired, the collab is
+/// served from `recovered_from` and can't be edited.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollabVerificationReport {
+  pub report_id: i64,
+  pub workspace_id: Uuid,
+  pub object_id: String,
+  pub collab_type: CollabType,
+  /// Where the corruption was first found, `http` or `realtime`.
+  pub detected_on: String,
+  pub error: String,
+  /// `None` when none of the snapshots of the collab could be decoded either.
+  pub recovered_from: Option<RecoveredCollabSnapshot>,
+  pub detection_count: i32,
+  pub created_at: DateTime<Utc>,
+  pub last_detected_at: DateTime<Utc>,
+  pub repaired_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListCollabVerificationReportsQuery {
+  /// Only the reports waiting for repair are listed by default.
+  pub include_repaired: Option<bool>,
+  pub offset: Option<i64>,
+  pub limit: Option<i64>,
+}
@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::dto::page_dto::Cursor;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceExportTask {
+  pub task_id: Uuid,
+  pub workspace_id: Uuid,
+  /// 0: pending, 1: running, 2: completed, 3: failed, 4: expired
+  pub status: i16,
+  /// 0: queued, 1: exporting collabs, 2: exporting blobs, 3: uploading, 4: done
+  pub stage: i16,
+  /// Size in bytes of the archive produced so far
+  pub bytes_written: i64,
+  /// Only available once the export is completed and has not expired yet
+  pub download_url: Option<String>,
+  pub error: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+  pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserNotification {
+  pub notification_id: Uuid,
+  pub workspace_id: Option<Uuid>,
+  pub kind: String,
+  pub payload: serde_json::Value,
+  pub is_read: bool,
+  pub created_at: DateTime<Utc>,
+}
+
+/// The notifications returned to the clients older than [crate::dto::page_dto::PAGE_API_VERSION],
+/// which get a [crate::dto::page_dto::Page] otherwise.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserNotifications {
+  pub notifications: Vec<UserNotification>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListUserNotificationsQuery {
+  #[serde(default)]
+  pub only_unread: bool,
+  /// The `next_cursor` of the previous page, `None` for the first page.
+  pub cursor: Option<Cursor>,
+  pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarkNotificationsReadParams {
+  pub notification_ids: Vec<Uuid>,
+}
+
+/// Notification preference of a user for an object.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationSubscriptionLevel {
+  /// Notified about all the activity of the object, even without participating in it.
+  Watch,
+  /// Notified about the activity of the objects the user participates in, or is mentioned in.
+  #[default]
+  Default,
+  /// Not notified, except for mentions unless [NotificationSettings::mute_mentions] is set.
+  Mute,
+}
+
+impl NotificationSubscriptionLevel {
+  pub fn value(&self) -> i16 {
+    match self {
+      NotificationSubscriptionLevel::Watch => 1,
+      NotificationSubscriptionLevel::Default => 0,
+      NotificationSubscriptionLevel::Mute => -1,
+    }
+  }
+}
+
+impl From<i16> for NotificationSubscriptionLevel {
+  fn from(value: i16) -> Self {
+    match value {
+      1 => NotificationSubscriptionLevel::Watch,
+      -1 => NotificationSubscriptionLevel::Mute,
+      _ => NotificationSubscriptionLevel::Default,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationSubscription {
+  pub workspace_id: Uuid,
+  pub object_id: Uuid,
+  pub level: NotificationSubscriptionLevel,
+  pub updated_at: DateTime<Utc>,
+}
+
+/// Only the objects with a level other than [NotificationSubscriptionLevel::Default] are listed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationSubscriptions {
+  pub subscriptions: Vec<NotificationSubscription>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListNotificationSubscriptionsQuery {
+  pub workspace_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetNotificationSubscriptionParams {
+  pub workspace_id: Uuid,
+  pub level: NotificationSubscriptionLevel,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationSettings {
+  /// By default, being mentioned in a muted object still notifies the user.
+  #[serde(default)]
+  pub mute_mentions: bool,
+}
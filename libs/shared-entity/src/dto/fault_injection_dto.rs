@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+/// Response header listing the faults injected while the request was handled, for example
+/// `latency=120ms;s3=40ms;error=503`.
+pub const X_FAULT_INJECTED: &str = "x-fault-injected";
+
+/// The faults injected by a staging server, set with `PUT /api/admin/fault-injection`. Nothing is
+/// injected by default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FaultInjectionParams {
+  /// The first rule matching a request applies, the others are ignored.
+  #[serde(default)]
+  pub routes: Vec<RouteFault>,
+  /// Waited before each Redis operation of the collab cache.
+  #[serde(default)]
+  pub redis_delay: Option<LatencyDistribution>,
+  /// Waited before each S3 request.
+  #[serde(default)]
+  pub s3_delay: Option<LatencyDistribution>,
+  /// Waited before each Postgres transaction of the collab cache, and before the connections
+  /// acquired to measure the load of Postgres.
+  #[serde(default)]
+  pub postgres_delay: Option<LatencyDistribution>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteFault {
+  /// Matches the requests whose path starts with it, for example `/api/workspace`.
+  pub path_prefix: String,
+  /// Matches any method when not set.
+  #[serde(default)]
+  pub method: Option<String>,
+  /// Waited before the request is handled.
+  #[serde(default)]
+  pub latency: Option<LatencyDistribution>,
+  /// Probability, between 0 and 1, that the request fails with `error_status` instead of being
+  /// handled.
+  #[serde(default)]
+  pub error_rate: f64,
+  #[serde(default = "default_error_status")]
+  pub error_status: u16,
+}
+
+fn default_error_status() -> u16 {
+  503
+}
+
+/// How long an injected delay lasts, in milliseconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LatencyDistribution {
+  Fixed {
+    ms: u64,
+  },
+  Uniform {
+    min_ms: u64,
+    max_ms: u64,
+  },
+  /// Samples below zero are clamped to zero.
+  Normal {
+    mean_ms: f64,
+    std_dev_ms: f64,
+  },
+}
@@ -0,0 +1,42 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The value of a feature flag. A flag only accepts the kind of value it was defined with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FeatureFlagValue {
+  /// Enabled or disabled for every workspace.
+  Bool { enabled: bool },
+  /// Enabled for a stable subset of the workspaces. Each workspace always falls in the same
+  /// bucket, so raising the percentage only adds workspaces.
+  Percentage { percentage: u8 },
+  /// Enabled for the listed workspaces only.
+  Allowlist { workspace_ids: Vec<String> },
+}
+
+impl FeatureFlagValue {
+  pub fn is_same_kind(&self, other: &FeatureFlagValue) -> bool {
+    std::mem::discriminant(self) == std::mem::discriminant(other)
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeatureFlag {
+  pub name: String,
+  pub description: String,
+  /// The value used until an admin sets one.
+  pub default_value: FeatureFlagValue,
+  pub value: FeatureFlagValue,
+  /// When the value was last set, `None` when the default value applies.
+  pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepeatedFeatureFlag {
+  pub items: Vec<FeatureFlag>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateFeatureFlagParams {
+  pub value: FeatureFlagValue,
+}
@@ -1,6 +1,32 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PutFileResponse {
   pub file_id: String,
 }
+
+/// An image that was processed by the server and stored as a blob of the workspace.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadImageResponse {
+  pub file_id: String,
+  pub url: String,
+}
+
+/// A previous revision of a blob, archived when its file id was overwritten.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlobVersion {
+  pub version: i64,
+  pub file_type: String,
+  pub file_size: i64,
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RepeatedBlobVersion(pub Vec<BlobVersion>);
+
+/// File ids of blobs stored under the same parent dir.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobFileIds {
+  pub file_ids: Vec<String>,
+}
@@ -1,3 +1,4 @@
+use database_entity::timestamp;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
@@ -7,6 +8,7 @@ pub struct SnapshotMeta {
   pub snapshot: Vec<u8>,
   /// Specifies the version of the snapshot
   pub snapshot_version: i32,
+  #[serde(with = "timestamp::epoch_seconds")]
   pub created_at: i64,
 }
 
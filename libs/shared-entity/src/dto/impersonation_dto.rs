@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateImpersonationParams {
+  /// The user to impersonate.
+  pub uid: i64,
+  /// Why support needs to access the account. It's shown to the user.
+  pub reason: String,
+  /// How long the access token is valid for. Defaults to 15 minutes, at most one hour.
+  #[serde(default)]
+  pub ttl_secs: Option<i64>,
+}
+
+/// The access token to send the requests with to act as the user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImpersonationToken {
+  pub impersonation_id: Uuid,
+  pub access_token: String,
+  pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrantImpersonationWriteParams {
+  /// Why support needs to change something in the account. It's shown to the user.
+  pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Impersonation {
+  pub impersonation_id: Uuid,
+  pub admin_uid: i64,
+  pub uid: i64,
+  pub reason: String,
+  /// Set once the writes are allowed, the impersonation is read-only until then.
+  pub write_reason: Option<String>,
+  pub write_granted_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+  pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Impersonations {
+  pub impersonations: Vec<Impersonation>,
+}
+
+/// A request made under an impersonation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImpersonationRequest {
+  pub method: String,
+  pub path: String,
+  pub status: u16,
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImpersonationRequests {
+  pub requests: Vec<ImpersonationRequest>,
+}
@@ -1,3 +1,5 @@
+use database_entity::dto::ImportSource;
+use database_entity::timestamp;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -10,6 +12,91 @@ pub struct UserImportTask {
 pub struct ImportTaskDetail {
   pub task_id: String,
   pub file_size: u64,
+  #[serde(with = "timestamp::epoch_seconds")]
   pub created_at: i64,
   pub status: i16,
+  /// Whether the [ImportReport] of the task can be downloaded
+  #[serde(default)]
+  pub has_report: bool,
+}
+
+/// Version of the [ImportReport] schema, bumped on every change which isn't backward compatible
+/// so that tools reading the reports can tell them apart.
+pub const IMPORT_REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// What happened to every item of an import, generated once the import ran and stored with its
+/// task.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImportReport {
+  pub schema_version: u32,
+  pub task_id: String,
+  pub workspace_id: String,
+  pub source: ImportSource,
+  #[serde(with = "timestamp::epoch_seconds")]
+  pub created_at: i64,
+  pub items: Vec<ImportReportItem>,
+  pub attachments: ImportAttachmentStats,
+  pub unresolved_links: Vec<UnresolvedImportLink>,
+  /// The "Import report" page created in the destination workspace, `None` when it couldn't be
+  /// written.
+  pub report_view_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImportReportItem {
+  pub id: String,
+  /// The name of the view of the item, `None` for the items without a view such as the row
+  /// documents.
+  pub name: Option<String>,
+  pub kind: ImportReportItemKind,
+  #[serde(flatten)]
+  pub outcome: ImportItemOutcome,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportReportItemKind {
+  Page,
+  Database,
+  RowDocument,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum ImportItemOutcome {
+  Imported,
+  /// The export contained another item with the same id, which was imported instead.
+  SkippedDuplicate,
+  Failed {
+    reason: String,
+  },
+  /// The item was imported, but some of its content, such as an attachment, wasn't.
+  Partial {
+    warnings: Vec<String>,
+  },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ImportAttachmentStats {
+  pub total: usize,
+  pub uploaded: usize,
+  pub uploaded_bytes: u64,
+  /// The attachments referenced several times by the same item, uploaded once.
+  pub duplicates: usize,
+  pub failed: Vec<FailedImportAttachment>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FailedImportAttachment {
+  /// The item the attachment belongs to
+  pub item_id: String,
+  pub file_path: String,
+  pub reason: String,
+}
+
+/// A link of an item to a page or file which isn't part of the export. The link is kept as text.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UnresolvedImportLink {
+  pub item_id: String,
+  pub target: String,
 }
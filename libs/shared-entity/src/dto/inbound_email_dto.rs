@@ -0,0 +1,28 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetInboundEmailParams {
+  /// The page the emails are appended to. It must be a document.
+  pub view_id: Uuid,
+}
+
+/// The address of a workspace which appends the emails it receives to a page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InboundEmailAddress {
+  pub address: String,
+  pub view_id: Uuid,
+  pub created_at: DateTime<Utc>,
+  /// Set once the address was rotated. The previous addresses don't accept emails anymore.
+  pub rotated_at: Option<DateTime<Utc>>,
+}
+
+/// What was appended to the page for an email received by an inbound address.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InboundEmailReceipt {
+  pub appended_blocks: usize,
+  pub uploaded_attachments: usize,
+  /// The parts of the email which were not appended, the same notes are added to the page.
+  pub notes: Vec<String>,
+}
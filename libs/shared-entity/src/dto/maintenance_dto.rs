@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Destructive maintenance jobs that can be run on a workspace
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceJob {
+  /// Remove objects in the workspace bucket directory that have no blob metadata
+  OrphanBlobGc,
+  /// Remove archived blob versions that fall outside the retention policy
+  BlobVersionRetention,
+}
+
+impl MaintenanceJob {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      MaintenanceJob::OrphanBlobGc => "orphan_blob_gc",
+      MaintenanceJob::BlobVersionRetention => "blob_version_retention",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunMaintenanceJobQuery {
+  /// Only compute what would be deleted. The report is kept so that it can be executed later
+  /// with the execute-last-dry-run endpoint.
+  #[serde(default)]
+  pub dry_run: bool,
+}
+
+/// What a maintenance job selected for deletion and, unless it was a dry run, what it deleted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceJobReport {
+  /// Only set for dry runs, which are persisted
+  pub report_id: Option<Uuid>,
+  pub job: MaintenanceJob,
+  pub workspace_id: Uuid,
+  pub dry_run: bool,
+  /// Hash of the selected item ids. Executing a dry run fails if the selection changed since.
+  pub selection_hash: String,
+  pub selected_count: usize,
+  pub estimated_bytes: i64,
+  /// A bounded sample of the selected item ids
+  pub sample_ids: Vec<String>,
+  /// Number of items deleted. `None` for dry runs.
+  pub deleted_count: Option<usize>,
+  pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,61 @@
+use chrono::{DateTime, Utc};
+use database_entity::dto::AFRole;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeWorkspaceParams {
+  /// The workspace whose content is moved into the workspace of the request. It's archived once
+  /// the merge is completed.
+  pub source_workspace_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceMergeTask {
+  pub task_id: Uuid,
+  pub target_workspace_id: Uuid,
+  pub source_workspace_id: Uuid,
+  /// 0: pending, 1: running, 2: completed, 3: failed
+  pub status: i16,
+  /// 0: queued, 1: moving collabs, 2: merging folders, 3: copying blobs, 4: archiving, 5: done
+  pub stage: i16,
+  pub moved_collabs: i64,
+  pub copied_blobs: i64,
+  pub report: WorkspaceMergeReport,
+  pub error: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+/// The conflicts met while merging. The members are resolved when the merge is requested, the
+/// page names once the folders are merged.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorkspaceMergeReport {
+  /// The space of the target workspace the pages of the source workspace were moved under.
+  #[serde(default)]
+  pub space_view_id: Option<String>,
+  #[serde(default)]
+  pub added_members: Vec<MergedMember>,
+  /// The members who have a lower role in the target workspace than in the source workspace.
+  #[serde(default)]
+  pub downgraded_members: Vec<MergedMember>,
+  /// The pages moved from the source workspace which have the same name as a page of the target
+  /// workspace.
+  #[serde(default)]
+  pub duplicate_page_names: Vec<DuplicatePageName>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergedMember {
+  pub email: String,
+  /// The role of the member in the source workspace.
+  pub source_role: AFRole,
+  /// The role of the member in the target workspace once merged.
+  pub role: AFRole,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicatePageName {
+  pub view_id: String,
+  pub name: String,
+}
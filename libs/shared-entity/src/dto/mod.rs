@@ -3,10 +3,28 @@ pub mod ai_dto;
 pub mod auth_dto;
 pub mod billing_dto;
 pub mod chat_dto;
+pub mod collab_compaction_dto;
+pub mod collab_recovery_dto;
+pub mod export_dto;
+pub mod fault_injection_dto;
+pub mod feature_flag_dto;
 pub mod file_dto;
 pub mod history_dto;
+pub mod impersonation_dto;
 pub mod import_dto;
+pub mod inbound_email_dto;
+pub mod maintenance_dto;
+pub mod merge_dto;
+pub mod moderation_dto;
+pub mod page_dto;
+pub mod policy_version_dto;
+pub mod provisioning_dto;
 pub mod publish_dto;
+pub mod realtime_dto;
 pub mod search_dto;
 pub mod server_info_dto;
+pub mod session_dto;
+pub mod similar_page_dto;
+pub mod unfurl_dto;
+pub mod webhook_dto;
 pub mod workspace_dto;
@@ -0,0 +1,157 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PublishedViewReportReason {
+  Spam,
+  Phishing,
+  Malware,
+  Harassment,
+  HateSpeech,
+  SexualContent,
+  Violence,
+  Copyright,
+  Other,
+}
+
+impl PublishedViewReportReason {
+  pub fn value(&self) -> i16 {
+    match self {
+      PublishedViewReportReason::Spam => 1,
+      PublishedViewReportReason::Phishing => 2,
+      PublishedViewReportReason::Malware => 3,
+      PublishedViewReportReason::Harassment => 4,
+      PublishedViewReportReason::HateSpeech => 5,
+      PublishedViewReportReason::SexualContent => 6,
+      PublishedViewReportReason::Violence => 7,
+      PublishedViewReportReason::Copyright => 8,
+      PublishedViewReportReason::Other => 0,
+    }
+  }
+}
+
+impl From<i16> for PublishedViewReportReason {
+  fn from(value: i16) -> Self {
+    match value {
+      1 => PublishedViewReportReason::Spam,
+      2 => PublishedViewReportReason::Phishing,
+      3 => PublishedViewReportReason::Malware,
+      4 => PublishedViewReportReason::Harassment,
+      5 => PublishedViewReportReason::HateSpeech,
+      6 => PublishedViewReportReason::SexualContent,
+      7 => PublishedViewReportReason::Violence,
+      8 => PublishedViewReportReason::Copyright,
+      _ => PublishedViewReportReason::Other,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PublishedViewReportStatus {
+  Pending,
+  Dismissed,
+  TakenDown,
+}
+
+impl PublishedViewReportStatus {
+  pub fn value(&self) -> i16 {
+    match self {
+      PublishedViewReportStatus::Pending => 0,
+      PublishedViewReportStatus::Dismissed => 1,
+      PublishedViewReportStatus::TakenDown => 2,
+    }
+  }
+}
+
+impl From<i16> for PublishedViewReportStatus {
+  fn from(value: i16) -> Self {
+    match value {
+      1 => PublishedViewReportStatus::Dismissed,
+      2 => PublishedViewReportStatus::TakenDown,
+      _ => PublishedViewReportStatus::Pending,
+    }
+  }
+}
+
+/// Report of a published view, sent by anyone reading it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportPublishedViewParams {
+  pub reason: PublishedViewReportReason,
+  pub details: Option<String>,
+  /// When set, the reporter is notified by email once the report is resolved.
+  pub reporter_email: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishedViewReport {
+  pub report_id: Uuid,
+  pub workspace_id: Uuid,
+  pub view_id: Uuid,
+  pub publish_namespace: String,
+  pub publish_name: String,
+  pub reason: PublishedViewReportReason,
+  pub details: Option<String>,
+  pub status: PublishedViewReportStatus,
+  pub created_at: DateTime<Utc>,
+  pub resolved_at: Option<DateTime<Utc>>,
+  pub resolution_note: Option<String>,
+  /// Risk score of the namespace the view is published in, see [PublishNamespaceRisk].
+  pub namespace_risk_score: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListPublishedViewReportsQuery {
+  pub status: Option<PublishedViewReportStatus>,
+  pub offset: Option<i64>,
+  pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PublishedViewReportAction {
+  Dismiss,
+  /// Unpublishes the view and blocks publishing it again.
+  TakeDown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvePublishedViewReportParams {
+  pub action: PublishedViewReportAction,
+  pub note: Option<String>,
+}
+
+/// Summary of the reports against the views published in a namespace over the last
+/// [PublishNamespaceRisk::WINDOW_DAYS] days. Reports which led to a takedown weigh more than the
+/// pending ones, and dismissed reports don't count.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PublishNamespaceRisk {
+  pub publish_namespace: String,
+  pub pending_reports: i64,
+  pub dismissed_reports: i64,
+  pub taken_down_reports: i64,
+  pub score: i64,
+}
+
+impl PublishNamespaceRisk {
+  pub const WINDOW_DAYS: i64 = 90;
+  const PENDING_WEIGHT: i64 = 1;
+  const TAKEN_DOWN_WEIGHT: i64 = 10;
+
+  pub fn new(
+    publish_namespace: String,
+    pending_reports: i64,
+    dismissed_reports: i64,
+    taken_down_reports: i64,
+  ) -> Self {
+    Self {
+      publish_namespace,
+      pending_reports,
+      dismissed_reports,
+      taken_down_reports,
+      score: pending_reports * Self::PENDING_WEIGHT + taken_down_reports * Self::TAKEN_DOWN_WEIGHT,
+    }
+  }
+}
@@ -0,0 +1,127 @@
+use std::fmt::{Display, Formatter};
+
+use app_error::AppError;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Requests sending `x-api-version` with at least this version get a [Page] from the list
+/// endpoints. The others keep the shape each endpoint used to return, until the deprecation
+/// window is over.
+pub const PAGE_API_VERSION: u32 = 3;
+
+/// Longest cursor accepted, the cursors issued are much shorter.
+const MAX_CURSOR_LEN: usize = 1024;
+
+/// A page of the items of a list endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+  pub items: Vec<T>,
+  /// Pass it as the cursor of the next request to get the next page. `None` on the last page.
+  pub next_cursor: Option<Cursor>,
+  /// Number of items in the whole list, for the endpoints which count them.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub total: Option<i64>,
+}
+
+impl<T> Page<T> {
+  /// The page made of the first `limit` items, fetched along with the item following them if
+  /// there is one: the next page then starts after the last item of this one.
+  pub fn from_items(
+    mut items: Vec<T>,
+    limit: usize,
+    next_cursor: impl FnOnce(&T) -> Cursor,
+  ) -> Self {
+    let next_cursor = if items.len() > limit {
+      items.truncate(limit);
+      items.last().map(next_cursor)
+    } else {
+      None
+    };
+    Self {
+      items,
+      next_cursor,
+      total: None,
+    }
+  }
+
+  pub fn with_total(mut self, total: i64) -> Self {
+    self.total = Some(total);
+    self
+  }
+
+  pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+    Page {
+      items: self.items.into_iter().map(f).collect(),
+      next_cursor: self.next_cursor,
+      total: self.total,
+    }
+  }
+}
+
+/// The query of a list endpoint returning a [Page].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PageQuery {
+  /// The `next_cursor` of the previous page, `None` for the first page.
+  pub cursor: Option<Cursor>,
+  pub limit: Option<u32>,
+}
+
+/// Position in a list, issued by the endpoint listing it. It's opaque to the clients: it holds
+/// the position along with the kind of list it was issued for, so that a cursor sent to another
+/// list endpoint, or altered, is rejected with [AppError::InvalidCursor] rather than read as the
+/// start of the list.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Cursor(String);
+
+#[derive(Serialize, Deserialize)]
+struct CursorPayload<T> {
+  #[serde(rename = "k")]
+  kind: String,
+  #[serde(rename = "p")]
+  position: T,
+}
+
+impl Cursor {
+  pub fn encode<T: Serialize>(kind: &str, position: &T) -> Self {
+    let payload = CursorPayload {
+      kind: kind.to_string(),
+      position,
+    };
+    // serializing a position made of numbers, strings and ids can't fail
+    let json = serde_json::to_vec(&payload).unwrap_or_default();
+    Self(URL_SAFE_NO_PAD.encode(json))
+  }
+
+  /// The position held by a cursor issued for the lists of the given kind.
+  pub fn decode<T: DeserializeOwned>(&self, kind: &str) -> Result<T, AppError> {
+    let invalid = || AppError::InvalidCursor(format!("not a cursor of {}", kind));
+    if self.0.is_empty() || self.0.len() > MAX_CURSOR_LEN {
+      return Err(invalid());
+    }
+    let json = URL_SAFE_NO_PAD.decode(&self.0).map_err(|_| invalid())?;
+    let payload: CursorPayload<T> = serde_json::from_slice(&json).map_err(|_| invalid())?;
+    if payload.kind != kind {
+      return Err(invalid());
+    }
+    Ok(payload.position)
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+impl From<String> for Cursor {
+  fn from(value: String) -> Self {
+    Self(value)
+  }
+}
+
+impl Display for Cursor {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.0)
+  }
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+/// Response header of the requests changing data, set to the version of the access control
+/// policies once the request is handled.
+pub const X_POLICY_VERSION: &str = "x-policy-version";
+/// Request header asking the server to enforce policies of at least this version, so that the
+/// permissions changed by a previous request apply on every server.
+pub const X_MIN_POLICY_VERSION: &str = "x-min-policy-version";
+
+/// Returned by `GET /health`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthStatus {
+  pub status: String,
+  /// Version of the access control policies enforced by the server.
+  pub policy_version: i64,
+}
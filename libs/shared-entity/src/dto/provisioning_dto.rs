@@ -0,0 +1,74 @@
+use chrono::{DateTime, Utc};
+use database_entity::dto::AFRole;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::dto::billing_dto::SubscriptionPlan;
+
+/// The workspace to provision, sent by a service account with
+/// `POST /api/admin/workspace-provisioning`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProvisionWorkspaceParams {
+  /// Chosen by the caller. Running the same provisioning again returns the one started first.
+  pub external_id: String,
+  pub workspace_name: String,
+  /// Rejected unless it's the region the deployment stores its data in.
+  #[serde(default)]
+  pub region: Option<String>,
+  /// Overrides the plan the workspace would get from the billing service.
+  #[serde(default)]
+  pub plan: Option<SubscriptionPlan>,
+  #[serde(default)]
+  pub members: Vec<ProvisionedMemberSpec>,
+  /// The template instantiated in the workspace.
+  #[serde(default)]
+  pub template_view_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProvisionedMemberSpec {
+  pub email: String,
+  pub role: AFRole,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProvisioningStatus {
+  Pending,
+  Running,
+  Completed,
+  Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum MemberProvisioningResult {
+  /// The user already had an account and joined the workspace.
+  Joined,
+  /// The user was sent an invitation to the workspace.
+  Invited,
+  /// The member couldn't be added, the other members are provisioned anyway.
+  Failed { reason: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProvisionedMember {
+  pub email: String,
+  pub role: AFRole,
+  #[serde(flatten)]
+  pub result: MemberProvisioningResult,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceProvisioning {
+  pub external_id: String,
+  /// Set once the workspace is created, which is before the members and the template are added.
+  pub workspace_id: Option<Uuid>,
+  pub status: ProvisioningStatus,
+  /// The members provisioned so far.
+  pub members: Vec<ProvisionedMember>,
+  pub template_view_id: Option<String>,
+  pub error: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
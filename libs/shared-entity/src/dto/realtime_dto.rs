@@ -0,0 +1,32 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TopWorkspacesByBandwidthQuery {
+  /// First day of the window (UTC), defaults to 6 days before `to`.
+  pub from: Option<NaiveDate>,
+  /// Last day of the window (UTC), included. Defaults to today.
+  pub to: Option<NaiveDate>,
+  pub limit: Option<i64>,
+}
+
+/// Bytes a workspace exchanged with the realtime clients over a window of days.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceBandwidth {
+  pub workspace_id: Uuid,
+  pub workspace_name: Option<String>,
+  /// Bytes received from the clients.
+  pub bytes_in: i64,
+  /// Bytes sent to the clients.
+  pub bytes_out: i64,
+  /// Part of `bytes_out` sent for collab updates.
+  #[serde(default)]
+  pub updates_out: i64,
+  /// Part of `bytes_out` sent for awareness updates.
+  #[serde(default)]
+  pub awareness_out: i64,
+  /// Part of `bytes_out` sent for notifications about the collabs, such as their editing locks.
+  #[serde(default)]
+  pub control_out: i64,
+}
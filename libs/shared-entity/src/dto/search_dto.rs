@@ -2,6 +2,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
+use crate::dto::page_dto::Cursor;
+
 /// Parameters used to customize the collab vector search query.
 /// In response, a list of [SearchDocumentResponseItem] is returned.
 #[derive(Clone, Debug, Deserialize)]
@@ -14,6 +16,10 @@ pub struct SearchDocumentRequest {
   /// Maximum length of the content string preview to return. Default: 180.
   #[serde(skip_serializing_if = "Option::is_none")]
   pub preview_size: Option<u32>,
+  /// The `next_cursor` of the previous page of results, for the clients getting a
+  /// [crate::dto::page_dto::Page].
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub cursor: Option<Cursor>,
 }
 
 /// Response array element for the collab vector search query.
@@ -46,6 +52,8 @@ pub struct SearchDocumentResponseItem {
 pub enum SearchContentType {
   /// Document block contents displayed as plain text.
   PlainText = 0,
+  /// Question or answer of a chat shared with the user, the object is the chat.
+  ChatConversation = 1,
 }
 
 impl SearchContentType {
@@ -53,6 +61,7 @@ impl SearchContentType {
   pub fn from_record(content_type: i32) -> Option<Self> {
     match content_type {
       0 => Some(SearchContentType::PlainText),
+      1 => Some(SearchContentType::ChatConversation),
       _ => None,
     }
   }
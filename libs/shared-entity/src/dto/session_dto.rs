@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A signed in device of the user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserSession {
+  pub session_id: Uuid,
+  pub device_id: Option<String>,
+  pub client_version: Option<String>,
+  pub user_agent: Option<String>,
+  pub ip_address: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub last_seen_at: DateTime<Utc>,
+  /// When the session was last refreshed, that is when its latest access token was issued.
+  #[serde(default)]
+  pub refreshed_at: Option<DateTime<Utc>>,
+  /// How many times the session was refreshed, each refresh rotating its refresh token.
+  #[serde(default)]
+  pub refresh_count: i64,
+  /// True for the session of the access token the sessions were listed with.
+  pub is_current: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserSessions {
+  pub sessions: Vec<UserSession>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevokedSessions {
+  pub session_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionRevocation {
+  pub session_id: Uuid,
+  pub revoked_by: i64,
+  /// True when an admin of the instance revoked the session, false when the user did.
+  pub revoked_by_admin: bool,
+  pub revoked_at: DateTime<Utc>,
+  /// Why the server revoked the session by itself, `None` when the user or an admin did, see
+  /// [SESSION_REVOKED_REFRESH_TOKEN_REUSED].
+  #[serde(default)]
+  pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionRevocations {
+  pub revocations: Vec<SessionRevocation>,
+}
+
+/// The session was revoked because one of its refresh tokens was used again after being
+/// rotated, which happens when the token was stolen.
+pub const SESSION_REVOKED_REFRESH_TOKEN_REUSED: &str = "refresh_token_reused";
@@ -0,0 +1,65 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SimilarPagesQuery {
+  /// How similar the pages of a cluster must be, from 0.75 to 1.0. Defaults to 0.9.
+  pub threshold: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarPage {
+  pub view_id: String,
+  pub name: String,
+  pub last_edited_time: DateTime<Utc>,
+  /// Length of the text of the page, in characters.
+  pub text_len: i32,
+  pub is_published: bool,
+}
+
+/// Pages whose content is nearly the same, the largest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarPageCluster {
+  pub pages: Vec<SimilarPage>,
+  /// The lowest similarity between two pages of the cluster.
+  pub similarity: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarPageClusters {
+  pub clusters: Vec<SimilarPageCluster>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashSimilarPagesParams {
+  pub view_ids: Vec<String>,
+  /// The published pages are skipped unless set.
+  #[serde(default)]
+  pub include_published: bool,
+  /// The pages edited during the last 7 days are skipped unless set.
+  #[serde(default)]
+  pub include_recently_edited: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkippedPageReason {
+  NotFound,
+  AlreadyInTrash,
+  /// The page, or one of its children, is published.
+  Published,
+  /// The page, or one of its children, was edited recently.
+  RecentlyEdited,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedPage {
+  pub view_id: String,
+  pub reason: SkippedPageReason,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedSimilarPages {
+  pub trashed: Vec<String>,
+  pub skipped: Vec<SkippedPage>,
+}
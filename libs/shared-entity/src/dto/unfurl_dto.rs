@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UnfurlQuery {
+  pub url: String,
+}
+
+/// Metadata of a web page, used to render a preview of a pasted link.
+/// When the page can not be fetched, only the `url` is set.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UnfurlMetadata {
+  /// Normalized url of the page.
+  pub url: String,
+  pub title: Option<String>,
+  pub description: Option<String>,
+  pub site_name: Option<String>,
+  /// Absolute url of the favicon.
+  pub favicon: Option<String>,
+  /// Absolute url of the open graph image.
+  pub image: Option<String>,
+}
+
+impl UnfurlMetadata {
+  pub fn minimal(url: String) -> Self {
+    Self {
+      url,
+      ..Default::default()
+    }
+  }
+
+  pub fn is_minimal(&self) -> bool {
+    self.title.is_none()
+      && self.description.is_none()
+      && self.site_name.is_none()
+      && self.favicon.is_none()
+      && self.image.is_none()
+  }
+}
@@ -0,0 +1,102 @@
+use chrono::{DateTime, Utc};
+use database_entity::dto::AFRole;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Header of the webhook deliveries holding `t=<unix seconds>,v1=<hex HMAC-SHA256>`, the HMAC
+/// being computed with the secret of the webhook over `<unix seconds>.<body>`.
+pub const X_APPFLOWY_SIGNATURE: &str = "x-appflowy-signature";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventType {
+  MemberAdded,
+  MemberRemoved,
+  RoleChanged,
+  InvitationSent,
+  InvitationAccepted,
+}
+
+impl WebhookEventType {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      WebhookEventType::MemberAdded => "member_added",
+      WebhookEventType::MemberRemoved => "member_removed",
+      WebhookEventType::RoleChanged => "role_changed",
+      WebhookEventType::InvitationSent => "invitation_sent",
+      WebhookEventType::InvitationAccepted => "invitation_accepted",
+    }
+  }
+
+  pub fn parse(event_type: &str) -> Option<Self> {
+    match event_type {
+      "member_added" => Some(WebhookEventType::MemberAdded),
+      "member_removed" => Some(WebhookEventType::MemberRemoved),
+      "role_changed" => Some(WebhookEventType::RoleChanged),
+      "invitation_sent" => Some(WebhookEventType::InvitationSent),
+      "invitation_accepted" => Some(WebhookEventType::InvitationAccepted),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWorkspaceWebhookParams {
+  pub url: String,
+  /// The event types delivered to the webhook, every type when empty.
+  #[serde(default)]
+  pub event_types: Vec<WebhookEventType>,
+  /// Deliver the events of a single change, such as a bulk invite, in one request.
+  #[serde(default)]
+  pub batch: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceWebhook {
+  pub id: Uuid,
+  pub url: String,
+  pub event_types: Vec<WebhookEventType>,
+  pub batch: bool,
+  pub created_at: DateTime<Utc>,
+}
+
+/// A webhook just created. Its secret is only returned once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatedWorkspaceWebhook {
+  #[serde(flatten)]
+  pub webhook: WorkspaceWebhook,
+  pub secret: String,
+}
+
+/// A user taking part in a membership event. Invitees who don't have an account yet are only
+/// known by their email.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct WebhookIdentity {
+  pub uuid: Option<Uuid>,
+  pub email: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct MembershipEvent {
+  pub event_type: WebhookEventType,
+  pub workspace_id: Uuid,
+  /// The user who made the change.
+  pub actor: WebhookIdentity,
+  /// The member or invitee the change applies to.
+  pub target: WebhookIdentity,
+  /// The role granted by the change, if any.
+  pub role: Option<AFRole>,
+  pub invitation_id: Option<Uuid>,
+  pub occurred_at: DateTime<Utc>,
+}
+
+/// The body of a webhook delivery. A batching webhook receives all the events of a change in one
+/// delivery, the other ones receive a delivery per event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+  /// The same across the retries of the delivery, so that the receivers can drop duplicates.
+  pub delivery_id: i64,
+  pub webhook_id: Uuid,
+  pub workspace_id: Uuid,
+  pub events: Vec<MembershipEvent>,
+}
@@ -1,12 +1,16 @@
-use chrono::{DateTime, Utc};
+use app_error::ErrorCode;
+use chrono::{DateTime, NaiveDate, Utc};
 use collab_entity::{CollabType, EncodedCollab};
 use database_entity::dto::{AFRole, AFWebUser, AFWorkspaceInvitationStatus, PublishInfo};
+use database_entity::timestamp;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use std::{collections::HashMap, ops::Deref};
 use uuid::Uuid;
 
+use crate::dto::collab_recovery_dto::RecoveredCollabSnapshot;
+
 #[derive(Deserialize, Serialize)]
 pub struct WorkspaceMembers(pub Vec<WorkspaceMember>);
 #[derive(Deserialize, Serialize)]
@@ -66,6 +70,15 @@ pub struct WorkspaceInviteQuery {
   pub status: Option<AFWorkspaceInvitationStatus>,
 }
 
+/// Resets the expiry of a pending invitation.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ExtendWorkspaceInvitationParams {
+  /// Also sends the invitation email again. An email queued for the invitation less than a minute
+  /// ago isn't queued a second time.
+  #[serde(default)]
+  pub resend_email: bool,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct WorkspaceMemberChangeset {
   pub email: String,
@@ -94,6 +107,64 @@ impl WorkspaceMemberChangeset {
 #[derive(Deserialize, Serialize)]
 pub struct WorkspaceSpaceUsage {
   pub consumed_capacity: u64,
+  /// The files of the workspace grouped by category. Archived blob versions only count toward
+  /// `consumed_capacity`.
+  #[serde(default)]
+  pub breakdown: Vec<FileTypeUsage>,
+  /// The files of the workspace grouped by the member who uploaded them, largest first.
+  #[serde(default)]
+  pub by_member: Vec<MemberBlobUsage>,
+}
+
+/// Category of a file, derived from its mime type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileTypeCategory {
+  Image,
+  Video,
+  Audio,
+  Pdf,
+  Other,
+}
+
+impl FileTypeCategory {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      FileTypeCategory::Image => "image",
+      FileTypeCategory::Video => "video",
+      FileTypeCategory::Audio => "audio",
+      FileTypeCategory::Pdf => "pdf",
+      FileTypeCategory::Other => "other",
+    }
+  }
+
+  /// The category named `name`, [FileTypeCategory::Other] for any unknown name.
+  pub fn from_name(name: &str) -> Self {
+    match name {
+      "image" => FileTypeCategory::Image,
+      "video" => FileTypeCategory::Video,
+      "audio" => FileTypeCategory::Audio,
+      "pdf" => FileTypeCategory::Pdf,
+      _ => FileTypeCategory::Other,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FileTypeUsage {
+  pub category: FileTypeCategory,
+  pub file_count: u64,
+  pub total_size: u64,
+}
+
+/// The files uploaded by a member of the workspace.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MemberBlobUsage {
+  /// `None` for the files whose uploader is unknown: those uploaded before the uploaders were
+  /// recorded and those of the deleted users.
+  pub uid: Option<i64>,
+  pub file_count: u64,
+  pub total_size: u64,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -108,6 +179,57 @@ pub struct BlobMetadata {
   pub modified_at: DateTime<Utc>,
 }
 
+/// The page of blob metadata returned to the clients older than
+/// [crate::dto::page_dto::PAGE_API_VERSION], which get a [crate::dto::page_dto::Page] otherwise.
+#[derive(Serialize, Deserialize)]
+pub struct BlobMetadataPage {
+  pub blobs: Vec<BlobMetadata>,
+  /// Pass it as the cursor of the next request to get the next page. `None` on the last page.
+  pub next_cursor: Option<String>,
+}
+
+/// A line of the export of the blob metadata of a workspace, served as newline-delimited JSON.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlobMetadataExportLine {
+  pub file_id: String,
+  pub file_type: String,
+  pub file_size: i64,
+  /// `None` for the blobs uploaded before their creation time was recorded.
+  pub created_at: Option<DateTime<Utc>>,
+  pub modified_at: DateTime<Utc>,
+}
+
+/// The formats of the export of the blob metadata of a workspace, picked with the `Accept`
+/// header of the request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlobMetadataExportFormat {
+  Ndjson,
+  Csv,
+}
+
+impl BlobMetadataExportFormat {
+  pub fn content_type(&self) -> &'static str {
+    match self {
+      BlobMetadataExportFormat::Ndjson => "application/x-ndjson",
+      BlobMetadataExportFormat::Csv => "text/csv; charset=utf-8",
+    }
+  }
+
+  /// CSV when the `Accept` header asks for it, newline-delimited JSON otherwise.
+  pub fn from_accept(accept: Option<&str>) -> Self {
+    let accepts_csv = accept.is_some_and(|accept| {
+      accept
+        .split(',')
+        .any(|media_range| media_range.trim().starts_with("text/csv"))
+    });
+    if accepts_csv {
+      BlobMetadataExportFormat::Csv
+    } else {
+      BlobMetadataExportFormat::Ndjson
+    }
+  }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct CreateWorkspaceParam {
   pub workspace_name: Option<String>,
@@ -146,6 +268,150 @@ pub struct CollabResponse {
   /// We can remove this 'serde(default)' after the 0325 version is stable.
   #[serde(default)]
   pub object_id: String,
+  /// Set when the stored state of the collab could not be decoded and `encode_collab` holds the
+  /// content of one of its snapshots instead.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub recovered_from: Option<RecoveredCollabSnapshot>,
+}
+
+/// The result of one of the collabs fetched with [BatchQueryCollabParams] by
+/// `POST /api/workspace/{workspace_id}/collab/batch`.
+///
+/// [BatchQueryCollabParams]: database_entity::dto::BatchQueryCollabParams
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchGetCollabItem {
+  Success(CollabResponse),
+  /// The collab couldn't be read, for instance [ErrorCode::RecordNotFound] when it doesn't exist
+  /// or [ErrorCode::NotEnoughPermissions] when the user can't read it.
+  Failed {
+    code: ErrorCode,
+    message: String,
+  },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchGetCollabResponse {
+  /// The result of each requested collab, by object id.
+  pub items: HashMap<String, BatchGetCollabItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkCreateCollabItem {
+  pub object_id: String,
+  pub collab_type: CollabType,
+  /// [EncodedCollab::encode_to_bytes] of the new collab.
+  pub encoded_collab: Vec<u8>,
+}
+
+/// Collabs created in a single transaction: either all of them are created or none.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkCreateCollabParams {
+  pub items: Vec<BulkCreateCollabItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkCreateCollabResult {
+  Created,
+  /// The collab is invalid, for instance [ErrorCode::CollabSizeExceeded] when it's too large.
+  Failed {
+    code: ErrorCode,
+    message: String,
+  },
+  /// The collab is valid but wasn't created, because another collab of the batch is invalid.
+  NotCreated,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkCreateCollabResponse {
+  /// The result of each collab of the batch, by object id.
+  pub items: HashMap<String, BulkCreateCollabResult>,
+}
+
+impl BulkCreateCollabResponse {
+  pub fn is_created(&self) -> bool {
+    self
+      .items
+      .values()
+      .all(|result| *result == BulkCreateCollabResult::Created)
+  }
+}
+
+/// A deleted collab, kept in the trash of its workspace until it's restored or purged. The
+/// server purges the collabs which stayed in the trash for 30 days by default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletedCollab {
+  pub object_id: String,
+  pub collab_type: CollabType,
+  pub deleted_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletedCollabs {
+  /// Most recently deleted first.
+  pub items: Vec<DeletedCollab>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollabDiffParams {
+  pub collab_type: CollabType,
+  /// Hash of the state vector of the copy held by the client, as returned by a previous
+  /// [CollabDiffResponse]. The collab is not sent back when it matches the server copy.
+  #[serde(default)]
+  pub state_vector_hash: Option<String>,
+  /// State vector (lib0 v1) of the copy held by the client. When it is set, a modified collab is
+  /// returned as an update relative to this state vector instead of the full doc state.
+  #[serde(default)]
+  pub state_vector: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollabDiffResponse {
+  pub object_id: String,
+  /// Hash of the state vector of the server copy.
+  pub state_vector_hash: String,
+  /// `None` when the copy held by the client is up to date.
+  pub encode_collab: Option<EncodedCollab>,
+  /// Whether the doc state of `encode_collab` is an update relative to the state vector of the
+  /// request, rather than the full doc state.
+  pub is_diff: bool,
+  /// See [CollabResponse::recovered_from].
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub recovered_from: Option<RecoveredCollabSnapshot>,
+}
+
+impl CollabDiffResponse {
+  pub fn is_modified(&self) -> bool {
+    self.encode_collab.is_some()
+  }
+}
+
+/// Exclusive editing lock of a collab. Other members can only read the collab while it's held.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AFEditingLock {
+  pub uid: i64,
+  pub name: String,
+  pub acquired_at: DateTime<Utc>,
+  /// The lock is released at this time unless the holder sends a heartbeat before.
+  pub expires_at: DateTime<Utc>,
+}
+
+/// A client connected to a collab, as announced by its awareness state.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AFCollabPresence {
+  pub uid: i64,
+  pub device_id: String,
+  /// The selection of the user, when the client shares it.
+  pub cursor: Option<Value>,
+  pub last_heartbeat_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseEditingLockQuery {
+  /// Release the lock held by another member. Only allowed for workspace owners.
+  #[serde(default)]
+  pub force: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -214,6 +480,10 @@ pub struct PageCollab {
 pub struct PublishedDuplicate {
   pub published_view_id: String,
   pub dest_view_id: String,
+  /// Identifies the duplication. Retrying a duplication with the same nonce doesn't create the
+  /// pages again. When not set, every request creates new pages.
+  #[serde(default)]
+  pub nonce: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -292,6 +562,28 @@ pub struct PublishPageParams {
   pub duplicate_enabled: Option<bool>,
 }
 
+/// Replaces the published revision of an already published view by the current content of the
+/// view. The publish name and settings of the view are kept.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RepublishPageParams {
+  pub visible_database_view_ids: Option<Vec<String>>,
+  /// When set to a time in the future, the new revision is captured right away but only replaces
+  /// the published one at this time.
+  #[serde(default, with = "timestamp::option_rfc3339")]
+  pub publish_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishRevision {
+  /// ETag served with the content of the published revision.
+  pub etag: String,
+  pub published_at: DateTime<Utc>,
+  /// Whether the view was edited after the published revision was captured. It's `None` when the
+  /// revision was uploaded by a client, as there is nothing to compare it with.
+  pub has_unpublished_changes: Option<bool>,
+  pub scheduled_publish_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Eq, PartialEq, Debug, Hash, Clone, Serialize_repr, Deserialize_repr)]
 #[repr(u8)]
 pub enum IconType {
@@ -360,6 +652,7 @@ pub struct ListDatabaseRowDetailParam {
 
 #[derive(Default, Debug, Deserialize, Serialize)]
 pub struct ListDatabaseRowUpdatedParam {
+  #[serde(default, with = "timestamp::option_rfc3339")]
   pub after: Option<DateTime<Utc>>,
 }
 
@@ -440,6 +733,25 @@ pub struct AFDatabaseField {
   pub is_primary: bool,
 }
 
+/// Row-level access rule of a database. When `restricted_rows` is set, the members of the
+/// workspace only see the rows whose `field_id` cell contains their email or their name. The
+/// owners of the workspace and the author of the rule always see every row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AFDatabaseRowAccess {
+  pub database_id: String,
+  pub field_id: String,
+  pub restricted_rows: bool,
+  pub created_by: i64,
+  pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpsertDatabaseRowAccess {
+  pub field_id: String,
+  #[serde(default)]
+  pub restricted_rows: bool,
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct AFInsertDatabaseField {
   pub name: String,
@@ -447,6 +759,70 @@ pub struct AFInsertDatabaseField {
   pub type_option_data: Option<serde_json::Value>, // TypeOptionData
 }
 
+/// Change the type of a database field. The existing cells are converted by a background task,
+/// see [AFFieldConversionTask].
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateDatabaseFieldType {
+  pub field_type: i64,                             // FieldType ID
+  pub type_option_data: Option<serde_json::Value>, // TypeOptionData of the new type
+  /// Creates the select options matching no option of the field, instead of reporting the cells
+  /// as inconvertible.
+  #[serde(default)]
+  pub create_select_options: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AFFieldConversionTask {
+  pub task_id: Uuid,
+  pub database_id: Uuid,
+  pub field_id: String,
+  pub from_field_type: i64,
+  pub to_field_type: i64,
+  /// 0: pending, 1: running, 2: completed, 3: failed
+  pub status: i16,
+  pub total_rows: i64,
+  pub converted_rows: i64,
+  pub report: AFFieldConversionReport,
+  pub error: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AFFieldConversionReport {
+  /// Number of cells now holding a value of the new type.
+  #[serde(default)]
+  pub converted_cells: i64,
+  /// The cells whose value has no counterpart in the new type. They are cleared, their value is
+  /// kept here.
+  #[serde(default)]
+  pub inconvertible_cells: Vec<AFInconvertibleCell>,
+  /// The filters, sorts and groups of the views which no longer made sense for the new type.
+  #[serde(default)]
+  pub dropped_view_settings: Vec<AFDroppedViewSetting>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AFInconvertibleCell {
+  pub row_id: String,
+  pub value: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AFDroppedViewSetting {
+  pub view_id: String,
+  pub kind: AFViewSettingKind,
+  pub id: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AFViewSettingKind {
+  Filter,
+  Sort,
+  Group,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct AddDatatabaseRow {
   pub cells: HashMap<String, serde_json::Value>,
@@ -459,3 +835,177 @@ pub struct UpsertDatatabaseRow {
   pub cells: HashMap<String, serde_json::Value>,
   pub document: Option<String>,
 }
+
+/// Rows are validated before any of them is inserted: the request fails as a whole if a cell
+/// does not match its field.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BatchAddDatabaseRows {
+  pub rows: Vec<AddDatatabaseRow>,
+  /// Creates the select options which do not exist yet, instead of rejecting the rows.
+  #[serde(default)]
+  pub create_select_options: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UpdateDatabaseRow {
+  pub cells: HashMap<String, serde_json::Value>,
+  pub document: Option<String>,
+  #[serde(default)]
+  pub create_select_options: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceStats {
+  pub workspace_id: String,
+  /// Number and encoded size of the objects of each type, in bytes.
+  pub objects: Vec<CollabTypeStats>,
+  /// Number of views at each depth of the folder, the views of the top level spaces being at
+  /// depth 1. The views in the trash are not counted.
+  pub folder_depth: Vec<FolderDepthCount>,
+  pub max_folder_depth: u32,
+  /// Changes since the snapshot of a week ago, `None` until the workspace has one.
+  pub week_over_week: Option<WorkspaceStatsDelta>,
+  /// When the stats were last recomputed from scratch.
+  pub computed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollabTypeStats {
+  pub collab_type: CollabType,
+  pub object_count: i64,
+  pub total_bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderDepthCount {
+  pub depth: u32,
+  pub view_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceStatsDelta {
+  /// Date of the snapshot compared to. It's the latest one taken at least a week ago.
+  pub since: NaiveDate,
+  pub objects: Vec<CollabTypeStats>,
+}
+
+/// The headings of a document, in the order they appear in it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentOutline {
+  pub object_id: String,
+  /// Hash of the state vector of the document the outline was extracted from, or the revision for
+  /// a published document.
+  pub version: String,
+  pub headings: Vec<OutlineHeading>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutlineHeading {
+  pub block_id: String,
+  pub level: u8,
+  pub text: String,
+  /// The toggle or callout the heading is nested in, `None` for a heading in the body of the
+  /// document. A renderer may only show those headings once their container is expanded.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub container: Option<OutlineContainer>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutlineContainer {
+  pub block_id: String,
+  /// The type of the block, such as `toggle_list` or `callout`.
+  pub ty: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FindInDocumentQuery {
+  pub query: String,
+  #[serde(default)]
+  pub case_sensitive: bool,
+  /// Only match whole words.
+  #[serde(default)]
+  pub whole_word: bool,
+  /// The query is a regular expression.
+  #[serde(default)]
+  pub regex: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentFindResult {
+  pub object_id: String,
+  /// Hash of the state vector of the document the matches were found in.
+  pub version: String,
+  /// The matches in reading order.
+  pub matches: Vec<BlockMatch>,
+  /// Whether matches were left out because there are too many.
+  pub truncated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockMatch {
+  pub block_id: String,
+  /// Offsets of the match in the text of the block, in UTF-16 code units like the offsets of the
+  /// editor selections.
+  pub start: u32,
+  pub end: u32,
+  /// The text of the block around the match, for rendering the result.
+  pub before: String,
+  pub matched: String,
+  pub after: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentSnapshotDiffQuery {
+  /// The snapshot the changes are made from.
+  pub from: i64,
+  /// The snapshot the changes lead to, the current version of the document when missing.
+  #[serde(default)]
+  pub to: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentDiff {
+  pub object_id: String,
+  pub from: i64,
+  pub to: Option<i64>,
+  /// The changed blocks in reading order. A removed block is placed where it was in the older
+  /// version.
+  pub changes: Vec<BlockChange>,
+  /// Whether changes were left out because the diff is too large.
+  pub truncated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum BlockChange {
+  Added {
+    block_id: String,
+    ty: String,
+    text: String,
+  },
+  Removed {
+    block_id: String,
+    ty: String,
+    text: String,
+  },
+  Changed {
+    block_id: String,
+    ty: String,
+    /// The type of the block in the older version, when it was converted to another type.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    previous_ty: Option<String>,
+    /// Whether the data of the block other than its text changed, such as a checked todo.
+    data_changed: bool,
+    /// The text of the block as a sequence of kept, inserted and deleted spans. Empty when the
+    /// text didn't change.
+    text: Vec<TextDiffOp>,
+  },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextDiffOp {
+  Equal(String),
+  Insert(String),
+  Delete(String),
+}
@@ -135,6 +135,15 @@ where
     let status_code = resp.status();
     if !status_code.is_success() {
       let body = resp.text().await?;
+      // retryable errors are answered with 503 and invalid cursors with 400, but they still
+      // carry the error code
+      if status_code == reqwest::StatusCode::SERVICE_UNAVAILABLE
+        || status_code == reqwest::StatusCode::BAD_REQUEST
+      {
+        if let Ok(resp) = serde_json::from_str(&body) {
+          return Ok(resp);
+        }
+      }
       anyhow::bail!("got error code: {}, body: {}", status_code, body)
     }
 
@@ -186,11 +195,19 @@ impl Display for AppResponseError {
 #[cfg(feature = "cloud")]
 impl actix_web::error::ResponseError for AppResponseError {
   fn status_code(&self) -> actix_web::http::StatusCode {
-    actix_web::http::StatusCode::OK
+    actix_web::http::StatusCode::from_u16(self.code.http_status())
+      .unwrap_or(actix_web::http::StatusCode::OK)
   }
 
   fn error_response(&self) -> actix_web::HttpResponse {
-    actix_web::HttpResponse::Ok().json(self)
+    let mut builder = actix_web::HttpResponse::build(self.status_code());
+    if self.status_code() == actix_web::http::StatusCode::SERVICE_UNAVAILABLE {
+      builder.insert_header((
+        actix_web::http::header::RETRY_AFTER,
+        app_error::STATEMENT_TIMEOUT_RETRY_AFTER_SECS,
+      ));
+    }
+    builder.json(self)
   }
 }
 
@@ -0,0 +1,361 @@
+//! What the server knows about each [CollabType]: how to build its default state, how to check
+//! its structure, how to extract its text and how large it may get.
+//!
+//! Supporting a new collab type takes one entry in [REGISTRY]. Types without an entry get
+//! [PERMISSIVE], which accepts any structure and doesn't extract any text. Opaque collabs, whose
+//! content is encrypted by the clients, get [OPAQUE] whatever their type.
+use std::fmt::{Display, Formatter};
+
+use anyhow::anyhow;
+use collab::core::collab::DataSource;
+use collab::core::origin::CollabOrigin;
+use collab::entity::{EncodedCollab, EncoderVersion};
+use collab::preclude::Collab;
+use collab_document::document::DocumentBody;
+use collab_document::document_data::default_document_collab_data;
+use collab_entity::CollabType;
+use yrs::{Any, Map, MapRef, Out, ReadTxn};
+
+const MIB: usize = 1024 * 1024;
+
+pub struct CollabTypeSpec {
+  pub collab_type: CollabType,
+  /// Builds the state of a new collab of this type. The types whose default state depends on
+  /// their workspace or owner don't have one.
+  default_state: Option<fn(&str) -> anyhow::Result<EncodedCollab>>,
+  validate: fn(&CollabType, &Collab) -> anyhow::Result<()>,
+  /// Lists what a collab of this type misses to be opened by the clients, see
+  /// [CollabTypeSpec::check_structure].
+  structure: fn(&Collab) -> Vec<String>,
+  extract_text: Option<fn(&Collab) -> Option<String>>,
+  /// Maximum length of the encoded collab accepted on write.
+  pub max_encoded_len: usize,
+  /// The content can't be decoded by the server: it's only stored and relayed.
+  opaque: bool,
+}
+
+static REGISTRY: [CollabTypeSpec; 6] = [
+  CollabTypeSpec {
+    collab_type: CollabType::Document,
+    default_state: Some(default_document_state),
+    validate: validate_required_data,
+    structure: document_structure,
+    extract_text: Some(document_text),
+    max_encoded_len: 32 * MIB,
+    opaque: false,
+  },
+  CollabTypeSpec {
+    collab_type: CollabType::Database,
+    default_state: None,
+    validate: validate_required_data,
+    structure: database_structure,
+    extract_text: None,
+    max_encoded_len: 64 * MIB,
+    opaque: false,
+  },
+  CollabTypeSpec {
+    collab_type: CollabType::WorkspaceDatabase,
+    default_state: None,
+    validate: validate_required_data,
+    structure: workspace_database_structure,
+    extract_text: None,
+    max_encoded_len: 16 * MIB,
+    opaque: false,
+  },
+  CollabTypeSpec {
+    collab_type: CollabType::Folder,
+    default_state: None,
+    validate: validate_required_data,
+    structure: folder_structure,
+    extract_text: None,
+    max_encoded_len: 32 * MIB,
+    opaque: false,
+  },
+  CollabTypeSpec {
+    collab_type: CollabType::DatabaseRow,
+    default_state: None,
+    validate: validate_required_data,
+    structure: no_structure,
+    extract_text: None,
+    max_encoded_len: 4 * MIB,
+    opaque: false,
+  },
+  CollabTypeSpec {
+    collab_type: CollabType::UserAwareness,
+    default_state: None,
+    validate: validate_required_data,
+    structure: no_structure,
+    extract_text: None,
+    max_encoded_len: 4 * MIB,
+    opaque: false,
+  },
+];
+
+static PERMISSIVE: CollabTypeSpec = CollabTypeSpec {
+  collab_type: CollabType::Unknown,
+  default_state: None,
+  validate: accept_any,
+  structure: no_structure,
+  extract_text: None,
+  max_encoded_len: 64 * MIB,
+  opaque: false,
+};
+
+static OPAQUE: CollabTypeSpec = CollabTypeSpec {
+  collab_type: CollabType::Unknown,
+  default_state: None,
+  validate: accept_any,
+  structure: no_structure,
+  extract_text: None,
+  max_encoded_len: 64 * MIB,
+  opaque: true,
+};
+
+/// The spec of the collab type, [PERMISSIVE] for the types which aren't registered.
+pub fn collab_type_spec(collab_type: &CollabType) -> &'static CollabTypeSpec {
+  REGISTRY
+    .iter()
+    .find(|spec| &spec.collab_type == collab_type)
+    .unwrap_or(&PERMISSIVE)
+}
+
+/// The spec of a collab: [OPAQUE] when its content is encrypted, the spec of its type otherwise.
+pub fn collab_spec(collab_type: &CollabType, opaque: bool) -> &'static CollabTypeSpec {
+  if opaque {
+    &OPAQUE
+  } else {
+    collab_type_spec(collab_type)
+  }
+}
+
+impl CollabTypeSpec {
+  /// The default state of a new collab of this type, or an empty collab when the type doesn't
+  /// have one.
+  pub fn default_encoded_collab(&self, object_id: &str) -> anyhow::Result<EncodedCollab> {
+    match self.default_state {
+      Some(build) => build(object_id),
+      None => empty_encoded_collab(object_id),
+    }
+  }
+
+  pub fn has_default_state(&self) -> bool {
+    self.default_state.is_some()
+  }
+
+  pub fn validate(&self, collab: &Collab) -> anyhow::Result<()> {
+    (self.validate)(&self.collab_type, collab)
+  }
+
+  /// Checks that the collab holds what the clients expect of its type, such as the page block of
+  /// a document or the root view of a folder. It's stricter than [CollabTypeSpec::validate],
+  /// which only checks the root data of the collab.
+  pub fn check_structure(&self, collab: &Collab) -> Result<(), InvalidCollabStructure> {
+    let missing = (self.structure)(collab);
+    if missing.is_empty() {
+      return Ok(());
+    }
+    Err(InvalidCollabStructure {
+      collab_type: self.collab_type.clone(),
+      missing,
+    })
+  }
+
+  pub fn is_opaque(&self) -> bool {
+    self.opaque
+  }
+
+  /// Decodes the collab and checks its size and structure. Only the size of opaque collabs is
+  /// checked.
+  pub fn validate_encoded(&self, object_id: &str, encoded_collab_v1: &[u8]) -> anyhow::Result<()> {
+    self.check_size(encoded_collab_v1.len())?;
+    if self.opaque {
+      return Ok(());
+    }
+    let encoded_collab = EncodedCollab::decode_from_bytes(encoded_collab_v1)?;
+    let doc_state = encoded_collab.doc_state.to_vec();
+    let data_source = match encoded_collab.version {
+      EncoderVersion::V1 => DataSource::DocStateV1(doc_state),
+      EncoderVersion::V2 => DataSource::DocStateV2(doc_state),
+    };
+    let collab =
+      Collab::new_with_source(CollabOrigin::Empty, object_id, data_source, vec![], false)?;
+    self.validate(&collab)
+  }
+
+  pub fn check_size(&self, encoded_len: usize) -> anyhow::Result<()> {
+    if encoded_len > self.max_encoded_len {
+      return Err(anyhow!(
+        "{:?} of {} bytes exceeds the limit of {} bytes",
+        self.collab_type,
+        encoded_len,
+        self.max_encoded_len
+      ));
+    }
+    Ok(())
+  }
+
+  pub fn supports_text(&self) -> bool {
+    self.extract_text.is_some()
+  }
+
+  /// The plain text of the collab, used to index it. `None` when the type has no text or the
+  /// collab misses the data holding it.
+  pub fn extract_text(&self, collab: &Collab) -> Option<String> {
+    self.extract_text.and_then(|extract| extract(collab))
+  }
+}
+
+/// A collab which doesn't have the structure of its type, see [CollabTypeSpec::check_structure].
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidCollabStructure {
+  pub collab_type: CollabType,
+  /// What the collab misses, as paths of map keys from its root.
+  pub missing: Vec<String>,
+}
+
+impl Display for InvalidCollabStructure {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{} collab is missing {}",
+      self.collab_type,
+      self.missing.join(", ")
+    )
+  }
+}
+
+impl std::error::Error for InvalidCollabStructure {}
+
+fn default_document_state(object_id: &str) -> anyhow::Result<EncodedCollab> {
+  Ok(default_document_collab_data(object_id)?)
+}
+
+fn empty_encoded_collab(object_id: &str) -> anyhow::Result<EncodedCollab> {
+  Collab::new_with_origin(CollabOrigin::Empty, object_id, vec![], false)
+    .encode_collab_v1(|_| Ok::<(), anyhow::Error>(()))
+}
+
+fn validate_required_data(collab_type: &CollabType, collab: &Collab) -> anyhow::Result<()> {
+  collab_type.validate_require_data(collab)?;
+  Ok(())
+}
+
+fn accept_any(_collab_type: &CollabType, _collab: &Collab) -> anyhow::Result<()> {
+  Ok(())
+}
+
+fn document_text(collab: &Collab) -> Option<String> {
+  let txn = collab.transact();
+  DocumentBody::from_collab(collab).and_then(|body| body.to_plain_text(txn, false, true).ok())
+}
+
+fn no_structure(_collab: &Collab) -> Vec<String> {
+  vec![]
+}
+
+/// The map at `key` of `map`, or records `path.key` as missing.
+fn required_map<T: ReadTxn>(
+  txn: &T,
+  map: &MapRef,
+  path: &str,
+  key: &str,
+  missing: &mut Vec<String>,
+) -> Option<MapRef> {
+  match map.get(txn, key) {
+    Some(Out::YMap(map)) => Some(map),
+    _ => {
+      missing.push(join_path(path, key));
+      None
+    },
+  }
+}
+
+/// The string at `key` of `map`, or records `path.key` as missing.
+fn required_string<T: ReadTxn>(
+  txn: &T,
+  map: &MapRef,
+  path: &str,
+  key: &str,
+  missing: &mut Vec<String>,
+) -> Option<String> {
+  match map.get(txn, key) {
+    Some(Out::Any(Any::String(value))) if !value.is_empty() => Some(value.to_string()),
+    _ => {
+      missing.push(join_path(path, key));
+      None
+    },
+  }
+}
+
+fn join_path(path: &str, key: &str) -> String {
+  if path.is_empty() {
+    key.to_string()
+  } else {
+    format!("{}.{}", path, key)
+  }
+}
+
+/// A document has a page id whose block is among its blocks, and its meta.
+fn document_structure(collab: &Collab) -> Vec<String> {
+  let txn = collab.transact();
+  let mut missing = vec![];
+  let Some(document) = required_map(&txn, &collab.data, "", "document", &mut missing) else {
+    return missing;
+  };
+  let page_id = required_string(&txn, &document, "document", "page_id", &mut missing);
+  let blocks = required_map(&txn, &document, "document", "blocks", &mut missing);
+  required_map(&txn, &document, "document", "meta", &mut missing);
+  if let (Some(page_id), Some(blocks)) = (page_id, blocks) {
+    if !blocks.contains_key(&txn, &page_id) {
+      missing.push(format!("document.blocks.{}", page_id));
+    }
+  }
+  missing
+}
+
+/// A folder has the id of its workspace and the root view of the workspace among its views.
+fn folder_structure(collab: &Collab) -> Vec<String> {
+  let txn = collab.transact();
+  let mut missing = vec![];
+  let Some(folder) = required_map(&txn, &collab.data, "", "folder", &mut missing) else {
+    return missing;
+  };
+  let workspace_id = required_map(&txn, &folder, "folder", "meta", &mut missing).and_then(|meta| {
+    required_string(
+      &txn,
+      &meta,
+      "folder.meta",
+      "current_workspace",
+      &mut missing,
+    )
+  });
+  let views = required_map(&txn, &folder, "folder", "views", &mut missing);
+  if let (Some(workspace_id), Some(views)) = (workspace_id, views) {
+    if !views.contains_key(&txn, &workspace_id) {
+      missing.push(format!("folder.views.{}", workspace_id));
+    }
+  }
+  missing
+}
+
+/// A database has its id, fields and views.
+fn database_structure(collab: &Collab) -> Vec<String> {
+  let txn = collab.transact();
+  let mut missing = vec![];
+  let Some(database) = required_map(&txn, &collab.data, "", "database", &mut missing) else {
+    return missing;
+  };
+  required_string(&txn, &database, "database", "id", &mut missing);
+  required_map(&txn, &database, "database", "fields", &mut missing);
+  required_map(&txn, &database, "database", "views", &mut missing);
+  missing
+}
+
+/// A workspace database has the list of the databases of the workspace.
+fn workspace_database_structure(collab: &Collab) -> Vec<String> {
+  let txn = collab.transact();
+  match collab.data.get(&txn, "databases") {
+    Some(Out::YArray(_)) => vec![],
+    _ => vec!["databases".to_string()],
+  }
+}
@@ -0,0 +1,142 @@
+//! The default state of the collabs the server creates from nothing, such as the collabs of a new
+//! workspace or the ones a repair replaces. Unlike [CollabTypeSpec::default_encoded_collab], the
+//! default state of every type holds what the clients expect of it, see
+//! [CollabTypeSpec::check_structure].
+//!
+//! [CollabTypeSpec::default_encoded_collab]: crate::collab_registry::CollabTypeSpec::default_encoded_collab
+//! [CollabTypeSpec::check_structure]: crate::collab_registry::CollabTypeSpec::check_structure
+use anyhow::anyhow;
+use collab::core::origin::CollabOrigin;
+use collab::entity::EncodedCollab;
+use collab::preclude::Collab;
+use collab_database::entity::{CreateDatabaseParams, CreateViewParams, FieldType};
+use collab_database::fields::{default_field_settings_for_fields, Field};
+use collab_database::views::DatabaseLayout;
+use collab_database::workspace_database::WorkspaceDatabase;
+use collab_entity::CollabType;
+use collab_folder::{
+  timestamp, Folder, FolderData, RepeatedViewIdentifier, ViewIdentifier, Workspace,
+};
+use collab_user::core::UserAwareness;
+
+use crate::collab_registry::collab_type_spec;
+use crate::database::database_collab::create_database_collab;
+use crate::gen_view_id;
+use crate::hierarchy_builder::{FlattedViews, ViewBuilder};
+
+pub const DEFAULT_WORKSPACE_NAME: &str = "Workspace";
+pub const DEFAULT_SPACE_NAME: &str = "General";
+
+/// The default state of a collab of the workspace. The folder, whose object id is the workspace
+/// id, is the root view of the workspace holding a space, recorded as created by the uid 0: the
+/// folder of a new workspace is built by [default_folder_data] for its owner instead. A database
+/// has a grid view with a primary field.
+pub async fn default_collab_data(
+  object_id: &str,
+  collab_type: &CollabType,
+  workspace_id: &str,
+) -> anyhow::Result<EncodedCollab> {
+  match collab_type {
+    CollabType::Folder => {
+      if object_id != workspace_id {
+        return Err(anyhow!(
+          "the folder {} must have the id of its workspace {}",
+          object_id,
+          workspace_id
+        ));
+      }
+      default_folder_data(0, workspace_id, DEFAULT_WORKSPACE_NAME)
+    },
+    CollabType::Database => default_database_data(object_id).await,
+    CollabType::WorkspaceDatabase => default_workspace_database_data(object_id, vec![]),
+    CollabType::UserAwareness => default_user_awareness_data(object_id),
+    _ => collab_type_spec(collab_type).default_encoded_collab(object_id),
+  }
+}
+
+/// The folder of a new workspace: the root view of the workspace with an empty space, which is
+/// the current view.
+pub fn default_folder_data(
+  uid: i64,
+  workspace_id: &str,
+  workspace_name: &str,
+) -> anyhow::Result<EncodedCollab> {
+  let created_at = timestamp();
+  let space = ViewBuilder::new(uid, workspace_id.to_string())
+    .with_name(DEFAULT_SPACE_NAME)
+    .with_extra(&format!(
+      "{{\"is_space\":true,\"space_icon\":\"interface_essential/home-3\",\"space_icon_color\":\"0xFFA34AFD\",\"space_permission\":0,\"space_created_at\":{}}}",
+      created_at
+    ))
+    .build();
+  let space_id = space.parent_view.id.clone();
+  let workspace = Workspace {
+    id: workspace_id.to_string(),
+    name: workspace_name.to_string(),
+    child_views: RepeatedViewIdentifier::new(vec![ViewIdentifier {
+      id: space_id.clone(),
+    }]),
+    created_at,
+    created_by: Some(uid),
+    last_edited_time: created_at,
+    last_edited_by: Some(uid),
+  };
+  let folder_data = FolderData {
+    workspace,
+    current_view: space_id,
+    views: FlattedViews::flatten_views(vec![space]),
+    favorites: Default::default(),
+    recent: Default::default(),
+    trash: Default::default(),
+    private: Default::default(),
+  };
+  let collab = Collab::new_with_origin(CollabOrigin::Empty, workspace_id, vec![], false);
+  let folder = Folder::create(uid, collab, None, folder_data);
+  Ok(folder.encode_collab()?)
+}
+
+/// A database whose only view is an empty grid, with the primary field the clients expect.
+pub async fn default_database_data(database_id: &str) -> anyhow::Result<EncodedCollab> {
+  let fields = vec![Field::from_field_type("Name", FieldType::RichText, true)];
+  let field_settings = default_field_settings_for_fields(&fields, DatabaseLayout::Grid);
+  let created_at = collab_database::database::timestamp();
+  let params = CreateDatabaseParams {
+    database_id: database_id.to_string(),
+    fields,
+    rows: vec![],
+    views: vec![CreateViewParams {
+      database_id: database_id.to_string(),
+      view_id: gen_view_id(),
+      name: "Grid".to_string(),
+      layout: DatabaseLayout::Grid,
+      field_settings,
+      created_at,
+      modified_at: created_at,
+      ..Default::default()
+    }],
+  };
+  let encoded_database = create_database_collab(params).await?;
+  Ok(encoded_database.encoded_database_collab.encoded_collab)
+}
+
+/// The workspace database of a new workspace, listing the given databases as pairs of the id of
+/// their view and of the database.
+pub fn default_workspace_database_data(
+  object_id: &str,
+  databases: Vec<(String, String)>,
+) -> anyhow::Result<EncodedCollab> {
+  let collab = Collab::new_with_origin(CollabOrigin::Empty, object_id, vec![], false);
+  let mut workspace_database = WorkspaceDatabase::create(collab);
+  for (view_id, database_id) in databases {
+    workspace_database.add_database(&database_id, vec![view_id]);
+  }
+  Ok(workspace_database.encode_collab_v1()?)
+}
+
+pub fn default_user_awareness_data(object_id: &str) -> anyhow::Result<EncodedCollab> {
+  let collab = Collab::new_with_origin(CollabOrigin::Empty, object_id, vec![], false);
+  let user_awareness = UserAwareness::create(collab, None)?;
+  let encoded_collab = user_awareness
+    .encode_collab_v1(|collab| CollabType::UserAwareness.validate_require_data(collab))?;
+  Ok(encoded_collab)
+}
@@ -14,7 +14,9 @@ use collab_folder::{
 
 use crate::hierarchy_builder::{FlattedViews, WorkspaceViewBuilder};
 
+pub mod collab_registry;
 pub mod database;
+pub mod default_collab;
 pub mod document;
 
 mod hierarchy_builder;
@@ -165,3 +167,35 @@ impl WorkspaceTemplateBuilder {
 pub fn gen_view_id() -> String {
   uuid::Uuid::new_v4().to_string()
 }
+
+/// Derives the ids of the objects created when content is instantiated into a workspace, for
+/// example when a template or a published page is duplicated into it.
+///
+/// The id of a new object is a UUIDv5 of the destination workspace, the id of the object it was
+/// copied from and the nonce of the instantiation. Retrying an instantiation with the same nonce
+/// produces the same ids, so the objects created by a previous attempt are overwritten instead of
+/// being duplicated, while a new instantiation uses a new nonce and gets fresh ids.
+#[derive(Debug, Clone)]
+pub struct ObjectIdDeriver {
+  workspace_id: String,
+  nonce: String,
+}
+
+impl ObjectIdDeriver {
+  pub fn new(workspace_id: impl Into<String>, nonce: impl Into<String>) -> Self {
+    Self {
+      workspace_id: workspace_id.into(),
+      nonce: nonce.into(),
+    }
+  }
+
+  /// Used when the caller didn't provide a nonce, every instantiation gets fresh ids.
+  pub fn with_random_nonce(workspace_id: impl Into<String>) -> Self {
+    Self::new(workspace_id, uuid::Uuid::new_v4().to_string())
+  }
+
+  pub fn derive(&self, source_id: &str) -> String {
+    let name = format!("{}:{}:{}", self.workspace_id, source_id, self.nonce);
+    uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_OID, name.as_bytes()).to_string()
+  }
+}
@@ -0,0 +1,133 @@
+use collab::core::collab::DataSource;
+use collab::core::origin::CollabOrigin;
+use collab::preclude::Collab;
+use collab_entity::CollabType;
+use yrs::{ArrayPrelim, Map, MapPrelim, MapRef};
+
+use crate::collab_registry::{collab_spec, collab_type_spec};
+
+fn open(object_id: &str, doc_state: Vec<u8>) -> Collab {
+  Collab::new_with_source(
+    CollabOrigin::Empty,
+    object_id,
+    DataSource::DocStateV1(doc_state),
+    vec![],
+    false,
+  )
+  .unwrap()
+}
+
+#[test]
+fn default_document_passes_validation_and_has_text_test() {
+  let spec = collab_type_spec(&CollabType::Document);
+  let encoded = spec.default_encoded_collab("doc").unwrap();
+  let collab = open("doc", encoded.doc_state.to_vec());
+  spec.validate(&collab).unwrap();
+  assert!(spec.supports_text());
+  assert!(spec.extract_text(&collab).is_some());
+  spec
+    .validate_encoded("doc", &encoded.encode_to_bytes().unwrap())
+    .unwrap();
+}
+
+#[test]
+fn types_without_default_state_degrade_to_an_empty_collab_test() {
+  for collab_type in [CollabType::Folder, CollabType::Unknown] {
+    let spec = collab_type_spec(&collab_type);
+    assert!(!spec.has_default_state());
+    let encoded = spec.default_encoded_collab("object").unwrap();
+    let collab = open("object", encoded.doc_state.to_vec());
+    assert!(spec.extract_text(&collab).is_none());
+  }
+  // an empty collab is not a folder, but it's accepted for an unknown type
+  let empty = collab_type_spec(&CollabType::Unknown)
+    .default_encoded_collab("object")
+    .unwrap();
+  let collab = open("object", empty.doc_state.to_vec());
+  assert!(collab_type_spec(&CollabType::Folder)
+    .validate(&collab)
+    .is_err());
+  assert!(collab_type_spec(&CollabType::Unknown)
+    .validate(&collab)
+    .is_ok());
+}
+
+#[test]
+fn oversized_collab_is_rejected_test() {
+  let spec = collab_type_spec(&CollabType::DatabaseRow);
+  assert!(spec.check_size(spec.max_encoded_len).is_ok());
+  assert!(spec.check_size(spec.max_encoded_len + 1).is_err());
+}
+
+#[test]
+fn opaque_collab_is_only_checked_for_size_test() {
+  let spec = collab_spec(&CollabType::Document, true);
+  assert!(spec.is_opaque());
+  assert!(!spec.supports_text());
+  // encrypted bytes are not an encoded collab
+  assert!(spec.validate_encoded("doc", &[7; 64]).is_ok());
+  assert!(collab_spec(&CollabType::Document, false)
+    .validate_encoded("doc", &[7; 64])
+    .is_err());
+  assert!(spec
+    .validate_encoded("doc", &vec![7; spec.max_encoded_len + 1])
+    .is_err());
+}
+
+#[test]
+fn document_written_as_a_folder_is_rejected_test() {
+  let document = collab_type_spec(&CollabType::Document);
+  let encoded = document.default_encoded_collab("object").unwrap();
+  let collab = open("object", encoded.doc_state.to_vec());
+  document.check_structure(&collab).unwrap();
+
+  let err = collab_type_spec(&CollabType::Folder)
+    .check_structure(&collab)
+    .unwrap_err();
+  assert_eq!(err.collab_type, CollabType::Folder);
+  assert_eq!(err.missing, vec!["folder".to_string()]);
+  assert_eq!(
+    collab_type_spec(&CollabType::WorkspaceDatabase)
+      .check_structure(&collab)
+      .unwrap_err()
+      .missing,
+    vec!["databases".to_string()]
+  );
+}
+
+#[test]
+fn missing_parts_of_the_structure_are_listed_test() {
+  let mut collab = Collab::new_with_origin(CollabOrigin::Empty, "object", vec![], false);
+  {
+    let mut txn = collab.context.transact_mut();
+    let document: MapRef = collab
+      .data
+      .insert(&mut txn, "document", MapPrelim::default());
+    document.insert(&mut txn, "page_id", "page");
+    document.insert(&mut txn, "blocks", MapPrelim::default());
+    let folder: MapRef = collab.data.insert(&mut txn, "folder", MapPrelim::default());
+    let meta: MapRef = folder.insert(&mut txn, "meta", MapPrelim::default());
+    meta.insert(&mut txn, "current_workspace", "workspace");
+    folder.insert(&mut txn, "views", MapPrelim::default());
+    collab
+      .data
+      .insert(&mut txn, "databases", ArrayPrelim::default());
+  }
+  let missing = |collab_type: CollabType| {
+    collab_type_spec(&collab_type)
+      .check_structure(&collab)
+      .map_or_else(|err| err.missing, |_| vec![])
+  };
+  assert_eq!(
+    missing(CollabType::Document),
+    vec!["document.meta", "document.blocks.page"]
+  );
+  assert_eq!(missing(CollabType::Folder), vec!["folder.views.workspace"]);
+  assert_eq!(missing(CollabType::Database), vec!["database".to_string()]);
+  assert!(missing(CollabType::WorkspaceDatabase).is_empty());
+  // the types without a known structure accept any collab
+  assert!(missing(CollabType::Unknown).is_empty());
+  assert!(collab_spec(&CollabType::Folder, true)
+    .check_structure(&collab)
+    .is_ok());
+}
@@ -0,0 +1,51 @@
+use collab::core::collab::DataSource;
+use collab::core::origin::CollabOrigin;
+use collab::preclude::Collab;
+use collab_entity::CollabType;
+
+use crate::collab_registry::collab_type_spec;
+use crate::default_collab::default_collab_data;
+
+#[tokio::test]
+async fn default_collab_data_passes_validation_test() {
+  let workspace_id = uuid::Uuid::new_v4().to_string();
+  for collab_type in [
+    CollabType::Document,
+    CollabType::Database,
+    CollabType::WorkspaceDatabase,
+    CollabType::Folder,
+    CollabType::UserAwareness,
+    CollabType::Unknown,
+  ] {
+    let object_id = if collab_type == CollabType::Folder {
+      workspace_id.clone()
+    } else {
+      uuid::Uuid::new_v4().to_string()
+    };
+    let encoded = default_collab_data(&object_id, &collab_type, &workspace_id)
+      .await
+      .unwrap();
+    let collab = Collab::new_with_source(
+      CollabOrigin::Empty,
+      &object_id,
+      DataSource::DocStateV1(encoded.doc_state.to_vec()),
+      vec![],
+      false,
+    )
+    .unwrap();
+    let spec = collab_type_spec(&collab_type);
+    spec.validate(&collab).unwrap();
+    spec.check_structure(&collab).unwrap();
+  }
+}
+
+#[tokio::test]
+async fn default_folder_must_have_the_workspace_id_test() {
+  let workspace_id = uuid::Uuid::new_v4().to_string();
+  let object_id = uuid::Uuid::new_v4().to_string();
+  assert!(
+    default_collab_data(&object_id, &CollabType::Folder, &workspace_id)
+      .await
+      .is_err()
+  );
+}
@@ -1 +1,4 @@
+mod collab_registry_tests;
+mod default_collab_tests;
 mod getting_started_tests;
+mod object_id_tests;
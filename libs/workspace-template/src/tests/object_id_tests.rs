@@ -0,0 +1,35 @@
+use crate::ObjectIdDeriver;
+
+#[test]
+fn derived_object_id_is_stable_across_retries_test() {
+  let workspace_id = uuid::Uuid::new_v4().to_string();
+  let source_id = uuid::Uuid::new_v4().to_string();
+  let first = ObjectIdDeriver::new(&workspace_id, "nonce");
+  let retry = ObjectIdDeriver::new(&workspace_id, "nonce");
+  assert_eq!(first.derive(&source_id), retry.derive(&source_id));
+  assert!(uuid::Uuid::parse_str(&first.derive(&source_id)).is_ok());
+}
+
+#[test]
+fn derived_object_id_depends_on_every_input_test() {
+  let workspace_id = uuid::Uuid::new_v4().to_string();
+  let other_workspace_id = uuid::Uuid::new_v4().to_string();
+  let source_id = "source";
+  let id = ObjectIdDeriver::new(&workspace_id, "nonce").derive(source_id);
+  assert_ne!(
+    id,
+    ObjectIdDeriver::new(&workspace_id, "nonce").derive("other source")
+  );
+  assert_ne!(
+    id,
+    ObjectIdDeriver::new(&workspace_id, "other nonce").derive(source_id)
+  );
+  assert_ne!(
+    id,
+    ObjectIdDeriver::new(&other_workspace_id, "nonce").derive(source_id)
+  );
+  assert_ne!(
+    ObjectIdDeriver::with_random_nonce(&workspace_id).derive(source_id),
+    ObjectIdDeriver::with_random_nonce(&workspace_id).derive(source_id)
+  );
+}
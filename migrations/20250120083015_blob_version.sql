@@ -0,0 +1,11 @@
+-- Previous revisions of a blob, kept when an existing file id is overwritten.
+CREATE TABLE IF NOT EXISTS af_blob_version (
+  workspace_id UUID NOT NULL REFERENCES af_workspace (workspace_id) ON DELETE CASCADE,
+  file_id VARCHAR NOT NULL,
+  version BIGINT NOT NULL,
+  object_key VARCHAR NOT NULL,
+  file_type VARCHAR NOT NULL,
+  file_size BIGINT NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  PRIMARY KEY (workspace_id, file_id, version)
+);
@@ -0,0 +1,36 @@
+-- Asynchronous "export everything" jobs requested by workspace owners.
+CREATE TABLE IF NOT EXISTS af_workspace_export (
+  task_id UUID NOT NULL PRIMARY KEY,
+  workspace_id UUID NOT NULL REFERENCES af_workspace (workspace_id) ON DELETE CASCADE,
+  created_by BIGINT NOT NULL REFERENCES af_user (uid) ON DELETE CASCADE,
+  status SMALLINT NOT NULL DEFAULT 0,   -- 0: pending, 1: running, 2: completed, 3: failed, 4: expired
+  stage SMALLINT NOT NULL DEFAULT 0,    -- 0: queued, 1: collabs, 2: blobs, 3: uploading, 4: done
+  bytes_written BIGINT NOT NULL DEFAULT 0,
+  s3_key TEXT,
+  download_url TEXT,
+  error TEXT,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  expires_at TIMESTAMP WITH TIME ZONE
+);
+
+-- Only one export per workspace may be pending or running at a time.
+CREATE UNIQUE INDEX IF NOT EXISTS uq_af_workspace_export_active
+ON af_workspace_export (workspace_id) WHERE status IN (0, 1);
+
+CREATE INDEX IF NOT EXISTS idx_af_workspace_export_status_expires_at
+ON af_workspace_export (status, expires_at);
+
+-- Per-user inbox notifications produced by background jobs.
+CREATE TABLE IF NOT EXISTS af_user_notification (
+  notification_id UUID NOT NULL DEFAULT gen_random_uuid () PRIMARY KEY,
+  uid BIGINT NOT NULL REFERENCES af_user (uid) ON DELETE CASCADE,
+  workspace_id UUID REFERENCES af_workspace (workspace_id) ON DELETE CASCADE,
+  kind TEXT NOT NULL,
+  payload JSONB NOT NULL DEFAULT '{}',
+  is_read BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_uid_created_at_on_af_user_notification
+ON af_user_notification (uid, created_at DESC);
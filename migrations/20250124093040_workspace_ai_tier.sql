@@ -0,0 +1,7 @@
+-- Subscription plan of a workspace as seen by the AI request scheduler. Rows are maintained by
+-- the billing service; workspaces without a row are scheduled as free workspaces.
+CREATE TABLE IF NOT EXISTS af_workspace_ai_tier (
+  workspace_id UUID NOT NULL PRIMARY KEY REFERENCES af_workspace (workspace_id) ON DELETE CASCADE,
+  plan SMALLINT NOT NULL DEFAULT 0,   -- same values as SubscriptionPlan: 0 free, 1 pro, 2 team, 3 ai max, 4 ai local
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
@@ -0,0 +1,16 @@
+-- Dry-run reports of destructive maintenance jobs. The selection is kept so that a follow-up
+-- execution can delete exactly what the dry run reported.
+CREATE TABLE IF NOT EXISTS af_maintenance_job_report (
+  report_id UUID NOT NULL DEFAULT gen_random_uuid () PRIMARY KEY,
+  workspace_id UUID NOT NULL REFERENCES af_workspace (workspace_id) ON DELETE CASCADE,
+  job TEXT NOT NULL,
+  created_by BIGINT NOT NULL REFERENCES af_user (uid) ON DELETE CASCADE,
+  selection_hash TEXT NOT NULL,
+  report JSONB NOT NULL,
+  selection JSONB NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  executed_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_maintenance_job_report_workspace_job
+ON af_maintenance_job_report (workspace_id, job, created_at DESC);
@@ -0,0 +1,21 @@
+-- Per-object notification preference of a user. A missing row means the default level, where the
+-- user is notified about the objects they participate in.
+CREATE TABLE IF NOT EXISTS af_notification_subscription (
+  uid BIGINT NOT NULL REFERENCES af_user (uid) ON DELETE CASCADE,
+  workspace_id UUID NOT NULL REFERENCES af_workspace (workspace_id) ON DELETE CASCADE,
+  object_id UUID NOT NULL,
+  -- 1: watch, -1: mute
+  level SMALLINT NOT NULL,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  PRIMARY KEY (uid, object_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_notification_subscription_object_id
+ON af_notification_subscription (object_id);
+
+CREATE TABLE IF NOT EXISTS af_user_notification_setting (
+  uid BIGINT NOT NULL PRIMARY KEY REFERENCES af_user (uid) ON DELETE CASCADE,
+  -- when set, muting an object also silences the mentions of the user in it
+  mute_mentions BOOLEAN NOT NULL DEFAULT FALSE,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
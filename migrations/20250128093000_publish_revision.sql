@@ -0,0 +1,27 @@
+-- The published content of a view is a revision captured when the view was published. Edits to
+-- the view don't change it until the view is published again.
+ALTER TABLE af_published_collab
+  ADD COLUMN IF NOT EXISTS revision_etag TEXT GENERATED ALWAYS AS (encode(sha256(blob), 'hex')) STORED,
+  -- Digest of the state vectors of the collabs the revision was generated from. It's null when the
+  -- revision was uploaded by a client.
+  ADD COLUMN IF NOT EXISTS source_fingerprint TEXT,
+  ADD COLUMN IF NOT EXISTS revision_created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+-- Revisions replacing the published revision of a view at `publish_at`. The appflowy worker
+-- applies them once they are due.
+CREATE TABLE IF NOT EXISTS af_scheduled_publish (
+  workspace_id UUID NOT NULL,
+  view_id UUID NOT NULL,
+  scheduled_by BIGINT NOT NULL REFERENCES af_user(uid) ON DELETE CASCADE,
+  metadata JSONB NOT NULL,
+  blob BYTEA NOT NULL,
+  source_fingerprint TEXT,
+  publish_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (workspace_id, view_id),
+  FOREIGN KEY (workspace_id, view_id)
+    REFERENCES af_published_collab (workspace_id, view_id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_scheduled_publish_publish_at
+  ON af_scheduled_publish (publish_at);
@@ -0,0 +1,35 @@
+-- Reports of published views, sent by their readers and reviewed by admins.
+CREATE TABLE IF NOT EXISTS af_published_view_report (
+  report_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  workspace_id UUID NOT NULL,
+  view_id UUID NOT NULL,
+  -- The namespace and name the view was reported under, kept after the view is unpublished.
+  publish_namespace TEXT NOT NULL,
+  publish_name TEXT NOT NULL,
+  reason SMALLINT NOT NULL,
+  details TEXT,
+  reporter_email TEXT,
+  -- 0: pending, 1: dismissed, 2: taken down
+  status SMALLINT NOT NULL DEFAULT 0,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  resolved_at TIMESTAMP WITH TIME ZONE,
+  resolved_by BIGINT REFERENCES af_user(uid) ON DELETE SET NULL,
+  resolution_note TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_published_view_report_status_created_at
+  ON af_published_view_report (status, created_at);
+CREATE INDEX IF NOT EXISTS idx_af_published_view_report_namespace_created_at
+  ON af_published_view_report (publish_namespace, created_at);
+CREATE INDEX IF NOT EXISTS idx_af_published_view_report_view_id
+  ON af_published_view_report (view_id);
+
+-- Views taken down by a moderator. They can't be published again while they are listed here.
+CREATE TABLE IF NOT EXISTS af_published_view_takedown (
+  view_id UUID PRIMARY KEY,
+  workspace_id UUID NOT NULL,
+  report_id UUID REFERENCES af_published_view_report(report_id) ON DELETE SET NULL,
+  taken_down_by BIGINT REFERENCES af_user(uid) ON DELETE SET NULL,
+  note TEXT,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,12 @@
+-- Bytes exchanged with the realtime clients of a workspace, per day (UTC).
+CREATE TABLE IF NOT EXISTS af_workspace_realtime_bandwidth (
+  workspace_id UUID NOT NULL REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+  day DATE NOT NULL,
+  bytes_in BIGINT NOT NULL DEFAULT 0,
+  bytes_out BIGINT NOT NULL DEFAULT 0,
+  updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (workspace_id, day)
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_workspace_realtime_bandwidth_day
+  ON af_workspace_realtime_bandwidth (day);
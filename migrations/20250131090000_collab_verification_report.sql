@@ -0,0 +1,25 @@
+-- Collabs whose stored state could not be decoded. While a report is not repaired, the collab is
+-- served from the snapshot it was recovered from and can't be edited.
+CREATE TABLE IF NOT EXISTS af_collab_verification_report (
+  report_id BIGSERIAL PRIMARY KEY,
+  workspace_id UUID NOT NULL REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+  object_id TEXT NOT NULL,
+  collab_type INT NOT NULL,
+  -- Where the corruption was found: 'http' or 'realtime'.
+  detected_on TEXT NOT NULL,
+  error TEXT NOT NULL,
+  -- The snapshot the collab was recovered from, if any could be decoded.
+  recovered_snapshot_id BIGINT,
+  recovered_snapshot_at TIMESTAMP WITH TIME ZONE,
+  detection_count INT NOT NULL DEFAULT 1,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  last_detected_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  repaired_at TIMESTAMP WITH TIME ZONE,
+  repaired_by BIGINT REFERENCES af_user(uid) ON DELETE SET NULL
+);
+
+-- A collab has at most one report waiting for repair, detecting it again only updates it.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_af_collab_verification_report_unrepaired
+  ON af_collab_verification_report (object_id) WHERE repaired_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_af_collab_verification_report_created_at
+  ON af_collab_verification_report (created_at);
@@ -0,0 +1,11 @@
+-- The inbound email address of a workspace. Emails sent to it are appended to the page it targets.
+-- Rotating the address replaces the token, the previous address stops accepting emails.
+CREATE TABLE IF NOT EXISTS af_workspace_inbound_email (
+  workspace_id UUID PRIMARY KEY REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+  view_id UUID NOT NULL,
+  token TEXT NOT NULL UNIQUE,
+  -- The emails are appended on behalf of this user.
+  created_by BIGINT NOT NULL REFERENCES af_user(uid) ON DELETE CASCADE,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  rotated_at TIMESTAMP WITH TIME ZONE
+);
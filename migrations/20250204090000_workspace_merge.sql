@@ -0,0 +1,26 @@
+-- Jobs moving the content of a source workspace into a target workspace.
+CREATE TABLE IF NOT EXISTS af_workspace_merge (
+  task_id UUID NOT NULL PRIMARY KEY,
+  target_workspace_id UUID NOT NULL REFERENCES af_workspace (workspace_id) ON DELETE CASCADE,
+  source_workspace_id UUID NOT NULL REFERENCES af_workspace (workspace_id) ON DELETE CASCADE,
+  created_by BIGINT NOT NULL REFERENCES af_user (uid) ON DELETE CASCADE,
+  status SMALLINT NOT NULL DEFAULT 0,   -- 0: pending, 1: running, 2: completed, 3: failed
+  stage SMALLINT NOT NULL DEFAULT 0,    -- 0: queued, 1: collabs, 2: folder, 3: blobs, 4: archive, 5: done
+  moved_collabs BIGINT NOT NULL DEFAULT 0,
+  copied_blobs BIGINT NOT NULL DEFAULT 0,
+  report JSONB NOT NULL DEFAULT '{}',
+  error TEXT,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- A workspace can only take part in one pending or running merge at a time.
+CREATE UNIQUE INDEX IF NOT EXISTS uq_af_workspace_merge_active_target
+ON af_workspace_merge (target_workspace_id) WHERE status IN (0, 1);
+CREATE UNIQUE INDEX IF NOT EXISTS uq_af_workspace_merge_active_source
+ON af_workspace_merge (source_workspace_id) WHERE status IN (0, 1);
+
+-- A merged workspace is archived and points at the workspace its content was moved to.
+ALTER TABLE af_workspace
+ADD COLUMN IF NOT EXISTS merged_into UUID REFERENCES af_workspace (workspace_id) ON DELETE SET NULL,
+ADD COLUMN IF NOT EXISTS archived_at TIMESTAMP WITH TIME ZONE;
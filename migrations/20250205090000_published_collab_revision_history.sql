@@ -0,0 +1,38 @@
+-- Revisions of a published view which were replaced by a newer one. They stay servable from their
+-- revision-addressed URL for a grace period, so pages cached by a CDN before the view was published
+-- again can still load their assets.
+CREATE TABLE IF NOT EXISTS af_published_collab_revision (
+  workspace_id UUID NOT NULL,
+  view_id UUID NOT NULL,
+  revision_etag TEXT NOT NULL,
+  blob BYTEA NOT NULL,
+  superseded_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (view_id, revision_etag)
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_published_collab_revision_superseded_at
+  ON af_published_collab_revision (superseded_at);
+
+-- Keep the replaced revision when a published view is published again, publishing either updates
+-- the row of the view or deletes it before inserting it again. Unpublishing a view drops its past
+-- revisions right away: unpublished content must not be served anymore.
+CREATE OR REPLACE FUNCTION af_published_collab_revision_history_fn()
+RETURNS TRIGGER AS $$
+BEGIN
+  IF TG_OP = 'UPDATE' AND NEW.unpublished_at IS NOT NULL THEN
+    DELETE FROM af_published_collab_revision WHERE view_id = NEW.view_id;
+  ELSIF OLD.unpublished_at IS NULL
+    AND (TG_OP = 'DELETE' OR OLD.revision_etag IS DISTINCT FROM NEW.revision_etag) THEN
+    INSERT INTO af_published_collab_revision (workspace_id, view_id, revision_etag, blob)
+    VALUES (OLD.workspace_id, OLD.view_id, OLD.revision_etag, OLD.blob)
+    ON CONFLICT (view_id, revision_etag) DO UPDATE SET superseded_at = CURRENT_TIMESTAMP;
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS af_published_collab_revision_history_trigger ON af_published_collab;
+CREATE TRIGGER af_published_collab_revision_history_trigger
+AFTER UPDATE OF blob, unpublished_at OR DELETE ON af_published_collab
+FOR EACH ROW
+EXECUTE FUNCTION af_published_collab_revision_history_fn();
@@ -0,0 +1,49 @@
+-- The sessions of the users. A session is the GoTrue session the access tokens refreshed with
+-- the same refresh token share, it's recorded the first time the server sees one of them.
+CREATE TABLE IF NOT EXISTS af_user_session (
+  session_id UUID PRIMARY KEY,
+  uid BIGINT NOT NULL REFERENCES af_user(uid) ON DELETE CASCADE,
+  device_id TEXT,
+  client_version TEXT,
+  user_agent TEXT,
+  ip_address TEXT,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  last_seen_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  revoked_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_user_session_uid ON af_user_session (uid, last_seen_at DESC);
+
+-- Audit trail of the revoked sessions. It's kept when the user is deleted.
+CREATE TABLE IF NOT EXISTS af_user_session_revocation (
+  id BIGSERIAL PRIMARY KEY,
+  session_id UUID NOT NULL,
+  uid BIGINT NOT NULL,
+  revoked_by BIGINT NOT NULL,
+  -- True when an admin of the instance revoked the session, false when the user did.
+  revoked_by_admin BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_user_session_revocation_uid
+  ON af_user_session_revocation (uid, created_at DESC);
+
+-- Tell every instance of the server that a session was revoked, so that they close its realtime
+-- connections and reject its access tokens.
+CREATE OR REPLACE FUNCTION notify_af_user_session_revoked() RETURNS TRIGGER AS $$
+BEGIN
+  IF OLD.revoked_at IS NULL AND NEW.revoked_at IS NOT NULL THEN
+    PERFORM pg_notify(
+      'af_user_session_channel',
+      json_build_object('session_id', NEW.session_id, 'uid', NEW.uid)::text
+    );
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS af_user_session_revoked_trigger ON af_user_session;
+CREATE TRIGGER af_user_session_revoked_trigger
+AFTER UPDATE OF revoked_at ON af_user_session
+FOR EACH ROW
+EXECUTE FUNCTION notify_af_user_session_revoked();
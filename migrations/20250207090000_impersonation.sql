@@ -0,0 +1,32 @@
+-- Impersonations of users by the admins of the instance, for support to see what a user sees.
+-- The rows are the audit trail of the impersonations, they're kept when the user is deleted.
+CREATE TABLE IF NOT EXISTS af_impersonation (
+  impersonation_id UUID PRIMARY KEY,
+  admin_uid BIGINT NOT NULL,
+  uid BIGINT NOT NULL,
+  reason TEXT NOT NULL,
+  -- Set once the admin is granted to write as the user, reads only are allowed until then.
+  write_reason TEXT,
+  write_granted_at TIMESTAMP WITH TIME ZONE,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  revoked_at TIMESTAMP WITH TIME ZONE,
+  revoked_by BIGINT
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_impersonation_active
+  ON af_impersonation (expires_at) WHERE revoked_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_af_impersonation_uid ON af_impersonation (uid, created_at DESC);
+
+-- Every request made under an impersonation, including the rejected ones.
+CREATE TABLE IF NOT EXISTS af_impersonation_request (
+  id BIGSERIAL PRIMARY KEY,
+  impersonation_id UUID NOT NULL REFERENCES af_impersonation(impersonation_id) ON DELETE CASCADE,
+  method TEXT NOT NULL,
+  path TEXT NOT NULL,
+  status SMALLINT NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_impersonation_request_impersonation_id
+  ON af_impersonation_request (impersonation_id, created_at DESC);
@@ -0,0 +1,14 @@
+-- Fingerprints of the text of the documents, computed when they're indexed, to find the
+-- documents which are near duplicates of each other.
+CREATE TABLE IF NOT EXISTS af_collab_fingerprint (
+  oid TEXT PRIMARY KEY,
+  workspace_id UUID NOT NULL REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+  -- 64 bits simhash of the text, see the indexer for how it's computed.
+  simhash BIGINT NOT NULL,
+  -- Length of the text, in characters.
+  text_len INTEGER NOT NULL,
+  updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_collab_fingerprint_workspace_id
+  ON af_collab_fingerprint (workspace_id);
@@ -0,0 +1,14 @@
+-- Row-level access rules of the database collabs. When the rows of a database are restricted,
+-- the members of the workspace only see the rows whose `field_id` cell names them, by email or
+-- by name. The owners of the workspace and the author of the rule see every row.
+CREATE TABLE IF NOT EXISTS af_database_row_access (
+  database_id UUID PRIMARY KEY,
+  workspace_id UUID NOT NULL REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+  field_id TEXT NOT NULL,
+  restricted_rows BOOLEAN NOT NULL DEFAULT FALSE,
+  created_by BIGINT NOT NULL REFERENCES af_user(uid) ON DELETE CASCADE,
+  updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_database_row_access_workspace_id
+  ON af_database_row_access (workspace_id);
@@ -0,0 +1,8 @@
+-- Version of the last collab content migration applied to each collab, see the collab
+-- migrations registered in appflowy-collaborate.
+ALTER TABLE af_collab
+ADD COLUMN migration_version INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_af_collab_migration_version
+ON af_collab (partition_key, migration_version, oid)
+WHERE deleted_at IS NULL;
@@ -0,0 +1,35 @@
+-- The user who created the chat. Chats created before the column was added are attributed to the
+-- author of their first question, and stay shared with their whole workspace as they were before.
+ALTER TABLE af_chat
+    ADD COLUMN owner_uid    BIGINT REFERENCES af_user (uid) ON DELETE SET NULL,
+    -- 0: private, 1: shared with the workspace, 2: shared with the members of af_chat_share_member
+    ADD COLUMN share_scope  SMALLINT NOT NULL DEFAULT 0,
+    -- whether the members the chat is shared with can ask questions too
+    ADD COLUMN collaborative BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN shared_at    TIMESTAMP WITH TIME ZONE DEFAULT NULL;
+
+UPDATE af_chat c
+SET owner_uid = (
+        SELECT (m.author ->> 'author_id')::BIGINT
+        FROM af_chat_messages m
+        JOIN af_user u ON u.uid = (m.author ->> 'author_id')::BIGINT
+        WHERE m.chat_id = c.chat_id AND (m.author ->> 'author_type')::INT = 1
+        ORDER BY m.message_id
+        LIMIT 1
+    ),
+    share_scope   = 1,
+    collaborative = TRUE,
+    shared_at     = c.created_at;
+
+CREATE TABLE IF NOT EXISTS af_chat_share_member
+(
+    chat_id UUID   NOT NULL REFERENCES af_chat (chat_id) ON DELETE CASCADE,
+    uid     BIGINT NOT NULL REFERENCES af_user (uid) ON DELETE CASCADE,
+    PRIMARY KEY (chat_id, uid)
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_chat_shared ON af_chat (workspace_id) WHERE share_scope <> 0;
+
+-- full text search over the questions and answers of the shared chats
+CREATE INDEX IF NOT EXISTS idx_af_chat_messages_content_fts
+    ON af_chat_messages USING GIN (to_tsvector('simple', content));
@@ -0,0 +1,9 @@
+-- Values of the feature flags set by the admins. The flags themselves are defined in code, a flag
+-- without a row here uses its default value.
+CREATE TABLE IF NOT EXISTS af_feature_flag
+(
+    name       TEXT PRIMARY KEY,
+    value      JSONB                    NOT NULL,
+    updated_by BIGINT REFERENCES af_user (uid) ON DELETE SET NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
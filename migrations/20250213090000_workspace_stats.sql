@@ -0,0 +1,64 @@
+-- Number and encoded size of the collabs of each workspace, per collab type. Only the workspaces
+-- in af_workspace_stats_state have been computed, the others are computed on their first read.
+CREATE TABLE IF NOT EXISTS af_workspace_stats
+(
+    workspace_id  UUID    NOT NULL REFERENCES af_workspace (workspace_id) ON DELETE CASCADE,
+    partition_key INTEGER NOT NULL,
+    object_count  BIGINT  NOT NULL DEFAULT 0,
+    total_bytes   BIGINT  NOT NULL DEFAULT 0,
+    PRIMARY KEY (workspace_id, partition_key)
+);
+
+CREATE TABLE IF NOT EXISTS af_workspace_stats_state
+(
+    workspace_id UUID PRIMARY KEY REFERENCES af_workspace (workspace_id) ON DELETE CASCADE,
+    computed_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Changes of af_collab not yet folded into af_workspace_stats. Appending them keeps the writes of
+-- af_collab from contending on the stats row of their workspace.
+CREATE TABLE IF NOT EXISTS af_workspace_stats_delta
+(
+    id            BIGSERIAL PRIMARY KEY,
+    workspace_id  UUID    NOT NULL,
+    partition_key INTEGER NOT NULL,
+    object_delta  INTEGER NOT NULL,
+    bytes_delta   BIGINT  NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_af_workspace_stats_delta_workspace
+    ON af_workspace_stats_delta (workspace_id);
+
+-- Daily copy of af_workspace_stats, used to compute the changes over a week.
+CREATE TABLE IF NOT EXISTS af_workspace_stats_snapshot
+(
+    workspace_id  UUID    NOT NULL REFERENCES af_workspace (workspace_id) ON DELETE CASCADE,
+    snapshot_date DATE    NOT NULL,
+    partition_key INTEGER NOT NULL,
+    object_count  BIGINT  NOT NULL,
+    total_bytes   BIGINT  NOT NULL,
+    PRIMARY KEY (workspace_id, snapshot_date, partition_key)
+);
+
+CREATE OR REPLACE FUNCTION af_collab_stats_delta()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') AND OLD.deleted_at IS NULL THEN
+        IF TG_OP = 'UPDATE' AND NEW.deleted_at IS NULL
+            AND COALESCE(OLD.len, 0) = COALESCE(NEW.len, 0) THEN
+            RETURN NULL;
+        END IF;
+        INSERT INTO af_workspace_stats_delta (workspace_id, partition_key, object_delta, bytes_delta)
+        VALUES (OLD.workspace_id, OLD.partition_key, -1, -COALESCE(OLD.len, 0));
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') AND NEW.deleted_at IS NULL THEN
+        INSERT INTO af_workspace_stats_delta (workspace_id, partition_key, object_delta, bytes_delta)
+        VALUES (NEW.workspace_id, NEW.partition_key, 1, COALESCE(NEW.len, 0));
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER af_collab_stats_delta_trigger
+AFTER INSERT OR UPDATE OF len, deleted_at OR DELETE ON public.af_collab
+FOR EACH ROW
+EXECUTE FUNCTION af_collab_stats_delta();
@@ -0,0 +1,30 @@
+-- Version of the access control policies, bumped by each change of the workspace members. The
+-- new version is announced on af_access_policy_version_channel so that every server reloads its
+-- policies.
+CREATE TABLE IF NOT EXISTS af_access_policy_version
+(
+    id         BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    version    BIGINT                   NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+INSERT INTO af_access_policy_version (id, version)
+VALUES (TRUE, 1)
+ON CONFLICT (id) DO NOTHING;
+
+CREATE OR REPLACE FUNCTION af_bump_access_policy_version() RETURNS trigger AS $$
+DECLARE
+    new_version BIGINT;
+BEGIN
+    UPDATE af_access_policy_version
+    SET version = version + 1, updated_at = NOW()
+    WHERE id
+    RETURNING version INTO new_version;
+    -- Notifications are only delivered on commit, once the new policies can be loaded.
+    PERFORM pg_notify('af_access_policy_version_channel', new_version::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER af_workspace_member_policy_version_trigger
+    AFTER INSERT OR UPDATE OF role_id, uid, workspace_id OR DELETE ON af_workspace_member
+    FOR EACH STATEMENT EXECUTE FUNCTION af_bump_access_policy_version();
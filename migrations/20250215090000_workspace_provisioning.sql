@@ -0,0 +1,16 @@
+-- Workspaces provisioned by a service account. A provisioning is identified by the id its caller
+-- supplies, so running the same provisioning script again returns the workspace created the first
+-- time instead of creating another one.
+CREATE TABLE IF NOT EXISTS af_workspace_provisioning (
+  created_by BIGINT NOT NULL REFERENCES af_user (uid) ON DELETE CASCADE,
+  external_id TEXT NOT NULL,
+  workspace_id UUID REFERENCES af_workspace (workspace_id) ON DELETE SET NULL,
+  spec JSONB NOT NULL,
+  status SMALLINT NOT NULL DEFAULT 0,   -- 0: pending, 1: running, 2: completed, 3: failed
+  members JSONB NOT NULL DEFAULT '[]',  -- the result of each member of the spec
+  template_view_id TEXT,                -- the root view of the instantiated template
+  error TEXT,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  PRIMARY KEY (created_by, external_id)
+);
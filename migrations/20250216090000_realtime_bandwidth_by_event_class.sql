@@ -0,0 +1,6 @@
+-- Bytes sent to the realtime clients for each class of events: collab updates, awareness updates,
+-- and notifications about the collab such as its editing lock. They're part of bytes_out.
+ALTER TABLE af_workspace_realtime_bandwidth
+  ADD COLUMN IF NOT EXISTS updates_out BIGINT NOT NULL DEFAULT 0,
+  ADD COLUMN IF NOT EXISTS awareness_out BIGINT NOT NULL DEFAULT 0,
+  ADD COLUMN IF NOT EXISTS control_out BIGINT NOT NULL DEFAULT 0;
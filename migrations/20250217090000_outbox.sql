@@ -0,0 +1,18 @@
+-- Side effects of the changes made in Postgres, such as purging the CDN or indexing a collab.
+-- They're inserted in the transaction of the change and dispatched by the appflowy worker, which
+-- retries them until they succeed.
+CREATE TABLE IF NOT EXISTS af_outbox (
+  id BIGSERIAL PRIMARY KEY,
+  topic TEXT NOT NULL,
+  payload JSONB NOT NULL,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  last_error TEXT,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  processed_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_outbox_pending
+  ON af_outbox (next_attempt_at) WHERE processed_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_af_outbox_processed
+  ON af_outbox (processed_at) WHERE processed_at IS NOT NULL;
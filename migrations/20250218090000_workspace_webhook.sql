@@ -0,0 +1,17 @@
+-- The endpoints notified of the membership events of a workspace. The deliveries are signed with
+-- the secret of the webhook and dispatched through af_outbox.
+CREATE TABLE IF NOT EXISTS af_workspace_webhook (
+  id UUID PRIMARY KEY,
+  workspace_id UUID NOT NULL REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+  url TEXT NOT NULL,
+  secret TEXT NOT NULL,
+  -- The event types delivered to the webhook, every type when empty.
+  event_types TEXT[] NOT NULL DEFAULT '{}',
+  -- Whether the events of a single change are delivered together rather than one by one.
+  batch BOOLEAN NOT NULL DEFAULT FALSE,
+  created_by BIGINT NOT NULL REFERENCES af_user(uid) ON DELETE CASCADE,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_workspace_webhook_workspace_id
+  ON af_workspace_webhook (workspace_id);
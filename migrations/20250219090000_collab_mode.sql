@@ -0,0 +1,14 @@
+-- The collabs which aren't stored as plain Yrs documents. An opaque collab is encrypted by the
+-- clients: the server stores and relays its updates as they are, without decoding them.
+-- The collabs without a row are plain.
+CREATE TABLE IF NOT EXISTS af_collab_mode (
+  oid TEXT PRIMARY KEY,
+  workspace_id UUID NOT NULL REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+  -- 1: opaque
+  mode SMALLINT NOT NULL,
+  created_by BIGINT NOT NULL REFERENCES af_user(uid) ON DELETE CASCADE,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_collab_mode_workspace_id
+  ON af_collab_mode (workspace_id);
@@ -0,0 +1,4 @@
+-- Lets the blob metadata of a workspace be listed page by page, in the order of
+-- get_workspace_blob_metadata_page.
+CREATE INDEX IF NOT EXISTS idx_af_blob_metadata_workspace_modified_at
+  ON af_blob_metadata (workspace_id, modified_at, file_id);
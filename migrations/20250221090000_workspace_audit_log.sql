@@ -0,0 +1,13 @@
+-- The actions on a workspace which its auditors may ask about, such as the exports of its member
+-- list. The rows are kept when the user who took the action is deleted.
+CREATE TABLE IF NOT EXISTS af_workspace_audit_log (
+  id BIGSERIAL PRIMARY KEY,
+  workspace_id UUID NOT NULL REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+  uid BIGINT NOT NULL,
+  action TEXT NOT NULL,
+  details JSONB NOT NULL DEFAULT '{}',
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_workspace_audit_log_workspace_id
+  ON af_workspace_audit_log (workspace_id, created_at DESC);
@@ -0,0 +1,19 @@
+-- Usage of each workspace per day (UTC), rolled up from the usage sources for billing: the bytes
+-- of its blobs, the AI tokens it consumed, its active members and the bytes it exchanged with
+-- the realtime clients. A day is rolled up again while its sources may still change, each rollup
+-- replacing the row of the previous one.
+CREATE TABLE IF NOT EXISTS af_workspace_usage_daily (
+  workspace_id UUID NOT NULL REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+  day DATE NOT NULL,
+  storage_bytes BIGINT NOT NULL DEFAULT 0,
+  ai_search_requests BIGINT NOT NULL DEFAULT 0,
+  ai_tokens BIGINT NOT NULL DEFAULT 0,
+  active_members BIGINT NOT NULL DEFAULT 0,
+  bandwidth_in BIGINT NOT NULL DEFAULT 0,
+  bandwidth_out BIGINT NOT NULL DEFAULT 0,
+  rolled_up_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (workspace_id, day)
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_workspace_usage_daily_day
+  ON af_workspace_usage_daily (day);
@@ -0,0 +1,21 @@
+-- The sha256 of the content of a blob, and the object storing it when the content is shared
+-- with other blobs of the workspace. A blob uploaded with the same content as an existing one
+-- only gets a metadata row pointing at the object of the first upload.
+ALTER TABLE af_blob_metadata
+  ADD COLUMN IF NOT EXISTS content_hash TEXT,
+  ADD COLUMN IF NOT EXISTS object_key TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_af_blob_metadata_content_hash
+  ON af_blob_metadata (workspace_id, content_hash)
+  WHERE content_hash IS NOT NULL;
+
+-- The objects holding a content shared by blobs of the workspace, with the number of blob
+-- metadata rows pointing at them. An object is deleted along with its last reference.
+CREATE TABLE IF NOT EXISTS af_blob_content (
+  workspace_id UUID NOT NULL REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+  content_hash TEXT NOT NULL,
+  object_key TEXT NOT NULL,
+  ref_count BIGINT NOT NULL DEFAULT 1,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (workspace_id, content_hash)
+);
@@ -0,0 +1,9 @@
+-- The refreshes of the sessions. The server sees a refresh as an access token of the session
+-- issued after the last one it saw.
+ALTER TABLE af_user_session
+  ADD COLUMN IF NOT EXISTS token_issued_at TIMESTAMP WITH TIME ZONE,
+  ADD COLUMN IF NOT EXISTS refresh_count BIGINT NOT NULL DEFAULT 0;
+
+-- Why a session was revoked, NULL when a user or an admin revoked it.
+ALTER TABLE af_user_session_revocation
+  ADD COLUMN IF NOT EXISTS reason TEXT;
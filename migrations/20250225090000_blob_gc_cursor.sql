@@ -0,0 +1,13 @@
+-- Progress of the collector of orphaned blobs run by the worker. The collector walks the
+-- workspaces in chunks ordered by id, so a pass interrupted by a restart resumes after the last
+-- workspace it processed. The single row is locked while a chunk is processed, which keeps
+-- several workers from collecting the same workspaces.
+CREATE TABLE IF NOT EXISTS af_blob_gc_cursor (
+  id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+  -- The last workspace processed by the current pass, NULL between passes
+  last_workspace_id UUID,
+  pass_finished_at TIMESTAMP WITH TIME ZONE,
+  updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO af_blob_gc_cursor (id) VALUES (TRUE) ON CONFLICT DO NOTHING;
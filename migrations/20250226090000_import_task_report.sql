@@ -0,0 +1,4 @@
+-- The report of an import, generated by the worker once the import ran: the outcome of every
+-- imported item, the attachment statistics and the links which couldn't be resolved.
+ALTER TABLE af_import_task
+  ADD COLUMN IF NOT EXISTS report JSONB;
@@ -0,0 +1,17 @@
+-- The blobs uploaded by the clients straight to the bucket with a presigned url, between the
+-- moment the url is issued and the moment the upload is completed. The declared size of a
+-- pending upload counts toward the storage limit of the workspace, so that the uploads issued
+-- together can't go over it. The rows the clients never complete are deleted by the worker
+-- along with their object.
+CREATE TABLE IF NOT EXISTS af_blob_pending_upload (
+  workspace_id UUID NOT NULL REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+  file_id TEXT NOT NULL,
+  object_key TEXT NOT NULL,
+  file_type TEXT NOT NULL,
+  file_size BIGINT NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (workspace_id, file_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_blob_pending_upload_created_at
+  ON af_blob_pending_upload (created_at);
@@ -0,0 +1,25 @@
+-- The multipart uploads of blobs in progress, and the parts uploaded so far, so that a client
+-- whose upload was interrupted can list the parts already uploaded and resume it. The uploads
+-- without activity for a while are aborted by the worker, which frees their parts in the bucket.
+CREATE TABLE IF NOT EXISTS af_blob_multipart_upload (
+  upload_id TEXT PRIMARY KEY,
+  workspace_id UUID NOT NULL REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+  file_id TEXT NOT NULL,
+  object_key TEXT NOT NULL,
+  file_type TEXT NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  -- bumped by each uploaded part
+  updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_blob_multipart_upload_updated_at
+  ON af_blob_multipart_upload (updated_at);
+
+CREATE TABLE IF NOT EXISTS af_blob_multipart_upload_part (
+  upload_id TEXT NOT NULL REFERENCES af_blob_multipart_upload(upload_id) ON DELETE CASCADE,
+  part_number INT NOT NULL,
+  e_tag TEXT NOT NULL,
+  part_size BIGINT NOT NULL,
+  uploaded_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (upload_id, part_number)
+);
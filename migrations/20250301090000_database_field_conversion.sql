@@ -0,0 +1,23 @@
+-- Jobs converting the cells of a database field whose type was changed, along with the filters,
+-- sorts and groups of the views referencing the field. The rows are converted in batches, the
+-- progress and the report of the cells which couldn't be converted are kept here.
+CREATE TABLE IF NOT EXISTS af_database_field_conversion (
+  task_id UUID NOT NULL PRIMARY KEY,
+  workspace_id UUID NOT NULL REFERENCES af_workspace (workspace_id) ON DELETE CASCADE,
+  database_id UUID NOT NULL,
+  field_id TEXT NOT NULL,
+  from_field_type BIGINT NOT NULL,
+  to_field_type BIGINT NOT NULL,
+  created_by BIGINT NOT NULL REFERENCES af_user (uid) ON DELETE CASCADE,
+  status SMALLINT NOT NULL DEFAULT 0,   -- 0: pending, 1: running, 2: completed, 3: failed
+  total_rows BIGINT NOT NULL DEFAULT 0,
+  converted_rows BIGINT NOT NULL DEFAULT 0,
+  report JSONB NOT NULL DEFAULT '{}',
+  error TEXT,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- The type of a field can only be changed again once its previous conversion is over.
+CREATE UNIQUE INDEX IF NOT EXISTS uq_af_database_field_conversion_active
+ON af_database_field_conversion (database_id, field_id) WHERE status IN (0, 1);
@@ -0,0 +1,19 @@
+-- Invitations expire, they can no longer be accepted past their expiry and are pruned by the
+-- appflowy worker some time after. The owner of the workspace can extend an invitation, which can
+-- send the invitation email again: `email_seq` is bumped whenever an email is queued, so that the
+-- queued emails superseded by a later one are dropped instead of being sent twice.
+ALTER TABLE af_workspace_invitation
+  ADD COLUMN IF NOT EXISTS expires_at TIMESTAMP WITH TIME ZONE,
+  ADD COLUMN IF NOT EXISTS email_seq INTEGER NOT NULL DEFAULT 0,
+  ADD COLUMN IF NOT EXISTS email_queued_at TIMESTAMP WITH TIME ZONE;
+
+UPDATE af_workspace_invitation
+SET expires_at = created_at + INTERVAL '14 days'
+WHERE expires_at IS NULL;
+
+ALTER TABLE af_workspace_invitation
+  ALTER COLUMN expires_at SET DEFAULT CURRENT_TIMESTAMP + INTERVAL '14 days',
+  ALTER COLUMN expires_at SET NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_af_workspace_invitation_pending_expires_at
+  ON af_workspace_invitation (expires_at) WHERE status = 0;
@@ -0,0 +1,33 @@
+-- Track when a blob was last downloaded, so that the appflowy worker can move the blobs nobody
+-- downloads in a while to a cheaper storage class. The downloads are buffered in Redis and
+-- written here about every minute. `storage_class` is NULL while the object is in the default
+-- storage class of the bucket.
+ALTER TABLE af_blob_metadata
+  ADD COLUMN IF NOT EXISTS last_accessed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  ADD COLUMN IF NOT EXISTS storage_class TEXT,
+  ADD COLUMN IF NOT EXISTS storage_class_changed_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_af_blob_metadata_cold
+  ON af_blob_metadata (workspace_id, last_accessed_at) WHERE storage_class IS NULL;
+
+-- Uploading the blob again stores its content in the default storage class, and counts as an
+-- access.
+CREATE OR REPLACE FUNCTION af_blob_metadata_reset_storage_class_fn()
+RETURNS TRIGGER AS $$
+BEGIN
+  IF OLD.file_size IS DISTINCT FROM NEW.file_size
+    OR OLD.content_hash IS DISTINCT FROM NEW.content_hash
+    OR OLD.object_key IS DISTINCT FROM NEW.object_key THEN
+    NEW.storage_class := NULL;
+    NEW.storage_class_changed_at := NULL;
+    NEW.last_accessed_at := CURRENT_TIMESTAMP;
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS af_blob_metadata_reset_storage_class_trigger ON af_blob_metadata;
+CREATE TRIGGER af_blob_metadata_reset_storage_class_trigger
+BEFORE UPDATE OF file_size, content_hash, object_key ON af_blob_metadata
+FOR EACH ROW
+EXECUTE FUNCTION af_blob_metadata_reset_storage_class_fn();
@@ -0,0 +1,16 @@
+-- Largest file a workspace can upload, by subscription plan. The plan of a workspace is the one
+-- recorded in af_workspace_ai_tier, workspaces without a row get the limit of the free plan.
+CREATE TABLE IF NOT EXISTS af_plan_blob_size_limit (
+  plan SMALLINT NOT NULL PRIMARY KEY,   -- same values as SubscriptionPlan: 0 free, 1 pro, 2 team, 3 ai max, 4 ai local
+  max_blob_size BIGINT NOT NULL
+);
+
+-- the AI plans are add-ons, they keep the limit of the free plan
+INSERT INTO af_plan_blob_size_limit (plan, max_blob_size)
+VALUES
+  (0, 10 * 1024 * 1024),
+  (1, 1024 * 1024 * 1024),
+  (2, 1024 * 1024 * 1024),
+  (3, 10 * 1024 * 1024),
+  (4, 10 * 1024 * 1024)
+ON CONFLICT (plan) DO NOTHING;
@@ -0,0 +1,63 @@
+-- Downscaled renditions of the image blobs, generated by the appflowy worker and stored under
+-- the object key of the blob suffixed with `@{variant}`. `file_id` is the metadata key of the
+-- blob. A variant is `stale` from the time its blob is uploaded again until it's regenerated,
+-- and stale variants aren't served.
+CREATE TABLE IF NOT EXISTS af_blob_variant (
+  workspace_id UUID NOT NULL REFERENCES af_workspace (workspace_id) ON DELETE CASCADE,
+  file_id TEXT NOT NULL,
+  variant TEXT NOT NULL,
+  object_key TEXT NOT NULL,
+  file_type TEXT NOT NULL,
+  file_size BIGINT NOT NULL,
+  stale BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (workspace_id, file_id, variant)
+);
+
+-- The blobs whose variants have to be generated, or deleted when the blob isn't an image anymore.
+-- `enqueued_at` is reset when the blob is uploaded again while its task is processed, so that the
+-- task is processed once more. Only the blobs uploaded from now on are queued.
+CREATE TABLE IF NOT EXISTS af_blob_variant_task (
+  workspace_id UUID NOT NULL,
+  file_id TEXT NOT NULL,
+  attempts INT NOT NULL DEFAULT 0,
+  next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  enqueued_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (workspace_id, file_id),
+  FOREIGN KEY (workspace_id, file_id)
+    REFERENCES af_blob_metadata (workspace_id, file_id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_af_blob_variant_task_next_attempt_at
+  ON af_blob_variant_task (next_attempt_at);
+
+-- Queue the raster images when they are uploaded. The GIFs, which can be animated, and the SVGs
+-- are served as they are.
+CREATE OR REPLACE FUNCTION af_blob_variant_enqueue_fn()
+RETURNS TRIGGER AS $$
+BEGIN
+  IF TG_OP = 'UPDATE' THEN
+    UPDATE af_blob_variant SET stale = TRUE
+    WHERE workspace_id = NEW.workspace_id AND file_id = NEW.file_id;
+  END IF;
+  IF NEW.file_type IN ('image/png', 'image/jpeg', 'image/jpg', 'image/webp', 'image/bmp', 'image/tiff')
+    OR (TG_OP = 'UPDATE' AND EXISTS (
+      SELECT 1 FROM af_blob_variant
+      WHERE workspace_id = NEW.workspace_id AND file_id = NEW.file_id
+    )) THEN
+    INSERT INTO af_blob_variant_task (workspace_id, file_id)
+    VALUES (NEW.workspace_id, NEW.file_id)
+    ON CONFLICT (workspace_id, file_id) DO UPDATE SET
+      attempts = 0,
+      next_attempt_at = CURRENT_TIMESTAMP,
+      enqueued_at = clock_timestamp();
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS af_blob_variant_enqueue_trigger ON af_blob_metadata;
+CREATE TRIGGER af_blob_variant_enqueue_trigger
+AFTER INSERT OR UPDATE OF file_type, file_size, content_hash, object_key ON af_blob_metadata
+FOR EACH ROW
+EXECUTE FUNCTION af_blob_variant_enqueue_fn();
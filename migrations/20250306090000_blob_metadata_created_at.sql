@@ -0,0 +1,8 @@
+-- When the blob was first uploaded, for the audits of the files of a workspace. It isn't known
+-- for the blobs uploaded before this migration, which are left NULL instead of getting the time
+-- of the migration.
+ALTER TABLE af_blob_metadata
+  ADD COLUMN IF NOT EXISTS created_at TIMESTAMP WITH TIME ZONE;
+
+ALTER TABLE af_blob_metadata
+  ALTER COLUMN created_at SET DEFAULT CURRENT_TIMESTAMP;
@@ -0,0 +1,101 @@
+-- The bytes stored by each workspace: the sizes of its blobs and of their archived versions.
+-- The counter is updated in the statement writing the blobs, by the triggers below, so that the
+-- usage is read without summing the blobs of the workspace.
+CREATE TABLE IF NOT EXISTS af_workspace_usage (
+  workspace_id UUID PRIMARY KEY REFERENCES af_workspace (workspace_id) ON DELETE CASCADE,
+  usage_bytes BIGINT NOT NULL DEFAULT 0,
+  updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO af_workspace_usage (workspace_id, usage_bytes)
+SELECT workspace_id, SUM(file_size)
+FROM (
+  SELECT workspace_id, file_size FROM af_blob_metadata
+  UNION ALL
+  SELECT workspace_id, file_size FROM af_blob_version
+) AS blobs
+GROUP BY workspace_id
+ON CONFLICT (workspace_id) DO UPDATE SET
+  usage_bytes = EXCLUDED.usage_bytes,
+  updated_at = CURRENT_TIMESTAMP;
+
+-- Add the sizes of the rows written by the statement, once per workspace. The rows skipped by
+-- ON CONFLICT DO NOTHING aren't in the transition tables, and a row replaced by ON CONFLICT DO
+-- UPDATE counts as the difference between its sizes. Deleting the rows doesn't create the
+-- counter: the rows deleted with their workspace would otherwise recreate it.
+CREATE OR REPLACE FUNCTION af_workspace_usage_fn()
+RETURNS TRIGGER AS $$
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    INSERT INTO af_workspace_usage (workspace_id, usage_bytes)
+    SELECT workspace_id, SUM(file_size) FROM new_rows GROUP BY workspace_id
+    ON CONFLICT (workspace_id) DO UPDATE SET
+      usage_bytes = af_workspace_usage.usage_bytes + EXCLUDED.usage_bytes,
+      updated_at = CURRENT_TIMESTAMP;
+  ELSIF TG_OP = 'UPDATE' THEN
+    INSERT INTO af_workspace_usage (workspace_id, usage_bytes)
+    SELECT workspace_id, SUM(file_size)
+    FROM (
+      SELECT workspace_id, file_size FROM new_rows
+      UNION ALL
+      SELECT workspace_id, -file_size FROM old_rows
+    ) AS delta
+    GROUP BY workspace_id
+    HAVING SUM(file_size) <> 0
+    ON CONFLICT (workspace_id) DO UPDATE SET
+      usage_bytes = af_workspace_usage.usage_bytes + EXCLUDED.usage_bytes,
+      updated_at = CURRENT_TIMESTAMP;
+  ELSE
+    UPDATE af_workspace_usage AS u
+    SET usage_bytes = u.usage_bytes - delta.file_size,
+        updated_at = CURRENT_TIMESTAMP
+    FROM (
+      SELECT workspace_id, SUM(file_size) AS file_size FROM old_rows GROUP BY workspace_id
+    ) AS delta
+    WHERE u.workspace_id = delta.workspace_id;
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS af_blob_metadata_usage_insert_trigger ON af_blob_metadata;
+CREATE TRIGGER af_blob_metadata_usage_insert_trigger
+AFTER INSERT ON af_blob_metadata
+REFERENCING NEW TABLE AS new_rows
+FOR EACH STATEMENT
+EXECUTE FUNCTION af_workspace_usage_fn();
+
+DROP TRIGGER IF EXISTS af_blob_metadata_usage_update_trigger ON af_blob_metadata;
+CREATE TRIGGER af_blob_metadata_usage_update_trigger
+AFTER UPDATE ON af_blob_metadata
+REFERENCING OLD TABLE AS old_rows NEW TABLE AS new_rows
+FOR EACH STATEMENT
+EXECUTE FUNCTION af_workspace_usage_fn();
+
+DROP TRIGGER IF EXISTS af_blob_metadata_usage_delete_trigger ON af_blob_metadata;
+CREATE TRIGGER af_blob_metadata_usage_delete_trigger
+AFTER DELETE ON af_blob_metadata
+REFERENCING OLD TABLE AS old_rows
+FOR EACH STATEMENT
+EXECUTE FUNCTION af_workspace_usage_fn();
+
+DROP TRIGGER IF EXISTS af_blob_version_usage_insert_trigger ON af_blob_version;
+CREATE TRIGGER af_blob_version_usage_insert_trigger
+AFTER INSERT ON af_blob_version
+REFERENCING NEW TABLE AS new_rows
+FOR EACH STATEMENT
+EXECUTE FUNCTION af_workspace_usage_fn();
+
+DROP TRIGGER IF EXISTS af_blob_version_usage_update_trigger ON af_blob_version;
+CREATE TRIGGER af_blob_version_usage_update_trigger
+AFTER UPDATE ON af_blob_version
+REFERENCING OLD TABLE AS old_rows NEW TABLE AS new_rows
+FOR EACH STATEMENT
+EXECUTE FUNCTION af_workspace_usage_fn();
+
+DROP TRIGGER IF EXISTS af_blob_version_usage_delete_trigger ON af_blob_version;
+CREATE TRIGGER af_blob_version_usage_delete_trigger
+AFTER DELETE ON af_blob_version
+REFERENCING OLD TABLE AS old_rows
+FOR EACH STATEMENT
+EXECUTE FUNCTION af_workspace_usage_fn();
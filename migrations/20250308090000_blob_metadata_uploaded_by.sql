@@ -0,0 +1,5 @@
+-- The user who uploaded the blob, so that the owners of a workspace can tell who uses its
+-- storage. It isn't known for the blobs uploaded before this migration, nor for the blobs of a
+-- deleted user, which are left NULL.
+ALTER TABLE af_blob_metadata
+  ADD COLUMN IF NOT EXISTS uploaded_by BIGINT REFERENCES af_user (uid) ON DELETE SET NULL;
@@ -0,0 +1,8 @@
+-- Blobs uploaded with a time to live, such as exports and AI generated images. An expired blob
+-- isn't served anymore, and the appflowy worker deletes it with its objects. It keeps counting
+-- toward the usage of its workspace until then.
+ALTER TABLE af_blob_metadata
+  ADD COLUMN IF NOT EXISTS expires_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_af_blob_metadata_expires_at
+  ON af_blob_metadata (expires_at) WHERE expires_at IS NOT NULL;
@@ -0,0 +1,13 @@
+-- Metadata of the collab snapshots kept in S3, listed in the version history of a collab. The
+-- snapshots taken before this table was added have no row.
+CREATE TABLE IF NOT EXISTS af_collab_snapshot_info (
+  workspace_id UUID NOT NULL REFERENCES af_workspace(workspace_id) ON DELETE CASCADE,
+  oid TEXT NOT NULL,
+  snapshot_id BIGINT NOT NULL,
+  -- size of the doc state of the snapshot, before compression
+  len BIGINT NOT NULL,
+  -- the user who took the snapshot, NULL for the snapshots taken by the server
+  created_by BIGINT,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (oid, snapshot_id)
+);
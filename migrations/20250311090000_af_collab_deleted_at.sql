@@ -0,0 +1,4 @@
+-- Collabs deleted by their users stay in the trash of their workspace, with deleted_at set,
+-- until they are restored or purged. The appflowy worker purges the ones deleted long ago.
+CREATE INDEX IF NOT EXISTS idx_af_collab_deleted_at
+  ON af_collab (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,16 @@
+-- Subscription plan of a workspace, as recorded by the billing service. Unlike af_workspace_ai_tier,
+-- which the AI request scheduler reads and where the AI add-ons replace the plan, this only holds
+-- the plan itself. Workspaces without a row, such as the ones of a self-hosted instance without a
+-- billing service, have no plan and aren't limited by af_plan_blob_size_limit.
+CREATE TABLE IF NOT EXISTS af_workspace_subscription_plan (
+  workspace_id UUID NOT NULL PRIMARY KEY REFERENCES af_workspace (workspace_id) ON DELETE CASCADE,
+  plan SMALLINT NOT NULL,   -- same values as SubscriptionPlan: 0 free, 1 pro, 2 team
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- the pro and team tiers are plans, the other tiers may hide the plan behind an add-on
+INSERT INTO af_workspace_subscription_plan (workspace_id, plan, updated_at)
+SELECT workspace_id, plan, updated_at
+FROM af_workspace_ai_tier
+WHERE plan IN (1, 2)
+ON CONFLICT (workspace_id) DO NOTHING;
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Error};
+use collab_rt_entity::{
+  MessageChunk, MAXIMUM_CHUNK_COUNT, MAXIMUM_CHUNK_DATA_SIZE, MAXIMUM_REALTIME_MESSAGE_SIZE,
+};
+use tracing::warn;
+
+/// Bytes of the incomplete messages kept for a connection. A client can't hold more than one
+/// message of the maximum size at a time.
+pub const MAXIMUM_BUFFERED_CHUNK_BYTES: usize = MAXIMUM_REALTIME_MESSAGE_SIZE as usize;
+/// An incomplete message is dropped when its chunks keep arriving for longer than this.
+pub const CHUNKED_MESSAGE_TIMEOUT: Duration = Duration::from_secs(30);
+
+struct PendingMessage {
+  count: u32,
+  next_index: u32,
+  data: Vec<u8>,
+  started_at: Instant,
+}
+
+/// Reassembles the messages a client split in [MessageChunk]s. The chunks of a message must
+/// arrive in order, as they are sent over the same connection. A message whose chunks don't
+/// follow each other, exceed the buffer or keep arriving past the timeout is dropped.
+pub struct ChunkAssembler {
+  maximum_buffered_bytes: usize,
+  timeout: Duration,
+  pending: HashMap<u64, PendingMessage>,
+  buffered_bytes: usize,
+}
+
+impl Default for ChunkAssembler {
+  fn default() -> Self {
+    Self::new(MAXIMUM_BUFFERED_CHUNK_BYTES, CHUNKED_MESSAGE_TIMEOUT)
+  }
+}
+
+impl ChunkAssembler {
+  pub fn new(maximum_buffered_bytes: usize, timeout: Duration) -> Self {
+    Self {
+      maximum_buffered_bytes,
+      timeout,
+      pending: HashMap::new(),
+      buffered_bytes: 0,
+    }
+  }
+
+  /// Returns the encoded message once its last chunk is pushed.
+  pub fn push(&mut self, chunk: MessageChunk, now: Instant) -> Result<Option<Vec<u8>>, Error> {
+    self.expire(now);
+    let message_id = chunk.message_id;
+    // the sizes are checked before anything is buffered, as they come from the client
+    if chunk.data.len() > MAXIMUM_CHUNK_DATA_SIZE {
+      self.discard(message_id);
+      return Err(anyhow!(
+        "chunk {} of message {} holds {} bytes, more than {}",
+        chunk.index,
+        message_id,
+        chunk.data.len(),
+        MAXIMUM_CHUNK_DATA_SIZE
+      ));
+    }
+    if chunk.count == 0 || chunk.count > MAXIMUM_CHUNK_COUNT || chunk.index >= chunk.count {
+      self.discard(message_id);
+      return Err(anyhow!(
+        "chunk {} of message {} claims {} chunks, at most {} are allowed",
+        chunk.index,
+        message_id,
+        chunk.count,
+        MAXIMUM_CHUNK_COUNT
+      ));
+    }
+    if chunk.index == 0 {
+      if self.pending.contains_key(&message_id) {
+        self.discard(message_id);
+        return Err(anyhow!(
+          "message {} restarted before completing",
+          message_id
+        ));
+      }
+      if chunk.count == 1 {
+        return Ok(Some(chunk.data.to_vec()));
+      }
+      self.pending.insert(
+        message_id,
+        PendingMessage {
+          count: chunk.count,
+          next_index: 0,
+          // grown as the chunks arrive, within the buffer cap
+          data: Vec::new(),
+          started_at: now,
+        },
+      );
+    }
+
+    let pending = match self.pending.get_mut(&message_id) {
+      Some(pending) => pending,
+      None => {
+        return Err(anyhow!(
+          "chunk {} of unknown message {}",
+          chunk.index,
+          message_id
+        ))
+      },
+    };
+    if chunk.count != pending.count || chunk.index != pending.next_index {
+      let expected = pending.next_index;
+      self.discard(message_id);
+      return Err(anyhow!(
+        "chunk {} of message {} arrived instead of chunk {}",
+        chunk.index,
+        message_id,
+        expected
+      ));
+    }
+    if self.buffered_bytes + chunk.data.len() > self.maximum_buffered_bytes {
+      self.discard(message_id);
+      return Err(anyhow!(
+        "message {} exceeds the chunk buffer of {} bytes",
+        message_id,
+        self.maximum_buffered_bytes
+      ));
+    }
+
+    pending.data.extend_from_slice(&chunk.data);
+    pending.next_index += 1;
+    self.buffered_bytes += chunk.data.len();
+    if pending.next_index < pending.count {
+      return Ok(None);
+    }
+    let pending = self
+      .pending
+      .remove(&message_id)
+      .expect("the message is pending");
+    self.buffered_bytes -= pending.data.len();
+    Ok(Some(pending.data))
+  }
+
+  /// Drops the messages started more than the timeout ago.
+  pub fn expire(&mut self, now: Instant) {
+    let timeout = self.timeout;
+    let expired = self
+      .pending
+      .iter()
+      .filter(|(_, pending)| now.duration_since(pending.started_at) > timeout)
+      .map(|(message_id, _)| *message_id)
+      .collect::<Vec<_>>();
+    for message_id in expired {
+      warn!(
+        "dropping chunked message {}: not completed in time",
+        message_id
+      );
+      self.discard(message_id);
+    }
+  }
+
+  pub fn buffered_bytes(&self) -> usize {
+    self.buffered_bytes
+  }
+
+  fn discard(&mut self, message_id: u64) {
+    if let Some(pending) = self.pending.remove(&message_id) {
+      self.buffered_bytes -= pending.data.len();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bytes::Bytes;
+
+  fn chunk(message_id: u64, index: u32, count: u32, len: usize) -> MessageChunk {
+    MessageChunk {
+      message_id,
+      index,
+      count,
+      data: Bytes::from(vec![index as u8; len]),
+    }
+  }
+
+  #[test]
+  fn reassemble_chunked_message_test() {
+    let mut assembler = ChunkAssembler::new(1024, Duration::from_secs(30));
+    let now = Instant::now();
+    assert!(assembler.push(chunk(1, 0, 3, 10), now).unwrap().is_none());
+    // the chunks of another message can arrive in between
+    assert!(assembler.push(chunk(2, 0, 2, 10), now).unwrap().is_none());
+    assert!(assembler.push(chunk(1, 1, 3, 10), now).unwrap().is_none());
+    assert_eq!(assembler.buffered_bytes(), 30);
+
+    let message = assembler.push(chunk(1, 2, 3, 5), now).unwrap().unwrap();
+    assert_eq!(message.len(), 25);
+    assert_eq!(&message[..10], &[0u8; 10]);
+    assert_eq!(&message[20..], &[2u8; 5]);
+    assert_eq!(assembler.buffered_bytes(), 10);
+  }
+
+  #[test]
+  fn chunks_out_of_sequence_are_dropped_test() {
+    let mut assembler = ChunkAssembler::new(1024, Duration::from_secs(30));
+    let now = Instant::now();
+    assert!(assembler.push(chunk(1, 1, 3, 10), now).is_err());
+    assembler.push(chunk(1, 0, 3, 10), now).unwrap();
+    assert!(assembler.push(chunk(1, 2, 3, 10), now).is_err());
+    assert_eq!(assembler.buffered_bytes(), 0);
+    // the rest of the dropped message is rejected too
+    assert!(assembler.push(chunk(1, 1, 3, 10), now).is_err());
+  }
+
+  #[test]
+  fn chunks_beyond_buffer_cap_are_dropped_test() {
+    let mut assembler = ChunkAssembler::new(25, Duration::from_secs(30));
+    let now = Instant::now();
+    assembler.push(chunk(1, 0, 10, 10), now).unwrap();
+    assembler.push(chunk(2, 0, 10, 10), now).unwrap();
+    assert!(assembler.push(chunk(1, 1, 10, 10), now).is_err());
+    assert_eq!(assembler.buffered_bytes(), 10);
+  }
+
+  #[test]
+  fn oversized_chunks_are_rejected_test() {
+    let mut assembler = ChunkAssembler::default();
+    let now = Instant::now();
+    assert!(assembler
+      .push(chunk(1, 0, 2, MAXIMUM_CHUNK_DATA_SIZE + 1), now)
+      .is_err());
+    assert!(assembler
+      .push(chunk(2, 0, MAXIMUM_CHUNK_COUNT + 1, 10), now)
+      .is_err());
+    assert!(assembler.push(chunk(3, 0, 0, 10), now).is_err());
+    assert_eq!(assembler.buffered_bytes(), 0);
+
+    // a pending message is dropped by an oversized chunk
+    assembler.push(chunk(4, 0, 3, 10), now).unwrap();
+    assert!(assembler
+      .push(chunk(4, 1, 3, MAXIMUM_CHUNK_DATA_SIZE + 1), now)
+      .is_err());
+    assert_eq!(assembler.buffered_bytes(), 0);
+  }
+
+  #[test]
+  fn incomplete_message_expires_test() {
+    let mut assembler = ChunkAssembler::new(1024, Duration::from_secs(30));
+    let now = Instant::now();
+    assembler.push(chunk(1, 0, 3, 10), now).unwrap();
+    assembler.expire(now + Duration::from_secs(31));
+    assert_eq!(assembler.buffered_bytes(), 0);
+    assert!(assembler
+      .push(chunk(1, 1, 3, 10), now + Duration::from_secs(31))
+      .is_err());
+  }
+}
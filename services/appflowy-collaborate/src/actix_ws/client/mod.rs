@@ -1,2 +1,3 @@
+mod chunk;
 pub mod rt_client;
 pub use crate::actix_ws::client::rt_client::*;
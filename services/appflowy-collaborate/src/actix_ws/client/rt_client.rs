@@ -1,4 +1,6 @@
+use crate::actix_ws::client::chunk::ChunkAssembler;
 use crate::actix_ws::entities::{ClientWebSocketMessage, Connect, Disconnect, RealtimeMessage};
+use crate::bandwidth::BandwidthCounter;
 use crate::error::RealtimeError;
 use crate::RealtimeClientWebsocketSink;
 use actix::{
@@ -11,7 +13,7 @@ use anyhow::anyhow;
 use async_trait::async_trait;
 use bytes::Bytes;
 use collab_rt_entity::user::RealtimeUser;
-use collab_rt_entity::SystemMessage;
+use collab_rt_entity::{MessageChunk, SystemMessage};
 use governor::clock::DefaultClock;
 use governor::middleware::NoOpMiddleware;
 use governor::state::{InMemoryState, NotKeyed};
@@ -48,6 +50,10 @@ pub struct RealtimeClient<S: RealtimeServer> {
   /// mechanism. This limits the number of messages a client can send per second, ensuring the server's
   /// mailbox does not get full from receiving too many messages at the same time.
   binary_rate_limiter: Arc<BinaryRateLimiter>,
+  /// Bytes of the binary frames exchanged over this connection.
+  bandwidth: BandwidthCounter,
+  /// The messages too large for a single frame, which the client splits in chunks.
+  chunks: ChunkAssembler,
 }
 
 impl<S> RealtimeClient<S>
@@ -73,11 +79,14 @@ where
       external_source: Some(external_source),
       client_version,
       binary_rate_limiter: Arc::new(rate_limiter),
+      bandwidth: BandwidthCounter::default(),
+      chunks: ChunkAssembler::default(),
     }
   }
 
   fn hb(&self, ctx: &mut ws::WebsocketContext<Self>) {
     ctx.run_interval(self.heartbeat_interval, move |act, ctx| {
+      act.chunks.expire(Instant::now());
       if Instant::now().duration_since(act.hb) > act.client_timeout {
         let user = act.user.clone();
         warn!(
@@ -116,6 +125,22 @@ where
   S: RealtimeServer,
 {
   fn handle_binary(&mut self, ctx: &mut WebsocketContext<RealtimeClient<S>>, bytes: Bytes) {
+    self.bandwidth.record_in(bytes.len());
+    // Only the reassembled message enters the pipeline, and counts against the rate limit.
+    let bytes = if MessageChunk::is_chunk(&bytes) {
+      let message =
+        MessageChunk::decode(&bytes).and_then(|chunk| self.chunks.push(chunk, Instant::now()));
+      match message {
+        Ok(Some(message)) => Bytes::from(message),
+        Ok(None) => return,
+        Err(err) => {
+          warn!("Dropping chunked message of user {}: {}", self.user, err);
+          return;
+        },
+      }
+    } else {
+      bytes
+    };
     // Immediately return if rate limit is exceeded.
     if let Err(e) = self.binary_rate_limiter.check() {
       trace!("Rate limit exceeded for user: {}, error: {}", self.user, e);
@@ -258,7 +283,11 @@ where
     // When the user is None which means the user is kicked off by the server, do not send
     // disconnect message to the server.
     let user = self.user.clone();
-    trace!("{} stopping websocket connect", user);
+    let usage = self.bandwidth.usage();
+    debug!(
+      "{} stopping websocket connect, received {} bytes, sent {} bytes",
+      user, usage.bytes_in, usage.bytes_out
+    );
     self.server.do_send(Disconnect { user });
     Running::Stop
   }
@@ -273,16 +302,30 @@ where
 
   fn handle(&mut self, message: RealtimeMessage, ctx: &mut Self::Context) {
     match message.encode() {
-      Ok(data) => ctx.binary(Bytes::from(data)),
+      Ok(data) => {
+        self.bandwidth.record_out(data.len());
+        ctx.binary(Bytes::from(data))
+      },
       Err(err) => error!("Error encoding message: {}", err),
     }
 
-    if let RealtimeMessage::System(SystemMessage::DuplicateConnection) = &message {
-      let reason = CloseReason {
-        code: CloseCode::Normal,
-        description: Some("Duplicate connection".to_string()),
-      };
-      ctx.close(Some(reason));
+    match &message {
+      RealtimeMessage::System(SystemMessage::DuplicateConnection) => {
+        let reason = CloseReason {
+          code: CloseCode::Normal,
+          description: Some("Duplicate connection".to_string()),
+        };
+        ctx.close(Some(reason));
+      },
+      RealtimeMessage::System(SystemMessage::KickOff) => {
+        let reason = CloseReason {
+          code: CloseCode::Policy,
+          description: Some("Kicked off by the server".to_string()),
+        };
+        ctx.close(Some(reason));
+        ctx.stop();
+      },
+      _ => {},
     }
   }
 }
@@ -22,18 +22,25 @@ use tracing::info;
 
 use crate::actix_ws::server::RealtimeServerActor;
 use crate::api::{collab_scope, ws_scope};
+use crate::bandwidth::{spawn_bandwidth_flush, RealtimeBandwidth};
 use crate::collab::access_control::CollabStorageAccessControlImpl;
+use crate::collab::compaction::{spawn_collab_compaction_sweeper, CollabCompactor};
+use crate::collab::migration::{CollabMigrations, CollabMigrator};
+use crate::collab::recovery::CollabRecovery;
 use access_control::casbin::access::AccessControl;
+use collab_stream::client::CollabRedisStream;
 use collab_stream::metrics::CollabStreamMetrics;
 use collab_stream::stream_router::{StreamRouter, StreamRouterOptions};
 use database::file::s3_client_impl::AwsS3BucketClientImpl;
 
 use crate::collab::cache::CollabCache;
-use crate::collab::storage::CollabStorageImpl;
+use crate::collab::storage::{CollabAccessControlStorage, CollabStorageImpl};
 use crate::command::{CLCommandReceiver, CLCommandSender};
 use crate::config::{get_env_var, Config, DatabaseSetting, S3Setting};
+use crate::feature_flags::FeatureFlags;
+use crate::load_shed::{spawn_load_shed_monitor, LoadShedder};
 use crate::pg_listener::PgListeners;
-use crate::snapshot::SnapshotControl;
+use crate::snapshot::{SnapshotControl, SnapshotPolicyResolver};
 use crate::state::{AppMetrics, AppState, UserCache};
 use crate::CollaborationServer;
 use indexer::collab_indexer::IndexerProvider;
@@ -41,6 +48,7 @@ use indexer::scheduler::{IndexerConfiguration, IndexerScheduler};
 
 pub struct Application {
   actix_server: Server,
+  realtime_server: CollaborationServer<CollabAccessControlStorage>,
 }
 
 impl Application {
@@ -55,13 +63,20 @@ impl Application {
       "Collab Service started at {}",
       listener.local_addr().unwrap()
     );
-    let actix_server = run_actix_server(listener, state, config, rt_cmd_recv).await?;
+    let (actix_server, realtime_server) =
+      run_actix_server(listener, state, config, rt_cmd_recv).await?;
 
-    Ok(Self { actix_server })
+    Ok(Self {
+      actix_server,
+      realtime_server,
+    })
   }
 
+  /// Runs the server until it's stopped, then saves the pending updates of the collab groups.
   pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
-    self.actix_server.await
+    self.actix_server.await?;
+    self.realtime_server.stop_all_groups().await;
+    Ok(())
   }
 }
 
@@ -70,9 +85,10 @@ pub async fn run_actix_server(
   state: AppState,
   config: Config,
   rt_cmd_recv: CLCommandReceiver,
-) -> Result<Server, Error> {
+) -> Result<(Server, CollaborationServer<CollabAccessControlStorage>), Error> {
   let storage = state.collab_access_control_storage.clone();
 
+  let realtime_bandwidth = Arc::new(RealtimeBandwidth::default());
   // Initialize metrics that which are registered in the registry.
   let realtime_server = CollaborationServer::<_>::new(
     storage.clone(),
@@ -85,11 +101,46 @@ pub async fn run_actix_server(
     state.redis_connection_manager.clone(),
     Duration::from_secs(config.collab.group_persistence_interval_secs),
     Duration::from_secs(config.collab.group_prune_grace_period_secs),
+    config.collab.folder_compaction_threshold,
+    SnapshotPolicyResolver::new(
+      state.pg_pool.clone(),
+      config.collab.snapshot_policies.clone(),
+    ),
     state.indexer_scheduler.clone(),
+    realtime_bandwidth.clone(),
+    CollabRecovery::new(state.pg_pool.clone()),
+    CollabMigrator::new(
+      state.pg_pool.clone(),
+      CollabMigrations::builtin(),
+      state.metrics.realtime_metrics.clone(),
+    ),
+    state.feature_flags.clone(),
+    state.load_shedder.clone(),
+    config.collab.group_unload.clone(),
+    config.collab.update_rate_limit.clone(),
+    config.collab.size_limits.clone(),
+    config.collab.full_state_sync.clone(),
+    config.collab.write_behind.clone(),
   )
   .await
   .unwrap();
-  let realtime_server_actor = Supervisor::start(|_| RealtimeServerActor(realtime_server));
+  spawn_bandwidth_flush(
+    realtime_bandwidth,
+    state.redis_connection_manager.clone(),
+    state.pg_pool.clone(),
+  );
+  spawn_collab_compaction_sweeper(
+    CollabCompactor::new(state.pg_pool.clone(), config.collab.compaction.clone()),
+    state.collab_access_control_storage.clone(),
+    CollabRedisStream::new_with_connection_manager(
+      state.redis_connection_manager.clone(),
+      state.redis_stream_router.clone(),
+    ),
+  );
+  let realtime_server_actor = Supervisor::start({
+    let realtime_server = realtime_server.clone();
+    move |_| RealtimeServerActor(realtime_server)
+  });
   let mut server = HttpServer::new(move || {
     App::new()
       .app_data(Data::new(state.clone()))
@@ -100,7 +151,7 @@ pub async fn run_actix_server(
   });
   server = server.listen(listener)?;
 
-  Ok(server.run())
+  Ok((server.run(), realtime_server))
 }
 
 pub async fn init_state(config: &Config, rt_cmd_tx: CLCommandSender) -> Result<AppState, Error> {
@@ -118,6 +169,14 @@ pub async fn init_state(config: &Config, rt_cmd_tx: CLCommandSender) -> Result<A
   )
   .await?;
 
+  info!("Loading feature flags...");
+  let feature_flags = FeatureFlags::new(
+    pg_pool.clone(),
+    redis::Client::open(config.redis_uri.expose_secret().as_str())?,
+    redis_conn_manager.clone(),
+  )
+  .await?;
+
   // Pg listeners
   info!("Setting up Pg listeners...");
   let pg_listeners = Arc::new(PgListeners::new(&pg_pool).await?);
@@ -130,7 +189,8 @@ pub async fn init_state(config: &Config, rt_cmd_tx: CLCommandSender) -> Result<A
     config.s3.bucket.clone(),
     config.s3.minio_url.clone(),
     config.s3.presigned_url_endpoint.clone(),
-  );
+  )
+  .with_metrics(metrics.s3_metrics.clone());
 
   let collab_access_control = CollabAccessControlImpl::new(access_control.clone());
   let workspace_access_control = WorkspaceAccessControlImpl::new(access_control.clone());
@@ -158,7 +218,13 @@ pub async fn init_state(config: &Config, rt_cmd_tx: CLCommandSender) -> Result<A
     collab_storage_access_control,
     snapshot_control,
     rt_cmd_tx,
+    config.collab.size_limits.clone(),
+  ));
+  let load_shedder = Arc::new(LoadShedder::new(
+    config.collab.load_shedding.clone(),
+    metrics.load_shed_metrics.clone(),
   ));
+  spawn_load_shed_monitor(load_shedder.clone(), Arc::downgrade(&collab_storage));
 
   info!("Setting up Indexer provider...");
   let embedder_config = IndexerConfiguration {
@@ -181,6 +247,7 @@ pub async fn init_state(config: &Config, rt_cmd_tx: CLCommandSender) -> Result<A
 
   let app_state = AppState {
     config: Arc::new(config.clone()),
+    pg_pool,
     pg_listeners,
     user_cache,
     redis_stream_router,
@@ -189,6 +256,8 @@ pub async fn init_state(config: &Config, rt_cmd_tx: CLCommandSender) -> Result<A
     collab_access_control_storage: collab_storage,
     metrics,
     indexer_scheduler,
+    feature_flags,
+    load_shedder,
   };
   Ok(app_state)
 }
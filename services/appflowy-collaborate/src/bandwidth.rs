@@ -0,0 +1,419 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{Days, NaiveDate, Utc};
+use dashmap::DashMap;
+use database::realtime_bandwidth::upsert_workspace_realtime_bandwidth;
+use redis::aio::ConnectionManager;
+use redis::{pipe, AsyncCommands};
+use sqlx::PgPool;
+use tracing::{trace, warn};
+use uuid::Uuid;
+
+use crate::config::get_env_var;
+
+/// Daily counters are kept in Redis for a few days, long enough for the previous day to be
+/// aggregated after midnight.
+const DAILY_KEY_EXPIRATION_SECS: i64 = 3 * 24 * 60 * 60;
+
+/// The classes of events a collab group sends to its subscribers, which they select with a
+/// [collab_rt_entity::SubscriptionFilter].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventClass {
+  Update,
+  Awareness,
+  Control,
+}
+
+impl EventClass {
+  const ALL: [EventClass; 3] = [
+    EventClass::Update,
+    EventClass::Awareness,
+    EventClass::Control,
+  ];
+
+  fn as_str(&self) -> &'static str {
+    match self {
+      EventClass::Update => "updates",
+      EventClass::Awareness => "awareness",
+      EventClass::Control => "control",
+    }
+  }
+}
+
+/// Bytes received from and sent to the realtime clients. It's updated on every frame, so it only
+/// uses atomics.
+#[derive(Debug, Default)]
+pub struct BandwidthCounter {
+  bytes_in: AtomicU64,
+  bytes_out: AtomicU64,
+  /// Part of the bytes sent for each [EventClass]. The connections only count their frames, so
+  /// they're only set by the collab groups.
+  bytes_out_by_class: [AtomicU64; 3],
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BandwidthUsage {
+  pub bytes_in: u64,
+  pub bytes_out: u64,
+  /// Bytes sent for each [EventClass], in the order of [EventClass::ALL].
+  pub bytes_out_by_class: [u64; 3],
+}
+
+impl BandwidthUsage {
+  pub fn is_empty(&self) -> bool {
+    self.bytes_in == 0 && self.bytes_out == 0
+  }
+}
+
+impl BandwidthCounter {
+  #[inline]
+  pub fn record_in(&self, len: usize) {
+    self.bytes_in.fetch_add(len as u64, Ordering::Relaxed);
+  }
+
+  #[inline]
+  pub fn record_out(&self, len: usize) {
+    self.bytes_out.fetch_add(len as u64, Ordering::Relaxed);
+  }
+
+  /// Counts the bytes sent for an event of the given class.
+  #[inline]
+  pub fn record_event_out(&self, class: EventClass, len: usize) {
+    self.record_out(len);
+    self.bytes_out_by_class[class as usize].fetch_add(len as u64, Ordering::Relaxed);
+  }
+
+  pub fn usage(&self) -> BandwidthUsage {
+    BandwidthUsage {
+      bytes_in: self.bytes_in.load(Ordering::Relaxed),
+      bytes_out: self.bytes_out.load(Ordering::Relaxed),
+      bytes_out_by_class: self
+        .bytes_out_by_class
+        .each_ref()
+        .map(|bytes| bytes.load(Ordering::Relaxed)),
+    }
+  }
+
+  /// Returns the bytes counted since the last call and resets the counter.
+  fn take(&self) -> BandwidthUsage {
+    BandwidthUsage {
+      bytes_in: self.bytes_in.swap(0, Ordering::Relaxed),
+      bytes_out: self.bytes_out.swap(0, Ordering::Relaxed),
+      bytes_out_by_class: self
+        .bytes_out_by_class
+        .each_ref()
+        .map(|bytes| bytes.swap(0, Ordering::Relaxed)),
+    }
+  }
+
+  /// Adds back the bytes which could not be flushed.
+  fn restore(&self, usage: BandwidthUsage) {
+    self.record_in(usage.bytes_in as usize);
+    self.record_out(usage.bytes_out as usize);
+    for (counter, bytes) in self.bytes_out_by_class.iter().zip(usage.bytes_out_by_class) {
+      counter.fetch_add(bytes, Ordering::Relaxed);
+    }
+  }
+}
+
+/// Realtime bandwidth of each workspace served by this instance. The collab groups of a workspace
+/// share its [BandwidthCounter], which is flushed periodically to Redis by
+/// [spawn_bandwidth_flush].
+#[derive(Default)]
+pub struct RealtimeBandwidth {
+  workspaces: DashMap<String, Arc<BandwidthCounter>>,
+}
+
+impl RealtimeBandwidth {
+  /// Returns the counter of the workspace. It's looked up once when a collab group is created, so
+  /// recording the bytes of a frame doesn't go through the map.
+  pub fn workspace(&self, workspace_id: &str) -> Arc<BandwidthCounter> {
+    self
+      .workspaces
+      .entry(workspace_id.to_string())
+      .or_default()
+      .clone()
+  }
+
+  /// Takes the usage of every workspace since the last flush. The counters no collab group refers
+  /// to anymore are dropped.
+  fn take_all(&self) -> Vec<(String, BandwidthUsage)> {
+    let mut usage = vec![];
+    self.workspaces.retain(|workspace_id, counter| {
+      let taken = counter.take();
+      if !taken.is_empty() {
+        usage.push((workspace_id.clone(), taken));
+      }
+      Arc::strong_count(counter) > 1
+    });
+    usage
+  }
+
+  fn restore(&self, usage: Vec<(String, BandwidthUsage)>) {
+    for (workspace_id, usage) in usage {
+      self.workspace(&workspace_id).restore(usage);
+    }
+  }
+}
+
+fn daily_key(day: NaiveDate) -> String {
+  format!("af:realtime_bandwidth:{}", day)
+}
+
+fn bytes_in_field(workspace_id: &str) -> String {
+  format!("{}:in", workspace_id)
+}
+
+fn bytes_out_field(workspace_id: &str) -> String {
+  format!("{}:out", workspace_id)
+}
+
+fn bytes_out_class_field(workspace_id: &str, class: EventClass) -> String {
+  format!("{}:out:{}", workspace_id, class.as_str())
+}
+
+/// Adds the usage to the daily counters shared by all the instances. Increments are only applied
+/// once, so a restarting instance only loses what it didn't flush yet.
+async fn flush_to_redis(
+  redis: &mut ConnectionManager,
+  day: NaiveDate,
+  usage: &[(String, BandwidthUsage)],
+) -> Result<(), redis::RedisError> {
+  let key = daily_key(day);
+  let mut pipeline = pipe();
+  pipeline.atomic();
+  for (workspace_id, usage) in usage {
+    pipeline
+      .hincr(&key, bytes_in_field(workspace_id), usage.bytes_in)
+      .ignore()
+      .hincr(&key, bytes_out_field(workspace_id), usage.bytes_out)
+      .ignore();
+    for (class, bytes) in EventClass::ALL.iter().zip(usage.bytes_out_by_class) {
+      if bytes > 0 {
+        pipeline
+          .hincr(&key, bytes_out_class_field(workspace_id, *class), bytes)
+          .ignore();
+      }
+    }
+  }
+  pipeline.expire(&key, DAILY_KEY_EXPIRATION_SECS).ignore();
+  let () = pipeline.query_async(redis).await?;
+  Ok(())
+}
+
+/// Parses the fields of a daily counter key into the usage of each workspace.
+fn parse_daily_counters(counters: HashMap<String, u64>) -> HashMap<Uuid, BandwidthUsage> {
+  let mut usage: HashMap<Uuid, BandwidthUsage> = HashMap::new();
+  for (field, bytes) in counters {
+    let Some((workspace_id, direction)) = field.split_once(':') else {
+      continue;
+    };
+    let Ok(workspace_id) = Uuid::parse_str(workspace_id) else {
+      continue;
+    };
+    let entry = usage.entry(workspace_id).or_default();
+    match direction {
+      "in" => entry.bytes_in = bytes,
+      "out" => entry.bytes_out = bytes,
+      _ => {
+        let class = EventClass::ALL
+          .iter()
+          .position(|class| direction.strip_prefix("out:") == Some(class.as_str()));
+        if let Some(class) = class {
+          entry.bytes_out_by_class[class] = bytes;
+        }
+      },
+    }
+  }
+  usage
+}
+
+/// Copies the daily counters of Redis into Postgres. The counters hold the totals of the day, so
+/// running it on several instances, or several times a day, stores the same values.
+async fn aggregate_day(
+  redis: &mut ConnectionManager,
+  pg_pool: &PgPool,
+  day: NaiveDate,
+) -> Result<(), anyhow::Error> {
+  let counters: HashMap<String, u64> = redis.hgetall(daily_key(day)).await?;
+  let usage = parse_daily_counters(counters);
+  if usage.is_empty() {
+    return Ok(());
+  }
+  let mut workspace_ids = Vec::with_capacity(usage.len());
+  let mut bytes_in = Vec::with_capacity(usage.len());
+  let mut bytes_out = Vec::with_capacity(usage.len());
+  let mut bytes_out_by_class: [Vec<i64>; 3] = Default::default();
+  for (workspace_id, usage) in usage {
+    workspace_ids.push(workspace_id);
+    bytes_in.push(usage.bytes_in as i64);
+    bytes_out.push(usage.bytes_out as i64);
+    for (class_bytes, bytes) in bytes_out_by_class.iter_mut().zip(usage.bytes_out_by_class) {
+      class_bytes.push(bytes as i64);
+    }
+  }
+  let [updates_out, awareness_out, control_out] = bytes_out_by_class;
+  upsert_workspace_realtime_bandwidth(
+    pg_pool,
+    day,
+    &workspace_ids,
+    &bytes_in,
+    &bytes_out,
+    &updates_out,
+    &awareness_out,
+    &control_out,
+  )
+  .await?;
+  Ok(())
+}
+
+/// Flushes the bandwidth counters of this instance to Redis and aggregates the daily counters
+/// into Postgres. The previous day is aggregated too, so that the increments flushed right before
+/// midnight end up in Postgres.
+pub fn spawn_bandwidth_flush(
+  bandwidth: Arc<RealtimeBandwidth>,
+  mut redis: ConnectionManager,
+  pg_pool: PgPool,
+) {
+  let flush_interval = Duration::from_secs(
+    get_env_var("APPFLOWY_REALTIME_BANDWIDTH_FLUSH_INTERVAL_SECS", "60")
+      .parse()
+      .unwrap_or(60),
+  );
+  let aggregate_interval = Duration::from_secs(
+    get_env_var(
+      "APPFLOWY_REALTIME_BANDWIDTH_AGGREGATE_INTERVAL_SECS",
+      "3600",
+    )
+    .parse()
+    .unwrap_or(3600),
+  );
+
+  tokio::spawn(async move {
+    let mut flush_tick = tokio::time::interval(flush_interval);
+    let mut aggregate_tick = tokio::time::interval(aggregate_interval);
+    loop {
+      tokio::select! {
+        _ = flush_tick.tick() => {
+          let usage = bandwidth.take_all();
+          if !usage.is_empty() {
+            let today = Utc::now().date_naive();
+            match flush_to_redis(&mut redis, today, &usage).await {
+              Ok(()) => trace!("flushed realtime bandwidth of {} workspaces", usage.len()),
+              Err(err) => {
+                warn!("failed to flush realtime bandwidth: {}", err);
+                bandwidth.restore(usage);
+              },
+            }
+          }
+        },
+        _ = aggregate_tick.tick() => {
+          let today = Utc::now().date_naive();
+          let days = [today.checked_sub_days(Days::new(1)), Some(today)];
+          for day in days.into_iter().flatten() {
+            if let Err(err) = aggregate_day(&mut redis, &pg_pool, day).await {
+              warn!("failed to aggregate realtime bandwidth of {}: {}", day, err);
+            }
+          }
+        },
+      }
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn unflushed_usage_is_kept_test() {
+    let bandwidth = RealtimeBandwidth::default();
+    let counter = bandwidth.workspace("w1");
+    counter.record_in(10);
+    counter.record_out(20);
+
+    let usage = bandwidth.take_all();
+    assert_eq!(
+      usage,
+      vec![(
+        "w1".to_string(),
+        BandwidthUsage {
+          bytes_in: 10,
+          bytes_out: 20,
+          bytes_out_by_class: [0; 3],
+        }
+      )]
+    );
+    assert!(counter.usage().is_empty());
+
+    // the flush failed, the next one sends the usage again
+    counter.record_in(1);
+    bandwidth.restore(usage);
+    assert_eq!(
+      bandwidth.take_all(),
+      vec![(
+        "w1".to_string(),
+        BandwidthUsage {
+          bytes_in: 11,
+          bytes_out: 20,
+          bytes_out_by_class: [0; 3],
+        }
+      )]
+    );
+  }
+
+  #[test]
+  fn event_bytes_are_counted_by_class_test() {
+    let counter = BandwidthCounter::default();
+    counter.record_event_out(EventClass::Update, 30);
+    counter.record_event_out(EventClass::Control, 2);
+    counter.record_out(8);
+    assert_eq!(
+      counter.take(),
+      BandwidthUsage {
+        bytes_in: 0,
+        bytes_out: 40,
+        bytes_out_by_class: [30, 0, 2],
+      }
+    );
+  }
+
+  #[test]
+  fn unused_workspace_counter_is_dropped_test() {
+    let bandwidth = RealtimeBandwidth::default();
+    let counter = bandwidth.workspace("w1");
+    counter.record_out(5);
+    drop(counter);
+
+    // the counter is still flushed once after its last group is gone
+    assert_eq!(bandwidth.take_all().len(), 1);
+    assert!(bandwidth.workspaces.is_empty());
+  }
+
+  #[test]
+  fn parse_daily_counters_test() {
+    let workspace_id = Uuid::new_v4();
+    let counters = HashMap::from([
+      (bytes_in_field(&workspace_id.to_string()), 100),
+      (bytes_out_field(&workspace_id.to_string()), 250),
+      (
+        bytes_out_class_field(&workspace_id.to_string(), EventClass::Awareness),
+        40,
+      ),
+      ("not a workspace:in".to_string(), 1),
+    ]);
+    let usage = parse_daily_counters(counters);
+    assert_eq!(usage.len(), 1);
+    assert_eq!(
+      usage[&workspace_id],
+      BandwidthUsage {
+        bytes_in: 100,
+        bytes_out: 250,
+        bytes_out_by_class: [0, 40, 0],
+      }
+    );
+  }
+}
@@ -2,13 +2,14 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use async_trait::async_trait;
+use tokio::time::MissedTickBehavior;
 use tokio_stream::wrappers::{BroadcastStream, ReceiverStream};
 use tokio_stream::StreamExt;
 use tracing::{error, trace};
 
 use access_control::collab::RealtimeAccessControl;
 use collab_rt_entity::user::RealtimeUser;
-use collab_rt_entity::ClientCollabMessage;
+use collab_rt_entity::{ClientCollabMessage, CollabAccess, PermissionDenied, SystemMessage};
 use collab_rt_entity::{MessageByObjectId, RealtimeMessage};
 
 use crate::util::channel_ext::UnboundedSenderSink;
@@ -18,6 +19,17 @@ pub trait RealtimeClientWebsocketSink: Send + Sync + 'static {
   fn do_send(&self, message: RealtimeMessage);
 }
 
+/// The access of a subscriber to a collab, negotiated when it subscribes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SubscriptionAccess {
+  ReadWrite,
+  /// The collab is synced, but the updates of the subscriber are answered with
+  /// [PermissionDenied] and aren't applied.
+  ReadOnly,
+  /// Only the messages that don't change the collab are handled.
+  Denied,
+}
+
 /// Manages message routing for client connections in a collaborative environment.
 ///
 /// acts as an intermediary that receives messages from individual client sessions and
@@ -35,6 +47,10 @@ pub struct ClientMessageRouter {
 }
 
 impl ClientMessageRouter {
+  /// How often an idle subscription checks whether the access policies changed, the access is
+  /// checked again for each message otherwise.
+  const ACCESS_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
   pub fn new(sink: impl RealtimeClientWebsocketSink) -> Self {
     // When receive a new connection, create a new [ClientStream] that holds the connection's websocket
     let (stream_tx, _) = tokio::sync::broadcast::channel(1000);
@@ -53,6 +69,9 @@ impl ClientMessageRouter {
   /// - An `UnboundedSenderSink<T>`, which is used to send updates to the connected client.
   /// - A `ReceiverStream<MessageByObjectId>`, which is used to receive authorized updates from the connected client.
   ///
+  /// The client is told whether it can edit the collab, see [CollabAccess]. When its access changes, the
+  /// stream ends, which ends the subscription, and the next message of the client subscribes it again.
+  ///
   pub fn init_client_communication<T>(
     &mut self,
     workspace_id: &str,
@@ -96,6 +115,7 @@ impl ClientMessageRouter {
         }
       }
     });
+    let client_ws_sink = self.sink.clone();
     let target_object_id = object_id.to_string();
     let stream_workspace_id = workspace_id.to_string();
     let user = user.clone();
@@ -104,7 +124,57 @@ impl ClientMessageRouter {
     let (client_msg_rx, rx) = tokio::sync::mpsc::channel(100);
     let client_stream = ReceiverStream::new(rx);
     tokio::spawn(async move {
-      while let Some(Ok(messages_by_oid)) = stream_rx.next().await {
+      let mut policy_version = access_control.policy_version();
+      let access = Self::negotiate_access(
+        &stream_workspace_id,
+        &user.uid,
+        &target_object_id,
+        access_control.as_ref(),
+      )
+      .await;
+      Self::notify_access(client_ws_sink.as_ref(), &target_object_id, access);
+      let mut access_check = tokio::time::interval(Self::ACCESS_CHECK_INTERVAL);
+      access_check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+      loop {
+        let messages_by_oid = tokio::select! {
+          msg = stream_rx.next() => match msg {
+            Some(Ok(messages_by_oid)) => Some(messages_by_oid),
+            _ => return,
+          },
+          _ = access_check.tick() => {
+            let version = access_control.policy_version();
+            if version == policy_version {
+              continue;
+            }
+            policy_version = version;
+            None
+          },
+        };
+
+        // the role of the user might have changed since the subscription
+        let current_access = Self::negotiate_access(
+          &stream_workspace_id,
+          &user.uid,
+          &target_object_id,
+          access_control.as_ref(),
+        )
+        .await;
+        if current_access != access {
+          trace!(
+            "{} access of user:{} changed from {:?} to {:?}, end the subscription",
+            target_object_id,
+            user.user_device(),
+            access,
+            current_access,
+          );
+          Self::notify_access(client_ws_sink.as_ref(), &target_object_id, current_access);
+          return;
+        }
+        let Some(messages_by_oid) = messages_by_oid else {
+          continue;
+        };
+
         for (message_object_id, original_messages) in messages_by_oid.into_inner() {
           // if the message is not for the target object, skip it. The stream_rx receives different
           // objects' messages, so we need to filter out the messages that are not for the target object.
@@ -115,14 +185,17 @@ impl ClientMessageRouter {
           // before applying user messages, we need to check if the user has the permission
           // valid_messages contains the messages that the user is allowed to apply
           // invalid_message contains the messages that the user is not allowed to apply
-          let (valid_messages, invalid_message) = Self::access_control(
-            &stream_workspace_id,
-            &user.uid,
-            &message_object_id,
-            access_control.clone(),
-            original_messages,
-          )
-          .await;
+          let (valid_messages, invalid_message) = Self::access_control(access, original_messages);
+          if access == SubscriptionAccess::ReadOnly {
+            for message in &invalid_message {
+              client_ws_sink.do_send(RealtimeMessage::System(SystemMessage::PermissionDenied(
+                PermissionDenied {
+                  object_id: message_object_id.clone(),
+                  msg_id: message.msg_id(),
+                },
+              )));
+            }
+          }
           trace!(
             "{} receive client:{}, device:{}, message: valid:{} invalid:{}",
             message_object_id,
@@ -162,24 +235,73 @@ impl ClientMessageRouter {
     self.sink.do_send(message);
   }
 
-  #[inline]
-  async fn access_control(
+  async fn negotiate_access(
     workspace_id: &str,
     uid: &i64,
     object_id: &str,
-    access_control: Arc<dyn RealtimeAccessControl>,
-    messages: Vec<ClientCollabMessage>,
-  ) -> (Vec<ClientCollabMessage>, Vec<ClientCollabMessage>) {
+    access_control: &dyn RealtimeAccessControl,
+  ) -> SubscriptionAccess {
     let can_write = access_control
       .can_write_collab(workspace_id, uid, object_id)
       .await
       .unwrap_or(false);
+    if can_write {
+      return SubscriptionAccess::ReadWrite;
+    }
+    let can_read = access_control
+      .can_read_collab(workspace_id, uid, object_id)
+      .await
+      .unwrap_or(false);
+    if can_read {
+      SubscriptionAccess::ReadOnly
+    } else {
+      SubscriptionAccess::Denied
+    }
+  }
+
+  fn notify_access(
+    sink: &dyn RealtimeClientWebsocketSink,
+    object_id: &str,
+    access: SubscriptionAccess,
+  ) {
+    let read_only = match access {
+      SubscriptionAccess::ReadWrite => false,
+      SubscriptionAccess::ReadOnly => true,
+      // the messages sent to the collab aren't answered at all
+      SubscriptionAccess::Denied => return,
+    };
+    sink.do_send(RealtimeMessage::System(SystemMessage::CollabAccess(
+      CollabAccess {
+        object_id: object_id.to_string(),
+        read_only,
+      },
+    )));
+  }
 
+  #[inline]
+  fn access_control(
+    access: SubscriptionAccess,
+    messages: Vec<ClientCollabMessage>,
+  ) -> (Vec<ClientCollabMessage>, Vec<ClientCollabMessage>) {
     let mut valid_messages = Vec::with_capacity(messages.len());
     let mut invalid_messages = Vec::with_capacity(messages.len());
 
     for message in messages {
-      if can_write {
+      let is_allowed = match access {
+        SubscriptionAccess::ReadWrite => true,
+        // the updates of the reader, including the ones it answers the sync of the server with,
+        // would change the collab
+        SubscriptionAccess::ReadOnly => !matches!(
+          message,
+          ClientCollabMessage::ClientUpdateSync { .. } | ClientCollabMessage::ServerInitSync(_)
+        ),
+        // selecting the events to receive doesn't change the collab, and the events are only
+        // sent to the users who can read it
+        SubscriptionAccess::Denied => {
+          matches!(message, ClientCollabMessage::ClientSubscriptionFilter(_))
+        },
+      };
+      if is_allowed {
         valid_messages.push(message);
       } else {
         invalid_messages.push(message);
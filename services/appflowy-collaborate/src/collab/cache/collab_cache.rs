@@ -6,6 +6,7 @@ use itertools::{Either, Itertools};
 use sqlx::{PgPool, Transaction};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::{error, event, Level};
 
 use super::disk_cache::CollabDiskCache;
@@ -13,7 +14,10 @@ use super::mem_cache::{cache_exp_secs_from_collab_type, CollabMemCache};
 use crate::CollabMetrics;
 use app_error::AppError;
 use database::file::s3_client_impl::AwsS3BucketClientImpl;
-use database_entity::dto::{CollabParams, PendingCollabWrite, QueryCollab, QueryCollabResult};
+use database::operation_delay::OperationDelay;
+use database_entity::dto::{
+  CollabMode, CollabParams, PendingCollabWrite, QueryCollab, QueryCollabResult,
+};
 
 #[derive(Clone)]
 pub struct CollabCache {
@@ -42,10 +46,26 @@ impl CollabCache {
     }
   }
 
+  /// Waits for the given [OperationDelay] before each operation is sent to Redis, and before each
+  /// transaction is started in Postgres.
+  pub fn with_operation_delay(mut self, operation_delay: Arc<dyn OperationDelay>) -> Self {
+    self.mem_cache = self.mem_cache.with_operation_delay(operation_delay.clone());
+    self.disk_cache = self.disk_cache.with_operation_delay(operation_delay);
+    self
+  }
+
   pub fn metrics(&self) -> &CollabMetrics {
     &self.metrics
   }
 
+  pub async fn pg_acquire_latency(&self) -> Result<Duration, AppError> {
+    self.disk_cache.acquire_latency().await
+  }
+
+  pub async fn redis_latency(&self) -> Result<Duration, AppError> {
+    self.mem_cache.ping_latency().await
+  }
+
   pub async fn bulk_insert_collab(
     &self,
     workspace_id: &str,
@@ -192,6 +212,30 @@ impl CollabCache {
     Ok(())
   }
 
+  /// Insert new collabs within the given transaction, see
+  /// [CollabDiskCache::bulk_insert_new_collab_with_transaction]. The collabs are also written to
+  /// the memory cache.
+  pub async fn bulk_insert_new_collab_with_transaction(
+    &self,
+    workspace_id: &str,
+    uid: &i64,
+    params_list: Vec<CollabParams>,
+    transaction: &mut Transaction<'_, sqlx::Postgres>,
+  ) -> Result<(), AppError> {
+    self
+      .disk_cache
+      .bulk_insert_new_collab_with_transaction(workspace_id, uid, params_list.clone(), transaction)
+      .await?;
+    for params in params_list {
+      self.cache_collab(
+        params.object_id,
+        params.collab_type,
+        params.encoded_collab_v1,
+      );
+    }
+    Ok(())
+  }
+
   fn cache_collab(&self, object_id: String, collab_type: CollabType, encode_collab_data: Bytes) {
     let mem_cache = self.mem_cache.clone();
     tokio::spawn(async move {
@@ -227,15 +271,21 @@ impl CollabCache {
     Ok(())
   }
 
-  pub async fn delete_collab(&self, workspace_id: &str, object_id: &str) -> Result<(), AppError> {
+  pub async fn delete_collab(&self, object_id: &str) -> Result<(), AppError> {
     self.mem_cache.remove_encode_collab(object_id).await?;
-    self
-      .disk_cache
-      .delete_collab(workspace_id, object_id)
-      .await?;
+    self.disk_cache.delete_collab(object_id).await?;
     Ok(())
   }
 
+  pub async fn is_collab_deleted(&self, object_id: &str) -> Result<bool, AppError> {
+    self.disk_cache.is_collab_deleted(object_id).await
+  }
+
+  /// Remove the collab from the memory cache, once it's deleted from the disk.
+  pub async fn evict_collab(&self, object_id: &str) -> Result<(), AppError> {
+    self.mem_cache.remove_encode_collab(object_id).await
+  }
+
   pub async fn is_exist(&self, workspace_id: &str, oid: &str) -> Result<bool, AppError> {
     if let Ok(value) = self.mem_cache.is_exist(oid).await {
       if value {
@@ -247,6 +297,10 @@ impl CollabCache {
     Ok(is_exist)
   }
 
+  pub async fn collab_mode(&self, object_id: &str) -> Result<CollabMode, AppError> {
+    self.disk_cache.collab_mode(object_id).await
+  }
+
   pub async fn batch_insert_collab(
     &self,
     records: Vec<PendingCollabWrite>,
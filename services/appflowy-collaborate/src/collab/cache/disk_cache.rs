@@ -1,7 +1,7 @@
 use anyhow::{anyhow, Context};
 use bytes::Bytes;
 use collab::entity::{EncodedCollab, EncoderVersion};
-use sqlx::{Error, PgPool, Transaction};
+use sqlx::{Error, PgPool, Postgres, Transaction};
 use std::collections::HashMap;
 use std::ops::DerefMut;
 use std::sync::Arc;
@@ -14,13 +14,17 @@ use crate::collab::cache::encode_collab_from_bytes;
 use crate::CollabMetrics;
 use app_error::AppError;
 use database::collab::{
-  batch_select_collab_blob, insert_into_af_collab, insert_into_af_collab_bulk_for_user,
-  is_collab_exists, select_blob_from_af_collab, AppResult,
+  batch_select_collab_blob, collab_object_key, insert_into_af_collab,
+  insert_into_af_collab_bulk_for_user, insert_new_collabs_bulk_for_user, is_collab_exists,
+  select_blob_from_af_collab, select_deleted_collab_oids, AppResult,
 };
+use database::collab_mode::select_collab_mode;
 use database::file::s3_client_impl::AwsS3BucketClientImpl;
 use database::file::{BucketClient, ResponseBlob};
+use database::operation_delay::{DelayedDependency, OperationDelay};
 use database_entity::dto::{
-  CollabParams, PendingCollabWrite, QueryCollab, QueryCollabResult, ZSTD_COMPRESSION_LEVEL,
+  CollabMode, CollabParams, PendingCollabWrite, QueryCollab, QueryCollabResult,
+  ZSTD_COMPRESSION_LEVEL,
 };
 
 #[derive(Clone)]
@@ -29,6 +33,7 @@ pub struct CollabDiskCache {
   s3: AwsS3BucketClientImpl,
   s3_collab_threshold: usize,
   metrics: Arc<CollabMetrics>,
+  operation_delay: Option<Arc<dyn OperationDelay>>,
 }
 
 impl CollabDiskCache {
@@ -43,9 +48,38 @@ impl CollabDiskCache {
       s3,
       s3_collab_threshold,
       metrics,
+      operation_delay: None,
     }
   }
 
+  /// Waits for the given [OperationDelay] before each transaction is started.
+  pub fn with_operation_delay(mut self, operation_delay: Arc<dyn OperationDelay>) -> Self {
+    self.operation_delay = Some(operation_delay);
+    self
+  }
+
+  async fn begin(&self, operation: &str) -> Result<Transaction<'static, Postgres>, Error> {
+    if let Some(operation_delay) = &self.operation_delay {
+      operation_delay
+        .delay(DelayedDependency::Postgres, operation)
+        .await;
+    }
+    self.pg_pool.begin().await
+  }
+
+  /// How long it takes to get a connection from the pool, which grows when Postgres can't keep up
+  /// with the queries.
+  pub async fn acquire_latency(&self) -> AppResult<Duration> {
+    let start = Instant::now();
+    if let Some(operation_delay) = &self.operation_delay {
+      operation_delay
+        .delay(DelayedDependency::Postgres, "acquire")
+        .await;
+    }
+    let _conn = self.pg_pool.acquire().await?;
+    Ok(start.elapsed())
+  }
+
   pub async fn is_exist(&self, workspace_id: &str, object_id: &str) -> AppResult<bool> {
     let dir = collab_key_prefix(workspace_id, object_id);
     let resp = self.s3.list_dir(&dir, 1).await?;
@@ -57,6 +91,10 @@ impl CollabDiskCache {
     }
   }
 
+  pub async fn collab_mode(&self, object_id: &str) -> AppResult<CollabMode> {
+    select_collab_mode(&self.pg_pool, object_id).await
+  }
+
   pub async fn upsert_collab(
     &self,
     workspace_id: &str,
@@ -65,8 +103,7 @@ impl CollabDiskCache {
   ) -> AppResult<()> {
     // Start a database transaction
     let mut transaction = self
-      .pg_pool
-      .begin()
+      .begin("upsert_collab")
       .await
       .context("Failed to acquire transaction for writing pending collaboration data")
       .map_err(AppError::from)?;
@@ -140,6 +177,11 @@ impl CollabDiskCache {
     let key = collab_key(workspace_id, &query.object_id);
     match self.s3.get_blob(&key).await {
       Ok(resp) => {
+        // the doc state of a collab in the trash is kept in S3 until it's purged
+        if self.is_collab_deleted(&query.object_id).await? {
+          let msg = format!("The collab is deleted: {:?}", query);
+          return Err(AppError::RecordNotFound(msg));
+        }
         self.metrics.s3_read_collab_count.inc();
         let blob = resp.to_blob();
         let now = Instant::now();
@@ -228,7 +270,7 @@ impl CollabDiskCache {
     let s3_count = blobs.len() as u64;
     let pg_count = delete_from_s3.len() as u64;
 
-    let mut transaction = self.pg_pool.begin().await?;
+    let mut transaction = self.begin("bulk_insert_collab").await?;
     let start = Instant::now();
     insert_into_af_collab_bulk_for_user(&mut transaction, uid, workspace_id, &params_list).await?;
     transaction.commit().await?;
@@ -248,6 +290,33 @@ impl CollabDiskCache {
     Ok(())
   }
 
+  /// Inserts new collabs within the given transaction, see [insert_new_collabs_bulk_for_user].
+  /// The collabs over the S3 threshold are uploaded once they're inserted, before the caller
+  /// commits the transaction.
+  pub async fn bulk_insert_new_collab_with_transaction(
+    &self,
+    workspace_id: &str,
+    uid: &i64,
+    mut params_list: Vec<CollabParams>,
+    transaction: &mut Transaction<'_, sqlx::Postgres>,
+  ) -> AppResult<()> {
+    let mut blobs = HashMap::new();
+    for param in params_list.iter_mut() {
+      if param.encoded_collab_v1.len() > self.s3_collab_threshold {
+        let key = collab_key(workspace_id, &param.object_id);
+        blobs.insert(key, std::mem::take(&mut param.encoded_collab_v1));
+      }
+    }
+    let s3_count = blobs.len() as u64;
+    let pg_count = params_list.len() as u64 - s3_count;
+
+    insert_new_collabs_bulk_for_user(transaction, uid, workspace_id, &params_list).await?;
+    batch_put_collab_to_s3(&self.s3, blobs).await?;
+    self.metrics.s3_write_collab_count.inc_by(s3_count);
+    self.metrics.pg_write_collab_count.inc_by(pg_count);
+    Ok(())
+  }
+
   pub async fn batch_insert_collab(
     &self,
     records: Vec<PendingCollabWrite>,
@@ -259,8 +328,7 @@ impl CollabDiskCache {
     let s3 = self.s3.clone();
     // Start a database transaction
     let mut transaction = self
-      .pg_pool
-      .begin()
+      .begin("batch_insert_collab")
       .await
       .context("Failed to acquire transaction for writing pending collaboration data")
       .map_err(AppError::from)?;
@@ -320,6 +388,30 @@ impl CollabDiskCache {
   ) -> HashMap<String, QueryCollabResult> {
     let mut results = HashMap::new();
     let not_found = batch_get_collab_from_s3(&self.s3, workspace_id, queries, &mut results).await;
+    if !results.is_empty() {
+      let object_ids: Vec<String> = results.keys().cloned().collect();
+      match select_deleted_collab_oids(&self.pg_pool, &object_ids).await {
+        Ok(deleted) => {
+          for object_id in deleted {
+            results.insert(
+              object_id,
+              QueryCollabResult::Failed {
+                error: "Record not found".to_string(),
+              },
+            );
+          }
+        },
+        Err(err) => {
+          // the collabs read from S3 may be in the trash, they aren't returned
+          error!("Failed to check the deleted collabs: {}", err);
+          for result in results.values_mut() {
+            *result = QueryCollabResult::Failed {
+              error: format!("Failed to check whether the collab is deleted: {}", err),
+            };
+          }
+        },
+      }
+    }
     let s3_fetch = results.len() as u64;
     batch_select_collab_blob(&self.pg_pool, not_found, &mut results).await;
     let pg_fetch = results.len() as u64 - s3_fetch;
@@ -328,7 +420,9 @@ impl CollabDiskCache {
     results
   }
 
-  pub async fn delete_collab(&self, workspace_id: &str, object_id: &str) -> AppResult<()> {
+  /// Moves the collab to the trash. Its doc state, in Postgres or S3, is kept until it's purged,
+  /// so that it can be restored.
+  pub async fn delete_collab(&self, object_id: &str) -> AppResult<()> {
     sqlx::query!(
       r#"
         UPDATE af_collab
@@ -340,11 +434,12 @@ impl CollabDiskCache {
     )
     .execute(&self.pg_pool)
     .await?;
-    let key = collab_key(workspace_id, object_id);
-    match self.s3.delete_blob(&key).await {
-      Ok(_) | Err(AppError::RecordNotFound(_)) => Ok(()),
-      Err(err) => Err(err),
-    }
+    Ok(())
+  }
+
+  pub async fn is_collab_deleted(&self, object_id: &str) -> AppResult<bool> {
+    let deleted = select_deleted_collab_oids(&self.pg_pool, &[object_id.to_string()]).await?;
+    Ok(!deleted.is_empty())
   }
 
   async fn insert_blob_with_retries(
@@ -504,8 +599,5 @@ fn collab_key_prefix(workspace_id: &str, object_id: &str) -> String {
 }
 
 fn collab_key(workspace_id: &str, object_id: &str) -> String {
-  format!(
-    "collabs/{}/{}/encoded_collab.v1.zstd",
-    workspace_id, object_id
-  )
+  collab_object_key(workspace_id, object_id)
 }
@@ -3,12 +3,14 @@ use collab::entity::EncodedCollab;
 use collab_entity::CollabType;
 use redis::{pipe, AsyncCommands};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::{error, instrument, trace};
 
 use crate::collab::cache::encode_collab_from_bytes;
 use crate::CollabMetrics;
 use app_error::AppError;
 use database::collab::CollabMetadata;
+use database::operation_delay::{DelayedDependency, OperationDelay};
 
 const SEVEN_DAYS: u64 = 604800;
 const ONE_MONTH: u64 = 2592000;
@@ -16,6 +18,7 @@ const ONE_MONTH: u64 = 2592000;
 pub struct CollabMemCache {
   connection_manager: redis::aio::ConnectionManager,
   metrics: Arc<CollabMetrics>,
+  operation_delay: Option<Arc<dyn OperationDelay>>,
 }
 
 impl CollabMemCache {
@@ -26,10 +29,37 @@ impl CollabMemCache {
     Self {
       connection_manager,
       metrics,
+      operation_delay: None,
     }
   }
 
+  /// Waits for the given [OperationDelay] before each operation is sent to Redis.
+  pub fn with_operation_delay(mut self, operation_delay: Arc<dyn OperationDelay>) -> Self {
+    self.operation_delay = Some(operation_delay);
+    self
+  }
+
+  async fn delay(&self, operation: &str) {
+    if let Some(operation_delay) = &self.operation_delay {
+      operation_delay
+        .delay(DelayedDependency::Redis, operation)
+        .await;
+    }
+  }
+
+  /// How long Redis takes to answer a ping, which grows with the commands queued before it.
+  pub async fn ping_latency(&self) -> Result<Duration, AppError> {
+    let start = Instant::now();
+    self.delay("ping").await;
+    let _: String = redis::cmd("PING")
+      .query_async(&mut self.connection_manager.clone())
+      .await
+      .map_err(|err| AppError::Internal(anyhow!("Failed to ping redis: {:?}", err)))?;
+    Ok(start.elapsed())
+  }
+
   pub async fn insert_collab_meta(&self, meta: CollabMetadata) -> Result<(), AppError> {
+    self.delay("insert_collab_meta").await;
     let key = collab_meta_key(&meta.object_id);
     let value = serde_json::to_string(&meta)?;
     let () = self
@@ -44,6 +74,7 @@ impl CollabMemCache {
   }
 
   pub async fn get_collab_meta(&self, object_id: &str) -> Result<CollabMetadata, AppError> {
+    self.delay("get_collab_meta").await;
     let key = collab_meta_key(object_id);
     let value: Option<String> = self
       .connection_manager
@@ -67,6 +98,7 @@ impl CollabMemCache {
 
   /// Checks if an object with the given ID exists in the cache.
   pub async fn is_exist(&self, object_id: &str) -> Result<bool, AppError> {
+    self.delay("is_exist").await;
     let cache_object_id = encode_collab_key(object_id);
     let exists: bool = self
       .connection_manager
@@ -78,6 +110,7 @@ impl CollabMemCache {
   }
 
   pub async fn remove_encode_collab(&self, object_id: &str) -> Result<(), AppError> {
+    self.delay("remove_encode_collab").await;
     let cache_object_id = encode_collab_key(object_id);
     self
       .connection_manager
@@ -180,6 +213,7 @@ impl CollabMemCache {
     timestamp: i64,
     expiration_seconds: Option<u64>,
   ) -> redis::RedisResult<()> {
+    self.delay("insert_encode_collab").await;
     let cache_object_id = encode_collab_key(object_id);
     let mut conn = self.connection_manager.clone();
     let key_exists: bool = conn.exists(&cache_object_id).await?;
@@ -259,6 +293,7 @@ impl CollabMemCache {
     &self,
     object_id: &str,
   ) -> redis::RedisResult<Option<(i64, Vec<u8>)>> {
+    self.delay("get_encode_collab").await;
     let cache_object_id = encode_collab_key(object_id);
     let mut conn = self.connection_manager.clone();
     // Attempt to retrieve the data from Redis
@@ -11,7 +11,7 @@ pub(crate) async fn encode_collab_from_bytes(bytes: Vec<u8>) -> Result<EncodedCo
   // FIXME: Implement metrics to determine the appropriate data size for decoding in a blocking task.
   tokio::task::spawn_blocking(move || match EncodedCollab::decode_from_bytes(&bytes) {
     Ok(encoded_collab) => Ok(encoded_collab),
-    Err(err) => Err(AppError::Internal(anyhow::anyhow!(
+    Err(err) => Err(AppError::DecodeUpdateError(format!(
       "Failed to decode collab from bytes: {:?}",
       err
     ))),
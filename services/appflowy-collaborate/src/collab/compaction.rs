@@ -0,0 +1,660 @@
+//! Replaces the stored state of a collab by a compacted copy, which only holds its current
+//! content, see [crate::group::compaction::compact_collab].
+//!
+//! The snapshot lease is held throughout, so that no realtime server persists the collab while
+//! it's compacted. The state before the compaction is kept as a snapshot. The compacted copy has
+//! none of the history the clients built their state on, so the compaction ends with the reset
+//! marker, which makes the groups of the collab, on every realtime server, tell their clients to
+//! load the collab again.
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+
+use app_error::AppError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use collab::core::origin::CollabOrigin;
+use collab::entity::{EncodedCollab, EncoderVersion};
+use collab_entity::CollabType;
+use collab_stream::client::CollabRedisStream;
+use collab_stream::model::{CollabStreamUpdate, MessageId, UpdateFlags};
+use database::collab::CollabStorage;
+use database::collab_compaction::select_collabs_over_len;
+use database::pg_row::AFCollabCompactionCandidateRow;
+use database_entity::dto::InsertSnapshotParams;
+use sqlx::PgPool;
+use tracing::{info, trace, warn};
+use yrs::updates::encoder::Encode;
+use yrs::{ReadTxn, StateVector, Update};
+
+use crate::collab::live_migration::{open_collab, replay, MigratingCollab, StoredCollab};
+use crate::config::get_env_var;
+use crate::group::compaction::compact_collab;
+
+/// Number of collabs loaded at once by the sweeper.
+const SWEEP_BATCH_SIZE: i64 = 100;
+
+#[derive(Debug, Clone)]
+pub struct CollabCompactionConfig {
+  /// Whether the sweeper periodically compacts the collabs larger than `min_len`.
+  pub sweeper_enabled: bool,
+  pub sweep_interval: Duration,
+  /// Bytes of the encoded collab above which the sweeper compacts it.
+  pub min_len: usize,
+  /// The compacted copy is only stored when it's at most this fraction of the collab.
+  pub max_ratio: f64,
+  /// Whether the sweeper leaves the collabs with connected clients alone, rather than making the
+  /// clients load them again.
+  pub skip_connected: bool,
+}
+
+impl Default for CollabCompactionConfig {
+  fn default() -> Self {
+    Self {
+      sweeper_enabled: false,
+      sweep_interval: Duration::from_secs(86400),
+      min_len: 8 * 1024 * 1024,
+      max_ratio: 0.5,
+      skip_connected: true,
+    }
+  }
+}
+
+impl CollabCompactionConfig {
+  pub fn from_env() -> Result<Self, anyhow::Error> {
+    let defaults = Self::default();
+    let config = Self {
+      sweeper_enabled: get_env_var(
+        "APPFLOWY_COLLAB_COMPACTION_SWEEPER_ENABLED",
+        &defaults.sweeper_enabled.to_string(),
+      )
+      .parse()?,
+      sweep_interval: Duration::from_secs(
+        get_env_var(
+          "APPFLOWY_COLLAB_COMPACTION_SWEEP_INTERVAL_SECS",
+          &defaults.sweep_interval.as_secs().to_string(),
+        )
+        .parse()?,
+      ),
+      min_len: get_env_var(
+        "APPFLOWY_COLLAB_COMPACTION_MIN_SIZE",
+        &defaults.min_len.to_string(),
+      )
+      .parse()?,
+      max_ratio: get_env_var(
+        "APPFLOWY_COLLAB_COMPACTION_MAX_RATIO",
+        &defaults.max_ratio.to_string(),
+      )
+      .parse()?,
+      skip_connected: get_env_var(
+        "APPFLOWY_COLLAB_COMPACTION_SKIP_CONNECTED",
+        &defaults.skip_connected.to_string(),
+      )
+      .parse()?,
+    };
+    if config.max_ratio.is_nan() || config.max_ratio <= 0.0 || config.max_ratio > 1.0 {
+      anyhow::bail!("the collab compaction ratio must be within (0, 1]");
+    }
+    if config.sweeper_enabled && config.sweep_interval.is_zero() {
+      anyhow::bail!("the collab compaction sweep interval must not be zero");
+    }
+    Ok(config)
+  }
+}
+
+/// A collab being compacted, on top of what's needed to migrate it.
+#[async_trait]
+pub trait CompactingCollab: MigratingCollab {
+  /// Whether clients are connected to the collab, on any realtime server.
+  async fn has_connected_clients(&self) -> Result<bool, AppError>;
+
+  /// Keeps `doc_state`, v1 encoded, as a snapshot of the collab and returns the snapshot id.
+  async fn snapshot(&self, doc_state: Vec<u8>) -> Result<i64, AppError>;
+
+  /// Writes the reset marker to the stream and drops the updates up to `message_id`, which
+  /// belong to the history removed by the compaction. Only called while holding the lease.
+  async fn reset(&self, message_id: Option<MessageId>) -> Result<(), AppError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompactionSkipped {
+  /// Clients are connected and the compaction was asked to leave the collab alone then.
+  Connected,
+  /// The collab is being persisted by a realtime server.
+  LeaseHeld,
+  /// The compacted copy isn't small enough to be worth making the clients reload the collab.
+  NoGain,
+  /// The collab was edited while it was compacted.
+  Edited,
+}
+
+impl CompactionSkipped {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      CompactionSkipped::Connected => "connected",
+      CompactionSkipped::LeaseHeld => "lease_held",
+      CompactionSkipped::NoGain => "no_gain",
+      CompactionSkipped::Edited => "edited",
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactionOutcome {
+  /// `None` when the collab was compacted.
+  pub skipped: Option<CompactionSkipped>,
+  /// Snapshot holding the collab as it was before the compaction.
+  pub snapshot_id: Option<i64>,
+  /// Bytes of the doc state before and after the compaction, zero when they weren't computed.
+  pub doc_state_len: usize,
+  pub compacted_len: usize,
+}
+
+impl CompactionOutcome {
+  fn skipped(reason: CompactionSkipped) -> Self {
+    Self {
+      skipped: Some(reason),
+      snapshot_id: None,
+      doc_state_len: 0,
+      compacted_len: 0,
+    }
+  }
+}
+
+/// Compacts the collab. With `skip_connected`, a collab with connected clients is left alone,
+/// otherwise they're made to load it again.
+pub async fn compact_stored_collab(
+  collab: &dyn CompactingCollab,
+  object_id: &str,
+  max_ratio: f64,
+  skip_connected: bool,
+) -> Result<CompactionOutcome, AppError> {
+  if skip_connected && collab.has_connected_clients().await? {
+    return Ok(CompactionOutcome::skipped(CompactionSkipped::Connected));
+  }
+  if !collab.acquire().await? {
+    return Ok(CompactionOutcome::skipped(CompactionSkipped::LeaseHeld));
+  }
+  let result = compact_leased(collab, object_id, max_ratio).await;
+  collab.release().await?;
+  result
+}
+
+/// Must be called while holding the lease.
+async fn compact_leased(
+  collab: &dyn CompactingCollab,
+  object_id: &str,
+  max_ratio: f64,
+) -> Result<CompactionOutcome, AppError> {
+  let stored = collab
+    .load_stored()
+    .await?
+    .ok_or_else(|| AppError::RecordNotFound(format!("collab {} is not stored", object_id)))?;
+  let updates = collab.updates_since(None).await?;
+  let last_message_id = updates.last().map(|(message_id, _)| *message_id);
+
+  let object_id_owned = object_id.to_string();
+  let (doc_state, compacted) = tokio::task::spawn_blocking(move || {
+    let mut current = open_collab(&object_id_owned, Some(&stored))?;
+    replay(&mut current, updates)?;
+    let doc_state = current
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+    // the compacted copy is stored in the encoding of the collab
+    let compacted = compact_collab(&current).map_err(AppError::Internal)?;
+    let txn = compacted.transact();
+    let encoded = match stored.version {
+      EncoderVersion::V1 => EncodedCollab::new_v1(
+        txn.state_vector().encode_v1(),
+        txn.encode_state_as_update_v1(&StateVector::default()),
+      ),
+      EncoderVersion::V2 => EncodedCollab::new_v2(
+        txn.state_vector().encode_v2(),
+        txn.encode_state_as_update_v2(&StateVector::default()),
+      ),
+    };
+    Ok::<_, AppError>((doc_state, encoded))
+  })
+  .await
+  .map_err(|err| AppError::Internal(err.into()))??;
+
+  let doc_state_len = doc_state.len();
+  let compacted_len = compacted.doc_state.len();
+  if compacted_len as f64 > doc_state_len as f64 * max_ratio {
+    trace!(
+      "skip compaction of collab {}: {} bytes compacted to {} bytes",
+      object_id,
+      doc_state_len,
+      compacted_len
+    );
+    return Ok(CompactionOutcome {
+      skipped: Some(CompactionSkipped::NoGain),
+      snapshot_id: None,
+      doc_state_len,
+      compacted_len,
+    });
+  }
+  // updates that arrived meanwhile are not part of the compacted copy
+  if !collab.updates_since(last_message_id).await?.is_empty() {
+    return Ok(CompactionOutcome {
+      skipped: Some(CompactionSkipped::Edited),
+      snapshot_id: None,
+      doc_state_len,
+      compacted_len,
+    });
+  }
+
+  let snapshot_id = collab.snapshot(doc_state).await?;
+  collab.store(compacted).await?;
+  collab.reset(last_message_id).await?;
+  info!(
+    "compacted collab {}: {} bytes to {} bytes, previous state kept in snapshot {}",
+    object_id, doc_state_len, compacted_len, snapshot_id
+  );
+  Ok(CompactionOutcome {
+    skipped: None,
+    snapshot_id: Some(snapshot_id),
+    doc_state_len,
+    compacted_len,
+  })
+}
+
+#[async_trait]
+impl CompactingCollab for StoredCollab {
+  async fn has_connected_clients(&self) -> Result<bool, AppError> {
+    let presences = self
+      .collab_redis_stream
+      .presence()
+      .get(&self.workspace_id, &self.object_id)
+      .await
+      .map_err(|err| AppError::Internal(err.into()))?;
+    Ok(!presences.is_empty())
+  }
+
+  async fn snapshot(&self, doc_state: Vec<u8>) -> Result<i64, AppError> {
+    let params = InsertSnapshotParams {
+      object_id: self.object_id.clone(),
+      doc_state: doc_state.into(),
+      workspace_id: self.workspace_id.clone(),
+      collab_type: self.collab_type.clone(),
+      created_by: None,
+    };
+    let meta = self.storage.create_snapshot(params).await?;
+    Ok(meta.snapshot_id)
+  }
+
+  async fn reset(&self, message_id: Option<MessageId>) -> Result<(), AppError> {
+    let reset = CollabStreamUpdate::new(
+      Update::default().encode_v1(),
+      CollabOrigin::Server,
+      UpdateFlags::IS_RESET,
+    );
+    self
+      .collab_redis_stream
+      .collab_update_sink(&self.workspace_id, &self.object_id)
+      .send(&reset)
+      .await
+      .map_err(|err| AppError::Internal(err.into()))?;
+    if let Some(message_id) = message_id {
+      let stream_key = CollabStreamUpdate::stream_key(&self.workspace_id, &self.object_id);
+      self
+        .collab_redis_stream
+        .prune_update_stream(&stream_key, message_id)
+        .await
+        .map_err(|err| AppError::Internal(err.into()))?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CompactionStats {
+  pub scanned: usize,
+  pub compacted: usize,
+  pub skipped: usize,
+  pub errors: usize,
+  /// Bytes removed from the doc states of the compacted collabs.
+  pub saved_len: usize,
+}
+
+impl Display for CompactionStats {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{} scanned, {} compacted, {} skipped, {} errors, {} bytes saved",
+      self.scanned, self.compacted, self.skipped, self.errors, self.saved_len
+    )
+  }
+}
+
+/// Compacts the stored collabs, on request or periodically for the collabs which grew larger
+/// than [CollabCompactionConfig::min_len].
+#[derive(Clone)]
+pub struct CollabCompactor {
+  pg_pool: PgPool,
+  config: CollabCompactionConfig,
+}
+
+impl CollabCompactor {
+  pub fn new(pg_pool: PgPool, config: CollabCompactionConfig) -> Self {
+    Self { pg_pool, config }
+  }
+
+  /// Compacts the collab of `row`, written on behalf of its owner. Encrypted collabs can't be
+  /// read by the server and are rejected.
+  pub async fn compact(
+    &self,
+    storage: Arc<dyn CollabStorage>,
+    collab_redis_stream: Arc<CollabRedisStream>,
+    row: &AFCollabCompactionCandidateRow,
+    skip_connected: bool,
+  ) -> Result<CompactionOutcome, AppError> {
+    if storage.collab_mode(&row.oid).await?.is_opaque() {
+      return Err(AppError::InvalidRequest(format!(
+        "collab {} is encrypted and can't be compacted by the server",
+        row.oid
+      )));
+    }
+    let collab = StoredCollab::new(
+      storage,
+      collab_redis_stream,
+      row.owner_uid,
+      row.workspace_id.to_string(),
+      row.oid.clone(),
+      CollabType::from(row.partition_key),
+    );
+    compact_stored_collab(&collab, &row.oid, self.config.max_ratio, skip_connected).await
+  }
+
+  /// Walks the collabs larger than the threshold which were written since `updated_since`, the
+  /// others were already considered by a previous sweep.
+  pub async fn sweep(
+    &self,
+    storage: Arc<dyn CollabStorage>,
+    collab_redis_stream: Arc<CollabRedisStream>,
+    updated_since: Option<DateTime<Utc>>,
+  ) -> Result<CompactionStats, AppError> {
+    let min_len = i32::try_from(self.config.min_len).unwrap_or(i32::MAX);
+    let mut stats = CompactionStats::default();
+    let mut after_oid = None;
+    loop {
+      let rows = select_collabs_over_len(
+        &self.pg_pool,
+        min_len,
+        updated_since,
+        after_oid.as_deref(),
+        SWEEP_BATCH_SIZE,
+      )
+      .await?;
+      let Some(last) = rows.last() else {
+        break;
+      };
+      after_oid = Some(last.oid.clone());
+      for row in rows {
+        stats.scanned += 1;
+        match self
+          .compact(
+            storage.clone(),
+            collab_redis_stream.clone(),
+            &row,
+            self.config.skip_connected,
+          )
+          .await
+        {
+          Ok(outcome) if outcome.skipped.is_none() => {
+            stats.compacted += 1;
+            stats.saved_len += outcome.doc_state_len.saturating_sub(outcome.compacted_len);
+          },
+          Ok(_) => stats.skipped += 1,
+          Err(err) => {
+            stats.errors += 1;
+            warn!("failed to compact collab {}: {}", row.oid, err);
+          },
+        }
+      }
+    }
+    Ok(stats)
+  }
+}
+
+/// Runs the sweeper periodically when [CollabCompactionConfig::sweeper_enabled] is set.
+pub fn spawn_collab_compaction_sweeper<S>(
+  compactor: CollabCompactor,
+  storage: Arc<S>,
+  collab_redis_stream: CollabRedisStream,
+) where
+  S: CollabStorage,
+{
+  if !compactor.config.sweeper_enabled {
+    return;
+  }
+  let storage: Arc<dyn CollabStorage> = storage;
+  let collab_redis_stream = Arc::new(collab_redis_stream);
+  tokio::spawn(async move {
+    let mut tick = tokio::time::interval(compactor.config.sweep_interval);
+    let mut updated_since = None;
+    loop {
+      tick.tick().await;
+      let started_at = Utc::now();
+      match compactor
+        .sweep(storage.clone(), collab_redis_stream.clone(), updated_since)
+        .await
+      {
+        Ok(stats) => {
+          info!("collab compaction sweep: {}", stats);
+          updated_since = Some(started_at);
+        },
+        Err(err) => warn!("collab compaction sweep failed: {}", err),
+      }
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Mutex;
+
+  use collab::preclude::Collab;
+  use yrs::updates::decoder::Decode;
+  use yrs::Map;
+
+  use super::*;
+
+  #[derive(Default)]
+  struct State {
+    stored: Option<EncodedCollab>,
+    updates: Vec<(MessageId, Vec<u8>, UpdateFlags)>,
+    connected: bool,
+    leased: bool,
+    snapshots: Vec<Vec<u8>>,
+    /// An edit written to the stream once the collab was loaded for its compaction.
+    edit_while_compacting: Option<Vec<u8>>,
+    reads: usize,
+  }
+
+  #[derive(Default)]
+  struct MemoryCollab {
+    state: Mutex<State>,
+  }
+
+  #[async_trait]
+  impl MigratingCollab for MemoryCollab {
+    async fn acquire(&self) -> Result<bool, AppError> {
+      let mut state = self.state.lock().unwrap();
+      Ok(!std::mem::replace(&mut state.leased, true))
+    }
+
+    async fn release(&self) -> Result<(), AppError> {
+      self.state.lock().unwrap().leased = false;
+      Ok(())
+    }
+
+    async fn load_stored(&self) -> Result<Option<EncodedCollab>, AppError> {
+      Ok(self.state.lock().unwrap().stored.clone())
+    }
+
+    async fn updates_since(
+      &self,
+      since: Option<MessageId>,
+    ) -> Result<Vec<(MessageId, CollabStreamUpdate)>, AppError> {
+      let mut state = self.state.lock().unwrap();
+      state.reads += 1;
+      if state.reads > 1 {
+        if let Some(update) = state.edit_while_compacting.take() {
+          let message_id = MessageId::new(state.updates.len() as u64 + 1, 0);
+          state
+            .updates
+            .push((message_id, update, UpdateFlags::default()));
+        }
+      }
+      let since = since.unwrap_or_default();
+      Ok(
+        state
+          .updates
+          .iter()
+          .filter(|(message_id, _, _)| *message_id > since)
+          .map(|(message_id, data, flags)| {
+            (
+              *message_id,
+              CollabStreamUpdate::new(data.clone(), CollabOrigin::Empty, *flags),
+            )
+          })
+          .collect(),
+      )
+    }
+
+    async fn store(&self, encoded_collab: EncodedCollab) -> Result<(), AppError> {
+      let mut state = self.state.lock().unwrap();
+      assert!(
+        state.leased,
+        "the collab is stored without holding the lease"
+      );
+      state.stored = Some(encoded_collab);
+      Ok(())
+    }
+  }
+
+  #[async_trait]
+  impl CompactingCollab for MemoryCollab {
+    async fn has_connected_clients(&self) -> Result<bool, AppError> {
+      Ok(self.state.lock().unwrap().connected)
+    }
+
+    async fn snapshot(&self, doc_state: Vec<u8>) -> Result<i64, AppError> {
+      let mut state = self.state.lock().unwrap();
+      state.snapshots.push(doc_state);
+      Ok(state.snapshots.len() as i64)
+    }
+
+    async fn reset(&self, message_id: Option<MessageId>) -> Result<(), AppError> {
+      let mut state = self.state.lock().unwrap();
+      let up_to = message_id.unwrap_or_default();
+      state
+        .updates
+        .retain(|(message_id, _, _)| *message_id > up_to);
+      let message_id = MessageId::new(up_to.timestamp_ms + 1, 0);
+      state.updates.push((
+        message_id,
+        Update::default().encode_v1(),
+        UpdateFlags::IS_RESET,
+      ));
+      Ok(())
+    }
+  }
+
+  /// A collab whose keys were written and removed over and over, by a client each time.
+  fn churned_collab() -> Collab {
+    let mut collab = Collab::new_with_origin(CollabOrigin::Empty, "document", vec![], false);
+    for i in 0..200 {
+      let mut editor = Collab::new_with_origin(CollabOrigin::Empty, "document", vec![], false);
+      let update = {
+        let mut txn = editor.context.transact_mut();
+        editor.data.insert(&mut txn, format!("key{}", i), "value");
+        txn.encode_update_v1()
+      };
+      let mut txn = collab.context.transact_mut();
+      txn
+        .apply_update(Update::decode_v1(&update).unwrap())
+        .unwrap();
+      collab.data.remove(&mut txn, &format!("key{}", i));
+    }
+    {
+      let mut txn = collab.context.transact_mut();
+      collab.data.insert(&mut txn, "title", "compacted");
+    }
+    collab
+  }
+
+  fn memory_collab(collab: &Collab) -> MemoryCollab {
+    let txn = collab.transact();
+    let stored = EncodedCollab::new_v1(
+      txn.state_vector().encode_v1(),
+      txn.encode_state_as_update_v1(&StateVector::default()),
+    );
+    MemoryCollab {
+      state: Mutex::new(State {
+        stored: Some(stored),
+        ..Default::default()
+      }),
+    }
+  }
+
+  #[tokio::test]
+  async fn compaction_keeps_content_and_snapshot_test() {
+    let collab = churned_collab();
+    let memory = memory_collab(&collab);
+    let outcome = compact_stored_collab(&memory, "document", 0.5, false)
+      .await
+      .unwrap();
+    assert_eq!(outcome.skipped, None);
+    assert_eq!(outcome.snapshot_id, Some(1));
+    assert!(outcome.compacted_len * 2 <= outcome.doc_state_len);
+
+    let state = memory.state.lock().unwrap();
+    assert!(!state.leased);
+    let compacted = open_collab("document", state.stored.as_ref()).unwrap();
+    assert_eq!(compacted.to_json_value(), collab.to_json_value());
+    // the snapshot holds the state from before the compaction
+    assert_eq!(state.snapshots[0].len(), outcome.doc_state_len);
+    // the groups of the collab are told to make their clients reload it
+    assert_eq!(state.updates.len(), 1);
+    assert!(state.updates[0].2.is_reset());
+  }
+
+  #[tokio::test]
+  async fn compaction_skips_collab_test() {
+    let collab = churned_collab();
+    let memory = memory_collab(&collab);
+    memory.state.lock().unwrap().connected = true;
+    let outcome = compact_stored_collab(&memory, "document", 0.5, true)
+      .await
+      .unwrap();
+    assert_eq!(outcome.skipped, Some(CompactionSkipped::Connected));
+
+    // a collab edited during its compaction is left as it is
+    let edit = {
+      let mut editor = Collab::new_with_origin(CollabOrigin::Empty, "document", vec![], false);
+      let mut txn = editor.context.transact_mut();
+      editor.data.insert(&mut txn, "edited", true);
+      txn.encode_update_v1()
+    };
+    {
+      let mut state = memory.state.lock().unwrap();
+      state.edit_while_compacting = Some(edit);
+      state.reads = 0;
+    }
+    let outcome = compact_stored_collab(&memory, "document", 0.5, false)
+      .await
+      .unwrap();
+    assert_eq!(outcome.skipped, Some(CompactionSkipped::Edited));
+
+    // nor is a collab whose compacted copy isn't small enough
+    let outcome = compact_stored_collab(&memory, "document", 0.001, false)
+      .await
+      .unwrap();
+    assert_eq!(outcome.skipped, Some(CompactionSkipped::NoGain));
+    let state = memory.state.lock().unwrap();
+    assert!(state.snapshots.is_empty());
+    assert!(!state.leased);
+  }
+}
@@ -0,0 +1,712 @@
+//! Moves a stored collab to another encoding of its doc state while it's being edited.
+//!
+//! The snapshot lease, which the realtime servers take to persist a collab, is held twice and
+//! briefly: to capture the base state, and to swap the converted state in. The conversion and the
+//! replay of the updates received meanwhile run without it. Editors never wait on the lease, they
+//! keep writing to the Redis stream of the collab, so a migration only delays its snapshots.
+use std::time::Duration;
+
+use anyhow::anyhow;
+use app_error::AppError;
+use async_trait::async_trait;
+use collab::core::collab::DataSource;
+use collab::core::origin::CollabOrigin;
+use collab::entity::{EncodedCollab, EncoderVersion};
+use collab::preclude::Collab;
+use collab_entity::CollabType;
+use collab_stream::client::CollabRedisStream;
+use collab_stream::lease::LeaseAcquisition;
+use collab_stream::model::{CollabStreamUpdate, MessageId};
+use database::collab::{CollabStorage, GetCollabOrigin};
+use database_entity::dto::{CollabParams, QueryCollabParams};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{info, trace};
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+use yrs::{ReadTxn, StateVector, Update};
+
+/// A collab being migrated: its stored state, the Redis stream of its updates and its snapshot
+/// lease.
+#[async_trait]
+pub trait MigratingCollab: Send + Sync {
+  /// Takes the snapshot lease, `false` when it's held by another server.
+  async fn acquire(&self) -> Result<bool, AppError>;
+
+  async fn release(&self) -> Result<(), AppError>;
+
+  /// The stored state, `None` when the collab was never persisted.
+  async fn load_stored(&self) -> Result<Option<EncodedCollab>, AppError>;
+
+  /// The updates of the stream after `since`, in order.
+  async fn updates_since(
+    &self,
+    since: Option<MessageId>,
+  ) -> Result<Vec<(MessageId, CollabStreamUpdate)>, AppError>;
+
+  /// Replaces the stored state. Only called while holding the lease.
+  async fn store(&self, encoded_collab: EncodedCollab) -> Result<(), AppError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct LiveMigrationOptions {
+  /// Number of times the migration starts over from a new base state before giving up.
+  pub max_attempts: usize,
+  /// Number of replays of the buffered updates after which an attempt is abandoned, because the
+  /// collab is edited faster than the updates are replayed.
+  pub max_catch_up_rounds: usize,
+  /// The state is swapped in once at most this many updates are buffered. They're replayed while
+  /// holding the lease.
+  pub swap_threshold: usize,
+  /// Wait before the next attempt when the lease is held by another server.
+  pub retry_delay: Duration,
+}
+
+impl Default for LiveMigrationOptions {
+  fn default() -> Self {
+    Self {
+      max_attempts: 5,
+      max_catch_up_rounds: 10,
+      swap_threshold: 32,
+      retry_delay: Duration::from_millis(200),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveMigrationOutcome {
+  /// `false` when the collab was already stored in the target encoding.
+  pub migrated: bool,
+  pub attempts: usize,
+  /// Updates of the stream replayed on top of the base state, over all the rounds.
+  pub replayed_updates: usize,
+  pub encoded_len: usize,
+}
+
+/// Why an attempt was abandoned, to start over from a new base state.
+enum Retry {
+  LeaseHeld,
+  /// The collab was compacted: the updates of the stream belong to another history.
+  Reset,
+  Overrun,
+}
+
+/// Migrates the stored state of the collab to the `target` encoding. The swapped state contains
+/// every update written to the stream up to the swap, and the state stored by the snapshots taken
+/// meanwhile, so the collab reads the same before and after.
+///
+/// Returns [AppError::ServiceTemporaryUnavailable] when the collab can't be caught up with in
+/// [LiveMigrationOptions::max_attempts] attempts.
+pub async fn migrate_collab_encoding(
+  collab: &dyn MigratingCollab,
+  object_id: &str,
+  target: EncoderVersion,
+  options: &LiveMigrationOptions,
+) -> Result<LiveMigrationOutcome, AppError> {
+  let mut outcome = LiveMigrationOutcome {
+    migrated: false,
+    attempts: 0,
+    replayed_updates: 0,
+    encoded_len: 0,
+  };
+  while outcome.attempts < options.max_attempts {
+    outcome.attempts += 1;
+    let retry = match attempt(collab, object_id, &target, options, &mut outcome).await? {
+      Ok(()) => return Ok(outcome),
+      Err(retry) => retry,
+    };
+    match retry {
+      Retry::LeaseHeld => tokio::time::sleep(options.retry_delay).await,
+      Retry::Reset => trace!("collab {} was reset during its migration", object_id),
+      Retry::Overrun => trace!(
+        "collab {} is edited faster than it's migrated, attempt {}",
+        object_id,
+        outcome.attempts
+      ),
+    }
+  }
+  Err(AppError::ServiceTemporaryUnavailable(format!(
+    "failed to migrate the encoding of collab {} in {} attempts",
+    object_id, outcome.attempts
+  )))
+}
+
+async fn attempt(
+  collab: &dyn MigratingCollab,
+  object_id: &str,
+  target: &EncoderVersion,
+  options: &LiveMigrationOptions,
+  outcome: &mut LiveMigrationOutcome,
+) -> Result<Result<(), Retry>, AppError> {
+  // 1. capture the base state
+  if !collab.acquire().await? {
+    return Ok(Err(Retry::LeaseHeld));
+  }
+  let captured = capture(collab).await;
+  collab.release().await?;
+  let (stored, updates) = captured?;
+  if stored
+    .as_ref()
+    .is_some_and(|stored| same_encoding(&stored.version, target))
+  {
+    return Ok(Ok(()));
+  }
+  if updates.iter().any(|(_, update)| update.flags.is_reset()) {
+    return Ok(Err(Retry::Reset));
+  }
+
+  // 2. convert it in the background
+  let mut last_message_id = updates.last().map(|(message_id, _)| *message_id);
+  outcome.replayed_updates += updates.len();
+  let object_id_owned = object_id.to_string();
+  let mut migrated = tokio::task::spawn_blocking(move || {
+    let mut migrated = open_collab(&object_id_owned, stored.as_ref())?;
+    replay(&mut migrated, updates)?;
+    Ok::<_, AppError>(migrated)
+  })
+  .await
+  .map_err(|err| AppError::Internal(err.into()))??;
+
+  // 3. replay the updates buffered during the conversion, until few enough are left
+  let mut rounds = 0;
+  loop {
+    let buffered = collab.updates_since(last_message_id).await?;
+    if buffered.len() <= options.swap_threshold {
+      break;
+    }
+    rounds += 1;
+    if rounds > options.max_catch_up_rounds {
+      return Ok(Err(Retry::Overrun));
+    }
+    if buffered.iter().any(|(_, update)| update.flags.is_reset()) {
+      return Ok(Err(Retry::Reset));
+    }
+    last_message_id = buffered.last().map(|(message_id, _)| *message_id);
+    outcome.replayed_updates += buffered.len();
+    replay(&mut migrated, buffered)?;
+  }
+
+  // 4. swap the converted state in
+  if !collab.acquire().await? {
+    return Ok(Err(Retry::LeaseHeld));
+  }
+  let swapped = swap(collab, object_id, target, &mut migrated, last_message_id).await;
+  collab.release().await?;
+  match swapped? {
+    Ok((replayed, encoded_len)) => {
+      outcome.migrated = true;
+      outcome.replayed_updates += replayed;
+      outcome.encoded_len = encoded_len;
+      info!(
+        "migrated collab {} to {:?}: {} bytes, {} updates replayed in {} attempts",
+        object_id, target, encoded_len, outcome.replayed_updates, outcome.attempts
+      );
+      Ok(Ok(()))
+    },
+    Err(retry) => Ok(Err(retry)),
+  }
+}
+
+async fn capture(
+  collab: &dyn MigratingCollab,
+) -> Result<(Option<EncodedCollab>, Vec<(MessageId, CollabStreamUpdate)>), AppError> {
+  let stored = collab.load_stored().await?;
+  let updates = collab.updates_since(None).await?;
+  Ok((stored, updates))
+}
+
+/// Brings the converted state up to date and stores it. Must be called while holding the lease.
+///
+/// The state stored meanwhile is merged in, since the snapshots may have pruned updates the
+/// migration didn't replay. It's made of the same updates, which are only applied once.
+async fn swap(
+  collab: &dyn MigratingCollab,
+  object_id: &str,
+  target: &EncoderVersion,
+  migrated: &mut Collab,
+  last_message_id: Option<MessageId>,
+) -> Result<Result<(usize, usize), Retry>, AppError> {
+  let rest = collab.updates_since(last_message_id).await?;
+  if rest.iter().any(|(_, update)| update.flags.is_reset()) {
+    return Ok(Err(Retry::Reset));
+  }
+  if let Some(stored) = collab.load_stored().await? {
+    let update = match stored.version {
+      EncoderVersion::V1 => Update::decode_v1(&stored.doc_state),
+      EncoderVersion::V2 => Update::decode_v2(&stored.doc_state),
+    }
+    .map_err(|err| AppError::DecodeUpdateError(err.to_string()))?;
+    migrated
+      .transact_mut()
+      .apply_update(update)
+      .map_err(|err| AppError::DecodeUpdateError(err.to_string()))?;
+  }
+  let replayed = rest.len();
+  replay(migrated, rest)?;
+
+  let encoded_collab = encode_collab(migrated, target);
+  check_encoded(object_id, migrated, &encoded_collab)?;
+  let encoded_len = encoded_collab.doc_state.len();
+  collab.store(encoded_collab).await?;
+  Ok(Ok((replayed, encoded_len)))
+}
+
+pub(crate) fn replay(
+  collab: &mut Collab,
+  updates: Vec<(MessageId, CollabStreamUpdate)>,
+) -> Result<(), AppError> {
+  let mut txn = collab.transact_mut();
+  for (_, update) in updates {
+    let update = update
+      .into_update()
+      .map_err(|err| AppError::DecodeUpdateError(err.to_string()))?;
+    txn
+      .apply_update(update)
+      .map_err(|err| AppError::DecodeUpdateError(err.to_string()))?;
+  }
+  Ok(())
+}
+
+fn encode_collab(collab: &Collab, target: &EncoderVersion) -> EncodedCollab {
+  let txn = collab.transact();
+  match target {
+    EncoderVersion::V1 => EncodedCollab::new_v1(
+      txn.state_vector().encode_v1(),
+      txn.encode_state_as_update_v1(&StateVector::default()),
+    ),
+    EncoderVersion::V2 => EncodedCollab::new_v2(
+      txn.state_vector().encode_v2(),
+      txn.encode_state_as_update_v2(&StateVector::default()),
+    ),
+  }
+}
+
+fn same_encoding(a: &EncoderVersion, b: &EncoderVersion) -> bool {
+  matches!(
+    (a, b),
+    (EncoderVersion::V1, EncoderVersion::V1) | (EncoderVersion::V2, EncoderVersion::V2)
+  )
+}
+
+/// Decodes the encoded state back, so that a collab is never swapped for a state which reads
+/// differently.
+fn check_encoded(
+  object_id: &str,
+  collab: &Collab,
+  encoded_collab: &EncodedCollab,
+) -> Result<(), AppError> {
+  let decoded = open_collab(object_id, Some(encoded_collab))?;
+  if decoded.transact().state_vector() != collab.transact().state_vector()
+    || decoded.to_json_value() != collab.to_json_value()
+  {
+    return Err(AppError::Internal(anyhow!(
+      "the {:?} encoding of collab {} doesn't read as the collab",
+      encoded_collab.version,
+      object_id
+    )));
+  }
+  Ok(())
+}
+
+pub(crate) fn open_collab(
+  object_id: &str,
+  encoded_collab: Option<&EncodedCollab>,
+) -> Result<Collab, AppError> {
+  let Some(encoded_collab) = encoded_collab else {
+    return Ok(Collab::new_with_origin(
+      CollabOrigin::Server,
+      object_id,
+      vec![],
+      false,
+    ));
+  };
+  let doc_state = encoded_collab.doc_state.to_vec();
+  let data_source = match encoded_collab.version {
+    EncoderVersion::V1 => DataSource::DocStateV1(doc_state),
+    EncoderVersion::V2 => DataSource::DocStateV2(doc_state),
+  };
+  Collab::new_with_source(CollabOrigin::Server, object_id, data_source, vec![], false)
+    .map_err(|err| AppError::DecodeUpdateError(err.to_string()))
+}
+
+/// A collab of the collab storage, edited through its Redis stream.
+pub struct StoredCollab {
+  pub(crate) storage: Arc<dyn CollabStorage>,
+  pub(crate) collab_redis_stream: Arc<CollabRedisStream>,
+  uid: i64,
+  pub(crate) workspace_id: String,
+  pub(crate) object_id: String,
+  pub(crate) collab_type: CollabType,
+  lease: Mutex<Option<LeaseAcquisition>>,
+}
+
+impl StoredCollab {
+  pub fn new(
+    storage: Arc<dyn CollabStorage>,
+    collab_redis_stream: Arc<CollabRedisStream>,
+    uid: i64,
+    workspace_id: String,
+    object_id: String,
+    collab_type: CollabType,
+  ) -> Self {
+    Self {
+      storage,
+      collab_redis_stream,
+      uid,
+      workspace_id,
+      object_id,
+      collab_type,
+      lease: Mutex::new(None),
+    }
+  }
+}
+
+#[async_trait]
+impl MigratingCollab for StoredCollab {
+  async fn acquire(&self) -> Result<bool, AppError> {
+    let lease = self
+      .collab_redis_stream
+      .lease(&self.workspace_id, &self.object_id)
+      .await
+      .map_err(|err| AppError::Internal(err.into()))?;
+    let acquired = lease.is_some();
+    *self.lease.lock().await = lease;
+    Ok(acquired)
+  }
+
+  async fn release(&self) -> Result<(), AppError> {
+    if let Some(mut lease) = self.lease.lock().await.take() {
+      lease
+        .release()
+        .await
+        .map_err(|err| AppError::Internal(err.into()))?;
+    }
+    Ok(())
+  }
+
+  async fn load_stored(&self) -> Result<Option<EncodedCollab>, AppError> {
+    let params = QueryCollabParams::new(
+      &self.object_id,
+      self.collab_type.clone(),
+      &self.workspace_id,
+    );
+    match self
+      .storage
+      .get_encode_collab(GetCollabOrigin::Server, params, false)
+      .await
+    {
+      Ok(encoded_collab) => Ok(Some(encoded_collab)),
+      Err(AppError::RecordNotFound(_)) => Ok(None),
+      Err(err) => Err(err),
+    }
+  }
+
+  async fn updates_since(
+    &self,
+    since: Option<MessageId>,
+  ) -> Result<Vec<(MessageId, CollabStreamUpdate)>, AppError> {
+    self
+      .collab_redis_stream
+      .current_collab_updates(&self.workspace_id, &self.object_id, since)
+      .await
+      .map_err(|err| AppError::Internal(err.into()))
+  }
+
+  async fn store(&self, encoded_collab: EncodedCollab) -> Result<(), AppError> {
+    let encoded_collab = encoded_collab
+      .encode_to_bytes()
+      .map_err(|err| AppError::Internal(err.into()))?;
+    let params = CollabParams::new(&self.object_id, self.collab_type.clone(), encoded_collab);
+    self
+      .storage
+      .queue_insert_or_update_collab(&self.workspace_id, &self.uid, params, true)
+      .await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeSet;
+  use std::sync::Mutex as StdMutex;
+
+  use collab_stream::model::UpdateFlags;
+
+  use super::*;
+
+  #[derive(Default)]
+  struct State {
+    stored: Option<EncodedCollab>,
+    updates: Vec<(MessageId, Vec<u8>, UpdateFlags)>,
+    leased: bool,
+    next_message_id: u64,
+  }
+
+  /// A collab held in memory, edited by a writer task through [MemoryCollab::push].
+  #[derive(Default)]
+  struct MemoryCollab {
+    state: StdMutex<State>,
+    /// Writes an edit before returning the updates, for a collab edited faster than it's migrated.
+    edit_on_read: Option<StdMutex<Collab>>,
+  }
+
+  impl MemoryCollab {
+    fn push<F: Into<UpdateFlags>>(&self, update: Vec<u8>, flags: F) {
+      let mut state = self.state.lock().unwrap();
+      state.next_message_id += 1;
+      let message_id = MessageId::new(state.next_message_id, 0);
+      state.updates.push((message_id, update, flags.into()));
+    }
+
+    /// What a client syncing the collab reads: the stored state and the updates of the stream.
+    fn observe(&self) -> Collab {
+      read(&self.state.lock().unwrap())
+    }
+
+    /// Takes a snapshot as the realtime server does: stores the collab in its current encoding
+    /// and prunes the stream, keeping the most recent updates.
+    fn snapshot(&self) -> bool {
+      let mut state = self.state.lock().unwrap();
+      if state.leased {
+        return false;
+      }
+      let collab = read(&state);
+      let version = match &state.stored {
+        Some(stored) if matches!(stored.version, EncoderVersion::V2) => EncoderVersion::V2,
+        _ => EncoderVersion::V1,
+      };
+      state.stored = Some(encode_collab(&collab, &version));
+      let pruned = state.updates.len().saturating_sub(3);
+      state.updates.drain(..pruned);
+      true
+    }
+  }
+
+  fn read(state: &State) -> Collab {
+    let mut collab = open_collab("document", state.stored.as_ref()).unwrap();
+    let updates = state
+      .updates
+      .iter()
+      .map(|(message_id, data, flags)| {
+        (
+          *message_id,
+          CollabStreamUpdate::new(data.clone(), CollabOrigin::Empty, *flags),
+        )
+      })
+      .collect();
+    replay(&mut collab, updates).unwrap();
+    collab
+  }
+
+  #[async_trait]
+  impl MigratingCollab for MemoryCollab {
+    async fn acquire(&self) -> Result<bool, AppError> {
+      let mut state = self.state.lock().unwrap();
+      Ok(!std::mem::replace(&mut state.leased, true))
+    }
+
+    async fn release(&self) -> Result<(), AppError> {
+      self.state.lock().unwrap().leased = false;
+      Ok(())
+    }
+
+    async fn load_stored(&self) -> Result<Option<EncodedCollab>, AppError> {
+      Ok(self.state.lock().unwrap().stored.clone())
+    }
+
+    async fn updates_since(
+      &self,
+      since: Option<MessageId>,
+    ) -> Result<Vec<(MessageId, CollabStreamUpdate)>, AppError> {
+      if let Some(writer) = &self.edit_on_read {
+        let update = edit(&mut writer.lock().unwrap(), "on read");
+        self.push(update, UpdateFlags::default());
+      }
+      let since = since.unwrap_or_default();
+      let state = self.state.lock().unwrap();
+      Ok(
+        state
+          .updates
+          .iter()
+          .filter(|(message_id, _, _)| *message_id > since)
+          .map(|(message_id, data, flags)| {
+            (
+              *message_id,
+              CollabStreamUpdate::new(data.clone(), CollabOrigin::Empty, *flags),
+            )
+          })
+          .collect(),
+      )
+    }
+
+    async fn store(&self, encoded_collab: EncodedCollab) -> Result<(), AppError> {
+      let mut state = self.state.lock().unwrap();
+      assert!(
+        state.leased,
+        "the state is swapped without holding the lease"
+      );
+      state.stored = Some(encoded_collab);
+      Ok(())
+    }
+  }
+
+  /// Inserts the key into the collab and returns the update of the edit.
+  fn edit(collab: &mut Collab, key: &str) -> Vec<u8> {
+    let before = collab.transact().state_vector();
+    {
+      let mut txn = collab.context.transact_mut();
+      collab.data.insert(&mut txn, key, key.to_string());
+    }
+    collab.transact().encode_state_as_update_v1(&before)
+  }
+
+  fn keys(collab: &Collab) -> BTreeSet<String> {
+    collab
+      .to_json_value()
+      .as_object()
+      .unwrap()
+      .keys()
+      .cloned()
+      .collect()
+  }
+
+  fn edit_key(i: usize) -> String {
+    format!("edit{:04}", i)
+  }
+
+  fn writer_collab() -> Collab {
+    Collab::new_with_origin(CollabOrigin::Empty, "document", vec![], false)
+  }
+
+  #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+  async fn concurrent_edits_survive_live_migration_test() {
+    const EDITS: usize = 2000;
+    let collab = Arc::new(MemoryCollab::default());
+    let mut writer = writer_collab();
+    for i in 0..200 {
+      let update = edit(&mut writer, &edit_key(i));
+      collab.push(update, UpdateFlags::default());
+    }
+    assert!(collab.snapshot());
+
+    let writer_task = {
+      let collab = collab.clone();
+      tokio::spawn(async move {
+        for i in 200..EDITS {
+          let update = edit(&mut writer, &edit_key(i));
+          collab.push(update, UpdateFlags::default());
+          if i % 25 == 0 {
+            collab.snapshot();
+            tokio::time::sleep(Duration::from_millis(1)).await;
+          }
+        }
+      })
+    };
+    // a client observing the collab during the migration sees the edits in the order they were
+    // made, without any missing
+    let reader_task = {
+      let collab = collab.clone();
+      tokio::spawn(async move {
+        let mut observed = 0;
+        while observed < EDITS {
+          let keys = keys(&collab.observe());
+          assert!(keys.len() >= observed, "an edit disappeared");
+          assert_eq!(
+            keys,
+            (0..keys.len()).map(edit_key).collect::<BTreeSet<_>>(),
+            "an edit is missing"
+          );
+          observed = keys.len();
+          tokio::task::yield_now().await;
+        }
+      })
+    };
+
+    let options = LiveMigrationOptions {
+      max_attempts: 100,
+      retry_delay: Duration::from_millis(1),
+      ..Default::default()
+    };
+    let outcome = migrate_collab_encoding(&*collab, "document", EncoderVersion::V2, &options)
+      .await
+      .unwrap();
+    assert!(outcome.migrated);
+    writer_task.await.unwrap();
+    reader_task.await.unwrap();
+
+    // the snapshots taken after the swap keep the new encoding
+    assert!(collab.snapshot());
+    assert!(matches!(
+      collab
+        .state
+        .lock()
+        .unwrap()
+        .stored
+        .as_ref()
+        .unwrap()
+        .version,
+      EncoderVersion::V2
+    ));
+    assert!(outcome.encoded_len > 0);
+    assert_eq!(
+      keys(&collab.observe()),
+      (0..EDITS).map(edit_key).collect::<BTreeSet<_>>()
+    );
+
+    let outcome = migrate_collab_encoding(&*collab, "document", EncoderVersion::V2, &options)
+      .await
+      .unwrap();
+    assert!(!outcome.migrated);
+  }
+
+  #[tokio::test]
+  async fn migration_gives_up_when_edits_outpace_it_test() {
+    let collab = MemoryCollab {
+      edit_on_read: Some(StdMutex::new(writer_collab())),
+      ..Default::default()
+    };
+    let options = LiveMigrationOptions {
+      max_attempts: 2,
+      max_catch_up_rounds: 3,
+      swap_threshold: 0,
+      retry_delay: Duration::from_millis(1),
+    };
+    let result = migrate_collab_encoding(&collab, "document", EncoderVersion::V2, &options).await;
+    assert!(matches!(
+      result,
+      Err(AppError::ServiceTemporaryUnavailable(_))
+    ));
+    let state = collab.state.lock().unwrap();
+    assert!(state.stored.is_none());
+    assert!(!state.leased);
+  }
+
+  #[tokio::test]
+  async fn migration_starts_over_after_a_reset_test() {
+    let collab = MemoryCollab::default();
+    let mut writer = writer_collab();
+    let update = edit(&mut writer, "before");
+    collab.push(update, UpdateFlags::default());
+    collab.push(Update::default().encode_v1(), UpdateFlags::IS_RESET);
+    let options = LiveMigrationOptions {
+      max_attempts: 1,
+      ..Default::default()
+    };
+    let result = migrate_collab_encoding(&collab, "document", EncoderVersion::V2, &options).await;
+    assert!(result.is_err());
+
+    // once the stream is pruned by the compaction, the migration goes through
+    {
+      let mut state = collab.state.lock().unwrap();
+      state.updates.clear();
+      state.stored = Some(encode_collab(&writer, &EncoderVersion::V1));
+    }
+    let outcome = migrate_collab_encoding(&collab, "document", EncoderVersion::V2, &options)
+      .await
+      .unwrap();
+    assert!(outcome.migrated);
+    assert_eq!(
+      keys(&collab.observe()),
+      BTreeSet::from(["before".to_string()])
+    );
+  }
+}
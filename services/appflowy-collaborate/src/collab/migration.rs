@@ -0,0 +1,689 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use app_error::AppError;
+use collab::core::collab::DataSource;
+use collab::core::origin::CollabOrigin;
+use collab::entity::{EncodedCollab, EncoderVersion};
+use collab::preclude::Collab;
+use collab_entity::CollabType;
+use collab_stream::client::CollabRedisStream;
+use collab_stream::model::{CollabStreamUpdate, UpdateFlags};
+use database::collab::{CollabStorage, GetCollabOrigin};
+use database::collab_migration::{
+  select_collab_migration_version, select_collabs_behind_migration_version,
+  update_collab_migration_version,
+};
+use database::pg_row::AFCollabMigrationCandidateRow;
+use database_entity::dto::QueryCollabParams;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use yrs::{Any, Map, MapRef, Out, ReadTxn, Transact};
+
+use crate::config::get_env_var;
+use crate::metrics::CollabRealtimeMetrics;
+
+/// Number of collabs loaded at once by the sweeper.
+const SWEEP_BATCH_SIZE: i64 = 100;
+
+/// A change of the conventions of the collab content, applied once to every stored collab of its
+/// type: lazily when the collab is opened by the realtime server, or by the sweeper.
+pub trait CollabMigration: Send + Sync {
+  /// Greater than the version of every migration registered before it. A version is never reused
+  /// once deployed, since it's recorded for every migrated collab.
+  fn version(&self) -> i32;
+
+  /// Name used in the logs and the metrics.
+  fn name(&self) -> &'static str;
+
+  fn collab_type(&self) -> CollabType;
+
+  /// Transforms the collab and returns whether anything was changed. A migration is also applied
+  /// to collabs which already follow the new conventions, like the ones written by up-to-date
+  /// clients, so it must leave them unchanged.
+  fn migrate(&self, collab: &mut Collab) -> Result<bool, anyhow::Error>;
+}
+
+/// The migrations registered in code, in version order.
+#[derive(Clone, Default)]
+pub struct CollabMigrations {
+  migrations: Vec<Arc<dyn CollabMigration>>,
+}
+
+impl CollabMigrations {
+  pub fn new(migrations: Vec<Arc<dyn CollabMigration>>) -> Result<Self, anyhow::Error> {
+    let mut version = 0;
+    for migration in &migrations {
+      if migration.version() <= version {
+        bail!(
+          "collab migration {} has version {}, which is not greater than {}",
+          migration.name(),
+          migration.version(),
+          version
+        );
+      }
+      version = migration.version();
+    }
+    Ok(Self { migrations })
+  }
+
+  /// The migrations run by the server. New migrations are appended at the end of the list.
+  pub fn builtin() -> Self {
+    Self::new(vec![]).expect("builtin collab migrations are not in version order")
+  }
+
+  /// The version every collab is brought to, 0 when no migration is registered.
+  pub fn latest_version(&self) -> i32 {
+    self
+      .migrations
+      .last()
+      .map(|migration| migration.version())
+      .unwrap_or_default()
+  }
+
+  fn collab_types(&self) -> Vec<CollabType> {
+    let mut collab_types = vec![];
+    for migration in &self.migrations {
+      let collab_type = migration.collab_type();
+      if !collab_types.contains(&collab_type) {
+        collab_types.push(collab_type);
+      }
+    }
+    collab_types
+  }
+
+  /// Applies, in order, the migrations of the collab type whose version is above `from_version`.
+  /// Stops at the first migration which fails: the changes of the migrations before it are kept,
+  /// and the collab stays at the version before the failed one so that it's retried later.
+  pub fn apply(
+    &self,
+    collab: &mut Collab,
+    collab_type: &CollabType,
+    from_version: i32,
+  ) -> MigrationOutcome {
+    let before = collab.transact().state_vector();
+    let mut outcome = MigrationOutcome {
+      from_version,
+      version: from_version,
+      update: None,
+      results: vec![],
+      written: false,
+    };
+    for migration in &self.migrations {
+      if migration.version() <= from_version {
+        continue;
+      }
+      if migration.collab_type() == *collab_type {
+        let result = match migration.migrate(collab) {
+          Ok(true) => {
+            outcome.update = Some(collab.transact().encode_state_as_update_v1(&before));
+            MigrationResult::Changed
+          },
+          Ok(false) => MigrationResult::Unchanged,
+          Err(err) => MigrationResult::Failed(err.to_string()),
+        };
+        let failed = matches!(result, MigrationResult::Failed(_));
+        outcome.results.push((migration.name(), result));
+        if failed {
+          break;
+        }
+      }
+      outcome.version = migration.version();
+    }
+    outcome
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MigrationResult {
+  Changed,
+  Unchanged,
+  Failed(String),
+}
+
+impl MigrationResult {
+  fn as_str(&self) -> &'static str {
+    match self {
+      MigrationResult::Changed => "changed",
+      MigrationResult::Unchanged => "unchanged",
+      MigrationResult::Failed(_) => "failed",
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct MigrationOutcome {
+  pub from_version: i32,
+  /// The version the collab is moved to.
+  pub version: i32,
+  /// The changes made by the migrations, as an update on top of the migrated collab.
+  pub update: Option<Vec<u8>>,
+  pub results: Vec<(&'static str, MigrationResult)>,
+  /// Whether the update and the version were written. They're not on a dry run, nor when the
+  /// collab was migrated concurrently by another server.
+  pub written: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MigrationCounts {
+  pub changed: usize,
+  pub unchanged: usize,
+  pub failed: usize,
+}
+
+/// What a sweep did, or would have done on a dry run.
+#[derive(Debug, Default, Clone)]
+pub struct MigrationStats {
+  pub scanned: usize,
+  /// Collabs whose content was changed by at least one migration.
+  pub changed: usize,
+  /// Collabs which couldn't be loaded or written.
+  pub errors: usize,
+  pub by_migration: BTreeMap<&'static str, MigrationCounts>,
+}
+
+impl MigrationStats {
+  fn record(&mut self, outcome: &MigrationOutcome) {
+    if outcome.update.is_some() {
+      self.changed += 1;
+    }
+    for (name, result) in &outcome.results {
+      let counts = self.by_migration.entry(name).or_default();
+      match result {
+        MigrationResult::Changed => counts.changed += 1,
+        MigrationResult::Unchanged => counts.unchanged += 1,
+        MigrationResult::Failed(_) => counts.failed += 1,
+      }
+    }
+  }
+}
+
+impl Display for MigrationStats {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "scanned {} collabs, changed {}, errors {}",
+      self.scanned, self.changed, self.errors
+    )?;
+    for (name, counts) in &self.by_migration {
+      write!(
+        f,
+        "; {}: changed {}, unchanged {}, failed {}",
+        name, counts.changed, counts.unchanged, counts.failed
+      )?;
+    }
+    Ok(())
+  }
+}
+
+/// Brings stored collabs to the latest migration version. Each migration runs on the decoded
+/// collab, with the updates still waiting in its Redis stream applied, and its changes are written
+/// to the stream as a regular update from the server, so that connected clients receive them like
+/// any other edit.
+#[derive(Clone)]
+pub struct CollabMigrator {
+  pg_pool: PgPool,
+  migrations: Arc<CollabMigrations>,
+  metrics: Arc<CollabRealtimeMetrics>,
+}
+
+impl CollabMigrator {
+  pub fn new(
+    pg_pool: PgPool,
+    migrations: CollabMigrations,
+    metrics: Arc<CollabRealtimeMetrics>,
+  ) -> Self {
+    Self {
+      pg_pool,
+      migrations: Arc::new(migrations),
+      metrics,
+    }
+  }
+
+  /// Migrates the collab opened by the realtime server, if it's behind the latest version.
+  /// Returns the update written to the stream.
+  pub async fn migrate_on_open(
+    &self,
+    collab_redis_stream: &CollabRedisStream,
+    workspace_id: &str,
+    object_id: &str,
+    collab_type: &CollabType,
+    collab: &mut Collab,
+  ) -> Result<Option<Vec<u8>>, AppError> {
+    let latest_version = self.migrations.latest_version();
+    if latest_version == 0 {
+      return Ok(None);
+    }
+    let version = select_collab_migration_version(&self.pg_pool, object_id, collab_type).await?;
+    match version {
+      Some(version) if version < latest_version => {
+        let outcome = self
+          .migrate(
+            collab_redis_stream,
+            workspace_id,
+            object_id,
+            collab_type,
+            version,
+            collab,
+            false,
+          )
+          .await?;
+        Ok(outcome.update.filter(|_| outcome.written))
+      },
+      _ => Ok(None),
+    }
+  }
+
+  /// Walks every stored collab behind the latest version once. On a dry run the migrations are
+  /// applied to the decoded collabs only, to find out what they would change.
+  pub async fn sweep<S>(
+    &self,
+    storage: &S,
+    collab_redis_stream: &CollabRedisStream,
+    dry_run: bool,
+  ) -> Result<MigrationStats, AppError>
+  where
+    S: CollabStorage + ?Sized,
+  {
+    let latest_version = self.migrations.latest_version();
+    let collab_types = self.migrations.collab_types();
+    let mut stats = MigrationStats::default();
+    let mut after_oid = None;
+    loop {
+      let rows = select_collabs_behind_migration_version(
+        &self.pg_pool,
+        &collab_types,
+        latest_version,
+        after_oid.as_deref(),
+        SWEEP_BATCH_SIZE,
+      )
+      .await?;
+      let Some(last) = rows.last() else {
+        break;
+      };
+      after_oid = Some(last.oid.clone());
+      for row in rows {
+        stats.scanned += 1;
+        match self
+          .sweep_collab(storage, collab_redis_stream, &row, dry_run)
+          .await
+        {
+          Ok(outcome) => stats.record(&outcome),
+          Err(err) => {
+            stats.errors += 1;
+            warn!("failed to migrate collab {}: {}", row.oid, err);
+          },
+        }
+      }
+    }
+    Ok(stats)
+  }
+
+  async fn sweep_collab<S>(
+    &self,
+    storage: &S,
+    collab_redis_stream: &CollabRedisStream,
+    row: &AFCollabMigrationCandidateRow,
+    dry_run: bool,
+  ) -> Result<MigrationOutcome, AppError>
+  where
+    S: CollabStorage + ?Sized,
+  {
+    let collab_type = CollabType::from(row.partition_key);
+    let workspace_id = row.workspace_id.to_string();
+    let params = QueryCollabParams::new(&row.oid, collab_type.clone(), &workspace_id);
+    let encoded_collab = storage
+      .get_encode_collab(GetCollabOrigin::Server, params, false)
+      .await?;
+    let mut collab = open_collab(&row.oid, &encoded_collab)?;
+    self
+      .migrate(
+        collab_redis_stream,
+        &workspace_id,
+        &row.oid,
+        &collab_type,
+        row.migration_version,
+        &mut collab,
+        dry_run,
+      )
+      .await
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  async fn migrate(
+    &self,
+    collab_redis_stream: &CollabRedisStream,
+    workspace_id: &str,
+    object_id: &str,
+    collab_type: &CollabType,
+    from_version: i32,
+    collab: &mut Collab,
+    dry_run: bool,
+  ) -> Result<MigrationOutcome, AppError> {
+    apply_pending_updates(collab_redis_stream, workspace_id, object_id, collab).await?;
+    let mut outcome = self.migrations.apply(collab, collab_type, from_version);
+    if dry_run || outcome.version == from_version {
+      return Ok(outcome);
+    }
+
+    // The version is moved first, so that a collab is never migrated by two servers at once.
+    let claimed = update_collab_migration_version(
+      &self.pg_pool,
+      object_id,
+      collab_type,
+      from_version,
+      outcome.version,
+    )
+    .await?;
+    if !claimed {
+      return Ok(outcome);
+    }
+    if let Some(update) = &outcome.update {
+      let update =
+        CollabStreamUpdate::new(update.clone(), CollabOrigin::Server, UpdateFlags::default());
+      let sent = collab_redis_stream
+        .collab_update_sink(workspace_id, object_id)
+        .send(&update)
+        .await;
+      if let Err(err) = sent {
+        // let the next attempt migrate the collab again
+        update_collab_migration_version(
+          &self.pg_pool,
+          object_id,
+          collab_type,
+          outcome.version,
+          from_version,
+        )
+        .await?;
+        return Err(AppError::Internal(anyhow!(
+          "failed to write the migration of collab {}: {}",
+          object_id,
+          err
+        )));
+      }
+    }
+    outcome.written = true;
+    for (name, result) in &outcome.results {
+      if let MigrationResult::Failed(err) = result {
+        warn!(
+          "collab migration {} failed on collab {}: {}",
+          name, object_id, err
+        );
+      }
+      self.metrics.record_collab_migration(name, result.as_str());
+    }
+    Ok(outcome)
+  }
+}
+
+/// Runs the sweeper periodically when `APPFLOWY_COLLAB_MIGRATION_SWEEPER` is `on`, or `dry-run`
+/// to only log what the migrations would change.
+pub fn spawn_collab_migration_sweeper<S>(
+  migrator: CollabMigrator,
+  storage: Arc<S>,
+  collab_redis_stream: CollabRedisStream,
+) where
+  S: CollabStorage,
+{
+  let dry_run = match get_env_var("APPFLOWY_COLLAB_MIGRATION_SWEEPER", "off").as_str() {
+    "off" => return,
+    "on" => false,
+    "dry-run" => true,
+    other => {
+      warn!("unknown collab migration sweeper mode: {}", other);
+      return;
+    },
+  };
+  if migrator.migrations.latest_version() == 0 {
+    return;
+  }
+  let interval = Duration::from_secs(
+    get_env_var("APPFLOWY_COLLAB_MIGRATION_SWEEP_INTERVAL_SECS", "3600")
+      .parse()
+      .unwrap_or(3600),
+  );
+
+  tokio::spawn(async move {
+    let mut tick = tokio::time::interval(interval);
+    loop {
+      tick.tick().await;
+      match migrator
+        .sweep(&*storage, &collab_redis_stream, dry_run)
+        .await
+      {
+        Ok(stats) if dry_run => info!("collab migration sweep (dry run): {}", stats),
+        Ok(stats) => info!("collab migration sweep: {}", stats),
+        Err(err) => warn!("collab migration sweep failed: {}", err),
+      }
+    }
+  });
+}
+
+/// Renames the blocks of a type in documents, e.g. when a block type is superseded by another one.
+pub struct RenameDocumentBlockType {
+  pub version: i32,
+  pub name: &'static str,
+  pub from: &'static str,
+  pub to: &'static str,
+}
+
+impl CollabMigration for RenameDocumentBlockType {
+  fn version(&self) -> i32 {
+    self.version
+  }
+
+  fn name(&self) -> &'static str {
+    self.name
+  }
+
+  fn collab_type(&self) -> CollabType {
+    CollabType::Document
+  }
+
+  fn migrate(&self, collab: &mut Collab) -> Result<bool, anyhow::Error> {
+    let mut txn = collab.context.transact_mut();
+    let Some(Out::YMap(document)) = collab.data.get(&txn, "document") else {
+      return Ok(false);
+    };
+    let Some(Out::YMap(blocks)) = document.get(&txn, "blocks") else {
+      return Ok(false);
+    };
+    let renamed = blocks
+      .iter(&txn)
+      .filter_map(|(_, block)| match block {
+        Out::YMap(block) => Some(block),
+        _ => None,
+      })
+      .filter(|block| {
+        matches!(block.get(&txn, "ty"), Some(Out::Any(Any::String(ty))) if &*ty == self.from)
+      })
+      .collect::<Vec<MapRef>>();
+    for block in &renamed {
+      block.insert(&mut txn, "ty", self.to);
+    }
+    Ok(!renamed.is_empty())
+  }
+}
+
+async fn apply_pending_updates(
+  collab_redis_stream: &CollabRedisStream,
+  workspace_id: &str,
+  object_id: &str,
+  collab: &mut Collab,
+) -> Result<(), AppError> {
+  let updates = collab_redis_stream
+    .current_collab_updates(workspace_id, object_id, None)
+    .await
+    .map_err(|err| AppError::Internal(err.into()))?;
+  let mut txn = collab.transact_mut();
+  for (_, update) in updates {
+    let update = update
+      .into_update()
+      .map_err(|err| AppError::DecodeUpdateError(err.to_string()))?;
+    txn
+      .apply_update(update)
+      .map_err(|err| AppError::DecodeUpdateError(err.to_string()))?;
+  }
+  Ok(())
+}
+
+fn open_collab(object_id: &str, encoded_collab: &EncodedCollab) -> Result<Collab, AppError> {
+  let doc_state = encoded_collab.doc_state.to_vec();
+  let data_source = match encoded_collab.version {
+    EncoderVersion::V1 => DataSource::DocStateV1(doc_state),
+    EncoderVersion::V2 => DataSource::DocStateV2(doc_state),
+  };
+  Collab::new_with_source(CollabOrigin::Server, object_id, data_source, vec![], false)
+    .map_err(|err| AppError::DecodeUpdateError(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+  use yrs::updates::decoder::Decode;
+  use yrs::{MapPrelim, StateVector, Update};
+
+  use super::*;
+
+  struct FailingMigration(i32);
+
+  impl CollabMigration for FailingMigration {
+    fn version(&self) -> i32 {
+      self.0
+    }
+
+    fn name(&self) -> &'static str {
+      "failing"
+    }
+
+    fn collab_type(&self) -> CollabType {
+      CollabType::Document
+    }
+
+    fn migrate(&self, _collab: &mut Collab) -> Result<bool, anyhow::Error> {
+      bail!("unexpected content")
+    }
+  }
+
+  fn rename(version: i32, from: &'static str, to: &'static str) -> Arc<dyn CollabMigration> {
+    Arc::new(RenameDocumentBlockType {
+      version,
+      name: "rename",
+      from,
+      to,
+    })
+  }
+
+  /// A document fixture with the blocks given as (id, type).
+  fn fixture_document(blocks: &[(&str, &str)]) -> Collab {
+    let mut collab = Collab::new_with_origin(CollabOrigin::Server, "document", vec![], false);
+    {
+      let mut txn = collab.context.transact_mut();
+      let document = collab
+        .data
+        .insert(&mut txn, "document", MapPrelim::default());
+      let block_map: MapRef = document.insert(&mut txn, "blocks", MapPrelim::default());
+      for (id, ty) in blocks {
+        let block: MapRef = block_map.insert(&mut txn, *id, MapPrelim::default());
+        block.insert(&mut txn, "id", *id);
+        block.insert(&mut txn, "ty", *ty);
+      }
+    }
+    collab
+  }
+
+  fn block_types(collab: &Collab) -> BTreeMap<String, String> {
+    collab.to_json_value()["document"]["blocks"]
+      .as_object()
+      .unwrap()
+      .iter()
+      .map(|(id, block)| (id.clone(), block["ty"].as_str().unwrap().to_string()))
+      .collect()
+  }
+
+  /// Applies the migrations to a copy of the fixture, and checks that the update of the outcome
+  /// brings another copy of the fixture to the same state, as a client receiving it would be.
+  fn apply_to_fixture(
+    migrations: &CollabMigrations,
+    fixture: &Collab,
+    from_version: i32,
+  ) -> (MigrationOutcome, Collab) {
+    let doc_state = fixture
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+    let copy = || {
+      let encoded_collab = EncodedCollab::new_v1(vec![], doc_state.clone());
+      open_collab("document", &encoded_collab).unwrap()
+    };
+    let mut migrated = copy();
+    let outcome = migrations.apply(&mut migrated, &CollabType::Document, from_version);
+
+    let mut client = copy();
+    if let Some(update) = &outcome.update {
+      client
+        .transact_mut()
+        .apply_update(Update::decode_v1(update).unwrap())
+        .unwrap();
+    }
+    assert_eq!(client.to_json_value(), migrated.to_json_value());
+    (outcome, migrated)
+  }
+
+  #[test]
+  fn migrations_transform_fixture_collabs_test() {
+    let migrations = CollabMigrations::new(vec![rename(1, "todo", "todo_list")]).unwrap();
+    let fixture = fixture_document(&[("a", "todo"), ("b", "paragraph"), ("c", "todo")]);
+    let (outcome, migrated) = apply_to_fixture(&migrations, &fixture, 0);
+    assert_eq!(outcome.version, 1);
+    assert_eq!(outcome.results, vec![("rename", MigrationResult::Changed)]);
+    assert_eq!(
+      block_types(&migrated),
+      BTreeMap::from([
+        ("a".to_string(), "todo_list".to_string()),
+        ("b".to_string(), "paragraph".to_string()),
+        ("c".to_string(), "todo_list".to_string()),
+      ])
+    );
+
+    // a collab following the new conventions is left unchanged
+    let (outcome, _) = apply_to_fixture(&migrations, &migrated, 0);
+    assert!(outcome.update.is_none());
+    assert_eq!(
+      outcome.results,
+      vec![("rename", MigrationResult::Unchanged)]
+    );
+
+    // migrations at or below the version of the collab are skipped
+    let (outcome, migrated) = apply_to_fixture(&migrations, &fixture, 1);
+    assert!(outcome.results.is_empty());
+    assert_eq!(block_types(&migrated)["a"], "todo");
+  }
+
+  #[test]
+  fn failed_migration_keeps_collab_at_previous_version_test() {
+    let migrations = CollabMigrations::new(vec![
+      rename(1, "todo", "todo_list"),
+      Arc::new(FailingMigration(2)),
+      rename(3, "paragraph", "text"),
+    ])
+    .unwrap();
+    let fixture = fixture_document(&[("a", "todo"), ("b", "paragraph")]);
+    let (outcome, migrated) = apply_to_fixture(&migrations, &fixture, 0);
+    assert_eq!(outcome.version, 1);
+    assert_eq!(outcome.results.len(), 2);
+    assert!(matches!(outcome.results[1].1, MigrationResult::Failed(_)));
+    assert_eq!(block_types(&migrated)["a"], "todo_list");
+    assert_eq!(block_types(&migrated)["b"], "paragraph");
+
+    // only the migrations of the collab type are applied, the others just move the version
+    let mut folder = Collab::new_with_origin(CollabOrigin::Server, "folder", vec![], false);
+    let outcome = migrations.apply(&mut folder, &CollabType::Folder, 0);
+    assert_eq!(outcome.version, 3);
+    assert!(outcome.results.is_empty());
+
+    assert!(CollabMigrations::new(vec![rename(2, "a", "b"), rename(2, "b", "c")]).is_err());
+    assert!(CollabMigrations::new(vec![rename(0, "a", "b")]).is_err());
+  }
+}
@@ -1,4 +1,9 @@
 pub mod access_control;
 pub mod cache;
+pub mod compaction;
+pub mod live_migration;
+pub mod migration;
+pub mod recovery;
+pub mod restore;
 pub mod storage;
 pub mod validator;
@@ -0,0 +1,342 @@
+use std::time::{Duration, Instant};
+
+use app_error::AppError;
+use collab::core::collab::DataSource;
+use collab::core::origin::CollabOrigin;
+use collab::entity::{EncodedCollab, EncoderVersion};
+use collab::preclude::Collab;
+use collab_entity::CollabType;
+use database::collab::{CollabStorage, GetCollabOrigin};
+use database::collab_verification::{
+  select_unrepaired_collab_verification_report, upsert_collab_verification_report,
+};
+use database_entity::dto::QueryCollabParams;
+use parking_lot::Mutex;
+use shared_entity::dto::collab_recovery_dto::RecoveredCollabSnapshot;
+use sqlx::PgPool;
+use tracing::{error, warn};
+use uuid::Uuid;
+use yrs::updates::decoder::Decode;
+use yrs::Update;
+
+/// Number of snapshots tried, newest first, before giving up on a collab.
+const MAX_RECOVERY_SNAPSHOTS: usize = 10;
+
+/// Where the corruption of a collab was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectedOn {
+  Http,
+  Realtime,
+}
+
+impl DetectedOn {
+  fn as_str(&self) -> &'static str {
+    match self {
+      DetectedOn::Http => "http",
+      DetectedOn::Realtime => "realtime",
+    }
+  }
+}
+
+pub struct RecoverableCollab {
+  pub encoded_collab: EncodedCollab,
+  /// Set when the stored state could not be decoded and `encoded_collab` is the content of a
+  /// snapshot.
+  pub recovered_from: Option<RecoveredCollabSnapshot>,
+}
+
+/// Serves the most recent snapshot of a collab whose stored state can't be decoded, and records
+/// the corruption in the verification report. A recovered collab stays read-only on the realtime
+/// server until an admin confirms the repair, so that nobody edits a rolled back state unknowingly.
+#[derive(Clone)]
+pub struct CollabRecovery {
+  pg_pool: PgPool,
+}
+
+impl CollabRecovery {
+  pub fn new(pg_pool: PgPool) -> Self {
+    Self { pg_pool }
+  }
+
+  /// Returns the encoded collab, or the most recent snapshot which can be decoded when the stored
+  /// state can't be. Fails with [AppError::DecodeUpdateError] when none of them can be decoded.
+  pub async fn get_encode_collab<S>(
+    &self,
+    storage: &S,
+    origin: GetCollabOrigin,
+    params: QueryCollabParams,
+    from_editing_collab: bool,
+    detected_on: DetectedOn,
+  ) -> Result<RecoverableCollab, AppError>
+  where
+    S: CollabStorage + ?Sized,
+  {
+    let workspace_id = params.workspace_id.clone();
+    let object_id = params.object_id.clone();
+    let collab_type = params.collab_type.clone();
+    let error = match storage
+      .get_encode_collab(origin, params, from_editing_collab)
+      .await
+    {
+      Ok(encoded_collab) => match verify_doc_state(encoded_collab).await? {
+        Ok(encoded_collab) => {
+          return Ok(RecoverableCollab {
+            encoded_collab,
+            recovered_from: None,
+          })
+        },
+        Err(err) => err,
+      },
+      Err(AppError::DecodeUpdateError(err)) => err,
+      Err(err) => return Err(err),
+    };
+
+    let recovered = recover_from_snapshots(storage, &workspace_id, &object_id, &collab_type).await;
+    let recovered_from = recovered.as_ref().map(|(_, snapshot)| snapshot.clone());
+    match &recovered_from {
+      Some(snapshot) => error!(
+        "collab {} of workspace {} could not be decoded: {}, recovered from snapshot {} taken at {}",
+        object_id, workspace_id, error, snapshot.snapshot_id, snapshot.created_at
+      ),
+      None => error!(
+        "collab {} of workspace {} could not be decoded: {}, no snapshot could be recovered",
+        object_id, workspace_id, error
+      ),
+    }
+    if let Err(err) = self
+      .record_corruption(
+        &workspace_id,
+        &object_id,
+        &collab_type,
+        detected_on,
+        &error,
+        recovered_from.as_ref(),
+      )
+      .await
+    {
+      warn!(
+        "failed to record corruption of collab {}: {}",
+        object_id, err
+      );
+    }
+
+    match recovered {
+      Some((encoded_collab, snapshot)) => Ok(RecoverableCollab {
+        encoded_collab,
+        recovered_from: Some(snapshot),
+      }),
+      None => Err(AppError::DecodeUpdateError(format!(
+        "collab {} could not be decoded and has no recoverable snapshot: {}",
+        object_id, error
+      ))),
+    }
+  }
+
+  /// Whether the collab was recovered from a snapshot and the repair was not confirmed yet.
+  pub async fn is_awaiting_repair(&self, object_id: &str) -> Result<bool, AppError> {
+    let report = select_unrepaired_collab_verification_report(&self.pg_pool, object_id).await?;
+    Ok(report.is_some())
+  }
+
+  async fn record_corruption(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    collab_type: &CollabType,
+    detected_on: DetectedOn,
+    error: &str,
+    recovered_from: Option<&RecoveredCollabSnapshot>,
+  ) -> Result<(), AppError> {
+    let workspace_id = Uuid::parse_str(workspace_id)?;
+    upsert_collab_verification_report(
+      &self.pg_pool,
+      &workspace_id,
+      object_id,
+      collab_type.value(),
+      detected_on.as_str(),
+      error,
+      recovered_from.map(|snapshot| snapshot.snapshot_id),
+      recovered_from.map(|snapshot| snapshot.created_at),
+    )
+    .await?;
+    Ok(())
+  }
+}
+
+/// A collab the realtime server opened from a snapshot. The repair is checked again at most every
+/// [RecoveredCollab::REPAIR_CHECK_INTERVAL] while users try to edit the collab.
+pub struct RecoveredCollab {
+  pub encoded_collab: EncodedCollab,
+  pub snapshot: RecoveredCollabSnapshot,
+  last_checked_at: Mutex<Instant>,
+}
+
+impl RecoveredCollab {
+  const REPAIR_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+  pub fn new(encoded_collab: EncodedCollab, snapshot: RecoveredCollabSnapshot) -> Self {
+    Self {
+      encoded_collab,
+      snapshot,
+      last_checked_at: Mutex::new(Instant::now()),
+    }
+  }
+
+  /// Returns true if the repair should be checked again, and restarts the interval.
+  pub fn should_check_repair(&self, now: Instant) -> bool {
+    let mut last_checked_at = self.last_checked_at.lock();
+    if now.saturating_duration_since(*last_checked_at) < Self::REPAIR_CHECK_INTERVAL {
+      return false;
+    }
+    *last_checked_at = now;
+    true
+  }
+}
+
+/// Checks that the doc state can be decoded as an update. It's cheaper than applying it, and
+/// catches the truncated or garbled states which would fail to open on the client.
+async fn verify_doc_state(
+  encoded_collab: EncodedCollab,
+) -> Result<Result<EncodedCollab, String>, AppError> {
+  if encoded_collab.doc_state.is_empty() {
+    return Ok(Ok(encoded_collab));
+  }
+  tokio::task::spawn_blocking(move || {
+    let result = match encoded_collab.version {
+      EncoderVersion::V1 => Update::decode_v1(&encoded_collab.doc_state),
+      EncoderVersion::V2 => Update::decode_v2(&encoded_collab.doc_state),
+    };
+    match result {
+      Ok(_) => Ok(encoded_collab),
+      Err(err) => Err(format!("failed to decode doc state: {}", err)),
+    }
+  })
+  .await
+  .map_err(|err| AppError::Internal(anyhow::anyhow!("Failed to spawn blocking task: {:?}", err)))
+}
+
+/// Returns the most recent snapshot of the collab which can be opened, trying at most
+/// [MAX_RECOVERY_SNAPSHOTS] of them.
+async fn recover_from_snapshots<S>(
+  storage: &S,
+  workspace_id: &str,
+  object_id: &str,
+  collab_type: &CollabType,
+) -> Option<(EncodedCollab, RecoveredCollabSnapshot)>
+where
+  S: CollabStorage + ?Sized,
+{
+  let metas = match storage
+    .get_collab_snapshot_list(workspace_id, object_id)
+    .await
+  {
+    Ok(metas) => metas.0,
+    Err(err) => {
+      warn!("failed to list snapshots of collab {}: {}", object_id, err);
+      return None;
+    },
+  };
+  for meta in metas.into_iter().take(MAX_RECOVERY_SNAPSHOTS) {
+    let snapshot_data = match storage
+      .get_collab_snapshot(workspace_id, &meta.object_id, &meta.snapshot_id)
+      .await
+    {
+      Ok(snapshot_data) => snapshot_data,
+      Err(err) => {
+        warn!(
+          "failed to get snapshot {} of collab {}: {}",
+          meta.snapshot_id, object_id, err
+        );
+        continue;
+      },
+    };
+    if let Some(encoded_collab) =
+      open_snapshot(object_id, collab_type, &snapshot_data.encoded_collab_v1)
+    {
+      return Some((
+        encoded_collab,
+        RecoveredCollabSnapshot {
+          snapshot_id: meta.snapshot_id,
+          created_at: meta.created_at,
+        },
+      ));
+    }
+  }
+  None
+}
+
+fn open_snapshot(
+  object_id: &str,
+  collab_type: &CollabType,
+  encoded_collab_v1: &[u8],
+) -> Option<EncodedCollab> {
+  let encoded_collab = EncodedCollab::decode_from_bytes(encoded_collab_v1).ok()?;
+  let data_source = match encoded_collab.version {
+    EncoderVersion::V1 => DataSource::DocStateV1(encoded_collab.doc_state.to_vec()),
+    EncoderVersion::V2 => DataSource::DocStateV2(encoded_collab.doc_state.to_vec()),
+  };
+  let collab =
+    Collab::new_with_source(CollabOrigin::Empty, object_id, data_source, vec![], false).ok()?;
+  collab_type.validate_require_data(&collab).ok()?;
+  Some(encoded_collab)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use yrs::updates::encoder::Encode;
+  use yrs::{Any, Map, ReadTxn, StateVector};
+
+  fn encoded_document_collab() -> EncodedCollab {
+    let mut collab = Collab::new_with_origin(CollabOrigin::Empty, "object", vec![], false);
+    {
+      let mut txn = collab.context.transact_mut();
+      collab
+        .data
+        .insert(&mut txn, "key", Any::String("value".into()));
+    }
+    let txn = collab.transact();
+    EncodedCollab::new_v1(
+      txn.state_vector().encode_v1(),
+      txn.encode_state_as_update_v1(&StateVector::default()),
+    )
+  }
+
+  #[tokio::test]
+  async fn garbled_doc_state_is_detected_test() {
+    let encoded_collab = encoded_document_collab();
+    assert!(verify_doc_state(encoded_collab.clone())
+      .await
+      .unwrap()
+      .is_ok());
+
+    let mut garbled = encoded_collab.doc_state.to_vec();
+    garbled.truncate(garbled.len() / 2);
+    let garbled = EncodedCollab::new_v1(encoded_collab.state_vector.clone(), garbled);
+    assert!(verify_doc_state(garbled).await.unwrap().is_err());
+  }
+
+  #[test]
+  fn undecodable_snapshot_is_skipped_test() {
+    assert!(open_snapshot("object", &CollabType::Unknown, &[1, 2, 3]).is_none());
+
+    let encoded_collab_v1 = encoded_document_collab().encode_to_bytes().unwrap();
+    assert!(open_snapshot("object", &CollabType::Unknown, &encoded_collab_v1).is_some());
+  }
+
+  #[test]
+  fn repair_check_interval_test() {
+    let recovered = RecoveredCollab::new(
+      encoded_document_collab(),
+      RecoveredCollabSnapshot {
+        snapshot_id: 1,
+        created_at: chrono::Utc::now(),
+      },
+    );
+    let now = Instant::now();
+    assert!(!recovered.should_check_repair(now));
+    let later = now + RecoveredCollab::REPAIR_CHECK_INTERVAL;
+    assert!(recovered.should_check_repair(later));
+    assert!(!recovered.should_check_repair(later));
+  }
+}
@@ -0,0 +1,169 @@
+use collab::preclude::Collab;
+use tracing::warn;
+use yrs::types::ToJson;
+use yrs::{
+  ArrayPrelim, GetString, Map, MapPrelim, MapRef, Out, ReadTxn, Text, TextPrelim, TransactionMut,
+};
+
+use crate::group::compaction::{copy_array, copy_map, copy_text, COMPACTION_CLIENT_ID_KEY};
+
+/// Replaces the content of `collab` by the content of `snapshot`, an older version of the same
+/// collab, and returns the update doing it.
+///
+/// Unlike loading the snapshot in place of the collab, the content is rewritten on top of the
+/// current state, so that the clients editing the collab converge to the content of the snapshot
+/// once they apply the update, without reloading it. The values which are the same in both
+/// versions are left untouched. Texts which differ are restored without their formatting.
+pub fn restore_collab_content(collab: &mut Collab, snapshot: &Collab) -> Vec<u8> {
+  let state_vector = collab.transact().state_vector();
+  {
+    let src_txn = snapshot.transact();
+    let mut dst_txn = collab.context.transact_mut();
+    replace_map(&src_txn, &snapshot.data, &mut dst_txn, &collab.data, &[]);
+    // the client id of the compaction belongs to the current copy of the collab
+    replace_map(
+      &src_txn,
+      &snapshot.meta,
+      &mut dst_txn,
+      &collab.meta,
+      &[COMPACTION_CLIENT_ID_KEY],
+    );
+  }
+  collab.transact().encode_state_as_update_v1(&state_vector)
+}
+
+fn replace_map<T: ReadTxn>(
+  src_txn: &T,
+  src: &MapRef,
+  dst_txn: &mut TransactionMut,
+  dst: &MapRef,
+  kept_keys: &[&str],
+) {
+  let removed_keys: Vec<String> = dst
+    .keys(dst_txn)
+    .filter(|key| !kept_keys.contains(key) && src.get(src_txn, key).is_none())
+    .map(|key| key.to_string())
+    .collect();
+  for key in removed_keys {
+    dst.remove(dst_txn, &key);
+  }
+
+  for (key, value) in src.iter(src_txn) {
+    if kept_keys.contains(&key) {
+      continue;
+    }
+    match (value, dst.get(dst_txn, key)) {
+      (Out::Any(value), Some(Out::Any(current))) if value == current => {},
+      (Out::YMap(map), Some(Out::YMap(current))) => {
+        replace_map(src_txn, &map, dst_txn, &current, &[]);
+      },
+      (Out::YArray(array), Some(Out::YArray(current)))
+        if array.to_json(src_txn) == current.to_json(dst_txn) => {},
+      (Out::YText(text), Some(Out::YText(current))) => {
+        let content = text.get_string(src_txn);
+        if current.get_string(dst_txn) != content {
+          let len = current.len(dst_txn);
+          current.remove_range(dst_txn, 0, len);
+          current.insert(dst_txn, 0, &content);
+        }
+      },
+      (value, _) => insert_value(src_txn, key, value, dst_txn, dst),
+    }
+  }
+}
+
+fn insert_value<T: ReadTxn>(
+  src_txn: &T,
+  key: &str,
+  value: Out,
+  dst_txn: &mut TransactionMut,
+  dst: &MapRef,
+) {
+  match value {
+    Out::Any(any) => {
+      dst.insert(dst_txn, key, any);
+    },
+    Out::YMap(map) => {
+      let child = dst.insert(dst_txn, key, MapPrelim::default());
+      if let Err(err) = copy_map(src_txn, &map, dst_txn, &child) {
+        warn!("skip value while restoring collab: {}", err);
+      }
+    },
+    Out::YArray(array) => {
+      let child = dst.insert(dst_txn, key, ArrayPrelim::default());
+      if let Err(err) = copy_array(src_txn, &array, dst_txn, &child) {
+        warn!("skip value while restoring collab: {}", err);
+      }
+    },
+    Out::YText(text) => {
+      let child = dst.insert(dst_txn, key, TextPrelim::new(""));
+      if let Err(err) = copy_text(src_txn, &text, dst_txn, &child) {
+        warn!("skip value while restoring collab: {}", err);
+      }
+    },
+    other => warn!(
+      "skip unsupported value while restoring collab: {}",
+      other.to_json(src_txn)
+    ),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use collab::core::collab::DataSource;
+  use collab::core::origin::CollabOrigin;
+  use yrs::updates::decoder::Decode;
+  use yrs::{Any, StateVector, TextRef, Update};
+
+  fn copy_of(collab: &Collab) -> Collab {
+    let doc_state = collab
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+    Collab::new_with_source(
+      CollabOrigin::Empty,
+      "object",
+      DataSource::DocStateV1(doc_state),
+      vec![],
+      false,
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn clients_converge_to_restored_snapshot_test() {
+    let mut collab = Collab::new_with_origin(CollabOrigin::Server, "object", vec![], false);
+    {
+      let mut txn = collab.context.transact_mut();
+      let text = collab.data.get_or_init_text(&mut txn, "text");
+      text.insert(&mut txn, 0, "hello world");
+      collab.data.insert(&mut txn, "count", Any::BigInt(1));
+      collab.data.insert(&mut txn, "title", "first");
+    }
+    let snapshot = copy_of(&collab);
+    {
+      let mut txn = collab.context.transact_mut();
+      let text: TextRef = collab.data.get_with_txn(&txn, "text").unwrap();
+      text.remove_range(&mut txn, 0, 6);
+      text.push(&mut txn, "!");
+      collab.data.insert(&mut txn, "count", Any::BigInt(2));
+      collab.data.remove(&mut txn, "title");
+      collab.data.insert(&mut txn, "added", "later");
+    }
+    // a client editing the collab before it's restored
+    let mut client = copy_of(&collab);
+
+    let update = restore_collab_content(&mut collab, &snapshot);
+    assert_eq!(collab.to_json_value(), snapshot.to_json_value());
+    client
+      .transact_mut()
+      .apply_update(Update::decode_v1(&update).unwrap())
+      .unwrap();
+    assert_eq!(client.to_json_value(), snapshot.to_json_value());
+
+    // restoring the same content again changes nothing
+    let state_vector = collab.transact().state_vector();
+    restore_collab_content(&mut collab, &snapshot);
+    assert_eq!(collab.transact().state_vector(), state_vector);
+  }
+}
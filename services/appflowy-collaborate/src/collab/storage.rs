@@ -12,8 +12,9 @@ use database::collab::{
   CollabStorageAccessControl, GetCollabOrigin,
 };
 use database_entity::dto::{
-  AFAccessLevel, AFSnapshotMeta, AFSnapshotMetas, CollabParams, InsertSnapshotParams,
-  PendingCollabWrite, QueryCollab, QueryCollabParams, QueryCollabResult, SnapshotData,
+  AFAccessLevel, AFCollabVersion, AFSnapshotMeta, AFSnapshotMetas, CollabMode, CollabParams,
+  InsertSnapshotParams, PendingCollabWrite, QueryCollab, QueryCollabParams, QueryCollabResult,
+  SnapshotData,
 };
 use itertools::{Either, Itertools};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
@@ -32,7 +33,8 @@ use yrs::Update;
 
 use crate::collab::access_control::CollabStorageAccessControlImpl;
 use crate::collab::cache::CollabCache;
-use crate::collab::validator::CollabValidator;
+use crate::collab::validator::{CollabSizeLimits, CollabValidator};
+use crate::load_shed::PressureSample;
 use crate::metrics::CollabMetrics;
 use crate::snapshot::SnapshotControl;
 
@@ -47,6 +49,7 @@ pub struct CollabStorageImpl<AC> {
   snapshot_control: SnapshotControl,
   rt_cmd_sender: CLCommandSender,
   queue: Sender<PendingCollabWrite>,
+  size_limits: CollabSizeLimits,
 }
 
 impl<AC> CollabStorageImpl<AC>
@@ -58,6 +61,7 @@ where
     access_control: AC,
     snapshot_control: SnapshotControl,
     rt_cmd_sender: CLCommandSender,
+    size_limits: CollabSizeLimits,
   ) -> Self {
     let (queue, reader) = channel(1000);
     tokio::spawn(Self::periodic_write_task(cache.clone(), reader));
@@ -67,6 +71,7 @@ where
       snapshot_control,
       rt_cmd_sender,
       queue,
+      size_limits,
     }
   }
 
@@ -74,6 +79,25 @@ where
     self.cache.metrics()
   }
 
+  pub async fn is_exist(&self, workspace_id: &str, object_id: &str) -> Result<bool, AppError> {
+    self.cache.is_exist(workspace_id, object_id).await
+  }
+
+  /// Number of collab writes queued and not persisted yet.
+  /// Remove a collab which was permanently deleted from the cache, see
+  /// [database::collab::delete_collab_cascade].
+  pub async fn evict_deleted_collab(&self, object_id: &str) -> Result<(), AppError> {
+    self.cache.evict_collab(object_id).await
+  }
+
+  pub fn pending_write_count(&self) -> usize {
+    self.queue.max_capacity() - self.queue.capacity()
+  }
+
+  pub async fn sample_pressure(&self) -> PressureSample {
+    PressureSample::collect(&self.cache, self.pending_write_count()).await
+  }
+
   const PENDING_WRITE_BUF_CAPACITY: usize = 20;
   async fn periodic_write_task(cache: CollabCache, mut reader: Receiver<PendingCollabWrite>) {
     let mut buf = Vec::with_capacity(Self::PENDING_WRITE_BUF_CAPACITY);
@@ -218,7 +242,11 @@ where
       params.object_id,
       params.collab_type
     );
-    if let Err(err) = params.check_encode_collab().await {
+    let mode = self.cache.collab_mode(&params.object_id).await?;
+    if let Err(err) = params.check_encode_collab(mode, &self.size_limits).await {
+      if matches!(err, AppError::CollabSizeExceeded { .. }) {
+        return Err(err);
+      }
       return Err(AppError::NoRequiredData(format!(
         "Invalid collab doc state detected for workspace_id: {}, uid: {}, object_id: {} collab_type:{}. Error details: {}",
         workspace_id, uid, params.object_id, params.collab_type, err
@@ -244,12 +272,15 @@ where
       .await
   }
 
-  /// Sends a collab message to all connected clients.
+  /// Sends a collab message to all connected clients, whichever realtime server they're
+  /// connected to.
   /// # Arguments
+  /// * `workspace_id` - The ID of the workspace of the collaboration object.
   /// * `object_id` - The ID of the collaboration object.
   /// * `collab_messages` - The list of collab messages to broadcast.
   pub async fn broadcast_encode_collab(
     &self,
+    workspace_id: String,
     object_id: String,
     collab_messages: Vec<ClientCollabMessage>,
   ) -> Result<(), AppError> {
@@ -258,6 +289,7 @@ where
     self
       .rt_cmd_sender
       .send(CollaborationCommand::ServerSendCollabMessage {
+        workspace_id,
         object_id,
         collab_messages,
         ret: sender,
@@ -397,6 +429,48 @@ where
     }
   }
 
+  #[instrument(level = "trace", skip_all, fields(count = params_list.len()), err)]
+  async fn batch_insert_new_collab_with_transaction(
+    &self,
+    workspace_id: &str,
+    uid: &i64,
+    params_list: Vec<CollabParams>,
+    transaction: &mut Transaction<'_, sqlx::Postgres>,
+  ) -> AppResult<()> {
+    for params in &params_list {
+      params.validate()?;
+    }
+    self
+      .check_write_workspace_permission(workspace_id, uid)
+      .await?;
+    for params in &params_list {
+      self
+        .access_control
+        .update_policy(uid, &params.object_id, AFAccessLevel::FullAccess)
+        .await?;
+    }
+
+    match tokio::time::timeout(
+      Duration::from_secs(120),
+      self.cache.bulk_insert_new_collab_with_transaction(
+        workspace_id,
+        uid,
+        params_list,
+        transaction,
+      ),
+    )
+    .await
+    {
+      Ok(result) => result,
+      Err(_) => {
+        error!("Timeout waiting for the bulk insert of new collabs");
+        Err(AppError::RequestTimeout(
+          "bulk insert of new collabs".to_string(),
+        ))
+      },
+    }
+  }
+
   #[instrument(level = "trace", skip_all, fields(oid = %params.object_id, from_editing_collab = %from_editing_collab))]
   async fn get_encode_collab(
     &self,
@@ -514,7 +588,7 @@ where
       .access_control
       .enforce_delete(workspace_id, uid, object_id)
       .await?;
-    self.cache.delete_collab(workspace_id, object_id).await?;
+    self.cache.delete_collab(object_id).await?;
     Ok(())
   }
 
@@ -567,4 +641,23 @@ where
       .get_collab_snapshot_list(workspace_id, oid)
       .await
   }
+
+  async fn get_collab_versions(
+    &self,
+    workspace_id: &str,
+    oid: &str,
+  ) -> AppResult<Vec<AFCollabVersion>> {
+    self
+      .snapshot_control
+      .get_collab_versions(workspace_id, oid)
+      .await
+  }
+
+  async fn collab_mode(&self, object_id: &str) -> AppResult<CollabMode> {
+    self.cache.collab_mode(object_id).await
+  }
+
+  async fn is_collab_deleted(&self, object_id: &str) -> AppResult<bool> {
+    self.cache.is_collab_deleted(object_id).await
+  }
 }
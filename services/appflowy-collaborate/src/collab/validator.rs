@@ -1,18 +1,124 @@
 use app_error::AppError;
 use async_trait::async_trait;
-use collab_rt_protocol::validate_encode_collab;
-use database_entity::dto::CollabParams;
+use collab::preclude::Collab;
+use database_entity::dto::{CollabMode, CollabParams};
+use tracing::warn;
+use workspace_template::collab_registry::{collab_spec, CollabTypeSpec};
+
+use crate::config::get_env_var;
+
+/// Size limits of what the clients write to a collab, on top of the limit of each collab type,
+/// see [CollabTypeSpec::max_encoded_len].
+#[derive(Debug, Clone)]
+pub struct CollabSizeLimits {
+  /// Maximum length of a single update sent over the websocket.
+  pub max_update_len: usize,
+  /// Maximum length of the encoded collab, checked when it's written.
+  pub max_doc_state_len: usize,
+}
+
+impl Default for CollabSizeLimits {
+  fn default() -> Self {
+    Self {
+      max_update_len: 10 * 1024 * 1024,
+      max_doc_state_len: 64 * 1024 * 1024,
+    }
+  }
+}
+
+impl CollabSizeLimits {
+  pub fn from_env() -> Result<Self, anyhow::Error> {
+    let defaults = Self::default();
+    let limits = Self {
+      max_update_len: get_env_var(
+        "APPFLOWY_COLLAB_MAX_UPDATE_SIZE",
+        &defaults.max_update_len.to_string(),
+      )
+      .parse()?,
+      max_doc_state_len: get_env_var(
+        "APPFLOWY_COLLAB_MAX_DOC_STATE_SIZE",
+        &defaults.max_doc_state_len.to_string(),
+      )
+      .parse()?,
+    };
+    if limits.max_update_len == 0 || limits.max_doc_state_len == 0 {
+      anyhow::bail!("the collab size limits must not be zero");
+    }
+    Ok(limits)
+  }
+
+  pub fn check_update(&self, len: usize) -> Result<(), AppError> {
+    check_len(self.max_update_len, len)
+  }
+
+  pub fn check_doc_state(&self, spec: &CollabTypeSpec, len: usize) -> Result<(), AppError> {
+    check_len(self.doc_state_limit(spec), len)
+  }
+
+  /// The lower of the configured limit and the limit of the collab type.
+  pub fn doc_state_limit(&self, spec: &CollabTypeSpec) -> usize {
+    self.max_doc_state_len.min(spec.max_encoded_len)
+  }
+}
+
+/// Checks the structure of a collab written to a workspace, see
+/// [CollabTypeSpec::check_structure]. Unless `strict`, set by
+/// [crate::feature_flags::STRICT_COLLAB_VALIDATION], an invalid collab is only logged.
+pub fn check_collab_structure(
+  spec: &CollabTypeSpec,
+  workspace_id: &str,
+  object_id: &str,
+  collab: &Collab,
+  strict: bool,
+) -> Result<(), AppError> {
+  let err = match spec.check_structure(collab) {
+    Ok(()) => return Ok(()),
+    Err(err) => err,
+  };
+  if !strict {
+    warn!(
+      "accepted invalid collab {} of workspace {}: {}",
+      object_id, workspace_id, err
+    );
+    return Ok(());
+  }
+  Err(AppError::InvalidCollabStructure {
+    collab_type: err.collab_type.to_string(),
+    missing: err.missing,
+  })
+}
+
+fn check_len(limit: usize, actual: usize) -> Result<(), AppError> {
+  if actual > limit {
+    return Err(AppError::CollabSizeExceeded { limit, actual });
+  }
+  Ok(())
+}
 
 #[async_trait]
 pub trait CollabValidator {
-  async fn check_encode_collab(&self) -> Result<(), AppError>;
+  async fn check_encode_collab(
+    &self,
+    mode: CollabMode,
+    limits: &CollabSizeLimits,
+  ) -> Result<(), AppError>;
 }
 
 #[async_trait]
 impl CollabValidator for CollabParams {
-  async fn check_encode_collab(&self) -> Result<(), AppError> {
-    validate_encode_collab(&self.object_id, &self.encoded_collab_v1, &self.collab_type)
+  async fn check_encode_collab(
+    &self,
+    mode: CollabMode,
+    limits: &CollabSizeLimits,
+  ) -> Result<(), AppError> {
+    let spec = collab_spec(&self.collab_type, mode.is_opaque());
+    limits.check_doc_state(spec, self.encoded_collab_v1.len())?;
+
+    let object_id = self.object_id.clone();
+    let encoded_collab_v1 = self.encoded_collab_v1.clone();
+    tokio::task::spawn_blocking(move || spec.validate_encoded(&object_id, &encoded_collab_v1))
       .await
+      .map_err(|err| AppError::Internal(err.into()))?
       .map_err(|err| AppError::NoRequiredData(err.to_string()))
   }
 }
@@ -7,6 +7,8 @@ use crate::{
 };
 use collab::entity::EncodedCollab;
 use collab_rt_entity::ClientCollabMessage;
+use collab_rt_protocol::{Message, MessageReader, SyncMessage};
+use collab_stream::model::{CollabStreamUpdate, UpdateFlags};
 use dashmap::DashMap;
 use database::collab::CollabStorage;
 use futures::StreamExt;
@@ -15,6 +17,7 @@ use std::{
   sync::{Arc, Weak},
 };
 use tracing::error;
+use yrs::updates::decoder::DecoderV1;
 pub type CLCommandSender = tokio::sync::mpsc::Sender<CollaborationCommand>;
 pub type CLCommandReceiver = tokio::sync::mpsc::Receiver<CollaborationCommand>;
 
@@ -29,7 +32,11 @@ pub enum CollaborationCommand {
     object_ids: Vec<String>,
     ret: BatchEncodeCollabSender,
   },
+  /// Sends updates made by the server to the clients of a collab. When the collab has no group on
+  /// this server, the updates are published to the update stream of the collab, for the groups
+  /// of the other servers to relay them.
   ServerSendCollabMessage {
+    workspace_id: String,
     object_id: String,
     collab_messages: Vec<ClientCollabMessage>,
     ret: tokio::sync::oneshot::Sender<Result<(), RealtimeError>>,
@@ -97,24 +104,114 @@ pub(crate) fn spawn_collaboration_command<S>(
           }
         },
         CollaborationCommand::ServerSendCollabMessage {
+          workspace_id,
           object_id,
           collab_messages,
           ret,
         } => {
-          if let Some(sender) = group_sender_by_object_id.get(&object_id) {
-            if let Err(err) = sender
-              .send(GroupCommand::HandleServerCollabMessage {
-                object_id,
-                collab_messages,
-                ret,
-              })
-              .await
-            {
-              tracing::error!("Send group command error: {}", err);
-            };
+          let sender = group_sender_by_object_id
+            .get(&object_id)
+            .map(|sender| sender.clone());
+          match sender {
+            Some(sender) => {
+              if let Err(err) = sender
+                .send(GroupCommand::HandleServerCollabMessage {
+                  object_id,
+                  collab_messages,
+                  ret,
+                })
+                .await
+              {
+                tracing::error!("Send group command error: {}", err);
+              };
+            },
+            None => match weak_groups.upgrade() {
+              Some(group_manager) => {
+                let res = match server_stream_updates(collab_messages) {
+                  Ok(updates) => {
+                    group_manager
+                      .publish_updates(&workspace_id, &object_id, updates)
+                      .await
+                  },
+                  Err(err) => Err(err),
+                };
+                let _ = ret.send(res);
+              },
+              None => {
+                let _ = ret.send(Ok(()));
+              },
+            },
           }
         },
       }
     }
   });
 }
+
+/// The collab updates carried by the messages of the server, to be written to the update stream.
+fn server_stream_updates(
+  messages: Vec<ClientCollabMessage>,
+) -> Result<Vec<CollabStreamUpdate>, RealtimeError> {
+  let mut updates = Vec::new();
+  for message in messages {
+    let data = match message {
+      ClientCollabMessage::ClientUpdateSync { data } => data,
+      // only the updates are shared with the other servers
+      _ => continue,
+    };
+    let mut decoder = DecoderV1::from(data.payload.as_ref());
+    for msg in MessageReader::new(&mut decoder) {
+      if let Message::Sync(SyncMessage::Update(update)) = msg? {
+        updates.push(CollabStreamUpdate::new(
+          update,
+          data.origin.clone(),
+          UpdateFlags::default(),
+        ));
+      }
+    }
+  }
+  Ok(updates)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use collab::core::origin::CollabOrigin;
+  use collab_rt_entity::{InitSync, UpdateSync};
+  use collab_rt_protocol::CustomMessage;
+  use yrs::updates::encoder::Encode;
+
+  #[test]
+  fn server_updates_are_extracted_test() {
+    let mut payload = Message::Sync(SyncMessage::Update(vec![1, 2])).encode_v1();
+    payload.extend(Message::Sync(SyncMessage::Update(vec![3])).encode_v1());
+    let messages = vec![
+      ClientCollabMessage::new_init_sync(InitSync::new(
+        CollabOrigin::Server,
+        "object".to_string(),
+        collab_entity::CollabType::Document,
+        "workspace".to_string(),
+        0,
+        vec![],
+      )),
+      ClientCollabMessage::new_update_sync(UpdateSync::new(
+        CollabOrigin::Server,
+        "object".to_string(),
+        payload,
+        1,
+      )),
+      ClientCollabMessage::new_update_sync(UpdateSync::new(
+        CollabOrigin::Server,
+        "object".to_string(),
+        Message::Custom(CustomMessage::EditingLock(None)).encode_v1(),
+        2,
+      )),
+    ];
+    let updates = server_stream_updates(messages).unwrap();
+    let data: Vec<_> = updates.iter().map(|update| update.data.clone()).collect();
+    assert_eq!(data, vec![vec![1, 2], vec![3]]);
+    assert!(updates
+      .iter()
+      .all(|update| update.sender == CollabOrigin::Server));
+  }
+}
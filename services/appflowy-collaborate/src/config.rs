@@ -7,6 +7,15 @@ use std::env::VarError;
 use std::fmt::Display;
 use std::str::FromStr;
 
+use crate::collab::compaction::CollabCompactionConfig;
+use crate::collab::validator::CollabSizeLimits;
+use crate::group::full_state::FullStateSyncConfig;
+use crate::group::unload::GroupUnloadConfig;
+use crate::group::write_behind::WriteBehindConfig;
+use crate::load_shed::LoadShedConfig;
+use crate::rate_limit::UpdateRateLimitConfig;
+use crate::snapshot::SnapshotPolicies;
+
 #[derive(Clone, Debug)]
 pub struct Config {
   pub app_env: Environment,
@@ -133,6 +142,16 @@ pub struct CollabSetting {
   pub edit_state_max_count: u32,
   pub edit_state_max_secs: i64,
   pub s3_collab_threshold: u64,
+  /// Folder collabs larger than this (in bytes) are rebuilt from their logical content.
+  pub folder_compaction_threshold: usize,
+  pub snapshot_policies: SnapshotPolicies,
+  pub load_shedding: LoadShedConfig,
+  pub group_unload: GroupUnloadConfig,
+  pub update_rate_limit: UpdateRateLimitConfig,
+  pub size_limits: CollabSizeLimits,
+  pub full_state_sync: FullStateSyncConfig,
+  pub write_behind: WriteBehindConfig,
+  pub compaction: CollabCompactionConfig,
 }
 
 pub fn get_env_var(key: &str, default: &str) -> String {
@@ -207,6 +226,19 @@ pub fn get_configuration() -> Result<Config, anyhow::Error> {
       edit_state_max_count: get_env_var("APPFLOWY_COLLAB_EDIT_STATE_MAX_COUNT", "100").parse()?,
       edit_state_max_secs: get_env_var("APPFLOWY_COLLAB_EDIT_STATE_MAX_SECS", "60").parse()?,
       s3_collab_threshold: get_env_var("APPFLOWY_COLLAB_S3_THRESHOLD", "8000").parse()?,
+      folder_compaction_threshold: get_env_var(
+        "APPFLOWY_COLLAB_FOLDER_COMPACTION_THRESHOLD",
+        "5242880",
+      )
+      .parse()?,
+      snapshot_policies: SnapshotPolicies::from_env()?,
+      load_shedding: LoadShedConfig::from_env()?,
+      group_unload: GroupUnloadConfig::from_env()?,
+      update_rate_limit: UpdateRateLimitConfig::from_env()?,
+      size_limits: CollabSizeLimits::from_env()?,
+      full_state_sync: FullStateSyncConfig::from_env()?,
+      write_behind: WriteBehindConfig::from_env()?,
+      compaction: CollabCompactionConfig::from_env()?,
     },
     redis_uri: get_env_var("APPFLOWY_REDIS_URI", "redis://localhost:6379").into(),
     redis_worker_count: get_env_var("APPFLOWY_REDIS_WORKERS", "60").parse()?,
@@ -0,0 +1,363 @@
+//! Feature flags shared by every server of the deployment.
+//!
+//! The flags are defined in code, see [flag_definitions]. The values set by the admins are stored
+//! in Postgres, and a snapshot of all of them is cached in Redis. Each server keeps the snapshot in
+//! memory and reloads it from Redis when a change is announced on [FEATURE_FLAGS_CHANNEL], so
+//! evaluating a flag never waits on Postgres or Redis.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use app_error::AppError;
+use arc_swap::ArcSwap;
+use chrono::{DateTime, Utc};
+use database::feature_flag::{select_feature_flags, upsert_feature_flag};
+use futures_util::StreamExt;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use shared_entity::dto::feature_flag_dto::{FeatureFlag, FeatureFlagValue};
+use sqlx::PgPool;
+use tracing::{error, info, warn};
+
+/// Compacts the folders whose encoded size is above `APPFLOWY_COLLAB_FOLDER_COMPACTION_THRESHOLD`.
+pub const FOLDER_COMPACTION: &str = "folder_compaction";
+/// Includes the messages of the shared chats in the search results. They're searched since the
+/// chats can be shared, the flag only allows turning it off.
+pub const SHARED_CHAT_SEARCH: &str = "shared_chat_search";
+/// Rejects the collabs written without the structure of their type. While it's off, they're only
+/// logged.
+pub const STRICT_COLLAB_VALIDATION: &str = "strict_collab_validation";
+
+const FEATURE_FLAGS_KEY: &str = "af:feature_flags";
+const FEATURE_FLAGS_CHANNEL: &str = "af:feature_flags:changed";
+const RESUBSCRIBE_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct FeatureFlagDefinition {
+  pub name: &'static str,
+  pub description: &'static str,
+  pub default_value: FeatureFlagValue,
+}
+
+pub fn flag_definitions() -> Vec<FeatureFlagDefinition> {
+  vec![
+    FeatureFlagDefinition {
+      name: FOLDER_COMPACTION,
+      description: "Compact the folders grown above the compaction threshold",
+      default_value: FeatureFlagValue::Percentage { percentage: 100 },
+    },
+    FeatureFlagDefinition {
+      name: SHARED_CHAT_SEARCH,
+      description: "Include the messages of the shared chats in the search results",
+      default_value: FeatureFlagValue::Percentage { percentage: 100 },
+    },
+    FeatureFlagDefinition {
+      name: STRICT_COLLAB_VALIDATION,
+      description: "Reject the collabs written without the structure of their type",
+      default_value: FeatureFlagValue::Percentage { percentage: 0 },
+    },
+  ]
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredFlag {
+  value: FeatureFlagValue,
+  updated_at: DateTime<Utc>,
+}
+
+/// The values set by the admins, as cached in Redis.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct FlagSnapshot {
+  flags: HashMap<String, StoredFlag>,
+}
+
+#[derive(Clone)]
+pub struct FeatureFlags {
+  inner: Arc<FeatureFlagsInner>,
+}
+
+struct FeatureFlagsInner {
+  pg_pool: PgPool,
+  redis: ConnectionManager,
+  definitions: Vec<FeatureFlagDefinition>,
+  snapshot: ArcSwap<FlagSnapshot>,
+}
+
+impl FeatureFlags {
+  /// Loads the snapshot, from Postgres when it isn't cached in Redis yet, and follows its changes
+  /// until the process stops.
+  pub async fn new(
+    pg_pool: PgPool,
+    redis_client: redis::Client,
+    redis: ConnectionManager,
+  ) -> Result<Self, AppError> {
+    let flags = Self {
+      inner: Arc::new(FeatureFlagsInner {
+        pg_pool,
+        redis,
+        definitions: flag_definitions(),
+        snapshot: ArcSwap::from_pointee(FlagSnapshot::default()),
+      }),
+    };
+    match load_cached_snapshot(&flags.inner).await? {
+      Some(snapshot) => flags.inner.snapshot.store(Arc::new(snapshot)),
+      None => flags.publish_snapshot(false).await?,
+    }
+    tokio::spawn(follow_snapshot_changes(
+      Arc::downgrade(&flags.inner),
+      redis_client,
+    ));
+    Ok(flags)
+  }
+
+  /// Whether the flag is enabled for the workspace. Unknown flags are disabled.
+  pub fn enabled(&self, name: &str, workspace_id: &str) -> bool {
+    let snapshot = self.inner.snapshot.load();
+    match snapshot.flags.get(name) {
+      Some(stored) => evaluate(&stored.value, name, workspace_id),
+      None => match self.definition(name) {
+        Some(definition) => evaluate(&definition.default_value, name, workspace_id),
+        None => {
+          warn!("evaluating unknown feature flag {}", name);
+          false
+        },
+      },
+    }
+  }
+
+  pub fn list(&self) -> Vec<FeatureFlag> {
+    let snapshot = self.inner.snapshot.load();
+    self
+      .inner
+      .definitions
+      .iter()
+      .map(|definition| flag_of(definition, &snapshot))
+      .collect()
+  }
+
+  /// Stores the value of the flag, then announces the new snapshot to every server.
+  pub async fn update(
+    &self,
+    name: &str,
+    value: FeatureFlagValue,
+    updated_by: i64,
+  ) -> Result<FeatureFlag, AppError> {
+    let definition = self
+      .definition(name)
+      .ok_or_else(|| AppError::RecordNotFound(format!("feature flag {} not found", name)))?;
+    if !definition.default_value.is_same_kind(&value) {
+      return Err(AppError::InvalidRequest(format!(
+        "feature flag {} expects a value like {:?}",
+        name, definition.default_value
+      )));
+    }
+    if let FeatureFlagValue::Percentage { percentage } = value {
+      if percentage > 100 {
+        return Err(AppError::InvalidRequest(
+          "percentage must be between 0 and 100".to_string(),
+        ));
+      }
+    }
+
+    let value = serde_json::to_value(&value).map_err(|err| AppError::Internal(err.into()))?;
+    upsert_feature_flag(&self.inner.pg_pool, name, value, updated_by).await?;
+    self.publish_snapshot(true).await?;
+    info!("feature flag {} updated by {}", name, updated_by);
+    Ok(flag_of(definition, &self.inner.snapshot.load()))
+  }
+
+  fn definition(&self, name: &str) -> Option<&FeatureFlagDefinition> {
+    self
+      .inner
+      .definitions
+      .iter()
+      .find(|definition| definition.name == name)
+  }
+
+  /// Rebuilds the snapshot from Postgres and caches it in Redis, notifying the other servers when
+  /// `notify` is set.
+  async fn publish_snapshot(&self, notify: bool) -> Result<(), AppError> {
+    let mut flags = HashMap::new();
+    for row in select_feature_flags(&self.inner.pg_pool).await? {
+      match serde_json::from_value::<FeatureFlagValue>(row.value) {
+        Ok(value) => {
+          flags.insert(
+            row.name,
+            StoredFlag {
+              value,
+              updated_at: row.updated_at,
+            },
+          );
+        },
+        Err(err) => error!("invalid value of feature flag {}: {}", row.name, err),
+      }
+    }
+    let snapshot = FlagSnapshot { flags };
+    let payload = serde_json::to_string(&snapshot).map_err(|err| AppError::Internal(err.into()))?;
+    let mut redis = self.inner.redis.clone();
+    redis
+      .set::<_, _, ()>(FEATURE_FLAGS_KEY, payload)
+      .await
+      .map_err(|err| AppError::Internal(anyhow!("failed to cache feature flags: {}", err)))?;
+    self.inner.snapshot.store(Arc::new(snapshot));
+    if notify {
+      redis
+        .publish::<_, _, ()>(FEATURE_FLAGS_CHANNEL, "")
+        .await
+        .map_err(|err| AppError::Internal(anyhow!("failed to announce feature flags: {}", err)))?;
+    }
+    Ok(())
+  }
+}
+
+async fn load_cached_snapshot(inner: &FeatureFlagsInner) -> Result<Option<FlagSnapshot>, AppError> {
+  let mut redis = inner.redis.clone();
+  let payload: Option<String> = redis
+    .get(FEATURE_FLAGS_KEY)
+    .await
+    .map_err(|err| AppError::Internal(anyhow!("failed to read feature flags: {}", err)))?;
+  match payload {
+    None => Ok(None),
+    Some(payload) => serde_json::from_str(&payload)
+      .map(Some)
+      .map_err(|err| AppError::Internal(err.into())),
+  }
+}
+
+/// Reloads the snapshot each time a change is announced. The snapshot is also reloaded after
+/// subscribing again, since changes may have been missed while the connection was lost.
+async fn follow_snapshot_changes(
+  inner: std::sync::Weak<FeatureFlagsInner>,
+  redis_client: redis::Client,
+) {
+  loop {
+    let mut pubsub = match redis_client.get_async_pubsub().await {
+      Ok(pubsub) => pubsub,
+      Err(err) => {
+        warn!("failed to connect to the feature flags channel: {}", err);
+        tokio::time::sleep(RESUBSCRIBE_INTERVAL).await;
+        continue;
+      },
+    };
+    if let Err(err) = pubsub.subscribe(FEATURE_FLAGS_CHANNEL).await {
+      warn!("failed to subscribe to the feature flags channel: {}", err);
+      tokio::time::sleep(RESUBSCRIBE_INTERVAL).await;
+      continue;
+    }
+
+    let mut messages = pubsub.on_message();
+    loop {
+      let inner = match inner.upgrade() {
+        Some(inner) => inner,
+        None => return,
+      };
+      match load_cached_snapshot(&inner).await {
+        Ok(Some(snapshot)) => inner.snapshot.store(Arc::new(snapshot)),
+        Ok(None) => {},
+        Err(err) => error!("failed to reload feature flags: {}", err),
+      }
+      drop(inner);
+      if messages.next().await.is_none() {
+        break;
+      }
+    }
+    tokio::time::sleep(RESUBSCRIBE_INTERVAL).await;
+  }
+}
+
+fn flag_of(definition: &FeatureFlagDefinition, snapshot: &FlagSnapshot) -> FeatureFlag {
+  let stored = snapshot.flags.get(definition.name);
+  FeatureFlag {
+    name: definition.name.to_string(),
+    description: definition.description.to_string(),
+    default_value: definition.default_value.clone(),
+    value: stored
+      .map(|stored| stored.value.clone())
+      .unwrap_or_else(|| definition.default_value.clone()),
+    updated_at: stored.map(|stored| stored.updated_at),
+  }
+}
+
+/// The bucket of a workspace only depends on the flag and the workspace, so that a percentage
+/// rollout enables the same workspaces on every server, and different flags don't enable the same
+/// workspaces first.
+fn rollout_bucket(name: &str, workspace_id: &str) -> u8 {
+  let digest = md5::compute(format!("{}:{}", name, workspace_id.to_lowercase()));
+  let mut bytes = [0u8; 8];
+  bytes.copy_from_slice(&digest.0[..8]);
+  (u64::from_be_bytes(bytes) % 100) as u8
+}
+
+fn evaluate(value: &FeatureFlagValue, name: &str, workspace_id: &str) -> bool {
+  match value {
+    FeatureFlagValue::Bool { enabled } => *enabled,
+    FeatureFlagValue::Percentage { percentage } => rollout_bucket(name, workspace_id) < *percentage,
+    FeatureFlagValue::Allowlist { workspace_ids } => workspace_ids
+      .iter()
+      .any(|id| id.eq_ignore_ascii_case(workspace_id)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use uuid::Uuid;
+
+  #[test]
+  fn percentage_rollout_is_deterministic_test() {
+    let workspace_ids = (0..1000)
+      .map(|_| Uuid::new_v4().to_string())
+      .collect::<Vec<_>>();
+    let enabled = |percentage: u8| {
+      workspace_ids
+        .iter()
+        .filter(|id| {
+          evaluate(
+            &FeatureFlagValue::Percentage { percentage },
+            SHARED_CHAT_SEARCH,
+            id,
+          )
+        })
+        .cloned()
+        .collect::<Vec<_>>()
+    };
+
+    assert!(enabled(0).is_empty());
+    assert_eq!(enabled(100).len(), workspace_ids.len());
+    let ten = enabled(10);
+    assert_eq!(ten, enabled(10));
+    assert!(ten.len() > 50 && ten.len() < 150, "{}", ten.len());
+    // raising the percentage only adds workspaces
+    let twenty = enabled(20);
+    assert!(ten.iter().all(|id| twenty.contains(id)));
+    // the workspace id casing doesn't matter
+    assert!(ten.iter().all(|id| evaluate(
+      &FeatureFlagValue::Percentage { percentage: 10 },
+      SHARED_CHAT_SEARCH,
+      &id.to_uppercase()
+    )));
+  }
+
+  #[test]
+  fn allowlist_and_bool_flags_test() {
+    let workspace_id = Uuid::new_v4().to_string();
+    let allowlist = FeatureFlagValue::Allowlist {
+      workspace_ids: vec![workspace_id.to_uppercase()],
+    };
+    assert!(evaluate(&allowlist, FOLDER_COMPACTION, &workspace_id));
+    assert!(!evaluate(
+      &allowlist,
+      FOLDER_COMPACTION,
+      &Uuid::new_v4().to_string()
+    ));
+    assert!(evaluate(
+      &FeatureFlagValue::Bool { enabled: true },
+      FOLDER_COMPACTION,
+      &workspace_id
+    ));
+    assert!(!FeatureFlagValue::Bool { enabled: true }
+      .is_same_kind(&FeatureFlagValue::Percentage { percentage: 10 }));
+  }
+}
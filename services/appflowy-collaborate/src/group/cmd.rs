@@ -15,7 +15,8 @@ use collab_entity::CollabType;
 use collab_rt_entity::user::RealtimeUser;
 use collab_rt_entity::CollabAck;
 use collab_rt_entity::{
-  AckCode, ClientCollabMessage, MessageByObjectId, ServerCollabMessage, SinkMessage, UpdateSync,
+  AckCode, ClientCollabMessage, CollabDeleted, MessageByObjectId, RealtimeMessage,
+  ServerCollabMessage, SinkMessage, SystemMessage, UpdateSync,
 };
 use collab_rt_protocol::{Message, SyncMessage};
 use database::collab::CollabStorage;
@@ -211,9 +212,23 @@ where
     }
 
     let is_group_exist = self.group_manager.contains_group(&object_id);
+    let is_user_subscribed = is_group_exist && self.group_manager.contains_user(&object_id, user);
+    // a deleted collab isn't synced until it's restored from the trash
+    if !is_user_subscribed && self.group_manager.is_collab_deleted(&object_id).await? {
+      if let Some(entry) = self.msg_router_by_user.get(user) {
+        trace!("refuse to sync deleted collab:{} with {}", object_id, user);
+        entry
+          .value()
+          .send_message(RealtimeMessage::System(SystemMessage::CollabDeleted(
+            CollabDeleted { object_id },
+          )))
+          .await;
+      }
+      return Ok(());
+    }
+
     if is_group_exist {
       // subscribe the user to the group. then the user will receive the changes from the group
-      let is_user_subscribed = self.group_manager.contains_user(&object_id, user);
       if !is_user_subscribed {
         // safety: messages is not empty because we have checked it before
         let first_message = messages.first().unwrap();
@@ -0,0 +1,342 @@
+use anyhow::anyhow;
+use collab::core::origin::CollabOrigin;
+use collab::preclude::Collab;
+use yrs::types::text::YChange;
+use yrs::types::ToJson;
+use yrs::{
+  Any, Array, ArrayPrelim, ArrayRef, Map, MapPrelim, MapRef, Out, ReadTxn, StateVector, Text,
+  TextPrelim, TextRef, TransactionMut,
+};
+
+/// Key of the collab meta map holding the client id used to build the compacted copy. Every
+/// client that synced with the compacted copy has this client id in its state vector.
+pub const COMPACTION_CLIENT_ID_KEY: &str = "compaction_client_id";
+
+/// Rebuilds the collab from its logical content into a fresh document.
+///
+/// Deleted items are kept by the original document forever (the server and most clients run with
+/// GC turned off), which makes collabs with a lot of churn, like the folder, grow without bound.
+/// The compacted copy only contains the live content, so it has no history at all: clients that
+/// synced with the original document can not merge their state into the new one and must reload
+/// it from scratch.
+///
+/// Fails when the collab holds a value which can't be copied, rather than leaving it out.
+pub fn compact_collab(collab: &Collab) -> Result<Collab, anyhow::Error> {
+  let object_id = collab.object_id().to_string();
+  let mut compacted = Collab::new_with_origin(CollabOrigin::Server, object_id, vec![], false);
+  {
+    let src_txn = collab.transact();
+    let mut dst_txn = compacted.context.transact_mut();
+    copy_map(&src_txn, &collab.data, &mut dst_txn, &compacted.data)?;
+    copy_map(&src_txn, &collab.meta, &mut dst_txn, &compacted.meta)?;
+  }
+
+  // All content of the compacted copy was written by a single client.
+  let client_id = compacted
+    .transact()
+    .state_vector()
+    .iter()
+    .map(|(client_id, _)| *client_id)
+    .next();
+  if let Some(client_id) = client_id {
+    let mut txn = compacted.context.transact_mut();
+    compacted.meta.insert(
+      &mut txn,
+      COMPACTION_CLIENT_ID_KEY,
+      Any::BigInt(client_id as i64),
+    );
+  }
+  Ok(compacted)
+}
+
+/// Returns the id of the client that built the compacted copy, or `None` if the collab was never
+/// compacted.
+pub fn compaction_client_id(collab: &Collab) -> Option<u64> {
+  let txn = collab.transact();
+  match collab.meta.get(&txn, COMPACTION_CLIENT_ID_KEY)? {
+    Out::Any(Any::BigInt(client_id)) => Some(client_id as u64),
+    Out::Any(Any::Number(client_id)) => Some(client_id as u64),
+    _ => None,
+  }
+}
+
+/// A client is stale when it has some state but never saw the compacted copy, which means its
+/// state was built on top of the history removed by the compaction.
+pub fn is_stale_generation(compaction_client_id: u64, remote_sv: &StateVector) -> bool {
+  !remote_sv.is_empty() && remote_sv.get(&compaction_client_id) == 0
+}
+
+pub(crate) fn copy_map<T: ReadTxn>(
+  src_txn: &T,
+  src: &MapRef,
+  dst_txn: &mut TransactionMut,
+  dst: &MapRef,
+) -> Result<(), anyhow::Error> {
+  for (key, value) in src.iter(src_txn) {
+    match value {
+      Out::Any(any) => {
+        dst.insert(dst_txn, key, any);
+      },
+      Out::YMap(map) => {
+        let child = dst.insert(dst_txn, key, MapPrelim::default());
+        copy_map(src_txn, &map, dst_txn, &child)?;
+      },
+      Out::YArray(array) => {
+        let child = dst.insert(dst_txn, key, ArrayPrelim::default());
+        copy_array(src_txn, &array, dst_txn, &child)?;
+      },
+      Out::YText(text) => {
+        let child = dst.insert(dst_txn, key, TextPrelim::new(""));
+        copy_text(src_txn, &text, dst_txn, &child)?;
+      },
+      other => return Err(unsupported_value(src_txn, other)),
+    }
+  }
+  Ok(())
+}
+
+pub(crate) fn copy_array<T: ReadTxn>(
+  src_txn: &T,
+  src: &ArrayRef,
+  dst_txn: &mut TransactionMut,
+  dst: &ArrayRef,
+) -> Result<(), anyhow::Error> {
+  for value in src.iter(src_txn) {
+    match value {
+      Out::Any(any) => {
+        dst.push_back(dst_txn, any);
+      },
+      Out::YMap(map) => {
+        let child = dst.push_back(dst_txn, MapPrelim::default());
+        copy_map(src_txn, &map, dst_txn, &child)?;
+      },
+      Out::YArray(array) => {
+        let child = dst.push_back(dst_txn, ArrayPrelim::default());
+        copy_array(src_txn, &array, dst_txn, &child)?;
+      },
+      Out::YText(text) => {
+        let child = dst.push_back(dst_txn, TextPrelim::new(""));
+        copy_text(src_txn, &text, dst_txn, &child)?;
+      },
+      other => return Err(unsupported_value(src_txn, other)),
+    }
+  }
+  Ok(())
+}
+
+/// Copies the delta of the text, so that its formatting attributes and embeds, like the
+/// mentions of a document, are kept.
+pub(crate) fn copy_text<T: ReadTxn>(
+  src_txn: &T,
+  src: &TextRef,
+  dst_txn: &mut TransactionMut,
+  dst: &TextRef,
+) -> Result<(), anyhow::Error> {
+  for diff in src.diff(src_txn, YChange::identity) {
+    let attributes = diff.attributes.map(|attrs| *attrs).unwrap_or_default();
+    let index = dst.len(dst_txn);
+    match diff.insert {
+      Out::Any(Any::String(chunk)) => {
+        dst.insert_with_attributes(dst_txn, index, &chunk, attributes);
+      },
+      Out::Any(embed) => {
+        dst.insert_embed_with_attributes(dst_txn, index, embed, attributes);
+      },
+      other => return Err(unsupported_value(src_txn, other)),
+    }
+  }
+  Ok(())
+}
+
+/// Dropping a value would lose the content of the collab, so the copy is refused instead.
+fn unsupported_value<T: ReadTxn>(txn: &T, value: Out) -> anyhow::Error {
+  anyhow!("unsupported value in collab: {}", value.to_json(txn))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use collab::core::collab::DataSource;
+  use collab_folder::hierarchy_builder::NestedChildViewBuilder;
+  use collab_folder::{Folder, FolderData, Workspace};
+  use std::collections::HashMap;
+  use std::sync::Arc;
+  use yrs::types::Attrs;
+  use yrs::updates::decoder::Decode;
+  use yrs::{GetString, Update};
+
+  const DELETED_VIEWS: usize = 10_000;
+
+  fn encoded_len(collab: &Collab) -> usize {
+    collab
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default())
+      .len()
+  }
+
+  fn bloated_folder() -> Folder {
+    let workspace_id = uuid::Uuid::new_v4().to_string();
+    let workspace = Workspace::new(workspace_id.clone(), "workspace".to_string(), 1);
+    // clients keep the deleted items around, the same as the server does
+    let collab = Collab::new_with_origin(CollabOrigin::Empty, &workspace_id, vec![], true);
+    let mut folder = Folder::create(1, collab, None, FolderData::new(workspace));
+
+    let name = "x".repeat(1024);
+    for _ in 0..DELETED_VIEWS {
+      let view = NestedChildViewBuilder::new(1, workspace_id.clone())
+        .with_name(&name)
+        .build()
+        .view;
+      let view_id = view.id.clone();
+      let mut txn = folder.collab.transact_mut();
+      folder.body.views.insert(&mut txn, view, None);
+      folder.body.views.delete_views(&mut txn, vec![view_id]);
+    }
+    folder
+  }
+
+  #[test]
+  fn compact_folder_with_deleted_views_test() {
+    let folder = bloated_folder();
+    let compacted = compact_collab(&folder.collab).unwrap();
+
+    let original_len = encoded_len(&folder.collab);
+    let compacted_len = encoded_len(&compacted);
+    assert!(
+      compacted_len * 10 <= original_len,
+      "compacted folder is {} bytes, original is {} bytes",
+      compacted_len,
+      original_len
+    );
+
+    let expected = folder.collab.data.to_json(&folder.collab.transact());
+    let actual = compacted.data.to_json(&compacted.transact());
+    assert_eq!(actual, expected);
+
+    let client_id = compaction_client_id(&compacted).unwrap();
+    assert!(compaction_client_id(&folder.collab).is_none());
+    assert!(is_stale_generation(
+      client_id,
+      &folder.collab.transact().state_vector()
+    ));
+    assert!(!is_stale_generation(
+      client_id,
+      &compacted.transact().state_vector()
+    ));
+    assert!(!is_stale_generation(client_id, &StateVector::default()));
+  }
+
+  #[test]
+  fn clients_converge_after_compaction_test() {
+    let mut original = Collab::new_with_origin(CollabOrigin::Empty, "object", vec![], true);
+    {
+      let mut txn = original.context.transact_mut();
+      let text = original.data.get_or_init_text(&mut txn, "text");
+      text.insert(&mut txn, 0, "hello world");
+      text.remove_range(&mut txn, 0, 6);
+      original.data.insert(&mut txn, "count", Any::BigInt(1));
+    }
+    let compacted = compact_collab(&original).unwrap();
+    let doc_state = compacted
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+
+    // both clients reload the compacted copy after the reset
+    let open = || {
+      Collab::new_with_source(
+        CollabOrigin::Empty,
+        "object",
+        DataSource::DocStateV1(doc_state.clone()),
+        vec![],
+        true,
+      )
+      .unwrap()
+    };
+    let mut client_a = open();
+    let mut client_b = open();
+    {
+      let mut txn = client_a.context.transact_mut();
+      let text = client_a.data.get_or_init_text(&mut txn, "text");
+      text.insert(&mut txn, 0, "a ");
+    }
+    {
+      let mut txn = client_b.context.transact_mut();
+      let text = client_b.data.get_or_init_text(&mut txn, "text");
+      text.push(&mut txn, " b");
+    }
+
+    let update_a = client_a
+      .transact()
+      .encode_state_as_update_v1(&client_b.transact().state_vector());
+    let update_b = client_b
+      .transact()
+      .encode_state_as_update_v1(&client_a.transact().state_vector());
+    client_a
+      .transact_mut()
+      .apply_update(Update::decode_v1(&update_b).unwrap())
+      .unwrap();
+    client_b
+      .transact_mut()
+      .apply_update(Update::decode_v1(&update_a).unwrap())
+      .unwrap();
+
+    assert_eq!(client_a.to_json_value(), client_b.to_json_value());
+    let txn = client_a.transact();
+    let text: yrs::TextRef = client_a.data.get_with_txn(&txn, "text").unwrap();
+    assert_eq!(text.get_string(&txn), "a world b");
+  }
+
+  fn block_delta(collab: &Collab) -> Vec<(Any, Option<Box<Attrs>>)> {
+    let txn = collab.transact();
+    let Some(Out::YMap(blocks)) = collab.data.get(&txn, "blocks") else {
+      panic!("missing blocks");
+    };
+    let Some(Out::YText(text)) = blocks.get(&txn, "block") else {
+      panic!("missing block text");
+    };
+    text
+      .diff(&txn, YChange::identity)
+      .into_iter()
+      .map(|diff| (diff.insert.to_json(&txn), diff.attributes))
+      .collect()
+  }
+
+  #[test]
+  fn compaction_keeps_text_formatting_test() {
+    let mut original = Collab::new_with_origin(CollabOrigin::Empty, "document", vec![], true);
+    {
+      let mut txn = original.context.transact_mut();
+      let blocks = original
+        .data
+        .insert(&mut txn, "blocks", MapPrelim::default());
+      let text = blocks.insert(&mut txn, "block", TextPrelim::new(""));
+      text.insert(&mut txn, 0, "see  for details");
+      let bold = Attrs::from([(Arc::from("bold"), Any::Bool(true))]);
+      text.format(&mut txn, 0, 3, bold);
+      // a page mention is a formatted `$`, as written by the document editor
+      let mention = Any::from(HashMap::from([
+        ("type".to_string(), Any::from("page")),
+        ("page_id".to_string(), Any::from("view")),
+      ]));
+      let mention = Attrs::from([(Arc::from("mention"), mention)]);
+      text.insert_with_attributes(&mut txn, 4, "$", mention);
+      text.insert_embed_with_attributes(&mut txn, 5, Any::from("image"), Attrs::new());
+      text.remove_range(&mut txn, 6, 4);
+    }
+    let compacted = compact_collab(&original).unwrap();
+
+    let expected = block_delta(&original);
+    assert_eq!(expected.len(), 5);
+    assert_eq!(block_delta(&compacted), expected);
+  }
+
+  #[test]
+  fn compaction_refuses_unsupported_value_test() {
+    let mut original = Collab::new_with_origin(CollabOrigin::Empty, "document", vec![], true);
+    {
+      let mut txn = original.context.transact_mut();
+      original.data.insert(&mut txn, "subdoc", yrs::Doc::new());
+    }
+    assert!(compact_collab(&original).is_err());
+  }
+}
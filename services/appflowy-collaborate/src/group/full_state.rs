@@ -0,0 +1,106 @@
+use collab_rt_entity::user::RealtimeUser;
+use collab_rt_protocol::FullStateThreshold;
+use semver::Version;
+
+use crate::config::get_env_var;
+
+/// When a client catching up with a collab gets its full doc state rather than the updates it's
+/// missing, see [collab_rt_protocol::SyncMessage::FullState].
+///
+/// Disabled by default: the released clients can't decode the full state message yet. Once they
+/// do, it's enabled with the first version decoding it as `min_client_version`.
+#[derive(Debug, Clone)]
+pub struct FullStateSyncConfig {
+  pub enabled: bool,
+  pub threshold: FullStateThreshold,
+  /// Older clients can't decode the full state message, they always get the missing updates.
+  pub min_client_version: Version,
+}
+
+impl Default for FullStateSyncConfig {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      threshold: FullStateThreshold::default(),
+      min_client_version: Version::new(0, 9, 0),
+    }
+  }
+}
+
+impl FullStateSyncConfig {
+  pub fn from_env() -> Result<Self, anyhow::Error> {
+    let defaults = Self::default();
+    let config = Self {
+      enabled: get_env_var(
+        "APPFLOWY_COLLAB_FULL_STATE_SYNC_ENABLED",
+        &defaults.enabled.to_string(),
+      )
+      .parse()?,
+      threshold: FullStateThreshold {
+        min_delta_len: get_env_var(
+          "APPFLOWY_COLLAB_FULL_STATE_SYNC_MIN_DELTA_SIZE",
+          &defaults.threshold.min_delta_len.to_string(),
+        )
+        .parse()?,
+        ratio: get_env_var(
+          "APPFLOWY_COLLAB_FULL_STATE_SYNC_RATIO",
+          &defaults.threshold.ratio.to_string(),
+        )
+        .parse()?,
+      },
+      min_client_version: get_env_var(
+        "APPFLOWY_COLLAB_FULL_STATE_SYNC_MIN_CLIENT_VERSION",
+        &defaults.min_client_version.to_string(),
+      )
+      .parse()?,
+    };
+    if config.threshold.ratio.is_nan() || config.threshold.ratio <= 0.0 {
+      anyhow::bail!("the full state sync ratio must be positive");
+    }
+    Ok(config)
+  }
+
+  /// The threshold to reply to the sync step 1 of `user` with, or `None` when its client doesn't
+  /// support full states.
+  pub fn threshold_for(&self, user: &RealtimeUser) -> Option<FullStateThreshold> {
+    if !self.enabled {
+      return None;
+    }
+    Version::parse(&user.app_version)
+      .ok()
+      .filter(|version| *version >= self.min_client_version)
+      .map(|_| self.threshold)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn user(app_version: &str) -> RealtimeUser {
+    RealtimeUser::new(
+      1,
+      "device".to_string(),
+      "session".to_string(),
+      0,
+      app_version.to_string(),
+    )
+  }
+
+  #[test]
+  fn full_state_is_only_sent_to_clients_decoding_it_test() {
+    let config = FullStateSyncConfig::default();
+    assert_eq!(config.threshold_for(&user("0.9.0")), None);
+
+    let config = FullStateSyncConfig {
+      enabled: true,
+      ..Default::default()
+    };
+    assert_eq!(config.threshold_for(&user("0.8.9")), None);
+    assert_eq!(config.threshold_for(&user("not a version")), None);
+    assert_eq!(
+      config.threshold_for(&user("0.9.0")),
+      Some(FullStateThreshold::default())
+    );
+  }
+}
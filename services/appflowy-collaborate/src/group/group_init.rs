@@ -1,40 +1,57 @@
 use crate::error::RealtimeError;
+use crate::group::compaction;
+use crate::group::full_state::FullStateSyncConfig;
+use crate::group::write_behind::{FlushTrigger, WriteBehindConfig, WriteBehindQueue};
 use anyhow::anyhow;
 use app_error::AppError;
-use arc_swap::ArcSwap;
+use arc_swap::{ArcSwap, ArcSwapOption};
 use collab::core::collab::DataSource;
 use collab::core::origin::CollabOrigin;
-use collab::entity::EncodedCollab;
+use collab::entity::{EncodedCollab, EncoderVersion};
 use collab::lock::RwLock;
 use collab::preclude::Collab;
 use collab_entity::CollabType;
 use collab_rt_entity::user::RealtimeUser;
 use collab_rt_entity::{
-  AckCode, AwarenessSync, BroadcastSync, CollabAck, MessageByObjectId, MsgId,
+  AckCode, AwarenessSync, BroadcastSync, CollabAck, MessageByObjectId, MsgId, ReplaySync,
+  SequencedBroadcast, SubscriptionFilter,
 };
 use collab_rt_entity::{ClientCollabMessage, CollabMessage};
-use collab_rt_protocol::{Message, MessageReader, RTProtocolError, SyncMessage};
+use collab_rt_protocol::{
+  append_opaque_frames, decode_opaque_frames, encode_missing_updates, CollabSizeExceeded,
+  CustomMessage, EditingLockMeta, FullStateThreshold, Message, MessageReader, RTProtocolError,
+  SyncMessage,
+};
 use collab_stream::client::CollabRedisStream;
 use collab_stream::collab_update_sink::{AwarenessUpdateSink, CollabUpdateSink};
-
+use collab_stream::edit_counter::{EditCount, EditCounterStore};
+use collab_stream::editing_lock::{EditingLock, EditingLockStore};
+use collab_stream::replay_buffer::{Replay, ReplayBuffer};
+
+use crate::bandwidth::{BandwidthCounter, EventClass};
+use crate::collab::recovery::{CollabRecovery, RecoveredCollab};
+use crate::collab::validator::{check_collab_structure, CollabSizeLimits};
+use crate::group::unload::GroupUsage;
+use crate::load_shed::{LoadShedder, ShedTier};
 use crate::metrics::CollabRealtimeMetrics;
+use crate::snapshot::{collab_type_name, EditVolume, SnapshotPolicy};
 use bytes::Bytes;
-use collab_document::document::DocumentBody;
 use collab_stream::error::StreamError;
 use collab_stream::model::{AwarenessStreamUpdate, CollabStreamUpdate, MessageId, UpdateFlags};
 use dashmap::DashMap;
 use database::collab::{CollabStorage, GetCollabOrigin};
-use database_entity::dto::{CollabParams, QueryCollabParams};
+use database_entity::dto::{CollabMode, CollabParams, InsertSnapshotParams, QueryCollabParams};
 use futures::{pin_mut, Sink, Stream};
 use futures_util::{SinkExt, StreamExt};
 use indexer::scheduler::{IndexerScheduler, UnindexedCollabTask, UnindexedData};
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime};
 use tokio::time::MissedTickBehavior;
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info, trace, warn};
 use uuid::Uuid;
+use workspace_template::collab_registry::{collab_spec, collab_type_spec};
 use yrs::updates::decoder::{Decode, DecoderV1};
 use yrs::updates::encoder::{Encode, Encoder, EncoderV1};
 use yrs::{ReadTxn, StateVector, Update};
@@ -50,6 +67,8 @@ struct CollabGroupState {
   workspace_id: String,
   object_id: String,
   collab_type: CollabType,
+  /// The updates of an opaque collab are stored and relayed without being decoded.
+  mode: CollabMode,
   /// A list of subscribers to this group. Each subscriber will receive updates from the
   /// broadcast.
   subscribers: DashMap<RealtimeUser, Subscription>,
@@ -58,10 +77,24 @@ struct CollabGroupState {
   /// Cancellation token triggered when current collab group is about to be stopped.
   /// This will also shut down all subsequent [Subscription]s.
   shutdown: CancellationToken,
+  /// Cancelled once the pending updates were saved after the shutdown, see [CollabGroup::stop].
+  stopped: CancellationToken,
   last_activity: ArcSwap<Instant>,
   seq_no: AtomicU32,
   /// The most recent state vector from a redis update.
   state_vector: RwLock<StateVector>,
+  editing_locks: EditingLockStore,
+  /// The last updates broadcast to the subscribers, replayed to the ones which reconnect.
+  replay_buffer: ReplayBuffer,
+  /// Cached editing lock of the collab. Updates from users other than the holder are rejected
+  /// while it's set.
+  editing_lock: ArcSwapOption<EditingLock>,
+  /// Bytes exchanged with the subscribers, shared by the groups of the workspace.
+  bandwidth: Arc<BandwidthCounter>,
+  recovery: CollabRecovery,
+  load_shedder: Arc<LoadShedder>,
+  size_limits: CollabSizeLimits,
+  full_state_sync: FullStateSyncConfig,
 }
 
 impl Drop for CollabGroup {
@@ -72,47 +105,82 @@ impl Drop for CollabGroup {
 }
 
 impl CollabGroup {
+  const EDITING_LOCK_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+  /// Estimated bytes held by a group besides its collab: its tasks, sinks and state vector.
+  const GROUP_OVERHEAD_BYTES: usize = 64 * 1024;
+  /// Estimated bytes held for each subscriber: its sink, stream and awareness state.
+  const SUBSCRIBER_OVERHEAD_BYTES: usize = 16 * 1024;
+
   #[allow(clippy::too_many_arguments)]
   pub fn new<S>(
     uid: i64,
     workspace_id: String,
     object_id: String,
     collab_type: CollabType,
+    mode: CollabMode,
     metrics: Arc<CollabRealtimeMetrics>,
     storage: Arc<S>,
     collab_redis_stream: Arc<CollabRedisStream>,
     persistence_interval: Duration,
     prune_grace_period: Duration,
+    folder_compaction_threshold: usize,
+    strict_validation: bool,
+    snapshot_policy: SnapshotPolicy,
     state_vector: StateVector,
     indexer_scheduler: Arc<IndexerScheduler>,
+    bandwidth: Arc<BandwidthCounter>,
+    recovery: CollabRecovery,
+    recovered: Option<RecoveredCollab>,
+    load_shedder: Arc<LoadShedder>,
+    size_limits: CollabSizeLimits,
+    full_state_sync: FullStateSyncConfig,
+    write_behind: WriteBehindConfig,
   ) -> Result<Self, StreamError>
   where
     S: CollabStorage,
   {
     let is_new_collab = state_vector.is_empty();
+    let editing_locks = collab_redis_stream.editing_locks();
+    let replay_buffer = collab_redis_stream.replay_buffer();
     let persister = CollabPersister::new(
       uid,
       workspace_id.clone(),
       object_id.clone(),
       collab_type.clone(),
+      mode,
       storage,
       collab_redis_stream,
       indexer_scheduler,
       metrics.clone(),
       prune_grace_period,
+      folder_compaction_threshold,
+      strict_validation,
+      snapshot_policy,
+      recovered,
+      write_behind,
     );
 
     let state = Arc::new(CollabGroupState {
       workspace_id,
       object_id,
       collab_type,
+      mode,
       subscribers: DashMap::new(),
       metrics,
       shutdown: CancellationToken::new(),
+      stopped: CancellationToken::new(),
       persister,
       last_activity: ArcSwap::new(Instant::now().into()),
       seq_no: AtomicU32::new(0),
       state_vector: state_vector.into(),
+      editing_locks,
+      replay_buffer,
+      editing_lock: ArcSwapOption::empty(),
+      bandwidth,
+      recovery,
+      load_shedder,
+      size_limits,
+      full_state_sync,
     });
 
     /*
@@ -144,6 +212,11 @@ impl CollabGroup {
       });
     }
 
+    // setup task used to release expired editing locks
+    {
+      tokio::spawn(Self::editing_lock_task(state.clone()));
+    }
+
     // setup periodic snapshot
     {
       tokio::spawn(Self::snapshot_task(
@@ -162,7 +235,6 @@ impl CollabGroup {
   }
 
   #[inline]
-  #[allow(dead_code)]
   pub fn object_id(&self) -> &str {
     &self.state.object_id
   }
@@ -171,8 +243,20 @@ impl CollabGroup {
     self.state.shutdown.is_cancelled()
   }
 
+  /// Stops the group and waits for the updates still pending in Redis to be saved, for at most
+  /// `timeout`. The updates which couldn't be saved stay in Redis and are saved by the next group
+  /// of the collab. Returns whether the final save completed.
+  pub async fn stop(&self, timeout: Duration) -> bool {
+    self.state.shutdown.cancel();
+    tokio::time::timeout(timeout, self.state.stopped.cancelled())
+      .await
+      .is_ok()
+  }
+
   /// Task used to receive collab updates from Redis.
   async fn inbound_task(state: Arc<CollabGroupState>) -> Result<(), RealtimeError> {
+    // the stream is read from its start: it only holds the updates which aren't persisted yet, so
+    // the group catches up with those written by the other servers before it was created
     let updates = state.persister.collab_redis_stream.live_collab_updates(
       &state.workspace_id,
       &state.object_id,
@@ -192,7 +276,7 @@ impl CollabGroup {
           match res {
             Some(Ok((message_id, update))) => {
               state.metrics.observe_collab_stream_latency(message_id.timestamp_ms);
-              Self::handle_inbound_update(&state, update).await;
+              Self::handle_inbound_update(&state, message_id, update).await;
             },
             Some(Err(err)) => {
               tracing::warn!("failed to handle incoming update for collab `{}`: {}", state.object_id, err);
@@ -208,25 +292,53 @@ impl CollabGroup {
     Ok(())
   }
 
-  async fn handle_inbound_update(state: &CollabGroupState, update: CollabStreamUpdate) {
+  async fn handle_inbound_update(
+    state: &CollabGroupState,
+    message_id: MessageId,
+    update: CollabStreamUpdate,
+  ) {
+    if update.flags.is_reset() {
+      Self::handle_reset(state).await;
+      return;
+    }
+
+    if update.flags.is_lock_changed() {
+      Self::reload_editing_lock(state, true).await;
+      return;
+    }
+
     // update state vector based on incoming message
-    match Update::decode_v1(&update.data) {
-      Ok(update) => state
-        .state_vector
-        .write()
-        .await
-        .merge(update.state_vector()),
-      Err(err) => {
-        tracing::error!(
-          "received malformed update for collab `{}`: {}",
-          state.object_id,
-          err
-        );
-        return;
-      },
+    if !state.mode.is_opaque() {
+      match Update::decode_v1(&update.data) {
+        Ok(update) => state
+          .state_vector
+          .write()
+          .await
+          .merge(update.state_vector()),
+        Err(err) => {
+          tracing::error!(
+            "received malformed update for collab `{}`: {}",
+            state.object_id,
+            err
+          );
+          return;
+        },
+      }
     }
+    state
+      .persister
+      .write_behind
+      .push(message_id, update.data.clone());
 
     let seq_num = state.seq_no.fetch_add(1, Ordering::SeqCst) + 1;
+    // the update is only encoded when a subscriber other than its sender receives it
+    let has_receivers = state
+      .subscribers
+      .iter()
+      .any(|e| e.value().accepts(EventClass::Update) && e.value().collab_origin != update.sender);
+    if !has_receivers {
+      return;
+    }
     tracing::trace!(
       "broadcasting collab update from {} ({} bytes) - seq_num: {}",
       update.sender,
@@ -234,26 +346,229 @@ impl CollabGroup {
       seq_num
     );
     let payload = Message::Sync(SyncMessage::Update(update.data)).encode_v1();
+    let payload_len = payload.len();
     let message = BroadcastSync::new(update.sender, state.object_id.clone(), payload, seq_num);
+    let sequenced = update.seq.map(|stream_seq| SequencedBroadcast {
+      broadcast: message.clone(),
+      stream_seq,
+    });
     for mut e in state.subscribers.iter_mut() {
       let subscription = e.value_mut();
-      if message.origin == subscription.collab_origin {
-        continue; // don't send update to its sender
+      // don't send update to its sender, nor to the subscribers which didn't select updates
+      if message.origin == subscription.collab_origin || !subscription.accepts(EventClass::Update) {
+        continue;
       }
 
-      if let Err(err) = subscription.sink.send(message.clone().into()).await {
-        tracing::debug!(
+      let msg: CollabMessage = match &sequenced {
+        Some(sequenced) if subscription.sequenced.load(Ordering::Acquire) => {
+          sequenced.clone().into()
+        },
+        _ => message.clone().into(),
+      };
+      match subscription.sink.send(msg).await {
+        Ok(()) => state
+          .bandwidth
+          .record_event_out(EventClass::Update, payload_len),
+        Err(err) => tracing::debug!(
           "failed to send collab `{}` update to `{}`: {}",
           state.object_id,
           subscription.collab_origin,
           err
-        );
+        ),
       }
 
       state.last_activity.store(Arc::new(Instant::now()));
     }
   }
 
+  /// The collab was replaced by a compacted copy. Connected clients hold the old history, so they
+  /// are told to load the collab again and the group is stopped. The next client to connect will
+  /// create a new group on top of the compacted copy.
+  async fn handle_reset(state: &CollabGroupState) {
+    info!(
+      "collab {}/{} was reset, notifying {} subscribers",
+      state.workspace_id,
+      state.object_id,
+      state.subscribers.len()
+    );
+    let seq_num = state.seq_no.load(Ordering::SeqCst);
+    let ack = CollabAck::new(CollabOrigin::Server, state.object_id.clone(), 0, seq_num)
+      .with_code(AckCode::Reset);
+    for mut e in state.subscribers.iter_mut() {
+      let subscription = e.value_mut();
+      if !subscription.accepts(EventClass::Control) {
+        continue;
+      }
+      if let Err(err) = subscription.sink.send(ack.clone().into()).await {
+        tracing::debug!(
+          "failed to send collab `{}` reset to `{}`: {}",
+          state.object_id,
+          subscription.collab_origin,
+          err
+        );
+      }
+    }
+    // the queued updates belong to the old history
+    state.persister.write_behind.clear();
+    state.shutdown.cancel();
+  }
+
+  /// Loads the editing lock of the collab, then periodically checks whether it expired. Expired
+  /// locks are reloaded, since the holder might have renewed them, and subscribers are notified
+  /// when they are gone.
+  async fn editing_lock_task(state: Arc<CollabGroupState>) {
+    Self::reload_editing_lock(&state, false).await;
+
+    let mut interval = tokio::time::interval(Self::EDITING_LOCK_CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+      tokio::select! {
+        _ = state.shutdown.cancelled() => break,
+        _ = interval.tick() => {
+          let is_expired = state
+            .editing_lock
+            .load_full()
+            .map(|lock| lock.is_expired())
+            .unwrap_or(false);
+          if is_expired {
+            Self::reload_editing_lock(&state, false).await;
+          }
+        }
+      }
+    }
+  }
+
+  /// Reloads the editing lock from Redis. Subscribers are notified when the holder changed, or
+  /// when `notify` is set.
+  async fn reload_editing_lock(state: &CollabGroupState, notify: bool) {
+    let lock = match state
+      .editing_locks
+      .get(&state.workspace_id, &state.object_id)
+      .await
+    {
+      Ok(lock) => lock.filter(|lock| !lock.is_expired()),
+      Err(err) => {
+        warn!(
+          "failed to load editing lock of collab {}: {}",
+          state.object_id, err
+        );
+        return;
+      },
+    };
+    let previous = state.editing_lock.swap(lock.clone().map(Arc::new));
+    let holder_changed = previous.map(|lock| lock.uid) != lock.as_ref().map(|lock| lock.uid);
+    if notify || holder_changed {
+      Self::broadcast_editing_lock(state, lock.as_ref()).await;
+    }
+  }
+
+  async fn broadcast_editing_lock(state: &CollabGroupState, lock: Option<&EditingLock>) {
+    if !state
+      .subscribers
+      .iter()
+      .any(|e| e.value().accepts(EventClass::Control))
+    {
+      return;
+    }
+    trace!(
+      "broadcasting editing lock of collab {}: {:?}",
+      state.object_id,
+      lock.map(|lock| lock.uid)
+    );
+    let payload =
+      Message::Custom(CustomMessage::EditingLock(lock.map(editing_lock_meta))).encode_v1();
+    let payload_len = payload.len();
+    // the lock is not a collab update, so the sequence number is left as is
+    let seq_num = state.seq_no.load(Ordering::SeqCst);
+    let message = BroadcastSync::new(
+      CollabOrigin::Server,
+      state.object_id.clone(),
+      payload,
+      seq_num,
+    );
+    for mut e in state.subscribers.iter_mut() {
+      let subscription = e.value_mut();
+      if !subscription.accepts(EventClass::Control) {
+        continue;
+      }
+      match subscription.sink.send(message.clone().into()).await {
+        Ok(()) => state
+          .bandwidth
+          .record_event_out(EventClass::Control, payload_len),
+        Err(err) => tracing::debug!(
+          "failed to send collab `{}` editing lock to `{}`: {}",
+          state.object_id,
+          subscription.collab_origin,
+          err
+        ),
+      }
+    }
+  }
+
+  /// Rejects updates from users other than the holder of the editing lock. Updates sent on behalf
+  /// of the server are always accepted.
+  async fn check_editing_lock(
+    state: &CollabGroupState,
+    origin: &CollabOrigin,
+  ) -> Result<(), RTProtocolError> {
+    let uid = match origin.client_user_id() {
+      Some(uid) => uid,
+      None => return Ok(()),
+    };
+    let mut lock = state.editing_lock.load_full();
+    if lock.as_ref().map(|lock| lock.is_expired()).unwrap_or(false) {
+      // the holder might have renewed the lock
+      Self::reload_editing_lock(state, false).await;
+      lock = state.editing_lock.load_full();
+    }
+    match lock {
+      Some(lock) if lock.uid != uid => {
+        Err(RTProtocolError::EditingLocked(editing_lock_meta(&lock)))
+      },
+      _ => Ok(()),
+    }
+  }
+
+  /// Rejects updates from users while the collab is served from the snapshot it was recovered
+  /// from. Once an admin confirmed the repair, the collab is loaded from the storage again.
+  /// Rejects the message when its tier is shed, the client sends it again after the
+  /// [AckCode::Retry].
+  #[inline]
+  fn check_load(state: &CollabGroupState, tier: ShedTier) -> Result<(), RTProtocolError> {
+    if state.load_shedder.should_shed(tier) {
+      return Err(RTProtocolError::Overloaded);
+    }
+    Ok(())
+  }
+
+  async fn check_read_only(
+    state: &CollabGroupState,
+    origin: &CollabOrigin,
+  ) -> Result<(), RTProtocolError> {
+    if origin.client_user_id().is_none() {
+      return Ok(());
+    }
+    let recovered = match state.persister.recovered.load_full() {
+      Some(recovered) => recovered,
+      None => return Ok(()),
+    };
+    if recovered.should_check_repair(Instant::now()) {
+      match state.recovery.is_awaiting_repair(&state.object_id).await {
+        Ok(true) => {},
+        Ok(false) => {
+          info!("repair of collab {} was confirmed", state.object_id);
+          state.persister.recovered.store(None);
+          return Ok(());
+        },
+        Err(err) => warn!(
+          "failed to check repair of collab {}: {}",
+          state.object_id, err
+        ),
+      }
+    }
+    Err(RTProtocolError::ReadOnly)
+  }
+
   /// Task used to receive awareness updates from Redis.
   async fn inbound_awareness_task(state: Arc<CollabGroupState>) -> Result<(), RealtimeError> {
     let updates = state.persister.collab_redis_stream.awareness_updates(
@@ -293,24 +608,33 @@ impl CollabGroup {
       update.data.len()
     );
     let sender = update.sender;
-    let message = AwarenessSync::new(
-      state.object_id.clone(),
-      Message::Awareness(update.data).encode_v1(),
-      CollabOrigin::Empty,
-    );
+    let has_receivers = state
+      .subscribers
+      .iter()
+      .any(|e| e.value().accepts(EventClass::Awareness) && e.value().collab_origin != sender);
+    if !has_receivers {
+      return;
+    }
+    let payload = Message::Awareness(update.data).encode_v1();
+    let payload_len = payload.len();
+    let message = AwarenessSync::new(state.object_id.clone(), payload, CollabOrigin::Empty);
     for mut e in state.subscribers.iter_mut() {
       let subscription = e.value_mut();
-      if sender == subscription.collab_origin {
-        continue; // don't send update to its sender
+      // don't send update to its sender, nor to the subscribers which didn't select awareness
+      if sender == subscription.collab_origin || !subscription.accepts(EventClass::Awareness) {
+        continue;
       }
 
-      if let Err(err) = subscription.sink.send(message.clone().into()).await {
-        tracing::debug!(
+      match subscription.sink.send(message.clone().into()).await {
+        Ok(()) => state
+          .bandwidth
+          .record_event_out(EventClass::Awareness, payload_len),
+        Err(err) => tracing::debug!(
           "failed to send awareness `{}` update to `{}`: {}",
           state.object_id,
           subscription.collab_origin,
           err
-        );
+        ),
       }
 
       state.last_activity.store(Arc::new(Instant::now()));
@@ -336,14 +660,27 @@ impl CollabGroup {
     loop {
       tokio::select! {
         _ = snapshot_tick.tick() => {
-          if let Err(err) = state.persister.save().await {
+          if let Err(err) = state.persister.flush(FlushTrigger::Interval).await {
+            tracing::warn!("failed to persist collab `{}/{}`: {}", state.workspace_id, state.object_id, err);
+          }
+        },
+        trigger = state.persister.write_behind.flush_requested() => {
+          if let Err(err) = state.persister.flush(trigger).await {
             tracing::warn!("failed to persist collab `{}/{}`: {}", state.workspace_id, state.object_id, err);
           }
         },
         _ = state.shutdown.cancelled() => {
-          if let Err(err) = state.persister.save().await {
-            tracing::warn!("failed to persist collab on shutdown `{}/{}`: {}", state.workspace_id, state.object_id, err);
+          // saved from Redis rather than from the queue, so that the updates which weren't read
+          // from the stream yet are saved too
+          let start = Instant::now();
+          match state.persister.save().await {
+            Ok(()) => state
+              .metrics
+              .record_write_behind_flush(FlushTrigger::Shutdown.as_str(), start.elapsed()),
+            Err(err) => tracing::warn!("failed to persist collab on shutdown `{}/{}`: {}", state.workspace_id, state.object_id, err),
           }
+          state.persister.write_behind.clear();
+          state.stopped.cancel();
           break;
         }
       }
@@ -353,6 +690,12 @@ impl CollabGroup {
   /// Generate embedding for the current Collab immediately
   ///
   pub async fn generate_embeddings(&self) -> Result<(), AppError> {
+    if self.state.mode.is_opaque() {
+      return Err(AppError::EncryptedCollab(format!(
+        "can't index collab {}",
+        self.object_id()
+      )));
+    }
     let collab = self
       .encode_collab()
       .await
@@ -380,6 +723,12 @@ impl CollabGroup {
     &self,
     state_vector: StateVector,
   ) -> Result<Vec<u8>, RealtimeError> {
+    if self.state.mode.is_opaque() {
+      return Err(RealtimeError::Internal(anyhow!(
+        "can't compute the missing update of encrypted collab {}",
+        self.object_id()
+      )));
+    }
     {
       // first check if we need to send any updates
       let collab_sv = self.state.state_vector.read().await;
@@ -400,7 +749,13 @@ impl CollabGroup {
     Ok(update)
   }
 
+  /// The current state of the collab. The state of an opaque collab is the sequence of the
+  /// updates it received, see [collab_rt_protocol::encode_opaque_frames].
   pub async fn encode_collab(&self) -> Result<EncodedCollab, RealtimeError> {
+    if self.state.mode.is_opaque() {
+      let doc_state = self.state.persister.load_opaque().await?;
+      return Ok(EncodedCollab::new_v1(Default::default(), doc_state));
+    }
     let snapshot = self.state.persister.load_compact().await?;
     let encode_collab = snapshot.collab.encode_collab_v1(|collab| {
       self
@@ -423,7 +778,47 @@ impl CollabGroup {
         self.state.object_id,
         user
       );
+      self.release_editing_lock_of(user);
+      let write_behind = &self.state.persister.write_behind;
+      if self.state.subscribers.is_empty() && write_behind.config().flush_on_last_disconnect {
+        write_behind.request_flush(FlushTrigger::LastDisconnect);
+      }
+    }
+  }
+
+  /// Releases the editing lock when its holder disconnects from the device the lock was acquired
+  /// from.
+  fn release_editing_lock_of(&self, user: &RealtimeUser) {
+    let is_holder = self
+      .state
+      .editing_lock
+      .load_full()
+      .map(|lock| lock.uid == user.uid && lock.device_id == user.device_id)
+      .unwrap_or(false);
+    let is_still_connected = self
+      .state
+      .subscribers
+      .iter()
+      .any(|e| e.key().uid == user.uid && e.key().device_id == user.device_id);
+    if !is_holder || is_still_connected {
+      return;
     }
+
+    let state = self.state.clone();
+    let uid = user.uid;
+    let device_id = user.device_id.clone();
+    tokio::spawn(async move {
+      if let Err(err) = state
+        .editing_locks
+        .release(&state.workspace_id, &state.object_id, uid, Some(&device_id))
+        .await
+      {
+        warn!(
+          "failed to release editing lock of collab {}: {}",
+          state.object_id, err
+        );
+      }
+    });
   }
 
   pub fn user_count(&self) -> usize {
@@ -434,6 +829,23 @@ impl CollabGroup {
     *self.state.last_activity.load_full()
   }
 
+  /// Estimated bytes held in memory by the group. The collab itself is loaded for each sync and
+  /// save, so it's counted with the size of its state as last loaded or saved.
+  pub fn resident_bytes(&self) -> usize {
+    self.state.persister.doc_size.load(Ordering::Relaxed)
+      + self.state.persister.write_behind.bytes()
+      + Self::GROUP_OVERHEAD_BYTES
+      + self.state.subscribers.len() * Self::SUBSCRIBER_OVERHEAD_BYTES
+  }
+
+  pub fn usage(&self) -> GroupUsage {
+    GroupUsage {
+      last_activity: self.modified_at(),
+      subscriber_count: self.state.subscribers.len(),
+      resident_bytes: self.resident_bytes(),
+    }
+  }
+
   /// Subscribes a new connection to the broadcast group for collaborative activities.
   ///
   pub fn subscribe<Sink, Stream>(
@@ -448,15 +860,28 @@ impl CollabGroup {
   {
     // create new subscription for new subscriber
     let subscriber_shutdown = self.state.shutdown.child_token();
+    let filter = Arc::new(AtomicU8::new(SubscriptionFilter::default().to_bits()));
+    let sequenced = Arc::new(AtomicBool::new(false));
+    let full_state = self.state.full_state_sync.threshold_for(user);
 
     tokio::spawn(Self::receive_from_client_task(
       self.state.clone(),
       sink.clone(),
       stream,
+      (*user).clone(),
       subscriber_origin.clone(),
+      filter.clone(),
+      sequenced.clone(),
+      full_state,
     ));
 
-    let sub = Subscription::new(sink, subscriber_origin, subscriber_shutdown);
+    let sub = Subscription::new(
+      sink,
+      subscriber_origin,
+      subscriber_shutdown,
+      filter,
+      sequenced,
+    );
     if self
       .state
       .subscribers
@@ -487,7 +912,11 @@ impl CollabGroup {
     state: Arc<CollabGroupState>,
     mut sink: Sink,
     mut stream: Stream,
+    user: RealtimeUser,
     origin: CollabOrigin,
+    filter: Arc<AtomicU8>,
+    sequenced: Arc<AtomicBool>,
+    full_state: Option<FullStateThreshold>,
   ) where
     Sink: SubscriptionSink + 'static,
     Stream: SubscriptionStream + 'static,
@@ -499,8 +928,15 @@ impl CollabGroup {
         }
         msg = stream.next() => {
           match msg {
-            None => break,
-            Some(msg) => if let Err(err) =  Self::handle_messages(&state, &mut sink, msg).await {
+            None => {
+              // the stream ends when the access of the subscriber changed, so that its next
+              // message subscribes it again. A newer subscription of the user is kept.
+              state
+                .subscribers
+                .remove_if(&user, |_, sub| Arc::ptr_eq(&sub.filter, &filter));
+              break;
+            },
+            Some(msg) => if let Err(err) =  Self::handle_messages(&state, &mut sink, &filter, &sequenced, full_state.as_ref(), msg).await {
               tracing::warn!(
                 "collab `{}` failed to handle message from `{}`: {}",
                 state.object_id,
@@ -518,6 +954,9 @@ impl CollabGroup {
   async fn handle_messages<Sink>(
     state: &CollabGroupState,
     sink: &mut Sink,
+    filter: &AtomicU8,
+    sequenced: &AtomicBool,
+    full_state: Option<&FullStateThreshold>,
     msg: MessageByObjectId,
   ) -> Result<(), RealtimeError>
   where
@@ -532,11 +971,21 @@ impl CollabGroup {
         continue;
       }
       for message in messages {
-        match Self::handle_client_message(state, message).await {
+        let result = match message {
+          ClientCollabMessage::ClientReplaySync(replay) => {
+            Self::handle_replay(state, sink, sequenced, replay).await
+          },
+          message => Self::handle_client_message(state, filter, full_state, message).await,
+        };
+        match result {
           Ok(response) => {
             trace!("[realtime]: sending response: {}", response);
+            let payload_len = response.payload.len();
+            // the acks answer the messages of the subscriber, so they're sent whatever its filter
             match sink.send(response.into()).await {
-              Ok(()) => {},
+              Ok(()) => state
+                .bandwidth
+                .record_event_out(EventClass::Control, payload_len),
               Err(err) => {
                 trace!("[realtime]: send failed: {}", err);
                 break;
@@ -556,13 +1005,95 @@ impl CollabGroup {
     Ok(())
   }
 
+  /// Sends the subscriber the updates it missed since the one numbered `last_seen_seq`, then
+  /// sends it the next broadcasts with their number. The updates it sent itself are replayed
+  /// too, it didn't get the acks of some of them.
+  async fn handle_replay<Sink>(
+    state: &CollabGroupState,
+    sink: &mut Sink,
+    sequenced: &AtomicBool,
+    replay: ReplaySync,
+  ) -> Result<CollabAck, RealtimeError>
+  where
+    Sink: SubscriptionSink + 'static,
+  {
+    trace!("[realtime]: {}", replay);
+    sequenced.store(true, Ordering::Release);
+    let mut code = AckCode::Success;
+    if let Some(last_seen) = replay.last_seen_seq {
+      let result = state
+        .replay_buffer
+        .since(&state.workspace_id, &state.object_id, last_seen)
+        .await;
+      match result {
+        Ok(Replay::Updates(entries)) => {
+          state.metrics.replay_hit_count.inc();
+          for entry in entries {
+            let payload = Message::Sync(SyncMessage::Update(entry.data)).encode_v1();
+            let payload_len = payload.len();
+            let broadcast = BroadcastSync::new(
+              entry.sender,
+              state.object_id.clone(),
+              payload,
+              state.seq_no.load(Ordering::SeqCst),
+            );
+            let message = SequencedBroadcast {
+              broadcast,
+              stream_seq: entry.seq,
+            };
+            sink.send(message.into()).await?;
+            state
+              .bandwidth
+              .record_event_out(EventClass::Update, payload_len);
+          }
+        },
+        Ok(Replay::Unavailable) => {
+          state.metrics.replay_miss_count.inc();
+          code = AckCode::ReplayUnavailable;
+        },
+        Err(err) => {
+          warn!(
+            "failed to read the replay buffer of collab {}: {}",
+            state.object_id, err
+          );
+          state.metrics.replay_miss_count.inc();
+          code = AckCode::ReplayUnavailable;
+        },
+      }
+    }
+    Ok(
+      CollabAck::new(
+        replay.origin,
+        state.object_id.to_string(),
+        replay.msg_id,
+        state.seq_no.load(Ordering::SeqCst),
+      )
+      .with_code(code),
+    )
+  }
+
   /// Handle the message sent from the client
   async fn handle_client_message(
     state: &CollabGroupState,
+    filter: &AtomicU8,
+    full_state: Option<&FullStateThreshold>,
     collab_msg: ClientCollabMessage,
   ) -> Result<CollabAck, RealtimeError> {
     let msg_id = collab_msg.msg_id();
     let message_origin = collab_msg.origin().clone();
+    state.bandwidth.record_in(collab_msg.payload().len());
+
+    // The filter applies to the next events sent to the subscriber, without subscribing again.
+    if let ClientCollabMessage::ClientSubscriptionFilter(update) = &collab_msg {
+      trace!("[realtime]: {}", update);
+      filter.store(update.filter.to_bits(), Ordering::Release);
+      return Ok(CollabAck::new(
+        message_origin,
+        state.object_id.to_string(),
+        msg_id,
+        state.seq_no.load(Ordering::SeqCst),
+      ));
+    }
 
     // If the payload is empty, we don't need to apply any updates .
     // Currently, only the ping message should has an empty payload.
@@ -587,7 +1118,7 @@ impl CollabGroup {
     let payload = collab_msg.payload();
 
     // Spawn a blocking task to handle the message
-    let result = Self::handle_message(state, payload, &message_origin, msg_id).await;
+    let result = Self::handle_message(state, payload, &message_origin, msg_id, full_state).await;
 
     match result {
       Ok(inner_result) => match inner_result {
@@ -606,6 +1137,7 @@ impl CollabGroup {
     payload: &[u8],
     message_origin: &CollabOrigin,
     msg_id: MsgId,
+    full_state: Option<&FullStateThreshold>,
   ) -> Result<Option<CollabAck>, RealtimeError> {
     let mut decoder = DecoderV1::from(payload);
     let reader = MessageReader::new(&mut decoder);
@@ -613,7 +1145,7 @@ impl CollabGroup {
     for msg in reader {
       match msg {
         Ok(msg) => {
-          match Self::handle_protocol_message(state, message_origin, msg).await {
+          match Self::handle_protocol_message(state, message_origin, msg, full_state).await {
             Ok(payload) => {
               // One ClientCollabMessage can have multiple Yrs [Message] in it, but we only need to
               // send one ack back to the client.
@@ -639,6 +1171,8 @@ impl CollabGroup {
                   state_vector_v1,
                   reason: _,
                 } => state_vector_v1.unwrap_or_default(),
+                RTProtocolError::EditingLocked(lock) => lock.to_vec(),
+                RTProtocolError::CollabSizeExceeded(size) => size.to_vec(),
                 _ => vec![],
               };
 
@@ -670,15 +1204,33 @@ impl CollabGroup {
     state: &CollabGroupState,
     origin: &CollabOrigin,
     msg: Message,
+    full_state: Option<&FullStateThreshold>,
   ) -> Result<Option<Vec<u8>>, RTProtocolError> {
     match msg {
       Message::Sync(msg) => match msg {
-        SyncMessage::SyncStep1(sv) => Self::handle_sync_step1(state, &sv).await,
-        SyncMessage::SyncStep2(update) => Self::handle_sync_step2(state, origin, update).await,
-        SyncMessage::Update(update) => Self::handle_update(state, origin, update).await,
+        SyncMessage::SyncStep1(sv) => {
+          Self::check_load(state, ShedTier::CatchUp)?;
+          Self::handle_sync_step1(state, &sv, full_state).await
+        },
+        // a full state is applied as any other update
+        SyncMessage::SyncStep2(update) | SyncMessage::FullState(update) => {
+          Self::check_load(state, ShedTier::Interactive)?;
+          Self::check_read_only(state, origin).await?;
+          Self::check_editing_lock(state, origin).await?;
+          Self::handle_sync_step2(state, origin, update).await
+        },
+        SyncMessage::Update(update) => {
+          Self::check_load(state, ShedTier::Interactive)?;
+          Self::check_read_only(state, origin).await?;
+          Self::check_editing_lock(state, origin).await?;
+          Self::handle_update(state, origin, update).await
+        },
       },
       //FIXME: where is the QueryAwareness protocol?
-      Message::Awareness(update) => Self::handle_awareness_update(state, origin, update).await,
+      Message::Awareness(update) => {
+        Self::check_load(state, ShedTier::Awareness)?;
+        Self::handle_awareness_update(state, origin, update).await
+      },
       Message::Auth(_reason) => Ok(None),
       Message::Custom(_msg) => Ok(None),
     }
@@ -687,7 +1239,11 @@ impl CollabGroup {
   async fn handle_sync_step1(
     state: &CollabGroupState,
     remote_sv: &StateVector,
+    full_state: Option<&FullStateThreshold>,
   ) -> Result<Option<Vec<u8>>, RTProtocolError> {
+    if state.mode.is_opaque() {
+      return Self::handle_opaque_sync_step1(state).await;
+    }
     if let Ok(sv) = state.state_vector.try_read() {
       // we optimistically try to obtain state vector lock for a fast track:
       // if we remote sv is up-to-date with current one, we don't need to do anything
@@ -710,28 +1266,71 @@ impl CollabGroup {
       .await
       .map_err(|err| RTProtocolError::Internal(err.into()))?;
 
+    // a client that never saw the compacted copy can not be synced with it
+    if let Some(compaction_client_id) = compaction::compaction_client_id(&snapshot.collab) {
+      if compaction::is_stale_generation(compaction_client_id, remote_sv) {
+        return Err(RTProtocolError::CollabReset);
+      }
+    }
+
     // prepare document state update and state vector
     let tx = snapshot.collab.transact();
-    let doc_state = tx.encode_state_as_update_v1(remote_sv);
+    let reply = encode_missing_updates(&tx, remote_sv, full_state);
     let local_sv = tx.state_vector();
     drop(tx);
 
     // Retrieve the latest document state from the client after they return online from offline editing.
-    tracing::trace!("sending missing data to client ({} bytes)", doc_state.len());
+    tracing::trace!("sending missing data to client: {}", reply);
     let mut encoder = EncoderV1::new();
-    Message::Sync(SyncMessage::SyncStep2(doc_state)).encode(&mut encoder);
+    Message::Sync(reply).encode(&mut encoder);
     //FIXME: this should never happen as response to sync step 1 from the client, but rather be
     //  send when a connection is established
     Message::Sync(SyncMessage::SyncStep1(local_sv)).encode(&mut encoder);
     Ok(Some(encoder.to_vec()))
   }
 
+  /// The server can't tell which updates of an opaque collab the client is missing, so it sends
+  /// all of them. Applying an update twice doesn't change the document of the client.
+  async fn handle_opaque_sync_step1(
+    state: &CollabGroupState,
+  ) -> Result<Option<Vec<u8>>, RTProtocolError> {
+    let doc_state = state
+      .persister
+      .load_opaque()
+      .await
+      .map_err(|err| RTProtocolError::Internal(err.into()))?;
+    let frames = decode_opaque_frames(&doc_state)?;
+    if frames.is_empty() {
+      return Ok(None);
+    }
+    tracing::trace!(
+      "sending {} opaque updates of collab {} to client",
+      frames.len(),
+      state.object_id
+    );
+    let mut encoder = EncoderV1::new();
+    for frame in frames {
+      Message::Sync(SyncMessage::SyncStep2(frame)).encode(&mut encoder);
+    }
+    Ok(Some(encoder.to_vec()))
+  }
+
   async fn handle_sync_step2(
     state: &CollabGroupState,
     origin: &CollabOrigin,
     update: Vec<u8>,
   ) -> Result<Option<Vec<u8>>, RTProtocolError> {
     state.metrics.collab_size.observe(update.len() as f64);
+    Self::check_update_size(state, update.len())?;
+    if state.mode.is_opaque() {
+      // the update is encrypted: only its size can be checked
+      state
+        .persister
+        .send_update(origin.clone(), update)
+        .await
+        .map_err(|err| RTProtocolError::Internal(err.into()))?;
+      return Ok(None);
+    }
 
     let start = tokio::time::Instant::now();
     // we try to decode update to make sure it's not malformed and to extract state vector
@@ -775,6 +1374,29 @@ impl CollabGroup {
     }
   }
 
+  /// Rejects an update larger than the configured limit, or one which would grow the doc state
+  /// past its limit. The doc state size is the one of the last load or save, so the updates
+  /// received since then can still take it over the limit: the save is rejected in that case.
+  fn check_update_size(state: &CollabGroupState, len: usize) -> Result<(), RTProtocolError> {
+    let size_exceeded = |limit: usize, actual: usize| {
+      RTProtocolError::CollabSizeExceeded(CollabSizeExceeded {
+        limit: limit as u64,
+        actual: actual as u64,
+      })
+    };
+    let limits = &state.size_limits;
+    if len > limits.max_update_len {
+      return Err(size_exceeded(limits.max_update_len, len));
+    }
+    let spec = collab_spec(&state.collab_type, state.mode.is_opaque());
+    let limit = limits.doc_state_limit(spec);
+    let doc_state_len = state.persister.doc_size.load(Ordering::Relaxed) + len;
+    if doc_state_len > limit {
+      return Err(size_exceeded(limit, doc_state_len));
+    }
+    Ok(())
+  }
+
   async fn handle_update(
     state: &CollabGroupState,
     origin: &CollabOrigin,
@@ -803,32 +1425,21 @@ impl CollabGroup {
       RTProtocolError::YrsApplyUpdate(_) => AckCode::CannotApplyUpdate,
       RTProtocolError::YrsEncodeState(_) => AckCode::EncodeStateAsUpdateFail,
       RTProtocolError::MissUpdates { .. } => AckCode::MissUpdate,
+      RTProtocolError::CollabReset => AckCode::Reset,
+      RTProtocolError::EditingLocked(_) => AckCode::EditingLocked,
+      RTProtocolError::ReadOnly => AckCode::ReadOnly,
+      RTProtocolError::CollabSizeExceeded(_) => AckCode::CollabSizeExceeded,
+      RTProtocolError::Overloaded => AckCode::Retry,
       _ => AckCode::Internal,
     }
   }
+}
 
-  /// Check if the group is active. A group is considered active if it has at least one
-  /// subscriber
-  pub fn is_inactive(&self) -> bool {
-    let modified_at = self.modified_at();
-    let elapsed_secs = modified_at.elapsed().as_secs();
-    // Mark the group as inactive if it has been inactive for more than 3 hours, regardless of the number of subscribers.
-    // Otherwise, return `true` only if there are no subscribers remaining in the group.
-    // If a client modifies a group that has already been marked as inactive (removed),
-    // the client will automatically send an initialization sync to reinitialize the group.
-    const MAXIMUM_SECS: u64 = 3 * 60 * 60;
-    if elapsed_secs > MAXIMUM_SECS {
-      info!(
-        "Group:{}:{} is inactive for {} seconds, subscribers: {}",
-        self.state.object_id,
-        self.state.collab_type,
-        modified_at.elapsed().as_secs(),
-        self.state.subscribers.len()
-      );
-      true
-    } else {
-      self.state.subscribers.is_empty()
-    }
+fn editing_lock_meta(lock: &EditingLock) -> EditingLockMeta {
+  EditingLockMeta {
+    uid: lock.uid,
+    name: lock.name.clone(),
+    expires_at: lock.expires_at,
   }
 }
 
@@ -848,10 +1459,20 @@ struct Subscription {
   collab_origin: CollabOrigin,
   sink: Box<dyn SubscriptionSink>,
   shutdown: CancellationToken,
+  /// The [SubscriptionFilter] of the subscriber, changed by the messages it sends.
+  filter: Arc<AtomicU8>,
+  /// Set once the subscriber sent a [ReplaySync], it then gets [SequencedBroadcast]s.
+  sequenced: Arc<AtomicBool>,
 }
 
 impl Subscription {
-  fn new<S>(sink: S, collab_origin: CollabOrigin, shutdown: CancellationToken) -> Self
+  fn new<S>(
+    sink: S,
+    collab_origin: CollabOrigin,
+    shutdown: CancellationToken,
+    filter: Arc<AtomicU8>,
+    sequenced: Arc<AtomicBool>,
+  ) -> Self
   where
     S: SubscriptionSink + 'static,
   {
@@ -859,6 +1480,17 @@ impl Subscription {
       sink: Box::new(sink),
       collab_origin,
       shutdown,
+      filter,
+      sequenced,
+    }
+  }
+
+  fn accepts(&self, class: EventClass) -> bool {
+    let filter = SubscriptionFilter::from_bits(self.filter.load(Ordering::Acquire));
+    match class {
+      EventClass::Update => filter.updates,
+      EventClass::Awareness => filter.awareness,
+      EventClass::Control => filter.control,
     }
   }
 }
@@ -875,6 +1507,7 @@ struct CollabPersister {
   workspace_id: String,
   object_id: String,
   collab_type: CollabType,
+  mode: CollabMode,
   storage: Arc<dyn CollabStorage>,
   collab_redis_stream: Arc<CollabRedisStream>,
   indexer_scheduler: Arc<IndexerScheduler>,
@@ -884,6 +1517,29 @@ struct CollabPersister {
   /// A grace period for prunning Redis collab updates. Instead of deleting all messages we
   /// read right away, we give 1min for other potential client to catch up.
   prune_grace_period: Duration,
+  /// Folder collabs with an encoded size above this threshold are compacted on the next snapshot.
+  folder_compaction_threshold: usize,
+  /// Compaction is attempted at most once per group, so that a folder which can not be made
+  /// smaller is not rebuilt on every snapshot.
+  compaction_attempted: AtomicBool,
+  /// Whether a new collab without the structure of its type is rejected rather than logged, see
+  /// [crate::collab::validator::check_collab_structure].
+  strict_validation: bool,
+  snapshot_policy: SnapshotPolicy,
+  /// Edits applied since the last restore point. The counters are kept in Redis, so that they're
+  /// carried over when the collab is opened again, on this server or on another one.
+  edit_counters: EditCounterStore,
+  /// Set while the collab is served from the snapshot it was recovered from, see
+  /// [CollabGroup::check_read_only].
+  recovered: ArcSwapOption<RecoveredCollab>,
+  /// Whether the stored doc state is v2 encoded, as of its last load. Snapshots are written in
+  /// the stored encoding, so that a collab moved to another encoding by
+  /// [crate::collab::live_migration] stays in it.
+  stored_v2: AtomicBool,
+  /// Bytes of the stored doc state as of its last load or save.
+  doc_size: AtomicUsize,
+  /// The updates read from Redis and not saved yet, see [WriteBehindConfig].
+  write_behind: WriteBehindQueue,
 }
 
 impl CollabPersister {
@@ -893,19 +1549,28 @@ impl CollabPersister {
     workspace_id: String,
     object_id: String,
     collab_type: CollabType,
+    mode: CollabMode,
     storage: Arc<dyn CollabStorage>,
     collab_redis_stream: Arc<CollabRedisStream>,
     indexer_scheduler: Arc<IndexerScheduler>,
     metrics: Arc<CollabRealtimeMetrics>,
     prune_grace_period: Duration,
+    folder_compaction_threshold: usize,
+    strict_validation: bool,
+    snapshot_policy: SnapshotPolicy,
+    recovered: Option<RecoveredCollab>,
+    write_behind: WriteBehindConfig,
   ) -> Self {
     let update_sink = collab_redis_stream.collab_update_sink(&workspace_id, &object_id);
     let awareness_sink = collab_redis_stream.awareness_update_sink(&workspace_id, &object_id);
+    let edit_counters = collab_redis_stream.edit_counters();
+    let write_behind = WriteBehindQueue::new(write_behind, metrics.clone());
     Self {
       uid,
       workspace_id,
       object_id,
       collab_type,
+      mode,
       storage,
       collab_redis_stream,
       indexer_scheduler,
@@ -913,6 +1578,15 @@ impl CollabPersister {
       update_sink,
       awareness_sink,
       prune_grace_period,
+      folder_compaction_threshold,
+      compaction_attempted: AtomicBool::new(false),
+      strict_validation,
+      snapshot_policy,
+      edit_counters,
+      recovered: ArcSwapOption::new(recovered.map(Arc::new)),
+      stored_v2: AtomicBool::new(false),
+      doc_size: AtomicUsize::new(0),
+      write_behind,
     }
   }
 
@@ -1002,6 +1676,7 @@ impl CollabPersister {
     let snapshot = CollabSnapshot {
       collab,
       last_message_id,
+      edits: vec![],
     };
     Ok(snapshot)
   }
@@ -1019,8 +1694,12 @@ impl CollabPersister {
     let mut i = 0;
     let mut collab = None;
     let mut last_message_id = None;
+    let mut edits = Vec::new();
     for (message_id, update) in updates {
       i += 1;
+      if !update.flags.is_lock_changed() {
+        edits.push((message_id, update.data.len()));
+      }
       let update: Update = update.into_update()?;
       if collab.is_none() {
         collab = Some(match self.load_collab_full().await? {
@@ -1066,13 +1745,60 @@ impl CollabPersister {
         Ok(Some(CollabSnapshot {
           collab,
           last_message_id,
+          edits,
         }))
       },
       None => Ok(None),
     }
   }
 
+  /// Saves the updates of the write-behind queue. Those of a collab are merged into one, applied
+  /// on top of its stored state, while those of an opaque collab are appended from Redis as they
+  /// were received. The saved updates leave the queue, the ones queued since stay in it, and so do
+  /// all of them when another server holds the snapshot lease.
+  async fn flush(&self, trigger: FlushTrigger) -> Result<(), RealtimeError> {
+    if self.write_behind.is_empty() {
+      tracing::trace!("collab {} state has not changed", self.object_id);
+      return Ok(());
+    }
+    let start = Instant::now();
+    let saved = if self.mode.is_opaque() {
+      self.save_opaque().await?
+    } else {
+      let batch = match self.write_behind.batch()? {
+        Some(batch) => batch,
+        None => return Ok(()),
+      };
+      let mut collab = match self.load_collab_full().await? {
+        Some(collab) => collab,
+        None => {
+          Collab::new_with_origin(CollabOrigin::Server, self.object_id.clone(), vec![], false)
+        },
+      };
+      collab
+        .transact_mut()
+        .apply_update(Update::decode_v1(&batch.update)?)
+        .map_err(|err| RTProtocolError::YrsApplyUpdate(err.to_string()))?;
+      self.metrics.apply_update_count.inc();
+      self
+        .save_attempt(&mut collab, batch.last_message_id, &batch.edits)
+        .await?
+        .then_some(batch.last_message_id)
+    };
+    if let Some(message_id) = saved {
+      self.write_behind.drain_until(message_id);
+      self
+        .metrics
+        .record_write_behind_flush(trigger.as_str(), start.elapsed());
+    }
+    Ok(())
+  }
+
+  /// Saves every update still in Redis, whether or not it was queued.
   async fn save(&self) -> Result<(), RealtimeError> {
+    if self.mode.is_opaque() {
+      return self.save_opaque().await.map(|_| ());
+    }
     // load collab but only if there were pending updates in Redis
     if let Some(mut snapshot) = self.load_if_changed().await? {
       tracing::debug!("requesting save for collab {}", self.object_id);
@@ -1080,7 +1806,9 @@ impl CollabPersister {
         // non-nil message_id means that we had to update the most recent collab state snapshot
         // with new updates from Redis. This means that our snapshot state is newer than the last
         // persisted one in the database
-        self.save_attempt(&mut snapshot.collab, message_id).await?;
+        self
+          .save_attempt(&mut snapshot.collab, message_id, &snapshot.edits)
+          .await?;
       }
     } else {
       tracing::trace!("collab {} state has not changed", self.object_id);
@@ -1092,11 +1820,14 @@ impl CollabPersister {
   /// first it will try to save it as a historical snapshot (will all updates available), then it
   /// will generate another (compact) snapshot variant that will be used as main one for loading
   /// for the sake of y-sync protocol.
+  ///
+  /// Returns `false` when another server holds the snapshot lease.
   async fn save_attempt(
     &self,
     collab: &mut Collab,
     message_id: MessageId,
-  ) -> Result<(), RealtimeError> {
+    edits: &[(MessageId, usize)],
+  ) -> Result<bool, RealtimeError> {
     // try to acquire snapshot lease - it's possible that multiple web services will try to
     // perform snapshot at the same time, so we'll use lease to let only one of them atm.
     if let Some(mut lease) = self
@@ -1104,24 +1835,42 @@ impl CollabPersister {
       .lease(&self.workspace_id, &self.object_id)
       .await?
     {
+      if self.doc_size.load(Ordering::Relaxed) == 0 {
+        // nothing is stored for the collab yet: it's only created by this save
+        check_collab_structure(
+          collab_type_spec(&self.collab_type),
+          &self.workspace_id,
+          &self.object_id,
+          collab,
+          self.strict_validation,
+        )
+        .map_err(|err| RealtimeError::Internal(err.into()))?;
+      }
+      let edit_count = self.record_edits(edits).await;
       let doc_state_light = collab
         .transact()
         .encode_state_as_update_v1(&StateVector::default());
       let light_len = doc_state_light.len();
-      self.write_collab(doc_state_light).await?;
-
-      match self.collab_type {
-        CollabType::Document => {
-          let txn = collab.transact();
-          if let Some(text) = DocumentBody::from_collab(collab)
-            .and_then(|body| body.to_plain_text(txn, false, true).ok())
-          {
-            self.index_collab_content(text);
-          }
-        },
-        _ => {
-          // TODO(nathan): support other collab type
-        },
+      let restore_point = self
+        .claim_restore_point(edit_count)
+        .await
+        .then(|| doc_state_light.clone());
+      if self.stored_v2.load(Ordering::Relaxed) {
+        let doc_state = collab
+          .transact()
+          .encode_state_as_update_v2(&StateVector::default());
+        self.write_collab(doc_state, EncoderVersion::V2).await?;
+      } else {
+        self
+          .write_collab(doc_state_light, EncoderVersion::V1)
+          .await?;
+      }
+      if let Some(doc_state) = restore_point {
+        self.queue_restore_point(doc_state).await;
+      }
+
+      if let Some(text) = collab_type_spec(&self.collab_type).extract_text(collab) {
+        self.index_collab_content(text);
       }
 
       tracing::debug!(
@@ -1131,12 +1880,25 @@ impl CollabPersister {
         light_len
       );
 
-      // 3. finally we can drop Redis messages
+      if self.should_compact(light_len) {
+        match self.compact_attempt(collab, message_id, light_len).await {
+          Ok(true) => {
+            let _ = lease.release().await;
+            return Ok(true);
+          },
+          Ok(false) => {},
+          Err(err) => warn!("failed to compact collab {}: {}", self.object_id, err),
+        }
+      }
+
+      // 3. finally we can drop Redis messages, but not the ones after the saved snapshot: they
+      // may still be queued, and Redis is where they're recovered from after a crash
       let now = SystemTime::UNIX_EPOCH.elapsed().unwrap().as_millis();
       let msg_id = MessageId {
         timestamp_ms: (now - self.prune_grace_period.as_millis()) as u64,
         sequence_number: 0,
-      };
+      }
+      .min(message_id);
       let stream_key = CollabStreamUpdate::stream_key(&self.workspace_id, &self.object_id);
       self
         .collab_redis_stream
@@ -1144,9 +1906,257 @@ impl CollabPersister {
         .await?;
 
       let _ = lease.release().await;
+      return Ok(true);
     }
 
-    Ok(())
+    Ok(false)
+  }
+
+  /// Loads the stored updates of an opaque collab, followed by the ones still in Redis.
+  async fn load_opaque(&self) -> Result<Vec<u8>, RealtimeError> {
+    let doc_state = self.load_opaque_stored().await?;
+    let updates = self
+      .collab_redis_stream
+      .current_collab_updates(&self.workspace_id, &self.object_id, None)
+      .await?;
+    Ok(append_opaque_frames(&doc_state, opaque_frames(updates)))
+  }
+
+  async fn load_opaque_stored(&self) -> Result<Vec<u8>, RealtimeError> {
+    let params = QueryCollabParams::new(
+      self.object_id.clone(),
+      self.collab_type.clone(),
+      self.workspace_id.clone(),
+    );
+    match self
+      .storage
+      .get_encode_collab(GetCollabOrigin::Server, params, false)
+      .await
+    {
+      Ok(encoded_collab) => {
+        self
+          .doc_size
+          .store(encoded_collab.doc_state.len(), Ordering::Relaxed);
+        Ok(encoded_collab.doc_state.to_vec())
+      },
+      Err(AppError::RecordNotFound(_)) => Ok(vec![]),
+      Err(err) => Err(RealtimeError::Internal(err.into())),
+    }
+  }
+
+  /// Appends the updates of an opaque collab received since the last save to its stored state.
+  /// They're kept as they are: opaque collabs are neither indexed nor compacted.
+  ///
+  /// Returns the id of the last saved update, or `None` when nothing was saved.
+  async fn save_opaque(&self) -> Result<Option<MessageId>, RealtimeError> {
+    let updates = self
+      .collab_redis_stream
+      .current_collab_updates(&self.workspace_id, &self.object_id, None)
+      .await?;
+    let message_id = match updates.last() {
+      Some((message_id, _)) => *message_id,
+      None => {
+        tracing::trace!("collab {} state has not changed", self.object_id);
+        return Ok(None);
+      },
+    };
+    if let Some(mut lease) = self
+      .collab_redis_stream
+      .lease(&self.workspace_id, &self.object_id)
+      .await?
+    {
+      let edits: Vec<_> = updates
+        .iter()
+        .filter(|(_, update)| !update.flags.is_lock_changed())
+        .map(|(message_id, update)| (*message_id, update.data.len()))
+        .collect();
+      let edit_count = self.record_edits(&edits).await;
+      let stored = self.load_opaque_stored().await?;
+      let doc_state = append_opaque_frames(&stored, opaque_frames(updates));
+      let len = doc_state.len();
+      let restore_point = self
+        .claim_restore_point(edit_count)
+        .await
+        .then(|| doc_state.clone());
+      self.write_collab(doc_state, EncoderVersion::V1).await?;
+      if let Some(doc_state) = restore_point {
+        self.queue_restore_point(doc_state).await;
+      }
+
+      // Unlike the updates of the other collabs, which can be applied twice, the saved updates
+      // are dropped right away, including the last one, so that they're not appended again.
+      let min_id = match message_id.sequence_number.checked_add(1) {
+        Some(sequence_number) => MessageId {
+          timestamp_ms: message_id.timestamp_ms,
+          sequence_number,
+        },
+        None => MessageId {
+          timestamp_ms: message_id.timestamp_ms + 1,
+          sequence_number: 0,
+        },
+      };
+      let stream_key = CollabStreamUpdate::stream_key(&self.workspace_id, &self.object_id);
+      self
+        .collab_redis_stream
+        .prune_update_stream(&stream_key, min_id)
+        .await?;
+      tracing::debug!(
+        "persisted opaque collab {} at {}: {} bytes",
+        self.object_id,
+        message_id,
+        len
+      );
+      let _ = lease.release().await;
+      return Ok(Some(message_id));
+    }
+    Ok(None)
+  }
+
+  /// Counts the edits read from Redis, including the ones already counted by a previous save.
+  /// Must be called while holding the snapshot lease.
+  async fn record_edits(&self, edits: &[(MessageId, usize)]) -> Option<EditCount> {
+    match self
+      .edit_counters
+      .record(&self.workspace_id, &self.object_id, edits)
+      .await
+    {
+      Ok(count) => Some(count),
+      Err(err) => {
+        warn!(
+          "failed to count the edits of collab {}: {}",
+          self.object_id, err
+        );
+        None
+      },
+    }
+  }
+
+  /// Returns `true` when the edits counted in `edit_count` are due for a restore point, see
+  /// [SnapshotPolicy]. The counter is reset first, so that a single server takes the restore
+  /// point even if several of them read the same count.
+  async fn claim_restore_point(&self, edit_count: Option<EditCount>) -> bool {
+    let edit_count = match edit_count {
+      Some(edit_count) => edit_count,
+      None => return false,
+    };
+    let volume = EditVolume {
+      updates: edit_count.updates,
+      bytes: edit_count.bytes,
+    };
+    let now = chrono::Utc::now().timestamp_millis();
+    let since = edit_count.since.unwrap_or(now);
+    let elapsed = Duration::from_millis(now.saturating_sub(since).max(0) as u64);
+    let trigger = match self.snapshot_policy.trigger(&volume, elapsed) {
+      Some(trigger) => trigger,
+      None => return false,
+    };
+    match self
+      .edit_counters
+      .reset(&self.workspace_id, &self.object_id, &edit_count)
+      .await
+    {
+      Ok(true) => {
+        trace!(
+          "restore point of collab {} due to {} after {} updates ({} bytes)",
+          self.object_id,
+          trigger.as_str(),
+          volume.updates,
+          volume.bytes
+        );
+        self.metrics.record_snapshot_trigger(
+          collab_type_name(&self.collab_type),
+          trigger.as_str(),
+          volume.updates,
+          elapsed,
+        );
+        true
+      },
+      Ok(false) => false,
+      Err(err) => {
+        warn!(
+          "failed to reset the edit counter of collab {}: {}",
+          self.object_id, err
+        );
+        false
+      },
+    }
+  }
+
+  /// Takes a restore point of the collab, see [SnapshotPolicy].
+  async fn queue_restore_point(&self, doc_state: Vec<u8>) {
+    let params = InsertSnapshotParams {
+      object_id: self.object_id.clone(),
+      doc_state: doc_state.into(),
+      workspace_id: self.workspace_id.clone(),
+      collab_type: self.collab_type.clone(),
+      created_by: None,
+    };
+    match self.storage.queue_snapshot(params).await {
+      Ok(_) => trace!("queued restore point of collab {}", self.object_id),
+      Err(err) => warn!(
+        "failed to queue restore point of collab {}: {}",
+        self.object_id, err
+      ),
+    }
+  }
+
+  fn should_compact(&self, len: usize) -> bool {
+    matches!(self.collab_type, CollabType::Folder)
+      && len > self.folder_compaction_threshold
+      && !self.compaction_attempted.swap(true, Ordering::SeqCst)
+  }
+
+  /// Replaces the collab by a compacted copy of `collab`, which must contain all Redis updates up
+  /// to `message_id` and be stored already. Must be called while holding the snapshot lease.
+  ///
+  /// Returns `false` when the collab was left untouched.
+  async fn compact_attempt(
+    &self,
+    collab: &Collab,
+    message_id: MessageId,
+    len: usize,
+  ) -> Result<bool, RealtimeError> {
+    let compacted = compaction::compact_collab(collab)?;
+    let doc_state = compacted
+      .transact()
+      .encode_state_as_update_v1(&StateVector::default());
+    let compacted_len = doc_state.len();
+    if compacted_len * 2 > len {
+      trace!(
+        "skip compaction of collab {}: {} bytes compacted to {} bytes",
+        self.object_id,
+        len,
+        compacted_len
+      );
+      return Ok(false);
+    }
+
+    // Updates in the stream belong to the old history. Instead of the grace period used by
+    // regular snapshots, they are dropped right away and replaced by a reset marker telling every
+    // group of this collab to resync its clients. The marker is only appended when no update
+    // arrived after the snapshot, since those aren't part of the compacted copy.
+    let reset = self
+      .collab_redis_stream
+      .reset_update_stream(&self.workspace_id, &self.object_id, message_id)
+      .await?;
+    if reset.is_none() {
+      trace!(
+        "skip compaction of collab {}: updates arrived after {}",
+        self.object_id,
+        message_id
+      );
+      return Ok(false);
+    }
+    // The compacted copy is only stored once the old history is gone from the stream. Until
+    // then, the stored copy is the uncompacted one, which holds every dropped update.
+    self.write_collab(doc_state, EncoderVersion::V1).await?;
+    self.stored_v2.store(false, Ordering::Relaxed);
+
+    info!(
+      "compacted collab {} at {}: {} bytes to {} bytes",
+      self.object_id, message_id, len, compacted_len
+    );
+    Ok(true)
   }
 
   async fn trim_awareness(&self) -> Result<(), RealtimeError> {
@@ -1158,8 +2168,17 @@ impl CollabPersister {
     Ok(())
   }
 
-  async fn write_collab(&self, doc_state_v1: Vec<u8>) -> Result<(), RealtimeError> {
-    let encoded_collab = EncodedCollab::new_v1(Default::default(), doc_state_v1)
+  async fn write_collab(
+    &self,
+    doc_state: Vec<u8>,
+    version: EncoderVersion,
+  ) -> Result<(), RealtimeError> {
+    self.doc_size.store(doc_state.len(), Ordering::Relaxed);
+    let encoded_collab = match version {
+      EncoderVersion::V1 => EncodedCollab::new_v1(Default::default(), doc_state),
+      EncoderVersion::V2 => EncodedCollab::new_v2(Default::default(), doc_state),
+    };
+    let encoded_collab = encoded_collab
       .encode_to_bytes()
       .map(Bytes::from)
       .map_err(|err| RealtimeError::BincodeEncode(err.to_string()))?;
@@ -1199,6 +2218,22 @@ impl CollabPersister {
   }
 
   async fn load_collab_full(&self) -> Result<Option<Collab>, RealtimeError> {
+    if let Some(recovered) = self.recovered.load_full() {
+      let doc_state = recovered.encoded_collab.doc_state.to_vec();
+      let data_source = match recovered.encoded_collab.version {
+        EncoderVersion::V1 => DataSource::DocStateV1(doc_state),
+        EncoderVersion::V2 => DataSource::DocStateV2(doc_state),
+      };
+      let collab = Collab::new_with_source(
+        CollabOrigin::Server,
+        &self.object_id,
+        data_source,
+        vec![],
+        false,
+      )?;
+      return Ok(Some(collab));
+    }
+
     // we didn't find a snapshot, or we want a lightweight collab version
     let params = QueryCollabParams::new(
       self.object_id.clone(),
@@ -1209,16 +2244,26 @@ impl CollabPersister {
       .storage
       .get_encode_collab(GetCollabOrigin::Server, params, false)
       .await;
-    let doc_state = match result {
-      Ok(encoded_collab) => encoded_collab.doc_state,
+    let encoded_collab = match result {
+      Ok(encoded_collab) => encoded_collab,
       Err(AppError::RecordNotFound(_)) => return Ok(None),
       Err(err) => return Err(RealtimeError::Internal(err.into())),
     };
 
+    let doc_state = encoded_collab.doc_state.to_vec();
+    self.doc_size.store(doc_state.len(), Ordering::Relaxed);
+    let data_source = match encoded_collab.version {
+      EncoderVersion::V1 => DataSource::DocStateV1(doc_state),
+      EncoderVersion::V2 => DataSource::DocStateV2(doc_state),
+    };
+    self.stored_v2.store(
+      matches!(encoded_collab.version, EncoderVersion::V2),
+      Ordering::Relaxed,
+    );
     let collab: Collab = Collab::new_with_source(
       CollabOrigin::Server,
       &self.object_id,
-      DataSource::DocStateV1(doc_state.into()),
+      data_source,
       vec![],
       false,
     )?;
@@ -1226,7 +2271,19 @@ impl CollabPersister {
   }
 }
 
+/// The payloads of the updates of an opaque collab, without the markers the server adds to the
+/// stream.
+fn opaque_frames(updates: Vec<(MessageId, CollabStreamUpdate)>) -> impl Iterator<Item = Vec<u8>> {
+  updates
+    .into_iter()
+    .filter(|(_, update)| !update.flags.is_lock_changed() && !update.flags.is_reset())
+    .map(|(_, update)| update.data)
+}
+
 pub struct CollabSnapshot {
   pub collab: Collab,
   pub last_message_id: Option<MessageId>,
+  /// The updates replayed on top of the stored state, with their length, see
+  /// [EditCounterStore::record].
+  pub edits: Vec<(MessageId, usize)>,
 }
@@ -2,25 +2,37 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use access_control::collab::RealtimeAccessControl;
-use app_error::AppError;
 use collab::core::collab::DataSource;
 use collab::core::origin::CollabOrigin;
-use collab::entity::EncodedCollab;
+use collab::entity::EncoderVersion;
 use collab::preclude::Collab;
 use collab_entity::CollabType;
 use collab_rt_entity::user::RealtimeUser;
 use collab_rt_entity::CollabMessage;
 use collab_stream::client::CollabRedisStream;
+use collab_stream::model::CollabStreamUpdate;
 use database::collab::{CollabStorage, GetCollabOrigin};
 use database_entity::dto::QueryCollabParams;
-use tracing::{instrument, trace};
-use yrs::{ReadTxn, StateVector};
+use futures_util::future::join_all;
+use tracing::{info, trace, warn};
+use yrs::updates::decoder::Decode;
+use yrs::{ReadTxn, StateVector, Update};
 
+use crate::bandwidth::RealtimeBandwidth;
 use crate::client::client_msg_router::ClientMessageRouter;
+use crate::collab::migration::CollabMigrator;
+use crate::collab::recovery::{CollabRecovery, DetectedOn, RecoverableCollab, RecoveredCollab};
+use crate::collab::validator::CollabSizeLimits;
 use crate::error::RealtimeError;
+use crate::feature_flags::{FeatureFlags, FOLDER_COMPACTION, STRICT_COLLAB_VALIDATION};
+use crate::group::full_state::FullStateSyncConfig;
 use crate::group::group_init::CollabGroup;
 use crate::group::state::GroupManagementState;
+use crate::group::unload::{GroupUnloadConfig, UnloadReason};
+use crate::group::write_behind::WriteBehindConfig;
+use crate::load_shed::LoadShedder;
 use crate::metrics::CollabRealtimeMetrics;
+use crate::snapshot::SnapshotPolicyResolver;
 use indexer::scheduler::IndexerScheduler;
 
 pub struct GroupManager<S> {
@@ -31,7 +43,17 @@ pub struct GroupManager<S> {
   collab_redis_stream: Arc<CollabRedisStream>,
   persistence_interval: Duration,
   prune_grace_period: Duration,
+  folder_compaction_threshold: usize,
+  snapshot_policies: SnapshotPolicyResolver,
   indexer_scheduler: Arc<IndexerScheduler>,
+  bandwidth: Arc<RealtimeBandwidth>,
+  recovery: CollabRecovery,
+  migrator: CollabMigrator,
+  feature_flags: FeatureFlags,
+  load_shedder: Arc<LoadShedder>,
+  size_limits: CollabSizeLimits,
+  full_state_sync: FullStateSyncConfig,
+  write_behind: WriteBehindConfig,
 }
 
 impl<S> GroupManager<S>
@@ -46,23 +68,75 @@ where
     collab_stream: CollabRedisStream,
     persistence_interval: Duration,
     prune_grace_period: Duration,
+    folder_compaction_threshold: usize,
+    snapshot_policies: SnapshotPolicyResolver,
     indexer_scheduler: Arc<IndexerScheduler>,
+    bandwidth: Arc<RealtimeBandwidth>,
+    recovery: CollabRecovery,
+    migrator: CollabMigrator,
+    feature_flags: FeatureFlags,
+    load_shedder: Arc<LoadShedder>,
+    unload_config: GroupUnloadConfig,
+    size_limits: CollabSizeLimits,
+    full_state_sync: FullStateSyncConfig,
+    write_behind: WriteBehindConfig,
   ) -> Result<Self, RealtimeError> {
     let collab_stream = Arc::new(collab_stream);
     Ok(Self {
-      state: GroupManagementState::new(metrics_calculate.clone()),
+      state: GroupManagementState::new(metrics_calculate.clone(), unload_config),
       storage,
       access_control,
       metrics_calculate,
       collab_redis_stream: collab_stream,
       persistence_interval,
       prune_grace_period,
+      folder_compaction_threshold,
+      snapshot_policies,
       indexer_scheduler,
+      bandwidth,
+      recovery,
+      migrator,
+      feature_flags,
+      load_shedder,
+      size_limits,
+      full_state_sync,
+      write_behind,
     })
   }
 
-  pub fn get_inactive_groups(&self) -> Vec<String> {
-    self.state.remove_inactive_groups()
+  pub fn unload_config(&self) -> &GroupUnloadConfig {
+    self.state.unload_config()
+  }
+
+  /// Removes the idle groups, and the least recently active ones when the memory budget is
+  /// exceeded. They must be stopped with [GroupManager::stop_groups] once nothing routes messages
+  /// to them anymore.
+  pub fn take_groups_to_unload(&self) -> Vec<(String, Arc<CollabGroup>, UnloadReason)> {
+    self.state.take_groups_to_unload()
+  }
+
+  /// Stops the unloaded groups, waiting for their pending updates to be saved.
+  pub async fn stop_groups(&self, groups: Vec<Arc<CollabGroup>>) {
+    let timeout = self.state.unload_config().flush_timeout;
+    let stopped = join_all(groups.iter().map(|group| group.stop(timeout))).await;
+    for (group, stopped) in groups.iter().zip(stopped) {
+      if !stopped {
+        // the updates stay in Redis, they're saved the next time the collab is loaded
+        warn!(
+          "collab {} wasn't saved within {:?} of being unloaded",
+          group.object_id(),
+          timeout
+        );
+      }
+    }
+  }
+
+  /// Stops all the groups, waiting for their pending updates to be saved, when the server shuts
+  /// down.
+  pub async fn stop_all_groups(&self) {
+    let groups = self.state.take_all_groups();
+    info!("saving {} collab groups before shutdown", groups.len());
+    self.stop_groups(groups).await;
   }
 
   pub fn contains_user(&self, object_id: &str, user: &RealtimeUser) -> bool {
@@ -77,10 +151,53 @@ where
     self.state.contains_group(object_id)
   }
 
+  /// Whether the collab is in the trash, no group is created for it until it's restored.
+  pub async fn is_collab_deleted(&self, object_id: &str) -> Result<bool, RealtimeError> {
+    self
+      .storage
+      .is_collab_deleted(object_id)
+      .await
+      .map_err(|err| RealtimeError::CannotCreateGroup(err.to_string()))
+  }
+
   pub async fn get_group(&self, object_id: &str) -> Option<Arc<CollabGroup>> {
     self.state.get_group(object_id).await
   }
 
+  /// Publishes updates to a collab which has no group on this server. The groups of the other
+  /// servers, which tail the update stream of the collab, relay them to their clients. The
+  /// updates are published even when no client is connected yet: a group created afterwards, on
+  /// any server, reads the stream from its start and catches up with them. The stream is trimmed
+  /// once a group persists them, and capped by
+  /// [collab_stream::collab_update_sink::CollabUpdateSink::MAX_STREAM_LEN] otherwise.
+  ///
+  /// The streams are per collab rather than per workspace, so that a server only reads the updates
+  /// of the collabs it has a group for. The updates carry their sender, which the group of the
+  /// sending server skips, in place of a node id.
+  pub async fn publish_updates(
+    &self,
+    workspace_id: &str,
+    object_id: &str,
+    updates: Vec<CollabStreamUpdate>,
+  ) -> Result<(), RealtimeError> {
+    if updates.is_empty() {
+      return Ok(());
+    }
+    let sink = self
+      .collab_redis_stream
+      .collab_update_sink(workspace_id, object_id);
+    let count = updates.len();
+    for update in updates {
+      sink.send(&update).await?;
+    }
+    trace!(
+      "[realtime]: published {} server updates of collab {}",
+      count,
+      object_id
+    );
+    Ok(())
+  }
+
   pub async fn subscribe_group(
     &self,
     user: &RealtimeUser,
@@ -118,23 +235,73 @@ where
     object_id: &str,
     collab_type: CollabType,
   ) -> Result<(), RealtimeError> {
-    let params = QueryCollabParams::new(object_id, collab_type.clone(), workspace_id);
-    let res = self
+    let mode = self
       .storage
-      .get_encode_collab(GetCollabOrigin::Server, params, false)
-      .await;
-    let state_vector = match res {
-      Ok(collab) => Collab::new_with_source(
-        CollabOrigin::Server,
-        object_id,
-        DataSource::DocStateV1(collab.doc_state.into()),
-        vec![],
-        false,
-      )?
-      .transact()
-      .state_vector(),
-      Err(err) if err.is_record_not_found() => StateVector::default(),
-      Err(err) => return Err(RealtimeError::CannotCreateGroup(err.to_string())),
+      .collab_mode(object_id)
+      .await
+      .map_err(|err| RealtimeError::CannotCreateGroup(err.to_string()))?;
+    // the server can't decode the updates of an opaque collab: they're relayed as they are, so
+    // there's nothing to migrate nor to recover
+    let (state_vector, recovered) = if mode.is_opaque() {
+      (StateVector::default(), None)
+    } else {
+      let params = QueryCollabParams::new(object_id, collab_type.clone(), workspace_id);
+      let res = self
+        .recovery
+        .get_encode_collab(
+          &*self.storage,
+          GetCollabOrigin::Server,
+          params,
+          false,
+          DetectedOn::Realtime,
+        )
+        .await;
+      match res {
+        Ok(RecoverableCollab {
+          encoded_collab,
+          recovered_from,
+        }) => {
+          let data_source = match encoded_collab.version {
+            EncoderVersion::V1 => DataSource::DocStateV1(encoded_collab.doc_state.to_vec()),
+            EncoderVersion::V2 => DataSource::DocStateV2(encoded_collab.doc_state.to_vec()),
+          };
+          let mut collab =
+            Collab::new_with_source(CollabOrigin::Server, object_id, data_source, vec![], false)?;
+          let mut state_vector = collab.transact().state_vector();
+          if recovered_from.is_none() {
+            // bring the collab to the latest content conventions before anyone edits it
+            let migrated = self
+              .migrator
+              .migrate_on_open(
+                &self.collab_redis_stream,
+                workspace_id,
+                object_id,
+                &collab_type,
+                &mut collab,
+              )
+              .await;
+            match migrated {
+              Ok(Some(update)) => match Update::decode_v1(&update) {
+                Ok(update) => state_vector.merge(update.state_vector()),
+                Err(err) => warn!(
+                  "failed to decode migration of collab {}: {}",
+                  object_id, err
+                ),
+              },
+              Ok(None) => {},
+              Err(err) => warn!("failed to migrate collab {}: {}", object_id, err),
+            }
+          }
+          let recovered = recovered_from.map(|snapshot| {
+            // the group stays read-only until an admin confirms the repair
+            self.metrics_calculate.recovered_collab_count.inc();
+            RecoveredCollab::new(encoded_collab, snapshot)
+          });
+          (state_vector, recovered)
+        },
+        Err(err) if err.is_record_not_found() => (StateVector::default(), None),
+        Err(err) => return Err(RealtimeError::CannotCreateGroup(err.to_string())),
+      }
     };
 
     trace!(
@@ -145,111 +312,43 @@ where
       collab_type
     );
 
+    let snapshot_policy = self
+      .snapshot_policies
+      .resolve(workspace_id, &collab_type)
+      .await;
+    let folder_compaction_threshold = if self.feature_flags.enabled(FOLDER_COMPACTION, workspace_id)
+    {
+      self.folder_compaction_threshold
+    } else {
+      usize::MAX
+    };
     let group = CollabGroup::new(
       user.uid,
       workspace_id.to_string(),
       object_id.to_string(),
       collab_type,
+      mode,
       self.metrics_calculate.clone(),
       self.storage.clone(),
       self.collab_redis_stream.clone(),
       self.persistence_interval,
       self.prune_grace_period,
+      folder_compaction_threshold,
+      self
+        .feature_flags
+        .enabled(STRICT_COLLAB_VALIDATION, workspace_id),
+      snapshot_policy,
       state_vector,
       self.indexer_scheduler.clone(),
+      self.bandwidth.workspace(workspace_id),
+      self.recovery.clone(),
+      recovered,
+      self.load_shedder.clone(),
+      self.size_limits.clone(),
+      self.full_state_sync.clone(),
+      self.write_behind.clone(),
     )?;
     self.state.insert_group(object_id, group);
     Ok(())
   }
 }
-
-#[allow(dead_code)]
-#[instrument(level = "trace", skip_all)]
-async fn load_collab<S>(
-  uid: i64,
-  object_id: &str,
-  params: QueryCollabParams,
-  storage: Arc<S>,
-) -> Result<(Collab, EncodedCollab), AppError>
-where
-  S: CollabStorage,
-{
-  let encode_collab = storage
-    .get_encode_collab(GetCollabOrigin::User { uid }, params.clone(), false)
-    .await?;
-  let result = Collab::new_with_source(
-    CollabOrigin::Server,
-    object_id,
-    DataSource::DocStateV1(encode_collab.doc_state.to_vec()),
-    vec![],
-    false,
-  );
-  match result {
-    Ok(collab) => Ok((collab, encode_collab)),
-    Err(err) => load_collab_from_snapshot(object_id, params, storage)
-      .await
-      .ok_or_else(|| AppError::Internal(err.into())),
-  }
-}
-
-async fn load_collab_from_snapshot<S>(
-  object_id: &str,
-  params: QueryCollabParams,
-  storage: Arc<S>,
-) -> Option<(Collab, EncodedCollab)>
-where
-  S: CollabStorage,
-{
-  let encode_collab = get_latest_snapshot(
-    &params.workspace_id,
-    object_id,
-    &*storage,
-    &params.collab_type,
-  )
-  .await?;
-  let collab = Collab::new_with_source(
-    CollabOrigin::Server,
-    object_id,
-    DataSource::DocStateV1(encode_collab.doc_state.to_vec()),
-    vec![],
-    false,
-  )
-  .ok()?;
-  Some((collab, encode_collab))
-}
-
-async fn get_latest_snapshot<S>(
-  workspace_id: &str,
-  object_id: &str,
-  storage: &S,
-  collab_type: &CollabType,
-) -> Option<EncodedCollab>
-where
-  S: CollabStorage,
-{
-  let metas = storage
-    .get_collab_snapshot_list(workspace_id, object_id)
-    .await
-    .ok()?
-    .0;
-  for meta in metas {
-    let snapshot_data = storage
-      .get_collab_snapshot(workspace_id, &meta.object_id, &meta.snapshot_id)
-      .await
-      .ok()?;
-    if let Ok(encoded_collab) = EncodedCollab::decode_from_bytes(&snapshot_data.encoded_collab_v1) {
-      if let Ok(collab) = Collab::new_with_source(
-        CollabOrigin::Empty,
-        object_id,
-        DataSource::DocStateV1(encoded_collab.doc_state.to_vec()),
-        vec![],
-        false,
-      ) {
-        // TODO(nathan): this check is not necessary, can be removed in the future.
-        collab_type.validate_require_data(&collab).ok()?;
-        return Some(encoded_collab);
-      }
-    }
-  }
-  None
-}
@@ -1,6 +1,10 @@
 pub(crate) mod cmd;
+pub(crate) mod compaction;
+pub mod full_state;
 pub(crate) mod group_init;
 pub(crate) mod manager;
 mod null_sender;
 mod plugin;
 mod state;
+pub mod unload;
+pub mod write_behind;
@@ -67,6 +67,7 @@ where
         doc_state: data,
         workspace_id,
         collab_type,
+        created_by: None,
       };
       storage.queue_snapshot(params).await?;
       trace!("successfully enqueued snapshot creation")
@@ -3,13 +3,14 @@ use dashmap::try_result::TryResult;
 use dashmap::DashMap;
 use std::collections::HashSet;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
-use tracing::{error, event, trace, warn};
+use tracing::{error, event, info, trace, warn};
 
 use crate::config::get_env_var;
 use crate::error::RealtimeError;
 use crate::group::group_init::CollabGroup;
+use crate::group::unload::{select_groups_to_unload, GroupUnloadConfig, UnloadReason};
 use crate::metrics::CollabRealtimeMetrics;
 use collab_rt_entity::user::RealtimeUser;
 
@@ -21,10 +22,19 @@ pub(crate) struct GroupManagementState {
   metrics_calculate: Arc<CollabRealtimeMetrics>,
   /// By default, the number of groups to remove in a single batch is 50.
   remove_batch_size: usize,
+  unload_config: GroupUnloadConfig,
+  /// When the groups were unloaded, to count the ones loaded again shortly after.
+  unloaded_at: Arc<DashMap<String, Instant>>,
 }
 
 impl GroupManagementState {
-  pub(crate) fn new(metrics_calculate: Arc<CollabRealtimeMetrics>) -> Self {
+  /// A group loaded again within this time after being unloaded counts as reloaded.
+  const RELOAD_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+  pub(crate) fn new(
+    metrics_calculate: Arc<CollabRealtimeMetrics>,
+    unload_config: GroupUnloadConfig,
+  ) -> Self {
     let remove_batch_size = get_env_var("APPFLOWY_COLLABORATE_REMOVE_BATCH_SIZE", "50")
       .parse::<usize>()
       .unwrap_or(50);
@@ -33,28 +43,79 @@ impl GroupManagementState {
       editing_by_user: Arc::new(DashMap::new()),
       metrics_calculate,
       remove_batch_size,
+      unload_config,
+      unloaded_at: Arc::new(DashMap::new()),
     }
   }
 
-  /// Returns group ids of inactive groups.
-  pub fn remove_inactive_groups(&self) -> Vec<String> {
-    let mut inactive_group_ids = vec![];
-    for entry in self.group_by_object_id.iter() {
-      let (object_id, group) = (entry.key(), entry.value());
-      if group.is_inactive() {
-        inactive_group_ids.push(object_id.clone());
-        if inactive_group_ids.len() > self.remove_batch_size {
-          break;
-        }
+  pub(crate) fn unload_config(&self) -> &GroupUnloadConfig {
+    &self.unload_config
+  }
+
+  /// Removes the groups to unload: the idle ones, then the least recently active ones while the
+  /// loaded groups exceed the memory budget. The removed groups are returned to be stopped, see
+  /// [CollabGroup::stop].
+  pub fn take_groups_to_unload(&self) -> Vec<(String, Arc<CollabGroup>, UnloadReason)> {
+    let now = Instant::now();
+    let groups = self
+      .group_by_object_id
+      .iter()
+      .map(|entry| (entry.key().clone(), entry.value().clone()))
+      .collect::<Vec<_>>();
+    let usages = groups
+      .iter()
+      .map(|(_, group)| group.usage())
+      .collect::<Vec<_>>();
+    let (selected, resident_bytes) =
+      select_groups_to_unload(&usages, &self.unload_config, self.remove_batch_size, now);
+    self
+      .metrics_calculate
+      .group_resident_bytes
+      .set(resident_bytes as i64);
+
+    let mut unloaded = Vec::with_capacity(selected.len());
+    for (i, reason) in selected {
+      let (object_id, group) = &groups[i];
+      if self.group_by_object_id.remove(object_id).is_none() {
+        continue;
       }
+      if reason == UnloadReason::MemoryBudget {
+        info!(
+          "unload group:{} over memory budget, last active {:?} ago",
+          object_id,
+          now.saturating_duration_since(usages[i].last_activity)
+        );
+      } else {
+        trace!("unload group:{} ({})", object_id, reason.as_str());
+      }
+      self.metrics_calculate.record_group_unload(reason.as_str());
+      self.unloaded_at.insert(object_id.clone(), now);
+      unloaded.push((object_id.clone(), group.clone(), reason));
     }
-    if !inactive_group_ids.is_empty() {
-      trace!("inactive group ids:{:?}", inactive_group_ids);
-    }
-    for object_id in &inactive_group_ids {
-      self.remove_group(object_id);
-    }
-    inactive_group_ids
+    self
+      .unloaded_at
+      .retain(|_, unloaded_at| now.saturating_duration_since(*unloaded_at) < Self::RELOAD_WINDOW);
+    self
+      .metrics_calculate
+      .opening_collab_count
+      .set(self.group_by_object_id.len() as i64);
+    unloaded
+  }
+
+  /// Removes all the groups, to be stopped.
+  pub(crate) fn take_all_groups(&self) -> Vec<Arc<CollabGroup>> {
+    let object_ids = self
+      .group_by_object_id
+      .iter()
+      .map(|entry| entry.key().clone())
+      .collect::<Vec<_>>();
+    let groups = object_ids
+      .iter()
+      .filter_map(|object_id| self.group_by_object_id.remove(object_id))
+      .map(|(_, group)| group)
+      .collect();
+    self.metrics_calculate.opening_collab_count.set(0);
+    groups
   }
 
   pub async fn get_group(&self, object_id: &str) -> Option<Arc<CollabGroup>> {
@@ -113,6 +174,9 @@ impl GroupManagementState {
       .group_by_object_id
       .insert(object_id.to_string(), group.into());
     self.metrics_calculate.opening_collab_count.inc();
+    if self.unloaded_at.remove(object_id).is_some() {
+      self.metrics_calculate.group_reload_count.inc();
+    }
   }
 
   pub(crate) fn contains_group(&self, object_id: &str) -> bool {
@@ -124,18 +188,6 @@ impl GroupManagementState {
     }
   }
 
-  pub(crate) fn remove_group(&self, object_id: &str) {
-    let group_not_found = self.group_by_object_id.remove(object_id).is_none();
-    if group_not_found {
-      // Log error if the group doesn't exist
-      error!("Group for object_id:{} not found", object_id);
-    }
-
-    self
-      .metrics_calculate
-      .opening_collab_count
-      .set(self.group_by_object_id.len() as i64);
-  }
   pub(crate) fn insert_user(
     &self,
     user: &RealtimeUser,
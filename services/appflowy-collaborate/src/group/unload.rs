@@ -0,0 +1,215 @@
+use std::time::{Duration, Instant};
+
+use crate::config::get_env_var;
+
+/// When the collab groups are unloaded from the memory of the realtime server. An unloaded group
+/// is loaded again on the next message a client sends for its collab.
+#[derive(Debug, Clone)]
+pub struct GroupUnloadConfig {
+  /// Groups without activity for this long are unloaded, even when users are still subscribed.
+  pub idle_timeout: Duration,
+  /// Estimated bytes the loaded groups may hold together. Beyond it, the least recently active
+  /// groups are unloaded until they fit. `0` disables the budget.
+  pub memory_budget_bytes: usize,
+  pub check_interval: Duration,
+  /// Time the final save of an unloaded group is waited for.
+  pub flush_timeout: Duration,
+}
+
+impl Default for GroupUnloadConfig {
+  fn default() -> Self {
+    Self {
+      idle_timeout: Duration::from_secs(3 * 60 * 60),
+      memory_budget_bytes: 1024 * 1024 * 1024,
+      check_interval: Duration::from_secs(20),
+      flush_timeout: Duration::from_secs(30),
+    }
+  }
+}
+
+impl GroupUnloadConfig {
+  pub fn from_env() -> Result<Self, anyhow::Error> {
+    let defaults = Self::default();
+    let config = Self {
+      idle_timeout: Duration::from_secs(
+        get_env_var(
+          "APPFLOWY_COLLAB_GROUP_IDLE_TIMEOUT_SECS",
+          &defaults.idle_timeout.as_secs().to_string(),
+        )
+        .parse()?,
+      ),
+      memory_budget_bytes: get_env_var(
+        "APPFLOWY_COLLAB_GROUP_MEMORY_BUDGET_MB",
+        &(defaults.memory_budget_bytes / (1024 * 1024)).to_string(),
+      )
+      .parse::<usize>()?
+        * 1024
+        * 1024,
+      check_interval: Duration::from_secs(
+        get_env_var(
+          "APPFLOWY_COLLAB_GROUP_UNLOAD_CHECK_INTERVAL_SECS",
+          &defaults.check_interval.as_secs().to_string(),
+        )
+        .parse()?,
+      ),
+      flush_timeout: Duration::from_secs(
+        get_env_var(
+          "APPFLOWY_COLLAB_GROUP_FLUSH_TIMEOUT_SECS",
+          &defaults.flush_timeout.as_secs().to_string(),
+        )
+        .parse()?,
+      ),
+    };
+    if config.idle_timeout.is_zero() || config.check_interval.is_zero() {
+      anyhow::bail!("the idle timeout and check interval of the collab groups must not be zero");
+    }
+    Ok(config)
+  }
+}
+
+/// Why a group was unloaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnloadReason {
+  /// The last user subscribed to it left.
+  NoSubscribers,
+  /// It saw no activity for [GroupUnloadConfig::idle_timeout].
+  Idle,
+  /// It was among the least recently active groups when the memory budget was exceeded.
+  MemoryBudget,
+}
+
+impl UnloadReason {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      UnloadReason::NoSubscribers => "no_subscribers",
+      UnloadReason::Idle => "idle",
+      UnloadReason::MemoryBudget => "memory_budget",
+    }
+  }
+}
+
+/// What the unloading of a loaded group is decided on.
+#[derive(Debug, Clone, Copy)]
+pub struct GroupUsage {
+  pub last_activity: Instant,
+  pub subscriber_count: usize,
+  pub resident_bytes: usize,
+}
+
+/// The groups to unload, as indexes into `groups` along with the reason, and the estimated bytes
+/// held by the groups kept. At most `max_idle` groups are unloaded for being idle, the groups
+/// exceeding the memory budget are all unloaded.
+pub fn select_groups_to_unload(
+  groups: &[GroupUsage],
+  config: &GroupUnloadConfig,
+  max_idle: usize,
+  now: Instant,
+) -> (Vec<(usize, UnloadReason)>, usize) {
+  let mut unloaded = Vec::new();
+  let mut kept = Vec::with_capacity(groups.len());
+  for (i, group) in groups.iter().enumerate() {
+    let reason = if now.saturating_duration_since(group.last_activity) > config.idle_timeout {
+      Some(UnloadReason::Idle)
+    } else if group.subscriber_count == 0 {
+      Some(UnloadReason::NoSubscribers)
+    } else {
+      None
+    };
+    match reason {
+      Some(reason) if unloaded.len() < max_idle => unloaded.push((i, reason)),
+      _ => kept.push(i),
+    }
+  }
+
+  let mut resident_bytes = kept
+    .iter()
+    .map(|i| groups[*i].resident_bytes)
+    .sum::<usize>();
+  if config.memory_budget_bytes > 0 && resident_bytes > config.memory_budget_bytes {
+    kept.sort_by_key(|i| groups[*i].last_activity);
+    for i in kept {
+      if resident_bytes <= config.memory_budget_bytes {
+        break;
+      }
+      resident_bytes -= groups[i].resident_bytes;
+      unloaded.push((i, UnloadReason::MemoryBudget));
+    }
+  }
+  (unloaded, resident_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn usage(
+    now: Instant,
+    idle_secs: u64,
+    subscriber_count: usize,
+    resident_bytes: usize,
+  ) -> GroupUsage {
+    GroupUsage {
+      last_activity: now - Duration::from_secs(idle_secs),
+      subscriber_count,
+      resident_bytes,
+    }
+  }
+
+  #[test]
+  fn idle_groups_are_unloaded_test() {
+    let now = Instant::now() + Duration::from_secs(4 * 60 * 60);
+    let config = GroupUnloadConfig {
+      idle_timeout: Duration::from_secs(600),
+      memory_budget_bytes: 0,
+      ..Default::default()
+    };
+    let groups = [
+      usage(now, 10, 1, 100),
+      usage(now, 601, 2, 100),
+      usage(now, 10, 0, 100),
+    ];
+    let (unloaded, resident_bytes) = select_groups_to_unload(&groups, &config, 50, now);
+    assert_eq!(
+      unloaded,
+      vec![(1, UnloadReason::Idle), (2, UnloadReason::NoSubscribers)]
+    );
+    assert_eq!(resident_bytes, 100);
+
+    let (unloaded, _) = select_groups_to_unload(&groups, &config, 1, now);
+    assert_eq!(unloaded, vec![(1, UnloadReason::Idle)]);
+  }
+
+  #[test]
+  fn least_recently_active_groups_are_unloaded_over_budget_test() {
+    let now = Instant::now() + Duration::from_secs(60);
+    let config = GroupUnloadConfig {
+      idle_timeout: Duration::from_secs(600),
+      memory_budget_bytes: 250,
+      ..Default::default()
+    };
+    let groups = [
+      usage(now, 30, 1, 100),
+      usage(now, 50, 1, 100),
+      usage(now, 10, 1, 100),
+      usage(now, 40, 1, 100),
+    ];
+    let (unloaded, resident_bytes) = select_groups_to_unload(&groups, &config, 50, now);
+    assert_eq!(
+      unloaded,
+      vec![
+        (1, UnloadReason::MemoryBudget),
+        (3, UnloadReason::MemoryBudget)
+      ]
+    );
+    assert_eq!(resident_bytes, 200);
+
+    // within the budget, nothing is unloaded
+    let config = GroupUnloadConfig {
+      memory_budget_bytes: 400,
+      ..config
+    };
+    let (unloaded, resident_bytes) = select_groups_to_unload(&groups, &config, 50, now);
+    assert!(unloaded.is_empty());
+    assert_eq!(resident_bytes, 400);
+  }
+}
@@ -0,0 +1,319 @@
+use std::sync::{Arc, Mutex};
+
+use collab_stream::model::MessageId;
+use tokio::sync::Notify;
+use yrs::merge_updates_v1;
+
+use crate::config::get_env_var;
+use crate::metrics::CollabRealtimeMetrics;
+
+/// When the updates of a collab are saved to Postgres. Every update is appended to the Redis
+/// stream of the collab as it arrives, which is what the next group of the collab recovers from
+/// after a crash, and queued in memory by the group, which saves the queued updates merged into
+/// one. Besides [crate::config::CollabSetting::group_persistence_interval_secs], the queue is
+/// saved once it reaches one of these thresholds, and on shutdown.
+#[derive(Debug, Clone)]
+pub struct WriteBehindConfig {
+  /// Number of queued updates which triggers a save.
+  pub max_pending_updates: usize,
+  /// Bytes of queued updates which trigger a save.
+  pub max_pending_bytes: usize,
+  /// Whether the queue is saved as soon as the last subscriber of the collab disconnected,
+  /// rather than when the group is unloaded.
+  pub flush_on_last_disconnect: bool,
+}
+
+impl Default for WriteBehindConfig {
+  fn default() -> Self {
+    Self {
+      max_pending_updates: 500,
+      max_pending_bytes: 4 * 1024 * 1024,
+      flush_on_last_disconnect: true,
+    }
+  }
+}
+
+impl WriteBehindConfig {
+  pub fn from_env() -> Result<Self, anyhow::Error> {
+    let defaults = Self::default();
+    let config = Self {
+      max_pending_updates: get_env_var(
+        "APPFLOWY_COLLAB_WRITE_BEHIND_MAX_UPDATES",
+        &defaults.max_pending_updates.to_string(),
+      )
+      .parse()?,
+      max_pending_bytes: get_env_var(
+        "APPFLOWY_COLLAB_WRITE_BEHIND_MAX_BYTES",
+        &defaults.max_pending_bytes.to_string(),
+      )
+      .parse()?,
+      flush_on_last_disconnect: get_env_var(
+        "APPFLOWY_COLLAB_WRITE_BEHIND_FLUSH_ON_DISCONNECT",
+        &defaults.flush_on_last_disconnect.to_string(),
+      )
+      .parse()?,
+    };
+    if config.max_pending_updates == 0 || config.max_pending_bytes == 0 {
+      anyhow::bail!("the write-behind thresholds must not be zero");
+    }
+    Ok(config)
+  }
+}
+
+/// Why the queue of a collab was saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FlushTrigger {
+  Interval,
+  Threshold,
+  LastDisconnect,
+  Shutdown,
+}
+
+impl FlushTrigger {
+  pub(crate) fn as_str(&self) -> &'static str {
+    match self {
+      FlushTrigger::Interval => "interval",
+      FlushTrigger::Threshold => "threshold",
+      FlushTrigger::LastDisconnect => "last_disconnect",
+      FlushTrigger::Shutdown => "shutdown",
+    }
+  }
+}
+
+/// The queued updates of a collab, merged into one.
+pub(crate) struct PendingBatch {
+  /// yrs::Update::encode_v1 of the merged updates.
+  pub update: Vec<u8>,
+  /// Stream id of the last merged update.
+  pub last_message_id: MessageId,
+  /// Stream id and length of each merged update.
+  pub edits: Vec<(MessageId, usize)>,
+}
+
+/// Updates read from the Redis stream of a collab and not saved yet, in stream order.
+#[derive(Default)]
+struct PendingUpdates {
+  /// The updates, the ones already merged by [PendingUpdates::batch] kept as a single entry
+  /// under the id of the last of them.
+  updates: Vec<(MessageId, Vec<u8>)>,
+  edits: Vec<(MessageId, usize)>,
+  bytes: usize,
+}
+
+impl PendingUpdates {
+  fn push(&mut self, message_id: MessageId, update: Vec<u8>) {
+    self.bytes += update.len();
+    self.edits.push((message_id, update.len()));
+    self.updates.push((message_id, update));
+  }
+
+  fn len(&self) -> usize {
+    self.edits.len()
+  }
+
+  fn reaches(&self, config: &WriteBehindConfig) -> bool {
+    self.len() >= config.max_pending_updates || self.bytes >= config.max_pending_bytes
+  }
+
+  /// Merges the queued updates into one, which replaces them in the queue so that the next
+  /// batch only merges the updates queued since.
+  fn batch(&mut self) -> Result<Option<PendingBatch>, yrs::encoding::read::Error> {
+    let last_message_id = match self.updates.last() {
+      Some((message_id, _)) => *message_id,
+      None => return Ok(None),
+    };
+    if self.updates.len() > 1 {
+      let merged = merge_updates_v1(self.updates.iter().map(|(_, update)| update.as_slice()))?;
+      self.bytes = merged.len();
+      self.updates = vec![(last_message_id, merged)];
+    }
+    Ok(Some(PendingBatch {
+      update: self.updates[0].1.clone(),
+      last_message_id,
+      edits: self.edits.clone(),
+    }))
+  }
+
+  /// Drops the updates up to `message_id`, once they were saved. Returns how many were dropped.
+  fn drain_until(&mut self, message_id: MessageId) -> usize {
+    let len = self.len();
+    self.updates.retain(|(id, _)| *id > message_id);
+    self.edits.retain(|(id, _)| *id > message_id);
+    self.bytes = self.updates.iter().map(|(_, update)| update.len()).sum();
+    len - self.len()
+  }
+}
+
+/// The write-behind queue of a collab group, see [WriteBehindConfig].
+pub(crate) struct WriteBehindQueue {
+  pending: Mutex<PendingUpdates>,
+  flush_requested: Notify,
+  /// Why the save was requested, taken by [WriteBehindQueue::flush_requested].
+  requested_by: Mutex<Option<FlushTrigger>>,
+  config: WriteBehindConfig,
+  metrics: Arc<CollabRealtimeMetrics>,
+}
+
+impl WriteBehindQueue {
+  pub(crate) fn new(config: WriteBehindConfig, metrics: Arc<CollabRealtimeMetrics>) -> Self {
+    Self {
+      pending: Mutex::new(PendingUpdates::default()),
+      flush_requested: Notify::new(),
+      requested_by: Mutex::new(None),
+      config,
+      metrics,
+    }
+  }
+
+  /// Queues an update read from the Redis stream, and requests a save when it makes the queue
+  /// reach a threshold.
+  pub(crate) fn push(&self, message_id: MessageId, update: Vec<u8>) {
+    let mut pending = self.pending.lock().unwrap();
+    let reached = pending.reaches(&self.config);
+    pending.push(message_id, update);
+    self.metrics.write_behind_queue_depth.inc();
+    // requested once when the threshold is crossed: a failed save is retried on the next interval
+    if !reached && pending.reaches(&self.config) {
+      drop(pending);
+      self.request_flush(FlushTrigger::Threshold);
+    }
+  }
+
+  /// Requests a save of the queue, unless it's empty.
+  pub(crate) fn request_flush(&self, trigger: FlushTrigger) {
+    if !self.is_empty() {
+      *self.requested_by.lock().unwrap() = Some(trigger);
+      self.flush_requested.notify_one();
+    }
+  }
+
+  /// Completes once a save was requested since the last call, with what requested it.
+  pub(crate) async fn flush_requested(&self) -> FlushTrigger {
+    self.flush_requested.notified().await;
+    self
+      .requested_by
+      .lock()
+      .unwrap()
+      .take()
+      .unwrap_or(FlushTrigger::Threshold)
+  }
+
+  pub(crate) fn config(&self) -> &WriteBehindConfig {
+    &self.config
+  }
+
+  pub(crate) fn is_empty(&self) -> bool {
+    self.pending.lock().unwrap().len() == 0
+  }
+
+  pub(crate) fn bytes(&self) -> usize {
+    self.pending.lock().unwrap().bytes
+  }
+
+  pub(crate) fn batch(&self) -> Result<Option<PendingBatch>, yrs::encoding::read::Error> {
+    self.pending.lock().unwrap().batch()
+  }
+
+  pub(crate) fn drain_until(&self, message_id: MessageId) {
+    let drained = self.pending.lock().unwrap().drain_until(message_id);
+    self.metrics.write_behind_queue_depth.dec_by(drained as i64);
+  }
+
+  /// Drops every queued update: they were saved from the Redis stream, or no longer apply to the
+  /// collab after it was reset.
+  pub(crate) fn clear(&self) {
+    let drained = std::mem::take(&mut *self.pending.lock().unwrap()).len();
+    self.metrics.write_behind_queue_depth.dec_by(drained as i64);
+  }
+}
+
+impl Drop for WriteBehindQueue {
+  fn drop(&mut self) {
+    self.clear();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use yrs::updates::decoder::Decode;
+  use yrs::{Doc, GetString, ReadTxn, Text, Transact, Update};
+
+  fn id(timestamp_ms: u64) -> MessageId {
+    MessageId::new(timestamp_ms, 0)
+  }
+
+  /// Updates of a collab inserting `a`, `b`, ... into its text, one after the other.
+  fn text_updates(count: usize) -> Vec<Vec<u8>> {
+    let doc = Doc::new();
+    let text = doc.get_or_insert_text("text");
+    let mut updates = vec![];
+    for i in 0..count {
+      let mut txn = doc.transact_mut();
+      let before = txn.state_vector();
+      let len = text.len(&txn);
+      text.insert(&mut txn, len, &((b'a' + i as u8) as char).to_string());
+      updates.push(txn.encode_state_as_update_v1(&before));
+    }
+    updates
+  }
+
+  #[test]
+  fn threshold_reached_test() {
+    let config = WriteBehindConfig {
+      max_pending_updates: 2,
+      max_pending_bytes: 1000,
+      flush_on_last_disconnect: true,
+    };
+    let mut pending = PendingUpdates::default();
+    pending.push(id(1), vec![0; 10]);
+    assert!(!pending.reaches(&config));
+    pending.push(id(2), vec![0; 10]);
+    assert!(pending.reaches(&config));
+
+    let mut pending = PendingUpdates::default();
+    pending.push(id(1), vec![0; 1000]);
+    assert!(pending.reaches(&config));
+  }
+
+  #[test]
+  fn batch_merges_updates_test() {
+    let mut pending = PendingUpdates::default();
+    for (i, update) in text_updates(3).into_iter().enumerate() {
+      pending.push(id(i as u64 + 1), update);
+    }
+    let batch = pending.batch().unwrap().unwrap();
+    assert_eq!(batch.last_message_id, id(3));
+    assert_eq!(batch.edits.len(), 3);
+    // the merged update replaces the queued ones, its edits are still counted one by one
+    assert_eq!(pending.updates.len(), 1);
+    assert_eq!(pending.len(), 3);
+
+    let doc = Doc::new();
+    let text = doc.get_or_insert_text("text");
+    let mut txn = doc.transact_mut();
+    txn
+      .apply_update(Update::decode_v1(&batch.update).unwrap())
+      .unwrap();
+    assert_eq!(text.get_string(&txn), "abc");
+  }
+
+  #[test]
+  fn drain_keeps_later_updates_test() {
+    let mut pending = PendingUpdates::default();
+    let mut updates = text_updates(3).into_iter();
+    pending.push(id(1), updates.next().unwrap());
+    pending.push(id(2), updates.next().unwrap());
+    let batch = pending.batch().unwrap().unwrap();
+    // queued while the batch was saved
+    pending.push(id(3), updates.next().unwrap());
+
+    assert_eq!(pending.drain_until(batch.last_message_id), 2);
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending.edits[0].0, id(3));
+    assert_eq!(pending.bytes, pending.updates[0].1.len());
+    assert!(pending.batch().unwrap().is_some());
+    assert_eq!(pending.drain_until(id(3)), 1);
+    assert!(pending.batch().unwrap().is_none());
+  }
+}
@@ -1,6 +1,7 @@
 pub mod actix_ws;
 pub mod api;
 pub mod application;
+pub mod bandwidth;
 mod client;
 pub mod collab;
 pub mod command;
@@ -8,10 +9,13 @@ pub mod compression;
 pub mod config;
 pub mod connect_state;
 pub mod error;
+pub mod feature_flags;
 pub mod group;
+pub mod load_shed;
 pub mod metrics;
 mod permission;
 mod pg_listener;
+pub mod rate_limit;
 mod rt_server;
 pub mod snapshot;
 mod state;
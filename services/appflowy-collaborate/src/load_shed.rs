@@ -0,0 +1,401 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use app_error::AppError;
+use database::collab::CollabStorageAccessControl;
+use tokio::time::{interval, timeout, MissedTickBehavior};
+use tracing::{trace, warn};
+
+use crate::collab::cache::CollabCache;
+use crate::collab::storage::CollabStorageImpl;
+use crate::config::get_env_var;
+use crate::metrics::LoadShedMetrics;
+
+/// A probe which doesn't answer within this time counts as this latency.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Part of the pressure kept from one sample to the next once the load goes down, so that the
+/// shedding stops gradually instead of flapping.
+const PRESSURE_DECAY: f64 = 0.5;
+
+/// The traffic rejected when the collab write path is overloaded, in the order it's shed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShedTier {
+  /// Awareness updates, such as the cursors and selections of the other users.
+  Awareness,
+  /// Syncs requested by the subscribers to catch up with a collab, which only read it.
+  CatchUp,
+  /// Collab writes sent to the HTTP API by imports and integrations rather than by an editor.
+  ApiWrite,
+  /// Updates of the users editing a collab.
+  Interactive,
+}
+
+impl ShedTier {
+  pub const ALL: [ShedTier; 4] = [
+    ShedTier::Awareness,
+    ShedTier::CatchUp,
+    ShedTier::ApiWrite,
+    ShedTier::Interactive,
+  ];
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      ShedTier::Awareness => "awareness",
+      ShedTier::CatchUp => "catch_up",
+      ShedTier::ApiWrite => "api_write",
+      ShedTier::Interactive => "interactive",
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadShedConfig {
+  pub enabled: bool,
+  /// Collab writes waiting to be persisted at which the queue counts as loaded.
+  pub queue_depth_threshold: usize,
+  pub pg_acquire_threshold: Duration,
+  pub redis_lag_threshold: Duration,
+  /// Pressure from which each tier is shed. The pressure is the highest signal relative to its
+  /// threshold, so a pressure of 1 means that a signal reached its threshold.
+  pub awareness_pressure: f64,
+  pub catch_up_pressure: f64,
+  pub api_write_pressure: f64,
+  pub interactive_pressure: f64,
+  /// Percentage of the API writes shed from `api_write_pressure`. All of them are shed from
+  /// `interactive_pressure`.
+  pub api_write_shed_percent: u64,
+  pub sample_interval: Duration,
+}
+
+impl Default for LoadShedConfig {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      queue_depth_threshold: 800,
+      pg_acquire_threshold: Duration::from_millis(500),
+      redis_lag_threshold: Duration::from_millis(200),
+      awareness_pressure: 1.0,
+      catch_up_pressure: 1.5,
+      api_write_pressure: 2.0,
+      interactive_pressure: 4.0,
+      api_write_shed_percent: 50,
+      sample_interval: Duration::from_secs(1),
+    }
+  }
+}
+
+impl LoadShedConfig {
+  pub fn from_env() -> Result<Self, anyhow::Error> {
+    let defaults = Self::default();
+    let config = Self {
+      enabled: get_env_var(
+        "APPFLOWY_COLLAB_LOAD_SHED_ENABLED",
+        &defaults.enabled.to_string(),
+      )
+      .parse()?,
+      queue_depth_threshold: get_env_var(
+        "APPFLOWY_COLLAB_LOAD_SHED_QUEUE_DEPTH_THRESHOLD",
+        &defaults.queue_depth_threshold.to_string(),
+      )
+      .parse()?,
+      pg_acquire_threshold: Duration::from_millis(
+        get_env_var(
+          "APPFLOWY_COLLAB_LOAD_SHED_PG_ACQUIRE_THRESHOLD_MS",
+          &defaults.pg_acquire_threshold.as_millis().to_string(),
+        )
+        .parse()?,
+      ),
+      redis_lag_threshold: Duration::from_millis(
+        get_env_var(
+          "APPFLOWY_COLLAB_LOAD_SHED_REDIS_LAG_THRESHOLD_MS",
+          &defaults.redis_lag_threshold.as_millis().to_string(),
+        )
+        .parse()?,
+      ),
+      awareness_pressure: get_env_var(
+        "APPFLOWY_COLLAB_LOAD_SHED_AWARENESS_PRESSURE",
+        &defaults.awareness_pressure.to_string(),
+      )
+      .parse()?,
+      catch_up_pressure: get_env_var(
+        "APPFLOWY_COLLAB_LOAD_SHED_CATCH_UP_PRESSURE",
+        &defaults.catch_up_pressure.to_string(),
+      )
+      .parse()?,
+      api_write_pressure: get_env_var(
+        "APPFLOWY_COLLAB_LOAD_SHED_API_WRITE_PRESSURE",
+        &defaults.api_write_pressure.to_string(),
+      )
+      .parse()?,
+      interactive_pressure: get_env_var(
+        "APPFLOWY_COLLAB_LOAD_SHED_INTERACTIVE_PRESSURE",
+        &defaults.interactive_pressure.to_string(),
+      )
+      .parse()?,
+      api_write_shed_percent: get_env_var(
+        "APPFLOWY_COLLAB_LOAD_SHED_API_WRITE_PERCENT",
+        &defaults.api_write_shed_percent.to_string(),
+      )
+      .parse()?,
+      sample_interval: Duration::from_millis(
+        get_env_var(
+          "APPFLOWY_COLLAB_LOAD_SHED_SAMPLE_INTERVAL_MS",
+          &defaults.sample_interval.as_millis().to_string(),
+        )
+        .parse()?,
+      ),
+    };
+    config.validate()?;
+    Ok(config)
+  }
+
+  fn validate(&self) -> Result<(), anyhow::Error> {
+    if self.queue_depth_threshold == 0
+      || self.pg_acquire_threshold.is_zero()
+      || self.redis_lag_threshold.is_zero()
+    {
+      anyhow::bail!("load shedding thresholds must be greater than zero");
+    }
+    if !(self.awareness_pressure <= self.catch_up_pressure
+      && self.catch_up_pressure <= self.api_write_pressure
+      && self.api_write_pressure <= self.interactive_pressure)
+    {
+      anyhow::bail!("load shedding pressures must increase from awareness to interactive tier");
+    }
+    if self.api_write_shed_percent > 100 {
+      anyhow::bail!("APPFLOWY_COLLAB_LOAD_SHED_API_WRITE_PERCENT must be at most 100");
+    }
+    Ok(())
+  }
+
+  /// The highest of the signals relative to its threshold: a single saturated dependency is
+  /// enough to slow down every write.
+  fn pressure_of(&self, sample: &PressureSample) -> f64 {
+    let queue = sample.pending_writes as f64 / self.queue_depth_threshold as f64;
+    let pg = sample.pg_acquire_latency.as_secs_f64() / self.pg_acquire_threshold.as_secs_f64();
+    let redis = sample.redis_lag.as_secs_f64() / self.redis_lag_threshold.as_secs_f64();
+    queue.max(pg).max(redis)
+  }
+}
+
+/// The signals the pressure is computed from.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PressureSample {
+  /// Collab writes queued by the storage and not persisted yet.
+  pub pending_writes: usize,
+  pub pg_acquire_latency: Duration,
+  pub redis_lag: Duration,
+}
+
+impl PressureSample {
+  pub async fn collect(cache: &CollabCache, pending_writes: usize) -> Self {
+    Self {
+      pending_writes,
+      pg_acquire_latency: probe("postgres", cache.pg_acquire_latency()).await,
+      redis_lag: probe("redis", cache.redis_latency()).await,
+    }
+  }
+}
+
+async fn probe(
+  dependency: &str,
+  latency: impl Future<Output = Result<Duration, AppError>>,
+) -> Duration {
+  match timeout(PROBE_TIMEOUT, latency).await {
+    Ok(Ok(latency)) => latency,
+    // a dependency which fails or doesn't answer is as loaded as it gets
+    Ok(Err(err)) => {
+      warn!("failed to probe the latency of {}: {}", dependency, err);
+      PROBE_TIMEOUT
+    },
+    Err(_) => PROBE_TIMEOUT,
+  }
+}
+
+/// Rejects the lowest priority traffic of the collab write path early when its dependencies can't
+/// keep up, rather than letting every write time out. The tiers are shed one after the other as the
+/// pressure grows, the updates of the editors last. Rejected messages and requests fail with a
+/// retryable error.
+pub struct LoadShedder {
+  config: LoadShedConfig,
+  /// Bits of the current pressure.
+  pressure: AtomicU64,
+  /// Counts the API writes, to shed the configured percentage of them.
+  api_writes: AtomicU64,
+  metrics: Arc<LoadShedMetrics>,
+}
+
+impl LoadShedder {
+  pub fn new(config: LoadShedConfig, metrics: Arc<LoadShedMetrics>) -> Self {
+    Self {
+      config,
+      pressure: AtomicU64::new(0f64.to_bits()),
+      api_writes: AtomicU64::new(0),
+      metrics,
+    }
+  }
+
+  pub fn pressure(&self) -> f64 {
+    f64::from_bits(self.pressure.load(Ordering::Relaxed))
+  }
+
+  /// Updates the pressure with a new sample. It follows a rising load right away, and decays
+  /// when the load goes down.
+  pub fn record_sample(&self, sample: &PressureSample) -> f64 {
+    let pressure = self
+      .config
+      .pressure_of(sample)
+      .max(self.pressure() * PRESSURE_DECAY);
+    self.pressure.store(pressure.to_bits(), Ordering::Relaxed);
+    self
+      .metrics
+      .pressure_percent
+      .set((pressure * 100.0).round() as i64);
+    pressure
+  }
+
+  pub fn should_shed(&self, tier: ShedTier) -> bool {
+    if !self.config.enabled {
+      return false;
+    }
+    let pressure = self.pressure();
+    let shed = match tier {
+      ShedTier::Awareness => pressure >= self.config.awareness_pressure,
+      ShedTier::CatchUp => pressure >= self.config.catch_up_pressure,
+      ShedTier::ApiWrite => {
+        pressure >= self.config.interactive_pressure
+          || (pressure >= self.config.api_write_pressure
+            && self.api_writes.fetch_add(1, Ordering::Relaxed) % 100
+              < self.config.api_write_shed_percent)
+      },
+      ShedTier::Interactive => pressure >= self.config.interactive_pressure,
+    };
+    if shed {
+      self.metrics.record_shed(tier.as_str());
+    }
+    shed
+  }
+
+  /// Fails with a retryable error when the tier is shed.
+  pub fn check(&self, tier: ShedTier) -> Result<(), AppError> {
+    if self.should_shed(tier) {
+      return Err(AppError::ServiceTemporaryUnavailable(format!(
+        "The server is overloaded and rejects {} traffic, retry later",
+        tier.as_str()
+      )));
+    }
+    Ok(())
+  }
+}
+
+/// Samples the pressure of the collab write path until the storage is dropped.
+pub fn spawn_load_shed_monitor<AC>(shedder: Arc<LoadShedder>, storage: Weak<CollabStorageImpl<AC>>)
+where
+  AC: CollabStorageAccessControl,
+{
+  if !shedder.config.enabled {
+    return;
+  }
+  tokio::spawn(async move {
+    let mut ticker = interval(shedder.config.sample_interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+      ticker.tick().await;
+      let Some(storage) = storage.upgrade() else {
+        break;
+      };
+      let sample = storage.sample_pressure().await;
+      let pressure = shedder.record_sample(&sample);
+      trace!("load shedding pressure {:.2}: {:?}", pressure, sample);
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn shed_tiers(shedder: &LoadShedder) -> Vec<&'static str> {
+    [
+      ShedTier::Awareness,
+      ShedTier::CatchUp,
+      ShedTier::Interactive,
+    ]
+    .into_iter()
+    .filter(|tier| shedder.should_shed(*tier))
+    .map(|tier| tier.as_str())
+    .collect()
+  }
+
+  #[test]
+  fn tiers_are_shed_in_order_test() {
+    let shedder = LoadShedder::new(LoadShedConfig::default(), Default::default());
+    let queued = |pending_writes| PressureSample {
+      pending_writes,
+      ..Default::default()
+    };
+    assert!(shed_tiers(&shedder).is_empty());
+
+    shedder.record_sample(&queued(800));
+    assert_eq!(shed_tiers(&shedder), vec!["awareness"]);
+    shedder.record_sample(&queued(1200));
+    assert_eq!(shed_tiers(&shedder), vec!["awareness", "catch_up"]);
+    shedder.record_sample(&queued(3200));
+    assert_eq!(
+      shed_tiers(&shedder),
+      vec!["awareness", "catch_up", "interactive"]
+    );
+
+    // the pressure decays once the load goes down
+    shedder.record_sample(&queued(0));
+    assert_eq!(shed_tiers(&shedder), vec!["awareness", "catch_up"]);
+    shedder.record_sample(&queued(0));
+    shedder.record_sample(&queued(0));
+    assert!(shed_tiers(&shedder).is_empty());
+  }
+
+  #[test]
+  fn percentage_of_api_writes_is_shed_test() {
+    let shedder = LoadShedder::new(LoadShedConfig::default(), Default::default());
+    let shed_api_writes = || {
+      (0..100)
+        .filter(|_| shedder.should_shed(ShedTier::ApiWrite))
+        .count()
+    };
+    assert_eq!(shed_api_writes(), 0);
+
+    // the slowest signal decides of the pressure
+    shedder.record_sample(&PressureSample {
+      pending_writes: 10,
+      pg_acquire_latency: Duration::from_millis(1100),
+      redis_lag: Duration::from_millis(1),
+    });
+    assert_eq!(shed_api_writes(), 50);
+    assert!(!shedder.should_shed(ShedTier::Interactive));
+
+    shedder.record_sample(&PressureSample {
+      redis_lag: Duration::from_millis(800),
+      ..Default::default()
+    });
+    assert_eq!(shed_api_writes(), 100);
+    assert_eq!(shedder.metrics.shed_count("api_write"), 150);
+  }
+
+  #[test]
+  fn disabled_shedder_never_sheds_test() {
+    let config = LoadShedConfig {
+      enabled: false,
+      ..Default::default()
+    };
+    let shedder = LoadShedder::new(config, Default::default());
+    shedder.record_sample(&PressureSample {
+      pending_writes: 100_000,
+      ..Default::default()
+    });
+    assert!(ShedTier::ALL
+      .into_iter()
+      .all(|tier| shedder.check(tier).is_ok()));
+  }
+}
@@ -1,8 +1,11 @@
 use chrono::Utc;
+use prometheus_client::encoding::EncodeLabelSet;
 use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
 use prometheus_client::metrics::gauge::Gauge;
 use prometheus_client::metrics::histogram::Histogram;
 use prometheus_client::registry::Registry;
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct CollabRealtimeMetrics {
@@ -25,6 +28,64 @@ pub struct CollabRealtimeMetrics {
   pub(crate) full_collab_size: Histogram,
   /// How long does it take since collab update is send to a stream to be read from it.
   pub(crate) collab_stream_latency: Histogram,
+  /// Number of collabs opened from a snapshot because their stored state could not be decoded.
+  pub(crate) recovered_collab_count: Gauge,
+  /// Number of collabs each collab migration was applied to, by outcome.
+  pub(crate) collab_migration_count: Family<CollabMigrationLabel, Counter>,
+  /// Estimated bytes held by the loaded groups, as of the last unload check.
+  pub(crate) group_resident_bytes: Gauge,
+  /// Number of groups unloaded, by reason.
+  pub(crate) group_unload_count: Family<GroupUnloadLabel, Counter>,
+  /// Number of groups loaded again within an hour of being unloaded.
+  pub(crate) group_reload_count: Counter,
+  /// Number of updates applied to a collab between two of its restore points.
+  pub(crate) snapshot_edit_updates: Histogram,
+  /// Seconds between two restore points of a collab.
+  pub(crate) snapshot_edit_interval: Histogram,
+  /// Number of restore points taken, by collab type and by the threshold which was reached.
+  pub(crate) snapshot_trigger_count: Family<SnapshotTriggerLabel, Counter>,
+  /// Number of collab updates dropped because their connection exceeded its rate limit.
+  pub(crate) throttled_update_count: Family<WorkspaceLabel, Counter>,
+  /// Number of connections closed because they kept exceeding their rate limit.
+  pub(crate) rate_limit_disconnect_count: Counter,
+  /// Number of reconnecting clients which got the updates they missed from the replay buffer.
+  pub(crate) replay_hit_count: Counter,
+  /// Number of reconnecting clients whose missed updates were no longer buffered.
+  pub(crate) replay_miss_count: Counter,
+  /// Number of updates queued by the groups and not saved yet, see
+  /// [crate::group::write_behind::WriteBehindConfig].
+  pub(crate) write_behind_queue_depth: Gauge,
+  /// How long it takes to save the queued updates of a collab, in milliseconds.
+  pub(crate) write_behind_flush_latency: Histogram,
+  /// Number of saves of the queued updates of a collab, by trigger.
+  pub(crate) write_behind_flush_count: Family<WriteBehindFlushLabel, Counter>,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct WorkspaceLabel {
+  pub workspace_id: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct SnapshotTriggerLabel {
+  pub collab_type: String,
+  pub trigger: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct GroupUnloadLabel {
+  pub reason: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct WriteBehindFlushLabel {
+  pub trigger: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct CollabMigrationLabel {
+  pub migration: String,
+  pub result: String,
 }
 
 impl CollabRealtimeMetrics {
@@ -64,8 +125,36 @@ impl CollabRealtimeMetrics {
         ]
         .into_iter(),
       ),
+      // updates between two restore points: 10, 50, 100, 500, 1000, 2000, 5000, 10000
+      snapshot_edit_updates: Histogram::new(
+        [10.0, 50.0, 100.0, 500.0, 1000.0, 2000.0, 5000.0, 10000.0].into_iter(),
+      ),
+      // seconds between two restore points: 1min, 5min, 15min, 30min, 1h, 6h, 12h, 24h
+      snapshot_edit_interval: Histogram::new(
+        [
+          60.0, 300.0, 900.0, 1800.0, 3600.0, 21600.0, 43200.0, 86400.0,
+        ]
+        .into_iter(),
+      ),
+      // time spent on saving the queued updates in milliseconds: 5ms, 20ms, 50ms, 100ms, 250ms,
+      // 500ms, 1s, 5s
+      write_behind_flush_latency: Histogram::new(
+        [5.0, 20.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 5000.0].into_iter(),
+      ),
+      write_behind_queue_depth: Default::default(),
+      write_behind_flush_count: Default::default(),
+      snapshot_trigger_count: Default::default(),
+      throttled_update_count: Default::default(),
+      rate_limit_disconnect_count: Default::default(),
+      replay_hit_count: Default::default(),
+      replay_miss_count: Default::default(),
       load_collab_count: Default::default(),
       load_full_collab_count: Default::default(),
+      recovered_collab_count: Default::default(),
+      collab_migration_count: Default::default(),
+      group_resident_bytes: Default::default(),
+      group_unload_count: Default::default(),
+      group_reload_count: Default::default(),
     }
   }
 
@@ -79,7 +168,7 @@ impl CollabRealtimeMetrics {
     );
     realtime_registry.register(
       "opening_collab_count",
-      "number of opening collabs",
+      "number of loaded collab groups",
       metrics.opening_collab_count.clone(),
     );
     realtime_registry.register(
@@ -127,9 +216,144 @@ impl CollabRealtimeMetrics {
       "latency since collab update is send to a stream to be read from it",
       metrics.collab_stream_latency.clone(),
     );
+    realtime_registry.register(
+      "recovered_collab_count",
+      "number of collabs opened from a snapshot because their stored state could not be decoded",
+      metrics.recovered_collab_count.clone(),
+    );
+    realtime_registry.register(
+      "collab_migration_count",
+      "number of collabs a collab migration was applied to, by migration and result",
+      metrics.collab_migration_count.clone(),
+    );
+    realtime_registry.register(
+      "group_resident_bytes",
+      "estimated bytes held by the loaded collab groups",
+      metrics.group_resident_bytes.clone(),
+    );
+    realtime_registry.register(
+      "group_unload_count",
+      "number of collab groups unloaded, by reason",
+      metrics.group_unload_count.clone(),
+    );
+    realtime_registry.register(
+      "group_reload_count",
+      "number of collab groups loaded again within an hour of being unloaded",
+      metrics.group_reload_count.clone(),
+    );
+    realtime_registry.register(
+      "snapshot_edit_updates",
+      "number of updates applied to a collab between two of its restore points",
+      metrics.snapshot_edit_updates.clone(),
+    );
+    realtime_registry.register(
+      "snapshot_edit_interval",
+      "seconds between two restore points of a collab",
+      metrics.snapshot_edit_interval.clone(),
+    );
+    realtime_registry.register(
+      "snapshot_trigger_count",
+      "number of restore points taken, by collab type and trigger",
+      metrics.snapshot_trigger_count.clone(),
+    );
+    realtime_registry.register(
+      "throttled_update_count",
+      "number of collab updates dropped by the rate limit of their connection, by workspace",
+      metrics.throttled_update_count.clone(),
+    );
+    realtime_registry.register(
+      "rate_limit_disconnect_count",
+      "number of connections closed because they kept exceeding their rate limit",
+      metrics.rate_limit_disconnect_count.clone(),
+    );
+    realtime_registry.register(
+      "replay_hit_count",
+      "number of reconnecting clients which got their missed updates from the replay buffer, the \
+      hit ratio is hits / (hits + misses)",
+      metrics.replay_hit_count.clone(),
+    );
+    realtime_registry.register(
+      "replay_miss_count",
+      "number of reconnecting clients which had to sync the whole collab because their missed \
+      updates were no longer buffered",
+      metrics.replay_miss_count.clone(),
+    );
+    realtime_registry.register(
+      "write_behind_queue_depth",
+      "number of collab updates queued by the groups and not saved yet",
+      metrics.write_behind_queue_depth.clone(),
+    );
+    realtime_registry.register(
+      "write_behind_flush_latency",
+      "time spent on saving the queued updates of a collab in milliseconds",
+      metrics.write_behind_flush_latency.clone(),
+    );
+    realtime_registry.register(
+      "write_behind_flush_count",
+      "number of saves of the queued updates of a collab, by trigger",
+      metrics.write_behind_flush_count.clone(),
+    );
     metrics
   }
 
+  pub(crate) fn record_snapshot_trigger(
+    &self,
+    collab_type: &str,
+    trigger: &str,
+    updates: u64,
+    interval: Duration,
+  ) {
+    self
+      .snapshot_trigger_count
+      .get_or_create(&SnapshotTriggerLabel {
+        collab_type: collab_type.to_string(),
+        trigger: trigger.to_string(),
+      })
+      .inc();
+    self.snapshot_edit_updates.observe(updates as f64);
+    self.snapshot_edit_interval.observe(interval.as_secs_f64());
+  }
+
+  pub(crate) fn record_throttled_updates(&self, workspace_id: &str, count: usize) {
+    self
+      .throttled_update_count
+      .get_or_create(&WorkspaceLabel {
+        workspace_id: workspace_id.to_string(),
+      })
+      .inc_by(count as u64);
+  }
+
+  pub(crate) fn record_group_unload(&self, reason: &str) {
+    self
+      .group_unload_count
+      .get_or_create(&GroupUnloadLabel {
+        reason: reason.to_string(),
+      })
+      .inc();
+  }
+
+  pub(crate) fn record_write_behind_flush(&self, trigger: &str, elapsed: Duration) {
+    self
+      .write_behind_flush_count
+      .get_or_create(&WriteBehindFlushLabel {
+        trigger: trigger.to_string(),
+      })
+      .inc();
+    self
+      .write_behind_flush_latency
+      .observe(elapsed.as_millis() as f64);
+  }
+
+  pub(crate) fn record_collab_migration(&self, migration: &str, result: &str) {
+    self
+      .collab_migration_count
+      .get_or_create(&CollabMigrationLabel {
+        migration: migration.to_string(),
+        result: result.to_string(),
+      })
+      .inc();
+  }
+
   pub fn observe_collab_stream_latency(&self, message_id_timestamp: u64) {
     let now = Utc::now().timestamp_millis() as u64;
     if now > message_id_timestamp {
@@ -140,6 +364,56 @@ impl CollabRealtimeMetrics {
   }
 }
 
+/// Decisions of the [crate::load_shed::LoadShedder].
+#[derive(Clone, Default)]
+pub struct LoadShedMetrics {
+  /// Number of messages and requests rejected, by tier.
+  pub(crate) shed_count: Family<LoadShedTierLabel, Counter>,
+  /// The last computed pressure, in percent of the thresholds.
+  pub(crate) pressure_percent: Gauge,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct LoadShedTierLabel {
+  pub tier: String,
+}
+
+impl LoadShedMetrics {
+  pub fn register(registry: &mut Registry) -> Self {
+    let metrics = Self::default();
+    let load_shed_registry = registry.sub_registry_with_prefix("load_shed");
+    load_shed_registry.register(
+      "shed_count",
+      "number of messages and requests rejected because of the load, by tier",
+      metrics.shed_count.clone(),
+    );
+    load_shed_registry.register(
+      "pressure_percent",
+      "load of the collab write path, in percent of the shedding thresholds",
+      metrics.pressure_percent.clone(),
+    );
+    metrics
+  }
+
+  pub fn shed_count(&self, tier: &str) -> u64 {
+    self
+      .shed_count
+      .get_or_create(&LoadShedTierLabel {
+        tier: tier.to_string(),
+      })
+      .get()
+  }
+
+  pub(crate) fn record_shed(&self, tier: &str) {
+    self
+      .shed_count
+      .get_or_create(&LoadShedTierLabel {
+        tier: tier.to_string(),
+      })
+      .inc();
+  }
+}
+
 #[derive(Clone)]
 pub struct CollabMetrics {
   pub write_snapshot: Counter,
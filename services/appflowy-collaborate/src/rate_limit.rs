@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use collab_rt_entity::user::RealtimeUser;
+use collab_rt_entity::ClientCollabMessage;
+use dashmap::DashMap;
+
+use crate::config::get_env_var;
+
+/// Limits of the collab updates sent by a websocket connection. The initial sync of a collab,
+/// awareness and other messages aren't limited.
+#[derive(Debug, Clone)]
+pub struct UpdateRateLimitConfig {
+  pub enabled: bool,
+  /// Updates a connection may send per second, over all its collabs.
+  pub connection_updates_per_sec: u32,
+  /// Updates a connection may send at once, ie. after a pause.
+  pub connection_burst: u32,
+  /// Updates a connection may send per second for a single collab. `0` disables the limit.
+  pub object_updates_per_sec: u32,
+  pub object_burst: u32,
+  /// A connection which gets this many updates dropped within `abuse_window` is kicked off.
+  pub max_throttled_per_window: u32,
+  pub abuse_window: Duration,
+}
+
+impl Default for UpdateRateLimitConfig {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      connection_updates_per_sec: 100,
+      connection_burst: 200,
+      object_updates_per_sec: 0,
+      object_burst: 100,
+      max_throttled_per_window: 1000,
+      abuse_window: Duration::from_secs(60),
+    }
+  }
+}
+
+impl UpdateRateLimitConfig {
+  pub fn from_env() -> Result<Self, anyhow::Error> {
+    let defaults = Self::default();
+    let config = Self {
+      enabled: get_env_var(
+        "APPFLOWY_COLLAB_UPDATE_RATE_LIMIT_ENABLED",
+        &defaults.enabled.to_string(),
+      )
+      .parse()?,
+      connection_updates_per_sec: get_env_var(
+        "APPFLOWY_COLLAB_UPDATE_RATE_LIMIT_PER_SEC",
+        &defaults.connection_updates_per_sec.to_string(),
+      )
+      .parse()?,
+      connection_burst: get_env_var(
+        "APPFLOWY_COLLAB_UPDATE_RATE_LIMIT_BURST",
+        &defaults.connection_burst.to_string(),
+      )
+      .parse()?,
+      object_updates_per_sec: get_env_var(
+        "APPFLOWY_COLLAB_UPDATE_RATE_LIMIT_OBJECT_PER_SEC",
+        &defaults.object_updates_per_sec.to_string(),
+      )
+      .parse()?,
+      object_burst: get_env_var(
+        "APPFLOWY_COLLAB_UPDATE_RATE_LIMIT_OBJECT_BURST",
+        &defaults.object_burst.to_string(),
+      )
+      .parse()?,
+      max_throttled_per_window: get_env_var(
+        "APPFLOWY_COLLAB_UPDATE_RATE_LIMIT_MAX_THROTTLED",
+        &defaults.max_throttled_per_window.to_string(),
+      )
+      .parse()?,
+      abuse_window: Duration::from_secs(
+        get_env_var(
+          "APPFLOWY_COLLAB_UPDATE_RATE_LIMIT_ABUSE_WINDOW_SECS",
+          &defaults.abuse_window.as_secs().to_string(),
+        )
+        .parse()?,
+      ),
+    };
+    if config.enabled && (config.connection_updates_per_sec == 0 || config.abuse_window.is_zero()) {
+      anyhow::bail!("the update rate limit and its abuse window must not be zero");
+    }
+    Ok(config)
+  }
+}
+
+struct TokenBucket {
+  /// Tokens added per second.
+  rate: f64,
+  capacity: f64,
+  tokens: f64,
+  refilled_at: Instant,
+}
+
+impl TokenBucket {
+  fn new(rate: u32, burst: u32, now: Instant) -> Self {
+    let capacity = burst.max(1) as f64;
+    Self {
+      rate: rate as f64,
+      capacity,
+      tokens: capacity,
+      refilled_at: now,
+    }
+  }
+
+  /// Takes a token, or returns the time until the next one is available.
+  fn try_take(&mut self, now: Instant) -> Result<(), Duration> {
+    let elapsed = now
+      .saturating_duration_since(self.refilled_at)
+      .as_secs_f64();
+    self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+    self.refilled_at = now;
+    if self.tokens >= 1.0 {
+      self.tokens -= 1.0;
+      Ok(())
+    } else {
+      Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+    }
+  }
+}
+
+struct ObjectLimit {
+  /// Learnt from the initial sync of the collab, to report the throttled updates by workspace.
+  workspace_id: Option<String>,
+  bucket: Option<TokenBucket>,
+}
+
+struct ConnectionLimit {
+  bucket: TokenBucket,
+  objects: HashMap<String, ObjectLimit>,
+  window_started_at: Instant,
+  throttled_in_window: u32,
+  /// The connection isn't notified again before this time.
+  notify_after: Option<Instant>,
+}
+
+/// What to do with the messages a connection sent for a collab.
+#[derive(Debug, Default)]
+pub struct RateLimitOutcome {
+  /// The messages to handle. The updates over the limit are left out.
+  pub messages: Vec<ClientCollabMessage>,
+  pub workspace_id: Option<String>,
+  pub throttled: usize,
+  /// Set when the connection should be told to retry after this time.
+  pub notify_retry_after: Option<Duration>,
+  /// The connection kept exceeding the limit and should be closed.
+  pub disconnect: bool,
+}
+
+/// Token buckets of the collab updates of each connection, see [UpdateRateLimitConfig].
+pub struct UpdateRateLimiter {
+  config: UpdateRateLimitConfig,
+  connections: DashMap<RealtimeUser, ConnectionLimit>,
+}
+
+impl UpdateRateLimiter {
+  pub fn new(config: UpdateRateLimitConfig) -> Self {
+    Self {
+      config,
+      connections: DashMap::new(),
+    }
+  }
+
+  pub fn check(
+    &self,
+    user: &RealtimeUser,
+    object_id: &str,
+    messages: Vec<ClientCollabMessage>,
+  ) -> RateLimitOutcome {
+    self.check_at(user, object_id, messages, Instant::now())
+  }
+
+  fn check_at(
+    &self,
+    user: &RealtimeUser,
+    object_id: &str,
+    messages: Vec<ClientCollabMessage>,
+    now: Instant,
+  ) -> RateLimitOutcome {
+    if !self.config.enabled {
+      return RateLimitOutcome {
+        messages,
+        ..Default::default()
+      };
+    }
+    let config = &self.config;
+    let mut connection = self
+      .connections
+      .entry(user.clone())
+      .or_insert_with(|| ConnectionLimit {
+        bucket: TokenBucket::new(
+          config.connection_updates_per_sec,
+          config.connection_burst,
+          now,
+        ),
+        objects: HashMap::new(),
+        window_started_at: now,
+        throttled_in_window: 0,
+        notify_after: None,
+      });
+    let connection = &mut *connection;
+    let object = connection
+      .objects
+      .entry(object_id.to_string())
+      .or_insert_with(|| ObjectLimit {
+        workspace_id: None,
+        bucket: (config.object_updates_per_sec > 0)
+          .then(|| TokenBucket::new(config.object_updates_per_sec, config.object_burst, now)),
+      });
+
+    let mut outcome = RateLimitOutcome::default();
+    let mut retry_after = Duration::ZERO;
+    for message in messages {
+      match &message {
+        ClientCollabMessage::ClientInitSync { data } => {
+          object.workspace_id = Some(data.workspace_id.clone());
+        },
+        ClientCollabMessage::ClientUpdateSync { .. } => {
+          let allowed = connection.bucket.try_take(now).and_then(|_| {
+            object
+              .bucket
+              .as_mut()
+              .map_or(Ok(()), |bucket| bucket.try_take(now))
+          });
+          if let Err(wait) = allowed {
+            outcome.throttled += 1;
+            retry_after = retry_after.max(wait);
+            continue;
+          }
+        },
+        _ => {},
+      }
+      outcome.messages.push(message);
+    }
+    outcome.workspace_id = object.workspace_id.clone();
+    if outcome.throttled == 0 {
+      return outcome;
+    }
+
+    if now.saturating_duration_since(connection.window_started_at) >= config.abuse_window {
+      connection.window_started_at = now;
+      connection.throttled_in_window = 0;
+    }
+    connection.throttled_in_window = connection
+      .throttled_in_window
+      .saturating_add(outcome.throttled as u32);
+    outcome.disconnect = connection.throttled_in_window >= config.max_throttled_per_window;
+    // a single notice per retry delay, rather than one for each dropped update
+    if connection.notify_after.map_or(true, |after| now >= after) {
+      connection.notify_after = Some(now + retry_after);
+      outcome.notify_retry_after = Some(retry_after);
+    }
+    outcome
+  }
+
+  pub fn remove_connection(&self, user: &RealtimeUser) {
+    self.connections.remove(user);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use collab::core::origin::CollabOrigin;
+  use collab_entity::CollabType;
+  use collab_rt_entity::{InitSync, UpdateSync};
+
+  fn user() -> RealtimeUser {
+    RealtimeUser::new(
+      1,
+      "device".to_string(),
+      "session".to_string(),
+      0,
+      "0.9.0".to_string(),
+    )
+  }
+
+  fn updates(count: usize) -> Vec<ClientCollabMessage> {
+    (0..count)
+      .map(|msg_id| {
+        ClientCollabMessage::new_update_sync(UpdateSync::new(
+          CollabOrigin::Empty,
+          "object".to_string(),
+          vec![1],
+          msg_id as u64,
+        ))
+      })
+      .collect()
+  }
+
+  fn init_sync() -> ClientCollabMessage {
+    ClientCollabMessage::new_init_sync(InitSync::new(
+      CollabOrigin::Empty,
+      "object".to_string(),
+      CollabType::Document,
+      "workspace".to_string(),
+      0,
+      vec![1],
+    ))
+  }
+
+  fn limiter(max_throttled_per_window: u32) -> UpdateRateLimiter {
+    UpdateRateLimiter::new(UpdateRateLimitConfig {
+      connection_updates_per_sec: 10,
+      connection_burst: 10,
+      max_throttled_per_window,
+      ..Default::default()
+    })
+  }
+
+  #[test]
+  fn updates_over_the_limit_are_throttled_test() {
+    let limiter = limiter(100);
+    let user = user();
+    let now = Instant::now();
+
+    let mut messages = vec![init_sync()];
+    messages.extend(updates(12));
+    let outcome = limiter.check_at(&user, "object", messages, now);
+    // the initial sync doesn't count against the limit
+    assert_eq!(outcome.messages.len(), 11);
+    assert_eq!(outcome.throttled, 2);
+    assert_eq!(outcome.workspace_id.as_deref(), Some("workspace"));
+    let retry_after = outcome.notify_retry_after.unwrap();
+    assert_eq!((retry_after.as_secs_f64() * 1000.0).round(), 100.0);
+    assert!(!outcome.disconnect);
+
+    // the connection is notified once per retry delay
+    let outcome = limiter.check_at(&user, "object", updates(1), now);
+    assert_eq!(outcome.throttled, 1);
+    assert_eq!(outcome.notify_retry_after, None);
+
+    // the bucket refills over time
+    let outcome = limiter.check_at(
+      &user,
+      "object",
+      updates(6),
+      now + Duration::from_millis(500),
+    );
+    assert_eq!((outcome.messages.len(), outcome.throttled), (5, 1));
+    assert!(outcome.notify_retry_after.is_some());
+  }
+
+  #[test]
+  fn sustained_abuse_disconnects_test() {
+    let limiter = limiter(20);
+    let user = user();
+    let now = Instant::now();
+
+    let outcome = limiter.check_at(&user, "object", updates(25), now);
+    assert_eq!(outcome.throttled, 15);
+    assert!(!outcome.disconnect);
+    let outcome = limiter.check_at(&user, "object", updates(5), now);
+    assert!(outcome.disconnect);
+
+    // a new connection starts afresh
+    limiter.remove_connection(&user);
+    let outcome = limiter.check_at(&user, "object", updates(10), now);
+    assert_eq!(outcome.throttled, 0);
+  }
+
+  #[test]
+  fn object_limit_test() {
+    let limiter = UpdateRateLimiter::new(UpdateRateLimitConfig {
+      object_updates_per_sec: 2,
+      object_burst: 2,
+      ..Default::default()
+    });
+    let user = user();
+    let now = Instant::now();
+    let outcome = limiter.check_at(&user, "object", updates(3), now);
+    assert_eq!(outcome.throttled, 1);
+    let outcome = limiter.check_at(&user, "other", updates(3), now);
+    assert_eq!(outcome.throttled, 1);
+  }
+}
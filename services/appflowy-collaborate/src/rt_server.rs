@@ -5,7 +5,9 @@ use access_control::collab::RealtimeAccessControl;
 use anyhow::{anyhow, Result};
 use app_error::AppError;
 use collab_rt_entity::user::{RealtimeUser, UserDevice};
-use collab_rt_entity::MessageByObjectId;
+use collab_rt_entity::{
+  ClientCollabMessage, MessageByObjectId, RealtimeMessage, SystemMessage, UpdateRateLimited,
+};
 use collab_stream::client::CollabRedisStream;
 use collab_stream::stream_router::StreamRouter;
 use dashmap::mapref::entry::Entry;
@@ -18,14 +20,25 @@ use tracing::{error, info, trace, warn};
 use yrs::updates::decoder::Decode;
 use yrs::StateVector;
 
+use crate::bandwidth::RealtimeBandwidth;
 use crate::client::client_msg_router::ClientMessageRouter;
+use crate::collab::migration::{spawn_collab_migration_sweeper, CollabMigrator};
+use crate::collab::recovery::CollabRecovery;
+use crate::collab::validator::CollabSizeLimits;
 use crate::command::{spawn_collaboration_command, CLCommandReceiver};
 use crate::config::get_env_var;
 use crate::connect_state::ConnectState;
 use crate::error::{CreateGroupFailedReason, RealtimeError};
+use crate::feature_flags::FeatureFlags;
 use crate::group::cmd::{GroupCommand, GroupCommandRunner, GroupCommandSender};
+use crate::group::full_state::FullStateSyncConfig;
 use crate::group::manager::GroupManager;
+use crate::group::unload::GroupUnloadConfig;
+use crate::group::write_behind::WriteBehindConfig;
+use crate::load_shed::LoadShedder;
+use crate::rate_limit::{UpdateRateLimitConfig, UpdateRateLimiter};
 use crate::rt_server::collaboration_runtime::COLLAB_RUNTIME;
+use crate::snapshot::SnapshotPolicyResolver;
 use database::collab::CollabStorage;
 use indexer::scheduler::IndexerScheduler;
 
@@ -38,9 +51,9 @@ pub struct CollaborationServer<S> {
   group_manager: Arc<GroupManager<S>>,
   connect_state: ConnectState,
   group_sender_by_object_id: Arc<DashMap<String, GroupCommandSender>>,
-  #[allow(dead_code)]
   metrics: Arc<CollabRealtimeMetrics>,
   enable_custom_runtime: bool,
+  update_rate_limiter: Arc<UpdateRateLimiter>,
 }
 
 impl<S> CollaborationServer<S>
@@ -57,7 +70,19 @@ where
     redis_connection_manager: ConnectionManager,
     group_persistence_interval: Duration,
     prune_grace_period: Duration,
+    folder_compaction_threshold: usize,
+    snapshot_policies: SnapshotPolicyResolver,
     indexer_scheduler: Arc<IndexerScheduler>,
+    bandwidth: Arc<RealtimeBandwidth>,
+    recovery: CollabRecovery,
+    migrator: CollabMigrator,
+    feature_flags: FeatureFlags,
+    load_shedder: Arc<LoadShedder>,
+    unload_config: GroupUnloadConfig,
+    update_rate_limit: UpdateRateLimitConfig,
+    size_limits: CollabSizeLimits,
+    full_state_sync: FullStateSyncConfig,
+    write_behind: WriteBehindConfig,
   ) -> Result<Self, RealtimeError> {
     let enable_custom_runtime = get_env_var("APPFLOWY_COLLABORATE_MULTI_THREAD", "false")
       .parse::<bool>()
@@ -70,6 +95,14 @@ where
     }
 
     let connect_state = ConnectState::new();
+    spawn_collab_migration_sweeper(
+      migrator.clone(),
+      storage.clone(),
+      CollabRedisStream::new_with_connection_manager(
+        redis_connection_manager.clone(),
+        redis_stream_router.clone(),
+      ),
+    );
     let collab_stream =
       CollabRedisStream::new_with_connection_manager(redis_connection_manager, redis_stream_router);
     let group_manager = Arc::new(
@@ -80,14 +113,25 @@ where
         collab_stream,
         group_persistence_interval,
         prune_grace_period,
+        folder_compaction_threshold,
+        snapshot_policies,
         indexer_scheduler.clone(),
+        bandwidth,
+        recovery,
+        migrator,
+        feature_flags,
+        load_shedder,
+        unload_config,
+        size_limits,
+        full_state_sync,
+        write_behind,
       )
       .await?,
     );
     let group_sender_by_object_id: Arc<DashMap<String, GroupCommandSender>> =
       Arc::new(Default::default());
 
-    spawn_period_check_inactive_group(Arc::downgrade(&group_manager), &group_sender_by_object_id);
+    spawn_period_unload_groups(Arc::downgrade(&group_manager), &group_sender_by_object_id);
 
     spawn_collaboration_command(
       command_recv,
@@ -101,9 +145,17 @@ where
       group_sender_by_object_id,
       metrics,
       enable_custom_runtime,
+      update_rate_limiter: Arc::new(UpdateRateLimiter::new(update_rate_limit)),
     })
   }
 
+  /// Stops all the collab groups, waiting for their pending updates to be saved. Called once the
+  /// http server stopped, so that a graceful restart leaves no update unsaved.
+  pub async fn stop_all_groups(&self) {
+    self.group_sender_by_object_id.clear();
+    self.group_manager.stop_all_groups().await;
+  }
+
   /// Handles a new user connection, replacing any existing connection for the same user.
   ///
   /// - Creates a new client stream for the connected user.
@@ -123,6 +175,7 @@ where
     {
       // Remove the old user from all collaboration groups.
       self.group_manager.remove_user(&old_user);
+      self.update_rate_limiter.remove_connection(&old_user);
     }
     self
       .metrics
@@ -148,6 +201,7 @@ where
         .set(self.connect_state.number_of_connected_users() as i64);
 
       self.group_manager.remove_user(&disconnect_user);
+      self.update_rate_limiter.remove_connection(&disconnect_user);
     }
 
     Ok(())
@@ -160,6 +214,10 @@ where
     message_by_oid: MessageByObjectId,
   ) -> Result<(), RealtimeError> {
     for (object_id, collab_messages) in message_by_oid.into_inner() {
+      let collab_messages = self.rate_limit(&user, &object_id, collab_messages);
+      if collab_messages.is_empty() {
+        continue;
+      }
       let group_cmd_sender = self.create_group_if_not_exist(&object_id);
       let cloned_user = user.clone();
       // Create a new task to send a message to the group command runner without waiting for the
@@ -201,6 +259,54 @@ where
     Ok(())
   }
 
+  /// Leaves out the updates over the rate limit of the connection, and tells the client when to
+  /// send them again. The connection is kicked off when it keeps exceeding the limit.
+  fn rate_limit(
+    &self,
+    user: &RealtimeUser,
+    object_id: &str,
+    messages: Vec<ClientCollabMessage>,
+  ) -> Vec<ClientCollabMessage> {
+    let outcome = self.update_rate_limiter.check(user, object_id, messages);
+    if outcome.throttled == 0 {
+      return outcome.messages;
+    }
+
+    let workspace_id = outcome.workspace_id.as_deref().unwrap_or("unknown");
+    self
+      .metrics
+      .record_throttled_updates(workspace_id, outcome.throttled);
+    trace!(
+      "{} dropped {} updates of {} over the rate limit",
+      user,
+      outcome.throttled,
+      object_id
+    );
+    if let Some(router) = self.connect_state.client_message_routers.get(user) {
+      if let Some(retry_after) = outcome.notify_retry_after {
+        router
+          .sink
+          .do_send(RealtimeMessage::System(SystemMessage::UpdateRateLimited(
+            UpdateRateLimited {
+              object_id: object_id.to_string(),
+              retry_after_ms: retry_after.as_millis() as u64,
+            },
+          )));
+      }
+      if outcome.disconnect {
+        warn!(
+          "kicking off {} from workspace {}: it kept exceeding the update rate limit",
+          user, workspace_id
+        );
+        self.metrics.rate_limit_disconnect_count.inc();
+        router
+          .sink
+          .do_send(RealtimeMessage::System(SystemMessage::KickOff));
+      }
+    }
+    outcome.messages
+  }
+
   #[inline]
   pub fn handle_client_http_update(
     &self,
@@ -381,13 +487,19 @@ where
   }
 }
 
-fn spawn_period_check_inactive_group<S>(
+/// Unloads the idle groups, and the least recently active ones over the memory budget, see
+/// [GroupUnloadConfig]. A group is loaded again when a client sends a message for its collab.
+fn spawn_period_unload_groups<S>(
   weak_groups: Weak<GroupManager<S>>,
   group_sender_by_object_id: &Arc<DashMap<String, GroupCommandSender>>,
 ) where
   S: CollabStorage,
 {
-  let mut interval = interval(Duration::from_secs(20));
+  let check_interval = match weak_groups.upgrade() {
+    Some(groups) => groups.unload_config().check_interval,
+    None => return,
+  };
+  let mut interval = interval(check_interval);
   let cloned_group_sender_by_object_id = group_sender_by_object_id.clone();
   tokio::spawn(async move {
     // when appflowy-collaborate start, wait for 60 seconds to start the check. Since no groups will
@@ -397,10 +509,14 @@ fn spawn_period_check_inactive_group<S>(
     loop {
       interval.tick().await;
       if let Some(groups) = weak_groups.upgrade() {
-        let inactive_group_ids = groups.get_inactive_groups();
-        for id in inactive_group_ids {
-          cloned_group_sender_by_object_id.remove(&id);
+        let unloaded = groups.take_groups_to_unload();
+        // stop routing messages to the groups before they're stopped, so that the next message of
+        // their collab loads a new group
+        for (object_id, _, _) in &unloaded {
+          cloned_group_sender_by_object_id.remove(object_id);
         }
+        let unloaded = unloaded.into_iter().map(|(_, group, _)| group).collect();
+        groups.stop_groups(unloaded).await;
       } else {
         break;
       }
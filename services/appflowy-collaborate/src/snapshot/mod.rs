@@ -1,3 +1,5 @@
+mod policy;
 mod snapshot_control;
 
+pub use policy::*;
 pub use snapshot_control::*;
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use collab_entity::CollabType;
+use database::workspace::select_workspace_ai_tier;
+use shared_entity::dto::billing_dto::SubscriptionPlan;
+use sqlx::PgPool;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::config::get_env_var;
+
+/// Decides when a restore point of a collab is taken, based on how much it was edited since the
+/// last one rather than on a fixed timer. A collab which was not edited never gets a snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotPolicy {
+  /// A snapshot is taken once this many updates were applied since the last one...
+  pub max_updates: u64,
+  /// ...or once the applied updates add up to this many bytes...
+  pub max_bytes: u64,
+  /// ...or, if the collab was edited at all, once this much time passed since the last one.
+  pub max_interval: Duration,
+}
+
+impl SnapshotPolicy {
+  pub fn is_due(&self, volume: &EditVolume, since_last_snapshot: Duration) -> bool {
+    self.trigger(volume, since_last_snapshot).is_some()
+  }
+
+  /// The threshold reached by the edits, if any.
+  pub fn trigger(
+    &self,
+    volume: &EditVolume,
+    since_last_snapshot: Duration,
+  ) -> Option<SnapshotTrigger> {
+    if volume.updates == 0 {
+      None
+    } else if volume.updates >= self.max_updates {
+      Some(SnapshotTrigger::Updates)
+    } else if volume.bytes >= self.max_bytes {
+      Some(SnapshotTrigger::Bytes)
+    } else if since_last_snapshot >= self.max_interval {
+      Some(SnapshotTrigger::Interval)
+    } else {
+      None
+    }
+  }
+
+  fn from_env(plan: &str, default: SnapshotPolicy) -> Result<Self, anyhow::Error> {
+    let plan = plan.to_uppercase();
+    Ok(Self {
+      max_updates: get_env_var(
+        &format!("APPFLOWY_COLLAB_SNAPSHOT_{}_MAX_UPDATES", plan),
+        &default.max_updates.to_string(),
+      )
+      .parse()?,
+      max_bytes: get_env_var(
+        &format!("APPFLOWY_COLLAB_SNAPSHOT_{}_MAX_BYTES", plan),
+        &default.max_bytes.to_string(),
+      )
+      .parse()?,
+      max_interval: Duration::from_secs(
+        get_env_var(
+          &format!("APPFLOWY_COLLAB_SNAPSHOT_{}_MAX_INTERVAL_SECS", plan),
+          &default.max_interval.as_secs().to_string(),
+        )
+        .parse()?,
+      ),
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotTrigger {
+  Updates,
+  Bytes,
+  Interval,
+}
+
+impl SnapshotTrigger {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      SnapshotTrigger::Updates => "updates",
+      SnapshotTrigger::Bytes => "bytes",
+      SnapshotTrigger::Interval => "interval",
+    }
+  }
+}
+
+/// Thresholds of a collab type replacing the ones of the plan of its workspace.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotPolicyOverride {
+  pub max_updates: Option<u64>,
+  pub max_interval: Option<Duration>,
+}
+
+impl SnapshotPolicyOverride {
+  pub fn apply(&self, policy: SnapshotPolicy) -> SnapshotPolicy {
+    SnapshotPolicy {
+      max_updates: self.max_updates.unwrap_or(policy.max_updates),
+      max_bytes: policy.max_bytes,
+      max_interval: self.max_interval.unwrap_or(policy.max_interval),
+    }
+  }
+
+  fn from_env(collab_type: &CollabType) -> Result<Option<Self>, anyhow::Error> {
+    let name = collab_type_name(collab_type).to_uppercase();
+    let get = |key: &str| {
+      std::env::var(format!("APPFLOWY_COLLAB_SNAPSHOT_{}_{}", name, key))
+        .ok()
+        .map(|value| value.parse::<u64>())
+        .transpose()
+    };
+    let policy_override = Self {
+      max_updates: get("MAX_UPDATES")?,
+      max_interval: get("MAX_INTERVAL_SECS")?.map(Duration::from_secs),
+    };
+    Ok((policy_override != Self::default()).then_some(policy_override))
+  }
+}
+
+/// Name of the collab type in the snapshot settings and metrics.
+pub fn collab_type_name(collab_type: &CollabType) -> &'static str {
+  match collab_type {
+    CollabType::Document => "document",
+    CollabType::Database => "database",
+    CollabType::WorkspaceDatabase => "workspace_database",
+    CollabType::Folder => "folder",
+    CollabType::DatabaseRow => "database_row",
+    CollabType::UserAwareness => "user_awareness",
+    CollabType::Unknown => "unknown",
+  }
+}
+
+/// Snapshot policies by subscription plan. A collab type can have its own update count and
+/// interval thresholds, which then apply whatever the plan.
+#[derive(Debug, Clone)]
+pub struct SnapshotPolicies {
+  pub free: SnapshotPolicy,
+  pub pro: SnapshotPolicy,
+  pub team: SnapshotPolicy,
+  pub by_collab_type: HashMap<CollabType, SnapshotPolicyOverride>,
+}
+
+impl SnapshotPolicies {
+  pub fn from_env() -> Result<Self, anyhow::Error> {
+    let defaults = Self::default();
+    let mut by_collab_type = HashMap::new();
+    for collab_type in [
+      CollabType::Document,
+      CollabType::Database,
+      CollabType::WorkspaceDatabase,
+      CollabType::Folder,
+      CollabType::DatabaseRow,
+      CollabType::UserAwareness,
+      CollabType::Unknown,
+    ] {
+      if let Some(policy_override) = SnapshotPolicyOverride::from_env(&collab_type)? {
+        by_collab_type.insert(collab_type, policy_override);
+      }
+    }
+    Ok(Self {
+      free: SnapshotPolicy::from_env("free", defaults.free)?,
+      pro: SnapshotPolicy::from_env("pro", defaults.pro)?,
+      team: SnapshotPolicy::from_env("team", defaults.team)?,
+      by_collab_type,
+    })
+  }
+
+  pub fn policy(&self, plan: Option<SubscriptionPlan>, collab_type: &CollabType) -> SnapshotPolicy {
+    let policy = self.for_plan(plan);
+    match self.by_collab_type.get(collab_type) {
+      Some(policy_override) => policy_override.apply(policy),
+      None => policy,
+    }
+  }
+
+  /// The AI add-ons don't change how often restore points are taken, so workspaces recorded with
+  /// them use the free policy.
+  pub fn for_plan(&self, plan: Option<SubscriptionPlan>) -> SnapshotPolicy {
+    match plan {
+      Some(SubscriptionPlan::Pro) => self.pro,
+      Some(SubscriptionPlan::Team) => self.team,
+      _ => self.free,
+    }
+  }
+}
+
+impl Default for SnapshotPolicies {
+  fn default() -> Self {
+    let paid = SnapshotPolicy {
+      max_updates: 500,
+      max_bytes: 1024 * 1024,
+      max_interval: Duration::from_secs(60 * 60),
+    };
+    Self {
+      free: SnapshotPolicy {
+        max_updates: 2000,
+        max_bytes: 4 * 1024 * 1024,
+        max_interval: Duration::from_secs(6 * 60 * 60),
+      },
+      pro: paid,
+      team: paid,
+      by_collab_type: HashMap::new(),
+    }
+  }
+}
+
+/// Resolves the [SnapshotPolicy] of a collab from its type and the subscription plan of its
+/// workspace.
+#[derive(Clone)]
+pub struct SnapshotPolicyResolver {
+  pg_pool: PgPool,
+  policies: SnapshotPolicies,
+}
+
+impl SnapshotPolicyResolver {
+  pub fn new(pg_pool: PgPool, policies: SnapshotPolicies) -> Self {
+    Self { pg_pool, policies }
+  }
+
+  pub async fn resolve(&self, workspace_id: &str, collab_type: &CollabType) -> SnapshotPolicy {
+    let plan = match Uuid::parse_str(workspace_id) {
+      Ok(workspace_id) => match select_workspace_ai_tier(&self.pg_pool, &workspace_id).await {
+        Ok(plan) => plan.and_then(|plan| SubscriptionPlan::try_from(plan).ok()),
+        Err(err) => {
+          warn!(
+            "failed to get subscription plan of workspace {}: {}",
+            workspace_id, err
+          );
+          None
+        },
+      },
+      Err(_) => None,
+    };
+    self.policies.policy(plan, collab_type)
+  }
+}
+
+/// The updates applied to a collab since its last snapshot, counted in Redis by
+/// [collab_stream::edit_counter::EditCounterStore].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EditVolume {
+  pub updates: u64,
+  pub bytes: u64,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const POLICY: SnapshotPolicy = SnapshotPolicy {
+    max_updates: 100,
+    max_bytes: 64 * 1024,
+    max_interval: Duration::from_secs(60 * 60),
+  };
+
+  /// Simulates a day of editing with `updates_per_minute` updates of `update_len` bytes, checking
+  /// the policy every minute the same way the persistence task does. Returns the number of
+  /// snapshots taken.
+  fn snapshots_per_day(updates_per_minute: u64, update_len: usize) -> usize {
+    let mut volume = EditVolume::default();
+    let mut last_snapshot_minute = 0;
+    let mut snapshots = 0;
+    for minute in 1..=24 * 60 {
+      volume.updates += updates_per_minute;
+      volume.bytes += updates_per_minute * update_len as u64;
+      let since_last_snapshot = Duration::from_secs((minute - last_snapshot_minute) * 60);
+      if POLICY.is_due(&volume, since_last_snapshot) {
+        volume = EditVolume::default();
+        last_snapshot_minute = minute;
+        snapshots += 1;
+      }
+    }
+    snapshots
+  }
+
+  #[test]
+  fn idle_collab_has_no_snapshots_test() {
+    assert_eq!(snapshots_per_day(0, 0), 0);
+  }
+
+  #[test]
+  fn snapshot_density_tracks_editing_intensity_test() {
+    // an occasional edit is covered by the maximum interval
+    let light = snapshots_per_day(1, 20);
+    assert_eq!(light, 24);
+
+    // 100 updates per snapshot, 10 updates per minute
+    let heavy = snapshots_per_day(10, 20);
+    assert_eq!(heavy, 24 * 6);
+
+    // large updates hit the byte threshold before the update count
+    let bulky = snapshots_per_day(10, 16 * 1024);
+    assert_eq!(bulky, 24 * 60);
+
+    assert!(light < heavy && heavy < bulky);
+  }
+
+  #[test]
+  fn snapshot_trigger_test() {
+    let volume = |updates, bytes| EditVolume { updates, bytes };
+    let recent = Duration::from_secs(60);
+    assert_eq!(POLICY.trigger(&volume(0, 0), POLICY.max_interval), None);
+    assert_eq!(POLICY.trigger(&volume(99, 1024), recent), None);
+    assert_eq!(
+      POLICY.trigger(&volume(100, 1024), recent),
+      Some(SnapshotTrigger::Updates)
+    );
+    assert_eq!(
+      POLICY.trigger(&volume(1, 64 * 1024), recent),
+      Some(SnapshotTrigger::Bytes)
+    );
+    assert_eq!(
+      POLICY.trigger(&volume(1, 10), POLICY.max_interval),
+      Some(SnapshotTrigger::Interval)
+    );
+  }
+
+  #[test]
+  fn snapshot_policy_by_plan_test() {
+    let policies = SnapshotPolicies::default();
+    assert_eq!(policies.for_plan(None), policies.free);
+    assert_eq!(
+      policies.for_plan(Some(SubscriptionPlan::AiMax)),
+      policies.free
+    );
+    assert_eq!(policies.for_plan(Some(SubscriptionPlan::Pro)), policies.pro);
+    assert_eq!(
+      policies.for_plan(Some(SubscriptionPlan::Team)),
+      policies.team
+    );
+  }
+
+  #[test]
+  fn snapshot_policy_by_collab_type_test() {
+    let mut policies = SnapshotPolicies::default();
+    policies.by_collab_type.insert(
+      CollabType::Folder,
+      SnapshotPolicyOverride {
+        max_updates: Some(50),
+        max_interval: None,
+      },
+    );
+    assert_eq!(
+      policies.policy(Some(SubscriptionPlan::Pro), &CollabType::Document),
+      policies.pro
+    );
+    let folder = policies.policy(Some(SubscriptionPlan::Pro), &CollabType::Folder);
+    assert_eq!(folder.max_updates, 50);
+    assert_eq!(folder.max_bytes, policies.pro.max_bytes);
+    assert_eq!(folder.max_interval, policies.pro.max_interval);
+  }
+}
@@ -1,23 +1,29 @@
 use std::sync::Arc;
 use std::time::Duration;
 
+use std::collections::HashMap;
+use std::str::FromStr;
+
 use chrono::{DateTime, Utc};
 use collab::entity::{EncodedCollab, EncoderVersion};
 use collab_entity::CollabType;
 use sqlx::PgPool;
 use tracing::{debug, error, trace, warn};
+use uuid::Uuid;
 use validator::Validate;
 
 use app_error::AppError;
 use database::collab::{
-  get_all_collab_snapshot_meta, latest_snapshot_time, select_snapshot, AppResult,
-  COLLAB_SNAPSHOT_LIMIT, SNAPSHOT_PER_HOUR,
+  collab_snapshot_object_key, delete_collab_snapshot_infos, get_all_collab_snapshot_meta,
+  insert_collab_snapshot_info, latest_snapshot_time, select_collab_snapshot_infos, select_snapshot,
+  AppResult, COLLAB_SNAPSHOT_LIMIT, SNAPSHOT_PER_HOUR,
 };
 use database::file::s3_client_impl::AwsS3BucketClientImpl;
 use database::file::{BucketClient, ResponseBlob};
 use database::history::ops::get_latest_snapshot;
 use database_entity::dto::{
-  AFSnapshotMeta, AFSnapshotMetas, InsertSnapshotParams, SnapshotData, ZSTD_COMPRESSION_LEVEL,
+  AFCollabVersion, AFSnapshotMeta, AFSnapshotMetas, InsertSnapshotParams, SnapshotData,
+  ZSTD_COMPRESSION_LEVEL,
 };
 
 use crate::metrics::CollabMetrics;
@@ -25,11 +31,7 @@ use crate::metrics::CollabMetrics;
 pub const SNAPSHOT_TICK_INTERVAL: Duration = Duration::from_secs(2);
 
 fn collab_snapshot_key(workspace_id: &str, object_id: &str, snapshot_id: i64) -> String {
-  let snapshot_id = u64::MAX - snapshot_id as u64;
-  format!(
-    "collabs/{}/{}/snapshot_{:16x}.v1.zstd",
-    workspace_id, object_id, snapshot_id
-  )
+  collab_snapshot_object_key(workspace_id, object_id, snapshot_id)
 }
 
 fn collab_snapshot_prefix(workspace_id: &str, object_id: &str) -> String {
@@ -125,6 +127,23 @@ impl SnapshotControl {
       self.collab_metrics.write_snapshot_failures.inc();
       return Err(err);
     }
+    // the snapshot is listed without its metadata when it can't be recorded
+    let workspace_id = Uuid::from_str(&params.workspace_id)?;
+    if let Err(err) = insert_collab_snapshot_info(
+      &self.pg_pool,
+      &workspace_id,
+      &params.object_id,
+      snapshot_id,
+      params.doc_state.len() as i64,
+      params.created_by,
+    )
+    .await
+    {
+      warn!(
+        "failed to record the metadata of snapshot {} of {}: {}",
+        snapshot_id, params.object_id, err
+      );
+    }
 
     // drop old snapshots if exceeds limit
     let list = self
@@ -145,8 +164,14 @@ impl SnapshotControl {
         .into_iter()
         .skip(COLLAB_SNAPSHOT_LIMIT as usize)
         .collect();
+      let trimmed_ids: Vec<_> = trimmed
+        .iter()
+        .filter_map(|key| get_meta(key.clone()))
+        .map(|meta| meta.snapshot_id)
+        .collect();
 
       self.s3.delete_blobs(trimmed).await?;
+      delete_collab_snapshot_infos(&self.pg_pool, &params.object_id, &trimmed_ids).await?;
     }
 
     Ok(AFSnapshotMeta {
@@ -219,6 +244,36 @@ impl SnapshotControl {
     }
   }
 
+  /// Returns the snapshots of the collab with the metadata recorded when they were taken.
+  pub async fn get_collab_versions(
+    &self,
+    workspace_id: &str,
+    oid: &str,
+  ) -> AppResult<Vec<AFCollabVersion>> {
+    let metas = self.get_collab_snapshot_list(workspace_id, oid).await?;
+    let workspace_id = Uuid::from_str(workspace_id)?;
+    let mut infos: HashMap<_, _> = select_collab_snapshot_infos(&self.pg_pool, &workspace_id, oid)
+      .await?
+      .into_iter()
+      .map(|info| (info.snapshot_id, info))
+      .collect();
+    let versions = metas
+      .0
+      .into_iter()
+      .map(|meta| {
+        let info = infos.remove(&meta.snapshot_id);
+        AFCollabVersion {
+          snapshot_id: meta.snapshot_id,
+          object_id: meta.object_id,
+          created_at: meta.created_at,
+          len: info.as_ref().map(|info| info.len),
+          created_by: info.and_then(|info| info.created_by),
+        }
+      })
+      .collect();
+    Ok(versions)
+  }
+
   pub async fn queue_snapshot(&self, params: InsertSnapshotParams) -> Result<(), AppError> {
     params.validate()?;
     trace!("Queuing snapshot for {}", params.object_id);
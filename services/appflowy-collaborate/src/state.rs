@@ -8,13 +8,16 @@ use uuid::Uuid;
 
 use crate::collab::storage::CollabAccessControlStorage;
 use crate::config::Config;
-use crate::metrics::CollabMetrics;
+use crate::feature_flags::FeatureFlags;
+use crate::load_shed::LoadShedder;
+use crate::metrics::{CollabMetrics, LoadShedMetrics};
 use crate::pg_listener::PgListeners;
 use crate::CollabRealtimeMetrics;
 use access_control::metrics::AccessControlMetrics;
 use app_error::AppError;
 use collab_stream::metrics::CollabStreamMetrics;
 use collab_stream::stream_router::StreamRouter;
+use database::file::s3_retry::S3Metrics;
 use database::user::{select_all_uid_uuid, select_uid_from_uuid};
 use indexer::metrics::EmbeddingMetrics;
 use indexer::scheduler::IndexerScheduler;
@@ -24,6 +27,7 @@ pub type RedisConnectionManager = redis::aio::ConnectionManager;
 #[derive(Clone)]
 pub struct AppState {
   pub config: Arc<Config>,
+  pub pg_pool: PgPool,
   pub pg_listeners: Arc<PgListeners>,
   pub user_cache: UserCache,
   pub redis_stream_router: Arc<StreamRouter>,
@@ -32,6 +36,9 @@ pub struct AppState {
   pub collab_access_control_storage: Arc<CollabAccessControlStorage>,
   pub metrics: AppMetrics,
   pub indexer_scheduler: Arc<IndexerScheduler>,
+  pub feature_flags: FeatureFlags,
+  /// Rejects part of the collab writes when their persistence can't keep up.
+  pub load_shedder: Arc<LoadShedder>,
 }
 
 #[derive(Clone)]
@@ -43,6 +50,8 @@ pub struct AppMetrics {
   pub collab_metrics: Arc<CollabMetrics>,
   pub collab_stream_metrics: Arc<CollabStreamMetrics>,
   pub embedding_metrics: Arc<EmbeddingMetrics>,
+  pub s3_metrics: Arc<S3Metrics>,
+  pub load_shed_metrics: Arc<LoadShedMetrics>,
 }
 
 impl Default for AppMetrics {
@@ -59,6 +68,8 @@ impl AppMetrics {
     let collab_metrics = Arc::new(CollabMetrics::register(&mut registry));
     let collab_stream_metrics = Arc::new(CollabStreamMetrics::register(&mut registry));
     let embedding_metrics = Arc::new(EmbeddingMetrics::register(&mut registry));
+    let s3_metrics = Arc::new(S3Metrics::register(&mut registry));
+    let load_shed_metrics = Arc::new(LoadShedMetrics::register(&mut registry));
     Self {
       registry: Arc::new(registry),
       access_control_metrics,
@@ -66,6 +77,8 @@ impl AppMetrics {
       collab_metrics,
       collab_stream_metrics,
       embedding_metrics,
+      s3_metrics,
+      load_shed_metrics,
     }
   }
 }
@@ -4,8 +4,18 @@ use redis::aio::ConnectionManager;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
 
+use crate::blob_gc_worker::worker::{run_blob_gc_worker, BlobGcConfig};
+use crate::cold_storage_worker::worker::{run_cold_storage_worker, ColdStorageConfig};
+use crate::export_worker::worker::run_export_worker;
 use crate::import_worker::worker::run_import_worker;
+use crate::invitation_worker::worker::{run_invitation_cleanup_worker, InvitationCleanupConfig};
+use crate::merge_worker::worker::run_merge_worker;
+use crate::outbox_worker::worker::{run_outbox_relay, OutboxRelayConfig};
+use crate::publish_worker::worker::run_publish_worker;
+use crate::thumbnail_worker::worker::{run_thumbnail_worker, ThumbnailConfig};
+use crate::trash_worker::worker::{run_trash_purge_worker, TrashPurgeConfig};
 use aws_sdk_s3::config::{Credentials, Region, SharedCredentialsProvider};
+use database::workspace_export::EXPORT_TASK_STREAM;
 
 use crate::import_worker::email_notifier::EmailNotifier;
 use crate::s3_client::S3ClientImpl;
@@ -13,7 +23,7 @@ use crate::s3_client::S3ClientImpl;
 use axum::Router;
 
 use crate::mailer::AFWorkerMailer;
-use crate::metric::ImportMetrics;
+use crate::metric::{BlobGcMetrics, ImportMetrics, OutboxMetrics};
 use appflowy_worker::indexer_worker::{run_background_indexer, BackgroundIndexerConfig};
 use axum::extract::State;
 use axum::http::StatusCode;
@@ -21,7 +31,7 @@ use axum::response::IntoResponse;
 use axum::routing::get;
 use indexer::metrics::EmbeddingMetrics;
 use indexer::thread_pool::ThreadPoolNoAbortBuilder;
-use infra::env_util::get_env_var;
+use infra::env_util::{get_env_var, get_env_var_opt};
 use mailer::sender::Mailer;
 use secrecy::{ExposeSecret, Secret};
 use std::sync::{Arc, Once};
@@ -127,6 +137,195 @@ pub async fn create_app(listener: TcpListener, config: Config) -> Result<(), Err
     maximum_import_file_size,
   ));
 
+  let export_statement_timeout =
+    get_env_var("APPFLOWY_WORKER_EXPORT_STATEMENT_TIMEOUT_MS", "10000")
+      .parse::<u64>()
+      .unwrap_or(10_000);
+  tokio::spawn(run_export_worker(
+    state.pg_pool.clone(),
+    state.redis_client.clone(),
+    Arc::new(state.s3_client.clone()),
+    state.mailer.clone(),
+    EXPORT_TASK_STREAM,
+    tick_interval,
+    Duration::from_millis(export_statement_timeout),
+  ));
+
+  tokio::spawn(run_merge_worker(
+    state.pg_pool.clone(),
+    state.redis_client.clone(),
+    Arc::new(state.s3_client.clone()),
+    "workspace_merge_task_stream",
+    tick_interval,
+  ));
+
+  let publish_tick_interval = get_env_var("APPFLOWY_WORKER_PUBLISH_TICK_INTERVAL", "30")
+    .parse::<u64>()
+    .unwrap_or(30);
+  tokio::spawn(run_publish_worker(
+    state.pg_pool.clone(),
+    Arc::new(state.s3_client.clone()),
+    publish_tick_interval,
+  ));
+
+  tokio::spawn(run_outbox_relay(
+    state.pg_pool.clone(),
+    state.redis_client.clone(),
+    state.mailer.clone(),
+    state.metrics.outbox_metrics.clone(),
+    OutboxRelayConfig {
+      tick_interval_secs: get_env_var("APPFLOWY_WORKER_OUTBOX_TICK_INTERVAL", "5")
+        .parse::<u64>()
+        .unwrap_or(5),
+      batch_size: get_env_var("APPFLOWY_WORKER_OUTBOX_BATCH_SIZE", "100")
+        .parse::<i64>()
+        .unwrap_or(100),
+      lease_secs: get_env_var("APPFLOWY_WORKER_OUTBOX_LEASE_SECS", "120")
+        .parse::<i64>()
+        .unwrap_or(120),
+      retention_secs: get_env_var("APPFLOWY_WORKER_OUTBOX_RETENTION_SECS", "604800")
+        .parse::<i64>()
+        .unwrap_or(604_800),
+      stuck_after_secs: get_env_var("APPFLOWY_WORKER_OUTBOX_STUCK_AFTER_SECS", "900")
+        .parse::<i64>()
+        .unwrap_or(900),
+      revision_grace_period_secs: get_env_var(
+        "APPFLOWY_PUBLISHED_COLLAB_REVISION_GRACE_PERIOD_SECS",
+        "86400",
+      )
+      .parse::<i64>()
+      .unwrap_or(86_400),
+      cdn_purge_url: get_env_var_opt("APPFLOWY_PUBLISHED_COLLAB_CDN_PURGE_URL"),
+      cdn_purge_token: get_env_var_opt("APPFLOWY_PUBLISHED_COLLAB_CDN_PURGE_TOKEN")
+        .map(Secret::new),
+    },
+  ));
+
+  tokio::spawn(run_blob_gc_worker(
+    state.pg_pool.clone(),
+    Arc::new(state.s3_client.clone()),
+    state.redis_client.clone(),
+    state.metrics.blob_gc_metrics.clone(),
+    BlobGcConfig {
+      enable: get_env_var("APPFLOWY_WORKER_BLOB_GC_ENABLED", "true")
+        .parse::<bool>()
+        .unwrap_or(true),
+      dry_run: get_env_var("APPFLOWY_WORKER_BLOB_GC_DRY_RUN", "true")
+        .parse::<bool>()
+        .unwrap_or(true),
+      grace_period_secs: get_env_var("APPFLOWY_WORKER_BLOB_GC_GRACE_PERIOD_SECS", "604800")
+        .parse::<i64>()
+        .unwrap_or(604_800),
+      pass_interval_secs: get_env_var("APPFLOWY_WORKER_BLOB_GC_PASS_INTERVAL_SECS", "86400")
+        .parse::<i64>()
+        .unwrap_or(86_400),
+      tick_interval_secs: get_env_var("APPFLOWY_WORKER_BLOB_GC_TICK_INTERVAL", "600")
+        .parse::<u64>()
+        .unwrap_or(600),
+      workspace_chunk_size: get_env_var("APPFLOWY_WORKER_BLOB_GC_WORKSPACE_CHUNK_SIZE", "50")
+        .parse::<i64>()
+        .unwrap_or(50),
+      pending_upload_ttl_secs: get_env_var(
+        "APPFLOWY_WORKER_BLOB_GC_PENDING_UPLOAD_TTL_SECS",
+        "86400",
+      )
+      .parse::<i64>()
+      .unwrap_or(86_400),
+      multipart_upload_ttl_secs: get_env_var(
+        "APPFLOWY_WORKER_BLOB_GC_MULTIPART_UPLOAD_TTL_SECS",
+        "86400",
+      )
+      .parse::<i64>()
+      .unwrap_or(86_400),
+    },
+  ));
+
+  tokio::spawn(run_cold_storage_worker(
+    state.pg_pool.clone(),
+    Arc::new(state.s3_client.clone()),
+    ColdStorageConfig {
+      enable: get_env_var("APPFLOWY_WORKER_COLD_STORAGE_ENABLED", "false")
+        .parse::<bool>()
+        .unwrap_or(false),
+      cold_after_secs: get_env_var("APPFLOWY_WORKER_COLD_STORAGE_COLD_AFTER_SECS", "15552000")
+        .parse::<i64>()
+        .unwrap_or(15_552_000),
+      storage_class: get_env_var("APPFLOWY_WORKER_COLD_STORAGE_STORAGE_CLASS", "STANDARD_IA"),
+      min_size_bytes: get_env_var("APPFLOWY_WORKER_COLD_STORAGE_MIN_SIZE_BYTES", "131072")
+        .parse::<i64>()
+        .unwrap_or(131_072),
+      tick_interval_secs: get_env_var("APPFLOWY_WORKER_COLD_STORAGE_TICK_INTERVAL", "600")
+        .parse::<u64>()
+        .unwrap_or(600),
+      workspace_chunk_size: get_env_var("APPFLOWY_WORKER_COLD_STORAGE_WORKSPACE_CHUNK_SIZE", "50")
+        .parse::<i64>()
+        .unwrap_or(50),
+    },
+  ));
+
+  tokio::spawn(run_thumbnail_worker(
+    state.pg_pool.clone(),
+    Arc::new(state.s3_client.clone()),
+    ThumbnailConfig {
+      enable: get_env_var("APPFLOWY_WORKER_THUMBNAIL_ENABLED", "false")
+        .parse::<bool>()
+        .unwrap_or(false),
+      tick_interval_secs: get_env_var("APPFLOWY_WORKER_THUMBNAIL_TICK_INTERVAL", "5")
+        .parse::<u64>()
+        .unwrap_or(5),
+      batch_size: get_env_var("APPFLOWY_WORKER_THUMBNAIL_BATCH_SIZE", "20")
+        .parse::<i64>()
+        .unwrap_or(20),
+      lease_secs: get_env_var("APPFLOWY_WORKER_THUMBNAIL_LEASE_SECS", "300")
+        .parse::<i64>()
+        .unwrap_or(300),
+      max_attempts: get_env_var("APPFLOWY_WORKER_THUMBNAIL_MAX_ATTEMPTS", "3")
+        .parse::<i32>()
+        .unwrap_or(3),
+      max_source_bytes: get_env_var("APPFLOWY_WORKER_THUMBNAIL_MAX_SOURCE_BYTES", "52428800")
+        .parse::<u64>()
+        .unwrap_or(52_428_800),
+    },
+  ));
+
+  tokio::spawn(run_invitation_cleanup_worker(
+    state.pg_pool.clone(),
+    InvitationCleanupConfig {
+      tick_interval_secs: get_env_var("APPFLOWY_WORKER_INVITATION_CLEANUP_TICK_INTERVAL", "3600")
+        .parse::<u64>()
+        .unwrap_or(3600),
+      grace_period_secs: get_env_var(
+        "APPFLOWY_WORKER_INVITATION_CLEANUP_GRACE_PERIOD_SECS",
+        "2592000",
+      )
+      .parse::<i64>()
+      .unwrap_or(2_592_000),
+      batch_size: get_env_var("APPFLOWY_WORKER_INVITATION_CLEANUP_BATCH_SIZE", "500")
+        .parse::<i64>()
+        .unwrap_or(500),
+    },
+  ));
+
+  tokio::spawn(run_trash_purge_worker(
+    state.pg_pool.clone(),
+    Arc::new(state.s3_client.clone()),
+    state.redis_client.clone(),
+    TrashPurgeConfig {
+      enable: get_env_var("APPFLOWY_WORKER_TRASH_PURGE_ENABLED", "true")
+        .parse::<bool>()
+        .unwrap_or(true),
+      retention_secs: get_env_var("APPFLOWY_WORKER_TRASH_PURGE_RETENTION_SECS", "2592000")
+        .parse::<i64>()
+        .unwrap_or(2_592_000),
+      tick_interval_secs: get_env_var("APPFLOWY_WORKER_TRASH_PURGE_TICK_INTERVAL", "3600")
+        .parse::<u64>()
+        .unwrap_or(3600),
+      batch_size: get_env_var("APPFLOWY_WORKER_TRASH_PURGE_BATCH_SIZE", "100")
+        .parse::<i64>()
+        .unwrap_or(100),
+    },
+  ));
+
   let threads = Arc::new(
     ThreadPoolNoAbortBuilder::new()
       .num_threads(30)
@@ -173,7 +372,6 @@ pub struct AppState {
   pub redis_client: ConnectionManager,
   pub pg_pool: PgPool,
   pub s3_client: S3ClientImpl,
-  #[allow(dead_code)]
   pub mailer: AFWorkerMailer,
   pub metrics: AppMetrics,
 }
@@ -232,6 +430,8 @@ pub async fn get_aws_s3_client(s3_setting: &S3Setting) -> Result<S3ClientImpl, E
   Ok(S3ClientImpl {
     inner: client,
     bucket: s3_setting.bucket.clone(),
+    endpoint: s3_setting.minio_url.clone(),
+    presigned_url_endpoint: s3_setting.presigned_url_endpoint.clone(),
   })
 }
 
@@ -241,6 +441,8 @@ pub struct AppMetrics {
   registry: Arc<prometheus_client::registry::Registry>,
   import_metrics: Arc<ImportMetrics>,
   embedder_metrics: Arc<EmbeddingMetrics>,
+  outbox_metrics: Arc<OutboxMetrics>,
+  blob_gc_metrics: Arc<BlobGcMetrics>,
 }
 
 impl AppMetrics {
@@ -248,10 +450,14 @@ impl AppMetrics {
     let mut registry = prometheus_client::registry::Registry::default();
     let import_metrics = Arc::new(ImportMetrics::register(&mut registry));
     let embedder_metrics = Arc::new(EmbeddingMetrics::register(&mut registry));
+    let outbox_metrics = Arc::new(OutboxMetrics::register(&mut registry));
+    let blob_gc_metrics = Arc::new(BlobGcMetrics::register(&mut registry));
     Self {
       registry: Arc::new(registry),
       import_metrics,
       embedder_metrics,
+      outbox_metrics,
+      blob_gc_metrics,
     }
   }
 }
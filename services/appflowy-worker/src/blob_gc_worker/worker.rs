@@ -0,0 +1,553 @@
+use crate::error::WorkerError;
+use crate::metric::BlobGcMetrics;
+use crate::s3_client::{S3Client, S3ClientImpl};
+use database::blob_gc::{
+  select_blob_gc_cursor_for_update, select_blob_referencing_object_ids, select_workspace_ids_after,
+  update_blob_gc_cursor,
+};
+use database::blob_variant::delete_blob_variants_of_files;
+use database::pg_row::AFBlobMetadataRow;
+use database::resource_usage::{
+  delete_all_blob_versions, delete_blob_metadata, delete_blob_metadata_bulk,
+  delete_blob_versions_of_files, delete_expired_multipart_uploads,
+  delete_expired_pending_blob_metadata, get_all_workspace_blob_metadata,
+  invalidate_workspace_usage_size_cache, release_blob_contents,
+  select_expired_blob_metadata_for_update,
+};
+use redis::aio::ConnectionManager;
+use sqlx::types::chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::ops::DerefMut;
+use std::slice;
+use std::sync::Arc;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info, trace};
+use uuid::Uuid;
+
+/// Length of the hyphenated object id prefixing the file id of a blob attached to an object.
+const OBJECT_ID_LEN: usize = 36;
+/// Number of expired presigned or multipart uploads deleted per statement.
+const EXPIRED_UPLOAD_BATCH_SIZE: i64 = 100;
+/// Number of expired blobs deleted per transaction.
+const EXPIRED_BLOB_BATCH_SIZE: i64 = 100;
+
+pub struct BlobGcConfig {
+  pub enable: bool,
+  /// Only log the orphaned blobs instead of deleting them.
+  pub dry_run: bool,
+  /// Blobs modified, or whose object was deleted, more recently than this are kept.
+  pub grace_period_secs: i64,
+  /// Minimum time between the start of two passes over all the workspaces.
+  pub pass_interval_secs: i64,
+  pub tick_interval_secs: u64,
+  pub workspace_chunk_size: i64,
+  /// The presigned uploads which weren't completed this long after their url was issued are
+  /// deleted, releasing the space reserved for them. The dry runs delete them too, since they
+  /// were never recorded as blobs.
+  pub pending_upload_ttl_secs: i64,
+  /// The multipart uploads without any part uploaded for this long are aborted, deleting their
+  /// parts from the bucket. The dry runs abort them too.
+  pub multipart_upload_ttl_secs: i64,
+}
+
+/// Deletes the blobs attached to an object which no longer exists, such as the images of a deleted
+/// document, along with their metadata. The workspaces are walked in chunks, and the progress
+/// is kept in Postgres so that a pass resumes after a restart and several workers can run this
+/// loop at the same time.
+pub async fn run_blob_gc_worker(
+  pg_pool: PgPool,
+  s3_client: Arc<S3ClientImpl>,
+  redis_client: ConnectionManager,
+  metrics: Arc<BlobGcMetrics>,
+  config: BlobGcConfig,
+) -> Result<(), WorkerError> {
+  if !config.enable {
+    info!("Blob gc worker is disabled");
+    return Ok(());
+  }
+  info!(
+    "Starting blob gc worker, dry run: {}, grace period: {}s",
+    config.dry_run, config.grace_period_secs
+  );
+  let gc = BlobGc {
+    pg_pool,
+    s3_client,
+    redis_client,
+    metrics,
+    config,
+  };
+  let mut tick = interval(std::time::Duration::from_secs(gc.config.tick_interval_secs));
+  tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+  loop {
+    tick.tick().await;
+    if let Err(err) = gc.delete_expired_uploads().await {
+      error!("[BlobGc] failed to delete expired uploads: {:?}", err);
+    }
+    if let Err(err) = gc.delete_expired_blobs().await {
+      error!("[BlobGc] failed to delete expired blobs: {:?}", err);
+    }
+    if let Err(err) = gc.abort_expired_multipart_uploads().await {
+      error!(
+        "[BlobGc] failed to abort expired multipart uploads: {:?}",
+        err
+      );
+    }
+    loop {
+      match gc.collect_next_chunk().await {
+        Ok(true) => continue,
+        Ok(false) => break,
+        Err(err) => {
+          error!("[BlobGc] failed to collect orphaned blobs: {:?}", err);
+          break;
+        },
+      }
+    }
+  }
+}
+
+#[derive(Debug, Default)]
+struct CollectedBlobs {
+  count: usize,
+  bytes: i64,
+}
+
+struct BlobGc {
+  pg_pool: PgPool,
+  s3_client: Arc<S3ClientImpl>,
+  redis_client: ConnectionManager,
+  metrics: Arc<BlobGcMetrics>,
+  config: BlobGcConfig,
+}
+
+impl BlobGc {
+  /// Delete the presigned uploads past their time to live, with their object when one was
+  /// uploaded.
+  async fn delete_expired_uploads(&self) -> Result<(), WorkerError> {
+    let created_before = Utc::now() - Duration::seconds(self.config.pending_upload_ttl_secs);
+    loop {
+      let object_keys = delete_expired_pending_blob_metadata(
+        &self.pg_pool,
+        created_before,
+        EXPIRED_UPLOAD_BATCH_SIZE,
+      )
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+      // the uploads of blobs recorded by another upload meanwhile have no object to delete
+      for object_key in &object_keys {
+        self.s3_client.delete_blob(object_key).await?;
+      }
+      if !object_keys.is_empty() {
+        info!("[BlobGc] deleted {} expired uploads", object_keys.len());
+        self
+          .metrics
+          .expired_upload_count
+          .inc_by(object_keys.len() as i64);
+      }
+      if (object_keys.len() as i64) < EXPIRED_UPLOAD_BATCH_SIZE {
+        return Ok(());
+      }
+    }
+  }
+
+  /// Delete the blobs past their expiry with their versions and variants, then their objects, in
+  /// batches. Their uploader asked for them to expire, so the dry runs delete them too.
+  async fn delete_expired_blobs(&self) -> Result<(), WorkerError> {
+    loop {
+      let mut txn = self
+        .pg_pool
+        .begin()
+        .await
+        .map_err(|err| WorkerError::Internal(err.into()))?;
+      let blobs =
+        select_expired_blob_metadata_for_update(&mut txn, Utc::now(), EXPIRED_BLOB_BATCH_SIZE)
+          .await
+          .map_err(|err| WorkerError::Internal(err.into()))?;
+      let count = blobs.len();
+      let bytes = blobs.iter().map(|blob| blob.file_size).sum::<i64>();
+      let mut file_ids_by_workspace: HashMap<Uuid, Vec<String>> = HashMap::new();
+      for blob in blobs {
+        file_ids_by_workspace
+          .entry(blob.workspace_id)
+          .or_default()
+          .push(blob.file_id);
+      }
+
+      let mut object_keys = vec![];
+      for (workspace_id, file_ids) in &file_ids_by_workspace {
+        let released = release_blob_contents(&mut txn, workspace_id, file_ids)
+          .await
+          .map_err(|err| WorkerError::Internal(err.into()))?;
+        let deleted = delete_blob_metadata_bulk(&mut txn, workspace_id, file_ids)
+          .await
+          .map_err(|err| WorkerError::Internal(err.into()))?;
+        object_keys.extend(
+          delete_blob_versions_of_files(&mut txn, workspace_id, file_ids)
+            .await
+            .map_err(|err| WorkerError::Internal(err.into()))?,
+        );
+        object_keys.extend(
+          delete_blob_variants_of_files(&mut txn, workspace_id, file_ids)
+            .await
+            .map_err(|err| WorkerError::Internal(err.into()))?,
+        );
+        object_keys.extend(
+          deleted
+            .iter()
+            .filter(|file_id| !released.shared_file_ids.contains(*file_id))
+            .map(|file_id| {
+              blob_object_key(workspace_id, file_id)
+                .unwrap_or_else(|| format!("{}/{}", workspace_id, file_id))
+            }),
+        );
+        object_keys.extend(released.orphaned_object_keys);
+      }
+      txn
+        .commit()
+        .await
+        .map_err(|err| WorkerError::Internal(err.into()))?;
+      for workspace_id in file_ids_by_workspace.keys() {
+        invalidate_workspace_usage_size_cache(&self.redis_client, workspace_id).await;
+      }
+
+      let failed = self.s3_client.delete_blobs(&object_keys).await?;
+      if !failed.is_empty() {
+        error!(
+          "[BlobGc] failed to delete {} objects of expired blobs: {:?}",
+          failed.len(),
+          failed
+        );
+      }
+      if count > 0 {
+        info!("[BlobGc] deleted {} expired blobs, {} bytes", count, bytes);
+        self.metrics.expired_blob_count.inc_by(count as i64);
+        self.metrics.reclaimed_bytes.inc_by(bytes);
+      }
+      if (count as i64) < EXPIRED_BLOB_BATCH_SIZE {
+        return Ok(());
+      }
+    }
+  }
+
+  /// Abort the multipart uploads abandoned by their client.
+  async fn abort_expired_multipart_uploads(&self) -> Result<(), WorkerError> {
+    let updated_before = Utc::now() - Duration::seconds(self.config.multipart_upload_ttl_secs);
+    loop {
+      let uploads =
+        delete_expired_multipart_uploads(&self.pg_pool, updated_before, EXPIRED_UPLOAD_BATCH_SIZE)
+          .await
+          .map_err(|err| WorkerError::Internal(err.into()))?;
+      for upload in &uploads {
+        self
+          .s3_client
+          .abort_multipart_upload(&upload.object_key, &upload.upload_id)
+          .await?;
+      }
+      if !uploads.is_empty() {
+        info!(
+          "[BlobGc] aborted {} expired multipart uploads",
+          uploads.len()
+        );
+        self
+          .metrics
+          .aborted_upload_count
+          .inc_by(uploads.len() as i64);
+      }
+      if (uploads.len() as i64) < EXPIRED_UPLOAD_BATCH_SIZE {
+        return Ok(());
+      }
+    }
+  }
+
+  /// Collect the orphaned blobs of the workspaces following the cursor. Returns whether the pass
+  /// has workspaces left.
+  async fn collect_next_chunk(&self) -> Result<bool, WorkerError> {
+    let mut txn = self
+      .pg_pool
+      .begin()
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    let cursor = match select_blob_gc_cursor_for_update(txn.deref_mut())
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?
+    {
+      Some(cursor) => cursor,
+      None => {
+        trace!("[BlobGc] another worker is collecting orphaned blobs");
+        return Ok(false);
+      },
+    };
+    let pass_started = cursor.last_workspace_id.is_some();
+    let pass_due = cursor.pass_finished_at.map_or(true, |finished_at| {
+      finished_at <= Utc::now() - Duration::seconds(self.config.pass_interval_secs)
+    });
+    if !pass_started && !pass_due {
+      return Ok(false);
+    }
+
+    let workspace_ids = select_workspace_ids_after(
+      &self.pg_pool,
+      cursor.last_workspace_id.as_ref(),
+      self.config.workspace_chunk_size,
+    )
+    .await
+    .map_err(|err| WorkerError::Internal(err.into()))?;
+    let mut collected = CollectedBlobs::default();
+    for workspace_id in &workspace_ids {
+      // a workspace failing is retried on the next pass, it doesn't hold the others back
+      match self.collect_workspace(workspace_id).await {
+        Ok(workspace) => {
+          collected.count += workspace.count;
+          collected.bytes += workspace.bytes;
+        },
+        Err(err) => error!(
+          "[BlobGc] failed to collect orphaned blobs of workspace {}: {:?}",
+          workspace_id, err
+        ),
+      }
+    }
+
+    let has_more = workspace_ids.len() as i64 >= self.config.workspace_chunk_size;
+    let next_cursor = if has_more { workspace_ids.last() } else { None };
+    update_blob_gc_cursor(txn.deref_mut(), next_cursor)
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    txn
+      .commit()
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+
+    if collected.count > 0 {
+      info!(
+        "[BlobGc] {} {} orphaned blobs, {} bytes, in {} workspaces",
+        if self.config.dry_run {
+          "found"
+        } else {
+          "deleted"
+        },
+        collected.count,
+        collected.bytes,
+        workspace_ids.len()
+      );
+    }
+    if !has_more {
+      info!("[BlobGc] finished a pass over all the workspaces");
+    }
+    Ok(has_more)
+  }
+
+  async fn collect_workspace(&self, workspace_id: &Uuid) -> Result<CollectedBlobs, WorkerError> {
+    let blobs = get_all_workspace_blob_metadata(&self.pg_pool, workspace_id)
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    let object_ids = blobs
+      .iter()
+      .filter_map(|blob| blob_object_id(&blob.file_id))
+      .map(str::to_string)
+      .collect::<HashSet<_>>()
+      .into_iter()
+      .collect::<Vec<_>>();
+    if object_ids.is_empty() {
+      return Ok(CollectedBlobs::default());
+    }
+
+    let kept_after = Utc::now() - Duration::seconds(self.config.grace_period_secs);
+    let referencing_ids =
+      select_blob_referencing_object_ids(&self.pg_pool, workspace_id, &object_ids, kept_after)
+        .await
+        .map_err(|err| WorkerError::Internal(err.into()))?;
+    let orphans = orphaned_blobs(blobs, &referencing_ids, kept_after);
+
+    let mut collected = CollectedBlobs::default();
+    for blob in orphans {
+      if self.config.dry_run {
+        info!(
+          "[BlobGc] dry run: would delete blob {} of workspace {}, {} bytes",
+          blob.file_id, workspace_id, blob.file_size
+        );
+        self.metrics.candidate_bytes.inc_by(blob.file_size);
+      } else {
+        self.delete_blob(workspace_id, &blob).await?;
+        self.metrics.deleted_blob_count.inc();
+        self.metrics.reclaimed_bytes.inc_by(blob.file_size);
+      }
+      collected.count += 1;
+      collected.bytes += blob.file_size;
+    }
+    Ok(collected)
+  }
+
+  /// Delete the metadata of the blob with its versions and variants, then its objects. A content shared with
+  /// other blobs is only deleted along with its last reference.
+  async fn delete_blob(
+    &self,
+    workspace_id: &Uuid,
+    blob: &AFBlobMetadataRow,
+  ) -> Result<(), WorkerError> {
+    let mut txn = self
+      .pg_pool
+      .begin()
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    let released = release_blob_contents(&mut txn, workspace_id, slice::from_ref(&blob.file_id))
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    delete_blob_metadata(&mut txn, workspace_id, &blob.file_id)
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    let mut object_keys = delete_all_blob_versions(&mut txn, workspace_id, &blob.file_id)
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    object_keys.extend(
+      delete_blob_variants_of_files(&mut txn, workspace_id, slice::from_ref(&blob.file_id))
+        .await
+        .map_err(|err| WorkerError::Internal(err.into()))?,
+    );
+    txn
+      .commit()
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    invalidate_workspace_usage_size_cache(&self.redis_client, workspace_id).await;
+
+    if !released.shared_file_ids.contains(&blob.file_id) {
+      object_keys.extend(blob_object_key(workspace_id, &blob.file_id));
+    }
+    object_keys.extend(released.orphaned_object_keys);
+    for object_key in object_keys {
+      self.s3_client.delete_blob(&object_key).await?;
+    }
+    info!(
+      "[BlobGc] deleted blob {} of workspace {}, {} bytes",
+      blob.file_id, workspace_id, blob.file_size
+    );
+    Ok(())
+  }
+}
+
+/// The object a blob is attached to, parsed from the `{object_id}_{file_id}` metadata key of a
+/// `BlobPathV1`. The blobs whose parent directory isn't an object id are never collected.
+fn blob_object_id(file_id: &str) -> Option<&str> {
+  let object_id = file_id.get(..OBJECT_ID_LEN)?;
+  let has_file_name = file_id[OBJECT_ID_LEN..]
+    .strip_prefix('_')
+    .is_some_and(|file_name| !file_name.is_empty());
+  (has_file_name && Uuid::parse_str(object_id).is_ok()).then_some(object_id)
+}
+
+/// Mirror of the object key of a `BlobPathV1`, computed from its metadata key.
+pub(crate) fn blob_object_key(workspace_id: &Uuid, file_id: &str) -> Option<String> {
+  let object_id = blob_object_id(file_id)?;
+  Some(format!(
+    "{}/{}/{}",
+    workspace_id,
+    object_id,
+    &file_id[OBJECT_ID_LEN + 1..]
+  ))
+}
+
+/// The object storing the content of the blob: its shared content, else the object of a
+/// `BlobPathV1`, else the legacy `{workspace_id}/{file_id}` object.
+pub(crate) async fn resolve_blob_object_key(
+  s3_client: &S3ClientImpl,
+  workspace_id: &Uuid,
+  blob: &AFBlobMetadataRow,
+) -> Result<Option<String>, WorkerError> {
+  if let Some(object_key) = &blob.object_key {
+    return Ok(Some(object_key.clone()));
+  }
+  if let Some(object_key) = blob_object_key(workspace_id, &blob.file_id) {
+    if s3_client.is_blob_exist(&object_key).await? {
+      return Ok(Some(object_key));
+    }
+  }
+  let object_key = format!("{}/{}", workspace_id, blob.file_id);
+  if s3_client.is_blob_exist(&object_key).await? {
+    return Ok(Some(object_key));
+  }
+  Ok(None)
+}
+
+/// The blobs attached to an object missing from `referencing_ids`, and not modified after
+/// `kept_after`.
+fn orphaned_blobs(
+  blobs: Vec<AFBlobMetadataRow>,
+  referencing_ids: &HashSet<String>,
+  kept_after: DateTime<Utc>,
+) -> Vec<AFBlobMetadataRow> {
+  blobs
+    .into_iter()
+    .filter(|blob| blob.modified_at <= kept_after)
+    .filter(|blob| {
+      blob_object_id(&blob.file_id).is_some_and(|object_id| !referencing_ids.contains(object_id))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn blob(file_id: &str, modified_at: DateTime<Utc>) -> AFBlobMetadataRow {
+    AFBlobMetadataRow {
+      workspace_id: Uuid::nil(),
+      file_id: file_id.to_string(),
+      file_type: "image/png".to_string(),
+      file_size: 1024,
+      modified_at,
+      status: 0,
+      content_hash: None,
+      object_key: None,
+      last_accessed_at: modified_at,
+      storage_class: None,
+      storage_class_changed_at: None,
+      created_at: Some(modified_at),
+      uploaded_by: None,
+      expires_at: None,
+    }
+  }
+
+  #[test]
+  fn blob_object_id_is_parsed_from_the_metadata_key_test() {
+    let object_id = "c1b2a3d4-0000-4000-8000-000000000001";
+    let file_id = format!("{}_image.png", object_id);
+    assert_eq!(blob_object_id(&file_id), Some(object_id));
+    // the file name may contain underscores itself
+    let file_id = format!("{}_my_image.png", object_id);
+    assert_eq!(blob_object_id(&file_id), Some(object_id));
+    assert_eq!(
+      blob_object_key(&Uuid::nil(), &file_id),
+      Some(format!("{}/{}/my_image.png", Uuid::nil(), object_id))
+    );
+
+    // V0 blobs and parent directories which aren't an object id are left alone
+    assert_eq!(blob_object_id("image.png"), None);
+    assert_eq!(
+      blob_object_id("chat_files_c1b2a3d4-0000-4000-8000-0001"),
+      None
+    );
+    assert_eq!(blob_object_id(&format!("{}_", object_id)), None);
+    assert_eq!(blob_object_id(&format!("{}image.png", object_id)), None);
+    assert_eq!(blob_object_key(&Uuid::nil(), "image.png"), None);
+  }
+
+  #[test]
+  fn orphaned_blobs_skip_referenced_and_recent_blobs_test() {
+    let live = "c1b2a3d4-0000-4000-8000-000000000001";
+    let deleted = "c1b2a3d4-0000-4000-8000-000000000002";
+    let now = Utc::now();
+    let kept_after = now - Duration::days(7);
+    let old = now - Duration::days(30);
+    let blobs = vec![
+      blob(&format!("{}_a.png", live), old),
+      blob(&format!("{}_b.png", deleted), old),
+      blob(&format!("{}_c.png", deleted), now),
+      blob("v0_blob", old),
+    ];
+    let referencing_ids = HashSet::from([live.to_string()]);
+    let orphans = orphaned_blobs(blobs, &referencing_ids, kept_after)
+      .into_iter()
+      .map(|blob| blob.file_id)
+      .collect::<Vec<_>>();
+    assert_eq!(orphans, vec![format!("{}_b.png", deleted)]);
+  }
+}
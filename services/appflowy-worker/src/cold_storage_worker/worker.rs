@@ -0,0 +1,168 @@
+use crate::blob_gc_worker::worker::resolve_blob_object_key;
+use crate::error::WorkerError;
+use crate::s3_client::S3ClientImpl;
+use database::blob_access::{get_cold_blobs, update_blob_storage_class};
+use database::blob_gc::select_workspace_ids_after;
+use sqlx::types::chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info, trace, warn};
+use uuid::Uuid;
+
+/// The storage classes whose objects are downloaded like the ones of the default storage class,
+/// without being restored first.
+const INSTANT_RETRIEVAL_STORAGE_CLASSES: [&str; 4] = [
+  "STANDARD_IA",
+  "ONEZONE_IA",
+  "INTELLIGENT_TIERING",
+  "GLACIER_IR",
+];
+
+pub struct ColdStorageConfig {
+  pub enable: bool,
+  /// The blobs not downloaded for this long are moved to `storage_class`.
+  pub cold_after_secs: i64,
+  pub storage_class: String,
+  /// The blobs smaller than this are kept in the default storage class, the infrequent access
+  /// classes bill a minimum size per object.
+  pub min_size_bytes: i64,
+  pub tick_interval_secs: u64,
+  pub workspace_chunk_size: i64,
+}
+
+/// Moves the blobs which weren't downloaded in a while to a cheaper storage class, and records
+/// the class on their metadata. Only the storage classes served right away are accepted, so the
+/// moved blobs are downloaded as before. The workspaces are walked in chunks, one chunk per tick.
+pub async fn run_cold_storage_worker(
+  pg_pool: PgPool,
+  s3_client: Arc<S3ClientImpl>,
+  config: ColdStorageConfig,
+) -> Result<(), WorkerError> {
+  if !config.enable {
+    info!("Cold storage worker is disabled");
+    return Ok(());
+  }
+  if !INSTANT_RETRIEVAL_STORAGE_CLASSES.contains(&config.storage_class.as_str()) {
+    error!(
+      "Cold storage worker is disabled, storage class {} isn't one of {:?}",
+      config.storage_class, INSTANT_RETRIEVAL_STORAGE_CLASSES
+    );
+    return Ok(());
+  }
+  info!(
+    "Starting cold storage worker, storage class: {}, cold after: {}s",
+    config.storage_class, config.cold_after_secs
+  );
+  let cold_storage = ColdStorage {
+    pg_pool,
+    s3_client,
+    config,
+  };
+  let mut tick = interval(std::time::Duration::from_secs(
+    cold_storage.config.tick_interval_secs,
+  ));
+  tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+  let mut cursor: Option<Uuid> = None;
+  loop {
+    tick.tick().await;
+    match cold_storage.transition_next_chunk(cursor.as_ref()).await {
+      Ok(next_cursor) => cursor = next_cursor,
+      Err(err) => error!("[ColdStorage] failed to move cold blobs: {:?}", err),
+    }
+  }
+}
+
+struct ColdStorage {
+  pg_pool: PgPool,
+  s3_client: Arc<S3ClientImpl>,
+  config: ColdStorageConfig,
+}
+
+impl ColdStorage {
+  /// Move the cold blobs of the workspaces following the cursor. Returns the cursor of the next
+  /// chunk, `None` once the pass is over.
+  async fn transition_next_chunk(
+    &self,
+    cursor: Option<&Uuid>,
+  ) -> Result<Option<Uuid>, WorkerError> {
+    let workspace_ids =
+      select_workspace_ids_after(&self.pg_pool, cursor, self.config.workspace_chunk_size)
+        .await
+        .map_err(|err| WorkerError::Internal(err.into()))?;
+    let mut count = 0;
+    for workspace_id in &workspace_ids {
+      // a workspace failing is retried on the next pass, it doesn't hold the others back
+      match self.transition_workspace(workspace_id).await {
+        Ok(workspace_count) => count += workspace_count,
+        Err(err) => error!(
+          "[ColdStorage] failed to move cold blobs of workspace {}: {:?}",
+          workspace_id, err
+        ),
+      }
+    }
+    if count > 0 {
+      info!(
+        "[ColdStorage] moved {} blobs to {} in {} workspaces",
+        count,
+        self.config.storage_class,
+        workspace_ids.len()
+      );
+    }
+    let has_more = workspace_ids.len() as i64 >= self.config.workspace_chunk_size;
+    Ok(if has_more {
+      workspace_ids.last().copied()
+    } else {
+      None
+    })
+  }
+
+  async fn transition_workspace(&self, workspace_id: &Uuid) -> Result<usize, WorkerError> {
+    let older_than = Utc::now() - Duration::seconds(self.config.cold_after_secs);
+    let blobs = get_cold_blobs(&self.pg_pool, workspace_id, older_than)
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    // the blobs sharing an object are all recorded along with the first one
+    let mut transitioned = HashSet::new();
+    for blob in blobs {
+      if blob.file_size < self.config.min_size_bytes {
+        continue;
+      }
+      if let Some(object_key) = &blob.object_key {
+        if !transitioned.insert(object_key.clone()) {
+          continue;
+        }
+      }
+      let Some(object_key) = resolve_blob_object_key(&self.s3_client, workspace_id, &blob).await?
+      else {
+        warn!(
+          "[ColdStorage] no object found for blob {} of workspace {}",
+          blob.file_id, workspace_id
+        );
+        continue;
+      };
+      self
+        .s3_client
+        .set_storage_class(&object_key, &self.config.storage_class)
+        .await?;
+      update_blob_storage_class(
+        &self.pg_pool,
+        workspace_id,
+        &blob.file_id,
+        blob.object_key.as_deref(),
+        &self.config.storage_class,
+      )
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+      trace!(
+        "[ColdStorage] moved blob {} of workspace {} to {}",
+        blob.file_id,
+        workspace_id,
+        self.config.storage_class
+      );
+      transitioned.insert(object_key);
+    }
+    Ok(transitioned.len())
+  }
+}
@@ -1,5 +1,5 @@
 use anyhow::{Context, Error};
-use infra::env_util::get_env_var;
+use infra::env_util::{get_env_var, get_env_var_opt};
 use mailer::config::MailerSetting;
 use secrecy::Secret;
 use serde::Deserialize;
@@ -44,6 +44,7 @@ impl Config {
         secret_key: get_env_var("APPFLOWY_S3_SECRET_KEY", "minioadmin").into(),
         bucket: get_env_var("APPFLOWY_S3_BUCKET", "appflowy"),
         region: get_env_var("APPFLOWY_S3_REGION", ""),
+        presigned_url_endpoint: get_env_var_opt("APPFLOWY_S3_PRESIGNED_URL_ENDPOINT"),
       },
       mailer: MailerSetting {
         smtp_host: get_env_var("APPFLOWY_MAILER_SMTP_HOST", "smtp.gmail.com"),
@@ -133,4 +134,5 @@ pub struct S3Setting {
   pub secret_key: Secret<String>,
   pub bucket: String,
   pub region: String,
+  pub presigned_url_endpoint: Option<String>,
 }
@@ -0,0 +1,629 @@
+use crate::error::WorkerError;
+use crate::mailer::{AFWorkerMailer, WorkspaceExportMailerParam, EXPORT_READY_TEMPLATE};
+use crate::s3_client::{S3Client, S3ClientImpl};
+use anyhow::anyhow;
+use async_zip::base::write::ZipFileWriter;
+use async_zip::{Compression, ZipEntryBuilder};
+use aws_sdk_s3::primitives::ByteStream;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use collab::entity::EncodedCollab;
+use database::notification::insert_user_notification;
+use database::statement_timeout::{
+  begin_with_statement_timeout, is_statement_timeout, AdaptivePageSize,
+};
+use database::workspace_export::{
+  claim_workspace_export_task, complete_workspace_export_task, expire_workspace_export_tasks,
+  fail_stale_workspace_export_tasks, fail_workspace_export_task, update_workspace_export_progress,
+  ExportStage, ExportTaskState,
+};
+use futures::{AsyncReadExt, AsyncWriteExt};
+use redis::aio::ConnectionManager;
+use redis::streams::{StreamId, StreamReadOptions, StreamReadReply};
+use redis::{AsyncCommands, RedisResult, Value};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::types::chrono::{Duration as ChronoDuration, Utc};
+use sqlx::PgPool;
+use std::env::temp_dir;
+use std::ops::DerefMut;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs;
+use tokio::time::{interval, MissedTickBehavior};
+use tokio_util::compat::TokioAsyncWriteCompatExt;
+use tracing::{error, info, trace, warn};
+use uuid::Uuid;
+
+const GROUP_NAME: &str = "export_task_group";
+const CONSUMER_NAME: &str = "appflowy_worker";
+/// Exported archives, and the links to download them, are kept for 7 days.
+const EXPORT_EXPIRATION_DAYS: i64 = 7;
+const EXPORT_CLEANUP_INTERVAL_SECS: u64 = 3600;
+pub const EXPORT_NOTIFICATION_KIND: &str = "workspace_export_ready";
+/// Collabs are read page by page, each page being a statement subject to the statement timeout of
+/// the export. When a page times out, it's read again with half as many collabs.
+const EXPORT_COLLAB_PAGE_SIZE: i64 = 200;
+const MIN_EXPORT_COLLAB_PAGE_SIZE: i64 = 10;
+/// A running export whose progress wasn't updated for this long is taken over when its task is
+/// read again, its worker is assumed to have stopped. When it isn't read again, it's failed by
+/// the cleanup. The progress is updated after each page of collabs and each blob.
+const EXPORT_TASK_LEASE_SECS: i64 = 15 * 60;
+
+pub async fn run_export_worker(
+  pg_pool: PgPool,
+  mut redis_client: ConnectionManager,
+  s3_client: Arc<S3ClientImpl>,
+  mailer: AFWorkerMailer,
+  stream_name: &str,
+  tick_interval_secs: u64,
+  statement_timeout: Duration,
+) -> Result<(), WorkerError> {
+  info!("Starting export worker");
+  if let Err(err) = ensure_consumer_group(stream_name, GROUP_NAME, &mut redis_client).await {
+    error!("Failed to ensure consumer group: {:?}", err);
+  }
+
+  let context = ExportContext {
+    pg_pool,
+    s3_client,
+    mailer,
+    statement_timeout,
+  };
+
+  // When the worker restarts, entries that were delivered to this consumer but never
+  // acknowledged are read again by passing "0" instead of ">".
+  let mut read_pending = true;
+  let mut tick = interval(Duration::from_secs(tick_interval_secs));
+  tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+  let mut cleanup_tick = interval(Duration::from_secs(EXPORT_CLEANUP_INTERVAL_SECS));
+  cleanup_tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+  loop {
+    tokio::select! {
+      _ = cleanup_tick.tick() => {
+        cleanup_expired_exports(&context).await;
+      },
+      _ = tick.tick() => {
+        let id = if read_pending { "0" } else { ">" };
+        let options = StreamReadOptions::default()
+          .group(GROUP_NAME, CONSUMER_NAME)
+          .count(1);
+        let reply: StreamReadReply = match redis_client
+          .xread_options(&[stream_name], &[id], &options)
+          .await
+        {
+          Ok(reply) => reply,
+          Err(err) => {
+            error!("Failed to read export tasks from Redis stream: {:?}", err);
+            if let Some("NOGROUP") = err.code() {
+              if let Err(err) = ensure_consumer_group(stream_name, GROUP_NAME, &mut redis_client).await {
+                error!("Failed to ensure consumer group: {:?}", err);
+              }
+            }
+            continue;
+          },
+        };
+
+        let entries = reply
+          .keys
+          .into_iter()
+          .flat_map(|key| key.ids)
+          .collect::<Vec<_>>();
+        if read_pending && entries.is_empty() {
+          read_pending = false;
+        }
+
+        for entry in entries {
+          match ExportTask::try_from(&entry) {
+            Ok(task) => {
+              if let Err(err) = process_export_task(&context, &task).await {
+                error!("[Export] task {} failed: {:?}", task.task_id, err);
+                if let Err(err) =
+                  fail_workspace_export_task(&context.pg_pool, &task.task_id, &err.to_string()).await
+                {
+                  error!("[Export] failed to mark task {} as failed: {:?}", task.task_id, err);
+                }
+              }
+            },
+            Err(err) => error!("Failed to deserialize export task: {:?}", err),
+          }
+
+          let ack: RedisResult<()> = redis_client.xack(stream_name, GROUP_NAME, &[&entry.id]).await;
+          if let Err(err) = ack {
+            error!("Failed to acknowledge export task {}: {:?}", entry.id, err);
+          }
+        }
+      },
+    }
+  }
+}
+
+struct ExportContext {
+  pg_pool: PgPool,
+  s3_client: Arc<S3ClientImpl>,
+  mailer: AFWorkerMailer,
+  statement_timeout: Duration,
+}
+
+/// Build the archive of a workspace and upload it to a temporary location. The archive
+/// contains:
+/// - `manifest.json`: workspace information and the number of exported items
+/// - `collabs.ndjson`: one collab per line, with its doc state encoded in base64
+/// - `blobs/`: every file uploaded to the workspace
+///
+/// The tasks are pushed to the stream by the outbox relay, which may push the same task more
+/// than once. The task is claimed before it's processed, so a task which is already running or
+/// finished is skipped, unless the worker running it stopped, see [EXPORT_TASK_LEASE_SECS].
+async fn process_export_task(
+  context: &ExportContext,
+  task: &ExportTask,
+) -> Result<(), WorkerError> {
+  let claimed =
+    claim_workspace_export_task(&context.pg_pool, &task.task_id, EXPORT_TASK_LEASE_SECS)
+      .await
+      .map_err(|err| anyhow!(err))?;
+  if claimed.is_none() {
+    trace!("[Export] task {} is not pending, skip it", task.task_id);
+    return Ok(());
+  }
+  info!(
+    "[Export] start exporting workspace:{}, task:{}",
+    task.workspace_id, task.task_id
+  );
+  let archive_path = temp_dir().join(format!("workspace_export_{}.zip", task.task_id));
+  let result = write_archive(context, task, &archive_path).await;
+  let result = match result {
+    Ok(()) => upload_archive(context, task, &archive_path).await,
+    Err(err) => Err(err),
+  };
+  if let Err(err) = fs::remove_file(&archive_path).await {
+    warn!(
+      "[Export] failed to remove archive {:?}: {}",
+      archive_path, err
+    );
+  }
+  result
+}
+
+async fn write_archive(
+  context: &ExportContext,
+  task: &ExportTask,
+  archive_path: &Path,
+) -> Result<(), WorkerError> {
+  let file = fs::File::create(archive_path).await?.compat_write();
+  let mut writer = ZipFileWriter::new(file);
+  let mut bytes_written = 0i64;
+
+  update_workspace_export_progress(
+    &context.pg_pool,
+    &task.task_id,
+    ExportTaskState::Running,
+    ExportStage::Collabs,
+    bytes_written,
+  )
+  .await
+  .map_err(|err| anyhow!(err))?;
+  let num_collabs = write_collabs(context, task, &mut writer, &mut bytes_written).await?;
+
+  update_workspace_export_progress(
+    &context.pg_pool,
+    &task.task_id,
+    ExportTaskState::Running,
+    ExportStage::Blobs,
+    bytes_written,
+  )
+  .await
+  .map_err(|err| anyhow!(err))?;
+  let num_blobs = write_blobs(context, task, &mut writer, &mut bytes_written).await?;
+
+  let manifest = json!({
+    "version": 1,
+    "task_id": task.task_id,
+    "workspace_id": task.workspace_id,
+    "workspace_name": task.workspace_name,
+    "exported_at": Utc::now().timestamp(),
+    "num_collabs": num_collabs,
+    "num_blobs": num_blobs,
+  });
+  let manifest = serde_json::to_vec_pretty(&manifest).map_err(|err| anyhow!(err))?;
+  writer
+    .write_entry_whole(
+      ZipEntryBuilder::new("manifest.json".to_string().into(), Compression::Deflate),
+      &manifest,
+    )
+    .await?;
+  writer.close().await?;
+  Ok(())
+}
+
+async fn write_collabs<W: futures::AsyncWrite + Unpin>(
+  context: &ExportContext,
+  task: &ExportTask,
+  writer: &mut ZipFileWriter<W>,
+  bytes_written: &mut i64,
+) -> Result<usize, WorkerError> {
+  let builder = ZipEntryBuilder::new("collabs.ndjson".to_string().into(), Compression::Deflate);
+  let mut entry = writer.write_entry_stream(builder).await?;
+  let mut num_collabs = 0;
+
+  let mut page_size = AdaptivePageSize::new(EXPORT_COLLAB_PAGE_SIZE, MIN_EXPORT_COLLAB_PAGE_SIZE);
+  let mut cursor: Option<(String, i32)> = None;
+  loop {
+    let rows = match select_collab_page(
+      context,
+      &task.workspace_id,
+      cursor.as_ref(),
+      page_size.get(),
+    )
+    .await
+    {
+      Ok(rows) => rows,
+      Err(err) if is_statement_timeout(&err) && page_size.shrink() => {
+        warn!(
+          "[Export] reading collabs of {} timed out, retry with pages of {}",
+          task.workspace_id,
+          page_size.get()
+        );
+        continue;
+      },
+      Err(err) => return Err(anyhow!(err).into()),
+    };
+    let is_last_page = (rows.len() as i64) < page_size.get();
+
+    for (object_id, partition_key, blob, encrypted) in rows {
+      let doc_state = if blob.is_empty() {
+        // The collab content has been moved to S3
+        let key = collab_key(&task.workspace_id, &object_id);
+        let mut resp = context.s3_client.get_blob_stream(&key).await?;
+        let mut compressed = Vec::new();
+        resp.stream.read_to_end(&mut compressed).await?;
+        zstd::decode_all(&*compressed)?
+      } else {
+        EncodedCollab::decode_from_bytes(&blob)
+          .map_err(|err| anyhow!("Failed to decode collab {}: {}", object_id, err))?
+          .doc_state
+          .to_vec()
+      };
+
+      let mut line = serde_json::to_vec(&json!({
+        "object_id": object_id,
+        "collab_type": partition_key,
+        "doc_state": STANDARD.encode(&doc_state),
+        // the doc state of an encrypted collab is exported as it's stored: a sequence of updates
+        // only its members can read
+        "encrypted": encrypted,
+      }))
+      .map_err(|err| anyhow!(err))?;
+      line.push(b'\n');
+      entry.write_all(&line).await?;
+      *bytes_written += line.len() as i64;
+      num_collabs += 1;
+      cursor = Some((object_id, partition_key));
+    }
+
+    update_workspace_export_progress(
+      &context.pg_pool,
+      &task.task_id,
+      ExportTaskState::Running,
+      ExportStage::Collabs,
+      *bytes_written,
+    )
+    .await
+    .map_err(|err| anyhow!(err))?;
+    if is_last_page {
+      break;
+    }
+  }
+  entry.close().await?;
+
+  trace!(
+    "[Export] {} exported {} collabs",
+    task.workspace_id,
+    num_collabs
+  );
+  Ok(num_collabs)
+}
+
+/// Read the collabs of the workspace following the given `(oid, partition_key)` cursor.
+async fn select_collab_page(
+  context: &ExportContext,
+  workspace_id: &Uuid,
+  cursor: Option<&(String, i32)>,
+  limit: i64,
+) -> Result<Vec<(String, i32, Vec<u8>, bool)>, sqlx::Error> {
+  let (oid, partition_key) = match cursor {
+    Some((oid, partition_key)) => (Some(oid.as_str()), Some(*partition_key)),
+    None => (None, None),
+  };
+  let mut txn = begin_with_statement_timeout(&context.pg_pool, context.statement_timeout).await?;
+  let rows = sqlx::query_as::<_, (String, i32, Vec<u8>, bool)>(
+    r#"
+      SELECT c.oid, c.partition_key, c.blob, m.oid IS NOT NULL AS encrypted
+      FROM af_collab c
+      LEFT JOIN af_collab_mode m ON m.oid = c.oid AND m.mode = 1
+      WHERE c.workspace_id = $1 AND c.deleted_at IS NULL
+        AND ($2::TEXT IS NULL OR (c.oid, c.partition_key) > ($2, $3))
+      ORDER BY c.oid, c.partition_key
+      LIMIT $4
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(oid)
+  .bind(partition_key)
+  .bind(limit)
+  .fetch_all(txn.deref_mut())
+  .await?;
+  txn.commit().await?;
+  Ok(rows)
+}
+
+async fn write_blobs<W: futures::AsyncWrite + Unpin>(
+  context: &ExportContext,
+  task: &ExportTask,
+  writer: &mut ZipFileWriter<W>,
+  bytes_written: &mut i64,
+) -> Result<usize, WorkerError> {
+  let blobs = sqlx::query_as::<_, (String, Option<String>)>(
+    r#"
+      SELECT file_id, object_key FROM af_blob_metadata
+      WHERE workspace_id = $1
+    "#,
+  )
+  .bind(task.workspace_id)
+  .fetch_all(&context.pg_pool)
+  .await
+  .map_err(|err| anyhow!(err))?;
+
+  let mut num_blobs = 0;
+  for (file_id, object_key) in blobs {
+    let object_key =
+      match blob_object_key(context, &task.workspace_id, &file_id, object_key).await? {
+        Some(key) => key,
+        None => {
+          warn!(
+            "[Export] blob {} of workspace {} not found, skip it",
+            file_id, task.workspace_id
+          );
+          continue;
+        },
+      };
+
+    let mut resp = context.s3_client.get_blob_stream(&object_key).await?;
+    let name = format!("blobs/{}", file_id);
+    let builder = ZipEntryBuilder::new(name.into(), Compression::Stored);
+    let mut entry = writer.write_entry_stream(builder).await?;
+    let copied = futures::io::copy(&mut resp.stream, &mut entry).await?;
+    entry.close().await?;
+    *bytes_written += copied as i64;
+    num_blobs += 1;
+
+    update_workspace_export_progress(
+      &context.pg_pool,
+      &task.task_id,
+      ExportTaskState::Running,
+      ExportStage::Blobs,
+      *bytes_written,
+    )
+    .await
+    .map_err(|err| anyhow!(err))?;
+  }
+  Ok(num_blobs)
+}
+
+/// Returns the key the blob is stored under. Recent blobs record it in the metadata. Files
+/// uploaded with the v1 api before that are stored under `{workspace_id}/{parent_dir}/{file_id}`
+/// and recorded as `{parent_dir}_{file_id}`. Both parts may contain `_`, so every split is tried.
+/// Older files are stored as `{workspace_id}/{file_id}`.
+async fn blob_object_key(
+  context: &ExportContext,
+  workspace_id: &Uuid,
+  file_id: &str,
+  object_key: Option<String>,
+) -> Result<Option<String>, WorkerError> {
+  if let Some(key) = object_key {
+    return Ok(Some(key));
+  }
+  for (index, _) in file_id.match_indices('_') {
+    let (parent_dir, id) = (&file_id[..index], &file_id[index + 1..]);
+    let key = format!("{}/{}/{}", workspace_id, parent_dir, id);
+    if context.s3_client.is_blob_exist(&key).await? {
+      return Ok(Some(key));
+    }
+  }
+
+  let key = format!("{}/{}", workspace_id, file_id);
+  if context.s3_client.is_blob_exist(&key).await? {
+    return Ok(Some(key));
+  }
+  Ok(None)
+}
+
+async fn upload_archive(
+  context: &ExportContext,
+  task: &ExportTask,
+  archive_path: &Path,
+) -> Result<(), WorkerError> {
+  let archive_size = fs::metadata(archive_path).await?.len() as i64;
+  update_workspace_export_progress(
+    &context.pg_pool,
+    &task.task_id,
+    ExportTaskState::Running,
+    ExportStage::Uploading,
+    archive_size,
+  )
+  .await
+  .map_err(|err| anyhow!(err))?;
+
+  let s3_key = export_key(&task.workspace_id, &task.task_id);
+  let body = ByteStream::from_path(archive_path)
+    .await
+    .map_err(|err| anyhow!("Failed to read archive: {}", err))?;
+  context
+    .s3_client
+    .put_blob(&s3_key, body, Some("application/zip"))
+    .await?;
+
+  let expires_at = Utc::now() + ChronoDuration::days(EXPORT_EXPIRATION_DAYS);
+  let download_url = context
+    .s3_client
+    .presigned_get_url(
+      &s3_key,
+      Duration::from_secs(EXPORT_EXPIRATION_DAYS as u64 * 24 * 3600),
+    )
+    .await?;
+
+  complete_workspace_export_task(
+    &context.pg_pool,
+    &task.task_id,
+    &s3_key,
+    &download_url,
+    archive_size,
+    expires_at,
+  )
+  .await
+  .map_err(|err| anyhow!(err))?;
+  info!(
+    "[Export] workspace:{} exported, task:{}, size:{}",
+    task.workspace_id, task.task_id, archive_size
+  );
+
+  let expires_at = expires_at.format("%Y-%m-%d %H:%M UTC").to_string();
+  if let Err(err) = insert_user_notification(
+    &context.pg_pool,
+    task.uid,
+    Some(&task.workspace_id),
+    EXPORT_NOTIFICATION_KIND,
+    &json!({
+      "task_id": task.task_id,
+      "workspace_name": task.workspace_name,
+      "download_url": download_url,
+      "expires_at": expires_at,
+    }),
+  )
+  .await
+  {
+    error!("[Export] failed to insert notification: {:?}", err);
+  }
+
+  let param = WorkspaceExportMailerParam {
+    user_name: task.user_name.clone(),
+    workspace_id: task.workspace_id.to_string(),
+    workspace_name: task.workspace_name.clone(),
+    download_url,
+    expires_at,
+  };
+  match serde_json::to_value(param) {
+    Ok(value) => {
+      if let Err(err) = context
+        .mailer
+        .send_email_template(
+          Some(task.user_name.clone()),
+          &task.user_email,
+          EXPORT_READY_TEMPLATE,
+          value,
+          "Your workspace export is ready",
+        )
+        .await
+      {
+        error!("[Export] failed to send export email: {}", err);
+      }
+    },
+    Err(err) => error!("[Export] failed to serialize mailer param: {}", err),
+  }
+  Ok(())
+}
+
+async fn cleanup_expired_exports(context: &ExportContext) {
+  match fail_stale_workspace_export_tasks(&context.pg_pool, EXPORT_TASK_LEASE_SECS).await {
+    Ok(stale) => {
+      for row in stale {
+        warn!(
+          "[Export] task {} of workspace {} was interrupted",
+          row.task_id, row.workspace_id
+        );
+      }
+    },
+    Err(err) => error!("[Export] failed to fail stale export tasks: {:?}", err),
+  }
+
+  let expired = match expire_workspace_export_tasks(&context.pg_pool).await {
+    Ok(expired) => expired,
+    Err(err) => {
+      error!("[Export] failed to expire export tasks: {:?}", err);
+      return;
+    },
+  };
+
+  for row in expired {
+    if let Some(s3_key) = row.s3_key {
+      if let Err(err) = context.s3_client.delete_blob(&s3_key).await {
+        error!(
+          "[Export] failed to delete expired archive {}: {:?}",
+          s3_key, err
+        );
+      }
+    }
+  }
+}
+
+#[inline]
+fn export_key(workspace_id: &Uuid, task_id: &Uuid) -> String {
+  format!("temp/export/{}/{}.zip", workspace_id, task_id)
+}
+
+#[inline]
+fn collab_key(workspace_id: &Uuid, object_id: &str) -> String {
+  format!(
+    "collabs/{}/{}/encoded_collab.v1.zstd",
+    workspace_id, object_id
+  )
+}
+
+async fn ensure_consumer_group(
+  stream_key: &str,
+  group_name: &str,
+  redis_client: &mut ConnectionManager,
+) -> Result<(), WorkerError> {
+  let result: RedisResult<()> = redis_client
+    .xgroup_create_mkstream(stream_key, group_name, "0")
+    .await;
+
+  if let Err(redis_error) = result {
+    if let Some("BUSYGROUP") = redis_error.code() {
+      return Ok(());
+    }
+    return Err(WorkerError::Internal(redis_error.into()));
+  }
+  Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportTask {
+  pub task_id: Uuid,
+  pub workspace_id: Uuid,
+  pub uid: i64,
+  pub user_name: String,
+  pub user_email: String,
+  pub workspace_name: String,
+  #[serde(default)]
+  pub created_at: Option<i64>,
+}
+
+impl TryFrom<&StreamId> for ExportTask {
+  type Error = WorkerError;
+
+  fn try_from(stream_id: &StreamId) -> Result<Self, Self::Error> {
+    let task_str = match stream_id.map.get("task") {
+      Some(Value::Data(data)) => String::from_utf8_lossy(data),
+      _ => {
+        return Err(WorkerError::Internal(anyhow!(
+          "Unexpected value type for export task field: {:?}",
+          stream_id
+        )))
+      },
+    };
+    serde_json::from_str::<ExportTask>(&task_str).map_err(|err| WorkerError::Internal(err.into()))
+  }
+}
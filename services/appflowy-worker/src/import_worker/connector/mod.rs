@@ -0,0 +1,213 @@
+//! The apps an import can read from. A [SourceConnector] turns an unzipped export into the views
+//! of the imported pages and the collabs and attachments backing them. What comes after, the
+//! import graph, the progress reports and the resume of unacked tasks, doesn't depend on the
+//! source.
+mod notion;
+mod obsidian;
+
+pub use notion::NotionConnector;
+pub use obsidian::ObsidianConnector;
+
+use crate::error::ImportError;
+use axum::async_trait;
+use collab::entity::EncodedCollab;
+use collab_entity::CollabType;
+use collab_folder::hierarchy_builder::ParentChildViews;
+use database_entity::dto::ImportSource;
+use futures::stream::LocalBoxStream;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+#[async_trait(?Send)]
+pub trait SourceConnector {
+  fn source(&self) -> ImportSource;
+
+  /// Reads the export: the hierarchy of its views, then its items one by one.
+  async fn import(self: Box<Self>) -> Result<ImportedSource, ImportError>;
+}
+
+pub struct ImportedSource {
+  /// The views of the imported items, nested as in the source, under the space they're imported
+  /// into.
+  pub hierarchy: Vec<ParentChildViews>,
+  pub items: LocalBoxStream<'static, ImportedItem>,
+}
+
+/// A page or a database of the source, along with the collabs and files it's made of.
+pub struct ImportedItem {
+  pub kind: ImportedItemKind,
+  pub collabs: Vec<ImportedItemCollab>,
+  pub attachments: Vec<ImportedAttachment>,
+  /// What couldn't be converted, the item being imported anyway.
+  pub warnings: Vec<String>,
+  /// The targets of the links to pages or files missing from the export, kept as text.
+  pub unresolved_links: Vec<String>,
+}
+
+pub enum ImportedItemKind {
+  Document,
+  Database {
+    database_id: String,
+    view_ids: Vec<String>,
+    row_document_ids: Vec<String>,
+  },
+}
+
+pub struct ImportedItemCollab {
+  pub object_id: String,
+  pub collab_type: CollabType,
+  pub encoded_collab: EncodedCollab,
+}
+
+/// A file referenced by the collab `object_id`, uploaded along with it.
+pub struct ImportedAttachment {
+  pub object_id: String,
+  pub file_path: String,
+}
+
+impl Display for ImportedItem {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let kind = match &self.kind {
+      ImportedItemKind::Document => "document",
+      ImportedItemKind::Database { .. } => "database",
+    };
+    write!(
+      f,
+      "ImportedItem {{ kind: {}, collabs: {}, attachments: {} }}",
+      kind,
+      self.collabs.len(),
+      self.attachments.len()
+    )
+  }
+}
+
+/// Opens the connector of `source`, or of the source detected from the content of `dir` when
+/// the task doesn't tell.
+pub fn open_connector(
+  source: Option<ImportSource>,
+  uid: i64,
+  dir: &PathBuf,
+  workspace_id: String,
+  host: String,
+) -> Result<Box<dyn SourceConnector>, ImportError> {
+  let source = source.unwrap_or_else(|| detect_source(dir));
+  let connector: Box<dyn SourceConnector> = match source {
+    ImportSource::Notion => Box::new(NotionConnector::new(uid, dir, workspace_id, host)?),
+    ImportSource::Obsidian => Box::new(ObsidianConnector::new(uid, dir, workspace_id, host)),
+  };
+  Ok(connector)
+}
+
+/// An Obsidian vault has a `.obsidian` settings directory, and its notes are named after their
+/// title. Notion suffixes the name of every exported page with the page id.
+pub fn detect_source(dir: &Path) -> ImportSource {
+  let mut notes = 0;
+  let mut notion_pages = 0;
+  let mut pending = vec![(dir.to_path_buf(), 0)];
+  while let Some((dir, depth)) = pending.pop() {
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+      continue;
+    };
+    for entry in entries.flatten() {
+      let path = entry.path();
+      let name = entry.file_name().to_string_lossy().to_string();
+      if path.is_dir() {
+        if name == ".obsidian" {
+          return ImportSource::Obsidian;
+        }
+        if depth < 2 && !name.starts_with('.') {
+          pending.push((path, depth + 1));
+        }
+      } else if let Some(stem) = name.strip_suffix(".md") {
+        notes += 1;
+        if has_notion_id(stem) {
+          notion_pages += 1;
+        }
+      }
+    }
+  }
+  if notes > 0 && notion_pages == 0 {
+    ImportSource::Obsidian
+  } else {
+    ImportSource::Notion
+  }
+}
+
+fn has_notion_id(stem: &str) -> bool {
+  stem
+    .rsplit_once(' ')
+    .map(|(_, id)| id.len() == 32 && id.chars().all(|c| c.is_ascii_hexdigit()))
+    .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use collab_importer::zip_tool::sync_zip::sync_unzip;
+  use futures::StreamExt;
+  use std::env::temp_dir;
+  use uuid::Uuid;
+
+  pub(crate) fn unzip_fixture(archive: &str) -> PathBuf {
+    let archive = Path::new(env!("CARGO_MANIFEST_DIR")).join(archive);
+    let storage_dir = temp_dir().join(format!("import_fixture_{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&storage_dir).unwrap();
+    sync_unzip(archive, storage_dir, None).unwrap().unzip_dir
+  }
+
+  /// The nested names and layouts of the views, two spaces of indent per level.
+  pub(crate) fn outline(views: &[ParentChildViews]) -> Vec<String> {
+    fn walk(views: &[ParentChildViews], depth: usize, lines: &mut Vec<String>) {
+      for view in views {
+        lines.push(format!(
+          "{}{} ({:?})",
+          "  ".repeat(depth),
+          view.view.name,
+          view.view.layout
+        ));
+        walk(&view.children, depth + 1, lines);
+      }
+    }
+    let mut lines = vec![];
+    walk(views, 0, &mut lines);
+    lines
+  }
+
+  pub(crate) async fn import_fixture(
+    source: Option<ImportSource>,
+    archive: &str,
+  ) -> (ImportSource, Vec<ParentChildViews>, Vec<ImportedItem>) {
+    let dir = unzip_fixture(archive);
+    let connector = open_connector(
+      source,
+      1,
+      &dir,
+      Uuid::new_v4().to_string(),
+      "http://localhost".to_string(),
+    )
+    .unwrap();
+    let source = connector.source();
+    let imported = connector.import().await.unwrap();
+    let items = imported.items.collect::<Vec<_>>().await;
+    (source, imported.hierarchy, items)
+  }
+
+  #[test]
+  fn detect_source_test() {
+    assert_eq!(
+      detect_source(&unzip_fixture("tests/asset/obsidian_vault.zip")),
+      ImportSource::Obsidian
+    );
+    assert_eq!(
+      detect_source(&unzip_fixture("../../tests/workspace/asset/blog_post.zip")),
+      ImportSource::Notion
+    );
+  }
+
+  #[test]
+  fn notion_page_names_carry_their_id_test() {
+    assert!(has_notion_id("Blog Post 104d4deadd2c808aa7dbd79eadeff0eb"));
+    assert!(!has_notion_id("Blog Post"));
+    assert!(!has_notion_id("Weekly review 2024"));
+  }
+}
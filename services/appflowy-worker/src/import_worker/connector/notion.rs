@@ -0,0 +1,136 @@
+use crate::error::ImportError;
+use crate::import_worker::connector::{
+  ImportedAttachment, ImportedItem, ImportedItemCollab, ImportedItemKind, ImportedSource,
+  SourceConnector,
+};
+use axum::async_trait;
+use collab_importer::imported_collab::ImportType;
+use collab_importer::notion::NotionImporter;
+use database_entity::dto::ImportSource;
+use futures::StreamExt;
+use std::path::PathBuf;
+
+/// Reads a Notion export: a markdown file per page, named after the title and the id of the
+/// page, and a csv file per database.
+pub struct NotionConnector {
+  importer: NotionImporter,
+}
+
+impl NotionConnector {
+  pub fn new(
+    uid: i64,
+    dir: &PathBuf,
+    workspace_id: String,
+    host: String,
+  ) -> Result<Self, ImportError> {
+    let importer =
+      NotionImporter::new(uid, dir, workspace_id, host).map_err(ImportError::ImportCollabError)?;
+    Ok(Self { importer })
+  }
+}
+
+#[async_trait(?Send)]
+impl SourceConnector for NotionConnector {
+  fn source(&self) -> ImportSource {
+    ImportSource::Notion
+  }
+
+  async fn import(self: Box<Self>) -> Result<ImportedSource, ImportError> {
+    let imported = self
+      .importer
+      .import()
+      .await
+      .map_err(ImportError::ImportCollabError)?;
+    let hierarchy = imported.build_nested_views().await.into_inner();
+    let items = imported
+      .into_collab_stream()
+      .await
+      .map(|info| ImportedItem {
+        kind: match info.import_type {
+          ImportType::Database {
+            database_id,
+            view_ids,
+            row_document_ids,
+          } => ImportedItemKind::Database {
+            database_id,
+            view_ids,
+            row_document_ids,
+          },
+          ImportType::Document => ImportedItemKind::Document,
+        },
+        collabs: info
+          .imported_collabs
+          .into_iter()
+          .map(|collab| ImportedItemCollab {
+            object_id: collab.object_id,
+            collab_type: collab.collab_type,
+            encoded_collab: collab.encoded_collab,
+          })
+          .collect(),
+        attachments: info
+          .resources
+          .into_iter()
+          .flat_map(|resource| {
+            let object_id = resource.object_id;
+            resource
+              .files
+              .into_iter()
+              .map(move |file_path| ImportedAttachment {
+                object_id: object_id.clone(),
+                file_path,
+              })
+          })
+          .collect(),
+        warnings: vec![],
+        unresolved_links: vec![],
+      })
+      .boxed_local();
+    Ok(ImportedSource { hierarchy, items })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::import_worker::connector::tests::{import_fixture, outline};
+  use crate::import_worker::connector::ImportedItemKind;
+  use database_entity::dto::ImportSource;
+
+  #[tokio::test]
+  async fn notion_blog_post_golden_test() {
+    let (source, hierarchy, items) =
+      import_fixture(None, "../../tests/workspace/asset/blog_post.zip").await;
+    assert_eq!(source, ImportSource::Notion);
+    assert_eq!(
+      outline(&hierarchy),
+      vec!["Imported Space (Document)", "  Blog Post (Document)"]
+    );
+    let blog_post_id = &hierarchy[0].children[0].view.id;
+    assert_eq!(items.len(), 1);
+    assert!(matches!(items[0].kind, ImportedItemKind::Document));
+    assert_eq!(items[0].collabs[0].object_id, *blog_post_id);
+    assert_eq!(items[0].attachments.len(), 3);
+    assert!(items[0]
+      .attachments
+      .iter()
+      .all(|attachment| attachment.object_id == *blog_post_id));
+  }
+
+  #[tokio::test]
+  async fn notion_project_and_task_golden_test() {
+    let (_, hierarchy, items) = import_fixture(
+      Some(ImportSource::Notion),
+      "../../tests/workspace/asset/project&task.zip",
+    )
+    .await;
+    let lines = outline(&hierarchy);
+    assert_eq!(
+      &lines[..2],
+      &["Imported Space (Document)", "  Projects & Tasks (Document)"]
+    );
+    assert!(lines.contains(&"    Projects (Grid)".to_string()));
+    assert!(lines.contains(&"    Tasks (Grid)".to_string()));
+    assert!(items
+      .iter()
+      .any(|item| matches!(item.kind, ImportedItemKind::Database { .. })));
+  }
+}
@@ -0,0 +1,600 @@
+use crate::error::ImportError;
+use crate::import_worker::connector::{
+  ImportedAttachment, ImportedItem, ImportedItemCollab, ImportedItemKind, ImportedSource,
+  SourceConnector,
+};
+use anyhow::anyhow;
+use axum::async_trait;
+use collab::entity::EncodedCollab;
+use collab_document::document::Document;
+use collab_document::document_data::default_document_collab_data;
+use collab_document::importer::md_importer::MDImporter;
+use collab_entity::CollabType;
+use collab_folder::hierarchy_builder::{NestedChildViewBuilder, ParentChildViews};
+use collab_folder::{SpaceInfo, ViewLayout};
+use collab_importer::util::FileId;
+use database_entity::dto::ImportSource;
+use futures::{stream, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::warn;
+use uuid::Uuid;
+
+const IMPORTED_SPACE_NAME: &str = "Imported Space";
+/// Stand in for the links to other notes while the markdown is converted, the index of the
+/// linked page in between.
+const MENTION_START: char = '\u{E000}';
+const MENTION_END: char = '\u{E001}';
+
+/// Reads an Obsidian vault: a markdown note per page, nested in folders, the notes linking each
+/// other with `[[wikilinks]]`. Every folder holding notes becomes a page too. The links become
+/// mentions of the linked pages and the embedded files are uploaded along with their note.
+pub struct ObsidianConnector {
+  uid: i64,
+  dir: PathBuf,
+  workspace_id: String,
+  host: String,
+}
+
+impl ObsidianConnector {
+  pub fn new(uid: i64, dir: &Path, workspace_id: String, host: String) -> Self {
+    Self {
+      uid,
+      dir: dir.to_path_buf(),
+      workspace_id,
+      host,
+    }
+  }
+}
+
+#[async_trait(?Send)]
+impl SourceConnector for ObsidianConnector {
+  fn source(&self) -> ImportSource {
+    ImportSource::Obsidian
+  }
+
+  async fn import(self: Box<Self>) -> Result<ImportedSource, ImportError> {
+    let dir = self.dir.clone();
+    let vault = tokio::task::spawn_blocking(move || scan_vault(&dir))
+      .await
+      .map_err(|err| ImportError::Internal(err.into()))?;
+
+    let space_id = Uuid::new_v4().to_string();
+    let mut space = NestedChildViewBuilder::new(self.uid, self.workspace_id.clone())
+      .with_view_id(space_id.clone())
+      .with_name(IMPORTED_SPACE_NAME)
+      .with_layout(ViewLayout::Document)
+      .with_extra(|extra| extra.with_space_info(SpaceInfo::default()).build())
+      .build();
+    space.children = entry_views(self.uid, &space_id, &vault.entries);
+
+    let mut pages = vec![(space_id, None)];
+    flatten_entries(&vault.entries, &mut pages);
+    let links = Arc::new(VaultLinks {
+      pages: vault.pages,
+      files: vault.files,
+      workspace_id: self.workspace_id,
+      host: self.host,
+    });
+    let items = stream::iter(pages)
+      .then(move |(view_id, note)| {
+        let links = links.clone();
+        async move {
+          let mut warnings = vec![];
+          let (encoded_collab, attachments, unresolved_links) = match note {
+            None => (empty_document(&view_id), vec![], vec![]),
+            Some(path) => match note_document(&view_id, &path, &links).await {
+              Ok(document) => document,
+              Err(err) => {
+                warn!("[Import]: failed to convert note {:?}: {}", path, err);
+                warnings.push(format!(
+                  "The note couldn't be converted and was imported empty: {}",
+                  err
+                ));
+                (empty_document(&view_id), vec![], vec![])
+              },
+            },
+          };
+          ImportedItem {
+            kind: ImportedItemKind::Document,
+            collabs: encoded_collab
+              .map(|encoded_collab| ImportedItemCollab {
+                object_id: view_id,
+                collab_type: CollabType::Document,
+                encoded_collab,
+              })
+              .into_iter()
+              .collect(),
+            attachments,
+            warnings,
+            unresolved_links,
+          }
+        }
+      })
+      .boxed_local();
+
+    Ok(ImportedSource {
+      hierarchy: vec![space],
+      items,
+    })
+  }
+}
+
+/// A folder or a note of the vault.
+struct VaultEntry {
+  view_id: String,
+  name: String,
+  /// The markdown file of a note, `None` for a folder.
+  note: Option<PathBuf>,
+  children: Vec<VaultEntry>,
+}
+
+struct Vault {
+  entries: Vec<VaultEntry>,
+  pages: HashMap<String, String>,
+  files: HashMap<String, PathBuf>,
+}
+
+/// What the wikilinks of a note may point to.
+struct VaultLinks {
+  /// The pages of the notes, by their lowercased path in the vault without the extension and by
+  /// their lowercased name.
+  pages: HashMap<String, String>,
+  /// The files which aren't notes, by their lowercased path in the vault and by their
+  /// lowercased name.
+  files: HashMap<String, PathBuf>,
+  workspace_id: String,
+  host: String,
+}
+
+impl VaultLinks {
+  fn page(&self, target: &str) -> Option<String> {
+    let key = link_path(target).to_lowercase();
+    let key = key.strip_suffix(".md").unwrap_or(&key);
+    self
+      .pages
+      .get(key)
+      .or_else(|| self.pages.get(key.rsplit('/').next().unwrap_or(key)))
+      .cloned()
+  }
+
+  fn file(&self, target: &str) -> Option<&PathBuf> {
+    let key = link_path(target).to_lowercase();
+    self
+      .files
+      .get(&key)
+      .or_else(|| self.files.get(key.rsplit('/').next().unwrap_or(&key)))
+  }
+}
+
+/// The linked note or file, without the heading or block the link points to.
+fn link_path(target: &str) -> &str {
+  target.split(['#', '^']).next().unwrap_or(target).trim()
+}
+
+/// Exports usually wrap the vault in a directory of its own.
+fn vault_root(dir: &Path) -> PathBuf {
+  let mut root = dir.to_path_buf();
+  loop {
+    if root.join(".obsidian").is_dir() {
+      return root;
+    }
+    match visible_entries(&root).as_slice() {
+      [only] if only.is_dir() => root = only.clone(),
+      _ => return root,
+    }
+  }
+}
+
+fn scan_vault(dir: &Path) -> Vault {
+  let root = vault_root(dir);
+  let mut pages = HashMap::new();
+  let mut files = HashMap::new();
+  let entries = scan_dir(&root, &root, &mut pages, &mut files);
+  Vault {
+    entries,
+    pages,
+    files,
+  }
+}
+
+/// The folders first, then the notes, each sorted by name as Obsidian lists them. The folders
+/// without any note are left out.
+fn scan_dir(
+  dir: &Path,
+  root: &Path,
+  pages: &mut HashMap<String, String>,
+  files: &mut HashMap<String, PathBuf>,
+) -> Vec<VaultEntry> {
+  let (dirs, others): (Vec<_>, Vec<_>) = visible_entries(dir)
+    .into_iter()
+    .partition(|path| path.is_dir());
+  let mut entries = vec![];
+  for path in dirs {
+    let children = scan_dir(&path, root, pages, files);
+    if !children.is_empty() {
+      entries.push(VaultEntry {
+        view_id: Uuid::new_v4().to_string(),
+        name: file_name(&path),
+        note: None,
+        children,
+      });
+    }
+  }
+  for path in others {
+    let relative = path
+      .strip_prefix(root)
+      .unwrap_or(&path)
+      .to_string_lossy()
+      .replace('\\', "/")
+      .to_lowercase();
+    match relative.strip_suffix(".md") {
+      Some(relative) => {
+        let view_id = Uuid::new_v4().to_string();
+        let name = file_name(&path)
+          .strip_suffix(".md")
+          .map(str::to_string)
+          .unwrap_or_default();
+        pages.insert(relative.to_string(), view_id.clone());
+        pages
+          .entry(name.to_lowercase())
+          .or_insert_with(|| view_id.clone());
+        entries.push(VaultEntry {
+          view_id,
+          name,
+          note: Some(path),
+          children: vec![],
+        });
+      },
+      None => {
+        files
+          .entry(file_name(&path).to_lowercase())
+          .or_insert_with(|| path.clone());
+        files.insert(relative, path);
+      },
+    }
+  }
+  entries
+}
+
+fn visible_entries(dir: &Path) -> Vec<PathBuf> {
+  let mut entries = std::fs::read_dir(dir)
+    .map(|entries| {
+      entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+          let name = file_name(path);
+          !name.starts_with('.') && name != "__MACOSX"
+        })
+        .collect::<Vec<_>>()
+    })
+    .unwrap_or_default();
+  entries.sort_by_key(|path| file_name(path).to_lowercase());
+  entries
+}
+
+fn file_name(path: &Path) -> String {
+  path
+    .file_name()
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_default()
+}
+
+fn entry_views(uid: i64, parent_id: &str, entries: &[VaultEntry]) -> Vec<ParentChildViews> {
+  entries
+    .iter()
+    .map(|entry| {
+      let mut view = NestedChildViewBuilder::new(uid, parent_id.to_string())
+        .with_view_id(entry.view_id.clone())
+        .with_name(&entry.name)
+        .with_layout(ViewLayout::Document)
+        .build();
+      view.children = entry_views(uid, &entry.view_id, &entry.children);
+      view
+    })
+    .collect()
+}
+
+fn flatten_entries(entries: &[VaultEntry], pages: &mut Vec<(String, Option<PathBuf>)>) {
+  for entry in entries {
+    pages.push((entry.view_id.clone(), entry.note.clone()));
+    flatten_entries(&entry.children, pages);
+  }
+}
+
+fn empty_document(view_id: &str) -> Option<EncodedCollab> {
+  default_document_collab_data(view_id)
+    .map_err(|err| warn!("[Import]: failed to create document {}: {:?}", view_id, err))
+    .ok()
+}
+
+async fn note_document(
+  view_id: &str,
+  path: &PathBuf,
+  links: &VaultLinks,
+) -> Result<(Option<EncodedCollab>, Vec<ImportedAttachment>, Vec<String>), ImportError> {
+  let markdown = tokio::fs::read_to_string(path)
+    .await
+    .map_err(|err| ImportError::Internal(err.into()))?;
+
+  let mut embed_urls = HashMap::new();
+  let mut attachments = vec![];
+  for link in wikilinks(&markdown) {
+    if !link.embed || embed_urls.contains_key(link.target) {
+      continue;
+    }
+    if let Some(file_path) = links.file(link.target) {
+      let file_id = FileId::from_path(file_path).await?;
+      embed_urls.insert(
+        link.target.to_string(),
+        format!(
+          "{}/api/file_storage/{}/v1/blob/{}/{}",
+          links.host, links.workspace_id, view_id, file_id
+        ),
+      );
+      attachments.push(ImportedAttachment {
+        object_id: view_id.to_string(),
+        file_path: file_path.to_string_lossy().to_string(),
+      });
+    }
+  }
+
+  let (markdown, mentions, unresolved_links) = rewrite_wikilinks(
+    &markdown,
+    |target| links.page(target),
+    |target| embed_urls.get(target).cloned(),
+  );
+  let view_id = view_id.to_string();
+  let encoded_collab =
+    tokio::task::spawn_blocking(move || markdown_document(&view_id, markdown, &mentions))
+      .await
+      .map_err(|err| ImportError::Internal(err.into()))??;
+  Ok((Some(encoded_collab), attachments, unresolved_links))
+}
+
+struct WikiLink<'a> {
+  range: Range<usize>,
+  /// `![[...]]`, showing the linked file in the note.
+  embed: bool,
+  target: &'a str,
+  label: Option<&'a str>,
+}
+
+fn wikilinks(markdown: &str) -> Vec<WikiLink<'_>> {
+  let mut links = vec![];
+  let mut from = 0;
+  while let Some(open) = markdown[from..].find("[[").map(|open| from + open) {
+    let Some(close) = markdown[open + 2..]
+      .find("]]")
+      .map(|close| open + 2 + close)
+    else {
+      break;
+    };
+    let inner = &markdown[open + 2..close];
+    if inner.contains('\n') || inner.contains("[[") {
+      from = open + 2;
+      continue;
+    }
+    let embed = markdown[..open].ends_with('!');
+    let (target, label) = match inner.split_once('|') {
+      Some((target, label)) => (target, Some(label.trim())),
+      None => (inner, None),
+    };
+    links.push(WikiLink {
+      range: if embed { open - 1 } else { open }..close + 2,
+      embed,
+      target: target.trim(),
+      label,
+    });
+    from = close + 2;
+  }
+  links
+}
+
+/// Turns the embedded files into markdown images and the links to other notes into placeholders
+/// for the mentions of their pages, returned in the order of their index. The links which don't
+/// resolve keep their text, their targets are returned last.
+fn rewrite_wikilinks(
+  markdown: &str,
+  page: impl Fn(&str) -> Option<String>,
+  embed_url: impl Fn(&str) -> Option<String>,
+) -> (String, Vec<String>, Vec<String>) {
+  let mut rewritten = String::with_capacity(markdown.len());
+  let mut mentions = vec![];
+  let mut unresolved = vec![];
+  let mut last = 0;
+  for link in wikilinks(markdown) {
+    rewritten.push_str(&markdown[last..link.range.start]);
+    if let Some(url) = link.embed.then(|| embed_url(link.target)).flatten() {
+      rewritten.push_str(&format!("![]({})", url));
+    } else if let Some(page_id) = page(link.target) {
+      rewritten.push(MENTION_START);
+      rewritten.push_str(&mentions.len().to_string());
+      rewritten.push(MENTION_END);
+      mentions.push(page_id);
+    } else {
+      rewritten.push_str(link.label.unwrap_or(link.target));
+      unresolved.push(link.target.to_string());
+    }
+    last = link.range.end;
+  }
+  rewritten.push_str(&markdown[last..]);
+  (rewritten, mentions, unresolved)
+}
+
+fn markdown_document(
+  view_id: &str,
+  markdown: String,
+  mentions: &[String],
+) -> Result<EncodedCollab, ImportError> {
+  let mut data = MDImporter::new(None)
+    .import(view_id, markdown)
+    .map_err(|err| ImportError::Internal(anyhow!("Failed to import markdown: {:?}", err)))?;
+  if !mentions.is_empty() {
+    if let Some(text_map) = data.meta.text_map.as_mut() {
+      for delta in text_map.values_mut() {
+        if delta.contains(MENTION_START) {
+          *delta = insert_mentions(delta, mentions);
+        }
+      }
+    }
+  }
+  let document = Document::create(view_id, data)
+    .map_err(|err| ImportError::Internal(anyhow!("Failed to create document: {:?}", err)))?;
+  document
+    .encode_collab()
+    .map_err(|err| ImportError::Internal(anyhow!("Failed to encode document: {:?}", err)))
+}
+
+/// Splits the inserts of the delta around the placeholders, each placeholder becoming a mention
+/// of its page.
+fn insert_mentions(delta: &str, mentions: &[String]) -> String {
+  let Ok(Value::Array(ops)) = serde_json::from_str::<Value>(delta) else {
+    return delta.to_string();
+  };
+  let mut new_ops = vec![];
+  for op in ops {
+    let Some(insert) = op.get("insert").and_then(Value::as_str) else {
+      new_ops.push(op);
+      continue;
+    };
+    let attributes = op.get("attributes").cloned();
+    let mut rest = insert;
+    while let Some(start) = rest.find(MENTION_START) {
+      let index_start = start + MENTION_START.len_utf8();
+      let Some(end) = rest[index_start..]
+        .find(MENTION_END)
+        .map(|end| index_start + end)
+      else {
+        break;
+      };
+      let Some(page_id) = rest[index_start..end]
+        .parse::<usize>()
+        .ok()
+        .and_then(|index| mentions.get(index))
+      else {
+        break;
+      };
+      push_insert(&mut new_ops, &rest[..start], &attributes);
+      new_ops.push(json!({
+        "insert": "$",
+        "attributes": { "mention": { "type": "page", "page_id": page_id } },
+      }));
+      rest = &rest[end + MENTION_END.len_utf8()..];
+    }
+    push_insert(&mut new_ops, rest, &attributes);
+  }
+  Value::Array(new_ops).to_string()
+}
+
+fn push_insert(ops: &mut Vec<Value>, text: &str, attributes: &Option<Value>) {
+  if text.is_empty() {
+    return;
+  }
+  let mut op = json!({ "insert": text });
+  if let Some(attributes) = attributes {
+    op["attributes"] = attributes.clone();
+  }
+  ops.push(op);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::import_worker::connector::tests::{import_fixture, outline, unzip_fixture};
+
+  const VAULT: &str = "tests/asset/obsidian_vault.zip";
+
+  #[tokio::test]
+  async fn obsidian_vault_golden_test() {
+    let (source, hierarchy, items) = import_fixture(None, VAULT).await;
+    assert_eq!(source, ImportSource::Obsidian);
+    assert_eq!(
+      outline(&hierarchy),
+      vec![
+        "Imported Space (Document)",
+        "  Projects (Document)",
+        "    Ideas (Document)",
+        "    Roadmap (Document)",
+        "  Welcome (Document)",
+      ]
+    );
+
+    // every view is backed by a document, the embedded image goes along with its note
+    assert_eq!(items.len(), 5);
+    assert!(items.iter().all(|item| item.collabs.len() == 1));
+    let welcome_id = &hierarchy[0].children[1].view.id;
+    let attachments = items
+      .iter()
+      .flat_map(|item| item.attachments.iter())
+      .collect::<Vec<_>>();
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].object_id, *welcome_id);
+    assert!(attachments[0]
+      .file_path
+      .ends_with("attachments/diagram.png"));
+    let welcome = items
+      .iter()
+      .find(|item| item.collabs[0].object_id == *welcome_id)
+      .unwrap();
+    assert_eq!(welcome.unresolved_links, vec!["Missing note"]);
+    assert!(items.iter().all(|item| item.warnings.is_empty()));
+  }
+
+  #[test]
+  fn wikilinks_resolve_to_page_mentions_test() {
+    let root = vault_root(&unzip_fixture(VAULT));
+    let vault = scan_vault(&root);
+    let links = VaultLinks {
+      pages: vault.pages,
+      files: vault.files,
+      workspace_id: "workspace".to_string(),
+      host: "http://localhost".to_string(),
+    };
+    let markdown = std::fs::read_to_string(root.join("Welcome.md")).unwrap();
+    let (rewritten, mentions, unresolved) = rewrite_wikilinks(
+      &markdown,
+      |target| links.page(target),
+      |target| links.file(target).map(|_| format!("blob/{}", target)),
+    );
+    assert_eq!(
+      rewritten,
+      "# Welcome\n\nStart with the \u{E000}0\u{E001} and the \u{E000}1\u{E001}.\n\n![](blob/diagram.png)\n\nSee also Missing note.\n"
+    );
+    assert_eq!(
+      mentions,
+      vec![
+        links.page("Projects/Roadmap").unwrap(),
+        links.page("ideas").unwrap(),
+      ]
+    );
+    assert_eq!(unresolved, vec!["Missing note"]);
+    assert_eq!(links.page("Welcome#Intro"), links.page("welcome.md"));
+  }
+
+  #[test]
+  fn insert_mentions_test() {
+    let delta = json!([
+      { "insert": "Start with the \u{E000}0\u{E001} and the \u{E000}1\u{E001}." },
+      { "insert": "bold", "attributes": { "bold": true } },
+    ])
+    .to_string();
+    let mentions = vec!["roadmap".to_string(), "ideas".to_string()];
+    let delta = serde_json::from_str::<Value>(&insert_mentions(&delta, &mentions)).unwrap();
+    assert_eq!(
+      delta,
+      json!([
+        { "insert": "Start with the " },
+        { "insert": "$", "attributes": { "mention": { "type": "page", "page_id": "roadmap" } } },
+        { "insert": " and the " },
+        { "insert": "$", "attributes": { "mention": { "type": "page", "page_id": "ideas" } } },
+        { "insert": "." },
+        { "insert": "bold", "attributes": { "bold": true } },
+      ])
+    );
+  }
+}
@@ -0,0 +1,418 @@
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::future::Future;
+use tracing::warn;
+
+/// What a node of an import stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportNodeKind {
+  /// A file referenced by a page.
+  Asset,
+  Database,
+  Page,
+  /// The document of a database row.
+  RowDocument,
+}
+
+impl ImportNodeKind {
+  /// Whether the dependents of a failed node of this kind are skipped. A page is still imported
+  /// when one of its assets can't be.
+  fn is_required_by_dependents(&self) -> bool {
+    !matches!(self, ImportNodeKind::Asset)
+  }
+}
+
+/// The nodes of an import along with their dependencies: parents come before their children,
+/// databases before their row documents and assets before the pages referencing them.
+pub struct ImportGraph<T> {
+  nodes: Vec<(String, ImportNodeKind, T)>,
+  index_by_id: HashMap<String, usize>,
+  deps: Vec<Vec<usize>>,
+}
+
+impl<T> Default for ImportGraph<T> {
+  fn default() -> Self {
+    Self {
+      nodes: vec![],
+      index_by_id: HashMap::new(),
+      deps: vec![],
+    }
+  }
+}
+
+impl<T> ImportGraph<T> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Returns false when a node with the same id was already added, in which case `payload` is
+  /// dropped.
+  pub fn add_node(&mut self, id: impl Into<String>, kind: ImportNodeKind, payload: T) -> bool {
+    let id = id.into();
+    if self.index_by_id.contains_key(&id) {
+      return false;
+    }
+    self.index_by_id.insert(id.clone(), self.nodes.len());
+    self.nodes.push((id, kind, payload));
+    self.deps.push(vec![]);
+    true
+  }
+
+  /// Makes `id` wait for `dependency`. Dependencies on unknown nodes are ignored, the imported
+  /// files not always containing the parents they refer to.
+  pub fn add_dependency(&mut self, id: &str, dependency: &str) {
+    if let (Some(&node), Some(&dependency)) =
+      (self.index_by_id.get(id), self.index_by_id.get(dependency))
+    {
+      if node != dependency && !self.deps[node].contains(&dependency) {
+        self.deps[node].push(dependency);
+      }
+    }
+  }
+
+  pub fn node_ids(&self) -> impl Iterator<Item = &str> {
+    self.nodes.iter().map(|(id, _, _)| id.as_str())
+  }
+
+  pub fn contains(&self, id: &str) -> bool {
+    self.index_by_id.contains_key(id)
+  }
+
+  pub fn len(&self) -> usize {
+    self.nodes.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.nodes.is_empty()
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedImportNode {
+  pub id: String,
+  pub kind: ImportNodeKind,
+  pub error: String,
+}
+
+#[derive(Debug)]
+pub struct ImportGraphOutcome<R> {
+  /// The results of the nodes which were processed, in the order they completed, so a node always
+  /// comes after its dependencies.
+  pub completed: Vec<(String, ImportNodeKind, R)>,
+  pub failed: Vec<FailedImportNode>,
+  /// The nodes which weren't processed because a node they depend on failed.
+  pub skipped: Vec<String>,
+}
+
+impl<R> ImportGraphOutcome<R> {
+  pub fn is_complete(&self) -> bool {
+    self.failed.is_empty() && self.skipped.is_empty()
+  }
+}
+
+/// Processes the nodes of the graph, with at most `concurrency` of them at the same time. A node
+/// starts once all of its dependencies are done. A failure doesn't stop the other nodes, only the
+/// dependents of the failed node are skipped.
+pub async fn run_import_graph<T, R, E, F, Fut>(
+  graph: ImportGraph<T>,
+  concurrency: usize,
+  process: F,
+) -> ImportGraphOutcome<R>
+where
+  E: Display,
+  F: Fn(String, ImportNodeKind, T) -> Fut,
+  Fut: Future<Output = Result<R, E>>,
+{
+  let concurrency = concurrency.max(1);
+  let node_count = graph.nodes.len();
+  let mut pending_deps = graph.deps.iter().map(Vec::len).collect::<Vec<_>>();
+  let mut dependents = vec![vec![]; node_count];
+  for (node, deps) in graph.deps.iter().enumerate() {
+    for &dependency in deps {
+      dependents[dependency].push(node);
+    }
+  }
+  let mut blocked = vec![false; node_count];
+  let mut done = vec![false; node_count];
+  let kinds = graph
+    .nodes
+    .iter()
+    .map(|(_, kind, _)| *kind)
+    .collect::<Vec<_>>();
+  let mut nodes = graph.nodes.into_iter().map(Some).collect::<Vec<_>>();
+
+  let mut outcome = ImportGraphOutcome {
+    completed: Vec::with_capacity(node_count),
+    failed: vec![],
+    skipped: vec![],
+  };
+  let mut ready = (0..node_count)
+    .filter(|&node| pending_deps[node] == 0)
+    .collect::<VecDeque<_>>();
+  let mut running = FuturesUnordered::new();
+  let mut finished_count = 0;
+
+  while finished_count < node_count {
+    while running.len() < concurrency {
+      let Some(node) = ready.pop_front() else {
+        break;
+      };
+      let (id, kind, payload) = nodes[node].take().expect("a node is only started once");
+      if blocked[node] {
+        outcome.skipped.push(id);
+        finish(
+          node,
+          false,
+          &kinds,
+          &dependents,
+          &mut pending_deps,
+          &mut blocked,
+          &mut done,
+          &mut ready,
+        );
+        finished_count += 1;
+        continue;
+      }
+      let fut = process(id.clone(), kind, payload);
+      running.push(async move { (node, id, kind, fut.await) });
+    }
+
+    match running.next().await {
+      Some((node, id, kind, result)) => {
+        let succeeded = match result {
+          Ok(value) => {
+            outcome.completed.push((id, kind, value));
+            true
+          },
+          Err(err) => {
+            outcome.failed.push(FailedImportNode {
+              id,
+              kind,
+              error: err.to_string(),
+            });
+            false
+          },
+        };
+        finish(
+          node,
+          succeeded,
+          &kinds,
+          &dependents,
+          &mut pending_deps,
+          &mut blocked,
+          &mut done,
+          &mut ready,
+        );
+        finished_count += 1;
+      },
+      None if ready.is_empty() => {
+        // The remaining nodes depend on each other. Start the first one regardless of its
+        // dependencies to break the cycle.
+        if let Some(node) = (0..node_count).find(|&node| !done[node] && nodes[node].is_some()) {
+          warn!(
+            "[Import] dependency cycle detected, importing {} before its dependencies",
+            nodes[node]
+              .as_ref()
+              .map(|(id, _, _)| id.as_str())
+              .unwrap_or("")
+          );
+          pending_deps[node] = 0;
+          ready.push_back(node);
+        }
+      },
+      None => {},
+    }
+  }
+  outcome
+}
+
+#[allow(clippy::too_many_arguments)]
+fn finish(
+  node: usize,
+  succeeded: bool,
+  kinds: &[ImportNodeKind],
+  dependents: &[Vec<usize>],
+  pending_deps: &mut [usize],
+  blocked: &mut [bool],
+  done: &mut [bool],
+  ready: &mut VecDeque<usize>,
+) {
+  done[node] = true;
+  let blocks_dependents = blocked[node] || (!succeeded && kinds[node].is_required_by_dependents());
+  for &dependent in &dependents[node] {
+    if done[dependent] || pending_deps[dependent] == 0 {
+      continue;
+    }
+    if blocks_dependents {
+      blocked[dependent] = true;
+    }
+    pending_deps[dependent] -= 1;
+    if pending_deps[dependent] == 0 {
+      ready.push_back(dependent);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::time::{Duration, Instant};
+
+  /// An export shaped like a Notion workspace: top level pages with nested pages, each of them
+  /// referencing a few assets, and databases with their row documents.
+  fn synthetic_export(
+    pages: usize,
+    children_per_page: usize,
+    assets_per_page: usize,
+  ) -> ImportGraph<()> {
+    let mut graph = ImportGraph::new();
+    for page in 0..pages {
+      let parent = format!("page-{}", page);
+      graph.add_node(parent.clone(), ImportNodeKind::Page, ());
+      for child in 0..children_per_page {
+        let id = format!("page-{}-{}", page, child);
+        graph.add_node(id.clone(), ImportNodeKind::Page, ());
+        graph.add_dependency(&id, &parent);
+      }
+      for asset in 0..assets_per_page {
+        let id = format!("asset-{}-{}", page, asset);
+        graph.add_node(id.clone(), ImportNodeKind::Asset, ());
+        graph.add_dependency(&parent, &id);
+      }
+      let database = format!("database-{}", page);
+      graph.add_node(database.clone(), ImportNodeKind::Database, ());
+      graph.add_dependency(&database, &parent);
+      for row in 0..children_per_page {
+        let id = format!("row-{}-{}", page, row);
+        graph.add_node(id.clone(), ImportNodeKind::RowDocument, ());
+        graph.add_dependency(&id, &database);
+      }
+    }
+    graph
+  }
+
+  #[tokio::test]
+  async fn nodes_start_after_their_dependencies_test() {
+    let graph = synthetic_export(10, 3, 2);
+    let deps = graph
+      .deps
+      .iter()
+      .enumerate()
+      .map(|(node, deps)| {
+        let ids = deps
+          .iter()
+          .map(|dep| graph.nodes[*dep].0.clone())
+          .collect::<Vec<_>>();
+        (graph.nodes[node].0.clone(), ids)
+      })
+      .collect::<HashMap<_, _>>();
+    let node_count = graph.len();
+    let outcome = run_import_graph(graph, 4, |_, _, _| async {
+      tokio::time::sleep(Duration::from_millis(1)).await;
+      Ok::<_, String>(())
+    })
+    .await;
+
+    assert!(outcome.is_complete());
+    assert_eq!(outcome.completed.len(), node_count);
+    let position = outcome
+      .completed
+      .iter()
+      .enumerate()
+      .map(|(position, (id, _, _))| (id.clone(), position))
+      .collect::<HashMap<_, _>>();
+    for (id, deps) in deps {
+      for dep in deps {
+        assert!(
+          position[&dep] < position[&id],
+          "{} completed before {}",
+          id,
+          dep
+        );
+      }
+    }
+  }
+
+  #[tokio::test]
+  async fn failed_node_skips_its_dependents_only_test() {
+    let graph = synthetic_export(3, 2, 1);
+    let outcome = run_import_graph(graph, 4, |id, _, _| async move {
+      match id.as_str() {
+        "database-1" | "asset-2-0" => Err(format!("cannot import {}", id)),
+        _ => Ok(()),
+      }
+    })
+    .await;
+
+    let mut failed = outcome
+      .failed
+      .iter()
+      .map(|node| node.id.as_str())
+      .collect::<Vec<_>>();
+    failed.sort();
+    assert_eq!(failed, vec!["asset-2-0", "database-1"]);
+    let mut skipped = outcome.skipped.clone();
+    skipped.sort();
+    assert_eq!(skipped, vec!["row-1-0", "row-1-1"]);
+    // the page of the failed asset and the siblings of the failed database are still imported
+    assert!(outcome.completed.iter().any(|(id, _, _)| id == "page-2"));
+    assert!(outcome.completed.iter().any(|(id, _, _)| id == "row-0-1"));
+  }
+
+  #[tokio::test]
+  async fn dependency_cycle_does_not_stall_test() {
+    let mut graph = ImportGraph::new();
+    graph.add_node("a", ImportNodeKind::Page, ());
+    graph.add_node("b", ImportNodeKind::Page, ());
+    graph.add_node("c", ImportNodeKind::Page, ());
+    graph.add_dependency("a", "b");
+    graph.add_dependency("b", "a");
+    graph.add_dependency("c", "b");
+    let outcome = run_import_graph(graph, 2, |_, _, _| async { Ok::<_, String>(()) }).await;
+    assert!(outcome.is_complete());
+    assert_eq!(outcome.completed.len(), 3);
+  }
+
+  /// Benchmark fixture: a 1,000 node export whose nodes take 2ms each finishes several times
+  /// faster with 16 workers than sequentially.
+  #[tokio::test]
+  async fn concurrent_import_is_faster_than_sequential_test() {
+    async fn run(concurrency: usize) -> (Duration, usize) {
+      let graph = synthetic_export(100, 3, 2);
+      let max_running = AtomicUsize::new(0);
+      let running = AtomicUsize::new(0);
+      let start = Instant::now();
+      let outcome = run_import_graph(graph, concurrency, |_, _, _| {
+        let running = &running;
+        let max_running = &max_running;
+        async move {
+          let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+          max_running.fetch_max(now, Ordering::SeqCst);
+          tokio::time::sleep(Duration::from_millis(2)).await;
+          running.fetch_sub(1, Ordering::SeqCst);
+          Ok::<_, String>(())
+        }
+      })
+      .await;
+      assert!(outcome.is_complete());
+      assert_eq!(outcome.completed.len(), 1_000);
+      (start.elapsed(), max_running.load(Ordering::SeqCst))
+    }
+
+    let (sequential, sequential_max) = run(1).await;
+    let (concurrent, concurrent_max) = run(16).await;
+    assert_eq!(sequential_max, 1);
+    assert!(concurrent_max <= 16);
+    assert!(
+      concurrent * 4 < sequential,
+      "sequential: {:?}, concurrent: {:?}",
+      sequential,
+      concurrent
+    );
+  }
+}
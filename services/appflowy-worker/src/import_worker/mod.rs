@@ -1,3 +1,5 @@
+pub mod connector;
 pub mod email_notifier;
+pub mod graph;
 pub mod report;
 pub mod worker;
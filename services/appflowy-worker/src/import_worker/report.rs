@@ -1,4 +1,20 @@
+use crate::error::ImportError;
+use crate::import_worker::graph::{FailedImportNode, ImportNodeKind};
+use anyhow::anyhow;
 use axum::async_trait;
+use collab::entity::EncodedCollab;
+use collab_document::blocks::Block;
+use collab_document::document::Document;
+use collab_document::document_data::default_document_data;
+use database_entity::dto::ImportSource;
+use serde_json::{json, Value};
+use shared_entity::dto::import_dto::{
+  FailedImportAttachment, ImportAttachmentStats, ImportItemOutcome, ImportReport, ImportReportItem,
+  ImportReportItemKind, UnresolvedImportLink, IMPORT_REPORT_SCHEMA_VERSION,
+};
+use sqlx::types::chrono::Utc;
+use std::collections::HashMap;
+use uuid::Uuid;
 
 #[async_trait]
 pub trait ImportNotifier: Send + Sync + 'static {
@@ -18,3 +34,417 @@ pub struct ImportResult {
   pub is_success: bool,
   pub value: serde_json::Value,
 }
+
+/// Paragraphs listed per section of the report page, the JSON report lists them all.
+const MAX_REPORT_PAGE_ENTRIES: usize = 200;
+pub const IMPORT_REPORT_PAGE_NAME: &str = "Import report";
+
+/// Collects what happened to the items of an import while they go through the import graph.
+#[derive(Default)]
+pub struct ImportReportBuilder {
+  items: Vec<ImportReportItem>,
+  index_by_id: HashMap<String, usize>,
+  /// The item and the path in the export of every attachment, by asset id
+  attachments_by_id: HashMap<String, (String, String)>,
+  warnings: HashMap<String, Vec<String>>,
+  attachments: ImportAttachmentStats,
+  unresolved_links: Vec<UnresolvedImportLink>,
+}
+
+impl ImportReportBuilder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Record a node added to the import graph, `is_new` being false for the duplicates which were
+  /// dropped.
+  pub fn add_item(&mut self, id: &str, kind: ImportNodeKind, is_new: bool) {
+    let kind = match kind {
+      ImportNodeKind::Asset => return,
+      ImportNodeKind::Database => ImportReportItemKind::Database,
+      ImportNodeKind::Page => ImportReportItemKind::Page,
+      ImportNodeKind::RowDocument => ImportReportItemKind::RowDocument,
+    };
+    let outcome = if is_new {
+      self.index_by_id.insert(id.to_string(), self.items.len());
+      ImportItemOutcome::Imported
+    } else {
+      ImportItemOutcome::SkippedDuplicate
+    };
+    self.items.push(ImportReportItem {
+      id: id.to_string(),
+      name: None,
+      kind,
+      outcome,
+    });
+  }
+
+  /// Record an attachment of an item, `file_path` being its path relative to the export.
+  pub fn add_attachment(&mut self, item_id: &str, asset_id: &str, file_path: &str, is_new: bool) {
+    self.attachments.total += 1;
+    if is_new {
+      self.attachments_by_id.insert(
+        asset_id.to_string(),
+        (item_id.to_string(), file_path.to_string()),
+      );
+    } else {
+      self.attachments.duplicates += 1;
+    }
+  }
+
+  pub fn add_warnings(&mut self, item_id: &str, warnings: Vec<String>) {
+    if !warnings.is_empty() {
+      self
+        .warnings
+        .entry(item_id.to_string())
+        .or_default()
+        .extend(warnings);
+    }
+  }
+
+  pub fn add_unresolved_links(&mut self, item_id: &str, targets: Vec<String>) {
+    self
+      .unresolved_links
+      .extend(targets.into_iter().map(|target| UnresolvedImportLink {
+        item_id: item_id.to_string(),
+        target,
+      }));
+  }
+
+  pub fn add_uploaded_attachment(&mut self, file_size: u64) {
+    self.attachments.uploaded += 1;
+    self.attachments.uploaded_bytes += file_size;
+  }
+
+  /// Record the nodes of the import graph which failed, and the ones skipped because a node they
+  /// depend on failed.
+  pub fn add_failures(&mut self, failed: &[FailedImportNode], skipped: &[String]) {
+    for node in failed {
+      if node.kind == ImportNodeKind::Asset {
+        let Some((item_id, file_path)) = self.attachments_by_id.get(&node.id).cloned() else {
+          continue;
+        };
+        self.add_warnings(
+          &item_id,
+          vec![format!(
+            "The attachment {} couldn't be imported: {}",
+            file_name(&file_path),
+            node.error
+          )],
+        );
+        self.attachments.failed.push(FailedImportAttachment {
+          item_id,
+          file_path,
+          reason: node.error.clone(),
+        });
+      } else {
+        self.set_outcome(
+          &node.id,
+          ImportItemOutcome::Failed {
+            reason: node.error.clone(),
+          },
+        );
+      }
+    }
+    for id in skipped {
+      self.set_outcome(
+        id,
+        ImportItemOutcome::Failed {
+          reason: "An item it belongs to couldn't be imported".to_string(),
+        },
+      );
+    }
+  }
+
+  fn set_outcome(&mut self, id: &str, outcome: ImportItemOutcome) {
+    if let Some(&index) = self.index_by_id.get(id) {
+      self.items[index].outcome = outcome;
+    }
+  }
+
+  /// The report of the import, naming the items after their view.
+  pub fn build(
+    mut self,
+    task_id: &Uuid,
+    workspace_id: &str,
+    source: ImportSource,
+    view_name: impl Fn(&str) -> Option<String>,
+  ) -> ImportReport {
+    for item in &mut self.items {
+      item.name = view_name(&item.id);
+      if item.outcome == ImportItemOutcome::Imported {
+        if let Some(warnings) = self.warnings.remove(&item.id) {
+          item.outcome = ImportItemOutcome::Partial { warnings };
+        }
+      }
+    }
+    ImportReport {
+      schema_version: IMPORT_REPORT_SCHEMA_VERSION,
+      task_id: task_id.to_string(),
+      workspace_id: workspace_id.to_string(),
+      source,
+      created_at: Utc::now().timestamp(),
+      items: self.items,
+      attachments: self.attachments,
+      unresolved_links: self.unresolved_links,
+      report_view_id: None,
+    }
+  }
+}
+
+fn file_name(file_path: &str) -> &str {
+  file_path.rsplit('/').next().unwrap_or(file_path)
+}
+
+enum ReportBlock {
+  Heading(String),
+  Paragraph(String),
+  ListItem(String),
+}
+
+/// A human-readable version of the report, written as a document in the imported workspace.
+pub fn report_document(view_id: &str, report: &ImportReport) -> Result<EncodedCollab, ImportError> {
+  let mut document = Document::create(view_id, default_document_data(view_id))
+    .map_err(|err| ImportError::Internal(anyhow!("Failed to create document: {}", err)))?;
+  let page_id = document
+    .get_page_id()
+    .ok_or_else(|| ImportError::Internal(anyhow!("The report document has no page block")))?;
+  let mut prev_id = document
+    .get_block_children_ids(&page_id)
+    .last()
+    .cloned()
+    .unwrap_or_default();
+  for block in report_blocks(report) {
+    let (ty, data, text) = match block {
+      ReportBlock::Heading(text) => ("heading", json!({ "level": 2 }), text),
+      ReportBlock::Paragraph(text) => ("paragraph", json!({}), text),
+      ReportBlock::ListItem(text) => ("bulleted_list", json!({}), text),
+    };
+    let data: HashMap<String, Value> =
+      serde_json::from_value(data).map_err(|err| ImportError::Internal(err.into()))?;
+    let block_id = Uuid::new_v4().to_string();
+    let text_id = Uuid::new_v4().to_string();
+    let block = Block {
+      id: block_id.clone(),
+      ty: ty.to_string(),
+      parent: page_id.clone(),
+      children: "".to_string(),
+      external_id: Some(text_id.clone()),
+      external_type: Some("text".to_string()),
+      data,
+    };
+    document
+      .insert_block(block, Some(prev_id.clone()))
+      .map_err(|err| ImportError::Internal(anyhow!("Failed to insert block: {}", err)))?;
+    document.apply_text_delta(&text_id, json!([{ "insert": text }]).to_string());
+    prev_id = block_id;
+  }
+  document
+    .encode_collab()
+    .map_err(|err| ImportError::Internal(anyhow!("Failed to encode document: {}", err)))
+}
+
+fn report_blocks(report: &ImportReport) -> Vec<ReportBlock> {
+  let name = |item_id: &str| {
+    report
+      .items
+      .iter()
+      .find(|item| item.id == item_id)
+      .and_then(|item| item.name.clone())
+      .unwrap_or_else(|| item_id.to_string())
+  };
+  let imported = report
+    .items
+    .iter()
+    .filter(|item| {
+      matches!(
+        item.outcome,
+        ImportItemOutcome::Imported | ImportItemOutcome::Partial { .. }
+      )
+    })
+    .count();
+  let attachments = &report.attachments;
+  let mut blocks = vec![
+    ReportBlock::Paragraph(format!(
+      "{} of the {} items of the export were imported.",
+      imported,
+      report.items.len()
+    )),
+    ReportBlock::Paragraph(format!(
+      "{} attachments were uploaded ({} bytes), {} were duplicates and {} couldn't be imported.",
+      attachments.uploaded,
+      attachments.uploaded_bytes,
+      attachments.duplicates,
+      attachments.failed.len()
+    )),
+  ];
+
+  let mut section = |title: &str, entries: Vec<String>| {
+    if entries.is_empty() {
+      return;
+    }
+    blocks.push(ReportBlock::Heading(title.to_string()));
+    let more = entries.len().saturating_sub(MAX_REPORT_PAGE_ENTRIES);
+    blocks.extend(
+      entries
+        .into_iter()
+        .take(MAX_REPORT_PAGE_ENTRIES)
+        .map(ReportBlock::ListItem),
+    );
+    if more > 0 {
+      blocks.push(ReportBlock::Paragraph(format!(
+        "And {} more, see the downloadable report.",
+        more
+      )));
+    }
+  };
+  section(
+    "Not imported",
+    report
+      .items
+      .iter()
+      .filter_map(|item| match &item.outcome {
+        ImportItemOutcome::Failed { reason } => Some(format!("{}: {}", name(&item.id), reason)),
+        _ => None,
+      })
+      .collect(),
+  );
+  section(
+    "Imported with warnings",
+    report
+      .items
+      .iter()
+      .filter_map(|item| match &item.outcome {
+        ImportItemOutcome::Partial { warnings } => {
+          Some(format!("{}: {}", name(&item.id), warnings.join(" ")))
+        },
+        _ => None,
+      })
+      .collect(),
+  );
+  section(
+    "Skipped duplicates",
+    report
+      .items
+      .iter()
+      .filter(|item| item.outcome == ImportItemOutcome::SkippedDuplicate)
+      .map(|item| name(&item.id))
+      .collect(),
+  );
+  section(
+    "Unresolved links",
+    report
+      .unresolved_links
+      .iter()
+      .map(|link| format!("{}: {}", name(&link.item_id), link.target))
+      .collect(),
+  );
+  blocks
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn import_report_outcomes_test() {
+    let mut builder = ImportReportBuilder::new();
+    builder.add_item("page", ImportNodeKind::Page, true);
+    builder.add_item("page", ImportNodeKind::Page, false);
+    builder.add_item("database", ImportNodeKind::Database, true);
+    builder.add_item("row", ImportNodeKind::RowDocument, true);
+    builder.add_item("other", ImportNodeKind::Page, true);
+    builder.add_item("page/a.png", ImportNodeKind::Asset, true);
+    builder.add_attachment("page", "page/a.png", "assets/a.png", true);
+    builder.add_attachment("page", "page/a.png", "assets/a.png", false);
+    builder.add_attachment("other", "other/b.png", "b.png", true);
+    builder.add_uploaded_attachment(1024);
+    builder.add_unresolved_links("other", vec!["Missing".to_string()]);
+    builder.add_failures(
+      &[
+        FailedImportNode {
+          id: "page/a.png".to_string(),
+          kind: ImportNodeKind::Asset,
+          error: "not found".to_string(),
+        },
+        FailedImportNode {
+          id: "database".to_string(),
+          kind: ImportNodeKind::Database,
+          error: "invalid csv".to_string(),
+        },
+      ],
+      &["row".to_string()],
+    );
+    let report = builder.build(&Uuid::nil(), "workspace", ImportSource::Notion, |id| {
+      (id == "page").then(|| "Page".to_string())
+    });
+
+    assert_eq!(report.schema_version, IMPORT_REPORT_SCHEMA_VERSION);
+    let outcomes = report
+      .items
+      .iter()
+      .map(|item| (item.id.as_str(), &item.outcome))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      outcomes,
+      vec![
+        (
+          "page",
+          &ImportItemOutcome::Partial {
+            warnings: vec!["The attachment a.png couldn't be imported: not found".to_string()]
+          }
+        ),
+        ("page", &ImportItemOutcome::SkippedDuplicate),
+        (
+          "database",
+          &ImportItemOutcome::Failed {
+            reason: "invalid csv".to_string()
+          }
+        ),
+        (
+          "row",
+          &ImportItemOutcome::Failed {
+            reason: "An item it belongs to couldn't be imported".to_string()
+          }
+        ),
+        ("other", &ImportItemOutcome::Imported),
+      ]
+    );
+    assert_eq!(report.items[0].name.as_deref(), Some("Page"));
+    assert_eq!(report.attachments.total, 3);
+    assert_eq!(report.attachments.duplicates, 1);
+    assert_eq!(report.attachments.uploaded, 1);
+    assert_eq!(report.attachments.uploaded_bytes, 1024);
+    assert_eq!(report.attachments.failed[0].item_id, "page");
+    assert_eq!(report.attachments.failed[0].file_path, "assets/a.png");
+    assert_eq!(report.unresolved_links[0].target, "Missing");
+
+    // the schema is flat for the tools reading it
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["items"][2]["outcome"], "failed");
+    assert_eq!(json["items"][2]["reason"], "invalid csv");
+    assert_eq!(
+      serde_json::from_value::<ImportReport>(json).unwrap(),
+      report
+    );
+
+    let blocks = report_blocks(&report);
+    let headings = blocks
+      .iter()
+      .filter_map(|block| match block {
+        ReportBlock::Heading(title) => Some(title.as_str()),
+        _ => None,
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(
+      headings,
+      vec![
+        "Not imported",
+        "Imported with warnings",
+        "Skipped duplicates",
+        "Unresolved links"
+      ]
+    );
+    assert!(report_document("report", &report).is_ok());
+  }
+}
@@ -1,4 +1,9 @@
-use crate::import_worker::report::{ImportNotifier, ImportProgress, ImportResult};
+use crate::import_worker::connector::{open_connector, ImportedItemKind};
+use crate::import_worker::graph::{run_import_graph, ImportGraph, ImportNodeKind};
+use crate::import_worker::report::{
+  report_document, ImportNotifier, ImportProgress, ImportReportBuilder, ImportResult,
+  IMPORT_REPORT_PAGE_NAME,
+};
 use crate::s3_client::{download_file, AutoRemoveDownloadedFile, S3StreamResponse};
 use anyhow::anyhow;
 use aws_sdk_s3::primitives::ByteStream;
@@ -12,19 +17,20 @@ use collab::core::origin::CollabOrigin;
 use collab::entity::{EncodedCollab, EncoderVersion};
 use collab_database::workspace_database::WorkspaceDatabase;
 use collab_entity::CollabType;
+use collab_folder::hierarchy_builder::NestedChildViewBuilder;
 use collab_folder::{Folder, View, ViewLayout};
-use collab_importer::imported_collab::ImportType;
-use collab_importer::notion::page::CollabResource;
-use collab_importer::notion::NotionImporter;
 use collab_importer::util::FileId;
-use database::collab::{insert_into_af_collab_bulk_for_user, select_blob_from_af_collab};
-use database::resource_usage::{insert_blob_metadata_bulk, BulkInsertMeta};
+use database::collab::{insert_new_collabs_bulk_for_user, select_blob_from_af_collab};
+use database::resource_usage::{
+  insert_blob_metadata_bulk, max_blob_size_for_workspace, update_workspace_usage_size_cache,
+  BulkInsertMeta,
+};
 use database::workspace::{
   delete_from_workspace, select_import_task, select_workspace_database_storage_id,
-  update_import_task_status, update_updated_at_of_workspace_with_uid, update_workspace_status,
-  ImportTaskState,
+  update_import_task_metadata, update_import_task_report, update_import_task_status,
+  update_updated_at_of_workspace_with_uid, update_workspace_status, ImportTaskState,
 };
-use database_entity::dto::CollabParams;
+use database_entity::dto::{CollabParams, ImportSource};
 
 use crate::metric::ImportMetrics;
 use async_zip::base::read::stream::{Ready, ZipFileReader};
@@ -43,7 +49,8 @@ use redis::{AsyncCommands, RedisResult, Value};
 
 use database::pg_row::AFImportTask;
 use serde::{Deserialize, Serialize};
-use serde_json::from_str;
+use serde_json::{from_str, json};
+use sha2::{Digest, Sha256};
 use sqlx::types::chrono::{DateTime, TimeZone, Utc};
 use sqlx::PgPool;
 use std::collections::{HashMap, HashSet};
@@ -874,33 +881,37 @@ async fn process_unzip_file(
 ) -> Result<(), ImportError> {
   let workspace_id =
     Uuid::parse_str(&import_task.workspace_id).map_err(|err| ImportError::Internal(err.into()))?;
-  let notion_importer = NotionImporter::new(
+  let connector = open_connector(
+    import_task.source,
     import_task.uid,
     unzip_dir_path,
     import_task.workspace_id.clone(),
     import_task.host.clone(),
-  )
-  .map_err(ImportError::ImportCollabError)?;
+  )?;
 
+  let source = connector.source();
   trace!(
-    "[Import]: {} start import notion data",
-    import_task.workspace_id
+    "[Import]: {} start import {:?} data",
+    import_task.workspace_id,
+    source
   );
-  let imported = notion_importer
-    .import()
-    .await
-    .map_err(ImportError::ImportCollabError)?;
-  let nested_views = imported.build_nested_views().await;
+  let imported = connector.import().await?;
+  let nested_views = imported.hierarchy;
+  // the report page goes along with the imported views, in the space they're imported into
+  let report_parent_id = nested_views
+    .first()
+    .map(|views| views.view.id.clone())
+    .unwrap_or_else(|| import_task.workspace_id.clone());
   trace!(
-    "[Import]: {} imported nested views:{}",
+    "[Import]: {} imported {} nested views",
     import_task.workspace_id,
-    nested_views
+    nested_views.len()
   );
 
   // 1. Open the workspace folder
   let folder_collab = get_encode_collab_from_bytes(
-    &imported.workspace_id,
-    &imported.workspace_id,
+    &import_task.workspace_id,
+    &import_task.workspace_id,
     &CollabType::Folder,
     pg_pool,
     s3_client,
@@ -910,7 +921,7 @@ async fn process_unzip_file(
     import_task.uid,
     CollabOrigin::Server,
     folder_collab.into(),
-    &imported.workspace_id,
+    &import_task.workspace_id,
     vec![],
   )
   .map_err(|err| ImportError::CannotOpenWorkspace(err.to_string()))?;
@@ -921,48 +932,144 @@ async fn process_unzip_file(
     import_task.workspace_id,
     nested_views.len()
   );
-  folder.insert_nested_views(nested_views.into_inner());
+  folder.insert_nested_views(nested_views);
 
-  let mut resources = vec![];
-  let mut collab_params_list = vec![];
+  // 3. Collect all collabs and resources into the import graph
+  let mut graph = ImportGraph::new();
   let mut database_view_ids_by_database_id: HashMap<String, Vec<String>> = HashMap::new();
   let mut orphan_view_ids = HashSet::new();
-
-  // 3. Collect all collabs and resources
-  let mut stream = imported.into_collab_stream().await;
-  while let Some(imported_collab_info) = stream.next().await {
+  let mut page_asset_ids = vec![];
+  let mut database_member_ids = vec![];
+  let mut report = ImportReportBuilder::new();
+  let mut items = imported.items;
+  while let Some(item) = items.next().await {
     trace!(
-      "[Import]: {} imported collab: {}",
+      "[Import]: {} imported item: {}",
       import_task.workspace_id,
-      imported_collab_info
-    );
-    resources.extend(imported_collab_info.resources);
-    collab_params_list.extend(
-      imported_collab_info
-        .imported_collabs
-        .into_iter()
-        .map(|imported_collab| CollabParams {
-          object_id: imported_collab.object_id,
-          collab_type: imported_collab.collab_type,
-          encoded_collab_v1: Bytes::from(imported_collab.encoded_collab.encode_to_bytes().unwrap()),
-        })
-        .collect::<Vec<_>>(),
+      item
     );
+    if let Some(item_id) = item.collabs.first().map(|collab| collab.object_id.clone()) {
+      report.add_warnings(&item_id, item.warnings);
+      report.add_unresolved_links(&item_id, item.unresolved_links);
+    }
+    for attachment in item.attachments {
+      let asset_id = format!("{}/{}", attachment.object_id, attachment.file_path);
+      let relative_path = Path::new(&attachment.file_path)
+        .strip_prefix(unzip_dir_path)
+        .unwrap_or(Path::new(&attachment.file_path))
+        .to_string_lossy()
+        .to_string();
+      let payload = ImportNodePayload::Asset {
+        object_id: attachment.object_id.clone(),
+        file_path: attachment.file_path,
+      };
+      let is_new = graph.add_node(asset_id.clone(), ImportNodeKind::Asset, payload);
+      report.add_attachment(&attachment.object_id, &asset_id, &relative_path, is_new);
+      if is_new {
+        page_asset_ids.push((attachment.object_id, asset_id));
+      }
+    }
 
-    match imported_collab_info.import_type {
-      ImportType::Database {
+    let (database_id, row_document_ids) = match item.kind {
+      ImportedItemKind::Database {
         database_id,
         view_ids,
         row_document_ids,
       } => {
-        database_view_ids_by_database_id.insert(database_id, view_ids);
-        orphan_view_ids.extend(row_document_ids);
+        database_view_ids_by_database_id.insert(database_id.clone(), view_ids);
+        orphan_view_ids.extend(row_document_ids.iter().cloned());
+        (Some(database_id), row_document_ids)
       },
-      ImportType::Document => {
-        // do nothing
+      ImportedItemKind::Document => (None, vec![]),
+    };
+    for imported_collab in item.collabs {
+      let kind = if row_document_ids.contains(&imported_collab.object_id) {
+        ImportNodeKind::RowDocument
+      } else if database_id.is_some() {
+        ImportNodeKind::Database
+      } else {
+        ImportNodeKind::Page
+      };
+      if let Some(database_id) = &database_id {
+        database_member_ids.push((imported_collab.object_id.clone(), database_id.clone()));
+      }
+      let is_new = graph.add_node(
+        imported_collab.object_id.clone(),
+        kind,
+        ImportNodePayload::Collab {
+          collab_type: imported_collab.collab_type,
+          encoded_collab: imported_collab.encoded_collab,
+        },
+      );
+      report.add_item(&imported_collab.object_id, kind, is_new);
+    }
+  }
+
+  // Parents before their children, databases before their rows and row documents, assets before
+  // the pages referencing them.
+  for (object_id, parent_view_id) in
+    import_node_parents(&folder, &graph, &database_view_ids_by_database_id)
+  {
+    graph.add_dependency(&object_id, &parent_view_id);
+  }
+  for (member_id, database_id) in &database_member_ids {
+    graph.add_dependency(member_id, database_id);
+  }
+  for (object_id, asset_id) in &page_asset_ids {
+    graph.add_dependency(object_id, asset_id);
+  }
+
+  // 4. Encode the collabs and gather the metadata of the assets concurrently
+  let concurrency = get_env_var("APPFLOWY_WORKER_IMPORT_CONCURRENCY", "8")
+    .parse::<usize>()
+    .unwrap_or(8);
+  trace!(
+    "[Import]: {} process {} import nodes with concurrency {}",
+    import_task.workspace_id,
+    graph.len(),
+    concurrency
+  );
+  let outcome = run_import_graph(graph, concurrency, process_import_node).await;
+  let mut collab_params_list = vec![];
+  let mut upload_resources = vec![];
+  for (_, _, output) in outcome.completed {
+    match output {
+      ImportNodeOutput::Collab(params) => collab_params_list.push(params),
+      ImportNodeOutput::Asset(resource) => {
+        report.add_uploaded_attachment(resource.meta.file_size as u64);
+        upload_resources.push(resource)
       },
     }
   }
+  report.add_failures(&outcome.failed, &outcome.skipped);
+  let mut not_imported_ids = outcome.skipped.iter().cloned().collect::<HashSet<_>>();
+  not_imported_ids.extend(outcome.failed.iter().map(|node| node.id.clone()));
+  if !not_imported_ids.is_empty() {
+    warn!(
+      "[Import]: {} failed to import {} nodes, skipped {} of their dependents",
+      import_task.workspace_id,
+      outcome.failed.len(),
+      outcome.skipped.len()
+    );
+    let metadata = json!({
+      "failed_nodes": outcome.failed,
+      "skipped_nodes": outcome.skipped,
+    });
+    if let Err(err) = update_import_task_metadata(import_task.task_id, metadata, pg_pool).await {
+      error!(
+        "[Import]: {} failed to record the nodes which weren't imported: {:?}",
+        import_task.workspace_id, err
+      );
+    }
+  }
+  // the views of a database which wasn't imported go along with it
+  for (database_id, view_ids) in &database_view_ids_by_database_id {
+    if not_imported_ids.contains(database_id) {
+      not_imported_ids.extend(view_ids.iter().cloned());
+    }
+  }
+  database_view_ids_by_database_id.retain(|database_id, _| !not_imported_ids.contains(database_id));
+  orphan_view_ids.retain(|view_id| !not_imported_ids.contains(view_id));
 
   let w_database_id = select_workspace_database_storage_id(pg_pool, &import_task.workspace_id)
     .await
@@ -974,7 +1081,7 @@ async fn process_unzip_file(
     })
     .map(|id| id.to_string())?;
 
-  // 4. Edit workspace database collab and then encode workspace database collab
+  // 5. Edit workspace database collab and then encode workspace database collab
   if !database_view_ids_by_database_id.is_empty() {
     let w_db_collab = get_encode_collab_from_bytes(
       &import_task.workspace_id,
@@ -1033,7 +1140,8 @@ async fn process_unzip_file(
     collab_params_list.push(w_database_collab_params);
   }
 
-  // 5. Insert orphan view to folder
+  // 6. Assemble the folder once every node is done: insert the orphan views and remove the views
+  // which weren't imported
   let orphan_views = orphan_view_ids
     .into_iter()
     .map(|orphan_view_id| {
@@ -1043,8 +1151,62 @@ async fn process_unzip_file(
   if !orphan_views.is_empty() {
     folder.insert_views(orphan_views);
   }
+  let missing_view_ids = not_imported_ids
+    .iter()
+    .filter(|id| folder.get_view(id).is_some())
+    .cloned()
+    .collect::<Vec<_>>();
+  if !missing_view_ids.is_empty() {
+    let mut txn = folder.collab.transact_mut();
+    folder.body.views.delete_views(&mut txn, missing_view_ids);
+  }
+
+  // 7. Write the report, with a page summing it up in the workspace. The import goes on without
+  // the page when it can't be created.
+  let mut report = report.build(
+    &import_task.task_id,
+    &import_task.workspace_id,
+    source,
+    |id| folder.get_view(id).map(|view| view.name.clone()),
+  );
+  let report_view_id = Uuid::new_v4().to_string();
+  match report_document(&report_view_id, &report).and_then(|collab| {
+    collab
+      .encode_to_bytes()
+      .map_err(|err| ImportError::Internal(err.into()))
+  }) {
+    Ok(encoded_collab_v1) => {
+      let report_view = NestedChildViewBuilder::new(import_task.uid, report_parent_id)
+        .with_view_id(report_view_id.clone())
+        .with_name(IMPORT_REPORT_PAGE_NAME)
+        .with_layout(ViewLayout::Document)
+        .build()
+        .view;
+      {
+        // first in the space, ahead of the imported pages
+        let mut txn = folder.collab.transact_mut();
+        folder.body.views.insert(&mut txn, report_view, Some(0));
+      }
+      collab_params_list.push(CollabParams {
+        object_id: report_view_id.clone(),
+        collab_type: CollabType::Document,
+        encoded_collab_v1: Bytes::from(encoded_collab_v1),
+      });
+      report.report_view_id = Some(report_view_id);
+    },
+    Err(err) => warn!(
+      "[Import]: {} failed to create the import report page: {:?}",
+      import_task.workspace_id, err
+    ),
+  }
+  if let Err(err) = update_import_task_report(pg_pool, &import_task.task_id, &report).await {
+    error!(
+      "[Import]: {} failed to store the import report: {:?}",
+      import_task.workspace_id, err
+    );
+  }
 
-  // 6. Encode Folder
+  // 8. Encode Folder
   let folder_collab = folder
     .encode_collab_v1(|collab| CollabType::Folder.validate_require_data(collab))
     .map_err(|err| ImportError::Internal(err.into()))?;
@@ -1076,9 +1238,7 @@ async fn process_unzip_file(
   );
   collab_params_list.push(folder_collab_params);
 
-  let upload_resources = process_resources(resources).await;
-
-  // 7. Start a transaction to insert all collabs
+  // 9. Start a transaction to insert all collabs
   let mut transaction = pg_pool.begin().await.map_err(|err| {
     ImportError::Internal(anyhow!(
       "Failed to start transaction when importing data: {:?}",
@@ -1091,8 +1251,8 @@ async fn process_unzip_file(
     import_task.workspace_id
   );
 
-  // 8. write all collab to disk
-  insert_into_af_collab_bulk_for_user(
+  // 10. write all collab to disk. The whole import is rolled back when a collab can't be inserted
+  insert_new_collabs_bulk_for_user(
     &mut transaction,
     &import_task.uid,
     &import_task.workspace_id,
@@ -1155,25 +1315,53 @@ async fn process_unzip_file(
     ))
   })?;
 
-  // insert metadata into database
-  let metas = upload_resources
-    .iter()
-    .map(|res| res.meta.clone())
-    .collect::<Vec<_>>();
-  let affected_rows = insert_blob_metadata_bulk(transaction.deref_mut(), &workspace_id, metas)
+  // the files larger than the plan of the workspace allows are neither recorded nor uploaded
+  let max_blob_size = max_blob_size_for_workspace(transaction.deref_mut(), &workspace_id)
     .await
     .map_err(|err| {
       ImportError::Internal(anyhow!(
-        "Failed to insert blob metadata into database when importing data: {:?}",
+        "Failed to get the maximum file size when importing data: {:?}",
         err
       ))
     })?;
+  if let Some(max_blob_size) = max_blob_size {
+    let count = upload_resources.len();
+    upload_resources.retain(|res| res.meta.file_size <= max_blob_size);
+    if upload_resources.len() != count {
+      warn!(
+        "[Import]: {}, skipped {} files larger than {} bytes",
+        import_task.workspace_id,
+        count - upload_resources.len(),
+        max_blob_size
+      );
+    }
+  }
 
-  if affected_rows != upload_resources.len() as u64 {
+  // insert metadata into database
+  let metas = upload_resources
+    .iter()
+    .map(|res| res.meta.clone())
+    .collect::<Vec<_>>();
+  // the imported files are attributed to the user who imported them
+  let inserted = insert_blob_metadata_bulk(
+    transaction.deref_mut(),
+    &workspace_id,
+    metas,
+    Some(import_task.uid),
+  )
+  .await
+  .map_err(|err| {
+    ImportError::Internal(anyhow!(
+      "Failed to insert blob metadata into database when importing data: {:?}",
+      err
+    ))
+  })?;
+
+  if inserted.rows_affected != upload_resources.len() as u64 {
     warn!(
       "[Import]: {}, Affected rows: {}, upload resources: {}",
       import_task.workspace_id,
-      affected_rows,
+      inserted.rows_affected,
       upload_resources.len()
     );
   }
@@ -1193,8 +1381,9 @@ async fn process_unzip_file(
 
     return result;
   }
+  update_workspace_usage_size_cache(redis_client, &workspace_id, inserted.inserted_bytes).await;
 
-  // 9. after inserting all collabs, upload all files to S3
+  // 11. after inserting all collabs, upload all files to S3
   trace!("[Import]: {} upload files to s3", import_task.workspace_id,);
   batch_upload_files_to_s3(&import_task.workspace_id, s3_client, upload_resources)
     .await
@@ -1478,6 +1667,9 @@ pub struct NotionImportTask {
   pub last_process_at: Option<i64>,
   #[serde(default)]
   pub file_size: Option<i64>,
+  /// The app the file was exported from, detected from the unzipped files when not set.
+  #[serde(default)]
+  pub source: Option<ImportSource>,
 }
 
 impl Display for NotionImportTask {
@@ -1539,32 +1731,83 @@ impl TryFrom<&StreamId> for ImportTask {
   }
 }
 
-async fn process_resources(resources: Vec<CollabResource>) -> Vec<UploadCollabResource> {
-  let upload_resources_stream = stream::iter(resources)
-    .flat_map(|resource| {
-      let object_id = resource.object_id.clone();
-      stream::iter(resource.files.into_iter().map(move |file_path| {
-        let object_id = object_id.clone();
-        let path = PathBuf::from(file_path.clone());
-        async move {
-          match insert_meta_from_path(&object_id, &path).await {
-            Ok(meta) => Some(UploadCollabResource {
-              object_id,
-              file_path,
-              meta,
-            }),
-            Err(_) => None,
-          }
-        }
+enum ImportNodePayload {
+  Collab {
+    collab_type: CollabType,
+    encoded_collab: EncodedCollab,
+  },
+  Asset {
+    object_id: String,
+    file_path: String,
+  },
+}
+
+enum ImportNodeOutput {
+  Collab(CollabParams),
+  Asset(UploadCollabResource),
+}
+
+async fn process_import_node(
+  id: String,
+  _kind: ImportNodeKind,
+  payload: ImportNodePayload,
+) -> Result<ImportNodeOutput, ImportError> {
+  match payload {
+    ImportNodePayload::Collab {
+      collab_type,
+      encoded_collab,
+    } => {
+      let encoded_collab_v1 = tokio::task::spawn_blocking(move || encoded_collab.encode_to_bytes())
+        .await
+        .map_err(|err| ImportError::Internal(err.into()))?
+        .map_err(|err| ImportError::Internal(anyhow!("Failed to encode collab {}: {}", id, err)))?;
+      Ok(ImportNodeOutput::Collab(CollabParams {
+        object_id: id,
+        collab_type,
+        encoded_collab_v1: Bytes::from(encoded_collab_v1),
       }))
-    })
-    // buffer_unordered method limits how many futures (tasks) are run concurrently.
-    .buffer_unordered(20);
+    },
+    ImportNodePayload::Asset {
+      object_id,
+      file_path,
+    } => {
+      let meta = insert_meta_from_path(&object_id, &PathBuf::from(&file_path)).await?;
+      Ok(ImportNodeOutput::Asset(UploadCollabResource {
+        object_id,
+        file_path,
+        meta,
+      }))
+    },
+  }
+}
 
-  upload_resources_stream
-    .filter_map(|result| async { result })
-    .collect::<Vec<UploadCollabResource>>()
-    .await
+/// The parent of every imported view in the folder, when the parent is imported too. A database
+/// comes after the parent of its views.
+fn import_node_parents(
+  folder: &Folder,
+  graph: &ImportGraph<ImportNodePayload>,
+  database_view_ids_by_database_id: &HashMap<String, Vec<String>>,
+) -> Vec<(String, String)> {
+  let parent_of = |view_id: &str| {
+    folder
+      .get_view(view_id)
+      .map(|view| view.parent_view_id.clone())
+      .filter(|parent_view_id| graph.contains(parent_view_id))
+  };
+  let mut parents = vec![];
+  for (database_id, view_ids) in database_view_ids_by_database_id {
+    for view_id in view_ids {
+      if let Some(parent_view_id) = parent_of(view_id) {
+        parents.push((database_id.clone(), parent_view_id));
+      }
+    }
+  }
+  for id in graph.node_ids() {
+    if let Some(parent_view_id) = parent_of(id) {
+      parents.push((id.to_string(), parent_view_id));
+    }
+  }
+  parents
 }
 
 struct UploadCollabResource {
@@ -1596,15 +1839,36 @@ async fn insert_meta_from_path(
     .await
     .map_err(|err| ImportError::Internal(err.into()))?
     .len() as i64;
+  let content_hash = file_content_hash(path).await?;
 
   Ok(BulkInsertMeta {
     object_id,
     file_id,
     file_type,
     file_size,
+    content_hash: Some(content_hash),
   })
 }
 
+/// The sha256 of the file, hex encoded as in [database::resource_usage::blob_content_hash]
+async fn file_content_hash(path: &Path) -> Result<String, ImportError> {
+  let mut file = fs::File::open(path)
+    .await
+    .map_err(|err| ImportError::Internal(err.into()))?;
+  let mut hasher = Sha256::new();
+  let mut buf = vec![0; 64 * 1024];
+  loop {
+    let n = tokio::io::AsyncReadExt::read(&mut file, &mut buf)
+      .await
+      .map_err(|err| ImportError::Internal(err.into()))?;
+    if n == 0 {
+      break;
+    }
+    hasher.update(&buf[..n]);
+  }
+  Ok(format!("{:x}", hasher.finalize()))
+}
+
 fn collab_key(workspace_id: &str, object_id: &str) -> String {
   format!(
     "collabs/{}/{}/encoded_collab.v1.zstd",
@@ -0,0 +1,55 @@
+use crate::error::WorkerError;
+use database::workspace::delete_expired_workspace_invitations;
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info};
+
+pub struct InvitationCleanupConfig {
+  pub tick_interval_secs: u64,
+  /// The expired invitations are kept this long, so that their invitee is told they expired
+  /// rather than not found.
+  pub grace_period_secs: i64,
+  pub batch_size: i64,
+}
+
+/// Deletes the pending workspace invitations which expired more than the grace period ago.
+/// Several workers can run this loop at the same time.
+pub async fn run_invitation_cleanup_worker(
+  pg_pool: PgPool,
+  config: InvitationCleanupConfig,
+) -> Result<(), WorkerError> {
+  info!(
+    "Starting invitation cleanup worker, grace period: {}s",
+    config.grace_period_secs
+  );
+  let mut tick = interval(Duration::from_secs(config.tick_interval_secs));
+  tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+  loop {
+    tick.tick().await;
+    loop {
+      match delete_expired_workspace_invitations(
+        &pg_pool,
+        config.grace_period_secs,
+        config.batch_size,
+      )
+      .await
+      {
+        Ok(0) => break,
+        Ok(count) => {
+          info!("[Invitation] deleted {} expired invitations", count);
+          if (count as i64) < config.batch_size {
+            break;
+          }
+        },
+        Err(err) => {
+          error!(
+            "[Invitation] failed to delete expired invitations: {:?}",
+            err
+          );
+          break;
+        },
+      }
+    }
+  }
+}
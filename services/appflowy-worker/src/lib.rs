@@ -1,6 +1,15 @@
+pub mod blob_gc_worker;
+pub mod cold_storage_worker;
 pub mod error;
+pub mod export_worker;
 pub mod import_worker;
 pub mod indexer_worker;
+pub mod invitation_worker;
 mod mailer;
+pub mod merge_worker;
 pub mod metric;
+pub mod outbox_worker;
+pub mod publish_worker;
 pub mod s3_client;
+pub mod thumbnail_worker;
+pub mod trash_worker;
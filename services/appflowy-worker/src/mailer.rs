@@ -3,6 +3,8 @@ use std::ops::Deref;
 
 pub const IMPORT_SUCCESS_TEMPLATE: &str = "import_notion_success";
 pub const IMPORT_FAIL_TEMPLATE: &str = "import_notion_fail";
+pub const EXPORT_READY_TEMPLATE: &str = "workspace_export_ready";
+pub const WORKSPACE_INVITE_TEMPLATE: &str = "workspace_invite";
 #[derive(Clone)]
 pub struct AFWorkerMailer(Mailer);
 
@@ -22,9 +24,17 @@ impl AFWorkerMailer {
     let import_data_fail =
       include_str!("../../../assets/mailer_templates/build_production/import_data_fail.html");
 
+    let workspace_export_ready =
+      include_str!("../../../assets/mailer_templates/build_production/workspace_export_ready.html");
+
+    let workspace_invite =
+      include_str!("../../../assets/mailer_templates/build_production/workspace_invitation.html");
+
     for (name, template) in [
       (IMPORT_SUCCESS_TEMPLATE, import_data_success),
       (IMPORT_FAIL_TEMPLATE, import_data_fail),
+      (EXPORT_READY_TEMPLATE, workspace_export_ready),
+      (WORKSPACE_INVITE_TEMPLATE, workspace_invite),
     ] {
       mailer
         .register_template(name, template)
@@ -50,6 +60,15 @@ pub struct ImportNotionMailerParam {
   pub error_detail: Option<String>,
 }
 
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WorkspaceExportMailerParam {
+  pub user_name: String,
+  pub workspace_id: String,
+  pub workspace_name: String,
+  pub download_url: String,
+  pub expires_at: String,
+}
+
 #[cfg(test)]
 mod tests {
   use crate::mailer::{AFWorkerMailer, ImportNotionMailerParam, IMPORT_SUCCESS_TEMPLATE};
@@ -1,8 +1,16 @@
 mod application;
+pub mod blob_gc_worker;
+pub mod cold_storage_worker;
 mod config;
 pub mod error;
+pub mod export_worker;
 pub mod import_worker;
+pub mod invitation_worker;
+pub mod merge_worker;
+pub mod outbox_worker;
+pub mod publish_worker;
 pub(crate) mod s3_client;
+pub mod thumbnail_worker;
 
 mod metric;
 
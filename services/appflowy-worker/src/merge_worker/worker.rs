@@ -0,0 +1,927 @@
+use crate::error::WorkerError;
+use crate::s3_client::{S3Client, S3ClientImpl};
+use anyhow::anyhow;
+use bytes::Bytes;
+use collab::core::origin::CollabOrigin;
+use collab::entity::{EncodedCollab, EncoderVersion};
+use collab_database::workspace_database::WorkspaceDatabase;
+use collab_entity::CollabType;
+use collab_folder::hierarchy_builder::NestedChildViewBuilder;
+use collab_folder::{Folder, RepeatedViewIdentifier, SpaceInfo, View};
+use collab_stream::collab_update_sink::CollabUpdateSink;
+use collab_stream::model::{CollabStreamUpdate, UpdateFlags};
+use database::collab::{insert_into_af_collab_bulk_for_user, select_blob_from_af_collab};
+use database::notification::insert_user_notification;
+use database::resource_usage::{acquire_blob_content, insert_blob_content};
+use database::workspace::{
+  select_workspace_database_storage_id, select_workspace_name_from_workspace_id,
+};
+use database::workspace_merge::{
+  archive_merged_workspace, fail_workspace_merge_task, move_merged_collabs,
+  select_workspace_merge_task_by_id, update_workspace_merge_progress,
+  update_workspace_merge_report, MergeStage, MergeTaskState,
+};
+use database_entity::dto::CollabParams;
+use futures::AsyncReadExt;
+use redis::aio::ConnectionManager;
+use redis::streams::{StreamId, StreamReadOptions, StreamReadReply};
+use redis::{AsyncCommands, RedisResult, Value};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::DerefMut;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info, trace, warn};
+use uuid::Uuid;
+
+const GROUP_NAME: &str = "workspace_merge_task_group";
+const CONSUMER_NAME: &str = "appflowy_worker";
+pub const MERGE_NOTIFICATION_KIND: &str = "workspace_merge_completed";
+const MERGE_COLLAB_PAGE_SIZE: i64 = 200;
+const MERGE_BLOB_PAGE_SIZE: i64 = 100;
+/// The folder, the workspace database and the user awareness collabs belong to the workspace
+/// itself, they're merged into the ones of the target workspace instead of being moved.
+const WORKSPACE_PARTITION_KEYS: [i32; 3] = [2, 3, 5];
+/// Same as the default space of a new workspace.
+const SPACE_ICON: &str = "interface_essential/home-3";
+const SPACE_ICON_COLOR: &str = "0xFFA34AFD";
+
+pub async fn run_merge_worker(
+  pg_pool: PgPool,
+  mut redis_client: ConnectionManager,
+  s3_client: Arc<S3ClientImpl>,
+  stream_name: &str,
+  tick_interval_secs: u64,
+) -> Result<(), WorkerError> {
+  info!("Starting workspace merge worker");
+  if let Err(err) = ensure_consumer_group(stream_name, GROUP_NAME, &mut redis_client).await {
+    error!("Failed to ensure consumer group: {:?}", err);
+  }
+
+  let context = MergeContext {
+    pg_pool,
+    redis_client: redis_client.clone(),
+    s3_client,
+  };
+
+  // When the worker restarts, entries that were delivered to this consumer but never
+  // acknowledged are read again by passing "0" instead of ">". The task then resumes from the
+  // stage it was running.
+  let mut read_pending = true;
+  let mut tick = interval(Duration::from_secs(tick_interval_secs));
+  tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+  loop {
+    tick.tick().await;
+    let id = if read_pending { "0" } else { ">" };
+    let options = StreamReadOptions::default()
+      .group(GROUP_NAME, CONSUMER_NAME)
+      .count(1);
+    let reply: StreamReadReply = match redis_client
+      .xread_options(&[stream_name], &[id], &options)
+      .await
+    {
+      Ok(reply) => reply,
+      Err(err) => {
+        error!("Failed to read merge tasks from Redis stream: {:?}", err);
+        if let Some("NOGROUP") = err.code() {
+          if let Err(err) = ensure_consumer_group(stream_name, GROUP_NAME, &mut redis_client).await
+          {
+            error!("Failed to ensure consumer group: {:?}", err);
+          }
+        }
+        continue;
+      },
+    };
+
+    let entries = reply
+      .keys
+      .into_iter()
+      .flat_map(|key| key.ids)
+      .collect::<Vec<_>>();
+    if read_pending && entries.is_empty() {
+      read_pending = false;
+    }
+
+    for entry in entries {
+      match MergeTask::try_from(&entry) {
+        Ok(task) => {
+          if let Err(err) = process_merge_task(&context, &task).await {
+            error!("[Merge] task {} failed: {:?}", task.task_id, err);
+            if let Err(err) =
+              fail_workspace_merge_task(&context.pg_pool, &task.task_id, &err.to_string()).await
+            {
+              error!(
+                "[Merge] failed to mark task {} as failed: {:?}",
+                task.task_id, err
+              );
+            }
+          }
+        },
+        Err(err) => error!("Failed to deserialize merge task: {:?}", err),
+      }
+
+      let ack: RedisResult<()> = redis_client
+        .xack(stream_name, GROUP_NAME, &[&entry.id])
+        .await;
+      if let Err(err) = ack {
+        error!("Failed to acknowledge merge task {}: {:?}", entry.id, err);
+      }
+    }
+  }
+}
+
+struct MergeContext {
+  pg_pool: PgPool,
+  redis_client: ConnectionManager,
+  s3_client: Arc<S3ClientImpl>,
+}
+
+/// Progress of a merge, persisted after each step so that a task picked up again resumes where
+/// it stopped. Every stage can be run again without moving or copying anything twice.
+struct MergeProgress {
+  stage: MergeStage,
+  moved_collabs: i64,
+  copied_blobs: i64,
+  report: serde_json::Value,
+}
+
+impl MergeProgress {
+  async fn save(&self, context: &MergeContext, task: &MergeTask) -> Result<(), WorkerError> {
+    update_workspace_merge_progress(
+      &context.pg_pool,
+      &task.task_id,
+      MergeTaskState::Running,
+      self.stage,
+      self.moved_collabs,
+      self.copied_blobs,
+    )
+    .await
+    .map_err(|err| anyhow!(err))?;
+    Ok(())
+  }
+}
+
+/// Move the content of the source workspace to the target workspace:
+/// - the documents and databases are moved as they are, an id can only be used by one workspace
+///   so they are kept
+/// - the pages are moved under a new space of the target workspace named after the source
+///   workspace
+/// - the files are copied, the ones of the source workspace are kept so that the links to them
+///   keep working
+///
+/// The source workspace is archived once its content was moved.
+async fn process_merge_task(context: &MergeContext, task: &MergeTask) -> Result<(), WorkerError> {
+  let row = select_workspace_merge_task_by_id(&context.pg_pool, &task.task_id)
+    .await
+    .map_err(|err| anyhow!(err))?;
+  match MergeTaskState::from(row.status) {
+    MergeTaskState::Completed | MergeTaskState::Failed => {
+      trace!("[Merge] task {} already finished, skip it", task.task_id);
+      return Ok(());
+    },
+    MergeTaskState::Pending | MergeTaskState::Running => {},
+  }
+  info!(
+    "[Merge] start merging workspace:{} into workspace:{}, task:{}, stage:{:?}",
+    task.source_workspace_id,
+    task.target_workspace_id,
+    task.task_id,
+    MergeStage::from(row.stage)
+  );
+
+  let mut progress = MergeProgress {
+    stage: MergeStage::from(row.stage).max(MergeStage::Collabs),
+    moved_collabs: row.moved_collabs,
+    copied_blobs: row.copied_blobs,
+    report: row.report,
+  };
+  progress.save(context, task).await?;
+
+  if progress.stage == MergeStage::Collabs {
+    move_collabs(context, task, &mut progress).await?;
+    progress.stage = MergeStage::Folder;
+    progress.save(context, task).await?;
+  }
+  if progress.stage == MergeStage::Folder {
+    merge_workspace_databases(context, task).await?;
+    merge_folders(context, task, &mut progress.report).await?;
+    update_workspace_merge_report(&context.pg_pool, &task.task_id, &progress.report)
+      .await
+      .map_err(|err| anyhow!(err))?;
+    progress.stage = MergeStage::Blobs;
+    progress.save(context, task).await?;
+  }
+  if progress.stage == MergeStage::Blobs {
+    copy_blobs(context, task, &mut progress).await?;
+    progress.stage = MergeStage::Archive;
+    progress.save(context, task).await?;
+  }
+  if progress.stage == MergeStage::Archive {
+    archive_merged_workspace(
+      &context.pg_pool,
+      &task.source_workspace_id,
+      &task.target_workspace_id,
+    )
+    .await
+    .map_err(|err| anyhow!(err))?;
+  }
+
+  update_workspace_merge_progress(
+    &context.pg_pool,
+    &task.task_id,
+    MergeTaskState::Completed,
+    MergeStage::Done,
+    progress.moved_collabs,
+    progress.copied_blobs,
+  )
+  .await
+  .map_err(|err| anyhow!(err))?;
+  info!(
+    "[Merge] workspace:{} merged into workspace:{}, task:{}, collabs:{}, blobs:{}",
+    task.source_workspace_id,
+    task.target_workspace_id,
+    task.task_id,
+    progress.moved_collabs,
+    progress.copied_blobs
+  );
+
+  if let Err(err) = insert_user_notification(
+    &context.pg_pool,
+    task.uid,
+    Some(&task.target_workspace_id),
+    MERGE_NOTIFICATION_KIND,
+    &json!({
+      "task_id": task.task_id,
+      "source_workspace_id": task.source_workspace_id,
+      "report": progress.report,
+    }),
+  )
+  .await
+  {
+    error!("[Merge] failed to insert notification: {:?}", err);
+  }
+  Ok(())
+}
+
+/// Move the documents and databases of the source workspace, page by page. A collab stored in S3
+/// is copied under the target workspace before its row is moved; once moved, it's no longer part
+/// of the next pages. The ids are kept, they can't collide with the ones of the target workspace,
+/// see [move_merged_collabs].
+async fn move_collabs(
+  context: &MergeContext,
+  task: &MergeTask,
+  progress: &mut MergeProgress,
+) -> Result<(), WorkerError> {
+  loop {
+    let rows = sqlx::query_as::<_, (String, bool)>(
+      r#"
+        SELECT oid, length(blob) = 0
+        FROM af_collab
+        WHERE workspace_id = $1 AND partition_key <> ALL($2)
+        ORDER BY oid
+        LIMIT $3
+      "#,
+    )
+    .bind(task.source_workspace_id)
+    .bind(&WORKSPACE_PARTITION_KEYS[..])
+    .bind(MERGE_COLLAB_PAGE_SIZE)
+    .fetch_all(&context.pg_pool)
+    .await
+    .map_err(|err| anyhow!(err))?;
+    if rows.is_empty() {
+      break;
+    }
+
+    let mut stored_in_s3 = vec![];
+    for (object_id, is_in_s3) in rows.iter() {
+      if *is_in_s3 {
+        let from_key = collab_key(&task.source_workspace_id, object_id);
+        let to_key = collab_key(&task.target_workspace_id, object_id);
+        context.s3_client.copy_blob(&from_key, &to_key).await?;
+        stored_in_s3.push(from_key);
+      }
+    }
+
+    let object_ids = rows
+      .into_iter()
+      .map(|(object_id, _)| object_id)
+      .collect::<Vec<_>>();
+    let mut txn = context.pg_pool.begin().await.map_err(|err| anyhow!(err))?;
+    let moved = move_merged_collabs(
+      &mut txn,
+      &task.source_workspace_id,
+      &task.target_workspace_id,
+      &object_ids,
+    )
+    .await
+    .map_err(|err| anyhow!(err))?;
+    txn.commit().await.map_err(|err| anyhow!(err))?;
+
+    // The cached metadata of the collabs still points at the source workspace
+    let meta_keys = object_ids
+      .iter()
+      .map(|object_id| collab_meta_key(object_id))
+      .collect::<Vec<_>>();
+    let mut redis_client = context.redis_client.clone();
+    let result: RedisResult<Value> = redis_client.del(meta_keys).await;
+    if let Err(err) = result {
+      warn!("[Merge] failed to remove cached collab metadata: {}", err);
+    }
+    for key in stored_in_s3 {
+      if let Err(err) = context.s3_client.delete_blob(&key).await {
+        warn!("[Merge] failed to delete moved collab {}: {:?}", key, err);
+      }
+    }
+
+    progress.moved_collabs += moved as i64;
+    progress.save(context, task).await?;
+  }
+  trace!(
+    "[Merge] {} moved {} collabs",
+    task.source_workspace_id,
+    progress.moved_collabs
+  );
+  Ok(())
+}
+
+/// Register the databases of the source workspace in the workspace database of the target
+/// workspace, so that they can be found and linked from it.
+async fn merge_workspace_databases(
+  context: &MergeContext,
+  task: &MergeTask,
+) -> Result<(), WorkerError> {
+  let source_oid =
+    select_workspace_database_storage_id(&context.pg_pool, &task.source_workspace_id.to_string())
+      .await
+      .map_err(|err| anyhow!(err))?
+      .to_string();
+  let target_oid =
+    select_workspace_database_storage_id(&context.pg_pool, &task.target_workspace_id.to_string())
+      .await
+      .map_err(|err| anyhow!(err))?
+      .to_string();
+
+  let source = load_collab(
+    context,
+    &task.source_workspace_id,
+    &source_oid,
+    &CollabType::WorkspaceDatabase,
+  )
+  .await?;
+  let source =
+    WorkspaceDatabase::from_collab_doc_state(&source_oid, CollabOrigin::Server, source.into())
+      .map_err(|err| anyhow!("Failed to open workspace database: {}", err))?;
+  let target = load_collab(
+    context,
+    &task.target_workspace_id,
+    &target_oid,
+    &CollabType::WorkspaceDatabase,
+  )
+  .await?;
+  let mut target =
+    WorkspaceDatabase::from_collab_doc_state(&target_oid, CollabOrigin::Server, target.into())
+      .map_err(|err| anyhow!("Failed to open workspace database: {}", err))?;
+
+  let existing_database_ids = target
+    .get_all_database_meta()
+    .into_iter()
+    .map(|meta| meta.database_id)
+    .collect::<HashSet<_>>();
+  let view_ids_by_database_id = source
+    .get_all_database_meta()
+    .into_iter()
+    .filter(|meta| !existing_database_ids.contains(&meta.database_id))
+    .map(|meta| (meta.database_id, meta.linked_views))
+    .collect::<HashMap<_, _>>();
+  if view_ids_by_database_id.is_empty() {
+    return Ok(());
+  }
+
+  let update = target
+    .batch_add_database(view_ids_by_database_id)
+    .encode_update_v1();
+  let encoded = target
+    .encode_collab_v1()
+    .map_err(|err| anyhow!("Failed to encode workspace database: {}", err))?;
+  save_collab(
+    context,
+    task,
+    &target_oid,
+    CollabType::WorkspaceDatabase,
+    encoded,
+    update,
+  )
+  .await
+}
+
+/// Move the pages of the source workspace under a new space of the target workspace, named after
+/// the source workspace. The space id is derived from both workspaces so that running the stage
+/// again, or merging again after a failed merge, doesn't create a second space. The pages having the same name as a page of the target
+/// workspace are reported.
+async fn merge_folders(
+  context: &MergeContext,
+  task: &MergeTask,
+  report: &mut serde_json::Value,
+) -> Result<(), WorkerError> {
+  let source_workspace_id = task.source_workspace_id.to_string();
+  let target_workspace_id = task.target_workspace_id.to_string();
+  let source = load_collab(
+    context,
+    &task.source_workspace_id,
+    &source_workspace_id,
+    &CollabType::Folder,
+  )
+  .await?;
+  let source = Folder::from_collab_doc_state(
+    task.uid,
+    CollabOrigin::Server,
+    source.into(),
+    &source_workspace_id,
+    vec![],
+  )
+  .map_err(|err| anyhow!("Failed to open folder: {}", err))?;
+  let target = load_collab(
+    context,
+    &task.target_workspace_id,
+    &target_workspace_id,
+    &CollabType::Folder,
+  )
+  .await?;
+  let mut target = Folder::from_collab_doc_state(
+    task.uid,
+    CollabOrigin::Server,
+    target.into(),
+    &target_workspace_id,
+    vec![],
+  )
+  .map_err(|err| anyhow!("Failed to open folder: {}", err))?;
+
+  // Parents come before their children, so that each view is inserted after its parent
+  let mut views = vec![];
+  let mut queue = VecDeque::from([source_workspace_id.clone()]);
+  while let Some(parent_view_id) = queue.pop_front() {
+    for view in source.get_views_belong_to(&parent_view_id) {
+      queue.push_back(view.id.clone());
+      views.push(view);
+    }
+  }
+  // The views which are not part of the hierarchy, like the documents of the database rows
+  views.extend(
+    source
+      .get_all_views()
+      .into_iter()
+      .filter(|view| view.id == view.parent_view_id),
+  );
+
+  let space_view_id = Uuid::new_v5(
+    &task.source_workspace_id,
+    task.target_workspace_id.as_bytes(),
+  )
+  .to_string();
+  let merged_view_ids = views
+    .iter()
+    .map(|view| view.id.clone())
+    .collect::<HashSet<_>>();
+  let mut target_names = HashSet::new();
+  for view in target.get_all_views() {
+    if view.id != space_view_id && !merged_view_ids.contains(&view.id) {
+      target_names.insert(normalize_page_name(&view.name));
+    }
+  }
+  let duplicate_page_names = views
+    .iter()
+    .filter(|view| view.id != view.parent_view_id)
+    .filter(|view| target_names.contains(&normalize_page_name(&view.name)))
+    .map(|view| json!({ "view_id": view.id, "name": view.name }))
+    .collect::<Vec<_>>();
+  report["space_view_id"] = json!(space_view_id);
+  report["duplicate_page_names"] = json!(duplicate_page_names);
+
+  if target.get_view(&space_view_id).is_some() {
+    trace!(
+      "[Merge] {} folder already merged into {}",
+      source_workspace_id,
+      target_workspace_id
+    );
+    return Ok(());
+  }
+
+  let space_name =
+    select_workspace_name_from_workspace_id(&context.pg_pool, &task.source_workspace_id)
+      .await
+      .map_err(|err| anyhow!(err))?
+      .unwrap_or_else(|| "Merged workspace".to_string());
+  let space_view = NestedChildViewBuilder::new(task.uid, target_workspace_id.clone())
+    .with_view_id(space_view_id.clone())
+    .with_name(&space_name)
+    .with_extra(|extra| {
+      extra
+        .with_space_info(SpaceInfo {
+          space_icon: Some(SPACE_ICON.to_string()),
+          space_icon_color: Some(SPACE_ICON_COLOR.to_string()),
+          ..Default::default()
+        })
+        .build()
+    })
+    .build()
+    .view;
+
+  let update = {
+    let mut txn = target.collab.transact_mut();
+    target.body.views.insert(&mut txn, space_view, None);
+    for view in views {
+      if target.body.views.get_view(&txn, &view.id).is_some() {
+        continue;
+      }
+      let mut view = View::clone(&view);
+      view.children = RepeatedViewIdentifier { items: vec![] };
+      if view.parent_view_id == source_workspace_id {
+        // The spaces of the source workspace become sections of the new space
+        if is_space(&view) {
+          view.extra = None;
+        }
+        view.parent_view_id = space_view_id.clone();
+      }
+      target.body.views.insert(&mut txn, view, None);
+    }
+    txn.encode_update_v1()
+  };
+  let encoded = target
+    .encode_collab_v1(|collab| CollabType::Folder.validate_require_data(collab))
+    .map_err(|err| anyhow!("Failed to encode folder: {}", err))?;
+  save_collab(
+    context,
+    task,
+    &target_workspace_id,
+    CollabType::Folder,
+    encoded,
+    update,
+  )
+  .await
+}
+
+/// Persist a collab of the target workspace modified by the merge. The update is also appended to
+/// the update stream of the collab, so that a group editing the collab applies it too instead of
+/// overwriting it.
+async fn save_collab(
+  context: &MergeContext,
+  task: &MergeTask,
+  object_id: &str,
+  collab_type: CollabType,
+  encoded: EncodedCollab,
+  update: Vec<u8>,
+) -> Result<(), WorkerError> {
+  let workspace_id = task.target_workspace_id.to_string();
+  let params = CollabParams {
+    object_id: object_id.to_string(),
+    collab_type,
+    encoded_collab_v1: Bytes::from(
+      encoded
+        .encode_to_bytes()
+        .map_err(|err| anyhow!("Failed to encode collab: {}", err))?,
+    ),
+  };
+  let mut txn = context.pg_pool.begin().await.map_err(|err| anyhow!(err))?;
+  insert_into_af_collab_bulk_for_user(&mut txn, &task.uid, &workspace_id, &[params])
+    .await
+    .map_err(|err| anyhow!(err))?;
+  txn.commit().await.map_err(|err| anyhow!(err))?;
+
+  // The collab is read from S3 first, the outdated copy must not shadow the saved one
+  let key = collab_key(&task.target_workspace_id, object_id);
+  if let Err(err) = context.s3_client.delete_blob(&key).await {
+    warn!(
+      "[Merge] failed to delete outdated collab {}: {:?}",
+      key, err
+    );
+  }
+  let mut redis_client = context.redis_client.clone();
+  let result: RedisResult<Value> = redis_client.del(encode_collab_key(object_id)).await;
+  if let Err(err) = result {
+    warn!(
+      "[Merge] failed to remove cached collab {}: {}",
+      object_id, err
+    );
+  }
+
+  let sink = CollabUpdateSink::new(context.redis_client.clone(), &workspace_id, object_id);
+  sink
+    .send(&CollabStreamUpdate::new(
+      update,
+      CollabOrigin::Server,
+      UpdateFlags::default(),
+    ))
+    .await
+    .map_err(|err| anyhow!("Failed to send update of {}: {}", object_id, err))?;
+  Ok(())
+}
+
+/// Copy the files of the source workspace to the target workspace. The files already copied by a
+/// previous run of the stage are skipped.
+///
+/// A file sharing its content with other files is stored by the object recorded in
+/// af_blob_content. Its content is copied once: the other files sharing it take a reference to the
+/// copy recorded in the target workspace, as does a file whose content was already uploaded to the
+/// target workspace.
+async fn copy_blobs(
+  context: &MergeContext,
+  task: &MergeTask,
+  progress: &mut MergeProgress,
+) -> Result<(), WorkerError> {
+  let source_prefix = format!("{}/", task.source_workspace_id);
+  let target_prefix = format!("{}/", task.target_workspace_id);
+  let mut cursor = String::new();
+  loop {
+    let rows = sqlx::query_as::<_, (String, Option<String>, Option<String>)>(
+      r#"
+        SELECT source.file_id, source.content_hash, source.object_key
+        FROM af_blob_metadata source
+        WHERE source.workspace_id = $1 AND source.file_id > $3
+          AND NOT EXISTS (
+            SELECT 1 FROM af_blob_metadata target
+            WHERE target.workspace_id = $2 AND target.file_id = source.file_id
+          )
+        ORDER BY source.file_id
+        LIMIT $4
+      "#,
+    )
+    .bind(task.source_workspace_id)
+    .bind(task.target_workspace_id)
+    .bind(&cursor)
+    .bind(MERGE_BLOB_PAGE_SIZE)
+    .fetch_all(&context.pg_pool)
+    .await
+    .map_err(|err| anyhow!(err))?;
+    if rows.is_empty() {
+      break;
+    }
+
+    for (file_id, content_hash, object_key) in rows {
+      cursor.clone_from(&file_id);
+      if let Some(content_hash) = &content_hash {
+        if share_copied_blob_content(context, task, &file_id, content_hash).await? {
+          progress.copied_blobs += 1;
+          continue;
+        }
+      }
+
+      // a file overwritten with versioning enabled keeps pointing at the object it was moved to
+      let is_moved = content_hash.is_none() && object_key.is_some();
+      let source_key = match object_key {
+        Some(object_key) => object_key,
+        None => match blob_object_key(context, &task.source_workspace_id, &file_id).await? {
+          Some(key) => key,
+          None => {
+            warn!(
+              "[Merge] blob {} of workspace {} not found, skip it",
+              file_id, task.source_workspace_id
+            );
+            continue;
+          },
+        },
+      };
+      let target_key = source_key.replacen(&source_prefix, &target_prefix, 1);
+      context
+        .s3_client
+        .copy_blob(&source_key, &target_key)
+        .await?;
+
+      let mut txn = context.pg_pool.begin().await.map_err(|err| anyhow!(err))?;
+      // the other files without a content hash are found from their file id, see [blob_object_key]
+      let recorded_key = match &content_hash {
+        Some(content_hash) => Some(
+          insert_blob_content(
+            &mut txn,
+            &task.target_workspace_id,
+            content_hash,
+            &target_key,
+          )
+          .await
+          .map_err(|err| anyhow!(err))?,
+        ),
+        None => is_moved.then(|| target_key.clone()),
+      };
+      let copied =
+        insert_copied_blob_metadata(&mut txn, task, &file_id, recorded_key.as_deref()).await?;
+      if copied {
+        txn.commit().await.map_err(|err| anyhow!(err))?;
+        progress.copied_blobs += 1;
+      }
+      // the target key follows from the source key, a file copied concurrently shares it, unless
+      // its content was recorded first under another object
+      if recorded_key.is_some_and(|recorded_key| recorded_key != target_key) {
+        if let Err(err) = context.s3_client.delete_blob(&target_key).await {
+          warn!(
+            "[Merge] failed to delete duplicated copy {}: {:?}",
+            target_key, err
+          );
+        }
+      }
+    }
+    progress.save(context, task).await?;
+  }
+  Ok(())
+}
+
+/// Records the file as sharing the content already stored in the target workspace, if any.
+/// Returns false when the content has to be copied.
+async fn share_copied_blob_content(
+  context: &MergeContext,
+  task: &MergeTask,
+  file_id: &str,
+  content_hash: &str,
+) -> Result<bool, WorkerError> {
+  let mut txn = context.pg_pool.begin().await.map_err(|err| anyhow!(err))?;
+  let Some(object_key) = acquire_blob_content(&mut txn, &task.target_workspace_id, content_hash)
+    .await
+    .map_err(|err| anyhow!(err))?
+  else {
+    return Ok(false);
+  };
+  if insert_copied_blob_metadata(&mut txn, task, file_id, Some(&object_key)).await? {
+    txn.commit().await.map_err(|err| anyhow!(err))?;
+  }
+  // a file copied concurrently keeps the reference it took, the one taken here is rolled back
+  Ok(true)
+}
+
+/// Inserts the metadata of the file in the target workspace, stored by the object `object_key`
+/// of af_blob_content when its content is shared. Returns false when the file was already copied.
+async fn insert_copied_blob_metadata(
+  txn: &mut Transaction<'_, Postgres>,
+  task: &MergeTask,
+  file_id: &str,
+  object_key: Option<&str>,
+) -> Result<bool, WorkerError> {
+  let inserted = sqlx::query(
+    r#"
+      INSERT INTO af_blob_metadata
+        (workspace_id, file_id, file_type, file_size, modified_at, status, uploaded_by,
+        expires_at, content_hash, object_key)
+      SELECT $2, file_id, file_type, file_size, modified_at, status, uploaded_by, expires_at,
+        content_hash, $4
+      FROM af_blob_metadata
+      WHERE workspace_id = $1 AND file_id = $3
+      ON CONFLICT (workspace_id, file_id) DO NOTHING
+    "#,
+  )
+  .bind(task.source_workspace_id)
+  .bind(task.target_workspace_id)
+  .bind(file_id)
+  .bind(object_key)
+  .execute(txn.deref_mut())
+  .await
+  .map_err(|err| anyhow!(err))?
+  .rows_affected();
+  Ok(inserted > 0)
+}
+
+/// Files uploaded with the v1 api are stored under `{workspace_id}/{parent_dir}/{file_id}` and
+/// recorded as `{parent_dir}_{file_id}`. Both parts may contain `_`, so every split is tried.
+/// Older files are stored as `{workspace_id}/{file_id}`.
+async fn blob_object_key(
+  context: &MergeContext,
+  workspace_id: &Uuid,
+  file_id: &str,
+) -> Result<Option<String>, WorkerError> {
+  for (index, _) in file_id.match_indices('_') {
+    let (parent_dir, id) = (&file_id[..index], &file_id[index + 1..]);
+    let key = format!("{}/{}/{}", workspace_id, parent_dir, id);
+    if context.s3_client.is_blob_exist(&key).await? {
+      return Ok(Some(key));
+    }
+  }
+
+  let key = format!("{}/{}", workspace_id, file_id);
+  if context.s3_client.is_blob_exist(&key).await? {
+    return Ok(Some(key));
+  }
+  Ok(None)
+}
+
+async fn load_collab(
+  context: &MergeContext,
+  workspace_id: &Uuid,
+  object_id: &str,
+  collab_type: &CollabType,
+) -> Result<EncodedCollab, WorkerError> {
+  let key = collab_key(workspace_id, object_id);
+  match context.s3_client.get_blob_stream(&key).await {
+    Ok(mut resp) => {
+      let mut compressed = Vec::new();
+      resp.stream.read_to_end(&mut compressed).await?;
+      Ok(EncodedCollab {
+        state_vector: Default::default(),
+        doc_state: zstd::decode_all(&*compressed)?.into(),
+        version: EncoderVersion::V1,
+      })
+    },
+    Err(WorkerError::RecordNotFound(_)) => {
+      let bytes = select_blob_from_af_collab(&context.pg_pool, collab_type, object_id)
+        .await
+        .map_err(|err| anyhow!(err))?;
+      Ok(
+        EncodedCollab::decode_from_bytes(&bytes)
+          .map_err(|err| anyhow!("Failed to decode collab {}: {}", object_id, err))?,
+      )
+    },
+    Err(err) => Err(err),
+  }
+}
+
+fn is_space(view: &View) -> bool {
+  view
+    .extra
+    .as_deref()
+    .and_then(|extra| serde_json::from_str::<serde_json::Value>(extra).ok())
+    .and_then(|extra| extra.get("is_space").and_then(|value| value.as_bool()))
+    .unwrap_or(false)
+}
+
+fn normalize_page_name(name: &str) -> String {
+  name.trim().to_lowercase()
+}
+
+#[inline]
+fn collab_key(workspace_id: &Uuid, object_id: &str) -> String {
+  format!(
+    "collabs/{}/{}/encoded_collab.v1.zstd",
+    workspace_id, object_id
+  )
+}
+
+#[inline]
+fn encode_collab_key(object_id: &str) -> String {
+  format!("encode_collab_v0:{}", object_id)
+}
+
+#[inline]
+fn collab_meta_key(object_id: &str) -> String {
+  format!("collab_meta_v0:{}", object_id)
+}
+
+async fn ensure_consumer_group(
+  stream_key: &str,
+  group_name: &str,
+  redis_client: &mut ConnectionManager,
+) -> Result<(), WorkerError> {
+  let result: RedisResult<()> = redis_client
+    .xgroup_create_mkstream(stream_key, group_name, "0")
+    .await;
+
+  if let Err(redis_error) = result {
+    if let Some("BUSYGROUP") = redis_error.code() {
+      return Ok(());
+    }
+    return Err(WorkerError::Internal(redis_error.into()));
+  }
+  Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeTask {
+  pub task_id: Uuid,
+  pub target_workspace_id: Uuid,
+  pub source_workspace_id: Uuid,
+  pub uid: i64,
+  #[serde(default)]
+  pub created_at: Option<i64>,
+}
+
+impl TryFrom<&StreamId> for MergeTask {
+  type Error = WorkerError;
+
+  fn try_from(stream_id: &StreamId) -> Result<Self, Self::Error> {
+    let task_str = match stream_id.map.get("task") {
+      Some(Value::Data(data)) => String::from_utf8_lossy(data),
+      _ => {
+        return Err(WorkerError::Internal(anyhow!(
+          "Unexpected value type for merge task field: {:?}",
+          stream_id
+        )))
+      },
+    };
+    serde_json::from_str::<MergeTask>(&task_str).map_err(|err| WorkerError::Internal(err.into()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn space_is_detected_from_extra_test() {
+    let mut view = NestedChildViewBuilder::new(1, "workspace".to_string())
+      .with_name("Page")
+      .build()
+      .view;
+    assert!(!is_space(&view));
+    view.extra = Some(json!({ "is_space": true, "space_icon": SPACE_ICON }).to_string());
+    assert!(is_space(&view));
+    assert_eq!(normalize_page_name("  Meeting Notes "), "meeting notes");
+  }
+}
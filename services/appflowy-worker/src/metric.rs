@@ -51,3 +51,99 @@ impl ImportMetrics {
     self.import_fail_count.inc_by(count);
   }
 }
+
+pub struct OutboxMetrics {
+  pub stuck_entries: Gauge,
+  pub dispatch_success_count: Gauge,
+  pub dispatch_fail_count: Gauge,
+}
+
+impl OutboxMetrics {
+  pub fn init() -> Self {
+    Self {
+      stuck_entries: Default::default(),
+      dispatch_success_count: Default::default(),
+      dispatch_fail_count: Default::default(),
+    }
+  }
+
+  pub fn register(registry: &mut Registry) -> Self {
+    let metrics = Self::init();
+    let outbox_registry = registry.sub_registry_with_prefix("outbox");
+    outbox_registry.register(
+      "stuck_entries",
+      "Number of outbox entries still not dispatched long after they were recorded",
+      metrics.stuck_entries.clone(),
+    );
+    outbox_registry.register(
+      "dispatch_success_count",
+      "outbox dispatch success count",
+      metrics.dispatch_success_count.clone(),
+    );
+    outbox_registry.register(
+      "dispatch_fail_count",
+      "outbox dispatch fail count",
+      metrics.dispatch_fail_count.clone(),
+    );
+    metrics
+  }
+}
+
+pub struct BlobGcMetrics {
+  pub deleted_blob_count: Gauge,
+  pub reclaimed_bytes: Gauge,
+  /// Bytes the dry runs would have reclaimed
+  pub candidate_bytes: Gauge,
+  pub expired_upload_count: Gauge,
+  pub aborted_upload_count: Gauge,
+  pub expired_blob_count: Gauge,
+}
+
+impl BlobGcMetrics {
+  pub fn init() -> Self {
+    Self {
+      deleted_blob_count: Default::default(),
+      reclaimed_bytes: Default::default(),
+      candidate_bytes: Default::default(),
+      expired_upload_count: Default::default(),
+      aborted_upload_count: Default::default(),
+      expired_blob_count: Default::default(),
+    }
+  }
+
+  pub fn register(registry: &mut Registry) -> Self {
+    let metrics = Self::init();
+    let blob_gc_registry = registry.sub_registry_with_prefix("blob_gc");
+    blob_gc_registry.register(
+      "deleted_blob_count",
+      "Number of orphaned blobs deleted",
+      metrics.deleted_blob_count.clone(),
+    );
+    blob_gc_registry.register(
+      "reclaimed_bytes",
+      "Bytes reclaimed by deleting orphaned blobs",
+      metrics.reclaimed_bytes.clone(),
+    );
+    blob_gc_registry.register(
+      "candidate_bytes",
+      "Bytes of the orphaned blobs found by the dry runs",
+      metrics.candidate_bytes.clone(),
+    );
+    blob_gc_registry.register(
+      "expired_upload_count",
+      "Number of presigned uploads deleted because they were never completed",
+      metrics.expired_upload_count.clone(),
+    );
+    blob_gc_registry.register(
+      "aborted_upload_count",
+      "Number of multipart uploads aborted because they were abandoned by their client",
+      metrics.aborted_upload_count.clone(),
+    );
+    blob_gc_registry.register(
+      "expired_blob_count",
+      "Number of blobs deleted because they expired",
+      metrics.expired_blob_count.clone(),
+    );
+    metrics
+  }
+}
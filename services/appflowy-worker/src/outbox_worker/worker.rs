@@ -0,0 +1,496 @@
+use crate::error::WorkerError;
+use crate::mailer::{AFWorkerMailer, WORKSPACE_INVITE_TEMPLATE};
+use crate::metric::OutboxMetrics;
+use anyhow::anyhow;
+use database::notification::insert_user_notification;
+use database::outbox::{
+  claim_outbox_entries, complete_outbox_entry, delete_processed_outbox_entries, fail_outbox_entry,
+  select_stuck_outbox_entry_count, CdnPurgeIntent, InvitationEmailIntent, NotificationIntent,
+  OutboxTopic, PublishCdnEvent, WebhookDeliveryIntent,
+};
+use database::pg_row::AFOutboxEntryRow;
+use database::publish::{
+  delete_expired_published_collab_revisions, select_published_collab_addresses,
+};
+use database::workspace::select_workspace_invitation_email_seq;
+use database::workspace_export::EXPORT_TASK_STREAM;
+use database::workspace_webhook::select_workspace_webhook;
+use indexer::queue::add_background_embed_task;
+use indexer::scheduler::UnindexedCollabTask;
+use infra::net_util::resolve_public_addr;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::redirect::Policy;
+use secrecy::{ExposeSecret, Secret};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::digest::Output;
+use sha2::{Digest, Sha256};
+use shared_entity::dto::webhook_dto::{WebhookDelivery, X_APPFLOWY_SIGNATURE};
+use sqlx::types::chrono::Utc;
+use sqlx::PgPool;
+use std::ops::DerefMut;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info, trace, warn};
+use uuid::Uuid;
+
+/// First delay before an entry which couldn't be dispatched is retried, doubled on every attempt.
+const RETRY_BASE_DELAY_SECS: i64 = 10;
+const RETRY_MAX_DELAY_SECS: i64 = 3600;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(600);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct OutboxRelayConfig {
+  pub tick_interval_secs: u64,
+  pub batch_size: i64,
+  /// How long a claimed entry is reserved for the relay which claimed it.
+  pub lease_secs: i64,
+  /// How long the dispatched entries are kept before being pruned.
+  pub retention_secs: i64,
+  /// Age after which an entry still not dispatched is reported as stuck.
+  pub stuck_after_secs: i64,
+  pub revision_grace_period_secs: i64,
+  /// Webhook called with the paths to purge from the CDN when a view is published again or
+  /// unpublished.
+  pub cdn_purge_url: Option<String>,
+  pub cdn_purge_token: Option<Secret<String>>,
+}
+
+/// Dispatches the side effects recorded in the outbox by the transactions of the server: CDN
+/// purges, indexer tasks, notifications and emails. An entry is dispatched at least once: it's only marked
+/// as done once its side effect succeeded, and retried with an exponential backoff otherwise.
+/// Several workers can run this loop at the same time, each entry being claimed by a single one.
+pub async fn run_outbox_relay(
+  pg_pool: PgPool,
+  redis_client: ConnectionManager,
+  mailer: AFWorkerMailer,
+  metrics: Arc<OutboxMetrics>,
+  config: OutboxRelayConfig,
+) -> Result<(), WorkerError> {
+  info!("Starting outbox relay");
+  let relay = OutboxRelay {
+    pg_pool,
+    redis_client,
+    mailer,
+    client: reqwest::Client::builder()
+      .timeout(Duration::from_secs(10))
+      .build()
+      .unwrap_or_default(),
+    metrics,
+    config,
+  };
+  let mut tick = interval(Duration::from_secs(relay.config.tick_interval_secs));
+  tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+  let mut pruned_at: Option<Instant> = None;
+  loop {
+    tick.tick().await;
+    loop {
+      match relay.dispatch_due_entries().await {
+        Ok(count) if count >= relay.config.batch_size as usize => continue,
+        Ok(_) => break,
+        Err(err) => {
+          error!("[Outbox] failed to dispatch entries: {:?}", err);
+          break;
+        },
+      }
+    }
+
+    if pruned_at.map_or(true, |at| at.elapsed() >= PRUNE_INTERVAL) {
+      match delete_processed_outbox_entries(&relay.pg_pool, relay.config.retention_secs).await {
+        Ok(0) => {},
+        Ok(count) => info!("[Outbox] pruned {} dispatched entries", count),
+        Err(err) => error!("[Outbox] failed to prune dispatched entries: {:?}", err),
+      }
+      pruned_at = Some(Instant::now());
+    }
+    match select_stuck_outbox_entry_count(&relay.pg_pool, relay.config.stuck_after_secs).await {
+      Ok(count) => {
+        relay.metrics.stuck_entries.set(count);
+      },
+      Err(err) => error!("[Outbox] failed to count stuck entries: {:?}", err),
+    }
+  }
+}
+
+struct OutboxRelay {
+  pg_pool: PgPool,
+  redis_client: ConnectionManager,
+  mailer: AFWorkerMailer,
+  client: reqwest::Client,
+  metrics: Arc<OutboxMetrics>,
+  config: OutboxRelayConfig,
+}
+
+impl OutboxRelay {
+  async fn dispatch_due_entries(&self) -> Result<usize, WorkerError> {
+    let entries = claim_outbox_entries(
+      &self.pg_pool,
+      self.config.batch_size,
+      self.config.lease_secs,
+    )
+    .await
+    .map_err(|err| WorkerError::Internal(err.into()))?;
+    let count = entries.len();
+    for entry in entries {
+      match self.dispatch(&entry).await {
+        Ok(()) => {
+          trace!("[Outbox] dispatched {} entry {}", entry.topic, entry.id);
+          self.metrics.dispatch_success_count.inc();
+        },
+        Err(err) => {
+          let retry_in_secs = retry_delay_secs(entry.attempts);
+          warn!(
+            "[Outbox] failed to dispatch {} entry {} (attempt {}), retrying in {}s: {}",
+            entry.topic, entry.id, entry.attempts, retry_in_secs, err
+          );
+          self.metrics.dispatch_fail_count.inc();
+          fail_outbox_entry(&self.pg_pool, entry.id, &err.to_string(), retry_in_secs)
+            .await
+            .map_err(|err| WorkerError::Internal(err.into()))?;
+        },
+      }
+    }
+    Ok(count)
+  }
+
+  /// Runs the side effect of the entry and marks it as done.
+  async fn dispatch(&self, entry: &AFOutboxEntryRow) -> Result<(), WorkerError> {
+    let topic = OutboxTopic::parse(&entry.topic)
+      .ok_or_else(|| anyhow!("unknown outbox topic: {}", entry.topic))?;
+    match topic {
+      OutboxTopic::CdnPurge => {
+        let intent: CdnPurgeIntent = decode_payload(entry)?;
+        self.purge_cdn(intent).await?;
+      },
+      OutboxTopic::IndexCollab => {
+        let task: UnindexedCollabTask = decode_payload(entry)?;
+        add_background_embed_task(self.redis_client.clone(), vec![task])
+          .await
+          .map_err(|err| WorkerError::Internal(err.into()))?;
+      },
+      OutboxTopic::InvitationEmail => {
+        let intent: InvitationEmailIntent = decode_payload(entry)?;
+        self.send_invitation_email(intent).await?;
+      },
+      OutboxTopic::Notification => {
+        // The inbox entries are inserted along with the completion of the outbox entry, so they're
+        // never inserted twice.
+        let intent: NotificationIntent = decode_payload(entry)?;
+        let mut txn = self
+          .pg_pool
+          .begin()
+          .await
+          .map_err(|err| WorkerError::Internal(err.into()))?;
+        for uid in &intent.recipients {
+          insert_user_notification(
+            txn.deref_mut(),
+            *uid,
+            intent.workspace_id.as_ref(),
+            &intent.kind,
+            &intent.payload,
+          )
+          .await
+          .map_err(|err| WorkerError::Internal(err.into()))?;
+        }
+        complete_outbox_entry(txn.deref_mut(), entry.id)
+          .await
+          .map_err(|err| WorkerError::Internal(err.into()))?;
+        txn
+          .commit()
+          .await
+          .map_err(|err| WorkerError::Internal(err.into()))?;
+        return Ok(());
+      },
+      OutboxTopic::WorkspaceExport => {
+        // a task pushed twice is only run once, see the export worker
+        let mut redis_client = self.redis_client.clone();
+        let _: () = redis_client
+          .xadd(
+            EXPORT_TASK_STREAM,
+            "*",
+            &[("task", entry.payload.to_string())],
+          )
+          .await
+          .map_err(|err| WorkerError::Internal(err.into()))?;
+      },
+      OutboxTopic::WorkspaceWebhook => {
+        let intent: WebhookDeliveryIntent = decode_payload(entry)?;
+        self.deliver_webhook(entry.id, intent).await?;
+      },
+    }
+    complete_outbox_entry(&self.pg_pool, entry.id)
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    Ok(())
+  }
+
+  /// Purges the entry points of the views from the CDN. The revisions which are past their grace
+  /// period are deleted along the way.
+  async fn purge_cdn(&self, intent: CdnPurgeIntent) -> Result<(), WorkerError> {
+    match delete_expired_published_collab_revisions(
+      &self.pg_pool,
+      self.config.revision_grace_period_secs,
+    )
+    .await
+    {
+      Ok(0) => {},
+      Ok(count) => info!("[Outbox] deleted {} expired published revisions", count),
+      Err(err) => warn!(
+        "[Outbox] failed to delete expired published revisions: {}",
+        err
+      ),
+    }
+
+    let Some(purge_url) = &self.config.cdn_purge_url else {
+      return Ok(());
+    };
+    let addresses =
+      select_published_collab_addresses(&self.pg_pool, &intent.workspace_id, &intent.view_ids)
+        .await
+        .map_err(|err| WorkerError::Internal(err.into()))?;
+    if addresses.is_empty() {
+      return Ok(());
+    }
+    let request = PurgeRequest {
+      event: intent.event,
+      workspace_id: intent.workspace_id,
+      view_ids: &intent.view_ids,
+      paths: purge_paths(&addresses),
+    };
+    let mut req = self.client.post(purge_url).json(&request);
+    if let Some(token) = &self.config.cdn_purge_token {
+      req = req.bearer_auth(token.expose_secret());
+    }
+    let resp = req
+      .send()
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    let status = resp.status();
+    if !status.is_success() {
+      let body = resp.text().await.unwrap_or_default();
+      return Err(WorkerError::Internal(anyhow!(
+        "purge webhook responded with {}: {}",
+        status,
+        body
+      )));
+    }
+    Ok(())
+  }
+
+  /// Sends the email of an invitation, unless a later email was queued for it or it can no longer
+  /// be accepted. The email is sent again if the entry couldn't be marked as done.
+  async fn send_invitation_email(&self, intent: InvitationEmailIntent) -> Result<(), WorkerError> {
+    let email_seq = select_workspace_invitation_email_seq(&self.pg_pool, &intent.invite_id)
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    if email_seq != Some(intent.email_seq) {
+      trace!(
+        "[Outbox] dropped email {} of invitation {}, superseded or no longer pending",
+        intent.email_seq,
+        intent.invite_id
+      );
+      return Ok(());
+    }
+    self
+      .mailer
+      .send_email_template(
+        intent.sender_name,
+        &intent.recipient,
+        WORKSPACE_INVITE_TEMPLATE,
+        intent.param,
+        &intent.subject,
+      )
+      .await
+      .map_err(WorkerError::Internal)?;
+    Ok(())
+  }
+
+  /// Posts the events to the webhook, signed with its secret. The events of a webhook which was
+  /// deleted since they were recorded are dropped.
+  async fn deliver_webhook(
+    &self,
+    delivery_id: i64,
+    intent: WebhookDeliveryIntent,
+  ) -> Result<(), WorkerError> {
+    let Some(webhook) = select_workspace_webhook(&self.pg_pool, &intent.webhook_id)
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?
+    else {
+      trace!("[Outbox] webhook {} was deleted", intent.webhook_id);
+      return Ok(());
+    };
+    let body = serde_json::to_vec(&WebhookDelivery {
+      delivery_id,
+      webhook_id: webhook.id,
+      workspace_id: webhook.workspace_id,
+      events: intent.events,
+    })
+    .map_err(|err| WorkerError::Internal(err.into()))?;
+    // the url was checked when the webhook was created, its host is resolved and checked again
+    // and the connection pinned to the checked address. Redirects are not followed, they could
+    // point anywhere.
+    let url = reqwest::Url::parse(&webhook.url).map_err(|err| WorkerError::Internal(err.into()))?;
+    let host = url
+      .host_str()
+      .ok_or_else(|| anyhow!("webhook url has no host"))?
+      .trim_start_matches('[')
+      .trim_end_matches(']')
+      .to_string();
+    let port = url
+      .port_or_known_default()
+      .ok_or_else(|| anyhow!("webhook url has no port"))?;
+    let addr = resolve_public_addr(&host, port)
+      .await
+      .map_err(WorkerError::Internal)?;
+    let client = reqwest::Client::builder()
+      .redirect(Policy::none())
+      .timeout(WEBHOOK_TIMEOUT)
+      .no_proxy()
+      .resolve(&host, addr)
+      .build()
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    let timestamp = Utc::now().timestamp();
+    let resp = client
+      .post(url)
+      .header(CONTENT_TYPE, "application/json")
+      .header(
+        X_APPFLOWY_SIGNATURE,
+        webhook_signature(&webhook.secret, timestamp, &body),
+      )
+      .body(body)
+      .send()
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    let status = resp.status();
+    if !status.is_success() {
+      return Err(WorkerError::Internal(anyhow!(
+        "webhook {} responded with {}",
+        webhook.id,
+        status
+      )));
+    }
+    Ok(())
+  }
+}
+
+#[derive(Debug, Serialize)]
+struct PurgeRequest<'a> {
+  event: PublishCdnEvent,
+  workspace_id: Uuid,
+  view_ids: &'a [Uuid],
+  paths: Vec<String>,
+}
+
+fn decode_payload<T: DeserializeOwned>(entry: &AFOutboxEntryRow) -> Result<T, WorkerError> {
+  serde_json::from_value(entry.payload.clone()).map_err(|err| {
+    WorkerError::Internal(anyhow!(
+      "invalid payload of {} entry {}: {}",
+      entry.topic,
+      entry.id,
+      err
+    ))
+  })
+}
+
+/// Delay before the next attempt of an entry which failed its `attempts`th dispatch.
+fn retry_delay_secs(attempts: i32) -> i64 {
+  let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+  RETRY_BASE_DELAY_SECS
+    .saturating_mul(1 << exponent)
+    .min(RETRY_MAX_DELAY_SECS)
+}
+
+/// `t=<timestamp>,v1=<signature>`, the signature being the HMAC-SHA256 of `<timestamp>.<body>`.
+/// The timestamp lets the receivers reject replayed deliveries.
+fn webhook_signature(secret: &str, timestamp: i64, body: &[u8]) -> String {
+  let mut message = format!("{}.", timestamp).into_bytes();
+  message.extend_from_slice(body);
+  format!(
+    "t={},v1={}",
+    timestamp,
+    hex::encode(hmac_sha256(secret.as_bytes(), &message))
+  )
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Output<Sha256> {
+  const BLOCK_SIZE: usize = 64;
+  let mut block = [0u8; BLOCK_SIZE];
+  if key.len() > BLOCK_SIZE {
+    block[..32].copy_from_slice(&Sha256::digest(key));
+  } else {
+    block[..key.len()].copy_from_slice(key);
+  }
+  let mut inner = Sha256::new();
+  inner.update(block.map(|b| b ^ 0x36));
+  inner.update(message);
+  let mut outer = Sha256::new();
+  outer.update(block.map(|b| b ^ 0x5c));
+  outer.update(inner.finalize());
+  outer.finalize()
+}
+
+/// The entry points of the published views: the page, its metadata and its latest blob.
+fn purge_paths(addresses: &[(String, String)]) -> Vec<String> {
+  addresses
+    .iter()
+    .flat_map(|(namespace, publish_name)| {
+      [
+        format!("/{}/{}", namespace, publish_name),
+        format!("/api/workspace/v1/published/{}/{}", namespace, publish_name),
+        format!(
+          "/api/workspace/published/{}/{}/blob",
+          namespace, publish_name
+        ),
+      ]
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn retry_delay_grows_exponentially_up_to_the_cap_test() {
+    assert_eq!(retry_delay_secs(0), 10);
+    assert_eq!(retry_delay_secs(1), 10);
+    assert_eq!(retry_delay_secs(2), 20);
+    assert_eq!(retry_delay_secs(5), 160);
+    assert_eq!(retry_delay_secs(9), RETRY_MAX_DELAY_SECS);
+    assert_eq!(retry_delay_secs(i32::MAX), RETRY_MAX_DELAY_SECS);
+  }
+
+  #[test]
+  fn webhook_signature_is_an_hmac_of_the_timestamp_and_body_test() {
+    // RFC 4231, test case 2
+    assert_eq!(
+      hex::encode(hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+      "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+    let signature = webhook_signature("secret", 1_700_000_000, b"{}");
+    assert_eq!(
+      signature,
+      format!(
+        "t=1700000000,v1={}",
+        hex::encode(hmac_sha256(b"secret", b"1700000000.{}"))
+      )
+    );
+    assert_ne!(signature, webhook_signature("other", 1_700_000_000, b"{}"));
+  }
+
+  #[test]
+  fn purge_paths_cover_entry_points_of_every_namespace_test() {
+    let addresses = vec![
+      ("original".to_string(), "page".to_string()),
+      ("custom".to_string(), "page".to_string()),
+    ];
+    let paths = purge_paths(&addresses);
+    assert_eq!(paths.len(), 6);
+    assert!(paths.contains(&"/custom/page".to_string()));
+    assert!(paths.contains(&"/api/workspace/v1/published/original/page".to_string()));
+    assert!(paths.contains(&"/api/workspace/published/custom/page/blob".to_string()));
+  }
+}
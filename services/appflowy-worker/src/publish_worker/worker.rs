@@ -0,0 +1,77 @@
+use crate::error::WorkerError;
+use crate::s3_client::{S3Client, S3ClientImpl};
+use database::publish::apply_due_scheduled_publishes;
+use sqlx::PgPool;
+use std::ops::DerefMut;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Number of scheduled revisions applied per transaction.
+const SCHEDULED_PUBLISH_BATCH_SIZE: i64 = 50;
+
+/// Swaps in the revisions of published views scheduled with a `publish_at` time once they are due.
+/// The scheduled revisions are kept in Postgres, so they survive restarts and several workers can
+/// run this loop at the same time.
+pub async fn run_publish_worker(
+  pg_pool: PgPool,
+  s3_client: Arc<S3ClientImpl>,
+  tick_interval_secs: u64,
+) -> Result<(), WorkerError> {
+  info!("Starting publish worker");
+  let mut tick = interval(Duration::from_secs(tick_interval_secs));
+  tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+  loop {
+    tick.tick().await;
+    loop {
+      match apply_scheduled_publishes(&pg_pool, s3_client.as_ref()).await {
+        Ok(count) if count >= SCHEDULED_PUBLISH_BATCH_SIZE as usize => continue,
+        Ok(_) => break,
+        Err(err) => {
+          error!("[Publish] failed to apply scheduled publishes: {:?}", err);
+          break;
+        },
+      }
+    }
+  }
+}
+
+async fn apply_scheduled_publishes(
+  pg_pool: &PgPool,
+  s3_client: &S3ClientImpl,
+) -> Result<usize, WorkerError> {
+  let mut txn = pg_pool
+    .begin()
+    .await
+    .map_err(|err| WorkerError::Internal(err.into()))?;
+  let applied = apply_due_scheduled_publishes(txn.deref_mut(), SCHEDULED_PUBLISH_BATCH_SIZE)
+    .await
+    .map_err(|err| WorkerError::Internal(err.into()))?;
+  // When published collabs are stored in S3, the copy of the previous revision must go or it
+  // would keep being served. Reads then fall back to the revision stored in Postgres until the
+  // view is published again. The transaction is only committed once the copies are deleted, so
+  // the revisions are applied again on failure.
+  for key in &applied {
+    s3_client
+      .delete_blob(&published_collab_key(&key.workspace_id, &key.view_id))
+      .await?;
+  }
+  txn
+    .commit()
+    .await
+    .map_err(|err| WorkerError::Internal(err.into()))?;
+  for key in &applied {
+    info!(
+      "[Publish] applied scheduled revision of view {} in workspace {}",
+      key.view_id, key.workspace_id
+    );
+  }
+  Ok(applied.len())
+}
+
+#[inline]
+fn published_collab_key(workspace_id: &Uuid, view_id: &Uuid) -> String {
+  format!("published-collab/{}/{}", workspace_id, view_id)
+}
@@ -4,16 +4,21 @@ use aws_sdk_s3::error::SdkError;
 use std::fs::Permissions;
 
 use anyhow::Result;
+use aws_sdk_s3::operation::abort_multipart_upload::AbortMultipartUploadError;
 use aws_sdk_s3::operation::get_object::GetObjectError;
 use aws_sdk_s3::operation::head_object::{HeadObjectError, HeadObjectOutput};
+use aws_sdk_s3::presigning::PresigningConfig;
 use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{Delete, MetadataDirective, ObjectIdentifier, StorageClass};
 use axum::async_trait;
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
+use database::file::s3_client_impl::copy_source;
 use futures::AsyncReadExt;
 use std::ops::Deref;
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 use tokio::fs;
 use tokio::fs::OpenOptions;
 use tokio::io::AsyncWriteExt;
@@ -45,9 +50,182 @@ pub struct BlobMeta {
 pub struct S3ClientImpl {
   pub inner: aws_sdk_s3::Client,
   pub bucket: String,
+  pub endpoint: String,
+  pub presigned_url_endpoint: Option<String>,
 }
 
 impl S3ClientImpl {
+  /// Generate a presigned url that allows downloading the object without credentials until
+  /// `expires_in` has elapsed.
+  pub async fn presigned_get_url(
+    &self,
+    object_key: &str,
+    expires_in: Duration,
+  ) -> Result<String, WorkerError> {
+    let config = PresigningConfig::builder()
+      .start_time(SystemTime::now())
+      .expires_in(expires_in)
+      .build()
+      .map_err(|err| WorkerError::Internal(anyhow!("Invalid presigning config: {}", err)))?;
+    let request = self
+      .inner
+      .get_object()
+      .bucket(&self.bucket)
+      .key(object_key)
+      .presigned(config)
+      .await
+      .map_err(|err| WorkerError::Internal(anyhow!("Generate presigned url failed: {:?}", err)))?;
+
+    let url = request.uri().to_string();
+    Ok(
+      self
+        .presigned_url_endpoint
+        .as_ref()
+        .map_or(url.clone(), |presigned| {
+          url.replace(&self.endpoint, presigned)
+        }),
+    )
+  }
+
+  /// Copy an object to another key of the same bucket. The source object is kept.
+  pub async fn copy_blob(
+    &self,
+    from_object_key: &str,
+    to_object_key: &str,
+  ) -> Result<(), WorkerError> {
+    self
+      .inner
+      .copy_object()
+      .bucket(&self.bucket)
+      .copy_source(copy_source(&self.bucket, from_object_key))
+      .key(to_object_key)
+      .send()
+      .await
+      .map_err(|err| WorkerError::Internal(anyhow!("Failed to copy object in S3: {:?}", err)))?;
+    trace!(
+      "copied object in S3: {} -> {}",
+      from_object_key,
+      to_object_key
+    );
+    Ok(())
+  }
+
+  /// Delete the objects with one request per thousand keys. Returns the keys which couldn't be
+  /// deleted, the missing objects count as deleted.
+  pub async fn delete_blobs(&self, object_keys: &[String]) -> Result<Vec<String>, WorkerError> {
+    const BATCH_SIZE: usize = 1000;
+    let mut failed = vec![];
+    for chunk in object_keys.chunks(BATCH_SIZE) {
+      let objects = chunk
+        .iter()
+        .map(|key| {
+          ObjectIdentifier::builder().key(key).build().map_err(|err| {
+            WorkerError::Internal(anyhow!("Failed to create object identifier: {}", err))
+          })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+      let delete = Delete::builder()
+        .set_objects(Some(objects))
+        .quiet(true)
+        .build()
+        .map_err(|err| {
+          WorkerError::Internal(anyhow!("Failed to create delete objects request: {}", err))
+        })?;
+      let output = self
+        .inner
+        .delete_objects()
+        .bucket(&self.bucket)
+        .delete(delete)
+        .send()
+        .await
+        .map_err(|err| {
+          WorkerError::Internal(anyhow!("Failed to delete objects from S3: {:?}", err))
+        })?;
+      for error in output.errors.unwrap_or_default() {
+        error!(
+          "failed to delete object {:?} from S3: {:?} {:?}",
+          error.key(),
+          error.code(),
+          error.message()
+        );
+        failed.extend(error.key);
+      }
+    }
+    trace!(
+      "deleted {} objects from S3",
+      object_keys.len() - failed.len()
+    );
+    Ok(failed)
+  }
+
+  /// Move the object to another storage class by copying it onto itself, keeping its metadata.
+  pub async fn set_storage_class(
+    &self,
+    object_key: &str,
+    storage_class: &str,
+  ) -> Result<(), WorkerError> {
+    self
+      .inner
+      .copy_object()
+      .bucket(&self.bucket)
+      .copy_source(copy_source(&self.bucket, object_key))
+      .key(object_key)
+      .storage_class(StorageClass::from(storage_class))
+      .metadata_directive(MetadataDirective::Copy)
+      .send()
+      .await
+      .map_err(|err| {
+        WorkerError::Internal(anyhow!(
+          "Failed to change the storage class of object in S3: {:?}",
+          err
+        ))
+      })?;
+    trace!(
+      "moved object in S3 to storage class {}: {}",
+      storage_class,
+      object_key
+    );
+    Ok(())
+  }
+
+  /// Abort a multipart upload, deleting the parts uploaded so far. The uploads which were already
+  /// completed or aborted are ignored.
+  pub async fn abort_multipart_upload(
+    &self,
+    object_key: &str,
+    upload_id: &str,
+  ) -> Result<(), WorkerError> {
+    match self
+      .inner
+      .abort_multipart_upload()
+      .bucket(&self.bucket)
+      .key(object_key)
+      .upload_id(upload_id)
+      .send()
+      .await
+    {
+      Ok(_) => {
+        trace!(
+          "aborted multipart upload in S3: {} - {}",
+          object_key,
+          upload_id
+        );
+        Ok(())
+      },
+      Err(SdkError::ServiceError(service_err)) => match service_err.err() {
+        AbortMultipartUploadError::NoSuchUpload(_) => Ok(()),
+        _ => Err(WorkerError::from(anyhow!(
+          "Failed to abort multipart upload in S3: {:?}",
+          service_err
+        ))),
+      },
+      Err(err) => Err(WorkerError::from(anyhow!(
+        "Failed to abort multipart upload in S3: {}",
+        err
+      ))),
+    }
+  }
+
   async fn get_head_object(&self, object_key: &str) -> Result<HeadObjectOutput, WorkerError> {
     self
       .inner
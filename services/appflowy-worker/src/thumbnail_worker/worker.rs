@@ -0,0 +1,388 @@
+use crate::blob_gc_worker::worker::{blob_object_key, resolve_blob_object_key};
+use crate::error::WorkerError;
+use crate::s3_client::{S3Client, S3ClientImpl};
+use anyhow::anyhow;
+use aws_sdk_s3::primitives::ByteStream;
+use database::blob_variant::{
+  blob_variant_object_key, claim_blob_variant_tasks, complete_blob_variant_task,
+  delete_blob_variants_of_files, upsert_blob_variant,
+};
+use database::pg_row::{AFBlobMetadataRow, AFBlobVariantTaskRow};
+use database::resource_usage::{get_blob_metadata, select_blob_metadata_for_update};
+use database_entity::file_dto::BlobVariant;
+use futures::AsyncReadExt;
+use image::imageops::FilterType;
+use image::io::Reader as ImageReader;
+use image::{DynamicImage, ImageFormat, ImageOutputFormat};
+use sqlx::PgPool;
+use std::io::Cursor;
+use std::ops::DerefMut;
+use std::slice;
+use std::sync::Arc;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info, trace, warn};
+use uuid::Uuid;
+
+/// The content types of the blobs the variants are generated for, as queued by the
+/// af_blob_variant_enqueue_fn trigger. The GIFs, which can be animated, and the SVGs are served as
+/// they are.
+const THUMBNAIL_SOURCE_TYPES: [&str; 6] = [
+  "image/png",
+  "image/jpeg",
+  "image/jpg",
+  "image/webp",
+  "image/bmp",
+  "image/tiff",
+];
+/// Images with more pixels than this are served as they are, without being decoded.
+const MAX_IMAGE_PIXELS: u64 = 50_000_000;
+
+pub struct ThumbnailConfig {
+  pub enable: bool,
+  pub tick_interval_secs: u64,
+  pub batch_size: i64,
+  /// Time given to a claimed task before it's handed out again.
+  pub lease_secs: i64,
+  /// A task failing this many times is dropped, the blob is then served without variants.
+  pub max_attempts: i32,
+  /// The larger images are served as they are.
+  pub max_source_bytes: u64,
+}
+
+/// Generates the variants of the images uploaded to the workspaces, queued in
+/// af_blob_variant_task when their metadata is written. The images are downscaled to each
+/// [BlobVariant] they're larger than, and the variants of a blob which isn't an image anymore are
+/// deleted. The tasks queued while the worker is disabled are processed once it's enabled.
+pub async fn run_thumbnail_worker(
+  pg_pool: PgPool,
+  s3_client: Arc<S3ClientImpl>,
+  config: ThumbnailConfig,
+) -> Result<(), WorkerError> {
+  if !config.enable {
+    info!("Thumbnail worker is disabled");
+    return Ok(());
+  }
+  info!(
+    "Starting thumbnail worker, batch size: {}, max source size: {} bytes",
+    config.batch_size, config.max_source_bytes
+  );
+  let thumbnails = Thumbnails {
+    pg_pool,
+    s3_client,
+    config,
+  };
+  let mut tick = interval(std::time::Duration::from_secs(
+    thumbnails.config.tick_interval_secs,
+  ));
+  tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+  loop {
+    tick.tick().await;
+    if let Err(err) = thumbnails.process_next_batch().await {
+      error!("[Thumbnail] failed to claim the tasks: {:?}", err);
+    }
+  }
+}
+
+struct Thumbnails {
+  pg_pool: PgPool,
+  s3_client: Arc<S3ClientImpl>,
+  config: ThumbnailConfig,
+}
+
+/// A variant encoded from the image of a blob.
+#[derive(Debug)]
+struct GeneratedVariant {
+  variant: BlobVariant,
+  content: Vec<u8>,
+  content_type: &'static str,
+}
+
+impl Thumbnails {
+  async fn process_next_batch(&self) -> Result<(), WorkerError> {
+    let tasks = claim_blob_variant_tasks(
+      &self.pg_pool,
+      self.config.batch_size,
+      self.config.lease_secs,
+    )
+    .await
+    .map_err(|err| WorkerError::Internal(err.into()))?;
+    for task in tasks {
+      let Err(err) = self.process_task(&task).await else {
+        continue;
+      };
+      if task.attempts < self.config.max_attempts {
+        // the task is handed out again once its lease expires
+        warn!(
+          "[Thumbnail] failed to generate the variants of blob {} of workspace {}, attempt {}: {:?}",
+          task.file_id, task.workspace_id, task.attempts, err
+        );
+        continue;
+      }
+      error!(
+        "[Thumbnail] giving up on the variants of blob {} of workspace {} after {} attempts: {:?}",
+        task.file_id, task.workspace_id, task.attempts, err
+      );
+      if let Err(err) = complete_blob_variant_task(
+        &self.pg_pool,
+        &task.workspace_id,
+        &task.file_id,
+        task.enqueued_at,
+      )
+      .await
+      {
+        error!(
+          "[Thumbnail] failed to drop the task of blob {} of workspace {}: {:?}",
+          task.file_id, task.workspace_id, err
+        );
+      }
+    }
+    Ok(())
+  }
+
+  async fn process_task(&self, task: &AFBlobVariantTaskRow) -> Result<(), WorkerError> {
+    let blob = match get_blob_metadata(&self.pg_pool, &task.workspace_id, &task.file_id).await {
+      Ok(blob) => blob,
+      // the task went away with the blob
+      Err(err) if err.is_record_not_found() => return Ok(()),
+      Err(err) => return Err(WorkerError::Internal(err.into())),
+    };
+    let variants = if is_thumbnail_source(&blob.file_type)
+      && blob.file_size as u64 <= self.config.max_source_bytes
+    {
+      self.generate_variants(&task.workspace_id, &blob).await?
+    } else {
+      vec![]
+    };
+    self.replace_variants(task, variants).await
+  }
+
+  async fn generate_variants(
+    &self,
+    workspace_id: &Uuid,
+    blob: &AFBlobMetadataRow,
+  ) -> Result<Vec<GeneratedVariant>, WorkerError> {
+    let Some(object_key) = resolve_blob_object_key(&self.s3_client, workspace_id, blob).await?
+    else {
+      return Err(WorkerError::RecordNotFound(format!(
+        "no object found for blob {} of workspace {}",
+        blob.file_id, workspace_id
+      )));
+    };
+    let response = self.s3_client.get_blob_stream(&object_key).await?;
+    let mut content = Vec::with_capacity(blob.file_size.max(0) as usize);
+    response
+      .stream
+      .take(self.config.max_source_bytes + 1)
+      .read_to_end(&mut content)
+      .await?;
+    if content.len() as u64 > self.config.max_source_bytes {
+      return Ok(vec![]);
+    }
+    tokio::task::spawn_blocking(move || encode_variants(&content))
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?
+      .map_err(WorkerError::Internal)
+  }
+
+  /// Upload the generated variants, then replace the variants recorded for the blob with them.
+  /// The variants are recorded as stale when the blob was uploaded again meanwhile, its task
+  /// being queued again.
+  async fn replace_variants(
+    &self,
+    task: &AFBlobVariantTaskRow,
+    variants: Vec<GeneratedVariant>,
+  ) -> Result<(), WorkerError> {
+    let blob_key = blob_object_key(&task.workspace_id, &task.file_id)
+      .unwrap_or_else(|| format!("{}/{}", task.workspace_id, task.file_id));
+    let mut uploaded = Vec::with_capacity(variants.len());
+    for variant in &variants {
+      let object_key = blob_variant_object_key(&blob_key, variant.variant);
+      self
+        .s3_client
+        .put_blob(
+          &object_key,
+          ByteStream::from(variant.content.clone()),
+          Some(variant.content_type),
+        )
+        .await?;
+      uploaded.push(object_key);
+    }
+
+    let mut txn = self
+      .pg_pool
+      .begin()
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    // the blob can't be deleted or uploaded again until the variants are recorded
+    let blob = select_blob_metadata_for_update(&mut txn, &task.workspace_id, &task.file_id)
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    if blob.is_none() {
+      drop(txn);
+      for object_key in uploaded {
+        self.s3_client.delete_blob(&object_key).await?;
+      }
+      return Ok(());
+    }
+    let current = complete_blob_variant_task(
+      txn.deref_mut(),
+      &task.workspace_id,
+      &task.file_id,
+      task.enqueued_at,
+    )
+    .await
+    .map_err(|err| WorkerError::Internal(err.into()))?;
+    let replaced =
+      delete_blob_variants_of_files(&mut txn, &task.workspace_id, slice::from_ref(&task.file_id))
+        .await
+        .map_err(|err| WorkerError::Internal(err.into()))?;
+    for (variant, object_key) in variants.iter().zip(&uploaded) {
+      upsert_blob_variant(
+        &mut txn,
+        &task.workspace_id,
+        &task.file_id,
+        variant.variant,
+        object_key,
+        variant.content_type,
+        variant.content.len() as i64,
+        !current,
+      )
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+    }
+    txn
+      .commit()
+      .await
+      .map_err(|err| WorkerError::Internal(err.into()))?;
+
+    // the variants generated again were overwritten in place
+    for object_key in replaced.iter().filter(|key| !uploaded.contains(key)) {
+      self.s3_client.delete_blob(object_key).await?;
+    }
+    trace!(
+      "[Thumbnail] generated {} variants of blob {} of workspace {}",
+      uploaded.len(),
+      task.file_id,
+      task.workspace_id
+    );
+    Ok(())
+  }
+}
+
+fn is_thumbnail_source(file_type: &str) -> bool {
+  THUMBNAIL_SOURCE_TYPES.contains(&file_type)
+}
+
+/// Downscale the image to each variant it's larger than. The images with an alpha channel are
+/// encoded as PNG, the others as JPEG. No variant is generated for the GIFs, which can be
+/// animated, for the contents which aren't a raster image, and for the images which are too
+/// large to be decoded.
+fn encode_variants(content: &[u8]) -> Result<Vec<GeneratedVariant>, anyhow::Error> {
+  let reader = ImageReader::new(Cursor::new(content)).with_guessed_format()?;
+  let format = match reader.format() {
+    None | Some(ImageFormat::Gif) => return Ok(vec![]),
+    Some(format) => format,
+  };
+  let Ok((width, height)) = reader.into_dimensions() else {
+    return Ok(vec![]);
+  };
+  if width == 0 || height == 0 || width as u64 * height as u64 > MAX_IMAGE_PIXELS {
+    return Ok(vec![]);
+  }
+  let variants = BlobVariant::ALL
+    .into_iter()
+    .filter(|variant| width.max(height) > variant.max_dimension())
+    .collect::<Vec<_>>();
+  if variants.is_empty() {
+    return Ok(vec![]);
+  }
+
+  let image = image::load_from_memory_with_format(content, format)
+    .map_err(|err| anyhow!("Failed to decode image: {}", err))?;
+  let has_alpha = image.color().has_alpha();
+  variants
+    .into_iter()
+    .map(|variant| {
+      let max_dimension = variant.max_dimension();
+      let resized = image.resize(max_dimension, max_dimension, FilterType::Lanczos3);
+      let (resized, output_format, content_type) = if has_alpha {
+        (resized, ImageOutputFormat::Png, "image/png")
+      } else {
+        (
+          DynamicImage::ImageRgb8(resized.to_rgb8()),
+          ImageOutputFormat::Jpeg(85),
+          "image/jpeg",
+        )
+      };
+      let mut content = Vec::new();
+      resized
+        .write_to(&mut content, output_format)
+        .map_err(|err| anyhow!("Failed to encode image: {}", err))?;
+      Ok(GeneratedVariant {
+        variant,
+        content,
+        content_type,
+      })
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use image::{ImageBuffer, Rgb, Rgba};
+
+  fn encode(image: DynamicImage, format: ImageOutputFormat) -> Vec<u8> {
+    let mut content = Vec::new();
+    image.write_to(&mut content, format).unwrap();
+    content
+  }
+
+  fn dimensions(content: &[u8]) -> (u32, u32) {
+    image::load_from_memory(content)
+      .unwrap()
+      .to_rgba8()
+      .dimensions()
+  }
+
+  #[test]
+  fn large_image_gets_both_variants_test() {
+    let image = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(2048, 1024, Rgb([200, 10, 10])));
+    let variants = encode_variants(&encode(image, ImageOutputFormat::Png)).unwrap();
+    assert_eq!(
+      variants.iter().map(|v| v.variant).collect::<Vec<_>>(),
+      vec![BlobVariant::Thumb, BlobVariant::Medium]
+    );
+    // without an alpha channel, the variants are JPEGs fitting in their bounds
+    assert!(variants.iter().all(|v| v.content_type == "image/jpeg"));
+    assert_eq!(dimensions(&variants[0].content), (256, 128));
+    assert_eq!(dimensions(&variants[1].content), (1024, 512));
+  }
+
+  #[test]
+  fn image_gets_the_variants_it_is_larger_than_test() {
+    let image = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(300, 600, Rgba([0, 0, 0, 128])));
+    let variants = encode_variants(&encode(image, ImageOutputFormat::Png)).unwrap();
+    assert_eq!(variants.len(), 1);
+    assert_eq!(variants[0].variant, BlobVariant::Thumb);
+    assert_eq!(variants[0].content_type, "image/png");
+    assert_eq!(dimensions(&variants[0].content), (128, 256));
+
+    let image = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(256, 100, Rgb([0, 0, 0])));
+    let variants = encode_variants(&encode(image, ImageOutputFormat::Jpeg(85))).unwrap();
+    assert!(variants.is_empty());
+  }
+
+  #[test]
+  fn gif_and_svg_are_passed_through_test() {
+    let image = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(300, 300, Rgba([0, 0, 0, 255])));
+    assert!(encode_variants(&encode(image, ImageOutputFormat::Gif))
+      .unwrap()
+      .is_empty());
+    let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" width="4096" height="4096"></svg>"#;
+    assert!(encode_variants(svg).unwrap().is_empty());
+    assert!(!is_thumbnail_source("image/gif"));
+    assert!(!is_thumbnail_source("image/svg+xml"));
+    assert!(is_thumbnail_source("image/webp"));
+  }
+}
@@ -0,0 +1,104 @@
+use crate::error::WorkerError;
+use crate::s3_client::S3ClientImpl;
+use database::collab::{delete_collab_cascade, select_collabs_deleted_before};
+use database::resource_usage::invalidate_workspace_usage_size_cache;
+use redis::aio::ConnectionManager;
+use sqlx::types::chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::ops::DerefMut;
+use std::sync::Arc;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info};
+
+pub struct TrashPurgeConfig {
+  pub enable: bool,
+  /// The deleted collabs are kept in the trash this long, so that they can be restored.
+  pub retention_secs: i64,
+  pub tick_interval_secs: u64,
+  /// Number of collabs purged per transaction.
+  pub batch_size: i64,
+}
+
+/// Purges the collabs which stayed in the trash for longer than the retention, along with their
+/// snapshots, embeddings and attached files, then deletes their objects from S3. Several workers
+/// can run this loop at the same time, each purges the collabs the others didn't lock.
+pub async fn run_trash_purge_worker(
+  pg_pool: PgPool,
+  s3_client: Arc<S3ClientImpl>,
+  redis_client: ConnectionManager,
+  config: TrashPurgeConfig,
+) -> Result<(), WorkerError> {
+  if !config.enable {
+    info!("Trash purge worker is disabled");
+    return Ok(());
+  }
+  info!(
+    "Starting trash purge worker, retention: {}s",
+    config.retention_secs
+  );
+  let mut tick = interval(std::time::Duration::from_secs(config.tick_interval_secs));
+  tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+  loop {
+    tick.tick().await;
+    loop {
+      match purge_expired_collabs(&pg_pool, &s3_client, &redis_client, &config).await {
+        Ok(0) => break,
+        Ok(count) => {
+          info!("[Trash] purged {} collabs", count);
+          if (count as i64) < config.batch_size {
+            break;
+          }
+        },
+        Err(err) => {
+          error!("[Trash] failed to purge the expired collabs: {:?}", err);
+          break;
+        },
+      }
+    }
+  }
+}
+
+/// Purges a batch of the collabs deleted before the retention. Returns how many were purged.
+async fn purge_expired_collabs(
+  pg_pool: &PgPool,
+  s3_client: &S3ClientImpl,
+  redis_client: &ConnectionManager,
+  config: &TrashPurgeConfig,
+) -> Result<usize, WorkerError> {
+  let deleted_before = Utc::now() - Duration::seconds(config.retention_secs);
+  let mut txn = pg_pool
+    .begin()
+    .await
+    .map_err(|err| WorkerError::Internal(err.into()))?;
+  let collabs = select_collabs_deleted_before(txn.deref_mut(), deleted_before, config.batch_size)
+    .await
+    .map_err(|err| WorkerError::Internal(err.into()))?;
+  let mut object_keys = vec![];
+  let mut workspace_ids = HashSet::new();
+  for collab in &collabs {
+    object_keys.extend(
+      delete_collab_cascade(&mut txn, &collab.workspace_id, &collab.oid)
+        .await
+        .map_err(|err| WorkerError::Internal(err.into()))?,
+    );
+    workspace_ids.insert(collab.workspace_id);
+  }
+  txn
+    .commit()
+    .await
+    .map_err(|err| WorkerError::Internal(err.into()))?;
+  for workspace_id in &workspace_ids {
+    invalidate_workspace_usage_size_cache(redis_client, workspace_id).await;
+  }
+
+  let failed = s3_client.delete_blobs(&object_keys).await?;
+  if !failed.is_empty() {
+    error!(
+      "[Trash] failed to delete {} objects of purged collabs: {:?}",
+      failed.len(),
+      failed
+    );
+  }
+  Ok(collabs.len())
+}
@@ -1,4 +1,6 @@
 use crate::api::util::ai_model_from_header;
+use crate::biz::chat::scheduler::queued_ai_stream;
+use crate::biz::workspace::ai_settings::workspace_ai_client;
 use crate::state::AppState;
 
 use actix_web::web::{Data, Json};
@@ -9,8 +11,7 @@ use appflowy_ai_client::dto::{
   TranslateRowResponse,
 };
 
-use futures_util::{stream, TryStreamExt};
-
+use database_entity::dto::AIFeature;
 use serde::Deserialize;
 use shared_entity::dto::ai_dto::{
   CompleteTextParams, SummarizeRowData, SummarizeRowParams, SummarizeRowResponse,
@@ -18,6 +19,7 @@ use shared_entity::dto::ai_dto::{
 use shared_entity::response::AppResponse;
 
 use tracing::{error, instrument, trace};
+use uuid::Uuid;
 
 pub fn ai_completion_scope() -> Scope {
   web::scope("/api/ai/{workspace_id}")
@@ -33,36 +35,39 @@ pub fn ai_completion_scope() -> Scope {
 
 async fn stream_complete_text_handler(
   state: Data<AppState>,
+  path: web::Path<Uuid>,
   payload: Json<CompleteTextParams>,
   req: HttpRequest,
 ) -> actix_web::Result<HttpResponse> {
+  let workspace_id = path.into_inner();
   let ai_model = ai_model_from_header(&req);
   let params = payload.into_inner();
+  let ai_client = workspace_ai_client(
+    &state.pg_pool,
+    &state.ai_client,
+    &workspace_id,
+    AIFeature::Completion,
+  )
+  .await?;
   state.metrics.ai_metrics.record_total_completion_count(1);
 
-  match state
-    .ai_client
-    .stream_completion_text(params, ai_model)
-    .await
-  {
-    Ok(stream) => Ok(
-      HttpResponse::Ok()
-        .content_type("text/event-stream")
-        .streaming(stream.map_err(AppError::from)),
-    ),
-    Err(err) => Ok(
-      HttpResponse::Ok()
-        .content_type("text/event-stream")
-        .streaming(stream::once(async move {
-          Err(AppError::AIServiceUnavailable(err.to_string()))
-        })),
-    ),
-  }
+  let ticket = state.ai_scheduler.enqueue(&workspace_id).await;
+  let stream = queued_ai_stream(
+    ticket,
+    state.ai_scheduler.keepalive_interval(),
+    move || async move { ai_client.stream_completion_text(params, ai_model).await },
+  );
+  Ok(
+    HttpResponse::Ok()
+      .content_type("text/event-stream")
+      .streaming(stream),
+  )
 }
 
 #[instrument(level = "debug", skip(state, payload), err)]
 async fn summarize_row_handler(
   state: Data<AppState>,
+  path: web::Path<Uuid>,
   payload: Json<SummarizeRowParams>,
   req: HttpRequest,
 ) -> actix_web::Result<Json<AppResponse<SummarizeRowResponse>>> {
@@ -82,12 +87,29 @@ async fn summarize_row_handler(
         );
       }
 
+      let workspace_id = path.into_inner();
+      let ai_client = workspace_ai_client(
+        &state.pg_pool,
+        &state.ai_client,
+        &workspace_id,
+        AIFeature::SummarizeRow,
+      )
+      .await?;
       state.metrics.ai_metrics.record_total_summary_row_count(1);
       let ai_model = ai_model_from_header(&req);
-      let result = state.ai_client.summarize_row(&content, ai_model).await;
+      let mut permit = state
+        .ai_scheduler
+        .enqueue(&workspace_id)
+        .await
+        .wait()
+        .await?;
+      let result = ai_client.summarize_row(&content, ai_model).await;
       let resp = match result {
         Ok(resp) => SummarizeRowResponse { text: resp.text },
         Err(err) => {
+          if err.is_rate_limited() {
+            permit.record_rate_limited();
+          }
           error!("Failed to summarize row: {:?}", err);
           SummarizeRowResponse {
             text: "No content".to_string(),
@@ -103,15 +125,33 @@ async fn summarize_row_handler(
 #[instrument(level = "debug", skip(state, payload), err)]
 async fn translate_row_handler(
   state: web::Data<AppState>,
+  path: web::Path<Uuid>,
   payload: web::Json<TranslateRowParams>,
   req: HttpRequest,
 ) -> actix_web::Result<Json<AppResponse<TranslateRowResponse>>> {
   let params = payload.into_inner();
   let ai_model = ai_model_from_header(&req);
+  let workspace_id = path.into_inner();
+  let ai_client = workspace_ai_client(
+    &state.pg_pool,
+    &state.ai_client,
+    &workspace_id,
+    AIFeature::TranslateRow,
+  )
+  .await?;
   state.metrics.ai_metrics.record_total_translate_row_count(1);
-  match state.ai_client.translate_row(params.data, ai_model).await {
+  let mut permit = state
+    .ai_scheduler
+    .enqueue(&workspace_id)
+    .await
+    .wait()
+    .await?;
+  match ai_client.translate_row(params.data, ai_model).await {
     Ok(resp) => Ok(AppResponse::Ok().with_data(resp).into()),
     Err(err) => {
+      if err.is_rate_limited() {
+        permit.record_rate_limited();
+      }
       error!("Failed to translate row: {:?}", err);
       Ok(
         AppResponse::Ok()
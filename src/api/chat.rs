@@ -2,6 +2,11 @@ use crate::biz::chat::ops::{
   create_chat, create_chat_message, delete_chat, generate_chat_message_answer, get_chat_messages,
   get_question_message, update_chat_message,
 };
+use crate::biz::chat::scheduler::{queued_ai_stream, stream_with_permit};
+use crate::biz::chat::share::{
+  enforce_chat_access, list_shared_chats, share_chat, unshare_chat, ChatAccess,
+};
+use crate::biz::workspace::ai_settings::workspace_ai_client;
 use crate::state::AppState;
 use actix_web::web::{Data, Json};
 use actix_web::{web, HttpRequest, HttpResponse, Scope};
@@ -9,6 +14,7 @@ use serde::Deserialize;
 
 use crate::api::util::ai_model_from_header;
 use app_error::AppError;
+use appflowy_ai_client::client::AppFlowyAIClient;
 use appflowy_ai_client::dto::{
   ChatQuestion, ChatQuestionQuery, CreateChatContext, MessageData, QuestionMetadata,
   RepeatedRelatedQuestion,
@@ -16,6 +22,7 @@ use appflowy_ai_client::dto::{
 use authentication::jwt::UserUuid;
 use bytes::Bytes;
 use database::chat;
+use database_entity::dto::AIFeature;
 use futures::Stream;
 use futures_util::stream;
 use futures_util::{FutureExt, TryStreamExt};
@@ -23,7 +30,8 @@ use pin_project::pin_project;
 use shared_entity::dto::chat_dto::{
   ChatAuthor, ChatMessage, ChatSettings, CreateAnswerMessageParams, CreateChatMessageParams,
   CreateChatMessageParamsV2, CreateChatParams, GetChatMessageParams, MessageCursor,
-  RepeatedChatMessage, UpdateChatMessageContentParams, UpdateChatParams,
+  RepeatedChatMessage, RepeatedSharedChat, ShareChatParams, UpdateChatMessageContentParams,
+  UpdateChatParams,
 };
 use shared_entity::response::{AppResponse, JsonAppResponse};
 use std::collections::HashMap;
@@ -41,6 +49,10 @@ pub fn chat_scope() -> Scope {
         web::resource("")
             .route(web::post().to(create_chat_handler))
       )
+      .service(
+        web::resource("/shared")
+            .route(web::get().to(list_shared_chats_handler))
+      )
       .service(
         web::resource("/{chat_id}")
             .route(web::delete().to(delete_chat_handler))
@@ -55,6 +67,13 @@ pub fn chat_scope() -> Scope {
             .route(web::post().to(update_chat_settings_handler))
       )
 
+      // Sharing
+      .service(
+        web::resource("/{chat_id}/share")
+            .route(web::put().to(share_chat_handler))
+            .route(web::delete().to(unshare_chat_handler))
+      )
+
       // Message management
       .service(
         web::resource("/{chat_id}/message")
@@ -110,30 +129,40 @@ async fn create_chat_handler(
   path: web::Path<String>,
   state: Data<AppState>,
   payload: Json<CreateChatParams>,
+  uuid: UserUuid,
 ) -> actix_web::Result<JsonAppResponse<()>> {
   let workspace_id = path.into_inner();
   let params = payload.into_inner();
-  create_chat(&state.pg_pool, params, &workspace_id).await?;
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  create_chat(&state.pg_pool, uid, params, &workspace_id).await?;
   Ok(AppResponse::Ok().into())
 }
 
 async fn delete_chat_handler(
   path: web::Path<(String, String)>,
   state: Data<AppState>,
+  uuid: UserUuid,
 ) -> actix_web::Result<JsonAppResponse<()>> {
   let (_workspace_id, chat_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  enforce_chat_access(&state.pg_pool, &chat_id, uid, ChatAccess::Owner).await?;
   delete_chat(&state.pg_pool, &chat_id).await?;
   Ok(AppResponse::Ok().into())
 }
 
 #[instrument(level = "debug", skip_all, err)]
 async fn create_chat_context_handler(
+  path: web::Path<(String, String)>,
   state: Data<AppState>,
   payload: Json<CreateChatContext>,
+  uuid: UserUuid,
 ) -> actix_web::Result<JsonAppResponse<()>> {
+  let (workspace_id, _chat_id) = path.into_inner();
   let params = payload.into_inner();
-  state
-    .ai_client
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  enforce_chat_access(&state.pg_pool, &params.chat_id, uid, ChatAccess::Ask).await?;
+  chat_ai_client(&state, &workspace_id)
+    .await?
     .create_chat_text_context(params)
     .await
     .map_err(AppError::from)?;
@@ -145,18 +174,15 @@ async fn update_question_handler(
   state: Data<AppState>,
   payload: Json<UpdateChatMessageContentParams>,
   req: HttpRequest,
+  uuid: UserUuid,
 ) -> actix_web::Result<JsonAppResponse<()>> {
   let (workspace_id, _chat_id) = path.into_inner();
   let params = payload.into_inner();
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  enforce_chat_access(&state.pg_pool, &params.chat_id, uid, ChatAccess::Ask).await?;
   let ai_model = ai_model_from_header(&req);
-  update_chat_message(
-    workspace_id,
-    &state.pg_pool,
-    params,
-    state.ai_client.clone(),
-    ai_model,
-  )
-  .await?;
+  let ai_client = chat_ai_client(&state, &workspace_id).await?;
+  update_chat_message(workspace_id, &state.pg_pool, params, ai_client, ai_model).await?;
   Ok(AppResponse::Ok().into())
 }
 
@@ -164,11 +190,14 @@ async fn get_related_message_handler(
   path: web::Path<(String, String, i64)>,
   state: Data<AppState>,
   req: HttpRequest,
+  uuid: UserUuid,
 ) -> actix_web::Result<JsonAppResponse<RepeatedRelatedQuestion>> {
-  let (_workspace_id, chat_id, message_id) = path.into_inner();
+  let (workspace_id, chat_id, message_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  enforce_chat_access(&state.pg_pool, &chat_id, uid, ChatAccess::Ask).await?;
   let ai_model = ai_model_from_header(&req);
-  let resp = state
-    .ai_client
+  let resp = chat_ai_client(&state, &workspace_id)
+    .await?
     .get_related_question(&chat_id, &message_id, ai_model)
     .await
     .map_err(|err| AppError::Internal(err.into()))?;
@@ -182,8 +211,11 @@ async fn create_question_handler(
   payload: Json<CreateChatMessageParams>,
   uuid: UserUuid,
 ) -> actix_web::Result<JsonAppResponse<ChatMessage>> {
-  let (_workspace_id, chat_id) = path.into_inner();
+  let (workspace_id, chat_id) = path.into_inner();
   let params = payload.into_inner();
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  enforce_chat_access(&state.pg_pool, &chat_id, uid, ChatAccess::Ask).await?;
+  let ai_client = chat_ai_client(&state, &workspace_id).await?;
 
   // When create a question, we will extract the metadata from the question content.
   // metadata might include user mention file,page,or user. For example, @Get started.
@@ -198,14 +230,12 @@ async fn create_question_handler(
       CreateChatContext::new(chat_id.clone(), data.content_type.to_string(), data.content)
         .with_metadata(desc);
     trace!("create context for question: {}", context);
-    state
-      .ai_client
+    ai_client
       .create_chat_text_context(context)
       .await
       .map_err(AppError::from)?;
   }
 
-  let uid = state.user_cache.get_user_uid(&uuid).await?;
   let resp = create_chat_message(&state.pg_pool, uid, chat_id, params).await?;
   Ok(AppResponse::Ok().with_data(resp).into())
 }
@@ -224,11 +254,14 @@ async fn save_answer_handler(
   path: web::Path<(String, String)>,
   payload: Json<CreateAnswerMessageParams>,
   state: Data<AppState>,
+  uuid: UserUuid,
 ) -> actix_web::Result<JsonAppResponse<ChatMessage>> {
   let payload = payload.into_inner();
   payload.validate().map_err(AppError::from)?;
 
   let (_workspace_id, chat_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  enforce_chat_access(&state.pg_pool, &chat_id, uid, ChatAccess::Ask).await?;
   let message = database::chat::chat_ops::insert_answer_message(
     &state.pg_pool,
     ChatAuthor::ai(),
@@ -245,13 +278,24 @@ async fn answer_handler(
   path: web::Path<(String, String, i64)>,
   state: Data<AppState>,
   req: HttpRequest,
+  uuid: UserUuid,
 ) -> actix_web::Result<JsonAppResponse<ChatMessage>> {
   let (workspace_id, chat_id, message_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  enforce_chat_access(&state.pg_pool, &chat_id, uid, ChatAccess::Ask).await?;
   let ai_model = ai_model_from_header(&req);
+  let ai_client = chat_ai_client(&state, &workspace_id).await?;
+  let workspace_uuid = Uuid::parse_str(&workspace_id).map_err(AppError::from)?;
+  let _permit = state
+    .ai_scheduler
+    .enqueue(&workspace_uuid)
+    .await
+    .wait()
+    .await?;
   let message = generate_chat_message_answer(
     workspace_id,
     &state.pg_pool,
-    state.ai_client.clone(),
+    ai_client,
     message_id,
     &chat_id,
     ai_model,
@@ -265,15 +309,25 @@ async fn answer_stream_handler(
   path: web::Path<(String, String, i64)>,
   state: Data<AppState>,
   req: HttpRequest,
+  uuid: UserUuid,
 ) -> actix_web::Result<HttpResponse> {
   let (workspace_id, chat_id, question_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  enforce_chat_access(&state.pg_pool, &chat_id, uid, ChatAccess::Ask).await?;
   let (content, metadata) =
     chat::chat_ops::select_chat_message_content(&state.pg_pool, question_id).await?;
   let rag_ids = chat::chat_ops::select_chat_rag_ids(&state.pg_pool, &chat_id).await?;
   let ai_model = ai_model_from_header(&req);
+  let ai_client = chat_ai_client(&state, &workspace_id).await?;
   state.metrics.ai_metrics.record_total_stream_count(1);
-  match state
-    .ai_client
+  let workspace_uuid = Uuid::parse_str(&workspace_id).map_err(AppError::from)?;
+  let mut permit = state
+    .ai_scheduler
+    .enqueue(&workspace_uuid)
+    .await
+    .wait()
+    .await?;
+  match ai_client
     .stream_question(
       workspace_id,
       &chat_id,
@@ -285,7 +339,7 @@ async fn answer_stream_handler(
     .await
   {
     Ok(answer_stream) => {
-      let new_answer_stream = answer_stream.map_err(AppError::from);
+      let new_answer_stream = stream_with_permit(answer_stream.map_err(AppError::from), permit);
       Ok(
         HttpResponse::Ok()
           .content_type("text/event-stream")
@@ -293,6 +347,9 @@ async fn answer_stream_handler(
       )
     },
     Err(err) => {
+      if err.is_rate_limited() {
+        permit.record_rate_limited();
+      }
       state.metrics.ai_metrics.record_failed_stream_count(1);
       Ok(
         HttpResponse::Ok()
@@ -310,12 +367,16 @@ async fn answer_stream_v2_handler(
   path: web::Path<(String, String, i64)>,
   state: Data<AppState>,
   req: HttpRequest,
+  uuid: UserUuid,
 ) -> actix_web::Result<HttpResponse> {
   let (workspace_id, chat_id, question_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  enforce_chat_access(&state.pg_pool, &chat_id, uid, ChatAccess::Ask).await?;
   let (content, metadata) =
     chat::chat_ops::select_chat_message_content(&state.pg_pool, question_id).await?;
   let rag_ids = chat::chat_ops::select_chat_rag_ids(&state.pg_pool, &chat_id).await?;
   let ai_model = ai_model_from_header(&req);
+  let ai_client = chat_ai_client(&state, &workspace_id).await?;
 
   state.metrics.ai_metrics.record_total_stream_count(1);
   trace!(
@@ -324,38 +385,33 @@ async fn answer_stream_v2_handler(
     content,
     rag_ids
   );
-  match state
-    .ai_client
-    .stream_question_v2(
-      workspace_id,
-      &chat_id,
-      question_id,
-      &content,
-      Some(metadata),
-      rag_ids,
-      ai_model,
-    )
-    .await
-  {
-    Ok(answer_stream) => {
-      let new_answer_stream = answer_stream.map_err(AppError::from);
-      Ok(
-        HttpResponse::Ok()
-          .content_type("text/event-stream")
-          .streaming(new_answer_stream),
-      )
+  let workspace_uuid = Uuid::parse_str(&workspace_id).map_err(AppError::from)?;
+  let ticket = state.ai_scheduler.enqueue(&workspace_uuid).await;
+  let metrics = state.metrics.ai_metrics.clone();
+  let answer_stream = queued_ai_stream(
+    ticket,
+    state.ai_scheduler.keepalive_interval(),
+    move || async move {
+      ai_client
+        .stream_question_v2(
+          workspace_id,
+          &chat_id,
+          question_id,
+          &content,
+          Some(metadata),
+          rag_ids,
+          ai_model,
+        )
+        .await
     },
-    Err(err) => {
-      state.metrics.ai_metrics.record_failed_stream_count(1);
-      Ok(
-        HttpResponse::ServiceUnavailable()
-          .content_type("text/event-stream")
-          .streaming(stream::once(async move {
-            Err(AppError::AIServiceUnavailable(err.to_string()))
-          })),
-      )
-    },
-  }
+  )
+  .inspect_err(move |_| metrics.record_failed_stream_count(1));
+
+  Ok(
+    HttpResponse::Ok()
+      .content_type("text/event-stream")
+      .streaming(answer_stream),
+  )
 }
 
 #[instrument(level = "debug", skip_all, err)]
@@ -364,13 +420,17 @@ async fn answer_stream_v3_handler(
   payload: Json<ChatQuestionQuery>,
   state: Data<AppState>,
   req: HttpRequest,
+  uuid: UserUuid,
 ) -> actix_web::Result<HttpResponse> {
   let (workspace_id, _) = path.into_inner();
   let payload = payload.into_inner();
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  enforce_chat_access(&state.pg_pool, &payload.chat_id, uid, ChatAccess::Ask).await?;
   let (content, metadata) =
     chat::chat_ops::select_chat_message_content(&state.pg_pool, payload.question_id).await?;
   let rag_ids = chat::chat_ops::select_chat_rag_ids(&state.pg_pool, &payload.chat_id).await?;
   let ai_model = ai_model_from_header(&req);
+  let ai_client = chat_ai_client(&state, &workspace_id).await?;
   state.metrics.ai_metrics.record_total_stream_count(1);
   if payload.format.output_content.is_image() {
     state.metrics.ai_metrics.record_stream_image_count(1);
@@ -391,30 +451,25 @@ async fn answer_stream_v3_handler(
   };
 
   trace!("[Chat] stream v3 {:?}", question);
-  match state
-    .ai_client
-    .stream_question_v3(ai_model, question, Some(60))
-    .await
-  {
-    Ok(answer_stream) => {
-      let new_answer_stream = answer_stream.map_err(AppError::from);
-      Ok(
-        HttpResponse::Ok()
-          .content_type("text/event-stream")
-          .streaming(new_answer_stream),
-      )
+  let workspace_uuid = Uuid::parse_str(&question.metadata.workspace_id).map_err(AppError::from)?;
+  let ticket = state.ai_scheduler.enqueue(&workspace_uuid).await;
+  let metrics = state.metrics.ai_metrics.clone();
+  let answer_stream = queued_ai_stream(
+    ticket,
+    state.ai_scheduler.keepalive_interval(),
+    move || async move {
+      ai_client
+        .stream_question_v3(ai_model, question, Some(60))
+        .await
     },
-    Err(err) => {
-      state.metrics.ai_metrics.record_failed_stream_count(1);
-      Ok(
-        HttpResponse::ServiceUnavailable()
-          .content_type("text/event-stream")
-          .streaming(stream::once(async move {
-            Err(AppError::AIServiceUnavailable(err.to_string()))
-          })),
-      )
-    },
-  }
+  )
+  .inspect_err(move |_| metrics.record_failed_stream_count(1));
+
+  Ok(
+    HttpResponse::Ok()
+      .content_type("text/event-stream")
+      .streaming(answer_stream),
+  )
 }
 
 #[instrument(level = "debug", skip_all, err)]
@@ -422,6 +477,7 @@ async fn get_chat_message_handler(
   path: web::Path<(String, String)>,
   query: web::Query<HashMap<String, String>>,
   state: Data<AppState>,
+  uuid: UserUuid,
 ) -> actix_web::Result<JsonAppResponse<RepeatedChatMessage>> {
   let mut params = GetChatMessageParams {
     cursor: MessageCursor::Offset(0),
@@ -442,6 +498,8 @@ async fn get_chat_message_handler(
 
   trace!("get chat messages: {:?}", params);
   let (_workspace_id, chat_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  enforce_chat_access(&state.pg_pool, &chat_id, uid, ChatAccess::Read).await?;
   let messages = get_chat_messages(&state.pg_pool, params, &chat_id).await?;
   Ok(AppResponse::Ok().with_data(messages).into())
 }
@@ -451,8 +509,11 @@ async fn get_chat_question_message_handler(
   path: web::Path<(String, String)>,
   query: web::Query<FindQuestionParams>,
   state: Data<AppState>,
+  uuid: UserUuid,
 ) -> actix_web::Result<JsonAppResponse<Option<ChatMessage>>> {
   let (_workspace_id, chat_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  enforce_chat_access(&state.pg_pool, &chat_id, uid, ChatAccess::Read).await?;
   let message = get_question_message(&state.pg_pool, &chat_id, query.0.answer_message_id).await?;
   Ok(AppResponse::Ok().with_data(message).into())
 }
@@ -461,8 +522,11 @@ async fn get_chat_question_message_handler(
 async fn get_chat_settings_handler(
   path: web::Path<(String, String)>,
   state: Data<AppState>,
+  uuid: UserUuid,
 ) -> actix_web::Result<JsonAppResponse<ChatSettings>> {
   let (_, chat_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  enforce_chat_access(&state.pg_pool, &chat_id, uid, ChatAccess::Read).await?;
   let chat_id_uuid = Uuid::parse_str(&chat_id).map_err(AppError::from)?;
   let settings = chat::chat_ops::select_chat_settings(&state.pg_pool, &chat_id_uuid).await?;
   Ok(AppResponse::Ok().with_data(settings).into())
@@ -472,13 +536,67 @@ async fn update_chat_settings_handler(
   path: web::Path<(String, String)>,
   state: Data<AppState>,
   payload: Json<UpdateChatParams>,
+  uuid: UserUuid,
 ) -> actix_web::Result<JsonAppResponse<()>> {
   let (_workspace_id, chat_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  enforce_chat_access(&state.pg_pool, &chat_id, uid, ChatAccess::Owner).await?;
   let chat_id_uuid = Uuid::parse_str(&chat_id).map_err(AppError::from)?;
   chat::chat_ops::update_chat_settings(&state.pg_pool, &chat_id_uuid, payload.into_inner()).await?;
   Ok(AppResponse::Ok().into())
 }
 
+async fn share_chat_handler(
+  path: web::Path<(String, String)>,
+  state: Data<AppState>,
+  payload: Json<ShareChatParams>,
+  uuid: UserUuid,
+) -> actix_web::Result<JsonAppResponse<()>> {
+  let (_workspace_id, chat_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  enforce_chat_access(&state.pg_pool, &chat_id, uid, ChatAccess::Owner).await?;
+  share_chat(&state.pg_pool, &chat_id, payload.into_inner()).await?;
+  Ok(AppResponse::Ok().into())
+}
+
+async fn unshare_chat_handler(
+  path: web::Path<(String, String)>,
+  state: Data<AppState>,
+  uuid: UserUuid,
+) -> actix_web::Result<JsonAppResponse<()>> {
+  let (_workspace_id, chat_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  enforce_chat_access(&state.pg_pool, &chat_id, uid, ChatAccess::Owner).await?;
+  unshare_chat(&state.pg_pool, &chat_id).await?;
+  Ok(AppResponse::Ok().into())
+}
+
+async fn list_shared_chats_handler(
+  path: web::Path<String>,
+  state: Data<AppState>,
+  uuid: UserUuid,
+) -> actix_web::Result<JsonAppResponse<RepeatedSharedChat>> {
+  let workspace_id = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  let chats = list_shared_chats(&state.pg_pool, &workspace_id, uid).await?;
+  Ok(AppResponse::Ok().with_data(chats).into())
+}
+
+/// The AI client answering the chats of the workspace, see [workspace_ai_client].
+async fn chat_ai_client(
+  state: &AppState,
+  workspace_id: &str,
+) -> Result<AppFlowyAIClient, AppError> {
+  let workspace_id = Uuid::parse_str(workspace_id)?;
+  workspace_ai_client(
+    &state.pg_pool,
+    &state.ai_client,
+    &workspace_id,
+    AIFeature::Chat,
+  )
+  .await
+}
+
 #[pin_project]
 pub struct FinalAnswerStream<S, F> {
   #[pin]
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use actix_web::web::{Data, Query};
+use actix_web::{web, Scope};
+use authentication::jwt::Authorization;
+use collab_stream::client::CollabRedisStream;
+use shared_entity::dto::collab_compaction_dto::{CollabCompactionResult, CompactCollabQuery};
+use shared_entity::dto::collab_recovery_dto::{
+  CollabVerificationReport, ListCollabVerificationReportsQuery,
+};
+use shared_entity::response::{AppResponse, JsonAppResponse};
+use uuid::Uuid;
+
+use crate::biz::collab::compaction::compact_collab_history;
+use crate::biz::collab::recovery::{confirm_collab_repair, list_collab_verification_reports};
+use crate::biz::moderation::check_admin;
+use crate::state::AppState;
+
+/// Collabs whose stored state could not be decoded, and the compaction of the stored collabs,
+/// restricted to the admins of the instance.
+pub fn collab_admin_scope() -> Scope {
+  web::scope("/api/admin/collab")
+    .service(
+      web::resource("/verification-reports")
+        .route(web::get().to(list_collab_verification_reports_handler)),
+    )
+    .service(
+      web::resource("/verification-reports/{report_id}/repair")
+        .route(web::post().to(confirm_collab_repair_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/{object_id}/compact")
+        .route(web::post().to(compact_collab_handler)),
+    )
+}
+
+async fn list_collab_verification_reports_handler(
+  auth: Authorization,
+  query: Query<ListCollabVerificationReportsQuery>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<Vec<CollabVerificationReport>>> {
+  check_admin(&auth)?;
+  let query = query.into_inner();
+  let reports = list_collab_verification_reports(
+    &state.pg_pool,
+    query.include_repaired.unwrap_or(false),
+    query.offset,
+    query.limit,
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(reports).into())
+}
+
+async fn confirm_collab_repair_handler(
+  auth: Authorization,
+  report_id: web::Path<i64>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<CollabVerificationReport>> {
+  check_admin(&auth)?;
+  let uid = state.user_cache.get_user_uid(&auth.uuid()?).await?;
+  let report = confirm_collab_repair(
+    &state.pg_pool,
+    &*state.collab_access_control_storage,
+    &state.collab_cache,
+    uid,
+    report_id.into_inner(),
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(report).into())
+}
+
+async fn compact_collab_handler(
+  auth: Authorization,
+  path: web::Path<(Uuid, String)>,
+  query: Query<CompactCollabQuery>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<CollabCompactionResult>> {
+  check_admin(&auth)?;
+  let (workspace_id, object_id) = path.into_inner();
+  let collab_redis_stream = Arc::new(CollabRedisStream::new_with_connection_manager(
+    state.redis_connection_manager.clone(),
+    state.redis_stream_router.clone(),
+  ));
+  let result = compact_collab_history(
+    &state.pg_pool,
+    state.collab_access_control_storage.clone(),
+    collab_redis_stream,
+    &state.config.collab.compaction,
+    &workspace_id,
+    &object_id,
+    query.skip_connected.unwrap_or(false),
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(result).into())
+}
@@ -12,12 +12,12 @@ use crate::biz::workspace::ops::{create_empty_workspace, create_upload_task, num
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
 use database::user::select_name_and_email_from_uuid;
-use database::workspace::select_import_task_by_state;
+use database::workspace::{select_import_task_by_state, select_import_task_report};
 use database_entity::dto::{CreateImportTask, CreateImportTaskResponse};
 use futures_util::StreamExt;
 use infra::env_util::get_env_var;
 use serde_json::json;
-use shared_entity::dto::import_dto::{ImportTaskDetail, UserImportTask};
+use shared_entity::dto::import_dto::{ImportReport, ImportTaskDetail, UserImportTask};
 use shared_entity::response::{AppResponse, JsonAppResponse};
 use std::env::temp_dir;
 use std::path::PathBuf;
@@ -35,6 +35,7 @@ pub fn data_import_scope() -> Scope {
         .route(web::get().to(get_import_detail_handler)),
     )
     .service(web::resource("/create").route(web::post().to(create_import_handler)))
+    .service(web::resource("/{task_id}/report").route(web::get().to(get_import_report_handler)))
 }
 
 #[instrument(level = "debug", skip_all)]
@@ -88,6 +89,7 @@ async fn create_import_handler(
          "s3_key": s3_key,
          "host": host,
          "workspace_name": &params.workspace_name,
+         "source": params.source,
       }
   });
 
@@ -127,6 +129,7 @@ async fn get_import_detail_handler(
           file_size: task.file_size as u64,
           created_at: task.created_at.timestamp(),
           status: task.status,
+          has_report: task.report.is_some(),
         })
         .collect::<Vec<_>>()
     })?;
@@ -141,6 +144,16 @@ async fn get_import_detail_handler(
   )
 }
 
+async fn get_import_report_handler(
+  user_uuid: UserUuid,
+  state: Data<AppState>,
+  task_id: web::Path<Uuid>,
+) -> actix_web::Result<JsonAppResponse<ImportReport>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let report = select_import_task_report(&state.pg_pool, &task_id, uid).await?;
+  Ok(AppResponse::Ok().with_data(report).into())
+}
+
 async fn import_data_handler(
   user_uuid: UserUuid,
   state: Data<AppState>,
@@ -0,0 +1,57 @@
+use actix_web::web::{Data, Json};
+use actix_web::{web, Scope};
+use app_error::AppError;
+use authentication::jwt::Authorization;
+use shared_entity::dto::fault_injection_dto::FaultInjectionParams;
+use shared_entity::response::{AppResponse, JsonAppResponse};
+
+use crate::biz::moderation::check_admin;
+use crate::middleware::fault_injection::FaultInjector;
+use crate::state::AppState;
+
+/// Adjusts the faults injected by a staging server, restricted to the admins of the instance. The
+/// requests fail when `APPFLOWY_FAULT_INJECTION_ENABLED` is not set.
+pub fn fault_injection_scope() -> Scope {
+  web::scope("/api/admin/fault-injection").service(
+    web::resource("")
+      .route(web::get().to(get_fault_injection_handler))
+      .route(web::put().to(set_fault_injection_handler))
+      .route(web::delete().to(clear_fault_injection_handler)),
+  )
+}
+
+fn fault_injector(state: &AppState) -> Result<&FaultInjector, AppError> {
+  state
+    .fault_injector
+    .as_deref()
+    .ok_or_else(|| AppError::InvalidRequest("Fault injection is not enabled".to_string()))
+}
+
+async fn get_fault_injection_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<FaultInjectionParams>> {
+  check_admin(&auth)?;
+  let params = fault_injector(&state)?.params();
+  Ok(AppResponse::Ok().with_data(params).into())
+}
+
+async fn set_fault_injection_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+  payload: Json<FaultInjectionParams>,
+) -> actix_web::Result<JsonAppResponse<FaultInjectionParams>> {
+  check_admin(&auth)?;
+  let injector = fault_injector(&state)?;
+  injector.set_params(payload.into_inner())?;
+  Ok(AppResponse::Ok().with_data(injector.params()).into())
+}
+
+async fn clear_fault_injection_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<()>> {
+  check_admin(&auth)?;
+  fault_injector(&state)?.set_params(FaultInjectionParams::default())?;
+  Ok(AppResponse::Ok().into())
+}
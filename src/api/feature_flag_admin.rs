@@ -0,0 +1,46 @@
+use actix_web::web::{Data, Json};
+use actix_web::{web, Scope};
+use authentication::jwt::Authorization;
+use shared_entity::dto::feature_flag_dto::{
+  FeatureFlag, RepeatedFeatureFlag, UpdateFeatureFlagParams,
+};
+use shared_entity::response::{AppResponse, JsonAppResponse};
+
+use crate::biz::moderation::check_admin;
+use crate::state::AppState;
+
+/// Feature flags of the deployment, restricted to the admins of the instance. An update reaches
+/// every server within a few milliseconds, without a redeploy.
+pub fn feature_flag_admin_scope() -> Scope {
+  web::scope("/api/admin/feature-flags")
+    .service(web::resource("").route(web::get().to(list_feature_flags_handler)))
+    .service(web::resource("/{name}").route(web::put().to(update_feature_flag_handler)))
+}
+
+async fn list_feature_flags_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<RepeatedFeatureFlag>> {
+  check_admin(&auth)?;
+  let items = state.feature_flags.list();
+  Ok(
+    AppResponse::Ok()
+      .with_data(RepeatedFeatureFlag { items })
+      .into(),
+  )
+}
+
+async fn update_feature_flag_handler(
+  auth: Authorization,
+  path: web::Path<String>,
+  state: Data<AppState>,
+  payload: Json<UpdateFeatureFlagParams>,
+) -> actix_web::Result<JsonAppResponse<FeatureFlag>> {
+  check_admin(&auth)?;
+  let admin_uid = state.user_cache.get_user_uid(&auth.uuid()?).await?;
+  let flag = state
+    .feature_flags
+    .update(&path.into_inner(), payload.into_inner().value, admin_uid)
+    .await?;
+  Ok(AppResponse::Ok().with_data(flag).into())
+}
@@ -1,41 +1,71 @@
 use access_control::act::Action;
 use actix_http::body::BoxBody;
 use actix_web::http::header::{
-  ContentLength, ContentType, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE,
-  LAST_MODIFIED,
+  ContentLength, ContentType, ACCEPT, ACCEPT_RANGES, CACHE_CONTROL, CONTENT_DISPOSITION,
+  CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_RANGE, LAST_MODIFIED,
+  RANGE, X_CONTENT_TYPE_OPTIONS,
 };
 use actix_web::web::{Json, Payload};
 use actix_web::{
   web::{self, Data},
-  HttpRequest, ResponseError, Scope,
+  Either, HttpRequest, ResponseError, Scope,
 };
 use actix_web::{HttpResponse, Result};
 use app_error::AppError;
 use authentication::jwt::UserUuid;
-use chrono::DateTime;
+use chrono::{DateTime, Utc};
+use database::blob_access::record_blob_access;
 use database::file::BlobKey;
-use database::resource_usage::{get_all_workspace_blob_metadata, get_workspace_usage_size};
+use database::resource_usage::{
+  get_blob_usage_by_member, get_workspace_blob_metadata_page, get_workspace_usage_breakdown,
+  get_workspace_usage_size_cached, max_blob_size_for_workspace, BlobAttributes,
+};
+use database::statement_timeout::begin_with_statement_timeout;
+use database_entity::dto::AFRole;
 use database_entity::file_dto::{
-  CompleteUploadRequest, CreateUploadRequest, CreateUploadResponse, UploadPartData,
-  UploadPartResponse,
+  BlobVariant, BlobVariantQuery, CompletePresignedUploadRequest, CompleteUploadRequest,
+  CreateUploadRequest, CreateUploadResponse, ListUploadPartsResponse, PresignedUploadRequest,
+  PresignedUploadResponse, PutBlobQuery, UploadPartData, UploadPartResponse,
 };
 
+use crate::api::util::{accepts_page_response, if_none_match};
 use crate::biz::data_import::LimitedPayload;
+use crate::biz::workspace::blob_export::export_workspace_blob_metadata;
+use crate::biz::workspace::content_type::{
+  check_declared_content_type, get_allowed_content_types, resolve_blob_content_type,
+};
+use crate::biz::workspace::image::is_reserved_file_id;
 use crate::state::AppState;
 use anyhow::anyhow;
-use aws_sdk_s3::primitives::ByteStream;
 use collab_importer::util::FileId;
-use database::pg_row::AFBlobStatus;
+use database::pg_row::{AFBlobMetadataRow, AFBlobStatus, AFBlobVariantRow};
 use serde::Deserialize;
-use shared_entity::dto::file_dto::PutFileResponse;
-use shared_entity::dto::workspace_dto::{BlobMetadata, RepeatedBlobMetaData, WorkspaceSpaceUsage};
+use shared_entity::dto::file_dto::{
+  BlobFileIds, BlobVersion, PutFileResponse, RepeatedBlobVersion,
+};
+use shared_entity::dto::page_dto::{Cursor, Page, PageQuery};
+use shared_entity::dto::workspace_dto::{
+  BlobMetadata, BlobMetadataExportFormat, BlobMetadataPage, WorkspaceSpaceUsage,
+};
 use shared_entity::response::{AppResponse, AppResponseError, JsonAppResponse};
 use sqlx::types::Uuid;
+use std::ops::DerefMut;
 use std::pin::Pin;
 use tokio::io::{AsyncRead, AsyncReadExt};
 use tokio_stream::StreamExt;
 use tokio_util::io::StreamReader;
-use tracing::{error, event, instrument, trace};
+use tracing::{error, event, instrument, trace, warn};
+
+const MAX_BLOB_METADATA_PAGE_SIZE: u32 = 1000;
+const BLOB_CURSOR_KIND: &str = "blob";
+const MAX_DELETE_BLOBS: usize = 1000;
+/// The largest object S3 accepts in a single PUT request
+const MAX_PRESIGNED_UPLOAD_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+/// 31536000 seconds = 1 year
+const BLOB_CACHE_CONTROL: &str = "public, immutable, max-age=31536000";
+/// A blob served in place of a variant which isn't generated yet is only cached for a while, so
+/// that the variant is served once it's generated.
+const VARIANT_FALLBACK_CACHE_CONTROL: &str = "public, max-age=300";
 
 pub fn file_storage_scope() -> Scope {
   web::scope("/api/file_storage")
@@ -57,15 +87,34 @@ pub fn file_storage_scope() -> Scope {
       web::resource("/{workspace_id}/blobs")
         .route(web::get().to(get_all_workspace_blob_metadata_handler)),
     )
+    .service(
+      web::resource("/{workspace_id}/blobs/export")
+        .route(web::get().to(export_workspace_blob_metadata_handler)),
+    )
     .service(web::resource("/{workspace_id}/create_upload").route(web::post().to(create_upload)))
     .service(
       web::resource("/{workspace_id}/upload_part/{parent_dir}/{file_id}/{upload_id}/{part_num}")
         .route(web::put().to(upload_part_handler)),
     )
+    // The parts uploaded so far, for the clients to resume an interrupted upload
+    .service(
+      web::resource("/{workspace_id}/upload_part/{parent_dir}/{file_id}/{upload_id}")
+        .route(web::get().to(list_upload_parts_handler)),
+    )
     .service(
       web::resource("/{workspace_id}/complete_upload")
         .route(web::put().to(complete_upload_handler)),
     )
+    // The large files are uploaded by the clients straight to the bucket, with the url returned
+    // by presigned-upload, then recorded with complete-presigned-upload.
+    .service(
+      web::resource("/{workspace_id}/presigned_upload")
+        .route(web::post().to(create_presigned_upload_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/presigned_upload/complete")
+        .route(web::put().to(complete_presigned_upload_handler)),
+    )
     .service(
       web::resource("/{workspace_id}/v1/blob/{parent_dir}/{file_id}")
         .route(web::get().to(get_blob_v1_handler))
@@ -75,13 +124,22 @@ pub fn file_storage_scope() -> Scope {
       web::resource("/{workspace_id}/v1/metadata/{parent_dir}/{file_id}")
         .route(web::get().to(get_blob_metadata_v1_handler)),
     )
+    .service(
+      web::resource("/{workspace_id}/v1/versions/{parent_dir}/{file_id}")
+        .route(web::get().to(list_blob_versions_v1_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/v1/versions/{parent_dir}/{file_id}/{version}/restore")
+        .route(web::post().to(restore_blob_version_v1_handler)),
+    )
     .service(
       // Upload the file in a single request. This process combines the steps of create-upload,
       // upload-part, and complete-upload into a single operation. The file can then be retrieved
       // using the get_blob_v1_handler. If you want to support resumeable uploads, you should use
       // the create-upload, upload-part, and complete-upload endpoints.
       web::resource("/{workspace_id}/v1/blob/{parent_dir}")
-        .route(web::put().to(put_blob_handler_v1)),
+        .route(web::put().to(put_blob_handler_v1))
+        .route(web::delete().to(delete_blobs_v1_handler)),
     )
 }
 
@@ -100,12 +158,22 @@ async fn create_upload(
   if req.file_id.is_empty() {
     return Err(AppError::InvalidRequest("file_id is empty".to_string()).into());
   }
+
+  if is_reserved_file_id(&req.file_id) {
+    return Err(AppError::InvalidRequest("file_id is reserved".to_string()).into());
+  }
   let uid = state.user_cache.get_user_uid(&user_uuid).await?;
   let workspace_id = workspace_id.into_inner();
   state
     .workspace_access_control
     .enforce_action(&uid, &workspace_id.to_string(), Action::Write)
     .await?;
+  // the parts are uploaded as they are, only the declared type can be checked
+  let allowed_content_types = get_allowed_content_types(&state.pg_pool, &workspace_id).await?;
+  check_declared_content_type(&req.content_type, &allowed_content_types)?;
+  if let Some(file_size) = req.file_size {
+    check_blob_size(&state, &workspace_id, file_size).await?;
+  }
 
   let key = BlobPathV1 {
     workspace_id,
@@ -154,7 +222,26 @@ async fn upload_part_handler(
     .enforce_action(&uid, &workspace_id.to_string(), Action::Write)
     .await?;
 
+  let key = BlobPathV1 {
+    workspace_id,
+    parent_dir: path_params.parent_dir,
+    file_id: path_params.file_id.clone(),
+  };
+  // the size of the file isn't always declared when the upload is created, the parts uploaded so
+  // far must not go over the limit
+  let uploaded_size = state
+    .bucket_storage
+    .list_upload_parts(key.clone(), &path_params.upload_id)
+    .await
+    .map_err(AppResponseError::from)?
+    .parts
+    .iter()
+    .filter(|part| part.part_number != path_params.part_num)
+    .map(|part| part.size)
+    .sum::<u64>();
   let content_length = content_length.into_inner().into_inner();
+  check_blob_size(&state, &workspace_id, uploaded_size + content_length as u64).await?;
+
   let mut content = Vec::with_capacity(content_length);
   while let Some(chunk) = payload.try_next().await? {
     content.extend_from_slice(&chunk);
@@ -171,21 +258,50 @@ async fn upload_part_handler(
     );
   }
   let data = UploadPartData {
-    file_id: path_params.file_id.clone(),
+    file_id: path_params.file_id,
     upload_id: path_params.upload_id,
     part_number: path_params.part_num,
     body: content,
   };
 
+  let resp = state
+    .bucket_storage
+    .upload_part(key, data)
+    .await
+    .map_err(AppResponseError::from)?;
+  Ok(AppResponse::Ok().with_data(resp).into())
+}
+
+#[derive(Deserialize)]
+struct ListUploadPartsPath {
+  workspace_id: Uuid,
+  parent_dir: String,
+  file_id: String,
+  upload_id: String,
+}
+
+#[instrument(level = "debug", skip_all, err)]
+async fn list_upload_parts_handler(
+  user_uuid: UserUuid,
+  path: web::Path<ListUploadPartsPath>,
+  state: web::Data<AppState>,
+) -> Result<JsonAppResponse<ListUploadPartsResponse>> {
+  let path_params = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let workspace_id = path_params.workspace_id;
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id.to_string(), Action::Write)
+    .await?;
+
   let key = BlobPathV1 {
     workspace_id,
     parent_dir: path_params.parent_dir,
     file_id: path_params.file_id,
   };
-
   let resp = state
     .bucket_storage
-    .upload_part(key, data)
+    .list_upload_parts(key, &path_params.upload_id)
     .await
     .map_err(AppResponseError::from)?;
   Ok(AppResponse::Ok().with_data(resp).into())
@@ -212,7 +328,97 @@ async fn complete_upload_handler(
   };
   state
     .bucket_storage
-    .complete_upload(key, req)
+    .complete_upload(key, req, BlobAttributes::uploaded_by(uid))
+    .await
+    .map_err(AppResponseError::from)?;
+
+  Ok(AppResponse::Ok().into())
+}
+
+#[instrument(skip_all, err)]
+async fn create_presigned_upload_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  state: web::Data<AppState>,
+  req: web::Json<PresignedUploadRequest>,
+) -> Result<JsonAppResponse<PresignedUploadResponse>> {
+  let req = req.into_inner();
+  if req.parent_dir.is_empty() {
+    return Err(AppError::InvalidRequest("parent_dir is empty".to_string()).into());
+  }
+
+  if req.file_id.is_empty() {
+    return Err(AppError::InvalidRequest("file_id is empty".to_string()).into());
+  }
+
+  if is_reserved_file_id(&req.file_id) {
+    return Err(AppError::InvalidRequest("file_id is reserved".to_string()).into());
+  }
+
+  if req.content_type.is_empty() {
+    return Err(AppError::InvalidRequest("content_type is empty".to_string()).into());
+  }
+
+  if req.content_length == 0 {
+    return Err(AppError::InvalidRequest("content_length is zero".to_string()).into());
+  }
+
+  if req.content_length > MAX_PRESIGNED_UPLOAD_SIZE {
+    return Err(
+      AppError::PayloadTooLarge(format!(
+        "Content length is {}, but the maximum is {}",
+        req.content_length, MAX_PRESIGNED_UPLOAD_SIZE
+      ))
+      .into(),
+    );
+  }
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let workspace_id = workspace_id.into_inner();
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id.to_string(), Action::Write)
+    .await?;
+  // the content is sent straight to the bucket, only the declared type and size can be checked
+  let allowed_content_types = get_allowed_content_types(&state.pg_pool, &workspace_id).await?;
+  check_declared_content_type(&req.content_type, &allowed_content_types)?;
+  check_blob_size(&state, &workspace_id, req.content_length).await?;
+
+  let key = BlobPathV1 {
+    workspace_id,
+    parent_dir: req.parent_dir.clone(),
+    file_id: req.file_id.clone(),
+  };
+  let resp = state
+    .bucket_storage
+    .create_presigned_upload(key, req)
+    .await
+    .map_err(AppResponseError::from)?;
+
+  Ok(AppResponse::Ok().with_data(resp).into())
+}
+
+async fn complete_presigned_upload_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  state: web::Data<AppState>,
+  req: web::Json<CompletePresignedUploadRequest>,
+) -> Result<JsonAppResponse<()>> {
+  let req = req.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let workspace_id = workspace_id.into_inner();
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id.to_string(), Action::Write)
+    .await?;
+
+  let key = BlobPathV1 {
+    workspace_id,
+    parent_dir: req.parent_dir,
+    file_id: req.file_id,
+  };
+  state
+    .bucket_storage
+    .complete_presigned_upload(key, BlobAttributes::uploaded_by(uid))
     .await
     .map_err(AppResponseError::from)?;
 
@@ -237,6 +443,7 @@ async fn put_blob_handler(
     .await?;
 
   let content_length = content_length.into_inner().into_inner();
+  check_blob_size(&state, &workspace_id, content_length as u64).await?;
   let content_type = content_type.into_inner().to_string();
   let content = {
     let mut payload_reader = payload_to_async_read(payload);
@@ -277,6 +484,9 @@ async fn put_blob_handler(
     content
   };
 
+  let allowed_content_types = get_allowed_content_types(&state.pg_pool, &workspace_id).await?;
+  let content_type = resolve_blob_content_type(&content_type, &content, &allowed_content_types)?;
+
   event!(
     tracing::Level::TRACE,
     "start put blob. workspace_id: {}, file_id: {}, content_length: {}",
@@ -285,11 +495,14 @@ async fn put_blob_handler(
     content_length
   );
 
-  let file_size = content.len();
-  let file_stream = ByteStream::from(content);
   state
     .bucket_storage
-    .put_blob_with_content_type(path, file_stream, content_type, file_size)
+    .put_blob_content(
+      path,
+      content,
+      content_type,
+      BlobAttributes::uploaded_by(uid),
+    )
     .await
     .map_err(AppResponseError::from)?;
 
@@ -322,10 +535,11 @@ async fn delete_blob_handler(
 async fn get_blob_v1_handler(
   state: Data<AppState>,
   path: web::Path<BlobPathV1>,
+  query: web::Query<BlobVariantQuery>,
   req: HttpRequest,
 ) -> Result<HttpResponse<BoxBody>> {
   let path = path.into_inner();
-  get_blob_by_object_key(state, &path, req).await
+  get_blob_by_object_key(state, &path, query.size, req).await
 }
 
 #[instrument(level = "debug", skip(state), err)]
@@ -350,9 +564,44 @@ async fn delete_blob_v1_handler(
   Ok(AppResponse::Ok().into())
 }
 
+/// Deletes the given blobs of the parent dir, such as the images of a document, in a single
+/// request. Returns the file ids of the blobs which were deleted.
+#[instrument(level = "debug", skip(state, payload), err)]
+async fn delete_blobs_v1_handler(
+  user_uuid: UserUuid,
+  state: Data<AppState>,
+  path: web::Path<BlobPathV2>,
+  payload: Json<BlobFileIds>,
+) -> Result<JsonAppResponse<BlobFileIds>> {
+  let path = path.into_inner();
+  let file_ids = payload.into_inner().file_ids;
+  if file_ids.len() > MAX_DELETE_BLOBS {
+    return Err(
+      AppError::InvalidRequest(format!(
+        "Cannot delete more than {} blobs at once",
+        MAX_DELETE_BLOBS
+      ))
+      .into(),
+    );
+  }
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &path.workspace_id.to_string(), Action::Write)
+    .await?;
+  let file_ids = state
+    .bucket_storage
+    .delete_blobs_bulk(&path.workspace_id, &path.parent_dir, &file_ids)
+    .await
+    .map_err(AppResponseError::from)?;
+  Ok(AppResponse::Ok().with_data(BlobFileIds { file_ids }).into())
+}
+
+/// Serve the blob, or its `variant` when one is requested and it was generated.
 async fn get_blob_by_object_key(
   state: Data<AppState>,
   key: &impl BlobKey,
+  variant: Option<BlobVariant>,
   req: HttpRequest,
 ) -> Result<HttpResponse<BoxBody>> {
   // Get the metadata
@@ -377,6 +626,36 @@ async fn get_blob_by_object_key(
     AFBlobStatus::Ok => {},
   };
 
+  if let Some(variant) = variant {
+    match state
+      .bucket_storage
+      .get_blob_variant(key.workspace_id(), &key.blob_metadata_key(), variant)
+      .await
+    {
+      Ok(Some((row, blob))) => {
+        return Ok(blob_variant_response(&req, &metadata, variant, row, blob));
+      },
+      Ok(None) => {},
+      // the blob is served instead
+      Err(err) => warn!(
+        "failed to get the {} variant of blob {}: {}",
+        variant.as_str(),
+        key.blob_metadata_key(),
+        err
+      ),
+    }
+  }
+
+  let etag = blob_etag(&metadata);
+  let last_modified = metadata.modified_at.to_rfc2822();
+  if if_none_match(&req, &etag) {
+    return Ok(
+      HttpResponse::NotModified()
+        .append_header((ETAG, etag))
+        .finish(),
+    );
+  }
+
   // Check if the file is modified since the last time
   if let Some(modified_since) = req
     .headers()
@@ -389,17 +668,76 @@ async fn get_blob_by_object_key(
     }
   }
 
-  trace!("Get blob data from bucket storage: {:?}", key.object_key());
-  let blob_result = state.bucket_storage.get_blob(key).await;
+  let blob_len = metadata.file_size.max(0) as u64;
+  let range = match requested_range(&req, &etag, &last_modified) {
+    None => None,
+    Some(range) => match parse_byte_range(range, blob_len) {
+      Ok(range) => range,
+      Err(()) => {
+        return Ok(
+          HttpResponse::RangeNotSatisfiable()
+            .append_header((CONTENT_RANGE, format!("bytes */{}", blob_len)))
+            .append_header((ACCEPT_RANGES, "bytes"))
+            .finish(),
+        );
+      },
+    },
+  };
+
+  trace!(
+    "Get blob data from bucket storage: {:?}, range: {:?}",
+    key.object_key(),
+    range
+  );
+  let blob_result = match range {
+    Some((start, end)) => {
+      state
+        .bucket_storage
+        .get_blob_range_with_metadata(key, &metadata, start, end)
+        .await
+    },
+    None => {
+      state
+        .bucket_storage
+        .get_blob_with_metadata(key, &metadata)
+        .await
+    },
+  };
   match blob_result {
     Ok(blob) => {
-      let response = HttpResponse::Ok()
-          .append_header((ETAG, key.e_tag()))
-          .append_header((CONTENT_TYPE, metadata.file_type))
-          .append_header((LAST_MODIFIED, metadata.modified_at.to_rfc2822()))
-          .append_header((CONTENT_LENGTH, blob.len()))
-          .append_header((CACHE_CONTROL, "public, immutable, max-age=31536000"))// 31536000 seconds = 1 year
-          .body(blob);
+      record_blob_access(
+        &state.redis_connection_manager,
+        key.workspace_id(),
+        &key.blob_metadata_key(),
+      )
+      .await;
+      let mut response = match range {
+        Some((start, end)) => {
+          let mut response = HttpResponse::PartialContent();
+          response.append_header((
+            CONTENT_RANGE,
+            format!("bytes {}-{}/{}", start, end, blob_len),
+          ));
+          response
+        },
+        None => HttpResponse::Ok(),
+      };
+      let response = response
+        .append_header((ETAG, etag))
+        .append_header((CONTENT_TYPE, metadata.file_type))
+        .append_header((X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .append_header((LAST_MODIFIED, last_modified))
+        .append_header((CONTENT_LENGTH, blob.len()))
+        .append_header((ACCEPT_RANGES, "bytes"))
+        .append_header((
+          CACHE_CONTROL,
+          if variant.is_some() {
+            VARIANT_FALLBACK_CACHE_CONTROL
+          } else {
+            BLOB_CACHE_CONTROL
+          },
+        ))
+        .body(blob);
 
       Ok(response)
     },
@@ -413,14 +751,102 @@ async fn get_blob_by_object_key(
   }
 }
 
+/// The entity tag of a blob: the hash of its content, or its file id and modification time for
+/// the blobs whose content wasn't hashed.
+fn blob_etag(metadata: &AFBlobMetadataRow) -> String {
+  match &metadata.content_hash {
+    Some(content_hash) => format!("\"{}\"", content_hash),
+    None => format!(
+      "\"{}-{}\"",
+      metadata.file_id,
+      metadata.modified_at.timestamp_millis()
+    ),
+  }
+}
+
+/// Serve a variant of the blob, as a whole: the variants are small enough not to be downloaded
+/// in ranges. Its entity tag is the one of the blob suffixed with the variant.
+fn blob_variant_response(
+  req: &HttpRequest,
+  metadata: &AFBlobMetadataRow,
+  variant: BlobVariant,
+  row: AFBlobVariantRow,
+  blob: Vec<u8>,
+) -> HttpResponse<BoxBody> {
+  let etag = format!(
+    "\"{}-{}\"",
+    blob_etag(metadata).trim_matches('"'),
+    variant.as_str()
+  );
+  if if_none_match(req, &etag) {
+    return HttpResponse::NotModified()
+      .append_header((ETAG, etag))
+      .finish();
+  }
+  HttpResponse::Ok()
+    .append_header((ETAG, etag))
+    .append_header((CONTENT_TYPE, row.file_type))
+    .append_header((X_CONTENT_TYPE_OPTIONS, "nosniff"))
+    .append_header((LAST_MODIFIED, metadata.modified_at.to_rfc2822()))
+    .append_header((CONTENT_LENGTH, blob.len()))
+    .append_header((CACHE_CONTROL, BLOB_CACHE_CONTROL))
+    .body(blob)
+}
+
+/// The `Range` header of the request, ignored when an `If-Range` header doesn't match the current
+/// version of the blob: the client then gets the whole content again.
+fn requested_range<'a>(req: &'a HttpRequest, etag: &str, last_modified: &str) -> Option<&'a str> {
+  let range = req.headers().get(RANGE)?.to_str().ok()?;
+  match req.headers().get(IF_RANGE).and_then(|h| h.to_str().ok()) {
+    Some(if_range) if if_range.trim() != etag && if_range.trim() != last_modified => None,
+    _ => Some(range),
+  }
+}
+
+/// Parse a `Range` header into the first and last byte, both included, of a blob of `len` bytes.
+/// Returns `None` for the range units other than bytes, which are ignored, and an error for the
+/// ranges which can't be satisfied. Only a single range is supported, such as `bytes=0-1023`,
+/// `bytes=1024-` or the suffix range `bytes=-1024` of the last 1024 bytes.
+fn parse_byte_range(range: &str, len: u64) -> Result<Option<(u64, u64)>, ()> {
+  let Some(spec) = range.trim().strip_prefix("bytes=") else {
+    return Ok(None);
+  };
+  if spec.contains(',') {
+    return Err(());
+  }
+  let (start, end) = spec.trim().split_once('-').ok_or(())?;
+  let (start, end) = (start.trim(), end.trim());
+  if len == 0 {
+    return Err(());
+  }
+  if start.is_empty() {
+    let suffix_len = end.parse::<u64>().map_err(|_| ())?;
+    if suffix_len == 0 {
+      return Err(());
+    }
+    return Ok(Some((len.saturating_sub(suffix_len), len - 1)));
+  }
+  let start = start.parse::<u64>().map_err(|_| ())?;
+  let end = if end.is_empty() {
+    len - 1
+  } else {
+    end.parse::<u64>().map_err(|_| ())?.min(len - 1)
+  };
+  if start >= len || start > end {
+    return Err(());
+  }
+  Ok(Some((start, end)))
+}
+
 #[instrument(level = "debug", skip(state), err)]
 async fn get_blob_handler(
   state: Data<AppState>,
   path: web::Path<BlobPathV0>,
+  query: web::Query<BlobVariantQuery>,
   req: HttpRequest,
 ) -> Result<HttpResponse<BoxBody>> {
   let blob_path = path.into_inner();
-  get_blob_by_object_key(state, &blob_path, req).await
+  get_blob_by_object_key(state, &blob_path, query.size, req).await
 }
 
 #[instrument(level = "debug", skip(state), err)]
@@ -471,44 +897,206 @@ async fn get_blob_metadata_v1_handler(
   Ok(Json(AppResponse::Ok().with_data(metadata)))
 }
 
+#[instrument(level = "debug", skip(state), err)]
+async fn list_blob_versions_v1_handler(
+  user_uuid: UserUuid,
+  state: Data<AppState>,
+  path: web::Path<BlobPathV1>,
+) -> Result<JsonAppResponse<RepeatedBlobVersion>> {
+  let path = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &path.workspace_id.to_string(), Action::Read)
+    .await?;
+
+  let versions = state
+    .bucket_storage
+    .list_blob_versions(&path)
+    .await
+    .map_err(AppResponseError::from)?
+    .into_iter()
+    .map(|row| BlobVersion {
+      version: row.version,
+      file_type: row.file_type,
+      file_size: row.file_size,
+      created_at: row.created_at,
+    })
+    .collect();
+  Ok(
+    AppResponse::Ok()
+      .with_data(RepeatedBlobVersion(versions))
+      .into(),
+  )
+}
+
+#[derive(Deserialize, Debug)]
+struct BlobVersionPath {
+  workspace_id: Uuid,
+  parent_dir: String,
+  file_id: String,
+  version: i64,
+}
+
+#[instrument(level = "debug", skip(state), err)]
+async fn restore_blob_version_v1_handler(
+  user_uuid: UserUuid,
+  state: Data<AppState>,
+  path: web::Path<BlobVersionPath>,
+) -> Result<JsonAppResponse<()>> {
+  let path = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &path.workspace_id.to_string(), Action::Write)
+    .await?;
+
+  let key = BlobPathV1 {
+    workspace_id: path.workspace_id,
+    parent_dir: path.parent_dir,
+    file_id: path.file_id,
+  };
+  state
+    .bucket_storage
+    .restore_blob_version(&key, path.version, Some(uid))
+    .await
+    .map_err(AppResponseError::from)?;
+  Ok(AppResponse::Ok().into())
+}
+
 #[instrument(level = "debug", skip(state), err)]
 async fn get_workspace_usage_handler(
   state: Data<AppState>,
   workspace_id: web::Path<Uuid>,
 ) -> Result<JsonAppResponse<WorkspaceSpaceUsage>> {
-  let current = get_workspace_usage_size(&state.pg_pool, &workspace_id)
+  let mut txn = begin_with_statement_timeout(
+    &state.pg_pool,
+    state.config.db_settings.usage_statement_timeout,
+  )
+  .await
+  .map_err(AppResponseError::from)?;
+  let current = get_workspace_usage_size_cached(
+    &state.pg_pool,
+    &state.redis_connection_manager,
+    &workspace_id,
+  )
+  .await
+  .map_err(AppResponseError::from)?;
+  let breakdown = get_workspace_usage_breakdown(txn.deref_mut(), &workspace_id)
     .await
     .map_err(AppResponseError::from)?;
+  let by_member = get_blob_usage_by_member(txn.deref_mut(), &workspace_id)
+    .await
+    .map_err(AppResponseError::from)?;
+  txn.commit().await.map_err(AppResponseError::from)?;
   let usage = WorkspaceSpaceUsage {
     consumed_capacity: current,
+    breakdown,
+    by_member,
   };
   Ok(AppResponse::Ok().with_data(usage).into())
 }
 
-// TODO(nathan): implement pagination
 #[instrument(level = "debug", skip(state), err)]
 async fn get_all_workspace_blob_metadata_handler(
   state: Data<AppState>,
   workspace_id: web::Path<Uuid>,
-) -> Result<JsonAppResponse<RepeatedBlobMetaData>> {
-  let metas = get_all_workspace_blob_metadata(&state.pg_pool, &workspace_id)
-    .await
-    .map_err(AppResponseError::from)?
-    .into_iter()
-    .map(|meta| BlobMetadata {
-      workspace_id: meta.workspace_id,
-      file_id: meta.file_id,
-      file_type: meta.file_type,
-      file_size: meta.file_size,
-      modified_at: meta.modified_at,
-    })
-    .collect::<Vec<_>>();
+  query: web::Query<PageQuery>,
+  req: HttpRequest,
+) -> Result<Either<JsonAppResponse<Page<BlobMetadata>>, JsonAppResponse<BlobMetadataPage>>> {
+  let query = query.into_inner();
+  let limit = query
+    .limit
+    .unwrap_or(MAX_BLOB_METADATA_PAGE_SIZE)
+    .clamp(1, MAX_BLOB_METADATA_PAGE_SIZE);
+  // the `(modified_at, file_id)` of the last blob of the previous page
+  let after = query
+    .cursor
+    .as_ref()
+    .map(|cursor| cursor.decode::<(DateTime<Utc>, String)>(BLOB_CURSOR_KIND))
+    .transpose()?;
+  let rows = get_workspace_blob_metadata_page(
+    &state.pg_pool,
+    &workspace_id,
+    after
+      .as_ref()
+      .map(|(modified_at, file_id)| (*modified_at, file_id.as_str())),
+    limit as i64 + 1,
+  )
+  .await
+  .map_err(AppResponseError::from)?;
+  let page = Page::from_items(rows, limit as usize, |meta| {
+    Cursor::encode(BLOB_CURSOR_KIND, &(meta.modified_at, &meta.file_id))
+  })
+  .map(|meta| BlobMetadata {
+    workspace_id: meta.workspace_id,
+    file_id: meta.file_id,
+    file_type: meta.file_type,
+    file_size: meta.file_size,
+    modified_at: meta.modified_at,
+  });
+  if accepts_page_response(req.headers()) {
+    return Ok(Either::Left(AppResponse::Ok().with_data(page).into()));
+  }
+  let legacy = BlobMetadataPage {
+    blobs: page.items,
+    next_cursor: page.next_cursor.map(|cursor| cursor.to_string()),
+  };
+  Ok(Either::Right(AppResponse::Ok().with_data(legacy).into()))
+}
+
+/// Streams the metadata of every blob of the workspace, for the audits of its files by the owners.
+/// Served as newline-delimited JSON, or as CSV when the `Accept` header asks for `text/csv`.
+#[instrument(skip_all, err)]
+async fn export_workspace_blob_metadata_handler(
+  user_uuid: UserUuid,
+  state: Data<AppState>,
+  workspace_id: web::Path<Uuid>,
+  req: HttpRequest,
+) -> Result<HttpResponse> {
+  let workspace_id = workspace_id.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  let format = BlobMetadataExportFormat::from_accept(
+    req
+      .headers()
+      .get(ACCEPT)
+      .and_then(|value| value.to_str().ok()),
+  );
+  let lines =
+    export_workspace_blob_metadata(state.pg_pool.clone(), workspace_id, uid, format).await?;
+  let extension = match format {
+    BlobMetadataExportFormat::Ndjson => "ndjson",
+    BlobMetadataExportFormat::Csv => "csv",
+  };
   Ok(
-    AppResponse::Ok()
-      .with_data(RepeatedBlobMetaData(metas))
-      .into(),
+    HttpResponse::Ok()
+      .content_type(format.content_type())
+      .insert_header((
+        CONTENT_DISPOSITION,
+        format!(
+          "attachment; filename=\"workspace_blobs_{}.{}\"",
+          workspace_id, extension
+        ),
+      ))
+      .streaming(lines),
   )
 }
+
+/// Rejects a file larger than the plan of the workspace allows, before any of its bytes are read.
+async fn check_blob_size(state: &AppState, workspace_id: &Uuid, size: u64) -> Result<(), AppError> {
+  if let Some(limit) = max_blob_size_for_workspace(&state.pg_pool, workspace_id).await? {
+    let limit = limit.max(0) as u64;
+    if size > limit {
+      return Err(AppError::FileTooLarge { size, limit });
+    }
+  }
+  Ok(())
+}
+
 fn payload_to_async_read(payload: Payload) -> Pin<Box<dyn AsyncRead>> {
   let mapped =
     payload.map(|chunk| chunk.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e)));
@@ -521,6 +1109,7 @@ async fn put_blob_handler_v1(
   user_uuid: UserUuid,
   state: Data<AppState>,
   path: web::Path<BlobPathV2>,
+  query: web::Query<PutBlobQuery>,
   content_type: web::Header<ContentType>,
   content_length: web::Header<ContentLength>,
   payload: Payload,
@@ -531,8 +1120,16 @@ async fn put_blob_handler_v1(
     .workspace_access_control
     .enforce_action(&uid, &path.workspace_id.to_string(), Action::Write)
     .await?;
+  if let Some(expires_at) = query.expires_at {
+    if expires_at <= Utc::now() {
+      return Err(
+        AppError::InvalidRequest(format!("expires_at {} is in the past", expires_at)).into(),
+      );
+    }
+  }
 
   let content_length = content_length.into_inner().into_inner();
+  check_blob_size(&state, &path.workspace_id, content_length as u64).await?;
   let content_type = content_type.into_inner().to_string();
 
   let mut content = Vec::with_capacity(content_length);
@@ -556,6 +1153,9 @@ async fn put_blob_handler_v1(
     offset += len;
   }
 
+  let allowed_content_types = get_allowed_content_types(&state.pg_pool, &path.workspace_id).await?;
+  let content_type = resolve_blob_content_type(&content_type, &content, &allowed_content_types)?;
+
   let file_id = FileId::from_bytes(&content, "".to_string());
   let resp_data = PutFileResponse {
     file_id: file_id.clone(),
@@ -568,14 +1168,13 @@ async fn put_blob_handler_v1(
     content_length
   );
 
-  let file_stream = ByteStream::from(content);
   state
     .bucket_storage
-    .put_blob_with_content_type(
+    .put_blob_content(
       BlobPathV1::from((path, file_id)),
-      file_stream,
+      content,
       content_type,
-      content_length,
+      BlobAttributes::uploaded_by(uid).with_expires_at(query.expires_at),
     )
     .await
     .map_err(AppResponseError::from)?;
@@ -608,7 +1207,7 @@ impl BlobKey for BlobPathV0 {
 }
 
 /// Use [BlobPathV1] when put/get object by multiple upload parts
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct BlobPathV1 {
   pub workspace_id: Uuid,
   pub parent_dir: String,
@@ -648,3 +1247,34 @@ impl From<(BlobPathV2, String)> for BlobPathV1 {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_byte_range_test() {
+    assert_eq!(parse_byte_range("bytes=0-9", 100), Ok(Some((0, 9))));
+    assert_eq!(parse_byte_range("bytes=90-", 100), Ok(Some((90, 99))));
+    // the end is clamped to the last byte
+    assert_eq!(parse_byte_range("bytes=90-200", 100), Ok(Some((90, 99))));
+    assert_eq!(parse_byte_range("bytes=-10", 100), Ok(Some((90, 99))));
+    assert_eq!(parse_byte_range("bytes=-1024", 100), Ok(Some((0, 99))));
+    assert_eq!(parse_byte_range("items=0-9", 100), Ok(None));
+  }
+
+  #[test]
+  fn unsatisfiable_byte_range_test() {
+    for range in [
+      "bytes=100-",
+      "bytes=10-5",
+      "bytes=-0",
+      "bytes=0-9,20-29",
+      "bytes=abc",
+      "bytes=-",
+    ] {
+      assert_eq!(parse_byte_range(range, 100), Err(()), "{}", range);
+    }
+    assert_eq!(parse_byte_range("bytes=0-", 0), Err(()));
+  }
+}
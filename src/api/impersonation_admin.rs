@@ -0,0 +1,104 @@
+use actix_web::web::{Data, Json};
+use actix_web::{web, Scope};
+use authentication::jwt::Authorization;
+use shared_entity::dto::impersonation_dto::{
+  CreateImpersonationParams, GrantImpersonationWriteParams, Impersonation, ImpersonationRequests,
+  ImpersonationToken, Impersonations,
+};
+use shared_entity::response::{AppResponse, JsonAppResponse};
+use uuid::Uuid;
+
+use crate::biz::moderation::check_admin;
+use crate::biz::user::impersonation::{
+  create_impersonation, grant_impersonation_write, list_active_impersonations,
+  list_impersonation_requests, revoke_impersonation,
+};
+use crate::state::AppState;
+
+/// Impersonation of the users by the admins of the instance, for support to see exactly what a
+/// user sees. The impersonations are read-only unless writes are granted, and every request made
+/// under one is recorded.
+pub fn impersonation_admin_scope() -> Scope {
+  web::scope("/api/admin/impersonations")
+    .service(
+      web::resource("")
+        .route(web::get().to(list_impersonations_handler))
+        .route(web::post().to(create_impersonation_handler)),
+    )
+    .service(
+      web::resource("/{impersonation_id}").route(web::delete().to(revoke_impersonation_handler)),
+    )
+    .service(
+      web::resource("/{impersonation_id}/write")
+        .route(web::post().to(grant_impersonation_write_handler)),
+    )
+    .service(
+      web::resource("/{impersonation_id}/requests")
+        .route(web::get().to(list_impersonation_requests_handler)),
+    )
+}
+
+async fn create_impersonation_handler(
+  auth: Authorization,
+  payload: Json<CreateImpersonationParams>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<ImpersonationToken>> {
+  check_admin(&auth)?;
+  let admin_uid = state.user_cache.get_user_uid(&auth.uuid()?).await?;
+  let token = create_impersonation(
+    &state.pg_pool,
+    &state.config.gotrue.jwt_secret,
+    admin_uid,
+    payload.into_inner(),
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(token).into())
+}
+
+async fn list_impersonations_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<Impersonations>> {
+  check_admin(&auth)?;
+  let impersonations = list_active_impersonations(&state.pg_pool).await?;
+  Ok(AppResponse::Ok().with_data(impersonations).into())
+}
+
+async fn revoke_impersonation_handler(
+  auth: Authorization,
+  path: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<()>> {
+  check_admin(&auth)?;
+  let admin_uid = state.user_cache.get_user_uid(&auth.uuid()?).await?;
+  revoke_impersonation(&state.pg_pool, admin_uid, &path.into_inner()).await?;
+  Ok(AppResponse::Ok().into())
+}
+
+async fn grant_impersonation_write_handler(
+  auth: Authorization,
+  path: web::Path<Uuid>,
+  payload: Json<GrantImpersonationWriteParams>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<Impersonation>> {
+  check_admin(&auth)?;
+  let admin_uid = state.user_cache.get_user_uid(&auth.uuid()?).await?;
+  let impersonation = grant_impersonation_write(
+    &state.pg_pool,
+    admin_uid,
+    &path.into_inner(),
+    payload.into_inner(),
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(impersonation).into())
+}
+
+async fn list_impersonation_requests_handler(
+  auth: Authorization,
+  path: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<ImpersonationRequests>> {
+  check_admin(&auth)?;
+  let requests = list_impersonation_requests(&state.pg_pool, &path.into_inner()).await?;
+  Ok(AppResponse::Ok().with_data(requests).into())
+}
@@ -0,0 +1,151 @@
+use access_control::act::Action;
+use actix_multipart::Multipart;
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::web::{Data, Path};
+use actix_web::{web, HttpRequest, Scope};
+use app_error::AppError;
+use futures_util::StreamExt;
+use shared_entity::dto::inbound_email_dto::InboundEmailReceipt;
+use shared_entity::response::{AppResponse, JsonAppResponse};
+use tracing::{info, trace};
+
+use crate::api::util::realtime_user_for_web_request;
+use crate::api::ws::RealtimeServerAddr;
+use crate::biz::inbound_email::provider::{
+  inbound_email_provider, InboundEmailFile, InboundEmailForm,
+};
+use crate::biz::inbound_email::{
+  deliver_inbound_email, find_inbound_email_target, inbound_email_domain,
+};
+use crate::state::AppState;
+
+/// Text fields longer than this are truncated, the body of an email is simplified and truncated
+/// further when it's appended anyway.
+const MAX_TEXT_FIELD_SIZE: usize = 2 * 1024 * 1024;
+
+/// The webhooks the email providers forward the emails sent to the inbound addresses to. They are
+/// not authenticated by a user, the request is verified by the provider instead.
+pub fn inbound_email_scope() -> Scope {
+  web::scope("/api/inbound-email")
+    .service(web::resource("/{provider}").route(web::post().to(inbound_email_handler)))
+}
+
+#[tracing::instrument(skip_all, err)]
+async fn inbound_email_handler(
+  provider: Path<String>,
+  state: Data<AppState>,
+  server: Data<RealtimeServerAddr>,
+  payload: Multipart,
+  req: HttpRequest,
+) -> actix_web::Result<JsonAppResponse<InboundEmailReceipt>> {
+  let setting = &state.config.inbound_email;
+  let domain = inbound_email_domain(setting)?;
+  let provider = inbound_email_provider(&provider, setting)?;
+  let form = read_inbound_email_form(payload, setting.max_email_size).await?;
+  let authorization = req
+    .headers()
+    .get(AUTHORIZATION)
+    .and_then(|value| value.to_str().ok());
+  provider.verify(authorization, &form)?;
+  let email = provider.parse(form)?;
+
+  let target = find_inbound_email_target(&state.pg_pool, domain, &email.recipients).await?;
+  state
+    .collab_access_control
+    .enforce_action(
+      &target.workspace_id.to_string(),
+      &target.created_by,
+      &target.view_id.to_string(),
+      Action::Write,
+    )
+    .await?;
+  let user = realtime_user_for_web_request(req.headers(), target.created_by)?;
+  let receipt = deliver_inbound_email(
+    &state.metrics.appflowy_web_metrics,
+    server,
+    user,
+    &state.collab_access_control_storage,
+    &state.bucket_storage,
+    state.config.appflowy_web_url.as_deref(),
+    setting.max_email_size,
+    &target,
+    email,
+  )
+  .await?;
+  info!(
+    "appended an inbound email to view {} of workspace {}: {} blocks, {} notes",
+    target.view_id,
+    target.workspace_id,
+    receipt.appended_blocks,
+    receipt.notes.len()
+  );
+  Ok(AppResponse::Ok().with_data(receipt).into())
+}
+
+/// Reads the form posted by the provider. Once `max_email_size` bytes were read, the remaining
+/// attachments are skipped rather than rejecting the email.
+async fn read_inbound_email_form(
+  mut payload: Multipart,
+  max_email_size: usize,
+) -> Result<InboundEmailForm, AppError> {
+  let mut form = InboundEmailForm::default();
+  let mut total_size = 0;
+  while let Some(field) = payload.next().await {
+    let mut field =
+      field.map_err(|err| AppError::InvalidRequest(format!("Invalid multipart form: {}", err)))?;
+    let Some(disposition) = field.content_disposition() else {
+      continue;
+    };
+    let name = disposition.get_name().unwrap_or_default().to_string();
+    let file_name = disposition.get_filename().map(|name| name.to_string());
+    let content_type = field
+      .content_type()
+      .map(|mime| mime.to_string())
+      .unwrap_or_else(|| "application/octet-stream".to_string());
+
+    let max_size = match file_name {
+      Some(_) => max_email_size.saturating_sub(total_size),
+      None => MAX_TEXT_FIELD_SIZE,
+    };
+    let mut data = Vec::new();
+    let mut exceeded = false;
+    while let Some(chunk) = field.next().await {
+      let chunk = chunk
+        .map_err(|err| AppError::InvalidRequest(format!("Invalid multipart form: {}", err)))?;
+      if exceeded {
+        continue;
+      }
+      let remaining = max_size - data.len();
+      if chunk.len() > remaining {
+        data.extend_from_slice(&chunk[..remaining]);
+        exceeded = true;
+      } else {
+        data.extend_from_slice(&chunk);
+      }
+    }
+
+    match file_name {
+      Some(file_name) => {
+        if exceeded {
+          trace!("skip attachment {} of inbound email", file_name);
+          form.skipped_files.push(file_name);
+        } else {
+          total_size += data.len();
+          form.files.push(InboundEmailFile {
+            field_name: name,
+            file_name,
+            content_type,
+            data: data.into(),
+          });
+        }
+      },
+      None => {
+        form.truncated |= exceeded;
+        form
+          .fields
+          .insert(name, String::from_utf8_lossy(&data).into_owned());
+      },
+    }
+  }
+  Ok(form)
+}
@@ -1,13 +1,24 @@
 pub mod access_request;
 pub mod ai;
 pub mod chat;
+pub mod collab_admin;
 pub mod data_import;
+pub mod fault_injection;
+pub mod feature_flag_admin;
 pub mod file_storage;
+pub mod impersonation_admin;
+pub mod inbound_email;
 pub mod metrics;
+pub mod moderation;
+pub mod realtime_admin;
 pub mod search;
 pub mod server_info;
+pub mod session_admin;
 pub mod template;
+pub mod unfurl;
+pub mod usage_admin;
 pub mod user;
 pub mod util;
 pub mod workspace;
+pub mod workspace_provisioning;
 pub mod ws;
@@ -0,0 +1,79 @@
+use actix_web::web::{Data, Json, Query};
+use actix_web::{web, Scope};
+use authentication::jwt::Authorization;
+use shared_entity::dto::moderation_dto::{
+  ListPublishedViewReportsQuery, PublishNamespaceRisk, PublishedViewReport,
+  ResolvePublishedViewReportParams,
+};
+use shared_entity::response::{AppResponse, JsonAppResponse};
+use uuid::Uuid;
+
+use crate::biz::moderation::{
+  check_admin, get_publish_namespace_risk, lift_published_view_takedown,
+  list_published_view_reports, resolve_report,
+};
+use crate::state::AppState;
+
+/// Moderation of the published views, restricted to the admins of the instance.
+pub fn moderation_scope() -> Scope {
+  web::scope("/api/admin/moderation")
+    .service(web::resource("/report").route(web::get().to(list_reports_handler)))
+    .service(
+      web::resource("/report/{report_id}/resolve").route(web::post().to(resolve_report_handler)),
+    )
+    .service(
+      web::resource("/namespace/{publish_namespace}/risk")
+        .route(web::get().to(get_namespace_risk_handler)),
+    )
+    .service(web::resource("/takedown/{view_id}").route(web::delete().to(lift_takedown_handler)))
+}
+
+async fn list_reports_handler(
+  auth: Authorization,
+  query: Query<ListPublishedViewReportsQuery>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<Vec<PublishedViewReport>>> {
+  check_admin(&auth)?;
+  let reports = list_published_view_reports(&state.pg_pool, query.into_inner()).await?;
+  Ok(AppResponse::Ok().with_data(reports).into())
+}
+
+async fn resolve_report_handler(
+  auth: Authorization,
+  report_id: web::Path<Uuid>,
+  params: Json<ResolvePublishedViewReportParams>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<PublishedViewReport>> {
+  check_admin(&auth)?;
+  let uid = state.user_cache.get_user_uid(&auth.uuid()?).await?;
+  let report = resolve_report(
+    &state.pg_pool,
+    &state.mailer,
+    state.config.appflowy_web_url.as_deref(),
+    &report_id.into_inner(),
+    uid,
+    params.into_inner(),
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(report).into())
+}
+
+async fn get_namespace_risk_handler(
+  auth: Authorization,
+  publish_namespace: web::Path<String>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<PublishNamespaceRisk>> {
+  check_admin(&auth)?;
+  let risk = get_publish_namespace_risk(&state.pg_pool, &publish_namespace).await?;
+  Ok(AppResponse::Ok().with_data(risk).into())
+}
+
+async fn lift_takedown_handler(
+  auth: Authorization,
+  view_id: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<()>> {
+  check_admin(&auth)?;
+  lift_published_view_takedown(&state.pg_pool, &view_id).await?;
+  Ok(AppResponse::Ok().into())
+}
@@ -0,0 +1,58 @@
+use actix_web::web::{Data, Query};
+use actix_web::{web, Scope};
+use app_error::AppError;
+use authentication::jwt::Authorization;
+use chrono::{Days, Utc};
+use database::realtime_bandwidth::select_top_workspaces_by_realtime_bandwidth;
+use shared_entity::dto::realtime_dto::{TopWorkspacesByBandwidthQuery, WorkspaceBandwidth};
+use shared_entity::response::{AppResponse, JsonAppResponse};
+
+use crate::biz::moderation::check_admin;
+use crate::state::AppState;
+
+const DEFAULT_WINDOW_DAYS: u64 = 7;
+const DEFAULT_TOP_WORKSPACES: i64 = 20;
+const MAX_TOP_WORKSPACES: i64 = 100;
+
+/// Realtime statistics of the instance, restricted to its admins.
+pub fn realtime_admin_scope() -> Scope {
+  web::scope("/api/admin/realtime").service(
+    web::resource("/bandwidth/top-workspaces")
+      .route(web::get().to(top_workspaces_by_bandwidth_handler)),
+  )
+}
+
+async fn top_workspaces_by_bandwidth_handler(
+  auth: Authorization,
+  query: Query<TopWorkspacesByBandwidthQuery>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<Vec<WorkspaceBandwidth>>> {
+  check_admin(&auth)?;
+  let query = query.into_inner();
+  let to = query.to.unwrap_or_else(|| Utc::now().date_naive());
+  let from = query
+    .from
+    .or_else(|| to.checked_sub_days(Days::new(DEFAULT_WINDOW_DAYS - 1)))
+    .unwrap_or(to);
+  if from > to {
+    return Err(AppError::InvalidRequest("from must not be after to".to_string()).into());
+  }
+  let limit = query
+    .limit
+    .unwrap_or(DEFAULT_TOP_WORKSPACES)
+    .clamp(1, MAX_TOP_WORKSPACES);
+  let workspaces = select_top_workspaces_by_realtime_bandwidth(&state.pg_pool, from, to, limit)
+    .await?
+    .into_iter()
+    .map(|row| WorkspaceBandwidth {
+      workspace_id: row.workspace_id,
+      workspace_name: row.workspace_name,
+      bytes_in: row.bytes_in,
+      bytes_out: row.bytes_out,
+      updates_out: row.updates_out,
+      awareness_out: row.awareness_out,
+      control_out: row.control_out,
+    })
+    .collect();
+  Ok(AppResponse::Ok().with_data(workspaces).into())
+}
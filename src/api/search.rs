@@ -1,26 +1,34 @@
 use access_control::act::Action;
 use actix_web::web::{Data, Query};
-use actix_web::{web, Scope};
+use actix_web::{web, Either, HttpRequest, Scope};
 use uuid::Uuid;
 
 use authentication::jwt::Authorization;
+use shared_entity::dto::page_dto::Page;
 use shared_entity::dto::search_dto::{SearchDocumentRequest, SearchDocumentResponseItem};
 use shared_entity::response::{AppResponse, JsonAppResponse};
 
-use crate::biz::search::search_document;
+use crate::api::util::accepts_page_response;
+use crate::biz::search::{search_document, search_document_page};
 use crate::state::AppState;
 
 pub fn search_scope() -> Scope {
   web::scope("/api/search/{workspace_id}")
     .service(web::resource("").route(web::get().to(document_search)))
 }
-#[tracing::instrument(skip(state, auth, payload), err)]
+#[tracing::instrument(skip(state, auth, payload, req), err)]
 async fn document_search(
   auth: Authorization,
   path: web::Path<Uuid>,
   payload: Query<SearchDocumentRequest>,
   state: Data<AppState>,
-) -> actix_web::Result<JsonAppResponse<Vec<SearchDocumentResponseItem>>> {
+  req: HttpRequest,
+) -> actix_web::Result<
+  Either<
+    JsonAppResponse<Page<SearchDocumentResponseItem>>,
+    JsonAppResponse<Vec<SearchDocumentResponseItem>>,
+  >,
+> {
   let workspace_id = path.into_inner();
   let request = payload.into_inner();
   let user_uuid = auth.uuid()?;
@@ -30,15 +38,32 @@ async fn document_search(
     .enforce_action(&uid, &workspace_id.to_string(), Action::Read)
     .await?;
   let metrics = &*state.metrics.request_metrics;
+  if accepts_page_response(req.headers()) {
+    let page = search_document_page(
+      &state.pg_pool,
+      state.config.db_settings.search_statement_timeout,
+      &state.collab_access_control_storage,
+      &state.indexer_scheduler,
+      &state.feature_flags,
+      uid,
+      workspace_id,
+      request,
+      metrics,
+    )
+    .await?;
+    return Ok(Either::Left(AppResponse::Ok().with_data(page).into()));
+  }
   let resp = search_document(
     &state.pg_pool,
+    state.config.db_settings.search_statement_timeout,
     &state.collab_access_control_storage,
     &state.indexer_scheduler,
+    &state.feature_flags,
     uid,
     workspace_id,
     request,
     metrics,
   )
   .await?;
-  Ok(AppResponse::Ok().with_data(resp).into())
+  Ok(Either::Right(AppResponse::Ok().with_data(resp).into()))
 }
@@ -0,0 +1,63 @@
+use actix_web::web::Data;
+use actix_web::{web, Scope};
+use authentication::jwt::Authorization;
+use database::user_session::SessionRevocation;
+use shared_entity::dto::session_dto::{RevokedSessions, SessionRevocations, UserSessions};
+use shared_entity::response::{AppResponse, JsonAppResponse};
+use uuid::Uuid;
+
+use crate::biz::moderation::check_admin;
+use crate::biz::user::user_session::{
+  list_session_revocations, list_user_sessions, revoke_sessions,
+};
+use crate::state::AppState;
+
+/// Sessions of any user of the instance, restricted to its admins. Revoking every session of a
+/// user signs them out of all their devices, during an incident response for instance.
+pub fn session_admin_scope() -> Scope {
+  web::scope("/api/admin/users")
+    .service(
+      web::resource("/{user_uuid}/sessions")
+        .route(web::get().to(list_sessions_handler))
+        .route(web::delete().to(revoke_sessions_handler)),
+    )
+    .service(
+      web::resource("/{user_uuid}/sessions/revocations")
+        .route(web::get().to(list_session_revocations_handler)),
+    )
+}
+
+async fn list_sessions_handler(
+  auth: Authorization,
+  path: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<UserSessions>> {
+  check_admin(&auth)?;
+  let uid = state.user_cache.get_user_uid(&path.into_inner()).await?;
+  let sessions = list_user_sessions(&state.pg_pool, uid, None).await?;
+  Ok(AppResponse::Ok().with_data(sessions).into())
+}
+
+async fn revoke_sessions_handler(
+  auth: Authorization,
+  path: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<RevokedSessions>> {
+  check_admin(&auth)?;
+  let admin_uid = state.user_cache.get_user_uid(&auth.uuid()?).await?;
+  let uid = state.user_cache.get_user_uid(&path.into_inner()).await?;
+  let revoked =
+    revoke_sessions(&state.pg_pool, uid, SessionRevocation::All, admin_uid, true).await?;
+  Ok(AppResponse::Ok().with_data(revoked).into())
+}
+
+async fn list_session_revocations_handler(
+  auth: Authorization,
+  path: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<SessionRevocations>> {
+  check_admin(&auth)?;
+  let uid = state.user_cache.get_user_uid(&path.into_inner()).await?;
+  let revocations = list_session_revocations(&state.pg_pool, uid).await?;
+  Ok(AppResponse::Ok().with_data(revocations).into())
+}
@@ -0,0 +1,25 @@
+use actix_web::web::{Data, Query};
+use actix_web::{web, Scope};
+
+use authentication::jwt::Authorization;
+use shared_entity::dto::unfurl_dto::{UnfurlMetadata, UnfurlQuery};
+use shared_entity::response::{AppResponse, JsonAppResponse};
+
+use crate::biz::unfurl::unfurl_url;
+use crate::state::AppState;
+
+pub fn unfurl_scope() -> Scope {
+  web::scope("/api/unfurl").service(web::resource("").route(web::get().to(unfurl_handler)))
+}
+
+#[tracing::instrument(skip(state, auth), err)]
+async fn unfurl_handler(
+  auth: Authorization,
+  query: Query<UnfurlQuery>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<UnfurlMetadata>> {
+  let user_uuid = auth.uuid()?;
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let metadata = unfurl_url(&state.redis_connection_manager, uid, &query.url).await?;
+  Ok(AppResponse::Ok().with_data(metadata).into())
+}
@@ -0,0 +1,85 @@
+use actix_web::web::{Data, Json, Path, Query};
+use actix_web::{web, HttpResponse, Scope};
+use authentication::jwt::Authorization;
+use database::resource_usage::{invalidate_workspace_usage_size_cache, recompute_workspace_usage};
+use shared_entity::dto::billing_dto::{
+  RecomputedWorkspaceUsage, RollupWorkspaceUsageParams, WorkspaceUsageDaily,
+  WorkspaceUsageDailyQuery,
+};
+use shared_entity::response::{AppResponse, JsonAppResponse};
+use uuid::Uuid;
+
+use crate::biz::moderation::check_admin;
+use crate::biz::workspace::usage::{
+  export_workspace_usage_csv, get_workspace_usage_daily, rollup_workspace_usage,
+};
+use crate::state::AppState;
+
+/// The usage of the workspaces rolled up for billing, restricted to the admins of the instance.
+pub fn usage_admin_scope() -> Scope {
+  web::scope("/api/admin/usage")
+    .service(web::resource("/daily").route(web::get().to(get_workspace_usage_daily_handler)))
+    .service(
+      web::resource("/daily/export").route(web::get().to(export_workspace_usage_daily_handler)),
+    )
+    .service(
+      web::resource("/daily/rollup").route(web::post().to(rollup_workspace_usage_daily_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/recompute")
+        .route(web::post().to(recompute_workspace_usage_handler)),
+    )
+}
+
+async fn get_workspace_usage_daily_handler(
+  auth: Authorization,
+  query: Query<WorkspaceUsageDailyQuery>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<Vec<WorkspaceUsageDaily>>> {
+  check_admin(&auth)?;
+  let usage = get_workspace_usage_daily(&state.pg_pool, &query).await?;
+  Ok(AppResponse::Ok().with_data(usage).into())
+}
+
+async fn export_workspace_usage_daily_handler(
+  auth: Authorization,
+  query: Query<WorkspaceUsageDailyQuery>,
+  state: Data<AppState>,
+) -> actix_web::Result<HttpResponse> {
+  check_admin(&auth)?;
+  let query = query.into_inner();
+  let filename = format!("workspace_usage_{}_{}.csv", query.from, query.to);
+  let csv = export_workspace_usage_csv(state.pg_pool.clone(), query)?;
+  Ok(
+    HttpResponse::Ok()
+      .content_type("text/csv; charset=utf-8")
+      .insert_header((
+        actix_web::http::header::CONTENT_DISPOSITION,
+        format!("attachment; filename=\"{}\"", filename),
+      ))
+      .streaming(csv),
+  )
+}
+
+/// Rolls up the days again, replacing their rows.
+async fn rollup_workspace_usage_daily_handler(
+  auth: Authorization,
+  payload: Json<RollupWorkspaceUsageParams>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<()>> {
+  check_admin(&auth)?;
+  rollup_workspace_usage(&state.pg_pool, payload.from, payload.to).await?;
+  Ok(AppResponse::Ok().into())
+}
+
+/// Recomputes the usage counter of the workspace from its blobs, to fix a drift.
+async fn recompute_workspace_usage_handler(
+  auth: Authorization,
+  workspace_id: Path<Uuid>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<RecomputedWorkspaceUsage>> {
+  check_admin(&auth)?;
+  let usage = recompute_workspace_usage(&state.pg_pool, &workspace_id).await?;
+  invalidate_workspace_usage_size_cache(&state.redis_connection_manager, &workspace_id).await;
+  Ok(AppResponse::Ok().with_data(usage).into())
+}
@@ -1,15 +1,32 @@
+use crate::api::util::accepts_page_response;
 use crate::biz::user::user_delete::delete_user;
 use crate::biz::user::user_info::{get_profile, get_user_workspace_info, update_user};
+use crate::biz::user::user_notification::{
+  get_notification_settings, list_notification_subscriptions, list_user_notifications,
+  mark_notifications_read, set_notification_subscription, update_notification_settings,
+};
+use crate::biz::user::user_session::{list_user_sessions, revoke_sessions, session_id_from_claims};
 use crate::biz::user::user_verify::verify_token;
 use crate::state::AppState;
+use access_control::act::Action;
 use actix_web::web::{Data, Json};
 use actix_web::Result;
-use actix_web::{web, Scope};
+use actix_web::{web, Either, HttpRequest, Scope};
+use app_error::AppError;
 use authentication::jwt::{Authorization, UserUuid};
+use database::user_session::SessionRevocation;
 use database_entity::dto::{AFUserProfile, AFUserWorkspaceInfo};
 use shared_entity::dto::auth_dto::{DeleteUserQuery, SignInTokenResponse, UpdateUserParams};
+use shared_entity::dto::export_dto::{
+  ListNotificationSubscriptionsQuery, ListUserNotificationsQuery, MarkNotificationsReadParams,
+  NotificationSettings, NotificationSubscriptions, SetNotificationSubscriptionParams,
+  UserNotification, UserNotifications,
+};
+use shared_entity::dto::page_dto::Page;
+use shared_entity::dto::session_dto::{RevokedSessions, UserSessions};
 use shared_entity::response::AppResponseError;
 use shared_entity::response::{AppResponse, JsonAppResponse};
+use uuid::Uuid;
 
 pub fn user_scope() -> Scope {
   web::scope("/api/user")
@@ -17,6 +34,31 @@ pub fn user_scope() -> Scope {
     .service(web::resource("/update").route(web::post().to(update_user_handler)))
     .service(web::resource("/profile").route(web::get().to(get_user_profile_handler)))
     .service(web::resource("/workspace").route(web::get().to(get_user_workspace_info_handler)))
+    .service(web::resource("/notification").route(web::get().to(list_user_notifications_handler)))
+    .service(
+      web::resource("/notification/read").route(web::post().to(mark_notifications_read_handler)),
+    )
+    .service(
+      web::resource("/notification/subscription")
+        .route(web::get().to(list_notification_subscriptions_handler)),
+    )
+    .service(
+      web::resource("/notification/subscription/{object_id}")
+        .route(web::put().to(set_notification_subscription_handler)),
+    )
+    .service(
+      web::resource("/notification/setting")
+        .route(web::get().to(get_notification_settings_handler))
+        .route(web::put().to(update_notification_settings_handler)),
+    )
+    .service(
+      web::resource("/session")
+        .route(web::get().to(list_user_sessions_handler))
+        .route(web::delete().to(revoke_other_user_sessions_handler)),
+    )
+    .service(
+      web::resource("/session/{session_id}").route(web::delete().to(revoke_user_session_handler)),
+    )
     .service(web::resource("").route(web::delete().to(delete_user_handler)))
 }
 
@@ -53,6 +95,148 @@ async fn get_user_workspace_info_handler(
   Ok(AppResponse::Ok().with_data(info).into())
 }
 
+#[tracing::instrument(skip(state), err)]
+async fn list_user_notifications_handler(
+  uuid: UserUuid,
+  state: Data<AppState>,
+  query: web::Query<ListUserNotificationsQuery>,
+  req: HttpRequest,
+) -> Result<Either<JsonAppResponse<Page<UserNotification>>, JsonAppResponse<UserNotifications>>> {
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  let ListUserNotificationsQuery {
+    only_unread,
+    cursor,
+    limit,
+  } = query.into_inner();
+  let page =
+    list_user_notifications(&state.pg_pool, uid, only_unread, cursor.as_ref(), limit).await?;
+  if accepts_page_response(req.headers()) {
+    return Ok(Either::Left(AppResponse::Ok().with_data(page).into()));
+  }
+  let notifications = UserNotifications {
+    notifications: page.items,
+  };
+  Ok(Either::Right(
+    AppResponse::Ok().with_data(notifications).into(),
+  ))
+}
+
+#[tracing::instrument(skip(state, payload), err)]
+async fn mark_notifications_read_handler(
+  uuid: UserUuid,
+  state: Data<AppState>,
+  payload: Json<MarkNotificationsReadParams>,
+) -> Result<JsonAppResponse<()>> {
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  mark_notifications_read(&state.pg_pool, uid, &payload.notification_ids).await?;
+  Ok(AppResponse::Ok().into())
+}
+
+#[tracing::instrument(skip(state), err)]
+async fn list_notification_subscriptions_handler(
+  uuid: UserUuid,
+  state: Data<AppState>,
+  query: web::Query<ListNotificationSubscriptionsQuery>,
+) -> Result<JsonAppResponse<NotificationSubscriptions>> {
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  let subscriptions =
+    list_notification_subscriptions(&state.pg_pool, uid, &query.workspace_id).await?;
+  Ok(AppResponse::Ok().with_data(subscriptions).into())
+}
+
+#[tracing::instrument(skip(state, payload), err)]
+async fn set_notification_subscription_handler(
+  uuid: UserUuid,
+  state: Data<AppState>,
+  path: web::Path<Uuid>,
+  payload: Json<SetNotificationSubscriptionParams>,
+) -> Result<JsonAppResponse<()>> {
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  let object_id = path.into_inner();
+  let SetNotificationSubscriptionParams {
+    workspace_id,
+    level,
+  } = payload.into_inner();
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id.to_string(), Action::Read)
+    .await?;
+  set_notification_subscription(&state.pg_pool, uid, &workspace_id, &object_id, level).await?;
+  Ok(AppResponse::Ok().into())
+}
+
+#[tracing::instrument(skip(state), err)]
+async fn get_notification_settings_handler(
+  uuid: UserUuid,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<NotificationSettings>> {
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  let settings = get_notification_settings(&state.pg_pool, uid).await?;
+  Ok(AppResponse::Ok().with_data(settings).into())
+}
+
+#[tracing::instrument(skip(state, payload), err)]
+async fn update_notification_settings_handler(
+  uuid: UserUuid,
+  state: Data<AppState>,
+  payload: Json<NotificationSettings>,
+) -> Result<JsonAppResponse<()>> {
+  let uid = state.user_cache.get_user_uid(&uuid).await?;
+  update_notification_settings(&state.pg_pool, uid, &payload).await?;
+  Ok(AppResponse::Ok().into())
+}
+
+#[tracing::instrument(skip(state, auth), err)]
+async fn list_user_sessions_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<UserSessions>> {
+  let uid = state.user_cache.get_user_uid(&auth.uuid()?).await?;
+  let current_session_id = session_id_from_claims(&auth.claims);
+  let sessions = list_user_sessions(&state.pg_pool, uid, current_session_id.as_ref()).await?;
+  Ok(AppResponse::Ok().with_data(sessions).into())
+}
+
+#[tracing::instrument(skip(state, auth), err)]
+async fn revoke_user_session_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+  path: web::Path<Uuid>,
+) -> Result<JsonAppResponse<RevokedSessions>> {
+  let uid = state.user_cache.get_user_uid(&auth.uuid()?).await?;
+  let session_id = path.into_inner();
+  let revoked = revoke_sessions(
+    &state.pg_pool,
+    uid,
+    SessionRevocation::One(&session_id),
+    uid,
+    false,
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(revoked).into())
+}
+
+/// Signs out every device of the user but the one sending the request.
+#[tracing::instrument(skip(state, auth), err)]
+async fn revoke_other_user_sessions_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<RevokedSessions>> {
+  let uid = state.user_cache.get_user_uid(&auth.uuid()?).await?;
+  let current_session_id = session_id_from_claims(&auth.claims).ok_or_else(|| {
+    AppError::InvalidRequest("The access token doesn't belong to a session".to_string())
+  })?;
+  let revoked = revoke_sessions(
+    &state.pg_pool,
+    uid,
+    SessionRevocation::AllExcept(&current_session_id),
+    uid,
+    false,
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(revoked).into())
+}
+
 #[tracing::instrument(skip(state, auth, payload), err)]
 async fn update_user_handler(
   auth: Authorization,
@@ -1,5 +1,5 @@
 use crate::domain::compression::{CompressionType, X_COMPRESSION_BUFFER_SIZE, X_COMPRESSION_TYPE};
-use actix_http::header::HeaderMap;
+use actix_http::header::{HeaderMap, IF_NONE_MATCH};
 use actix_web::web::Payload;
 use app_error::AppError;
 
@@ -8,11 +8,13 @@ use async_trait::async_trait;
 use byteorder::{ByteOrder, LittleEndian};
 use chrono::Utc;
 use collab_rt_entity::user::RealtimeUser;
-use collab_rt_protocol::validate_encode_collab;
-use database_entity::dto::CollabParams;
+use database_entity::dto::{CollabMode, CollabParams};
+use database_entity::timestamp::X_API_VERSION;
+use shared_entity::dto::page_dto::PAGE_API_VERSION;
 use std::str::FromStr;
 use tokio_stream::StreamExt;
 use uuid::Uuid;
+use workspace_template::collab_registry::collab_spec;
 
 #[inline]
 pub fn compress_type_from_header_value(headers: &HeaderMap) -> Result<CompressionType, AppError> {
@@ -79,6 +81,22 @@ pub fn client_version_from_headers(headers: &HeaderMap) -> Result<&str, AppError
   )
 }
 
+/// The version of the API the client was built for, sent in [X_API_VERSION]. 0 for the clients
+/// which don't send it.
+pub fn api_version_from_headers(headers: &HeaderMap) -> u32 {
+  headers
+    .get(X_API_VERSION)
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.trim().parse::<u32>().ok())
+    .unwrap_or(0)
+}
+
+/// Whether the client takes the [shared_entity::dto::page_dto::Page] responses of the list
+/// endpoints, rather than the shape each of them used to return.
+pub fn accepts_page_response(headers: &HeaderMap) -> bool {
+  api_version_from_headers(headers) >= PAGE_API_VERSION
+}
+
 /// Retrieve device ID from headers
 pub fn device_id_from_headers(headers: &HeaderMap) -> Result<&str, AppError> {
   value_from_headers(
@@ -112,14 +130,25 @@ pub fn realtime_user_for_web_request(
 
 #[async_trait]
 pub trait CollabValidator {
-  async fn check_encode_collab(&self) -> Result<(), AppError>;
+  async fn check_encode_collab(&self, mode: CollabMode) -> Result<(), AppError>;
 }
 
 #[async_trait]
 impl CollabValidator for CollabParams {
-  async fn check_encode_collab(&self) -> Result<(), AppError> {
-    validate_encode_collab(&self.object_id, &self.encoded_collab_v1, &self.collab_type)
+  async fn check_encode_collab(&self, mode: CollabMode) -> Result<(), AppError> {
+    let spec = collab_spec(&self.collab_type, mode.is_opaque());
+    spec
+      .check_size(self.encoded_collab_v1.len())
+      .map_err(|_| AppError::CollabSizeExceeded {
+        limit: spec.max_encoded_len,
+        actual: self.encoded_collab_v1.len(),
+      })?;
+
+    let object_id = self.object_id.clone();
+    let encoded_collab_v1 = self.encoded_collab_v1.clone();
+    tokio::task::spawn_blocking(move || spec.validate_encoded(&object_id, &encoded_collab_v1))
       .await
+      .map_err(|err| AppError::Internal(err.into()))?
       .map_err(|err| AppError::NoRequiredData(err.to_string()))
   }
 }
@@ -219,6 +248,22 @@ pub(crate) fn ai_model_from_header(req: &HttpRequest) -> &str {
     .unwrap_or("Default")
 }
 
+/// Whether the `If-None-Match` header of the request matches the entity tag, quoted or not.
+pub(crate) fn if_none_match(req: &HttpRequest, etag: &str) -> bool {
+  let etag = etag.trim_matches('"');
+  req
+    .headers()
+    .get(IF_NONE_MATCH)
+    .and_then(|value| value.to_str().ok())
+    .map(|value| {
+      value.split(',').any(|tag| {
+        let tag = tag.trim();
+        tag == "*" || tag.trim_start_matches("W/").trim_matches('"') == etag
+      })
+    })
+    .unwrap_or(false)
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
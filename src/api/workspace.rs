@@ -1,37 +1,66 @@
-use crate::api::util::{client_version_from_headers, realtime_user_for_web_request, PayloadReader};
+use crate::api::util::{
+  accepts_page_response, client_version_from_headers, if_none_match, realtime_user_for_web_request,
+  PayloadReader,
+};
 use crate::api::util::{compress_type_from_header_value, device_id_from_headers};
 use crate::api::ws::RealtimeServerAddr;
 use crate::biz;
+use crate::biz::collab::batch_get::batch_get_collab_for_user;
+use crate::biz::collab::bulk_create::bulk_create_collab_for_user;
+use crate::biz::collab::field_conversion::{change_database_field_type, get_field_conversion_task};
 use crate::biz::collab::ops::{
   get_user_favorite_folder_views, get_user_recent_folder_views, get_user_trash_folder_views,
 };
+use crate::biz::collab::trash::{
+  list_deleted_collabs, purge_collab_from_trash, restore_collab_from_trash,
+};
+use crate::biz::data_import::LimitedPayload;
+use crate::biz::inbound_email::{
+  get_inbound_email_address, inbound_email_domain, remove_inbound_email_address,
+  rotate_inbound_email_address, set_inbound_email_address,
+};
+use crate::biz::maintenance::{run_workspace_maintenance_job, JobMode};
 use crate::biz::user::user_verify::verify_token;
 use crate::biz::workspace;
+use crate::biz::workspace::export::{create_workspace_export, get_workspace_export};
+use crate::biz::workspace::image::{upload_page_image, upload_workspace_icon, ImageKind};
+use crate::biz::workspace::member_export::export_workspace_members_csv;
+use crate::biz::workspace::merge::{create_workspace_merge, get_workspace_merge};
 use crate::biz::workspace::ops::{
   create_comment_on_published_view, create_reaction_on_comment, get_comments_on_published_view,
   get_reactions_on_published_view, remove_comment_on_published_view, remove_reaction_on_comment,
 };
 use crate::biz::workspace::page_view::{
   create_page, create_space, delete_all_pages_from_trash, delete_trash, get_page_view_collab,
-  move_page, move_page_to_trash, publish_page, restore_all_pages_from_trash,
-  restore_page_from_trash, unpublish_page, update_page, update_page_collab_data, update_space,
+  get_publish_revision, move_page, move_page_to_trash, publish_page, republish_page,
+  restore_all_pages_from_trash, restore_page_from_trash, unpublish_page, update_page,
+  update_page_collab_data, update_space,
 };
 use crate::biz::workspace::publish::get_workspace_default_publish_view_info_meta;
 use crate::biz::workspace::quick_note::{
   create_quick_note, delete_quick_note, list_quick_notes, update_quick_note,
 };
+use crate::biz::workspace::similar_page::{list_similar_pages, trash_similar_pages};
+use crate::biz::workspace::webhook::{
+  create_workspace_webhook, list_workspace_webhooks, remove_workspace_webhook,
+};
 use crate::domain::compression::{
   blocking_decompress, decompress, CompressionType, X_COMPRESSION_TYPE,
 };
 use crate::state::AppState;
 use access_control::act::Action;
+use actix_web::http::header::{ContentLength, CACHE_CONTROL, CONTENT_LOCATION, ETAG};
 use actix_web::web::{Bytes, Path, Payload};
 use actix_web::web::{Data, Json, PayloadConfig};
-use actix_web::{web, HttpResponse, ResponseError, Scope};
+use actix_web::{web, Either, HttpResponse, ResponseError, Scope};
 use actix_web::{HttpRequest, Result};
 use anyhow::{anyhow, Context};
 use app_error::{AppError, ErrorCode};
 use appflowy_collaborate::actix_ws::entities::{ClientHttpStreamMessage, ClientHttpUpdateMessage};
+use appflowy_collaborate::collab::recovery::DetectedOn;
+use appflowy_collaborate::collab::validator::check_collab_structure;
+use appflowy_collaborate::feature_flags::STRICT_COLLAB_VALIDATION;
+use appflowy_collaborate::load_shed::ShedTier;
 use authentication::jwt::{Authorization, OptionalUserUuid, UserUuid};
 use bytes::BytesMut;
 use chrono::{DateTime, Duration, Utc};
@@ -40,7 +69,6 @@ use collab::core::origin::CollabOrigin;
 use collab::entity::EncodedCollab;
 use collab::preclude::Collab;
 use collab_database::entity::FieldType;
-use collab_document::document::Document;
 use collab_entity::CollabType;
 use collab_folder::timestamp;
 use collab_rt_entity::collab_proto::{CollabDocStateParams, PayloadCompressionType};
@@ -49,6 +77,12 @@ use collab_rt_entity::user::RealtimeUser;
 use collab_rt_entity::RealtimeMessage;
 use collab_rt_protocol::collab_from_encode_collab;
 use database::collab::{CollabStorage, GetCollabOrigin};
+use database::collab_mode::select_opaque_collab_oids;
+use database::outbox::{insert_outbox_entry, OutboxTopic};
+use database::publish::{
+  select_published_collab_blob_at_revision, select_published_collab_etag,
+  select_published_collab_workspace_view_id,
+};
 use database::user::select_uid_from_email;
 use database_entity::dto::PublishCollabItem;
 use database_entity::dto::PublishInfo;
@@ -57,7 +91,22 @@ use indexer::scheduler::{UnindexedCollabTask, UnindexedData};
 use prost::Message as ProstMessage;
 use rayon::prelude::*;
 use sha2::{Digest, Sha256};
+use shared_entity::dto::export_dto::WorkspaceExportTask;
+use shared_entity::dto::file_dto::UploadImageResponse;
+use shared_entity::dto::inbound_email_dto::{InboundEmailAddress, SetInboundEmailParams};
+use shared_entity::dto::maintenance_dto::{
+  MaintenanceJob, MaintenanceJobReport, RunMaintenanceJobQuery,
+};
+use shared_entity::dto::merge_dto::{MergeWorkspaceParams, WorkspaceMergeTask};
+use shared_entity::dto::moderation_dto::ReportPublishedViewParams;
+use shared_entity::dto::page_dto::{self, PageQuery};
 use shared_entity::dto::publish_dto::DuplicatePublishedPageResponse;
+use shared_entity::dto::similar_page_dto::{
+  SimilarPageClusters, SimilarPagesQuery, TrashSimilarPagesParams, TrashedSimilarPages,
+};
+use shared_entity::dto::webhook_dto::{
+  CreateWorkspaceWebhookParams, CreatedWorkspaceWebhook, WorkspaceWebhook,
+};
 use shared_entity::dto::workspace_dto::*;
 use shared_entity::response::AppResponseError;
 use shared_entity::response::{AppResponse, JsonAppResponse};
@@ -69,6 +118,7 @@ use tokio_tungstenite::tungstenite::Message;
 use tracing::{error, event, instrument, trace};
 use uuid::Uuid;
 use validator::Validate;
+use workspace_template::collab_registry::collab_spec;
 
 pub const WORKSPACE_ID_PATH: &str = "workspace_id";
 pub const COLLAB_OBJECT_ID_PATH: &str = "object_id";
@@ -91,7 +141,13 @@ pub fn workspace_scope() -> Scope {
         .route(web::patch().to(patch_workspace_handler)),
     )
     .service(
-      web::resource("/{workspace_id}/invite").route(web::post().to(post_workspace_invite_handler)), // invite members to workspace
+      web::resource("/{workspace_id}/invite")
+        .route(web::post().to(post_workspace_invite_handler)) // invite members to workspace
+        .route(web::get().to(get_workspace_pending_invites_handler)), // show pending invites of workspace
+    )
+    .service(
+      web::resource("/{workspace_id}/invite/{invite_id}/extend")
+        .route(web::post().to(post_extend_workspace_invite_handler)),
     )
     .service(
       web::resource("/invite").route(web::get().to(get_workspace_invite_handler)), // show invites for user
@@ -109,6 +165,34 @@ pub fn workspace_scope() -> Scope {
         .route(web::get().to(get_workspace_settings_handler))
         .route(web::post().to(post_workspace_settings_handler)),
     )
+    .service(
+      web::resource("/{workspace_id}/maintenance/{job}")
+        .route(web::post().to(run_maintenance_job_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/maintenance/{job}/execute-last-dry-run")
+        .route(web::post().to(execute_last_maintenance_dry_run_handler)),
+    )
+    .service(web::resource("/{workspace_id}/icon").route(web::put().to(put_workspace_icon_handler)))
+    .service(
+      web::resource("/{workspace_id}/inbound-email")
+        .route(web::get().to(get_inbound_email_handler))
+        .route(web::put().to(put_inbound_email_handler))
+        .route(web::delete().to(delete_inbound_email_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/inbound-email/rotate")
+        .route(web::post().to(rotate_inbound_email_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/webhook")
+        .route(web::get().to(list_workspace_webhooks_handler))
+        .route(web::post().to(create_workspace_webhook_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/webhook/{webhook_id}")
+        .route(web::delete().to(delete_workspace_webhook_handler)),
+    )
     .service(web::resource("/{workspace_id}/open").route(web::put().to(open_workspace_handler)))
     .service(web::resource("/{workspace_id}/leave").route(web::post().to(leave_workspace_handler)))
     .service(
@@ -121,6 +205,21 @@ pub fn workspace_scope() -> Scope {
       web::resource("/{workspace_id}/member/user/{user_id}")
         .route(web::get().to(get_workspace_member_handler)),
     )
+    .service(
+      web::resource("/{workspace_id}/member/export")
+        .route(web::get().to(export_workspace_members_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/collab/batch")
+        .route(web::post().to(post_collab_batch_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/collab/bulk")
+        .app_data(
+          web::JsonConfig::default().limit(256 * 1024 * 1024), // 256 MB
+        )
+        .route(web::post().to(bulk_create_collab_handler)),
+    )
     .service(
       web::resource("/{workspace_id}/collab/{object_id}")
         .app_data(
@@ -135,6 +234,22 @@ pub fn workspace_scope() -> Scope {
       web::resource("/v1/{workspace_id}/collab/{object_id}")
         .route(web::get().to(v1_get_collab_handler)),
     )
+    .service(
+      web::resource("/v1/{workspace_id}/collab/{object_id}/diff")
+        .route(web::post().to(v1_collab_diff_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/document/{object_id}/outline")
+        .route(web::get().to(get_document_outline_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/document/{object_id}/find")
+        .route(web::get().to(find_in_document_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/document/{object_id}/snapshot/diff")
+        .route(web::get().to(diff_document_snapshots_handler)),
+    )
     .service(
       web::resource("/v1/{workspace_id}/collab/{object_id}/full-sync")
         .route(web::post().to(collab_full_sync_handler)),
@@ -143,6 +258,25 @@ pub fn workspace_scope() -> Scope {
       web::resource("/v1/{workspace_id}/collab/{object_id}/web-update")
         .route(web::post().to(post_web_update_handler)),
     )
+    .service(
+      web::resource("/{workspace_id}/collab/{object_id}/editing-lock")
+        .route(web::get().to(get_editing_lock_handler))
+        .route(web::post().to(acquire_editing_lock_handler))
+        .route(web::delete().to(release_editing_lock_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/collab/{object_id}/editing-lock/heartbeat")
+        .route(web::post().to(heartbeat_editing_lock_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/collab/{object_id}/presence")
+        .route(web::get().to(get_collab_presence_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/collab/{object_id}/mode")
+        .route(web::get().to(get_collab_mode_handler))
+        .route(web::put().to(put_collab_mode_handler)),
+    )
     .service(
       web::resource("/{workspace_id}/collab/{object_id}/embed-info")
         .route(web::get().to(get_collab_embed_info_handler)),
@@ -163,6 +297,14 @@ pub fn workspace_scope() -> Scope {
         .route(web::get().to(get_page_view_handler))
         .route(web::patch().to(update_page_view_handler)),
     )
+    .service(
+      web::resource("/{workspace_id}/page-view/{view_id}/icon")
+        .route(web::put().to(put_page_view_icon_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/page-view/{view_id}/cover")
+        .route(web::put().to(put_page_view_cover_handler)),
+    )
     .service(
       web::resource("/{workspace_id}/page-view/{view_id}/move")
         .route(web::post().to(move_page_handler)),
@@ -191,6 +333,14 @@ pub fn workspace_scope() -> Scope {
       web::resource("/{workspace_id}/page-view/{view_id}/unpublish")
         .route(web::post().to(unpublish_page_handler)),
     )
+    .service(
+      web::resource("/{workspace_id}/page-view/{view_id}/republish")
+        .route(web::post().to(republish_page_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/page-view/{view_id}/publish-revision")
+        .route(web::get().to(get_publish_revision_handler)),
+    )
     .service(
       web::resource("/{workspace_id}/batch/collab")
         .route(web::post().to(batch_create_collab_handler)),
@@ -198,6 +348,13 @@ pub fn workspace_scope() -> Scope {
     .service(
       web::resource("/{workspace_id}/usage").route(web::get().to(get_workspace_usage_handler)),
     )
+    .service(
+      web::resource("/{workspace_id}/stats").route(web::get().to(get_workspace_stats_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/stats/recompute")
+        .route(web::post().to(recompute_workspace_stats_handler)),
+    )
     .service(
       web::resource("/{workspace_id}/{object_id}/snapshot")
         .route(web::get().to(get_collab_snapshot_handler))
@@ -207,6 +364,14 @@ pub fn workspace_scope() -> Scope {
       web::resource("/{workspace_id}/{object_id}/snapshot/list")
         .route(web::get().to(get_all_collab_snapshot_list_handler)),
     )
+    .service(
+      web::resource("/{workspace_id}/{object_id}/snapshot/history")
+        .route(web::get().to(get_collab_version_history_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/{object_id}/snapshot/{snapshot_id}/restore")
+        .route(web::post().to(restore_collab_snapshot_handler)),
+    )
     .service(
       web::resource("/published/{publish_namespace}")
         .route(web::get().to(get_default_published_collab_info_meta_handler)),
@@ -219,6 +384,18 @@ pub fn workspace_scope() -> Scope {
       web::resource("/published/{publish_namespace}/{publish_name}/blob")
         .route(web::get().to(get_published_collab_blob_handler)),
     )
+    .service(
+      web::resource("/published/{publish_namespace}/{publish_name}/blob/{revision}")
+        .route(web::get().to(get_published_collab_blob_at_revision_handler)),
+    )
+    .service(
+      web::resource("/published/{publish_namespace}/{publish_name}/outline/{revision}")
+        .route(web::get().to(get_published_document_outline_handler)),
+    )
+    .service(
+      web::resource("/published/{publish_namespace}/{publish_name}/report")
+        .route(web::post().to(report_published_collab_handler)),
+    )
     .service(
       web::resource("{workspace_id}/published-duplicate")
         .route(web::post().to(post_published_duplicate_handler)),
@@ -273,10 +450,30 @@ pub fn workspace_scope() -> Scope {
       web::resource("/{workspace_id}/favorite").route(web::get().to(get_favorite_views_handler)),
     )
     .service(web::resource("/{workspace_id}/trash").route(web::get().to(get_trash_views_handler)))
+    .service(
+      web::resource("/{workspace_id}/trash/collabs")
+        .route(web::get().to(list_deleted_collabs_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/trash/collabs/{object_id}")
+        .route(web::delete().to(purge_deleted_collab_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/trash/collabs/{object_id}/restore")
+        .route(web::post().to(restore_deleted_collab_handler)),
+    )
     .service(
       web::resource("/{workspace_id}/trash/{view_id}")
         .route(web::delete().to(delete_page_from_trash_handler)),
     )
+    .service(
+      web::resource("/{workspace_id}/similar-pages")
+        .route(web::get().to(list_similar_pages_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/similar-pages/move-to-trash")
+        .route(web::post().to(trash_similar_pages_handler)),
+    )
     .service(
       web::resource("/published-outline/{publish_namespace}")
         .route(web::get().to(get_workspace_publish_outline_handler)),
@@ -294,11 +491,29 @@ pub fn workspace_scope() -> Scope {
         .route(web::post().to(post_database_row_handler))
         .route(web::put().to(put_database_row_handler)),
     )
+    .service(
+      web::resource("/{workspace_id}/database/{database_id}/row/batch")
+        .route(web::post().to(batch_post_database_row_handler)),
+    )
     .service(
       web::resource("/{workspace_id}/database/{database_id}/fields")
         .route(web::get().to(get_database_fields_handler))
         .route(web::post().to(post_database_fields_handler)),
     )
+    .service(
+      web::resource("/{workspace_id}/database/{database_id}/fields/{field_id}/type")
+        .route(web::put().to(put_database_field_type_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/database/{database_id}/fields/conversion/{task_id}")
+        .route(web::get().to(get_database_field_conversion_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/database/{database_id}/row-access")
+        .route(web::get().to(get_database_row_access_handler))
+        .route(web::put().to(put_database_row_access_handler))
+        .route(web::delete().to(delete_database_row_access_handler)),
+    )
     .service(
       web::resource("/{workspace_id}/database/{database_id}/row/updated")
         .route(web::get().to(list_database_row_id_updated_handler)),
@@ -307,6 +522,11 @@ pub fn workspace_scope() -> Scope {
       web::resource("/{workspace_id}/database/{database_id}/row/detail")
         .route(web::get().to(list_database_row_details_handler)),
     )
+    .service(
+      web::resource("/{workspace_id}/database/{database_id}/row/{row_id}")
+        .route(web::patch().to(patch_database_row_handler))
+        .route(web::delete().to(delete_database_row_handler)),
+    )
     .service(
       web::resource("/{workspace_id}/quick-note")
         .route(web::get().to(list_quick_notes_handler))
@@ -317,6 +537,21 @@ pub fn workspace_scope() -> Scope {
         .route(web::put().to(update_quick_note_handler))
         .route(web::delete().to(delete_quick_note_handler)),
     )
+    .service(
+      web::resource("/{workspace_id}/export")
+        .route(web::post().to(create_workspace_export_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/export/{task_id}")
+        .route(web::get().to(get_workspace_export_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/merge").route(web::post().to(create_workspace_merge_handler)),
+    )
+    .service(
+      web::resource("/{workspace_id}/merge/{task_id}")
+        .route(web::get().to(get_workspace_merge_handler)),
+    )
 }
 
 pub fn collab_scope() -> Scope {
@@ -441,6 +676,7 @@ async fn post_workspace_invite_handler(
     &user_uuid,
     &workspace_id,
     invitations,
+    state.config.workspace_invitation.expires_in_secs(),
     state.config.appflowy_web_url.as_deref(),
     &state.config.admin_frontend_path_prefix,
   )
@@ -448,6 +684,50 @@ async fn post_workspace_invite_handler(
   Ok(AppResponse::Ok().into())
 }
 
+async fn get_workspace_pending_invites_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<Vec<AFWorkspacePendingInvitation>>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  let invitations =
+    workspace::ops::list_workspace_pending_invitations(&state.pg_pool, &workspace_id).await?;
+  Ok(AppResponse::Ok().with_data(invitations).into())
+}
+
+#[instrument(skip(payload, state), err)]
+async fn post_extend_workspace_invite_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, Uuid)>,
+  payload: Json<ExtendWorkspaceInvitationParams>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<AFWorkspacePendingInvitation>> {
+  let (workspace_id, invite_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  let invitation = workspace::ops::extend_workspace_invitation(
+    &state.gotrue_admin,
+    &state.pg_pool,
+    &state.gotrue_client,
+    &user_uuid,
+    &workspace_id,
+    &invite_id,
+    payload.into_inner(),
+    state.config.workspace_invitation.expires_in_secs(),
+    state.config.appflowy_web_url.as_deref(),
+    &state.config.admin_frontend_path_prefix,
+  )
+  .await?;
+  Ok(AppResponse::Ok().with_data(invitation).into())
+}
+
 async fn get_workspace_invite_handler(
   user_uuid: UserUuid,
   state: Data<AppState>,
@@ -531,12 +811,27 @@ async fn get_workspace_members_handler(
   user_uuid: UserUuid,
   state: Data<AppState>,
   workspace_id: web::Path<Uuid>,
-) -> Result<JsonAppResponse<Vec<AFWorkspaceMember>>> {
+  query: web::Query<PageQuery>,
+  filter: web::Query<WorkspaceMemberFilter>,
+  req: HttpRequest,
+) -> Result<
+  Either<
+    JsonAppResponse<page_dto::Page<AFWorkspaceMember>>,
+    JsonAppResponse<Vec<AFWorkspaceMember>>,
+  >,
+> {
   let uid = state.user_cache.get_user_uid(&user_uuid).await?;
   state
     .workspace_access_control
     .enforce_role(&uid, &workspace_id.to_string(), AFRole::Member)
     .await?;
+  if accepts_page_response(req.headers()) {
+    let page =
+      workspace::ops::get_workspace_member_page(&state.pg_pool, &workspace_id, &filter, &query)
+        .await?;
+    return Ok(Either::Left(AppResponse::Ok().with_data(page).into()));
+  }
+  // the clients older than the pages get every member at once
   let members = workspace::ops::get_workspace_members(&state.pg_pool, &workspace_id)
     .await?
     .into_iter()
@@ -548,7 +843,38 @@ async fn get_workspace_members_handler(
     })
     .collect();
 
-  Ok(AppResponse::Ok().with_data(members).into())
+  Ok(Either::Right(AppResponse::Ok().with_data(members).into()))
+}
+
+/// Streams the members matching the filter as a CSV file, for the audits of the workspace.
+#[instrument(skip_all, err)]
+async fn export_workspace_members_handler(
+  user_uuid: UserUuid,
+  state: Data<AppState>,
+  workspace_id: web::Path<Uuid>,
+  query: web::Query<WorkspaceMemberFilter>,
+) -> Result<HttpResponse> {
+  let workspace_id = workspace_id.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  let csv =
+    export_workspace_members_csv(state.pg_pool.clone(), workspace_id, uid, query.into_inner())
+      .await?;
+  Ok(
+    HttpResponse::Ok()
+      .content_type("text/csv; charset=utf-8")
+      .insert_header((
+        actix_web::http::header::CONTENT_DISPOSITION,
+        format!(
+          "attachment; filename=\"workspace_members_{}.csv\"",
+          workspace_id
+        ),
+      ))
+      .streaming(csv),
+  )
 }
 
 #[instrument(skip_all, err)]
@@ -572,6 +898,7 @@ async fn remove_workspace_member_handler(
     .collect::<Vec<String>>();
   workspace::ops::remove_workspace_members(
     &state.pg_pool,
+    &user_uuid,
     &workspace_id,
     &member_emails,
     state.workspace_access_control.clone(),
@@ -670,6 +997,7 @@ async fn update_workspace_member_handler(
       .map_err(AppResponseError::from)?;
     workspace::ops::update_workspace_member(
       &changeset_uid,
+      &user_uuid,
       &state.pg_pool,
       &workspace_id,
       &changeset,
@@ -689,6 +1017,7 @@ async fn create_collab_handler(
   req: HttpRequest,
 ) -> Result<Json<AppResponse<()>>> {
   let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state.load_shedder.check(ShedTier::ApiWrite)?;
   let params = match req.headers().get(X_COMPRESSION_TYPE) {
     None => serde_json::from_slice::<CreateCollabParams>(&payload).map_err(|err| {
       AppError::InvalidRequest(format!(
@@ -719,42 +1048,64 @@ async fn create_collab_handler(
     );
   }
 
-  let collab = collab_from_encode_collab(&params.object_id, &params.encoded_collab_v1)
-    .await
-    .map_err(|err| {
-      AppError::NoRequiredData(format!(
-        "Failed to create collab from encoded collab: {}",
-        err
-      ))
-    })?;
-
-  if let Err(err) = params.collab_type.validate_require_data(&collab) {
-    return Err(
-      AppError::NoRequiredData(format!(
-        "collab doc state is not correct:{},{}",
-        params.object_id, err
-      ))
-      .into(),
-    );
-  }
+  let mode = biz::collab::mode::get_collab_mode(&state.pg_pool, &params.object_id).await?;
+  let spec = collab_spec(&params.collab_type, mode.is_opaque());
+  state
+    .config
+    .collab
+    .size_limits
+    .check_doc_state(spec, params.encoded_collab_v1.len())?;
+  // the content of an opaque collab is encrypted: only its size can be checked
+  let collab = if spec.is_opaque() {
+    None
+  } else {
+    let collab = collab_from_encode_collab(&params.object_id, &params.encoded_collab_v1)
+      .await
+      .map_err(|err| {
+        AppError::NoRequiredData(format!(
+          "Failed to create collab from encoded collab: {}",
+          err
+        ))
+      })?;
+    if let Err(err) = spec.validate(&collab) {
+      return Err(
+        AppError::NoRequiredData(format!(
+          "collab doc state is not correct:{},{}",
+          params.object_id, err
+        ))
+        .into(),
+      );
+    }
+    check_collab_structure(
+      spec,
+      &workspace_id,
+      &params.object_id,
+      &collab,
+      state
+        .feature_flags
+        .enabled(STRICT_COLLAB_VALIDATION, &workspace_id),
+    )?;
+    Some(collab)
+  };
 
+  let mut pending_index = None;
   if state
     .indexer_scheduler
-    .can_index_workspace(&workspace_id)
-    .await?
+    .is_indexing_enabled(&params.collab_type)
+    && state
+      .indexer_scheduler
+      .can_index_workspace(&workspace_id)
+      .await?
   {
-    if let Ok(text) = Document::open(collab).and_then(|doc| doc.to_plain_text(false, true)) {
+    if let Some(text) = collab.as_ref().and_then(|collab| spec.extract_text(collab)) {
       let workspace_id_uuid =
         Uuid::parse_str(&workspace_id).map_err(|err| AppError::Internal(err.into()))?;
-      let pending = UnindexedCollabTask::new(
+      pending_index = Some(UnindexedCollabTask::new(
         workspace_id_uuid,
         params.object_id.clone(),
         params.collab_type.clone(),
         UnindexedData::Text(text),
-      );
-      state
-        .indexer_scheduler
-        .index_pending_collab_one(pending, false)?;
+      ));
     }
   }
 
@@ -766,6 +1117,11 @@ async fn create_collab_handler(
     .map_err(AppError::from)?;
   let start = Instant::now();
 
+  // indexed by the appflowy worker once the collab is committed
+  if let Some(pending) = &pending_index {
+    insert_outbox_entry(&mut transaction, OutboxTopic::IndexCollab, pending).await?;
+  }
+
   let action = format!("Create new collab: {}", params);
   state
     .collab_access_control_storage
@@ -791,6 +1147,7 @@ async fn batch_create_collab_handler(
   req: HttpRequest,
 ) -> Result<Json<AppResponse<()>>> {
   let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state.load_shedder.check(ShedTier::ApiWrite)?;
   let workspace_id_uuid = workspace_id.into_inner();
   let workspace_id = workspace_id_uuid.to_string();
   let compress_type = compress_type_from_header_value(req.headers())?;
@@ -824,7 +1181,7 @@ async fn batch_create_collab_handler(
     }
   }
   // Perform decompression and processing in a Rayon thread pool
-  let mut collab_params_list = tokio::task::spawn_blocking(move || match compress_type {
+  let decompressed = tokio::task::spawn_blocking(move || match compress_type {
     CompressionType::Brotli { buffer_size } => offset_len_list
       .into_par_iter()
       .filter_map(|(offset, len)| {
@@ -832,37 +1189,7 @@ async fn batch_create_collab_handler(
         match decompress(compressed_data.to_vec(), buffer_size) {
           Ok(decompressed_data) => {
             let params = CollabParams::from_bytes(&decompressed_data).ok()?;
-            if params.validate().is_ok() {
-              let encoded_collab =
-                EncodedCollab::decode_from_bytes(&params.encoded_collab_v1).ok()?;
-              let collab = Collab::new_with_source(
-                CollabOrigin::Empty,
-                &params.object_id,
-                DataSource::DocStateV1(encoded_collab.doc_state.to_vec()),
-                vec![],
-                false,
-              )
-              .ok()?;
-
-              match params.collab_type.validate_require_data(&collab) {
-                Ok(_) => {
-                  match params.collab_type {
-                    CollabType::Document => {
-                      let index_text =
-                        Document::open(collab).and_then(|doc| doc.to_plain_text(false, true));
-                      Some((Some(index_text), params))
-                    },
-                    _ => {
-                      // TODO(nathan): support other types
-                      Some((None, params))
-                    },
-                  }
-                },
-                Err(_) => None,
-              }
-            } else {
-              None
-            }
+            params.validate().is_ok().then_some(params)
           },
           Err(err) => {
             error!("Failed to decompress data: {:?}", err);
@@ -875,6 +1202,58 @@ async fn batch_create_collab_handler(
   .await
   .map_err(|_| AppError::InvalidRequest("Failed to decompress data".to_string()))?;
 
+  let object_ids = decompressed
+    .iter()
+    .map(|params| params.object_id.clone())
+    .collect::<Vec<_>>();
+  let opaque_object_ids = select_opaque_collab_oids(&state.pg_pool, &object_ids).await?;
+  let size_limits = state.config.collab.size_limits.clone();
+  let strict_validation = state
+    .feature_flags
+    .enabled(STRICT_COLLAB_VALIDATION, &workspace_id);
+  let validated_workspace_id = workspace_id.clone();
+  let mut collab_params_list = tokio::task::spawn_blocking(move || {
+    decompressed
+      .into_par_iter()
+      .filter_map(|params| {
+        let spec = collab_spec(
+          &params.collab_type,
+          opaque_object_ids.contains(&params.object_id),
+        );
+        size_limits
+          .check_doc_state(spec, params.encoded_collab_v1.len())
+          .ok()?;
+        // the content of an opaque collab is encrypted: only its size can be checked
+        if spec.is_opaque() {
+          return Some((None, params));
+        }
+        let encoded_collab = EncodedCollab::decode_from_bytes(&params.encoded_collab_v1).ok()?;
+        let collab = Collab::new_with_source(
+          CollabOrigin::Empty,
+          &params.object_id,
+          DataSource::DocStateV1(encoded_collab.doc_state.to_vec()),
+          vec![],
+          false,
+        )
+        .ok()?;
+
+        spec.validate(&collab).ok()?;
+        check_collab_structure(
+          spec,
+          &validated_workspace_id,
+          &params.object_id,
+          &collab,
+          strict_validation,
+        )
+        .ok()?;
+        let index_text = spec.extract_text(&collab);
+        Some((index_text, params))
+      })
+      .collect::<Vec<_>>()
+  })
+  .await
+  .map_err(|_| AppError::InvalidRequest("Failed to validate collab data".to_string()))?;
+
   if collab_params_list.is_empty() {
     return Err(AppError::InvalidRequest("Empty collab params list".to_string()).into());
   }
@@ -901,18 +1280,15 @@ async fn batch_create_collab_handler(
           .indexer_scheduler
           .is_indexing_enabled(&p.1.collab_type)
       })
-      .flat_map(|value| match std::mem::take(&mut value.0) {
-        None => None,
-        Some(text) => text
-          .map(|text| {
-            UnindexedCollabTask::new(
-              workspace_id_uuid,
-              value.1.object_id.clone(),
-              value.1.collab_type.clone(),
-              UnindexedData::Text(text),
-            )
-          })
-          .ok(),
+      .flat_map(|value| {
+        std::mem::take(&mut value.0).map(|text| {
+          UnindexedCollabTask::new(
+            workspace_id_uuid,
+            value.1.object_id.clone(),
+            value.1.collab_type.clone(),
+            UnindexedData::Text(text),
+          )
+        })
       })
       .collect::<Vec<_>>();
   }
@@ -957,15 +1333,27 @@ async fn get_collab_handler(
     .map_err(AppResponseError::from)?;
   let params = payload.into_inner();
   let object_id = params.object_id.clone();
-  let encode_collab = state
-    .collab_access_control_storage
-    .get_encode_collab(GetCollabOrigin::User { uid }, params, true)
+  let workspace_id = params.workspace_id.clone();
+  let collab = state
+    .collab_recovery
+    .get_encode_collab(
+      &*state.collab_access_control_storage,
+      GetCollabOrigin::User { uid },
+      params,
+      true,
+      DetectedOn::Http,
+    )
     .await
     .map_err(AppResponseError::from)?;
+  let encode_collab = state
+    .row_access_control
+    .project_collab(&workspace_id, uid, &object_id, collab.encoded_collab)
+    .await?;
 
   let resp = CollabResponse {
     encode_collab,
     object_id,
+    recovered_from: collab.recovered_from,
   };
 
   Ok(Json(AppResponse::Ok().with_data(resp)))
@@ -986,65 +1374,413 @@ async fn v1_get_collab_handler(
     .map_err(AppResponseError::from)?;
 
   let param = QueryCollabParams {
-    workspace_id,
+    workspace_id: workspace_id.clone(),
     inner: QueryCollab {
       object_id: object_id.clone(),
       collab_type,
     },
   };
 
-  let encode_collab = state
-    .collab_access_control_storage
-    .get_encode_collab(GetCollabOrigin::User { uid }, param, true)
+  let collab = state
+    .collab_recovery
+    .get_encode_collab(
+      &*state.collab_access_control_storage,
+      GetCollabOrigin::User { uid },
+      param,
+      true,
+      DetectedOn::Http,
+    )
     .await
     .map_err(AppResponseError::from)?;
+  let encode_collab = state
+    .row_access_control
+    .project_collab(&workspace_id, uid, &object_id, collab.encoded_collab)
+    .await?;
 
   let resp = CollabResponse {
     encode_collab,
     object_id,
+    recovered_from: collab.recovered_from,
   };
 
   Ok(Json(AppResponse::Ok().with_data(resp)))
 }
 
-#[instrument(level = "debug", skip_all)]
-async fn post_web_update_handler(
+/// Conditional fetch of a collab. The collab is only returned when the state vector hash sent by
+/// the client differs from the one of the server copy.
+async fn v1_collab_diff_handler(
   user_uuid: UserUuid,
-  path: web::Path<(Uuid, Uuid)>,
-  payload: Json<UpdateCollabWebParams>,
+  path: web::Path<(String, String)>,
+  payload: Json<CollabDiffParams>,
   state: Data<AppState>,
-  server: Data<RealtimeServerAddr>,
-  req: HttpRequest,
-) -> Result<Json<AppResponse<()>>> {
+) -> Result<Json<AppResponse<CollabDiffResponse>>> {
+  let (workspace_id, object_id) = path.into_inner();
+  let params = payload.into_inner();
   let uid = state
     .user_cache
     .get_user_uid(&user_uuid)
     .await
     .map_err(AppResponseError::from)?;
-  let (workspace_id, object_id) = path.into_inner();
-  state
-    .collab_access_control
-    .enforce_action(
-      &workspace_id.to_string(),
-      &uid,
-      &object_id.to_string(),
-      Action::Write,
+  biz::collab::mode::ensure_plaintext_collab(&state.pg_pool, &object_id, "diff").await?;
+
+  let param = QueryCollabParams {
+    workspace_id: workspace_id.clone(),
+    inner: QueryCollab {
+      object_id: object_id.clone(),
+      collab_type: params.collab_type.clone(),
+    },
+  };
+  let collab = state
+    .collab_recovery
+    .get_encode_collab(
+      &*state.collab_access_control_storage,
+      GetCollabOrigin::User { uid },
+      param,
+      true,
+      DetectedOn::Http,
     )
-    .await?;
-  let user = realtime_user_for_web_request(req.headers(), uid)?;
-  trace!("create onetime web realtime user: {}", user);
+    .await
+    .map_err(AppResponseError::from)?;
 
-  let payload = payload.into_inner();
-  let collab_type = payload.collab_type.clone();
+  let encode_collab = state
+    .row_access_control
+    .project_collab(&workspace_id, uid, &object_id, collab.encoded_collab)
+    .await?;
+  let mut resp = tokio::task::spawn_blocking(move || {
+    biz::collab::diff::diff_encode_collab(&object_id, encode_collab, params)
+  })
+  .await
+  .map_err(|err| AppError::Internal(anyhow!("Failed to diff collab: {}", err)))??;
+  resp.recovered_from = collab.recovered_from;
+  Ok(Json(AppResponse::Ok().with_data(resp)))
+}
 
-  update_page_collab_data(
-    &state.metrics.appflowy_web_metrics,
-    server,
-    user,
-    workspace_id,
-    object_id,
-    collab_type,
-    payload.doc_state,
+/// The headings of a document, for rendering its table of contents without loading it.
+async fn get_document_outline_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(String, String)>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<DocumentOutline>>> {
+  let (workspace_id, object_id) = path.into_inner();
+  let uid = state
+    .user_cache
+    .get_user_uid(&user_uuid)
+    .await
+    .map_err(AppResponseError::from)?;
+  biz::collab::mode::ensure_plaintext_collab(&state.pg_pool, &object_id, "outline").await?;
+  let param = QueryCollabParams {
+    workspace_id: workspace_id.clone(),
+    inner: QueryCollab {
+      object_id: object_id.clone(),
+      collab_type: CollabType::Document,
+    },
+  };
+  let collab = state
+    .collab_recovery
+    .get_encode_collab(
+      &*state.collab_access_control_storage,
+      GetCollabOrigin::User { uid },
+      param,
+      true,
+      DetectedOn::Http,
+    )
+    .await
+    .map_err(AppResponseError::from)?;
+  let cache = state.document_outline_cache.clone();
+  let outline = tokio::task::spawn_blocking(move || {
+    biz::collab::document_outline::encoded_document_outline(
+      &cache,
+      &object_id,
+      collab.encoded_collab,
+    )
+  })
+  .await
+  .map_err(|err| AppError::Internal(anyhow!("Failed to extract outline: {}", err)))??;
+  Ok(Json(AppResponse::Ok().with_data(outline)))
+}
+
+/// Find in a document, for the clients which can't load the whole document to search it.
+async fn find_in_document_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(String, String)>,
+  query: web::Query<FindInDocumentQuery>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<DocumentFindResult>>> {
+  let (workspace_id, object_id) = path.into_inner();
+  let uid = state
+    .user_cache
+    .get_user_uid(&user_uuid)
+    .await
+    .map_err(AppResponseError::from)?;
+  biz::collab::mode::ensure_plaintext_collab(&state.pg_pool, &object_id, "find").await?;
+  let param = QueryCollabParams {
+    workspace_id: workspace_id.clone(),
+    inner: QueryCollab {
+      object_id: object_id.clone(),
+      collab_type: CollabType::Document,
+    },
+  };
+  let collab = state
+    .collab_recovery
+    .get_encode_collab(
+      &*state.collab_access_control_storage,
+      GetCollabOrigin::User { uid },
+      param,
+      true,
+      DetectedOn::Http,
+    )
+    .await
+    .map_err(AppResponseError::from)?;
+  let cache = state.document_text_cache.clone();
+  let query = query.into_inner();
+  let result = tokio::task::spawn_blocking(move || {
+    biz::collab::document_find::find_in_encoded_document(
+      &cache,
+      &object_id,
+      collab.encoded_collab,
+      &query,
+    )
+  })
+  .await
+  .map_err(|err| AppError::Internal(anyhow!("Failed to find in document: {}", err)))??;
+  Ok(Json(AppResponse::Ok().with_data(result)))
+}
+
+/// What changed between two versions of a document, for rendering a "what changed" view.
+#[instrument(level = "debug", skip(state, query), err)]
+async fn diff_document_snapshots_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, String)>,
+  query: web::Query<DocumentSnapshotDiffQuery>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<DocumentDiff>>> {
+  let (workspace_id, object_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .collab_access_control
+    .enforce_action(&workspace_id.to_string(), &uid, &object_id, Action::Read)
+    .await?;
+  biz::collab::mode::ensure_plaintext_collab(&state.pg_pool, &object_id, "diff").await?;
+  let diff = biz::collab::version_history::diff_document_snapshots(
+    &state.collab_access_control_storage,
+    uid,
+    &workspace_id.to_string(),
+    &object_id,
+    query.into_inner(),
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(diff)))
+}
+
+async fn get_editing_lock_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, Uuid)>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<Option<AFEditingLock>>>> {
+  let (workspace_id, object_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .collab_access_control
+    .enforce_action(
+      &workspace_id.to_string(),
+      &uid,
+      &object_id.to_string(),
+      Action::Read,
+    )
+    .await?;
+  let lock = biz::collab::editing_lock::get_editing_lock(
+    &state.redis_connection_manager,
+    &workspace_id,
+    &object_id,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(lock)))
+}
+
+#[instrument(level = "debug", skip_all, err)]
+async fn acquire_editing_lock_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, Uuid)>,
+  state: Data<AppState>,
+  req: HttpRequest,
+) -> Result<Json<AppResponse<AFEditingLock>>> {
+  let (workspace_id, object_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .collab_access_control
+    .enforce_action(
+      &workspace_id.to_string(),
+      &uid,
+      &object_id.to_string(),
+      Action::Write,
+    )
+    .await?;
+  let device_id = device_id_from_headers(req.headers())?;
+  let lock = biz::collab::editing_lock::acquire_editing_lock(
+    &state.pg_pool,
+    &state.redis_connection_manager,
+    &workspace_id,
+    &object_id,
+    uid,
+    &user_uuid,
+    device_id,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(lock)))
+}
+
+async fn heartbeat_editing_lock_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, Uuid)>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<AFEditingLock>>> {
+  let (workspace_id, object_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let lock = biz::collab::editing_lock::heartbeat_editing_lock(
+    &state.redis_connection_manager,
+    &workspace_id,
+    &object_id,
+    uid,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(lock)))
+}
+
+async fn get_collab_presence_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, Uuid)>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<Vec<AFCollabPresence>>>> {
+  let (workspace_id, object_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id.to_string(), Action::Read)
+    .await?;
+  let presences = biz::collab::presence::get_collab_presence(
+    &state.redis_connection_manager,
+    &workspace_id,
+    &object_id,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(presences)))
+}
+
+async fn get_collab_mode_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, Uuid)>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<CollabMode>>> {
+  let (workspace_id, object_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .collab_access_control
+    .enforce_action(
+      &workspace_id.to_string(),
+      &uid,
+      &object_id.to_string(),
+      Action::Read,
+    )
+    .await?;
+  let mode = biz::collab::mode::get_collab_mode(&state.pg_pool, &object_id.to_string()).await?;
+  Ok(Json(AppResponse::Ok().with_data(mode)))
+}
+
+/// Sets the mode of a collab before it's created. Clients encrypting a collab make it opaque, so
+/// that the server stores and relays its updates without decoding them.
+async fn put_collab_mode_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, Uuid)>,
+  payload: Json<UpdateCollabModeParams>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<()>>> {
+  let (workspace_id, object_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Member)
+    .await?;
+  biz::collab::mode::set_collab_mode(
+    &state.pg_pool,
+    &state.collab_access_control_storage,
+    &workspace_id,
+    &object_id.to_string(),
+    payload.into_inner().mode,
+    uid,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok()))
+}
+
+#[instrument(level = "debug", skip_all, err)]
+async fn release_editing_lock_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, Uuid)>,
+  query: web::Query<ReleaseEditingLockQuery>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<()>>> {
+  let (workspace_id, object_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let force = query.into_inner().force;
+  if force {
+    state
+      .workspace_access_control
+      .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+      .await?;
+  }
+  biz::collab::editing_lock::release_editing_lock(
+    &state.redis_connection_manager,
+    &workspace_id,
+    &object_id,
+    uid,
+    force,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok()))
+}
+
+#[instrument(level = "debug", skip_all)]
+async fn post_web_update_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, Uuid)>,
+  payload: Json<UpdateCollabWebParams>,
+  state: Data<AppState>,
+  server: Data<RealtimeServerAddr>,
+  req: HttpRequest,
+) -> Result<Json<AppResponse<()>>> {
+  let uid = state
+    .user_cache
+    .get_user_uid(&user_uuid)
+    .await
+    .map_err(AppResponseError::from)?;
+  let (workspace_id, object_id) = path.into_inner();
+  state
+    .collab_access_control
+    .enforce_action(
+      &workspace_id.to_string(),
+      &uid,
+      &object_id.to_string(),
+      Action::Write,
+    )
+    .await?;
+  state
+    .row_access_control
+    .enforce_write(&workspace_id.to_string(), uid, &object_id.to_string())
+    .await?;
+  let user = realtime_user_for_web_request(req.headers(), uid)?;
+  trace!("create onetime web realtime user: {}", user);
+
+  let payload = payload.into_inner();
+  let collab_type = payload.collab_type.clone();
+
+  update_page_collab_data(
+    &state.metrics.appflowy_web_metrics,
+    server,
+    user,
+    workspace_id,
+    object_id,
+    collab_type,
+    payload.doc_state,
   )
   .await?;
   Ok(Json(AppResponse::Ok()))
@@ -1243,7 +1979,9 @@ async fn delete_page_from_trash_handler(
     &state.metrics.appflowy_web_metrics,
     server,
     user,
+    &state.pg_pool,
     &state.collab_access_control_storage,
+    &state.bucket_storage,
     workspace_id,
     &view_id,
   )
@@ -1273,7 +2011,9 @@ async fn delete_all_pages_from_trash_handler(
     &state.metrics.appflowy_web_metrics,
     server,
     user,
+    &state.pg_pool,
     &state.collab_access_control_storage,
+    &state.bucket_storage,
     workspace_id,
   )
   .await?;
@@ -1344,6 +2084,62 @@ async fn unpublish_page_handler(
   Ok(Json(AppResponse::Ok()))
 }
 
+async fn republish_page_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, Uuid)>,
+  payload: Json<RepublishPageParams>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<()>>> {
+  let (workspace_id, view_id) = path.into_inner();
+  let uid = state
+    .user_cache
+    .get_user_uid(&user_uuid)
+    .await
+    .map_err(AppResponseError::from)?;
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Member)
+    .await?;
+  republish_page(
+    &state.pg_pool,
+    &state.collab_access_control_storage,
+    state.published_collab_store.as_ref(),
+    uid,
+    *user_uuid,
+    workspace_id,
+    view_id,
+    payload.into_inner(),
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok()))
+}
+
+async fn get_publish_revision_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, Uuid)>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<PublishRevision>>> {
+  let (workspace_id, view_id) = path.into_inner();
+  let uid = state
+    .user_cache
+    .get_user_uid(&user_uuid)
+    .await
+    .map_err(AppResponseError::from)?;
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Member)
+    .await?;
+  let revision = get_publish_revision(
+    &state.pg_pool,
+    &state.collab_access_control_storage,
+    uid,
+    workspace_id,
+    view_id,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(revision)))
+}
+
 async fn update_page_view_handler(
   user_uuid: UserUuid,
   path: web::Path<(Uuid, String)>,
@@ -1374,7 +2170,319 @@ async fn update_page_view_handler(
     extra.as_ref(),
   )
   .await?;
-  Ok(Json(AppResponse::Ok()))
+  Ok(Json(AppResponse::Ok()))
+}
+
+async fn run_maintenance_job_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, MaintenanceJob)>,
+  query: web::Query<RunMaintenanceJobQuery>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<MaintenanceJobReport>> {
+  let (workspace_id, job) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  let report = run_workspace_maintenance_job(
+    &state.pg_pool,
+    &state.bucket_storage,
+    job,
+    workspace_id,
+    uid,
+    JobMode::Run {
+      dry_run: query.dry_run,
+    },
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(report)))
+}
+
+async fn execute_last_maintenance_dry_run_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, MaintenanceJob)>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<MaintenanceJobReport>> {
+  let (workspace_id, job) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  let report = run_workspace_maintenance_job(
+    &state.pg_pool,
+    &state.bucket_storage,
+    job,
+    workspace_id,
+    uid,
+    JobMode::ExecuteLastDryRun,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(report)))
+}
+
+async fn put_workspace_icon_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  state: Data<AppState>,
+  content_length: web::Header<ContentLength>,
+  payload: Payload,
+) -> Result<JsonAppResponse<UploadImageResponse>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let workspace_id = workspace_id.into_inner();
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id.to_string(), Action::Write)
+    .await?;
+  let raw = read_image_payload(
+    payload,
+    content_length.into_inner().into_inner(),
+    ImageKind::Icon,
+  )
+  .await?;
+  let resp = upload_workspace_icon(
+    &state.pg_pool,
+    &state.bucket_storage,
+    state.config.appflowy_web_url.as_deref(),
+    workspace_id,
+    uid,
+    raw,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(resp)))
+}
+
+async fn get_inbound_email_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<InboundEmailAddress>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let workspace_id = workspace_id.into_inner();
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Member)
+    .await?;
+  let domain = inbound_email_domain(&state.config.inbound_email)?;
+  let address = get_inbound_email_address(&state.pg_pool, domain, &workspace_id).await?;
+  Ok(Json(AppResponse::Ok().with_data(address)))
+}
+
+async fn put_inbound_email_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  payload: Json<SetInboundEmailParams>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<InboundEmailAddress>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let workspace_id = workspace_id.into_inner();
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  let view_id = payload.into_inner().view_id;
+  state
+    .collab_access_control
+    .enforce_action(
+      &workspace_id.to_string(),
+      &uid,
+      &view_id.to_string(),
+      Action::Write,
+    )
+    .await?;
+  let domain = inbound_email_domain(&state.config.inbound_email)?;
+  let address = set_inbound_email_address(
+    &state.pg_pool,
+    &state.collab_access_control_storage,
+    domain,
+    &workspace_id,
+    &view_id,
+    uid,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(address)))
+}
+
+async fn rotate_inbound_email_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<InboundEmailAddress>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let workspace_id = workspace_id.into_inner();
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  let domain = inbound_email_domain(&state.config.inbound_email)?;
+  let address = rotate_inbound_email_address(&state.pg_pool, domain, &workspace_id).await?;
+  Ok(Json(AppResponse::Ok().with_data(address)))
+}
+
+async fn delete_inbound_email_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<()>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let workspace_id = workspace_id.into_inner();
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  remove_inbound_email_address(&state.pg_pool, &workspace_id).await?;
+  Ok(Json(AppResponse::Ok()))
+}
+
+async fn list_workspace_webhooks_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<Vec<WorkspaceWebhook>>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let workspace_id = workspace_id.into_inner();
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  let webhooks = list_workspace_webhooks(&state.pg_pool, &workspace_id).await?;
+  Ok(Json(AppResponse::Ok().with_data(webhooks)))
+}
+
+async fn create_workspace_webhook_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  payload: Json<CreateWorkspaceWebhookParams>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<CreatedWorkspaceWebhook>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let workspace_id = workspace_id.into_inner();
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  let webhook =
+    create_workspace_webhook(&state.pg_pool, &workspace_id, uid, payload.into_inner()).await?;
+  Ok(Json(AppResponse::Ok().with_data(webhook)))
+}
+
+async fn delete_workspace_webhook_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, Uuid)>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<()>> {
+  let (workspace_id, webhook_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  remove_workspace_webhook(&state.pg_pool, &workspace_id, &webhook_id).await?;
+  Ok(Json(AppResponse::Ok()))
+}
+
+async fn put_page_view_icon_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, String)>,
+  state: Data<AppState>,
+  server: Data<RealtimeServerAddr>,
+  content_length: web::Header<ContentLength>,
+  payload: Payload,
+  req: HttpRequest,
+) -> Result<JsonAppResponse<UploadImageResponse>> {
+  upload_page_view_image(
+    user_uuid,
+    path.into_inner(),
+    state,
+    server,
+    content_length.into_inner().into_inner(),
+    payload,
+    req,
+    ImageKind::Icon,
+  )
+  .await
+}
+
+async fn put_page_view_cover_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, String)>,
+  state: Data<AppState>,
+  server: Data<RealtimeServerAddr>,
+  content_length: web::Header<ContentLength>,
+  payload: Payload,
+  req: HttpRequest,
+) -> Result<JsonAppResponse<UploadImageResponse>> {
+  upload_page_view_image(
+    user_uuid,
+    path.into_inner(),
+    state,
+    server,
+    content_length.into_inner().into_inner(),
+    payload,
+    req,
+    ImageKind::Cover,
+  )
+  .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn upload_page_view_image(
+  user_uuid: UserUuid,
+  (workspace_id, view_id): (Uuid, String),
+  state: Data<AppState>,
+  server: Data<RealtimeServerAddr>,
+  content_length: usize,
+  payload: Payload,
+  req: HttpRequest,
+  kind: ImageKind,
+) -> Result<JsonAppResponse<UploadImageResponse>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id.to_string(), Action::Write)
+    .await?;
+  let raw = read_image_payload(payload, content_length, kind).await?;
+  let user = realtime_user_for_web_request(req.headers(), uid)?;
+  let resp = upload_page_image(
+    &state.metrics.appflowy_web_metrics,
+    server,
+    user,
+    &state.collab_access_control_storage,
+    &state.bucket_storage,
+    state.config.appflowy_web_url.as_deref(),
+    workspace_id,
+    &view_id,
+    raw,
+    kind,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(resp)))
+}
+
+/// Read an image upload, rejecting it before buffering if it is larger than the raw size allowed
+/// for the kind of image, independently of the general blob size limit.
+async fn read_image_payload(
+  payload: Payload,
+  content_length: usize,
+  kind: ImageKind,
+) -> Result<Vec<u8>, AppError> {
+  if content_length == 0 {
+    return Err(AppError::InvalidRequest("The image is empty".to_string()));
+  }
+  if content_length > kind.max_upload_size() {
+    return Err(AppError::PayloadTooLarge(format!(
+      "The image exceeds the maximum size of {} bytes",
+      kind.max_upload_size()
+    )));
+  }
+
+  let mut raw = Vec::with_capacity(content_length);
+  let mut limited_payload = LimitedPayload::new(payload, content_length);
+  while let Some(bytes) = limited_payload.next().await {
+    raw.extend_from_slice(&bytes?);
+  }
+  Ok(raw)
 }
 
 async fn get_page_view_handler(
@@ -1447,6 +2555,7 @@ async fn create_collab_snapshot_handler(
       workspace_id,
       doc_state: data,
       collab_type,
+      created_by: Some(uid),
     })
     .await?;
 
@@ -1468,6 +2577,59 @@ async fn get_all_collab_snapshot_list_handler(
   Ok(Json(AppResponse::Ok().with_data(data)))
 }
 
+#[instrument(level = "trace", skip(path, state), err)]
+async fn get_collab_version_history_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, String)>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<Vec<AFCollabVersion>>>> {
+  let (workspace_id, object_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .collab_access_control
+    .enforce_action(&workspace_id.to_string(), &uid, &object_id, Action::Read)
+    .await?;
+  state
+    .row_access_control
+    .enforce_read(&workspace_id.to_string(), uid, &object_id)
+    .await?;
+  let versions = state
+    .collab_access_control_storage
+    .get_collab_versions(&workspace_id.to_string(), &object_id)
+    .await?;
+  Ok(Json(AppResponse::Ok().with_data(versions)))
+}
+
+#[instrument(level = "debug", skip(state, payload), err)]
+async fn restore_collab_snapshot_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, String, i64)>,
+  state: Data<AppState>,
+  payload: Json<CollabType>,
+) -> Result<Json<AppResponse<AFSnapshotMeta>>> {
+  let (workspace_id, object_id, snapshot_id) = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .collab_access_control
+    .enforce_action(&workspace_id.to_string(), &uid, &object_id, Action::Write)
+    .await?;
+  state
+    .row_access_control
+    .enforce_write(&workspace_id.to_string(), uid, &object_id)
+    .await?;
+  let pre_restore_snapshot = biz::collab::version_history::restore_collab_snapshot(
+    state.collab_access_control_storage.clone(),
+    &state.pg_pool,
+    uid,
+    &workspace_id.to_string(),
+    &object_id,
+    payload.into_inner(),
+    snapshot_id,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(pre_restore_snapshot)))
+}
+
 #[instrument(level = "debug", skip(payload, state), err)]
 async fn batch_get_collab_handler(
   user_uuid: UserUuid,
@@ -1481,15 +2643,69 @@ async fn batch_get_collab_handler(
     .get_user_uid(&user_uuid)
     .await
     .map_err(AppResponseError::from)?;
+  let results = state
+    .collab_access_control_storage
+    .batch_get_collab(&uid, &workspace_id, payload.into_inner().0, false)
+    .await;
   let result = BatchQueryCollabResult(
     state
-      .collab_access_control_storage
-      .batch_get_collab(&uid, &workspace_id, payload.into_inner().0, false)
+      .row_access_control
+      .project_collab_results(&workspace_id, uid, results)
       .await,
   );
   Ok(Json(AppResponse::Ok().with_data(result)))
 }
 
+#[instrument(level = "debug", skip(payload, state), err)]
+async fn post_collab_batch_handler(
+  user_uuid: UserUuid,
+  path: Path<Uuid>,
+  state: Data<AppState>,
+  payload: Json<BatchQueryCollabParams>,
+) -> Result<Json<AppResponse<BatchGetCollabResponse>>> {
+  let workspace_id = path.into_inner().to_string();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let items = batch_get_collab_for_user(
+    &state.collab_recovery,
+    &state.collab_access_control_storage,
+    &state.row_access_control,
+    uid,
+    &workspace_id,
+    payload.into_inner().0,
+  )
+  .await?;
+  Ok(Json(
+    AppResponse::Ok().with_data(BatchGetCollabResponse { items }),
+  ))
+}
+
+#[instrument(skip(state, payload), err)]
+async fn bulk_create_collab_handler(
+  user_uuid: UserUuid,
+  path: Path<Uuid>,
+  state: Data<AppState>,
+  payload: Json<BulkCreateCollabParams>,
+) -> Result<Json<AppResponse<BulkCreateCollabResponse>>> {
+  let workspace_id = path.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state.load_shedder.check(ShedTier::ApiWrite)?;
+  let items = bulk_create_collab_for_user(
+    &state.pg_pool,
+    &state.collab_access_control_storage,
+    &state.indexer_scheduler,
+    &state.config.collab.size_limits,
+    &state.feature_flags,
+    &state.metrics.collab_metrics,
+    uid,
+    &workspace_id,
+    payload.into_inner().items,
+  )
+  .await?;
+  Ok(Json(
+    AppResponse::Ok().with_data(BulkCreateCollabResponse { items }),
+  ))
+}
+
 #[instrument(skip(state, payload), err)]
 async fn update_collab_handler(
   user_uuid: UserUuid,
@@ -1498,9 +2714,50 @@ async fn update_collab_handler(
 ) -> Result<Json<AppResponse<()>>> {
   let (params, workspace_id) = payload.into_inner().split();
   let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state.load_shedder.check(ShedTier::ApiWrite)?;
+  state
+    .row_access_control
+    .enforce_write(&workspace_id, uid, &params.object_id)
+    .await?;
 
   let create_params = CreateCollabParams::from((workspace_id.to_string(), params));
   let (params, workspace_id) = create_params.split();
+  let mode = biz::collab::mode::get_collab_mode(&state.pg_pool, &params.object_id).await?;
+  let spec = collab_spec(&params.collab_type, mode.is_opaque());
+  state
+    .config
+    .collab
+    .size_limits
+    .check_doc_state(spec, params.encoded_collab_v1.len())?;
+  // the content of an opaque collab is encrypted: only its size can be checked
+  let collab = if spec.is_opaque() {
+    None
+  } else {
+    let collab = collab_from_encode_collab(&params.object_id, &params.encoded_collab_v1)
+      .await
+      .map_err(|err| {
+        AppError::InvalidRequest(format!(
+          "Failed to create collab from encoded collab: {}",
+          err
+        ))
+      })?;
+    spec.validate(&collab).map_err(|err| {
+      AppError::NoRequiredData(format!(
+        "collab doc state is not correct:{},{}",
+        params.object_id, err
+      ))
+    })?;
+    check_collab_structure(
+      spec,
+      &workspace_id,
+      &params.object_id,
+      &collab,
+      state
+        .feature_flags
+        .enabled(STRICT_COLLAB_VALIDATION, &workspace_id),
+    )?;
+    Some(collab)
+  };
   if state
     .indexer_scheduler
     .can_index_workspace(&workspace_id)
@@ -1509,41 +2766,18 @@ async fn update_collab_handler(
     let workspace_id_uuid =
       Uuid::parse_str(&workspace_id).map_err(|err| AppError::Internal(err.into()))?;
 
-    match params.collab_type {
-      CollabType::Document => {
-        let collab = collab_from_encode_collab(&params.object_id, &params.encoded_collab_v1)
-          .await
-          .map_err(|err| {
-            AppError::InvalidRequest(format!(
-              "Failed to create collab from encoded collab: {}",
-              err
-            ))
-          })?;
-        params
-          .collab_type
-          .validate_require_data(&collab)
-          .map_err(|err| {
-            AppError::NoRequiredData(format!(
-              "collab doc state is not correct:{},{}",
-              params.object_id, err
-            ))
-          })?;
-
-        if let Ok(text) = Document::open(collab).and_then(|doc| doc.to_plain_text(false, true)) {
-          let pending = UnindexedCollabTask::new(
-            workspace_id_uuid,
-            params.object_id.clone(),
-            params.collab_type.clone(),
-            UnindexedData::Text(text),
-          );
-          state
-            .indexer_scheduler
-            .index_pending_collab_one(pending, true)?;
-        }
-      },
-      _ => {
-        // TODO(nathan): support other collab type
-      },
+    if let Some(collab) = collab.as_ref().filter(|_| spec.supports_text()) {
+      if let Some(text) = spec.extract_text(collab) {
+        let pending = UnindexedCollabTask::new(
+          workspace_id_uuid,
+          params.object_id.clone(),
+          params.collab_type.clone(),
+          UnindexedData::Text(text),
+        );
+        state
+          .indexer_scheduler
+          .index_pending_collab_one(pending, true)?;
+      }
     }
   }
 
@@ -1676,25 +2910,165 @@ async fn get_default_published_collab_info_meta_handler(
 async fn get_v1_published_collab_handler(
   path_param: web::Path<(String, String)>,
   state: Data<AppState>,
-) -> Result<Json<AppResponse<serde_json::Value>>> {
+) -> Result<HttpResponse> {
   let (workspace_namespace, publish_name) = path_param.into_inner();
   let metadata = state
     .published_collab_store
     .get_collab_metadata(&workspace_namespace, &publish_name)
     .await?;
-  Ok(Json(AppResponse::Ok().with_data(metadata)))
+  Ok(
+    HttpResponse::Ok()
+      .insert_header((CACHE_CONTROL, published_entry_cache_control(&state)))
+      .json(AppResponse::Ok().with_data(metadata)),
+  )
 }
 
+/// The published revision of a view only changes when the view is published again, so the blob is
+/// served with the ETag of the revision and the client can revalidate its copy with
+/// `If-None-Match`.
 async fn get_published_collab_blob_handler(
   path_param: web::Path<(String, String)>,
   state: Data<AppState>,
-) -> Result<Vec<u8>> {
+  req: HttpRequest,
+) -> Result<HttpResponse> {
   let (publish_namespace, publish_name) = path_param.into_inner();
+  let etag =
+    select_published_collab_etag(&state.pg_pool, &publish_namespace, &publish_name).await?;
+  if let Some(etag) = &etag {
+    if if_none_match(&req, etag) {
+      return Ok(
+        HttpResponse::NotModified()
+          .insert_header((ETAG, format!("\"{}\"", etag)))
+          .insert_header((CACHE_CONTROL, published_entry_cache_control(&state)))
+          .finish(),
+      );
+    }
+  }
   let collab_data = state
     .published_collab_store
     .get_collab_blob_by_publish_namespace(&publish_namespace, &publish_name)
     .await?;
-  Ok(collab_data)
+  let mut resp = HttpResponse::Ok();
+  resp.insert_header((CACHE_CONTROL, published_entry_cache_control(&state)));
+  if let Some(etag) = etag {
+    resp.insert_header((
+      CONTENT_LOCATION,
+      format!(
+        "/api/workspace/published/{}/{}/blob/{}",
+        publish_namespace, publish_name, etag
+      ),
+    ));
+    resp.insert_header((ETAG, format!("\"{}\"", etag)));
+  }
+  Ok(resp.body(collab_data))
+}
+
+/// The blob of a revision never changes, so it's cached for good. The revision can be the
+/// published one, or one that was replaced recently: a page cached before its view was published
+/// again keeps loading during the grace period.
+async fn get_published_collab_blob_at_revision_handler(
+  path_param: web::Path<(String, String, String)>,
+  state: Data<AppState>,
+) -> Result<HttpResponse> {
+  let (publish_namespace, publish_name, revision) = path_param.into_inner();
+  let collab_data = select_published_collab_blob_at_revision(
+    &state.pg_pool,
+    &publish_namespace,
+    &publish_name,
+    &revision,
+    state.config.published_collab.revision_grace_period_secs,
+  )
+  .await?
+  .ok_or_else(|| {
+    AppError::RecordNotFound(format!(
+      "revision {} of {}/{} is not published",
+      revision, publish_namespace, publish_name
+    ))
+  })?;
+  Ok(
+    HttpResponse::Ok()
+      .insert_header((ETAG, format!("\"{}\"", revision)))
+      .insert_header((CACHE_CONTROL, "public, immutable, max-age=31536000"))
+      .body(collab_data),
+  )
+}
+
+/// The headings of a published revision of a document. Like the blob of the revision, it never
+/// changes.
+async fn get_published_document_outline_handler(
+  path_param: web::Path<(String, String, String)>,
+  state: Data<AppState>,
+) -> Result<HttpResponse> {
+  let (publish_namespace, publish_name, revision) = path_param.into_inner();
+  let key =
+    select_published_collab_workspace_view_id(&state.pg_pool, &publish_namespace, &publish_name)
+      .await?;
+  let blob = select_published_collab_blob_at_revision(
+    &state.pg_pool,
+    &publish_namespace,
+    &publish_name,
+    &revision,
+    state.config.published_collab.revision_grace_period_secs,
+  )
+  .await?
+  .ok_or_else(|| {
+    AppError::RecordNotFound(format!(
+      "revision {} of {}/{} is not published",
+      revision, publish_namespace, publish_name
+    ))
+  })?;
+  let cache = state.document_outline_cache.clone();
+  let etag = format!("\"{}\"", revision);
+  let outline = tokio::task::spawn_blocking(move || {
+    biz::collab::document_outline::published_document_outline(
+      &cache,
+      &key.view_id.to_string(),
+      &revision,
+      blob,
+    )
+  })
+  .await
+  .map_err(|err| AppError::Internal(anyhow!("Failed to extract outline: {}", err)))??;
+  Ok(
+    HttpResponse::Ok()
+      .insert_header((ETAG, etag))
+      .insert_header((CACHE_CONTROL, "public, immutable, max-age=31536000"))
+      .json(AppResponse::Ok().with_data(outline)),
+  )
+}
+
+/// The entry points of a published view return its latest revision, they are only cached for a
+/// short time.
+fn published_entry_cache_control(state: &AppState) -> String {
+  format!(
+    "public, max-age={}",
+    state.config.published_collab.entry_max_age_secs
+  )
+}
+
+/// Anyone reading a published view can report it, without being signed in.
+async fn report_published_collab_handler(
+  path_param: web::Path<(String, String)>,
+  params: Json<ReportPublishedViewParams>,
+  state: Data<AppState>,
+  req: HttpRequest,
+) -> Result<Json<AppResponse<()>>> {
+  let (publish_namespace, publish_name) = path_param.into_inner();
+  let reporter_addr = req
+    .connection_info()
+    .realip_remote_addr()
+    .unwrap_or_default()
+    .to_string();
+  biz::moderation::report_published_view(
+    &state.pg_pool,
+    &state.redis_connection_manager,
+    &reporter_addr,
+    &publish_namespace,
+    &publish_name,
+    params.into_inner(),
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok()))
 }
 
 async fn post_published_duplicate_handler(
@@ -1713,11 +3087,13 @@ async fn post_published_duplicate_handler(
     biz::workspace::publish_dup::duplicate_published_collab_to_workspace(
       &state.pg_pool,
       state.bucket_client.clone(),
+      state.bucket_storage.clone(),
       state.collab_access_control_storage.clone(),
       uid,
       params.published_view_id,
       workspace_id.into_inner(),
       params.dest_view_id,
+      params.nonce,
     )
     .await?;
 
@@ -1912,6 +3288,11 @@ async fn post_publish_collabs_handler(
       AppError::InvalidRequest(String::from("did not receive any data to publish")).into(),
     );
   }
+  let view_ids = accumulator
+    .iter()
+    .map(|item| item.meta.view_id.to_string())
+    .collect::<Vec<_>>();
+  biz::collab::mode::ensure_plaintext_collabs(&state.pg_pool, &view_ids, "publish").await?;
   state
     .published_collab_store
     .publish_collabs(accumulator, &workspace_id, &user_uuid)
@@ -2014,6 +3395,39 @@ async fn get_workspace_usage_handler(
   Ok(Json(AppResponse::Ok().with_data(res)))
 }
 
+async fn get_workspace_stats_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<WorkspaceStats>>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id.to_string(), Action::Read)
+    .await?;
+  let stats = biz::workspace::stats::get_workspace_stats(
+    &state.pg_pool,
+    &state.collab_access_control_storage,
+    workspace_id.into_inner(),
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(stats)))
+}
+
+async fn recompute_workspace_stats_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<()>>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  biz::workspace::stats::recompute_stats(&state.pg_pool, workspace_id.into_inner()).await?;
+  Ok(Json(AppResponse::Ok()))
+}
+
 async fn get_workspace_folder_handler(
   user_uuid: UserUuid,
   workspace_id: web::Path<Uuid>,
@@ -2049,71 +3463,192 @@ async fn get_workspace_folder_handler(
   Ok(Json(AppResponse::Ok().with_data(folder_view)))
 }
 
-async fn get_recent_views_handler(
+async fn get_recent_views_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<RecentSectionItems>>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let workspace_id = workspace_id.into_inner();
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id.to_string(), Action::Read)
+    .await?;
+  let folder_views = get_user_recent_folder_views(
+    &state.collab_access_control_storage,
+    &state.pg_pool,
+    uid,
+    workspace_id,
+  )
+  .await?;
+  let section_items = RecentSectionItems {
+    views: folder_views,
+  };
+  Ok(Json(AppResponse::Ok().with_data(section_items)))
+}
+
+async fn get_favorite_views_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<FavoriteSectionItems>>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let workspace_id = workspace_id.into_inner();
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id.to_string(), Action::Read)
+    .await?;
+  let folder_views = get_user_favorite_folder_views(
+    &state.collab_access_control_storage,
+    &state.pg_pool,
+    uid,
+    workspace_id,
+  )
+  .await?;
+  let section_items = FavoriteSectionItems {
+    views: folder_views,
+  };
+  Ok(Json(AppResponse::Ok().with_data(section_items)))
+}
+
+async fn get_trash_views_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<TrashSectionItems>>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let workspace_id = workspace_id.into_inner();
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id.to_string(), Action::Read)
+    .await?;
+  let folder_views =
+    get_user_trash_folder_views(&state.collab_access_control_storage, uid, workspace_id).await?;
+  let section_items = TrashSectionItems {
+    views: folder_views,
+  };
+  Ok(Json(AppResponse::Ok().with_data(section_items)))
+}
+
+async fn list_deleted_collabs_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<DeletedCollabs>>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let workspace_id = workspace_id.into_inner();
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id.to_string(), Action::Read)
+    .await?;
+  let items = list_deleted_collabs(
+    &state.pg_pool,
+    state.collab_access_control.as_ref(),
+    &workspace_id,
+    uid,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(DeletedCollabs { items })))
+}
+
+async fn restore_deleted_collab_handler(
+  user_uuid: UserUuid,
+  path: web::Path<(Uuid, String)>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<()>>> {
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let (workspace_id, object_id) = path.into_inner();
+  // the same permission as deleting it
+  state
+    .collab_access_control
+    .enforce_access_level(
+      &workspace_id.to_string(),
+      &uid,
+      &object_id,
+      AFAccessLevel::FullAccess,
+    )
+    .await?;
+  restore_collab_from_trash(&state.pg_pool, &workspace_id, &object_id).await?;
+  Ok(Json(AppResponse::Ok()))
+}
+
+async fn purge_deleted_collab_handler(
   user_uuid: UserUuid,
-  workspace_id: web::Path<Uuid>,
+  path: web::Path<(Uuid, String)>,
   state: Data<AppState>,
-) -> Result<Json<AppResponse<RecentSectionItems>>> {
+) -> Result<Json<AppResponse<()>>> {
   let uid = state.user_cache.get_user_uid(&user_uuid).await?;
-  let workspace_id = workspace_id.into_inner();
+  let (workspace_id, object_id) = path.into_inner();
+  // the same permission as deleting it
   state
-    .workspace_access_control
-    .enforce_action(&uid, &workspace_id.to_string(), Action::Read)
+    .collab_access_control
+    .enforce_access_level(
+      &workspace_id.to_string(),
+      &uid,
+      &object_id,
+      AFAccessLevel::FullAccess,
+    )
     .await?;
-  let folder_views = get_user_recent_folder_views(
-    &state.collab_access_control_storage,
+  purge_collab_from_trash(
     &state.pg_pool,
-    uid,
-    workspace_id,
+    &state.collab_access_control_storage,
+    &state.bucket_storage,
+    &workspace_id,
+    &object_id,
   )
   .await?;
-  let section_items = RecentSectionItems {
-    views: folder_views,
-  };
-  Ok(Json(AppResponse::Ok().with_data(section_items)))
+  Ok(Json(AppResponse::Ok()))
 }
 
-async fn get_favorite_views_handler(
+async fn list_similar_pages_handler(
   user_uuid: UserUuid,
   workspace_id: web::Path<Uuid>,
+  query: web::Query<SimilarPagesQuery>,
   state: Data<AppState>,
-) -> Result<Json<AppResponse<FavoriteSectionItems>>> {
+) -> Result<Json<AppResponse<SimilarPageClusters>>> {
   let uid = state.user_cache.get_user_uid(&user_uuid).await?;
   let workspace_id = workspace_id.into_inner();
   state
     .workspace_access_control
     .enforce_action(&uid, &workspace_id.to_string(), Action::Read)
     .await?;
-  let folder_views = get_user_favorite_folder_views(
-    &state.collab_access_control_storage,
+  let clusters = list_similar_pages(
     &state.pg_pool,
+    &state.collab_access_control_storage,
     uid,
     workspace_id,
+    query.threshold,
   )
   .await?;
-  let section_items = FavoriteSectionItems {
-    views: folder_views,
-  };
-  Ok(Json(AppResponse::Ok().with_data(section_items)))
+  Ok(Json(AppResponse::Ok().with_data(clusters)))
 }
 
-async fn get_trash_views_handler(
+async fn trash_similar_pages_handler(
   user_uuid: UserUuid,
   workspace_id: web::Path<Uuid>,
+  payload: Json<TrashSimilarPagesParams>,
   state: Data<AppState>,
-) -> Result<Json<AppResponse<TrashSectionItems>>> {
+  server: Data<RealtimeServerAddr>,
+  req: HttpRequest,
+) -> Result<Json<AppResponse<TrashedSimilarPages>>> {
   let uid = state.user_cache.get_user_uid(&user_uuid).await?;
   let workspace_id = workspace_id.into_inner();
   state
     .workspace_access_control
-    .enforce_action(&uid, &workspace_id.to_string(), Action::Read)
+    .enforce_action(&uid, &workspace_id.to_string(), Action::Write)
     .await?;
-  let folder_views =
-    get_user_trash_folder_views(&state.collab_access_control_storage, uid, workspace_id).await?;
-  let section_items = TrashSectionItems {
-    views: folder_views,
-  };
-  Ok(Json(AppResponse::Ok().with_data(section_items)))
+  let user = realtime_user_for_web_request(req.headers(), uid)?;
+  let trashed = trash_similar_pages(
+    &state.metrics.appflowy_web_metrics,
+    server,
+    user,
+    &state.pg_pool,
+    &state.collab_access_control_storage,
+    workspace_id,
+    payload.into_inner(),
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(trashed)))
 }
 
 async fn get_workspace_publish_outline_handler(
@@ -2159,12 +3694,19 @@ async fn list_database_row_id_handler(
     .enforce_action(&uid, &workspace_id, Action::Read)
     .await?;
 
-  let db_rows = biz::collab::ops::list_database_row_ids(
+  let mut db_rows = biz::collab::ops::list_database_row_ids(
     &state.collab_access_control_storage,
     &workspace_id,
     &db_id,
   )
   .await?;
+  if let Some(visible_row_ids) = state
+    .row_access_control
+    .visible_row_ids(&workspace_id, uid, &db_id)
+    .await?
+  {
+    db_rows.retain(|row| visible_row_ids.contains(&row.id));
+  }
   Ok(Json(AppResponse::Ok().with_data(db_rows)))
 }
 
@@ -2229,6 +3771,10 @@ async fn put_database_row_handler(
     ])
   };
   let row_id_str = row_id.to_string();
+  state
+    .row_access_control
+    .enforce_write(&workspace_id, uid, &row_id_str)
+    .await?;
 
   biz::collab::ops::upsert_database_row(
     state.collab_access_control_storage.clone(),
@@ -2244,6 +3790,156 @@ async fn put_database_row_handler(
   Ok(Json(AppResponse::Ok().with_data(row_id_str)))
 }
 
+async fn batch_post_database_row_handler(
+  user_uuid: UserUuid,
+  path_param: web::Path<(String, String)>,
+  state: Data<AppState>,
+  payload: Json<BatchAddDatabaseRows>,
+) -> Result<Json<AppResponse<Vec<String>>>> {
+  let (workspace_id, db_id) = path_param.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id, Action::Write)
+    .await?;
+
+  let BatchAddDatabaseRows {
+    rows,
+    create_select_options,
+  } = payload.into_inner();
+  let row_ids = biz::collab::ops::batch_insert_database_rows(
+    state.collab_access_control_storage.clone(),
+    &state.pg_pool,
+    &workspace_id,
+    &db_id,
+    uid,
+    rows,
+    create_select_options,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(row_ids)))
+}
+
+async fn patch_database_row_handler(
+  user_uuid: UserUuid,
+  path_param: web::Path<(String, String, String)>,
+  state: Data<AppState>,
+  payload: Json<UpdateDatabaseRow>,
+) -> Result<Json<AppResponse<()>>> {
+  let (workspace_id, db_id, row_id) = path_param.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id, Action::Write)
+    .await?;
+  state
+    .row_access_control
+    .enforce_write(&workspace_id, uid, &row_id)
+    .await?;
+
+  let UpdateDatabaseRow {
+    cells,
+    document,
+    create_select_options,
+  } = payload.into_inner();
+  biz::collab::ops::update_database_row(
+    state.collab_access_control_storage.clone(),
+    &state.pg_pool,
+    &workspace_id,
+    &db_id,
+    uid,
+    &row_id,
+    cells,
+    document,
+    create_select_options,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok()))
+}
+
+async fn delete_database_row_handler(
+  user_uuid: UserUuid,
+  path_param: web::Path<(String, String, String)>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<()>>> {
+  let (workspace_id, db_id, row_id) = path_param.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id, Action::Write)
+    .await?;
+  state
+    .row_access_control
+    .enforce_write(&workspace_id, uid, &row_id)
+    .await?;
+
+  biz::collab::ops::delete_database_row(
+    state.collab_access_control_storage.clone(),
+    &state.pg_pool,
+    &workspace_id,
+    &db_id,
+    uid,
+    &row_id,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok()))
+}
+
+async fn get_database_row_access_handler(
+  user_uuid: UserUuid,
+  path_param: web::Path<(Uuid, Uuid)>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<AFDatabaseRowAccess>>> {
+  let (workspace_id, db_id) = path_param.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id.to_string(), Action::Read)
+    .await?;
+  let rule = state
+    .row_access_control
+    .get_rule(&workspace_id, &db_id)
+    .await?;
+  Ok(Json(AppResponse::Ok().with_data(rule)))
+}
+
+async fn put_database_row_access_handler(
+  user_uuid: UserUuid,
+  path_param: web::Path<(Uuid, Uuid)>,
+  state: Data<AppState>,
+  payload: Json<UpsertDatabaseRowAccess>,
+) -> Result<Json<AppResponse<AFDatabaseRowAccess>>> {
+  let (workspace_id, db_id) = path_param.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  let rule = state
+    .row_access_control
+    .upsert_rule(&workspace_id, &db_id, uid, payload.into_inner())
+    .await?;
+  Ok(Json(AppResponse::Ok().with_data(rule)))
+}
+
+async fn delete_database_row_access_handler(
+  user_uuid: UserUuid,
+  path_param: web::Path<(Uuid, Uuid)>,
+  state: Data<AppState>,
+) -> Result<Json<AppResponse<()>>> {
+  let (workspace_id, db_id) = path_param.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  state
+    .row_access_control
+    .delete_rule(&workspace_id, &db_id)
+    .await?;
+  Ok(Json(AppResponse::Ok()))
+}
+
 async fn get_database_fields_handler(
   user_uuid: UserUuid,
   path_param: web::Path<(String, String)>,
@@ -2292,6 +3988,47 @@ async fn post_database_fields_handler(
   Ok(Json(AppResponse::Ok().with_data(field_id)))
 }
 
+async fn put_database_field_type_handler(
+  user_uuid: UserUuid,
+  path_param: web::Path<(Uuid, Uuid, String)>,
+  state: Data<AppState>,
+  params: Json<UpdateDatabaseFieldType>,
+) -> Result<JsonAppResponse<AFFieldConversionTask>> {
+  let (workspace_id, db_id, field_id) = path_param.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id.to_string(), Action::Write)
+    .await?;
+
+  let task = change_database_field_type(
+    &state.pg_pool,
+    state.collab_access_control_storage.clone(),
+    uid,
+    &workspace_id,
+    &db_id,
+    &field_id,
+    params.into_inner(),
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(task)))
+}
+
+async fn get_database_field_conversion_handler(
+  user_uuid: UserUuid,
+  path_param: web::Path<(Uuid, Uuid, Uuid)>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<AFFieldConversionTask>> {
+  let (workspace_id, db_id, task_id) = path_param.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_action(&uid, &workspace_id.to_string(), Action::Read)
+    .await?;
+  let task = get_field_conversion_task(&state.pg_pool, &workspace_id, &db_id, &task_id).await?;
+  Ok(Json(AppResponse::Ok().with_data(task)))
+}
+
 async fn list_database_row_id_updated_handler(
   user_uuid: UserUuid,
   path_param: web::Path<(String, String)>,
@@ -2311,7 +4048,7 @@ async fn list_database_row_id_updated_handler(
     .after
     .unwrap_or_else(|| Utc::now() - Duration::hours(1));
 
-  let db_rows = biz::collab::ops::list_database_row_ids_updated(
+  let mut db_rows = biz::collab::ops::list_database_row_ids_updated(
     &state.collab_access_control_storage,
     &state.pg_pool,
     &workspace_id,
@@ -2319,6 +4056,13 @@ async fn list_database_row_id_updated_handler(
     &after,
   )
   .await?;
+  if let Some(visible_row_ids) = state
+    .row_access_control
+    .visible_row_ids(&workspace_id, uid, &db_id)
+    .await?
+  {
+    db_rows.retain(|row| visible_row_ids.contains(&row.row_id));
+  }
   Ok(Json(AppResponse::Ok().with_data(db_rows)))
 }
 
@@ -2354,6 +4098,19 @@ async fn list_database_row_details_handler(
     .enforce_action(&uid, &workspace_id, Action::Read)
     .await?;
 
+  let visible_row_ids = state
+    .row_access_control
+    .visible_row_ids(&workspace_id, uid, &db_id)
+    .await?;
+  let row_ids = row_ids
+    .into_iter()
+    .filter(|row_id| {
+      visible_row_ids
+        .as_ref()
+        .map_or(true, |visible_row_ids| visible_row_ids.contains(*row_id))
+    })
+    .collect::<Vec<_>>();
+
   static UNSUPPORTED_FIELD_TYPES: &[FieldType] = &[FieldType::Relation];
 
   let db_rows = biz::collab::ops::list_database_row_details(
@@ -2420,6 +4177,7 @@ async fn get_collab_embed_info_handler(
 ) -> Result<Json<AppResponse<AFCollabEmbedInfo>>> {
   let (_, object_id) = path.into_inner();
   let collab_type = query.into_inner().collab_type;
+  biz::collab::mode::ensure_plaintext_collab(&state.pg_pool, &object_id, "embeddings").await?;
   let info = database::collab::select_collab_embed_info(&state.pg_pool, &object_id, collab_type)
     .await
     .map_err(AppResponseError::from)?
@@ -2512,6 +4270,10 @@ async fn collab_full_sync_handler(
     .get_user_uid(&user_uuid)
     .await
     .map_err(AppResponseError::from)?;
+  state
+    .row_access_control
+    .enforce_write(&workspace_id.to_string(), uid, &object_id.to_string())
+    .await?;
 
   let user = RealtimeUser {
     uid,
@@ -2627,3 +4389,76 @@ async fn delete_quick_note_handler(
   delete_quick_note(&state.pg_pool, quick_note_id).await?;
   Ok(Json(AppResponse::Ok()))
 }
+
+async fn create_workspace_export_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<WorkspaceExportTask>> {
+  let workspace_id = workspace_id.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  let task = create_workspace_export(&state.pg_pool, &user_uuid, uid, &workspace_id).await?;
+  Ok(Json(AppResponse::Ok().with_data(task)))
+}
+
+async fn get_workspace_export_handler(
+  user_uuid: UserUuid,
+  path_param: web::Path<(Uuid, Uuid)>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<WorkspaceExportTask>> {
+  let (workspace_id, task_id) = path_param.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  let task = get_workspace_export(&state.pg_pool, &workspace_id, &task_id).await?;
+  Ok(Json(AppResponse::Ok().with_data(task)))
+}
+
+/// Merge the workspace of the payload into the workspace of the path. The user must own both.
+async fn create_workspace_merge_handler(
+  user_uuid: UserUuid,
+  workspace_id: web::Path<Uuid>,
+  payload: Json<MergeWorkspaceParams>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<WorkspaceMergeTask>> {
+  let workspace_id = workspace_id.into_inner();
+  let source_workspace_id = payload.into_inner().source_workspace_id;
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  for id in [&workspace_id, &source_workspace_id] {
+    state
+      .workspace_access_control
+      .enforce_role(&uid, &id.to_string(), AFRole::Owner)
+      .await?;
+  }
+  let task = create_workspace_merge(
+    &state.pg_pool,
+    &state.redis_connection_manager,
+    state.workspace_access_control.clone(),
+    uid,
+    &workspace_id,
+    &source_workspace_id,
+  )
+  .await?;
+  Ok(Json(AppResponse::Ok().with_data(task)))
+}
+
+async fn get_workspace_merge_handler(
+  user_uuid: UserUuid,
+  path_param: web::Path<(Uuid, Uuid)>,
+  state: Data<AppState>,
+) -> Result<JsonAppResponse<WorkspaceMergeTask>> {
+  let (workspace_id, task_id) = path_param.into_inner();
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  state
+    .workspace_access_control
+    .enforce_role(&uid, &workspace_id.to_string(), AFRole::Owner)
+    .await?;
+  let task = get_workspace_merge(&state.pg_pool, &workspace_id, &task_id).await?;
+  Ok(Json(AppResponse::Ok().with_data(task)))
+}
@@ -0,0 +1,40 @@
+use actix_web::web::{Data, Json};
+use actix_web::{web, Scope};
+use authentication::jwt::Authorization;
+use shared_entity::dto::provisioning_dto::{ProvisionWorkspaceParams, WorkspaceProvisioning};
+use shared_entity::response::{AppResponse, JsonAppResponse};
+
+use crate::biz::moderation::check_admin;
+use crate::biz::workspace::provisioning::{get_workspace_provisioning, provision_workspace};
+use crate::state::AppState;
+
+/// Workspaces created by the service accounts of the instance, which are users with the admin
+/// role. A provisioning is only visible to the account that started it.
+pub fn workspace_provisioning_scope() -> Scope {
+  web::scope("/api/admin/workspace-provisioning")
+    .service(web::resource("").route(web::post().to(provision_workspace_handler)))
+    .service(web::resource("/{external_id}").route(web::get().to(get_provisioning_handler)))
+}
+
+async fn provision_workspace_handler(
+  auth: Authorization,
+  state: Data<AppState>,
+  payload: Json<ProvisionWorkspaceParams>,
+) -> actix_web::Result<JsonAppResponse<WorkspaceProvisioning>> {
+  check_admin(&auth)?;
+  let user_uuid = auth.uuid()?;
+  let uid = state.user_cache.get_user_uid(&user_uuid).await?;
+  let provisioning = provision_workspace(&state, user_uuid, uid, payload.into_inner()).await?;
+  Ok(AppResponse::Ok().with_data(provisioning).into())
+}
+
+async fn get_provisioning_handler(
+  auth: Authorization,
+  path: web::Path<String>,
+  state: Data<AppState>,
+) -> actix_web::Result<JsonAppResponse<WorkspaceProvisioning>> {
+  check_admin(&auth)?;
+  let uid = state.user_cache.get_user_uid(&auth.uuid()?).await?;
+  let provisioning = get_workspace_provisioning(&state, uid, &path.into_inner()).await?;
+  Ok(AppResponse::Ok().with_data(provisioning).into())
+}
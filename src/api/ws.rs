@@ -2,12 +2,13 @@ use std::collections::HashMap;
 use std::time::Duration;
 
 use actix::Addr;
-use actix_http::header::AUTHORIZATION;
+use actix_http::header::{AUTHORIZATION, USER_AGENT};
 use actix_web::web::{Data, Path, Payload};
 use actix_web::{get, web, HttpRequest, HttpResponse, Result, Scope};
 use actix_web_actors::ws;
 use secrecy::Secret;
 use semver::Version;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::Sender;
 use tracing::{debug, error, instrument, trace};
@@ -18,9 +19,11 @@ use appflowy_collaborate::actix_ws::server::RealtimeServerActor;
 use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
 use authentication::jwt::{authorization_from_token, UserUuid};
 use collab_rt_entity::user::{AFUserChange, RealtimeUser, UserMessage};
-use collab_rt_entity::RealtimeMessage;
+use collab_rt_entity::{RealtimeMessage, SystemMessage};
 use shared_entity::response::AppResponseError;
 
+use crate::biz::user::impersonation::impersonation_from_claims;
+use crate::biz::user::user_session::{session_id_from_claims, SessionDevice};
 use crate::state::AppState;
 
 pub fn ws_scope() -> Scope {
@@ -116,11 +119,41 @@ async fn start_connect(
   connect_at: i64,
 ) -> Result<HttpResponse> {
   let auth = authorization_from_token(access_token.as_str(), jwt_secret)?;
+  // Realtime connections send changes, which the impersonations can't be trusted with: the
+  // impersonated requests are checked and recorded one by one over HTTP.
+  if impersonation_from_claims(&auth.claims).is_some() {
+    return Err(AppError::NotEnoughPermissions.into());
+  }
+  let user_session_id = session_id_from_claims(&auth.claims);
+  let token_issued_at = auth.claims.iat;
   let user_uuid = UserUuid::from_auth(auth)?;
   let result = state.user_cache.get_user_uid(&user_uuid).await;
 
   match result {
     Ok(uid) => {
+      if let Some(user_session_id) = &user_session_id {
+        let device = SessionDevice {
+          device_id: Some(device_id.clone()),
+          client_version: Some(client_app_version.to_string()),
+          user_agent: request
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|s| s.to_string()),
+          ip_address: request
+            .connection_info()
+            .realip_remote_addr()
+            .map(|s| s.to_string()),
+        };
+        let revoked = state
+          .user_session_tracker
+          .touch(user_session_id, &user_uuid, &device, token_issued_at)
+          .await?;
+        if revoked {
+          return Err(AppError::UserUnAuthorized("The session was revoked".to_string()).into());
+        }
+      }
+
       debug!(
         "🚀new websocket connect: uid={}, device_id={}, client_version:{}",
         uid, device_id, client_app_version
@@ -145,6 +178,10 @@ async fn start_connect(
         10,
       );
 
+      // Close the connection once its session is revoked, on whichever instance.
+      if let Some(user_session_id) = user_session_id {
+        listen_on_user_session_revoked(state, user_session_id, tx.clone());
+      }
       // Receive user change notifications and send them to the client.
       listen_on_user_change(state, uid, tx);
 
@@ -194,6 +231,36 @@ fn listen_on_user_change(state: &Data<AppState>, uid: i64, tx: Sender<RealtimeMe
   });
 }
 
+fn listen_on_user_session_revoked(
+  state: &Data<AppState>,
+  user_session_id: uuid::Uuid,
+  tx: Sender<RealtimeMessage>,
+) {
+  let mut revoked_recv = state.pg_listeners.subscribe_user_session_revoked();
+  actix::spawn(async move {
+    loop {
+      match revoked_recv.recv().await {
+        Ok(notification) if notification.session_id == user_session_id => {
+          debug!(
+            "session {} was revoked, closing its connection",
+            user_session_id
+          );
+          let _ = tx
+            .send(RealtimeMessage::System(SystemMessage::KickOff))
+            .await;
+          break;
+        },
+        Ok(_) | Err(RecvError::Lagged(_)) => {
+          if tx.is_closed() {
+            break;
+          }
+        },
+        Err(RecvError::Closed) => break,
+      }
+    }
+  });
+}
+
 struct ConnectInfo {
   access_token: String,
   client_version: Version,
@@ -28,20 +28,29 @@ use aws_sdk_s3::types::{
 };
 use mailer::config::MailerSetting;
 use secrecy::{ExposeSecret, Secret};
+use shared_entity::dto::policy_version_dto::HealthStatus;
 use sqlx::{postgres::PgPoolOptions, PgPool};
 use tokio::sync::RwLock;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use appflowy_ai_client::client::AppFlowyAIClient;
 use appflowy_collaborate::actix_ws::server::RealtimeServerActor;
+use appflowy_collaborate::bandwidth::{spawn_bandwidth_flush, RealtimeBandwidth};
 use appflowy_collaborate::collab::cache::CollabCache;
-use appflowy_collaborate::collab::storage::CollabStorageImpl;
+use appflowy_collaborate::collab::compaction::{spawn_collab_compaction_sweeper, CollabCompactor};
+use appflowy_collaborate::collab::migration::{CollabMigrations, CollabMigrator};
+use appflowy_collaborate::collab::recovery::CollabRecovery;
+use appflowy_collaborate::collab::storage::{CollabAccessControlStorage, CollabStorageImpl};
 use appflowy_collaborate::command::{CLCommandReceiver, CLCommandSender};
-use appflowy_collaborate::snapshot::SnapshotControl;
+use appflowy_collaborate::feature_flags::FeatureFlags;
+use appflowy_collaborate::load_shed::{spawn_load_shed_monitor, LoadShedder};
+use appflowy_collaborate::snapshot::{SnapshotControl, SnapshotPolicyResolver};
 use appflowy_collaborate::CollaborationServer;
+use collab_stream::client::CollabRedisStream;
 use collab_stream::metrics::CollabStreamMetrics;
 use collab_stream::stream_router::{StreamRouter, StreamRouterOptions};
 use database::file::s3_client_impl::{AwsS3BucketClientImpl, S3BucketStorage};
+use database::file::BlobVersionPolicy;
 use indexer::collab_indexer::IndexerProvider;
 use indexer::scheduler::{IndexerConfiguration, IndexerScheduler};
 use infra::env_util::get_env_var;
@@ -51,30 +60,57 @@ use snowflake::Snowflake;
 use crate::api::access_request::access_request_scope;
 use crate::api::ai::ai_completion_scope;
 use crate::api::chat::chat_scope;
+use crate::api::collab_admin::collab_admin_scope;
 use crate::api::data_import::data_import_scope;
+use crate::api::fault_injection::fault_injection_scope;
+use crate::api::feature_flag_admin::feature_flag_admin_scope;
 use crate::api::file_storage::file_storage_scope;
+use crate::api::impersonation_admin::impersonation_admin_scope;
+use crate::api::inbound_email::inbound_email_scope;
 use crate::api::metrics::metrics_scope;
+use crate::api::moderation::moderation_scope;
+use crate::api::realtime_admin::realtime_admin_scope;
 use crate::api::search::search_scope;
 use crate::api::server_info::server_info_scope;
+use crate::api::session_admin::session_admin_scope;
 use crate::api::template::template_scope;
+use crate::api::unfurl::unfurl_scope;
+use crate::api::usage_admin::usage_admin_scope;
 use crate::api::user::user_scope;
 use crate::api::workspace::{collab_scope, workspace_scope};
+use crate::api::workspace_provisioning::workspace_provisioning_scope;
 use crate::api::ws::ws_scope;
+use crate::biz::chat::scheduler::AIRequestScheduler;
+use crate::biz::collab::document_find::DocumentTextCache;
+use crate::biz::collab::document_outline::DocumentOutlineCache;
+use crate::biz::collab::row_access::{RowAccessControl, RowAccessRealtimeAccessControl};
 use crate::biz::pg_listener::PgListeners;
+use crate::biz::user::user_session::UserSessionTracker;
+use crate::biz::workspace::blob_access::spawn_blob_access_flush;
 use crate::biz::workspace::publish::{
   PublishedCollabPostgresStore, PublishedCollabS3StoreWithPostgresFallback, PublishedCollabStore,
 };
+use crate::biz::workspace::stats::spawn_workspace_stats_refresher;
+use crate::biz::workspace::usage::spawn_workspace_usage_rollup;
 use crate::config::config::{
   Config, DatabaseSetting, GoTrueSetting, PublishedCollabStorageBackend, S3Setting,
 };
 use crate::mailer::AFCloudMailer;
+use crate::middleware::fault_injection::{
+  FaultInjectionMiddleware, FaultInjector, FAULT_INJECTION_MARKER,
+};
+use crate::middleware::impersonation_mw::ImpersonationMiddleware;
 use crate::middleware::metrics_mw::MetricsMiddleware;
+use crate::middleware::policy_version_mw::PolicyVersionMiddleware;
 use crate::middleware::request_id::RequestIdMiddleware;
+use crate::middleware::timestamp_format_mw::TimestampFormatMiddleware;
+use crate::middleware::user_session_mw::UserSessionMiddleware;
 use crate::state::{AppMetrics, AppState, GoTrueAdmin, UserCache};
 
 pub struct Application {
   port: u16,
   actix_server: Server,
+  realtime_server: CollaborationServer<CollabAccessControlStorage>,
 }
 
 impl Application {
@@ -87,13 +123,21 @@ impl Application {
     let listener = TcpListener::bind(&address)?;
     let port = listener.local_addr().unwrap().port();
     info!("Server started at {}", listener.local_addr().unwrap());
-    let actix_server = run_actix_server(listener, state, config, rt_cmd_recv).await?;
-
-    Ok(Self { port, actix_server })
+    let (actix_server, realtime_server) =
+      run_actix_server(listener, state, config, rt_cmd_recv).await?;
+
+    Ok(Self {
+      port,
+      actix_server,
+      realtime_server,
+    })
   }
 
+  /// Runs the server until it's stopped, then saves the pending updates of the collab groups.
   pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
-    self.actix_server.await
+    self.actix_server.await?;
+    self.realtime_server.stop_all_groups().await;
+    Ok(())
   }
 
   pub fn port(&self) -> u16 {
@@ -106,7 +150,7 @@ pub async fn run_actix_server(
   state: AppState,
   config: Config,
   rt_cmd_recv: CLCommandReceiver,
-) -> Result<Server, Error> {
+) -> Result<(Server, CollaborationServer<CollabAccessControlStorage>), Error> {
   let redis_store = RedisSessionStore::new(config.redis_uri.expose_secret())
     .await
     .map_err(|e| {
@@ -119,6 +163,7 @@ pub async fn run_actix_server(
 
   let storage = state.collab_access_control_storage.clone();
 
+  let realtime_bandwidth = Arc::new(RealtimeBandwidth::default());
   // Initialize metrics that which are registered in the registry.
   let realtime_server = CollaborationServer::<_>::new(
     storage.clone(),
@@ -129,15 +174,66 @@ pub async fn run_actix_server(
     state.redis_connection_manager.clone(),
     Duration::from_secs(config.collab.group_persistence_interval_secs),
     Duration::from_secs(config.collab.group_prune_grace_period_secs),
+    config.collab.folder_compaction_threshold,
+    SnapshotPolicyResolver::new(
+      state.pg_pool.clone(),
+      config.collab.snapshot_policies.clone(),
+    ),
     state.indexer_scheduler.clone(),
+    realtime_bandwidth.clone(),
+    state.collab_recovery.clone(),
+    CollabMigrator::new(
+      state.pg_pool.clone(),
+      CollabMigrations::builtin(),
+      state.metrics.realtime_metrics.clone(),
+    ),
+    state.feature_flags.clone(),
+    state.load_shedder.clone(),
+    config.collab.group_unload.clone(),
+    config.collab.update_rate_limit.clone(),
+    config.collab.size_limits.clone(),
+    config.collab.full_state_sync.clone(),
+    config.collab.write_behind.clone(),
   )
   .await
   .unwrap();
+  spawn_bandwidth_flush(
+    realtime_bandwidth,
+    state.redis_connection_manager.clone(),
+    state.pg_pool.clone(),
+  );
+  spawn_collab_compaction_sweeper(
+    CollabCompactor::new(state.pg_pool.clone(), config.collab.compaction.clone()),
+    state.collab_access_control_storage.clone(),
+    CollabRedisStream::new_with_connection_manager(
+      state.redis_connection_manager.clone(),
+      state.redis_stream_router.clone(),
+    ),
+  );
+  spawn_workspace_stats_refresher(state.pg_pool.clone());
+  spawn_workspace_usage_rollup(state.pg_pool.clone());
+  spawn_blob_access_flush(
+    state.redis_connection_manager.clone(),
+    state.pg_pool.clone(),
+  );
 
-  let realtime_server_actor = Supervisor::start(|_| RealtimeServerActor(realtime_server));
+  let realtime_server_actor = Supervisor::start({
+    let realtime_server = realtime_server.clone();
+    move |_| RealtimeServerActor(realtime_server)
+  });
   let mut server = HttpServer::new(move || {
     App::new()
       .wrap(NormalizePath::trim())
+      .wrap(TimestampFormatMiddleware::new(
+        state.config.application.legacy_timestamps,
+      ))
+      .wrap(FaultInjectionMiddleware::new(state.fault_injector.clone()))
+      .wrap(UserSessionMiddleware::new(state.user_session_tracker.clone()))
+      .wrap(ImpersonationMiddleware::new(state.pg_pool.clone()))
+      .wrap(PolicyVersionMiddleware::new(
+        state.pg_pool.clone(),
+        state.access_control.clone(),
+      ))
        // Middleware is registered for each App, scope, or Resource and executed in opposite order as registration
       .wrap(MetricsMiddleware)
       .wrap(IdentityMiddleware::default())
@@ -157,8 +253,19 @@ pub async fn run_actix_server(
       .service(metrics_scope())
       .service(search_scope())
       .service(template_scope())
+      .service(unfurl_scope())
+      .service(inbound_email_scope())
+      .service(moderation_scope())
+      .service(realtime_admin_scope())
+      .service(usage_admin_scope())
+      .service(collab_admin_scope())
       .service(data_import_scope())
       .service(access_request_scope())
+      .service(fault_injection_scope())
+      .service(feature_flag_admin_scope())
+      .service(session_admin_scope())
+      .service(impersonation_admin_scope())
+      .service(workspace_provisioning_scope())
       .route("/health", web::get().to(health_check))
       .app_data(Data::new(state.metrics.registry.clone()))
       .app_data(Data::new(state.metrics.request_metrics.clone()))
@@ -173,13 +280,17 @@ pub async fn run_actix_server(
 
   server = server.listen(listener)?;
 
-  Ok(server.run())
+  Ok((server.run(), realtime_server))
 }
 
 pub async fn init_state(config: &Config, rt_cmd_tx: CLCommandSender) -> Result<AppState, Error> {
   // Print the feature flags
 
   let metrics = AppMetrics::new();
+  let fault_injector = config.fault_injection.enabled.then(|| {
+    warn!("{} fault injection is enabled", FAULT_INJECTION_MARKER);
+    Arc::new(FaultInjector::default())
+  });
 
   // Postgres
   info!("Preparing to run database migrations...");
@@ -188,17 +299,16 @@ pub async fn init_state(config: &Config, rt_cmd_tx: CLCommandSender) -> Result<A
 
   // Bucket storage
   info!("Setting up S3 bucket...");
-  let s3_client = AwsS3BucketClientImpl::new(
+  let mut s3_client = AwsS3BucketClientImpl::new(
     get_aws_s3_client(&config.s3).await?,
     config.s3.bucket.clone(),
     config.s3.minio_url.clone(),
     config.s3.presigned_url_endpoint.clone(),
-  );
-  let bucket_storage = Arc::new(S3BucketStorage::from_bucket_impl(
-    s3_client.clone(),
-    pg_pool.clone(),
-  ));
-
+  )
+  .with_metrics(metrics.s3_metrics.clone());
+  if let Some(fault_injector) = &fault_injector {
+    s3_client = s3_client.with_operation_delay(fault_injector.clone());
+  }
   // Published Collab Storage
   info!("Setting up Published Collab storage...");
   let published_collab_store: Arc<dyn PublishedCollabStore> =
@@ -234,11 +344,43 @@ pub async fn init_state(config: &Config, rt_cmd_tx: CLCommandSender) -> Result<A
   )
   .await?;
 
+  let blob_version_policy = config
+    .file_storage
+    .enable_blob_versioning
+    .then(|| BlobVersionPolicy {
+      max_versions: config.file_storage.max_blob_versions,
+      max_age_days: (config.file_storage.blob_version_retention_days > 0)
+        .then_some(config.file_storage.blob_version_retention_days),
+    });
+  let bucket_storage = Arc::new(
+    S3BucketStorage::from_bucket_impl(s3_client.clone(), pg_pool.clone())
+      .with_version_policy(blob_version_policy)
+      .with_storage_limit(
+        (config.file_storage.workspace_storage_limit_bytes > 0)
+          .then_some(config.file_storage.workspace_storage_limit_bytes),
+      )
+      .with_usage_cache(redis_conn_manager.clone()),
+  );
+
+  info!("Loading feature flags...");
+  let feature_flags = FeatureFlags::new(
+    pg_pool.clone(),
+    redis::Client::open(config.redis_uri.expose_secret().as_str())?,
+    redis_conn_manager.clone(),
+  )
+  .await?;
+
   info!("Setup AppFlowy AI: {}", config.appflowy_ai.url());
   let appflowy_ai_client = AppFlowyAIClient::new(&config.appflowy_ai.url());
+  let ai_scheduler = Arc::new(AIRequestScheduler::new(
+    config.ai_scheduler.clone(),
+    pg_pool.clone(),
+    metrics.ai_metrics.clone(),
+  ));
   // Pg listeners
   info!("Setting up Pg listeners...");
   let pg_listeners = Arc::new(PgListeners::new(&pg_pool).await?);
+  let user_session_tracker = UserSessionTracker::new(pg_pool.clone(), &pg_listeners);
   // let collab_member_listener = pg_listeners.subscribe_collab_member_change();
 
   info!(
@@ -263,17 +405,20 @@ pub async fn init_state(config: &Config, rt_cmd_tx: CLCommandSender) -> Result<A
     };
   let realtime_access_control: Arc<dyn RealtimeAccessControl> =
     if config.access_control.is_enabled && config.access_control.enable_realtime_access_control {
-      Arc::new(RealtimeCollabAccessControlImpl::new(access_control))
+      Arc::new(RealtimeCollabAccessControlImpl::new(access_control.clone()))
     } else {
       Arc::new(NoOpsRealtimeCollabAccessControlImpl::new())
     };
-  let collab_cache = CollabCache::new(
+  let mut collab_cache = CollabCache::new(
     redis_conn_manager.clone(),
     pg_pool.clone(),
     s3_client.clone(),
     metrics.collab_metrics.clone(),
     config.collab.s3_collab_threshold as usize,
   );
+  if let Some(fault_injector) = &fault_injector {
+    collab_cache = collab_cache.with_operation_delay(fault_injector.clone());
+  }
 
   let collab_storage_access_control = CollabStorageAccessControlImpl {
     collab_access_control: collab_access_control.clone(),
@@ -291,7 +436,16 @@ pub async fn init_state(config: &Config, rt_cmd_tx: CLCommandSender) -> Result<A
     collab_storage_access_control,
     snapshot_control,
     rt_cmd_tx,
+    config.collab.size_limits.clone(),
+  ));
+  let load_shedder = Arc::new(LoadShedder::new(
+    config.collab.load_shedding.clone(),
+    metrics.load_shed_metrics.clone(),
   ));
+  spawn_load_shed_monitor(
+    load_shedder.clone(),
+    Arc::downgrade(&collab_access_control_storage),
+  );
 
   let mailer = get_mailer(&config.mailer).await?;
 
@@ -317,6 +471,13 @@ pub async fn init_state(config: &Config, rt_cmd_tx: CLCommandSender) -> Result<A
     redis_conn_manager.clone(),
   );
 
+  let collab_recovery = CollabRecovery::new(pg_pool.clone());
+  let row_access_control =
+    RowAccessControl::new(pg_pool.clone(), collab_access_control_storage.clone());
+  let realtime_access_control: Arc<dyn RealtimeAccessControl> = Arc::new(
+    RowAccessRealtimeAccessControl::new(realtime_access_control, row_access_control.clone()),
+  );
+
   info!("Application state initialized");
   Ok(AppState {
     pg_pool,
@@ -328,9 +489,11 @@ pub async fn init_state(config: &Config, rt_cmd_tx: CLCommandSender) -> Result<A
     redis_connection_manager: redis_conn_manager,
     collab_cache,
     collab_access_control_storage,
+    collab_recovery,
     collab_access_control,
     workspace_access_control,
     realtime_access_control,
+    row_access_control,
     bucket_storage,
     published_collab_store,
     bucket_client: s3_client,
@@ -339,7 +502,15 @@ pub async fn init_state(config: &Config, rt_cmd_tx: CLCommandSender) -> Result<A
     gotrue_admin,
     mailer,
     ai_client: appflowy_ai_client,
+    ai_scheduler,
     indexer_scheduler,
+    fault_injector,
+    user_session_tracker,
+    feature_flags,
+    access_control,
+    document_outline_cache: DocumentOutlineCache::default(),
+    document_text_cache: DocumentTextCache::default(),
+    load_shedder,
   })
 }
 
@@ -506,6 +677,9 @@ async fn get_gotrue_client(setting: &GoTrueSetting) -> Result<gotrue::api::Clien
   Ok(gotrue_client)
 }
 
-async fn health_check() -> impl Responder {
-  HttpResponse::Ok().body("OK")
+async fn health_check(state: Data<AppState>) -> impl Responder {
+  HttpResponse::Ok().json(HealthStatus {
+    status: "OK".to_string(),
+    policy_version: state.access_control.policy_version(),
+  })
 }
@@ -0,0 +1,233 @@
+//! Replays the update stream of a collab on top of a persisted baseline, and compares the result
+//! with the collab stored in Postgres, to reproduce divergences between the two.
+//!
+//! cargo run --bin collab_replay -- --workspace <id> --object <id> --collab-type <i32> [options]
+//!
+//! Options:
+//!   --snapshot <sid>        start from the snapshot instead of an empty collab
+//!   --baseline <file>       start from an `EncodedCollab` file instead of an empty collab
+//!   --from <message id>     first stream entry to replay, included
+//!   --to <message id>       last stream entry to replay, included
+//!   --entries <file>        replay the entries of a dump file instead of the Redis stream
+//!   --dump <file>           write the entries read from the Redis stream to a dump file
+//!   --export <file>         write the replayed collab as an `EncodedCollab` file
+//!
+//! The tool never writes to Postgres or Redis: the Postgres sessions are read-only and only
+//! `XRANGE` is used on Redis.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use appflowy_cloud::biz::collab::replay::{
+  open_collab, replay_entries, Divergence, ReplayEntry, ReplayReport,
+};
+use collab::core::origin::CollabOrigin;
+use collab::entity::EncodedCollab;
+use collab::preclude::Collab;
+use collab_entity::CollabType;
+use collab_stream::client::CollabRedisStream;
+use collab_stream::metrics::CollabStreamMetrics;
+use collab_stream::model::MessageId;
+use database::collab::{select_blob_from_af_collab, select_snapshot};
+use prometheus_client::registry::Registry;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::{Executor, PgPool};
+use yrs::updates::encoder::Encode;
+use yrs::{ReadTxn, StateVector, Transact};
+
+struct Args {
+  workspace_id: String,
+  object_id: String,
+  collab_type: CollabType,
+  snapshot: Option<i64>,
+  baseline: Option<PathBuf>,
+  from: Option<MessageId>,
+  to: Option<MessageId>,
+  entries: Option<PathBuf>,
+  dump: Option<PathBuf>,
+  export: Option<PathBuf>,
+}
+
+impl Args {
+  fn parse() -> Result<Self> {
+    let mut workspace_id = None;
+    let mut object_id = None;
+    let mut collab_type = None;
+    let mut args = Args {
+      workspace_id: String::new(),
+      object_id: String::new(),
+      collab_type: CollabType::Unknown,
+      snapshot: None,
+      baseline: None,
+      from: None,
+      to: None,
+      entries: None,
+      dump: None,
+      export: None,
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(flag) = iter.next() {
+      let value = iter
+        .next()
+        .ok_or_else(|| anyhow!("missing value for {}", flag))?;
+      match flag.as_str() {
+        "--workspace" => workspace_id = Some(value),
+        "--object" => object_id = Some(value),
+        "--collab-type" => collab_type = Some(CollabType::from(value.parse::<i32>()?)),
+        "--snapshot" => args.snapshot = Some(value.parse()?),
+        "--baseline" => args.baseline = Some(value.into()),
+        "--from" => args.from = Some(MessageId::try_from(value)?),
+        "--to" => args.to = Some(MessageId::try_from(value)?),
+        "--entries" => args.entries = Some(value.into()),
+        "--dump" => args.dump = Some(value.into()),
+        "--export" => args.export = Some(value.into()),
+        _ => bail!("unknown option {}", flag),
+      }
+    }
+    if args.snapshot.is_some() && args.baseline.is_some() {
+      bail!("--snapshot and --baseline can't be used together");
+    }
+    args.workspace_id = workspace_id.context("--workspace is required")?;
+    args.object_id = object_id.context("--object is required")?;
+    args.collab_type = collab_type.context("--collab-type is required")?;
+    Ok(args)
+  }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+  dotenvy::dotenv().ok();
+  let args = Args::parse()?;
+  let pg_pool = read_only_pg_pool().await?;
+
+  let mut baseline = load_baseline(&pg_pool, &args).await?;
+  let entries = match &args.entries {
+    Some(path) => serde_json::from_slice(&std::fs::read(path)?)?,
+    None => read_stream_entries(&args).await?,
+  };
+  if let Some(path) = &args.dump {
+    std::fs::write(path, serde_json::to_vec(&entries)?)?;
+    println!("dumped {} entries to {}", entries.len(), path.display());
+  }
+
+  let blob = select_blob_from_af_collab(&pg_pool, &args.collab_type, &args.object_id)
+    .await
+    .context("stored collab not found")?;
+  let live = open_collab(&args.object_id, &EncodedCollab::decode_from_bytes(&blob)?)?;
+
+  let report = replay_entries(&mut baseline, entries, &live);
+  print_report(&report);
+
+  if let Some(path) = &args.export {
+    let txn = baseline.transact();
+    let encoded_collab = EncodedCollab::new_v1(
+      txn.state_vector().encode_v1(),
+      txn.encode_state_as_update_v1(&StateVector::default()),
+    );
+    std::fs::write(path, encoded_collab.encode_to_bytes()?)?;
+    println!("exported the replayed collab to {}", path.display());
+  }
+  if !report.is_consistent() {
+    std::process::exit(1);
+  }
+  Ok(())
+}
+
+/// Every session of the pool is read-only, so nothing done by the tool can change the stored
+/// collabs, whatever the queries.
+async fn read_only_pg_pool() -> Result<PgPool> {
+  let url = std::env::var("APPFLOWY_DATABASE_URL").context("APPFLOWY_DATABASE_URL is not set")?;
+  let pg_pool = PgPoolOptions::new()
+    .max_connections(1)
+    .after_connect(|conn, _| {
+      Box::pin(async move {
+        conn
+          .execute("SET SESSION CHARACTERISTICS AS TRANSACTION READ ONLY")
+          .await?;
+        Ok(())
+      })
+    })
+    .connect(&url)
+    .await?;
+  Ok(pg_pool)
+}
+
+async fn load_baseline(pg_pool: &PgPool, args: &Args) -> Result<Collab> {
+  let encoded_collab = match (&args.baseline, args.snapshot) {
+    (Some(path), _) => EncodedCollab::decode_from_bytes(&std::fs::read(path)?)?,
+    (None, Some(snapshot_id)) => {
+      let row = select_snapshot(pg_pool, &args.workspace_id, &args.object_id, &snapshot_id)
+        .await?
+        .ok_or_else(|| anyhow!("snapshot {} not found", snapshot_id))?;
+      EncodedCollab::decode_from_bytes(&row.blob)?
+    },
+    (None, None) => {
+      return Ok(Collab::new_with_origin(
+        CollabOrigin::Server,
+        &args.object_id,
+        vec![],
+        false,
+      ))
+    },
+  };
+  Ok(open_collab(&args.object_id, &encoded_collab)?)
+}
+
+async fn read_stream_entries(args: &Args) -> Result<Vec<ReplayEntry>> {
+  let redis_uri = std::env::var("APPFLOWY_REDIS_URI").context("APPFLOWY_REDIS_URI is not set")?;
+  let redis_client = redis::Client::open(redis_uri)?;
+  let metrics = CollabStreamMetrics::register(&mut Registry::default());
+  let stream = CollabRedisStream::new(redis_client, Arc::new(metrics)).await?;
+  let entries = stream
+    .collab_updates_range(&args.workspace_id, &args.object_id, args.from, args.to)
+    .await?
+    .into_iter()
+    .map(|(message_id, update)| ReplayEntry::from_stream(message_id, update))
+    .collect();
+  Ok(entries)
+}
+
+fn print_report(report: &ReplayReport) {
+  println!("applied {} entries", report.applied);
+  for message_id in &report.resets {
+    println!(
+      "entry {} reset the collab, the entries before it belong to the previous copy",
+      message_id
+    );
+  }
+  match &report.divergence {
+    None => println!("no diverging entry"),
+    Some(Divergence::Invalid { message_id, error }) => {
+      println!(
+        "first diverging entry {}: invalid update: {}",
+        message_id, error
+      )
+    },
+    Some(Divergence::Pending { message_id }) => println!(
+      "first diverging entry {}: depends on updates missing from the replay",
+      message_id
+    ),
+    Some(Divergence::Ahead {
+      message_id,
+      client_ids,
+    }) => println!(
+      "first diverging entry {}: contains updates of clients {:?} missing from the stored collab",
+      message_id, client_ids
+    ),
+  }
+  if !report.missing_client_ids.is_empty() {
+    println!(
+      "the stored collab contains updates of clients {:?} missing from the replay",
+      report.missing_client_ids
+    );
+  }
+  println!(
+    "replayed: state vector {} content {}",
+    report.replayed.state_vector_hash, report.replayed.content_hash
+  );
+  println!(
+    "stored:   state vector {} content {}",
+    report.live.state_vector_hash, report.live.content_hash
+  );
+}
@@ -1,6 +1,15 @@
+use prometheus_client::encoding::EncodeLabelSet;
 use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
 
-#[derive(Default, Clone)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct TierLabel {
+  pub tier: String,
+}
+
+#[derive(Clone)]
 pub struct AIMetrics {
   total_stream_count: Counter,
   failed_stream_count: Counter,
@@ -8,6 +17,32 @@ pub struct AIMetrics {
   total_completion_count: Counter,
   total_summary_row_count: Counter,
   total_translate_row_count: Counter,
+  scheduler_queue_depth: Family<TierLabel, Gauge>,
+  scheduler_wait_seconds: Family<TierLabel, Histogram, fn() -> Histogram>,
+  scheduler_shed_count: Family<TierLabel, Counter>,
+  scheduler_rate_limited_count: Counter,
+  scheduler_concurrency_limit: Gauge,
+}
+
+impl Default for AIMetrics {
+  fn default() -> Self {
+    Self {
+      total_stream_count: Default::default(),
+      failed_stream_count: Default::default(),
+      stream_image_count: Default::default(),
+      total_completion_count: Default::default(),
+      total_summary_row_count: Default::default(),
+      total_translate_row_count: Default::default(),
+      scheduler_queue_depth: Default::default(),
+      scheduler_wait_seconds: Family::new_with_constructor(|| {
+        // 10ms .. ~80s
+        Histogram::new(exponential_buckets(0.01, 2.0, 14))
+      }),
+      scheduler_shed_count: Default::default(),
+      scheduler_rate_limited_count: Default::default(),
+      scheduler_concurrency_limit: Default::default(),
+    }
+  }
 }
 
 impl AIMetrics {
@@ -46,6 +81,31 @@ impl AIMetrics {
       "Total count of translation rows processed",
       metrics.total_translate_row_count.clone(),
     );
+    realtime_registry.register(
+      "scheduler_queue_depth",
+      "Number of AI requests waiting in the scheduler queue",
+      metrics.scheduler_queue_depth.clone(),
+    );
+    realtime_registry.register(
+      "scheduler_wait_seconds",
+      "Time AI requests spent in the scheduler queue before being dispatched",
+      metrics.scheduler_wait_seconds.clone(),
+    );
+    realtime_registry.register(
+      "scheduler_shed_count",
+      "Total count of AI requests rejected after waiting past the queue deadline",
+      metrics.scheduler_shed_count.clone(),
+    );
+    realtime_registry.register(
+      "scheduler_rate_limited_count",
+      "Total count of AI requests rate limited by the provider",
+      metrics.scheduler_rate_limited_count.clone(),
+    );
+    realtime_registry.register(
+      "scheduler_concurrency_limit",
+      "Current adaptive concurrency limit of AI requests",
+      metrics.scheduler_concurrency_limit.clone(),
+    );
 
     metrics
   }
@@ -73,4 +133,39 @@ impl AIMetrics {
   pub fn record_total_translate_row_count(&self, count: u64) {
     self.total_translate_row_count.inc_by(count);
   }
+
+  pub fn record_scheduler_queue_depth(&self, tier: &str, delta: i64) {
+    self
+      .scheduler_queue_depth
+      .get_or_create(&TierLabel {
+        tier: tier.to_string(),
+      })
+      .inc_by(delta);
+  }
+
+  pub fn record_scheduler_wait(&self, tier: &str, secs: f64) {
+    self
+      .scheduler_wait_seconds
+      .get_or_create(&TierLabel {
+        tier: tier.to_string(),
+      })
+      .observe(secs);
+  }
+
+  pub fn record_scheduler_shed(&self, tier: &str) {
+    self
+      .scheduler_shed_count
+      .get_or_create(&TierLabel {
+        tier: tier.to_string(),
+      })
+      .inc();
+  }
+
+  pub fn record_scheduler_rate_limited(&self) {
+    self.scheduler_rate_limited_count.inc();
+  }
+
+  pub fn record_scheduler_concurrency_limit(&self, limit: i64) {
+    self.scheduler_concurrency_limit.set(limit);
+  }
 }
@@ -1,2 +1,4 @@
 pub mod metrics;
 pub mod ops;
+pub mod scheduler;
+pub mod share;
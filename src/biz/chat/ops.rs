@@ -10,6 +10,7 @@ use database::chat::chat_ops::{
   insert_answer_message_with_transaction, insert_chat, insert_question_message,
   select_chat_message_matching_reply_message_id, select_chat_messages,
 };
+use database::chat::chat_share::update_chat_owner;
 use futures::stream::Stream;
 use serde_json::json;
 use shared_entity::dto::chat_dto::{
@@ -17,19 +18,26 @@ use shared_entity::dto::chat_dto::{
   CreateChatParams, GetChatMessageParams, RepeatedChatMessage, UpdateChatMessageContentParams,
 };
 use sqlx::PgPool;
+use std::ops::DerefMut;
 use tracing::{error, info, trace};
+use uuid::Uuid;
 
 use validator::Validate;
 
 pub(crate) async fn create_chat(
   pg_pool: &PgPool,
+  uid: i64,
   params: CreateChatParams,
   workspace_id: &str,
 ) -> Result<(), AppError> {
   params.validate()?;
   trace!("[Chat] create chat {:?}", params);
 
-  insert_chat(pg_pool, workspace_id, params).await?;
+  let chat_id = Uuid::parse_str(&params.chat_id)?;
+  let mut txn = pg_pool.begin().await?;
+  insert_chat(txn.deref_mut(), workspace_id, params).await?;
+  update_chat_owner(txn.deref_mut(), &chat_id, uid).await?;
+  txn.commit().await?;
   Ok(())
 }
 
@@ -0,0 +1,209 @@
+use app_error::AppError;
+use database::chat::chat_share::{
+  select_chat_access, select_shared_chats, transfer_chats_of_deleted_user, update_chat_share,
+};
+use database::pg_row::AFChatAccessRow;
+use shared_entity::dto::chat_dto::{
+  ChatShareScope, RepeatedSharedChat, ShareChatParams, SharedChat,
+};
+use sqlx::PgPool;
+use tracing::info;
+use uuid::Uuid;
+
+/// What a user can do with a chat. The levels are ordered, each one allowing what the previous
+/// ones allow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChatAccess {
+  /// Read the conversation.
+  Read,
+  /// Ask questions and stream their answers.
+  Ask,
+  /// Change the settings of the chat, share or delete it.
+  Owner,
+}
+
+/// Chats created before they had an owner stay accessible to every member of their workspace.
+pub fn chat_access(row: &AFChatAccessRow, uid: i64) -> Option<ChatAccess> {
+  let owner_uid = match row.owner_uid {
+    None => return row.is_workspace_member.then_some(ChatAccess::Owner),
+    Some(owner_uid) => owner_uid,
+  };
+  if owner_uid == uid {
+    return Some(ChatAccess::Owner);
+  }
+  let shared_with_user = match ChatShareScope::from(row.share_scope) {
+    ChatShareScope::Private => false,
+    ChatShareScope::Workspace => row.is_workspace_member,
+    ChatShareScope::Members => row.is_workspace_member && row.is_share_member,
+  };
+  if !shared_with_user {
+    return None;
+  }
+  if row.collaborative {
+    Some(ChatAccess::Ask)
+  } else {
+    Some(ChatAccess::Read)
+  }
+}
+
+pub async fn enforce_chat_access(
+  pg_pool: &PgPool,
+  chat_id: &str,
+  uid: i64,
+  required: ChatAccess,
+) -> Result<(), AppError> {
+  let chat_id = Uuid::parse_str(chat_id)?;
+  let row = select_chat_access(pg_pool, &chat_id, uid)
+    .await?
+    .ok_or_else(|| AppError::RecordNotFound(format!("chat {} not found", chat_id)))?;
+  match chat_access(&row, uid) {
+    Some(access) if access >= required => Ok(()),
+    _ => Err(AppError::NotEnoughPermissions),
+  }
+}
+
+pub async fn share_chat(
+  pg_pool: &PgPool,
+  chat_id: &str,
+  params: ShareChatParams,
+) -> Result<(), AppError> {
+  let chat_id = Uuid::parse_str(chat_id)?;
+  let member_emails = match params.scope {
+    ChatShareScope::Members => {
+      if params.member_emails.is_empty() {
+        return Err(AppError::InvalidRequest(
+          "member_emails can't be empty when sharing a chat with members".to_string(),
+        ));
+      }
+      normalize_member_emails(&params.member_emails)
+    },
+    _ => vec![],
+  };
+
+  let mut txn = pg_pool.begin().await?;
+  let inserted = update_chat_share(
+    &mut txn,
+    &chat_id,
+    params.scope as i16,
+    params.collaborative,
+    &member_emails,
+  )
+  .await?;
+  if inserted as usize != member_emails.len() {
+    return Err(AppError::InvalidRequest(
+      "the chat can only be shared with members of its workspace".to_string(),
+    ));
+  }
+  txn.commit().await?;
+  info!("[Chat] shared chat {} with {:?}", chat_id, params.scope);
+  Ok(())
+}
+
+/// The emails are matched case insensitively, so each member is only listed once. Otherwise a
+/// member listed twice would be counted as an email which isn't a member of the workspace.
+fn normalize_member_emails(member_emails: &[String]) -> Vec<String> {
+  let mut member_emails = member_emails
+    .iter()
+    .map(|email| email.trim().to_lowercase())
+    .collect::<Vec<_>>();
+  member_emails.sort();
+  member_emails.dedup();
+  member_emails
+}
+
+pub async fn unshare_chat(pg_pool: &PgPool, chat_id: &str) -> Result<(), AppError> {
+  let chat_id = Uuid::parse_str(chat_id)?;
+  let mut txn = pg_pool.begin().await?;
+  update_chat_share(
+    &mut txn,
+    &chat_id,
+    ChatShareScope::Private as i16,
+    false,
+    &[],
+  )
+  .await?;
+  txn.commit().await?;
+  Ok(())
+}
+
+pub async fn list_shared_chats(
+  pg_pool: &PgPool,
+  workspace_id: &str,
+  uid: i64,
+) -> Result<RepeatedSharedChat, AppError> {
+  let workspace_id = Uuid::parse_str(workspace_id)?;
+  let items = select_shared_chats(pg_pool, &workspace_id, uid)
+    .await?
+    .into_iter()
+    .map(|row| SharedChat {
+      chat_id: row.chat_id.to_string(),
+      name: row.name,
+      owner_uid: row.owner_uid,
+      owner_name: row.owner_name,
+      scope: ChatShareScope::from(row.share_scope),
+      collaborative: row.collaborative,
+      shared_at: row.shared_at,
+    })
+    .collect();
+  Ok(RepeatedSharedChat { items })
+}
+
+pub async fn transfer_chats_before_user_delete(pg_pool: &PgPool, uid: i64) -> Result<(), AppError> {
+  let mut txn = pg_pool.begin().await?;
+  let transferred = transfer_chats_of_deleted_user(&mut txn, uid).await?;
+  txn.commit().await?;
+  info!(
+    "[Chat] transferred {} shared chats of user {} to the workspace owners",
+    transferred, uid
+  );
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn access_row(owner_uid: Option<i64>, share_scope: ChatShareScope) -> AFChatAccessRow {
+    AFChatAccessRow {
+      workspace_id: Uuid::new_v4(),
+      owner_uid,
+      share_scope: share_scope as i16,
+      collaborative: false,
+      is_workspace_member: true,
+      is_share_member: false,
+    }
+  }
+
+  #[test]
+  fn chat_access_follows_share_scope_test() {
+    let row = access_row(Some(1), ChatShareScope::Private);
+    assert_eq!(chat_access(&row, 1), Some(ChatAccess::Owner));
+    assert_eq!(chat_access(&row, 2), None);
+
+    let mut row = access_row(Some(1), ChatShareScope::Workspace);
+    assert_eq!(chat_access(&row, 2), Some(ChatAccess::Read));
+    row.collaborative = true;
+    assert_eq!(chat_access(&row, 2), Some(ChatAccess::Ask));
+    row.is_workspace_member = false;
+    assert_eq!(chat_access(&row, 2), None);
+
+    let mut row = access_row(Some(1), ChatShareScope::Members);
+    assert_eq!(chat_access(&row, 2), None);
+    row.is_share_member = true;
+    assert_eq!(chat_access(&row, 2), Some(ChatAccess::Read));
+
+    // chats created before they had an owner
+    let row = access_row(None, ChatShareScope::Private);
+    assert_eq!(chat_access(&row, 2), Some(ChatAccess::Owner));
+  }
+
+  #[test]
+  fn member_emails_are_listed_once_test() {
+    let emails = normalize_member_emails(&[
+      "Reader@AppFlowy.io".to_string(),
+      "reader@appflowy.io ".to_string(),
+      "other@appflowy.io".to_string(),
+    ]);
+    assert_eq!(emails, vec!["other@appflowy.io", "reader@appflowy.io"]);
+  }
+}
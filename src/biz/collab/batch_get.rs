@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use app_error::AppError;
+use appflowy_collaborate::collab::recovery::{CollabRecovery, DetectedOn};
+use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
+use database::collab::GetCollabOrigin;
+use database_entity::dto::{QueryCollab, QueryCollabParams};
+use futures_util::{stream, StreamExt};
+use shared_entity::dto::workspace_dto::{BatchGetCollabItem, CollabResponse};
+
+use crate::biz::collab::row_access::RowAccessControl;
+
+/// The most collabs which can be fetched with one request.
+pub const MAX_BATCH_GET_COLLAB: usize = 50;
+/// Number of collabs read at the same time.
+const BATCH_GET_COLLAB_CONCURRENCY: usize = 8;
+
+/// Fetches each collab as the single collab endpoint does, checking the access of the user to
+/// each one. A collab which can't be read, such as a missing one, gets a failed item instead of
+/// failing the whole batch.
+pub async fn batch_get_collab_for_user(
+  collab_recovery: &CollabRecovery,
+  collab_storage: &CollabAccessControlStorage,
+  row_access_control: &RowAccessControl,
+  uid: i64,
+  workspace_id: &str,
+  queries: Vec<QueryCollab>,
+) -> Result<HashMap<String, BatchGetCollabItem>, AppError> {
+  if queries.len() > MAX_BATCH_GET_COLLAB {
+    return Err(AppError::InvalidRequest(format!(
+      "At most {} collabs can be fetched at once, got {}",
+      MAX_BATCH_GET_COLLAB,
+      queries.len()
+    )));
+  }
+
+  let items = stream::iter(queries)
+    .map(|query| async move {
+      let object_id = query.object_id.clone();
+      let item = match get_collab_for_user(
+        collab_recovery,
+        collab_storage,
+        row_access_control,
+        uid,
+        workspace_id,
+        query,
+      )
+      .await
+      {
+        Ok(collab) => BatchGetCollabItem::Success(collab),
+        Err(err) => BatchGetCollabItem::Failed {
+          code: err.code(),
+          message: err.to_string(),
+        },
+      };
+      (object_id, item)
+    })
+    .buffer_unordered(BATCH_GET_COLLAB_CONCURRENCY)
+    .collect()
+    .await;
+  Ok(items)
+}
+
+async fn get_collab_for_user(
+  collab_recovery: &CollabRecovery,
+  collab_storage: &CollabAccessControlStorage,
+  row_access_control: &RowAccessControl,
+  uid: i64,
+  workspace_id: &str,
+  query: QueryCollab,
+) -> Result<CollabResponse, AppError> {
+  let object_id = query.object_id.clone();
+  let collab = collab_recovery
+    .get_encode_collab(
+      collab_storage,
+      GetCollabOrigin::User { uid },
+      QueryCollabParams::new(&object_id, query.collab_type, workspace_id),
+      true,
+      DetectedOn::Http,
+    )
+    .await?;
+  let encode_collab = row_access_control
+    .project_collab(workspace_id, uid, &object_id, collab.encoded_collab)
+    .await?;
+  Ok(CollabResponse {
+    encode_collab,
+    object_id,
+    recovered_from: collab.recovered_from,
+  })
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use anyhow::Context;
+use app_error::AppError;
+use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
+use appflowy_collaborate::collab::validator::{check_collab_structure, CollabSizeLimits};
+use appflowy_collaborate::feature_flags::{FeatureFlags, STRICT_COLLAB_VALIDATION};
+use appflowy_collaborate::CollabMetrics;
+use bytes::Bytes;
+use collab_rt_protocol::collab_from_encode_collab;
+use database::collab::{duplicate_object_ids, CollabStorage};
+use database::outbox::{insert_outbox_entry, OutboxTopic};
+use database_entity::dto::CollabParams;
+use indexer::scheduler::{IndexerScheduler, UnindexedCollabTask, UnindexedData};
+use shared_entity::dto::workspace_dto::{BulkCreateCollabItem, BulkCreateCollabResult};
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+use workspace_template::collab_registry::collab_spec;
+
+use crate::biz::collab::mode::get_collab_mode;
+
+/// The most collabs which can be created with one request.
+pub const MAX_BULK_CREATE_COLLAB: usize = 500;
+
+/// Creates the collabs in a single transaction, checking each one as the single collab endpoint
+/// does. When any of them is invalid, none is created: the invalid ones get a failed result and
+/// the others [BulkCreateCollabResult::NotCreated].
+#[allow(clippy::too_many_arguments)]
+pub async fn bulk_create_collab_for_user(
+  pg_pool: &PgPool,
+  collab_storage: &CollabAccessControlStorage,
+  indexer_scheduler: &IndexerScheduler,
+  size_limits: &CollabSizeLimits,
+  feature_flags: &FeatureFlags,
+  metrics: &CollabMetrics,
+  uid: i64,
+  workspace_id: &Uuid,
+  items: Vec<BulkCreateCollabItem>,
+) -> Result<HashMap<String, BulkCreateCollabResult>, AppError> {
+  if items.is_empty() || items.len() > MAX_BULK_CREATE_COLLAB {
+    return Err(AppError::InvalidRequest(format!(
+      "Between 1 and {} collabs can be created at once, got {}",
+      MAX_BULK_CREATE_COLLAB,
+      items.len()
+    )));
+  }
+  let duplicates = duplicate_object_ids(items.iter().map(|item| item.object_id.as_str()));
+  if !duplicates.is_empty() {
+    return Err(AppError::InvalidRequest(format!(
+      "Duplicate object ids in the batch: {}",
+      duplicates.join(", ")
+    )));
+  }
+
+  let workspace_id_str = workspace_id.to_string();
+  let strict = feature_flags.enabled(STRICT_COLLAB_VALIDATION, &workspace_id_str);
+  let can_index = indexer_scheduler
+    .can_index_workspace(&workspace_id_str)
+    .await?;
+
+  let mut results = HashMap::with_capacity(items.len());
+  let mut params_list = Vec::with_capacity(items.len());
+  let mut pending_indexes = vec![];
+  for item in items {
+    let object_id = item.object_id.clone();
+    match prepare_new_collab(
+      pg_pool,
+      indexer_scheduler,
+      size_limits,
+      strict,
+      can_index,
+      workspace_id,
+      item,
+    )
+    .await
+    {
+      Ok((params, pending_index)) => {
+        results.insert(object_id, BulkCreateCollabResult::Created);
+        params_list.push(params);
+        pending_indexes.extend(pending_index);
+      },
+      Err(err) => {
+        results.insert(
+          object_id,
+          BulkCreateCollabResult::Failed {
+            code: err.code(),
+            message: err.to_string(),
+          },
+        );
+      },
+    }
+  }
+  if params_list.len() < results.len() {
+    for result in results.values_mut() {
+      if *result == BulkCreateCollabResult::Created {
+        *result = BulkCreateCollabResult::NotCreated;
+      }
+    }
+    return Ok(results);
+  }
+
+  let mut transaction = pg_pool
+    .begin()
+    .await
+    .context("acquire transaction to create collabs in bulk")?;
+  let start = Instant::now();
+  // indexed by the appflowy worker once the collabs are committed
+  for pending in &pending_indexes {
+    insert_outbox_entry(&mut transaction, OutboxTopic::IndexCollab, pending).await?;
+  }
+  collab_storage
+    .batch_insert_new_collab_with_transaction(
+      &workspace_id_str,
+      &uid,
+      params_list,
+      &mut transaction,
+    )
+    .await?;
+  transaction
+    .commit()
+    .await
+    .context("fail to commit the transaction to create collabs in bulk")?;
+  metrics.observe_pg_tx(start.elapsed());
+  Ok(results)
+}
+
+/// Checks a new collab, and returns it along with its index task when its text can be indexed.
+async fn prepare_new_collab(
+  pg_pool: &PgPool,
+  indexer_scheduler: &IndexerScheduler,
+  size_limits: &CollabSizeLimits,
+  strict: bool,
+  can_index: bool,
+  workspace_id: &Uuid,
+  item: BulkCreateCollabItem,
+) -> Result<(CollabParams, Option<UnindexedCollabTask>), AppError> {
+  let workspace_id_str = workspace_id.to_string();
+  let params = CollabParams {
+    object_id: item.object_id,
+    collab_type: item.collab_type,
+    encoded_collab_v1: Bytes::from(item.encoded_collab),
+  };
+  params.validate()?;
+  if params.object_id == workspace_id_str {
+    return Err(AppError::InvalidRequest(
+      "object_id cannot be the same as workspace_id".to_string(),
+    ));
+  }
+
+  let mode = get_collab_mode(pg_pool, &params.object_id).await?;
+  let spec = collab_spec(&params.collab_type, mode.is_opaque());
+  size_limits.check_doc_state(spec, params.encoded_collab_v1.len())?;
+  // the content of an opaque collab is encrypted: only its size can be checked
+  if spec.is_opaque() {
+    return Ok((params, None));
+  }
+  let collab = collab_from_encode_collab(&params.object_id, &params.encoded_collab_v1)
+    .await
+    .map_err(|err| {
+      AppError::NoRequiredData(format!(
+        "Failed to create collab from encoded collab: {}",
+        err
+      ))
+    })?;
+  spec.validate(&collab).map_err(|err| {
+    AppError::NoRequiredData(format!(
+      "collab doc state is not correct:{},{}",
+      params.object_id, err
+    ))
+  })?;
+  check_collab_structure(spec, &workspace_id_str, &params.object_id, &collab, strict)?;
+
+  let pending_index = if can_index && indexer_scheduler.is_indexing_enabled(&params.collab_type) {
+    spec.extract_text(&collab).map(|text| {
+      UnindexedCollabTask::new(
+        *workspace_id,
+        params.object_id.clone(),
+        params.collab_type.clone(),
+        UnindexedData::Text(text),
+      )
+    })
+  } else {
+    None
+  };
+  Ok((params, pending_index))
+}
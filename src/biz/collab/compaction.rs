@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use app_error::AppError;
+use appflowy_collaborate::collab::compaction::{
+  CollabCompactionConfig, CollabCompactor, CompactionOutcome,
+};
+use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
+use collab_stream::client::CollabRedisStream;
+use database::collab_compaction::select_collab_compaction_candidate;
+use shared_entity::dto::collab_compaction_dto::CollabCompactionResult;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Replaces the stored state of the collab by a compacted copy, keeping the previous state as a
+/// snapshot. Unless `skip_connected` is set, the connected clients are made to load the collab
+/// again.
+pub async fn compact_collab_history(
+  pg_pool: &PgPool,
+  collab_storage: Arc<CollabAccessControlStorage>,
+  collab_redis_stream: Arc<CollabRedisStream>,
+  config: &CollabCompactionConfig,
+  workspace_id: &Uuid,
+  object_id: &str,
+  skip_connected: bool,
+) -> Result<CollabCompactionResult, AppError> {
+  let row = select_collab_compaction_candidate(pg_pool, workspace_id, object_id)
+    .await?
+    .ok_or_else(|| {
+      AppError::RecordNotFound(format!(
+        "collab {} not found in workspace {}",
+        object_id, workspace_id
+      ))
+    })?;
+  let outcome = CollabCompactor::new(pg_pool.clone(), config.clone())
+    .compact(collab_storage, collab_redis_stream, &row, skip_connected)
+    .await?;
+  Ok(compaction_result(outcome))
+}
+
+fn compaction_result(outcome: CompactionOutcome) -> CollabCompactionResult {
+  CollabCompactionResult {
+    compacted: outcome.skipped.is_none(),
+    skipped_reason: outcome.skipped.map(|reason| reason.as_str().to_string()),
+    snapshot_id: outcome.snapshot_id,
+    doc_state_len: outcome.doc_state_len,
+    compacted_len: outcome.compacted_len,
+  }
+}
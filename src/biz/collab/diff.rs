@@ -0,0 +1,147 @@
+use anyhow::anyhow;
+use app_error::AppError;
+use collab::core::collab::DataSource;
+use collab::core::origin::CollabOrigin;
+use collab::entity::{EncodedCollab, EncoderVersion};
+use collab::preclude::Collab;
+use sha2::{Digest, Sha256};
+use shared_entity::dto::workspace_dto::{CollabDiffParams, CollabDiffResponse};
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+use yrs::{ReadTxn, StateVector, Transact};
+
+/// Hash of the state of the collab: its state vector and its delete set. Deleting content does not
+/// advance the state vector, so two copies which only differ by a deletion are told apart by
+/// their delete sets. See [hash_collab_state].
+pub fn state_vector_hash(
+  object_id: &str,
+  encode_collab: &EncodedCollab,
+) -> Result<String, AppError> {
+  let data_source = match encode_collab.version {
+    EncoderVersion::V1 => DataSource::DocStateV1(encode_collab.doc_state.to_vec()),
+    EncoderVersion::V2 => DataSource::DocStateV2(encode_collab.doc_state.to_vec()),
+  };
+  let collab = Collab::new_with_source(CollabOrigin::Server, object_id, data_source, vec![], false)
+    .map_err(|err| AppError::Internal(anyhow!("Failed to open collab: {}", err)))?;
+  let hash = hash_collab_state(&collab.transact());
+  Ok(hash)
+}
+
+/// The entries are sorted before hashing because neither the state vector nor the delete set
+/// are encoded in a canonical order. A collab without deletions hashes like its state vector,
+/// see [hash_state_vector].
+pub fn hash_collab_state<T: ReadTxn>(txn: &T) -> String {
+  let snapshot = txn.snapshot();
+  let mut hasher = Sha256::new();
+  update_with_clocks(&mut hasher, &snapshot.state_map);
+  let mut deleted: Vec<(u64, u32, u32)> = snapshot
+    .delete_set
+    .iter()
+    .flat_map(|(client_id, ranges)| {
+      ranges
+        .iter()
+        .map(move |range| (*client_id, range.start, range.end))
+    })
+    .collect();
+  deleted.sort_unstable();
+  for (client_id, start, end) in deleted {
+    hasher.update(client_id.to_be_bytes());
+    hasher.update(start.to_be_bytes());
+    hasher.update(end.to_be_bytes());
+  }
+  format!("{:x}", hasher.finalize())
+}
+
+pub fn hash_state_vector(state_vector: &StateVector) -> String {
+  let mut hasher = Sha256::new();
+  update_with_clocks(&mut hasher, state_vector);
+  format!("{:x}", hasher.finalize())
+}
+
+fn update_with_clocks(hasher: &mut Sha256, state_vector: &StateVector) {
+  let mut clocks: Vec<(u64, u32)> = state_vector
+    .iter()
+    .map(|(client_id, clock)| (*client_id, *clock))
+    .collect();
+  clocks.sort_unstable();
+  for (client_id, clock) in clocks {
+    hasher.update(client_id.to_be_bytes());
+    hasher.update(clock.to_be_bytes());
+  }
+}
+
+/// Compare the copy of the collab held by the client with the server copy.
+///
+/// When the hashes match only the hash is returned. Otherwise the server copy is returned, as an
+/// update relative to the client state vector if the client sent one.
+pub fn diff_encode_collab(
+  object_id: &str,
+  encode_collab: EncodedCollab,
+  params: CollabDiffParams,
+) -> Result<CollabDiffResponse, AppError> {
+  let state_vector_hash = state_vector_hash(object_id, &encode_collab)?;
+  if params.state_vector_hash.as_deref() == Some(state_vector_hash.as_str()) {
+    return Ok(CollabDiffResponse {
+      object_id: object_id.to_string(),
+      state_vector_hash,
+      encode_collab: None,
+      is_diff: false,
+      recovered_from: None,
+    });
+  }
+
+  let client_state_vector = match params.state_vector {
+    Some(state_vector) => Some(StateVector::decode_v1(&state_vector).map_err(|err| {
+      AppError::InvalidRequest(format!("Failed to decode state vector: {}", err))
+    })?),
+    None => None,
+  };
+  let (encode_collab, is_diff) = match client_state_vector {
+    None => (encode_collab, false),
+    Some(client_state_vector) => {
+      let data_source = match encode_collab.version {
+        EncoderVersion::V1 => DataSource::DocStateV1(encode_collab.doc_state.to_vec()),
+        EncoderVersion::V2 => DataSource::DocStateV2(encode_collab.doc_state.to_vec()),
+      };
+      let collab =
+        Collab::new_with_source(CollabOrigin::Server, object_id, data_source, vec![], false)
+          .map_err(|err| AppError::Internal(anyhow!("Failed to open collab: {}", err)))?;
+      let txn = collab.transact();
+      let update = txn.encode_state_as_update_v1(&client_state_vector);
+      let encode_collab = EncodedCollab::new_v1(txn.state_vector().encode_v1(), update);
+      (encode_collab, true)
+    },
+  };
+
+  Ok(CollabDiffResponse {
+    object_id: object_id.to_string(),
+    state_vector_hash,
+    encode_collab: Some(encode_collab),
+    is_diff,
+    recovered_from: None,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use yrs::{Doc, GetString, Text};
+
+  use super::*;
+
+  #[test]
+  fn deletion_changes_the_collab_state_hash_test() {
+    let doc = Doc::with_client_id(1);
+    let text = doc.get_or_insert_text("text");
+    text.insert(&mut doc.transact_mut(), 0, "hello world");
+    let before = doc.transact().state_vector();
+    let inserted = hash_collab_state(&doc.transact());
+    // a collab without deletions hashes like its state vector
+    assert_eq!(inserted, hash_state_vector(&before));
+
+    text.remove_range(&mut doc.transact_mut(), 5, 6);
+    assert_eq!(text.get_string(&doc.transact()), "hello");
+    // the deletion doesn't advance the state vector, only the delete set tells them apart
+    assert_eq!(doc.transact().state_vector(), before);
+    assert_ne!(hash_collab_state(&doc.transact()), inserted);
+  }
+}
@@ -0,0 +1,322 @@
+use app_error::AppError;
+use collab::entity::EncodedCollab;
+use collab_document::blocks::DocumentData;
+use fancy_regex::{Regex, RegexBuilder};
+use shared_entity::dto::workspace_dto::{BlockMatch, DocumentFindResult, FindInDocumentQuery};
+
+use super::diff::state_vector_hash;
+use super::document_outline::{
+  block_text, children, document_data, open_encoded_collab, DocumentCache,
+};
+
+const MAX_QUERY_LEN: usize = 256;
+const MAX_MATCHES: usize = 1000;
+/// Number of characters of the block text kept on each side of a match.
+const CONTEXT_CHARS: usize = 40;
+/// Bound the work of a regular expression query, which is run on every block of the document.
+const REGEX_BACKTRACK_LIMIT: usize = 100_000;
+const REGEX_SIZE_LIMIT: usize = 1024 * 1024;
+
+pub struct BlockText {
+  pub block_id: String,
+  pub text: String,
+}
+
+/// The texts of the blocks of the documents searched recently, in reading order.
+pub type DocumentTextCache = DocumentCache<Vec<BlockText>>;
+
+/// Finds the query in the latest version of a document. The texts of its blocks are extracted again
+/// whenever the state vector of the document changed, so the matches always point to its current
+/// blocks.
+pub fn find_in_encoded_document(
+  cache: &DocumentTextCache,
+  object_id: &str,
+  encode_collab: EncodedCollab,
+  query: &FindInDocumentQuery,
+) -> Result<DocumentFindResult, AppError> {
+  let finder = build_finder(query)?;
+  let version = state_vector_hash(object_id, &encode_collab)?;
+  let blocks = cache.get_or_extract(object_id, &version, || {
+    let collab = open_encoded_collab(object_id, encode_collab)?;
+    Ok(
+      document_data(object_id, collab)?
+        .map(|data| block_texts(&data))
+        .unwrap_or_default(),
+    )
+  })?;
+  let (matches, truncated) = find_matches(&finder, &blocks, MAX_MATCHES)?;
+  Ok(DocumentFindResult {
+    object_id: object_id.to_string(),
+    version,
+    matches,
+    truncated,
+  })
+}
+
+/// The blocks with a text, in reading order.
+pub fn block_texts(data: &DocumentData) -> Vec<BlockText> {
+  let mut blocks = vec![];
+  let root = match data.blocks.get(&data.page_id) {
+    Some(root) => root,
+    None => return blocks,
+  };
+  let mut stack = children(data, root).rev().collect::<Vec<_>>();
+  while let Some(block) = stack.pop() {
+    let text = block_text(data, block);
+    if !text.is_empty() {
+      blocks.push(BlockText {
+        block_id: block.id.clone(),
+        text,
+      });
+    }
+    stack.extend(children(data, block).rev());
+  }
+  blocks
+}
+
+fn build_finder(query: &FindInDocumentQuery) -> Result<Regex, AppError> {
+  if query.query.is_empty() {
+    return Err(AppError::InvalidRequest("The query is empty".to_string()));
+  }
+  if query.query.chars().count() > MAX_QUERY_LEN {
+    return Err(AppError::InvalidRequest(format!(
+      "The query is longer than {} characters",
+      MAX_QUERY_LEN
+    )));
+  }
+  let mut pattern = if query.regex {
+    query.query.clone()
+  } else {
+    fancy_regex::escape(&query.query).into_owned()
+  };
+  if query.whole_word {
+    pattern = format!(r"\b(?:{})\b", pattern);
+  }
+  if !query.case_sensitive {
+    pattern = format!("(?i){}", pattern);
+  }
+  RegexBuilder::new(&pattern)
+    .backtrack_limit(REGEX_BACKTRACK_LIMIT)
+    .delegate_size_limit(REGEX_SIZE_LIMIT)
+    .delegate_dfa_size_limit(REGEX_SIZE_LIMIT)
+    .build()
+    .map_err(|err| AppError::InvalidRequest(format!("Invalid query: {}", err)))
+}
+
+/// The non-empty matches of the finder in the blocks, at most `limit` of them. Returns whether
+/// more were left out.
+fn find_matches(
+  finder: &Regex,
+  blocks: &[BlockText],
+  limit: usize,
+) -> Result<(Vec<BlockMatch>, bool), AppError> {
+  let mut matches = vec![];
+  for block in blocks {
+    let text = &block.text;
+    // offsets are counted incrementally, the matches of a block being in order
+    let (mut byte_offset, mut utf16_offset) = (0, 0);
+    for found in finder.find_iter(text) {
+      let found = found
+        .map_err(|err| AppError::InvalidRequest(format!("The query is too complex: {}", err)))?;
+      if found.start() == found.end() {
+        continue;
+      }
+      if matches.len() == limit {
+        return Ok((matches, true));
+      }
+      utf16_offset += text[byte_offset..found.start()].encode_utf16().count();
+      let start = utf16_offset;
+      utf16_offset += found.as_str().encode_utf16().count();
+      byte_offset = found.end();
+
+      let before = &text[..found.start()];
+      let before_start = before
+        .char_indices()
+        .rev()
+        .nth(CONTEXT_CHARS - 1)
+        .map(|(index, _)| index)
+        .unwrap_or(0);
+      matches.push(BlockMatch {
+        block_id: block.block_id.clone(),
+        start: start as u32,
+        end: utf16_offset as u32,
+        before: before[before_start..].to_string(),
+        matched: found.as_str().to_string(),
+        after: text[found.end()..].chars().take(CONTEXT_CHARS).collect(),
+      });
+    }
+  }
+  Ok((matches, false))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+  use workspace_template::document::parser::JsonToDocumentParser;
+
+  fn fixture_blocks() -> Vec<BlockText> {
+    let data = JsonToDocumentParser::json_to_document(json!({
+      "type": "page",
+      "children": [
+        { "type": "heading", "data": { "level": 1, "delta": [{ "insert": "Release notes" }] } },
+        {
+          "type": "toggle_list",
+          "data": { "delta": [{ "insert": "Notes 🚀 on the release" }] },
+          "children": [
+            { "type": "paragraph", "data": { "delta": [{ "insert": "A released build" }] } }
+          ]
+        },
+        { "type": "paragraph", "data": { "delta": [] } },
+        { "type": "paragraph", "data": { "delta": [{ "insert": "Last RELEASE" }] } }
+      ]
+    }))
+    .unwrap();
+    block_texts(&data)
+  }
+
+  fn find(blocks: &[BlockText], query: FindInDocumentQuery) -> Vec<(usize, u32, u32)> {
+    let finder = build_finder(&query).unwrap();
+    let (matches, _) = find_matches(&finder, blocks, MAX_MATCHES).unwrap();
+    matches
+      .iter()
+      .map(|found| {
+        let index = blocks
+          .iter()
+          .position(|block| block.block_id == found.block_id)
+          .unwrap();
+        (index, found.start, found.end)
+      })
+      .collect()
+  }
+
+  fn query(query: &str) -> FindInDocumentQuery {
+    FindInDocumentQuery {
+      query: query.to_string(),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn block_texts_are_in_reading_order_test() {
+    let texts = fixture_blocks()
+      .into_iter()
+      .map(|block| block.text)
+      .collect::<Vec<_>>();
+    assert_eq!(
+      texts,
+      vec![
+        "Release notes",
+        "Notes 🚀 on the release",
+        "A released build",
+        "Last RELEASE"
+      ]
+    );
+  }
+
+  #[test]
+  fn find_in_document_options_test() {
+    let blocks = fixture_blocks();
+    assert_eq!(
+      find(&blocks, query("release")),
+      vec![(0, 0, 7), (1, 16, 23), (2, 2, 9), (3, 5, 12)]
+    );
+    assert_eq!(
+      find(
+        &blocks,
+        FindInDocumentQuery {
+          case_sensitive: true,
+          ..query("release")
+        }
+      ),
+      vec![(1, 16, 23), (2, 2, 9)]
+    );
+    assert_eq!(
+      find(
+        &blocks,
+        FindInDocumentQuery {
+          whole_word: true,
+          ..query("release")
+        }
+      ),
+      vec![(0, 0, 7), (1, 16, 23), (3, 5, 12)]
+    );
+    assert_eq!(
+      find(
+        &blocks,
+        FindInDocumentQuery {
+          regex: true,
+          ..query(r"releas\w+")
+        }
+      ),
+      vec![(0, 0, 7), (1, 16, 23), (2, 2, 10), (3, 5, 12)]
+    );
+    // a plain query is not a regular expression, and an empty match is not a match
+    assert!(find(&blocks, query(r"releas\w+")).is_empty());
+    assert!(find(
+      &blocks,
+      FindInDocumentQuery {
+        regex: true,
+        ..query("x*")
+      }
+    )
+    .is_empty());
+  }
+
+  #[test]
+  fn block_match_context_test() {
+    let blocks = fixture_blocks();
+    let finder = build_finder(&query("on")).unwrap();
+    let (matches, truncated) = find_matches(&finder, &blocks, MAX_MATCHES).unwrap();
+    assert!(!truncated);
+    assert_eq!(matches.len(), 1);
+    // the emoji takes two UTF-16 code units
+    assert_eq!((matches[0].start, matches[0].end), (9, 11));
+    assert_eq!(matches[0].before, "Notes 🚀 ");
+    assert_eq!(matches[0].matched, "on");
+    assert_eq!(matches[0].after, " the release");
+
+    let long = vec![BlockText {
+      block_id: "long".to_string(),
+      text: format!("{}needle{}", "a".repeat(100), "b".repeat(100)),
+    }];
+    let finder = build_finder(&query("needle")).unwrap();
+    let (matches, _) = find_matches(&finder, &long, MAX_MATCHES).unwrap();
+    assert_eq!(matches[0].before, "a".repeat(CONTEXT_CHARS));
+    assert_eq!(matches[0].after, "b".repeat(CONTEXT_CHARS));
+  }
+
+  #[test]
+  fn find_in_document_limits_test() {
+    let blocks = fixture_blocks();
+    let finder = build_finder(&query("e")).unwrap();
+    let (matches, truncated) = find_matches(&finder, &blocks, 3).unwrap();
+    assert_eq!(matches.len(), 3);
+    assert!(truncated);
+
+    assert!(build_finder(&query("")).is_err());
+    assert!(build_finder(&query(&"a".repeat(MAX_QUERY_LEN + 1))).is_err());
+    assert!(build_finder(&FindInDocumentQuery {
+      regex: true,
+      ..query("(unclosed")
+    })
+    .is_err());
+    assert!(build_finder(&FindInDocumentQuery {
+      regex: true,
+      ..query(r"(?:\w{1000}){1000}")
+    })
+    .is_err());
+
+    // a pattern which backtracks on every block gives up instead of hanging the server
+    let finder = build_finder(&FindInDocumentQuery {
+      regex: true,
+      ..query(r"(a+)+\1b")
+    })
+    .unwrap();
+    let blocks = vec![BlockText {
+      block_id: "a".to_string(),
+      text: "a".repeat(64),
+    }];
+    assert!(find_matches(&finder, &blocks, MAX_MATCHES).is_err());
+  }
+}
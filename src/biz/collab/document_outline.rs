@@ -0,0 +1,298 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use app_error::AppError;
+use collab::core::collab::DataSource;
+use collab::core::origin::CollabOrigin;
+use collab::entity::{EncodedCollab, EncoderVersion};
+use collab::preclude::Collab;
+use collab_document::blocks::{Block, DocumentData};
+use collab_document::document::Document;
+use collab_document::error::DocumentError;
+use dashmap::DashMap;
+use serde_json::Value;
+use shared_entity::dto::workspace_dto::{DocumentOutline, OutlineContainer, OutlineHeading};
+
+use super::diff::state_vector_hash;
+use super::utils::collab_from_doc_state;
+
+const HEADING_BLOCK: &str = "heading";
+const CONTAINER_BLOCKS: [&str; 2] = ["toggle_list", "callout"];
+const MAX_CACHED_DOCUMENTS: usize = 10_000;
+
+/// Data extracted from the documents requested recently, each one along with the version of the
+/// document it was extracted from. Only the latest version of a document is kept.
+pub struct DocumentCache<T> {
+  entries: Arc<DashMap<String, (String, Arc<T>)>>,
+}
+
+impl<T> Clone for DocumentCache<T> {
+  fn clone(&self) -> Self {
+    Self {
+      entries: self.entries.clone(),
+    }
+  }
+}
+
+impl<T> Default for DocumentCache<T> {
+  fn default() -> Self {
+    Self {
+      entries: Default::default(),
+    }
+  }
+}
+
+impl<T> DocumentCache<T> {
+  pub(super) fn get_or_extract(
+    &self,
+    object_id: &str,
+    version: &str,
+    extract: impl FnOnce() -> Result<T, AppError>,
+  ) -> Result<Arc<T>, AppError> {
+    let cached = self
+      .entries
+      .get(object_id)
+      .filter(|entry| entry.0 == version)
+      .map(|entry| entry.1.clone());
+    if let Some(cached) = cached {
+      return Ok(cached);
+    }
+    let extracted = Arc::new(extract()?);
+    if self.entries.len() >= MAX_CACHED_DOCUMENTS && !self.entries.contains_key(object_id) {
+      let evicted = self.entries.iter().next().map(|entry| entry.key().clone());
+      if let Some(evicted) = evicted {
+        self.entries.remove(&evicted);
+      }
+    }
+    self.entries.insert(
+      object_id.to_string(),
+      (version.to_string(), extracted.clone()),
+    );
+    Ok(extracted)
+  }
+}
+
+pub type DocumentOutlineCache = DocumentCache<Vec<OutlineHeading>>;
+
+fn to_outline(
+  object_id: &str,
+  version: String,
+  headings: Arc<Vec<OutlineHeading>>,
+) -> DocumentOutline {
+  DocumentOutline {
+    object_id: object_id.to_string(),
+    version,
+    headings: headings.as_ref().clone(),
+  }
+}
+
+/// Outline of the latest version of a document, identified by the hash of its state. The hash
+/// covers the deletions too, see [state_vector_hash], so removing a heading invalidates the cached
+/// outline even though the state vector stays the same.
+pub fn encoded_document_outline(
+  cache: &DocumentOutlineCache,
+  object_id: &str,
+  encode_collab: EncodedCollab,
+) -> Result<DocumentOutline, AppError> {
+  let version = state_vector_hash(object_id, &encode_collab)?;
+  let headings = cache.get_or_extract(object_id, &version, || {
+    let collab = open_encoded_collab(object_id, encode_collab)?;
+    collab_outline(object_id, collab)
+  })?;
+  Ok(to_outline(object_id, version, headings))
+}
+
+/// Outline of a published revision of a document. A revision never changes, so it's only
+/// extracted once.
+pub fn published_document_outline(
+  cache: &DocumentOutlineCache,
+  view_id: &str,
+  revision: &str,
+  blob: Vec<u8>,
+) -> Result<DocumentOutline, AppError> {
+  let headings = cache.get_or_extract(view_id, revision, || {
+    let collab = collab_from_doc_state(blob, view_id)?;
+    collab_outline(view_id, collab)
+  })?;
+  Ok(to_outline(view_id, revision.to_string(), headings))
+}
+
+pub(super) fn open_encoded_collab(
+  object_id: &str,
+  encode_collab: EncodedCollab,
+) -> Result<Collab, AppError> {
+  let data_source = match encode_collab.version {
+    EncoderVersion::V1 => DataSource::DocStateV1(encode_collab.doc_state.to_vec()),
+    EncoderVersion::V2 => DataSource::DocStateV2(encode_collab.doc_state.to_vec()),
+  };
+  Collab::new_with_source(CollabOrigin::Server, object_id, data_source, vec![], false)
+    .map_err(|err| AppError::Internal(anyhow!("Failed to open collab: {}", err)))
+}
+
+/// The data of the document, `None` when it's still empty.
+pub(super) fn document_data(
+  object_id: &str,
+  collab: Collab,
+) -> Result<Option<DocumentData>, AppError> {
+  let document = Document::open(collab)
+    .map_err(|err| AppError::InvalidRequest(format!("{} is not a document: {}", object_id, err)))?;
+  match document.get_document_data() {
+    Ok(data) => Ok(Some(data)),
+    Err(DocumentError::NoRequiredData) => Ok(None),
+    Err(err) => Err(AppError::Internal(anyhow!(
+      "Failed to read document {}: {}",
+      object_id,
+      err
+    ))),
+  }
+}
+
+fn collab_outline(object_id: &str, collab: Collab) -> Result<Vec<OutlineHeading>, AppError> {
+  Ok(
+    document_data(object_id, collab)?
+      .map(|data| document_outline(&data))
+      .unwrap_or_default(),
+  )
+}
+
+/// The heading blocks of the document in reading order. A heading nested in a toggle or a callout
+/// is marked with the innermost one.
+pub fn document_outline(data: &DocumentData) -> Vec<OutlineHeading> {
+  let mut headings = vec![];
+  let root = match data.blocks.get(&data.page_id) {
+    Some(root) => root,
+    None => return headings,
+  };
+  let mut stack: Vec<(&Block, Option<&Block>)> = children(data, root)
+    .rev()
+    .map(|child| (child, None))
+    .collect();
+  while let Some((block, container)) = stack.pop() {
+    if block.ty == HEADING_BLOCK {
+      headings.push(OutlineHeading {
+        block_id: block.id.clone(),
+        level: heading_level(block),
+        text: block_text(data, block),
+        container: container.map(|container| OutlineContainer {
+          block_id: container.id.clone(),
+          ty: container.ty.clone(),
+        }),
+      });
+    }
+    let child_container = if CONTAINER_BLOCKS.contains(&block.ty.as_str()) {
+      Some(block)
+    } else {
+      container
+    };
+    for child in children(data, block).rev() {
+      stack.push((child, child_container));
+    }
+  }
+  headings
+}
+
+pub(super) fn children<'a>(
+  data: &'a DocumentData,
+  block: &Block,
+) -> impl DoubleEndedIterator<Item = &'a Block> {
+  data
+    .meta
+    .children_map
+    .get(&block.children)
+    .into_iter()
+    .flatten()
+    .filter_map(|child_id| data.blocks.get(child_id))
+}
+
+fn heading_level(block: &Block) -> u8 {
+  block
+    .data
+    .get("level")
+    .and_then(Value::as_u64)
+    .map(|level| level.clamp(1, 6) as u8)
+    .unwrap_or(1)
+}
+
+/// The text of a block is stored as a delta in the text map of the document, older documents keep
+/// it in the data of the block.
+pub(super) fn block_text(data: &DocumentData, block: &Block) -> String {
+  let delta = block
+    .external_id
+    .as_ref()
+    .and_then(|external_id| data.meta.text_map.as_ref()?.get(external_id))
+    .and_then(|delta| serde_json::from_str::<Value>(delta).ok())
+    .or_else(|| block.data.get("delta").cloned());
+  delta
+    .as_ref()
+    .and_then(Value::as_array)
+    .map(|ops| {
+      ops
+        .iter()
+        .filter_map(|op| op.get("insert").and_then(Value::as_str))
+        .collect()
+    })
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+  use workspace_template::document::parser::JsonToDocumentParser;
+
+  #[test]
+  fn document_outline_test() {
+    let data = JsonToDocumentParser::json_to_document(json!({
+      "type": "page",
+      "children": [
+        { "type": "heading", "data": { "level": 1, "delta": [{ "insert": "Intro" }] } },
+        { "type": "paragraph", "data": { "delta": [{ "insert": "text" }] } },
+        {
+          "type": "toggle_list",
+          "data": { "delta": [{ "insert": "More" }] },
+          "children": [
+            {
+              "type": "heading",
+              "data": { "level": 2, "delta": [{ "insert": "Hidden " }, { "insert": "details" }] }
+            }
+          ]
+        },
+        { "type": "heading", "data": { "level": 9, "delta": [] } }
+      ]
+    }))
+    .unwrap();
+
+    let headings = document_outline(&data);
+    let summary = headings
+      .iter()
+      .map(|heading| {
+        (
+          heading.level,
+          heading.text.as_str(),
+          heading
+            .container
+            .as_ref()
+            .map(|container| container.ty.as_str()),
+        )
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(
+      summary,
+      vec![
+        (1, "Intro", None),
+        (2, "Hidden details", Some("toggle_list")),
+        (6, "", None),
+      ]
+    );
+  }
+
+  #[test]
+  fn document_without_headings_has_empty_outline_test() {
+    let data = JsonToDocumentParser::json_to_document(json!({
+      "type": "page",
+      "children": [{ "type": "paragraph", "data": { "delta": [{ "insert": "text" }] } }]
+    }))
+    .unwrap();
+    assert!(document_outline(&data).is_empty());
+  }
+}
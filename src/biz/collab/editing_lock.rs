@@ -0,0 +1,140 @@
+use app_error::AppError;
+use chrono::{DateTime, Utc};
+use collab_stream::editing_lock::{EditingLock, EditingLockAcquisition, EditingLockStore};
+use database::user::select_name_from_uuid;
+use database::workspace::select_workspace_settings;
+use redis::aio::ConnectionManager;
+use shared_entity::dto::workspace_dto::AFEditingLock;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub async fn get_editing_lock(
+  redis: &ConnectionManager,
+  workspace_id: &Uuid,
+  object_id: &Uuid,
+) -> Result<Option<AFEditingLock>, AppError> {
+  let lock = EditingLockStore::new(redis.clone())
+    .get(&workspace_id.to_string(), &object_id.to_string())
+    .await
+    .map_err(|err| AppError::Internal(err.into()))?;
+  Ok(lock.filter(|lock| !lock.is_expired()).map(af_editing_lock))
+}
+
+/// Acquires the editing lock of the collab for the given user, or renews it if the user already
+/// holds it. Fails with [AppError::EditingLocked] when the lock is held by another member.
+pub async fn acquire_editing_lock(
+  pg_pool: &PgPool,
+  redis: &ConnectionManager,
+  workspace_id: &Uuid,
+  object_id: &Uuid,
+  uid: i64,
+  user_uuid: &Uuid,
+  device_id: &str,
+) -> Result<AFEditingLock, AppError> {
+  let settings = select_workspace_settings(pg_pool, workspace_id)
+    .await?
+    .unwrap_or_default();
+  if !settings.enable_editing_lock {
+    return Err(AppError::InvalidRequest(
+      "Editing lock is not enabled for this workspace".to_string(),
+    ));
+  }
+
+  let name = select_name_from_uuid(pg_pool, user_uuid).await?;
+  let acquisition = EditingLockStore::new(redis.clone())
+    .acquire(
+      &workspace_id.to_string(),
+      &object_id.to_string(),
+      uid,
+      &name,
+      device_id,
+      EditingLockStore::DEFAULT_TTL,
+    )
+    .await
+    .map_err(|err| AppError::Internal(err.into()))?;
+  match acquisition {
+    EditingLockAcquisition::Acquired(lock) => Ok(af_editing_lock(lock)),
+    EditingLockAcquisition::Locked(lock) => Err(locked_by(&lock)),
+  }
+}
+
+pub async fn heartbeat_editing_lock(
+  redis: &ConnectionManager,
+  workspace_id: &Uuid,
+  object_id: &Uuid,
+  uid: i64,
+) -> Result<AFEditingLock, AppError> {
+  let store = EditingLockStore::new(redis.clone());
+  let workspace_id = workspace_id.to_string();
+  let object_id = object_id.to_string();
+  let lock = store
+    .heartbeat(
+      &workspace_id,
+      &object_id,
+      uid,
+      EditingLockStore::DEFAULT_TTL,
+    )
+    .await
+    .map_err(|err| AppError::Internal(err.into()))?;
+  match lock {
+    Some(lock) => Ok(af_editing_lock(lock)),
+    None => {
+      let current = store
+        .get(&workspace_id, &object_id)
+        .await
+        .map_err(|err| AppError::Internal(err.into()))?;
+      match current {
+        Some(lock) => Err(locked_by(&lock)),
+        None => Err(AppError::RecordNotFound(format!(
+          "Editing lock of {} is not held",
+          object_id
+        ))),
+      }
+    },
+  }
+}
+
+/// Releases the editing lock held by the given user. When `force` is set, the lock is released
+/// regardless of its holder. The caller is responsible for checking that the user is allowed to
+/// do so.
+pub async fn release_editing_lock(
+  redis: &ConnectionManager,
+  workspace_id: &Uuid,
+  object_id: &Uuid,
+  uid: i64,
+  force: bool,
+) -> Result<(), AppError> {
+  let store = EditingLockStore::new(redis.clone());
+  let workspace_id = workspace_id.to_string();
+  let object_id = object_id.to_string();
+  let released = if force {
+    store.force_release(&workspace_id, &object_id).await
+  } else {
+    store.release(&workspace_id, &object_id, uid, None).await
+  }
+  .map_err(|err| AppError::Internal(err.into()))?;
+
+  if !released {
+    let current = store
+      .get(&workspace_id, &object_id)
+      .await
+      .map_err(|err| AppError::Internal(err.into()))?;
+    if let Some(lock) = current {
+      return Err(locked_by(&lock));
+    }
+  }
+  Ok(())
+}
+
+fn locked_by(lock: &EditingLock) -> AppError {
+  AppError::EditingLocked(format!("The page is locked by {}", lock.name))
+}
+
+fn af_editing_lock(lock: EditingLock) -> AFEditingLock {
+  AFEditingLock {
+    uid: lock.uid,
+    name: lock.name,
+    acquired_at: DateTime::from_timestamp_millis(lock.acquired_at).unwrap_or_else(Utc::now),
+    expires_at: DateTime::from_timestamp_millis(lock.expires_at).unwrap_or_else(Utc::now),
+  }
+}
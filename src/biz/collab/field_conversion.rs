@@ -0,0 +1,1103 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use app_error::AppError;
+use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
+use chrono::{DateTime, Timelike, Utc};
+use collab::preclude::Collab;
+use collab_database::entity::FieldType;
+use collab_database::fields::select_type_option::{
+  MultiSelectTypeOption, SelectOption, SingleSelectTypeOption,
+};
+use collab_database::fields::{
+  type_option_cell_reader, type_option_cell_writer, Field, TypeOptionData,
+};
+use collab_database::rows::{new_cell_builder, Cell, DatabaseRowBody, RowDetail};
+use collab_database::views::DatabaseView;
+use collab_entity::{CollabType, EncodedCollab};
+use database::collab::CollabStorage;
+use database::database_field_conversion::{
+  insert_field_conversion_task, select_field_conversion_task, update_field_conversion_failed,
+  update_field_conversion_progress, FieldConversionState,
+};
+use database::pg_row::AFDatabaseFieldConversionRow;
+use database_entity::dto::{CollabParams, QueryCollab, QueryCollabResult};
+use serde_json::Value;
+use shared_entity::dto::workspace_dto::{
+  AFDroppedViewSetting, AFFieldConversionReport, AFFieldConversionTask, AFInconvertibleCell,
+  AFViewSettingKind, UpdateDatabaseFieldType,
+};
+use sqlx::PgPool;
+use tracing::{error, info};
+use uuid::Uuid;
+use yrs::Any;
+
+use super::ops::save_database_collab;
+use super::utils::{
+  add_select_options, collab_from_doc_state, collab_to_bin, get_latest_collab_database_body,
+  parse_checkbox, parse_timestamp, select_options,
+};
+use crate::biz::workspace::ops::broadcast_update_with_timeout;
+
+/// Number of rows converted, then saved, at a time. The progress of the task is updated after
+/// each batch.
+const CONVERSION_BATCH_SIZE: usize = 100;
+
+/// The types a field can be changed to, the ones whose cells can be written by the server.
+const CONVERTIBLE_FIELD_TYPES: &[FieldType] = &[
+  FieldType::RichText,
+  FieldType::URL,
+  FieldType::Number,
+  FieldType::Checkbox,
+  FieldType::DateTime,
+  FieldType::SingleSelect,
+  FieldType::MultiSelect,
+];
+
+/// Change the type of a field of the database. The field and the views referencing it are updated
+/// right away: the filters on the field which don't apply to the new type and the groups by the
+/// field are dropped, and reported. The existing cells are then converted in the background, in
+/// batches, and the returned task reports the progress of the conversion.
+pub async fn change_database_field_type(
+  pg_pool: &PgPool,
+  collab_storage: Arc<CollabAccessControlStorage>,
+  uid: i64,
+  workspace_id: &Uuid,
+  database_id: &Uuid,
+  field_id: &str,
+  params: UpdateDatabaseFieldType,
+) -> Result<AFFieldConversionTask, AppError> {
+  let workspace_id_str = workspace_id.to_string();
+  let database_id_str = database_id.to_string();
+  let (mut db_collab, db_body) =
+    get_latest_collab_database_body(&collab_storage, &workspace_id_str, &database_id_str).await?;
+  let (field, row_ids) = {
+    let txn = db_collab.transact();
+    let field = db_body
+      .fields
+      .get_all_fields(&txn)
+      .into_iter()
+      .find(|field| field.id == field_id)
+      .ok_or_else(|| {
+        AppError::RecordNotFound(format!(
+          "Field {} does not exist in database {}",
+          field_id, database_id
+        ))
+      })?;
+    let row_ids = db_body
+      .views
+      .get_all_views(&txn)
+      .into_iter()
+      .flat_map(|view| view.row_orders)
+      .map(|row_order| row_order.id.to_string())
+      .collect::<HashSet<_>>();
+    (field, row_ids)
+  };
+  let from = FieldType::from(field.field_type);
+  let to = FieldType::from(params.field_type);
+  if from == to {
+    return Err(AppError::InvalidRequest(format!(
+      "Field `{}` is already of type {:?}",
+      field.name, to
+    )));
+  }
+  if !CONVERTIBLE_FIELD_TYPES.contains(&to) {
+    return Err(AppError::InvalidRequest(format!(
+      "Field `{}` can not be changed to type {:?}",
+      field.name, to
+    )));
+  }
+  let type_option_data = match params.type_option_data {
+    Some(type_option_data) => Some(
+      serde_json::from_value::<TypeOptionData>(type_option_data).map_err(|err| {
+        AppError::InvalidRequest(format!("Failed to parse type option: {:?}", err))
+      })?,
+    ),
+    // the options of a select field are kept when it's changed to the other select type
+    None => copy_select_options(&field, &from, &to),
+  };
+
+  let task_id = Uuid::new_v4();
+  let row = insert_field_conversion_task(
+    pg_pool,
+    &task_id,
+    workspace_id,
+    database_id,
+    field_id,
+    from.clone().into(),
+    params.field_type,
+    uid,
+  )
+  .await?;
+
+  let (db_collab_update, dropped_view_settings) = {
+    let mut txn = db_collab.transact_mut();
+    db_body.fields.update_field(&mut txn, field_id, |f| {
+      f.set_field_type(to.clone().into());
+    });
+    if let Some(type_option_data) = type_option_data {
+      db_body.fields.update_field(&mut txn, field_id, |f| {
+        f.set_type_option(to.clone().into(), Some(type_option_data));
+      });
+    }
+    let mut db_views = db_body.views.get_all_views(&txn);
+    let dropped = update_dependent_views(&mut db_views, field_id, &from, &to);
+    if !dropped.is_empty() {
+      db_body.views.clear(&mut txn);
+      for view in db_views {
+        db_body.views.insert_view(&mut txn, view);
+      }
+    }
+    (txn.encode_update_v1(), dropped)
+  };
+  let report = AFFieldConversionReport {
+    dropped_view_settings,
+    ..Default::default()
+  };
+  let saved = save_database_collab(
+    collab_storage.clone(),
+    pg_pool,
+    &workspace_id_str,
+    &database_id_str,
+    uid,
+    db_collab,
+    db_collab_update,
+  )
+  .await;
+  if let Err(err) = saved {
+    update_field_conversion_failed(pg_pool, &task_id, &err.to_string()).await?;
+    return Err(err);
+  }
+  update_field_conversion_progress(
+    pg_pool,
+    &task_id,
+    FieldConversionState::Running,
+    row_ids.len() as i64,
+    0,
+    &serde_json::to_value(&report)?,
+  )
+  .await?;
+  info!(
+    "User:{} changed field {} of database {} from {:?} to {:?}, task:{}",
+    uid, field_id, database_id, from, to, task_id
+  );
+
+  let row_ids_len = row_ids.len() as i64;
+  let report_snapshot = report.clone();
+  let mut row_ids = row_ids.into_iter().collect::<Vec<_>>();
+  row_ids.sort();
+  let conversion = FieldCellConversion {
+    pg_pool: pg_pool.clone(),
+    collab_storage,
+    uid,
+    task_id,
+    workspace_id: workspace_id_str,
+    database_id: database_id_str,
+    field_id: field_id.to_string(),
+    to,
+    create_select_options: params.create_select_options,
+  };
+  tokio::spawn(async move {
+    if let Err(err) = conversion.run(row_ids, report).await {
+      error!(
+        "[FieldConversion] failed to convert the cells of field {}: {}",
+        conversion.field_id, err
+      );
+      if let Err(err) =
+        update_field_conversion_failed(&conversion.pg_pool, &conversion.task_id, &err.to_string())
+          .await
+      {
+        error!("[FieldConversion] failed to record the failure: {}", err);
+      }
+    }
+  });
+
+  let mut task = conversion_task_from_row(row)?;
+  task.status = FieldConversionState::Running as i16;
+  task.total_rows = row_ids_len;
+  task.report = report_snapshot;
+  Ok(task)
+}
+
+pub async fn get_field_conversion_task(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  database_id: &Uuid,
+  task_id: &Uuid,
+) -> Result<AFFieldConversionTask, AppError> {
+  let row = select_field_conversion_task(pg_pool, workspace_id, database_id, task_id).await?;
+  conversion_task_from_row(row)
+}
+
+struct FieldCellConversion {
+  pg_pool: PgPool,
+  collab_storage: Arc<CollabAccessControlStorage>,
+  uid: i64,
+  task_id: Uuid,
+  workspace_id: String,
+  database_id: String,
+  field_id: String,
+  to: FieldType,
+  create_select_options: bool,
+}
+
+/// A row whose cell of the converted field gets a new value.
+struct ConvertedRow {
+  row_id: String,
+  collab: Collab,
+  /// The value of the new type, [Value::Null] to clear the cell.
+  value: Value,
+}
+
+impl FieldCellConversion {
+  async fn run(
+    &self,
+    row_ids: Vec<String>,
+    mut report: AFFieldConversionReport,
+  ) -> Result<(), AppError> {
+    let mut converted_rows = 0;
+    for batch in row_ids.chunks(CONVERSION_BATCH_SIZE) {
+      self.convert_batch(batch, &mut report).await?;
+      converted_rows += batch.len() as i64;
+      update_field_conversion_progress(
+        &self.pg_pool,
+        &self.task_id,
+        FieldConversionState::Running,
+        row_ids.len() as i64,
+        converted_rows,
+        &serde_json::to_value(&report)?,
+      )
+      .await?;
+    }
+    update_field_conversion_progress(
+      &self.pg_pool,
+      &self.task_id,
+      FieldConversionState::Completed,
+      row_ids.len() as i64,
+      converted_rows,
+      &serde_json::to_value(&report)?,
+    )
+    .await?;
+    info!(
+      "[FieldConversion] converted {} cells of field {}, {} could not be converted",
+      report.converted_cells,
+      self.field_id,
+      report.inconvertible_cells.len()
+    );
+    Ok(())
+  }
+
+  async fn convert_batch(
+    &self,
+    row_ids: &[String],
+    report: &mut AFFieldConversionReport,
+  ) -> Result<(), AppError> {
+    let mut field = self.current_field().await?;
+    let queries = row_ids
+      .iter()
+      .map(|row_id| QueryCollab {
+        object_id: row_id.clone(),
+        collab_type: CollabType::DatabaseRow,
+      })
+      .collect();
+    let results = self
+      .collab_storage
+      .batch_get_collab(&self.uid, &self.workspace_id, queries, true)
+      .await;
+
+    let mut rows = vec![];
+    for (row_id, result) in results {
+      let QueryCollabResult::Success { encode_collab_v1 } = result else {
+        continue;
+      };
+      let Some(collab) = EncodedCollab::decode_from_bytes(&encode_collab_v1)
+        .ok()
+        .and_then(|encoded_collab| {
+          collab_from_doc_state(encoded_collab.doc_state.to_vec(), &row_id).ok()
+        })
+      else {
+        continue;
+      };
+      let Some(cell) = RowDetail::from_collab(&collab)
+        .and_then(|row_detail| row_detail.row.cells.get(&self.field_id).cloned())
+      else {
+        continue;
+      };
+      // the cells written since the type was changed are of the new type already
+      let cell_type = cell_field_type(&cell).unwrap_or_else(|| self.to.clone());
+      if cell_type == self.to {
+        continue;
+      }
+      let type_option_data = field
+        .get_any_type_option(cell_type.type_id())
+        .unwrap_or_default();
+      let value = type_option_cell_reader(type_option_data, &cell_type).json_cell(&cell);
+      rows.push((row_id, collab, cell_type, value));
+    }
+
+    let converted = rows
+      .iter()
+      .map(|(_, _, cell_type, value)| convert_cell_value(cell_type, &self.to, value))
+      .collect::<Vec<_>>();
+    if self.create_select_options {
+      let missing = self.missing_select_options(&field, &converted);
+      if !missing.is_empty() {
+        field = self.add_select_options(&field, &missing).await?;
+      }
+    }
+    let options = select_options(&field, &self.to);
+
+    let mut converted_rows = Vec::with_capacity(rows.len());
+    for ((row_id, collab, _, value), converted) in rows.into_iter().zip(converted) {
+      let converted = converted.filter(|converted| {
+        select_option_names(&self.to, converted)
+          .iter()
+          .all(|name| is_select_option(&options, name))
+      });
+      let value = match converted {
+        Some(converted) => {
+          if !converted.is_null() {
+            report.converted_cells += 1;
+          }
+          converted
+        },
+        None => {
+          report.inconvertible_cells.push(AFInconvertibleCell {
+            row_id: row_id.clone(),
+            value,
+          });
+          Value::Null
+        },
+      };
+      converted_rows.push(ConvertedRow {
+        row_id,
+        collab,
+        value,
+      });
+    }
+    self.save_rows(&field, converted_rows).await
+  }
+
+  /// The field being converted, which must still be of the type it's converted to.
+  async fn current_field(&self) -> Result<Field, AppError> {
+    let (db_collab, db_body) =
+      get_latest_collab_database_body(&self.collab_storage, &self.workspace_id, &self.database_id)
+        .await?;
+    let field = db_body
+      .fields
+      .get_all_fields(&db_collab.transact())
+      .into_iter()
+      .find(|field| field.id == self.field_id)
+      .ok_or_else(|| {
+        AppError::RecordNotFound(format!(
+          "Field {} was deleted during its conversion",
+          self.field_id
+        ))
+      })?;
+    if FieldType::from(field.field_type) != self.to {
+      return Err(AppError::InvalidRequest(format!(
+        "The type of field {} was changed again during its conversion",
+        self.field_id
+      )));
+    }
+    Ok(field)
+  }
+
+  fn missing_select_options(&self, field: &Field, converted: &[Option<Value>]) -> Vec<String> {
+    let options = select_options(field, &self.to);
+    let mut missing: Vec<String> = vec![];
+    for name in converted
+      .iter()
+      .flatten()
+      .flat_map(|value| select_option_names(&self.to, value))
+    {
+      if !is_select_option(&options, &name) && !missing.contains(&name) {
+        missing.push(name);
+      }
+    }
+    missing
+  }
+
+  async fn add_select_options(&self, field: &Field, names: &[String]) -> Result<Field, AppError> {
+    let (mut db_collab, db_body) =
+      get_latest_collab_database_body(&self.collab_storage, &self.workspace_id, &self.database_id)
+        .await?;
+    let db_collab_update = {
+      let mut txn = db_collab.transact_mut();
+      if let Some(type_option_data) = add_select_options(field, names) {
+        db_body.fields.update_field(&mut txn, &field.id, |f| {
+          f.set_type_option(self.to.clone().into(), Some(type_option_data));
+        });
+      }
+      txn.encode_update_v1()
+    };
+    save_database_collab(
+      self.collab_storage.clone(),
+      &self.pg_pool,
+      &self.workspace_id,
+      &self.database_id,
+      self.uid,
+      db_collab,
+      db_collab_update,
+    )
+    .await?;
+    self.current_field().await
+  }
+
+  async fn save_rows(&self, field: &Field, rows: Vec<ConvertedRow>) -> Result<(), AppError> {
+    let type_option_data = field
+      .get_any_type_option(self.to.type_id())
+      .unwrap_or_default();
+    let cell_writer = type_option_cell_writer(type_option_data, &self.to);
+    let mut updates = Vec::with_capacity(rows.len());
+    let mut pg_txn = self.pg_pool.begin().await?;
+    for ConvertedRow {
+      row_id,
+      mut collab,
+      value,
+    } in rows
+    {
+      let row_body = DatabaseRowBody::open(row_id.clone().into(), &mut collab).map_err(|err| {
+        AppError::Internal(anyhow::anyhow!(
+          "Failed to open database row {}: {}",
+          row_id,
+          err
+        ))
+      })?;
+      let new_cell = if value.is_null() {
+        new_cell_builder(self.to.clone())
+      } else {
+        cell_writer.convert_json_to_cell(value)
+      };
+      let update = {
+        let mut txn = collab.transact_mut();
+        row_body.update(&mut txn, |row_update| {
+          row_update.update_cells(|cells_update| {
+            cells_update.insert_cell(&self.field_id, new_cell);
+          });
+        });
+        txn.encode_update_v1()
+      };
+      let encoded_collab_v1 = collab_to_bin(collab, CollabType::DatabaseRow).await?;
+      self
+        .collab_storage
+        .upsert_new_collab_with_transaction(
+          &self.workspace_id,
+          &self.uid,
+          CollabParams {
+            object_id: row_id.clone(),
+            encoded_collab_v1: encoded_collab_v1.into(),
+            collab_type: CollabType::DatabaseRow,
+          },
+          &mut pg_txn,
+          "converting database row cells from server",
+        )
+        .await?;
+      updates.push((row_id, update));
+    }
+    pg_txn.commit().await?;
+    for (row_id, update) in updates {
+      broadcast_update_with_timeout(
+        self.collab_storage.clone(),
+        self.workspace_id.clone(),
+        row_id,
+        update,
+      )
+      .await;
+    }
+    Ok(())
+  }
+}
+
+/// The type of the field a cell was written for.
+fn cell_field_type(cell: &Cell) -> Option<FieldType> {
+  match cell.get("field_type")? {
+    Any::BigInt(field_type) => Some(FieldType::from(*field_type)),
+    Any::Number(field_type) => Some(FieldType::from(*field_type as i64)),
+    _ => None,
+  }
+}
+
+/// Convert the value of a cell, as read from a field of type `from`, to a value which can be
+/// written to a field of type `to`. Returns [Value::Null] for an empty cell, and `None` when the
+/// value has no counterpart in the new type. The supported conversions are:
+/// - any value to text, the select options by their names,
+/// - text to a number, a checkbox, a date, or a URL,
+/// - text to the select options of the same name, a multi-select splitting the text on commas,
+/// - a number to a checkbox, or to a date as a unix timestamp,
+/// - a checkbox to a number,
+/// - a single-select to a multi-select, and back when a single option is selected.
+pub fn convert_cell_value(from: &FieldType, to: &FieldType, value: &Value) -> Option<Value> {
+  if is_empty_cell_value(value) {
+    return Some(Value::Null);
+  }
+  match (from, to) {
+    (
+      FieldType::RichText
+      | FieldType::URL
+      | FieldType::Number
+      | FieldType::Checkbox
+      | FieldType::DateTime
+      | FieldType::SingleSelect
+      | FieldType::MultiSelect,
+      FieldType::RichText,
+    ) => Some(Value::String(cell_text(from, value)?)),
+    (FieldType::RichText | FieldType::URL, FieldType::URL) => {
+      Some(Value::String(cell_text(from, value)?.trim().to_string()))
+    },
+    (FieldType::RichText | FieldType::URL, FieldType::Number) => {
+      let text = cell_text(from, value)?;
+      let number = text.trim();
+      number
+        .parse::<f64>()
+        .ok()
+        .map(|_| Value::String(number.to_string()))
+    },
+    (FieldType::Checkbox, FieldType::Number) => {
+      cell_checked(value).map(|checked| Value::from(checked as i64))
+    },
+    (FieldType::RichText | FieldType::URL, FieldType::Checkbox) => {
+      parse_checkbox(&cell_text(from, value)?).map(Value::Bool)
+    },
+    (FieldType::Number, FieldType::Checkbox) => cell_number(value).map(|n| Value::Bool(n != 0.0)),
+    (FieldType::RichText | FieldType::URL, FieldType::DateTime) => {
+      parse_timestamp(&cell_text(from, value)?).map(Value::from)
+    },
+    (FieldType::Number, FieldType::DateTime) => cell_number(value)
+      .filter(|n| n.fract() == 0.0)
+      .map(|n| Value::from(n as i64)),
+    (FieldType::RichText | FieldType::URL, FieldType::SingleSelect) => {
+      Some(Value::String(cell_text(from, value)?.trim().to_string()))
+    },
+    (FieldType::RichText | FieldType::URL, FieldType::MultiSelect) => {
+      let names = cell_text(from, value)?
+        .split(',')
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .map(|name| Value::String(name.to_string()))
+        .collect::<Vec<_>>();
+      Some(Value::Array(names))
+    },
+    (FieldType::SingleSelect | FieldType::MultiSelect, FieldType::SingleSelect) => {
+      match cell_option_names(value).as_slice() {
+        [name] => Some(Value::String(name.clone())),
+        _ => None,
+      }
+    },
+    (FieldType::SingleSelect | FieldType::MultiSelect, FieldType::MultiSelect) => {
+      Some(Value::Array(
+        cell_option_names(value)
+          .into_iter()
+          .map(Value::String)
+          .collect(),
+      ))
+    },
+    _ => None,
+  }
+}
+
+fn is_empty_cell_value(value: &Value) -> bool {
+  match value {
+    Value::Null => true,
+    Value::String(s) => s.trim().is_empty(),
+    Value::Array(values) => values.is_empty(),
+    _ => false,
+  }
+}
+
+fn cell_text(from: &FieldType, value: &Value) -> Option<String> {
+  match (from, value) {
+    (FieldType::SingleSelect | FieldType::MultiSelect, _) => {
+      Some(cell_option_names(value).join(", "))
+    },
+    (FieldType::Checkbox, _) => {
+      cell_checked(value).map(|checked| if checked { "Yes" } else { "No" }.to_string())
+    },
+    (FieldType::DateTime, Value::Number(n)) => n
+      .as_i64()
+      .and_then(|timestamp| DateTime::<Utc>::from_timestamp(timestamp, 0))
+      .map(|date_time| {
+        if date_time.num_seconds_from_midnight() == 0 {
+          date_time.format("%Y-%m-%d").to_string()
+        } else {
+          date_time.to_rfc3339()
+        }
+      }),
+    (_, Value::String(s)) => Some(s.clone()),
+    (_, Value::Number(n)) => Some(n.to_string()),
+    (_, Value::Bool(b)) => Some(b.to_string()),
+    _ => None,
+  }
+}
+
+fn cell_number(value: &Value) -> Option<f64> {
+  match value {
+    Value::Number(n) => n.as_f64(),
+    Value::String(s) => s.trim().parse::<f64>().ok(),
+    _ => None,
+  }
+}
+
+fn cell_checked(value: &Value) -> Option<bool> {
+  match value {
+    Value::Bool(checked) => Some(*checked),
+    Value::String(s) => parse_checkbox(s),
+    _ => None,
+  }
+}
+
+/// The names of the options selected in a select cell, read either as a comma separated string
+/// or as a list.
+fn cell_option_names(value: &Value) -> Vec<String> {
+  let names: Vec<String> = match value {
+    Value::String(names) => names.split(',').map(|name| name.to_string()).collect(),
+    Value::Array(names) => names
+      .iter()
+      .filter_map(|name| name.as_str().map(|name| name.to_string()))
+      .collect(),
+    _ => vec![],
+  };
+  names
+    .into_iter()
+    .map(|name| name.trim().to_string())
+    .filter(|name| !name.is_empty())
+    .collect()
+}
+
+/// The option names of a converted value, for the select types.
+fn select_option_names(field_type: &FieldType, value: &Value) -> Vec<String> {
+  match field_type {
+    FieldType::SingleSelect | FieldType::MultiSelect => cell_option_names(value),
+    _ => vec![],
+  }
+}
+
+fn is_select_option(options: &[SelectOption], name: &str) -> bool {
+  options
+    .iter()
+    .any(|option| option.name == name || option.id == name)
+}
+
+/// The options of a select field changed to the other select type, moved to the type option of
+/// the new type.
+fn copy_select_options(field: &Field, from: &FieldType, to: &FieldType) -> Option<TypeOptionData> {
+  let options = select_options(field, from);
+  if options.is_empty() {
+    return None;
+  }
+  let type_option_data = field.get_any_type_option(to.type_id()).unwrap_or_default();
+  let merge = |existing: &mut Vec<SelectOption>| {
+    for option in options {
+      if !existing.iter().any(|o| o.id == option.id) {
+        existing.push(option);
+      }
+    }
+  };
+  match (from, to) {
+    (FieldType::MultiSelect, FieldType::SingleSelect) => {
+      let mut type_option = SingleSelectTypeOption::from(type_option_data);
+      merge(&mut type_option.options);
+      Some(type_option.into())
+    },
+    (FieldType::SingleSelect, FieldType::MultiSelect) => {
+      let mut type_option = MultiSelectTypeOption::from(type_option_data);
+      merge(&mut type_option.options);
+      Some(type_option.into())
+    },
+    _ => None,
+  }
+}
+
+/// The filters of the same kind apply to the field types of the same family.
+#[derive(Debug, PartialEq, Eq)]
+enum FilterFamily {
+  Text,
+  Select,
+  Date,
+  Other(i64),
+}
+
+fn filter_family(field_type: &FieldType) -> FilterFamily {
+  match field_type {
+    FieldType::RichText | FieldType::URL => FilterFamily::Text,
+    FieldType::SingleSelect | FieldType::MultiSelect => FilterFamily::Select,
+    FieldType::DateTime | FieldType::CreatedTime | FieldType::LastEditedTime => FilterFamily::Date,
+    _ => FilterFamily::Other(field_type.clone().into()),
+  }
+}
+
+/// Update the filters, sorts and groups of the views which reference a field whose type changed.
+/// The filters on the field keep applying when the new type is of the same family, and are
+/// dropped otherwise. The sorts keep applying to every type a field can be changed to. The
+/// groups by the field are made of the values of the previous type, and are dropped. Returns the
+/// dropped settings.
+pub fn update_dependent_views(
+  views: &mut [DatabaseView],
+  field_id: &str,
+  from: &FieldType,
+  to: &FieldType,
+) -> Vec<AFDroppedViewSetting> {
+  let same_filter_family = filter_family(from) == filter_family(to);
+  let to_type: i64 = to.clone().into();
+  let mut dropped = vec![];
+  for view in views.iter_mut() {
+    let view_id = view.id.clone();
+    let mut drop_setting = |kind: AFViewSettingKind, setting: &HashMap<String, Any>| {
+      dropped.push(AFDroppedViewSetting {
+        view_id: view_id.clone(),
+        kind,
+        id: setting_str(setting, "id").unwrap_or_default().to_string(),
+      });
+    };
+    view.filters.retain_mut(|filter| {
+      if !references_field(filter, field_id) {
+        return true;
+      }
+      if same_filter_family {
+        retarget_filter(filter, field_id, to_type);
+        return true;
+      }
+      drop_setting(AFViewSettingKind::Filter, filter);
+      false
+    });
+    view.group_settings.retain(|group| {
+      if setting_str(group, "field_id") != Some(field_id) {
+        return true;
+      }
+      drop_setting(AFViewSettingKind::Group, group);
+      false
+    });
+    for sort in view.sorts.iter_mut() {
+      if setting_str(sort, "field_id") == Some(field_id) && sort.contains_key("ty") {
+        sort.insert("ty".to_string(), Any::BigInt(to_type));
+      }
+    }
+  }
+  dropped
+}
+
+fn setting_str<'a>(setting: &'a HashMap<String, Any>, key: &str) -> Option<&'a str> {
+  match setting.get(key)? {
+    Any::String(value) => Some(value),
+    _ => None,
+  }
+}
+
+/// Whether the filter, or one of the filters it combines, applies to the field.
+fn references_field(filter: &HashMap<String, Any>, field_id: &str) -> bool {
+  if setting_str(filter, "field_id") == Some(field_id) {
+    return true;
+  }
+  match filter.get("children") {
+    Some(Any::Array(children)) => children.iter().any(|child| match child {
+      Any::Map(child) => references_field(child, field_id),
+      _ => false,
+    }),
+    _ => false,
+  }
+}
+
+/// Set the field type of the filters applying to the field.
+fn retarget_filter(filter: &mut HashMap<String, Any>, field_id: &str, to_type: i64) {
+  if setting_str(filter, "field_id") == Some(field_id) {
+    filter.insert("ty".to_string(), Any::BigInt(to_type));
+  }
+  if let Some(Any::Array(children)) = filter.get("children") {
+    let children = children
+      .iter()
+      .map(|child| match child {
+        Any::Map(child) => {
+          let mut child = child.as_ref().clone();
+          retarget_filter(&mut child, field_id, to_type);
+          Any::Map(Arc::new(child))
+        },
+        child => child.clone(),
+      })
+      .collect::<Vec<_>>();
+    filter.insert("children".to_string(), Any::Array(children.into()));
+  }
+}
+
+fn conversion_task_from_row(
+  row: AFDatabaseFieldConversionRow,
+) -> Result<AFFieldConversionTask, AppError> {
+  Ok(AFFieldConversionTask {
+    task_id: row.task_id,
+    database_id: row.database_id,
+    field_id: row.field_id,
+    from_field_type: row.from_field_type,
+    to_field_type: row.to_field_type,
+    status: row.status,
+    total_rows: row.total_rows,
+    converted_rows: row.converted_rows,
+    report: serde_json::from_value(row.report)?,
+    error: row.error,
+    created_at: row.created_at,
+    updated_at: row.updated_at,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn convert(from: FieldType, to: FieldType, value: Value) -> Option<Value> {
+    convert_cell_value(&from, &to, &value)
+  }
+
+  #[test]
+  fn number_to_text_test() {
+    assert_eq!(
+      convert(FieldType::Number, FieldType::RichText, json!(12.5)),
+      Some(json!("12.5"))
+    );
+    assert_eq!(
+      convert(FieldType::Number, FieldType::RichText, json!("42")),
+      Some(json!("42"))
+    );
+  }
+
+  #[test]
+  fn text_to_number_test() {
+    assert_eq!(
+      convert(FieldType::RichText, FieldType::Number, json!(" 3.14 ")),
+      Some(json!("3.14"))
+    );
+    assert_eq!(
+      convert(FieldType::RichText, FieldType::Number, json!("three")),
+      None
+    );
+  }
+
+  #[test]
+  fn empty_cells_are_cleared_test() {
+    for (from, to) in [
+      (FieldType::RichText, FieldType::Number),
+      (FieldType::MultiSelect, FieldType::SingleSelect),
+      (FieldType::Checklist, FieldType::RichText),
+    ] {
+      for value in [json!(null), json!("  "), json!([])] {
+        assert_eq!(convert(from.clone(), to.clone(), value), Some(Value::Null));
+      }
+    }
+  }
+
+  #[test]
+  fn text_to_select_test() {
+    assert_eq!(
+      convert(
+        FieldType::RichText,
+        FieldType::SingleSelect,
+        json!(" Done ")
+      ),
+      Some(json!("Done"))
+    );
+    assert_eq!(
+      convert(
+        FieldType::RichText,
+        FieldType::MultiSelect,
+        json!("red, green,,blue")
+      ),
+      Some(json!(["red", "green", "blue"]))
+    );
+  }
+
+  #[test]
+  fn select_to_text_test() {
+    assert_eq!(
+      convert(
+        FieldType::MultiSelect,
+        FieldType::RichText,
+        json!("red,green")
+      ),
+      Some(json!("red, green"))
+    );
+    assert_eq!(
+      convert(
+        FieldType::SingleSelect,
+        FieldType::RichText,
+        json!(["Done"])
+      ),
+      Some(json!("Done"))
+    );
+  }
+
+  #[test]
+  fn select_to_select_test() {
+    assert_eq!(
+      convert(
+        FieldType::SingleSelect,
+        FieldType::MultiSelect,
+        json!("Done")
+      ),
+      Some(json!(["Done"]))
+    );
+    assert_eq!(
+      convert(
+        FieldType::MultiSelect,
+        FieldType::SingleSelect,
+        json!(["Done"])
+      ),
+      Some(json!("Done"))
+    );
+    // a single-select holds one option only
+    assert_eq!(
+      convert(
+        FieldType::MultiSelect,
+        FieldType::SingleSelect,
+        json!(["red", "green"])
+      ),
+      None
+    );
+  }
+
+  #[test]
+  fn checkbox_conversions_test() {
+    assert_eq!(
+      convert(FieldType::RichText, FieldType::Checkbox, json!("yes")),
+      Some(json!(true))
+    );
+    assert_eq!(
+      convert(FieldType::RichText, FieldType::Checkbox, json!("maybe")),
+      None
+    );
+    assert_eq!(
+      convert(FieldType::Number, FieldType::Checkbox, json!(0)),
+      Some(json!(false))
+    );
+    assert_eq!(
+      convert(FieldType::Checkbox, FieldType::Number, json!(true)),
+      Some(json!(1))
+    );
+    assert_eq!(
+      convert(FieldType::Checkbox, FieldType::RichText, json!("No")),
+      Some(json!("No"))
+    );
+  }
+
+  #[test]
+  fn date_conversions_test() {
+    assert_eq!(
+      convert(
+        FieldType::RichText,
+        FieldType::DateTime,
+        json!("2024-03-01")
+      ),
+      Some(json!(1709251200))
+    );
+    assert_eq!(
+      convert(FieldType::RichText, FieldType::DateTime, json!("next week")),
+      None
+    );
+    assert_eq!(
+      convert(FieldType::Number, FieldType::DateTime, json!(1709251200)),
+      Some(json!(1709251200))
+    );
+    assert_eq!(
+      convert(FieldType::DateTime, FieldType::RichText, json!(1709251200)),
+      Some(json!("2024-03-01"))
+    );
+    assert_eq!(
+      convert(FieldType::DateTime, FieldType::RichText, json!(1709254800)),
+      Some(json!("2024-03-01T01:00:00+00:00"))
+    );
+  }
+
+  #[test]
+  fn text_to_url_test() {
+    assert_eq!(
+      convert(
+        FieldType::RichText,
+        FieldType::URL,
+        json!(" https://appflowy.io ")
+      ),
+      Some(json!("https://appflowy.io"))
+    );
+  }
+
+  #[test]
+  fn unsupported_conversions_are_inconvertible_test() {
+    assert_eq!(
+      convert(FieldType::DateTime, FieldType::Number, json!(1709251200)),
+      None
+    );
+    assert_eq!(
+      convert(FieldType::Checkbox, FieldType::SingleSelect, json!(true)),
+      None
+    );
+  }
+
+  fn view(filters: Vec<HashMap<String, Any>>, groups: Vec<HashMap<String, Any>>) -> DatabaseView {
+    DatabaseView {
+      id: "view".to_string(),
+      filters,
+      group_settings: groups,
+      ..Default::default()
+    }
+  }
+
+  fn setting(id: &str, field_id: &str) -> HashMap<String, Any> {
+    HashMap::from([
+      ("id".to_string(), Any::String(id.into())),
+      ("field_id".to_string(), Any::String(field_id.into())),
+      ("ty".to_string(), Any::BigInt(FieldType::RichText.into())),
+    ])
+  }
+
+  #[test]
+  fn filters_of_another_family_are_dropped_test() {
+    let mut views = vec![view(
+      vec![setting("f1", "field"), setting("f2", "other")],
+      vec![setting("g1", "field")],
+    )];
+    let dropped = update_dependent_views(
+      &mut views,
+      "field",
+      &FieldType::RichText,
+      &FieldType::SingleSelect,
+    );
+    assert_eq!(
+      dropped,
+      vec![
+        AFDroppedViewSetting {
+          view_id: "view".to_string(),
+          kind: AFViewSettingKind::Filter,
+          id: "f1".to_string(),
+        },
+        AFDroppedViewSetting {
+          view_id: "view".to_string(),
+          kind: AFViewSettingKind::Group,
+          id: "g1".to_string(),
+        },
+      ]
+    );
+    assert_eq!(views[0].filters.len(), 1);
+    assert_eq!(setting_str(&views[0].filters[0], "id"), Some("f2"));
+    assert!(views[0].group_settings.is_empty());
+  }
+
+  #[test]
+  fn filters_of_the_same_family_are_kept_test() {
+    let nested = HashMap::from([
+      ("id".to_string(), Any::String("f2".into())),
+      (
+        "children".to_string(),
+        Any::Array(vec![Any::Map(Arc::new(setting("f3", "field")))].into()),
+      ),
+    ]);
+    let mut views = vec![view(vec![setting("f1", "field"), nested], vec![])];
+    let dropped =
+      update_dependent_views(&mut views, "field", &FieldType::RichText, &FieldType::URL);
+    assert!(dropped.is_empty());
+    let url_type = Any::BigInt(FieldType::URL.into());
+    assert_eq!(views[0].filters[0].get("ty"), Some(&url_type));
+    let Some(Any::Array(children)) = views[0].filters[1].get("children") else {
+      panic!("the nested filters are kept");
+    };
+    let Any::Map(child) = &children[0] else {
+      panic!("the nested filter is a map");
+    };
+    assert_eq!(child.get("ty"), Some(&url_type));
+  }
+}
@@ -50,14 +50,19 @@ pub fn private_space_and_trash_view_ids(folder: &Folder) -> PrivateSpaceAndTrash
 }
 
 /// Return all folders belonging to a workspace, excluding private sections which the user does not have access to.
+/// The views whose collab is deleted are left out like the ones in the trash.
 pub fn collab_folder_to_folder_view(
   workspace_id: Uuid,
   root_view_id: &str,
   folder: &Folder,
   max_depth: u32,
   pubished_view_ids: &HashSet<String>,
+  deleted_view_ids: &HashSet<String>,
 ) -> Result<FolderView, AppError> {
-  let private_space_and_trash_view_ids = private_space_and_trash_view_ids(folder);
+  let mut private_space_and_trash_view_ids = private_space_and_trash_view_ids(folder);
+  private_space_and_trash_view_ids
+    .view_ids_in_trash
+    .extend(deleted_view_ids.iter().cloned());
 
   to_folder_view(
     workspace_id,
@@ -1,4 +1,19 @@
+pub mod batch_get;
+pub mod bulk_create;
+pub mod compaction;
+pub mod diff;
+pub mod document_find;
+pub mod document_outline;
+pub mod editing_lock;
+pub mod field_conversion;
 pub mod folder_view;
+pub mod mode;
 pub mod ops;
+pub mod presence;
 pub mod publish_outline;
+pub mod recovery;
+pub mod replay;
+pub mod row_access;
+pub mod trash;
 pub mod utils;
+pub mod version_history;
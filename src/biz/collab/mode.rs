@@ -0,0 +1,61 @@
+use app_error::AppError;
+use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
+use database::collab_mode::{select_collab_mode, select_opaque_collab_oids, upsert_collab_mode};
+use database_entity::dto::CollabMode;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub async fn get_collab_mode(pg_pool: &PgPool, object_id: &str) -> Result<CollabMode, AppError> {
+  select_collab_mode(pg_pool, object_id).await
+}
+
+/// Sets the mode of the collab. The server can't convert the content of an existing collab, so
+/// the mode can only be chosen before the collab is created.
+pub async fn set_collab_mode(
+  pg_pool: &PgPool,
+  collab_storage: &CollabAccessControlStorage,
+  workspace_id: &Uuid,
+  object_id: &str,
+  mode: CollabMode,
+  uid: i64,
+) -> Result<(), AppError> {
+  let current = select_collab_mode(pg_pool, object_id).await?;
+  if current == mode {
+    return Ok(());
+  }
+  if collab_storage
+    .is_exist(&workspace_id.to_string(), object_id)
+    .await?
+  {
+    return Err(AppError::InvalidRequest(format!(
+      "The mode of collab {} can't be changed once it's created",
+      object_id
+    )));
+  }
+  upsert_collab_mode(pg_pool, object_id, workspace_id, mode, uid).await
+}
+
+/// Fails with [AppError::EncryptedCollab] when the collab is opaque, for the features which need
+/// to read its content.
+pub async fn ensure_plaintext_collab(
+  pg_pool: &PgPool,
+  object_id: &str,
+  feature: &str,
+) -> Result<(), AppError> {
+  ensure_plaintext_collabs(pg_pool, &[object_id.to_string()], feature).await
+}
+
+pub async fn ensure_plaintext_collabs(
+  pg_pool: &PgPool,
+  object_ids: &[String],
+  feature: &str,
+) -> Result<(), AppError> {
+  let opaque = select_opaque_collab_oids(pg_pool, object_ids).await?;
+  match opaque.into_iter().next() {
+    Some(object_id) => Err(AppError::EncryptedCollab(format!(
+      "{} of collab {}",
+      feature, object_id
+    ))),
+    None => Ok(()),
+  }
+}
@@ -30,6 +30,7 @@ use collab_folder::Folder;
 use collab_folder::SectionItem;
 use collab_folder::{CollabOrigin, SpaceInfo};
 use collab_rt_entity::user::RealtimeUser;
+use database::collab::select_deleted_collabs;
 use database::collab::select_last_updated_database_row_ids;
 use database::collab::select_workspace_database_oid;
 use database::collab::{CollabStorage, GetCollabOrigin};
@@ -45,6 +46,7 @@ use shared_entity::dto::workspace_dto::AFDatabaseField;
 use shared_entity::dto::workspace_dto::AFDatabaseRow;
 use shared_entity::dto::workspace_dto::AFDatabaseRowDetail;
 use shared_entity::dto::workspace_dto::AFInsertDatabaseField;
+use shared_entity::dto::workspace_dto::AddDatatabaseRow;
 use shared_entity::dto::workspace_dto::DatabaseRowUpdatedItem;
 use shared_entity::dto::workspace_dto::FavoriteFolderView;
 use shared_entity::dto::workspace_dto::FolderViewMinimal;
@@ -70,6 +72,7 @@ use super::folder_view::section_items_to_recent_folder_view;
 use super::folder_view::section_items_to_trash_folder_view;
 use super::folder_view::to_dto_folder_view_miminal;
 use super::publish_outline::collab_folder_to_published_outline;
+use super::utils::add_select_options;
 use super::utils::collab_to_bin;
 use super::utils::create_row_document;
 use super::utils::field_by_id_name_uniq;
@@ -80,6 +83,7 @@ use super::utils::get_latest_collab_folder;
 use super::utils::get_row_details_serde;
 use super::utils::type_option_reader_by_id;
 use super::utils::type_options_serde;
+use super::utils::validate_cell_values;
 use super::utils::write_to_database_row;
 use super::utils::CreatedRowDocument;
 use super::utils::DocChanges;
@@ -283,12 +287,18 @@ pub async fn get_user_workspace_structure(
     .into_iter()
     .map(|id| id.to_string())
     .collect();
+  let deleted_view_ids: HashSet<String> = select_deleted_collabs(pg_pool, &workspace_id)
+    .await?
+    .into_iter()
+    .map(|row| row.oid)
+    .collect();
   collab_folder_to_folder_view(
     workspace_id,
     root_view_id,
     &patched_folder,
     depth,
     &publish_view_ids,
+    &deleted_view_ids,
   )
 }
 
@@ -577,6 +587,7 @@ pub async fn insert_database_row(
     broadcast_update_with_timeout(
       collab_storage.clone(),
       workspace_uuid_str.to_string(),
+      workspace_uuid_str.to_string(),
       created_doc.folder_updates,
     )
     .await;
@@ -615,6 +626,7 @@ pub async fn insert_database_row(
   db_txn.commit().await?;
   broadcast_update_with_timeout(
     collab_storage,
+    workspace_uuid_str.to_string(),
     database_uuid_str.to_string(),
     db_collab_update,
   )
@@ -703,6 +715,7 @@ pub async fn upsert_database_row(
     .await?;
   broadcast_update_with_timeout(
     collab_storage.clone(),
+    workspace_uuid_str.to_string(),
     row_id.to_string(),
     db_row_collab_updates,
   )
@@ -725,7 +738,13 @@ pub async fn upsert_database_row(
             "updating database row document from server",
           )
           .await?;
-        broadcast_update_with_timeout(collab_storage, doc_id, doc_update).await;
+        broadcast_update_with_timeout(
+          collab_storage,
+          workspace_uuid_str.to_string(),
+          doc_id,
+          doc_update,
+        )
+        .await;
       },
       DocChanges::Insert(created_doc) => {
         let CreatedRowDocument {
@@ -766,6 +785,7 @@ pub async fn upsert_database_row(
         broadcast_update_with_timeout(
           collab_storage,
           workspace_uuid_str.to_string(),
+          workspace_uuid_str.to_string(),
           folder_updates,
         )
         .await;
@@ -777,6 +797,275 @@ pub async fn upsert_database_row(
   Ok(())
 }
 
+pub const MAX_DATABASE_ROWS_PER_BATCH: usize = 100;
+
+/// Inserts the rows after validating all of them, see [validate_cell_values]. The rows are
+/// inserted one by one, so the rows inserted before a failure are kept.
+/// Returns the ids of the new rows, in the order of the given rows.
+#[allow(clippy::too_many_arguments)]
+pub async fn batch_insert_database_rows(
+  collab_storage: Arc<CollabAccessControlStorage>,
+  pg_pool: &PgPool,
+  workspace_uuid_str: &str,
+  database_uuid_str: &str,
+  uid: i64,
+  rows: Vec<AddDatatabaseRow>,
+  create_select_options: bool,
+) -> Result<Vec<String>, AppError> {
+  if rows.is_empty() || rows.len() > MAX_DATABASE_ROWS_PER_BATCH {
+    return Err(AppError::InvalidRequest(format!(
+      "Expected between 1 and {} rows, got {}",
+      MAX_DATABASE_ROWS_PER_BATCH,
+      rows.len()
+    )));
+  }
+
+  let (cells, documents): (Vec<_>, Vec<_>) = rows
+    .into_iter()
+    .map(|row| (row.cells, row.document))
+    .unzip();
+  let cells = validate_database_cells(
+    &collab_storage,
+    pg_pool,
+    workspace_uuid_str,
+    database_uuid_str,
+    uid,
+    cells,
+    create_select_options,
+  )
+  .await?;
+
+  let mut row_ids = Vec::with_capacity(cells.len());
+  for (cell_value_by_id, document) in cells.into_iter().zip(documents) {
+    let row_id = insert_database_row(
+      collab_storage.clone(),
+      pg_pool,
+      workspace_uuid_str,
+      database_uuid_str,
+      uid,
+      None,
+      cell_value_by_id,
+      document,
+    )
+    .await?;
+    row_ids.push(row_id);
+  }
+  Ok(row_ids)
+}
+
+/// Updates the cells of an existing row of the database after validating them, see
+/// [validate_cell_values].
+#[allow(clippy::too_many_arguments)]
+pub async fn update_database_row(
+  collab_storage: Arc<CollabAccessControlStorage>,
+  pg_pool: &PgPool,
+  workspace_uuid_str: &str,
+  database_uuid_str: &str,
+  uid: i64,
+  row_id: &str,
+  cell_value_by_id: HashMap<String, serde_json::Value>,
+  row_doc_content: Option<String>,
+  create_select_options: bool,
+) -> Result<(), AppError> {
+  let row_exists = list_database_row_ids(&collab_storage, workspace_uuid_str, database_uuid_str)
+    .await?
+    .iter()
+    .any(|row| row.id == row_id);
+  if !row_exists {
+    return Err(AppError::RecordNotFound(format!(
+      "Row {} does not exist in database {}",
+      row_id, database_uuid_str
+    )));
+  }
+
+  let mut cells = validate_database_cells(
+    &collab_storage,
+    pg_pool,
+    workspace_uuid_str,
+    database_uuid_str,
+    uid,
+    vec![cell_value_by_id],
+    create_select_options,
+  )
+  .await?;
+  upsert_database_row(
+    collab_storage,
+    pg_pool,
+    workspace_uuid_str,
+    database_uuid_str,
+    uid,
+    row_id,
+    cells.pop().unwrap_or_default(),
+    row_doc_content,
+  )
+  .await
+}
+
+/// Removes the row from all views of the database. The row collab itself is kept, the same as
+/// when a row is deleted from the client.
+pub async fn delete_database_row(
+  collab_storage: Arc<CollabAccessControlStorage>,
+  pg_pool: &PgPool,
+  workspace_uuid_str: &str,
+  database_uuid_str: &str,
+  uid: i64,
+  row_id: &str,
+) -> Result<(), AppError> {
+  let (mut db_collab, db_body) =
+    get_latest_collab_database_body(&collab_storage, workspace_uuid_str, database_uuid_str).await?;
+
+  let db_collab_update = {
+    let mut txn = db_collab.transact_mut();
+    let mut db_views = db_body.views.get_all_views(&txn);
+    let mut removed = false;
+    for db_view in db_views.iter_mut() {
+      let row_count = db_view.row_orders.len();
+      db_view
+        .row_orders
+        .retain(|row_order| row_order.id.to_string() != row_id);
+      removed |= db_view.row_orders.len() != row_count;
+    }
+    if !removed {
+      return Err(AppError::RecordNotFound(format!(
+        "Row {} does not exist in database {}",
+        row_id, database_uuid_str
+      )));
+    }
+    db_body.views.clear(&mut txn);
+    for view in db_views {
+      db_body.views.insert_view(&mut txn, view);
+    }
+    txn.encode_update_v1()
+  };
+
+  save_database_collab(
+    collab_storage,
+    pg_pool,
+    workspace_uuid_str,
+    database_uuid_str,
+    uid,
+    db_collab,
+    db_collab_update,
+  )
+  .await
+}
+
+/// Validates the cells of each row against the fields of the database. Select options which do
+/// not exist yet are added to their field when `create_select_options` is set, and rejected
+/// otherwise. Returns the cells of each row keyed by field id.
+#[allow(clippy::too_many_arguments)]
+async fn validate_database_cells(
+  collab_storage: &Arc<CollabAccessControlStorage>,
+  pg_pool: &PgPool,
+  workspace_uuid_str: &str,
+  database_uuid_str: &str,
+  uid: i64,
+  rows: Vec<HashMap<String, serde_json::Value>>,
+  create_select_options: bool,
+) -> Result<Vec<HashMap<String, serde_json::Value>>, AppError> {
+  let (mut db_collab, db_body) =
+    get_latest_collab_database_body(collab_storage, workspace_uuid_str, database_uuid_str).await?;
+  let fields = db_body.fields.get_all_fields(&db_collab.transact());
+
+  let mut missing_select_options: HashMap<String, Vec<String>> = HashMap::new();
+  let mut validated_rows = Vec::with_capacity(rows.len());
+  for cell_value_by_id in rows {
+    let validated = validate_cell_values(&fields, cell_value_by_id)?;
+    for (field_id, names) in validated.missing_select_options {
+      let field_missing = missing_select_options.entry(field_id).or_default();
+      for name in names {
+        if !field_missing.contains(&name) {
+          field_missing.push(name);
+        }
+      }
+    }
+    validated_rows.push(validated.cell_value_by_id);
+  }
+  if missing_select_options.is_empty() {
+    return Ok(validated_rows);
+  }
+
+  if !create_select_options {
+    let (field_id, names) = missing_select_options
+      .iter()
+      .next()
+      .expect("missing select options is not empty");
+    let field_name = fields
+      .iter()
+      .find(|field| &field.id == field_id)
+      .map(|field| field.name.as_str())
+      .unwrap_or(field_id);
+    return Err(AppError::InvalidRequest(format!(
+      "Select option `{}` does not exist in field `{}`",
+      names.join("`, `"),
+      field_name
+    )));
+  }
+
+  let db_collab_update = {
+    let mut txn = db_collab.transact_mut();
+    for field in &fields {
+      let names = match missing_select_options.get(&field.id) {
+        Some(names) => names,
+        None => continue,
+      };
+      if let Some(type_option_data) = add_select_options(field, names) {
+        let field_type = FieldType::from(field.field_type);
+        db_body.fields.update_field(&mut txn, &field.id, |f| {
+          f.set_type_option(field_type.into(), Some(type_option_data.clone()));
+        });
+      }
+    }
+    txn.encode_update_v1()
+  };
+  save_database_collab(
+    collab_storage.clone(),
+    pg_pool,
+    workspace_uuid_str,
+    database_uuid_str,
+    uid,
+    db_collab,
+    db_collab_update,
+  )
+  .await?;
+  Ok(validated_rows)
+}
+
+pub async fn save_database_collab(
+  collab_storage: Arc<CollabAccessControlStorage>,
+  pg_pool: &PgPool,
+  workspace_uuid_str: &str,
+  database_uuid_str: &str,
+  uid: i64,
+  db_collab: Collab,
+  db_collab_update: Vec<u8>,
+) -> Result<(), AppError> {
+  let updated_db_collab = collab_to_bin(db_collab, CollabType::Database).await?;
+  let mut pg_txn = pg_pool.begin().await?;
+  collab_storage
+    .upsert_new_collab_with_transaction(
+      workspace_uuid_str,
+      &uid,
+      CollabParams {
+        object_id: database_uuid_str.to_string(),
+        encoded_collab_v1: updated_db_collab.into(),
+        collab_type: CollabType::Database,
+      },
+      &mut pg_txn,
+      "inserting updated database from server",
+    )
+    .await?;
+  pg_txn.commit().await?;
+  broadcast_update_with_timeout(
+    collab_storage,
+    workspace_uuid_str.to_string(),
+    database_uuid_str.to_string(),
+    db_collab_update,
+  )
+  .await;
+  Ok(())
+}
+
 pub async fn get_database_fields(
   collab_storage: &CollabAccessControlStorage,
   workspace_uuid_str: &str,
@@ -865,7 +1154,13 @@ pub async fn add_database_field(
     .await?;
 
   pg_txn.commit().await?;
-  broadcast_update_with_timeout(collab_storage, database_id.to_string(), db_collab_update).await;
+  broadcast_update_with_timeout(
+    collab_storage,
+    workspace_id.to_string(),
+    database_id.to_string(),
+    db_collab_update,
+  )
+  .await;
 
   Ok(new_id)
 }
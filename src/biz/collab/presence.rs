@@ -0,0 +1,31 @@
+use app_error::AppError;
+use chrono::{DateTime, Utc};
+use collab_stream::presence::PresenceStore;
+use redis::aio::ConnectionManager;
+use shared_entity::dto::workspace_dto::AFCollabPresence;
+use uuid::Uuid;
+
+/// Returns the clients currently connected to the collab, on any realtime server. A user
+/// connected from several devices, or with several copies of the collab, appears once for each.
+pub async fn get_collab_presence(
+  redis: &ConnectionManager,
+  workspace_id: &Uuid,
+  object_id: &Uuid,
+) -> Result<Vec<AFCollabPresence>, AppError> {
+  let presences = PresenceStore::new(redis.clone())
+    .get(&workspace_id.to_string(), &object_id.to_string())
+    .await
+    .map_err(|err| AppError::Internal(err.into()))?;
+  Ok(
+    presences
+      .into_iter()
+      .map(|presence| AFCollabPresence {
+        uid: presence.uid,
+        device_id: presence.device_id,
+        cursor: presence.state.get("selection").cloned(),
+        last_heartbeat_at: DateTime::from_timestamp_millis(presence.last_seen as i64)
+          .unwrap_or_else(Utc::now),
+      })
+      .collect(),
+  )
+}
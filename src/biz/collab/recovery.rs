@@ -0,0 +1,112 @@
+use app_error::AppError;
+use appflowy_collaborate::collab::cache::CollabCache;
+use collab_entity::CollabType;
+use database::collab::CollabStorage;
+use database::collab_verification::{
+  select_collab_verification_report, select_collab_verification_reports,
+  update_collab_verification_report_repaired,
+};
+use database::pg_row::AFCollabVerificationReportRow;
+use database::workspace::select_workspace;
+use database_entity::dto::CollabParams;
+use shared_entity::dto::collab_recovery_dto::{CollabVerificationReport, RecoveredCollabSnapshot};
+use sqlx::PgPool;
+use tracing::info;
+
+const DEFAULT_REPORT_LIMIT: i64 = 50;
+const MAX_REPORT_LIMIT: i64 = 200;
+
+pub async fn list_collab_verification_reports(
+  pg_pool: &PgPool,
+  include_repaired: bool,
+  offset: Option<i64>,
+  limit: Option<i64>,
+) -> Result<Vec<CollabVerificationReport>, AppError> {
+  let offset = offset.unwrap_or(0).max(0);
+  let limit = limit
+    .unwrap_or(DEFAULT_REPORT_LIMIT)
+    .clamp(1, MAX_REPORT_LIMIT);
+  let reports = select_collab_verification_reports(pg_pool, include_repaired, offset, limit)
+    .await?
+    .into_iter()
+    .map(collab_verification_report)
+    .collect();
+  Ok(reports)
+}
+
+/// Confirms the repair of a collab which was recovered from a snapshot. The snapshot it was served
+/// from replaces the stored state, and the realtime server accepts updates to the collab again.
+/// When no snapshot could be recovered, the admin is expected to have repaired the collab by other
+/// means and the report is only marked as repaired.
+pub async fn confirm_collab_repair<S>(
+  pg_pool: &PgPool,
+  storage: &S,
+  collab_cache: &CollabCache,
+  repaired_by: i64,
+  report_id: i64,
+) -> Result<CollabVerificationReport, AppError>
+where
+  S: CollabStorage + ?Sized,
+{
+  let report = select_collab_verification_report(pg_pool, report_id)
+    .await?
+    .ok_or_else(|| AppError::RecordNotFound(format!("report {} not found", report_id)))?;
+  if report.repaired_at.is_some() {
+    return Err(AppError::InvalidRequest(format!(
+      "report {} is already repaired",
+      report_id
+    )));
+  }
+
+  if let Some(snapshot_id) = report.recovered_snapshot_id {
+    let workspace_id = report.workspace_id.to_string();
+    let snapshot = storage
+      .get_collab_snapshot(&workspace_id, &report.object_id, &snapshot_id)
+      .await?;
+    // the collab keeps its owner, the admin is usually not a member of the workspace
+    let owner_uid = select_workspace(pg_pool, &report.workspace_id)
+      .await?
+      .owner_uid
+      .unwrap_or(repaired_by);
+    let params = CollabParams {
+      object_id: report.object_id.clone(),
+      encoded_collab_v1: snapshot.encoded_collab_v1.into(),
+      collab_type: CollabType::from(report.collab_type),
+    };
+    collab_cache
+      .insert_encode_collab_to_disk(&workspace_id, &owner_uid, params)
+      .await?;
+    info!(
+      "restored collab {} from snapshot {}",
+      report.object_id, snapshot_id
+    );
+  }
+
+  let report = update_collab_verification_report_repaired(pg_pool, report_id, repaired_by)
+    .await?
+    .ok_or_else(|| AppError::InvalidRequest(format!("report {} is already repaired", report_id)))?;
+  Ok(collab_verification_report(report))
+}
+
+fn collab_verification_report(row: AFCollabVerificationReportRow) -> CollabVerificationReport {
+  let recovered_from = match (row.recovered_snapshot_id, row.recovered_snapshot_at) {
+    (Some(snapshot_id), Some(created_at)) => Some(RecoveredCollabSnapshot {
+      snapshot_id,
+      created_at,
+    }),
+    _ => None,
+  };
+  CollabVerificationReport {
+    report_id: row.report_id,
+    workspace_id: row.workspace_id,
+    object_id: row.object_id,
+    collab_type: CollabType::from(row.collab_type),
+    detected_on: row.detected_on,
+    error: row.error,
+    recovered_from,
+    detection_count: row.detection_count,
+    created_at: row.created_at,
+    last_detected_at: row.last_detected_at,
+    repaired_at: row.repaired_at,
+  }
+}
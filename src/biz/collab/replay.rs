@@ -0,0 +1,245 @@
+use anyhow::anyhow;
+use app_error::AppError;
+use collab::core::collab::DataSource;
+use collab::core::origin::CollabOrigin;
+use collab::entity::{EncodedCollab, EncoderVersion};
+use collab::preclude::Collab;
+use collab_stream::model::{CollabStreamUpdate, MessageId, UpdateFlags};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use yrs::{ReadTxn, StateVector, Transact};
+
+use super::diff::hash_state_vector;
+
+/// An entry of the update stream of a collab, as read from Redis or from a dump file written by
+/// the `collab_replay` tool.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayEntry {
+  pub message_id: String,
+  pub flags: u8,
+  pub data: Vec<u8>,
+}
+
+impl ReplayEntry {
+  pub fn from_stream(message_id: MessageId, update: CollabStreamUpdate) -> Self {
+    Self {
+      message_id: message_id.to_string(),
+      flags: update.flags.into(),
+      data: update.data,
+    }
+  }
+
+  fn flags(&self) -> UpdateFlags {
+    self.flags.into()
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollabStateHash {
+  pub state_vector_hash: String,
+  pub content_hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+  /// The entry couldn't be decoded or applied.
+  Invalid { message_id: String, error: String },
+  /// The entry depends on updates which are neither in the baseline nor in the previous entries.
+  Pending { message_id: String },
+  /// Once the entry is applied, the replayed collab contains updates of these clients which are
+  /// not in the stored collab.
+  Ahead {
+    message_id: String,
+    client_ids: Vec<u64>,
+  },
+}
+
+#[derive(Debug)]
+pub struct ReplayReport {
+  pub applied: usize,
+  /// The entries marking that the collab was replaced by a compacted copy. The entries before the
+  /// last one belong to the previous copy.
+  pub resets: Vec<String>,
+  pub divergence: Option<Divergence>,
+  /// The clients whose updates are in the stored collab but weren't replayed.
+  pub missing_client_ids: Vec<u64>,
+  pub replayed: CollabStateHash,
+  pub live: CollabStateHash,
+}
+
+impl ReplayReport {
+  pub fn is_consistent(&self) -> bool {
+    self.divergence.is_none() && self.replayed == self.live
+  }
+}
+
+pub fn open_collab(object_id: &str, encoded_collab: &EncodedCollab) -> Result<Collab, AppError> {
+  let doc_state = encoded_collab.doc_state.to_vec();
+  let data_source = match encoded_collab.version {
+    EncoderVersion::V1 => DataSource::DocStateV1(doc_state),
+    EncoderVersion::V2 => DataSource::DocStateV2(doc_state),
+  };
+  Collab::new_with_source(CollabOrigin::Server, object_id, data_source, vec![], false)
+    .map_err(|err| AppError::Internal(anyhow!("Failed to open collab: {}", err)))
+}
+
+/// Applies the entries in order on top of the baseline, checking after each one that the
+/// replayed collab doesn't contain anything the stored collab doesn't. Only the first
+/// divergence is reported, the remaining entries are still applied to compare the final states.
+pub fn replay_entries(
+  baseline: &mut Collab,
+  entries: Vec<ReplayEntry>,
+  live: &Collab,
+) -> ReplayReport {
+  let live_state_vector = live.transact().state_vector();
+  let mut applied = 0;
+  let mut resets = vec![];
+  let mut divergence = None;
+  for entry in entries {
+    let flags = entry.flags();
+    if flags.is_reset() {
+      resets.push(entry.message_id.clone());
+    }
+    let message_id = entry.message_id;
+    let update = CollabStreamUpdate::new(entry.data, CollabOrigin::Empty, flags).into_update();
+    let mut txn = baseline.transact_mut();
+    let result = update
+      .map_err(|err| err.to_string())
+      .and_then(|update| txn.apply_update(update).map_err(|err| err.to_string()));
+    if let Err(error) = result {
+      divergence.get_or_insert(Divergence::Invalid { message_id, error });
+      continue;
+    }
+    applied += 1;
+    if divergence.is_some() {
+      continue;
+    }
+    if txn.store().pending_update().is_some() || txn.store().pending_ds().is_some() {
+      divergence = Some(Divergence::Pending { message_id });
+      continue;
+    }
+    let client_ids = clients_ahead(&txn.state_vector(), &live_state_vector);
+    if !client_ids.is_empty() {
+      divergence = Some(Divergence::Ahead {
+        message_id,
+        client_ids,
+      });
+    }
+  }
+
+  let replayed_state_vector = baseline.transact().state_vector();
+  ReplayReport {
+    applied,
+    resets,
+    divergence,
+    missing_client_ids: clients_ahead(&live_state_vector, &replayed_state_vector),
+    replayed: collab_state_hash(baseline),
+    live: collab_state_hash(live),
+  }
+}
+
+pub fn collab_state_hash(collab: &Collab) -> CollabStateHash {
+  let state_vector_hash = hash_state_vector(&collab.transact().state_vector());
+  let content = serde_json::to_vec(&collab.to_json_value()).unwrap_or_default();
+  CollabStateHash {
+    state_vector_hash,
+    content_hash: format!("{:x}", Sha256::digest(content)),
+  }
+}
+
+/// The clients whose clock in `state_vector` is ahead of the one in `other`, sorted.
+fn clients_ahead(state_vector: &StateVector, other: &StateVector) -> Vec<u64> {
+  let mut client_ids = state_vector
+    .iter()
+    .filter(|(client_id, clock)| **clock > other.get(client_id))
+    .map(|(client_id, _)| *client_id)
+    .collect::<Vec<_>>();
+  client_ids.sort_unstable();
+  client_ids
+}
+
+#[cfg(test)]
+mod tests {
+  use yrs::updates::decoder::Decode;
+  use yrs::{Doc, Text, Update};
+
+  use super::*;
+
+  fn text_update(doc: &Doc, content: &str) -> Vec<u8> {
+    let text = doc.get_or_insert_text("text");
+    let mut txn = doc.transact_mut();
+    let before = txn.state_vector();
+    let len = text.len(&txn);
+    text.insert(&mut txn, len, content);
+    txn.encode_state_as_update_v1(&before)
+  }
+
+  fn entry(message_id: &str, data: Vec<u8>) -> ReplayEntry {
+    ReplayEntry {
+      message_id: message_id.to_string(),
+      flags: 0,
+      data,
+    }
+  }
+
+  fn collab_from_updates(updates: &[&Vec<u8>]) -> Collab {
+    let mut collab = Collab::new_with_origin(CollabOrigin::Server, "object", vec![], false);
+    let mut txn = collab.transact_mut();
+    for update in updates {
+      txn
+        .apply_update(Update::decode_v1(update).unwrap())
+        .unwrap();
+    }
+    drop(txn);
+    collab
+  }
+
+  #[test]
+  fn replay_reports_first_diverging_entry_test() {
+    let doc = Doc::with_client_id(1);
+    let first = text_update(&doc, "hello");
+    let second = text_update(&doc, " world");
+    let other = Doc::with_client_id(2);
+    let unknown = text_update(&other, "lost");
+
+    let live = collab_from_updates(&[&first, &second]);
+    let mut baseline = Collab::new_with_origin(CollabOrigin::Server, "object", vec![], false);
+    let report = replay_entries(
+      &mut baseline,
+      vec![entry("1-0", first.clone()), entry("2-0", second.clone())],
+      &live,
+    );
+    assert!(report.is_consistent());
+    assert_eq!(report.applied, 2);
+
+    let mut baseline = Collab::new_with_origin(CollabOrigin::Server, "object", vec![], false);
+    let report = replay_entries(
+      &mut baseline,
+      vec![
+        entry("1-0", first),
+        entry("2-0", unknown),
+        entry("3-0", vec![0xff]),
+      ],
+      &live,
+    );
+    assert!(!report.is_consistent());
+    assert_eq!(
+      report.divergence,
+      Some(Divergence::Ahead {
+        message_id: "2-0".to_string(),
+        client_ids: vec![2],
+      })
+    );
+    assert_eq!(report.missing_client_ids, vec![1]);
+
+    // an entry depending on updates which weren't replayed
+    let mut baseline = Collab::new_with_origin(CollabOrigin::Server, "object", vec![], false);
+    let report = replay_entries(&mut baseline, vec![entry("2-0", second)], &live);
+    assert_eq!(
+      report.divergence,
+      Some(Divergence::Pending {
+        message_id: "2-0".to_string()
+      })
+    );
+  }
+}
@@ -0,0 +1,741 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use access_control::collab::RealtimeAccessControl;
+use app_error::AppError;
+use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
+use async_trait::async_trait;
+use collab_database::database::DatabaseBody;
+use collab_database::rows::RowDetail;
+use collab_database::workspace_database::NoPersistenceDatabaseCollabService;
+use collab_entity::{CollabType, EncodedCollab};
+use dashmap::DashMap;
+use database::collab::CollabStorage;
+use database::database_row_access::{
+  delete_database_row_access, select_database_row_access, select_restricted_database_row_access,
+  upsert_database_row_access,
+};
+use database::pg_row::AFDatabaseRowAccessRow;
+use database::workspace::select_workspace_member;
+use database_entity::dto::{AFRole, QueryCollab, QueryCollabResult};
+use shared_entity::dto::workspace_dto::{AFDatabaseRowAccess, UpsertDatabaseRowAccess};
+use sqlx::PgPool;
+use tracing::warn;
+use uuid::Uuid;
+use yrs::updates::encoder::Encode;
+use yrs::{ReadTxn, StateVector, Transact};
+
+use super::utils::{
+  collab_from_doc_state, get_latest_collab_database_body, get_row_details_serde,
+  type_option_reader_by_id,
+};
+
+/// The restricted rows are loaded from the collabs, the cells naming who can see a row are only
+/// read again once this long has passed.
+const RESTRICTED_ROWS_TTL: Duration = Duration::from_secs(30);
+/// A row created since the restricted rows were loaded makes them load again, at most this often.
+const RESTRICTED_ROWS_MIN_RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Enforces the row-level access rules of the databases. For a database whose rows are
+/// restricted, a member who isn't an owner:
+/// - can neither read nor write the rows which don't name them,
+/// - only gets a projected copy of the database collab, without these rows, which they can't
+///   write to and which isn't synced in realtime,
+/// - only gets the rows naming them from the row listing API.
+///
+/// A row names a member by their uid or their email, never by their name, which they can change
+/// at will. A row whose cell wasn't read yet, such as a row created since the rows were loaded,
+/// doesn't name anyone.
+#[derive(Clone)]
+pub struct RowAccessControl {
+  pg_pool: PgPool,
+  collab_storage: Arc<CollabAccessControlStorage>,
+  restricted_rows: Arc<DashMap<Uuid, Arc<RestrictedRows>>>,
+}
+
+/// The restricted databases of a workspace.
+struct RestrictedRows {
+  loaded_at: Instant,
+  databases: HashMap<String, RestrictedDatabase>,
+  /// Database id by row id.
+  row_databases: HashMap<String, String>,
+  members: DashMap<i64, Option<RowAccessMember>>,
+  /// The database of the objects which weren't listed when the rows were loaded, `None` when
+  /// the object isn't a row of a restricted database.
+  unlisted_rows: DashMap<String, Option<String>>,
+}
+
+struct RestrictedDatabase {
+  created_by: i64,
+  /// The uids and lowercased emails found in the cell of the rule's field, by row id.
+  row_owners: HashMap<String, HashSet<String>>,
+}
+
+#[derive(Clone)]
+struct RowAccessMember {
+  uid: i64,
+  email: String,
+  is_owner: bool,
+}
+
+enum RowAccess {
+  Visible,
+  Hidden,
+  /// A restricted database, of which only these rows can be seen.
+  Projected(HashSet<String>),
+}
+
+impl RestrictedDatabase {
+  fn sees_all_rows(&self, member: &RowAccessMember) -> bool {
+    member.is_owner || member.uid == self.created_by
+  }
+
+  fn is_visible(&self, row_id: &str, member: &RowAccessMember) -> bool {
+    self.row_owners.get(row_id).is_some_and(|owners| {
+      owners.contains(&member.uid.to_string()) || owners.contains(&member.email.to_lowercase())
+    })
+  }
+
+  fn visible_row_ids(&self, member: &RowAccessMember) -> HashSet<String> {
+    self
+      .row_owners
+      .keys()
+      .filter(|row_id| self.is_visible(row_id, member))
+      .cloned()
+      .collect()
+  }
+}
+
+impl RowAccessControl {
+  pub fn new(pg_pool: PgPool, collab_storage: Arc<CollabAccessControlStorage>) -> Self {
+    Self {
+      pg_pool,
+      collab_storage,
+      restricted_rows: Arc::new(DashMap::new()),
+    }
+  }
+
+  /// The rows of the database the user can see. `None` when the rows of the database aren't
+  /// restricted or when the user sees all of them.
+  pub async fn visible_row_ids(
+    &self,
+    workspace_id: &str,
+    uid: i64,
+    database_id: &str,
+  ) -> Result<Option<HashSet<String>>, AppError> {
+    match self.row_access(workspace_id, uid, database_id).await? {
+      RowAccess::Projected(visible_row_ids) => Ok(Some(visible_row_ids)),
+      RowAccess::Visible | RowAccess::Hidden => Ok(None),
+    }
+  }
+
+  /// Fails when the object is a row hidden from the user. A restricted database can be read, as
+  /// long as only its projected copy is returned.
+  pub async fn enforce_read(
+    &self,
+    workspace_id: &str,
+    uid: i64,
+    object_id: &str,
+  ) -> Result<(), AppError> {
+    match self.row_access(workspace_id, uid, object_id).await? {
+      RowAccess::Hidden => Err(hidden_row_error(object_id)),
+      RowAccess::Visible | RowAccess::Projected(_) => Ok(()),
+    }
+  }
+
+  /// Fails when the object is a row hidden from the user, or a restricted database the user only
+  /// gets a projected copy of.
+  pub async fn enforce_write(
+    &self,
+    workspace_id: &str,
+    uid: i64,
+    object_id: &str,
+  ) -> Result<(), AppError> {
+    match self.row_access(workspace_id, uid, object_id).await? {
+      RowAccess::Visible => Ok(()),
+      RowAccess::Hidden => Err(hidden_row_error(object_id)),
+      RowAccess::Projected(_) => Err(AppError::NotEnoughPermissions(format!(
+        "The rows of database {} are restricted, it can only be changed through the row API",
+        object_id
+      ))),
+    }
+  }
+
+  /// Returns the copy of the collab the user can read: the collab itself, unless it's a row
+  /// hidden from the user, or a restricted database, of which only the visible rows are kept.
+  pub async fn project_collab(
+    &self,
+    workspace_id: &str,
+    uid: i64,
+    object_id: &str,
+    encoded_collab: EncodedCollab,
+  ) -> Result<EncodedCollab, AppError> {
+    match self.row_access(workspace_id, uid, object_id).await? {
+      RowAccess::Visible => Ok(encoded_collab),
+      RowAccess::Hidden => Err(hidden_row_error(object_id)),
+      RowAccess::Projected(visible_row_ids) => {
+        let object_id = object_id.to_string();
+        tokio::task::spawn_blocking(move || {
+          project_database_collab(&object_id, encoded_collab, &visible_row_ids)
+        })
+        .await
+        .map_err(|err| AppError::Internal(err.into()))?
+      },
+    }
+  }
+
+  /// Same as [Self::project_collab], for the results of a batch query.
+  pub async fn project_collab_results(
+    &self,
+    workspace_id: &str,
+    uid: i64,
+    results: HashMap<String, QueryCollabResult>,
+  ) -> HashMap<String, QueryCollabResult> {
+    let mut projected = HashMap::with_capacity(results.len());
+    for (object_id, result) in results {
+      let result = match result {
+        QueryCollabResult::Success { encode_collab_v1 } => {
+          match self
+            .project_collab_bytes(workspace_id, uid, &object_id, encode_collab_v1)
+            .await
+          {
+            Ok(encode_collab_v1) => QueryCollabResult::Success { encode_collab_v1 },
+            Err(err) => QueryCollabResult::Failed {
+              error: err.to_string(),
+            },
+          }
+        },
+        failed => failed,
+      };
+      projected.insert(object_id, result);
+    }
+    projected
+  }
+
+  async fn project_collab_bytes(
+    &self,
+    workspace_id: &str,
+    uid: i64,
+    object_id: &str,
+    encode_collab_v1: Vec<u8>,
+  ) -> Result<Vec<u8>, AppError> {
+    match self.row_access(workspace_id, uid, object_id).await? {
+      RowAccess::Visible => Ok(encode_collab_v1),
+      _ => {
+        let encoded_collab = EncodedCollab::decode_from_bytes(&encode_collab_v1)
+          .map_err(|err| AppError::Internal(err.into()))?;
+        let projected = self
+          .project_collab(workspace_id, uid, object_id, encoded_collab)
+          .await?;
+        projected
+          .encode_to_bytes()
+          .map_err(|err| AppError::Internal(err.into()))
+      },
+    }
+  }
+
+  /// Whether the collab can be synced in realtime by the user.
+  pub async fn can_sync(
+    &self,
+    workspace_id: &str,
+    uid: i64,
+    object_id: &str,
+  ) -> Result<bool, AppError> {
+    Ok(matches!(
+      self.row_access(workspace_id, uid, object_id).await?,
+      RowAccess::Visible
+    ))
+  }
+
+  pub async fn get_rule(
+    &self,
+    workspace_id: &Uuid,
+    database_id: &Uuid,
+  ) -> Result<AFDatabaseRowAccess, AppError> {
+    select_database_row_access(&self.pg_pool, workspace_id, database_id)
+      .await?
+      .map(row_access_from_row)
+      .ok_or_else(|| {
+        AppError::RecordNotFound(format!("database {} has no row access rule", database_id))
+      })
+  }
+
+  pub async fn upsert_rule(
+    &self,
+    workspace_id: &Uuid,
+    database_id: &Uuid,
+    uid: i64,
+    params: UpsertDatabaseRowAccess,
+  ) -> Result<AFDatabaseRowAccess, AppError> {
+    let (db_collab, db_body) = get_latest_collab_database_body(
+      &self.collab_storage,
+      &workspace_id.to_string(),
+      &database_id.to_string(),
+    )
+    .await?;
+    let has_field = db_body
+      .fields
+      .get_all_fields(&db_collab.transact())
+      .iter()
+      .any(|field| field.id == params.field_id);
+    if !has_field {
+      return Err(AppError::InvalidRequest(format!(
+        "field {} does not exist in database {}",
+        params.field_id, database_id
+      )));
+    }
+    let row = upsert_database_row_access(
+      &self.pg_pool,
+      workspace_id,
+      database_id,
+      &params.field_id,
+      params.restricted_rows,
+      uid,
+    )
+    .await?;
+    self.restricted_rows.remove(workspace_id);
+    Ok(row_access_from_row(row))
+  }
+
+  pub async fn delete_rule(&self, workspace_id: &Uuid, database_id: &Uuid) -> Result<(), AppError> {
+    if !delete_database_row_access(&self.pg_pool, workspace_id, database_id).await? {
+      return Err(AppError::RecordNotFound(format!(
+        "database {} has no row access rule",
+        database_id
+      )));
+    }
+    self.restricted_rows.remove(workspace_id);
+    Ok(())
+  }
+
+  async fn row_access(
+    &self,
+    workspace_id: &str,
+    uid: i64,
+    object_id: &str,
+  ) -> Result<RowAccess, AppError> {
+    let Ok(workspace_uuid) = Uuid::parse_str(workspace_id) else {
+      return Ok(RowAccess::Visible);
+    };
+    let mut restricted = self.restricted_rows(&workspace_uuid).await?;
+    let mut unlisted_database = None;
+    if !restricted.databases.contains_key(object_id)
+      && !restricted.row_databases.contains_key(object_id)
+    {
+      unlisted_database = self
+        .unlisted_row_database(&restricted, workspace_id, uid, object_id)
+        .await;
+      // a row created since the rows were loaded: its cell is read by loading them again
+      if unlisted_database.is_some()
+        && restricted.loaded_at.elapsed() >= RESTRICTED_ROWS_MIN_RELOAD_INTERVAL
+      {
+        restricted = self.reload_restricted_rows(&workspace_uuid).await?;
+      }
+    }
+    let (database, row_id) = match restricted.databases.get(object_id) {
+      Some(database) => (database, None),
+      None => match restricted
+        .row_databases
+        .get(object_id)
+        .or(unlisted_database.as_ref())
+        .and_then(|database_id| restricted.databases.get(database_id))
+      {
+        Some(database) => (database, Some(object_id)),
+        None => return Ok(RowAccess::Visible),
+      },
+    };
+    let Some(member) = self.member(&restricted, &workspace_uuid, uid).await? else {
+      return Ok(RowAccess::Hidden);
+    };
+    if database.sees_all_rows(&member) {
+      return Ok(RowAccess::Visible);
+    }
+    Ok(match row_id {
+      None => RowAccess::Projected(database.visible_row_ids(&member)),
+      Some(row_id) if database.is_visible(row_id, &member) => RowAccess::Visible,
+      Some(_) => RowAccess::Hidden,
+    })
+  }
+
+  async fn member(
+    &self,
+    restricted: &RestrictedRows,
+    workspace_id: &Uuid,
+    uid: i64,
+  ) -> Result<Option<RowAccessMember>, AppError> {
+    if let Some(member) = restricted.members.get(&uid) {
+      return Ok(member.clone());
+    }
+    let member = match select_workspace_member(&self.pg_pool, &uid, workspace_id).await {
+      Ok(row) => Some(RowAccessMember {
+        uid: row.uid,
+        email: row.email,
+        is_owner: row.role == AFRole::Owner,
+      }),
+      Err(AppError::RecordNotFound(_)) => None,
+      Err(err) => return Err(err),
+    };
+    restricted.members.insert(uid, member.clone());
+    Ok(member)
+  }
+
+  /// The database of a row which wasn't listed by any restricted database when the rows were
+  /// loaded, provided it belongs to one of them.
+  async fn unlisted_row_database(
+    &self,
+    restricted: &RestrictedRows,
+    workspace_id: &str,
+    uid: i64,
+    object_id: &str,
+  ) -> Option<String> {
+    if restricted.databases.is_empty() {
+      return None;
+    }
+    if let Some(database_id) = restricted.unlisted_rows.get(object_id) {
+      return database_id.clone();
+    }
+    let query = QueryCollab {
+      object_id: object_id.to_string(),
+      collab_type: CollabType::DatabaseRow,
+    };
+    let results = self
+      .collab_storage
+      .batch_get_collab(&uid, workspace_id, vec![query], true)
+      .await;
+    // an object which isn't stored yet is new, and can't be a row of another member
+    let database_id = match results.into_values().next() {
+      Some(QueryCollabResult::Success { encode_collab_v1 }) => {
+        EncodedCollab::decode_from_bytes(&encode_collab_v1)
+          .ok()
+          .and_then(|encoded_collab| {
+            collab_from_doc_state(encoded_collab.doc_state.to_vec(), object_id).ok()
+          })
+          .and_then(|collab| RowDetail::from_collab(&collab))
+          .map(|row_detail| row_detail.row.database_id)
+          .filter(|database_id| restricted.databases.contains_key(database_id))
+      },
+      _ => None,
+    };
+    restricted
+      .unlisted_rows
+      .insert(object_id.to_string(), database_id.clone());
+    database_id
+  }
+
+  async fn restricted_rows(&self, workspace_id: &Uuid) -> Result<Arc<RestrictedRows>, AppError> {
+    if let Some(restricted) = self.restricted_rows.get(workspace_id) {
+      if restricted.loaded_at.elapsed() < RESTRICTED_ROWS_TTL {
+        return Ok(restricted.clone());
+      }
+    }
+    self.reload_restricted_rows(workspace_id).await
+  }
+
+  async fn reload_restricted_rows(
+    &self,
+    workspace_id: &Uuid,
+  ) -> Result<Arc<RestrictedRows>, AppError> {
+    let restricted = Arc::new(self.load_restricted_rows(workspace_id).await?);
+    self
+      .restricted_rows
+      .insert(*workspace_id, restricted.clone());
+    Ok(restricted)
+  }
+
+  async fn load_restricted_rows(&self, workspace_id: &Uuid) -> Result<RestrictedRows, AppError> {
+    let rules = select_restricted_database_row_access(&self.pg_pool, workspace_id).await?;
+    let mut databases = HashMap::with_capacity(rules.len());
+    let mut row_databases = HashMap::new();
+    for rule in rules {
+      let database_id = rule.database_id.to_string();
+      let row_owners = match self.load_row_owners(&rule).await {
+        Ok(row_owners) => row_owners,
+        // A database which can't be read has no visible rows, rather than no restriction.
+        Err(err) => {
+          warn!(
+            "Failed to load the restricted rows of database {}: {}",
+            database_id, err
+          );
+          HashMap::new()
+        },
+      };
+      for row_id in row_owners.keys() {
+        row_databases.insert(row_id.clone(), database_id.clone());
+      }
+      databases.insert(
+        database_id,
+        RestrictedDatabase {
+          created_by: rule.created_by,
+          row_owners,
+        },
+      );
+    }
+    Ok(RestrictedRows {
+      loaded_at: Instant::now(),
+      databases,
+      row_databases,
+      members: DashMap::new(),
+      unlisted_rows: DashMap::new(),
+    })
+  }
+
+  async fn load_row_owners(
+    &self,
+    rule: &AFDatabaseRowAccessRow,
+  ) -> Result<HashMap<String, HashSet<String>>, AppError> {
+    let workspace_id = rule.workspace_id.to_string();
+    let (db_collab, db_body) = get_latest_collab_database_body(
+      &self.collab_storage,
+      &workspace_id,
+      &rule.database_id.to_string(),
+    )
+    .await?;
+    let (row_ids, field) = {
+      let txn = db_collab.transact();
+      let row_ids = db_body
+        .views
+        .get_all_views(&txn)
+        .into_iter()
+        .flat_map(|view| view.row_orders)
+        .map(|row_order| row_order.id.to_string())
+        .collect::<HashSet<_>>();
+      let field = db_body
+        .fields
+        .get_all_fields(&txn)
+        .into_iter()
+        .find(|field| field.id == rule.field_id);
+      (row_ids, field)
+    };
+
+    // Without the field, no row names anyone.
+    let mut row_owners = row_ids
+      .iter()
+      .map(|row_id| (row_id.clone(), HashSet::new()))
+      .collect::<HashMap<_, _>>();
+    let Some(field) = field else {
+      return Ok(row_owners);
+    };
+    let type_option_reader_by_id = type_option_reader_by_id(std::slice::from_ref(&field));
+    let field_by_id = HashMap::from([(field.id.clone(), field)]);
+    let queries = row_ids
+      .into_iter()
+      .map(|row_id| QueryCollab {
+        object_id: row_id,
+        collab_type: CollabType::DatabaseRow,
+      })
+      .collect();
+    let results = self
+      .collab_storage
+      .batch_get_collab(&rule.created_by, &workspace_id, queries, true)
+      .await;
+    for (row_id, result) in results {
+      let QueryCollabResult::Success { encode_collab_v1 } = result else {
+        continue;
+      };
+      let Some(row_detail) = EncodedCollab::decode_from_bytes(&encode_collab_v1)
+        .ok()
+        .and_then(|encoded_collab| {
+          collab_from_doc_state(encoded_collab.doc_state.to_vec(), &row_id).ok()
+        })
+        .and_then(|collab| RowDetail::from_collab(&collab))
+      else {
+        continue;
+      };
+      let cells = get_row_details_serde(row_detail, &field_by_id, &type_option_reader_by_id);
+      if let (Some(value), Some(owners)) = (cells.values().next(), row_owners.get_mut(&row_id)) {
+        collect_cell_owners(value, owners);
+      }
+    }
+    Ok(row_owners)
+  }
+}
+
+/// Hides the rows restricted by the row-level access rules from the realtime sync, along with
+/// the restricted databases themselves, of which the hidden rows can't be removed in realtime.
+pub struct RowAccessRealtimeAccessControl {
+  inner: Arc<dyn RealtimeAccessControl>,
+  row_access: RowAccessControl,
+}
+
+impl RowAccessRealtimeAccessControl {
+  pub fn new(inner: Arc<dyn RealtimeAccessControl>, row_access: RowAccessControl) -> Self {
+    Self { inner, row_access }
+  }
+}
+
+#[async_trait]
+impl RealtimeAccessControl for RowAccessRealtimeAccessControl {
+  async fn can_write_collab(
+    &self,
+    workspace_id: &str,
+    uid: &i64,
+    oid: &str,
+  ) -> Result<bool, AppError> {
+    if !self.inner.can_write_collab(workspace_id, uid, oid).await? {
+      return Ok(false);
+    }
+    self.row_access.can_sync(workspace_id, *uid, oid).await
+  }
+
+  async fn can_read_collab(
+    &self,
+    workspace_id: &str,
+    uid: &i64,
+    oid: &str,
+  ) -> Result<bool, AppError> {
+    if !self.inner.can_read_collab(workspace_id, uid, oid).await? {
+      return Ok(false);
+    }
+    self.row_access.can_sync(workspace_id, *uid, oid).await
+  }
+
+  fn policy_version(&self) -> i64 {
+    self.inner.policy_version()
+  }
+}
+
+/// Removes the rows which aren't visible from every view of the database. The copy is never
+/// saved: the removals are made by a client id of its own, which the server copy doesn't know
+/// about.
+fn project_database_collab(
+  object_id: &str,
+  encoded_collab: EncodedCollab,
+  visible_row_ids: &HashSet<String>,
+) -> Result<EncodedCollab, AppError> {
+  let mut collab = collab_from_doc_state(encoded_collab.doc_state.to_vec(), object_id)?;
+  let db_body =
+    DatabaseBody::from_collab(&collab, Arc::new(NoPersistenceDatabaseCollabService), None)
+      .ok_or_else(|| AppError::Internal(anyhow::anyhow!("{} is not a database", object_id)))?;
+  let mut txn = collab.transact_mut();
+  let mut db_views = db_body.views.get_all_views(&txn);
+  for db_view in db_views.iter_mut() {
+    db_view
+      .row_orders
+      .retain(|row_order| visible_row_ids.contains(&row_order.id.to_string()));
+  }
+  db_body.views.clear(&mut txn);
+  for view in db_views {
+    db_body.views.insert_view(&mut txn, view);
+  }
+  Ok(EncodedCollab::new_v1(
+    txn.state_vector().encode_v1(),
+    txn.encode_state_as_update_v1(&StateVector::default()),
+  ))
+}
+
+/// The uids or emails a cell refers to: the text or number of the cell, or the names of its
+/// selected options, split on commas for the text cells naming several people.
+fn collect_cell_owners(value: &serde_json::Value, owners: &mut HashSet<String>) {
+  match value {
+    serde_json::Value::Number(number) => {
+      if let Some(uid) = number.as_i64() {
+        owners.insert(uid.to_string());
+      }
+    },
+    serde_json::Value::String(text) => owners.extend(
+      text
+        .split(',')
+        .map(|owner| owner.trim().to_lowercase())
+        .filter(|owner| !owner.is_empty()),
+    ),
+    serde_json::Value::Array(values) => {
+      for value in values {
+        collect_cell_owners(value, owners);
+      }
+    },
+    _ => {},
+  }
+}
+
+fn hidden_row_error(row_id: &str) -> AppError {
+  AppError::NotEnoughPermissions(format!("row {} is restricted", row_id))
+}
+
+fn row_access_from_row(row: AFDatabaseRowAccessRow) -> AFDatabaseRowAccess {
+  AFDatabaseRowAccess {
+    database_id: row.database_id.to_string(),
+    field_id: row.field_id,
+    restricted_rows: row.restricted_rows,
+    created_by: row.created_by,
+    updated_at: row.updated_at,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  fn member(uid: i64, email: &str, is_owner: bool) -> RowAccessMember {
+    RowAccessMember {
+      uid,
+      email: email.to_string(),
+      is_owner,
+    }
+  }
+
+  #[test]
+  fn rows_are_visible_to_the_members_their_cell_names_test() {
+    let mut owners = HashSet::new();
+    collect_cell_owners(&json!("Alice@AppFlowy.io, 3 "), &mut owners);
+    collect_cell_owners(&json!(["Carol@appflowy.io", ["dave"]]), &mut owners);
+    collect_cell_owners(&json!(12), &mut owners);
+    collect_cell_owners(&json!(true), &mut owners);
+    assert_eq!(
+      owners,
+      HashSet::from(
+        ["alice@appflowy.io", "3", "carol@appflowy.io", "dave", "12"].map(String::from)
+      )
+    );
+
+    let database = RestrictedDatabase {
+      created_by: 1,
+      row_owners: HashMap::from([
+        ("r1".to_string(), owners),
+        ("r2".to_string(), HashSet::new()),
+      ]),
+    };
+    let alice = member(2, "alice@appflowy.io", false);
+    let bob = member(3, "bob@appflowy.io", false);
+    let eve = member(4, "eve@appflowy.io", false);
+    assert!(database.is_visible("r1", &alice));
+    assert!(database.is_visible("r1", &bob));
+    assert!(database.is_visible("r1", &member(12, "someone@appflowy.io", false)));
+    assert_eq!(
+      database.visible_row_ids(&alice),
+      HashSet::from(["r1".to_string()])
+    );
+    assert!(database.visible_row_ids(&eve).is_empty());
+
+    assert!(!database.sees_all_rows(&eve));
+    assert!(database.sees_all_rows(&member(1, "author@appflowy.io", false)));
+    assert!(database.sees_all_rows(&member(5, "owner@appflowy.io", true)));
+  }
+
+  #[test]
+  fn rows_are_not_visible_to_the_members_named_by_their_name_test() {
+    // `dave` is the name of a member, which they can change to impersonate another member
+    let database = RestrictedDatabase {
+      created_by: 1,
+      row_owners: HashMap::from([(
+        "r1".to_string(),
+        HashSet::from(["dave".to_string(), "alice".to_string()]),
+      )]),
+    };
+    assert!(!database.is_visible("r1", &member(2, "dave@appflowy.io", false)));
+    assert!(!database.is_visible("r1", &member(3, "alice@appflowy.io", false)));
+  }
+
+  #[test]
+  fn rows_whose_cell_was_not_read_are_hidden_test() {
+    let database = RestrictedDatabase {
+      created_by: 1,
+      row_owners: HashMap::from([("r1".to_string(), HashSet::from(["2".to_string()]))]),
+    };
+    let alice = member(2, "alice@appflowy.io", false);
+    assert!(!database.is_visible("created_since", &alice));
+    assert!(!database.visible_row_ids(&alice).contains("created_since"));
+  }
+}
@@ -0,0 +1,79 @@
+use access_control::act::Action;
+use access_control::collab::CollabAccessControl;
+use app_error::AppError;
+use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
+use collab_entity::CollabType;
+use database::collab::{is_collab_in_trash, restore_deleted_collab, select_deleted_collabs};
+use database::file::s3_client_impl::S3BucketStorage;
+use shared_entity::dto::workspace_dto::DeletedCollab;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::biz::workspace::ops::purge_collabs;
+
+/// Lists the deleted collabs of the workspace the user could read before they were deleted.
+pub async fn list_deleted_collabs(
+  pg_pool: &PgPool,
+  collab_access_control: &dyn CollabAccessControl,
+  workspace_id: &Uuid,
+  uid: i64,
+) -> Result<Vec<DeletedCollab>, AppError> {
+  let rows = select_deleted_collabs(pg_pool, workspace_id).await?;
+  let workspace_id = workspace_id.to_string();
+  let mut deleted_collabs = Vec::with_capacity(rows.len());
+  for row in rows {
+    match collab_access_control
+      .enforce_action(&workspace_id, &uid, &row.oid, Action::Read)
+      .await
+    {
+      Ok(()) => deleted_collabs.push(DeletedCollab {
+        object_id: row.oid,
+        collab_type: CollabType::from(row.partition_key),
+        deleted_at: row.deleted_at,
+      }),
+      Err(err) if err.is_not_enough_permissions() => continue,
+      Err(err) => return Err(err),
+    }
+  }
+  Ok(deleted_collabs)
+}
+
+/// Takes the collab out of the trash, it's read and synced again as before it was deleted.
+pub async fn restore_collab_from_trash(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  object_id: &str,
+) -> Result<(), AppError> {
+  if !restore_deleted_collab(pg_pool, workspace_id, object_id).await? {
+    return Err(AppError::RecordNotFound(format!(
+      "collab {} is not in the trash of workspace {}",
+      object_id, workspace_id
+    )));
+  }
+  Ok(())
+}
+
+/// Permanently deletes a collab of the trash, see [purge_collabs].
+pub async fn purge_collab_from_trash(
+  pg_pool: &PgPool,
+  collab_storage: &CollabAccessControlStorage,
+  bucket_storage: &S3BucketStorage,
+  workspace_id: &Uuid,
+  object_id: &str,
+) -> Result<(), AppError> {
+  if !is_collab_in_trash(pg_pool, workspace_id, object_id).await? {
+    return Err(AppError::RecordNotFound(format!(
+      "collab {} is not in the trash of workspace {}",
+      object_id, workspace_id
+    )));
+  }
+  purge_collabs(
+    pg_pool,
+    bucket_storage,
+    workspace_id,
+    &[object_id.to_string()],
+  )
+  .await?;
+  collab_storage.evict_deleted_collab(object_id).await?;
+  Ok(())
+}
@@ -4,6 +4,10 @@ use collab::core::collab::DataSource;
 use collab::preclude::Collab;
 use collab_database::database::DatabaseBody;
 use collab_database::entity::FieldType;
+use collab_database::fields::select_type_option::MultiSelectTypeOption;
+use collab_database::fields::select_type_option::SelectOption;
+use collab_database::fields::select_type_option::SelectOptionColor;
+use collab_database::fields::select_type_option::SingleSelectTypeOption;
 use collab_database::fields::type_option_cell_reader;
 use collab_database::fields::type_option_cell_writer;
 use collab_database::fields::Field;
@@ -515,6 +519,203 @@ pub async fn write_to_database_row(
   Ok(())
 }
 
+/// Cell values which passed [validate_cell_values], keyed by field id.
+#[derive(Debug, Default)]
+pub struct ValidatedCells {
+  pub cell_value_by_id: HashMap<String, serde_json::Value>,
+  /// Names of the select options which are not defined yet, keyed by field id.
+  pub missing_select_options: HashMap<String, Vec<String>>,
+}
+
+/// Unlike [write_to_database_row], which skips the values it can not write, rejects the cells
+/// whose field does not exist or whose value does not match the type of the field. Fields are
+/// looked up by id, then by name. Dates given as RFC 3339 or `YYYY-MM-DD` strings are converted
+/// to timestamps.
+pub fn validate_cell_values(
+  fields: &[Field],
+  cell_value_by_id: HashMap<String, serde_json::Value>,
+) -> Result<ValidatedCells, AppError> {
+  let field_by_id: HashMap<&str, &Field> = fields
+    .iter()
+    .map(|field| (field.id.as_str(), field))
+    .collect();
+  let field_by_name = field_by_name_uniq(fields.to_vec());
+
+  let mut validated = ValidatedCells::default();
+  for (id, value) in cell_value_by_id {
+    let field = match field_by_id.get(id.as_str()) {
+      Some(field) => *field,
+      None => field_by_name
+        .get(&id)
+        .ok_or_else(|| AppError::InvalidRequest(format!("Field `{}` does not exist", id)))?,
+    };
+    let field_type = FieldType::from(field.field_type);
+    let value = match (&field_type, &value) {
+      (
+        FieldType::CreatedTime
+        | FieldType::LastEditedTime
+        | FieldType::Relation
+        | FieldType::Checklist
+        | FieldType::Media
+        | FieldType::Summary
+        | FieldType::Translate
+        | FieldType::Time,
+        _,
+      ) => {
+        return Err(AppError::InvalidRequest(format!(
+          "Field `{}` of type {:?} can not be written",
+          field.name, field_type
+        )))
+      },
+      (_, serde_json::Value::Null) => value,
+      (FieldType::RichText | FieldType::URL, serde_json::Value::String(_)) => value,
+      (
+        FieldType::RichText | FieldType::URL,
+        serde_json::Value::Number(_) | serde_json::Value::Bool(_),
+      ) => serde_json::Value::String(value.to_string()),
+      (FieldType::Number, serde_json::Value::Number(_)) => value,
+      (FieldType::Number, serde_json::Value::String(s)) if s.trim().parse::<f64>().is_ok() => value,
+      (FieldType::DateTime, serde_json::Value::Number(n)) if n.is_i64() => value,
+      (FieldType::DateTime, serde_json::Value::String(s)) => match parse_timestamp(s) {
+        Some(timestamp) => serde_json::Value::from(timestamp),
+        None => return Err(invalid_cell_value(field, &value)),
+      },
+      (FieldType::Checkbox, serde_json::Value::Bool(_)) => value,
+      (FieldType::Checkbox, serde_json::Value::String(s)) => match parse_checkbox(s) {
+        Some(checked) => serde_json::Value::Bool(checked),
+        None => return Err(invalid_cell_value(field, &value)),
+      },
+      (FieldType::SingleSelect | FieldType::MultiSelect, _) => {
+        let names = select_option_names(&value).ok_or_else(|| invalid_cell_value(field, &value))?;
+        if field_type == FieldType::SingleSelect && names.len() > 1 {
+          return Err(invalid_cell_value(field, &value));
+        }
+        let options = select_options(field, &field_type);
+        let missing: Vec<String> = names
+          .into_iter()
+          .filter(|name| {
+            !options
+              .iter()
+              .any(|option| &option.name == name || &option.id == name)
+          })
+          .collect();
+        if !missing.is_empty() {
+          let field_missing = validated
+            .missing_select_options
+            .entry(field.id.clone())
+            .or_default();
+          for name in missing {
+            if !field_missing.contains(&name) {
+              field_missing.push(name);
+            }
+          }
+        }
+        value
+      },
+      _ => return Err(invalid_cell_value(field, &value)),
+    };
+    validated.cell_value_by_id.insert(field.id.clone(), value);
+  }
+  Ok(validated)
+}
+
+/// Returns the type option data of the select field with the given options appended.
+pub fn add_select_options(field: &Field, names: &[String]) -> Option<TypeOptionData> {
+  const COLORS: [SelectOptionColor; 8] = [
+    SelectOptionColor::Purple,
+    SelectOptionColor::Pink,
+    SelectOptionColor::Orange,
+    SelectOptionColor::Yellow,
+    SelectOptionColor::Lime,
+    SelectOptionColor::Green,
+    SelectOptionColor::Aqua,
+    SelectOptionColor::Blue,
+  ];
+  let field_type = FieldType::from(field.field_type);
+  let type_option_data = field
+    .get_any_type_option(field_type.type_id())
+    .unwrap_or_default();
+  let new_options = |existing: usize| {
+    names
+      .iter()
+      .enumerate()
+      .map(|(i, name)| SelectOption::with_color(name, COLORS[(existing + i) % COLORS.len()]))
+      .collect::<Vec<_>>()
+  };
+  match field_type {
+    FieldType::SingleSelect => {
+      let mut type_option = SingleSelectTypeOption::from(type_option_data);
+      let options = new_options(type_option.options.len());
+      type_option.options.extend(options);
+      Some(type_option.into())
+    },
+    FieldType::MultiSelect => {
+      let mut type_option = MultiSelectTypeOption::from(type_option_data);
+      let options = new_options(type_option.options.len());
+      type_option.options.extend(options);
+      Some(type_option.into())
+    },
+    _ => None,
+  }
+}
+
+pub fn select_options(field: &Field, field_type: &FieldType) -> Vec<SelectOption> {
+  let type_option_data = field
+    .get_any_type_option(field_type.type_id())
+    .unwrap_or_default();
+  match field_type {
+    FieldType::SingleSelect => SingleSelectTypeOption::from(type_option_data)
+      .options
+      .clone(),
+    FieldType::MultiSelect => MultiSelectTypeOption::from(type_option_data)
+      .options
+      .clone(),
+    _ => vec![],
+  }
+}
+
+fn select_option_names(value: &serde_json::Value) -> Option<Vec<String>> {
+  match value {
+    serde_json::Value::String(name) => Some(vec![name.trim().to_string()]),
+    serde_json::Value::Array(names) => names
+      .iter()
+      .map(|name| name.as_str().map(|name| name.trim().to_string()))
+      .collect(),
+    _ => None,
+  }
+}
+
+pub fn parse_timestamp(s: &str) -> Option<i64> {
+  let s = s.trim();
+  if let Ok(timestamp) = s.parse::<i64>() {
+    return Some(timestamp);
+  }
+  if let Ok(date_time) = chrono::DateTime::parse_from_rfc3339(s) {
+    return Some(date_time.timestamp());
+  }
+  chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+    .ok()
+    .and_then(|date| date.and_hms_opt(0, 0, 0))
+    .map(|date_time| date_time.and_utc().timestamp())
+}
+
+pub fn parse_checkbox(s: &str) -> Option<bool> {
+  match s.trim().to_lowercase().as_str() {
+    "true" | "yes" | "1" => Some(true),
+    "false" | "no" | "0" | "" => Some(false),
+    _ => None,
+  }
+}
+
+fn invalid_cell_value(field: &Field, value: &serde_json::Value) -> AppError {
+  AppError::InvalidRequest(format!(
+    "Invalid value for {:?} field `{}`: {}",
+    FieldType::from(field.field_type),
+    field.name,
+    value
+  ))
+}
+
 pub async fn create_row_document(
   workspace_id: &str,
   uid: i64,
@@ -0,0 +1,447 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use app_error::AppError;
+use appflowy_collaborate::collab::restore::restore_collab_content;
+use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
+use collab::entity::EncodedCollab;
+use collab_document::blocks::{Block, DocumentData};
+use collab_entity::CollabType;
+use database::collab::{CollabStorage, GetCollabOrigin};
+use database_entity::dto::{AFSnapshotMeta, CollabParams, InsertSnapshotParams};
+use shared_entity::dto::workspace_dto::{
+  BlockChange, DocumentDiff, DocumentSnapshotDiffQuery, TextDiffOp,
+};
+use similar::{ChangeTag, TextDiff};
+use sqlx::PgPool;
+use tracing::info;
+use yrs::{ReadTxn, StateVector};
+
+use super::document_outline::{block_text, children, document_data, open_encoded_collab};
+use super::replay::open_collab;
+use super::utils::{collab_to_bin, get_latest_collab_encoded};
+use crate::biz::workspace::ops::broadcast_update_with_timeout;
+
+/// Most blocks listed in the diff of two versions of a document.
+const MAX_DIFF_CHANGES: usize = 1000;
+/// Most bytes of the texts listed in the diff of two versions of a document.
+const MAX_DIFF_TEXT_LEN: usize = 256 * 1024;
+/// Bounds the work of the diff of the text of a single block. Once reached, the rest of the text
+/// is listed as deleted and inserted.
+const TEXT_DIFF_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Restores the content of the collab to the one of its snapshot `snapshot_id`. A snapshot of the
+/// current content is taken first and returned, so that the restore can be undone by restoring
+/// it. The change is broadcast to the clients editing the collab, which converge to the restored
+/// content without reloading it.
+pub async fn restore_collab_snapshot(
+  collab_storage: Arc<CollabAccessControlStorage>,
+  pg_pool: &PgPool,
+  uid: i64,
+  workspace_id: &str,
+  object_id: &str,
+  collab_type: CollabType,
+  snapshot_id: i64,
+) -> Result<AFSnapshotMeta, AppError> {
+  if collab_storage.collab_mode(object_id).await?.is_opaque() {
+    return Err(AppError::InvalidRequest(format!(
+      "collab {} is encrypted and can't be restored by the server",
+      object_id
+    )));
+  }
+  let snapshot = collab_storage
+    .get_collab_snapshot(workspace_id, object_id, &snapshot_id)
+    .await?;
+  let snapshot = EncodedCollab::decode_from_bytes(&snapshot.encoded_collab_v1)
+    .map_err(|err| AppError::Internal(err.into()))?;
+  let snapshot = open_collab(object_id, &snapshot)?;
+
+  let current = get_latest_collab_encoded(
+    &collab_storage,
+    GetCollabOrigin::User { uid },
+    workspace_id,
+    object_id,
+    collab_type.clone(),
+  )
+  .await?;
+  let mut collab = open_collab(object_id, &current)?;
+  let doc_state = collab
+    .transact()
+    .encode_state_as_update_v1(&StateVector::default());
+  let pre_restore_snapshot = collab_storage
+    .create_snapshot(InsertSnapshotParams {
+      object_id: object_id.to_string(),
+      doc_state: doc_state.into(),
+      workspace_id: workspace_id.to_string(),
+      collab_type: collab_type.clone(),
+      created_by: Some(uid),
+    })
+    .await?;
+
+  let update = restore_collab_content(&mut collab, &snapshot);
+  let encoded_collab_v1 = collab_to_bin(collab, collab_type.clone()).await?;
+  let mut txn = pg_pool.begin().await?;
+  collab_storage
+    .upsert_new_collab_with_transaction(
+      workspace_id,
+      &uid,
+      CollabParams {
+        object_id: object_id.to_string(),
+        encoded_collab_v1: encoded_collab_v1.into(),
+        collab_type,
+      },
+      &mut txn,
+      "restoring collab snapshot",
+    )
+    .await?;
+  txn.commit().await?;
+  broadcast_update_with_timeout(
+    collab_storage,
+    workspace_id.to_string(),
+    object_id.to_string(),
+    update,
+  )
+  .await;
+  info!(
+    "user {} restored collab {} from snapshot {}, previous content kept in snapshot {}",
+    uid, object_id, snapshot_id, pre_restore_snapshot.snapshot_id
+  );
+  Ok(pre_restore_snapshot)
+}
+
+/// Compares two snapshots of a document, or a snapshot and the current version of the document
+/// when `query.to` is missing.
+pub async fn diff_document_snapshots(
+  collab_storage: &CollabAccessControlStorage,
+  uid: i64,
+  workspace_id: &str,
+  object_id: &str,
+  query: DocumentSnapshotDiffQuery,
+) -> Result<DocumentDiff, AppError> {
+  let from = snapshot_encoded_collab(collab_storage, workspace_id, object_id, query.from).await?;
+  let to = match query.to {
+    Some(snapshot_id) => {
+      snapshot_encoded_collab(collab_storage, workspace_id, object_id, snapshot_id).await?
+    },
+    None => {
+      get_latest_collab_encoded(
+        collab_storage,
+        GetCollabOrigin::User { uid },
+        workspace_id,
+        object_id,
+        CollabType::Document,
+      )
+      .await?
+    },
+  };
+
+  let object_id = object_id.to_string();
+  tokio::task::spawn_blocking(move || {
+    let from_data = document_data(&object_id, open_encoded_collab(&object_id, from)?)?;
+    let to_data = document_data(&object_id, open_encoded_collab(&object_id, to)?)?;
+    let (changes, truncated) = document_diff(from_data.as_ref(), to_data.as_ref());
+    Ok(DocumentDiff {
+      object_id,
+      from: query.from,
+      to: query.to,
+      changes,
+      truncated,
+    })
+  })
+  .await
+  .map_err(|err| AppError::Internal(anyhow!("Failed to diff document: {}", err)))?
+}
+
+async fn snapshot_encoded_collab(
+  collab_storage: &CollabAccessControlStorage,
+  workspace_id: &str,
+  object_id: &str,
+  snapshot_id: i64,
+) -> Result<EncodedCollab, AppError> {
+  let snapshot = collab_storage
+    .get_collab_snapshot(workspace_id, object_id, &snapshot_id)
+    .await?;
+  EncodedCollab::decode_from_bytes(&snapshot.encoded_collab_v1)
+    .map_err(|err| AppError::Internal(err.into()))
+}
+
+/// The blocks added, removed and changed from one version of a document to another, matched by
+/// block id, in the reading order of the newer version. Only the texts of the blocks present in
+/// both versions are compared, each one on its own. Returns whether changes were left out once
+/// the diff reached [MAX_DIFF_CHANGES] blocks or [MAX_DIFF_TEXT_LEN] bytes of text.
+pub fn document_diff(
+  from: Option<&DocumentData>,
+  to: Option<&DocumentData>,
+) -> (Vec<BlockChange>, bool) {
+  let from_blocks = from.map(reading_order).unwrap_or_default();
+  let to_blocks = to.map(reading_order).unwrap_or_default();
+  let to_ids: HashSet<&str> = to_blocks.iter().map(|block| block.id.as_str()).collect();
+  let from_by_id: HashMap<&str, &Block> = from_blocks
+    .iter()
+    .map(|block| (block.id.as_str(), *block))
+    .collect();
+
+  // a removed block is listed after the closest block before it which is kept
+  let mut removed: HashMap<Option<&str>, Vec<&Block>> = HashMap::new();
+  let mut kept = None;
+  for block in &from_blocks {
+    if to_ids.contains(block.id.as_str()) {
+      kept = Some(block.id.as_str());
+    } else {
+      removed.entry(kept).or_default().push(block);
+    }
+  }
+
+  let mut diff = DiffBuilder::default();
+  if let Some(from) = from {
+    diff.push_removed(from, removed.remove(&None));
+  }
+  if let Some(to) = to {
+    for block in to_blocks {
+      match from.zip(from_by_id.get(block.id.as_str())) {
+        None => diff.push(BlockChange::Added {
+          block_id: block.id.clone(),
+          ty: block.ty.clone(),
+          text: block_text(to, block),
+        }),
+        Some((from, previous)) => {
+          if let Some(change) = changed_block(from, previous, to, block) {
+            diff.push(change);
+          }
+          diff.push_removed(from, removed.remove(&Some(block.id.as_str())));
+        },
+      }
+    }
+  }
+  (diff.changes, diff.truncated)
+}
+
+#[derive(Default)]
+struct DiffBuilder {
+  changes: Vec<BlockChange>,
+  text_len: usize,
+  truncated: bool,
+}
+
+impl DiffBuilder {
+  fn push_removed(&mut self, from: &DocumentData, blocks: Option<Vec<&Block>>) {
+    for block in blocks.unwrap_or_default() {
+      self.push(BlockChange::Removed {
+        block_id: block.id.clone(),
+        ty: block.ty.clone(),
+        text: block_text(from, block),
+      });
+    }
+  }
+
+  fn push(&mut self, change: BlockChange) {
+    if self.truncated {
+      return;
+    }
+    let text_len = match &change {
+      BlockChange::Added { text, .. } | BlockChange::Removed { text, .. } => text.len(),
+      BlockChange::Changed { text, .. } => text.iter().map(text_diff_op_len).sum(),
+    };
+    if self.changes.len() == MAX_DIFF_CHANGES || self.text_len + text_len > MAX_DIFF_TEXT_LEN {
+      self.truncated = true;
+      return;
+    }
+    self.text_len += text_len;
+    self.changes.push(change);
+  }
+}
+
+fn text_diff_op_len(op: &TextDiffOp) -> usize {
+  match op {
+    TextDiffOp::Equal(text) | TextDiffOp::Insert(text) | TextDiffOp::Delete(text) => text.len(),
+  }
+}
+
+fn changed_block(
+  from: &DocumentData,
+  previous: &Block,
+  to: &DocumentData,
+  block: &Block,
+) -> Option<BlockChange> {
+  let previous_text = block_text(from, previous);
+  let text = block_text(to, block);
+  let previous_ty = (previous.ty != block.ty).then(|| previous.ty.clone());
+  let data_changed = block_data(previous) != block_data(block);
+  if previous_text == text && previous_ty.is_none() && !data_changed {
+    return None;
+  }
+  let text = if previous_text == text {
+    vec![]
+  } else {
+    text_diff(&previous_text, &text)
+  };
+  Some(BlockChange::Changed {
+    block_id: block.id.clone(),
+    ty: block.ty.clone(),
+    previous_ty,
+    data_changed,
+    text,
+  })
+}
+
+/// The data of the block other than its text, which older documents keep in it.
+fn block_data(block: &Block) -> HashMap<&str, &serde_json::Value> {
+  block
+    .data
+    .iter()
+    .filter(|(key, _)| key.as_str() != "delta")
+    .map(|(key, value)| (key.as_str(), value))
+    .collect()
+}
+
+fn text_diff(from: &str, to: &str) -> Vec<TextDiffOp> {
+  let diff = TextDiff::configure()
+    .timeout(TEXT_DIFF_TIMEOUT)
+    .diff_chars(from, to);
+  let mut ops: Vec<TextDiffOp> = vec![];
+  for change in diff.iter_all_changes() {
+    let value = change.value();
+    match (ops.last_mut(), change.tag()) {
+      (Some(TextDiffOp::Equal(text)), ChangeTag::Equal)
+      | (Some(TextDiffOp::Insert(text)), ChangeTag::Insert)
+      | (Some(TextDiffOp::Delete(text)), ChangeTag::Delete) => text.push_str(value),
+      (_, ChangeTag::Equal) => ops.push(TextDiffOp::Equal(value.to_string())),
+      (_, ChangeTag::Insert) => ops.push(TextDiffOp::Insert(value.to_string())),
+      (_, ChangeTag::Delete) => ops.push(TextDiffOp::Delete(value.to_string())),
+    }
+  }
+  ops
+}
+
+/// All the blocks of the document, in reading order.
+fn reading_order(data: &DocumentData) -> Vec<&Block> {
+  let mut blocks = vec![];
+  let root = match data.blocks.get(&data.page_id) {
+    Some(root) => root,
+    None => return blocks,
+  };
+  let mut stack = children(data, root).rev().collect::<Vec<_>>();
+  while let Some(block) = stack.pop() {
+    blocks.push(block);
+    stack.extend(children(data, block).rev());
+  }
+  blocks
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+  use workspace_template::document::parser::JsonToDocumentParser;
+
+  fn block_ids(data: &DocumentData) -> Vec<String> {
+    reading_order(data)
+      .iter()
+      .map(|block| block.id.clone())
+      .collect()
+  }
+
+  fn set_text(data: &mut DocumentData, block_id: &str, text: &str) {
+    let external_id = data.blocks[block_id].external_id.clone().unwrap();
+    data
+      .meta
+      .text_map
+      .as_mut()
+      .unwrap()
+      .insert(external_id, json!([{ "insert": text }]).to_string());
+  }
+
+  fn page_children(data: &mut DocumentData) -> &mut Vec<String> {
+    let children = data.blocks[&data.page_id].children.clone();
+    data.meta.children_map.get_mut(&children).unwrap()
+  }
+
+  #[test]
+  fn document_diff_test() {
+    let from = JsonToDocumentParser::json_to_document(json!({
+      "type": "page",
+      "children": [
+        { "type": "paragraph", "data": { "delta": [{ "insert": "one two" }] } },
+        { "type": "paragraph", "data": { "delta": [{ "insert": "gone" }] } },
+        { "type": "todo_list", "data": { "checked": false, "delta": [{ "insert": "task" }] } },
+        { "type": "paragraph", "data": { "delta": [{ "insert": "same" }] } }
+      ]
+    }))
+    .unwrap();
+    let ids = block_ids(&from);
+    let mut to = from.clone();
+    set_text(&mut to, &ids[0], "one six");
+    page_children(&mut to).retain(|id| id != &ids[1]);
+    to.blocks.remove(&ids[1]);
+    to.blocks
+      .get_mut(&ids[2])
+      .unwrap()
+      .data
+      .insert("checked".to_string(), json!(true));
+    let mut added = to.blocks[&ids[3]].clone();
+    added.id = "added".to_string();
+    added.external_id = None;
+    added
+      .data
+      .insert("delta".to_string(), json!([{ "insert": "new" }]));
+    to.blocks.insert(added.id.clone(), added);
+    page_children(&mut to).push("added".to_string());
+
+    let (changes, truncated) = document_diff(Some(&from), Some(&to));
+    assert!(!truncated);
+    assert_eq!(
+      changes,
+      vec![
+        BlockChange::Changed {
+          block_id: ids[0].clone(),
+          ty: "paragraph".to_string(),
+          previous_ty: None,
+          data_changed: false,
+          text: vec![
+            TextDiffOp::Equal("one ".to_string()),
+            TextDiffOp::Delete("two".to_string()),
+            TextDiffOp::Insert("six".to_string()),
+          ],
+        },
+        // the removed block stays where it was
+        BlockChange::Removed {
+          block_id: ids[1].clone(),
+          ty: "paragraph".to_string(),
+          text: "gone".to_string(),
+        },
+        BlockChange::Changed {
+          block_id: ids[2].clone(),
+          ty: "todo_list".to_string(),
+          previous_ty: None,
+          data_changed: true,
+          text: vec![],
+        },
+        BlockChange::Added {
+          block_id: "added".to_string(),
+          ty: "paragraph".to_string(),
+          text: "new".to_string(),
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn document_diff_is_truncated_test() {
+    let paragraphs: Vec<_> = (0..MAX_DIFF_CHANGES + 1)
+      .map(|i| json!({ "type": "paragraph", "data": { "delta": [{ "insert": i.to_string() }] } }))
+      .collect();
+    let to =
+      JsonToDocumentParser::json_to_document(json!({ "type": "page", "children": paragraphs }))
+        .unwrap();
+
+    // an empty document
+    let (changes, truncated) = document_diff(None, Some(&to));
+    assert_eq!(changes.len(), MAX_DIFF_CHANGES);
+    assert!(truncated);
+    assert!(changes
+      .iter()
+      .all(|change| matches!(change, BlockChange::Added { .. })));
+  }
+}
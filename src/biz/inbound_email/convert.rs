@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use app_error::AppError;
+use collab_document::blocks::Block;
+use collab_document::document::Document;
+use infra::text_offset::{truncate_chars, CharOffset};
+use nanoid::nanoid;
+use scraper::{ElementRef, Html, Node};
+use serde_json::json;
+
+use super::provider::InboundEmail;
+
+/// Paragraphs after this many are not appended, the email is truncated.
+const MAX_PARAGRAPHS: usize = 500;
+const MAX_PARAGRAPH_LEN: CharOffset = CharOffset(10_000);
+const MAX_SUBJECT_LEN: CharOffset = CharOffset(256);
+/// Elements nested deeper than this are ignored when the html body is simplified.
+const MAX_HTML_DEPTH: usize = 128;
+
+const SKIPPED_HTML_TAGS: &[&str] = &["head", "script", "style", "noscript", "template"];
+const BLOCK_HTML_TAGS: &[&str] = &[
+  "address",
+  "article",
+  "blockquote",
+  "div",
+  "footer",
+  "h1",
+  "h2",
+  "h3",
+  "h4",
+  "h5",
+  "h6",
+  "header",
+  "hr",
+  "li",
+  "ol",
+  "p",
+  "pre",
+  "section",
+  "table",
+  "tr",
+  "ul",
+];
+
+/// A block of the document an email is appended as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmailBlock {
+  Heading(String),
+  Paragraph(String),
+  Image {
+    url: String,
+  },
+  File {
+    url: String,
+    name: String,
+  },
+  /// Explains which part of the email was not appended.
+  Note(String),
+  Divider,
+}
+
+/// The subject of the email and its body, simplified to paragraphs.
+#[derive(Debug, Default)]
+pub struct EmailContent {
+  pub subject: String,
+  pub from: String,
+  pub paragraphs: Vec<String>,
+  pub truncated: bool,
+}
+
+/// Simplifies the body of the email to paragraphs. The text body is used when the email has one,
+/// the html body is reduced to its text otherwise.
+pub fn email_content(email: &InboundEmail) -> EmailContent {
+  let text = match email.text.as_deref().filter(|text| !text.trim().is_empty()) {
+    Some(text) => text.replace("\r\n", "\n"),
+    None => email.html.as_deref().map(html_to_text).unwrap_or_default(),
+  };
+  let mut truncated = email.truncated;
+  let mut paragraphs = text_paragraphs(&text);
+  if paragraphs.len() > MAX_PARAGRAPHS {
+    paragraphs.truncate(MAX_PARAGRAPHS);
+    truncated = true;
+  }
+  for paragraph in paragraphs.iter_mut() {
+    if truncate_chars(paragraph, MAX_PARAGRAPH_LEN) {
+      truncated = true;
+    }
+  }
+
+  let mut subject = email.subject.trim().to_string();
+  if subject.is_empty() {
+    subject = "(no subject)".to_string();
+  }
+  truncate_chars(&mut subject, MAX_SUBJECT_LEN);
+  EmailContent {
+    subject,
+    from: email.from.trim().to_string(),
+    paragraphs,
+    truncated,
+  }
+}
+
+/// Splits the text on blank lines. The line breaks within a paragraph are kept, the indentation
+/// is not.
+fn text_paragraphs(text: &str) -> Vec<String> {
+  let mut paragraphs = vec![];
+  let mut lines: Vec<&str> = vec![];
+  for line in text.lines().map(|line| line.trim()) {
+    if line.is_empty() {
+      if !lines.is_empty() {
+        paragraphs.push(lines.join("\n"));
+        lines.clear();
+      }
+    } else {
+      lines.push(line);
+    }
+  }
+  if !lines.is_empty() {
+    paragraphs.push(lines.join("\n"));
+  }
+  paragraphs
+}
+
+/// Returns the text of the html, with a blank line between the block elements.
+pub fn html_to_text(html: &str) -> String {
+  let document = Html::parse_document(html);
+  let mut text = String::new();
+  push_element_text(document.root_element(), 0, &mut text);
+  text
+}
+
+fn push_element_text(element: ElementRef, depth: usize, text: &mut String) {
+  if depth > MAX_HTML_DEPTH {
+    return;
+  }
+  for child in element.children() {
+    match child.value() {
+      Node::Text(value) => push_collapsed_whitespace(value, text),
+      Node::Element(child_element) => {
+        let name = child_element.name();
+        if SKIPPED_HTML_TAGS.contains(&name) {
+          continue;
+        }
+        if name == "br" {
+          text.push('\n');
+          continue;
+        }
+        let Some(child) = ElementRef::wrap(child) else {
+          continue;
+        };
+        let is_block = BLOCK_HTML_TAGS.contains(&name);
+        if is_block {
+          text.push_str("\n\n");
+        }
+        push_element_text(child, depth + 1, text);
+        if is_block {
+          text.push_str("\n\n");
+        }
+      },
+      _ => {},
+    }
+  }
+}
+
+fn push_collapsed_whitespace(value: &str, text: &mut String) {
+  let mut previous_is_space = text.ends_with(char::is_whitespace);
+  for c in value.chars() {
+    if c.is_whitespace() {
+      if !previous_is_space {
+        text.push(' ');
+      }
+      previous_is_space = true;
+    } else {
+      text.push(c);
+      previous_is_space = false;
+    }
+  }
+}
+
+/// Appends the blocks at the end of the document. Returns the number of blocks appended.
+pub fn append_email_blocks(
+  document: &mut Document,
+  blocks: Vec<EmailBlock>,
+) -> Result<usize, AppError> {
+  let page_id = document
+    .get_page_id()
+    .ok_or_else(|| AppError::NoRequiredData("The document has no page block".to_string()))?;
+  let mut prev_id = document
+    .get_block_children_ids(&page_id)
+    .last()
+    .cloned()
+    .unwrap_or_default();
+
+  let count = blocks.len();
+  for block in blocks {
+    let (ty, data, delta) = match block {
+      EmailBlock::Heading(text) => ("heading", json!({ "level": 2 }), Some(text)),
+      EmailBlock::Paragraph(text) => ("paragraph", json!({}), Some(text)),
+      EmailBlock::Image { url } => (
+        "image",
+        json!({ "url": url, "image_type": 1, "align": "center" }),
+        None,
+      ),
+      EmailBlock::File { url, name } => (
+        "file",
+        json!({
+          "url": url,
+          "name": name,
+          "uploaded_at": chrono::Utc::now().timestamp_millis(),
+          "url_type": 1,
+        }),
+        None,
+      ),
+      EmailBlock::Note(text) => ("callout", json!({ "icon": "⚠️" }), Some(text)),
+      EmailBlock::Divider => ("divider", json!({}), None),
+    };
+    let data: HashMap<String, serde_json::Value> = serde_json::from_value(data)?;
+    let block_id = nanoid!(10);
+    let text_id = delta.as_ref().map(|_| nanoid!(10));
+    let block = Block {
+      id: block_id.clone(),
+      ty: ty.to_string(),
+      parent: page_id.clone(),
+      children: "".to_string(),
+      external_id: text_id.clone(),
+      external_type: text_id.as_ref().map(|_| "text".to_string()),
+      data,
+    };
+    document
+      .insert_block(block, Some(prev_id.clone()))
+      .map_err(|err| AppError::Internal(anyhow::anyhow!("Failed to insert block: {}", err)))?;
+    if let (Some(text_id), Some(text)) = (text_id, delta) {
+      document.apply_text_delta(&text_id, json!([{ "insert": text }]).to_string());
+    }
+    prev_id = block_id;
+  }
+  Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn html_is_simplified_to_paragraphs_test() {
+    let html = r#"<html><head><style>p { color: red; }</style></head>
+      <body><div><p>Hello   <b>world</b></p><p>Second<br>line</p></div>
+      <script>alert(1)</script><ul><li>one</li><li>two</li></ul></body></html>"#;
+    let paragraphs = text_paragraphs(&html_to_text(html));
+    assert_eq!(
+      paragraphs,
+      vec!["Hello world", "Second\nline", "one", "two"]
+    );
+  }
+
+  #[test]
+  fn text_body_is_preferred_test() {
+    let email = InboundEmail {
+      subject: "  ".to_string(),
+      text: Some("First line\r\nstill first\r\n\r\n\r\nSecond".to_string()),
+      html: Some("<p>ignored</p>".to_string()),
+      ..Default::default()
+    };
+    let content = email_content(&email);
+    assert_eq!(content.subject, "(no subject)");
+    assert_eq!(
+      content.paragraphs,
+      vec!["First line\nstill first", "Second"]
+    );
+    assert!(!content.truncated);
+  }
+
+  #[test]
+  fn long_email_is_truncated_test() {
+    let text = (0..MAX_PARAGRAPHS + 10)
+      .map(|i| i.to_string())
+      .collect::<Vec<_>>()
+      .join("\n\n");
+    let email = InboundEmail {
+      text: Some(text),
+      ..Default::default()
+    };
+    let content = email_content(&email);
+    assert_eq!(content.paragraphs.len(), MAX_PARAGRAPHS);
+    assert!(content.truncated);
+
+    let mut value = "héllo".to_string();
+    assert!(truncate_chars(&mut value, CharOffset(2)));
+    assert_eq!(value, "hé");
+  }
+}
@@ -0,0 +1,5 @@
+pub mod convert;
+mod ops;
+pub mod provider;
+
+pub use self::ops::*;
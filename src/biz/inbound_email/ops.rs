@@ -0,0 +1,334 @@
+use actix_web::web::Data;
+use app_error::AppError;
+use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
+use aws_sdk_s3::primitives::ByteStream;
+use collab_entity::CollabType;
+use collab_folder::ViewLayout;
+use collab_importer::util::FileId;
+use collab_rt_entity::user::RealtimeUser;
+use database::collab::GetCollabOrigin;
+use database::file::s3_client_impl::S3BucketStorage;
+use database::inbound_email::{
+  delete_workspace_inbound_email, select_workspace_inbound_email,
+  select_workspace_inbound_email_by_token, update_workspace_inbound_email_token,
+  upsert_workspace_inbound_email,
+};
+use database::pg_row::AFWorkspaceInboundEmailRow;
+use database::resource_usage::BlobAttributes;
+use nanoid::nanoid;
+use shared_entity::dto::inbound_email_dto::{InboundEmailAddress, InboundEmailReceipt};
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::convert::{append_email_blocks, email_content, EmailBlock};
+use super::provider::{inbound_address_token, InboundEmail, InboundEmailFile};
+use crate::api::file_storage::BlobPathV1;
+use crate::api::metrics::AppFlowyWebMetrics;
+use crate::api::ws::RealtimeServerAddr;
+use crate::biz::collab::utils::{get_latest_collab_document, get_latest_collab_folder};
+use crate::biz::workspace::image::blob_url;
+use crate::biz::workspace::page_view::update_page_collab_data;
+use crate::config::config::InboundEmailSetting;
+
+/// Tokens are lowercase, the local part of an address is often lowercased on the way.
+const TOKEN_ALPHABET: [char; 36] = [
+  'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's',
+  't', 'u', 'v', 'w', 'x', 'y', 'z', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9',
+];
+const TOKEN_LEN: usize = 24;
+const MAX_ATTACHMENT_SIZE: usize = 20 * 1024 * 1024;
+const IMAGE_CONTENT_TYPES: &[&str] = &["image/gif", "image/jpeg", "image/png", "image/webp"];
+const FILE_CONTENT_TYPES: &[&str] = &[
+  "application/msword",
+  "application/pdf",
+  "application/vnd.ms-excel",
+  "application/vnd.ms-powerpoint",
+  "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+  "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+  "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+  "application/zip",
+  "text/calendar",
+  "text/csv",
+  "text/markdown",
+  "text/plain",
+];
+
+/// Returns the domain of the inbound addresses, inbound email is disabled when it's not set.
+pub fn inbound_email_domain(setting: &InboundEmailSetting) -> Result<&str, AppError> {
+  setting
+    .domain
+    .as_deref()
+    .ok_or_else(|| AppError::InvalidRequest("Inbound email is not enabled".to_string()))
+}
+
+fn generate_token() -> String {
+  nanoid!(TOKEN_LEN, &TOKEN_ALPHABET)
+}
+
+fn to_inbound_email_address(domain: &str, row: AFWorkspaceInboundEmailRow) -> InboundEmailAddress {
+  InboundEmailAddress {
+    address: format!("{}@{}", row.token, domain),
+    view_id: row.view_id,
+    created_at: row.created_at,
+    rotated_at: row.rotated_at,
+  }
+}
+
+pub async fn get_inbound_email_address(
+  pg_pool: &PgPool,
+  domain: &str,
+  workspace_id: &Uuid,
+) -> Result<InboundEmailAddress, AppError> {
+  let row = select_workspace_inbound_email(pg_pool, workspace_id)
+    .await?
+    .ok_or_else(|| {
+      AppError::RecordNotFound(format!(
+        "Workspace {} has no inbound email address",
+        workspace_id
+      ))
+    })?;
+  Ok(to_inbound_email_address(domain, row))
+}
+
+/// Sets the page the emails sent to the inbound address of the workspace are appended to, and
+/// creates the address if the workspace doesn't have one yet. The emails are appended on behalf of
+/// the user who set the page.
+pub async fn set_inbound_email_address(
+  pg_pool: &PgPool,
+  collab_storage: &CollabAccessControlStorage,
+  domain: &str,
+  workspace_id: &Uuid,
+  view_id: &Uuid,
+  uid: i64,
+) -> Result<InboundEmailAddress, AppError> {
+  let folder = get_latest_collab_folder(
+    collab_storage,
+    GetCollabOrigin::User { uid },
+    &workspace_id.to_string(),
+  )
+  .await?;
+  let view = folder
+    .get_view(&view_id.to_string())
+    .ok_or_else(|| AppError::InvalidFolderView(format!("View {} not found", view_id)))?;
+  if view.layout != ViewLayout::Document {
+    return Err(AppError::InvalidRequest(format!(
+      "Emails can only be appended to a document, view {} is not one",
+      view_id
+    )));
+  }
+
+  let row =
+    upsert_workspace_inbound_email(pg_pool, workspace_id, view_id, &generate_token(), uid).await?;
+  Ok(to_inbound_email_address(domain, row))
+}
+
+/// Replaces the inbound address of the workspace. The previous address stops accepting emails
+/// right away.
+pub async fn rotate_inbound_email_address(
+  pg_pool: &PgPool,
+  domain: &str,
+  workspace_id: &Uuid,
+) -> Result<InboundEmailAddress, AppError> {
+  let row = update_workspace_inbound_email_token(pg_pool, workspace_id, &generate_token())
+    .await?
+    .ok_or_else(|| {
+      AppError::RecordNotFound(format!(
+        "Workspace {} has no inbound email address",
+        workspace_id
+      ))
+    })?;
+  info!(
+    "rotated the inbound email address of workspace {}",
+    workspace_id
+  );
+  Ok(to_inbound_email_address(domain, row))
+}
+
+pub async fn remove_inbound_email_address(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+) -> Result<(), AppError> {
+  delete_workspace_inbound_email(pg_pool, workspace_id).await
+}
+
+/// Returns the inbound address the email was delivered to.
+pub async fn find_inbound_email_target(
+  pg_pool: &PgPool,
+  domain: &str,
+  recipients: &[String],
+) -> Result<AFWorkspaceInboundEmailRow, AppError> {
+  for token in recipients
+    .iter()
+    .filter_map(|recipient| inbound_address_token(recipient, domain))
+  {
+    if let Some(row) = select_workspace_inbound_email_by_token(pg_pool, &token).await? {
+      return Ok(row);
+    }
+  }
+  Err(AppError::RecordNotFound(
+    "The email was not sent to an inbound address".to_string(),
+  ))
+}
+
+/// Appends the email to the page of the inbound address: the subject as a heading, the body as
+/// paragraphs and the attachments as image and file blocks. The parts of the email which can't be
+/// appended, because it's too large or an attachment is not supported, are replaced by a note.
+#[allow(clippy::too_many_arguments)]
+pub async fn deliver_inbound_email(
+  appflowy_web_metrics: &AppFlowyWebMetrics,
+  server: Data<RealtimeServerAddr>,
+  user: RealtimeUser,
+  collab_storage: &CollabAccessControlStorage,
+  bucket_storage: &S3BucketStorage,
+  base_url: Option<&str>,
+  max_email_size: usize,
+  target: &AFWorkspaceInboundEmailRow,
+  email: InboundEmail,
+) -> Result<InboundEmailReceipt, AppError> {
+  let workspace_id = target.workspace_id;
+  let view_id = target.view_id.to_string();
+  let mut document = get_latest_collab_document(
+    collab_storage,
+    GetCollabOrigin::User { uid: user.uid },
+    &workspace_id.to_string(),
+    &view_id,
+  )
+  .await?;
+
+  let content = email_content(&email);
+  let mut blocks = vec![EmailBlock::Heading(content.subject)];
+  if !content.from.is_empty() {
+    blocks.push(EmailBlock::Paragraph(format!("From: {}", content.from)));
+  }
+  blocks.extend(content.paragraphs.into_iter().map(EmailBlock::Paragraph));
+
+  let mut notes = vec![];
+  if content.truncated {
+    notes.push("The email was too long, only its beginning was added.".to_string());
+  }
+  let mut uploaded_attachments = 0;
+  for attachment in email.attachments {
+    match upload_attachment(
+      bucket_storage,
+      base_url,
+      workspace_id,
+      user.uid,
+      &view_id,
+      attachment,
+    )
+    .await
+    {
+      Ok(block) => {
+        uploaded_attachments += 1;
+        blocks.push(block);
+      },
+      Err(note) => notes.push(note),
+    }
+  }
+  for name in email.skipped_attachments {
+    notes.push(format!(
+      "The attachment {} was not added, the email is larger than {} MB.",
+      name,
+      max_email_size / (1024 * 1024)
+    ));
+  }
+  blocks.extend(notes.iter().cloned().map(EmailBlock::Note));
+  blocks.push(EmailBlock::Divider);
+
+  let appended_blocks = append_email_blocks(&mut document, blocks)?;
+  let doc_state = document
+    .encode_collab()
+    .map_err(|err| AppError::Internal(anyhow::anyhow!("Failed to encode document: {}", err)))?
+    .doc_state
+    .to_vec();
+  update_page_collab_data(
+    appflowy_web_metrics,
+    server,
+    user,
+    workspace_id,
+    target.view_id,
+    CollabType::Document,
+    doc_state,
+  )
+  .await?;
+  Ok(InboundEmailReceipt {
+    appended_blocks,
+    uploaded_attachments,
+    notes,
+  })
+}
+
+/// Uploads the attachment next to the other files of the page. Returns the note replacing the
+/// attachment when it can't be uploaded.
+async fn upload_attachment(
+  bucket_storage: &S3BucketStorage,
+  base_url: Option<&str>,
+  workspace_id: Uuid,
+  uid: i64,
+  view_id: &str,
+  attachment: InboundEmailFile,
+) -> Result<EmailBlock, String> {
+  let content_type = attachment
+    .content_type
+    .split(';')
+    .next()
+    .unwrap_or_default()
+    .trim()
+    .to_ascii_lowercase();
+  let is_image = IMAGE_CONTENT_TYPES.contains(&content_type.as_str());
+  if !is_image && !FILE_CONTENT_TYPES.contains(&content_type.as_str()) {
+    return Err(format!(
+      "The attachment {} was not added, its type {} is not supported.",
+      attachment.file_name, content_type
+    ));
+  }
+  if attachment.data.len() > MAX_ATTACHMENT_SIZE {
+    return Err(format!(
+      "The attachment {} was not added, it's larger than {} MB.",
+      attachment.file_name,
+      MAX_ATTACHMENT_SIZE / (1024 * 1024)
+    ));
+  }
+
+  let ext = attachment
+    .file_name
+    .rsplit_once('.')
+    .map(|(_, ext)| ext.to_ascii_lowercase())
+    .unwrap_or_default();
+  let key = BlobPathV1 {
+    workspace_id,
+    parent_dir: view_id.to_string(),
+    file_id: FileId::from_bytes(&attachment.data, ext),
+  };
+  let file_size = attachment.data.len();
+  if let Err(err) = bucket_storage
+    .put_blob_with_content_type(
+      key.clone(),
+      ByteStream::from(attachment.data),
+      content_type,
+      file_size,
+      BlobAttributes::uploaded_by(uid),
+    )
+    .await
+  {
+    warn!(
+      "failed to upload attachment {} of an inbound email: {}",
+      attachment.file_name, err
+    );
+    return Err(format!(
+      "The attachment {} could not be uploaded.",
+      attachment.file_name
+    ));
+  }
+
+  let url = blob_url(base_url, &key);
+  if is_image {
+    Ok(EmailBlock::Image { url })
+  } else {
+    Ok(EmailBlock::File {
+      url,
+      name: attachment.file_name,
+    })
+  }
+}
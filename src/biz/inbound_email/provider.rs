@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+
+use app_error::AppError;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use sha2::digest::Output;
+use sha2::{Digest, Sha256};
+
+use crate::config::config::InboundEmailSetting;
+
+/// Mailgun requests older than this are rejected, so that a captured request can't be replayed.
+const MAILGUN_MAX_TIMESTAMP_AGE_SECS: i64 = 15 * 60;
+
+/// The multipart form posted by the email provider.
+#[derive(Debug, Default)]
+pub struct InboundEmailForm {
+  pub fields: HashMap<String, String>,
+  pub files: Vec<InboundEmailFile>,
+  /// Name of the attachments which were not read because the email is larger than the maximum
+  /// size.
+  pub skipped_files: Vec<String>,
+  /// Set when a text field was longer than the maximum size, its end was not read.
+  pub truncated: bool,
+}
+
+impl InboundEmailForm {
+  fn field(&self, name: &str) -> Option<&str> {
+    self
+      .fields
+      .get(name)
+      .map(|value| value.as_str())
+      .filter(|value| !value.trim().is_empty())
+  }
+
+  fn take_field(&mut self, name: &str) -> Option<String> {
+    self
+      .fields
+      .remove(name)
+      .filter(|value| !value.trim().is_empty())
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct InboundEmailFile {
+  /// Name of the form field the file was sent in.
+  pub field_name: String,
+  pub file_name: String,
+  pub content_type: String,
+  pub data: Bytes,
+}
+
+/// An email, in the same shape whatever provider received it.
+#[derive(Debug, Default)]
+pub struct InboundEmail {
+  /// Addresses the email was delivered to. One of them is the inbound address of a workspace.
+  pub recipients: Vec<String>,
+  pub from: String,
+  pub subject: String,
+  pub text: Option<String>,
+  pub html: Option<String>,
+  pub attachments: Vec<InboundEmailFile>,
+  pub skipped_attachments: Vec<String>,
+  pub truncated: bool,
+}
+
+/// A provider which forwards the emails it receives to the server.
+pub trait InboundEmailProvider: Send + Sync {
+  /// Checks that the form was posted by the provider. `authorization` is the value of the
+  /// `Authorization` header of the request.
+  fn verify(&self, authorization: Option<&str>, form: &InboundEmailForm) -> Result<(), AppError>;
+
+  fn parse(&self, form: InboundEmailForm) -> Result<InboundEmail, AppError>;
+}
+
+/// Returns the provider with the given name, if it's configured.
+pub fn inbound_email_provider(
+  name: &str,
+  setting: &InboundEmailSetting,
+) -> Result<Box<dyn InboundEmailProvider>, AppError> {
+  let not_configured =
+    || AppError::InvalidRequest(format!("Inbound email provider {} is not configured", name));
+  match name {
+    "mailgun" => {
+      let signing_key = setting
+        .mailgun_signing_key
+        .clone()
+        .ok_or_else(not_configured)?;
+      Ok(Box::new(MailgunProvider { signing_key }))
+    },
+    "sendgrid" => {
+      let password = setting
+        .sendgrid_password
+        .clone()
+        .ok_or_else(not_configured)?;
+      Ok(Box::new(SendGridProvider { password }))
+    },
+    _ => Err(AppError::InvalidRequest(format!(
+      "Unsupported inbound email provider: {}",
+      name
+    ))),
+  }
+}
+
+/// Mailgun route forwarding to the webhook. The form is signed with the webhook signing key of the
+/// account.
+pub struct MailgunProvider {
+  signing_key: Secret<String>,
+}
+
+impl InboundEmailProvider for MailgunProvider {
+  fn verify(&self, _authorization: Option<&str>, form: &InboundEmailForm) -> Result<(), AppError> {
+    let unauthorized = || AppError::UserUnAuthorized("Invalid Mailgun signature".to_string());
+    let timestamp = form.field("timestamp").ok_or_else(unauthorized)?;
+    let token = form.field("token").ok_or_else(unauthorized)?;
+    let signature = form.field("signature").ok_or_else(unauthorized)?;
+
+    let sent_at: i64 = timestamp.parse().map_err(|_| unauthorized())?;
+    if (Utc::now().timestamp() - sent_at).abs() > MAILGUN_MAX_TIMESTAMP_AGE_SECS {
+      return Err(unauthorized());
+    }
+    let expected = format!(
+      "{:x}",
+      hmac_sha256(
+        self.signing_key.expose_secret().as_bytes(),
+        format!("{}{}", timestamp, token).as_bytes()
+      )
+    );
+    if !constant_time_eq(expected.as_bytes(), signature.as_bytes()) {
+      return Err(unauthorized());
+    }
+    Ok(())
+  }
+
+  fn parse(&self, mut form: InboundEmailForm) -> Result<InboundEmail, AppError> {
+    let recipients = form
+      .field("recipient")
+      .map(split_addresses)
+      .unwrap_or_default();
+    let from = form
+      .take_field("from")
+      .or_else(|| form.take_field("sender"))
+      .unwrap_or_default();
+    Ok(InboundEmail {
+      recipients,
+      from,
+      subject: form.take_field("subject").unwrap_or_default(),
+      text: form.take_field("body-plain"),
+      html: form.take_field("body-html"),
+      attachments: take_attachments(&mut form.files, "attachment-"),
+      skipped_attachments: form.skipped_files,
+      truncated: form.truncated,
+    })
+  }
+}
+
+/// SendGrid inbound parse webhook. SendGrid doesn't sign the form, the url of the webhook is
+/// configured with basic auth credentials instead.
+pub struct SendGridProvider {
+  password: Secret<String>,
+}
+
+#[derive(Deserialize)]
+struct SendGridEnvelope {
+  #[serde(default)]
+  to: Vec<String>,
+}
+
+impl InboundEmailProvider for SendGridProvider {
+  fn verify(&self, authorization: Option<&str>, _form: &InboundEmailForm) -> Result<(), AppError> {
+    let unauthorized =
+      || AppError::UserUnAuthorized("Invalid SendGrid webhook credentials".to_string());
+    let credentials = authorization
+      .and_then(|value| value.strip_prefix("Basic "))
+      .and_then(|value| STANDARD.decode(value.trim()).ok())
+      .and_then(|value| String::from_utf8(value).ok())
+      .ok_or_else(unauthorized)?;
+    let (_, password) = credentials.split_once(':').ok_or_else(unauthorized)?;
+    if !constant_time_eq(
+      password.as_bytes(),
+      self.password.expose_secret().as_bytes(),
+    ) {
+      return Err(unauthorized());
+    }
+    Ok(())
+  }
+
+  fn parse(&self, mut form: InboundEmailForm) -> Result<InboundEmail, AppError> {
+    // The envelope holds the addresses the email was delivered to, the `to` header may not
+    // contain the inbound address when it was in bcc.
+    let recipients = form
+      .field("envelope")
+      .and_then(|envelope| serde_json::from_str::<SendGridEnvelope>(envelope).ok())
+      .map(|envelope| envelope.to)
+      .filter(|to| !to.is_empty())
+      .or_else(|| form.field("to").map(split_addresses))
+      .unwrap_or_default();
+    Ok(InboundEmail {
+      recipients,
+      from: form.take_field("from").unwrap_or_default(),
+      subject: form.take_field("subject").unwrap_or_default(),
+      text: form.take_field("text"),
+      html: form.take_field("html"),
+      attachments: take_attachments(&mut form.files, "attachment"),
+      skipped_attachments: form.skipped_files,
+      truncated: form.truncated,
+    })
+  }
+}
+
+/// Returns the files sent in the fields whose name starts with the prefix, in the order of their
+/// number.
+fn take_attachments(files: &mut Vec<InboundEmailFile>, prefix: &str) -> Vec<InboundEmailFile> {
+  let mut attachments: Vec<(u32, InboundEmailFile)> = std::mem::take(files)
+    .into_iter()
+    .filter_map(|file| {
+      let index = file.field_name.strip_prefix(prefix)?.parse().ok()?;
+      Some((index, file))
+    })
+    .collect();
+  attachments.sort_by_key(|(index, _)| *index);
+  attachments.into_iter().map(|(_, file)| file).collect()
+}
+
+/// Splits a list of addresses like `Jane <jane@example.com>, john@example.com` into the bare
+/// addresses.
+pub fn split_addresses(value: &str) -> Vec<String> {
+  value
+    .split(',')
+    .filter_map(|address| {
+      let address = address.trim();
+      let address = match (address.rfind('<'), address.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &address[start + 1..end],
+        _ => address,
+      };
+      let address = address.trim();
+      address.contains('@').then(|| address.to_string())
+    })
+    .collect()
+}
+
+/// Returns the token of an inbound address, `<token>@<domain>`. Sub-addressing is supported, the
+/// token of `inbox+<token>@<domain>` is what follows the last `+`.
+pub fn inbound_address_token(address: &str, domain: &str) -> Option<String> {
+  let (local, address_domain) = address.trim().rsplit_once('@')?;
+  if !address_domain.eq_ignore_ascii_case(domain) {
+    return None;
+  }
+  let token = local.rsplit('+').next()?;
+  (!token.is_empty()).then(|| token.to_ascii_lowercase())
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Output<Sha256> {
+  const BLOCK_SIZE: usize = 64;
+  let mut block = [0u8; BLOCK_SIZE];
+  if key.len() > BLOCK_SIZE {
+    block[..32].copy_from_slice(&Sha256::digest(key));
+  } else {
+    block[..key.len()].copy_from_slice(key);
+  }
+  let mut inner = Sha256::new();
+  inner.update(block.map(|b| b ^ 0x36));
+  inner.update(message);
+  let mut outer = Sha256::new();
+  outer.update(block.map(|b| b ^ 0x5c));
+  outer.update(inner.finalize());
+  outer.finalize()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  if a.len() != b.len() {
+    return false;
+  }
+  a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn form(fields: &[(&str, &str)]) -> InboundEmailForm {
+    InboundEmailForm {
+      fields: fields
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect(),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn hmac_sha256_test() {
+    // RFC 4231, test case 2
+    assert_eq!(
+      format!(
+        "{:x}",
+        hmac_sha256(b"Jefe", b"what do ya want for nothing?")
+      ),
+      "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+  }
+
+  #[test]
+  fn mailgun_signature_test() {
+    let provider = MailgunProvider {
+      signing_key: Secret::new("key".to_string()),
+    };
+    let timestamp = Utc::now().timestamp().to_string();
+    let signature = format!(
+      "{:x}",
+      hmac_sha256(b"key", format!("{}token", timestamp).as_bytes())
+    );
+    let valid = form(&[
+      ("timestamp", &timestamp),
+      ("token", "token"),
+      ("signature", &signature),
+    ]);
+    assert!(provider.verify(None, &valid).is_ok());
+
+    let forged = form(&[
+      ("timestamp", &timestamp),
+      ("token", "other"),
+      ("signature", &signature),
+    ]);
+    assert!(provider.verify(None, &forged).is_err());
+
+    let expired_timestamp = (Utc::now().timestamp() - 3600).to_string();
+    let expired_signature = format!(
+      "{:x}",
+      hmac_sha256(b"key", format!("{}token", expired_timestamp).as_bytes())
+    );
+    let expired = form(&[
+      ("timestamp", &expired_timestamp),
+      ("token", "token"),
+      ("signature", &expired_signature),
+    ]);
+    assert!(provider.verify(None, &expired).is_err());
+  }
+
+  #[test]
+  fn sendgrid_basic_auth_test() {
+    let provider = SendGridProvider {
+      password: Secret::new("secret".to_string()),
+    };
+    let valid = format!("Basic {}", STANDARD.encode("appflowy:secret"));
+    assert!(provider
+      .verify(Some(&valid), &InboundEmailForm::default())
+      .is_ok());
+    let invalid = format!("Basic {}", STANDARD.encode("appflowy:other"));
+    assert!(provider
+      .verify(Some(&invalid), &InboundEmailForm::default())
+      .is_err());
+    assert!(provider.verify(None, &InboundEmailForm::default()).is_err());
+  }
+
+  #[test]
+  fn sendgrid_recipients_from_envelope_test() {
+    let provider = SendGridProvider {
+      password: Secret::new("secret".to_string()),
+    };
+    let email = provider
+      .parse(form(&[
+        ("to", "Team <team@example.com>"),
+        (
+          "envelope",
+          r#"{"to":["abc@inbound.example.com"],"from":"jane@example.com"}"#,
+        ),
+        ("subject", "Hello"),
+      ]))
+      .unwrap();
+    assert_eq!(email.recipients, vec!["abc@inbound.example.com"]);
+    assert_eq!(email.subject, "Hello");
+  }
+
+  #[test]
+  fn attachments_are_ordered_test() {
+    let file = |field_name: &str| InboundEmailFile {
+      field_name: field_name.to_string(),
+      file_name: field_name.to_string(),
+      content_type: "text/plain".to_string(),
+      data: Bytes::new(),
+    };
+    let mut files = vec![file("attachment-10"), file("attachment-2"), file("other")];
+    let attachments = take_attachments(&mut files, "attachment-");
+    let names: Vec<_> = attachments.iter().map(|f| f.field_name.as_str()).collect();
+    assert_eq!(names, vec!["attachment-2", "attachment-10"]);
+  }
+
+  #[test]
+  fn inbound_address_token_test() {
+    let domain = "inbound.example.com";
+    assert_eq!(
+      inbound_address_token("AbC123@Inbound.example.com", domain),
+      Some("abc123".to_string())
+    );
+    assert_eq!(
+      inbound_address_token("inbox+abc123@inbound.example.com", domain),
+      Some("abc123".to_string())
+    );
+    assert_eq!(inbound_address_token("abc123@example.com", domain), None);
+    assert_eq!(
+      split_addresses("Jane <jane@example.com>, john@example.com, undisclosed"),
+      vec!["jane@example.com", "john@example.com"]
+    );
+  }
+}
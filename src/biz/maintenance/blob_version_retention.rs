@@ -0,0 +1,93 @@
+use super::DestructiveJob;
+use app_error::AppError;
+use async_trait::async_trait;
+use database::file::s3_client_impl::S3BucketStorage;
+use database::file::BlobVersionPolicy;
+use database::resource_usage::{delete_blob_versions, select_workspace_expired_blob_versions};
+use serde::{Deserialize, Serialize};
+use shared_entity::dto::maintenance_dto::MaintenanceJob;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpiredBlobVersion {
+  pub file_id: String,
+  pub version: i64,
+  pub object_key: String,
+  pub file_size: i64,
+}
+
+/// Applies the blob version retention policy to every file of the workspace. Versions are
+/// otherwise only pruned when their file is overwritten, so age based expiry needs this sweep.
+pub struct BlobVersionRetention {
+  pg_pool: PgPool,
+  bucket_storage: Arc<S3BucketStorage>,
+  policy: BlobVersionPolicy,
+}
+
+impl BlobVersionRetention {
+  pub fn new(
+    pg_pool: PgPool,
+    bucket_storage: Arc<S3BucketStorage>,
+    policy: BlobVersionPolicy,
+  ) -> Self {
+    Self {
+      pg_pool,
+      bucket_storage,
+      policy,
+    }
+  }
+}
+
+#[async_trait]
+impl DestructiveJob for BlobVersionRetention {
+  type Item = ExpiredBlobVersion;
+
+  fn job(&self) -> MaintenanceJob {
+    MaintenanceJob::BlobVersionRetention
+  }
+
+  async fn select(&self, workspace_id: &Uuid) -> Result<Vec<Self::Item>, AppError> {
+    let versions = select_workspace_expired_blob_versions(
+      &self.pg_pool,
+      workspace_id,
+      self.policy.max_versions,
+      self.policy.max_age_days,
+    )
+    .await?;
+    Ok(
+      versions
+        .into_iter()
+        .map(|row| ExpiredBlobVersion {
+          file_id: row.file_id,
+          version: row.version,
+          object_key: row.object_key,
+          file_size: row.file_size,
+        })
+        .collect(),
+    )
+  }
+
+  fn item_id(item: &Self::Item) -> String {
+    format!("{}@v{}", item.file_id, item.version)
+  }
+
+  fn item_size(item: &Self::Item) -> i64 {
+    item.file_size
+  }
+
+  async fn delete(&self, workspace_id: &Uuid, items: Vec<Self::Item>) -> Result<usize, AppError> {
+    let versions: Vec<(String, i64)> = items
+      .into_iter()
+      .map(|item| (item.file_id, item.version))
+      .collect();
+    let mut tx = self.pg_pool.begin().await?;
+    let object_keys = delete_blob_versions(&mut tx, workspace_id, &versions).await?;
+    tx.commit().await?;
+
+    let count = object_keys.len();
+    self.bucket_storage.delete_objects(object_keys).await?;
+    Ok(count)
+  }
+}
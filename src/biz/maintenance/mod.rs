@@ -0,0 +1,212 @@
+pub mod blob_version_retention;
+pub mod orphan_blob_gc;
+
+use crate::biz::maintenance::blob_version_retention::BlobVersionRetention;
+use crate::biz::maintenance::orphan_blob_gc::{OrphanBlobGc, ORPHAN_BLOB_MIN_AGE};
+use app_error::AppError;
+use async_trait::async_trait;
+use chrono::Utc;
+use database::file::s3_client_impl::S3BucketStorage;
+use database::maintenance::{
+  insert_maintenance_job_report, mark_maintenance_job_report_executed,
+  select_last_maintenance_job_report_for_update,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use shared_entity::dto::maintenance_dto::{MaintenanceJob, MaintenanceJobReport};
+use sqlx::PgPool;
+use std::ops::DerefMut;
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+/// Number of item ids included in a report
+const REPORT_SAMPLE_SIZE: usize = 20;
+
+/// A job that deletes data. Jobs are split into a selection step, which must not mutate anything,
+/// and a deletion step, so that every job can be dry-run with [run_maintenance_job] and the dry
+/// run executed later with [execute_last_dry_run].
+#[async_trait]
+pub trait DestructiveJob: Send + Sync {
+  type Item: Serialize + DeserializeOwned + Send + Sync;
+
+  fn job(&self) -> MaintenanceJob;
+
+  /// Select the items that the job would delete
+  async fn select(&self, workspace_id: &Uuid) -> Result<Vec<Self::Item>, AppError>;
+
+  /// Stable identifier of the item, used for the selection hash and the report samples
+  fn item_id(item: &Self::Item) -> String;
+
+  /// Number of bytes freed by deleting the item
+  fn item_size(item: &Self::Item) -> i64;
+
+  /// Delete the given items. Returns the number of items that were deleted.
+  async fn delete(&self, workspace_id: &Uuid, items: Vec<Self::Item>) -> Result<usize, AppError>;
+}
+
+/// Run the job on the workspace. When `dry_run` is true nothing is deleted, and the report is
+/// persisted together with the selection so that it can be executed with [execute_last_dry_run].
+pub async fn run_maintenance_job<J: DestructiveJob>(
+  pg_pool: &PgPool,
+  job: &J,
+  workspace_id: Uuid,
+  uid: i64,
+  dry_run: bool,
+) -> Result<MaintenanceJobReport, AppError> {
+  let items = job.select(&workspace_id).await?;
+  let mut report = build_report(job, workspace_id, &items, dry_run);
+  if dry_run {
+    let row = insert_maintenance_job_report(
+      pg_pool,
+      &workspace_id,
+      job.job().as_str(),
+      uid,
+      &report.selection_hash,
+      &serde_json::to_value(&report)?,
+      &serde_json::to_value(&items)?,
+    )
+    .await?;
+    report.report_id = Some(row.report_id);
+    report.created_at = row.created_at;
+  } else {
+    report.deleted_count = Some(job.delete(&workspace_id, items).await?);
+  }
+
+  info!("[Maintenance] {:?}", report);
+  Ok(report)
+}
+
+/// Delete exactly what the last dry run of the job selected. Fails with
+/// [AppError::StaleDryRun] if the last dry run was already executed, or if running the selection
+/// again gives a different result, in which case a new dry run has to be reviewed.
+pub async fn execute_last_dry_run<J: DestructiveJob>(
+  pg_pool: &PgPool,
+  job: &J,
+  workspace_id: Uuid,
+) -> Result<MaintenanceJobReport, AppError> {
+  let mut tx = pg_pool.begin().await?;
+  let row = select_last_maintenance_job_report_for_update(
+    tx.deref_mut(),
+    &workspace_id,
+    job.job().as_str(),
+  )
+  .await?
+  .ok_or_else(|| {
+    AppError::RecordNotFound(format!(
+      "No dry run of {} found for workspace {}",
+      job.job().as_str(),
+      workspace_id
+    ))
+  })?;
+  if row.executed_at.is_some() {
+    return Err(AppError::StaleDryRun(
+      "The last dry run was already executed".to_string(),
+    ));
+  }
+
+  let current = job.select(&workspace_id).await?;
+  if selection_hash::<J>(&current) != row.selection_hash {
+    return Err(AppError::StaleDryRun(
+      "The selection changed since the last dry run, please run a new dry run".to_string(),
+    ));
+  }
+
+  let items: Vec<J::Item> = serde_json::from_value(row.selection)?;
+  let mut report: MaintenanceJobReport = serde_json::from_value(row.report)?;
+  report.report_id = Some(row.report_id);
+  report.dry_run = false;
+  report.deleted_count = Some(job.delete(&workspace_id, items).await?);
+  mark_maintenance_job_report_executed(
+    tx.deref_mut(),
+    &row.report_id,
+    &serde_json::to_value(&report)?,
+  )
+  .await?;
+  tx.commit().await?;
+
+  info!("[Maintenance] executed dry run: {:?}", report);
+  Ok(report)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum JobMode {
+  Run { dry_run: bool },
+  ExecuteLastDryRun,
+}
+
+/// Run one of the built-in maintenance jobs on the workspace
+pub async fn run_workspace_maintenance_job(
+  pg_pool: &PgPool,
+  bucket_storage: &Arc<S3BucketStorage>,
+  job: MaintenanceJob,
+  workspace_id: Uuid,
+  uid: i64,
+  mode: JobMode,
+) -> Result<MaintenanceJobReport, AppError> {
+  match job {
+    MaintenanceJob::OrphanBlobGc => {
+      let job = OrphanBlobGc::new(pg_pool.clone(), bucket_storage.clone(), ORPHAN_BLOB_MIN_AGE);
+      run_with_mode(pg_pool, &job, workspace_id, uid, mode).await
+    },
+    MaintenanceJob::BlobVersionRetention => {
+      let policy = bucket_storage
+        .version_policy()
+        .cloned()
+        .ok_or_else(|| AppError::InvalidRequest("Blob versioning is not enabled".to_string()))?;
+      let job = BlobVersionRetention::new(pg_pool.clone(), bucket_storage.clone(), policy);
+      run_with_mode(pg_pool, &job, workspace_id, uid, mode).await
+    },
+  }
+}
+
+async fn run_with_mode<J: DestructiveJob>(
+  pg_pool: &PgPool,
+  job: &J,
+  workspace_id: Uuid,
+  uid: i64,
+  mode: JobMode,
+) -> Result<MaintenanceJobReport, AppError> {
+  match mode {
+    JobMode::Run { dry_run } => run_maintenance_job(pg_pool, job, workspace_id, uid, dry_run).await,
+    JobMode::ExecuteLastDryRun => execute_last_dry_run(pg_pool, job, workspace_id).await,
+  }
+}
+
+fn build_report<J: DestructiveJob>(
+  job: &J,
+  workspace_id: Uuid,
+  items: &[J::Item],
+  dry_run: bool,
+) -> MaintenanceJobReport {
+  MaintenanceJobReport {
+    report_id: None,
+    job: job.job(),
+    workspace_id,
+    dry_run,
+    selection_hash: selection_hash::<J>(items),
+    selected_count: items.len(),
+    estimated_bytes: items.iter().map(J::item_size).sum(),
+    sample_ids: items
+      .iter()
+      .take(REPORT_SAMPLE_SIZE)
+      .map(J::item_id)
+      .collect(),
+    deleted_count: None,
+    created_at: Utc::now(),
+  }
+}
+
+/// Hash of the sorted item ids, so that two selections of the same items in a different order
+/// have the same hash.
+fn selection_hash<J: DestructiveJob>(items: &[J::Item]) -> String {
+  let mut ids: Vec<String> = items.iter().map(J::item_id).collect();
+  ids.sort();
+  let mut hasher = Sha256::new();
+  for id in ids {
+    hasher.update(id.as_bytes());
+    hasher.update([0]);
+  }
+  format!("{:x}", hasher.finalize())
+}
@@ -0,0 +1,121 @@
+use super::DestructiveJob;
+use app_error::AppError;
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use database::blob_variant::select_workspace_blob_variant_keys;
+use database::file::s3_client_impl::S3BucketStorage;
+use database::resource_usage::{
+  get_all_workspace_blob_ids, select_workspace_blob_content_keys,
+  select_workspace_blob_version_keys,
+};
+use serde::{Deserialize, Serialize};
+use shared_entity::dto::maintenance_dto::MaintenanceJob;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Objects younger than this are never collected, because a blob is written to the bucket
+/// before its metadata is inserted.
+pub const ORPHAN_BLOB_MIN_AGE: Duration = Duration::hours(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrphanBlob {
+  pub object_key: String,
+  pub size: i64,
+}
+
+/// Removes the objects of the workspace directory in the bucket that are neither a blob with
+/// metadata, a content shared by blobs, an archived blob version, nor a variant of a blob. They
+/// are left behind by uploads that raced with a workspace or blob deletion.
+pub struct OrphanBlobGc {
+  pg_pool: PgPool,
+  bucket_storage: Arc<S3BucketStorage>,
+  min_age: Duration,
+}
+
+impl OrphanBlobGc {
+  pub fn new(pg_pool: PgPool, bucket_storage: Arc<S3BucketStorage>, min_age: Duration) -> Self {
+    Self {
+      pg_pool,
+      bucket_storage,
+      min_age,
+    }
+  }
+}
+
+/// Mirror of the blob metadata key of [crate::api::file_storage::BlobPathV1] and its V0
+/// counterpart, computed from the object key.
+fn blob_metadata_key(workspace_id: &Uuid, object_key: &str) -> Option<String> {
+  let path = object_key.strip_prefix(&format!("{}/", workspace_id))?;
+  Some(match path.rsplit_once('/') {
+    Some((parent_dir, file_id)) => format!("{}_{}", parent_dir, file_id),
+    None => path.to_string(),
+  })
+}
+
+#[async_trait]
+impl DestructiveJob for OrphanBlobGc {
+  type Item = OrphanBlob;
+
+  fn job(&self) -> MaintenanceJob {
+    MaintenanceJob::OrphanBlobGc
+  }
+
+  async fn select(&self, workspace_id: &Uuid) -> Result<Vec<Self::Item>, AppError> {
+    let objects = self
+      .bucket_storage
+      .list_objects(&format!("{}/", workspace_id))
+      .await?;
+    let file_ids: HashSet<String> = get_all_workspace_blob_ids(&self.pg_pool, workspace_id)
+      .await?
+      .into_iter()
+      .collect();
+    let mut kept_keys: HashSet<String> =
+      select_workspace_blob_version_keys(&self.pg_pool, workspace_id)
+        .await?
+        .into_iter()
+        .collect();
+    // the content of a deleted blob is kept while other blobs share it
+    kept_keys.extend(select_workspace_blob_content_keys(&self.pg_pool, workspace_id).await?);
+    // the `@thumb` and `@medium` renditions of the images
+    kept_keys.extend(select_workspace_blob_variant_keys(&self.pg_pool, workspace_id).await?);
+
+    let created_before = Utc::now() - self.min_age;
+    let mut orphans: Vec<OrphanBlob> = objects
+      .into_iter()
+      .filter(|object| {
+        object
+          .last_modified
+          .is_some_and(|last_modified| last_modified <= created_before)
+      })
+      .filter(|object| !kept_keys.contains(&object.key))
+      .filter(|object| {
+        blob_metadata_key(workspace_id, &object.key).is_some_and(|key| !file_ids.contains(&key))
+      })
+      .map(|object| OrphanBlob {
+        object_key: object.key,
+        size: object.size,
+      })
+      .collect();
+    orphans.sort_by(|a, b| a.object_key.cmp(&b.object_key));
+    Ok(orphans)
+  }
+
+  fn item_id(item: &Self::Item) -> String {
+    item.object_key.clone()
+  }
+
+  fn item_size(item: &Self::Item) -> i64 {
+    item.size
+  }
+
+  async fn delete(&self, _workspace_id: &Uuid, items: Vec<Self::Item>) -> Result<usize, AppError> {
+    let count = items.len();
+    self
+      .bucket_storage
+      .delete_objects(items.into_iter().map(|item| item.object_key).collect())
+      .await?;
+    Ok(count)
+  }
+}
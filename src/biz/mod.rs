@@ -2,9 +2,13 @@ pub mod access_request;
 pub mod chat;
 pub mod collab;
 pub mod data_import;
+pub mod inbound_email;
+pub mod maintenance;
+pub mod moderation;
 pub mod pg_listener;
 pub mod search;
 pub mod template;
+pub mod unfurl;
 pub mod user;
 pub mod utils;
 pub mod workspace;
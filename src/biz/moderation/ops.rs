@@ -0,0 +1,332 @@
+use anyhow::anyhow;
+use app_error::AppError;
+use authentication::jwt::Authorization;
+use database::moderation::{
+  delete_published_view_takedown, insert_published_view_report, insert_published_view_takedown,
+  resolve_pending_published_view_reports, resolve_published_view_report,
+  select_publish_namespace_report_counts, select_published_view_report,
+  select_published_view_reports,
+};
+use database::pg_row::AFPublishedViewReportRow;
+use database::publish::set_published_collabs_as_unpublished;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use sha2::{Digest, Sha256};
+use shared_entity::dto::moderation_dto::{
+  ListPublishedViewReportsQuery, PublishNamespaceRisk, PublishedViewReport,
+  PublishedViewReportAction, PublishedViewReportStatus, ReportPublishedViewParams,
+  ResolvePublishedViewReportParams,
+};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::ops::DerefMut;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::domain::UserEmail;
+use crate::mailer::{AFCloudMailer, PublishedViewReportResolvedMailerParam};
+
+/// Role given by GoTrue to the admins of the instance.
+const ADMIN_ROLE: &str = "supabase_admin";
+const REPORT_RATE_LIMIT_WINDOW_SECS: i64 = 60 * 60;
+const REPORT_RATE_LIMIT_PER_WINDOW: i64 = 10;
+const MAX_REPORT_DETAILS_LEN: usize = 2000;
+const DEFAULT_REPORT_PAGE_SIZE: i64 = 50;
+const MAX_REPORT_PAGE_SIZE: i64 = 200;
+
+pub fn check_admin(auth: &Authorization) -> Result<(), AppError> {
+  if auth.claims.role == ADMIN_ROLE {
+    Ok(())
+  } else {
+    Err(AppError::NotEnoughPermissions)
+  }
+}
+
+/// Records a report of the view published under the given namespace and name. Anyone can report
+/// a published view, so the reports are rate limited by the address they are sent from.
+pub async fn report_published_view(
+  pg_pool: &PgPool,
+  redis: &ConnectionManager,
+  reporter_addr: &str,
+  publish_namespace: &str,
+  publish_name: &str,
+  params: ReportPublishedViewParams,
+) -> Result<Uuid, AppError> {
+  let details = params
+    .details
+    .as_deref()
+    .map(str::trim)
+    .filter(|details| !details.is_empty());
+  if details.is_some_and(|details| details.chars().count() > MAX_REPORT_DETAILS_LEN) {
+    return Err(AppError::InvalidRequest(format!(
+      "The details of a report can not exceed {} characters",
+      MAX_REPORT_DETAILS_LEN
+    )));
+  }
+  let reporter_email = params
+    .reporter_email
+    .as_deref()
+    .map(str::trim)
+    .filter(|email| !email.is_empty());
+  if let Some(email) = reporter_email {
+    UserEmail::parse(email.to_string()).map_err(AppError::InvalidRequest)?;
+  }
+
+  check_report_rate_limit(redis, reporter_addr).await?;
+  insert_published_view_report(
+    pg_pool,
+    publish_namespace,
+    publish_name,
+    params.reason.value(),
+    details,
+    reporter_email,
+  )
+  .await?
+  .ok_or_else(|| {
+    AppError::RecordNotFound(format!(
+      "No view is published at {}/{}",
+      publish_namespace, publish_name
+    ))
+  })
+}
+
+async fn check_report_rate_limit(
+  redis: &ConnectionManager,
+  reporter_addr: &str,
+) -> Result<(), AppError> {
+  // The address is hashed so that it isn't kept in clear in Redis
+  let key = format!(
+    "af:publish_report:rate:{:x}",
+    Sha256::digest(reporter_addr.as_bytes())
+  );
+  let mut conn = redis.clone();
+  let count: i64 = conn
+    .incr(&key, 1)
+    .await
+    .map_err(|err| AppError::Internal(anyhow!("Failed to check report rate limit: {}", err)))?;
+  if count == 1 {
+    let () = conn
+      .expire(&key, REPORT_RATE_LIMIT_WINDOW_SECS)
+      .await
+      .map_err(|err| AppError::Internal(anyhow!("Failed to check report rate limit: {}", err)))?;
+  }
+  if count > REPORT_RATE_LIMIT_PER_WINDOW {
+    return Err(AppError::TooManyRequests(
+      "Too many reports sent, please try again later".to_string(),
+    ));
+  }
+  Ok(())
+}
+
+pub async fn list_published_view_reports(
+  pg_pool: &PgPool,
+  query: ListPublishedViewReportsQuery,
+) -> Result<Vec<PublishedViewReport>, AppError> {
+  let limit = query
+    .limit
+    .unwrap_or(DEFAULT_REPORT_PAGE_SIZE)
+    .clamp(1, MAX_REPORT_PAGE_SIZE);
+  let rows = select_published_view_reports(
+    pg_pool,
+    query.status.map(|status| status.value()),
+    query.offset.unwrap_or(0).max(0),
+    limit,
+  )
+  .await?;
+  to_published_view_reports(pg_pool, rows).await
+}
+
+/// Resolves a pending report. Taking the view down unpublishes it, blocks publishing it again and
+/// resolves the other pending reports of the view as well. The reporters who left an email are
+/// notified.
+pub async fn resolve_report(
+  pg_pool: &PgPool,
+  mailer: &AFCloudMailer,
+  appflowy_web_url: Option<&str>,
+  report_id: &Uuid,
+  uid: i64,
+  params: ResolvePublishedViewReportParams,
+) -> Result<PublishedViewReport, AppError> {
+  let status = match params.action {
+    PublishedViewReportAction::Dismiss => PublishedViewReportStatus::Dismissed,
+    PublishedViewReportAction::TakeDown => PublishedViewReportStatus::TakenDown,
+  };
+  let note = params.note.as_deref();
+
+  let mut txn = pg_pool.begin().await?;
+  let report =
+    match resolve_published_view_report(txn.deref_mut(), report_id, status.value(), uid, note)
+      .await?
+    {
+      Some(report) => report,
+      None => {
+        return match select_published_view_report(txn.deref_mut(), report_id).await? {
+          Some(_) => Err(AppError::InvalidRequest(format!(
+            "Report {} is already resolved",
+            report_id
+          ))),
+          None => Err(AppError::RecordNotFound(format!(
+            "Report {} not found",
+            report_id
+          ))),
+        };
+      },
+    };
+  let mut resolved = vec![];
+  if status == PublishedViewReportStatus::TakenDown {
+    insert_published_view_takedown(
+      txn.deref_mut(),
+      &report.workspace_id,
+      &report.view_id,
+      &report.report_id,
+      uid,
+      note,
+    )
+    .await?;
+    set_published_collabs_as_unpublished(txn.deref_mut(), &report.workspace_id, &[report.view_id])
+      .await?;
+    resolved = resolve_pending_published_view_reports(
+      txn.deref_mut(),
+      &report.view_id,
+      status.value(),
+      uid,
+      note,
+    )
+    .await?;
+  }
+  txn.commit().await?;
+
+  for report in std::iter::once(&report).chain(resolved.iter()) {
+    notify_reporter(mailer, appflowy_web_url, report, status);
+  }
+  Ok(
+    to_published_view_reports(pg_pool, vec![report])
+      .await?
+      .remove(0),
+  )
+}
+
+fn notify_reporter(
+  mailer: &AFCloudMailer,
+  appflowy_web_url: Option<&str>,
+  report: &AFPublishedViewReportRow,
+  status: PublishedViewReportStatus,
+) {
+  let Some(email) = report.reporter_email.clone() else {
+    return;
+  };
+  let publish_path = format!("{}/{}", report.publish_namespace, report.publish_name);
+  let publish_url = match appflowy_web_url {
+    Some(url) => format!("{}/{}", url.trim_end_matches('/'), publish_path),
+    None => publish_path,
+  };
+  let outcome = match status {
+    PublishedViewReportStatus::TakenDown => "The page was taken down",
+    _ => "The page was found not to break our terms",
+  }
+  .to_string();
+  let mailer = mailer.clone();
+  tokio::spawn(async move {
+    if let Err(err) = mailer
+      .send_published_view_report_resolved(
+        &email,
+        PublishedViewReportResolvedMailerParam {
+          publish_url,
+          outcome,
+        },
+      )
+      .await
+    {
+      error!("Failed to send report resolved email: {:?}", err);
+    }
+  });
+}
+
+pub async fn get_publish_namespace_risk(
+  pg_pool: &PgPool,
+  publish_namespace: &str,
+) -> Result<PublishNamespaceRisk, AppError> {
+  let risks = get_publish_namespace_risks(pg_pool, &[publish_namespace.to_string()]).await?;
+  Ok(
+    risks
+      .get(publish_namespace)
+      .cloned()
+      .unwrap_or_else(|| PublishNamespaceRisk::new(publish_namespace.to_string(), 0, 0, 0)),
+  )
+}
+
+async fn get_publish_namespace_risks(
+  pg_pool: &PgPool,
+  publish_namespaces: &[String],
+) -> Result<HashMap<String, PublishNamespaceRisk>, AppError> {
+  let rows = select_publish_namespace_report_counts(
+    pg_pool,
+    publish_namespaces,
+    PublishNamespaceRisk::WINDOW_DAYS,
+  )
+  .await?;
+  Ok(
+    rows
+      .into_iter()
+      .map(|row| {
+        let risk = PublishNamespaceRisk::new(
+          row.publish_namespace,
+          row.pending,
+          row.dismissed,
+          row.taken_down,
+        );
+        (risk.publish_namespace.clone(), risk)
+      })
+      .collect(),
+  )
+}
+
+/// Allows publishing a view which was taken down again. The view stays unpublished until its
+/// owner publishes it.
+pub async fn lift_published_view_takedown(
+  pg_pool: &PgPool,
+  view_id: &Uuid,
+) -> Result<(), AppError> {
+  if !delete_published_view_takedown(pg_pool, view_id).await? {
+    return Err(AppError::RecordNotFound(format!(
+      "View {} is not taken down",
+      view_id
+    )));
+  }
+  Ok(())
+}
+
+async fn to_published_view_reports(
+  pg_pool: &PgPool,
+  rows: Vec<AFPublishedViewReportRow>,
+) -> Result<Vec<PublishedViewReport>, AppError> {
+  let mut namespaces: Vec<String> = rows
+    .iter()
+    .map(|row| row.publish_namespace.clone())
+    .collect();
+  namespaces.sort_unstable();
+  namespaces.dedup();
+  let risks = get_publish_namespace_risks(pg_pool, &namespaces).await?;
+  Ok(
+    rows
+      .into_iter()
+      .map(|row| PublishedViewReport {
+        namespace_risk_score: risks
+          .get(&row.publish_namespace)
+          .map(|risk| risk.score)
+          .unwrap_or(0),
+        report_id: row.report_id,
+        workspace_id: row.workspace_id,
+        view_id: row.view_id,
+        publish_namespace: row.publish_namespace,
+        publish_name: row.publish_name,
+        reason: row.reason.into(),
+        details: row.details,
+        status: row.status.into(),
+        created_at: row.created_at,
+        resolved_at: row.resolved_at,
+        resolution_note: row.resolution_note,
+      })
+      .collect(),
+  )
+}
@@ -1,16 +1,29 @@
 use anyhow::Error;
 use database::listener::PostgresDBListener;
-use database::pg_row::AFUserNotification;
+use database::pg_row::{AFUserNotification, AFUserSessionRevokedNotification};
 use sqlx::PgPool;
 
 pub struct PgListeners {
   user_listener: UserListener,
+  user_session_listener: UserSessionListener,
 }
 
 impl PgListeners {
   pub async fn new(pg_pool: &PgPool) -> Result<Self, Error> {
     let user_listener = UserListener::new(pg_pool, "af_user_channel").await?;
-    Ok(Self { user_listener })
+    let user_session_listener =
+      UserSessionListener::new(pg_pool, "af_user_session_channel").await?;
+    Ok(Self {
+      user_listener,
+      user_session_listener,
+    })
+  }
+
+  /// Notified on every instance whenever a session is revoked.
+  pub fn subscribe_user_session_revoked(
+    &self,
+  ) -> tokio::sync::broadcast::Receiver<AFUserSessionRevokedNotification> {
+    self.user_session_listener.notify.subscribe()
   }
 
   pub fn subscribe_user_change(&self, uid: i64) -> tokio::sync::mpsc::Receiver<AFUserNotification> {
@@ -30,3 +43,4 @@ impl PgListeners {
 }
 
 pub type UserListener = PostgresDBListener<AFUserNotification>;
+pub type UserSessionListener = PostgresDBListener<AFUserSessionRevokedNotification>;
@@ -8,12 +8,18 @@ use appflowy_ai_client::dto::{
   EmbeddingEncodingFormat, EmbeddingInput, EmbeddingModel, EmbeddingOutput, EmbeddingRequest,
 };
 use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
+use appflowy_collaborate::feature_flags::{FeatureFlags, SHARED_CHAT_SEARCH};
 use collab_folder::{Folder, View};
+use database::chat::chat_share::search_shared_chat_messages;
 use database::collab::GetCollabOrigin;
 use std::collections::HashSet;
+use std::ops::DerefMut;
 use std::sync::Arc;
+use std::time::Duration;
 
 use database::index::{search_documents, SearchDocumentParams};
+use database::statement_timeout::begin_with_statement_timeout;
+use shared_entity::dto::page_dto::{Cursor, Page};
 use shared_entity::dto::search_dto::{
   SearchContentType, SearchDocumentRequest, SearchDocumentResponseItem,
 };
@@ -23,6 +29,12 @@ use indexer::scheduler::IndexerScheduler;
 use uuid::Uuid;
 
 static MAX_SEARCH_DEPTH: i32 = 10;
+const DEFAULT_SEARCH_LIMIT: u32 = 10;
+const MAX_SEARCH_PAGE_SIZE: u32 = 50;
+/// Number of results the pages of a search end after: each page searches again for the results
+/// of the previous pages.
+const MAX_SEARCH_RESULTS: usize = 200;
+const SEARCH_CURSOR_KIND: &str = "search";
 
 fn is_view_searchable(view: &View, workspace_id: &str) -> bool {
   view.id != workspace_id && view.parent_view_id != workspace_id && view.layout.is_document()
@@ -70,14 +82,109 @@ fn populate_searchable_view_ids(
   }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn search_document(
   pg_pool: &PgPool,
+  statement_timeout: Duration,
   collab_storage: &CollabAccessControlStorage,
   indexer_scheduler: &Arc<IndexerScheduler>,
+  feature_flags: &FeatureFlags,
   uid: i64,
   workspace_uuid: Uuid,
   request: SearchDocumentRequest,
   metrics: &RequestMetrics,
+) -> Result<Vec<SearchDocumentResponseItem>, AppError> {
+  let limit = request.limit.unwrap_or(DEFAULT_SEARCH_LIMIT) as i32;
+  search_document_results(
+    pg_pool,
+    statement_timeout,
+    collab_storage,
+    indexer_scheduler,
+    feature_flags,
+    uid,
+    workspace_uuid,
+    &request,
+    limit,
+    metrics,
+  )
+  .await
+}
+
+/// The results of the search page by page, best match first. Each page runs the search again,
+/// for the results up to the end of the page, and the pages end after [MAX_SEARCH_RESULTS].
+#[allow(clippy::too_many_arguments)]
+pub async fn search_document_page(
+  pg_pool: &PgPool,
+  statement_timeout: Duration,
+  collab_storage: &CollabAccessControlStorage,
+  indexer_scheduler: &Arc<IndexerScheduler>,
+  feature_flags: &FeatureFlags,
+  uid: i64,
+  workspace_uuid: Uuid,
+  request: SearchDocumentRequest,
+  metrics: &RequestMetrics,
+) -> Result<Page<SearchDocumentResponseItem>, AppError> {
+  let limit = request
+    .limit
+    .unwrap_or(DEFAULT_SEARCH_LIMIT)
+    .clamp(1, MAX_SEARCH_PAGE_SIZE) as usize;
+  // the cursor holds the number of results of the previous pages, and the query they were
+  // searched for
+  let query_fingerprint = format!("{:x}", md5::compute(&request.query));
+  let offset = match &request.cursor {
+    None => 0,
+    Some(cursor) => {
+      let (offset, fingerprint) = cursor.decode::<(usize, String)>(SEARCH_CURSOR_KIND)?;
+      if fingerprint != query_fingerprint || offset >= MAX_SEARCH_RESULTS {
+        return Err(AppError::InvalidCursor(format!(
+          "not a cursor of {}",
+          SEARCH_CURSOR_KIND
+        )));
+      }
+      offset
+    },
+  };
+  let end = (offset + limit).min(MAX_SEARCH_RESULTS);
+  let mut results = search_document_results(
+    pg_pool,
+    statement_timeout,
+    collab_storage,
+    indexer_scheduler,
+    feature_flags,
+    uid,
+    workspace_uuid,
+    &request,
+    (end + 1) as i32,
+    metrics,
+  )
+  .await?;
+  results.sort_by(|a, b| a.score.total_cmp(&b.score));
+  results.truncate(end + 1);
+  let results = results.into_iter().skip(offset).collect::<Vec<_>>();
+  let page_len = end - offset;
+  let next_cursor =
+    |_: &SearchDocumentResponseItem| Cursor::encode(SEARCH_CURSOR_KIND, &(end, &query_fingerprint));
+  let mut page = Page::from_items(results, page_len, next_cursor);
+  if end >= MAX_SEARCH_RESULTS {
+    page.next_cursor = None;
+  }
+  Ok(page)
+}
+
+/// Up to `limit` documents matching the query, then up to `limit` shared chats when the hybrid
+/// search is enabled.
+#[allow(clippy::too_many_arguments)]
+async fn search_document_results(
+  pg_pool: &PgPool,
+  statement_timeout: Duration,
+  collab_storage: &CollabAccessControlStorage,
+  indexer_scheduler: &Arc<IndexerScheduler>,
+  feature_flags: &FeatureFlags,
+  uid: i64,
+  workspace_uuid: Uuid,
+  request: &SearchDocumentRequest,
+  limit: i32,
+  metrics: &RequestMetrics,
 ) -> Result<Vec<SearchDocumentResponseItem>, AppError> {
   let embeddings = indexer_scheduler
     .create_search_embeddings(EmbeddingRequest {
@@ -125,19 +232,35 @@ pub async fn search_document(
     0,
     MAX_SEARCH_DEPTH,
   );
+  let preview = request.preview_size.unwrap_or(500) as i32;
+  let mut txn = begin_with_statement_timeout(pg_pool, statement_timeout).await?;
   let results = search_documents(
-    pg_pool,
+    txn.deref_mut(),
     SearchDocumentParams {
       user_id: uid,
       workspace_id: workspace_uuid,
-      limit: request.limit.unwrap_or(10) as i32,
-      preview: request.preview_size.unwrap_or(500) as i32,
+      limit,
+      preview,
       embedding,
       searchable_view_ids: searchable_view_ids.into_iter().collect(),
     },
     total_tokens,
   )
   .await?;
+  let chat_results = if feature_flags.enabled(SHARED_CHAT_SEARCH, &workspace_uuid.to_string()) {
+    search_shared_chat_messages(
+      txn.deref_mut(),
+      &workspace_uuid,
+      uid,
+      &request.query,
+      limit,
+      preview,
+    )
+    .await?
+  } else {
+    vec![]
+  };
+  txn.commit().await?;
   tracing::trace!(
     "user {} search request in workspace {} returned {} results for query: `{}`",
     uid,
@@ -146,6 +269,19 @@ pub async fn search_document(
     request.query
   );
 
+  // the shared chats are matched by full text search, their rank is turned into a distance so
+  // that they sort like the documents
+  let chat_items = chat_results
+    .into_iter()
+    .map(|item| SearchDocumentResponseItem {
+      object_id: item.chat_id.to_string(),
+      workspace_id: item.workspace_id.to_string(),
+      score: 1.0 - item.rank.clamp(0.0, 1.0) as f64,
+      content_type: Some(SearchContentType::ChatConversation),
+      preview: Some(item.preview),
+      created_by: item.owner_name.unwrap_or_default(),
+      created_at: item.created_at,
+    });
   Ok(
     results
       .into_iter()
@@ -158,6 +294,7 @@ pub async fn search_document(
         created_by: item.created_by,
         created_at: item.created_at,
       })
+      .chain(chat_items)
       .collect(),
   )
 }
@@ -0,0 +1,3 @@
+mod ops;
+
+pub use self::ops::*;
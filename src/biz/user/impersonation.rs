@@ -0,0 +1,265 @@
+use std::ops::DerefMut;
+
+use anyhow::anyhow;
+use app_error::AppError;
+use chrono::{Duration, Utc};
+use database::impersonation::{
+  insert_impersonation, revoke_impersonation as revoke_impersonation_row,
+  select_active_impersonations, select_impersonation_requests, update_impersonation_write_grant,
+};
+use database::notification::insert_user_notification;
+use database::pg_row::AFImpersonationRow;
+use database::user::select_uuid_and_email_from_uid;
+use gotrue_entity::gotrue_jwt::GoTrueJWTClaims;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use shared_entity::dto::impersonation_dto::{
+  CreateImpersonationParams, GrantImpersonationWriteParams, Impersonation, ImpersonationRequest,
+  ImpersonationRequests, ImpersonationToken, Impersonations,
+};
+use sqlx::PgPool;
+use tracing::info;
+use uuid::Uuid;
+
+const DEFAULT_TTL_SECS: i64 = 15 * 60;
+const MAX_TTL_SECS: i64 = 60 * 60;
+const MAX_REQUESTS: i64 = 500;
+const IMPERSONATION_ISSUER: &str = "appflowy_cloud_impersonation";
+pub const SUPPORT_ACCESS_NOTIFICATION: &str = "support_access";
+pub const SUPPORT_WRITE_ACCESS_NOTIFICATION: &str = "support_write_access";
+
+/// Carried in the `app_metadata` of the access tokens issued for an impersonation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImpersonationClaim {
+  pub impersonation_id: Uuid,
+  pub admin_uid: i64,
+}
+
+/// The impersonation the access token was issued for, if any.
+pub fn impersonation_from_claims(claims: &GoTrueJWTClaims) -> Option<ImpersonationClaim> {
+  if claims.iss.as_deref() != Some(IMPERSONATION_ISSUER) {
+    return None;
+  }
+  let claim = claims.app_metadata.get("impersonation")?;
+  serde_json::from_value(claim.clone()).ok()
+}
+
+/// Issues an access token to act as the user, read-only until writes are granted with
+/// [grant_impersonation_write]. The user is notified that support accessed their account.
+///
+/// The token has no GoTrue session: it can't be refreshed, and it's rejected as soon as the
+/// impersonation expires or is revoked.
+pub async fn create_impersonation(
+  pg_pool: &PgPool,
+  jwt_secret: &Secret<String>,
+  admin_uid: i64,
+  params: CreateImpersonationParams,
+) -> Result<ImpersonationToken, AppError> {
+  let reason = params.reason.trim();
+  if reason.is_empty() {
+    return Err(AppError::InvalidRequest(
+      "A reason is required to impersonate a user".to_string(),
+    ));
+  }
+  if params.uid == admin_uid {
+    return Err(AppError::InvalidRequest(
+      "An admin can't impersonate themselves".to_string(),
+    ));
+  }
+  let ttl_secs = params
+    .ttl_secs
+    .unwrap_or(DEFAULT_TTL_SECS)
+    .clamp(1, MAX_TTL_SECS);
+  let (user_uuid, email) = select_uuid_and_email_from_uid(pg_pool, params.uid).await?;
+
+  let impersonation_id = Uuid::new_v4();
+  let now = Utc::now();
+  let expires_at = now + Duration::seconds(ttl_secs);
+  let claims = GoTrueJWTClaims {
+    aud: Some("authenticated".to_string()),
+    exp: Some(expires_at.timestamp()),
+    jti: Some(impersonation_id.to_string()),
+    iat: Some(now.timestamp()),
+    iss: Some(IMPERSONATION_ISSUER.to_string()),
+    nbf: None,
+    sub: Some(user_uuid.to_string()),
+    email,
+    phone: "".to_string(),
+    app_metadata: json!({
+      "impersonation": ImpersonationClaim {
+        impersonation_id,
+        admin_uid,
+      },
+    }),
+    user_metadata: json!({}),
+    role: "authenticated".to_string(),
+    aal: None,
+    amr: None,
+    session_id: None,
+  };
+  let access_token = claims
+    .encode(jwt_secret.expose_secret().as_bytes())
+    .map_err(|err| AppError::Internal(anyhow!("Failed to sign impersonation token: {}", err)))?;
+
+  let mut txn = pg_pool.begin().await?;
+  let row = insert_impersonation(
+    txn.deref_mut(),
+    &impersonation_id,
+    admin_uid,
+    params.uid,
+    reason,
+    expires_at,
+  )
+  .await?;
+  insert_user_notification(
+    txn.deref_mut(),
+    params.uid,
+    None,
+    SUPPORT_ACCESS_NOTIFICATION,
+    &json!({
+      "impersonation_id": impersonation_id,
+      "reason": reason,
+      "expires_at": expires_at,
+    }),
+  )
+  .await?;
+  txn.commit().await?;
+  info!(
+    "Admin:{} impersonates user:{}, impersonation:{}, reason: {}",
+    admin_uid, params.uid, impersonation_id, reason
+  );
+
+  Ok(ImpersonationToken {
+    impersonation_id,
+    access_token,
+    expires_at: row.expires_at,
+  })
+}
+
+/// Allows the writes under the impersonation. The user is notified again.
+pub async fn grant_impersonation_write(
+  pg_pool: &PgPool,
+  admin_uid: i64,
+  impersonation_id: &Uuid,
+  params: GrantImpersonationWriteParams,
+) -> Result<Impersonation, AppError> {
+  let reason = params.reason.trim();
+  if reason.is_empty() {
+    return Err(AppError::InvalidRequest(
+      "A reason is required to write as the user".to_string(),
+    ));
+  }
+  let mut txn = pg_pool.begin().await?;
+  let row = update_impersonation_write_grant(txn.deref_mut(), impersonation_id, reason)
+    .await?
+    .ok_or_else(|| {
+      AppError::RecordNotFound(format!(
+        "impersonation {} is expired or revoked",
+        impersonation_id
+      ))
+    })?;
+  insert_user_notification(
+    txn.deref_mut(),
+    row.uid,
+    None,
+    SUPPORT_WRITE_ACCESS_NOTIFICATION,
+    &json!({
+      "impersonation_id": impersonation_id,
+      "reason": reason,
+      "expires_at": row.expires_at,
+    }),
+  )
+  .await?;
+  txn.commit().await?;
+  info!(
+    "Admin:{} granted writes to impersonation:{} of user:{}, reason: {}",
+    admin_uid, impersonation_id, row.uid, reason
+  );
+  Ok(impersonation_from_row(row))
+}
+
+pub async fn list_active_impersonations(pg_pool: &PgPool) -> Result<Impersonations, AppError> {
+  let impersonations = select_active_impersonations(pg_pool)
+    .await?
+    .into_iter()
+    .map(impersonation_from_row)
+    .collect();
+  Ok(Impersonations { impersonations })
+}
+
+pub async fn revoke_impersonation(
+  pg_pool: &PgPool,
+  admin_uid: i64,
+  impersonation_id: &Uuid,
+) -> Result<(), AppError> {
+  if !revoke_impersonation_row(pg_pool, impersonation_id, admin_uid).await? {
+    return Err(AppError::RecordNotFound(format!(
+      "impersonation {} is expired or revoked",
+      impersonation_id
+    )));
+  }
+  info!(
+    "Admin:{} revoked impersonation:{}",
+    admin_uid, impersonation_id
+  );
+  Ok(())
+}
+
+pub async fn list_impersonation_requests(
+  pg_pool: &PgPool,
+  impersonation_id: &Uuid,
+) -> Result<ImpersonationRequests, AppError> {
+  let requests = select_impersonation_requests(pg_pool, impersonation_id, MAX_REQUESTS)
+    .await?
+    .into_iter()
+    .map(|row| ImpersonationRequest {
+      method: row.method,
+      path: row.path,
+      status: row.status as u16,
+      created_at: row.created_at,
+    })
+    .collect();
+  Ok(ImpersonationRequests { requests })
+}
+
+fn impersonation_from_row(row: AFImpersonationRow) -> Impersonation {
+  Impersonation {
+    impersonation_id: row.impersonation_id,
+    admin_uid: row.admin_uid,
+    uid: row.uid,
+    reason: row.reason,
+    write_reason: row.write_reason,
+    write_granted_at: row.write_granted_at,
+    created_at: row.created_at,
+    expires_at: row.expires_at,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn only_tokens_issued_for_an_impersonation_carry_one_test() {
+    let claim = ImpersonationClaim {
+      impersonation_id: Uuid::new_v4(),
+      admin_uid: 1,
+    };
+    let mut claims: GoTrueJWTClaims = serde_json::from_value(json!({
+      "email": "user@appflowy.io",
+      "phone": "",
+      "app_metadata": { "impersonation": claim },
+      "user_metadata": {},
+      "role": "authenticated",
+    }))
+    .unwrap();
+    // The app metadata alone isn't enough, the token must have been issued by the server.
+    assert!(impersonation_from_claims(&claims).is_none());
+
+    claims.iss = Some(IMPERSONATION_ISSUER.to_string());
+    let found = impersonation_from_claims(&claims).unwrap();
+    assert_eq!(found.impersonation_id, claim.impersonation_id);
+    assert_eq!(found.admin_uid, 1);
+  }
+}
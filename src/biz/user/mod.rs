@@ -1,4 +1,7 @@
+pub mod impersonation;
 pub mod user_delete;
 pub mod user_info;
 pub mod user_init;
+pub mod user_notification;
+pub mod user_session;
 pub mod user_verify;
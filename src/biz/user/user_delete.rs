@@ -1,10 +1,12 @@
 use std::sync::Arc;
 
+use crate::biz::chat::share::transfer_chats_before_user_delete;
 use crate::state::GoTrueAdmin;
 use crate::{biz::workspace::ops::delete_workspace_for_user, config::config::AppleOAuthSetting};
 use app_error::ErrorCode;
 use authentication::jwt::Authorization;
 use database::file::s3_client_impl::S3BucketStorage;
+use database::user::select_uid_from_uuid;
 use database::workspace::select_user_owned_workspaces_id;
 use gotrue::params::AdminDeleteUserParams;
 use secrecy::{ExposeSecret, Secret};
@@ -36,6 +38,10 @@ pub async fn delete_user(
     };
   }
 
+  // the shared chats of the user outlive the account
+  let uid = select_uid_from_uuid(pg_pool, &user_uuid).await?;
+  transfer_chats_before_user_delete(pg_pool, uid).await?;
+
   let admin_token = gotrue_admin.token().await?;
   gotrue_client
     .admin_delete_user(
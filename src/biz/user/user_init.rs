@@ -2,18 +2,16 @@ use std::sync::Arc;
 
 use app_error::AppError;
 use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
-use collab::core::origin::CollabOrigin;
-use collab::preclude::Collab;
-use collab_database::workspace_database::WorkspaceDatabase;
 use collab_entity::CollabType;
-use collab_folder::{Folder, FolderData, Workspace};
-use collab_user::core::UserAwareness;
 use database::collab::CollabStorage;
 use database::pg_row::AFWorkspaceRow;
 use database_entity::dto::CollabParams;
 use sqlx::Transaction;
 use tracing::{error, instrument, trace};
 use uuid::Uuid;
+use workspace_template::default_collab::{
+  default_folder_data, default_user_awareness_data, default_workspace_database_data,
+};
 use workspace_template::{TemplateObjectId, WorkspaceTemplate, WorkspaceTemplateBuilder};
 
 /// This function generates templates for a workspace and stores them in the database.
@@ -117,13 +115,7 @@ pub(crate) async fn create_user_awareness(
 ) -> Result<String, AppError> {
   let object_id = user_awareness_object_id(user_uuid, workspace_id).to_string();
   let collab_type = CollabType::UserAwareness;
-  let collab = Collab::new_with_origin(CollabOrigin::Empty, object_id.clone(), vec![], false);
-
-  // TODO(nathan): Maybe using hardcode encoded collab
-  let user_awareness = UserAwareness::create(collab, None)?;
-  let encode_collab = user_awareness
-    .encode_collab_v1(|collab| collab_type.validate_require_data(collab))
-    .map_err(|err| AppError::Internal(err.into()))?;
+  let encode_collab = default_user_awareness_data(&object_id).map_err(AppError::Internal)?;
   let encoded_collab_v1 = encode_collab
     .encode_to_bytes()
     .map_err(|err| AppError::Internal(anyhow::Error::from(err)))?;
@@ -151,15 +143,7 @@ pub(crate) async fn create_workspace_collab(
   storage: &Arc<CollabAccessControlStorage>,
   txn: &mut Transaction<'_, sqlx::Postgres>,
 ) -> Result<(), AppError> {
-  let workspace = Workspace::new(workspace_id.to_string(), name.to_string(), uid);
-  let folder_data = FolderData::new(workspace);
-
-  let collab = Collab::new_with_origin(CollabOrigin::Empty, workspace_id, vec![], false);
-  let folder = Folder::create(uid, collab, None, folder_data);
-  let encode_collab = folder
-    .encode_collab()
-    .map_err(|err| AppError::Internal(err.into()))?;
-
+  let encode_collab = default_folder_data(uid, workspace_id, name).map_err(AppError::Internal)?;
   let encoded_collab_v1 = encode_collab
     .encode_to_bytes()
     .map_err(|err| AppError::Internal(anyhow::Error::from(err)))?;
@@ -189,14 +173,8 @@ pub(crate) async fn create_workspace_database_collab(
   initial_database_records: Vec<(String, String)>,
 ) -> Result<(), AppError> {
   let collab_type = CollabType::WorkspaceDatabase;
-  let collab = Collab::new_with_origin(CollabOrigin::Empty, object_id, vec![], false);
-  let mut workspace_database = WorkspaceDatabase::create(collab);
-  for (object_id, database_id) in initial_database_records {
-    workspace_database.add_database(&database_id, vec![object_id]);
-  }
-  let encode_collab = workspace_database
-    .encode_collab_v1()
-    .map_err(|err| AppError::Internal(err.into()))?;
+  let encode_collab = default_workspace_database_data(object_id, initial_database_records)
+    .map_err(AppError::Internal)?;
 
   let encoded_collab_v1 = encode_collab
     .encode_to_bytes()
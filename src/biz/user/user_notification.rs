@@ -0,0 +1,255 @@
+use std::collections::{BTreeSet, HashSet};
+
+use app_error::AppError;
+use chrono::{DateTime, Utc};
+use database::notification::{
+  delete_notification_subscription, mark_user_notifications_read,
+  select_object_notification_subscriptions, select_uids_muting_mentions, select_user_mute_mentions,
+  select_user_notification_subscriptions, select_user_notifications,
+  upsert_notification_subscription, upsert_user_mute_mentions,
+};
+use database::outbox::{insert_outbox_entry, NotificationIntent, OutboxTopic};
+use shared_entity::dto::export_dto::{
+  NotificationSettings, NotificationSubscription, NotificationSubscriptionLevel,
+  NotificationSubscriptions, UserNotification,
+};
+use shared_entity::dto::page_dto::{Cursor, Page};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+const DEFAULT_NOTIFICATION_LIMIT: i64 = 50;
+const MAX_NOTIFICATION_LIMIT: i64 = 200;
+const NOTIFICATION_CURSOR_KIND: &str = "user_notification";
+
+/// The notifications of the user, newest first.
+pub async fn list_user_notifications(
+  pg_pool: &PgPool,
+  uid: i64,
+  only_unread: bool,
+  cursor: Option<&Cursor>,
+  limit: Option<i64>,
+) -> Result<Page<UserNotification>, AppError> {
+  let limit = limit
+    .unwrap_or(DEFAULT_NOTIFICATION_LIMIT)
+    .clamp(1, MAX_NOTIFICATION_LIMIT);
+  // the `(created_at, notification_id)` of the last notification of the previous page
+  let before = cursor
+    .map(|cursor| cursor.decode::<(DateTime<Utc>, Uuid)>(NOTIFICATION_CURSOR_KIND))
+    .transpose()?;
+  let rows = select_user_notifications(pg_pool, uid, only_unread, before, limit + 1).await?;
+  let page = Page::from_items(rows, limit as usize, |row| {
+    Cursor::encode(
+      NOTIFICATION_CURSOR_KIND,
+      &(row.created_at, row.notification_id),
+    )
+  })
+  .map(|row| UserNotification {
+    notification_id: row.notification_id,
+    workspace_id: row.workspace_id,
+    kind: row.kind,
+    payload: row.payload,
+    is_read: row.is_read,
+    created_at: row.created_at,
+  });
+  Ok(page)
+}
+
+pub async fn mark_notifications_read(
+  pg_pool: &PgPool,
+  uid: i64,
+  notification_ids: &[Uuid],
+) -> Result<(), AppError> {
+  mark_user_notifications_read(pg_pool, uid, notification_ids).await?;
+  Ok(())
+}
+
+pub async fn list_notification_subscriptions(
+  pg_pool: &PgPool,
+  uid: i64,
+  workspace_id: &Uuid,
+) -> Result<NotificationSubscriptions, AppError> {
+  let rows = select_user_notification_subscriptions(pg_pool, uid, workspace_id).await?;
+  let subscriptions = rows
+    .into_iter()
+    .map(|row| NotificationSubscription {
+      workspace_id: row.workspace_id,
+      object_id: row.object_id,
+      level: NotificationSubscriptionLevel::from(row.level),
+      updated_at: row.updated_at,
+    })
+    .collect();
+  Ok(NotificationSubscriptions { subscriptions })
+}
+
+pub async fn set_notification_subscription(
+  pg_pool: &PgPool,
+  uid: i64,
+  workspace_id: &Uuid,
+  object_id: &Uuid,
+  level: NotificationSubscriptionLevel,
+) -> Result<(), AppError> {
+  match level {
+    NotificationSubscriptionLevel::Default => {
+      delete_notification_subscription(pg_pool, uid, object_id).await?
+    },
+    level => {
+      upsert_notification_subscription(pg_pool, uid, workspace_id, object_id, level.value()).await?
+    },
+  }
+  Ok(())
+}
+
+pub async fn get_notification_settings(
+  pg_pool: &PgPool,
+  uid: i64,
+) -> Result<NotificationSettings, AppError> {
+  let mute_mentions = select_user_mute_mentions(pg_pool, uid).await?;
+  Ok(NotificationSettings { mute_mentions })
+}
+
+pub async fn update_notification_settings(
+  pg_pool: &PgPool,
+  uid: i64,
+  settings: &NotificationSettings,
+) -> Result<(), AppError> {
+  upsert_user_mute_mentions(pg_pool, uid, settings.mute_mentions).await?;
+  Ok(())
+}
+
+/// The users concerned by an activity on an object, before their preferences are applied.
+#[derive(Debug, Default)]
+pub struct ObjectActivity<'a> {
+  /// The user who caused the activity. They are never notified about it.
+  pub actor: i64,
+  /// The users who took part in the object, ie. the other commenters of a thread.
+  pub participants: &'a [i64],
+  pub mentioned: &'a [i64],
+}
+
+/// Records an inbox entry of the given kind for every user who should be notified about the
+/// activity, according to their preferences for the object. The entries are inserted by the
+/// appflowy worker once the caller commits `txn`, so they're only sent along with the activity.
+/// The recipients are returned so that the caller can send the emails, if any, to the same users.
+pub async fn notify_object_activity(
+  pg_pool: &PgPool,
+  txn: &mut Transaction<'_, Postgres>,
+  workspace_id: &Uuid,
+  object_id: &Uuid,
+  activity: ObjectActivity<'_>,
+  kind: &str,
+  payload: &serde_json::Value,
+) -> Result<Vec<i64>, AppError> {
+  let recipients = resolve_notification_recipients(pg_pool, object_id, &activity).await?;
+  if recipients.is_empty() {
+    return Ok(recipients);
+  }
+  let intent = NotificationIntent {
+    recipients: recipients.clone(),
+    workspace_id: Some(*workspace_id),
+    kind: kind.to_string(),
+    payload: payload.clone(),
+  };
+  insert_outbox_entry(txn, OutboxTopic::Notification, &intent).await?;
+  Ok(recipients)
+}
+
+pub async fn resolve_notification_recipients(
+  pg_pool: &PgPool,
+  object_id: &Uuid,
+  activity: &ObjectActivity<'_>,
+) -> Result<Vec<i64>, AppError> {
+  let subscriptions: Vec<(i64, NotificationSubscriptionLevel)> =
+    select_object_notification_subscriptions(pg_pool, object_id)
+      .await?
+      .into_iter()
+      .map(|row| (row.uid, NotificationSubscriptionLevel::from(row.level)))
+      .collect();
+  let muted_mentions: Vec<i64> = subscriptions
+    .iter()
+    .filter(|(uid, level)| {
+      *level == NotificationSubscriptionLevel::Mute && activity.mentioned.contains(uid)
+    })
+    .map(|(uid, _)| *uid)
+    .collect();
+  let muting_mentions: HashSet<i64> = if muted_mentions.is_empty() {
+    HashSet::new()
+  } else {
+    select_uids_muting_mentions(pg_pool, &muted_mentions)
+      .await?
+      .into_iter()
+      .collect()
+  };
+  Ok(filter_notification_recipients(
+    activity,
+    &subscriptions,
+    &muting_mentions,
+  ))
+}
+
+/// The participants and mentioned users are notified unless they muted the object, where a
+/// mention still notifies unless the user also mutes mentions. The users watching the object are
+/// notified regardless of whether they took part in it.
+fn filter_notification_recipients(
+  activity: &ObjectActivity<'_>,
+  subscriptions: &[(i64, NotificationSubscriptionLevel)],
+  muting_mentions: &HashSet<i64>,
+) -> Vec<i64> {
+  let is_muted = |uid: &i64| {
+    subscriptions
+      .iter()
+      .any(|(id, level)| id == uid && *level == NotificationSubscriptionLevel::Mute)
+  };
+  let mut recipients = BTreeSet::new();
+  recipients.extend(activity.participants.iter().filter(|uid| !is_muted(uid)));
+  recipients.extend(
+    activity
+      .mentioned
+      .iter()
+      .filter(|uid| !is_muted(uid) || !muting_mentions.contains(uid)),
+  );
+  recipients.extend(
+    subscriptions
+      .iter()
+      .filter(|(_, level)| *level == NotificationSubscriptionLevel::Watch)
+      .map(|(uid, _)| uid),
+  );
+  recipients.remove(&activity.actor);
+  recipients.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use shared_entity::dto::export_dto::NotificationSubscriptionLevel::{Mute, Watch};
+
+  #[test]
+  fn notification_preferences_test() {
+    let activity = ObjectActivity {
+      actor: 1,
+      participants: &[1, 2, 3],
+      mentioned: &[4, 5],
+    };
+    // 2 muted the object, 5 muted it while being mentioned and 6 watches it
+    let subscriptions = [(2, Mute), (5, Mute), (6, Watch)];
+    assert_eq!(
+      filter_notification_recipients(&activity, &subscriptions, &HashSet::new()),
+      vec![3, 4, 5, 6]
+    );
+
+    // 5 also mutes mentions
+    assert_eq!(
+      filter_notification_recipients(&activity, &subscriptions, &HashSet::from([5])),
+      vec![3, 4, 6]
+    );
+  }
+
+  #[test]
+  fn actor_is_not_notified_test() {
+    let activity = ObjectActivity {
+      actor: 1,
+      participants: &[],
+      mentioned: &[1],
+    };
+    assert!(filter_notification_recipients(&activity, &[(1, Watch)], &HashSet::new()).is_empty());
+  }
+}
@@ -0,0 +1,235 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use app_error::AppError;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use database::pg_row::AFUserSessionRow;
+use database::user_session::{
+  is_refresh_token_family_revoked, revoke_user_sessions, select_user_session_revocations,
+  select_user_sessions, upsert_user_session, SessionRevocation,
+};
+use gotrue_entity::gotrue_jwt::GoTrueJWTClaims;
+use shared_entity::dto::session_dto::{
+  RevokedSessions, SessionRevocation as SessionRevocationDto, SessionRevocations, UserSession,
+  UserSessions, SESSION_REVOKED_REFRESH_TOKEN_REUSED,
+};
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::biz::pg_listener::PgListeners;
+
+/// A session is recorded as seen at most once per interval.
+const TOUCH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// The access tokens of a revoked session are rejected for that long, which outlasts their expiry.
+const REVOKED_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+const MAX_TRACKED_SESSIONS: usize = 100_000;
+const MAX_REVOCATIONS: i64 = 100;
+
+/// What the server knows about the device a session was used from.
+#[derive(Debug, Default, Clone)]
+pub struct SessionDevice {
+  pub device_id: Option<String>,
+  pub client_version: Option<String>,
+  pub user_agent: Option<String>,
+  pub ip_address: Option<String>,
+}
+
+/// Records when the sessions of the users are seen and refreshed, and remembers the sessions
+/// revoked on any instance of the server so that their access tokens are rejected until they
+/// expire.
+pub struct UserSessionTracker {
+  pg_pool: PgPool,
+  /// When each session was last recorded, and the issue time of the access token it was seen with.
+  last_seen: DashMap<Uuid, (Instant, Option<i64>)>,
+  revoked: DashMap<Uuid, Instant>,
+}
+
+impl UserSessionTracker {
+  pub fn new(pg_pool: PgPool, pg_listeners: &PgListeners) -> Arc<Self> {
+    let tracker = Arc::new(Self {
+      pg_pool,
+      last_seen: DashMap::new(),
+      revoked: DashMap::new(),
+    });
+    let mut revoked_recv = pg_listeners.subscribe_user_session_revoked();
+    let weak_tracker = Arc::downgrade(&tracker);
+    tokio::spawn(async move {
+      while let Ok(notification) = revoked_recv.recv().await {
+        match weak_tracker.upgrade() {
+          Some(tracker) => tracker.mark_revoked(notification.session_id),
+          None => break,
+        }
+      }
+    });
+    tracker
+  }
+
+  pub fn is_revoked(&self, session_id: &Uuid) -> bool {
+    self.revoked.contains_key(session_id)
+  }
+
+  /// Records that the session was seen from the device with an access token issued at
+  /// `issued_at`, in seconds since the epoch. A session is recorded right away when its access
+  /// token changed, so that every refresh is counted. Returns true if the session was revoked,
+  /// including when GoTrue detected the reuse of one of its refresh tokens.
+  pub async fn touch(
+    &self,
+    session_id: &Uuid,
+    user_uuid: &Uuid,
+    device: &SessionDevice,
+    issued_at: Option<i64>,
+  ) -> Result<bool, AppError> {
+    if self.is_revoked(session_id) {
+      return Ok(true);
+    }
+    if let Some(last_seen) = self.last_seen.get(session_id) {
+      let (last_seen_at, last_issued_at) = *last_seen;
+      if last_seen_at.elapsed() < TOUCH_INTERVAL && last_issued_at >= issued_at {
+        return Ok(false);
+      }
+    }
+
+    let row = upsert_user_session(
+      &self.pg_pool,
+      session_id,
+      user_uuid,
+      device.device_id.as_deref(),
+      device.client_version.as_deref(),
+      device.user_agent.as_deref(),
+      device.ip_address.as_deref(),
+      issued_at.and_then(|issued_at| DateTime::<Utc>::from_timestamp(issued_at, 0)),
+    )
+    .await?;
+    if self.last_seen.len() >= MAX_TRACKED_SESSIONS {
+      self
+        .last_seen
+        .retain(|_, (last_seen_at, _)| last_seen_at.elapsed() < TOUCH_INTERVAL);
+    }
+    self
+      .last_seen
+      .insert(*session_id, (Instant::now(), issued_at));
+    let Some(row) = row else {
+      return Ok(false);
+    };
+    if row.revoked_at.is_some() {
+      self.mark_revoked(*session_id);
+      return Ok(true);
+    }
+    if is_refresh_token_family_revoked(&self.pg_pool, session_id).await? {
+      warn!(
+        "A refresh token of session {} of user:{} was reused, revoking the session",
+        session_id, row.uid
+      );
+      let mut txn = self.pg_pool.begin().await?;
+      revoke_user_sessions(
+        &mut txn,
+        row.uid,
+        SessionRevocation::One(session_id),
+        row.uid,
+        false,
+        Some(SESSION_REVOKED_REFRESH_TOKEN_REUSED),
+      )
+      .await?;
+      txn.commit().await?;
+      self.mark_revoked(*session_id);
+      return Ok(true);
+    }
+    Ok(false)
+  }
+
+  fn mark_revoked(&self, session_id: Uuid) {
+    self
+      .revoked
+      .retain(|_, revoked_at| revoked_at.elapsed() < REVOKED_RETENTION);
+    self.revoked.insert(session_id, Instant::now());
+    self.last_seen.remove(&session_id);
+  }
+}
+
+/// The GoTrue session the access token was issued for.
+pub fn session_id_from_claims(claims: &GoTrueJWTClaims) -> Option<Uuid> {
+  claims
+    .session_id
+    .as_deref()
+    .and_then(|session_id| Uuid::parse_str(session_id).ok())
+}
+
+pub async fn list_user_sessions(
+  pg_pool: &PgPool,
+  uid: i64,
+  current_session_id: Option<&Uuid>,
+) -> Result<UserSessions, AppError> {
+  let sessions = select_user_sessions(pg_pool, uid)
+    .await?
+    .into_iter()
+    .map(|row| user_session_from_row(row, current_session_id))
+    .collect();
+  Ok(UserSessions { sessions })
+}
+
+/// Revokes sessions of the user. The revoked sessions can't be refreshed anymore, their realtime
+/// connections are closed and their access tokens are rejected by every instance of the server.
+pub async fn revoke_sessions(
+  pg_pool: &PgPool,
+  uid: i64,
+  revocation: SessionRevocation<'_>,
+  revoked_by: i64,
+  revoked_by_admin: bool,
+) -> Result<RevokedSessions, AppError> {
+  let mut txn = pg_pool.begin().await?;
+  let session_ids = revoke_user_sessions(
+    &mut txn,
+    uid,
+    revocation,
+    revoked_by,
+    revoked_by_admin,
+    None,
+  )
+  .await?;
+  txn.commit().await?;
+  info!(
+    "User:{} revoked {} sessions of user:{}, by admin: {}",
+    revoked_by,
+    session_ids.len(),
+    uid,
+    revoked_by_admin
+  );
+  Ok(RevokedSessions { session_ids })
+}
+
+pub async fn list_session_revocations(
+  pg_pool: &PgPool,
+  uid: i64,
+) -> Result<SessionRevocations, AppError> {
+  let revocations = select_user_session_revocations(pg_pool, uid, MAX_REVOCATIONS)
+    .await?
+    .into_iter()
+    .map(
+      |(session_id, revoked_by, revoked_by_admin, revoked_at, reason)| SessionRevocationDto {
+        session_id,
+        revoked_by,
+        revoked_by_admin,
+        revoked_at,
+        reason,
+      },
+    )
+    .collect();
+  Ok(SessionRevocations { revocations })
+}
+
+fn user_session_from_row(row: AFUserSessionRow, current_session_id: Option<&Uuid>) -> UserSession {
+  UserSession {
+    is_current: current_session_id == Some(&row.session_id),
+    session_id: row.session_id,
+    device_id: row.device_id,
+    client_version: row.client_version,
+    user_agent: row.user_agent,
+    ip_address: row.ip_address,
+    created_at: row.created_at,
+    last_seen_at: row.last_seen_at,
+    refreshed_at: row.token_issued_at.filter(|_| row.refresh_count > 0),
+    refresh_count: row.refresh_count,
+  }
+}
@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+use database::blob_access::flush_blob_access;
+use infra::env_util::get_env_var;
+use redis::aio::ConnectionManager;
+use sqlx::PgPool;
+use tokio::time::MissedTickBehavior;
+use tracing::{error, trace};
+
+/// Writes the blob downloads buffered in Redis to the `last_accessed_at` of the blobs, every
+/// minute by default.
+pub fn spawn_blob_access_flush(redis: ConnectionManager, pg_pool: PgPool) {
+  let interval = Duration::from_secs(
+    get_env_var("APPFLOWY_BLOB_ACCESS_FLUSH_INTERVAL_SECS", "60")
+      .parse()
+      .unwrap_or(60),
+  );
+  tokio::spawn(async move {
+    let mut tick = tokio::time::interval(interval);
+    tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+      tick.tick().await;
+      match flush_blob_access(&redis, &pg_pool).await {
+        Ok(count) => trace!("flushed the accesses of {} blobs", count),
+        Err(err) => error!("failed to flush the blob accesses: {}", err),
+      }
+    }
+  });
+}
@@ -0,0 +1,151 @@
+use app_error::AppError;
+use async_stream::stream;
+use bytes::Bytes;
+use chrono::Utc;
+use database::pg_row::AFBlobMetadataRow;
+use database::resource_usage::get_all_workspace_blob_metadata_stream;
+use database::workspace_audit_log::insert_workspace_audit_log;
+use futures_util::{Stream, TryStreamExt};
+use serde_json::json;
+use shared_entity::dto::workspace_dto::{BlobMetadataExportFormat, BlobMetadataExportLine};
+use sqlx::PgPool;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::biz::workspace::member_export::{csv_line, format_time};
+
+pub const BLOB_METADATA_EXPORT_AUDIT_ACTION: &str = "blob_metadata_export";
+
+const BLOB_METADATA_EXPORT_COLUMNS: [&str; 5] = [
+  "file_id",
+  "file_type",
+  "file_size",
+  "created_at",
+  "modified_at",
+];
+
+/// Records the export in the audit log of the workspace, then streams the metadata of all its
+/// blobs, one line per blob ordered by file id. The CSV starts with a line naming the columns.
+/// The metadata is read from the database as the lines are sent.
+pub async fn export_workspace_blob_metadata(
+  pg_pool: PgPool,
+  workspace_id: Uuid,
+  uid: i64,
+  format: BlobMetadataExportFormat,
+) -> Result<impl Stream<Item = Result<Bytes, AppError>>, AppError> {
+  insert_workspace_audit_log(
+    &pg_pool,
+    &workspace_id,
+    uid,
+    BLOB_METADATA_EXPORT_AUDIT_ACTION,
+    &json!({ "content_type": format.content_type(), "generated_at": Utc::now() }),
+  )
+  .await?;
+
+  Ok(stream! {
+    if format == BlobMetadataExportFormat::Csv {
+      yield Ok(Bytes::from(csv_line(&BLOB_METADATA_EXPORT_COLUMNS)));
+    }
+    let mut rows = get_all_workspace_blob_metadata_stream(&pg_pool, workspace_id);
+    loop {
+      match rows.try_next().await {
+        Ok(Some(row)) => yield blob_metadata_line(row, format).map(Bytes::from),
+        Ok(None) => break,
+        Err(err) => {
+          error!("Failed to export the blob metadata of workspace {}: {}", workspace_id, err);
+          yield Err(AppError::from(err));
+          break;
+        },
+      }
+    }
+  })
+}
+
+fn blob_metadata_line(
+  row: AFBlobMetadataRow,
+  format: BlobMetadataExportFormat,
+) -> Result<String, AppError> {
+  match format {
+    BlobMetadataExportFormat::Csv => Ok(csv_line(&[
+      &row.file_id,
+      &row.file_type,
+      &row.file_size.to_string(),
+      &row.created_at.as_ref().map(format_time).unwrap_or_default(),
+      &format_time(&row.modified_at),
+    ])),
+    BlobMetadataExportFormat::Ndjson => {
+      let line = BlobMetadataExportLine {
+        file_id: row.file_id,
+        file_type: row.file_type,
+        file_size: row.file_size,
+        created_at: row.created_at,
+        modified_at: row.modified_at,
+      };
+      let mut line = serde_json::to_string(&line)?;
+      line.push('\n');
+      Ok(line)
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::DateTime;
+
+  fn row(file_id: &str, created_at: Option<DateTime<Utc>>) -> AFBlobMetadataRow {
+    let modified_at = DateTime::parse_from_rfc3339("2025-01-02T03:04:05Z")
+      .unwrap()
+      .with_timezone(&Utc);
+    AFBlobMetadataRow {
+      workspace_id: Uuid::nil(),
+      file_id: file_id.to_string(),
+      file_type: "image/png".to_string(),
+      file_size: 1024,
+      modified_at,
+      status: 0,
+      content_hash: None,
+      object_key: None,
+      last_accessed_at: modified_at,
+      storage_class: None,
+      storage_class_changed_at: None,
+      created_at,
+      uploaded_by: None,
+      expires_at: None,
+    }
+  }
+
+  #[test]
+  fn blob_metadata_csv_line_test() {
+    let created_at = DateTime::parse_from_rfc3339("2025-01-01T00:00:00Z")
+      .unwrap()
+      .with_timezone(&Utc);
+    assert_eq!(
+      blob_metadata_line(
+        row("doc_a_report, final.png", Some(created_at)),
+        BlobMetadataExportFormat::Csv
+      )
+      .unwrap(),
+      "\"doc_a_report, final.png\",image/png,1024,2025-01-01T00:00:00Z,2025-01-02T03:04:05Z\r\n"
+    );
+    // the creation time of the blobs uploaded before it was recorded is left empty
+    assert_eq!(
+      blob_metadata_line(row("-1.png", None), BlobMetadataExportFormat::Csv).unwrap(),
+      "'-1.png,image/png,1024,,2025-01-02T03:04:05Z\r\n"
+    );
+  }
+
+  #[test]
+  fn blob_metadata_ndjson_line_test() {
+    let line = blob_metadata_line(
+      row("doc_a_photo.png", None),
+      BlobMetadataExportFormat::Ndjson,
+    )
+    .unwrap();
+    assert!(line.ends_with('\n') && !line[..line.len() - 1].contains('\n'));
+    let line: BlobMetadataExportLine = serde_json::from_str(&line).unwrap();
+    assert_eq!(line.file_id, "doc_a_photo.png");
+    assert_eq!(line.file_size, 1024);
+    assert_eq!(line.created_at, None);
+  }
+}
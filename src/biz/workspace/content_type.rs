@@ -0,0 +1,398 @@
+use app_error::AppError;
+use database::workspace::select_workspace_settings;
+use mime::Mime;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// The content types a blob can be uploaded with when the workspace doesn't restrict them. A
+/// pattern ending with `*` matches every type starting with what precedes it.
+pub const DEFAULT_ALLOWED_CONTENT_TYPES: &[&str] = &[
+  "image/*",
+  "video/*",
+  "audio/*",
+  "text/plain",
+  "text/markdown",
+  "text/csv",
+  "application/pdf",
+  "application/json",
+  "application/zip",
+  "application/x-zip-compressed",
+  "application/gzip",
+  "application/epub+zip",
+  "application/rtf",
+  "application/msword",
+  "application/vnd.ms-excel",
+  "application/vnd.ms-powerpoint",
+  "application/vnd.openxmlformats-officedocument.*",
+  "application/vnd.oasis.opendocument.*",
+  "application/octet-stream",
+];
+
+/// Longest list of content types a workspace can allow.
+const MAX_ALLOWED_CONTENT_TYPES: usize = 100;
+
+/// Content types a browser runs scripts from. They're only allowed when listed as they are, a
+/// pattern such as `image/*` doesn't match them.
+const ACTIVE_CONTENT_TYPES: &[&str] = &[
+  "text/html",
+  "application/xhtml+xml",
+  "image/svg+xml",
+  "text/xml",
+  "application/xml",
+  "text/javascript",
+  "application/javascript",
+  "application/x-javascript",
+  "text/ecmascript",
+];
+
+/// Declared types which are served without being rendered, whatever the content.
+const INERT_CONTENT_TYPES: &[&str] = &["text/plain", "application/octet-stream"];
+
+/// The tags an HTML document can start with, as sniffed by the browsers.
+const HTML_TAGS: &[&[u8]] = &[
+  b"<!doctype html",
+  b"<html",
+  b"<head",
+  b"<script",
+  b"<iframe",
+  b"<h1",
+  b"<div",
+  b"<font",
+  b"<table",
+  b"<a",
+  b"<style",
+  b"<title",
+  b"<b",
+  b"<body",
+  b"<br",
+  b"<p",
+  b"<!--",
+];
+
+/// The content types the blobs of the workspace can be uploaded with.
+pub async fn get_allowed_content_types(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+) -> Result<Vec<String>, AppError> {
+  let settings = select_workspace_settings(pg_pool, workspace_id)
+    .await?
+    .unwrap_or_default();
+  Ok(settings.allowed_blob_content_types)
+}
+
+/// Rejects the lists of content types a workspace can't be restricted to.
+pub fn validate_allowed_content_types(allowed: &mut Vec<String>) -> Result<(), AppError> {
+  if allowed.len() > MAX_ALLOWED_CONTENT_TYPES {
+    return Err(AppError::InvalidRequest(format!(
+      "at most {} content types can be allowed",
+      MAX_ALLOWED_CONTENT_TYPES
+    )));
+  }
+  for pattern in allowed.iter_mut() {
+    *pattern = pattern.trim().to_ascii_lowercase();
+    let is_valid = match pattern.strip_suffix('*') {
+      Some(prefix) => prefix.contains('/'),
+      None => pattern.parse::<Mime>().is_ok(),
+    };
+    if !is_valid {
+      return Err(AppError::InvalidRequest(format!(
+        "{} is not a content type",
+        pattern
+      )));
+    }
+  }
+  Ok(())
+}
+
+/// Rejects a declared content type which isn't allowed, for the uploads whose content doesn't go
+/// through the server.
+pub fn check_declared_content_type(declared: &str, allowed: &[String]) -> Result<(), AppError> {
+  let essence = essence_of(declared)?;
+  check_allowed(&essence, allowed)
+}
+
+/// The content type to store a blob with. The first bytes of the content are compared to the
+/// declared type: a mismatch is corrected to the type of the content, unless the content is an
+/// HTML or script document, which is rejected so that it's never served under another type. The
+/// resulting type must be allowed by the workspace, see [DEFAULT_ALLOWED_CONTENT_TYPES].
+pub fn resolve_blob_content_type(
+  declared: &str,
+  content: &[u8],
+  allowed: &[String],
+) -> Result<String, AppError> {
+  let essence = essence_of(declared)?;
+  let content_type = match sniff_content_type(content) {
+    Some(sniffed) if ACTIVE_CONTENT_TYPES.contains(&sniffed) => {
+      if essence != sniffed && !INERT_CONTENT_TYPES.contains(&essence.as_str()) {
+        return Err(AppError::InvalidContentType(format!(
+          "the content is {} but was declared as {}",
+          sniffed, essence
+        )));
+      }
+      declared.to_string()
+    },
+    Some(sniffed) if is_compatible(&essence, sniffed) => declared.to_string(),
+    Some(sniffed) => sniffed.to_string(),
+    None if has_signature(&essence) => {
+      return Err(AppError::InvalidContentType(format!(
+        "the content is not {}",
+        essence
+      )));
+    },
+    None => declared.to_string(),
+  };
+  check_allowed(&essence_of(&content_type)?, allowed)?;
+  Ok(content_type)
+}
+
+fn essence_of(content_type: &str) -> Result<String, AppError> {
+  let mime = content_type
+    .parse::<Mime>()
+    .map_err(|_| AppError::InvalidContentType(format!("{} is not a content type", content_type)))?;
+  Ok(mime.essence_str().to_ascii_lowercase())
+}
+
+fn check_allowed(essence: &str, allowed: &[String]) -> Result<(), AppError> {
+  let is_allowed = if allowed.is_empty() {
+    DEFAULT_ALLOWED_CONTENT_TYPES
+      .iter()
+      .any(|pattern| matches_pattern(essence, pattern))
+  } else {
+    allowed
+      .iter()
+      .any(|pattern| matches_pattern(essence, pattern))
+  };
+  if is_allowed {
+    Ok(())
+  } else {
+    Err(AppError::InvalidContentType(format!(
+      "{} files can't be uploaded to this workspace",
+      essence
+    )))
+  }
+}
+
+fn matches_pattern(essence: &str, pattern: &str) -> bool {
+  match pattern.strip_suffix('*') {
+    Some(prefix) => !ACTIVE_CONTENT_TYPES.contains(&essence) && essence.starts_with(prefix),
+    None => essence == pattern,
+  }
+}
+
+/// The type of the content, told from its first bytes. `None` when it has no known signature.
+fn sniff_content_type(content: &[u8]) -> Option<&'static str> {
+  let starts_with = |prefix: &[u8]| content.starts_with(prefix);
+  let riff_kind = content.get(8..12).filter(|_| starts_with(b"RIFF"));
+  let ftyp_brand = content
+    .get(8..12)
+    .filter(|_| content.get(4..8) == Some(&b"ftyp"[..]));
+
+  let content_type = if starts_with(b"\x89PNG\r\n\x1a\n") {
+    "image/png"
+  } else if starts_with(b"\xff\xd8\xff") {
+    "image/jpeg"
+  } else if starts_with(b"GIF87a") || starts_with(b"GIF89a") {
+    "image/gif"
+  } else if riff_kind == Some(&b"WEBP"[..]) {
+    "image/webp"
+  } else if riff_kind == Some(&b"WAVE"[..]) {
+    "audio/wav"
+  } else if riff_kind == Some(&b"AVI "[..]) {
+    "video/x-msvideo"
+  } else if starts_with(b"II*\x00") || starts_with(b"MM\x00*") {
+    "image/tiff"
+  } else if starts_with(b"%PDF-") {
+    "application/pdf"
+  } else if let Some(brand) = ftyp_brand {
+    match brand {
+      b"avif" | b"avis" => "image/avif",
+      b"heic" | b"heix" | b"mif1" | b"msf1" => "image/heic",
+      b"qt  " => "video/quicktime",
+      b"M4A " => "audio/mp4",
+      _ => "video/mp4",
+    }
+  } else if starts_with(b"\x1a\x45\xdf\xa3") {
+    "video/webm"
+  } else if starts_with(b"OggS") {
+    "audio/ogg"
+  } else if starts_with(b"ID3") {
+    "audio/mpeg"
+  } else if starts_with(b"fLaC") {
+    "audio/flac"
+  } else if starts_with(b"PK\x03\x04") {
+    "application/zip"
+  } else if starts_with(b"\x1f\x8b") {
+    "application/gzip"
+  } else {
+    return sniff_markup(content);
+  };
+  Some(content_type)
+}
+
+/// Tells HTML, SVG and XML documents apart from plain text, the way the browsers do.
+fn sniff_markup(content: &[u8]) -> Option<&'static str> {
+  let content = content.strip_prefix(b"\xef\xbb\xbf").unwrap_or(content);
+  let start = content.iter().position(|b| !b.is_ascii_whitespace())?;
+  let head = &content[start..content.len().min(start + 1024)];
+  let lower = head.to_ascii_lowercase();
+  for tag in HTML_TAGS {
+    if let Some(rest) = lower.strip_prefix(*tag) {
+      // the tag must end right after its name, `<br` is not `<bread`
+      if *tag == b"<!--" || matches!(rest.first(), Some(b' ' | b'>' | b'\t' | b'\n' | b'\r')) {
+        return Some("text/html");
+      }
+    }
+  }
+  if lower.starts_with(b"<svg") {
+    return Some("image/svg+xml");
+  }
+  if lower.starts_with(b"<?xml") {
+    let is_svg = lower.windows(4).any(|window| window == b"<svg");
+    return Some(if is_svg { "image/svg+xml" } else { "text/xml" });
+  }
+  None
+}
+
+/// The types with a signature [sniff_content_type] recognizes, along with their aliases.
+fn has_signature(essence: &str) -> bool {
+  canonical_type(essence).is_some()
+}
+
+fn canonical_type(essence: &str) -> Option<&'static str> {
+  let canonical = match essence {
+    "image/png" => "image/png",
+    "image/jpeg" | "image/jpg" | "image/pjpeg" => "image/jpeg",
+    "image/gif" => "image/gif",
+    "image/webp" => "image/webp",
+    "image/tiff" => "image/tiff",
+    "image/avif" => "image/avif",
+    "image/heic" | "image/heif" => "image/heic",
+    "application/pdf" => "application/pdf",
+    "video/mp4" => "video/mp4",
+    "video/quicktime" => "video/quicktime",
+    "video/x-msvideo" | "video/avi" => "video/x-msvideo",
+    "video/webm" | "video/x-matroska" | "audio/webm" => "video/webm",
+    "audio/ogg" | "video/ogg" | "application/ogg" => "audio/ogg",
+    "audio/mpeg" | "audio/mp3" => "audio/mpeg",
+    "audio/mp4" | "audio/x-m4a" | "audio/m4a" => "audio/mp4",
+    "audio/wav" | "audio/x-wav" | "audio/wave" => "audio/wav",
+    "audio/flac" | "audio/x-flac" => "audio/flac",
+    "application/zip" | "application/x-zip-compressed" => "application/zip",
+    "application/gzip" | "application/x-gzip" => "application/gzip",
+    _ => return None,
+  };
+  Some(canonical)
+}
+
+fn is_compatible(essence: &str, sniffed: &str) -> bool {
+  if canonical_type(essence) == Some(sniffed) {
+    return true;
+  }
+  match sniffed {
+    // documents, archives and packages are zip or gzip files under their own type
+    "application/zip" | "application/gzip" => {
+      essence.starts_with("application/") && !has_signature(essence)
+    },
+    // the container of an audio track and of a video is the same
+    "video/mp4" => essence.starts_with("audio/mp4") || essence == "audio/aac",
+    "audio/ogg" => essence.ends_with("/ogg"),
+    _ => false,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR";
+
+  #[test]
+  fn html_declared_as_image_is_rejected_test() {
+    let html = b"<!DOCTYPE html><html><script>alert(1)</script></html>";
+    let result = resolve_blob_content_type("image/png", html, &[]);
+    assert!(matches!(result, Err(AppError::InvalidContentType(_))));
+
+    let html = b"\n  <html>\n<body onload=\"alert(1)\"></body></html>";
+    let result = resolve_blob_content_type("image/jpeg", html, &[]);
+    assert!(matches!(result, Err(AppError::InvalidContentType(_))));
+
+    let svg = b"<?xml version=\"1.0\"?><svg xmlns=\"http://www.w3.org/2000/svg\"></svg>";
+    let result = resolve_blob_content_type("image/png", svg, &[]);
+    assert!(matches!(result, Err(AppError::InvalidContentType(_))));
+
+    // text is served as it is
+    let content_type = resolve_blob_content_type("text/plain; charset=utf-8", html, &[]).unwrap();
+    assert_eq!(content_type, "text/plain; charset=utf-8");
+  }
+
+  #[test]
+  fn mismatched_content_type_is_corrected_test() {
+    assert_eq!(
+      resolve_blob_content_type("image/jpeg", PNG, &[]).unwrap(),
+      "image/png"
+    );
+    assert_eq!(
+      resolve_blob_content_type("application/octet-stream", b"%PDF-1.7\n", &[]).unwrap(),
+      "application/pdf"
+    );
+    assert_eq!(
+      resolve_blob_content_type("image/png", PNG, &[]).unwrap(),
+      "image/png"
+    );
+    assert_eq!(
+      resolve_blob_content_type("image/jpg", b"\xff\xd8\xff\xe0", &[]).unwrap(),
+      "image/jpg"
+    );
+    let mp4 = b"\x00\x00\x00\x18ftypmp42\x00\x00\x00\x00";
+    assert_eq!(
+      resolve_blob_content_type("video/mp4", mp4, &[]).unwrap(),
+      "video/mp4"
+    );
+    // the documents stored as zip files keep their type
+    let docx = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+    assert_eq!(
+      resolve_blob_content_type(docx, b"PK\x03\x04\x14\x00", &[]).unwrap(),
+      docx
+    );
+  }
+
+  #[test]
+  fn content_without_declared_signature_is_rejected_test() {
+    let result = resolve_blob_content_type("image/png", b"hello world", &[]);
+    assert!(matches!(result, Err(AppError::InvalidContentType(_))));
+    assert_eq!(
+      resolve_blob_content_type("text/plain", b"hello world", &[]).unwrap(),
+      "text/plain"
+    );
+    let result = resolve_blob_content_type("not a type", b"hello world", &[]);
+    assert!(matches!(result, Err(AppError::InvalidContentType(_))));
+  }
+
+  #[test]
+  fn workspace_allowlist_is_enforced_test() {
+    // html isn't allowed by default, even declared as html
+    let result = resolve_blob_content_type("text/html", b"<html></html>", &[]);
+    assert!(matches!(result, Err(AppError::InvalidContentType(_))));
+
+    let allowed = vec!["image/*".to_string()];
+    assert!(resolve_blob_content_type("image/png", PNG, &allowed).is_ok());
+    let result = resolve_blob_content_type("text/plain", b"hello", &allowed);
+    assert!(matches!(result, Err(AppError::InvalidContentType(_))));
+    let result = resolve_blob_content_type("image/svg+xml", b"<svg></svg>", &allowed);
+    assert!(matches!(result, Err(AppError::InvalidContentType(_))));
+
+    let allowed = vec!["text/html".to_string()];
+    assert!(resolve_blob_content_type("text/html", b"<html></html>", &allowed).is_ok());
+    assert!(check_declared_content_type("text/html", &allowed).is_ok());
+    assert!(check_declared_content_type("image/png", &allowed).is_err());
+  }
+
+  #[test]
+  fn validate_allowed_content_types_test() {
+    let mut allowed = vec![" Image/* ".to_string(), "application/pdf".to_string()];
+    validate_allowed_content_types(&mut allowed).unwrap();
+    assert_eq!(allowed, vec!["image/*", "application/pdf"]);
+    assert!(validate_allowed_content_types(&mut vec!["*".to_string()]).is_err());
+    assert!(validate_allowed_content_types(&mut vec!["pdf".to_string()]).is_err());
+  }
+}
@@ -0,0 +1,74 @@
+use std::ops::DerefMut;
+
+use app_error::AppError;
+use database::outbox::{insert_outbox_entry, OutboxTopic};
+use database::pg_row::AFWorkspaceExportRow;
+use database::user::select_name_and_email_from_uuid;
+use database::workspace::select_workspace_name_from_workspace_id;
+use database::workspace_export::{insert_workspace_export_task, select_workspace_export_task};
+use serde_json::json;
+use shared_entity::dto::export_dto::WorkspaceExportTask;
+use sqlx::PgPool;
+use tracing::info;
+use uuid::Uuid;
+
+/// Create a new export task for the workspace and hand it over to the worker. Only one export
+/// per workspace can be pending or running at the same time.
+pub async fn create_workspace_export(
+  pg_pool: &PgPool,
+  user_uuid: &Uuid,
+  uid: i64,
+  workspace_id: &Uuid,
+) -> Result<WorkspaceExportTask, AppError> {
+  let (user_name, user_email) = select_name_and_email_from_uuid(pg_pool, user_uuid).await?;
+  let workspace_name = select_workspace_name_from_workspace_id(pg_pool, workspace_id)
+    .await?
+    .unwrap_or_default();
+
+  // the task is pushed to the stream of the worker by the outbox relay, once the row is committed
+  let task_id = Uuid::new_v4();
+  let mut txn = pg_pool.begin().await?;
+  let row = insert_workspace_export_task(txn.deref_mut(), &task_id, workspace_id, uid).await?;
+  info!(
+    "User:{} request export of workspace:{}, task:{}",
+    uid, workspace_id, task_id
+  );
+
+  let task = json!({
+    "task_id": task_id,
+    "workspace_id": workspace_id,
+    "uid": uid,
+    "user_name": user_name,
+    "user_email": user_email,
+    "workspace_name": workspace_name,
+    "created_at": row.created_at.timestamp(),
+  });
+  insert_outbox_entry(&mut txn, OutboxTopic::WorkspaceExport, &task).await?;
+  txn.commit().await?;
+
+  Ok(export_task_from_row(row))
+}
+
+pub async fn get_workspace_export(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  task_id: &Uuid,
+) -> Result<WorkspaceExportTask, AppError> {
+  let row = select_workspace_export_task(pg_pool, workspace_id, task_id).await?;
+  Ok(export_task_from_row(row))
+}
+
+fn export_task_from_row(row: AFWorkspaceExportRow) -> WorkspaceExportTask {
+  WorkspaceExportTask {
+    task_id: row.task_id,
+    workspace_id: row.workspace_id,
+    status: row.status,
+    stage: row.stage,
+    bytes_written: row.bytes_written,
+    download_url: row.download_url,
+    error: row.error,
+    created_at: row.created_at,
+    updated_at: row.updated_at,
+    expires_at: row.expires_at,
+  }
+}
@@ -0,0 +1,329 @@
+use crate::api::file_storage::BlobPathV1;
+use crate::api::metrics::AppFlowyWebMetrics;
+use crate::api::ws::RealtimeServerAddr;
+use crate::biz::workspace::page_view::{update_page_cover, update_page_icon};
+use actix_web::web::Data;
+use anyhow::anyhow;
+use app_error::AppError;
+use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
+use aws_sdk_s3::primitives::ByteStream;
+use collab_rt_entity::user::RealtimeUser;
+use database::file::s3_client_impl::S3BucketStorage;
+use database::resource_usage::BlobAttributes;
+use database::workspace::{change_workspace_icon, select_workspace};
+use image::imageops::FilterType;
+use image::io::Reader as ImageReader;
+use image::{DynamicImage, ImageOutputFormat};
+use shared_entity::dto::file_dto::UploadImageResponse;
+use shared_entity::dto::workspace_dto::{IconType, ViewIcon};
+use sqlx::PgPool;
+use std::io::Cursor;
+use tracing::{error, trace};
+use uuid::Uuid;
+
+/// Maximum size of a raw icon upload. Checked before the image is decoded.
+pub const MAX_ICON_UPLOAD_SIZE: usize = 5 * 1024 * 1024;
+/// Maximum size of a raw cover upload. Checked before the image is decoded.
+pub const MAX_COVER_UPLOAD_SIZE: usize = 10 * 1024 * 1024;
+/// Images with more pixels than this are rejected without being decoded.
+const MAX_IMAGE_PIXELS: u64 = 50_000_000;
+/// File ids generated for processed images start with this prefix. Regular uploads can not use
+/// it, so the server can safely delete the images it replaced.
+pub const RESERVED_IMAGE_FILE_ID_PREFIX: &str = "af_image_";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ImageKind {
+  Icon,
+  Cover,
+}
+
+impl ImageKind {
+  pub fn max_upload_size(&self) -> usize {
+    match self {
+      ImageKind::Icon => MAX_ICON_UPLOAD_SIZE,
+      ImageKind::Cover => MAX_COVER_UPLOAD_SIZE,
+    }
+  }
+
+  /// Bound of the longest side of the processed image.
+  fn max_dimension(&self) -> u32 {
+    match self {
+      ImageKind::Icon => 256,
+      ImageKind::Cover => 1600,
+    }
+  }
+
+  fn as_str(&self) -> &'static str {
+    match self {
+      ImageKind::Icon => "icon",
+      ImageKind::Cover => "cover",
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct ProcessedImage {
+  pub data: Vec<u8>,
+  pub content_type: &'static str,
+  pub width: u32,
+  pub height: u32,
+}
+
+/// Decode the uploaded image, downscale it so that it fits in the bounds of `kind` and re-encode
+/// it. Icons are stored as PNG to keep transparency, covers as JPEG.
+pub fn process_image(raw: &[u8], kind: ImageKind) -> Result<ProcessedImage, AppError> {
+  if raw.len() > kind.max_upload_size() {
+    return Err(AppError::PayloadTooLarge(format!(
+      "The {} image exceeds the maximum size of {} bytes",
+      kind.as_str(),
+      kind.max_upload_size()
+    )));
+  }
+
+  let reader = ImageReader::new(Cursor::new(raw))
+    .with_guessed_format()
+    .map_err(|err| AppError::Internal(anyhow!("Failed to read image: {}", err)))?;
+  let format = reader
+    .format()
+    .ok_or_else(|| AppError::InvalidRequest("The uploaded file is not an image".to_string()))?;
+  let (width, height) = reader
+    .into_dimensions()
+    .map_err(|err| AppError::InvalidRequest(format!("Invalid image: {}", err)))?;
+  if width == 0 || height == 0 || width as u64 * height as u64 > MAX_IMAGE_PIXELS {
+    return Err(AppError::InvalidRequest(format!(
+      "Unsupported image dimensions: {}x{}",
+      width, height
+    )));
+  }
+
+  let image = image::load_from_memory_with_format(raw, format)
+    .map_err(|err| AppError::InvalidRequest(format!("Invalid image: {}", err)))?;
+  let max_dimension = kind.max_dimension();
+  let image = if image.width() > max_dimension || image.height() > max_dimension {
+    image.resize(max_dimension, max_dimension, FilterType::Lanczos3)
+  } else {
+    image
+  };
+
+  let (image, output_format, content_type) = match kind {
+    ImageKind::Icon => (image, ImageOutputFormat::Png, "image/png"),
+    ImageKind::Cover => (
+      DynamicImage::ImageRgb8(image.to_rgb8()),
+      ImageOutputFormat::Jpeg(85),
+      "image/jpeg",
+    ),
+  };
+  let mut data = Vec::new();
+  image
+    .write_to(&mut data, output_format)
+    .map_err(|err| AppError::Internal(anyhow!("Failed to encode image: {}", err)))?;
+  Ok(ProcessedImage {
+    data,
+    content_type,
+    width: image.width(),
+    height: image.height(),
+  })
+}
+
+pub fn is_reserved_file_id(file_id: &str) -> bool {
+  file_id.starts_with(RESERVED_IMAGE_FILE_ID_PREFIX)
+}
+
+pub(crate) fn blob_url(base_url: Option<&str>, key: &BlobPathV1) -> String {
+  format!(
+    "{}/api/file_storage/{}/v1/blob/{}/{}",
+    base_url.unwrap_or_default().trim_end_matches('/'),
+    key.workspace_id,
+    key.parent_dir,
+    key.file_id
+  )
+}
+
+/// Returns the blob key of a url generated by [blob_url], if the url points to an image that was
+/// processed by the server for the given workspace.
+fn reserved_image_key_from_url(workspace_id: &Uuid, url: &str) -> Option<BlobPathV1> {
+  let (_, path) = url.split_once("/api/file_storage/")?;
+  let segments: Vec<&str> = path.split('/').collect();
+  match segments.as_slice() {
+    [ws, "v1", "blob", parent_dir, file_id]
+      if *ws == workspace_id.to_string() && is_reserved_file_id(file_id) =>
+    {
+      Some(BlobPathV1 {
+        workspace_id: *workspace_id,
+        parent_dir: parent_dir.to_string(),
+        file_id: file_id.to_string(),
+      })
+    },
+    _ => None,
+  }
+}
+
+async fn store_image(
+  bucket_storage: &S3BucketStorage,
+  workspace_id: Uuid,
+  uid: i64,
+  parent_dir: String,
+  raw: Vec<u8>,
+  kind: ImageKind,
+) -> Result<BlobPathV1, AppError> {
+  let processed = tokio::task::spawn_blocking(move || process_image(&raw, kind))
+    .await
+    .map_err(|err| AppError::Internal(anyhow!("Failed to process image: {}", err)))??;
+  trace!(
+    "processed {} image for workspace {}: {}x{}, {} bytes",
+    kind.as_str(),
+    workspace_id,
+    processed.width,
+    processed.height,
+    processed.data.len()
+  );
+
+  let key = BlobPathV1 {
+    workspace_id,
+    parent_dir,
+    file_id: format!(
+      "{}{}_{}",
+      RESERVED_IMAGE_FILE_ID_PREFIX,
+      kind.as_str(),
+      Uuid::new_v4().simple()
+    ),
+  };
+  let file_size = processed.data.len();
+  bucket_storage
+    .put_blob_with_content_type(
+      key.clone(),
+      ByteStream::from(processed.data),
+      processed.content_type.to_string(),
+      file_size,
+      BlobAttributes::uploaded_by(uid),
+    )
+    .await?;
+  Ok(key)
+}
+
+async fn delete_image(bucket_storage: &S3BucketStorage, key: BlobPathV1) {
+  let file_id = key.file_id.clone();
+  if let Err(err) = bucket_storage.delete_blob(key).await {
+    error!("Failed to delete image {}: {}", file_id, err);
+  }
+}
+
+/// Process the uploaded image, store it and use it as the icon of the workspace. The previous
+/// icon is deleted if it was also uploaded through this endpoint.
+pub async fn upload_workspace_icon(
+  pg_pool: &PgPool,
+  bucket_storage: &S3BucketStorage,
+  base_url: Option<&str>,
+  workspace_id: Uuid,
+  uid: i64,
+  raw: Vec<u8>,
+) -> Result<UploadImageResponse, AppError> {
+  let old_icon = select_workspace(pg_pool, &workspace_id).await?.icon;
+  let key = store_image(
+    bucket_storage,
+    workspace_id,
+    uid,
+    workspace_id.to_string(),
+    raw,
+    ImageKind::Icon,
+  )
+  .await?;
+  let url = blob_url(base_url, &key);
+
+  let result = async {
+    let mut tx = pg_pool.begin().await?;
+    change_workspace_icon(&mut tx, &workspace_id, &url).await?;
+    tx.commit().await?;
+    Ok::<_, AppError>(())
+  }
+  .await;
+  let file_id = key.file_id.clone();
+  if let Err(err) = result {
+    delete_image(bucket_storage, key).await;
+    return Err(err);
+  }
+
+  if let Some(old_key) = old_icon
+    .as_deref()
+    .and_then(|url| reserved_image_key_from_url(&workspace_id, url))
+  {
+    delete_image(bucket_storage, old_key).await;
+  }
+  Ok(UploadImageResponse { file_id, url })
+}
+
+/// Process the uploaded image, store it and use it as the icon or the cover of the view. The
+/// previous image is deleted if it was also uploaded through this endpoint.
+#[allow(clippy::too_many_arguments)]
+pub async fn upload_page_image(
+  appflowy_web_metrics: &AppFlowyWebMetrics,
+  server: Data<RealtimeServerAddr>,
+  user: RealtimeUser,
+  collab_storage: &CollabAccessControlStorage,
+  bucket_storage: &S3BucketStorage,
+  base_url: Option<&str>,
+  workspace_id: Uuid,
+  view_id: &str,
+  raw: Vec<u8>,
+  kind: ImageKind,
+) -> Result<UploadImageResponse, AppError> {
+  let key = store_image(
+    bucket_storage,
+    workspace_id,
+    user.uid,
+    view_id.to_string(),
+    raw,
+    kind,
+  )
+  .await?;
+  let url = blob_url(base_url, &key);
+
+  let result = match kind {
+    ImageKind::Icon => update_page_icon(
+      appflowy_web_metrics,
+      server,
+      user,
+      collab_storage,
+      workspace_id,
+      view_id,
+      ViewIcon {
+        ty: IconType::Url,
+        value: url.clone(),
+      },
+    )
+    .await
+    .map(|old_icon| {
+      old_icon
+        .filter(|icon| icon.ty == IconType::Url)
+        .map(|icon| icon.value)
+    }),
+    ImageKind::Cover => {
+      update_page_cover(
+        appflowy_web_metrics,
+        server,
+        user,
+        collab_storage,
+        workspace_id,
+        view_id,
+        &url,
+      )
+      .await
+    },
+  };
+  let file_id = key.file_id.clone();
+  let old_url = match result {
+    Ok(old_url) => old_url,
+    Err(err) => {
+      delete_image(bucket_storage, key).await;
+      return Err(err);
+    },
+  };
+
+  if let Some(old_key) = old_url
+    .as_deref()
+    .and_then(|url| reserved_image_key_from_url(&workspace_id, url))
+  {
+    delete_image(bucket_storage, old_key).await;
+  }
+  Ok(UploadImageResponse { file_id, url })
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::ops::DerefMut;
+use std::sync::Arc;
+
+use access_control::workspace::WorkspaceAccessControl;
+use anyhow::anyhow;
+use app_error::AppError;
+use database::pg_row::AFWorkspaceMergeRow;
+use database::workspace::select_workspace_member_list;
+use database::workspace_merge::{
+  has_pending_import_task, insert_workspace_merge_task, is_workspace_merge_active,
+  select_workspace_archive, select_workspace_merge_task, update_workspace_merge_report,
+  upsert_merged_workspace_member,
+};
+use database_entity::dto::AFRole;
+use redis::AsyncCommands;
+use serde_json::json;
+use shared_entity::dto::merge_dto::{MergedMember, WorkspaceMergeReport, WorkspaceMergeTask};
+use sqlx::PgPool;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::state::RedisConnectionManager;
+
+pub const MERGE_TASK_STREAM: &str = "workspace_merge_task_stream";
+
+/// Merge the source workspace into the target workspace. The members of the source workspace
+/// join the target workspace right away, the content is moved by the worker, which archives the
+/// source workspace once it's done.
+///
+/// The merge is refused while either workspace has an import waiting to be processed, takes part
+/// in another merge or was already archived. A merge which failed can be requested again, it
+/// carries on with what the failed merge didn't move.
+pub async fn create_workspace_merge(
+  pg_pool: &PgPool,
+  redis_client: &RedisConnectionManager,
+  workspace_access_control: Arc<dyn WorkspaceAccessControl>,
+  uid: i64,
+  target_workspace_id: &Uuid,
+  source_workspace_id: &Uuid,
+) -> Result<WorkspaceMergeTask, AppError> {
+  if target_workspace_id == source_workspace_id {
+    return Err(AppError::InvalidRequest(
+      "A workspace can't be merged into itself".to_string(),
+    ));
+  }
+  let workspace_ids = [*target_workspace_id, *source_workspace_id];
+  for workspace_id in workspace_ids.iter() {
+    if select_workspace_archive(pg_pool, workspace_id)
+      .await?
+      .is_some()
+    {
+      return Err(AppError::WorkspaceMergeBlocked(format!(
+        "workspace {} is archived",
+        workspace_id
+      )));
+    }
+  }
+  if has_pending_import_task(pg_pool, &workspace_ids).await? {
+    return Err(AppError::WorkspaceMergeBlocked(
+      "An import is running in one of the workspaces, retry once it's finished".to_string(),
+    ));
+  }
+  if is_workspace_merge_active(pg_pool, &workspace_ids).await? {
+    return Err(AppError::WorkspaceMergeBlocked(
+      "One of the workspaces already has a merge in progress".to_string(),
+    ));
+  }
+
+  let task_id = Uuid::new_v4();
+  let mut txn = pg_pool.begin().await?;
+  let report = WorkspaceMergeReport::default();
+  let row = insert_workspace_merge_task(
+    txn.deref_mut(),
+    &task_id,
+    target_workspace_id,
+    source_workspace_id,
+    uid,
+    &serde_json::to_value(&report)?,
+  )
+  .await?;
+  let (report, roles) = merge_workspace_members(
+    pg_pool,
+    &mut txn,
+    target_workspace_id,
+    source_workspace_id,
+    report,
+  )
+  .await?;
+  let row = AFWorkspaceMergeRow {
+    report: serde_json::to_value(&report)?,
+    ..row
+  };
+  update_workspace_merge_report(txn.deref_mut(), &task_id, &row.report).await?;
+  txn.commit().await?;
+
+  for (member_uid, role) in roles {
+    workspace_access_control
+      .insert_role(&member_uid, target_workspace_id, role)
+      .await?;
+  }
+  info!(
+    "User:{} request merge of workspace:{} into workspace:{}, task:{}",
+    uid, source_workspace_id, target_workspace_id, task_id
+  );
+
+  let task = json!({
+    "task_id": task_id,
+    "target_workspace_id": target_workspace_id,
+    "source_workspace_id": source_workspace_id,
+    "uid": uid,
+    "created_at": row.created_at.timestamp(),
+  });
+  let _: () = redis_client
+    .clone()
+    .xadd(MERGE_TASK_STREAM, "*", &[("task", task.to_string())])
+    .await
+    .map_err(|err| AppError::Internal(anyhow!("Failed to push task to Redis stream: {}", err)))?;
+
+  merge_task_from_row(row)
+}
+
+pub async fn get_workspace_merge(
+  pg_pool: &PgPool,
+  target_workspace_id: &Uuid,
+  task_id: &Uuid,
+) -> Result<WorkspaceMergeTask, AppError> {
+  let row = select_workspace_merge_task(pg_pool, target_workspace_id, task_id).await?;
+  merge_task_from_row(row)
+}
+
+/// Add the members of the source workspace to the target workspace. A member of both keeps the
+/// higher of their roles. The target workspace keeps its owners: the owners of the source
+/// workspace who don't own the target workspace join it as members, and are reported as
+/// downgraded. Returns the roles the members have in the target workspace once merged.
+async fn merge_workspace_members(
+  pg_pool: &PgPool,
+  txn: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+  target_workspace_id: &Uuid,
+  source_workspace_id: &Uuid,
+  mut report: WorkspaceMergeReport,
+) -> Result<(WorkspaceMergeReport, Vec<(i64, AFRole)>), AppError> {
+  let target_roles = select_workspace_member_list(pg_pool, target_workspace_id)
+    .await?
+    .into_iter()
+    .map(|member| (member.uid, member.role))
+    .collect::<HashMap<_, _>>();
+  let source_members = select_workspace_member_list(pg_pool, source_workspace_id).await?;
+
+  let mut roles = vec![];
+  for member in source_members {
+    let target_role = target_roles.get(&member.uid).cloned();
+    let role = merged_role(&member.role, target_role.as_ref());
+    if target_role.as_ref() != Some(&role) {
+      upsert_merged_workspace_member(txn, target_workspace_id, member.uid, role.clone()).await?;
+      roles.push((member.uid, role.clone()));
+    }
+
+    let merged_member = MergedMember {
+      email: member.email,
+      source_role: member.role.clone(),
+      role: role.clone(),
+    };
+    if role_rank(&role) > role_rank(&member.role) {
+      report.downgraded_members.push(merged_member);
+    } else if target_role.is_none() {
+      report.added_members.push(merged_member);
+    }
+  }
+  Ok((report, roles))
+}
+
+/// The role of a member of the source workspace once merged, given the role they have in the
+/// target workspace if they're already a member of it.
+fn merged_role(source_role: &AFRole, target_role: Option<&AFRole>) -> AFRole {
+  let source_role = match (source_role, target_role) {
+    (AFRole::Owner, Some(AFRole::Owner)) => AFRole::Owner,
+    (AFRole::Owner, _) => AFRole::Member,
+    (role, _) => role.clone(),
+  };
+  match target_role {
+    Some(target_role) if role_rank(target_role) < role_rank(&source_role) => target_role.clone(),
+    _ => source_role,
+  }
+}
+
+/// The lower the rank, the higher the role.
+fn role_rank(role: &AFRole) -> i32 {
+  role.clone().into()
+}
+
+fn merge_task_from_row(row: AFWorkspaceMergeRow) -> Result<WorkspaceMergeTask, AppError> {
+  Ok(WorkspaceMergeTask {
+    task_id: row.task_id,
+    target_workspace_id: row.target_workspace_id,
+    source_workspace_id: row.source_workspace_id,
+    status: row.status,
+    stage: row.stage,
+    moved_collabs: row.moved_collabs,
+    copied_blobs: row.copied_blobs,
+    report: serde_json::from_value(row.report)?,
+    error: row.error,
+    created_at: row.created_at,
+    updated_at: row.updated_at,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn higher_role_wins_but_target_keeps_its_owners_test() {
+    assert_eq!(merged_role(&AFRole::Member, None), AFRole::Member);
+    assert_eq!(
+      merged_role(&AFRole::Guest, Some(&AFRole::Member)),
+      AFRole::Member
+    );
+    assert_eq!(
+      merged_role(&AFRole::Member, Some(&AFRole::Guest)),
+      AFRole::Member
+    );
+    assert_eq!(merged_role(&AFRole::Owner, None), AFRole::Member);
+    assert_eq!(
+      merged_role(&AFRole::Owner, Some(&AFRole::Guest)),
+      AFRole::Member
+    );
+    assert_eq!(
+      merged_role(&AFRole::Owner, Some(&AFRole::Owner)),
+      AFRole::Owner
+    );
+  }
+}
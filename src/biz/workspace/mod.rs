@@ -1,5 +1,19 @@
+pub mod ai_settings;
+pub mod blob_access;
+pub mod blob_export;
+pub mod content_type;
+pub mod export;
+pub mod image;
+pub mod member_export;
+pub mod merge;
 pub mod ops;
 pub mod page_view;
+pub mod provisioning;
 pub mod publish;
+pub mod publish_cdn;
 pub mod publish_dup;
 pub mod quick_note;
+pub mod similar_page;
+pub mod stats;
+pub mod usage;
+pub mod webhook;
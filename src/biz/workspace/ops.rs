@@ -6,6 +6,7 @@ use database_entity::dto::AFWorkspaceSettingsChange;
 use std::collections::HashMap;
 
 use anyhow::{anyhow, Context};
+use chrono::{DateTime, Utc};
 use redis::AsyncCommands;
 use serde_json::json;
 use sqlx::{types::uuid, PgPool};
@@ -19,19 +20,25 @@ use yrs::updates::encoder::Encode;
 use access_control::workspace::WorkspaceAccessControl;
 use app_error::AppError;
 use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
+use database::collab::{delete_collab_cascade, select_workspace_collab_oids};
 use database::file::s3_client_impl::S3BucketStorage;
 use database::pg_row::AFWorkspaceMemberRow;
 
+use database::outbox::{insert_outbox_entry, InvitationEmailIntent, OutboxTopic};
 use database::user::select_uid_from_email;
 use database::workspace::*;
 use database_entity::dto::{
-  AFRole, AFWorkspace, AFWorkspaceInvitation, AFWorkspaceInvitationStatus, AFWorkspaceSettings,
-  GlobalComment, Reaction, WorkspaceUsage,
+  AFRole, AFWorkspace, AFWorkspaceInvitation, AFWorkspaceInvitationStatus, AFWorkspaceMember,
+  AFWorkspacePendingInvitation, AFWorkspaceSettings, GlobalComment, Reaction,
+  WorkspaceMemberFilter, WorkspaceMemberSortBy, WorkspaceUsage,
 };
 use gotrue::params::{GenerateLinkParams, GenerateLinkType};
 
+use shared_entity::dto::page_dto::{Cursor, Page, PageQuery};
+use shared_entity::dto::webhook_dto::WebhookEventType;
 use shared_entity::dto::workspace_dto::{
-  CreateWorkspaceMember, WorkspaceMemberChangeset, WorkspaceMemberInvitation,
+  CreateWorkspaceMember, ExtendWorkspaceInvitationParams, WorkspaceMemberChangeset,
+  WorkspaceMemberInvitation,
 };
 use shared_entity::response::AppResponseError;
 use workspace_template::document::getting_started::GettingStartedTemplate;
@@ -40,17 +47,33 @@ use crate::biz::user::user_init::{
   create_user_awareness, create_workspace_collab, create_workspace_database_collab,
   initialize_workspace_for_user,
 };
-use crate::mailer::{AFCloudMailer, WorkspaceInviteMailerParam};
+use crate::biz::workspace::ai_settings::validate_ai_settings;
+use crate::biz::workspace::content_type::validate_allowed_content_types;
+use crate::biz::workspace::webhook::{identity, MembershipEvents};
+use crate::mailer::{workspace_invite_subject, AFCloudMailer, WorkspaceInviteMailerParam};
 use crate::state::{GoTrueAdmin, RedisConnectionManager};
 
 const MAX_COMMENT_LENGTH: usize = 5000;
+const DEFAULT_MEMBER_PAGE_SIZE: u32 = 100;
+const MAX_MEMBER_PAGE_SIZE: u32 = 500;
+const MEMBER_CURSOR_KIND: &str = "workspace_member";
+const DEFAULT_WORKSPACE_ICON_URL: &str =
+  "https://miro.medium.com/v2/resize:fit:2400/1*mTPfm7CwU31-tLhtLNkyJw.png";
+const DEFAULT_USER_ICON_URL: &str =
+  "https://cdn.pixabay.com/photo/2015/10/05/22/37/blank-profile-picture-973460_1280.png";
+/// An owner extending an invitation again within this time doesn't queue its email again.
+pub const INVITATION_EMAIL_COOLDOWN_SECS: i64 = 60;
 
 pub async fn delete_workspace_for_user(
   pg_pool: PgPool,
   workspace_id: Uuid,
   bucket_storage: Arc<S3BucketStorage>,
 ) -> Result<(), AppResponseError> {
-  // remove files from s3
+  // remove the collabs along with their snapshots, embeddings and attached files
+  let object_ids = select_workspace_collab_oids(&pg_pool, &workspace_id).await?;
+  purge_collabs(&pg_pool, &bucket_storage, &workspace_id, &object_ids).await?;
+
+  // remove the remaining files from s3
   bucket_storage
     .remove_dir(workspace_id.to_string().as_str())
     .await?;
@@ -64,6 +87,37 @@ pub async fn delete_workspace_for_user(
   Ok(())
 }
 
+/// Number of collabs deleted in one transaction by [purge_collabs].
+const PURGE_COLLAB_CHUNK_SIZE: usize = 100;
+
+/// Permanently deletes the collabs of the workspace with [delete_collab_cascade], then the
+/// objects they kept in the bucket. The collabs are deleted in chunks, one transaction each.
+pub async fn purge_collabs(
+  pg_pool: &PgPool,
+  bucket_storage: &S3BucketStorage,
+  workspace_id: &Uuid,
+  object_ids: &[String],
+) -> Result<(), AppError> {
+  for chunk in object_ids.chunks(PURGE_COLLAB_CHUNK_SIZE) {
+    let mut txn = pg_pool
+      .begin()
+      .await
+      .context("acquire transaction to purge collabs")?;
+    let mut object_keys = vec![];
+    for object_id in chunk {
+      object_keys.extend(delete_collab_cascade(&mut txn, workspace_id, object_id).await?);
+    }
+    txn
+      .commit()
+      .await
+      .context("fail to commit the transaction to purge collabs")?;
+    bucket_storage
+      .delete_cascaded_objects(workspace_id, object_keys)
+      .await;
+  }
+  Ok(())
+}
+
 /// Create an empty workspace with default folder, workspace database and user awareness collab
 /// object.
 pub async fn create_empty_workspace(
@@ -338,10 +392,33 @@ pub async fn accept_workspace_invite(
       )));
     }
   }
+  let expires_at = select_workspace_invitation_expiry_for_update(&mut txn, invite_id).await?;
+  if expires_at <= chrono::Utc::now() {
+    return Err(AppError::InvitationExpired(format!(
+      "invitation {} expired at {}",
+      invite_id, expires_at
+    )));
+  }
   update_workspace_invitation_set_status_accepted(&mut txn, user_uuid, invite_id).await?;
   let invited_uid = inv
     .invitee_uid
     .ok_or_else(|| AppError::Internal(anyhow::anyhow!("Invitee uid is missing for {:?}", inv)))?;
+  let invitee_email = database::user::select_email_from_user_uuid(pg_pool, user_uuid).await?;
+  let invitee = identity(Some(*user_uuid), Some(invitee_email));
+  let mut events = MembershipEvents::new(inv.workspace_id, invitee.clone());
+  events.push(
+    WebhookEventType::InvitationAccepted,
+    invitee.clone(),
+    Some(inv.role.clone()),
+    Some(*invite_id),
+  );
+  events.push(
+    WebhookEventType::MemberAdded,
+    invitee,
+    Some(inv.role.clone()),
+    Some(*invite_id),
+  );
+  events.queue(&mut txn).await?;
   workspace_access_control
     .insert_role(&invited_uid, &inv.workspace_id, inv.role)
     .await?;
@@ -359,6 +436,7 @@ pub async fn invite_workspace_members(
   inviter: &Uuid,
   workspace_id: &Uuid,
   invitations: Vec<WorkspaceMemberInvitation>,
+  invitation_expires_in_secs: i64,
   appflowy_web_url: Option<&str>,
   admin_frontend_path_prefix: &str,
 ) -> Result<(), AppError> {
@@ -386,6 +464,8 @@ pub async fn invite_workspace_members(
   let pending_invitations =
     database::workspace::select_workspace_pending_invitations(pg_pool, workspace_id).await?;
 
+  let mut events = MembershipEvents::new(*workspace_id, identity(Some(*inviter), None));
+
   // check if any of the invited users are already members of the workspace
   for invitation in &invitations {
     if workspace_members_by_email.contains_key(&invitation.email) {
@@ -397,16 +477,11 @@ pub async fn invite_workspace_members(
   }
 
   for invitation in invitations {
-    let inviter_name = inviter_name.clone();
-    let workspace_name = workspace_name.clone();
-    let workspace_member_count = workspace_member_count.to_string();
-
-    // use default icon until we have workspace icon
-    let workspace_icon_url =
-      "https://miro.medium.com/v2/resize:fit:2400/1*mTPfm7CwU31-tLhtLNkyJw.png".to_string();
-    let user_icon_url =
-      "https://cdn.pixabay.com/photo/2015/10/05/22/37/blank-profile-picture-973460_1280.png"
-        .to_string();
+    let mut param = invite_mailer_param(
+      inviter_name.clone(),
+      workspace_name.clone(),
+      workspace_member_count,
+    );
 
     let invite_id = match pending_invitations.get(&invitation.email) {
       None => {
@@ -419,64 +494,47 @@ pub async fn invite_workspace_members(
           inviter,
           invitation.email.as_str(),
           &invitation.role,
+          invitation_expires_in_secs,
         )
         .await?;
+        events.push(
+          WebhookEventType::InvitationSent,
+          identity(None, Some(invitation.email.clone())),
+          Some(invitation.role.clone()),
+          Some(invite_id),
+        );
         invite_id
       },
       Some(invite_id) => {
+        // inviting the user again extends the invitation
         tracing::warn!("User already invited: {}", invitation.email);
+        update_workspace_invitation_expiry(
+          txn.deref_mut(),
+          workspace_id,
+          invite_id,
+          invitation_expires_in_secs,
+        )
+        .await?;
         *invite_id
       },
     };
 
-    // Generate a link such that when clicked, the user is added to the workspace.
-    let accept_url = {
-      match appflowy_web_url {
-        Some(appflowy_web_url) => format!(
-          "{}/accept-invitation?invited_id={}",
-          appflowy_web_url, invite_id
-        ),
-        None => {
-          gotrue_client
-          .admin_generate_link(
-            &admin_token,
-            &GenerateLinkParams {
-              type_: GenerateLinkType::MagicLink,
-              email: invitation.email.clone(),
-              redirect_to: format!(
-                "{}/web/login-callback?action=accept_workspace_invite&workspace_invitation_id={}&workspace_name={}&workspace_icon={}&user_name={}&user_icon={}&workspace_member_count={}",
-                admin_frontend_path_prefix,
-                invite_id,
-                workspace_name,
-                workspace_icon_url,
-                inviter_name,
-                user_icon_url,
-                workspace_member_count,
-              ),
-              ..Default::default()
-            },
-          )
-          .await?
-          .action_link
-        },
-      }
-    };
+    param.accept_url = invitation_accept_url(
+      gotrue_client,
+      &admin_token,
+      appflowy_web_url,
+      admin_frontend_path_prefix,
+      &invite_id,
+      &invitation.email,
+      &param,
+    )
+    .await?;
 
     if !invitation.skip_email_send {
       let cloned_mailer = mailer.clone();
       let email_sending = tokio::spawn(async move {
         cloned_mailer
-          .send_workspace_invite(
-            &invitation.email,
-            WorkspaceInviteMailerParam {
-              user_icon_url,
-              username: inviter_name,
-              workspace_name,
-              workspace_icon_url,
-              workspace_member_count,
-              accept_url,
-            },
-          )
+          .send_workspace_invite(&invitation.email, param)
           .await
       });
       if invitation.wait_email_send {
@@ -490,6 +548,7 @@ pub async fn invite_workspace_members(
     }
   }
 
+  events.queue(&mut txn).await?;
   txn
     .commit()
     .await
@@ -497,6 +556,170 @@ pub async fn invite_workspace_members(
   Ok(())
 }
 
+/// The parameters of the invitation email, but its link.
+fn invite_mailer_param(
+  inviter_name: String,
+  workspace_name: String,
+  workspace_member_count: i64,
+) -> WorkspaceInviteMailerParam {
+  // use default icon until we have workspace icon
+  WorkspaceInviteMailerParam {
+    user_icon_url: DEFAULT_USER_ICON_URL.to_string(),
+    username: inviter_name,
+    workspace_name,
+    workspace_icon_url: DEFAULT_WORKSPACE_ICON_URL.to_string(),
+    workspace_member_count: workspace_member_count.to_string(),
+    accept_url: String::new(),
+  }
+}
+
+/// Generate a link such that when clicked, the user is added to the workspace.
+async fn invitation_accept_url(
+  gotrue_client: &gotrue::api::Client,
+  admin_token: &str,
+  appflowy_web_url: Option<&str>,
+  admin_frontend_path_prefix: &str,
+  invite_id: &Uuid,
+  invitee_email: &str,
+  param: &WorkspaceInviteMailerParam,
+) -> Result<String, AppError> {
+  let accept_url = match appflowy_web_url {
+    Some(appflowy_web_url) => format!(
+      "{}/accept-invitation?invited_id={}",
+      appflowy_web_url, invite_id
+    ),
+    None => {
+      gotrue_client
+        .admin_generate_link(
+          admin_token,
+          &GenerateLinkParams {
+            type_: GenerateLinkType::MagicLink,
+            email: invitee_email.to_string(),
+            redirect_to: format!(
+              "{}/web/login-callback?action=accept_workspace_invite&workspace_invitation_id={}&workspace_name={}&workspace_icon={}&user_name={}&user_icon={}&workspace_member_count={}",
+              admin_frontend_path_prefix,
+              invite_id,
+              param.workspace_name,
+              param.workspace_icon_url,
+              param.username,
+              param.user_icon_url,
+              param.workspace_member_count,
+            ),
+            ..Default::default()
+          },
+        )
+        .await?
+        .action_link
+    },
+  };
+  Ok(accept_url)
+}
+
+pub async fn list_workspace_pending_invitations(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+) -> Result<Vec<AFWorkspacePendingInvitation>, AppError> {
+  let rows = select_workspace_pending_invitation_list(pg_pool, workspace_id).await?;
+  Ok(
+    rows
+      .into_iter()
+      .map(AFWorkspacePendingInvitation::from)
+      .collect(),
+  )
+}
+
+/// Resets the expiry of a pending invitation, expired or not. When asked to, the invitation email
+/// is queued in the outbox again, with a fresh link, unless one was queued less than
+/// [INVITATION_EMAIL_COOLDOWN_SECS] ago. An email queued before this one is dropped rather than
+/// sent if it's still in the outbox.
+#[instrument(level = "debug", skip_all, err)]
+#[allow(clippy::too_many_arguments)]
+pub async fn extend_workspace_invitation(
+  gotrue_admin: &GoTrueAdmin,
+  pg_pool: &PgPool,
+  gotrue_client: &gotrue::api::Client,
+  inviter: &Uuid,
+  workspace_id: &Uuid,
+  invite_id: &Uuid,
+  params: ExtendWorkspaceInvitationParams,
+  invitation_expires_in_secs: i64,
+  appflowy_web_url: Option<&str>,
+  admin_frontend_path_prefix: &str,
+) -> Result<AFWorkspacePendingInvitation, AppError> {
+  let mut txn = pg_pool
+    .begin()
+    .await
+    .context("Begin transaction to extend workspace invitation")?;
+  update_workspace_invitation_expiry(
+    txn.deref_mut(),
+    workspace_id,
+    invite_id,
+    invitation_expires_in_secs,
+  )
+  .await?;
+
+  if params.resend_email {
+    match update_workspace_invitation_email_seq(
+      txn.deref_mut(),
+      invite_id,
+      INVITATION_EMAIL_COOLDOWN_SECS,
+    )
+    .await?
+    {
+      Some(email_seq) => {
+        let invitation =
+          select_workspace_pending_invitation(txn.deref_mut(), workspace_id, invite_id).await?;
+        let inviter_name = database::user::select_name_from_uuid(pg_pool, inviter).await?;
+        let workspace_name = select_workspace_name_from_workspace_id(pg_pool, workspace_id)
+          .await?
+          .unwrap_or_default();
+        let workspace_member_count =
+          select_workspace_member_count_from_workspace_id(pg_pool, workspace_id)
+            .await?
+            .unwrap_or_default();
+        let mut param = invite_mailer_param(inviter_name, workspace_name, workspace_member_count);
+        let admin_token = gotrue_admin.token().await?;
+        param.accept_url = invitation_accept_url(
+          gotrue_client,
+          &admin_token,
+          appflowy_web_url,
+          admin_frontend_path_prefix,
+          invite_id,
+          &invitation.invitee_email,
+          &param,
+        )
+        .await?;
+        insert_outbox_entry(
+          &mut txn,
+          OutboxTopic::InvitationEmail,
+          &InvitationEmailIntent {
+            invite_id: *invite_id,
+            email_seq,
+            recipient: invitation.invitee_email,
+            sender_name: Some(param.username.clone()),
+            subject: workspace_invite_subject(&param),
+            param: serde_json::to_value(&param)?,
+          },
+        )
+        .await?;
+      },
+      None => tracing::info!(
+        "Invitation email of {} was queued less than {}s ago, not queued again",
+        invite_id,
+        INVITATION_EMAIL_COOLDOWN_SECS
+      ),
+    }
+  }
+
+  let invitation =
+    select_workspace_pending_invitation(txn.deref_mut(), workspace_id, invite_id).await?;
+  txn
+    .commit()
+    .await
+    .context("Commit transaction to extend workspace invitation")?;
+  Ok(invitation.into())
+}
+
 #[instrument(level = "debug", skip_all, err)]
 pub async fn list_workspace_invitations_for_user(
   pg_pool: &PgPool,
@@ -527,7 +750,7 @@ pub async fn get_workspace_invitations_for_user(
 // use in tests only
 pub async fn add_workspace_members_db_only(
   pg_pool: &PgPool,
-  _user_uuid: &Uuid,
+  user_uuid: &Uuid,
   workspace_id: &Uuid,
   members: Vec<CreateWorkspaceMember>,
 ) -> Result<(), AppError> {
@@ -536,10 +759,18 @@ pub async fn add_workspace_members_db_only(
     .await
     .context("Begin transaction to insert workspace members")?;
 
+  let mut events = MembershipEvents::new(*workspace_id, identity(Some(*user_uuid), None));
   for member in members.into_iter() {
     upsert_workspace_member_with_txn(&mut txn, workspace_id, &member.email, member.role.clone())
       .await?;
+    events.push(
+      WebhookEventType::MemberAdded,
+      identity(None, Some(member.email)),
+      Some(member.role),
+      None,
+    );
   }
+  events.queue(&mut txn).await?;
 
   txn
     .commit()
@@ -556,11 +787,19 @@ pub async fn leave_workspace(
   workspace_access_control: Arc<dyn WorkspaceAccessControl>,
 ) -> Result<(), AppResponseError> {
   let email = database::user::select_email_from_user_uuid(pg_pool, user_uuid).await?;
-  remove_workspace_members(pg_pool, workspace_id, &[email], workspace_access_control).await
+  remove_workspace_members(
+    pg_pool,
+    user_uuid,
+    workspace_id,
+    &[email],
+    workspace_access_control,
+  )
+  .await
 }
 
 pub async fn remove_workspace_members(
   pg_pool: &PgPool,
+  actor: &Uuid,
   workspace_id: &Uuid,
   member_emails: &[String],
   workspace_access_control: Arc<dyn WorkspaceAccessControl>,
@@ -570,8 +809,15 @@ pub async fn remove_workspace_members(
     .await
     .context("Begin transaction to delete workspace members")?;
 
+  let mut events = MembershipEvents::new(*workspace_id, identity(Some(*actor), None));
   for email in member_emails {
     delete_workspace_members(&mut txn, workspace_id, email.as_str()).await?;
+    events.push(
+      WebhookEventType::MemberRemoved,
+      identity(None, Some(email.clone())),
+      None,
+      None,
+    );
     if let Ok(uid) = select_uid_from_email(txn.deref_mut(), email)
       .await
       .map_err(AppResponseError::from)
@@ -582,6 +828,7 @@ pub async fn remove_workspace_members(
     }
   }
 
+  events.queue(&mut txn).await?;
   txn
     .commit()
     .await
@@ -596,6 +843,73 @@ pub async fn get_workspace_members(
   Ok(select_workspace_member_list(pg_pool, workspace_id).await?)
 }
 
+/// The members of the workspace matching the filter, in its order, along with the number of
+/// members it matches.
+pub async fn get_workspace_member_page(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  filter: &WorkspaceMemberFilter,
+  query: &PageQuery,
+) -> Result<Page<AFWorkspaceMember>, AppError> {
+  let limit = query
+    .limit
+    .unwrap_or(DEFAULT_MEMBER_PAGE_SIZE)
+    .clamp(1, MAX_MEMBER_PAGE_SIZE);
+  let kind = member_cursor_kind(filter);
+  // the sort key and uid of the last member of the previous page
+  let after = query
+    .cursor
+    .as_ref()
+    .map(|cursor| -> Result<_, AppError> {
+      Ok(match filter.sort_by {
+        WorkspaceMemberSortBy::JoinedAt => {
+          let (joined_at, uid) = cursor.decode::<(DateTime<Utc>, i64)>(&kind)?;
+          WorkspaceMemberPosition::JoinedAt(joined_at, uid)
+        },
+        WorkspaceMemberSortBy::Name | WorkspaceMemberSortBy::Email => {
+          let (text, uid) = cursor.decode::<(String, i64)>(&kind)?;
+          WorkspaceMemberPosition::Text(text, uid)
+        },
+        WorkspaceMemberSortBy::Role => {
+          let (role_id, uid) = cursor.decode::<(i32, i64)>(&kind)?;
+          WorkspaceMemberPosition::Role(role_id, uid)
+        },
+      })
+    })
+    .transpose()?;
+  let rows =
+    select_workspace_member_page(pg_pool, workspace_id, filter, after, limit as i64 + 1).await?;
+  let total = select_workspace_member_match_count(pg_pool, workspace_id, filter).await?;
+  let page = Page::from_items(rows, limit as usize, |member| match filter.sort_by {
+    WorkspaceMemberSortBy::JoinedAt => Cursor::encode(&kind, &(member.joined_at, member.uid)),
+    WorkspaceMemberSortBy::Name => Cursor::encode(&kind, &(&member.name, member.uid)),
+    WorkspaceMemberSortBy::Email => Cursor::encode(&kind, &(&member.email, member.uid)),
+    WorkspaceMemberSortBy::Role => Cursor::encode(&kind, &(member.role_id, member.uid)),
+  })
+  .with_total(total)
+  .map(|member| AFWorkspaceMember {
+    name: member.name,
+    email: member.email,
+    role: AFRole::from(member.role_id),
+    avatar_url: None,
+  });
+  Ok(page)
+}
+
+/// The members are listed in the order they joined unless the filter sorts them otherwise, the
+/// cursors of each order are of their own kind so that they're not mixed up.
+fn member_cursor_kind(filter: &WorkspaceMemberFilter) -> String {
+  let sort_by = match filter.sort_by {
+    WorkspaceMemberSortBy::JoinedAt if !filter.descending => return MEMBER_CURSOR_KIND.to_string(),
+    WorkspaceMemberSortBy::JoinedAt => "joined_at",
+    WorkspaceMemberSortBy::Name => "name",
+    WorkspaceMemberSortBy::Email => "email",
+    WorkspaceMemberSortBy::Role => "role",
+  };
+  let direction = if filter.descending { "desc" } else { "asc" };
+  format!("{}:{}:{}", MEMBER_CURSOR_KIND, sort_by, direction)
+}
+
 pub async fn get_workspace_member(
   uid: &i64,
   pg_pool: &PgPool,
@@ -606,13 +920,36 @@ pub async fn get_workspace_member(
 
 pub async fn update_workspace_member(
   uid: &i64,
+  actor: &Uuid,
   pg_pool: &PgPool,
   workspace_id: &Uuid,
   changeset: &WorkspaceMemberChangeset,
   workspace_access_control: Arc<dyn WorkspaceAccessControl>,
 ) -> Result<(), AppError> {
   if let Some(role) = &changeset.role {
-    upsert_workspace_member(pg_pool, workspace_id, &changeset.email, role.clone()).await?;
+    let mut txn = pg_pool
+      .begin()
+      .await
+      .context("Begin transaction to update workspace member")?;
+    upsert_workspace_member(
+      txn.deref_mut(),
+      workspace_id,
+      &changeset.email,
+      role.clone(),
+    )
+    .await?;
+    let mut events = MembershipEvents::new(*workspace_id, identity(Some(*actor), None));
+    events.push(
+      WebhookEventType::RoleChanged,
+      identity(None, Some(changeset.email.clone())),
+      Some(role.clone()),
+      None,
+    );
+    events.queue(&mut txn).await?;
+    txn
+      .commit()
+      .await
+      .context("Commit transaction to update workspace member")?;
     workspace_access_control
       .insert_role(uid, workspace_id, role.clone())
       .await?;
@@ -656,6 +993,20 @@ pub async fn update_workspace_settings(
     setting.ai_model = ai_model;
   }
 
+  if let Some(enable_editing_lock) = change.enable_editing_lock {
+    setting.enable_editing_lock = enable_editing_lock;
+  }
+
+  if let Some(mut ai) = change.ai {
+    validate_ai_settings(&mut ai)?;
+    setting.ai = ai;
+  }
+
+  if let Some(mut allowed) = change.allowed_blob_content_types {
+    validate_allowed_content_types(&mut allowed)?;
+    setting.allowed_blob_content_types = allowed;
+  }
+
   // Update the workspace settings in the database
   upsert_workspace_settings(&mut tx, workspace_id, &setting).await?;
   tx.commit().await?;
@@ -732,9 +1083,10 @@ pub async fn num_pending_task(uid: i64, pg_pool: &PgPool) -> Result<i64, AppErro
   Ok(count)
 }
 
-/// broadcast updates to collab group if exists
+/// broadcast updates to the clients of the collab, on any realtime server
 pub async fn broadcast_update(
   collab_storage: &CollabAccessControlStorage,
+  workspace_id: &str,
   oid: &str,
   encoded_update: Vec<u8>,
 ) -> Result<(), AppError> {
@@ -750,7 +1102,7 @@ pub async fn broadcast_update(
   };
 
   collab_storage
-    .broadcast_encode_collab(oid.to_string(), vec![msg])
+    .broadcast_encode_collab(workspace_id.to_string(), oid.to_string(), vec![msg])
     .await?;
 
   Ok(())
@@ -760,6 +1112,7 @@ pub async fn broadcast_update(
 /// waits for a maximum of 30 seconds for the broadcast to complete
 pub async fn broadcast_update_with_timeout(
   collab_storage: Arc<CollabAccessControlStorage>,
+  workspace_id: String,
   oid: String,
   encoded_update: Vec<u8>,
 ) -> tokio::task::JoinHandle<()> {
@@ -767,7 +1120,7 @@ pub async fn broadcast_update_with_timeout(
     tracing::info!("broadcasting update to group: {}", oid);
     let res = match tokio::time::timeout(
       Duration::from_secs(30),
-      broadcast_update(&collab_storage, &oid, encoded_update),
+      broadcast_update(&collab_storage, &workspace_id, &oid, encoded_update),
     )
     .await
     {
@@ -1,6 +1,7 @@
 use crate::api::metrics::AppFlowyWebMetrics;
 use crate::api::ws::RealtimeServerAddr;
 use crate::biz::chat::ops::create_chat;
+use crate::biz::collab::diff::{hash_collab_state, state_vector_hash};
 use crate::biz::collab::folder_view::{
   check_if_view_is_space, parse_extra_field_as_json, to_dto_view_icon, to_dto_view_layout,
   to_folder_view_icon, to_space_permission,
@@ -16,7 +17,7 @@ use app_error::AppError;
 use appflowy_collaborate::actix_ws::entities::ClientHttpUpdateMessage;
 use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
 use bytes::Bytes;
-use chrono::DateTime;
+use chrono::{DateTime, Utc};
 use collab::core::collab::Collab;
 use collab_database::database::{
   gen_database_group_id, gen_database_id, gen_field_id, gen_row_id, Database, DatabaseContext,
@@ -41,7 +42,12 @@ use collab_folder::hierarchy_builder::NestedChildViewBuilder;
 use collab_folder::{timestamp, CollabOrigin, Folder, SpaceInfo};
 use collab_rt_entity::user::RealtimeUser;
 use database::collab::{select_workspace_database_oid, CollabStorage, GetCollabOrigin};
-use database::publish::select_published_view_ids_for_workspace;
+use database::file::s3_client_impl::S3BucketStorage;
+use database::publish::{
+  delete_scheduled_publish, select_published_collab_revision,
+  select_published_view_ids_for_workspace, update_published_collab_source_fingerprint,
+  upsert_scheduled_publish,
+};
 use database::user::select_web_user_from_uid;
 use database_entity::dto::{
   CollabParams, PublishCollabItem, PublishCollabMetadata, QueryCollab, QueryCollabResult,
@@ -50,10 +56,12 @@ use fancy_regex::Regex;
 use itertools::Itertools;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use serde_json::json;
+use sha2::{Digest, Sha256};
 use shared_entity::dto::chat_dto::CreateChatParams;
 use shared_entity::dto::publish_dto::{PublishDatabaseData, PublishViewInfo, PublishViewMetaData};
 use shared_entity::dto::workspace_dto::{
-  FolderView, Page, PageCollab, PageCollabData, Space, SpacePermission, ViewIcon, ViewLayout,
+  FolderView, Page, PageCollab, PageCollabData, PublishRevision, RepublishPageParams, Space,
+  SpacePermission, ViewIcon, ViewLayout,
 };
 use sqlx::PgPool;
 use std::collections::{HashMap, HashSet};
@@ -63,7 +71,9 @@ use tokio::time::timeout_at;
 use tracing::instrument;
 use uuid::Uuid;
 use workspace_template::document::parser::JsonToDocumentParser;
+use yrs::ReadTxn;
 
+use super::ops::purge_collabs;
 use super::publish::PublishedCollabStore;
 
 #[allow(clippy::too_many_arguments)]
@@ -663,7 +673,10 @@ async fn delete_view_from_trash(view_id: &str, folder: &mut Folder) -> Result<Ve
   Ok(encoded_update)
 }
 
-async fn delete_all_views_from_trash(folder: &mut Folder) -> Result<Vec<u8>, AppError> {
+/// Returns the ids of the deleted views along with the folder update.
+async fn delete_all_views_from_trash(
+  folder: &mut Folder,
+) -> Result<(Vec<String>, Vec<u8>), AppError> {
   let all_trash_ids: Vec<String> = folder
     .get_all_trash_sections()
     .iter()
@@ -679,11 +692,14 @@ async fn delete_all_views_from_trash(folder: &mut Folder) -> Result<Vec<u8>, App
     {
       op.clear(&mut txn);
     };
-    folder.body.views.delete_views(&mut txn, all_trash_ids);
+    folder
+      .body
+      .views
+      .delete_views(&mut txn, all_trash_ids.clone());
     txn.encode_update_v1()
   };
 
-  Ok(encoded_update)
+  Ok((all_trash_ids, encoded_update))
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -971,6 +987,7 @@ async fn create_chat_page(
   let rag_ids = get_rag_ids(&folder, parent_view_id).await;
   create_chat(
     pg_pool,
+    user.uid,
     CreateChatParams {
       chat_id: view_id.clone(),
       name: name.unwrap_or_default().to_string(),
@@ -1097,11 +1114,14 @@ pub async fn restore_all_pages_from_trash(
   Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn delete_trash(
   appflowy_web_metrics: &AppFlowyWebMetrics,
   server: Data<RealtimeServerAddr>,
   user: RealtimeUser,
+  pg_pool: &PgPool,
   collab_storage: &CollabAccessControlStorage,
+  bucket_storage: &S3BucketStorage,
   workspace_id: Uuid,
   view_id: &str,
 ) -> Result<(), AppError> {
@@ -1111,6 +1131,14 @@ pub async fn delete_trash(
     get_latest_collab_folder(collab_storage, collab_origin, &workspace_id.to_string()).await?;
   let update = delete_view_from_trash(view_id, &mut folder).await?;
   update_workspace_folder_data(appflowy_web_metrics, server, user, workspace_id, update).await?;
+  purge_deleted_views(
+    pg_pool,
+    collab_storage,
+    bucket_storage,
+    &workspace_id,
+    &[view_id.to_string()],
+  )
+  .await;
   Ok(())
 }
 
@@ -1118,18 +1146,53 @@ pub async fn delete_all_pages_from_trash(
   appflowy_web_metrics: &AppFlowyWebMetrics,
   server: Data<RealtimeServerAddr>,
   user: RealtimeUser,
+  pg_pool: &PgPool,
   collab_storage: &CollabAccessControlStorage,
+  bucket_storage: &S3BucketStorage,
   workspace_id: Uuid,
 ) -> Result<(), AppError> {
   let uid = user.uid;
   let collab_origin = GetCollabOrigin::User { uid };
   let mut folder =
     get_latest_collab_folder(collab_storage, collab_origin, &workspace_id.to_string()).await?;
-  let update = delete_all_views_from_trash(&mut folder).await?;
+  let (view_ids, update) = delete_all_views_from_trash(&mut folder).await?;
   update_workspace_folder_data(appflowy_web_metrics, server, user, workspace_id, update).await?;
+  purge_deleted_views(
+    pg_pool,
+    collab_storage,
+    bucket_storage,
+    &workspace_id,
+    &view_ids,
+  )
+  .await;
   Ok(())
 }
 
+/// The views deleted from the trash can't be restored: their collabs are deleted along with their
+/// snapshots, embeddings and attached files, see [purge_collabs]. Failing to purge them doesn't
+/// fail the deletion, they are only left behind.
+async fn purge_deleted_views(
+  pg_pool: &PgPool,
+  collab_storage: &CollabAccessControlStorage,
+  bucket_storage: &S3BucketStorage,
+  workspace_id: &Uuid,
+  view_ids: &[String],
+) {
+  if let Err(err) = purge_collabs(pg_pool, bucket_storage, workspace_id, view_ids).await {
+    tracing::warn!("failed to purge the views deleted from the trash: {}", err);
+    return;
+  }
+  for view_id in view_ids {
+    if let Err(err) = collab_storage.evict_deleted_collab(view_id).await {
+      tracing::warn!(
+        "failed to evict deleted view {} from the cache: {}",
+        view_id,
+        err
+      );
+    }
+  }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn update_page(
   appflowy_web_metrics: &AppFlowyWebMetrics,
@@ -1160,6 +1223,99 @@ pub async fn update_page(
   Ok(())
 }
 
+/// Replace the icon of the view and return the icon it had before.
+pub async fn update_page_icon(
+  appflowy_web_metrics: &AppFlowyWebMetrics,
+  server: Data<RealtimeServerAddr>,
+  user: RealtimeUser,
+  collab_storage: &CollabAccessControlStorage,
+  workspace_id: Uuid,
+  view_id: &str,
+  icon: ViewIcon,
+) -> Result<Option<ViewIcon>, AppError> {
+  let collab_origin = GetCollabOrigin::User { uid: user.uid };
+  let mut folder =
+    get_latest_collab_folder(collab_storage, collab_origin, &workspace_id.to_string()).await?;
+  let view = folder
+    .get_view(view_id)
+    .ok_or(AppError::InvalidFolderView(format!(
+      "View {} not found",
+      view_id
+    )))?;
+  let old_icon = view
+    .icon
+    .as_ref()
+    .map(|icon| to_dto_view_icon(icon.clone()));
+  let folder_update = {
+    let mut txn = folder.collab.transact_mut();
+    folder.body.views.update_view(&mut txn, view_id, |update| {
+      update.set_icon(Some(to_folder_view_icon(icon))).done()
+    });
+    txn.encode_update_v1()
+  };
+  update_workspace_folder_data(
+    appflowy_web_metrics,
+    server,
+    user,
+    workspace_id,
+    folder_update,
+  )
+  .await?;
+  Ok(old_icon)
+}
+
+/// Set a custom cover image on the view and return the cover url it had before, if any.
+pub async fn update_page_cover(
+  appflowy_web_metrics: &AppFlowyWebMetrics,
+  server: Data<RealtimeServerAddr>,
+  user: RealtimeUser,
+  collab_storage: &CollabAccessControlStorage,
+  workspace_id: Uuid,
+  view_id: &str,
+  cover_url: &str,
+) -> Result<Option<String>, AppError> {
+  let collab_origin = GetCollabOrigin::User { uid: user.uid };
+  let mut folder =
+    get_latest_collab_folder(collab_storage, collab_origin, &workspace_id.to_string()).await?;
+  let view = folder
+    .get_view(view_id)
+    .ok_or(AppError::InvalidFolderView(format!(
+      "View {} not found",
+      view_id
+    )))?;
+  let mut extra = view
+    .extra
+    .as_deref()
+    .map(parse_extra_field_as_json)
+    .filter(|extra| extra.is_object())
+    .unwrap_or_else(|| json!({}));
+  let old_cover = extra
+    .get("cover")
+    .and_then(|cover| cover.get("value"))
+    .and_then(|value| value.as_str())
+    .map(|value| value.to_string());
+  extra["cover"] = json!({
+    "type": "custom",
+    "value": cover_url,
+  });
+  let folder_update = {
+    let mut txn = folder.collab.transact_mut();
+    folder.body.views.update_view(&mut txn, view_id, |update| {
+      update.set_extra(&extra.to_string()).done()
+    });
+    txn.encode_update_v1()
+  };
+  update_workspace_folder_data(
+    appflowy_web_metrics,
+    server,
+    user,
+    workspace_id,
+    folder_update,
+  )
+  .await?;
+  Ok(old_cover)
+}
+
 static INVALID_URL_CHARS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[^\w-]").unwrap());
 
 fn replace_invalid_url_chars(input: &str) -> String {
@@ -1196,6 +1352,169 @@ pub async fn publish_page(
   comments_enabled: bool,
   duplicate_enabled: bool,
 ) -> Result<(), AppError> {
+  let generated = generate_publish_data(
+    pg_pool,
+    collab_access_control_storage,
+    uid,
+    workspace_id,
+    view_id,
+    visible_database_view_ids,
+  )
+  .await?;
+  let view_uuid = Uuid::parse_str(view_id).unwrap();
+  publish_collab_store
+    .publish_collabs(
+      vec![PublishCollabItem {
+        meta: PublishCollabMetadata {
+          view_id: view_uuid,
+          publish_name: publish_name
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| generate_publish_name(view_id, &generated.view_name)),
+          metadata: generated.metadata,
+        },
+        data: generated.data,
+        comments_enabled,
+        duplicate_enabled,
+      }],
+      &workspace_id,
+      &user_uuid,
+    )
+    .await?;
+  update_published_collab_source_fingerprint(
+    pg_pool,
+    &workspace_id,
+    &view_uuid,
+    &generated.source_fingerprint,
+  )
+  .await?;
+  Ok(())
+}
+
+/// Captures a new revision of an already published view. Unless `publish_at` is in the future, the
+/// new revision replaces the published one right away. Otherwise it's kept aside until the
+/// appflowy worker swaps it in at `publish_at`.
+#[allow(clippy::too_many_arguments)]
+pub async fn republish_page(
+  pg_pool: &PgPool,
+  collab_access_control_storage: &CollabAccessControlStorage,
+  publish_collab_store: &dyn PublishedCollabStore,
+  uid: i64,
+  user_uuid: Uuid,
+  workspace_id: Uuid,
+  view_id: Uuid,
+  params: RepublishPageParams,
+) -> Result<(), AppError> {
+  let revision = select_published_collab_revision(pg_pool, &workspace_id, &view_id)
+    .await?
+    .ok_or_else(|| AppError::RecordNotFound(format!("View {} is not published", view_id)))?;
+  let generated = generate_publish_data(
+    pg_pool,
+    collab_access_control_storage,
+    uid,
+    workspace_id,
+    &view_id.to_string(),
+    params.visible_database_view_ids,
+  )
+  .await?;
+
+  match params
+    .publish_at
+    .filter(|publish_at| *publish_at > Utc::now())
+  {
+    Some(publish_at) => {
+      upsert_scheduled_publish(
+        pg_pool,
+        &workspace_id,
+        &view_id,
+        uid,
+        &generated.metadata,
+        &generated.data,
+        &generated.source_fingerprint,
+        &publish_at,
+      )
+      .await?;
+    },
+    None => {
+      delete_scheduled_publish(pg_pool, &workspace_id, &view_id).await?;
+      publish_collab_store
+        .publish_collabs(
+          vec![PublishCollabItem {
+            meta: PublishCollabMetadata {
+              view_id,
+              publish_name: revision.publish_name,
+              metadata: generated.metadata,
+            },
+            data: generated.data,
+            comments_enabled: revision.comments_enabled,
+            duplicate_enabled: revision.duplicate_enabled,
+          }],
+          &workspace_id,
+          &user_uuid,
+        )
+        .await?;
+      update_published_collab_source_fingerprint(
+        pg_pool,
+        &workspace_id,
+        &view_id,
+        &generated.source_fingerprint,
+      )
+      .await?;
+    },
+  }
+  Ok(())
+}
+
+pub async fn get_publish_revision(
+  pg_pool: &PgPool,
+  collab_access_control_storage: &CollabAccessControlStorage,
+  uid: i64,
+  workspace_id: Uuid,
+  view_id: Uuid,
+) -> Result<PublishRevision, AppError> {
+  let revision = select_published_collab_revision(pg_pool, &workspace_id, &view_id)
+    .await?
+    .ok_or_else(|| AppError::RecordNotFound(format!("View {} is not published", view_id)))?;
+  let has_unpublished_changes = match &revision.source_fingerprint {
+    Some(published) => {
+      let live = generate_publish_data(
+        pg_pool,
+        collab_access_control_storage,
+        uid,
+        workspace_id,
+        &view_id.to_string(),
+        None,
+      )
+      .await?;
+      Some(live.source_fingerprint != *published)
+    },
+    None => None,
+  };
+  Ok(PublishRevision {
+    etag: revision.revision_etag,
+    published_at: revision.revision_created_at,
+    has_unpublished_changes,
+    scheduled_publish_at: revision.scheduled_publish_at,
+  })
+}
+
+/// The content of a view captured for publishing.
+struct GeneratedPublishData {
+  view_name: String,
+  metadata: serde_json::Value,
+  data: Vec<u8>,
+  /// Digest of the state vectors of the collabs `data` was generated from. Comparing it with the
+  /// digest of the current collabs tells whether the view was edited since.
+  source_fingerprint: String,
+}
+
+async fn generate_publish_data(
+  pg_pool: &PgPool,
+  collab_access_control_storage: &CollabAccessControlStorage,
+  uid: i64,
+  workspace_id: Uuid,
+  view_id: &str,
+  visible_database_view_ids: Option<Vec<String>>,
+) -> Result<GeneratedPublishData, AppError> {
   let folder = get_latest_collab_folder(
     collab_access_control_storage,
     GetCollabOrigin::User { uid },
@@ -1231,7 +1550,7 @@ pub async fn publish_page(
     ancestor_views: vec![],
   };
 
-  let publish_data = match view.layout {
+  let (data, source_fingerprint) = match view.layout {
     collab_folder::ViewLayout::Document => {
       generate_publish_data_for_document(collab_access_control_storage, uid, workspace_id, view_id)
         .await
@@ -1253,25 +1572,12 @@ pub async fn publish_page(
       "AI Chat cannot be published".to_string(),
     )),
   }?;
-  publish_collab_store
-    .publish_collabs(
-      vec![PublishCollabItem {
-        meta: PublishCollabMetadata {
-          view_id: Uuid::parse_str(view_id).unwrap(),
-          publish_name: publish_name
-            .map(|name| name.to_string())
-            .unwrap_or_else(|| generate_publish_name(view_id, &view.name)),
-          metadata: serde_json::value::to_value(metadata).unwrap(),
-        },
-        data: publish_data,
-        comments_enabled,
-        duplicate_enabled,
-      }],
-      &workspace_id,
-      &user_uuid,
-    )
-    .await?;
-  Ok(())
+  Ok(GeneratedPublishData {
+    view_name: view.name.clone(),
+    metadata: serde_json::value::to_value(metadata).unwrap(),
+    data,
+    source_fingerprint,
+  })
 }
 
 async fn generate_publish_data_for_document(
@@ -1279,7 +1585,7 @@ async fn generate_publish_data_for_document(
   uid: i64,
   workspace_id: Uuid,
   view_id: &str,
-) -> Result<Vec<u8>, AppError> {
+) -> Result<(Vec<u8>, String), AppError> {
   let collab = get_latest_collab_encoded(
     collab_access_control_storage,
     GetCollabOrigin::User { uid },
@@ -1288,7 +1594,11 @@ async fn generate_publish_data_for_document(
     CollabType::Document,
   )
   .await?;
-  Ok(collab.doc_state.to_vec())
+  let source_fingerprint = source_fingerprint(vec![(
+    view_id.to_string(),
+    state_vector_hash(&view_id.to_string(), &collab)?,
+  )]);
+  Ok((collab.doc_state.to_vec(), source_fingerprint))
 }
 
 async fn generate_publish_data_for_database(
@@ -1298,7 +1608,7 @@ async fn generate_publish_data_for_database(
   workspace_id: Uuid,
   view_id: &str,
   visible_database_view_ids: Option<Vec<String>>,
-) -> Result<Vec<u8>, AppError> {
+) -> Result<(Vec<u8>, String), AppError> {
   let (_, ws_db) = get_latest_workspace_database(
     collab_storage,
     pg_pool,
@@ -1338,10 +1648,13 @@ async fn generate_publish_data_for_database(
     CollabType::DatabaseRow,
   )
   .await?;
-  let row_data: HashMap<String, Vec<u8>> = encoded_rows
-    .into_iter()
-    .map(|(oid, encoded_collab)| (oid, encoded_collab.doc_state.to_vec()))
-    .collect();
+  let mut state_vector_hashes = Vec::with_capacity(encoded_rows.len() + 1);
+  state_vector_hashes.push((db_oid.clone(), hash_collab_state(&db_collab.transact())));
+  let mut row_data: HashMap<String, Vec<u8>> = HashMap::with_capacity(encoded_rows.len());
+  for (oid, encoded_collab) in encoded_rows {
+    state_vector_hashes.push((oid.clone(), state_vector_hash(&oid, &encoded_collab)?));
+    row_data.insert(oid, encoded_collab.doc_state.to_vec());
+  }
 
   let row_document_ids = row_ids
     .iter()
@@ -1360,10 +1673,13 @@ async fn generate_publish_data_for_database(
     CollabType::Document,
   )
   .await?;
-  let row_document_data: HashMap<String, Vec<u8>> = encoded_row_documents
-    .into_iter()
-    .map(|(oid, encoded_collab)| (oid, encoded_collab.doc_state.to_vec()))
-    .collect();
+  let mut row_document_data: HashMap<String, Vec<u8>> =
+    HashMap::with_capacity(encoded_row_documents.len());
+  for (oid, encoded_collab) in encoded_row_documents {
+    state_vector_hashes.push((oid.clone(), state_vector_hash(&oid, &encoded_collab)?));
+    row_document_data.insert(oid, encoded_collab.doc_state.to_vec());
+  }
+  let source_fingerprint = source_fingerprint(state_vector_hashes);
 
   let data = PublishDatabaseData {
     database_collab: collab_to_doc_state(db_collab, CollabType::Database).await?,
@@ -1372,7 +1688,21 @@ async fn generate_publish_data_for_database(
     visible_database_view_ids: visible_database_view_ids.unwrap_or(vec![view_id.to_string()]),
     database_relations: HashMap::from([(db_oid, view_id.to_string())]),
   };
-  Ok(serde_json::ser::to_vec(&data)?)
+  Ok((serde_json::ser::to_vec(&data)?, source_fingerprint))
+}
+
+/// Digest of the state vector hashes of the collabs a published revision was generated from,
+/// keyed by object id. The digest doesn't depend on the order of the collabs.
+fn source_fingerprint(mut state_vector_hashes: Vec<(String, String)>) -> String {
+  state_vector_hashes.sort_unstable();
+  let mut hasher = Sha256::new();
+  for (oid, hash) in state_vector_hashes {
+    hasher.update(oid.as_bytes());
+    hasher.update(b":");
+    hasher.update(hash.as_bytes());
+    hasher.update(b"\n");
+  }
+  format!("{:x}", hasher.finalize())
 }
 
 pub async fn unpublish_page(
@@ -0,0 +1,389 @@
+use app_error::AppError;
+use database::pg_row::AFWorkspaceProvisioningRow;
+use database::user::select_uid_from_email;
+use database::workspace::{
+  delete_from_workspace, upsert_workspace_ai_tier, upsert_workspace_member_with_txn,
+  upsert_workspace_subscription_plan,
+};
+use database::workspace_provisioning::{
+  finish_workspace_provisioning, insert_workspace_provisioning, select_workspace_provisioning,
+  start_workspace_provisioning, update_workspace_provisioning_progress,
+  update_workspace_provisioning_workspace, ProvisioningState,
+};
+use database_entity::dto::AFRole;
+use shared_entity::dto::billing_dto::SubscriptionPlan;
+use shared_entity::dto::provisioning_dto::{
+  MemberProvisioningResult, ProvisionWorkspaceParams, ProvisionedMember, ProvisionedMemberSpec,
+  ProvisioningStatus, WorkspaceProvisioning,
+};
+use shared_entity::dto::workspace_dto::WorkspaceMemberInvitation;
+use shared_entity::response::AppResponseError;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::biz::workspace::ops::{create_empty_workspace, invite_workspace_members};
+use crate::biz::workspace::publish_dup::duplicate_published_collab_to_workspace;
+use crate::state::AppState;
+
+const MAX_EXTERNAL_ID_LEN: usize = 128;
+
+/// A running provisioning that didn't record any progress for this long is considered dead, and
+/// the next request for it runs it again.
+const STALE_PROVISIONING_SECS: i64 = 600;
+
+/// Creates the workspace right away and adds its members and template in the background. Calling
+/// it again with the same external id returns the same provisioning, and resumes it when its
+/// previous run failed or stopped.
+pub async fn provision_workspace(
+  state: &AppState,
+  user_uuid: Uuid,
+  uid: i64,
+  params: ProvisionWorkspaceParams,
+) -> Result<WorkspaceProvisioning, AppResponseError> {
+  validate_provision_params(&params, &state.config.s3.region)?;
+  let spec = serde_json::to_value(&params)?;
+  let row =
+    match insert_workspace_provisioning(&state.pg_pool, uid, &params.external_id, &spec).await? {
+      Some(row) => row,
+      None => {
+        let row = select_workspace_provisioning(&state.pg_pool, uid, &params.external_id).await?;
+        if row.spec != spec {
+          return Err(
+            AppError::InvalidRequest(format!(
+              "provisioning {} was started with a different spec",
+              params.external_id
+            ))
+            .into(),
+          );
+        }
+        row
+      },
+    };
+
+  let workspace_id = match row.workspace_id {
+    Some(workspace_id) => workspace_id,
+    None => {
+      let workspace = create_empty_workspace(
+        &state.pg_pool,
+        state.workspace_access_control.clone(),
+        &state.collab_access_control_storage,
+        &user_uuid,
+        uid,
+        &params.workspace_name,
+      )
+      .await?;
+      let created = workspace.workspace_id;
+      let workspace_id =
+        update_workspace_provisioning_workspace(&state.pg_pool, uid, &params.external_id, &created)
+          .await?;
+      if workspace_id != created {
+        // a concurrent request for the same provisioning created its workspace first
+        delete_from_workspace(&state.pg_pool, &created).await?;
+      }
+      workspace_id
+    },
+  };
+
+  if start_workspace_provisioning(
+    &state.pg_pool,
+    uid,
+    &params.external_id,
+    STALE_PROVISIONING_SECS,
+  )
+  .await?
+  {
+    let previous = previous_member_results(&row);
+    let template_done = row.template_view_id.is_some();
+    let state = state.clone();
+    tokio::spawn(async move {
+      run_provisioning(
+        &state,
+        user_uuid,
+        uid,
+        workspace_id,
+        params,
+        previous,
+        template_done,
+      )
+      .await;
+    });
+  }
+
+  let row = select_workspace_provisioning(&state.pg_pool, uid, &row.external_id).await?;
+  Ok(to_workspace_provisioning(row))
+}
+
+pub async fn get_workspace_provisioning(
+  state: &AppState,
+  uid: i64,
+  external_id: &str,
+) -> Result<WorkspaceProvisioning, AppError> {
+  let row = select_workspace_provisioning(&state.pg_pool, uid, external_id).await?;
+  Ok(to_workspace_provisioning(row))
+}
+
+fn validate_provision_params(
+  params: &ProvisionWorkspaceParams,
+  deployment_region: &str,
+) -> Result<(), AppError> {
+  if params.external_id.trim().is_empty() || params.external_id.len() > MAX_EXTERNAL_ID_LEN {
+    return Err(AppError::InvalidRequest(format!(
+      "external_id must have between 1 and {} characters",
+      MAX_EXTERNAL_ID_LEN
+    )));
+  }
+  if params.workspace_name.trim().is_empty() {
+    return Err(AppError::InvalidRequest(
+      "workspace_name can't be empty".to_string(),
+    ));
+  }
+  if let Some(region) = &params.region {
+    if region != deployment_region {
+      return Err(AppError::InvalidRequest(format!(
+        "region {} is not available, this deployment stores its data in {}",
+        region, deployment_region
+      )));
+    }
+  }
+  if params
+    .members
+    .iter()
+    .any(|member| member.role == AFRole::Owner)
+  {
+    return Err(AppError::InvalidRequest(
+      "the provisioned members can't be owners of the workspace".to_string(),
+    ));
+  }
+  Ok(())
+}
+
+async fn run_provisioning(
+  state: &AppState,
+  user_uuid: Uuid,
+  uid: i64,
+  workspace_id: Uuid,
+  params: ProvisionWorkspaceParams,
+  previous: Vec<ProvisionedMember>,
+  template_done: bool,
+) {
+  let external_id = params.external_id.as_str();
+  let mut errors = vec![];
+
+  if let Some(plan) = params.plan.clone() {
+    // the AI plans are add-ons, the workspace keeps the plan recorded by the billing service
+    let is_add_on = matches!(plan, SubscriptionPlan::AiMax | SubscriptionPlan::AiLocal);
+    let plan = plan as i16;
+    if let Err(err) = upsert_workspace_ai_tier(&state.pg_pool, &workspace_id, plan).await {
+      errors.push(format!("failed to set the plan: {}", err));
+    }
+    if !is_add_on {
+      if let Err(err) =
+        upsert_workspace_subscription_plan(&state.pg_pool, &workspace_id, plan).await
+      {
+        errors.push(format!("failed to set the plan: {}", err));
+      }
+    }
+  }
+
+  if let Some(view_id) = params.template_view_id.filter(|_| !template_done) {
+    match duplicate_published_collab_to_workspace(
+      &state.pg_pool,
+      state.bucket_client.clone(),
+      state.bucket_storage.clone(),
+      state.collab_access_control_storage.clone(),
+      uid,
+      view_id.to_string(),
+      workspace_id.to_string(),
+      workspace_id.to_string(),
+      Some(external_id.to_string()),
+    )
+    .await
+    {
+      Ok(root_view_id) => {
+        let progress = serde_json::to_value(&previous).unwrap_or_default();
+        if let Err(err) = update_workspace_provisioning_progress(
+          &state.pg_pool,
+          uid,
+          external_id,
+          &progress,
+          Some(&root_view_id),
+        )
+        .await
+        {
+          error!(
+            "failed to save the progress of provisioning {}: {}",
+            external_id, err
+          );
+        }
+      },
+      Err(err) => errors.push(format!("failed to instantiate the template: {}", err)),
+    }
+  }
+
+  let mut members = Vec::with_capacity(params.members.len());
+  for spec in &params.members {
+    let result = match previous.iter().find(|member| member.email == spec.email) {
+      Some(member) if !matches!(member.result, MemberProvisioningResult::Failed { .. }) => {
+        member.result.clone()
+      },
+      _ => provision_member(state, &user_uuid, &workspace_id, spec).await,
+    };
+    if let MemberProvisioningResult::Failed { reason } = &result {
+      warn!(
+        "failed to provision member {} of workspace {}: {}",
+        spec.email, workspace_id, reason
+      );
+    }
+    members.push(ProvisionedMember {
+      email: spec.email.clone(),
+      role: spec.role.clone(),
+      result,
+    });
+    let progress = serde_json::to_value(&members).unwrap_or_default();
+    if let Err(err) =
+      update_workspace_provisioning_progress(&state.pg_pool, uid, external_id, &progress, None)
+        .await
+    {
+      error!(
+        "failed to save the progress of provisioning {}: {}",
+        external_id, err
+      );
+    }
+  }
+
+  let (state_after, error) = if errors.is_empty() {
+    (ProvisioningState::Completed, None)
+  } else {
+    (ProvisioningState::Failed, Some(errors.join("; ")))
+  };
+  if let Err(err) = finish_workspace_provisioning(
+    &state.pg_pool,
+    uid,
+    external_id,
+    state_after,
+    error.as_deref(),
+  )
+  .await
+  {
+    error!("failed to finish provisioning {}: {}", external_id, err);
+    return;
+  }
+  info!(
+    "provisioning {} of workspace {} finished: {:?}",
+    external_id, workspace_id, state_after
+  );
+}
+
+/// Users with an account join the workspace directly, the others are invited by email.
+async fn provision_member(
+  state: &AppState,
+  user_uuid: &Uuid,
+  workspace_id: &Uuid,
+  spec: &ProvisionedMemberSpec,
+) -> MemberProvisioningResult {
+  let result = match select_uid_from_email(&state.pg_pool, &spec.email).await {
+    Ok(member_uid) => add_existing_member(state, workspace_id, member_uid, spec)
+      .await
+      .map(|_| MemberProvisioningResult::Joined),
+    Err(AppError::RecordNotFound(_)) => invite_workspace_members(
+      &state.mailer,
+      &state.gotrue_admin,
+      &state.pg_pool,
+      &state.gotrue_client,
+      user_uuid,
+      workspace_id,
+      vec![WorkspaceMemberInvitation {
+        email: spec.email.clone(),
+        role: spec.role.clone(),
+        skip_email_send: false,
+        wait_email_send: true,
+      }],
+      state.config.workspace_invitation.expires_in_secs(),
+      state.config.appflowy_web_url.as_deref(),
+      &state.config.admin_frontend_path_prefix,
+    )
+    .await
+    .map(|_| MemberProvisioningResult::Invited),
+    Err(err) => Err(err),
+  };
+  result.unwrap_or_else(|err| MemberProvisioningResult::Failed {
+    reason: err.to_string(),
+  })
+}
+
+async fn add_existing_member(
+  state: &AppState,
+  workspace_id: &Uuid,
+  member_uid: i64,
+  spec: &ProvisionedMemberSpec,
+) -> Result<(), AppError> {
+  let mut txn = state.pg_pool.begin().await?;
+  upsert_workspace_member_with_txn(&mut txn, workspace_id, &spec.email, spec.role.clone()).await?;
+  state
+    .workspace_access_control
+    .insert_role(&member_uid, workspace_id, spec.role.clone())
+    .await?;
+  txn.commit().await?;
+  Ok(())
+}
+
+fn previous_member_results(row: &AFWorkspaceProvisioningRow) -> Vec<ProvisionedMember> {
+  serde_json::from_value(row.members.clone()).unwrap_or_default()
+}
+
+fn to_workspace_provisioning(row: AFWorkspaceProvisioningRow) -> WorkspaceProvisioning {
+  let members = previous_member_results(&row);
+  let status = match ProvisioningState::from(row.status) {
+    ProvisioningState::Pending => ProvisioningStatus::Pending,
+    ProvisioningState::Running => ProvisioningStatus::Running,
+    ProvisioningState::Completed => ProvisioningStatus::Completed,
+    ProvisioningState::Failed => ProvisioningStatus::Failed,
+  };
+  WorkspaceProvisioning {
+    external_id: row.external_id,
+    workspace_id: row.workspace_id,
+    status,
+    members,
+    template_view_id: row.template_view_id,
+    error: row.error,
+    created_at: row.created_at,
+    updated_at: row.updated_at,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn params() -> ProvisionWorkspaceParams {
+    ProvisionWorkspaceParams {
+      external_id: "acme-onboarding".to_string(),
+      workspace_name: "Acme".to_string(),
+      region: None,
+      plan: None,
+      members: vec![ProvisionedMemberSpec {
+        email: "ops@acme.com".to_string(),
+        role: AFRole::Member,
+      }],
+      template_view_id: None,
+    }
+  }
+
+  #[test]
+  fn validate_provision_params_test() {
+    assert!(validate_provision_params(&params(), "us-east-1").is_ok());
+
+    let mut in_region = params();
+    in_region.region = Some("us-east-1".to_string());
+    assert!(validate_provision_params(&in_region, "us-east-1").is_ok());
+    assert!(validate_provision_params(&in_region, "eu-west-1").is_err());
+
+    let mut no_external_id = params();
+    no_external_id.external_id = " ".to_string();
+    assert!(validate_provision_params(&no_external_id, "us-east-1").is_err());
+
+    let mut owner = params();
+    owner.members[0].role = AFRole::Owner;
+    assert!(validate_provision_params(&owner, "us-east-1").is_err());
+  }
+}
@@ -1,6 +1,7 @@
 use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
 use database::{
   collab::GetCollabOrigin,
+  moderation::select_taken_down_view_ids,
   publish::{
     insert_non_orginal_workspace_publish_namespace, select_all_published_collab_info,
     select_default_published_view_id, select_default_published_view_id_for_namespace,
@@ -11,6 +12,7 @@ use database::{
   workspace::{select_publish_name_exists, select_view_id_from_publish_name},
 };
 use database_entity::dto::PatchPublishedCollab;
+use std::ops::DerefMut;
 use std::sync::Arc;
 
 use app_error::AppError;
@@ -27,6 +29,7 @@ use uuid::Uuid;
 
 use database::{
   file::{s3_client_impl::AwsS3BucketClientImpl, BucketClient, ResponseBlob},
+  outbox::PublishCdnEvent,
   publish::{
     insert_or_replace_publish_collabs, select_publish_collab_meta, select_published_collab_blob,
     select_published_collab_info, select_published_collab_workspace_view_id,
@@ -42,6 +45,8 @@ use crate::{
   biz::collab::{folder_view::to_dto_folder_view_miminal, utils::get_latest_collab_folder},
 };
 
+use super::publish_cdn::queue_cdn_purge;
+
 async fn check_workspace_owner_or_publisher(
   pg_pool: &PgPool,
   user_uuid: &Uuid,
@@ -323,6 +328,7 @@ impl PublishedCollabStore for PublishedCollabPostgresStore {
     workspace_id: &Uuid,
     user_uuid: &Uuid,
   ) -> Result<(), AppError> {
+    check_views_not_taken_down(&self.pg_pool, &publish_items).await?;
     for publish_item in &publish_items {
       check_collab_publish_name(publish_item.meta.publish_name.as_str())?;
       check_view_id_publish_name_conflict(
@@ -334,9 +340,18 @@ impl PublishedCollabStore for PublishedCollabPostgresStore {
       .await?;
     }
     let publish_items_batch_size = publish_items.len() as i64;
-    let result =
-      insert_or_replace_publish_collabs(&self.pg_pool, workspace_id, user_uuid, publish_items)
-        .await;
+    let view_ids = publish_items
+      .iter()
+      .map(|item| item.meta.view_id)
+      .collect::<Vec<_>>();
+    let result = publish_and_purge(
+      &self.pg_pool,
+      workspace_id,
+      user_uuid,
+      publish_items,
+      view_ids,
+    )
+    .await;
     if result.is_err() {
       self
         .metrics
@@ -410,8 +425,7 @@ impl PublishedCollabStore for PublishedCollabPostgresStore {
     user_uuid: &Uuid,
   ) -> Result<(), AppError> {
     check_workspace_owner_or_publisher(&self.pg_pool, user_uuid, workspace_id, view_ids).await?;
-    set_published_collabs_as_unpublished(&self.pg_pool, workspace_id, view_ids).await?;
-    Ok(())
+    unpublish_and_purge(&self.pg_pool, workspace_id, view_ids).await
   }
 
   async fn patch_collabs(
@@ -454,6 +468,7 @@ impl PublishedCollabStore for PublishedCollabS3StoreWithPostgresFallback {
   ) -> Result<(), AppError> {
     let publish_items_batch_size = publish_items.len() as i64;
     let mut handles: Vec<tokio::task::JoinHandle<()>> = vec![];
+    check_views_not_taken_down(&self.pg_pool, &publish_items).await?;
     for publish_item in &publish_items {
       check_collab_publish_name(publish_item.meta.publish_name.as_str())?;
       check_view_id_publish_name_conflict(
@@ -483,9 +498,18 @@ impl PublishedCollabStore for PublishedCollabS3StoreWithPostgresFallback {
       handle.await?;
     }
 
-    let result =
-      insert_or_replace_publish_collabs(&self.pg_pool, workspace_id, user_uuid, publish_items)
-        .await;
+    let view_ids = publish_items
+      .iter()
+      .map(|item| item.meta.view_id)
+      .collect::<Vec<_>>();
+    let result = publish_and_purge(
+      &self.pg_pool,
+      workspace_id,
+      user_uuid,
+      publish_items,
+      view_ids,
+    )
+    .await;
     if result.is_err() {
       self
         .metrics
@@ -601,8 +625,7 @@ impl PublishedCollabStore for PublishedCollabS3StoreWithPostgresFallback {
       .map(|view_id| get_collab_s3_key(workspace_id, view_id))
       .collect::<Vec<String>>();
     self.bucket_client.delete_blobs(object_keys).await?;
-    set_published_collabs_as_unpublished(&self.pg_pool, workspace_id, view_ids).await?;
-    Ok(())
+    unpublish_and_purge(&self.pg_pool, workspace_id, view_ids).await
   }
 
   async fn patch_collabs(
@@ -615,6 +638,39 @@ impl PublishedCollabStore for PublishedCollabS3StoreWithPostgresFallback {
   }
 }
 
+/// Publishes the views and records the purge of their entry points in the same transaction.
+async fn publish_and_purge(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  user_uuid: &Uuid,
+  publish_items: Vec<PublishCollabItem<serde_json::Value, Vec<u8>>>,
+  view_ids: Vec<Uuid>,
+) -> Result<(), AppError> {
+  let mut txn = pg_pool.begin().await?;
+  insert_or_replace_publish_collabs(&mut txn, workspace_id, user_uuid, publish_items).await?;
+  queue_cdn_purge(&mut txn, PublishCdnEvent::Publish, *workspace_id, view_ids).await?;
+  txn.commit().await?;
+  Ok(())
+}
+
+async fn unpublish_and_purge(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  view_ids: &[Uuid],
+) -> Result<(), AppError> {
+  let mut txn = pg_pool.begin().await?;
+  set_published_collabs_as_unpublished(txn.deref_mut(), workspace_id, view_ids).await?;
+  queue_cdn_purge(
+    &mut txn,
+    PublishCdnEvent::Unpublish,
+    *workspace_id,
+    view_ids.to_vec(),
+  )
+  .await?;
+  txn.commit().await?;
+  Ok(())
+}
+
 async fn patch_collabs(
   pg_pool: &PgPool,
   workspace_id: &Uuid,
@@ -655,6 +711,22 @@ async fn check_publish_name_already_exists(
   Ok(())
 }
 
+/// Views taken down by a moderator can only be published again once an admin lifts the takedown.
+async fn check_views_not_taken_down(
+  pg_pool: &PgPool,
+  publish_items: &[PublishCollabItem<serde_json::Value, Vec<u8>>],
+) -> Result<(), AppError> {
+  let view_ids: Vec<Uuid> = publish_items.iter().map(|item| item.meta.view_id).collect();
+  let taken_down = select_taken_down_view_ids(pg_pool, &view_ids).await?;
+  if !taken_down.is_empty() {
+    return Err(AppError::PublishTakenDown(format!(
+      "The views {:?} were taken down and can not be published",
+      taken_down
+    )));
+  }
+  Ok(())
+}
+
 /// Check if the `publish_name` already exists on another view
 async fn check_view_id_publish_name_conflict(
   pg_pool: &PgPool,
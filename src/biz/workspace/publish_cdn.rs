@@ -0,0 +1,30 @@
+use app_error::AppError;
+use database::outbox::{insert_outbox_entry, CdnPurgeIntent, OutboxTopic, PublishCdnEvent};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+/// Keeps the CDN in front of the published views up to date. The revision-addressed URLs never
+/// change, only the entry points, which return the latest revision, have to be purged when a view
+/// is published again or unpublished.
+///
+/// The purge is recorded in the transaction publishing or unpublishing the views, and sent by the
+/// appflowy worker, which also deletes the revisions past their grace period. The revision
+/// published by the worker once a scheduled publish is due isn't purged, the entry points only
+/// being cached for a short time.
+pub async fn queue_cdn_purge(
+  txn: &mut Transaction<'_, Postgres>,
+  event: PublishCdnEvent,
+  workspace_id: Uuid,
+  view_ids: Vec<Uuid>,
+) -> Result<(), AppError> {
+  if view_ids.is_empty() {
+    return Ok(());
+  }
+  let intent = CdnPurgeIntent {
+    event,
+    workspace_id,
+    view_ids,
+  };
+  insert_outbox_entry(txn, OutboxTopic::CdnPurge, &intent).await?;
+  Ok(())
+}
@@ -3,7 +3,6 @@ use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
 
 use anyhow::anyhow;
 use bytes::Bytes;
-use collab_database::database::gen_row_id;
 use collab_database::database::DatabaseBody;
 use collab_database::entity::FieldType;
 use collab_database::rows::meta_id_from_row_id;
@@ -19,12 +18,13 @@ use collab_entity::CollabType;
 use collab_folder::{CollabOrigin, Folder, RepeatedViewIdentifier, View};
 use database::collab::GetCollabOrigin;
 use database::collab::{select_workspace_database_oid, CollabStorage};
-use database::file::s3_client_impl::AwsS3BucketClientImpl;
+use database::file::s3_client_impl::{AwsS3BucketClientImpl, S3BucketStorage};
 use database::file::BucketClient;
 use database::file::ResponseBlob;
 use database::publish::select_published_data_for_view_id;
 use database::publish::select_published_metadata_for_view_id;
 use database_entity::dto::CollabParams;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use shared_entity::dto::publish_dto::{PublishDatabaseData, PublishViewInfo, PublishViewMetaData};
 use shared_entity::dto::workspace_dto::ViewLayout;
 use sqlx::PgPool;
@@ -36,7 +36,8 @@ use crate::biz::collab::folder_view::to_folder_view_icon;
 use crate::biz::collab::folder_view::to_folder_view_layout;
 use crate::biz::collab::utils::collab_from_doc_state;
 use tracing::error;
-use workspace_template::gen_view_id;
+use uuid::Uuid;
+use workspace_template::ObjectIdDeriver;
 use yrs::Any;
 use yrs::Array;
 use yrs::ArrayRef;
@@ -53,19 +54,27 @@ use super::ops::broadcast_update_with_timeout;
 pub async fn duplicate_published_collab_to_workspace(
   pg_pool: &PgPool,
   bucket_client: AwsS3BucketClientImpl,
+  bucket_storage: Arc<S3BucketStorage>,
   collab_storage: Arc<CollabAccessControlStorage>,
   dest_uid: i64,
   publish_view_id: String,
   dest_workspace_id: String,
   dest_view_id: String,
+  nonce: Option<String>,
 ) -> Result<String, AppError> {
+  let id_deriver = match nonce {
+    Some(nonce) => ObjectIdDeriver::new(&dest_workspace_id, nonce),
+    None => ObjectIdDeriver::with_random_nonce(&dest_workspace_id),
+  };
   let copier = PublishCollabDuplicator::new(
     pg_pool.clone(),
     bucket_client,
+    bucket_storage,
     collab_storage.clone(),
     dest_uid,
     dest_workspace_id,
     dest_view_id,
+    id_deriver,
   );
 
   let time_now = chrono::Utc::now().timestamp_millis();
@@ -105,22 +114,29 @@ pub struct PublishCollabDuplicator {
   pg_pool: PgPool,
   /// for fetching published data from s3
   bucket_client: AwsS3BucketClientImpl,
+  /// for copying the files of the published documents to the dest workspace
+  bucket_storage: Arc<S3BucketStorage>,
   /// user initiating the duplication
   duplicator_uid: i64,
   /// workspace to duplicate into
   dest_workspace_id: String,
   /// view of workspace to duplicate into
   dest_view_id: String,
+  /// derives the ids of the duplicated objects from the published ones
+  id_deriver: ObjectIdDeriver,
 }
 
 impl PublishCollabDuplicator {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     pg_pool: PgPool,
     bucket_client: AwsS3BucketClientImpl,
+    bucket_storage: Arc<S3BucketStorage>,
     collab_storage: Arc<CollabAccessControlStorage>,
     dest_uid: i64,
     dest_workspace_id: String,
     dest_view_id: String,
+    id_deriver: ObjectIdDeriver,
   ) -> Self {
     let ts_now = chrono::Utc::now().timestamp();
     Self {
@@ -134,17 +150,19 @@ impl PublishCollabDuplicator {
       duplicated_db_row: HashMap::new(),
       pg_pool,
       bucket_client,
+      bucket_storage,
       collab_storage,
       duplicator_uid: dest_uid,
       dest_workspace_id,
       dest_view_id,
+      id_deriver,
     }
   }
 
   async fn duplicate(mut self, publish_view_id: &str) -> Result<String, AppError> {
     // new view after deep copy
     // this is the root of the document/database duplicated
-    let root_view_id = gen_view_id();
+    let root_view_id = self.id_deriver.derive(publish_view_id);
     let mut root_view = match self
       .deep_copy(root_view_id.clone(), publish_view_id)
       .await?
@@ -171,9 +189,11 @@ impl PublishCollabDuplicator {
       ts_now: _,
       pg_pool,
       bucket_client: _,
+      bucket_storage: _,
       duplicator_uid,
       dest_workspace_id,
       dest_view_id,
+      id_deriver: _,
     } = self;
 
     // insert all collab object accumulated
@@ -220,8 +240,15 @@ impl PublishCollabDuplicator {
       })?;
       let (ws_db_updates, updated_ws_w_db_collab) = tokio::task::spawn_blocking(move || {
         let ws_db_updates = {
+          // databases added by a previous attempt of the same duplication are kept as is
+          let existing_database_ids = ws_db
+            .get_all_database_meta()
+            .into_iter()
+            .map(|db_meta| db_meta.database_id)
+            .collect::<HashSet<_>>();
           let view_ids_by_database_id = workspace_databases
             .into_iter()
+            .filter(|(database_id, _)| !existing_database_ids.contains(database_id))
             .map(|(database_id, view_ids)| (database_id, view_ids.into_iter().collect()))
             .collect::<HashMap<_, _>>();
 
@@ -256,7 +283,13 @@ impl PublishCollabDuplicator {
           "duplicate workspace database collab",
         )
         .await?;
-      broadcast_update(&collab_storage, &ws_db_oid, ws_db_updates).await?;
+      broadcast_update(
+        &collab_storage,
+        &dest_workspace_id,
+        &ws_db_oid,
+        ws_db_updates,
+      )
+      .await?;
     }
 
     let collab_folder_encoded = get_latest_collab_encoded(
@@ -290,7 +323,16 @@ impl PublishCollabDuplicator {
         let mut duplicated_view_ids = HashSet::new();
         duplicated_view_ids.insert(root_view.id.clone());
         duplicated_view_ids.insert(dest_view_id);
-        folder.body.views.insert(&mut folder_txn, root_view, None);
+        // views inserted by a previous attempt of the same duplication are not inserted again,
+        // otherwise they would be listed twice in the children of their parent
+        if folder
+          .body
+          .views
+          .get_view(&folder_txn, &root_view.id)
+          .is_none()
+        {
+          folder.body.views.insert(&mut folder_txn, root_view, None);
+        }
 
         // when child views are added, it must have a parent view that is previously added
         // TODO: if there are too many child views, consider using topological sort
@@ -305,10 +347,12 @@ impl PublishCollabDuplicator {
             // or if view is standalone (view_id == parent_view_id)
             if duplicated_view_ids.contains(&view.parent_view_id) || *view_id == view.parent_view_id
             {
-              folder
-                .body
-                .views
-                .insert(&mut folder_txn, view.clone(), None);
+              if folder.body.views.get_view(&folder_txn, view_id).is_none() {
+                folder
+                  .body
+                  .views
+                  .insert(&mut folder_txn, view.clone(), None);
+              }
               duplicated_view_ids.insert(view_id.clone());
               inserted.push(view_id.clone());
             }
@@ -364,6 +408,7 @@ impl PublishCollabDuplicator {
     // broadcast folder changes
     tokio::spawn(broadcast_update_with_timeout(
       collab_storage,
+      dest_workspace_id.clone(),
       dest_workspace_id,
       encoded_update,
     ));
@@ -386,7 +431,7 @@ impl PublishCollabDuplicator {
     );
 
     // attempt to get metadata and doc_state for published view
-    let (metadata, published_blob) = match self
+    let (published_workspace_id, metadata, published_blob) = match self
       .get_published_data_for_view_id(&publish_view_id.parse()?)
       .await?
     {
@@ -411,7 +456,13 @@ impl PublishCollabDuplicator {
         let doc_collab = collab_from_doc_state(published_blob, "")?;
         let doc = Document::open(doc_collab).map_err(|e| AppError::Unhandled(e.to_string()))?;
         let new_doc_view = self
-          .deep_copy_doc(publish_view_id, new_view_id, doc, metadata)
+          .deep_copy_doc(
+            &published_workspace_id,
+            publish_view_id,
+            new_view_id,
+            doc,
+            metadata,
+          )
           .await?;
         Ok(Some(new_doc_view))
       },
@@ -432,6 +483,7 @@ impl PublishCollabDuplicator {
 
   async fn deep_copy_doc<'a>(
     &mut self,
+    pub_workspace_id: &Uuid,
     pub_view_id: &str,
     dup_view_id: String,
     doc: Document,
@@ -455,6 +507,13 @@ impl PublishCollabDuplicator {
       tracing::error!("failed to deep copy doc databases: {}", err);
     };
 
+    if let Err(err) = self
+      .deep_copy_doc_blobs(pub_workspace_id, &dup_view_id, &mut doc_data)
+      .await
+    {
+      tracing::error!("failed to deep copy doc files: {}", err);
+    }
+
     {
       // write modified doc_data back to storage
       let empty_collab = collab_from_doc_state(vec![], &dup_view_id)?;
@@ -540,7 +599,9 @@ impl PublishCollabDuplicator {
       },
       None => {
         // Call deep_copy and await the result
-        if let Some(mut new_view) = Box::pin(self.deep_copy(gen_view_id(), pub_view_id)).await? {
+        if let Some(mut new_view) =
+          Box::pin(self.deep_copy(self.id_deriver.derive(pub_view_id), pub_view_id)).await?
+        {
           if new_view.parent_view_id.is_empty() {
             new_view.parent_view_id.clone_from(parent_view_id);
           }
@@ -559,6 +620,61 @@ impl PublishCollabDuplicator {
     }
   }
 
+  /// Copies the files of the published document, such as its images, to the dest workspace and
+  /// points the blocks at the copies, so that they keep working once the published workspace
+  /// deletes them or when the dest workspace members can't access it. The blocks whose file
+  /// couldn't be copied keep pointing at the published workspace.
+  async fn deep_copy_doc_blobs(
+    &self,
+    pub_workspace_id: &Uuid,
+    dup_view_id: &str,
+    doc_data: &mut DocumentData,
+  ) -> Result<(), AppError> {
+    let file_ids = doc_data
+      .blocks
+      .values()
+      .filter_map(|block| block.data.get("url")?.as_str())
+      .filter_map(parse_blob_url)
+      .filter(|blob_url| blob_url.workspace_id == *pub_workspace_id)
+      .map(|blob_url| blob_url.file_id)
+      .collect::<HashSet<_>>()
+      .into_iter()
+      .collect::<Vec<_>>();
+    if file_ids.is_empty() {
+      return Ok(());
+    }
+
+    let dest_workspace_id: Uuid = self.dest_workspace_id.parse()?;
+    let copied_file_ids = self
+      .bucket_storage
+      .copy_blobs(
+        pub_workspace_id,
+        &dest_workspace_id,
+        dup_view_id,
+        &file_ids,
+        Some(self.duplicator_uid),
+      )
+      .await?;
+    for block in doc_data.blocks.values_mut() {
+      let new_url = block
+        .data
+        .get("url")
+        .and_then(|url| url.as_str())
+        .and_then(parse_blob_url)
+        .filter(|blob_url| blob_url.workspace_id == *pub_workspace_id)
+        .and_then(|blob_url| {
+          let new_file_id = copied_file_ids.get(&blob_url.file_id)?;
+          Some(blob_url.copied_url(&dest_workspace_id, dup_view_id, new_file_id))
+        });
+      if let Some(new_url) = new_url {
+        block
+          .data
+          .insert("url".to_string(), serde_json::Value::String(new_url));
+      }
+    }
+    Ok(())
+  }
+
   async fn deep_copy_doc_databases(
     &mut self,
     pub_view_id: &str,
@@ -634,7 +750,7 @@ impl PublishCollabDuplicator {
     view_id: &str,
     doc_view_id: &String,
   ) -> Result<Option<String>, AppError> {
-    let (metadata, published_blob) = match self
+    let (_, metadata, published_blob) = match self
       .get_published_data_for_view_id(&view_id.parse()?)
       .await?
     {
@@ -647,7 +763,12 @@ impl PublishCollabDuplicator {
 
     let published_db = serde_json::from_slice::<PublishDatabaseData>(&published_blob)?;
     let mut parent_view = self
-      .deep_copy_database_view(gen_view_id(), published_db, &metadata, view_id)
+      .deep_copy_database_view(
+        self.id_deriver.derive(view_id),
+        published_db,
+        &metadata,
+        view_id,
+      )
       .await?;
     let parent_view_id = parent_view.id.clone();
     if parent_view.parent_view_id.is_empty() {
@@ -667,7 +788,7 @@ impl PublishCollabDuplicator {
     parent_id: &str,
     doc_view_id: &String,
   ) -> Result<Option<(String, String)>, AppError> {
-    let (metadata, published_blob) = match self
+    let (_, metadata, published_blob) = match self
       .get_published_data_for_view_id(&view_id.parse()?)
       .await?
     {
@@ -680,7 +801,12 @@ impl PublishCollabDuplicator {
 
     let published_db = serde_json::from_slice::<PublishDatabaseData>(&published_blob)?;
     let mut parent_view = self
-      .deep_copy_database_view(gen_view_id(), published_db, &metadata, parent_id)
+      .deep_copy_database_view(
+        self.id_deriver.derive(parent_id),
+        published_db,
+        &metadata,
+        parent_id,
+      )
       .await?;
     let parent_view_id = parent_view.id.clone();
     if parent_view.parent_view_id.is_empty() {
@@ -736,7 +862,7 @@ impl PublishCollabDuplicator {
     if let Some(db_id) = self.duplicated_refs.get(&pub_db_id).cloned().flatten() {
       return Ok((pub_db_id, db_id, true));
     }
-    let new_db_id = gen_view_id();
+    let new_db_id = self.id_deriver.derive(&pub_db_id);
     self
       .duplicated_refs
       .insert(pub_db_id.clone(), Some(new_db_id.clone()));
@@ -754,7 +880,7 @@ impl PublishCollabDuplicator {
             .insert(pub_db_id.clone(), new_view_id.clone());
           new_view_id.clone()
         } else {
-          gen_view_id()
+          self.id_deriver.derive(&db_view.id)
         };
         self
           .duplicated_db_view
@@ -779,10 +905,10 @@ impl PublishCollabDuplicator {
     // this will mark the rows as duplicated
     for pub_row_id in published_db.database_row_collabs.keys() {
       // assign a new id for the row
-      let dup_row_id = gen_row_id();
+      let dup_row_id = self.id_deriver.derive(pub_row_id);
       self
         .duplicated_db_row
-        .insert(pub_row_id.clone(), dup_row_id.to_string());
+        .insert(pub_row_id.clone(), dup_row_id);
     }
 
     {
@@ -1129,21 +1255,22 @@ impl PublishCollabDuplicator {
     }
   }
 
+  /// Returns the workspace the view is published from, along with its published data.
   async fn get_published_data_for_view_id(
     &self,
     view_id: &uuid::Uuid,
-  ) -> Result<Option<(PublishViewMetaData, Vec<u8>)>, AppError> {
+  ) -> Result<Option<(Uuid, PublishViewMetaData, Vec<u8>)>, AppError> {
     let result = select_published_metadata_for_view_id(&self.pg_pool, view_id).await?;
     match result {
       Some((workspace_id, js_val)) => {
         let metadata = serde_json::from_value(js_val)?;
         let object_key = format!("published-collab/{}/{}", workspace_id, view_id);
         match self.bucket_client.get_blob(&object_key).await {
-          Ok(resp) => Ok(Some((metadata, resp.to_blob()))),
+          Ok(resp) => Ok(Some((workspace_id, metadata, resp.to_blob()))),
           Err(_) => match select_published_data_for_view_id(&self.pg_pool, view_id).await? {
             Some((js_val, blob)) => {
               let metadata = serde_json::from_value(js_val)?;
-              Ok(Some((metadata, blob)))
+              Ok(Some((workspace_id, metadata, blob)))
             },
             None => Ok(None),
           },
@@ -1154,6 +1281,59 @@ impl PublishCollabDuplicator {
   }
 }
 
+/// Characters escaped in the path segments of the blob urls.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_');
+
+/// A blob url of the file storage api, either
+/// `{base}/api/file_storage/{workspace_id}/v1/blob/{parent_dir}/{file_id}` or the older
+/// `{base}/api/file_storage/{workspace_id}/blob/{file_id}`.
+#[derive(Debug, PartialEq)]
+struct BlobUrl {
+  /// Everything before `/api/file_storage`
+  base_url: String,
+  workspace_id: Uuid,
+  /// The key of the blob metadata
+  file_id: String,
+}
+
+impl BlobUrl {
+  /// The url of the copy of the blob made by [S3BucketStorage::copy_blobs] into `parent_dir` of
+  /// the workspace.
+  fn copied_url(&self, workspace_id: &Uuid, parent_dir: &str, new_file_id: &str) -> String {
+    let name = new_file_id
+      .strip_prefix(&format!("{}_", parent_dir))
+      .unwrap_or(new_file_id);
+    format!(
+      "{}/api/file_storage/{}/v1/blob/{}/{}",
+      self.base_url,
+      workspace_id,
+      utf8_percent_encode(parent_dir, PATH_SEGMENT),
+      utf8_percent_encode(name, PATH_SEGMENT),
+    )
+  }
+}
+
+fn parse_blob_url(url: &str) -> Option<BlobUrl> {
+  let (base_url, path) = url.split_once("/api/file_storage/")?;
+  let path = path.split(['?', '#']).next()?;
+  let segments = path
+    .split('/')
+    .map(|segment| Some(percent_decode_str(segment).decode_utf8().ok()?.to_string()))
+    .collect::<Option<Vec<_>>>()?;
+  let (workspace_id, file_id) = match segments.as_slice() {
+    [workspace_id, v1, blob, parent_dir, file_id] if v1 == "v1" && blob == "blob" => {
+      (workspace_id, format!("{}_{}", parent_dir, file_id))
+    },
+    [workspace_id, blob, file_id] if blob == "blob" => (workspace_id, file_id.clone()),
+    _ => return None,
+  };
+  Some(BlobUrl {
+    base_url: base_url.to_string(),
+    workspace_id: workspace_id.parse().ok()?,
+    file_id,
+  })
+}
+
 fn view_info_by_view_id(meta: &PublishViewMetaData) -> HashMap<String, PublishViewInfo> {
   let mut acc = HashMap::new();
   acc.insert(meta.view.view_id.clone(), meta.view.clone());
@@ -1170,3 +1350,47 @@ fn add_to_view_info(acc: &mut HashMap<String, PublishViewInfo>, view_infos: &[Pu
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_and_copy_blob_url_test() {
+    let workspace_id = Uuid::new_v4();
+    let url = format!(
+      "https://cloud.appflowy.io/api/file_storage/{}/v1/blob/doc%20id/image.png",
+      workspace_id
+    );
+    let blob_url = parse_blob_url(&url).unwrap();
+    assert_eq!(
+      blob_url,
+      BlobUrl {
+        base_url: "https://cloud.appflowy.io".to_string(),
+        workspace_id,
+        file_id: "doc id_image.png".to_string(),
+      }
+    );
+
+    let url = format!(
+      "https://cloud.appflowy.io/api/file_storage/{}/blob/image.png?v=1",
+      workspace_id
+    );
+    assert_eq!(parse_blob_url(&url).unwrap().file_id, "image.png");
+    assert!(parse_blob_url("https://example.com/image.png").is_none());
+    assert!(parse_blob_url("https://cloud.appflowy.io/api/file_storage/x/blob/a.png").is_none());
+
+    let dest_workspace_id = Uuid::new_v4();
+    let copied_url = blob_url.copied_url(&dest_workspace_id, "dup", "dup_doc id_image.png");
+    assert_eq!(
+      copied_url,
+      format!(
+        "https://cloud.appflowy.io/api/file_storage/{}/v1/blob/dup/doc%20id_image.png",
+        dest_workspace_id
+      )
+    );
+    let parsed = parse_blob_url(&copied_url).unwrap();
+    assert_eq!(parsed.workspace_id, dest_workspace_id);
+    assert_eq!(parsed.file_id, "dup_doc id_image.png");
+  }
+}
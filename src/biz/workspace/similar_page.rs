@@ -0,0 +1,255 @@
+use std::collections::{HashMap, HashSet};
+
+use actix_web::web::Data;
+use app_error::AppError;
+use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
+use chrono::{DateTime, Duration, Utc};
+use collab_folder::Folder;
+use collab_rt_entity::user::RealtimeUser;
+use database::collab::GetCollabOrigin;
+use database::index::select_workspace_collab_fingerprints;
+use database::publish::select_published_view_ids_for_workspace;
+use indexer::fingerprint::similarity;
+use shared_entity::dto::similar_page_dto::{
+  SimilarPage, SimilarPageCluster, SimilarPageClusters, SkippedPage, SkippedPageReason,
+  TrashSimilarPagesParams, TrashedSimilarPages,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::page_view::move_page_to_trash;
+use crate::api::metrics::AppFlowyWebMetrics;
+use crate::api::ws::RealtimeServerAddr;
+use crate::biz::collab::utils::get_latest_collab_folder;
+
+const DEFAULT_THRESHOLD: f32 = 0.9;
+const MIN_THRESHOLD: f32 = 0.75;
+/// Comparing every page with every other one, the number of pages compared is bounded.
+const MAX_COMPARED_PAGES: i64 = 5000;
+const RECENTLY_EDITED_DAYS: i64 = 7;
+
+/// Group the pages of the workspace whose content is nearly the same, from the fingerprints
+/// computed when the pages are indexed. The pages in the trash are left out.
+pub async fn list_similar_pages(
+  pg_pool: &PgPool,
+  collab_storage: &CollabAccessControlStorage,
+  uid: i64,
+  workspace_id: Uuid,
+  threshold: Option<f32>,
+) -> Result<SimilarPageClusters, AppError> {
+  let threshold = threshold
+    .unwrap_or(DEFAULT_THRESHOLD)
+    .clamp(MIN_THRESHOLD, 1.0);
+  let folder = get_latest_collab_folder(
+    collab_storage,
+    GetCollabOrigin::User { uid },
+    &workspace_id.to_string(),
+  )
+  .await?;
+  let trashed = trashed_view_ids(&folder);
+  let published = published_view_ids(pg_pool, workspace_id).await?;
+
+  let pages = select_workspace_collab_fingerprints(pg_pool, &workspace_id, MAX_COMPARED_PAGES)
+    .await?
+    .into_iter()
+    .filter(|row| !trashed.contains(&row.oid))
+    .filter_map(|row| {
+      let view = folder.get_view(&row.oid)?;
+      let page = SimilarPage {
+        view_id: row.oid,
+        name: view.name.clone(),
+        last_edited_time: DateTime::from_timestamp(view.last_edited_time, 0)
+          .unwrap_or(row.updated_at),
+        text_len: row.text_len,
+        is_published: published.contains(&view.id),
+      };
+      Some((row.simhash, page))
+    })
+    .collect::<Vec<_>>();
+
+  let fingerprints = pages
+    .iter()
+    .map(|(simhash, _)| *simhash)
+    .collect::<Vec<_>>();
+  let mut pages = pages.into_iter().map(Some).collect::<Vec<_>>();
+  let clusters = cluster_fingerprints(&fingerprints, threshold)
+    .into_iter()
+    .map(|indices| {
+      let similarity = lowest_similarity(&fingerprints, &indices);
+      let pages = indices
+        .into_iter()
+        .filter_map(|index| pages[index].take().map(|(_, page)| page))
+        .collect();
+      SimilarPageCluster { pages, similarity }
+    })
+    .collect();
+  Ok(SimilarPageClusters { clusters })
+}
+
+/// Move the pages to the trash, skipping the published and the recently edited ones unless
+/// asked otherwise. A page is skipped as well if one of its children, which would be moved to the
+/// trash along with it, would be.
+pub async fn trash_similar_pages(
+  appflowy_web_metrics: &AppFlowyWebMetrics,
+  server: Data<RealtimeServerAddr>,
+  user: RealtimeUser,
+  pg_pool: &PgPool,
+  collab_storage: &CollabAccessControlStorage,
+  workspace_id: Uuid,
+  params: TrashSimilarPagesParams,
+) -> Result<TrashedSimilarPages, AppError> {
+  let folder = get_latest_collab_folder(
+    collab_storage,
+    GetCollabOrigin::User { uid: user.uid },
+    &workspace_id.to_string(),
+  )
+  .await?;
+  let trashed = trashed_view_ids(&folder);
+  let published = published_view_ids(pg_pool, workspace_id).await?;
+  let recently = (Utc::now() - Duration::days(RECENTLY_EDITED_DAYS)).timestamp();
+
+  let mut result = TrashedSimilarPages {
+    trashed: vec![],
+    skipped: vec![],
+  };
+  for view_id in params.view_ids.into_iter().collect::<HashSet<_>>() {
+    let Some(view) = folder.get_view(&view_id) else {
+      result.skipped.push(SkippedPage {
+        view_id,
+        reason: SkippedPageReason::NotFound,
+      });
+      continue;
+    };
+    let mut views = folder.get_views_belong_to(&view_id);
+    views.push(view);
+    let reason = if trashed.contains(&view_id) {
+      Some(SkippedPageReason::AlreadyInTrash)
+    } else if !params.include_published && views.iter().any(|v| published.contains(&v.id)) {
+      Some(SkippedPageReason::Published)
+    } else if !params.include_recently_edited
+      && views.iter().any(|v| v.last_edited_time >= recently)
+    {
+      Some(SkippedPageReason::RecentlyEdited)
+    } else {
+      None
+    };
+    match reason {
+      Some(reason) => result.skipped.push(SkippedPage { view_id, reason }),
+      None => {
+        move_page_to_trash(
+          appflowy_web_metrics,
+          server.clone(),
+          user.clone(),
+          collab_storage,
+          workspace_id,
+          &view_id,
+        )
+        .await?;
+        result.trashed.push(view_id);
+      },
+    }
+  }
+  Ok(result)
+}
+
+/// The views in the trash, along with their children.
+fn trashed_view_ids(folder: &Folder) -> HashSet<String> {
+  let mut view_ids = HashSet::new();
+  for info in folder.get_my_trash_info() {
+    view_ids.extend(
+      folder
+        .get_views_belong_to(&info.id)
+        .iter()
+        .map(|view| view.id.clone()),
+    );
+    view_ids.insert(info.id);
+  }
+  view_ids
+}
+
+async fn published_view_ids(
+  pg_pool: &PgPool,
+  workspace_id: Uuid,
+) -> Result<HashSet<String>, AppError> {
+  Ok(
+    select_published_view_ids_for_workspace(pg_pool, workspace_id)
+      .await?
+      .into_iter()
+      .map(|view_id| view_id.to_string())
+      .collect(),
+  )
+}
+
+/// Group the fingerprints which are at least `threshold` similar to one of the others of their
+/// group. Returns the groups of more than one fingerprint, as indices into `fingerprints`, in
+/// the order of the fingerprints.
+fn cluster_fingerprints(fingerprints: &[i64], threshold: f32) -> Vec<Vec<usize>> {
+  let mut parents = (0..fingerprints.len()).collect::<Vec<_>>();
+  for a in 0..fingerprints.len() {
+    for b in a + 1..fingerprints.len() {
+      if similarity(fingerprints[a], fingerprints[b]) >= threshold {
+        let (root_a, root_b) = (find(&mut parents, a), find(&mut parents, b));
+        if root_a != root_b {
+          parents[root_b.max(root_a)] = root_a.min(root_b);
+        }
+      }
+    }
+  }
+
+  let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+  for index in 0..fingerprints.len() {
+    let root = find(&mut parents, index);
+    clusters.entry(root).or_default().push(index);
+  }
+  let mut clusters = clusters
+    .into_values()
+    .filter(|cluster| cluster.len() > 1)
+    .collect::<Vec<_>>();
+  clusters.sort_by_key(|cluster| cluster[0]);
+  clusters
+}
+
+fn find(parents: &mut [usize], index: usize) -> usize {
+  let mut root = index;
+  while parents[root] != root {
+    root = parents[root];
+  }
+  parents[index] = root;
+  root
+}
+
+fn lowest_similarity(fingerprints: &[i64], indices: &[usize]) -> f32 {
+  let mut lowest = 1.0f32;
+  for (n, a) in indices.iter().enumerate() {
+    for b in &indices[n + 1..] {
+      lowest = lowest.min(similarity(fingerprints[*a], fingerprints[*b]));
+    }
+  }
+  lowest
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn near_duplicates_are_clustered_transitively_test() {
+    let base = 0b1011_0110_i64;
+    let fingerprints = [
+      base,
+      base ^ 0b1,               // 1 bit away from the first
+      0x0f0f_0f0f_0f0f,         // unrelated
+      base ^ (0b11 << 40),      // 2 bits away from the first
+      base ^ 0b1 ^ (0b1 << 20), // 1 bit away from the second
+      !0x0f0f_0f0f_0f0f,        // unrelated to everything
+    ];
+    let clusters = cluster_fingerprints(&fingerprints, 1.0 - 2.0 / 64.0);
+    assert_eq!(clusters, vec![vec![0, 1, 3, 4]]);
+    assert_eq!(
+      lowest_similarity(&fingerprints, &clusters[0]),
+      1.0 - 4.0 / 64.0
+    );
+
+    assert!(cluster_fingerprints(&fingerprints, 1.0).is_empty());
+  }
+}
@@ -0,0 +1,210 @@
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+
+use app_error::AppError;
+use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
+use chrono::{Days, NaiveDate, Utc};
+use collab_entity::CollabType;
+use collab_folder::Folder;
+use database::collab::GetCollabOrigin;
+use database::pg_row::AFWorkspaceStatsRow;
+use database::workspace_stats::{
+  fold_workspace_stats_deltas, insert_workspace_stats_snapshots, recompute_workspace_stats,
+  select_workspace_stats, select_workspace_stats_computed_at, select_workspace_stats_snapshot,
+};
+use infra::env_util::get_env_var;
+use shared_entity::dto::workspace_dto::{
+  CollabTypeStats, FolderDepthCount, WorkspaceStats, WorkspaceStatsDelta,
+};
+use sqlx::PgPool;
+use tracing::{error, info, trace};
+use uuid::Uuid;
+
+use crate::biz::collab::folder_view::private_space_and_trash_view_ids;
+use crate::biz::collab::utils::get_latest_collab_folder;
+
+const FOLD_BATCH_SIZE: i64 = 10_000;
+
+/// The object counts and sizes come from the cached stats, the folder depths from the folder.
+/// The stats of a workspace are computed on its first request.
+pub async fn get_workspace_stats(
+  pg_pool: &PgPool,
+  collab_storage: &CollabAccessControlStorage,
+  workspace_id: Uuid,
+) -> Result<WorkspaceStats, AppError> {
+  let computed_at = match select_workspace_stats_computed_at(pg_pool, &workspace_id).await? {
+    Some(computed_at) => computed_at,
+    None => {
+      recompute_workspace_stats(pg_pool, &workspace_id).await?;
+      select_workspace_stats_computed_at(pg_pool, &workspace_id)
+        .await?
+        .unwrap_or_else(Utc::now)
+    },
+  };
+  let rows = select_workspace_stats(pg_pool, &workspace_id).await?;
+  let week_ago = Utc::now().date_naive() - Days::new(7);
+  let week_over_week = select_workspace_stats_snapshot(pg_pool, &workspace_id, week_ago)
+    .await?
+    .map(|(since, snapshot)| WorkspaceStatsDelta {
+      since,
+      objects: stats_delta(&rows, &snapshot),
+    });
+
+  let folder = get_latest_collab_folder(
+    collab_storage,
+    GetCollabOrigin::Server,
+    &workspace_id.to_string(),
+  )
+  .await?;
+  let depths = folder_depth_distribution(&folder, &workspace_id.to_string());
+  Ok(WorkspaceStats {
+    workspace_id: workspace_id.to_string(),
+    objects: rows.iter().map(collab_type_stats).collect(),
+    max_folder_depth: depths.keys().last().copied().unwrap_or(0),
+    folder_depth: depths
+      .into_iter()
+      .map(|(depth, view_count)| FolderDepthCount { depth, view_count })
+      .collect(),
+    week_over_week,
+    computed_at,
+  })
+}
+
+/// Recomputes the stats of the workspace from scratch, repairing the cached ones.
+pub async fn recompute_stats(pg_pool: &PgPool, workspace_id: Uuid) -> Result<(), AppError> {
+  recompute_workspace_stats(pg_pool, &workspace_id).await?;
+  info!("recomputed the stats of workspace {}", workspace_id);
+  Ok(())
+}
+
+/// Folds the changes recorded when collabs are written into the cached stats, and takes the daily
+/// snapshots used for the week over week changes.
+pub fn spawn_workspace_stats_refresher(pg_pool: PgPool) {
+  let interval = Duration::from_secs(
+    get_env_var("APPFLOWY_WORKSPACE_STATS_REFRESH_INTERVAL_SECS", "30")
+      .parse()
+      .unwrap_or(30),
+  );
+  tokio::spawn(async move {
+    let mut tick = tokio::time::interval(interval);
+    let mut snapshot_date: Option<NaiveDate> = None;
+    loop {
+      tick.tick().await;
+      loop {
+        match fold_workspace_stats_deltas(&pg_pool, FOLD_BATCH_SIZE).await {
+          Ok(folded) => {
+            trace!("folded {} workspace stats changes", folded);
+            if folded < FOLD_BATCH_SIZE as u64 {
+              break;
+            }
+          },
+          Err(err) => {
+            error!("failed to fold workspace stats changes: {}", err);
+            break;
+          },
+        }
+      }
+
+      let today = Utc::now().date_naive();
+      if snapshot_date != Some(today) {
+        match insert_workspace_stats_snapshots(&pg_pool, today).await {
+          Ok(inserted) => {
+            info!("took {} workspace stats snapshots for {}", inserted, today);
+            snapshot_date = Some(today);
+          },
+          Err(err) => error!("failed to take workspace stats snapshots: {}", err),
+        }
+      }
+    }
+  });
+}
+
+fn collab_type_stats(row: &AFWorkspaceStatsRow) -> CollabTypeStats {
+  CollabTypeStats {
+    collab_type: CollabType::from(row.partition_key),
+    object_count: row.object_count,
+    total_bytes: row.total_bytes,
+  }
+}
+
+fn stats_delta(
+  current: &[AFWorkspaceStatsRow],
+  snapshot: &[AFWorkspaceStatsRow],
+) -> Vec<CollabTypeStats> {
+  let mut deltas: BTreeMap<i32, (i64, i64)> = BTreeMap::new();
+  for row in current {
+    let delta = deltas.entry(row.partition_key).or_default();
+    delta.0 += row.object_count;
+    delta.1 += row.total_bytes;
+  }
+  for row in snapshot {
+    let delta = deltas.entry(row.partition_key).or_default();
+    delta.0 -= row.object_count;
+    delta.1 -= row.total_bytes;
+  }
+  deltas
+    .into_iter()
+    .map(
+      |(partition_key, (object_count, total_bytes))| CollabTypeStats {
+        collab_type: CollabType::from(partition_key),
+        object_count,
+        total_bytes,
+      },
+    )
+    .collect()
+}
+
+/// Number of views at each depth below the workspace, skipping the views in the trash.
+fn folder_depth_distribution(folder: &Folder, workspace_id: &str) -> BTreeMap<u32, u64> {
+  let trash = private_space_and_trash_view_ids(folder).view_ids_in_trash;
+  let mut depths = BTreeMap::new();
+  let mut visited = HashSet::new();
+  let mut stack = vec![(workspace_id.to_string(), 0u32)];
+  while let Some((view_id, depth)) = stack.pop() {
+    if !visited.insert(view_id.clone()) || trash.contains(&view_id) {
+      continue;
+    }
+    let view = match folder.get_view(&view_id) {
+      Some(view) => view,
+      None => continue,
+    };
+    if depth > 0 {
+      *depths.entry(depth).or_insert(0) += 1;
+    }
+    for child in view.children.iter() {
+      stack.push((child.id.clone(), depth + 1));
+    }
+  }
+  depths
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn row(partition_key: i32, object_count: i64, total_bytes: i64) -> AFWorkspaceStatsRow {
+    AFWorkspaceStatsRow {
+      partition_key,
+      object_count,
+      total_bytes,
+    }
+  }
+
+  #[test]
+  fn stats_delta_includes_types_missing_on_either_side_test() {
+    let current = vec![row(0, 12, 1200), row(1, 3, 300)];
+    let snapshot = vec![row(0, 10, 1000), row(4, 2, 20)];
+    let deltas = stats_delta(&current, &snapshot)
+      .into_iter()
+      .map(|stats| (stats.collab_type, stats.object_count, stats.total_bytes))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      deltas,
+      vec![
+        (CollabType::Document, 2, 200),
+        (CollabType::Database, 3, 300),
+        (CollabType::DatabaseRow, -2, -20),
+      ]
+    );
+  }
+}
@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+use app_error::AppError;
+use async_stream::stream;
+use bytes::Bytes;
+use chrono::{DateTime, Days, NaiveDate, NaiveTime, Utc};
+use database::pg_row::AFWorkspaceUsageDailyRow;
+use database::workspace_usage::{
+  select_workspace_usage_daily, select_workspace_usage_daily_stream, upsert_workspace_usage_daily,
+};
+use futures_util::{Stream, TryStreamExt};
+use infra::env_util::get_env_var;
+use shared_entity::dto::billing_dto::{WorkspaceUsageDaily, WorkspaceUsageDailyQuery};
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::biz::workspace::member_export::{csv_line, format_time};
+
+/// Longest range of days read or rolled up at once.
+const MAX_USAGE_RANGE_DAYS: u64 = 366;
+
+const USAGE_CSV_COLUMNS: [&str; 10] = [
+  "day",
+  "workspace_id",
+  "workspace_name",
+  "storage_bytes",
+  "ai_search_requests",
+  "ai_tokens",
+  "active_members",
+  "bandwidth_in",
+  "bandwidth_out",
+  "rolled_up_at",
+];
+
+/// Rolls up the usage of the workspaces on each day between `from` and `to`, both included.
+/// Returns the number of rows written.
+pub async fn rollup_workspace_usage(
+  pg_pool: &PgPool,
+  from: NaiveDate,
+  to: NaiveDate,
+) -> Result<u64, AppError> {
+  check_usage_range(from, to)?;
+  let mut rows = 0;
+  for day in from.iter_days().take_while(|day| *day <= to) {
+    let (start, end) = day_bounds(day);
+    rows += upsert_workspace_usage_daily(pg_pool, day, start, end).await?;
+  }
+  Ok(rows)
+}
+
+/// Rolls up the days that ended since the last rollup, once a day. The days before them are
+/// rolled up again, so that the usage recorded late, such as the bandwidth flushed after
+/// midnight, ends up in their rows.
+pub fn spawn_workspace_usage_rollup(pg_pool: PgPool) {
+  let interval = Duration::from_secs(
+    get_env_var("APPFLOWY_USAGE_ROLLUP_INTERVAL_SECS", "3600")
+      .parse()
+      .unwrap_or(3600),
+  );
+  let late_days: u64 = get_env_var("APPFLOWY_USAGE_ROLLUP_LATE_DAYS", "2")
+    .parse()
+    .unwrap_or(2);
+  tokio::spawn(async move {
+    let mut tick = tokio::time::interval(interval);
+    let mut rolled_up_through: Option<NaiveDate> = None;
+    loop {
+      tick.tick().await;
+      let Some(yesterday) = Utc::now().date_naive().pred_opt() else {
+        continue;
+      };
+      if rolled_up_through == Some(yesterday) {
+        continue;
+      }
+      let from = yesterday - Days::new(late_days);
+      match rollup_workspace_usage(&pg_pool, from, yesterday).await {
+        Ok(rows) => {
+          info!(
+            "rolled up {} workspace usage rows from {} to {}",
+            rows, from, yesterday
+          );
+          rolled_up_through = Some(yesterday);
+        },
+        Err(err) => error!("failed to roll up the workspace usage: {}", err),
+      }
+    }
+  });
+}
+
+pub async fn get_workspace_usage_daily(
+  pg_pool: &PgPool,
+  query: &WorkspaceUsageDailyQuery,
+) -> Result<Vec<WorkspaceUsageDaily>, AppError> {
+  check_usage_range(query.from, query.to)?;
+  let rows =
+    select_workspace_usage_daily(pg_pool, query.from, query.to, query.workspace_id).await?;
+  Ok(rows.into_iter().map(to_workspace_usage_daily).collect())
+}
+
+/// Streams the rolled up usage as CSV, one row per workspace and day, ordered by day.
+pub fn export_workspace_usage_csv(
+  pg_pool: PgPool,
+  query: WorkspaceUsageDailyQuery,
+) -> Result<impl Stream<Item = Result<Bytes, AppError>>, AppError> {
+  check_usage_range(query.from, query.to)?;
+  Ok(stream! {
+    yield Ok(Bytes::from(csv_line(&USAGE_CSV_COLUMNS)));
+    let mut rows =
+      select_workspace_usage_daily_stream(&pg_pool, query.from, query.to, query.workspace_id);
+    loop {
+      match rows.try_next().await {
+        Ok(Some(row)) => yield Ok(Bytes::from(usage_csv_line(&row))),
+        Ok(None) => break,
+        Err(err) => {
+          error!("Failed to export the workspace usage: {}", err);
+          yield Err(AppError::from(err));
+          break;
+        },
+      }
+    }
+  })
+}
+
+fn check_usage_range(from: NaiveDate, to: NaiveDate) -> Result<(), AppError> {
+  if from > to {
+    return Err(AppError::InvalidRequest(
+      "from must not be after to".to_string(),
+    ));
+  }
+  if (to - from).num_days() >= MAX_USAGE_RANGE_DAYS as i64 {
+    return Err(AppError::InvalidRequest(format!(
+      "The range must not exceed {} days",
+      MAX_USAGE_RANGE_DAYS
+    )));
+  }
+  Ok(())
+}
+
+/// The start of the day and the start of the next one, in UTC.
+fn day_bounds(day: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+  let start = day.and_time(NaiveTime::MIN).and_utc();
+  (start, start + chrono::Duration::days(1))
+}
+
+fn usage_csv_line(row: &AFWorkspaceUsageDailyRow) -> String {
+  csv_line(&[
+    &row.day.to_string(),
+    &row.workspace_id.to_string(),
+    row.workspace_name.as_deref().unwrap_or_default(),
+    &row.storage_bytes.to_string(),
+    &row.ai_search_requests.to_string(),
+    &row.ai_tokens.to_string(),
+    &row.active_members.to_string(),
+    &row.bandwidth_in.to_string(),
+    &row.bandwidth_out.to_string(),
+    &format_time(&row.rolled_up_at),
+  ])
+}
+
+fn to_workspace_usage_daily(row: AFWorkspaceUsageDailyRow) -> WorkspaceUsageDaily {
+  WorkspaceUsageDaily {
+    workspace_id: row.workspace_id,
+    workspace_name: row.workspace_name,
+    day: row.day,
+    storage_bytes: row.storage_bytes,
+    ai_search_requests: row.ai_search_requests,
+    ai_tokens: row.ai_tokens,
+    active_members: row.active_members,
+    bandwidth_in: row.bandwidth_in,
+    bandwidth_out: row.bandwidth_out,
+    rolled_up_at: row.rolled_up_at,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn day_bounds_test() {
+    let day = NaiveDate::from_ymd_opt(2025, 2, 28).unwrap();
+    let (start, end) = day_bounds(day);
+    assert_eq!(start.to_rfc3339(), "2025-02-28T00:00:00+00:00");
+    assert_eq!(end.to_rfc3339(), "2025-03-01T00:00:00+00:00");
+  }
+
+  #[test]
+  fn usage_range_is_bounded_test() {
+    let day = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+    assert!(check_usage_range(day, day).is_ok());
+    assert!(check_usage_range(day, day + Days::new(365)).is_ok());
+    assert!(check_usage_range(day, day + Days::new(366)).is_err());
+    assert!(check_usage_range(day + Days::new(1), day).is_err());
+  }
+}
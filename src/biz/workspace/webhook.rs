@@ -0,0 +1,267 @@
+use app_error::AppError;
+use chrono::Utc;
+use database::outbox::{insert_outbox_entry, OutboxTopic, WebhookDeliveryIntent};
+use database::pg_row::AFWorkspaceWebhookRow;
+use database::workspace_webhook::{
+  delete_workspace_webhook, insert_workspace_webhook, select_workspace_webhooks,
+};
+use database_entity::dto::AFRole;
+use infra::net_util::resolve_public_addr;
+use nanoid::nanoid;
+use shared_entity::dto::webhook_dto::{
+  CreateWorkspaceWebhookParams, CreatedWorkspaceWebhook, MembershipEvent, WebhookEventType,
+  WebhookIdentity, WorkspaceWebhook,
+};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::ops::DerefMut;
+use url::Url;
+use uuid::Uuid;
+
+const SECRET_LEN: usize = 40;
+
+pub async fn create_workspace_webhook(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  created_by: i64,
+  params: CreateWorkspaceWebhookParams,
+) -> Result<CreatedWorkspaceWebhook, AppError> {
+  let url = Url::parse(params.url.trim())
+    .map_err(|err| AppError::InvalidRequest(format!("Invalid webhook url: {}", err)))?;
+  if url.scheme() != "https" && url.scheme() != "http" {
+    return Err(AppError::InvalidRequest(
+      "Webhook url must be http or https".to_string(),
+    ));
+  }
+  // checked again by the worker at delivery time, as the host may be rebound meanwhile
+  let host = url
+    .host_str()
+    .ok_or_else(|| AppError::InvalidRequest("Webhook url has no host".to_string()))?
+    .trim_start_matches('[')
+    .trim_end_matches(']');
+  let port = url
+    .port_or_known_default()
+    .ok_or_else(|| AppError::InvalidRequest("Webhook url has no port".to_string()))?;
+  resolve_public_addr(host, port)
+    .await
+    .map_err(|err| AppError::InvalidRequest(format!("Invalid webhook url: {}", err)))?;
+  let mut event_types = params
+    .event_types
+    .iter()
+    .map(|event_type| event_type.as_str().to_string())
+    .collect::<Vec<_>>();
+  event_types.sort();
+  event_types.dedup();
+
+  let secret = nanoid!(SECRET_LEN);
+  let row = insert_workspace_webhook(
+    pg_pool,
+    &Uuid::new_v4(),
+    workspace_id,
+    url.as_str(),
+    &secret,
+    &event_types,
+    params.batch,
+    created_by,
+  )
+  .await?;
+  Ok(CreatedWorkspaceWebhook {
+    webhook: to_workspace_webhook(row),
+    secret,
+  })
+}
+
+pub async fn list_workspace_webhooks(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+) -> Result<Vec<WorkspaceWebhook>, AppError> {
+  let rows = select_workspace_webhooks(pg_pool, workspace_id).await?;
+  Ok(rows.into_iter().map(to_workspace_webhook).collect())
+}
+
+pub async fn remove_workspace_webhook(
+  pg_pool: &PgPool,
+  workspace_id: &Uuid,
+  webhook_id: &Uuid,
+) -> Result<(), AppError> {
+  if !delete_workspace_webhook(pg_pool, workspace_id, webhook_id).await? {
+    return Err(AppError::RecordNotFound(format!(
+      "Webhook {} not found in workspace {}",
+      webhook_id, workspace_id
+    )));
+  }
+  Ok(())
+}
+
+fn to_workspace_webhook(row: AFWorkspaceWebhookRow) -> WorkspaceWebhook {
+  WorkspaceWebhook {
+    id: row.id,
+    url: row.url,
+    event_types: row
+      .event_types
+      .iter()
+      .filter_map(|event_type| WebhookEventType::parse(event_type))
+      .collect(),
+    batch: row.batch,
+    created_at: row.created_at,
+  }
+}
+
+/// The membership events of a single change, delivered to the webhooks of the workspace once the
+/// change is committed.
+pub struct MembershipEvents {
+  workspace_id: Uuid,
+  actor: WebhookIdentity,
+  events: Vec<MembershipEvent>,
+}
+
+impl MembershipEvents {
+  pub fn new(workspace_id: Uuid, actor: WebhookIdentity) -> Self {
+    Self {
+      workspace_id,
+      actor,
+      events: vec![],
+    }
+  }
+
+  pub fn push(
+    &mut self,
+    event_type: WebhookEventType,
+    target: WebhookIdentity,
+    role: Option<AFRole>,
+    invitation_id: Option<Uuid>,
+  ) {
+    self.events.push(MembershipEvent {
+      event_type,
+      workspace_id: self.workspace_id,
+      actor: self.actor.clone(),
+      target,
+      role,
+      invitation_id,
+      occurred_at: Utc::now(),
+    });
+  }
+
+  /// Records the deliveries of the events to the webhooks subscribed to them in `txn`. They're
+  /// signed and sent by the appflowy worker, which retries them until the webhook accepts them.
+  pub async fn queue(self, txn: &mut Transaction<'_, Postgres>) -> Result<(), AppError> {
+    if self.events.is_empty() {
+      return Ok(());
+    }
+    let webhooks = select_workspace_webhooks(txn.deref_mut(), &self.workspace_id).await?;
+    for intent in plan_webhook_deliveries(&webhooks, self.events) {
+      insert_outbox_entry(txn, OutboxTopic::WorkspaceWebhook, &intent).await?;
+    }
+    Ok(())
+  }
+}
+
+pub fn identity(uuid: Option<Uuid>, email: Option<String>) -> WebhookIdentity {
+  WebhookIdentity { uuid, email }
+}
+
+/// Splits the events into deliveries: one per webhook subscribed to any of them when it batches,
+/// one per event and webhook otherwise.
+fn plan_webhook_deliveries(
+  webhooks: &[AFWorkspaceWebhookRow],
+  events: Vec<MembershipEvent>,
+) -> Vec<WebhookDeliveryIntent> {
+  let mut deliveries = vec![];
+  for webhook in webhooks {
+    let subscribed = events
+      .iter()
+      .filter(|event| {
+        webhook.event_types.is_empty()
+          || webhook
+            .event_types
+            .iter()
+            .any(|event_type| event_type == event.event_type.as_str())
+      })
+      .cloned()
+      .collect::<Vec<_>>();
+    if subscribed.is_empty() {
+      continue;
+    }
+    if webhook.batch {
+      deliveries.push(WebhookDeliveryIntent {
+        webhook_id: webhook.id,
+        events: subscribed,
+      });
+    } else {
+      deliveries.extend(subscribed.into_iter().map(|event| WebhookDeliveryIntent {
+        webhook_id: webhook.id,
+        events: vec![event],
+      }));
+    }
+  }
+  deliveries
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn webhook(event_types: &[WebhookEventType], batch: bool) -> AFWorkspaceWebhookRow {
+    AFWorkspaceWebhookRow {
+      id: Uuid::new_v4(),
+      workspace_id: Uuid::nil(),
+      url: "https://hr.example.com/appflowy".to_string(),
+      secret: "secret".to_string(),
+      event_types: event_types
+        .iter()
+        .map(|event_type| event_type.as_str().to_string())
+        .collect(),
+      batch,
+      created_by: 1,
+      created_at: Utc::now(),
+    }
+  }
+
+  fn bulk_invite(count: usize) -> Vec<MembershipEvent> {
+    let mut events = MembershipEvents::new(
+      Uuid::nil(),
+      identity(Some(Uuid::new_v4()), Some("owner@example.com".to_string())),
+    );
+    for i in 0..count {
+      events.push(
+        WebhookEventType::InvitationSent,
+        identity(None, Some(format!("user{}@example.com", i))),
+        Some(AFRole::Member),
+        Some(Uuid::new_v4()),
+      );
+    }
+    events.events
+  }
+
+  #[test]
+  fn bulk_invite_is_delivered_in_one_batch_test() {
+    let batching = webhook(&[], true);
+    let deliveries = plan_webhook_deliveries(&[batching.clone()], bulk_invite(25));
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].webhook_id, batching.id);
+    assert_eq!(deliveries[0].events.len(), 25);
+    assert_eq!(
+      deliveries[0].events[0].target.email.as_deref(),
+      Some("user0@example.com")
+    );
+    assert_eq!(
+      deliveries[0].events[0].actor.email.as_deref(),
+      Some("owner@example.com")
+    );
+  }
+
+  #[test]
+  fn bulk_invite_is_delivered_per_address_without_batching_test() {
+    let deliveries = plan_webhook_deliveries(&[webhook(&[], false)], bulk_invite(25));
+    assert_eq!(deliveries.len(), 25);
+    assert!(deliveries.iter().all(|delivery| delivery.events.len() == 1));
+  }
+
+  #[test]
+  fn webhooks_only_receive_the_event_types_they_subscribed_to_test() {
+    let removals = webhook(&[WebhookEventType::MemberRemoved], true);
+    let invites = webhook(&[WebhookEventType::InvitationSent], true);
+    let deliveries = plan_webhook_deliveries(&[removals, invites.clone()], bulk_invite(3));
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].webhook_id, invites.id);
+  }
+}
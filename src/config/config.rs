@@ -1,5 +1,6 @@
 use std::fmt::Display;
 use std::str::FromStr;
+use std::time::Duration;
 
 use anyhow::Context;
 use secrecy::{ExposeSecret, Secret};
@@ -7,6 +8,14 @@ use semver::Version;
 use serde::Deserialize;
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
 
+use appflowy_collaborate::collab::compaction::CollabCompactionConfig;
+use appflowy_collaborate::collab::validator::CollabSizeLimits;
+use appflowy_collaborate::group::full_state::FullStateSyncConfig;
+use appflowy_collaborate::group::unload::GroupUnloadConfig;
+use appflowy_collaborate::group::write_behind::WriteBehindConfig;
+use appflowy_collaborate::load_shed::LoadShedConfig;
+use appflowy_collaborate::rate_limit::UpdateRateLimitConfig;
+use appflowy_collaborate::snapshot::SnapshotPolicies;
 use infra::env_util::{get_env_var, get_env_var_opt};
 use mailer::config::MailerSetting;
 
@@ -21,11 +30,16 @@ pub struct Config {
   pub redis_uri: Secret<String>,
   pub redis_worker_count: usize,
   pub s3: S3Setting,
+  pub file_storage: FileStorageSetting,
   pub appflowy_ai: AppFlowyAISetting,
+  pub ai_scheduler: AISchedulerSetting,
   pub collab: CollabSetting,
   pub published_collab: PublishedCollabSetting,
   pub mailer: MailerSetting,
   pub apple_oauth: AppleOAuthSetting,
+  pub inbound_email: InboundEmailSetting,
+  pub fault_injection: FaultInjectionSetting,
+  pub workspace_invitation: WorkspaceInvitationSetting,
   pub appflowy_web_url: Option<String>,
   pub admin_frontend_path_prefix: String,
 }
@@ -45,6 +59,38 @@ pub struct AppleOAuthSetting {
   pub client_secret: Secret<String>,
 }
 
+/// Emails sent to the inbound address of a workspace are forwarded by the provider to
+/// `/api/inbound-email/{provider}`. Inbound email is disabled when no domain is set.
+#[derive(Clone, Debug)]
+pub struct InboundEmailSetting {
+  pub domain: Option<String>,
+  pub mailgun_signing_key: Option<Secret<String>>,
+  /// Password of the basic auth credentials in the url of the SendGrid inbound parse webhook.
+  pub sendgrid_password: Option<Secret<String>>,
+  /// Maximum size of an email, attachments included. The attachments which don't fit are skipped.
+  pub max_email_size: usize,
+}
+
+/// Lets QA slow down routes, fail them and delay the Redis and S3 operations through
+/// `/api/admin/fault-injection`. Only meant for staging deployments: the server refuses to start
+/// in production when it's enabled.
+#[derive(Clone, Debug)]
+pub struct FaultInjectionSetting {
+  pub enabled: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct WorkspaceInvitationSetting {
+  /// Days an invitation can be accepted for after it was sent or last extended.
+  pub expiry_days: i64,
+}
+
+impl WorkspaceInvitationSetting {
+  pub fn expires_in_secs(&self) -> i64 {
+    self.expiry_days * 24 * 60 * 60
+  }
+}
+
 #[derive(serde::Deserialize, Clone, Debug)]
 pub struct CasbinSetting {
   pub pool_size: u32,
@@ -62,6 +108,17 @@ pub struct S3Setting {
   pub presigned_url_endpoint: Option<String>,
 }
 
+#[derive(Clone, Debug)]
+pub struct FileStorageSetting {
+  /// Keep the previous revision of a blob when its file id is overwritten.
+  pub enable_blob_versioning: bool,
+  pub max_blob_versions: i64,
+  /// Archived blob versions older than this are pruned. 0 disables age based pruning.
+  pub blob_version_retention_days: i64,
+  /// Maximum total size of the blobs of a workspace. 0 leaves the storage unlimited.
+  pub workspace_storage_limit_bytes: u64,
+}
+
 #[derive(serde::Deserialize, Clone, Debug)]
 pub struct GoTrueSetting {
   pub base_url: String,
@@ -87,6 +144,20 @@ impl AppFlowyAISetting {
   }
 }
 
+#[derive(Clone, Debug)]
+pub struct AISchedulerSetting {
+  pub enabled: bool,
+  /// Upper bound of the adaptive concurrency limit of requests sent to the AI provider.
+  pub max_concurrency: usize,
+  /// The concurrency limit never shrinks below this value, even under sustained rate limiting.
+  pub min_concurrency: usize,
+  /// Maximum number of concurrent AI requests for a single workspace.
+  pub max_in_flight_per_workspace: usize,
+  /// Requests waiting in the queue longer than this are rejected with a retryable error.
+  pub queue_deadline_secs: u64,
+  pub keepalive_interval_secs: u64,
+}
+
 // We are using 127.0.0.1 as our host in address, we are instructing our
 // application to only accept connections coming from the same machine. However,
 // request from the hose machine which is not seen as local by our Docker image.
@@ -99,6 +170,9 @@ impl AppFlowyAISetting {
 pub struct ApplicationSetting {
   pub port: u16,
   pub host: String,
+  /// Keeps the epoch seconds in the responses to the clients that didn't opt in to the RFC3339
+  /// timestamps, during their deprecation window.
+  pub legacy_timestamps: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -110,6 +184,10 @@ pub struct DatabaseSetting {
   /// connections are reserved for system applications.
   /// When we exceed the limit of the database connection, then it shows an error message.
   pub max_connections: u32,
+  /// Statement timeout of the document search queries.
+  pub search_statement_timeout: Duration,
+  /// Statement timeout of the queries computing the storage usage of a workspace.
+  pub usage_statement_timeout: Duration,
 }
 
 impl Display for DatabaseSetting {
@@ -142,6 +220,16 @@ pub struct CollabSetting {
   pub edit_state_max_count: u32,
   pub edit_state_max_secs: i64,
   pub s3_collab_threshold: u64,
+  /// Folder collabs larger than this (in bytes) are rebuilt from their logical content.
+  pub folder_compaction_threshold: usize,
+  pub snapshot_policies: SnapshotPolicies,
+  pub load_shedding: LoadShedConfig,
+  pub group_unload: GroupUnloadConfig,
+  pub update_rate_limit: UpdateRateLimitConfig,
+  pub size_limits: CollabSizeLimits,
+  pub full_state_sync: FullStateSyncConfig,
+  pub write_behind: WriteBehindConfig,
+  pub compaction: CollabCompactionConfig,
 }
 
 #[derive(Clone, Debug)]
@@ -153,6 +241,11 @@ pub enum PublishedCollabStorageBackend {
 #[derive(Clone, Debug)]
 pub struct PublishedCollabSetting {
   pub storage_backend: PublishedCollabStorageBackend,
+  /// How long a published revision stays servable from its revision-addressed URL once a newer
+  /// revision replaced it.
+  pub revision_grace_period_secs: i64,
+  /// `max-age` of the entry points of published views, which return the latest revision.
+  pub entry_max_age_secs: u64,
 }
 
 impl TryFrom<&str> for PublishedCollabStorageBackend {
@@ -198,6 +291,16 @@ pub fn get_configuration() -> Result<Config, anyhow::Error> {
       max_connections: get_env_var("APPFLOWY_DATABASE_MAX_CONNECTIONS", "40")
         .parse()
         .context("fail to get APPFLOWY_DATABASE_MAX_CONNECTIONS")?,
+      search_statement_timeout: Duration::from_millis(
+        get_env_var("APPFLOWY_DATABASE_SEARCH_STATEMENT_TIMEOUT_MS", "2000")
+          .parse()
+          .context("fail to get APPFLOWY_DATABASE_SEARCH_STATEMENT_TIMEOUT_MS")?,
+      ),
+      usage_statement_timeout: Duration::from_millis(
+        get_env_var("APPFLOWY_DATABASE_USAGE_STATEMENT_TIMEOUT_MS", "5000")
+          .parse()
+          .context("fail to get APPFLOWY_DATABASE_USAGE_STATEMENT_TIMEOUT_MS")?,
+      ),
     },
     gotrue: GoTrueSetting {
       base_url: get_env_var("APPFLOWY_GOTRUE_BASE_URL", "http://localhost:9999"),
@@ -209,6 +312,9 @@ pub fn get_configuration() -> Result<Config, anyhow::Error> {
     application: ApplicationSetting {
       port: get_env_var("APPFLOWY_APPLICATION_PORT", "8000").parse()?,
       host: get_env_var("APPFLOWY_APPLICATION_HOST", "0.0.0.0"),
+      legacy_timestamps: get_env_var("APPFLOWY_API_LEGACY_TIMESTAMPS", "true")
+        .parse()
+        .context("fail to get APPFLOWY_API_LEGACY_TIMESTAMPS")?,
     },
     websocket: WebsocketSetting {
       heartbeat_interval: get_env_var("APPFLOWY_WEBSOCKET_HEARTBEAT_INTERVAL", "6").parse()?,
@@ -231,10 +337,42 @@ pub fn get_configuration() -> Result<Config, anyhow::Error> {
       region: get_env_var("APPFLOWY_S3_REGION", ""),
       presigned_url_endpoint: get_env_var_opt("APPFLOWY_S3_PRESIGNED_URL_ENDPOINT"),
     },
+    file_storage: FileStorageSetting {
+      enable_blob_versioning: get_env_var("APPFLOWY_FILE_STORAGE_ENABLE_BLOB_VERSIONING", "false")
+        .parse()
+        .context("fail to get APPFLOWY_FILE_STORAGE_ENABLE_BLOB_VERSIONING")?,
+      max_blob_versions: get_env_var("APPFLOWY_FILE_STORAGE_MAX_BLOB_VERSIONS", "10").parse()?,
+      blob_version_retention_days: get_env_var(
+        "APPFLOWY_FILE_STORAGE_BLOB_VERSION_RETENTION_DAYS",
+        "30",
+      )
+      .parse()?,
+      workspace_storage_limit_bytes: get_env_var(
+        "APPFLOWY_FILE_STORAGE_WORKSPACE_LIMIT_BYTES",
+        "0",
+      )
+      .parse()?,
+    },
     appflowy_ai: AppFlowyAISetting {
       port: get_env_var("AI_SERVER_PORT", "5001").into(),
       host: get_env_var("AI_SERVER_HOST", "localhost").into(),
     },
+    ai_scheduler: AISchedulerSetting {
+      enabled: get_env_var("APPFLOWY_AI_SCHEDULER_ENABLED", "true")
+        .parse()
+        .context("fail to get APPFLOWY_AI_SCHEDULER_ENABLED")?,
+      max_concurrency: get_env_var("APPFLOWY_AI_SCHEDULER_MAX_CONCURRENCY", "64").parse()?,
+      min_concurrency: get_env_var("APPFLOWY_AI_SCHEDULER_MIN_CONCURRENCY", "4").parse()?,
+      max_in_flight_per_workspace: get_env_var(
+        "APPFLOWY_AI_SCHEDULER_MAX_IN_FLIGHT_PER_WORKSPACE",
+        "4",
+      )
+      .parse()?,
+      queue_deadline_secs: get_env_var("APPFLOWY_AI_SCHEDULER_QUEUE_DEADLINE_SECS", "30")
+        .parse()?,
+      keepalive_interval_secs: get_env_var("APPFLOWY_AI_SCHEDULER_KEEPALIVE_INTERVAL_SECS", "2")
+        .parse()?,
+    },
     collab: CollabSetting {
       group_persistence_interval_secs: get_env_var(
         "APPFLOWY_COLLAB_GROUP_PERSISTENCE_INTERVAL",
@@ -246,11 +384,31 @@ pub fn get_configuration() -> Result<Config, anyhow::Error> {
       edit_state_max_count: get_env_var("APPFLOWY_COLLAB_EDIT_STATE_MAX_COUNT", "100").parse()?,
       edit_state_max_secs: get_env_var("APPFLOWY_COLLAB_EDIT_STATE_MAX_SECS", "60").parse()?,
       s3_collab_threshold: get_env_var("APPFLOWY_COLLAB_S3_THRESHOLD", "8000").parse()?,
+      folder_compaction_threshold: get_env_var(
+        "APPFLOWY_COLLAB_FOLDER_COMPACTION_THRESHOLD",
+        "5242880",
+      )
+      .parse()?,
+      snapshot_policies: SnapshotPolicies::from_env()?,
+      load_shedding: LoadShedConfig::from_env()?,
+      group_unload: GroupUnloadConfig::from_env()?,
+      update_rate_limit: UpdateRateLimitConfig::from_env()?,
+      size_limits: CollabSizeLimits::from_env()?,
+      full_state_sync: FullStateSyncConfig::from_env()?,
+      write_behind: WriteBehindConfig::from_env()?,
+      compaction: CollabCompactionConfig::from_env()?,
     },
     published_collab: PublishedCollabSetting {
       storage_backend: get_env_var("APPFLOWY_PUBLISHED_COLLAB_STORAGE_BACKEND", "postgres")
         .as_str()
         .try_into()?,
+      revision_grace_period_secs: get_env_var(
+        "APPFLOWY_PUBLISHED_COLLAB_REVISION_GRACE_PERIOD_SECS",
+        "86400",
+      )
+      .parse()?,
+      entry_max_age_secs: get_env_var("APPFLOWY_PUBLISHED_COLLAB_ENTRY_MAX_AGE_SECS", "60")
+        .parse()?,
     },
     mailer: MailerSetting {
       smtp_host: get_env_var("APPFLOWY_MAILER_SMTP_HOST", "smtp.gmail.com"),
@@ -264,9 +422,30 @@ pub fn get_configuration() -> Result<Config, anyhow::Error> {
       client_id: get_env_var("APPFLOWY_APPLE_OAUTH_CLIENT_ID", ""),
       client_secret: get_env_var("APPFLOWY_APPLE_OAUTH_CLIENT_SECRET", "").into(),
     },
+    inbound_email: InboundEmailSetting {
+      domain: get_env_var_opt("APPFLOWY_INBOUND_EMAIL_DOMAIN"),
+      mailgun_signing_key: get_env_var_opt("APPFLOWY_INBOUND_EMAIL_MAILGUN_SIGNING_KEY")
+        .map(Secret::new),
+      sendgrid_password: get_env_var_opt("APPFLOWY_INBOUND_EMAIL_SENDGRID_PASSWORD")
+        .map(Secret::new),
+      max_email_size: get_env_var("APPFLOWY_INBOUND_EMAIL_MAX_SIZE", "26214400").parse()?,
+    },
+    fault_injection: FaultInjectionSetting {
+      enabled: get_env_var("APPFLOWY_FAULT_INJECTION_ENABLED", "false")
+        .parse()
+        .context("fail to get APPFLOWY_FAULT_INJECTION_ENABLED")?,
+    },
+    workspace_invitation: WorkspaceInvitationSetting {
+      expiry_days: get_env_var("APPFLOWY_WORKSPACE_INVITATION_EXPIRY_DAYS", "14")
+        .parse()
+        .context("fail to get APPFLOWY_WORKSPACE_INVITATION_EXPIRY_DAYS")?,
+    },
     appflowy_web_url: get_env_var_opt("APPFLOWY_WEB_URL"),
     admin_frontend_path_prefix: get_env_var("APPFLOWY_ADMIN_FRONTEND_PATH_PREFIX", ""),
   };
+  if config.fault_injection.enabled && matches!(config.app_env, Environment::Production) {
+    anyhow::bail!("APPFLOWY_FAULT_INJECTION_ENABLED can not be set in the production environment");
+  }
   Ok(config)
 }
 
@@ -5,6 +5,7 @@ pub const WORKSPACE_INVITE_TEMPLATE_NAME: &str = "workspace_invite";
 pub const WORKSPACE_ACCESS_REQUEST_TEMPLATE_NAME: &str = "workspace_access_request";
 pub const WORKSPACE_ACCESS_REQUEST_APPROVED_NOTIFICATION_TEMPLATE_NAME: &str =
   "workspace_access_request_approved_notification";
+pub const PUBLISHED_VIEW_REPORT_RESOLVED_TEMPLATE_NAME: &str = "published_view_report_resolved";
 
 #[derive(Clone)]
 pub struct AFCloudMailer(Mailer);
@@ -19,10 +20,7 @@ impl AFCloudMailer {
     email: &str,
     param: WorkspaceInviteMailerParam,
   ) -> Result<(), anyhow::Error> {
-    let subject = format!(
-      "{} invited you to {} in AppFlowy",
-      param.username, param.workspace_name
-    );
+    let subject = workspace_invite_subject(&param);
     self
       .0
       .send_email_template(
@@ -84,6 +82,24 @@ impl AFCloudMailer {
       )
       .await
   }
+
+  pub async fn send_published_view_report_resolved(
+    &self,
+    email: &str,
+    param: PublishedViewReportResolvedMailerParam,
+  ) -> Result<(), anyhow::Error> {
+    let subject = "Your report of a published page was reviewed";
+    self
+      .0
+      .send_email_template(
+        None,
+        email,
+        PUBLISHED_VIEW_REPORT_RESOLVED_TEMPLATE_NAME,
+        param,
+        subject,
+      )
+      .await
+  }
 }
 
 async fn register_mailer(mailer: &mut Mailer) -> Result<(), anyhow::Error> {
@@ -94,6 +110,8 @@ async fn register_mailer(mailer: &mut Mailer) -> Result<(), anyhow::Error> {
   let access_request_approved_notification_template = include_str!(
     "../assets/mailer_templates/build_production/access_request_approved_notification.html"
   );
+  let published_view_report_resolved_template =
+    include_str!("../assets/mailer_templates/build_production/published_view_report_resolved.html");
   let template_strings = HashMap::from([
     (WORKSPACE_INVITE_TEMPLATE_NAME, workspace_invite_template),
     (
@@ -104,6 +122,10 @@ async fn register_mailer(mailer: &mut Mailer) -> Result<(), anyhow::Error> {
       WORKSPACE_ACCESS_REQUEST_APPROVED_NOTIFICATION_TEMPLATE_NAME,
       access_request_approved_notification_template,
     ),
+    (
+      PUBLISHED_VIEW_REPORT_RESOLVED_TEMPLATE_NAME,
+      published_view_report_resolved_template,
+    ),
   ]);
 
   for (template_name, template_string) in template_strings {
@@ -116,6 +138,13 @@ async fn register_mailer(mailer: &mut Mailer) -> Result<(), anyhow::Error> {
   Ok(())
 }
 
+pub fn workspace_invite_subject(param: &WorkspaceInviteMailerParam) -> String {
+  format!(
+    "{} invited you to {} in AppFlowy",
+    param.username, param.workspace_name
+  )
+}
+
 #[derive(serde::Serialize)]
 pub struct WorkspaceInviteMailerParam {
   pub user_icon_url: String,
@@ -143,3 +172,9 @@ pub struct WorkspaceAccessRequestApprovedMailerParam {
   pub workspace_member_count: i64,
   pub launch_workspace_url: String,
 }
+
+#[derive(serde::Serialize)]
+pub struct PublishedViewReportResolvedMailerParam {
+  pub publish_url: String,
+  pub outcome: String,
+}
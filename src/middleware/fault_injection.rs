@@ -0,0 +1,403 @@
+use std::cell::RefCell;
+use std::f64::consts::PI;
+use std::future::{ready, Ready};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use actix_http::header::{HeaderName, HeaderValue};
+use actix_http::{Method, StatusCode};
+use actix_service::{forward_ready, Service, Transform};
+use actix_web::body::EitherBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::HttpResponse;
+use app_error::{AppError, ErrorCode};
+use async_trait::async_trait;
+use database::operation_delay::{DelayedDependency, OperationDelay};
+use futures_util::future::LocalBoxFuture;
+use rand::Rng;
+use shared_entity::dto::fault_injection_dto::{
+  FaultInjectionParams, LatencyDistribution, RouteFault, X_FAULT_INJECTED,
+};
+use shared_entity::response::AppResponse;
+use tracing::{info, warn};
+
+/// Prefix of the logs of every injected fault, so that they are easy to tell apart from the real
+/// ones.
+pub const FAULT_INJECTION_MARKER: &str = "[FAULT-INJECTION]";
+/// The requests adjusting the faults are never slowed down nor failed.
+const CONTROL_PATH: &str = "/api/admin/fault-injection";
+const MAX_LATENCY_MS: u64 = 60_000;
+
+tokio::task_local! {
+  /// The faults injected while the current request is handled, reported in [X_FAULT_INJECTED].
+  static INJECTED_FAULTS: RefCell<Vec<String>>;
+}
+
+/// Records a fault in the response header of the request being handled, if any. The operations
+/// of a spawned task are not reported.
+fn record_injection(fault: String) {
+  let _ = INJECTED_FAULTS.try_with(|faults| faults.borrow_mut().push(fault));
+}
+
+/// Holds the faults injected by a staging server. It's only created when
+/// `APPFLOWY_FAULT_INJECTION_ENABLED` is set, and the parameters are adjusted at runtime by the
+/// admins of the instance.
+#[derive(Default)]
+pub struct FaultInjector {
+  params: RwLock<FaultInjectionParams>,
+}
+
+impl FaultInjector {
+  pub fn params(&self) -> FaultInjectionParams {
+    self.params.read().unwrap().clone()
+  }
+
+  pub fn set_params(&self, params: FaultInjectionParams) -> Result<(), AppError> {
+    validate_params(&params)?;
+    info!(
+      "{} parameters updated: {:?}",
+      FAULT_INJECTION_MARKER, params
+    );
+    *self.params.write().unwrap() = params;
+    Ok(())
+  }
+
+  fn route_fault(&self, method: &Method, path: &str) -> Option<RouteFault> {
+    self
+      .params
+      .read()
+      .unwrap()
+      .routes
+      .iter()
+      .find(|fault| route_fault_matches(fault, method, path))
+      .cloned()
+  }
+}
+
+#[async_trait]
+impl OperationDelay for FaultInjector {
+  async fn delay(&self, dependency: DelayedDependency, operation: &str) {
+    let distribution = {
+      let params = self.params.read().unwrap();
+      match dependency {
+        DelayedDependency::Redis => params.redis_delay.clone(),
+        DelayedDependency::S3 => params.s3_delay.clone(),
+        DelayedDependency::Postgres => params.postgres_delay.clone(),
+      }
+    };
+    let Some(distribution) = distribution else {
+      return;
+    };
+    let delay = sample_latency(&distribution, &mut rand::thread_rng());
+    if delay.is_zero() {
+      return;
+    }
+    warn!(
+      "{} delaying {} operation {} by {}ms",
+      FAULT_INJECTION_MARKER,
+      dependency.as_str(),
+      operation,
+      delay.as_millis()
+    );
+    record_injection(format!("{}={}ms", dependency.as_str(), delay.as_millis()));
+    tokio::time::sleep(delay).await;
+  }
+}
+
+fn route_fault_matches(fault: &RouteFault, method: &Method, path: &str) -> bool {
+  path.starts_with(&fault.path_prefix)
+    && fault.method.as_deref().map_or(true, |expected| {
+      expected.eq_ignore_ascii_case(method.as_str())
+    })
+}
+
+fn validate_params(params: &FaultInjectionParams) -> Result<(), AppError> {
+  for fault in &params.routes {
+    if !fault.path_prefix.starts_with('/') {
+      return Err(AppError::InvalidRequest(format!(
+        "path_prefix {} must start with /",
+        fault.path_prefix
+      )));
+    }
+    if let Some(method) = &fault.method {
+      Method::from_bytes(method.as_bytes())
+        .map_err(|_| AppError::InvalidRequest(format!("{} is not a valid method", method)))?;
+    }
+    if !(0.0..=1.0).contains(&fault.error_rate) {
+      return Err(AppError::InvalidRequest(
+        "error_rate must be between 0 and 1".to_string(),
+      ));
+    }
+    if !(500..=599).contains(&fault.error_status) {
+      return Err(AppError::InvalidRequest(
+        "error_status must be a 5xx status".to_string(),
+      ));
+    }
+    if let Some(latency) = &fault.latency {
+      validate_distribution(latency)?;
+    }
+  }
+  for distribution in [
+    &params.redis_delay,
+    &params.s3_delay,
+    &params.postgres_delay,
+  ]
+  .into_iter()
+  .flatten()
+  {
+    validate_distribution(distribution)?;
+  }
+  Ok(())
+}
+
+fn validate_distribution(distribution: &LatencyDistribution) -> Result<(), AppError> {
+  let max_ms = MAX_LATENCY_MS as f64;
+  let is_valid = match *distribution {
+    LatencyDistribution::Fixed { ms } => ms <= MAX_LATENCY_MS,
+    LatencyDistribution::Uniform { min_ms, max_ms } => min_ms <= max_ms && max_ms <= MAX_LATENCY_MS,
+    LatencyDistribution::Normal {
+      mean_ms,
+      std_dev_ms,
+    } => (0.0..=max_ms).contains(&mean_ms) && (0.0..=max_ms).contains(&std_dev_ms),
+  };
+  if is_valid {
+    Ok(())
+  } else {
+    Err(AppError::InvalidRequest(format!(
+      "Invalid latency distribution {:?}, latencies are at most {}ms",
+      distribution, MAX_LATENCY_MS
+    )))
+  }
+}
+
+fn sample_latency<R: Rng>(distribution: &LatencyDistribution, rng: &mut R) -> Duration {
+  let ms = match *distribution {
+    LatencyDistribution::Fixed { ms } => ms as f64,
+    LatencyDistribution::Uniform { min_ms, max_ms } => rng.gen_range(min_ms..=max_ms) as f64,
+    LatencyDistribution::Normal {
+      mean_ms,
+      std_dev_ms,
+    } => {
+      // Box-Muller transform
+      let u1: f64 = rng.gen_range(f64::EPSILON..1.0);
+      let u2: f64 = rng.gen();
+      mean_ms + std_dev_ms * (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+    },
+  };
+  Duration::from_millis(ms.clamp(0.0, MAX_LATENCY_MS as f64) as u64)
+}
+
+/// Returns the latency to add to the request, and the status it fails with if it must fail.
+fn sample_route_fault<R: Rng>(fault: &RouteFault, rng: &mut R) -> (Duration, Option<StatusCode>) {
+  let latency = fault
+    .latency
+    .as_ref()
+    .map(|distribution| sample_latency(distribution, rng))
+    .unwrap_or_default();
+  let error_status = (fault.error_rate > 0.0 && rng.gen_bool(fault.error_rate))
+    .then(|| StatusCode::from_u16(fault.error_status).unwrap_or(StatusCode::SERVICE_UNAVAILABLE));
+  (latency, error_status)
+}
+
+/// Slows down and fails the requests according to the [FaultInjector], and lists the injected
+/// faults in the [X_FAULT_INJECTED] response header. Requests go through untouched when fault
+/// injection is not enabled.
+pub struct FaultInjectionMiddleware {
+  injector: Option<Arc<FaultInjector>>,
+}
+
+impl FaultInjectionMiddleware {
+  pub fn new(injector: Option<Arc<FaultInjector>>) -> Self {
+    Self { injector }
+  }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for FaultInjectionMiddleware
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+  S::Future: 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<EitherBody<B>>;
+  type Error = actix_web::Error;
+  type Transform = FaultInjectionMiddlewareService<S>;
+  type InitError = ();
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(FaultInjectionMiddlewareService {
+      service,
+      injector: self.injector.clone(),
+    }))
+  }
+}
+
+pub struct FaultInjectionMiddlewareService<S> {
+  service: S,
+  injector: Option<Arc<FaultInjector>>,
+}
+
+impl<S, B> Service<ServiceRequest> for FaultInjectionMiddlewareService<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+  S::Future: 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<EitherBody<B>>;
+  type Error = actix_web::Error;
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  forward_ready!(service);
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let injector = match &self.injector {
+      Some(injector) if !req.path().starts_with(CONTROL_PATH) => injector,
+      _ => {
+        let fut = self.service.call(req);
+        return Box::pin(async move { Ok(fut.await?.map_into_left_body()) });
+      },
+    };
+
+    let (latency, error_status) = match injector.route_fault(req.method(), req.path()) {
+      Some(fault) => sample_route_fault(&fault, &mut rand::thread_rng()),
+      None => (Duration::ZERO, None),
+    };
+    let request = format!("{} {}", req.method(), req.path());
+    let outcome = match error_status {
+      Some(status) => Err((req, status)),
+      None => Ok(self.service.call(req)),
+    };
+
+    Box::pin(INJECTED_FAULTS.scope(RefCell::new(vec![]), async move {
+      if !latency.is_zero() {
+        warn!(
+          "{} delaying {} by {}ms",
+          FAULT_INJECTION_MARKER,
+          request,
+          latency.as_millis()
+        );
+        record_injection(format!("latency={}ms", latency.as_millis()));
+        tokio::time::sleep(latency).await;
+      }
+
+      let mut res = match outcome {
+        Ok(fut) => fut.await?.map_into_left_body(),
+        Err((req, status)) => {
+          warn!(
+            "{} failing {} with {}",
+            FAULT_INJECTION_MARKER, request, status
+          );
+          record_injection(format!("error={}", status.as_u16()));
+          let response = HttpResponse::build(status).json(AppResponse::<()>::new(
+            ErrorCode::ServiceTemporaryUnavailable,
+            "Injected fault",
+          ));
+          req.into_response(response).map_into_right_body()
+        },
+      };
+
+      let faults = INJECTED_FAULTS.with(|faults| std::mem::take(&mut *faults.borrow_mut()));
+      if !faults.is_empty() {
+        if let Ok(value) = HeaderValue::from_str(&faults.join(";")) {
+          res
+            .headers_mut()
+            .insert(HeaderName::from_static(X_FAULT_INJECTED), value);
+        }
+      }
+      Ok(res)
+    }))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
+
+  fn route_fault(path_prefix: &str, method: Option<&str>) -> RouteFault {
+    RouteFault {
+      path_prefix: path_prefix.to_string(),
+      method: method.map(|method| method.to_string()),
+      latency: None,
+      error_rate: 0.0,
+      error_status: 503,
+    }
+  }
+
+  #[test]
+  fn first_matching_route_fault_applies_test() {
+    let injector = FaultInjector::default();
+    let mut params = FaultInjectionParams::default();
+    let mut failing = route_fault("/api/workspace", Some("post"));
+    failing.error_rate = 1.0;
+    params.routes = vec![failing, route_fault("/api", None)];
+    injector.set_params(params).unwrap();
+
+    let fault = injector
+      .route_fault(&Method::POST, "/api/workspace/abc")
+      .unwrap();
+    assert_eq!(fault.error_rate, 1.0);
+    let fault = injector
+      .route_fault(&Method::GET, "/api/workspace/abc")
+      .unwrap();
+    assert_eq!(fault.path_prefix, "/api");
+    assert!(injector.route_fault(&Method::GET, "/health").is_none());
+  }
+
+  #[test]
+  fn invalid_params_are_rejected_test() {
+    let injector = FaultInjector::default();
+    let mut fault = route_fault("/api", None);
+    fault.error_status = 404;
+    let params = FaultInjectionParams {
+      routes: vec![fault],
+      ..Default::default()
+    };
+    assert!(injector.set_params(params).is_err());
+
+    let params = FaultInjectionParams {
+      s3_delay: Some(LatencyDistribution::Uniform {
+        min_ms: 100,
+        max_ms: 10,
+      }),
+      ..Default::default()
+    };
+    assert!(injector.set_params(params).is_err());
+    assert_eq!(injector.params(), FaultInjectionParams::default());
+  }
+
+  #[test]
+  fn latency_is_sampled_within_bounds_test() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let fixed = LatencyDistribution::Fixed { ms: 120 };
+    assert_eq!(sample_latency(&fixed, &mut rng), Duration::from_millis(120));
+
+    let uniform = LatencyDistribution::Uniform {
+      min_ms: 10,
+      max_ms: 20,
+    };
+    let normal = LatencyDistribution::Normal {
+      mean_ms: 5.0,
+      std_dev_ms: 50.0,
+    };
+    for _ in 0..1000 {
+      let latency = sample_latency(&uniform, &mut rng);
+      assert!(latency >= Duration::from_millis(10) && latency <= Duration::from_millis(20));
+      assert!(sample_latency(&normal, &mut rng) <= Duration::from_millis(MAX_LATENCY_MS));
+    }
+  }
+
+  #[test]
+  fn error_is_injected_at_rate_test() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut fault = route_fault("/api", None);
+    assert!(sample_route_fault(&fault, &mut rng).1.is_none());
+
+    fault.error_rate = 1.0;
+    fault.error_status = 502;
+    let (latency, status) = sample_route_fault(&fault, &mut rng);
+    assert!(latency.is_zero());
+    assert_eq!(status, Some(StatusCode::BAD_GATEWAY));
+  }
+}
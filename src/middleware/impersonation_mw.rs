@@ -0,0 +1,151 @@
+use std::future::{ready, Ready};
+
+use actix_http::Method;
+use actix_service::{forward_ready, Service, Transform};
+use actix_web::body::EitherBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::web::Data;
+use actix_web::HttpResponse;
+use app_error::ErrorCode;
+use database::impersonation::{insert_impersonation_request, select_active_impersonation};
+use futures_util::future::LocalBoxFuture;
+use gotrue_entity::gotrue_jwt::GoTrueJWTClaims;
+use secrecy::{ExposeSecret, Secret};
+use shared_entity::response::AppResponse;
+use sqlx::PgPool;
+use tracing::{info, warn};
+
+use crate::biz::user::impersonation::{impersonation_from_claims, ImpersonationClaim};
+
+/// Checks the requests made with the access tokens issued for an impersonation: the
+/// impersonation must still be active, and it must have been granted writes for any request
+/// which isn't a read. Every such request is logged and recorded in the audit trail of the
+/// impersonation, including the rejected ones.
+pub struct ImpersonationMiddleware {
+  pg_pool: PgPool,
+}
+
+impl ImpersonationMiddleware {
+  pub fn new(pg_pool: PgPool) -> Self {
+    Self { pg_pool }
+  }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ImpersonationMiddleware
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+  S::Future: 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<EitherBody<B>>;
+  type Error = actix_web::Error;
+  type Transform = ImpersonationMiddlewareService<S>;
+  type InitError = ();
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(ImpersonationMiddlewareService {
+      service,
+      pg_pool: self.pg_pool.clone(),
+    }))
+  }
+}
+
+pub struct ImpersonationMiddlewareService<S> {
+  service: S,
+  pg_pool: PgPool,
+}
+
+impl<S, B> Service<ServiceRequest> for ImpersonationMiddlewareService<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+  S::Future: 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<EitherBody<B>>;
+  type Error = actix_web::Error;
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  forward_ready!(service);
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let Some(claim) = impersonation_from_request(&req) else {
+      let fut = self.service.call(req);
+      return Box::pin(async move { Ok(fut.await?.map_into_left_body()) });
+    };
+
+    let pg_pool = self.pg_pool.clone();
+    let method = req.method().clone();
+    let path = req.path().to_string();
+    let is_read = matches!(method, Method::GET | Method::HEAD | Method::OPTIONS);
+    let http_req = req.request().clone();
+    // The request is only handled once the impersonation is known to allow it.
+    let fut = self.service.call(req);
+    Box::pin(async move {
+      let rejection = match select_active_impersonation(&pg_pool, &claim.impersonation_id).await {
+        Ok(Some(row)) if is_read || row.write_granted_at.is_some() => None,
+        Ok(Some(_)) => Some(HttpResponse::Forbidden().json(AppResponse::<()>::new(
+          ErrorCode::NotEnoughPermissions,
+          "The impersonation is read-only, writes must be granted first",
+        ))),
+        Ok(None) => Some(HttpResponse::Unauthorized().json(AppResponse::<()>::new(
+          ErrorCode::UserUnAuthorized,
+          "The impersonation expired or was revoked",
+        ))),
+        Err(err) => {
+          warn!(
+            "Failed to check impersonation {}: {}",
+            claim.impersonation_id, err
+          );
+          Some(
+            HttpResponse::ServiceUnavailable().json(AppResponse::<()>::new(
+              ErrorCode::Internal,
+              "The impersonation couldn't be checked",
+            )),
+          )
+        },
+      };
+
+      let response = match rejection {
+        Some(response) => ServiceResponse::new(http_req, response).map_into_right_body(),
+        None => fut.await?.map_into_left_body(),
+      };
+      let status = response.status().as_u16();
+      info!(
+        impersonation_id = %claim.impersonation_id,
+        admin_uid = claim.admin_uid,
+        "Impersonated request: {} {} -> {}",
+        method,
+        path,
+        status
+      );
+      if let Err(err) = insert_impersonation_request(
+        &pg_pool,
+        &claim.impersonation_id,
+        method.as_str(),
+        &path,
+        status as i16,
+      )
+      .await
+      {
+        warn!(
+          "Failed to record request of impersonation {}: {}",
+          claim.impersonation_id, err
+        );
+      }
+      Ok(response)
+    })
+  }
+}
+
+fn impersonation_from_request(req: &ServiceRequest) -> Option<ImpersonationClaim> {
+  let jwt_secret = req.app_data::<Data<Secret<String>>>()?;
+  let token = req
+    .headers()
+    .get("Authorization")?
+    .to_str()
+    .ok()?
+    .strip_prefix("Bearer ")?;
+  let claims = GoTrueJWTClaims::decode(token, jwt_secret.expose_secret().as_bytes()).ok()?;
+  impersonation_from_claims(&claims)
+}
@@ -1,2 +1,7 @@
+pub mod fault_injection;
+pub mod impersonation_mw;
 pub mod metrics_mw;
+pub mod policy_version_mw;
 pub mod request_id;
+pub mod timestamp_format_mw;
+pub mod user_session_mw;
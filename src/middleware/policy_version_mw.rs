@@ -0,0 +1,112 @@
+use std::future::{ready, Ready};
+
+use access_control::casbin::access::AccessControl;
+use actix_http::header::{HeaderName, HeaderValue};
+use actix_http::Method;
+use actix_service::{forward_ready, Service, Transform};
+use actix_web::body::EitherBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::HttpResponse;
+use database::access_policy::select_access_policy_version;
+use futures_util::future::LocalBoxFuture;
+use shared_entity::dto::policy_version_dto::{X_MIN_POLICY_VERSION, X_POLICY_VERSION};
+use shared_entity::response::AppResponseError;
+use sqlx::PgPool;
+use tracing::warn;
+
+/// Makes the permission changes consistent across the servers. The requests changing data return
+/// the version of the access control policies in [X_POLICY_VERSION]. A client sending it back in
+/// [X_MIN_POLICY_VERSION] has its requests enforced with policies of at least that version, even
+/// when the server handling them didn't load them yet.
+pub struct PolicyVersionMiddleware {
+  pg_pool: PgPool,
+  access_control: AccessControl,
+}
+
+impl PolicyVersionMiddleware {
+  pub fn new(pg_pool: PgPool, access_control: AccessControl) -> Self {
+    Self {
+      pg_pool,
+      access_control,
+    }
+  }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for PolicyVersionMiddleware
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+  S::Future: 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<EitherBody<B>>;
+  type Error = actix_web::Error;
+  type Transform = PolicyVersionMiddlewareService<S>;
+  type InitError = ();
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(PolicyVersionMiddlewareService {
+      service,
+      pg_pool: self.pg_pool.clone(),
+      access_control: self.access_control.clone(),
+    }))
+  }
+}
+
+pub struct PolicyVersionMiddlewareService<S> {
+  service: S,
+  pg_pool: PgPool,
+  access_control: AccessControl,
+}
+
+impl<S, B> Service<ServiceRequest> for PolicyVersionMiddlewareService<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+  S::Future: 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<EitherBody<B>>;
+  type Error = actix_web::Error;
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  forward_ready!(service);
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let min_version = req
+      .headers()
+      .get(X_MIN_POLICY_VERSION)
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| value.parse::<i64>().ok());
+    let is_read = matches!(
+      req.method(),
+      &Method::GET | &Method::HEAD | &Method::OPTIONS
+    );
+    let pg_pool = self.pg_pool.clone();
+    let access_control = self.access_control.clone();
+    let http_req = req.request().clone();
+    // The request is only handled once the required policies are enforced.
+    let fut = self.service.call(req);
+    Box::pin(async move {
+      if let Some(min_version) = min_version {
+        if let Err(err) = access_control.require_policy_version(min_version).await {
+          let response = HttpResponse::from_error(AppResponseError::from(err));
+          return Ok(ServiceResponse::new(http_req, response).map_into_right_body());
+        }
+      }
+
+      let mut response = fut.await?;
+      if !is_read && response.status().is_success() {
+        match select_access_policy_version(&pg_pool).await {
+          Ok(version) => {
+            response.headers_mut().insert(
+              HeaderName::from_static(X_POLICY_VERSION),
+              HeaderValue::from(version),
+            );
+          },
+          Err(err) => warn!("failed to read the access policy version: {}", err),
+        }
+      }
+      Ok(response.map_into_left_body())
+    })
+  }
+}
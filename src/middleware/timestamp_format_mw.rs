@@ -0,0 +1,89 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use actix_service::{forward_ready, Service, Transform};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use database_entity::timestamp::{with_legacy_format, RFC3339_API_VERSION};
+use pin_project::pin_project;
+
+use crate::api::util::api_version_from_headers;
+
+/// Picks the format of the timestamps in the responses. Clients sending `x-api-version` with a
+/// version of at least [RFC3339_API_VERSION] get RFC3339 timestamps. The others keep the epoch
+/// seconds they used to get while `legacy_by_default` is set.
+pub struct TimestampFormatMiddleware {
+  legacy_by_default: bool,
+}
+
+impl TimestampFormatMiddleware {
+  pub fn new(legacy_by_default: bool) -> Self {
+    Self { legacy_by_default }
+  }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for TimestampFormatMiddleware
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+  S::Future: 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<B>;
+  type Error = actix_web::Error;
+  type Transform = TimestampFormatMiddlewareService<S>;
+  type InitError = ();
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(TimestampFormatMiddlewareService {
+      service,
+      legacy_by_default: self.legacy_by_default,
+    }))
+  }
+}
+
+pub struct TimestampFormatMiddlewareService<S> {
+  service: S,
+  legacy_by_default: bool,
+}
+
+impl<S, B> Service<ServiceRequest> for TimestampFormatMiddlewareService<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+  S::Future: 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<B>;
+  type Error = actix_web::Error;
+  type Future = TimestampFormatFuture<S::Future>;
+
+  forward_ready!(service);
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let api_version = api_version_from_headers(req.headers());
+    let legacy = self.legacy_by_default && api_version < RFC3339_API_VERSION;
+    TimestampFormatFuture {
+      fut: with_legacy_format(legacy, || self.service.call(req)),
+      legacy,
+    }
+  }
+}
+
+/// The responses are serialized while the handlers are polled, so every poll runs in the
+/// timestamp format of the request.
+#[pin_project]
+pub struct TimestampFormatFuture<F> {
+  #[pin]
+  fut: F,
+  legacy: bool,
+}
+
+impl<F: Future> Future for TimestampFormatFuture<F> {
+  type Output = F::Output;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let this = self.project();
+    let fut = this.fut;
+    with_legacy_format(*this.legacy, || fut.poll(cx))
+  }
+}
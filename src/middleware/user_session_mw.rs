@@ -0,0 +1,133 @@
+use std::future::{ready, Ready};
+use std::sync::Arc;
+
+use actix_http::header::USER_AGENT;
+use actix_service::{forward_ready, Service, Transform};
+use actix_web::body::EitherBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::web::Data;
+use actix_web::HttpResponse;
+use app_error::ErrorCode;
+use futures_util::future::LocalBoxFuture;
+use gotrue_entity::gotrue_jwt::GoTrueJWTClaims;
+use secrecy::{ExposeSecret, Secret};
+use shared_entity::response::AppResponse;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::api::util::{client_version_from_headers, device_id_from_headers};
+use crate::biz::user::user_session::{session_id_from_claims, SessionDevice, UserSessionTracker};
+
+/// Records the sessions the authenticated requests are sent with, and rejects the requests of the
+/// revoked sessions. Requests without a valid access token are left to the handlers.
+pub struct UserSessionMiddleware {
+  tracker: Arc<UserSessionTracker>,
+}
+
+impl UserSessionMiddleware {
+  pub fn new(tracker: Arc<UserSessionTracker>) -> Self {
+    Self { tracker }
+  }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for UserSessionMiddleware
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+  S::Future: 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<EitherBody<B>>;
+  type Error = actix_web::Error;
+  type Transform = UserSessionMiddlewareService<S>;
+  type InitError = ();
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(UserSessionMiddlewareService {
+      service,
+      tracker: self.tracker.clone(),
+    }))
+  }
+}
+
+pub struct UserSessionMiddlewareService<S> {
+  service: S,
+  tracker: Arc<UserSessionTracker>,
+}
+
+impl<S, B> Service<ServiceRequest> for UserSessionMiddlewareService<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+  S::Future: 'static,
+  B: 'static,
+{
+  type Response = ServiceResponse<EitherBody<B>>;
+  type Error = actix_web::Error;
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  forward_ready!(service);
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let Some((session_id, user_uuid, issued_at)) = session_from_request(&req) else {
+      let fut = self.service.call(req);
+      return Box::pin(async move { Ok(fut.await?.map_into_left_body()) });
+    };
+
+    let device = SessionDevice {
+      device_id: device_id_from_headers(req.headers())
+        .ok()
+        .map(|s| s.to_string()),
+      client_version: client_version_from_headers(req.headers())
+        .ok()
+        .map(|s| s.to_string()),
+      user_agent: req
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|s| s.to_string()),
+      ip_address: req
+        .connection_info()
+        .realip_remote_addr()
+        .map(|s| s.to_string()),
+    };
+    let tracker = self.tracker.clone();
+    // The request is only handled once the session is known not to be revoked.
+    let http_req = req.request().clone();
+    let fut = self.service.call(req);
+    Box::pin(async move {
+      let revoked = match tracker
+        .touch(&session_id, &user_uuid, &device, issued_at)
+        .await
+      {
+        Ok(revoked) => revoked,
+        Err(err) => {
+          warn!("Failed to record session {}: {}", session_id, err);
+          tracker.is_revoked(&session_id)
+        },
+      };
+      if revoked {
+        let response = HttpResponse::Unauthorized().json(AppResponse::<()>::new(
+          ErrorCode::UserUnAuthorized,
+          "The session was revoked",
+        ));
+        return Ok(ServiceResponse::new(http_req, response).map_into_right_body());
+      }
+      Ok(fut.await?.map_into_left_body())
+    })
+  }
+}
+
+/// The session, the user and the issue time of the access token of the request.
+fn session_from_request(req: &ServiceRequest) -> Option<(Uuid, Uuid, Option<i64>)> {
+  let jwt_secret = req.app_data::<Data<Secret<String>>>()?;
+  let token = req
+    .headers()
+    .get("Authorization")?
+    .to_str()
+    .ok()?
+    .strip_prefix("Bearer ")?;
+  let claims = GoTrueJWTClaims::decode(token, jwt_secret.expose_secret().as_bytes()).ok()?;
+  let session_id = session_id_from_claims(&claims)?;
+  let user_uuid = Uuid::parse_str(claims.sub.as_deref()?).ok()?;
+  Some((session_id, user_uuid, claims.iat))
+}
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use access_control::casbin::access::AccessControl;
 use access_control::collab::{CollabAccessControl, RealtimeAccessControl};
 use access_control::workspace::WorkspaceAccessControl;
 use dashmap::DashMap;
@@ -13,12 +14,16 @@ use access_control::metrics::AccessControlMetrics;
 use app_error::AppError;
 use appflowy_ai_client::client::AppFlowyAIClient;
 use appflowy_collaborate::collab::cache::CollabCache;
+use appflowy_collaborate::collab::recovery::CollabRecovery;
 use appflowy_collaborate::collab::storage::CollabAccessControlStorage;
-use appflowy_collaborate::metrics::CollabMetrics;
+use appflowy_collaborate::feature_flags::FeatureFlags;
+use appflowy_collaborate::load_shed::LoadShedder;
+use appflowy_collaborate::metrics::{CollabMetrics, LoadShedMetrics};
 use appflowy_collaborate::CollabRealtimeMetrics;
 use collab_stream::metrics::CollabStreamMetrics;
 use collab_stream::stream_router::StreamRouter;
 use database::file::s3_client_impl::{AwsS3BucketClientImpl, S3BucketStorage};
+use database::file::s3_retry::S3Metrics;
 use database::user::{select_all_uid_uuid, select_uid_from_uuid};
 use gotrue::grant::{Grant, PasswordGrant};
 use indexer::metrics::EmbeddingMetrics;
@@ -27,10 +32,16 @@ use snowflake::Snowflake;
 
 use crate::api::metrics::{AppFlowyWebMetrics, PublishedCollabMetrics, RequestMetrics};
 use crate::biz::chat::metrics::AIMetrics;
+use crate::biz::chat::scheduler::AIRequestScheduler;
+use crate::biz::collab::document_find::DocumentTextCache;
+use crate::biz::collab::document_outline::DocumentOutlineCache;
+use crate::biz::collab::row_access::RowAccessControl;
 use crate::biz::pg_listener::PgListeners;
+use crate::biz::user::user_session::UserSessionTracker;
 use crate::biz::workspace::publish::PublishedCollabStore;
 use crate::config::config::Config;
 use crate::mailer::AFCloudMailer;
+use crate::middleware::fault_injection::FaultInjector;
 
 pub type RedisConnectionManager = redis::aio::ConnectionManager;
 #[derive(Clone)]
@@ -44,9 +55,11 @@ pub struct AppState {
   pub redis_connection_manager: RedisConnectionManager,
   pub collab_cache: CollabCache,
   pub collab_access_control_storage: Arc<CollabAccessControlStorage>,
+  pub collab_recovery: CollabRecovery,
   pub collab_access_control: Arc<dyn CollabAccessControl>,
   pub workspace_access_control: Arc<dyn WorkspaceAccessControl>,
   pub realtime_access_control: Arc<dyn RealtimeAccessControl>,
+  pub row_access_control: RowAccessControl,
   pub bucket_storage: Arc<S3BucketStorage>,
   pub published_collab_store: Arc<dyn PublishedCollabStore>,
   pub bucket_client: AwsS3BucketClientImpl,
@@ -55,7 +68,17 @@ pub struct AppState {
   pub gotrue_admin: GoTrueAdmin,
   pub mailer: AFCloudMailer,
   pub ai_client: AppFlowyAIClient,
+  pub ai_scheduler: Arc<AIRequestScheduler>,
   pub indexer_scheduler: Arc<IndexerScheduler>,
+  /// Only set when `APPFLOWY_FAULT_INJECTION_ENABLED` is.
+  pub fault_injector: Option<Arc<FaultInjector>>,
+  pub user_session_tracker: Arc<UserSessionTracker>,
+  pub feature_flags: FeatureFlags,
+  pub access_control: AccessControl,
+  pub document_outline_cache: DocumentOutlineCache,
+  pub document_text_cache: DocumentTextCache,
+  /// Rejects part of the collab writes when their persistence can't keep up.
+  pub load_shedder: Arc<LoadShedder>,
 }
 
 impl AppState {
@@ -129,6 +152,8 @@ pub struct AppMetrics {
   pub embedding_metrics: Arc<EmbeddingMetrics>,
   pub collab_stream_metrics: Arc<CollabStreamMetrics>,
   pub ai_metrics: Arc<AIMetrics>,
+  pub s3_metrics: Arc<S3Metrics>,
+  pub load_shed_metrics: Arc<LoadShedMetrics>,
 }
 
 impl Default for AppMetrics {
@@ -149,6 +174,8 @@ impl AppMetrics {
     let embedding_metrics = Arc::new(EmbeddingMetrics::register(&mut registry));
     let collab_stream_metrics = Arc::new(CollabStreamMetrics::register(&mut registry));
     let ai_metrics = Arc::new(AIMetrics::register(&mut registry));
+    let s3_metrics = Arc::new(S3Metrics::register(&mut registry));
+    let load_shed_metrics = Arc::new(LoadShedMetrics::register(&mut registry));
     Self {
       registry: Arc::new(registry),
       request_metrics,
@@ -160,6 +187,8 @@ impl AppMetrics {
       embedding_metrics,
       collab_stream_metrics,
       ai_metrics,
+      s3_metrics,
+      load_shed_metrics,
     }
   }
 }
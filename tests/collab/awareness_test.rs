@@ -1,5 +1,6 @@
 use std::time::Duration;
 
+use app_error::ErrorCode;
 use collab_entity::CollabType;
 use tokio::time::sleep;
 
@@ -89,3 +90,57 @@ async fn assert_num_connected_client_within_secs(
     }
   }
 }
+
+#[tokio::test]
+async fn get_collab_presence_test() {
+  let collab_type = CollabType::Unknown;
+  let mut owner = TestClient::new_user().await;
+  let mut guest = TestClient::new_user().await;
+  let stranger = TestClient::new_user().await;
+
+  let workspace_id = owner.workspace_id().await;
+  owner
+    .invite_and_accepted_workspace_member(&workspace_id, &guest, AFRole::Member)
+    .await
+    .unwrap();
+  let object_id = owner
+    .create_and_edit_collab(&workspace_id, collab_type.clone())
+    .await;
+  guest
+    .open_collab(&workspace_id, &object_id, collab_type)
+    .await;
+  guest.wait_object_sync_complete(&object_id).await.unwrap();
+  sleep(Duration::from_secs(1)).await;
+
+  let owner_uid = owner.uid().await;
+  let guest_uid = guest.uid().await;
+  let mut uids: Vec<i64> = presence_uids(&guest, &workspace_id, &object_id).await;
+  uids.sort();
+  let mut expected = vec![owner_uid, guest_uid];
+  expected.sort();
+  assert_eq!(uids, expected);
+
+  // the guest closes the collab
+  guest.clean_awareness_state(&object_id).await;
+  sleep(Duration::from_secs(2)).await;
+  let uids = presence_uids(&owner, &workspace_id, &object_id).await;
+  assert_eq!(uids, vec![owner_uid]);
+
+  let err = stranger
+    .api_client
+    .get_collab_presence(&workspace_id, &object_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+}
+
+async fn presence_uids(client: &TestClient, workspace_id: &str, object_id: &str) -> Vec<i64> {
+  client
+    .api_client
+    .get_collab_presence(workspace_id, object_id)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|presence| presence.uid)
+    .collect()
+}
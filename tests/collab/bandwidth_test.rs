@@ -0,0 +1,27 @@
+use app_error::ErrorCode;
+use client_api_test::{admin_user_client, TestClient};
+use shared_entity::dto::realtime_dto::TopWorkspacesByBandwidthQuery;
+
+#[tokio::test]
+async fn top_workspaces_by_realtime_bandwidth_requires_admin_test() {
+  let client = TestClient::new_user_without_ws_conn().await;
+  let err = client
+    .api_client
+    .get_top_workspaces_by_realtime_bandwidth(&TopWorkspacesByBandwidthQuery::default())
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+
+  let admin = admin_user_client().await;
+  let workspaces = admin
+    .get_top_workspaces_by_realtime_bandwidth(&TopWorkspacesByBandwidthQuery {
+      limit: Some(5),
+      ..Default::default()
+    })
+    .await
+    .unwrap();
+  assert!(workspaces.len() <= 5);
+  assert!(workspaces
+    .windows(2)
+    .all(|w| w[0].bytes_in + w[0].bytes_out >= w[1].bytes_in + w[1].bytes_out));
+}
@@ -0,0 +1,179 @@
+use client_api::collab_cache::{CachedCollab, CollabCache, FileCollabCache, InMemoryCollabCache};
+use client_api_test::TestClient;
+use collab_entity::CollabType;
+use database_entity::dto::{CreateCollabParams, QueryCollabParams};
+use shared_entity::dto::workspace_dto::CollabDiffParams;
+use uuid::Uuid;
+
+use crate::collab::util::test_encode_collab_v1;
+
+async fn create_test_collab(test_client: &TestClient, workspace_id: &str, value: &str) -> String {
+  let object_id = Uuid::new_v4().to_string();
+  let encode_collab = test_encode_collab_v1(&object_id, "title", value);
+  test_client
+    .api_client
+    .create_collab(CreateCollabParams {
+      workspace_id: workspace_id.to_string(),
+      object_id: object_id.clone(),
+      encoded_collab_v1: encode_collab.encode_to_bytes().unwrap(),
+      collab_type: CollabType::Unknown,
+    })
+    .await
+    .unwrap();
+  object_id
+}
+
+#[tokio::test]
+async fn collab_diff_not_modified_test() {
+  let test_client = TestClient::new_user().await;
+  let workspace_id = test_client.workspace_id().await;
+  let object_id = create_test_collab(&test_client, &workspace_id, "hello").await;
+
+  let first = test_client
+    .api_client
+    .get_collab_diff(
+      &workspace_id,
+      &object_id,
+      CollabDiffParams {
+        collab_type: CollabType::Unknown,
+        state_vector_hash: None,
+        state_vector: None,
+      },
+    )
+    .await
+    .unwrap();
+  assert!(first.is_modified());
+  assert!(!first.is_diff);
+
+  let second = test_client
+    .api_client
+    .get_collab_diff(
+      &workspace_id,
+      &object_id,
+      CollabDiffParams {
+        collab_type: CollabType::Unknown,
+        state_vector_hash: Some(first.state_vector_hash.clone()),
+        state_vector: None,
+      },
+    )
+    .await
+    .unwrap();
+  assert!(!second.is_modified());
+  assert_eq!(second.state_vector_hash, first.state_vector_hash);
+
+  // An outdated hash returns the changes relative to the state vector of the client.
+  let third = test_client
+    .api_client
+    .get_collab_diff(
+      &workspace_id,
+      &object_id,
+      CollabDiffParams {
+        collab_type: CollabType::Unknown,
+        state_vector_hash: Some("outdated".to_string()),
+        state_vector: Some(first.encode_collab.unwrap().state_vector.to_vec()),
+      },
+    )
+    .await
+    .unwrap();
+  assert!(third.is_modified());
+  assert!(third.is_diff);
+}
+
+#[tokio::test]
+async fn cached_collab_refetched_after_update_test() {
+  let test_client = TestClient::new_user().await;
+  let workspace_id = test_client.workspace_id().await;
+  let object_id = create_test_collab(&test_client, &workspace_id, "hello").await;
+
+  let cache = InMemoryCollabCache::new();
+  let first = test_client
+    .api_client
+    .get_collab_diff(
+      &workspace_id,
+      &object_id,
+      CollabDiffParams {
+        collab_type: CollabType::Unknown,
+        state_vector_hash: None,
+        state_vector: None,
+      },
+    )
+    .await
+    .unwrap();
+  cache.put(
+    &object_id,
+    CachedCollab {
+      encode_collab: first.encode_collab.unwrap(),
+      state_vector_hash: first.state_vector_hash,
+    },
+  );
+
+  let encode_collab = test_encode_collab_v1(&object_id, "title", "world");
+  test_client
+    .api_client
+    .update_collab(CreateCollabParams {
+      workspace_id: workspace_id.clone(),
+      object_id: object_id.clone(),
+      encoded_collab_v1: encode_collab.encode_to_bytes().unwrap(),
+      collab_type: CollabType::Unknown,
+    })
+    .await
+    .unwrap();
+
+  let cached = cache.get(&object_id).unwrap();
+  let resp = test_client
+    .api_client
+    .get_collab_diff(
+      &workspace_id,
+      &object_id,
+      CollabDiffParams {
+        collab_type: CollabType::Unknown,
+        state_vector_hash: Some(cached.state_vector_hash.clone()),
+        state_vector: None,
+      },
+    )
+    .await
+    .unwrap();
+  assert!(resp.is_modified());
+  assert_ne!(resp.state_vector_hash, cached.state_vector_hash);
+
+  // The collab returned by get_collab is the same with or without the diff endpoint.
+  let full = test_client
+    .api_client
+    .get_collab(QueryCollabParams::new(
+      &object_id,
+      CollabType::Unknown,
+      &workspace_id,
+    ))
+    .await
+    .unwrap();
+  assert_eq!(
+    full.encode_collab.doc_state,
+    resp.encode_collab.unwrap().doc_state
+  );
+}
+
+#[test]
+fn file_collab_cache_test() {
+  let dir = tempfile::tempdir().unwrap();
+  let object_id = Uuid::new_v4().to_string();
+  let cached = CachedCollab {
+    encode_collab: test_encode_collab_v1(&object_id, "title", "hello"),
+    state_vector_hash: "hash".to_string(),
+  };
+
+  let cache = FileCollabCache::new(dir.path()).unwrap();
+  assert!(cache.get(&object_id).is_none());
+  cache.put(&object_id, cached.clone());
+
+  // The cache survives a restart of the client.
+  let cache = FileCollabCache::new(dir.path()).unwrap();
+  let loaded = cache.get(&object_id).unwrap();
+  assert_eq!(loaded.state_vector_hash, cached.state_vector_hash);
+  assert_eq!(
+    loaded.encode_collab.doc_state,
+    cached.encode_collab.doc_state
+  );
+
+  cache.invalidate(&object_id);
+  assert!(cache.get(&object_id).is_none());
+}
@@ -0,0 +1,113 @@
+use app_error::ErrorCode;
+use client_api_test::{
+  admin_user_client, generate_unique_registered_user_client, workspace_id_from_client,
+};
+use collab::core::collab::DataSource;
+use collab::core::origin::CollabOrigin;
+use collab::entity::EncodedCollab;
+use collab::preclude::Collab;
+use collab_entity::CollabType;
+use database_entity::dto::{CreateCollabParams, QueryCollabParams};
+use shared_entity::dto::collab_compaction_dto::CompactCollabQuery;
+use uuid::Uuid;
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+use yrs::{Map, ReadTxn, StateVector, Update};
+
+/// A collab whose keys were written by a client each, and removed.
+fn churned_encoded_collab(object_id: &str) -> EncodedCollab {
+  let mut collab = Collab::new_with_origin(CollabOrigin::Empty, object_id, vec![], false);
+  for i in 0..200 {
+    let mut editor = Collab::new_with_origin(CollabOrigin::Empty, object_id, vec![], false);
+    let update = {
+      let mut txn = editor.context.transact_mut();
+      editor.data.insert(&mut txn, format!("key{}", i), "value");
+      txn.encode_update_v1()
+    };
+    let mut txn = collab.context.transact_mut();
+    txn
+      .apply_update(Update::decode_v1(&update).unwrap())
+      .unwrap();
+    collab.data.remove(&mut txn, &format!("key{}", i));
+  }
+  {
+    let mut txn = collab.context.transact_mut();
+    collab.data.insert(&mut txn, "title", "compacted");
+  }
+  let txn = collab.transact();
+  EncodedCollab::new_v1(
+    txn.state_vector().encode_v1(),
+    txn.encode_state_as_update_v1(&StateVector::default()),
+  )
+}
+
+#[tokio::test]
+async fn admin_compacts_collab_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let object_id = Uuid::new_v4().to_string();
+  let encoded_collab = churned_encoded_collab(&object_id);
+  c.create_collab(CreateCollabParams {
+    object_id: object_id.clone(),
+    collab_type: CollabType::Unknown,
+    workspace_id: workspace_id.clone(),
+    encoded_collab_v1: encoded_collab.encode_to_bytes().unwrap(),
+  })
+  .await
+  .unwrap();
+
+  let query = CompactCollabQuery::default();
+  let err = c
+    .compact_collab(&workspace_id, &object_id, &query)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+
+  let admin = admin_user_client().await;
+  let result = admin
+    .compact_collab(&workspace_id, &object_id, &query)
+    .await
+    .unwrap();
+  assert!(result.compacted, "{:?}", result);
+  assert!(result.compacted_len * 2 <= result.doc_state_len);
+
+  // the content is the same, without the history
+  let compacted = c
+    .get_collab(QueryCollabParams::new(
+      &object_id,
+      CollabType::Unknown,
+      &workspace_id,
+    ))
+    .await
+    .unwrap()
+    .encode_collab;
+  assert!(compacted.doc_state.len() < encoded_collab.doc_state.len());
+  let compacted = Collab::new_with_source(
+    CollabOrigin::Empty,
+    &object_id,
+    DataSource::DocStateV1(compacted.doc_state.to_vec()),
+    vec![],
+    false,
+  )
+  .unwrap();
+  assert_eq!(compacted.to_json_value()["title"], "compacted");
+
+  // the state from before the compaction is kept as a snapshot
+  let snapshots = c
+    .get_snapshot_list(&workspace_id, &object_id)
+    .await
+    .unwrap();
+  let snapshot_id = result.snapshot_id.unwrap();
+  assert!(snapshots
+    .0
+    .iter()
+    .any(|snapshot| snapshot.snapshot_id == snapshot_id));
+
+  // there's nothing left to compact
+  let result = admin
+    .compact_collab(&workspace_id, &object_id, &query)
+    .await
+    .unwrap();
+  assert!(!result.compacted);
+  assert_eq!(result.skipped_reason.as_deref(), Some("no_gain"));
+}
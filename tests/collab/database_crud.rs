@@ -1,9 +1,12 @@
 use std::collections::HashMap;
 
+use app_error::ErrorCode;
 use client_api_test::{generate_unique_registered_user_client, workspace_id_from_client};
 use collab_database::entity::FieldType;
 use serde_json::json;
-use shared_entity::dto::workspace_dto::AFInsertDatabaseField;
+use shared_entity::dto::workspace_dto::{
+  AFInsertDatabaseField, AddDatatabaseRow, UpdateDatabaseFieldType,
+};
 
 #[tokio::test]
 async fn database_row_upsert_with_doc() {
@@ -330,3 +333,287 @@ async fn database_insert_row_with_doc() {
     Some("\nThis is a document of a database row".to_string())
   );
 }
+
+#[tokio::test]
+async fn database_batch_insert_rows() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let databases = c.list_databases(&workspace_id).await.unwrap();
+  let todo_db = &databases[0];
+  let initial_row_count = c
+    .list_database_row_ids(&workspace_id, &todo_db.id)
+    .await
+    .unwrap()
+    .len();
+  let my_num_field_id = c
+    .add_database_field(
+      &workspace_id,
+      &todo_db.id,
+      &AFInsertDatabaseField {
+        name: "MyNumberColumn".to_string(),
+        field_type: FieldType::Number.into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+
+  let rows = (0..3)
+    .map(|i| AddDatatabaseRow {
+      cells: HashMap::from([
+        (String::from("Description"), json!(format!("task {}", i))),
+        (String::from("Status"), json!("Doing")),
+        (my_num_field_id.clone(), json!(i.to_string())),
+      ]),
+      document: None,
+    })
+    .collect::<Vec<_>>();
+  let row_ids = c
+    .batch_add_database_items(&workspace_id, &todo_db.id, rows, false)
+    .await
+    .unwrap();
+  assert_eq!(row_ids.len(), 3);
+
+  let row_ids_ref: Vec<&str> = row_ids.iter().map(|id| id.as_str()).collect();
+  let row_details = c
+    .list_database_row_details(&workspace_id, &todo_db.id, &row_ids_ref, false)
+    .await
+    .unwrap();
+  assert_eq!(row_details.len(), 3);
+  for row_detail in &row_details {
+    assert_eq!(row_detail.cells["Status"], "Doing");
+  }
+
+  // an invalid row rejects the whole batch
+  let rows = vec![
+    AddDatatabaseRow {
+      cells: HashMap::from([(String::from("Description"), json!("valid"))]),
+      document: None,
+    },
+    AddDatatabaseRow {
+      cells: HashMap::from([(String::from("MyNumberColumn"), json!("not a number"))]),
+      document: None,
+    },
+  ];
+  let err = c
+    .batch_add_database_items(&workspace_id, &todo_db.id, rows, false)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequest);
+  let all_rows = c
+    .list_database_row_ids(&workspace_id, &todo_db.id)
+    .await
+    .unwrap();
+  assert_eq!(all_rows.len(), initial_row_count + 3);
+
+  let rows = vec![AddDatatabaseRow {
+    cells: HashMap::from([(String::from("UnknownColumn"), json!("value"))]),
+    document: None,
+  }];
+  let err = c
+    .batch_add_database_items(&workspace_id, &todo_db.id, rows, false)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequest);
+
+  let rows = (0..101)
+    .map(|_| AddDatatabaseRow {
+      cells: HashMap::new(),
+      document: None,
+    })
+    .collect::<Vec<_>>();
+  let err = c
+    .batch_add_database_items(&workspace_id, &todo_db.id, rows, false)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequest);
+}
+
+#[tokio::test]
+async fn database_insert_row_with_new_select_option() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let databases = c.list_databases(&workspace_id).await.unwrap();
+  let todo_db = &databases[0];
+
+  let row = || AddDatatabaseRow {
+    cells: HashMap::from([(String::from("Status"), json!("Blocked"))]),
+    document: None,
+  };
+  let err = c
+    .batch_add_database_items(&workspace_id, &todo_db.id, vec![row()], false)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequest);
+
+  let row_ids = c
+    .batch_add_database_items(&workspace_id, &todo_db.id, vec![row(), row()], true)
+    .await
+    .unwrap();
+  let row_details = c
+    .list_database_row_details(&workspace_id, &todo_db.id, &[&row_ids[0]], false)
+    .await
+    .unwrap();
+  assert_eq!(row_details[0].cells["Status"], "Blocked");
+
+  // the option is only created once
+  let fields = c
+    .get_database_fields(&workspace_id, &todo_db.id)
+    .await
+    .unwrap();
+  let status_field = fields.iter().find(|f| f.name == "Status").unwrap();
+  let options = status_field.type_option["content"]["options"]
+    .as_array()
+    .unwrap();
+  let blocked = options
+    .iter()
+    .filter(|option| option["name"] == "Blocked")
+    .count();
+  assert_eq!(blocked, 1);
+}
+
+#[tokio::test]
+async fn database_update_and_delete_row() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let databases = c.list_databases(&workspace_id).await.unwrap();
+  let todo_db = &databases[0];
+
+  let row_id = c
+    .add_database_item(
+      &workspace_id,
+      &todo_db.id,
+      HashMap::from([(String::from("Description"), json!("my task"))]),
+      None,
+    )
+    .await
+    .unwrap();
+  c.update_database_item(
+    &workspace_id,
+    &todo_db.id,
+    &row_id,
+    HashMap::from([(String::from("Status"), json!("Done"))]),
+    None,
+    false,
+  )
+  .await
+  .unwrap();
+  let row_details = c
+    .list_database_row_details(&workspace_id, &todo_db.id, &[&row_id], false)
+    .await
+    .unwrap();
+  assert_eq!(row_details[0].cells["Description"], "my task");
+  assert_eq!(row_details[0].cells["Status"], "Done");
+
+  // rows which are not part of the database can not be updated
+  let err = c
+    .update_database_item(
+      &workspace_id,
+      &todo_db.id,
+      &uuid::Uuid::new_v4().to_string(),
+      HashMap::from([(String::from("Status"), json!("Done"))]),
+      None,
+      false,
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+
+  c.delete_database_item(&workspace_id, &todo_db.id, &row_id)
+    .await
+    .unwrap();
+  let rows = c
+    .list_database_row_ids(&workspace_id, &todo_db.id)
+    .await
+    .unwrap();
+  assert!(rows.iter().all(|row| row.id != row_id));
+  let err = c
+    .delete_database_item(&workspace_id, &todo_db.id, &row_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+}
+
+#[tokio::test]
+async fn database_field_type_conversion() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let databases = c.list_databases(&workspace_id).await.unwrap();
+  let todo_db = &databases[0];
+  let field_id = c
+    .add_database_field(
+      &workspace_id,
+      &todo_db.id,
+      &AFInsertDatabaseField {
+        name: "Estimate".to_string(),
+        field_type: FieldType::RichText.into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+  let rows = ["3", "unknown"]
+    .into_iter()
+    .map(|estimate| AddDatatabaseRow {
+      cells: HashMap::from([(field_id.clone(), json!(estimate))]),
+      document: None,
+    })
+    .collect::<Vec<_>>();
+  let row_ids = c
+    .batch_add_database_items(&workspace_id, &todo_db.id, rows, false)
+    .await
+    .unwrap();
+
+  let task = c
+    .update_database_field_type(
+      &workspace_id,
+      &todo_db.id,
+      &field_id,
+      &UpdateDatabaseFieldType {
+        field_type: FieldType::Number.into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+  let mut completed = None;
+  for _ in 0..30 {
+    let task = c
+      .get_database_field_conversion(&workspace_id, &todo_db.id, &task.task_id.to_string())
+      .await
+      .unwrap();
+    if task.status == 2 {
+      completed = Some(task);
+      break;
+    }
+    assert_ne!(task.status, 3, "{:?}", task.error);
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+  }
+  let task = completed.expect("the conversion completes");
+  assert_eq!(task.converted_rows, task.total_rows);
+  assert_eq!(task.report.inconvertible_cells.len(), 1);
+  assert_eq!(task.report.inconvertible_cells[0].row_id, row_ids[1]);
+  assert_eq!(task.report.inconvertible_cells[0].value, json!("unknown"));
+
+  let fields = c
+    .get_database_fields(&workspace_id, &todo_db.id)
+    .await
+    .unwrap();
+  let field = fields.iter().find(|f| f.id == field_id).unwrap();
+  assert_eq!(field.field_type, format!("{:?}", FieldType::Number));
+
+  // a field can't be converted to the type it already has
+  let err = c
+    .update_database_field_type(
+      &workspace_id,
+      &todo_db.id,
+      &field_id,
+      &UpdateDatabaseFieldType {
+        field_type: FieldType::Number.into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequest);
+}
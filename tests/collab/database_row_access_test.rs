@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use app_error::ErrorCode;
+use client_api_test::TestClient;
+use collab_database::entity::FieldType;
+use collab_entity::CollabType;
+use database_entity::dto::{AFRole, QueryCollabParams};
+use serde_json::json;
+use shared_entity::dto::workspace_dto::{AFInsertDatabaseField, UpsertDatabaseRowAccess};
+
+#[tokio::test]
+async fn restricted_rows_are_only_visible_to_the_members_they_name_test() {
+  let owner = TestClient::new_user().await;
+  let member = TestClient::new_user().await;
+  let workspace_id = owner.workspace_id().await;
+  owner
+    .invite_and_accepted_workspace_member(&workspace_id, &member, AFRole::Member)
+    .await
+    .unwrap();
+  let database_id = owner
+    .api_client
+    .list_databases(&workspace_id)
+    .await
+    .unwrap()[0]
+    .id
+    .clone();
+
+  let field_id = owner
+    .api_client
+    .add_database_field(
+      &workspace_id,
+      &database_id,
+      &AFInsertDatabaseField {
+        name: "Owner".to_string(),
+        field_type: FieldType::RichText.into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+  let member_row_id = owner
+    .api_client
+    .add_database_item(
+      &workspace_id,
+      &database_id,
+      HashMap::from([("Owner".to_string(), json!(member.email().await))]),
+      None,
+    )
+    .await
+    .unwrap();
+  let other_row_id = owner
+    .api_client
+    .add_database_item(
+      &workspace_id,
+      &database_id,
+      HashMap::from([("Owner".to_string(), json!("someone@appflowy.io"))]),
+      None,
+    )
+    .await
+    .unwrap();
+
+  // only the owner of the workspace can restrict the rows
+  let params = UpsertDatabaseRowAccess {
+    field_id: field_id.clone(),
+    restricted_rows: true,
+  };
+  let err = member
+    .api_client
+    .upsert_database_row_access(&workspace_id, &database_id, &params)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+  let rule = owner
+    .api_client
+    .upsert_database_row_access(&workspace_id, &database_id, &params)
+    .await
+    .unwrap();
+  assert_eq!(rule.field_id, field_id);
+  assert!(rule.restricted_rows);
+
+  let member_rows = member
+    .api_client
+    .list_database_row_ids(&workspace_id, &database_id)
+    .await
+    .unwrap();
+  assert!(member_rows.iter().any(|row| row.id == member_row_id));
+  assert!(!member_rows.iter().any(|row| row.id == other_row_id));
+  let owner_rows = owner
+    .api_client
+    .list_database_row_ids(&workspace_id, &database_id)
+    .await
+    .unwrap();
+  assert!(owner_rows.iter().any(|row| row.id == other_row_id));
+
+  let details = member
+    .api_client
+    .list_database_row_details(
+      &workspace_id,
+      &database_id,
+      &[&member_row_id, &other_row_id],
+      false,
+    )
+    .await
+    .unwrap();
+  assert_eq!(details.len(), 1);
+  assert_eq!(details[0].id, member_row_id);
+
+  // once the rule is removed, every member sees every row again
+  owner
+    .api_client
+    .delete_database_row_access(&workspace_id, &database_id)
+    .await
+    .unwrap();
+  let member_rows = member
+    .api_client
+    .list_database_row_ids(&workspace_id, &database_id)
+    .await
+    .unwrap();
+  assert!(member_rows.iter().any(|row| row.id == other_row_id));
+}
+
+#[tokio::test]
+async fn rows_created_after_the_restriction_are_hidden_until_they_name_the_member_test() {
+  let owner = TestClient::new_user().await;
+  let member = TestClient::new_user().await;
+  let workspace_id = owner.workspace_id().await;
+  owner
+    .invite_and_accepted_workspace_member(&workspace_id, &member, AFRole::Member)
+    .await
+    .unwrap();
+  let database_id = owner
+    .api_client
+    .list_databases(&workspace_id)
+    .await
+    .unwrap()[0]
+    .id
+    .clone();
+  let field_id = owner
+    .api_client
+    .add_database_field(
+      &workspace_id,
+      &database_id,
+      &AFInsertDatabaseField {
+        name: "Owner".to_string(),
+        field_type: FieldType::RichText.into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+  owner
+    .api_client
+    .upsert_database_row_access(
+      &workspace_id,
+      &database_id,
+      &UpsertDatabaseRowAccess {
+        field_id,
+        restricted_rows: true,
+      },
+    )
+    .await
+    .unwrap();
+  // load the restricted rows before the rows below are created
+  member
+    .api_client
+    .list_database_row_ids(&workspace_id, &database_id)
+    .await
+    .unwrap();
+
+  let other_row_id = add_row(
+    &owner,
+    &workspace_id,
+    &database_id,
+    json!("someone@appflowy.io"),
+  )
+  .await;
+  let member_uid = member.uid().await.to_string();
+  let member_row_id = add_row(&owner, &workspace_id, &database_id, json!(member_uid)).await;
+  // the name of a member isn't enough, they could change it to the name of another member
+  let member_name = member.get_user_profile().await.name.unwrap();
+  let named_row_id = add_row(&owner, &workspace_id, &database_id, json!(member_name)).await;
+
+  let err = member
+    .api_client
+    .get_collab(QueryCollabParams::new(
+      &other_row_id,
+      CollabType::DatabaseRow,
+      &workspace_id,
+    ))
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+  let err = member
+    .api_client
+    .get_collab(QueryCollabParams::new(
+      &named_row_id,
+      CollabType::DatabaseRow,
+      &workspace_id,
+    ))
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+
+  // the rows were loaded again, and the row naming the member by their uid is visible to them
+  tokio::time::sleep(Duration::from_secs(1)).await;
+  member
+    .api_client
+    .get_collab(QueryCollabParams::new(
+      &member_row_id,
+      CollabType::DatabaseRow,
+      &workspace_id,
+    ))
+    .await
+    .unwrap();
+  let member_rows = member
+    .api_client
+    .list_database_row_ids(&workspace_id, &database_id)
+    .await
+    .unwrap();
+  assert!(member_rows.iter().any(|row| row.id == member_row_id));
+  assert!(!member_rows.iter().any(|row| row.id == other_row_id));
+  assert!(!member_rows.iter().any(|row| row.id == named_row_id));
+}
+
+async fn add_row(
+  client: &TestClient,
+  workspace_id: &str,
+  database_id: &str,
+  owner_cell: serde_json::Value,
+) -> String {
+  client
+    .api_client
+    .add_database_item(
+      workspace_id,
+      database_id,
+      HashMap::from([("Owner".to_string(), owner_cell)]),
+      None,
+    )
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn restricted_member_can_not_restore_hidden_row_snapshot_test() {
+  let owner = TestClient::new_user().await;
+  let member = TestClient::new_user().await;
+  let workspace_id = owner.workspace_id().await;
+  owner
+    .invite_and_accepted_workspace_member(&workspace_id, &member, AFRole::Member)
+    .await
+    .unwrap();
+  let database_id = owner
+    .api_client
+    .list_databases(&workspace_id)
+    .await
+    .unwrap()[0]
+    .id
+    .clone();
+  let field_id = owner
+    .api_client
+    .add_database_field(
+      &workspace_id,
+      &database_id,
+      &AFInsertDatabaseField {
+        name: "Owner".to_string(),
+        field_type: FieldType::RichText.into(),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+  let other_row_id = add_row(
+    &owner,
+    &workspace_id,
+    &database_id,
+    json!("someone@appflowy.io"),
+  )
+  .await;
+  owner
+    .api_client
+    .upsert_database_row_access(
+      &workspace_id,
+      &database_id,
+      &UpsertDatabaseRowAccess {
+        field_id,
+        restricted_rows: true,
+      },
+    )
+    .await
+    .unwrap();
+
+  let row_snapshot = owner
+    .create_snapshot(&workspace_id, &other_row_id, CollabType::DatabaseRow)
+    .await
+    .unwrap();
+  let database_snapshot = owner
+    .create_snapshot(&workspace_id, &database_id, CollabType::Database)
+    .await
+    .unwrap();
+
+  // the versions of a hidden row can neither be listed nor restored
+  let err = member
+    .api_client
+    .get_snapshots(&workspace_id, &other_row_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+  let err = member
+    .api_client
+    .restore_snapshot(
+      &workspace_id,
+      &other_row_id,
+      row_snapshot.snapshot_id,
+      CollabType::DatabaseRow,
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+
+  // nor can the restricted database, which would bring back the hidden rows
+  let err = member
+    .api_client
+    .restore_snapshot(
+      &workspace_id,
+      &database_id,
+      database_snapshot.snapshot_id,
+      CollabType::Database,
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+
+  // the owner still can
+  owner
+    .api_client
+    .restore_snapshot(
+      &workspace_id,
+      &other_row_id,
+      row_snapshot.snapshot_id,
+      CollabType::DatabaseRow,
+    )
+    .await
+    .unwrap();
+}
@@ -0,0 +1,181 @@
+use std::time::Duration;
+
+use app_error::ErrorCode;
+use client_api_test::{assert_server_collab, TestClient};
+use collab::core::collab::DataSource;
+use collab::core::origin::CollabOrigin;
+use collab::preclude::Collab;
+use collab_entity::CollabType;
+use database_entity::dto::{AFRole, AFWorkspaceSettingsChange, QueryCollabParams};
+use serde_json::json;
+use tokio::time::sleep;
+
+#[tokio::test]
+async fn editing_lock_disabled_by_default_test() {
+  let mut client = TestClient::new_user().await;
+  let workspace_id = client.workspace_id().await;
+  let object_id = client
+    .create_and_edit_collab(&workspace_id, CollabType::Unknown)
+    .await;
+
+  let err = client
+    .api_client
+    .acquire_editing_lock(&workspace_id, &object_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequest);
+}
+
+#[tokio::test]
+async fn editing_lock_rejects_updates_from_other_members_test() {
+  let collab_type = CollabType::Unknown;
+  let mut owner = TestClient::new_user().await;
+  let mut editor = TestClient::new_user().await;
+  let workspace_id = owner.workspace_id().await;
+  owner
+    .api_client
+    .update_workspace_settings(
+      &workspace_id,
+      &AFWorkspaceSettingsChange::new().enable_editing_lock(true),
+    )
+    .await
+    .unwrap();
+
+  let object_id = owner
+    .create_and_edit_collab(&workspace_id, collab_type.clone())
+    .await;
+  owner
+    .invite_and_accepted_workspace_member(&workspace_id, &editor, AFRole::Member)
+    .await
+    .unwrap();
+  editor
+    .open_collab(&workspace_id, &object_id, collab_type.clone())
+    .await;
+
+  let mut lock_rx = owner.ws_client.subscribe_editing_lock();
+  let lock = editor
+    .api_client
+    .acquire_editing_lock(&workspace_id, &object_id)
+    .await
+    .unwrap();
+  assert_eq!(lock.uid, editor.uid().await);
+
+  // the owner is notified about the lock
+  let changed = tokio::time::timeout(Duration::from_secs(10), lock_rx.recv())
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(changed.object_id, object_id);
+  assert_eq!(changed.lock.unwrap().uid, lock.uid);
+
+  let err = owner
+    .api_client
+    .acquire_editing_lock(&workspace_id, &object_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::EditingLocked);
+
+  // updates of the owner are rejected while the editor holds the lock
+  owner.insert_into(&object_id, "rejected", "owner").await;
+  sleep(Duration::from_secs(2)).await;
+  editor.insert_into(&object_id, "accepted", "editor").await;
+  editor.wait_object_sync_complete(&object_id).await.unwrap();
+  assert_server_collab(
+    &workspace_id,
+    &mut owner.api_client,
+    &object_id,
+    &collab_type,
+    10,
+    json!({ "accepted": "editor" }),
+  )
+  .await
+  .unwrap();
+  let data = owner
+    .api_client
+    .get_collab(QueryCollabParams::new(
+      &object_id,
+      collab_type.clone(),
+      &workspace_id,
+    ))
+    .await
+    .unwrap();
+  let json = Collab::new_with_source(
+    CollabOrigin::Empty,
+    &object_id,
+    DataSource::DocStateV1(data.encode_collab.doc_state.to_vec()),
+    vec![],
+    false,
+  )
+  .unwrap()
+  .to_json_value();
+  assert!(json.get("rejected").is_none());
+
+  // a member can not release the lock held by another member
+  let err = owner
+    .api_client
+    .release_editing_lock(&workspace_id, &object_id, false)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::EditingLocked);
+
+  // but the owner of the workspace can force it
+  owner
+    .api_client
+    .release_editing_lock(&workspace_id, &object_id, true)
+    .await
+    .unwrap();
+  let lock = owner
+    .api_client
+    .get_editing_lock(&workspace_id, &object_id)
+    .await
+    .unwrap();
+  assert!(lock.is_none());
+}
+
+#[tokio::test]
+async fn editing_lock_released_on_disconnect_test() {
+  let collab_type = CollabType::Unknown;
+  let mut owner = TestClient::new_user().await;
+  let workspace_id = owner.workspace_id().await;
+  owner
+    .api_client
+    .update_workspace_settings(
+      &workspace_id,
+      &AFWorkspaceSettingsChange::new().enable_editing_lock(true),
+    )
+    .await
+    .unwrap();
+  let object_id = owner
+    .create_and_edit_collab(&workspace_id, collab_type.clone())
+    .await;
+
+  owner
+    .api_client
+    .acquire_editing_lock(&workspace_id, &object_id)
+    .await
+    .unwrap();
+  owner
+    .api_client
+    .heartbeat_editing_lock(&workspace_id, &object_id)
+    .await
+    .unwrap();
+  owner.disconnect().await;
+
+  let mut released = false;
+  for _ in 0..10 {
+    sleep(Duration::from_secs(1)).await;
+    let lock = owner
+      .api_client
+      .get_editing_lock(&workspace_id, &object_id)
+      .await
+      .unwrap();
+    if lock.is_none() {
+      released = true;
+      break;
+    }
+  }
+  assert!(
+    released,
+    "lock should be released when the holder disconnects"
+  );
+}
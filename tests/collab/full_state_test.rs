@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use client_api::entity::AFRole;
+use collab_entity::CollabType;
+use serde_json::json;
+use tokio::time::sleep;
+
+use client_api_test::{assert_client_collab_include_value, TestClient};
+
+/// The first app version decoding the full state message, the one the test server is configured
+/// with as `APPFLOWY_COLLAB_FULL_STATE_SYNC_MIN_CLIENT_VERSION`.
+const FULL_STATE_CLIENT_VERSION: &str = "0.9.0";
+
+#[tokio::test]
+async fn client_catching_up_with_a_large_delta_gets_the_full_state_test() {
+  let collab_type = CollabType::Unknown;
+  let mut client_1 = TestClient::new_user().await;
+  let mut client_2 = TestClient::new_user_with_client_version(FULL_STATE_CLIENT_VERSION).await;
+  let workspace_id = client_1.workspace_id().await;
+  let object_id = client_1
+    .create_and_edit_collab(&workspace_id, collab_type.clone())
+    .await;
+  client_1
+    .invite_and_accepted_workspace_member(&workspace_id, &client_2, AFRole::Member)
+    .await
+    .unwrap();
+  client_2
+    .open_collab(&workspace_id, &object_id, collab_type.clone())
+    .await;
+  client_2
+    .wait_object_sync_complete(&object_id)
+    .await
+    .unwrap();
+  client_2.ws_client.disable_receive_message();
+
+  // the missing updates are most of the doc state, which is sent instead
+  let content = "a".repeat(256 * 1024);
+  {
+    let mut lock = client_1
+      .collabs
+      .get_mut(&object_id)
+      .unwrap()
+      .collab
+      .write()
+      .await;
+    lock.insert("content", content.clone());
+  }
+  client_1
+    .wait_object_sync_complete(&object_id)
+    .await
+    .unwrap();
+  sleep(Duration::from_secs(2)).await;
+
+  client_2.ws_client.enable_receive_message();
+  assert_client_collab_include_value(&mut client_2, &object_id, json!({ "content": content }))
+    .await
+    .unwrap();
+}
@@ -1,10 +1,18 @@
 mod awareness_test;
+mod bandwidth_test;
+mod collab_cache_test;
 mod collab_curd_test;
 mod collab_embedding_test;
+mod compaction_test;
 mod database_crud;
+mod database_row_access_test;
+mod editing_lock_test;
+mod full_state_test;
 mod missing_update_test;
 mod multi_devices_edit;
+mod opaque_collab_test;
 mod permission_test;
+mod recovery_test;
 mod single_device_edit;
 mod snapshot_test;
 mod storage_test;
@@ -0,0 +1,83 @@
+use app_error::ErrorCode;
+use client_api_test::{assert_client_collab_include_value, TestClient};
+use collab_entity::CollabType;
+use database_entity::dto::{AFRole, CollabMode};
+use serde_json::json;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn opaque_collab_syncs_between_two_clients_test() {
+  let collab_type = CollabType::Unknown;
+  let mut owner = TestClient::new_user().await;
+  let mut editor = TestClient::new_user().await;
+  let workspace_id = owner.workspace_id().await;
+  owner
+    .invite_and_accepted_workspace_member(&workspace_id, &editor, AFRole::Member)
+    .await
+    .unwrap();
+
+  let object_id = Uuid::new_v4().to_string();
+  owner
+    .api_client
+    .set_collab_mode(&workspace_id, &object_id, CollabMode::Opaque)
+    .await
+    .unwrap();
+  let mode = editor
+    .api_client
+    .get_collab_mode(&workspace_id, &object_id)
+    .await
+    .unwrap();
+  assert_eq!(mode, CollabMode::Opaque);
+
+  // the server relays the updates without decoding them
+  owner
+    .open_collab(&workspace_id, &object_id, collab_type.clone())
+    .await;
+  owner
+    .insert_into(&object_id, "title", "written by owner")
+    .await;
+  owner.wait_object_sync_complete(&object_id).await.unwrap();
+
+  editor
+    .open_collab(&workspace_id, &object_id, collab_type.clone())
+    .await;
+  editor.wait_object_sync_complete(&object_id).await.unwrap();
+  assert_client_collab_include_value(
+    &mut editor,
+    &object_id,
+    json!({ "title": "written by owner" }),
+  )
+  .await
+  .unwrap();
+
+  editor
+    .insert_into(&object_id, "reply", "written by editor")
+    .await;
+  editor.wait_object_sync_complete(&object_id).await.unwrap();
+  assert_client_collab_include_value(
+    &mut owner,
+    &object_id,
+    json!({ "title": "written by owner", "reply": "written by editor" }),
+  )
+  .await
+  .unwrap();
+}
+
+#[tokio::test]
+async fn opaque_collab_rejects_plaintext_features_test() {
+  let mut client = TestClient::new_user().await;
+  let workspace_id = client.workspace_id().await;
+  let object_id = Uuid::new_v4().to_string();
+  client
+    .api_client
+    .set_collab_mode(&workspace_id, &object_id, CollabMode::Opaque)
+    .await
+    .unwrap();
+
+  let err = client
+    .api_client
+    .get_document_outline(&workspace_id, &object_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::EncryptedCollab);
+}
@@ -5,7 +5,7 @@ use std::time::Duration;
 use assert_json_diff::{assert_json_eq, assert_json_include};
 use collab_entity::CollabType;
 use serde_json::json;
-use tokio::time::sleep;
+use tokio::time::{sleep, timeout};
 use uuid::Uuid;
 
 use client_api_test::{
@@ -197,6 +197,64 @@ async fn edit_collab_with_readonly_permission_test() {
   .unwrap();
 }
 
+#[tokio::test]
+async fn reader_update_is_denied_test() {
+  let collab_type = CollabType::Unknown;
+  let mut client_1 = TestClient::new_user().await;
+  let mut client_2 = TestClient::new_user().await;
+
+  let workspace_id = client_1.workspace_id().await;
+  let object_id = client_1
+    .create_and_edit_collab(&workspace_id, collab_type.clone())
+    .await;
+  client_1
+    .insert_into(&object_id, "title", "hello world")
+    .await;
+  client_1
+    .wait_object_sync_complete(&object_id)
+    .await
+    .unwrap();
+  client_1
+    .invite_and_accepted_workspace_member(&workspace_id, &client_2, AFRole::Guest)
+    .await
+    .unwrap();
+
+  // the reader syncs the collab and is told it's read-only
+  let mut access_rx = client_2.ws_client.subscribe_collab_access();
+  let mut denied_rx = client_2.ws_client.subscribe_permission_denied();
+  client_2
+    .open_collab(&workspace_id, &object_id, collab_type.clone())
+    .await;
+  let access = timeout(Duration::from_secs(10), access_rx.recv())
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(access.object_id, object_id);
+  assert!(access.read_only);
+  assert!(client_2.ws_client.is_collab_read_only(&object_id));
+  assert_client_collab_include_value(&mut client_2, &object_id, json!({ "title": "hello world" }))
+    .await
+    .unwrap();
+
+  // its update is answered with an error and isn't applied
+  client_2.insert_into(&object_id, "name", "AppFlowy").await;
+  let denied = timeout(Duration::from_secs(10), denied_rx.recv())
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(denied.object_id, object_id);
+  assert_server_collab(
+    &workspace_id,
+    &mut client_1.api_client,
+    &object_id,
+    &collab_type,
+    5,
+    json!({ "title": "hello world" }),
+  )
+  .await
+  .unwrap();
+}
+
 #[tokio::test]
 async fn edit_collab_with_read_and_write_permission_test() {
   let collab_type = CollabType::Unknown;
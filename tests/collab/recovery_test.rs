@@ -0,0 +1,30 @@
+use app_error::ErrorCode;
+use client_api_test::{admin_user_client, TestClient};
+use shared_entity::dto::collab_recovery_dto::ListCollabVerificationReportsQuery;
+
+#[tokio::test]
+async fn collab_verification_reports_require_admin_test() {
+  let client = TestClient::new_user_without_ws_conn().await;
+  let err = client
+    .api_client
+    .list_collab_verification_reports(&ListCollabVerificationReportsQuery::default())
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+  let err = client
+    .api_client
+    .confirm_collab_repair(1)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+
+  let admin = admin_user_client().await;
+  let reports = admin
+    .list_collab_verification_reports(&ListCollabVerificationReportsQuery::default())
+    .await
+    .unwrap();
+  assert!(reports.iter().all(|report| report.repaired_at.is_none()));
+
+  let err = admin.confirm_collab_repair(i64::MAX).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+}
@@ -1,4 +1,4 @@
-use client_api_test::{assert_server_collab, TestClient};
+use client_api_test::{assert_client_collab_within_secs, assert_server_collab, TestClient};
 use collab::core::collab::DataSource;
 use collab::core::origin::CollabOrigin;
 use collab::entity::EncodedCollab;
@@ -58,6 +58,79 @@ async fn read_write_snapshot() {
   verify_snapshot_state(&c, &wid, &oid, &m2.snapshot_id, json!({"title": "t2"})).await;
 }
 
+#[tokio::test]
+async fn restore_snapshot_test() {
+  let mut c = TestClient::new_user().await;
+  let wid = c.workspace_id().await;
+  let oid = c.create_and_edit_collab(&wid, CollabType::Unknown).await;
+  c.open_collab(&wid, &oid, CollabType::Unknown).await;
+  c.insert_into(&oid, "title", "t1").await;
+  c.wait_object_sync_complete(&oid).await.unwrap();
+  assert_server_collab(
+    &wid,
+    &mut c.api_client,
+    &oid,
+    &CollabType::Unknown,
+    10,
+    json!({"title": "t1"}),
+  )
+  .await
+  .unwrap();
+  let m1 = c
+    .create_snapshot(&wid, &oid, CollabType::Unknown)
+    .await
+    .unwrap();
+
+  c.insert_into(&oid, "title", "t2").await;
+  c.wait_object_sync_complete(&oid).await.unwrap();
+  assert_server_collab(
+    &wid,
+    &mut c.api_client,
+    &oid,
+    &CollabType::Unknown,
+    10,
+    json!({"title": "t2"}),
+  )
+  .await
+  .unwrap();
+
+  let pre_restore = c
+    .api_client
+    .restore_snapshot(&wid, &oid, m1.snapshot_id, CollabType::Unknown)
+    .await
+    .unwrap();
+  // the connected client converges without reloading the collab
+  assert_client_collab_within_secs(&mut c, &oid, "title", json!({"title": "t1"}), 30).await;
+  assert_server_collab(
+    &wid,
+    &mut c.api_client,
+    &oid,
+    &CollabType::Unknown,
+    10,
+    json!({"title": "t1"}),
+  )
+  .await
+  .unwrap();
+
+  // the content before the restore is kept, taken by the user
+  verify_snapshot_state(
+    &c,
+    &wid,
+    &oid,
+    &pre_restore.snapshot_id,
+    json!({"title": "t2"}),
+  )
+  .await;
+  let uid = c.uid().await;
+  let versions = c.api_client.get_snapshots(&wid, &oid).await.unwrap();
+  assert_eq!(versions.len(), 2);
+  assert_eq!(versions[0].snapshot_id, pre_restore.snapshot_id);
+  assert!(versions
+    .iter()
+    .all(|version| version.created_by == Some(uid)));
+  assert!(versions.iter().all(|version| version.len.unwrap_or(0) > 0));
+}
+
 async fn verify_snapshot_state(
   c: &TestClient,
   workspace_id: &str,
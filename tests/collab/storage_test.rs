@@ -8,14 +8,21 @@ use collab::preclude::{Doc, Transact};
 use collab_entity::CollabType;
 use database::collab::CollabMetadata;
 use database_entity::dto::{
-  CreateCollabParams, DeleteCollabParams, QueryCollab, QueryCollabParams, QueryCollabResult,
+  AFRole, CreateCollabParams, DeleteCollabParams, QueryCollab, QueryCollabParams, QueryCollabResult,
+};
+use shared_entity::dto::workspace_dto::{
+  BatchGetCollabItem, BulkCreateCollabItem, BulkCreateCollabResult,
 };
 use sqlx::types::Uuid;
 use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::timeout;
 use workspace_template::document::getting_started::GettingStartedTemplate;
 use workspace_template::WorkspaceTemplateBuilder;
 
-use crate::collab::util::{redis_connection_manager, test_encode_collab_v1};
+use crate::collab::util::{
+  make_big_collab_doc_state, redis_connection_manager, test_encode_collab_v1,
+};
 
 #[tokio::test]
 async fn success_insert_collab_test() {
@@ -154,6 +161,144 @@ async fn success_part_batch_get_collab_test() {
   }
 }
 
+#[tokio::test]
+async fn batch_get_collabs_per_item_result_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let existing_id = Uuid::new_v4().to_string();
+  let missing_id = Uuid::new_v4().to_string();
+  let encode_collab = test_encode_collab_v1(&existing_id, "title", "hello world");
+  c.create_collab(CreateCollabParams {
+    object_id: existing_id.clone(),
+    encoded_collab_v1: encode_collab.encode_to_bytes().unwrap(),
+    collab_type: CollabType::Unknown,
+    workspace_id: workspace_id.clone(),
+  })
+  .await
+  .unwrap();
+
+  let queries = vec![
+    QueryCollab {
+      object_id: existing_id.clone(),
+      collab_type: CollabType::Unknown,
+    },
+    QueryCollab {
+      object_id: missing_id.clone(),
+      collab_type: CollabType::Unknown,
+    },
+  ];
+  let items = c
+    .batch_get_collabs(&workspace_id, queries.clone())
+    .await
+    .unwrap();
+  assert_eq!(items.len(), 2);
+  match items.get(&existing_id).unwrap() {
+    BatchGetCollabItem::Success(collab) => {
+      assert_eq!(collab.object_id, existing_id);
+      assert_eq!(collab.encode_collab.doc_state, encode_collab.doc_state);
+    },
+    item => panic!("unexpected item {:?}", item),
+  }
+  match items.get(&missing_id).unwrap() {
+    BatchGetCollabItem::Failed { code, .. } => assert_eq!(*code, ErrorCode::RecordNotFound),
+    item => panic!("unexpected item {:?}", item),
+  }
+
+  // the access is checked for each collab
+  let (other, _user) = generate_unique_registered_user_client().await;
+  let items = other
+    .batch_get_collabs(&workspace_id, queries)
+    .await
+    .unwrap();
+  assert!(matches!(
+    items.get(&existing_id).unwrap(),
+    BatchGetCollabItem::Failed {
+      code: ErrorCode::NotEnoughPermissions,
+      ..
+    }
+  ));
+
+  let queries = (0..51)
+    .map(|_| QueryCollab {
+      object_id: Uuid::new_v4().to_string(),
+      collab_type: CollabType::Unknown,
+    })
+    .collect();
+  let err = c
+    .batch_get_collabs(&workspace_id, queries)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequest);
+}
+
+#[tokio::test]
+async fn bulk_create_collabs_all_or_nothing_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let item = |object_id: &str| BulkCreateCollabItem {
+    object_id: object_id.to_string(),
+    collab_type: CollabType::Unknown,
+    encoded_collab: test_encode_collab_v1(object_id, "title", "hello world")
+      .encode_to_bytes()
+      .unwrap(),
+  };
+
+  let object_ids = vec![Uuid::new_v4().to_string(), Uuid::new_v4().to_string()];
+  let resp = c
+    .bulk_create_collabs(
+      &workspace_id,
+      object_ids.iter().map(|id| item(id)).collect(),
+    )
+    .await
+    .unwrap();
+  assert!(resp.is_created());
+  assert_eq!(resp.items.len(), 2);
+  for object_id in &object_ids {
+    c.get_collab(QueryCollabParams::new(
+      object_id,
+      CollabType::Unknown,
+      &workspace_id,
+    ))
+    .await
+    .unwrap();
+  }
+
+  // an invalid collab fails the whole batch
+  let valid_id = Uuid::new_v4().to_string();
+  let invalid_id = Uuid::new_v4().to_string();
+  let mut invalid = item(&invalid_id);
+  invalid.encoded_collab = vec![1, 2, 3];
+  let resp = c
+    .bulk_create_collabs(&workspace_id, vec![item(&valid_id), invalid])
+    .await
+    .unwrap();
+  assert!(!resp.is_created());
+  assert_eq!(
+    resp.items.get(&valid_id).unwrap(),
+    &BulkCreateCollabResult::NotCreated
+  );
+  assert!(matches!(
+    resp.items.get(&invalid_id).unwrap(),
+    BulkCreateCollabResult::Failed { .. }
+  ));
+  let err = c
+    .get_collab(QueryCollabParams::new(
+      &valid_id,
+      CollabType::Unknown,
+      &workspace_id,
+    ))
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+
+  // duplicate object ids are rejected up front
+  let err = c
+    .bulk_create_collabs(&workspace_id, vec![item(&valid_id), item(&valid_id)])
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequest);
+}
+
 #[tokio::test]
 async fn success_delete_collab_test() {
   let (c, _user) = generate_unique_registered_user_client().await;
@@ -191,6 +336,215 @@ async fn success_delete_collab_test() {
   assert_eq!(error.code, ErrorCode::RecordNotFound);
 }
 
+#[tokio::test]
+async fn restore_and_purge_deleted_collab_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let object_id = Uuid::new_v4().to_string();
+  let encode_collab = test_encode_collab_v1(&object_id, "title", "hello world")
+    .encode_to_bytes()
+    .unwrap();
+  c.create_collab(CreateCollabParams {
+    object_id: object_id.clone(),
+    encoded_collab_v1: encode_collab,
+    collab_type: CollabType::Unknown,
+    workspace_id: workspace_id.clone(),
+  })
+  .await
+  .unwrap();
+  let query = QueryCollabParams::new(&object_id, CollabType::Unknown, &workspace_id);
+  let delete = DeleteCollabParams {
+    object_id: object_id.clone(),
+    workspace_id: workspace_id.clone(),
+  };
+
+  // the deleted collab is listed in the trash until it's restored
+  c.delete_collab(delete.clone()).await.unwrap();
+  let trash = c.list_deleted_collabs(&workspace_id).await.unwrap();
+  assert_eq!(trash.items.len(), 1);
+  assert_eq!(trash.items[0].object_id, object_id);
+  c.restore_collab(&workspace_id, &object_id).await.unwrap();
+  c.get_collab(query.clone()).await.unwrap();
+  assert!(c
+    .list_deleted_collabs(&workspace_id)
+    .await
+    .unwrap()
+    .items
+    .is_empty());
+  let err = c
+    .restore_collab(&workspace_id, &object_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+
+  // only the collabs of the trash can be purged, and they can't be restored after
+  let err = c.purge_collab(&workspace_id, &object_id).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+  c.delete_collab(delete).await.unwrap();
+  c.purge_collab(&workspace_id, &object_id).await.unwrap();
+  assert!(c
+    .list_deleted_collabs(&workspace_id)
+    .await
+    .unwrap()
+    .items
+    .is_empty());
+  let err = c
+    .restore_collab(&workspace_id, &object_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+  let err = c.get_collab(query).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+}
+
+#[tokio::test]
+async fn deleted_collab_is_left_out_of_batch_get_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let kept_id = Uuid::new_v4().to_string();
+  let deleted_id = Uuid::new_v4().to_string();
+  let mut queries = vec![];
+  for object_id in [&kept_id, &deleted_id] {
+    let encode_collab = test_encode_collab_v1(object_id, "title", "hello world")
+      .encode_to_bytes()
+      .unwrap();
+    c.create_collab(CreateCollabParams {
+      object_id: object_id.clone(),
+      encoded_collab_v1: encode_collab,
+      collab_type: CollabType::Unknown,
+      workspace_id: workspace_id.clone(),
+    })
+    .await
+    .unwrap();
+    queries.push(QueryCollab {
+      object_id: object_id.clone(),
+      collab_type: CollabType::Unknown,
+    });
+  }
+  c.delete_collab(DeleteCollabParams {
+    object_id: deleted_id.clone(),
+    workspace_id: workspace_id.clone(),
+  })
+  .await
+  .unwrap();
+
+  let results = c
+    .batch_get_collab(&workspace_id, queries.clone())
+    .await
+    .unwrap()
+    .0;
+  assert!(matches!(
+    results.get(&kept_id).unwrap(),
+    QueryCollabResult::Success { .. }
+  ));
+  assert!(matches!(
+    results.get(&deleted_id).unwrap(),
+    QueryCollabResult::Failed { .. }
+  ));
+  let items = c.batch_get_collabs(&workspace_id, queries).await.unwrap();
+  assert!(matches!(
+    items.get(&kept_id).unwrap(),
+    BatchGetCollabItem::Success(_)
+  ));
+  assert!(matches!(
+    items.get(&deleted_id).unwrap(),
+    BatchGetCollabItem::Failed {
+      code: ErrorCode::RecordNotFound,
+      ..
+    }
+  ));
+}
+
+#[tokio::test]
+async fn deleted_collab_is_not_synced_test() {
+  let collab_type = CollabType::Unknown;
+  let mut client = TestClient::new_user().await;
+  let workspace_id = client.workspace_id().await;
+  let object_id = Uuid::new_v4().to_string();
+  let encode_collab = test_encode_collab_v1(&object_id, "title", "hello world")
+    .encode_to_bytes()
+    .unwrap();
+  client
+    .api_client
+    .create_collab(CreateCollabParams {
+      object_id: object_id.clone(),
+      encoded_collab_v1: encode_collab,
+      collab_type: collab_type.clone(),
+      workspace_id: workspace_id.clone(),
+    })
+    .await
+    .unwrap();
+  client
+    .api_client
+    .delete_collab(DeleteCollabParams {
+      object_id: object_id.clone(),
+      workspace_id: workspace_id.clone(),
+    })
+    .await
+    .unwrap();
+
+  let mut deleted_rx = client.ws_client.subscribe_collab_deleted();
+  client
+    .open_collab(&workspace_id, &object_id, collab_type)
+    .await;
+  let deleted = timeout(Duration::from_secs(10), deleted_rx.recv())
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(deleted.object_id, object_id);
+}
+
+#[tokio::test]
+async fn only_members_with_full_access_can_restore_or_purge_test() {
+  let owner = TestClient::new_user().await;
+  let guest = TestClient::new_user().await;
+  let workspace_id = owner.workspace_id().await;
+  owner
+    .invite_and_accepted_workspace_member(&workspace_id, &guest, AFRole::Guest)
+    .await
+    .unwrap();
+  let object_id = Uuid::new_v4().to_string();
+  let encode_collab = test_encode_collab_v1(&object_id, "title", "hello world")
+    .encode_to_bytes()
+    .unwrap();
+  owner
+    .api_client
+    .create_collab(CreateCollabParams {
+      object_id: object_id.clone(),
+      encoded_collab_v1: encode_collab,
+      collab_type: CollabType::Unknown,
+      workspace_id: workspace_id.clone(),
+    })
+    .await
+    .unwrap();
+  owner
+    .api_client
+    .delete_collab(DeleteCollabParams {
+      object_id: object_id.clone(),
+      workspace_id: workspace_id.clone(),
+    })
+    .await
+    .unwrap();
+
+  let err = guest
+    .api_client
+    .purge_collab(&workspace_id, &object_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+  let err = guest
+    .api_client
+    .restore_collab(&workspace_id, &object_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+  owner
+    .api_client
+    .purge_collab(&workspace_id, &object_id)
+    .await
+    .unwrap();
+}
+
 #[tokio::test]
 async fn fail_insert_collab_with_empty_payload_test() {
   let (c, _user) = generate_unique_registered_user_client().await;
@@ -208,6 +562,36 @@ async fn fail_insert_collab_with_empty_payload_test() {
   assert_eq!(error.code, ErrorCode::NoRequiredData);
 }
 
+#[tokio::test]
+async fn fail_insert_collab_over_size_limit_test() {
+  let (c, _user) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c).await;
+  let object_id = Uuid::new_v4().to_string();
+  // user awareness collabs are limited to 4 MiB
+  let doc_state = make_big_collab_doc_state(&object_id, "text", "a".repeat(5 * 1024 * 1024));
+  let encoded_collab = EncodedCollab::new_v1(Default::default(), doc_state);
+  let error = c
+    .create_collab(CreateCollabParams {
+      object_id: object_id.clone(),
+      encoded_collab_v1: encoded_collab.encode_to_bytes().unwrap(),
+      collab_type: CollabType::UserAwareness,
+      workspace_id: workspace_id.clone(),
+    })
+    .await
+    .unwrap_err();
+  assert_eq!(error.code, ErrorCode::CollabSizeExceeded);
+
+  let error = c
+    .get_collab(QueryCollabParams::new(
+      &object_id,
+      CollabType::UserAwareness,
+      &workspace_id,
+    ))
+    .await
+    .unwrap_err();
+  assert_eq!(error.code, ErrorCode::RecordNotFound);
+}
+
 #[tokio::test]
 async fn fail_insert_collab_with_invalid_workspace_id_test() {
   let (c, _user) = generate_unique_registered_user_client().await;
@@ -9,11 +9,13 @@ use collab_document::blocks::Block;
 use collab_document::document::Document;
 use collab_document::document_data::default_document_collab_data;
 use collab_entity::CollabType;
+use infra::text_offset::{byte_range_to_utf16, ByteOffset, Utf16Offset};
 use nanoid::nanoid;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use redis::aio::ConnectionManager;
 use tokio::time::sleep;
+use workspace_template::default_collab::default_collab_data;
 
 #[allow(dead_code)]
 pub fn generate_random_bytes(size: usize) -> Vec<u8> {
@@ -153,22 +155,16 @@ pub fn alex_banker_story() -> Vec<&'static str> {
 }
 
 #[allow(dead_code)]
-pub fn empty_collab_doc_state(object_id: &str, collab_type: CollabType) -> Vec<u8> {
-  match collab_type {
-    CollabType::Document => default_document_collab_data(object_id)
-      .unwrap()
-      .doc_state
-      .to_vec(),
-    CollabType::Unknown => {
-      let collab = Collab::new_with_origin(CollabOrigin::Empty, object_id, vec![], false);
-      collab
-        .encode_collab_v1(|_| Ok::<(), anyhow::Error>(()))
-        .unwrap()
-        .doc_state
-        .to_vec()
-    },
-    _ => panic!("collab type not supported"),
-  }
+pub async fn empty_collab_doc_state(
+  object_id: &str,
+  collab_type: CollabType,
+  workspace_id: &str,
+) -> Vec<u8> {
+  default_collab_data(object_id, &collab_type, workspace_id)
+    .await
+    .unwrap()
+    .doc_state
+    .to_vec()
 }
 
 pub fn test_encode_collab_v1(object_id: &str, key: &str, value: &str) -> EncodedCollab {
@@ -277,15 +273,21 @@ impl TestScenario {
       let mut txn = collab.context.transact_mut();
       let txt = collab.data.get_or_init_text(&mut txn, "text-id");
       for patch in t.patches.iter() {
-        let at = patch.0;
-        let delete = patch.1;
         let content = patch.2.as_str();
+        // The positions of some traces are in bytes, the text of the collab counts UTF-16 units.
+        let (at, delete) = if self.using_byte_positions {
+          let current = txt.get_string(&txn);
+          byte_range_to_utf16(&current, ByteOffset(patch.0), patch.1)
+            .expect("patch positions must be on character boundaries")
+        } else {
+          (Utf16Offset(patch.0 as u32), patch.1 as u32)
+        };
 
         if delete != 0 {
-          txt.remove_range(&mut txn, at as u32, delete as u32);
+          txt.remove_range(&mut txn, at.0, delete);
         }
         if !content.is_empty() {
-          txt.insert(&mut txn, at as u32, content);
+          txt.insert(&mut txn, at.0, content);
         }
       }
     }
@@ -0,0 +1,143 @@
+use client_api::Client;
+use client_api_test::{generate_unique_registered_user_client, workspace_id_from_client};
+use database_entity::file_dto::{CompleteUploadRequest, CompletedPartRequest, CreateUploadRequest};
+use uuid::Uuid;
+
+async fn upload_blob(
+  client: &Client,
+  workspace_id: &str,
+  parent_dir: &str,
+  file_id: &str,
+  data: &[u8],
+) {
+  let upload = client
+    .create_upload(
+      workspace_id,
+      CreateUploadRequest {
+        file_id: file_id.to_string(),
+        parent_dir: parent_dir.to_string(),
+        content_type: mime::TEXT_PLAIN_UTF_8.to_string(),
+        file_size: Some(data.len() as u64),
+      },
+    )
+    .await
+    .unwrap();
+  let part = client
+    .upload_part(
+      workspace_id,
+      parent_dir,
+      file_id,
+      &upload.upload_id,
+      1,
+      data.to_vec(),
+    )
+    .await
+    .unwrap();
+  client
+    .complete_upload(
+      workspace_id,
+      CompleteUploadRequest {
+        file_id: file_id.to_string(),
+        parent_dir: parent_dir.to_string(),
+        upload_id: upload.upload_id,
+        parts: vec![CompletedPartRequest {
+          e_tag: part.e_tag,
+          part_number: part.part_num,
+        }],
+      },
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn overwrite_blob_archives_previous_version_test() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let parent_dir = workspace_id.clone();
+  let file_id = Uuid::new_v4().to_string();
+
+  upload_blob(&c1, &workspace_id, &parent_dir, &file_id, b"first").await;
+  let versions = c1
+    .list_blob_versions_v1(&workspace_id, &parent_dir, &file_id)
+    .await
+    .unwrap();
+  assert!(versions.0.is_empty());
+
+  upload_blob(
+    &c1,
+    &workspace_id,
+    &parent_dir,
+    &file_id,
+    b"second revision",
+  )
+  .await;
+  let (_, data) = c1
+    .get_blob_v1(&workspace_id, &parent_dir, &file_id)
+    .await
+    .unwrap();
+  assert_eq!(data, b"second revision");
+
+  let versions = c1
+    .list_blob_versions_v1(&workspace_id, &parent_dir, &file_id)
+    .await
+    .unwrap();
+  assert_eq!(versions.0.len(), 1);
+  assert_eq!(versions.0[0].file_size, b"first".len() as i64);
+}
+
+#[tokio::test]
+async fn restore_blob_version_test() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let parent_dir = workspace_id.clone();
+  let file_id = Uuid::new_v4().to_string();
+
+  upload_blob(&c1, &workspace_id, &parent_dir, &file_id, b"first").await;
+  upload_blob(
+    &c1,
+    &workspace_id,
+    &parent_dir,
+    &file_id,
+    b"second revision",
+  )
+  .await;
+  let versions = c1
+    .list_blob_versions_v1(&workspace_id, &parent_dir, &file_id)
+    .await
+    .unwrap();
+  c1.restore_blob_version_v1(&workspace_id, &parent_dir, &file_id, versions.0[0].version)
+    .await
+    .unwrap();
+
+  let (_, data) = c1
+    .get_blob_v1(&workspace_id, &parent_dir, &file_id)
+    .await
+    .unwrap();
+  assert_eq!(data, b"first");
+
+  // the replaced content is archived too, so the restore can be undone
+  let versions = c1
+    .list_blob_versions_v1(&workspace_id, &parent_dir, &file_id)
+    .await
+    .unwrap();
+  assert_eq!(versions.0.len(), 2);
+  assert_eq!(versions.0[0].file_size, b"second revision".len() as i64);
+  c1.restore_blob_version_v1(&workspace_id, &parent_dir, &file_id, versions.0[0].version)
+    .await
+    .unwrap();
+  let (_, data) = c1
+    .get_blob_v1(&workspace_id, &parent_dir, &file_id)
+    .await
+    .unwrap();
+  assert_eq!(data, b"second revision");
+
+  c1.delete_blob_v1(&workspace_id, &parent_dir, &file_id)
+    .await
+    .unwrap();
+  let versions = c1
+    .list_blob_versions_v1(&workspace_id, &parent_dir, &file_id)
+    .await
+    .unwrap();
+  assert!(versions.0.is_empty());
+}
@@ -0,0 +1,83 @@
+use app_error::ErrorCode;
+use client_api_test::{generate_unique_registered_user_client, workspace_id_from_client};
+use image::{DynamicImage, ImageOutputFormat, RgbaImage};
+
+fn png_image(width: u32, height: u32) -> Vec<u8> {
+  let image = DynamicImage::ImageRgba8(RgbaImage::new(width, height));
+  let mut data = Vec::new();
+  image.write_to(&mut data, ImageOutputFormat::Png).unwrap();
+  data
+}
+
+#[tokio::test]
+async fn workspace_icon_is_downscaled_test() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let resp = c1
+    .upload_workspace_icon(&workspace_id, png_image(1024, 512), &mime::IMAGE_PNG)
+    .await
+    .unwrap();
+
+  let (got_mime, got_data) = c1
+    .get_blob_v1(&workspace_id, &workspace_id, &resp.file_id)
+    .await
+    .unwrap();
+  assert_eq!(got_mime, mime::IMAGE_PNG);
+  let icon = image::load_from_memory(&got_data).unwrap();
+  assert_eq!((icon.width(), icon.height()), (256, 128));
+
+  let workspace = c1
+    .get_workspaces()
+    .await
+    .unwrap()
+    .into_iter()
+    .find(|workspace| workspace.workspace_id.to_string() == workspace_id)
+    .unwrap();
+  assert_eq!(workspace.icon, resp.url);
+}
+
+#[tokio::test]
+async fn replaced_workspace_icon_is_deleted_test() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let first = c1
+    .upload_workspace_icon(&workspace_id, png_image(64, 64), &mime::IMAGE_PNG)
+    .await
+    .unwrap();
+  let second = c1
+    .upload_workspace_icon(&workspace_id, png_image(64, 64), &mime::IMAGE_PNG)
+    .await
+    .unwrap();
+  assert_ne!(first.file_id, second.file_id);
+
+  let err = c1
+    .get_blob_v1(&workspace_id, &workspace_id, &first.file_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+  c1.get_blob_v1(&workspace_id, &workspace_id, &second.file_id)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn reject_non_image_icon_test() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let err = c1
+    .upload_workspace_icon(&workspace_id, "hello world", &mime::IMAGE_PNG)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequest);
+}
+
+#[tokio::test]
+async fn reject_oversized_icon_upload_test() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let err = c1
+    .upload_workspace_icon(&workspace_id, vec![0; 6 * 1024 * 1024], &mime::IMAGE_PNG)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::PayloadTooLarge);
+}
@@ -1,9 +1,12 @@
 use std::borrow::Cow;
 use std::ops::Deref;
 
+mod blob_version_test;
 mod delete_dir_test;
+mod image_test;
 mod multiple_part_test;
 mod put_and_get;
+mod s3_retry_test;
 mod usage;
 
 use appflowy_cloud::application::get_aws_s3_client;
@@ -3,8 +3,17 @@ use super::TestBucket;
 use app_error::ErrorCode;
 
 use crate::collab::util::generate_random_string;
-use client_api_test::{generate_unique_registered_user_client, workspace_id_from_client};
+use client_api_test::{
+  generate_unique_registered_user_client, workspace_id_from_client, TestClient,
+};
 use database::file::{BucketClient, ResponseBlob};
+use database_entity::dto::{AFRole, AFWorkspaceSettingsChange};
+use database_entity::file_dto::PresignedUploadRequest;
+use reqwest::header::{
+  HeaderName, ACCEPT_RANGES, CACHE_CONTROL, CONTENT_RANGE, ETAG, IF_NONE_MATCH, RANGE,
+};
+use reqwest::{Method, Response, StatusCode};
+use shared_entity::dto::workspace_dto::{BlobMetadataExportFormat, BlobMetadataExportLine};
 
 #[tokio::test]
 async fn get_but_not_exists() {
@@ -180,3 +189,245 @@ async fn put_and_put_and_get_v1() {
   assert_eq!(String::from_utf8(got_data).unwrap(), data);
   assert_eq!(got_mime, mime);
 }
+
+#[tokio::test]
+async fn get_blob_range() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let mime = mime::TEXT_PLAIN_UTF_8;
+  let data = generate_random_string(2048);
+  let file_id = uuid::Uuid::new_v4().to_string();
+  let url = c1.get_blob_url(&workspace_id, &file_id);
+  c1.put_blob(&url, data.clone(), &mime).await.unwrap();
+
+  let get_range = |range: &'static str| {
+    let c1 = &c1;
+    let url = &url;
+    async move {
+      c1.http_client_with_auth(Method::GET, url)
+        .await
+        .unwrap()
+        .header(RANGE, range)
+        .send()
+        .await
+        .unwrap()
+    }
+  };
+  let header =
+    |resp: &Response, name: HeaderName| resp.headers()[name].to_str().unwrap().to_string();
+
+  let resp = get_range("bytes=10-19").await;
+  assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+  assert_eq!(header(&resp, CONTENT_RANGE), "bytes 10-19/2048");
+  assert_eq!(header(&resp, ACCEPT_RANGES), "bytes");
+  let etag = header(&resp, ETAG);
+  assert_eq!(resp.text().await.unwrap(), data[10..20]);
+
+  let resp = get_range("bytes=-1024").await;
+  assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+  assert_eq!(header(&resp, CONTENT_RANGE), "bytes 1024-2047/2048");
+  assert_eq!(resp.text().await.unwrap(), data[1024..]);
+
+  for range in ["bytes=4096-", "bytes=0-9,20-29"] {
+    let resp = get_range(range).await;
+    assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(header(&resp, CONTENT_RANGE), "bytes */2048");
+  }
+
+  // the entity tag allows conditional requests
+  let resp = c1
+    .http_client_with_auth(Method::GET, &url)
+    .await
+    .unwrap()
+    .header(IF_NONE_MATCH, etag)
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+}
+
+#[tokio::test]
+async fn get_blob_variant_falls_back_to_blob() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let mime = mime::TEXT_PLAIN_UTF_8;
+  let data = "not generated yet";
+  let file_id = uuid::Uuid::new_v4().to_string();
+  let url = c1.get_blob_url(&workspace_id, &file_id);
+  c1.put_blob(&url, data, &mime).await.unwrap();
+
+  // without a variant, the blob is served and only cached for a while
+  let resp = c1
+    .http_client_with_auth(Method::GET, &format!("{}?size=thumb", url))
+    .await
+    .unwrap()
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(resp.status(), StatusCode::OK);
+  assert_eq!(
+    resp.headers()[CACHE_CONTROL].to_str().unwrap(),
+    "public, max-age=300"
+  );
+  assert_eq!(resp.text().await.unwrap(), data);
+
+  let resp = c1
+    .http_client_with_auth(Method::GET, &format!("{}?size=huge", url))
+    .await
+    .unwrap()
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn put_html_named_as_png_is_rejected() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let data = "<!DOCTYPE html><html><body><script>alert(document.cookie)</script></body></html>";
+  let url = c1.get_blob_url(&workspace_id, "avatar.png");
+  let err = c1.put_blob(&url, data, &mime::IMAGE_PNG).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidContentType);
+
+  let err = c1.get_blob(&url).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+}
+
+#[tokio::test]
+async fn put_blob_with_mismatched_content_type() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let data = b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n".to_vec();
+  let url = c1.get_blob_url(&workspace_id, "report.jpg");
+  c1.put_blob(&url, data.clone(), &mime::IMAGE_JPEG)
+    .await
+    .unwrap();
+
+  // stored with the type of its content
+  let (got_mime, got_data) = c1.get_blob(&url).await.unwrap();
+  assert_eq!(got_mime, mime::APPLICATION_PDF);
+  assert_eq!(got_data, data);
+}
+
+#[tokio::test]
+async fn put_blob_outside_of_workspace_allowlist() {
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  c1.update_workspace_settings(
+    &workspace_id,
+    &AFWorkspaceSettingsChange::new().allowed_blob_content_types(vec!["image/*".to_string()]),
+  )
+  .await
+  .unwrap();
+
+  let url = c1.get_blob_url(&workspace_id, "notes.txt");
+  let err = c1
+    .put_blob(&url, "hello world", &mime::TEXT_PLAIN_UTF_8)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidContentType);
+
+  let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR".to_vec();
+  let url = c1.get_blob_url(&workspace_id, "image.png");
+  c1.put_blob(&url, png, &mime::IMAGE_PNG).await.unwrap();
+}
+
+#[tokio::test]
+async fn put_blob_at_and_over_plan_size_limit() {
+  // the limit of the free plan
+  const LIMIT: usize = 10 * 1024 * 1024;
+  let (c1, _user1) = generate_unique_registered_user_client().await;
+  let workspace_id = workspace_id_from_client(&c1).await;
+  let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR".to_vec();
+  png.resize(LIMIT, 0);
+  let url = c1.get_blob_url(&workspace_id, "at_limit.png");
+  c1.put_blob(&url, png.clone(), &mime::IMAGE_PNG)
+    .await
+    .unwrap();
+
+  png.push(0);
+  let url = c1.get_blob_url(&workspace_id, "over_limit.png");
+  let err = c1.put_blob(&url, png, &mime::IMAGE_PNG).await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::FileTooLarge);
+  assert!(err.message.contains(&LIMIT.to_string()));
+
+  // the presigned uploads are checked before the url is issued
+  let req = |content_length| PresignedUploadRequest {
+    file_id: "presigned.png".to_string(),
+    parent_dir: "doc".to_string(),
+    content_type: "image/png".to_string(),
+    content_length,
+  };
+  c1.create_presigned_upload(&workspace_id, req(LIMIT as u64))
+    .await
+    .unwrap();
+  let err = c1
+    .create_presigned_upload(&workspace_id, req(LIMIT as u64 + 1))
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::FileTooLarge);
+}
+
+#[tokio::test]
+async fn export_workspace_blob_metadata_test() {
+  let owner = TestClient::new_user().await;
+  let member = TestClient::new_user().await;
+  let workspace_id = owner.workspace_id().await;
+  let mut file_ids = vec![
+    uuid::Uuid::new_v4().to_string(),
+    uuid::Uuid::new_v4().to_string(),
+  ];
+  file_ids.sort();
+  for file_id in &file_ids {
+    let url = owner.api_client.get_blob_url(&workspace_id, file_id);
+    owner
+      .api_client
+      .put_blob(&url, "exported", &mime::TEXT_PLAIN_UTF_8)
+      .await
+      .unwrap();
+  }
+
+  // one JSON object per line, ordered by file id
+  let ndjson = owner
+    .api_client
+    .export_workspace_blob_metadata(&workspace_id, BlobMetadataExportFormat::Ndjson)
+    .await
+    .unwrap();
+  let lines = ndjson
+    .lines()
+    .map(|line| serde_json::from_str::<BlobMetadataExportLine>(line).unwrap())
+    .collect::<Vec<_>>();
+  assert_eq!(
+    lines.iter().map(|line| &line.file_id).collect::<Vec<_>>(),
+    file_ids.iter().collect::<Vec<_>>()
+  );
+  assert!(lines
+    .iter()
+    .all(|line| line.file_size == 8 && line.created_at.is_some()));
+
+  let csv = owner
+    .api_client
+    .export_workspace_blob_metadata(&workspace_id, BlobMetadataExportFormat::Csv)
+    .await
+    .unwrap();
+  let rows = csv.lines().collect::<Vec<_>>();
+  assert_eq!(
+    rows[0],
+    "file_id,file_type,file_size,created_at,modified_at"
+  );
+  assert_eq!(rows.len(), 3);
+  assert!(rows[1].starts_with(&format!("{},text/plain", file_ids[0])));
+
+  // only the owners can export the metadata
+  owner
+    .invite_and_accepted_workspace_member(&workspace_id, &member, AFRole::Member)
+    .await
+    .unwrap();
+  let err = member
+    .api_client
+    .export_workspace_blob_metadata(&workspace_id, BlobMetadataExportFormat::Ndjson)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+}
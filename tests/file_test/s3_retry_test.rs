@@ -0,0 +1,63 @@
+use database::file::s3_retry::{CircuitBreaker, CircuitBreakerConfig, S3Metrics};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn breaker(config: CircuitBreakerConfig) -> (CircuitBreaker, Arc<S3Metrics>) {
+  let metrics = Arc::new(S3Metrics::default());
+  (CircuitBreaker::new(config, metrics.clone()), metrics)
+}
+
+#[test]
+fn breaker_opens_when_error_rate_spikes_test() {
+  let (breaker, metrics) = breaker(CircuitBreakerConfig {
+    min_requests: 4,
+    ..Default::default()
+  });
+  for _ in 0..3 {
+    assert!(breaker.try_acquire());
+    breaker.record(false);
+  }
+  // not enough requests yet
+  assert!(!breaker.is_open());
+  breaker.record(false);
+  assert!(breaker.is_open());
+  assert!(!breaker.try_acquire());
+  assert_eq!(metrics.breaker_opened_count.get(), 1);
+}
+
+#[test]
+fn breaker_stays_closed_on_low_error_rate_test() {
+  let (breaker, _) = breaker(CircuitBreakerConfig {
+    min_requests: 4,
+    ..Default::default()
+  });
+  for i in 0..20 {
+    breaker.record(i % 3 != 0);
+  }
+  assert!(!breaker.is_open());
+}
+
+#[test]
+fn breaker_closes_after_successful_probe_test() {
+  let (breaker, metrics) = breaker(CircuitBreakerConfig {
+    min_requests: 1,
+    open_duration: Duration::ZERO,
+    ..Default::default()
+  });
+  breaker.record(false);
+  assert!(breaker.is_open());
+
+  // the first request after the open duration is a probe, other requests wait for its outcome
+  assert!(breaker.try_acquire());
+  assert!(!breaker.try_acquire());
+  breaker.record(false);
+  assert!(breaker.is_open());
+
+  assert!(breaker.try_acquire());
+  breaker.record(true);
+  assert!(!breaker.is_open());
+  assert!(breaker.try_acquire());
+  assert_eq!(metrics.breaker_half_opened_count.get(), 2);
+  assert_eq!(metrics.breaker_closed_count.get(), 1);
+  assert_eq!(metrics.breaker_state.get(), 0);
+}
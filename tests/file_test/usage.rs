@@ -11,6 +11,12 @@ async fn workspace_usage_put_blob_test() {
 
   let usage = client.get_workspace_usage().await;
   assert_eq!(usage.consumed_capacity, 6);
+  assert_eq!(usage.by_member.len(), 1);
+  assert_eq!(usage.by_member[0].uid, Some(client.uid().await));
+  assert_eq!(
+    (usage.by_member[0].file_count, usage.by_member[0].total_size),
+    (2, 6)
+  );
 
   // after the test, delete the files
   client.delete_file(&file_id_1).await;
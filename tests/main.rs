@@ -6,6 +6,7 @@ mod gotrue;
 mod search;
 mod server_info;
 mod sql_test;
+mod unfurl;
 mod user;
 mod websocket;
 mod workspace;
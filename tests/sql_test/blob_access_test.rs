@@ -0,0 +1,106 @@
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use chrono::{Duration, Utc};
+use database::blob_access::{
+  get_cold_blobs, update_blob_last_accessed_at, update_blob_storage_class,
+};
+use database::resource_usage::{get_blob_metadata, insert_blob_metadata, BlobAttributes};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[sqlx::test(migrations = false)]
+async fn cold_blobs_follow_blob_accesses_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  for file_id in ["doc_a", "doc_b"] {
+    insert_blob_metadata(
+      &pool,
+      file_id,
+      &workspace_id,
+      "image/png",
+      100,
+      None,
+      BlobAttributes::default(),
+    )
+    .await
+    .unwrap();
+  }
+  // the blobs just uploaded aren't cold
+  let older_than = Utc::now() - Duration::days(180);
+  assert!(get_cold_blobs(&pool, &workspace_id, older_than)
+    .await
+    .unwrap()
+    .is_empty());
+
+  sqlx::query("UPDATE af_blob_metadata SET last_accessed_at = $2 WHERE workspace_id = $1")
+    .bind(workspace_id)
+    .bind(Utc::now() - Duration::days(200))
+    .execute(&pool)
+    .await
+    .unwrap();
+  // a download moves `last_accessed_at` forward only
+  let updated = update_blob_last_accessed_at(
+    &pool,
+    &[workspace_id, workspace_id, workspace_id],
+    &[
+      "doc_a".to_string(),
+      "doc_b".to_string(),
+      "doc_missing".to_string(),
+    ],
+    &[Utc::now() - Duration::days(300), Utc::now(), Utc::now()],
+  )
+  .await
+  .unwrap();
+  assert_eq!(updated, 1);
+  let cold = get_cold_blobs(&pool, &workspace_id, older_than)
+    .await
+    .unwrap();
+  assert_eq!(
+    cold
+      .iter()
+      .map(|blob| blob.file_id.as_str())
+      .collect::<Vec<_>>(),
+    vec!["doc_a"]
+  );
+
+  // a blob moved to another storage class is no longer a candidate
+  let updated = update_blob_storage_class(&pool, &workspace_id, "doc_a", None, "STANDARD_IA")
+    .await
+    .unwrap();
+  assert_eq!(updated, 1);
+  let blob = get_blob_metadata(&pool, &workspace_id, "doc_a")
+    .await
+    .unwrap();
+  assert_eq!(blob.storage_class.as_deref(), Some("STANDARD_IA"));
+  assert!(blob.storage_class_changed_at.is_some());
+  assert!(get_cold_blobs(&pool, &workspace_id, older_than)
+    .await
+    .unwrap()
+    .is_empty());
+
+  // uploading it again stores it in the default storage class
+  insert_blob_metadata(
+    &pool,
+    "doc_a",
+    &workspace_id,
+    "image/png",
+    120,
+    None,
+    BlobAttributes::default(),
+  )
+  .await
+  .unwrap();
+  let blob = get_blob_metadata(&pool, &workspace_id, "doc_a")
+    .await
+    .unwrap();
+  assert_eq!(blob.storage_class, None);
+  assert!(blob.last_accessed_at > older_than);
+}
@@ -0,0 +1,165 @@
+use crate::file_test::TestBucket;
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use app_error::AppError;
+use appflowy_cloud::api::file_storage::BlobPathV1;
+use database::file::s3_client_impl::S3BucketStorage;
+use database::file::{copied_blob_file_id, BlobKey};
+use database::resource_usage::{get_workspace_usage_size, is_blob_metadata_exists, BlobAttributes};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn create_workspace(pool: &PgPool) -> Uuid {
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  Uuid::parse_str(&user.workspace_id).unwrap()
+}
+
+fn blob_key(workspace_id: Uuid, parent_dir: &str, file_id: &str) -> BlobPathV1 {
+  BlobPathV1 {
+    workspace_id,
+    parent_dir: parent_dir.to_string(),
+    file_id: file_id.to_string(),
+  }
+}
+
+#[sqlx::test(migrations = false)]
+async fn copy_blobs_between_workspaces_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let src_workspace_id = create_workspace(&pool).await;
+  let dst_workspace_id = create_workspace(&pool).await;
+  let storage = S3BucketStorage::from_bucket_impl(TestBucket::new().await.0, pool.clone());
+
+  let image = blob_key(src_workspace_id, "doc", "image.png");
+  storage
+    .put_blob_content(
+      image.clone(),
+      vec![1u8; 100],
+      "image/png".to_string(),
+      BlobAttributes::default(),
+    )
+    .await
+    .unwrap();
+  let video = blob_key(src_workspace_id, "doc", "video.mp4");
+  storage
+    .put_blob_content(
+      video.clone(),
+      vec![2u8; 300],
+      "video/mp4".to_string(),
+      BlobAttributes::default(),
+    )
+    .await
+    .unwrap();
+
+  let file_ids = vec![
+    image.blob_metadata_key(),
+    video.blob_metadata_key(),
+    "doc_missing.png".to_string(),
+  ];
+  let copied = storage
+    .copy_blobs(&src_workspace_id, &dst_workspace_id, "dup", &file_ids, None)
+    .await
+    .unwrap();
+  // the file ids without metadata are left out
+  assert_eq!(copied.len(), 2);
+  assert_eq!(
+    copied[&image.blob_metadata_key()],
+    copied_blob_file_id("dup", &image.blob_metadata_key())
+  );
+
+  let copied_image = blob_key(dst_workspace_id, "dup", &image.blob_metadata_key());
+  assert_eq!(
+    copied_image.blob_metadata_key(),
+    copied[&image.blob_metadata_key()]
+  );
+  let metadata = storage
+    .get_blob_metadata(&dst_workspace_id, &copied_image.blob_metadata_key())
+    .await
+    .unwrap();
+  assert_eq!(metadata.file_size, 100);
+  assert_eq!(metadata.file_type, "image/png");
+  let content = storage
+    .get_blob_with_metadata(&copied_image, &metadata)
+    .await
+    .unwrap();
+  assert_eq!(content, vec![1u8; 100]);
+  let usage = get_workspace_usage_size(&pool, &dst_workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(usage, 400);
+
+  // the blobs copied before are returned without being copied again
+  let again = storage
+    .copy_blobs(&src_workspace_id, &dst_workspace_id, "dup", &file_ids, None)
+    .await
+    .unwrap();
+  assert_eq!(again, copied);
+  let usage = get_workspace_usage_size(&pool, &dst_workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(usage, 400);
+
+  // the source workspace keeps its blobs
+  let image_content = storage.get_blob(&image).await.unwrap();
+  assert_eq!(image_content, vec![1u8; 100]);
+}
+
+#[sqlx::test(migrations = false)]
+async fn copy_blobs_over_destination_quota_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let src_workspace_id = create_workspace(&pool).await;
+  let dst_workspace_id = create_workspace(&pool).await;
+  let storage = S3BucketStorage::from_bucket_impl(TestBucket::new().await.0, pool.clone())
+    .with_storage_limit(Some(500));
+
+  let first = blob_key(src_workspace_id, "doc", "first.png");
+  let second = blob_key(src_workspace_id, "doc", "second.png");
+  for key in [&first, &second] {
+    storage
+      .put_blob_content(
+        key.clone(),
+        vec![3u8; 300],
+        "image/png".to_string(),
+        BlobAttributes::default(),
+      )
+      .await
+      .unwrap();
+  }
+
+  // each blob fits, but not both of them
+  let file_ids = vec![first.blob_metadata_key(), second.blob_metadata_key()];
+  let result = storage
+    .copy_blobs(&src_workspace_id, &dst_workspace_id, "dup", &file_ids, None)
+    .await;
+  assert!(matches!(result, Err(AppError::StorageSpaceNotEnough)));
+  for file_id in &file_ids {
+    let exists = is_blob_metadata_exists(
+      &pool,
+      &dst_workspace_id,
+      &copied_blob_file_id("dup", file_id),
+    )
+    .await
+    .unwrap();
+    assert!(!exists);
+  }
+  let usage = get_workspace_usage_size(&pool, &dst_workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(usage, 0);
+
+  let copied = storage
+    .copy_blobs(
+      &src_workspace_id,
+      &dst_workspace_id,
+      "dup",
+      &file_ids[..1],
+      None,
+    )
+    .await
+    .unwrap();
+  assert_eq!(copied.len(), 1);
+}
@@ -0,0 +1,161 @@
+use crate::file_test::TestBucket;
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use appflowy_cloud::api::file_storage::BlobPathV1;
+use database::file::s3_client_impl::S3BucketStorage;
+use database::file::{BlobKey, BucketClient};
+use database::resource_usage::{
+  blob_content_hash, find_blob_by_content_hash, get_workspace_usage_size, BlobAttributes,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn create_workspace(pool: &PgPool) -> Uuid {
+  setup_db(pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  Uuid::parse_str(&user.workspace_id).unwrap()
+}
+
+fn blob_key(workspace_id: Uuid, parent_dir: &str) -> BlobPathV1 {
+  BlobPathV1 {
+    workspace_id,
+    parent_dir: parent_dir.to_string(),
+    file_id: "screenshot.png".to_string(),
+  }
+}
+
+async fn object_exists(bucket: &TestBucket, object_key: &str) -> bool {
+  bucket.get_blob(object_key).await.is_ok()
+}
+
+#[sqlx::test(migrations = false)]
+async fn shared_blob_content_lifecycle_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  let bucket = TestBucket::new().await;
+  let storage = S3BucketStorage::from_bucket_impl(bucket.0.clone(), pool.clone());
+  let content = b"the same screenshot".to_vec();
+  let content_hash = blob_content_hash(&content);
+
+  // the same file pasted into two documents is uploaded once
+  let first = blob_key(workspace_id, "doc_a");
+  let second = blob_key(workspace_id, "doc_b");
+  for key in [&first, &second] {
+    storage
+      .put_blob_content(
+        key.clone(),
+        content.clone(),
+        "image/png".to_string(),
+        BlobAttributes::default(),
+      )
+      .await
+      .unwrap();
+  }
+  assert!(object_exists(&bucket, &first.object_key()).await);
+  assert!(!object_exists(&bucket, &second.object_key()).await);
+  let shared = find_blob_by_content_hash(&pool, &workspace_id, &content_hash)
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(shared.object_key, first.object_key());
+  assert_eq!(shared.ref_count, 2);
+  let metadata = storage
+    .get_blob_metadata(&workspace_id, &second.blob_metadata_key())
+    .await
+    .unwrap();
+  assert_eq!(
+    metadata.content_hash.as_deref(),
+    Some(content_hash.as_str())
+  );
+  assert_eq!(metadata.object_key, Some(first.object_key()));
+  assert_eq!(
+    storage
+      .get_blob_with_metadata(&second, &metadata)
+      .await
+      .unwrap(),
+    content
+  );
+  // each blob still counts toward the usage of the workspace
+  let usage = get_workspace_usage_size(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(usage, 2 * content.len() as u64);
+
+  // deleting the blob which uploaded the content keeps the object for the other one
+  storage.delete_blob(first.clone()).await.unwrap();
+  assert!(object_exists(&bucket, &first.object_key()).await);
+  let shared = find_blob_by_content_hash(&pool, &workspace_id, &content_hash)
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(shared.ref_count, 1);
+
+  // the object goes away with its last reference
+  storage.delete_blob(second.clone()).await.unwrap();
+  assert!(!object_exists(&bucket, &first.object_key()).await);
+  assert!(
+    find_blob_by_content_hash(&pool, &workspace_id, &content_hash)
+      .await
+      .unwrap()
+      .is_none()
+  );
+
+  // and the next upload of the content stores it again
+  storage
+    .put_blob_content(
+      second.clone(),
+      content.clone(),
+      "image/png".to_string(),
+      BlobAttributes::default(),
+    )
+    .await
+    .unwrap();
+  assert!(object_exists(&bucket, &second.object_key()).await);
+}
+
+#[sqlx::test(migrations = false)]
+async fn bulk_delete_releases_shared_content_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  let bucket = TestBucket::new().await;
+  let storage = S3BucketStorage::from_bucket_impl(bucket.0.clone(), pool.clone());
+  let content = b"an attachment".to_vec();
+  let content_hash = blob_content_hash(&content);
+
+  let kept = blob_key(workspace_id, "doc_kept");
+  let deleted = blob_key(workspace_id, "doc_deleted");
+  for key in [&deleted, &kept] {
+    storage
+      .put_blob_content(
+        key.clone(),
+        content.clone(),
+        "image/png".to_string(),
+        BlobAttributes::default(),
+      )
+      .await
+      .unwrap();
+  }
+
+  let file_ids = storage
+    .delete_object_blobs(&workspace_id, "doc_deleted")
+    .await
+    .unwrap();
+  assert_eq!(file_ids, vec![deleted.blob_metadata_key()]);
+  assert!(object_exists(&bucket, &deleted.object_key()).await);
+
+  let file_ids = storage
+    .delete_blobs_bulk(&workspace_id, "doc_kept", &[kept.file_id.clone()])
+    .await
+    .unwrap();
+  assert_eq!(file_ids, vec![kept.file_id.clone()]);
+  assert!(!object_exists(&bucket, &deleted.object_key()).await);
+  assert!(
+    find_blob_by_content_hash(&pool, &workspace_id, &content_hash)
+      .await
+      .unwrap()
+      .is_none()
+  );
+}
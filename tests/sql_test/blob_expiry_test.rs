@@ -0,0 +1,218 @@
+use crate::file_test::TestBucket;
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use app_error::AppError;
+use appflowy_cloud::api::file_storage::BlobPathV1;
+use chrono::{Duration, Utc};
+use database::file::s3_client_impl::S3BucketStorage;
+use database::file::BlobKey;
+use database::resource_usage::{
+  delete_blob_metadata_bulk, get_workspace_usage_size, insert_blob_metadata,
+  select_expired_blob_metadata_for_update, BlobAttributes,
+};
+use sqlx::PgPool;
+use std::ops::DerefMut;
+use uuid::Uuid;
+
+async fn create_workspace(pool: &PgPool) -> Uuid {
+  setup_db(pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  Uuid::parse_str(&user.workspace_id).unwrap()
+}
+
+#[sqlx::test(migrations = false)]
+async fn expired_blobs_are_selected_in_expiry_order_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  let now = Utc::now();
+  for (file_id, expires_at) in [
+    ("doc_later", Some(now - Duration::minutes(1))),
+    ("doc_sooner", Some(now - Duration::hours(1))),
+    ("doc_future", Some(now + Duration::hours(1))),
+    ("doc_permanent", None),
+  ] {
+    insert_blob_metadata(
+      &pool,
+      file_id,
+      &workspace_id,
+      "application/zip",
+      10,
+      None,
+      BlobAttributes::default().with_expires_at(expires_at),
+    )
+    .await
+    .unwrap();
+  }
+
+  let mut txn = pool.begin().await.unwrap();
+  let expired = select_expired_blob_metadata_for_update(&mut txn, now, 10)
+    .await
+    .unwrap();
+  let file_ids: Vec<_> = expired.iter().map(|blob| blob.file_id.as_str()).collect();
+  assert_eq!(file_ids, vec!["doc_sooner", "doc_later"]);
+  // the expired blobs are counted until they're deleted
+  assert_eq!(
+    get_workspace_usage_size(txn.deref_mut(), &workspace_id)
+      .await
+      .unwrap(),
+    40
+  );
+
+  let file_ids: Vec<_> = expired.into_iter().map(|blob| blob.file_id).collect();
+  delete_blob_metadata_bulk(&mut txn, &workspace_id, &file_ids)
+    .await
+    .unwrap();
+  txn.commit().await.unwrap();
+  assert_eq!(
+    get_workspace_usage_size(&pool, &workspace_id)
+      .await
+      .unwrap(),
+    20
+  );
+}
+
+#[sqlx::test(migrations = false)]
+async fn uploading_again_replaces_the_expiry_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  let expires_at = Utc::now() - Duration::minutes(1);
+  insert_blob_metadata(
+    &pool,
+    "doc_export",
+    &workspace_id,
+    "application/zip",
+    10,
+    None,
+    BlobAttributes::uploaded_by(1).with_expires_at(Some(expires_at)),
+  )
+  .await
+  .unwrap();
+  // the same content uploaded without an expiry becomes permanent
+  insert_blob_metadata(
+    &pool,
+    "doc_export",
+    &workspace_id,
+    "application/zip",
+    10,
+    None,
+    BlobAttributes::default(),
+  )
+  .await
+  .unwrap();
+
+  let mut txn = pool.begin().await.unwrap();
+  assert!(
+    select_expired_blob_metadata_for_update(&mut txn, Utc::now(), 10)
+      .await
+      .unwrap()
+      .is_empty()
+  );
+}
+
+#[sqlx::test(migrations = false)]
+async fn uploading_again_as_temporary_keeps_a_permanent_blob_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  insert_blob_metadata(
+    &pool,
+    "doc_image",
+    &workspace_id,
+    "image/png",
+    10,
+    None,
+    BlobAttributes::uploaded_by(1),
+  )
+  .await
+  .unwrap();
+  // the same content uploaded as a temporary file doesn't make the permanent one expire
+  insert_blob_metadata(
+    &pool,
+    "doc_image",
+    &workspace_id,
+    "image/png",
+    10,
+    None,
+    BlobAttributes::uploaded_by(2).with_expires_at(Some(Utc::now() - Duration::minutes(1))),
+  )
+  .await
+  .unwrap();
+
+  let mut txn = pool.begin().await.unwrap();
+  assert!(
+    select_expired_blob_metadata_for_update(&mut txn, Utc::now(), 10)
+      .await
+      .unwrap()
+      .is_empty()
+  );
+}
+
+#[sqlx::test(migrations = false)]
+async fn uploading_again_keeps_the_later_expiry_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  let now = Utc::now();
+  for expires_at in [now + Duration::hours(1), now - Duration::minutes(1)] {
+    insert_blob_metadata(
+      &pool,
+      "doc_export",
+      &workspace_id,
+      "application/zip",
+      10,
+      None,
+      BlobAttributes::uploaded_by(1).with_expires_at(Some(expires_at)),
+    )
+    .await
+    .unwrap();
+  }
+
+  let mut txn = pool.begin().await.unwrap();
+  assert!(select_expired_blob_metadata_for_update(&mut txn, now, 10)
+    .await
+    .unwrap()
+    .is_empty());
+  let expired = select_expired_blob_metadata_for_update(&mut txn, now + Duration::hours(2), 10)
+    .await
+    .unwrap();
+  assert_eq!(expired.len(), 1);
+}
+
+#[sqlx::test(migrations = false)]
+async fn expired_blob_is_not_found_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  let bucket = TestBucket::new().await;
+  let storage = S3BucketStorage::from_bucket_impl(bucket.0.clone(), pool.clone());
+  let key = BlobPathV1 {
+    workspace_id,
+    parent_dir: "doc_a".to_string(),
+    file_id: "export.zip".to_string(),
+  };
+  storage
+    .put_blob_content(
+      key.clone(),
+      b"archive".to_vec(),
+      "application/zip".to_string(),
+      BlobAttributes::default().with_expires_at(Some(Utc::now() + Duration::hours(1))),
+    )
+    .await
+    .unwrap();
+  let metadata = storage
+    .get_blob_metadata(&workspace_id, &key.blob_metadata_key())
+    .await
+    .unwrap();
+  assert!(metadata.expires_at.is_some());
+
+  sqlx::query(
+    "UPDATE af_blob_metadata SET expires_at = $3 WHERE workspace_id = $1 AND file_id = $2",
+  )
+  .bind(workspace_id)
+  .bind(key.blob_metadata_key())
+  .bind(Utc::now() - Duration::seconds(1))
+  .execute(&pool)
+  .await
+  .unwrap();
+  let result = storage
+    .get_blob_metadata(&workspace_id, &key.blob_metadata_key())
+    .await;
+  assert!(matches!(result, Err(AppError::RecordNotFound(_))));
+}
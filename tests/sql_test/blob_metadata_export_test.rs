@@ -0,0 +1,57 @@
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use database::resource_usage::{
+  get_all_workspace_blob_metadata_stream, insert_blob_metadata, BlobAttributes,
+};
+use futures_util::TryStreamExt;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[sqlx::test(migrations = false)]
+async fn stream_workspace_blob_metadata_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  for file_id in ["doc_b_photo.png", "doc_a_notes.txt", "doc_a_cover.jpg"] {
+    insert_blob_metadata(
+      &pool,
+      file_id,
+      &workspace_id,
+      "text/plain",
+      4,
+      None,
+      BlobAttributes::default(),
+    )
+    .await
+    .unwrap();
+  }
+  // a blob uploaded before the creation times were recorded
+  sqlx::query(
+    "UPDATE af_blob_metadata SET created_at = NULL WHERE workspace_id = $1 AND file_id = $2",
+  )
+  .bind(workspace_id)
+  .bind("doc_a_cover.jpg")
+  .execute(&pool)
+  .await
+  .unwrap();
+
+  let rows = get_all_workspace_blob_metadata_stream(&pool, workspace_id)
+    .try_collect::<Vec<_>>()
+    .await
+    .unwrap();
+  assert_eq!(
+    rows
+      .iter()
+      .map(|row| row.file_id.as_str())
+      .collect::<Vec<_>>(),
+    vec!["doc_a_cover.jpg", "doc_a_notes.txt", "doc_b_photo.png"]
+  );
+  assert!(rows[0].created_at.is_none());
+  assert!(rows[1..].iter().all(|row| row.created_at.is_some()));
+}
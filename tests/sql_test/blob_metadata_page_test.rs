@@ -0,0 +1,115 @@
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use chrono::{DateTime, Utc};
+use database::resource_usage::{
+  delete_blob_metadata, get_workspace_blob_metadata_page, insert_blob_metadata, BlobAttributes,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn create_workspace(pool: &PgPool) -> Uuid {
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  Uuid::parse_str(&user.workspace_id).unwrap()
+}
+
+#[sqlx::test(migrations = false)]
+async fn blob_metadata_page_of_empty_workspace_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let workspace_id = create_workspace(&pool).await;
+
+  let rows = get_workspace_blob_metadata_page(&pool, &workspace_id, None, 10)
+    .await
+    .unwrap();
+  assert!(rows.is_empty());
+}
+
+#[sqlx::test(migrations = false)]
+async fn blob_metadata_pages_cover_every_blob_once_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let workspace_id = create_workspace(&pool).await;
+  for i in 0..5 {
+    insert_blob_metadata(
+      &pool,
+      &format!("file_{}", i),
+      &workspace_id,
+      "text/plain",
+      1,
+      None,
+      BlobAttributes::default(),
+    )
+    .await
+    .unwrap();
+  }
+
+  let mut file_ids = vec![];
+  let mut after: Option<(DateTime<Utc>, String)> = None;
+  let mut pages = 0;
+  loop {
+    let rows = get_workspace_blob_metadata_page(
+      &pool,
+      &workspace_id,
+      after.as_ref().map(|(at, file_id)| (*at, file_id.as_str())),
+      2,
+    )
+    .await
+    .unwrap();
+    pages += 1;
+    after = rows
+      .last()
+      .map(|row| (row.modified_at, row.file_id.clone()));
+    let page_len = rows.len();
+    file_ids.extend(rows.into_iter().map(|row| row.file_id));
+    if page_len < 2 {
+      break;
+    }
+  }
+  assert_eq!(pages, 3);
+  assert_eq!(
+    file_ids,
+    vec!["file_0", "file_1", "file_2", "file_3", "file_4"]
+  );
+}
+
+#[sqlx::test(migrations = false)]
+async fn blob_metadata_cursor_of_deleted_blob_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let workspace_id = create_workspace(&pool).await;
+  for i in 0..4 {
+    insert_blob_metadata(
+      &pool,
+      &format!("file_{}", i),
+      &workspace_id,
+      "text/plain",
+      1,
+      None,
+      BlobAttributes::default(),
+    )
+    .await
+    .unwrap();
+  }
+
+  let rows = get_workspace_blob_metadata_page(&pool, &workspace_id, None, 2)
+    .await
+    .unwrap();
+  let last = rows.last().unwrap();
+  assert_eq!(last.file_id, "file_1");
+  let after = (last.modified_at, last.file_id.clone());
+
+  // the row the cursor points at is deleted before the next page is requested
+  let mut txn = pool.begin().await.unwrap();
+  delete_blob_metadata(&mut txn, &workspace_id, "file_1")
+    .await
+    .unwrap();
+  txn.commit().await.unwrap();
+
+  let rows = get_workspace_blob_metadata_page(&pool, &workspace_id, Some((after.0, &after.1)), 2)
+    .await
+    .unwrap();
+  let file_ids = rows.into_iter().map(|row| row.file_id).collect::<Vec<_>>();
+  assert_eq!(file_ids, vec!["file_2", "file_3"]);
+}
@@ -0,0 +1,175 @@
+use crate::file_test::TestBucket;
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use app_error::AppError;
+use appflowy_cloud::api::file_storage::BlobPathV1;
+use database::file::s3_client_impl::S3BucketStorage;
+use database::file::{BlobKey, BlobVersionPolicy, BucketClient};
+use database::resource_usage::{
+  get_workspace_usage_size, insert_blob_metadata, insert_blob_metadata_with_limit, BlobAttributes,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const LIMIT_BYTES: u64 = 1000;
+
+async fn upload(
+  pool: &PgPool,
+  workspace_id: &Uuid,
+  file_id: &str,
+  file_size: usize,
+) -> Result<(), AppError> {
+  let mut txn = pool.begin().await.unwrap();
+  insert_blob_metadata_with_limit(
+    &mut txn,
+    workspace_id,
+    file_id,
+    "image/png",
+    file_size,
+    LIMIT_BYTES,
+    BlobAttributes::default(),
+  )
+  .await?;
+  txn.commit().await.unwrap();
+  Ok(())
+}
+
+#[sqlx::test(migrations = false)]
+async fn concurrent_uploads_never_exceed_quota_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  // all 20 uploads together exceed the limit by 20 bytes, each one alone fits
+  let file_size = LIMIT_BYTES as usize / 20 + 1;
+  let uploads = (0..20).map(|i| {
+    let pool = pool.clone();
+    tokio::spawn(async move {
+      upload(
+        &pool,
+        &workspace_id,
+        &format!("parent_file{}", i),
+        file_size,
+      )
+      .await
+    })
+  });
+  let results = futures::future::join_all(uploads).await;
+  let rejected = results
+    .into_iter()
+    .map(|result| result.unwrap())
+    .filter(|result| matches!(result, Err(AppError::StorageSpaceNotEnough)))
+    .count();
+  assert_eq!(rejected, 1);
+
+  let usage = get_workspace_usage_size(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert!(usage <= LIMIT_BYTES);
+  assert_eq!(usage, 19 * file_size as u64);
+}
+
+#[sqlx::test(migrations = false)]
+async fn replaced_blob_does_not_count_toward_quota_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  insert_blob_metadata(
+    &pool,
+    "parent_a",
+    &workspace_id,
+    "image/png",
+    600,
+    None,
+    BlobAttributes::default(),
+  )
+  .await
+  .unwrap();
+  let result = upload(&pool, &workspace_id, "parent_b", 600).await;
+  assert!(matches!(result, Err(AppError::StorageSpaceNotEnough)));
+
+  // replacing a file only counts the size of the new content
+  upload(&pool, &workspace_id, "parent_a", 900).await.unwrap();
+  let usage = get_workspace_usage_size(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(usage, 900);
+
+  let result = upload(&pool, &Uuid::new_v4(), "parent_a", 1).await;
+  assert!(matches!(result, Err(AppError::RecordNotFound(_))));
+}
+
+#[sqlx::test(migrations = false)]
+async fn overwritten_blob_counts_its_archived_version_toward_quota_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+  let storage = S3BucketStorage::from_bucket_impl(TestBucket::new().await.0, pool.clone())
+    .with_storage_limit(Some(LIMIT_BYTES))
+    .with_version_policy(Some(BlobVersionPolicy {
+      max_versions: 10,
+      max_age_days: None,
+    }));
+  let key = BlobPathV1 {
+    workspace_id,
+    parent_dir: "doc".to_string(),
+    file_id: "notes.txt".to_string(),
+  };
+  let (storage, key) = (&storage, &key);
+  let put = move |size: usize| {
+    storage.put_blob_content(
+      key.clone(),
+      vec![0; size],
+      "text/plain".to_string(),
+      BlobAttributes::default(),
+    )
+  };
+
+  put(400).await.unwrap();
+  // the first content is archived, both count toward the usage
+  put(500).await.unwrap();
+  let usage = get_workspace_usage_size(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(usage, 900);
+
+  // a smaller content doesn't fit either, since the current one would be archived
+  let result = put(200).await;
+  assert!(matches!(result, Err(AppError::StorageSpaceNotEnough)));
+  let usage = get_workspace_usage_size(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(usage, 900);
+  let metadata = storage
+    .get_blob_metadata(&workspace_id, &key.blob_metadata_key())
+    .await
+    .unwrap();
+  assert_eq!(metadata.file_size, 500);
+
+  // the object written for the refused content is deleted
+  let objects = TestBucket::new()
+    .await
+    .list_objects(&key.object_key())
+    .await
+    .unwrap();
+  assert_eq!(objects.len(), 2);
+}
@@ -0,0 +1,109 @@
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use database::resource_usage::{
+  get_workspace_usage_size, insert_blob_metadata_bulk, max_blob_size_for_workspace, BulkInsertMeta,
+};
+use database::workspace::{upsert_workspace_ai_tier, upsert_workspace_subscription_plan};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const FREE_LIMIT: i64 = 10 * 1024 * 1024;
+const PRO_LIMIT: i64 = 1024 * 1024 * 1024;
+
+async fn create_workspace(pool: &PgPool) -> Uuid {
+  setup_db(pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  Uuid::parse_str(&user.workspace_id).unwrap()
+}
+
+fn meta(file_id: &str, file_size: i64) -> BulkInsertMeta {
+  BulkInsertMeta {
+    object_id: "doc".to_string(),
+    file_id: file_id.to_string(),
+    file_type: "image/png".to_string(),
+    file_size,
+    content_hash: None,
+  }
+}
+
+#[sqlx::test(migrations = false)]
+async fn max_blob_size_follows_plan_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  // without a recorded plan, such as on a self-hosted instance, there is no limit
+  let limit = max_blob_size_for_workspace(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(limit, None);
+
+  upsert_workspace_subscription_plan(&pool, &workspace_id, 0)
+    .await
+    .unwrap();
+  let limit = max_blob_size_for_workspace(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(limit, Some(FREE_LIMIT));
+
+  upsert_workspace_subscription_plan(&pool, &workspace_id, 1)
+    .await
+    .unwrap();
+  let limit = max_blob_size_for_workspace(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(limit, Some(PRO_LIMIT));
+
+  // an AI add-on doesn't replace the plan of the workspace
+  upsert_workspace_ai_tier(&pool, &workspace_id, 3)
+    .await
+    .unwrap();
+  let limit = max_blob_size_for_workspace(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(limit, Some(PRO_LIMIT));
+
+  let err = max_blob_size_for_workspace(&pool, &Uuid::new_v4())
+    .await
+    .unwrap_err();
+  assert!(err.is_record_not_found());
+}
+
+#[sqlx::test(migrations = false)]
+async fn bulk_insert_skips_blobs_over_plan_limit_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  upsert_workspace_subscription_plan(&pool, &workspace_id, 0)
+    .await
+    .unwrap();
+  let metadata = vec![
+    meta("at_limit", FREE_LIMIT),
+    meta("over_limit", FREE_LIMIT + 1),
+  ];
+  let inserted = insert_blob_metadata_bulk(&pool, &workspace_id, metadata, None)
+    .await
+    .unwrap();
+  assert_eq!(inserted.rows_affected, 1);
+  assert_eq!(inserted.inserted_bytes, FREE_LIMIT);
+  assert_eq!(inserted.too_large, 1);
+  let usage = get_workspace_usage_size(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(usage, FREE_LIMIT as u64);
+
+  // the same file fits the limit of the pro plan
+  upsert_workspace_subscription_plan(&pool, &workspace_id, 1)
+    .await
+    .unwrap();
+  let inserted = insert_blob_metadata_bulk(
+    &pool,
+    &workspace_id,
+    vec![meta("over_limit", FREE_LIMIT + 1)],
+    None,
+  )
+  .await
+  .unwrap();
+  assert_eq!(inserted.rows_affected, 1);
+  assert_eq!(inserted.too_large, 0);
+}
@@ -0,0 +1,96 @@
+use crate::sql_test::util::{setup_db, test_create_user, TestUser};
+
+use database::resource_usage::{
+  get_blob_usage_by_member, insert_blob_metadata, insert_blob_metadata_bulk, BlobAttributes,
+  BulkInsertMeta,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn create_user(pool: &PgPool) -> TestUser {
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  test_create_user(pool, user_uuid, &email, &name)
+    .await
+    .unwrap()
+}
+
+#[sqlx::test(migrations = false)]
+async fn blob_usage_is_grouped_by_uploader_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let owner = create_user(&pool).await;
+  let importer = create_user(&pool).await;
+  let workspace_id = Uuid::parse_str(&owner.workspace_id).unwrap();
+
+  insert_blob_metadata(
+    &pool,
+    "doc_a",
+    &workspace_id,
+    "image/png",
+    100,
+    None,
+    BlobAttributes::uploaded_by(owner.uid),
+  )
+  .await
+  .unwrap();
+  let metadata = ["a", "b"]
+    .into_iter()
+    .map(|file_id| BulkInsertMeta {
+      object_id: "imported".to_string(),
+      file_id: file_id.to_string(),
+      file_type: "image/png".to_string(),
+      file_size: 300,
+      content_hash: None,
+    })
+    .collect();
+  insert_blob_metadata_bulk(&pool, &workspace_id, metadata, Some(importer.uid))
+    .await
+    .unwrap();
+  // a blob uploaded before the uploaders were recorded
+  insert_blob_metadata(
+    &pool,
+    "doc_old",
+    &workspace_id,
+    "image/png",
+    7,
+    None,
+    BlobAttributes::default(),
+  )
+  .await
+  .unwrap();
+
+  let usage = get_blob_usage_by_member(&pool, &workspace_id)
+    .await
+    .unwrap();
+  let usage = usage
+    .iter()
+    .map(|usage| (usage.uid, usage.file_count, usage.total_size))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    usage,
+    vec![
+      (Some(importer.uid), 2, 600),
+      (Some(owner.uid), 1, 100),
+      (None, 1, 7)
+    ]
+  );
+
+  // uploading a blob again without a known uploader keeps its uploader
+  insert_blob_metadata(
+    &pool,
+    "doc_a",
+    &workspace_id,
+    "image/png",
+    50,
+    None,
+    BlobAttributes::default(),
+  )
+  .await
+  .unwrap();
+  let usage = get_blob_usage_by_member(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(usage[1].uid, Some(owner.uid));
+  assert_eq!(usage[1].total_size, 50);
+}
@@ -0,0 +1,170 @@
+use crate::file_test::TestBucket;
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use appflowy_cloud::api::file_storage::BlobPathV1;
+use aws_sdk_s3::primitives::ByteStream;
+use database::blob_variant::{
+  blob_variant_object_key, claim_blob_variant_tasks, complete_blob_variant_task,
+  select_blob_variant, upsert_blob_variant,
+};
+use database::file::s3_client_impl::S3BucketStorage;
+use database::file::{BlobKey, BucketClient};
+use database::resource_usage::BlobAttributes;
+use database_entity::file_dto::BlobVariant;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn create_workspace(pool: &PgPool) -> Uuid {
+  setup_db(pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  Uuid::parse_str(&user.workspace_id).unwrap()
+}
+
+fn blob_key(workspace_id: Uuid, file_id: &str) -> BlobPathV1 {
+  BlobPathV1 {
+    workspace_id,
+    parent_dir: "doc_a".to_string(),
+    file_id: file_id.to_string(),
+  }
+}
+
+#[sqlx::test(migrations = false)]
+async fn blob_variant_lifecycle_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  let bucket = TestBucket::new().await;
+  let storage = S3BucketStorage::from_bucket_impl(bucket.0.clone(), pool.clone());
+  let image = blob_key(workspace_id, "photo.png");
+  let animation = blob_key(workspace_id, "animation.gif");
+  for (key, file_type) in [(&image, "image/png"), (&animation, "image/gif")] {
+    storage
+      .put_blob_content(
+        key.clone(),
+        b"pixels".to_vec(),
+        file_type.to_string(),
+        BlobAttributes::default(),
+      )
+      .await
+      .unwrap();
+  }
+
+  // only the raster image is queued
+  let tasks = claim_blob_variant_tasks(&pool, 10, 300).await.unwrap();
+  assert_eq!(tasks.len(), 1);
+  assert_eq!(tasks[0].file_id, image.blob_metadata_key());
+  assert_eq!(tasks[0].attempts, 1);
+  // a claimed task isn't handed out again until its lease expires
+  assert!(claim_blob_variant_tasks(&pool, 10, 300)
+    .await
+    .unwrap()
+    .is_empty());
+
+  // record the thumbnail the way the worker does
+  let variant_key = blob_variant_object_key(&image.object_key(), BlobVariant::Thumb);
+  bucket
+    .put_blob(
+      &variant_key,
+      ByteStream::from(b"thumb".to_vec()),
+      Some("image/png"),
+    )
+    .await
+    .unwrap();
+  let mut txn = pool.begin().await.unwrap();
+  assert!(complete_blob_variant_task(
+    &mut *txn,
+    &workspace_id,
+    &tasks[0].file_id,
+    tasks[0].enqueued_at
+  )
+  .await
+  .unwrap());
+  upsert_blob_variant(
+    &mut txn,
+    &workspace_id,
+    &image.blob_metadata_key(),
+    BlobVariant::Thumb,
+    &variant_key,
+    "image/png",
+    5,
+    false,
+  )
+  .await
+  .unwrap();
+  txn.commit().await.unwrap();
+
+  let (row, content) = storage
+    .get_blob_variant(
+      &workspace_id,
+      &image.blob_metadata_key(),
+      BlobVariant::Thumb,
+    )
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(row.object_key, variant_key);
+  assert_eq!(content, b"thumb".to_vec());
+  // the medium variant wasn't generated, the blob is served instead
+  assert!(storage
+    .get_blob_variant(
+      &workspace_id,
+      &image.blob_metadata_key(),
+      BlobVariant::Medium
+    )
+    .await
+    .unwrap()
+    .is_none());
+
+  // uploading the image again makes its variants stale until they're generated again
+  storage
+    .put_blob_content(
+      image.clone(),
+      b"new pixels".to_vec(),
+      "image/png".to_string(),
+      BlobAttributes::default(),
+    )
+    .await
+    .unwrap();
+  assert!(select_blob_variant(
+    &pool,
+    &workspace_id,
+    &image.blob_metadata_key(),
+    BlobVariant::Thumb
+  )
+  .await
+  .unwrap()
+  .is_none());
+  let requeued = claim_blob_variant_tasks(&pool, 10, 300).await.unwrap();
+  assert_eq!(requeued.len(), 1);
+  assert_eq!(requeued[0].attempts, 1);
+  // the task claimed before the upload doesn't complete the new one
+  assert!(!complete_blob_variant_task(
+    &pool,
+    &workspace_id,
+    &tasks[0].file_id,
+    tasks[0].enqueued_at
+  )
+  .await
+  .unwrap());
+
+  // deleting the image deletes its variants
+  storage.delete_blob(image.clone()).await.unwrap();
+  let variants: i64 =
+    sqlx::query_scalar("SELECT COUNT(*) FROM af_blob_variant WHERE workspace_id = $1")
+      .bind(workspace_id)
+      .fetch_one(&pool)
+      .await
+      .unwrap();
+  assert_eq!(variants, 0);
+  assert!(bucket.get_blob(&variant_key).await.is_err());
+  let tasks: i64 =
+    sqlx::query_scalar("SELECT COUNT(*) FROM af_blob_variant_task WHERE workspace_id = $1")
+      .bind(workspace_id)
+      .fetch_one(&pool)
+      .await
+      .unwrap();
+  assert_eq!(tasks, 0);
+}
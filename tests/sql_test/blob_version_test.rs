@@ -0,0 +1,244 @@
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use database::resource_usage::{
+  delete_all_blob_versions, delete_blob_metadata_bulk, delete_blob_metadata_by_prefix,
+  delete_expired_blob_versions, get_workspace_usage_breakdown, get_workspace_usage_size,
+  insert_blob_metadata, insert_blob_metadata_bulk, insert_blob_version, select_blob_versions,
+  select_next_blob_version, BlobAttributes, BulkInsertMeta,
+};
+use shared_entity::dto::workspace_dto::FileTypeCategory;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[sqlx::test(migrations = false)]
+async fn blob_version_retention_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+  let file_id = "parent_file";
+
+  insert_blob_metadata(
+    &pool,
+    file_id,
+    &workspace_id,
+    "text/plain",
+    10,
+    None,
+    BlobAttributes::default(),
+  )
+  .await
+  .unwrap();
+
+  let mut txn = pool.begin().await.unwrap();
+  for _ in 0..5 {
+    let version = select_next_blob_version(&mut txn, &workspace_id, file_id)
+      .await
+      .unwrap();
+    let object_key = format!("{}/parent/file@v{}", workspace_id, version);
+    insert_blob_version(
+      &mut txn,
+      &workspace_id,
+      file_id,
+      version,
+      &object_key,
+      "text/plain",
+      3,
+    )
+    .await
+    .unwrap();
+  }
+  txn.commit().await.unwrap();
+
+  // archived versions count toward the workspace usage
+  let usage = get_workspace_usage_size(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(usage, 10 + 5 * 3);
+
+  let mut txn = pool.begin().await.unwrap();
+  let pruned = delete_expired_blob_versions(&mut txn, &workspace_id, file_id, 2, Some(30))
+    .await
+    .unwrap();
+  txn.commit().await.unwrap();
+  assert_eq!(pruned.len(), 3);
+
+  let versions = select_blob_versions(&pool, &workspace_id, file_id)
+    .await
+    .unwrap();
+  let kept = versions.iter().map(|v| v.version).collect::<Vec<_>>();
+  assert_eq!(kept, vec![5, 4]);
+
+  let mut txn = pool.begin().await.unwrap();
+  let deleted = delete_all_blob_versions(&mut txn, &workspace_id, file_id)
+    .await
+    .unwrap();
+  txn.commit().await.unwrap();
+  assert_eq!(deleted.len(), 2);
+  let usage = get_workspace_usage_size(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(usage, 10);
+}
+
+#[sqlx::test(migrations = false)]
+async fn delete_blob_metadata_by_prefix_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  // `_` and `%` match any character in a LIKE pattern, the prefix must match them literally
+  for file_id in [
+    "doc_%_a",
+    "doc_%_b",
+    "docX%_c",
+    "doc_%Xd",
+    "doc_%_e_f",
+    "other_a",
+  ] {
+    insert_blob_metadata(
+      &pool,
+      file_id,
+      &workspace_id,
+      "image/png",
+      10,
+      None,
+      BlobAttributes::default(),
+    )
+    .await
+    .unwrap();
+  }
+
+  let mut txn = pool.begin().await.unwrap();
+  let mut deleted = delete_blob_metadata_by_prefix(&mut txn, &workspace_id, "doc_%")
+    .await
+    .unwrap();
+  txn.commit().await.unwrap();
+  deleted.sort();
+  assert_eq!(deleted, vec!["doc_%_a", "doc_%_b", "doc_%_e_f"]);
+
+  let usage = get_workspace_usage_size(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(usage, 3 * 10);
+}
+
+#[sqlx::test(migrations = false)]
+async fn workspace_usage_breakdown_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  for (file_id, file_type, file_size) in [
+    ("a", "image/png", 100),
+    ("b", "IMAGE/JPEG", 200),
+    ("c", "video/mp4", 1000),
+    ("d", "audio/mpeg; charset=binary", 50),
+    ("e", "application/pdf", 30),
+    ("f", "", 7),
+    ("g", "application/octet-stream", 3),
+  ] {
+    insert_blob_metadata(
+      &pool,
+      file_id,
+      &workspace_id,
+      file_type,
+      file_size,
+      None,
+      BlobAttributes::default(),
+    )
+    .await
+    .unwrap();
+  }
+
+  let breakdown = get_workspace_usage_breakdown(&pool, &workspace_id)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|usage| (usage.category, usage.file_count, usage.total_size))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    breakdown,
+    vec![
+      (FileTypeCategory::Audio, 1, 50),
+      (FileTypeCategory::Image, 2, 300),
+      (FileTypeCategory::Other, 2, 10),
+      (FileTypeCategory::Pdf, 1, 30),
+      (FileTypeCategory::Video, 1, 1000),
+    ]
+  );
+}
+
+#[sqlx::test(migrations = false)]
+async fn delete_blob_metadata_bulk_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  let metadata = (0..1000)
+    .map(|i| BulkInsertMeta {
+      object_id: "doc".to_string(),
+      file_id: format!("image_{}", i),
+      file_type: "image/png".to_string(),
+      file_size: 10,
+      content_hash: None,
+    })
+    .collect();
+  let inserted = insert_blob_metadata_bulk(&pool, &workspace_id, metadata, None)
+    .await
+    .unwrap();
+  assert_eq!(inserted.rows_affected, 1000);
+  assert_eq!(inserted.inserted_bytes, 10_000);
+  insert_blob_metadata(
+    &pool,
+    "doc_kept",
+    &workspace_id,
+    "image/png",
+    10,
+    None,
+    BlobAttributes::default(),
+  )
+  .await
+  .unwrap();
+
+  // the file ids without metadata are skipped
+  let file_ids = (0..1000)
+    .map(|i| format!("doc_image_{}", i))
+    .chain(["doc_missing".to_string()])
+    .collect::<Vec<_>>();
+  let mut txn = pool.begin().await.unwrap();
+  let deleted = delete_blob_metadata_bulk(&mut txn, &workspace_id, &file_ids)
+    .await
+    .unwrap();
+  txn.commit().await.unwrap();
+  assert_eq!(deleted.len(), 1000);
+  assert!(!deleted.contains(&"doc_missing".to_string()));
+
+  let usage = get_workspace_usage_size(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(usage, 10);
+}
@@ -0,0 +1,106 @@
+use crate::sql_test::util::{setup_db, test_create_user};
+use database::chat::chat_ops::{insert_chat, select_chat};
+use database::chat::chat_share::{
+  select_shared_chats, transfer_chats_of_deleted_user, update_chat_owner, update_chat_share,
+};
+use database::workspace::upsert_workspace_member;
+use database_entity::dto::AFRole;
+use shared_entity::dto::chat_dto::{ChatShareScope, CreateChatParams};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn create_owned_chat(pool: &PgPool, workspace_id: &str, owner_uid: i64) -> Uuid {
+  let chat_id = Uuid::new_v4();
+  insert_chat(
+    pool,
+    workspace_id,
+    CreateChatParams {
+      chat_id: chat_id.to_string(),
+      name: "shared chat".to_string(),
+      rag_ids: vec![],
+    },
+  )
+  .await
+  .unwrap();
+  update_chat_owner(pool, &chat_id, owner_uid).await.unwrap();
+  chat_id
+}
+
+#[sqlx::test(migrations = false)]
+async fn shared_chat_scope_and_owner_deletion_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+
+  let mut users = vec![];
+  for _ in 0..3 {
+    let user_uuid = Uuid::new_v4();
+    let name = user_uuid.to_string();
+    let email = format!("{}@appflowy.io", name);
+    let user = test_create_user(&pool, user_uuid, &email, &name)
+      .await
+      .unwrap();
+    users.push((user, email));
+  }
+  let (owner, _) = &users[0];
+  let workspace_id = Uuid::parse_str(&owner.workspace_id).unwrap();
+  for (_, email) in &users[1..] {
+    upsert_workspace_member(&pool, &workspace_id, email, AFRole::Member)
+      .await
+      .unwrap();
+  }
+  let (sharer, _) = &users[1];
+  let (reader, reader_email) = &users[2];
+
+  let workspace_chat = create_owned_chat(&pool, &owner.workspace_id, sharer.uid).await;
+  let members_chat = create_owned_chat(&pool, &owner.workspace_id, sharer.uid).await;
+  let private_chat = create_owned_chat(&pool, &owner.workspace_id, sharer.uid).await;
+
+  let mut txn = pool.begin().await.unwrap();
+  update_chat_share(
+    &mut txn,
+    &workspace_chat,
+    ChatShareScope::Workspace as i16,
+    false,
+    &[],
+  )
+  .await
+  .unwrap();
+  let inserted = update_chat_share(
+    &mut txn,
+    &members_chat,
+    ChatShareScope::Members as i16,
+    true,
+    &[
+      reader_email.to_uppercase(),
+      "stranger@appflowy.io".to_string(),
+    ],
+  )
+  .await
+  .unwrap();
+  txn.commit().await.unwrap();
+  // only the members of the workspace can be listed
+  assert_eq!(inserted, 1);
+
+  let shared_with_owner = select_shared_chats(&pool, &workspace_id, owner.uid)
+    .await
+    .unwrap();
+  assert_eq!(shared_with_owner.len(), 1);
+  assert_eq!(shared_with_owner[0].chat_id, workspace_chat);
+  let shared_with_reader = select_shared_chats(&pool, &workspace_id, reader.uid)
+    .await
+    .unwrap();
+  assert_eq!(shared_with_reader.len(), 2);
+
+  // the sharer deletes their account
+  let mut txn = pool.begin().await.unwrap();
+  let transferred = transfer_chats_of_deleted_user(&mut txn, sharer.uid)
+    .await
+    .unwrap();
+  txn.commit().await.unwrap();
+  assert_eq!(transferred, 2);
+
+  let chat = select_chat(&pool, &workspace_chat.to_string())
+    .await
+    .unwrap();
+  assert_eq!(chat.owner_uid, Some(owner.uid));
+  assert!(select_chat(&pool, &private_chat.to_string()).await.is_err());
+}
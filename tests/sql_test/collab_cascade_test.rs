@@ -0,0 +1,175 @@
+use crate::sql_test::util::{generate_random_bytes, setup_db, test_create_user};
+
+use chrono::{Duration, Utc};
+use collab_entity::CollabType;
+use database::collab::{
+  collab_object_key, delete_collab_cascade, insert_collab_snapshot_info, insert_into_af_collab,
+  select_collabs_deleted_before, select_deleted_collabs,
+};
+use database::index::upsert_collab_fingerprint;
+use database::resource_usage::{insert_blob_metadata_bulk, BulkInsertMeta};
+use database_entity::dto::CollabParams;
+use sqlx::PgPool;
+use std::ops::DerefMut;
+use uuid::Uuid;
+
+async fn count_rows(pool: &PgPool, table: &str, object_id: &str) -> i64 {
+  sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {} WHERE oid = $1", table))
+    .bind(object_id)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+#[sqlx::test(migrations = false)]
+async fn delete_collab_cascade_leaves_nothing_behind_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let user = test_create_user(&pool, user_uuid, &format!("{}@appflowy.io", name), &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  // a document with two images, an embedding and a snapshot
+  let object_id = Uuid::new_v4().to_string();
+  let mut txn = pool.begin().await.unwrap();
+  insert_into_af_collab(
+    &mut txn,
+    &user.uid,
+    &user.workspace_id,
+    &CollabParams {
+      object_id: object_id.clone(),
+      collab_type: CollabType::Document,
+      encoded_collab_v1: generate_random_bytes(1024).into(),
+    },
+  )
+  .await
+  .unwrap();
+  txn.commit().await.unwrap();
+  let images = ["first.png", "second.png"].map(|file_id| BulkInsertMeta {
+    object_id: object_id.clone(),
+    file_id: file_id.to_string(),
+    file_type: "image/png".to_string(),
+    file_size: 100,
+    content_hash: None,
+  });
+  let inserted = insert_blob_metadata_bulk(&pool, &workspace_id, images.to_vec(), None)
+    .await
+    .unwrap();
+  assert_eq!(inserted.rows_affected, 2);
+  sqlx::query(
+    r#"
+      INSERT INTO af_collab_embeddings (fragment_id, oid, partition_key, content_type, content)
+      VALUES ($1, $2, 0, 0, 'hello world')
+    "#,
+  )
+  .bind(Uuid::new_v4().to_string())
+  .bind(&object_id)
+  .execute(&pool)
+  .await
+  .unwrap();
+  upsert_collab_fingerprint(&pool, &workspace_id, &object_id, 1, 11)
+    .await
+    .unwrap();
+  insert_collab_snapshot_info(&pool, &workspace_id, &object_id, 42, 1024, Some(user.uid))
+    .await
+    .unwrap();
+
+  let mut txn = pool.begin().await.unwrap();
+  let object_keys = delete_collab_cascade(&mut txn, &workspace_id, &object_id)
+    .await
+    .unwrap();
+  txn.commit().await.unwrap();
+
+  for table in [
+    "af_collab",
+    "af_collab_embeddings",
+    "af_collab_fingerprint",
+    "af_collab_snapshot_info",
+    "af_collab_snapshot",
+  ] {
+    assert_eq!(count_rows(&pool, table, &object_id).await, 0, "{}", table);
+  }
+  let blobs: i64 = sqlx::query_scalar(
+    "SELECT COUNT(*) FROM af_blob_metadata WHERE workspace_id = $1 AND file_id LIKE $2",
+  )
+  .bind(workspace_id)
+  .bind(format!("{}_%", object_id))
+  .fetch_one(&pool)
+  .await
+  .unwrap();
+  assert_eq!(blobs, 0);
+
+  // the caller deletes the doc state, the snapshot and both images from the bucket
+  assert_eq!(object_keys.len(), 4);
+  assert!(object_keys.contains(&collab_object_key(&user.workspace_id, &object_id)));
+  for file_id in ["first.png", "second.png"] {
+    assert!(object_keys.contains(&format!("{}/{}/{}", workspace_id, object_id, file_id)));
+  }
+}
+
+#[sqlx::test(migrations = false)]
+async fn trash_sweep_only_selects_collabs_past_the_retention_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let user = test_create_user(&pool, user_uuid, &format!("{}@appflowy.io", name), &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  // one collab deleted 31 days ago, one a day ago and one still alive
+  let mut object_ids = vec![];
+  let mut txn = pool.begin().await.unwrap();
+  for _ in 0..3 {
+    let object_id = Uuid::new_v4().to_string();
+    insert_into_af_collab(
+      &mut txn,
+      &user.uid,
+      &user.workspace_id,
+      &CollabParams {
+        object_id: object_id.clone(),
+        collab_type: CollabType::Document,
+        encoded_collab_v1: generate_random_bytes(1024).into(),
+      },
+    )
+    .await
+    .unwrap();
+    object_ids.push(object_id);
+  }
+  txn.commit().await.unwrap();
+  for (object_id, days) in [(&object_ids[0], 31), (&object_ids[1], 1)] {
+    sqlx::query(
+      "UPDATE af_collab SET deleted_at = NOW() - make_interval(days => $2) WHERE oid = $1",
+    )
+    .bind(object_id)
+    .bind(days)
+    .execute(&pool)
+    .await
+    .unwrap();
+  }
+
+  let retention = Duration::days(30);
+  let mut txn = pool.begin().await.unwrap();
+  let expired = select_collabs_deleted_before(txn.deref_mut(), Utc::now() - retention, 10)
+    .await
+    .unwrap();
+  assert_eq!(expired.len(), 1);
+  assert_eq!(expired[0].oid, object_ids[0]);
+  delete_collab_cascade(&mut txn, &workspace_id, &expired[0].oid)
+    .await
+    .unwrap();
+  txn.commit().await.unwrap();
+
+  assert_eq!(count_rows(&pool, "af_collab", &object_ids[0]).await, 0);
+  // the collab deleted a day ago can still be restored
+  let oids = select_deleted_collabs(&pool, &workspace_id)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| row.oid)
+    .collect::<Vec<_>>();
+  assert_eq!(oids, vec![object_ids[1].clone()]);
+  assert_eq!(count_rows(&pool, "af_collab", &object_ids[2]).await, 1);
+}
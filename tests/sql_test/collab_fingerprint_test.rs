@@ -0,0 +1,49 @@
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use database::index::{
+  delete_collab_fingerprint, select_workspace_collab_fingerprints, upsert_collab_fingerprint,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[sqlx::test(migrations = false)]
+async fn collab_fingerprint_is_replaced_when_reindexed_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let user = test_create_user(&pool, user_uuid, &format!("{}@appflowy.io", name), &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  let small = Uuid::new_v4().to_string();
+  let large = Uuid::new_v4().to_string();
+  upsert_collab_fingerprint(&pool, &workspace_id, &small, 1, 10)
+    .await
+    .unwrap();
+  upsert_collab_fingerprint(&pool, &workspace_id, &large, 2, 20)
+    .await
+    .unwrap();
+  upsert_collab_fingerprint(&pool, &workspace_id, &small, 3, 30)
+    .await
+    .unwrap();
+
+  // the largest documents come first
+  let rows = select_workspace_collab_fingerprints(&pool, &workspace_id, 10)
+    .await
+    .unwrap();
+  assert_eq!(rows.len(), 2);
+  assert_eq!(rows[0].oid, small);
+  assert_eq!(rows[0].simhash, 3);
+  assert_eq!(rows[1].oid, large);
+
+  delete_collab_fingerprint(&pool, &large).await.unwrap();
+  let rows = select_workspace_collab_fingerprints(&pool, &workspace_id, 10)
+    .await
+    .unwrap();
+  assert_eq!(rows.len(), 1);
+  let other_workspace = select_workspace_collab_fingerprints(&pool, &Uuid::new_v4(), 10)
+    .await
+    .unwrap();
+  assert!(other_workspace.is_empty());
+}
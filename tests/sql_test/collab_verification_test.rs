@@ -0,0 +1,87 @@
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use chrono::Utc;
+use database::collab_verification::{
+  select_unrepaired_collab_verification_report, update_collab_verification_report_repaired,
+  upsert_collab_verification_report,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[sqlx::test(migrations = false)]
+async fn collab_has_one_report_awaiting_repair_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+  let object_id = Uuid::new_v4().to_string();
+
+  let first = upsert_collab_verification_report(
+    &pool,
+    &workspace_id,
+    &object_id,
+    0,
+    "http",
+    "failed to decode doc state",
+    None,
+    None,
+  )
+  .await
+  .unwrap();
+  // detected again by the realtime server, this time a snapshot could be recovered
+  let snapshot_at = Utc::now();
+  let second = upsert_collab_verification_report(
+    &pool,
+    &workspace_id,
+    &object_id,
+    0,
+    "realtime",
+    "failed to decode doc state",
+    Some(42),
+    Some(snapshot_at),
+  )
+  .await
+  .unwrap();
+  assert_eq!(first.report_id, second.report_id);
+  assert_eq!(second.detection_count, 2);
+  assert_eq!(second.detected_on, "http");
+  assert_eq!(second.recovered_snapshot_id, Some(42));
+
+  let repaired = update_collab_verification_report_repaired(&pool, second.report_id, user.uid)
+    .await
+    .unwrap()
+    .unwrap();
+  assert!(repaired.repaired_at.is_some());
+  assert!(
+    update_collab_verification_report_repaired(&pool, second.report_id, user.uid)
+      .await
+      .unwrap()
+      .is_none()
+  );
+  assert!(
+    select_unrepaired_collab_verification_report(&pool, &object_id)
+      .await
+      .unwrap()
+      .is_none()
+  );
+
+  // a corruption found after the repair opens a new report
+  let third = upsert_collab_verification_report(
+    &pool,
+    &workspace_id,
+    &object_id,
+    0,
+    "http",
+    "failed to decode doc state",
+    None,
+    None,
+  )
+  .await
+  .unwrap();
+  assert_ne!(third.report_id, second.report_id);
+  assert_eq!(third.detection_count, 1);
+}
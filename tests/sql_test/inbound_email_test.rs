@@ -0,0 +1,59 @@
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use database::inbound_email::{
+  select_workspace_inbound_email, select_workspace_inbound_email_by_token,
+  update_workspace_inbound_email_token, upsert_workspace_inbound_email,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[sqlx::test(migrations = false)]
+async fn rotated_inbound_address_stops_resolving_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  let view_id = Uuid::new_v4();
+  let created = upsert_workspace_inbound_email(&pool, &workspace_id, &view_id, "first", user.uid)
+    .await
+    .unwrap();
+  assert_eq!(created.token, "first");
+
+  // changing the page keeps the address
+  let other_view_id = Uuid::new_v4();
+  let updated =
+    upsert_workspace_inbound_email(&pool, &workspace_id, &other_view_id, "unused", user.uid)
+      .await
+      .unwrap();
+  assert_eq!(updated.token, "first");
+  assert_eq!(updated.view_id, other_view_id);
+
+  let rotated = update_workspace_inbound_email_token(&pool, &workspace_id, "second")
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(rotated.token, "second");
+  assert!(rotated.rotated_at.is_some());
+  assert!(select_workspace_inbound_email_by_token(&pool, "first")
+    .await
+    .unwrap()
+    .is_none());
+  let resolved = select_workspace_inbound_email_by_token(&pool, "second")
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(resolved.workspace_id, workspace_id);
+  assert_eq!(
+    select_workspace_inbound_email(&pool, &workspace_id)
+      .await
+      .unwrap()
+      .unwrap()
+      .view_id,
+    other_view_id
+  );
+}
@@ -0,0 +1,116 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use appflowy_collaborate::collab::cache::CollabCache;
+use appflowy_collaborate::load_shed::{LoadShedConfig, LoadShedder, PressureSample, ShedTier};
+use appflowy_collaborate::metrics::{CollabMetrics, LoadShedMetrics};
+use async_trait::async_trait;
+use database::operation_delay::{DelayedDependency, OperationDelay};
+use sqlx::PgPool;
+
+use crate::collab::util::redis_connection_manager;
+use crate::file_test::TestBucket;
+
+/// Slows down Postgres, as a database which can't keep up with the queries would.
+#[derive(Default)]
+struct SlowPostgres {
+  delay_ms: AtomicU64,
+}
+
+#[async_trait]
+impl OperationDelay for SlowPostgres {
+  async fn delay(&self, dependency: DelayedDependency, _operation: &str) {
+    let delay_ms = self.delay_ms.load(Ordering::Relaxed);
+    if dependency == DelayedDependency::Postgres && delay_ms > 0 {
+      tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+    }
+  }
+}
+
+fn shed_tiers(shedder: &LoadShedder) -> Vec<&'static str> {
+  [
+    ShedTier::Awareness,
+    ShedTier::CatchUp,
+    ShedTier::Interactive,
+  ]
+  .into_iter()
+  .filter(|tier| shedder.should_shed(*tier))
+  .map(|tier| tier.as_str())
+  .collect()
+}
+
+fn shed_api_writes(shedder: &LoadShedder) -> usize {
+  (0..100)
+    .filter(|_| shedder.should_shed(ShedTier::ApiWrite))
+    .count()
+}
+
+#[sqlx::test(migrations = false)]
+async fn load_shedding_tiers_with_slow_postgres_sql_test(pool: PgPool) {
+  let slow_postgres = Arc::new(SlowPostgres::default());
+  let cache = CollabCache::new(
+    redis_connection_manager().await,
+    pool,
+    TestBucket::new().await.0,
+    Arc::new(CollabMetrics::default()),
+    8000,
+  )
+  .with_operation_delay(slow_postgres.clone());
+  let metrics = Arc::new(LoadShedMetrics::default());
+  let shedder = LoadShedder::new(
+    LoadShedConfig {
+      pg_acquire_threshold: Duration::from_millis(200),
+      // only Postgres is slowed down
+      redis_lag_threshold: Duration::from_secs(5),
+      ..Default::default()
+    },
+    metrics.clone(),
+  );
+  let sample = |delay_ms: u64| {
+    slow_postgres.delay_ms.store(delay_ms, Ordering::Relaxed);
+    let cache = cache.clone();
+    async move { PressureSample::collect(&cache, 0).await }
+  };
+
+  shedder.record_sample(&sample(0).await);
+  assert!(shed_tiers(&shedder).is_empty());
+  assert_eq!(shed_api_writes(&shedder), 0);
+
+  // awareness goes first
+  let pressure = shedder.record_sample(&sample(240).await);
+  assert!((1.0..1.5).contains(&pressure), "{}", pressure);
+  assert_eq!(shed_tiers(&shedder), vec!["awareness"]);
+  assert_eq!(shed_api_writes(&shedder), 0);
+
+  // then the catch-up syncs
+  shedder.record_sample(&sample(340).await);
+  assert_eq!(shed_tiers(&shedder), vec!["awareness", "catch_up"]);
+  assert_eq!(shed_api_writes(&shedder), 0);
+
+  // then half of the API writes, the editors still being served
+  shedder.record_sample(&sample(500).await);
+  assert_eq!(shed_tiers(&shedder), vec!["awareness", "catch_up"]);
+  assert_eq!(shed_api_writes(&shedder), 50);
+
+  // and the editors last
+  shedder.record_sample(&sample(900).await);
+  assert_eq!(
+    shed_tiers(&shedder),
+    vec!["awareness", "catch_up", "interactive"]
+  );
+  assert_eq!(shed_api_writes(&shedder), 100);
+  assert!(shedder.check(ShedTier::Interactive).is_err());
+
+  // the shedding stops once Postgres recovers
+  for _ in 0..4 {
+    shedder.record_sample(&sample(0).await);
+  }
+  assert!(shed_tiers(&shedder).is_empty());
+  assert_eq!(shed_api_writes(&shedder), 0);
+
+  assert_eq!(metrics.shed_count("awareness"), 4);
+  assert_eq!(metrics.shed_count("catch_up"), 3);
+  assert_eq!(metrics.shed_count("api_write"), 150);
+  assert_eq!(metrics.shed_count("interactive"), 2);
+}
@@ -0,0 +1,289 @@
+use crate::file_test::TestBucket;
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use app_error::ErrorCode;
+use appflowy_cloud::biz::maintenance::blob_version_retention::BlobVersionRetention;
+use appflowy_cloud::biz::maintenance::orphan_blob_gc::OrphanBlobGc;
+use appflowy_cloud::biz::maintenance::{execute_last_dry_run, run_maintenance_job};
+use aws_sdk_s3::primitives::ByteStream;
+use chrono::Duration;
+use database::blob_variant::{blob_variant_object_key, upsert_blob_variant};
+use database::file::s3_client_impl::S3BucketStorage;
+use database::file::{BlobVersionPolicy, BucketClient};
+use database::resource_usage::{
+  insert_blob_metadata, insert_blob_version, select_blob_versions, BlobAttributes,
+};
+use database_entity::file_dto::BlobVariant;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+async fn put_object(bucket: &TestBucket, object_key: &str) {
+  bucket
+    .put_blob(
+      object_key,
+      ByteStream::from(b"hello world".to_vec()),
+      Some("text/plain"),
+    )
+    .await
+    .unwrap();
+}
+
+async fn object_exists(bucket: &TestBucket, object_key: &str) -> bool {
+  bucket.get_blob(object_key).await.is_ok()
+}
+
+#[sqlx::test(migrations = false)]
+async fn orphan_blob_gc_dry_run_matches_execution_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  let bucket = TestBucket::new().await;
+  let storage = Arc::new(S3BucketStorage::from_bucket_impl(
+    bucket.0.clone(),
+    pool.clone(),
+  ));
+
+  // a blob with metadata, one of its archived versions, and an object without metadata
+  let tracked_key = format!("{}/parent/tracked", workspace_id);
+  let version_key = format!("{}@v1", tracked_key);
+  let orphan_key = format!("{}/parent/orphan", workspace_id);
+  put_object(&bucket, &tracked_key).await;
+  put_object(&bucket, &version_key).await;
+  put_object(&bucket, &orphan_key).await;
+  insert_blob_metadata(
+    &pool,
+    "parent_tracked",
+    &workspace_id,
+    "text/plain",
+    11,
+    None,
+    BlobAttributes::default(),
+  )
+  .await
+  .unwrap();
+  let mut tx = pool.begin().await.unwrap();
+  insert_blob_version(
+    &mut tx,
+    &workspace_id,
+    "parent_tracked",
+    1,
+    &version_key,
+    "text/plain",
+    11,
+  )
+  .await
+  .unwrap();
+  tx.commit().await.unwrap();
+
+  let job = OrphanBlobGc::new(pool.clone(), storage, Duration::zero());
+  let dry_run = run_maintenance_job(&pool, &job, workspace_id, user.uid, true)
+    .await
+    .unwrap();
+  assert!(dry_run.report_id.is_some());
+  assert_eq!(dry_run.selected_count, 1);
+  assert_eq!(dry_run.estimated_bytes, 11);
+  assert_eq!(dry_run.sample_ids, vec![orphan_key.clone()]);
+  assert_eq!(dry_run.deleted_count, None);
+  // a dry run does not delete anything
+  assert!(object_exists(&bucket, &orphan_key).await);
+
+  let executed = execute_last_dry_run(&pool, &job, workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(executed.report_id, dry_run.report_id);
+  assert_eq!(executed.selection_hash, dry_run.selection_hash);
+  assert_eq!(executed.deleted_count, Some(dry_run.selected_count));
+  assert!(!object_exists(&bucket, &orphan_key).await);
+  assert!(object_exists(&bucket, &tracked_key).await);
+  assert!(object_exists(&bucket, &version_key).await);
+
+  // the same dry run can not be executed twice
+  let err = execute_last_dry_run(&pool, &job, workspace_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code(), ErrorCode::StaleDryRun);
+
+  let dry_run = run_maintenance_job(&pool, &job, workspace_id, user.uid, true)
+    .await
+    .unwrap();
+  assert_eq!(dry_run.selected_count, 0);
+}
+
+#[sqlx::test(migrations = false)]
+async fn orphan_blob_gc_keeps_blob_variants_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  let bucket = TestBucket::new().await;
+  let storage = Arc::new(S3BucketStorage::from_bucket_impl(
+    bucket.0.clone(),
+    pool.clone(),
+  ));
+
+  // an image with metadata and its variants, which have no metadata of their own
+  let image_key = format!("{}/parent/photo", workspace_id);
+  put_object(&bucket, &image_key).await;
+  insert_blob_metadata(
+    &pool,
+    "parent_photo",
+    &workspace_id,
+    "image/png",
+    11,
+    None,
+    BlobAttributes::default(),
+  )
+  .await
+  .unwrap();
+  let mut variant_keys = vec![];
+  let mut tx = pool.begin().await.unwrap();
+  for variant in BlobVariant::ALL {
+    let variant_key = blob_variant_object_key(&image_key, variant);
+    put_object(&bucket, &variant_key).await;
+    upsert_blob_variant(
+      &mut tx,
+      &workspace_id,
+      "parent_photo",
+      variant,
+      &variant_key,
+      "image/webp",
+      11,
+      false,
+    )
+    .await
+    .unwrap();
+    variant_keys.push(variant_key);
+  }
+  tx.commit().await.unwrap();
+
+  let job = OrphanBlobGc::new(pool.clone(), storage, Duration::zero());
+  let executed = run_maintenance_job(&pool, &job, workspace_id, user.uid, false)
+    .await
+    .unwrap();
+  assert_eq!(executed.selected_count, 0);
+  assert!(object_exists(&bucket, &image_key).await);
+  for variant_key in variant_keys {
+    assert!(object_exists(&bucket, &variant_key).await);
+  }
+}
+
+#[sqlx::test(migrations = false)]
+async fn drifted_dry_run_is_not_executed_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  let bucket = TestBucket::new().await;
+  let storage = Arc::new(S3BucketStorage::from_bucket_impl(
+    bucket.0.clone(),
+    pool.clone(),
+  ));
+  let first_orphan = format!("{}/parent/first", workspace_id);
+  put_object(&bucket, &first_orphan).await;
+
+  let job = OrphanBlobGc::new(pool.clone(), storage, Duration::zero());
+  let dry_run = run_maintenance_job(&pool, &job, workspace_id, user.uid, true)
+    .await
+    .unwrap();
+  assert_eq!(dry_run.selected_count, 1);
+
+  // the selection changes after the dry run
+  let second_orphan = format!("{}/parent/second", workspace_id);
+  put_object(&bucket, &second_orphan).await;
+  let err = execute_last_dry_run(&pool, &job, workspace_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code(), ErrorCode::StaleDryRun);
+  assert!(object_exists(&bucket, &first_orphan).await);
+  assert!(object_exists(&bucket, &second_orphan).await);
+}
+
+#[sqlx::test(migrations = false)]
+async fn blob_version_retention_dry_run_matches_execution_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  let bucket = TestBucket::new().await;
+  let storage = Arc::new(S3BucketStorage::from_bucket_impl(
+    bucket.0.clone(),
+    pool.clone(),
+  ));
+  let object_key = format!("{}/parent/file", workspace_id);
+  let mut tx = pool.begin().await.unwrap();
+  for version in 1..=3 {
+    let version_key = format!("{}@v{}", object_key, version);
+    put_object(&bucket, &version_key).await;
+    insert_blob_version(
+      &mut tx,
+      &workspace_id,
+      "parent_file",
+      version,
+      &version_key,
+      "text/plain",
+      11,
+    )
+    .await
+    .unwrap();
+  }
+  tx.commit().await.unwrap();
+
+  let policy = BlobVersionPolicy {
+    max_versions: 1,
+    max_age_days: None,
+  };
+  let job = BlobVersionRetention::new(pool.clone(), storage, policy);
+  let dry_run = run_maintenance_job(&pool, &job, workspace_id, user.uid, true)
+    .await
+    .unwrap();
+  assert_eq!(dry_run.selected_count, 2);
+  assert_eq!(dry_run.estimated_bytes, 22);
+  assert_eq!(
+    dry_run.sample_ids,
+    vec!["parent_file@v1".to_string(), "parent_file@v2".to_string()]
+  );
+  let versions = select_blob_versions(&pool, &workspace_id, "parent_file")
+    .await
+    .unwrap();
+  assert_eq!(versions.len(), 3);
+
+  let executed = run_maintenance_job(&pool, &job, workspace_id, user.uid, false)
+    .await
+    .unwrap();
+  assert!(executed.report_id.is_none());
+  assert_eq!(executed.selection_hash, dry_run.selection_hash);
+  assert_eq!(executed.deleted_count, Some(dry_run.selected_count));
+
+  let versions = select_blob_versions(&pool, &workspace_id, "parent_file")
+    .await
+    .unwrap();
+  assert_eq!(
+    versions.iter().map(|v| v.version).collect::<Vec<_>>(),
+    vec![3]
+  );
+  assert!(!object_exists(&bucket, &format!("{}@v1", object_key)).await);
+  assert!(!object_exists(&bucket, &format!("{}@v2", object_key)).await);
+  assert!(object_exists(&bucket, &format!("{}@v3", object_key)).await);
+}
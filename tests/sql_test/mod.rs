@@ -1,4 +1,33 @@
+mod blob_access_test;
+mod blob_copy_test;
+mod blob_dedup_test;
+mod blob_expiry_test;
+mod blob_metadata_export_test;
+mod blob_metadata_page_test;
+mod blob_quota_test;
+mod blob_size_limit_test;
+mod blob_usage_by_member_test;
+mod blob_variant_test;
+mod blob_version_test;
+mod chat_share_test;
 mod chat_test;
+mod collab_cascade_test;
+mod collab_fingerprint_test;
+mod collab_verification_test;
 mod history_test;
+mod inbound_email_test;
+mod load_shed_test;
+mod maintenance_test;
+mod multipart_upload_test;
+mod presigned_upload_test;
+mod statement_timeout_test;
+mod usage_cache_test;
 pub(crate) mod util;
+mod workspace_export_test;
+mod workspace_invitation_test;
+mod workspace_member_export_test;
+mod workspace_merge_test;
+mod workspace_stats_test;
 mod workspace_test;
+mod workspace_usage_counter_test;
+mod workspace_usage_test;
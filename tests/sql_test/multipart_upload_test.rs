@@ -0,0 +1,234 @@
+use crate::file_test::TestBucket;
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use app_error::AppError;
+use appflowy_cloud::api::file_storage::BlobPathV1;
+use database::file::s3_client_impl::S3BucketStorage;
+use database::file::{BlobKey, BlobVersionPolicy};
+use database::resource_usage::{
+  delete_expired_multipart_uploads, get_workspace_usage_size, BlobAttributes,
+};
+use database_entity::file_dto::{
+  CompleteUploadRequest, CompletedPartRequest, CreateUploadRequest, UploadPartData,
+};
+use sqlx::types::chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// The smallest part S3 accepts, but for the last one
+const PART_SIZE: usize = 5 * 1024 * 1024;
+
+async fn create_workspace(pool: &PgPool) -> Uuid {
+  setup_db(pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  Uuid::parse_str(&user.workspace_id).unwrap()
+}
+
+fn blob_key(workspace_id: Uuid, file_id: &str) -> BlobPathV1 {
+  BlobPathV1 {
+    workspace_id,
+    parent_dir: "doc".to_string(),
+    file_id: file_id.to_string(),
+  }
+}
+
+async fn create_upload(storage: &S3BucketStorage, key: &BlobPathV1) -> String {
+  storage
+    .create_upload(
+      key.clone(),
+      CreateUploadRequest {
+        file_id: key.file_id.clone(),
+        parent_dir: key.parent_dir.clone(),
+        content_type: "video/mp4".to_string(),
+        file_size: None,
+      },
+    )
+    .await
+    .unwrap()
+    .upload_id
+}
+
+async fn upload_part(
+  storage: &S3BucketStorage,
+  key: &BlobPathV1,
+  upload_id: &str,
+  part_number: i32,
+  size: usize,
+) {
+  storage
+    .upload_part(
+      key.clone(),
+      UploadPartData {
+        file_id: key.file_id.clone(),
+        upload_id: upload_id.to_string(),
+        part_number,
+        body: vec![part_number as u8; size],
+      },
+    )
+    .await
+    .unwrap();
+}
+
+async fn complete_upload(
+  storage: &S3BucketStorage,
+  key: &BlobPathV1,
+  upload_id: &str,
+) -> Result<(), AppError> {
+  let parts = storage
+    .list_upload_parts(key.clone(), upload_id)
+    .await?
+    .parts
+    .into_iter()
+    .map(|part| CompletedPartRequest {
+      e_tag: part.e_tag,
+      part_number: part.part_number,
+    })
+    .collect();
+  storage
+    .complete_upload(
+      key.clone(),
+      CompleteUploadRequest {
+        file_id: key.file_id.clone(),
+        parent_dir: key.parent_dir.clone(),
+        upload_id: upload_id.to_string(),
+        parts,
+      },
+      BlobAttributes::default(),
+    )
+    .await
+}
+
+#[sqlx::test(migrations = false)]
+async fn multipart_upload_resumes_from_listed_parts_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  let storage = S3BucketStorage::from_bucket_impl(TestBucket::new().await.0, pool.clone());
+  let video = blob_key(workspace_id, "video.mp4");
+  let upload_id = create_upload(&storage, &video).await;
+
+  upload_part(&storage, &video, &upload_id, 1, PART_SIZE).await;
+  // the client is interrupted, then lists the parts the server got before uploading the rest
+  let listed = storage
+    .list_upload_parts(video.clone(), &upload_id)
+    .await
+    .unwrap();
+  assert_eq!(listed.parts.len(), 1);
+  assert_eq!(listed.parts[0].part_number, 1);
+  assert_eq!(listed.parts[0].size, PART_SIZE as u64);
+
+  upload_part(&storage, &video, &upload_id, 2, 100).await;
+  complete_upload(&storage, &video, &upload_id).await.unwrap();
+  let metadata = storage
+    .get_blob_metadata(&workspace_id, &video.blob_metadata_key())
+    .await
+    .unwrap();
+  assert_eq!(metadata.file_size, (PART_SIZE + 100) as i64);
+
+  // a completed upload can't be resumed
+  let result = storage.list_upload_parts(video.clone(), &upload_id).await;
+  assert!(matches!(result, Err(AppError::RecordNotFound(_))));
+
+  // nor used for another blob
+  let other = blob_key(workspace_id, "other.mp4");
+  let other_upload_id = create_upload(&storage, &other).await;
+  let result = storage
+    .list_upload_parts(video.clone(), &other_upload_id)
+    .await;
+  assert!(matches!(result, Err(AppError::RecordNotFound(_))));
+}
+
+#[sqlx::test(migrations = false)]
+async fn multipart_upload_over_quota_is_discarded_on_completion_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  let storage = S3BucketStorage::from_bucket_impl(TestBucket::new().await.0, pool.clone())
+    .with_storage_limit(Some(PART_SIZE as u64));
+  let video = blob_key(workspace_id, "video.mp4");
+  let upload_id = create_upload(&storage, &video).await;
+  upload_part(&storage, &video, &upload_id, 1, PART_SIZE).await;
+  upload_part(&storage, &video, &upload_id, 2, 100).await;
+
+  let result = complete_upload(&storage, &video, &upload_id).await;
+  assert!(matches!(result, Err(AppError::StorageSpaceNotEnough)));
+  assert!(storage
+    .get_blob_metadata(&workspace_id, &video.blob_metadata_key())
+    .await
+    .is_err());
+  assert_eq!(
+    get_workspace_usage_size(&pool, &workspace_id)
+      .await
+      .unwrap(),
+    0
+  );
+}
+
+#[sqlx::test(migrations = false)]
+async fn multipart_upload_of_blob_created_meanwhile_is_refused_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  let storage = S3BucketStorage::from_bucket_impl(TestBucket::new().await.0, pool.clone())
+    .with_version_policy(Some(BlobVersionPolicy {
+      max_versions: 10,
+      max_age_days: None,
+    }));
+  let video = blob_key(workspace_id, "video.mp4");
+  let upload_id = create_upload(&storage, &video).await;
+  upload_part(&storage, &video, &upload_id, 1, 100).await;
+  storage
+    .put_blob_content(
+      video.clone(),
+      b"created meanwhile".to_vec(),
+      "video/mp4".to_string(),
+      BlobAttributes::default(),
+    )
+    .await
+    .unwrap();
+
+  // the upload isn't reported as completed while its content would be lost
+  let result = complete_upload(&storage, &video, &upload_id).await;
+  assert!(matches!(result, Err(AppError::RecordAlreadyExists(_))));
+  let metadata = storage
+    .get_blob_metadata(&workspace_id, &video.blob_metadata_key())
+    .await
+    .unwrap();
+  assert_eq!(metadata.file_size, b"created meanwhile".len() as i64);
+}
+
+#[sqlx::test(migrations = false)]
+async fn inactive_multipart_uploads_expire_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  let storage = S3BucketStorage::from_bucket_impl(TestBucket::new().await.0, pool.clone());
+  let abandoned = blob_key(workspace_id, "abandoned.mp4");
+  let abandoned_upload_id = create_upload(&storage, &abandoned).await;
+  let active = blob_key(workspace_id, "active.mp4");
+  let active_upload_id = create_upload(&storage, &active).await;
+  sqlx::query("UPDATE af_blob_multipart_upload SET updated_at = $1")
+    .bind(Utc::now() - Duration::days(2))
+    .execute(&pool)
+    .await
+    .unwrap();
+  // a part pushes back the expiration of its upload
+  upload_part(&storage, &active, &active_upload_id, 1, PART_SIZE).await;
+
+  let expired = delete_expired_multipart_uploads(&pool, Utc::now() - Duration::days(1), 100)
+    .await
+    .unwrap();
+  assert_eq!(expired.len(), 1);
+  assert_eq!(expired[0].upload_id, abandoned_upload_id);
+  assert_eq!(expired[0].object_key, abandoned.object_key());
+  let result = storage
+    .list_upload_parts(abandoned.clone(), &abandoned_upload_id)
+    .await;
+  assert!(matches!(result, Err(AppError::RecordNotFound(_))));
+  assert_eq!(
+    storage
+      .list_upload_parts(active.clone(), &active_upload_id)
+      .await
+      .unwrap()
+      .parts
+      .len(),
+    1
+  );
+}
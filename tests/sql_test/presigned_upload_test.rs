@@ -0,0 +1,185 @@
+use crate::file_test::TestBucket;
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use app_error::AppError;
+use appflowy_cloud::api::file_storage::BlobPathV1;
+use aws_sdk_s3::primitives::ByteStream;
+use database::file::s3_client_impl::S3BucketStorage;
+use database::file::{BlobKey, BucketClient};
+use database::resource_usage::{
+  delete_expired_pending_blob_metadata, get_workspace_usage_size, select_pending_blob_metadata,
+  BlobAttributes,
+};
+use database_entity::file_dto::PresignedUploadRequest;
+use sqlx::types::chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const LIMIT_BYTES: u64 = 1000;
+
+async fn create_workspace(pool: &PgPool) -> Uuid {
+  setup_db(pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  Uuid::parse_str(&user.workspace_id).unwrap()
+}
+
+fn blob_key(workspace_id: Uuid, file_id: &str) -> BlobPathV1 {
+  BlobPathV1 {
+    workspace_id,
+    parent_dir: "doc".to_string(),
+    file_id: file_id.to_string(),
+  }
+}
+
+fn upload_request(key: &BlobPathV1, content_length: u64) -> PresignedUploadRequest {
+  PresignedUploadRequest {
+    file_id: key.file_id.clone(),
+    parent_dir: key.parent_dir.clone(),
+    content_type: "video/mp4".to_string(),
+    content_length,
+  }
+}
+
+#[sqlx::test(migrations = false)]
+async fn presigned_upload_reserves_quota_until_completed_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  let storage = S3BucketStorage::from_bucket_impl(TestBucket::new().await.0, pool.clone())
+    .with_storage_limit(Some(LIMIT_BYTES));
+
+  let video = blob_key(workspace_id, "video.mp4");
+  let resp = storage
+    .create_presigned_upload(video.clone(), upload_request(&video, 600))
+    .await
+    .unwrap();
+  // the declared size is reserved while the video is being uploaded
+  let other = blob_key(workspace_id, "other.mp4");
+  let result = storage
+    .create_presigned_upload(other.clone(), upload_request(&other, 600))
+    .await;
+  assert!(matches!(result, Err(AppError::StorageSpaceNotEnough)));
+
+  // nothing was uploaded yet
+  let result = storage
+    .complete_presigned_upload(video.clone(), BlobAttributes::default())
+    .await;
+  assert!(matches!(result, Err(AppError::RecordNotFound(_))));
+
+  let uploaded = reqwest::Client::new()
+    .put(&resp.presigned_url)
+    .header("Content-Type", "video/mp4")
+    .body(vec![7u8; 600])
+    .send()
+    .await
+    .unwrap();
+  assert!(uploaded.status().is_success(), "{:?}", uploaded);
+  storage
+    .complete_presigned_upload(video.clone(), BlobAttributes::default())
+    .await
+    .unwrap();
+
+  let metadata = storage
+    .get_blob_metadata(&workspace_id, &video.blob_metadata_key())
+    .await
+    .unwrap();
+  assert_eq!(metadata.file_size, 600);
+  assert_eq!(metadata.file_type, "video/mp4");
+  assert!(
+    select_pending_blob_metadata(&pool, &workspace_id, &video.blob_metadata_key())
+      .await
+      .unwrap()
+      .is_none()
+  );
+  let usage = get_workspace_usage_size(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(usage, 600);
+
+  // a completed upload can't be completed nor issued again
+  let result = storage
+    .complete_presigned_upload(video.clone(), BlobAttributes::default())
+    .await;
+  assert!(matches!(result, Err(AppError::RecordNotFound(_))));
+  let result = storage
+    .create_presigned_upload(video.clone(), upload_request(&video, 10))
+    .await;
+  assert!(matches!(result, Err(AppError::RecordAlreadyExists(_))));
+}
+
+#[sqlx::test(migrations = false)]
+async fn presigned_upload_with_another_size_is_rejected_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  let bucket = TestBucket::new().await;
+  let storage = S3BucketStorage::from_bucket_impl(bucket.0.clone(), pool.clone())
+    .with_storage_limit(Some(LIMIT_BYTES));
+
+  let video = blob_key(workspace_id, "video.mp4");
+  storage
+    .create_presigned_upload(video.clone(), upload_request(&video, 100))
+    .await
+    .unwrap();
+  bucket
+    .put_blob(
+      &video.object_key(),
+      ByteStream::from(vec![7u8; 900]),
+      Some("video/mp4"),
+    )
+    .await
+    .unwrap();
+  let result = storage
+    .complete_presigned_upload(video.clone(), BlobAttributes::default())
+    .await;
+  assert!(matches!(result, Err(AppError::InvalidRequest(_))));
+  let usage = get_workspace_usage_size(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(usage, 0);
+}
+
+#[sqlx::test(migrations = false)]
+async fn expired_pending_uploads_are_deleted_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  let storage = S3BucketStorage::from_bucket_impl(TestBucket::new().await.0, pool.clone())
+    .with_storage_limit(Some(LIMIT_BYTES));
+
+  let abandoned = blob_key(workspace_id, "abandoned.mp4");
+  let recent = blob_key(workspace_id, "recent.mp4");
+  for key in [&abandoned, &recent] {
+    storage
+      .create_presigned_upload(key.clone(), upload_request(key, 400))
+      .await
+      .unwrap();
+  }
+  sqlx::query(
+    "UPDATE af_blob_pending_upload SET created_at = NOW() - INTERVAL '25 hours' \
+     WHERE workspace_id = $1 AND file_id = $2",
+  )
+  .bind(workspace_id)
+  .bind(abandoned.blob_metadata_key())
+  .execute(&pool)
+  .await
+  .unwrap();
+
+  let object_keys =
+    delete_expired_pending_blob_metadata(&pool, Utc::now() - Duration::hours(24), 100)
+      .await
+      .unwrap();
+  assert_eq!(object_keys, vec![abandoned.object_key()]);
+  assert!(
+    select_pending_blob_metadata(&pool, &workspace_id, &recent.blob_metadata_key())
+      .await
+      .unwrap()
+      .is_some()
+  );
+
+  // the space reserved for the abandoned upload is released
+  let other = blob_key(workspace_id, "other.mp4");
+  storage
+    .create_presigned_upload(other.clone(), upload_request(&other, 600))
+    .await
+    .unwrap();
+}
@@ -0,0 +1,97 @@
+use std::ops::DerefMut;
+use std::time::Duration;
+
+use app_error::{AppError, ErrorCode};
+use database::statement_timeout::{
+  begin_with_statement_timeout, is_statement_timeout, AdaptivePageSize,
+};
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn statement_timeout_error_mapping_sql_test(pool: PgPool) {
+  let mut txn = begin_with_statement_timeout(&pool, Duration::from_millis(100))
+    .await
+    .unwrap();
+  let err = sqlx::query("SELECT pg_sleep(1)")
+    .execute(txn.deref_mut())
+    .await
+    .unwrap_err();
+  assert!(is_statement_timeout(&err));
+  drop(txn);
+
+  let err = AppError::from(err);
+  assert_eq!(err.code(), ErrorCode::StatementTimeout);
+  assert_eq!(err.code().http_status(), 503);
+
+  // the timeout only applies to the transaction it was set in
+  sqlx::query("SELECT pg_sleep(0.3)")
+    .execute(&pool)
+    .await
+    .unwrap();
+}
+
+const TOTAL_ROWS: i64 = 60;
+
+/// A page whose cost grows with its size: 5ms per row.
+async fn select_slow_page(
+  pool: &PgPool,
+  timeout: Duration,
+  offset: i64,
+  limit: i64,
+) -> Result<Vec<i64>, sqlx::Error> {
+  let mut txn = begin_with_statement_timeout(pool, timeout).await?;
+  let rows = sqlx::query_scalar(
+    r#"
+      WITH slow AS (SELECT pg_sleep($2::FLOAT8 / 200))
+      SELECT n::BIGINT FROM slow, generate_series($1 + 1, LEAST($1 + $2, $3)) AS n
+    "#,
+  )
+  .bind(offset)
+  .bind(limit)
+  .bind(TOTAL_ROWS)
+  .fetch_all(txn.deref_mut())
+  .await?;
+  txn.commit().await?;
+  Ok(rows)
+}
+
+#[sqlx::test(migrations = false)]
+async fn statement_timeout_page_size_fallback_sql_test(pool: PgPool) {
+  let timeout = Duration::from_millis(200);
+  let mut page_size = AdaptivePageSize::new(100, 10);
+  let mut rows = vec![];
+  let mut timeouts = 0;
+  loop {
+    let page = match select_slow_page(&pool, timeout, rows.len() as i64, page_size.get()).await {
+      Ok(page) => page,
+      Err(err) if is_statement_timeout(&err) && page_size.shrink() => {
+        timeouts += 1;
+        continue;
+      },
+      Err(err) => panic!("unexpected error: {}", err),
+    };
+    let is_last_page = (page.len() as i64) < page_size.get();
+    rows.extend(page);
+    if is_last_page {
+      break;
+    }
+  }
+
+  // pages of 100 and 50 rows time out, pages of 25 rows don't
+  assert_eq!(timeouts, 2);
+  assert_eq!(page_size.get(), 25);
+  assert_eq!(rows, (1..=TOTAL_ROWS).collect::<Vec<_>>());
+}
+
+#[sqlx::test(migrations = false)]
+async fn statement_timeout_at_minimum_page_size_sql_test(pool: PgPool) {
+  let mut page_size = AdaptivePageSize::new(20, 10);
+  let err = select_slow_page(&pool, Duration::from_millis(10), 0, page_size.get())
+    .await
+    .unwrap_err();
+  assert!(is_statement_timeout(&err));
+  assert!(page_size.shrink());
+  assert_eq!(page_size.get(), 10);
+  // the page can't get any smaller, the read fails
+  assert!(!page_size.shrink());
+}
@@ -0,0 +1,106 @@
+use crate::collab::util::redis_connection_manager;
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use database::resource_usage::{
+  delete_blob_metadata, get_workspace_usage_size, get_workspace_usage_size_cached,
+  insert_blob_metadata, invalidate_workspace_usage_size_cache, update_workspace_usage_size_cache,
+  BlobAttributes,
+};
+use redis::AsyncCommands;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[sqlx::test(migrations = false)]
+async fn cached_usage_follows_blob_writes_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let redis = redis_connection_manager().await;
+
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+  let cached_usage = || get_workspace_usage_size_cached(&pool, &redis, &workspace_id);
+
+  let delta = insert_blob_metadata(
+    &pool,
+    "doc_a",
+    &workspace_id,
+    "image/png",
+    100,
+    None,
+    BlobAttributes::default(),
+  )
+  .await
+  .unwrap();
+  assert_eq!(delta, 100);
+  // the missing counter is populated from the blobs
+  assert_eq!(cached_usage().await.unwrap(), 100);
+
+  // replacing a blob only counts the difference
+  let delta = insert_blob_metadata(
+    &pool,
+    "doc_a",
+    &workspace_id,
+    "image/png",
+    150,
+    None,
+    BlobAttributes::default(),
+  )
+  .await
+  .unwrap();
+  assert_eq!(delta, 50);
+  update_workspace_usage_size_cache(&redis, &workspace_id, delta).await;
+  let delta = insert_blob_metadata(
+    &pool,
+    "doc_b",
+    &workspace_id,
+    "image/png",
+    30,
+    None,
+    BlobAttributes::default(),
+  )
+  .await
+  .unwrap();
+  update_workspace_usage_size_cache(&redis, &workspace_id, delta).await;
+  assert_eq!(cached_usage().await.unwrap(), 180);
+
+  let mut txn = pool.begin().await.unwrap();
+  let freed = delete_blob_metadata(&mut txn, &workspace_id, "doc_a")
+    .await
+    .unwrap();
+  let missing = delete_blob_metadata(&mut txn, &workspace_id, "doc_missing")
+    .await
+    .unwrap();
+  txn.commit().await.unwrap();
+  assert_eq!((freed, missing), (150, 0));
+  update_workspace_usage_size_cache(&redis, &workspace_id, -freed).await;
+  assert_eq!(cached_usage().await.unwrap(), 30);
+  assert_eq!(
+    cached_usage().await.unwrap(),
+    get_workspace_usage_size(&pool, &workspace_id)
+      .await
+      .unwrap()
+  );
+
+  // an invalidated counter isn't recreated by an update, which would only hold the change
+  invalidate_workspace_usage_size_cache(&redis, &workspace_id).await;
+  insert_blob_metadata(
+    &pool,
+    "doc_c",
+    &workspace_id,
+    "image/png",
+    20,
+    None,
+    BlobAttributes::default(),
+  )
+  .await
+  .unwrap();
+  update_workspace_usage_size_cache(&redis, &workspace_id, 20).await;
+  let key = format!("af:workspace_usage_size:{}", workspace_id);
+  let counter: Option<i64> = redis.clone().get(&key).await.unwrap();
+  assert_eq!(counter, None);
+  assert_eq!(cached_usage().await.unwrap(), 50);
+}
@@ -0,0 +1,148 @@
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use app_error::ErrorCode;
+use chrono::{Duration, Utc};
+use database::workspace_export::{
+  claim_workspace_export_task, complete_workspace_export_task, expire_workspace_export_tasks,
+  fail_stale_workspace_export_tasks, fail_workspace_export_task, insert_workspace_export_task,
+  select_workspace_export_task, ExportTaskState,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const LEASE_SECS: i64 = 600;
+
+#[sqlx::test(migrations = false)]
+async fn only_one_active_export_per_workspace_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  let first_task = Uuid::new_v4();
+  insert_workspace_export_task(&pool, &first_task, &workspace_id, user.uid)
+    .await
+    .unwrap();
+
+  // a second export can not be created while the first one is pending
+  let err = insert_workspace_export_task(&pool, &Uuid::new_v4(), &workspace_id, user.uid)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code(), ErrorCode::ExportTaskAlreadyRunning);
+
+  // once the first export is finished, a new one can be requested
+  fail_workspace_export_task(&pool, &first_task, "test")
+    .await
+    .unwrap();
+  let second_task = Uuid::new_v4();
+  insert_workspace_export_task(&pool, &second_task, &workspace_id, user.uid)
+    .await
+    .unwrap();
+
+  // completed exports whose link is expired are marked as expired
+  complete_workspace_export_task(
+    &pool,
+    &second_task,
+    "temp/export/archive.zip",
+    "https://example.com/archive.zip",
+    1024,
+    Utc::now() - Duration::minutes(1),
+  )
+  .await
+  .unwrap();
+  let expired = expire_workspace_export_tasks(&pool).await.unwrap();
+  assert_eq!(expired.len(), 1);
+  assert_eq!(
+    expired[0].s3_key.as_deref(),
+    Some("temp/export/archive.zip")
+  );
+
+  let row = select_workspace_export_task(&pool, &workspace_id, &second_task)
+    .await
+    .unwrap();
+  assert_eq!(ExportTaskState::from(row.status), ExportTaskState::Expired);
+  assert!(row.download_url.is_none());
+}
+
+#[sqlx::test(migrations = false)]
+async fn export_task_is_claimed_once_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  let task_id = Uuid::new_v4();
+  insert_workspace_export_task(&pool, &task_id, &workspace_id, user.uid)
+    .await
+    .unwrap();
+
+  let row = claim_workspace_export_task(&pool, &task_id, LEASE_SECS)
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(ExportTaskState::from(row.status), ExportTaskState::Running);
+
+  // the same task delivered twice is only processed by the first worker claiming it
+  let row = claim_workspace_export_task(&pool, &task_id, LEASE_SECS)
+    .await
+    .unwrap();
+  assert!(row.is_none());
+
+  // the task of a worker which stopped is claimed again once its lease is over
+  sqlx::query(
+    "UPDATE af_workspace_export SET updated_at = NOW() - INTERVAL '1 hour' WHERE task_id = $1",
+  )
+  .bind(task_id)
+  .execute(&pool)
+  .await
+  .unwrap();
+  let row = claim_workspace_export_task(&pool, &task_id, LEASE_SECS)
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(ExportTaskState::from(row.status), ExportTaskState::Running);
+  let row = claim_workspace_export_task(&pool, &task_id, LEASE_SECS)
+    .await
+    .unwrap();
+  assert!(row.is_none());
+
+  // a stale task which isn't read again is failed by the cleanup, and can't be claimed anymore
+  let stale = fail_stale_workspace_export_tasks(&pool, LEASE_SECS)
+    .await
+    .unwrap();
+  assert!(stale.is_empty());
+  sqlx::query(
+    "UPDATE af_workspace_export SET updated_at = NOW() - INTERVAL '1 hour' WHERE task_id = $1",
+  )
+  .bind(task_id)
+  .execute(&pool)
+  .await
+  .unwrap();
+  let stale = fail_stale_workspace_export_tasks(&pool, LEASE_SECS)
+    .await
+    .unwrap();
+  assert_eq!(stale.len(), 1);
+  assert_eq!(
+    ExportTaskState::from(stale[0].status),
+    ExportTaskState::Failed
+  );
+  let row = claim_workspace_export_task(&pool, &task_id, LEASE_SECS)
+    .await
+    .unwrap();
+  assert!(row.is_none());
+
+  // the workspace can then be exported again
+  insert_workspace_export_task(&pool, &Uuid::new_v4(), &workspace_id, user.uid)
+    .await
+    .unwrap();
+}
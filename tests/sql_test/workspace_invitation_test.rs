@@ -0,0 +1,112 @@
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use database::workspace::{
+  delete_expired_workspace_invitations, insert_workspace_invitation,
+  select_workspace_invitation_email_seq, select_workspace_pending_invitation_list,
+  update_workspace_invitation_email_seq, update_workspace_invitation_expiry,
+};
+use database_entity::dto::AFRole;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+#[sqlx::test(migrations = false)]
+async fn workspace_invitation_expiry_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  let fresh_id = Uuid::new_v4();
+  let expired_id = Uuid::new_v4();
+  let mut txn = pool.begin().await.unwrap();
+  for (invite_id, invitee, expires_in_secs) in [
+    (fresh_id, "fresh@appflowy.io", 14 * DAY_SECS),
+    (expired_id, "expired@appflowy.io", -2 * DAY_SECS),
+  ] {
+    insert_workspace_invitation(
+      &mut txn,
+      &invite_id,
+      &workspace_id,
+      &user_uuid,
+      invitee,
+      &AFRole::Member,
+      expires_in_secs,
+    )
+    .await
+    .unwrap();
+  }
+  txn.commit().await.unwrap();
+
+  // the soonest to expire is listed first, with no time left
+  let invitations = select_workspace_pending_invitation_list(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(
+    invitations.iter().map(|i| i.invite_id).collect::<Vec<_>>(),
+    vec![expired_id, fresh_id]
+  );
+  assert_eq!(invitations[0].expires_in_secs, 0);
+  assert!(invitations[1].expires_in_secs > 13 * DAY_SECS);
+
+  // a second email within the cooldown isn't queued
+  let seq = update_workspace_invitation_email_seq(&pool, &fresh_id, 60)
+    .await
+    .unwrap();
+  assert_eq!(seq, Some(1));
+  let seq = update_workspace_invitation_email_seq(&pool, &fresh_id, 60)
+    .await
+    .unwrap();
+  assert_eq!(seq, None);
+  let seq = update_workspace_invitation_email_seq(&pool, &fresh_id, 0)
+    .await
+    .unwrap();
+  assert_eq!(seq, Some(2));
+  assert_eq!(
+    select_workspace_invitation_email_seq(&pool, &fresh_id)
+      .await
+      .unwrap(),
+    Some(2)
+  );
+  // the emails of an expired invitation are dropped
+  assert_eq!(
+    select_workspace_invitation_email_seq(&pool, &expired_id)
+      .await
+      .unwrap(),
+    None
+  );
+
+  // only the invitations expired for longer than the grace period are pruned
+  let deleted = delete_expired_workspace_invitations(&pool, 3 * DAY_SECS, 100)
+    .await
+    .unwrap();
+  assert_eq!(deleted, 0);
+  let deleted = delete_expired_workspace_invitations(&pool, DAY_SECS, 100)
+    .await
+    .unwrap();
+  assert_eq!(deleted, 1);
+  let invitations = select_workspace_pending_invitation_list(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(invitations.len(), 1);
+
+  // extending an invitation resets its expiry
+  update_workspace_invitation_expiry(&pool, &workspace_id, &fresh_id, DAY_SECS)
+    .await
+    .unwrap();
+  let invitations = select_workspace_pending_invitation_list(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert!(invitations[0].expires_in_secs <= DAY_SECS);
+  assert!(
+    update_workspace_invitation_expiry(&pool, &workspace_id, &expired_id, DAY_SECS)
+      .await
+      .is_err()
+  );
+}
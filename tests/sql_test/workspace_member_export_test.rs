@@ -0,0 +1,113 @@
+use crate::sql_test::util::{setup_db, test_create_user};
+use database::workspace::{select_workspace_member_export_stream, upsert_workspace_member};
+use database::workspace_audit_log::insert_workspace_audit_log;
+use database_entity::dto::{AFRole, WorkspaceMemberFilter, WorkspaceMemberSortBy};
+use futures_util::TryStreamExt;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn export_emails(
+  pool: &PgPool,
+  workspace_id: Uuid,
+  filter: WorkspaceMemberFilter,
+) -> Vec<String> {
+  select_workspace_member_export_stream(pool, workspace_id, &filter, false)
+    .map_ok(|row| row.email)
+    .try_collect()
+    .await
+    .unwrap()
+}
+
+#[sqlx::test(migrations = false)]
+async fn workspace_member_export_filter_and_order_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+
+  let mut users = vec![];
+  for name in ["owner", "bob", "alice_100%"] {
+    let user_uuid = Uuid::new_v4();
+    let email = format!("{}@appflowy.io", user_uuid);
+    let user = test_create_user(&pool, user_uuid, &email, name)
+      .await
+      .unwrap();
+    users.push((user, email));
+  }
+  let workspace_id = Uuid::parse_str(&users[0].0.workspace_id).unwrap();
+  upsert_workspace_member(&pool, &workspace_id, &users[1].1, AFRole::Member)
+    .await
+    .unwrap();
+  upsert_workspace_member(&pool, &workspace_id, &users[2].1, AFRole::Guest)
+    .await
+    .unwrap();
+
+  let by_join = export_emails(&pool, workspace_id, WorkspaceMemberFilter::default()).await;
+  assert_eq!(
+    by_join,
+    vec![users[0].1.clone(), users[1].1.clone(), users[2].1.clone()]
+  );
+
+  let by_name_desc = export_emails(
+    &pool,
+    workspace_id,
+    WorkspaceMemberFilter {
+      sort_by: WorkspaceMemberSortBy::Name,
+      descending: true,
+      ..Default::default()
+    },
+  )
+  .await;
+  assert_eq!(
+    by_name_desc,
+    vec![users[0].1.clone(), users[1].1.clone(), users[2].1.clone()]
+  );
+
+  let guests = export_emails(
+    &pool,
+    workspace_id,
+    WorkspaceMemberFilter {
+      role: Some(AFRole::Guest),
+      ..Default::default()
+    },
+  )
+  .await;
+  assert_eq!(guests, vec![users[2].1.clone()]);
+
+  // The wildcards of the search are matched literally.
+  let searched = export_emails(
+    &pool,
+    workspace_id,
+    WorkspaceMemberFilter {
+      search: Some(" 100% ".to_string()),
+      ..Default::default()
+    },
+  )
+  .await;
+  assert_eq!(searched, vec![users[2].1.clone()]);
+  let not_found = export_emails(
+    &pool,
+    workspace_id,
+    WorkspaceMemberFilter {
+      search: Some("b_b".to_string()),
+      ..Default::default()
+    },
+  )
+  .await;
+  assert!(not_found.is_empty());
+
+  insert_workspace_audit_log(
+    &pool,
+    &workspace_id,
+    users[0].0.uid,
+    "member_export",
+    &serde_json::json!({ "role": "guest" }),
+  )
+  .await
+  .unwrap();
+  let logged: i64 = sqlx::query_scalar(
+    "SELECT COUNT(*) FROM af_workspace_audit_log WHERE workspace_id = $1 AND action = 'member_export'",
+  )
+  .bind(workspace_id)
+  .fetch_one(&pool)
+  .await
+  .unwrap();
+  assert_eq!(logged, 1);
+}
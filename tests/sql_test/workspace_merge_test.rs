@@ -0,0 +1,156 @@
+use crate::sql_test::util::{setup_db, test_create_user, TestUser};
+
+use app_error::ErrorCode;
+use collab_entity::CollabType;
+use database::collab::insert_into_af_collab;
+use database::workspace::select_user_role;
+use database::workspace_merge::{
+  archive_merged_workspace, fail_workspace_merge_task, insert_workspace_merge_task,
+  is_workspace_merge_active, move_merged_collabs, select_workspace_archive,
+  upsert_merged_workspace_member,
+};
+use database_entity::dto::AFRole;
+use database_entity::dto::CollabParams;
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn create_user(pool: &PgPool) -> (TestUser, Uuid) {
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+  (user, workspace_id)
+}
+
+#[sqlx::test(migrations = false)]
+async fn workspace_takes_part_in_one_merge_at_a_time_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let (user, target) = create_user(&pool).await;
+  let (_, source) = create_user(&pool).await;
+  let (_, other) = create_user(&pool).await;
+
+  let task_id = Uuid::new_v4();
+  insert_workspace_merge_task(&pool, &task_id, &target, &source, user.uid, &json!({}))
+    .await
+    .unwrap();
+  assert!(is_workspace_merge_active(&pool, &[source]).await.unwrap());
+  assert!(!is_workspace_merge_active(&pool, &[other]).await.unwrap());
+
+  // the source workspace can't be merged into another workspace meanwhile
+  let err = insert_workspace_merge_task(
+    &pool,
+    &Uuid::new_v4(),
+    &other,
+    &source,
+    user.uid,
+    &json!({}),
+  )
+  .await
+  .unwrap_err();
+  assert_eq!(err.code(), ErrorCode::WorkspaceMergeBlocked);
+
+  // once the merge failed, it can be requested again
+  fail_workspace_merge_task(&pool, &task_id, "test")
+    .await
+    .unwrap();
+  assert!(!is_workspace_merge_active(&pool, &[source, target])
+    .await
+    .unwrap());
+  insert_workspace_merge_task(
+    &pool,
+    &Uuid::new_v4(),
+    &target,
+    &source,
+    user.uid,
+    &json!({}),
+  )
+  .await
+  .unwrap();
+
+  assert!(select_workspace_archive(&pool, &source)
+    .await
+    .unwrap()
+    .is_none());
+  archive_merged_workspace(&pool, &source, &target)
+    .await
+    .unwrap();
+  let (_, merged_into) = select_workspace_archive(&pool, &source)
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(merged_into, Some(target));
+}
+
+#[sqlx::test(migrations = false)]
+async fn merged_member_keeps_higher_role_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let (_, target) = create_user(&pool).await;
+  let (member, _) = create_user(&pool).await;
+
+  let mut txn = pool.begin().await.unwrap();
+  upsert_merged_workspace_member(&mut txn, &target, member.uid, AFRole::Member)
+    .await
+    .unwrap();
+  upsert_merged_workspace_member(&mut txn, &target, member.uid, AFRole::Guest)
+    .await
+    .unwrap();
+  txn.commit().await.unwrap();
+  let role = select_user_role(&pool, &member.uid, &target).await.unwrap();
+  assert_eq!(role, AFRole::Member);
+}
+
+async fn collab_workspace_id(pool: &PgPool, object_id: &str) -> Uuid {
+  sqlx::query_scalar("SELECT workspace_id FROM af_collab WHERE oid = $1")
+    .bind(object_id)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+#[sqlx::test(migrations = false)]
+async fn merged_collabs_keep_their_ids_sql_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let (source_user, source) = create_user(&pool).await;
+  let (target_user, target) = create_user(&pool).await;
+
+  let object_id = Uuid::new_v4().to_string();
+  let params = CollabParams {
+    object_id: object_id.clone(),
+    collab_type: CollabType::Document,
+    encoded_collab_v1: vec![1, 2, 3].into(),
+  };
+  let mut txn = pool.begin().await.unwrap();
+  insert_into_af_collab(&mut txn, &source_user.uid, &source.to_string(), &params)
+    .await
+    .unwrap();
+  txn.commit().await.unwrap();
+
+  // the id can't be used by the target workspace meanwhile, the collab stays in the source
+  let mut txn = pool.begin().await.unwrap();
+  insert_into_af_collab(&mut txn, &target_user.uid, &target.to_string(), &params)
+    .await
+    .unwrap();
+  txn.commit().await.unwrap();
+  assert_eq!(collab_workspace_id(&pool, &object_id).await, source);
+
+  let object_ids = vec![object_id.clone()];
+  let mut txn = pool.begin().await.unwrap();
+  let moved = move_merged_collabs(&mut txn, &source, &target, &object_ids)
+    .await
+    .unwrap();
+  txn.commit().await.unwrap();
+  assert_eq!(moved, 1);
+  assert_eq!(collab_workspace_id(&pool, &object_id).await, target);
+
+  // running the stage again doesn't move anything
+  let mut txn = pool.begin().await.unwrap();
+  let moved = move_merged_collabs(&mut txn, &source, &target, &object_ids)
+    .await
+    .unwrap();
+  txn.commit().await.unwrap();
+  assert_eq!(moved, 0);
+}
@@ -0,0 +1,61 @@
+use crate::sql_test::util::{generate_random_bytes, setup_db, test_create_user};
+
+use collab_entity::CollabType;
+use database::collab::insert_into_af_collab;
+use database::workspace_stats::{
+  fold_workspace_stats_deltas, recompute_workspace_stats, select_workspace_stats,
+};
+use database_entity::dto::CollabParams;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[sqlx::test(migrations = false)]
+async fn folded_stats_match_recomputed_stats_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+  recompute_workspace_stats(&pool, &workspace_id)
+    .await
+    .unwrap();
+  let before = select_workspace_stats(&pool, &workspace_id).await.unwrap();
+
+  for _ in 0..3 {
+    let params = CollabParams {
+      object_id: Uuid::new_v4().to_string(),
+      collab_type: CollabType::Document,
+      encoded_collab_v1: generate_random_bytes(1024).into(),
+    };
+    let mut txn = pool.begin().await.unwrap();
+    insert_into_af_collab(&mut txn, &user.uid, &user.workspace_id, &params)
+      .await
+      .unwrap();
+    txn.commit().await.unwrap();
+  }
+
+  // the pending changes are counted before they are folded
+  let pending = select_workspace_stats(&pool, &workspace_id).await.unwrap();
+  fold_workspace_stats_deltas(&pool, 10_000).await.unwrap();
+  let folded = select_workspace_stats(&pool, &workspace_id).await.unwrap();
+  recompute_workspace_stats(&pool, &workspace_id)
+    .await
+    .unwrap();
+  let recomputed = select_workspace_stats(&pool, &workspace_id).await.unwrap();
+
+  let documents = |rows: &[database::pg_row::AFWorkspaceStatsRow]| {
+    rows
+      .iter()
+      .find(|row| row.partition_key == 0)
+      .map(|row| (row.object_count, row.total_bytes))
+      .unwrap_or((0, 0))
+  };
+  let (count_before, _) = documents(&before);
+  assert_eq!(documents(&pending).0, count_before + 3);
+  assert_eq!(documents(&pending), documents(&folded));
+  assert_eq!(documents(&folded), documents(&recomputed));
+}
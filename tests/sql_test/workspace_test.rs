@@ -1,9 +1,10 @@
 use crate::sql_test::util::{generate_random_bytes, setup_db, test_create_user};
 
+use app_error::AppError;
 use collab_entity::CollabType;
 use database::collab::{
-  insert_into_af_collab, insert_into_af_collab_bulk_for_user, select_blob_from_af_collab,
-  select_collab_meta_from_af_collab,
+  insert_into_af_collab, insert_into_af_collab_bulk_for_user, insert_new_collabs_bulk_for_user,
+  select_blob_from_af_collab, select_collab_meta_from_af_collab,
 };
 use database_entity::dto::CollabParams;
 use sqlx::PgPool;
@@ -189,6 +190,65 @@ async fn test_bulk_insert_duplicate_oid_partition_key(pool: PgPool) {
   assert_eq!(data, encoded_collab_v1); // should equal the data that insert first time
 }
 
+#[sqlx::test(migrations = false)]
+async fn test_insert_new_collabs_bulk_rejects_conflicts(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+  let user = test_create_user(&pool, uuid::Uuid::new_v4(), "test@appflowy.io", "test_user")
+    .await
+    .unwrap();
+  let other = test_create_user(&pool, uuid::Uuid::new_v4(), "other@appflowy.io", "other")
+    .await
+    .unwrap();
+  let params = |object_id: &str| CollabParams {
+    object_id: object_id.to_string(),
+    collab_type: CollabType::Unknown,
+    encoded_collab_v1: generate_random_bytes(1024).into(),
+  };
+
+  // duplicate object ids
+  let object_id = uuid::Uuid::new_v4().to_string();
+  let mut txn = pool.begin().await.unwrap();
+  let err = insert_new_collabs_bulk_for_user(
+    &mut txn,
+    &user.uid,
+    &user.workspace_id,
+    &[params(&object_id), params(&object_id)],
+  )
+  .await
+  .unwrap_err();
+  assert!(matches!(err, AppError::InvalidRequest(_)));
+  drop(txn);
+
+  // a collab of another workspace
+  let mut txn = pool.begin().await.unwrap();
+  insert_new_collabs_bulk_for_user(
+    &mut txn,
+    &other.uid,
+    &other.workspace_id,
+    &[params(&object_id)],
+  )
+  .await
+  .unwrap();
+  txn.commit().await.unwrap();
+  let new_id = uuid::Uuid::new_v4().to_string();
+  let mut txn = pool.begin().await.unwrap();
+  let err = insert_new_collabs_bulk_for_user(
+    &mut txn,
+    &user.uid,
+    &user.workspace_id,
+    &[params(&new_id), params(&object_id)],
+  )
+  .await
+  .unwrap_err();
+  assert!(matches!(err, AppError::RecordAlreadyExists(_)));
+  drop(txn);
+  assert!(
+    select_blob_from_af_collab(&pool, &CollabType::Unknown, &new_id)
+      .await
+      .is_err()
+  );
+}
+
 #[sqlx::test(migrations = false)]
 async fn test_batch_insert_comparison(pool: PgPool) {
   setup_db(&pool).await.unwrap();
@@ -0,0 +1,167 @@
+use crate::sql_test::util::{setup_db, test_create_user};
+
+use database::resource_usage::{
+  delete_blob_metadata_bulk, get_workspace_usage_size, insert_blob_metadata,
+  insert_blob_metadata_bulk, recompute_workspace_usage, BlobAttributes, BulkInsertMeta,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn create_workspace(pool: &PgPool) -> Uuid {
+  setup_db(pool).await.unwrap();
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  Uuid::parse_str(&user.workspace_id).unwrap()
+}
+
+fn meta(file_id: &str, file_size: i64) -> BulkInsertMeta {
+  BulkInsertMeta {
+    object_id: "doc".to_string(),
+    file_id: file_id.to_string(),
+    file_type: "image/png".to_string(),
+    file_size,
+    content_hash: None,
+  }
+}
+
+#[sqlx::test(migrations = false)]
+async fn usage_counter_follows_bulk_insert_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  assert_eq!(
+    get_workspace_usage_size(&pool, &workspace_id)
+      .await
+      .unwrap(),
+    0
+  );
+
+  let inserted = insert_blob_metadata_bulk(
+    &pool,
+    &workspace_id,
+    vec![meta("doc_a", 100), meta("doc_b", 20)],
+    None,
+  )
+  .await
+  .unwrap();
+  assert_eq!(inserted.inserted_bytes, 120);
+  assert_eq!(
+    get_workspace_usage_size(&pool, &workspace_id)
+      .await
+      .unwrap(),
+    120
+  );
+
+  // doc_a already exists and is left as it is: only doc_c counts, with its own size
+  let inserted = insert_blob_metadata_bulk(
+    &pool,
+    &workspace_id,
+    vec![meta("doc_a", 5000), meta("doc_c", 3)],
+    None,
+  )
+  .await
+  .unwrap();
+  assert_eq!(inserted.rows_affected, 1);
+  assert_eq!(inserted.inserted_bytes, 3);
+  assert_eq!(
+    get_workspace_usage_size(&pool, &workspace_id)
+      .await
+      .unwrap(),
+    123
+  );
+
+  // a replaced blob counts as the difference between its sizes
+  let delta = insert_blob_metadata(
+    &pool,
+    "doc_b",
+    &workspace_id,
+    "image/png",
+    50,
+    None,
+    BlobAttributes::default(),
+  )
+  .await
+  .unwrap();
+  assert_eq!(delta, 30);
+  assert_eq!(
+    get_workspace_usage_size(&pool, &workspace_id)
+      .await
+      .unwrap(),
+    153
+  );
+
+  let mut txn = pool.begin().await.unwrap();
+  delete_blob_metadata_bulk(
+    &mut txn,
+    &workspace_id,
+    &[
+      "doc_a".to_string(),
+      "doc_c".to_string(),
+      "doc_missing".to_string(),
+    ],
+  )
+  .await
+  .unwrap();
+  txn.commit().await.unwrap();
+  assert_eq!(
+    get_workspace_usage_size(&pool, &workspace_id)
+      .await
+      .unwrap(),
+    50
+  );
+
+  // the counter didn't drift from the blobs
+  let recomputed = recompute_workspace_usage(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(
+    (recomputed.previous_bytes, recomputed.usage_bytes),
+    (50, 50)
+  );
+}
+
+#[sqlx::test(migrations = false)]
+async fn recompute_fixes_drifted_usage_counter_sql_test(pool: PgPool) {
+  let workspace_id = create_workspace(&pool).await;
+  insert_blob_metadata_bulk(
+    &pool,
+    &workspace_id,
+    vec![meta("doc_a", 100), meta("doc_b", 20)],
+    None,
+  )
+  .await
+  .unwrap();
+
+  sqlx::query("UPDATE af_workspace_usage SET usage_bytes = 7 WHERE workspace_id = $1")
+    .bind(workspace_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+  assert_eq!(
+    get_workspace_usage_size(&pool, &workspace_id)
+      .await
+      .unwrap(),
+    7
+  );
+
+  let recomputed = recompute_workspace_usage(&pool, &workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(
+    (recomputed.previous_bytes, recomputed.usage_bytes),
+    (7, 120)
+  );
+  assert_eq!(
+    get_workspace_usage_size(&pool, &workspace_id)
+      .await
+      .unwrap(),
+    120
+  );
+
+  let err = recompute_workspace_usage(&pool, &Uuid::new_v4())
+    .await
+    .unwrap_err();
+  assert!(err.is_record_not_found());
+}
@@ -0,0 +1,118 @@
+use crate::sql_test::util::{setup_db, test_create_user};
+use chrono::{Days, NaiveDate, NaiveTime, Utc};
+use database::pg_row::AFWorkspaceUsageDailyRow;
+use database::realtime_bandwidth::upsert_workspace_realtime_bandwidth;
+use database::workspace_usage::{select_workspace_usage_daily, upsert_workspace_usage_daily};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn usage_of_day(
+  pool: &PgPool,
+  workspace_id: Uuid,
+  day: NaiveDate,
+) -> AFWorkspaceUsageDailyRow {
+  let mut rows = select_workspace_usage_daily(pool, day, day, Some(workspace_id))
+    .await
+    .unwrap();
+  assert_eq!(rows.len(), 1);
+  rows.remove(0)
+}
+
+#[sqlx::test(migrations = false)]
+async fn workspace_usage_rollup_is_idempotent_and_takes_late_data_test(pool: PgPool) {
+  setup_db(&pool).await.unwrap();
+
+  let user_uuid = Uuid::new_v4();
+  let name = user_uuid.to_string();
+  let email = format!("{}@appflowy.io", name);
+  let user = test_create_user(&pool, user_uuid, &email, &name)
+    .await
+    .unwrap();
+  let workspace_id = Uuid::parse_str(&user.workspace_id).unwrap();
+
+  let day = Utc::now().date_naive();
+  let start = day.and_time(NaiveTime::MIN).and_utc();
+  let end = (day + Days::new(1)).and_time(NaiveTime::MIN).and_utc();
+
+  sqlx::query(
+    "INSERT INTO af_blob_metadata (workspace_id, file_id, file_type, file_size) VALUES ($1, 'a', 'image/png', 1000), ($1, 'b', 'image/png', 24)",
+  )
+  .bind(workspace_id)
+  .execute(&pool)
+  .await
+  .unwrap();
+  sqlx::query(
+    "INSERT INTO af_workspace_ai_usage (created_at, workspace_id, search_requests, search_tokens_consumed, index_tokens_consumed) VALUES ($1, $2, 3, 120, 80)",
+  )
+  .bind(day)
+  .bind(workspace_id)
+  .execute(&pool)
+  .await
+  .unwrap();
+  sqlx::query("INSERT INTO af_user_session (session_id, uid) VALUES ($1, $2), ($3, $2)")
+    .bind(Uuid::new_v4())
+    .bind(user.uid)
+    .bind(Uuid::new_v4())
+    .execute(&pool)
+    .await
+    .unwrap();
+  upsert_workspace_realtime_bandwidth(
+    &pool,
+    day,
+    &[workspace_id],
+    &[100],
+    &[200],
+    &[150],
+    &[50],
+    &[0],
+  )
+  .await
+  .unwrap();
+
+  upsert_workspace_usage_daily(&pool, day, start, end)
+    .await
+    .unwrap();
+  upsert_workspace_usage_daily(&pool, day, start, end)
+    .await
+    .unwrap();
+  let row = usage_of_day(&pool, workspace_id, day).await;
+  assert_eq!(row.storage_bytes, 1024);
+  assert_eq!(row.ai_search_requests, 3);
+  assert_eq!(row.ai_tokens, 200);
+  assert_eq!(row.active_members, 1);
+  assert_eq!(row.bandwidth_in, 100);
+  assert_eq!(row.bandwidth_out, 200);
+
+  // A delayed flush of the bandwidth counters, rolled up again.
+  upsert_workspace_realtime_bandwidth(
+    &pool,
+    day,
+    &[workspace_id],
+    &[300],
+    &[500],
+    &[400],
+    &[100],
+    &[0],
+  )
+  .await
+  .unwrap();
+  upsert_workspace_usage_daily(&pool, day, start, end)
+    .await
+    .unwrap();
+  let row = usage_of_day(&pool, workspace_id, day).await;
+  assert_eq!(row.bandwidth_in, 300);
+  assert_eq!(row.bandwidth_out, 500);
+  assert_eq!(row.storage_bytes, 1024);
+
+  // The workspace didn't exist the day before.
+  let yesterday = day - Days::new(1);
+  upsert_workspace_usage_daily(&pool, yesterday, start - Days::new(1), start)
+    .await
+    .unwrap();
+  assert!(
+    select_workspace_usage_daily(&pool, yesterday, yesterday, Some(workspace_id))
+      .await
+      .unwrap()
+      .is_empty()
+  );
+}
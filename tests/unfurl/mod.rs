@@ -0,0 +1 @@
+mod unfurl_test;
@@ -0,0 +1,28 @@
+use app_error::ErrorCode;
+use client_api_test::generate_unique_registered_user_client;
+
+#[tokio::test]
+async fn unfurl_private_address_returns_minimal_metadata_test() {
+  let (c, _) = generate_unique_registered_user_client().await;
+  for url in [
+    "http://127.0.0.1:8000/api/health",
+    "http://169.254.169.254/latest/meta-data/",
+    "http://localhost/#fragment",
+  ] {
+    let metadata = c.unfurl_url(url).await.unwrap();
+    assert!(metadata.is_minimal(), "{} should not be fetched", url);
+  }
+
+  let metadata = c
+    .unfurl_url("http://10.0.0.1/page?utm_source=test")
+    .await
+    .unwrap();
+  assert_eq!(metadata.url, "http://10.0.0.1/page");
+}
+
+#[tokio::test]
+async fn unfurl_unsupported_url_test() {
+  let (c, _) = generate_unique_registered_user_client().await;
+  let err = c.unfurl_url("file:///etc/passwd").await.unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidRequest);
+}
@@ -0,0 +1,120 @@
+use appflowy_cloud::biz::user::impersonation::SUPPORT_ACCESS_NOTIFICATION;
+use client_api_test::{admin_user_client, generate_unique_registered_user_client, LOCALHOST_URL};
+use reqwest::StatusCode;
+use serde_json::json;
+use shared_entity::dto::impersonation_dto::{
+  CreateImpersonationParams, GrantImpersonationWriteParams,
+};
+
+#[tokio::test]
+async fn impersonation_is_read_only_until_writes_are_granted() {
+  let admin = admin_user_client().await;
+  let (client, _user) = generate_unique_registered_user_client().await;
+  let uid = client.get_profile().await.unwrap().uid;
+  let token = admin
+    .admin_create_impersonation(&CreateImpersonationParams {
+      uid,
+      reason: "The user can't open their pages".to_string(),
+      ttl_secs: None,
+    })
+    .await
+    .unwrap();
+
+  let http_client = reqwest::Client::new();
+  let profile_url = format!("{}/api/user/profile", LOCALHOST_URL.as_ref());
+  let update_url = format!("{}/api/user/update", LOCALHOST_URL.as_ref());
+  let resp = http_client
+    .get(&profile_url)
+    .bearer_auth(&token.access_token)
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(resp.status(), StatusCode::OK);
+  let resp = http_client
+    .post(&update_url)
+    .bearer_auth(&token.access_token)
+    .json(&json!({ "name": "support" }))
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+  admin
+    .admin_grant_impersonation_write(
+      &token.impersonation_id,
+      &GrantImpersonationWriteParams {
+        reason: "Fix the name of the user".to_string(),
+      },
+    )
+    .await
+    .unwrap();
+  let resp = http_client
+    .post(&update_url)
+    .bearer_auth(&token.access_token)
+    .json(&json!({ "name": "support" }))
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(resp.status(), StatusCode::OK);
+
+  // every request is recorded, the rejected one included
+  let requests = admin
+    .admin_list_impersonation_requests(&token.impersonation_id)
+    .await
+    .unwrap()
+    .requests;
+  assert_eq!(requests.len(), 3);
+  assert_eq!(requests.iter().filter(|r| r.status == 403).count(), 1);
+
+  // the user is told that support accessed their account
+  let notifications = client
+    .list_user_notifications(false, None, None)
+    .await
+    .unwrap()
+    .items;
+  assert!(notifications
+    .iter()
+    .any(|n| n.kind == SUPPORT_ACCESS_NOTIFICATION));
+}
+
+#[tokio::test]
+async fn revoked_impersonation_is_rejected() {
+  let admin = admin_user_client().await;
+  let (client, _user) = generate_unique_registered_user_client().await;
+  let uid = client.get_profile().await.unwrap().uid;
+  let token = admin
+    .admin_create_impersonation(&CreateImpersonationParams {
+      uid,
+      reason: "Check the permissions of the user".to_string(),
+      ttl_secs: Some(60),
+    })
+    .await
+    .unwrap();
+  let impersonations = admin.admin_list_impersonations().await.unwrap();
+  assert!(impersonations
+    .impersonations
+    .iter()
+    .any(|i| i.impersonation_id == token.impersonation_id));
+
+  admin
+    .admin_revoke_impersonation(&token.impersonation_id)
+    .await
+    .unwrap();
+  let resp = reqwest::Client::new()
+    .get(format!("{}/api/user/profile", LOCALHOST_URL.as_ref()))
+    .bearer_auth(&token.access_token)
+    .send()
+    .await
+    .unwrap();
+  assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+  // only the admins can impersonate users
+  assert!(client
+    .admin_create_impersonation(&CreateImpersonationParams {
+      uid,
+      reason: "no".to_string(),
+      ttl_secs: None,
+    })
+    .await
+    .is_err());
+}
@@ -1,5 +1,8 @@
 mod delete;
+mod impersonation;
+mod notification_subscription;
 mod refresh;
+mod session;
 mod sign_in;
 mod sign_out;
 mod sign_up;
@@ -0,0 +1,85 @@
+use client_api_test::{generate_unique_registered_user_client, workspace_id_from_client};
+use shared_entity::dto::export_dto::{NotificationSettings, NotificationSubscriptionLevel};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn set_notification_subscription_test() {
+  let (c, _) = generate_unique_registered_user_client().await;
+  let workspace_id = Uuid::parse_str(&workspace_id_from_client(&c).await).unwrap();
+  let muted = Uuid::new_v4();
+  let watched = Uuid::new_v4();
+
+  c.set_notification_subscription(&workspace_id, &muted, NotificationSubscriptionLevel::Mute)
+    .await
+    .unwrap();
+  c.set_notification_subscription(
+    &workspace_id,
+    &watched,
+    NotificationSubscriptionLevel::Watch,
+  )
+  .await
+  .unwrap();
+  let subscriptions = c
+    .list_notification_subscriptions(&workspace_id)
+    .await
+    .unwrap()
+    .subscriptions;
+  assert_eq!(subscriptions.len(), 2);
+  let level_of = |object_id: &Uuid| {
+    subscriptions
+      .iter()
+      .find(|s| &s.object_id == object_id)
+      .map(|s| s.level)
+  };
+  assert_eq!(level_of(&muted), Some(NotificationSubscriptionLevel::Mute));
+  assert_eq!(
+    level_of(&watched),
+    Some(NotificationSubscriptionLevel::Watch)
+  );
+
+  // going back to the default level removes the preference
+  c.set_notification_subscription(
+    &workspace_id,
+    &muted,
+    NotificationSubscriptionLevel::Default,
+  )
+  .await
+  .unwrap();
+  let subscriptions = c
+    .list_notification_subscriptions(&workspace_id)
+    .await
+    .unwrap()
+    .subscriptions;
+  assert_eq!(subscriptions.len(), 1);
+  assert_eq!(subscriptions[0].object_id, watched);
+}
+
+#[tokio::test]
+async fn notification_settings_test() {
+  let (c, _) = generate_unique_registered_user_client().await;
+  let settings = c.get_notification_settings().await.unwrap();
+  assert!(!settings.mute_mentions);
+
+  c.update_notification_settings(&NotificationSettings {
+    mute_mentions: true,
+  })
+  .await
+  .unwrap();
+  let settings = c.get_notification_settings().await.unwrap();
+  assert!(settings.mute_mentions);
+}
+
+#[tokio::test]
+async fn set_notification_subscription_requires_membership_test() {
+  let (owner, _) = generate_unique_registered_user_client().await;
+  let (other, _) = generate_unique_registered_user_client().await;
+  let workspace_id = Uuid::parse_str(&workspace_id_from_client(&owner).await).unwrap();
+  let result = other
+    .set_notification_subscription(
+      &workspace_id,
+      &Uuid::new_v4(),
+      NotificationSubscriptionLevel::Watch,
+    )
+    .await;
+  assert!(result.is_err());
+}
@@ -0,0 +1,79 @@
+use client_api_test::{generate_unique_registered_user_client, localhost_client};
+use std::time::Duration;
+
+#[tokio::test]
+async fn revoke_other_sessions_signs_out_other_devices() {
+  let (laptop, user) = generate_unique_registered_user_client().await;
+  let phone = localhost_client();
+  phone
+    .sign_in_password(&user.email, &user.password)
+    .await
+    .unwrap();
+  laptop.get_profile().await.unwrap();
+  phone.get_profile().await.unwrap();
+
+  let sessions = laptop.list_user_sessions().await.unwrap().sessions;
+  assert_eq!(sessions.len(), 2);
+  assert_eq!(sessions.iter().filter(|s| s.is_current).count(), 1);
+
+  let revoked = laptop.revoke_other_user_sessions().await.unwrap();
+  assert!(!revoked.session_ids.is_empty());
+
+  // the revoked session can neither be used nor refreshed
+  assert!(phone.get_profile().await.is_err());
+  assert!(phone.refresh_token("").await.is_err());
+
+  laptop.get_profile().await.unwrap();
+  let sessions = laptop.list_user_sessions().await.unwrap().sessions;
+  assert_eq!(sessions.len(), 1);
+  assert!(sessions[0].is_current);
+}
+
+#[tokio::test]
+async fn only_admin_can_revoke_sessions_of_another_user() {
+  let (client, _user) = generate_unique_registered_user_client().await;
+  let (other, _) = generate_unique_registered_user_client().await;
+  let other_uuid = other.get_profile().await.unwrap().uuid;
+  assert!(client
+    .admin_revoke_user_sessions(&other_uuid)
+    .await
+    .is_err());
+  other.get_profile().await.unwrap();
+}
+
+#[tokio::test]
+async fn concurrent_refreshes_rotate_the_refresh_token_once() {
+  let (client, _user) = generate_unique_registered_user_client().await;
+  client.get_profile().await.unwrap();
+  // the access tokens are issued with a precision of a second
+  tokio::time::sleep(Duration::from_secs(1)).await;
+  let refreshes = (0..10).map(|_| client.refresh_token("concurrent refresh"));
+  for result in futures::future::join_all(refreshes).await {
+    result.unwrap();
+  }
+
+  let sessions = client.list_user_sessions().await.unwrap().sessions;
+  let current = sessions.iter().find(|s| s.is_current).unwrap();
+  assert_eq!(current.refresh_count, 1);
+  assert!(current.refreshed_at.is_some());
+}
+
+#[tokio::test]
+async fn reused_refresh_token_signs_the_session_out() {
+  let (client, _user) = generate_unique_registered_user_client().await;
+  let stolen_token = client.get_token().unwrap();
+  client.refresh_token("rotate").await.unwrap();
+
+  // the previous refresh token still works right after the rotation, as when several tabs
+  // refresh at the same time
+  let tab = localhost_client();
+  tab.restore_token(&stolen_token).unwrap();
+  tab.refresh_token("concurrent tab").await.unwrap();
+
+  // past the reuse interval, using it again revokes every refresh token of the session
+  tokio::time::sleep(Duration::from_secs(11)).await;
+  let thief = localhost_client();
+  thief.restore_token(&stolen_token).unwrap();
+  assert!(thief.refresh_token("reuse").await.is_err());
+  assert!(client.refresh_token("after reuse").await.is_err());
+}
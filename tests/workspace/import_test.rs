@@ -2,6 +2,7 @@ use anyhow::Error;
 use client_api_test::TestClient;
 use collab_document::importer::define::{BlockType, URL_FIELD};
 use collab_folder::ViewLayout;
+use shared_entity::dto::import_dto::{ImportItemOutcome, IMPORT_REPORT_SCHEMA_VERSION};
 
 use std::path::PathBuf;
 use std::time::Duration;
@@ -78,6 +79,38 @@ async fn import_blog_post_test() {
     "expected URLs to be empty: {:?}",
     expected_urls
   );
+
+  // Step 9: The report of the import is downloadable and written as the first page of the space
+  let task = client
+    .api_client
+    .get_import_list()
+    .await
+    .unwrap()
+    .tasks
+    .pop()
+    .unwrap();
+  assert!(task.has_report);
+  let report = client
+    .api_client
+    .get_import_report(&task.task_id)
+    .await
+    .unwrap();
+  assert_eq!(report.schema_version, IMPORT_REPORT_SCHEMA_VERSION);
+  assert_eq!(report.attachments.uploaded, 3);
+  assert!(report.attachments.failed.is_empty());
+  let blog_post = report
+    .items
+    .iter()
+    .find(|item| item.id == imported_view.id)
+    .unwrap();
+  assert_eq!(blog_post.outcome, ImportItemOutcome::Imported);
+  let report_view = folder
+    .get_views_belong_to(&space_view.id)
+    .into_iter()
+    .next()
+    .unwrap();
+  assert_eq!(report_view.name, "Import report");
+  assert_eq!(report.report_view_id, Some(report_view.id.clone()));
 }
 
 #[tokio::test]
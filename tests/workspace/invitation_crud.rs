@@ -2,7 +2,9 @@ use anyhow::Context;
 use app_error::ErrorCode;
 use client_api_test::{generate_unique_registered_user_client, TestClient};
 use database_entity::dto::{AFRole, AFWorkspaceInvitationStatus};
-use shared_entity::dto::workspace_dto::{QueryWorkspaceParam, WorkspaceMemberInvitation};
+use shared_entity::dto::workspace_dto::{
+  ExtendWorkspaceInvitationParams, QueryWorkspaceParam, WorkspaceMemberInvitation,
+};
 
 #[tokio::test]
 async fn invite_workspace_crud() {
@@ -174,3 +176,83 @@ async fn invite_wait_email_sending_success() {
     .context("failed to send email to invite workspace members")
     .unwrap();
 }
+
+#[tokio::test]
+async fn extend_workspace_invitation_test() {
+  let (alice_client, _alice) = generate_unique_registered_user_client().await;
+  let workspace_id = alice_client
+    .get_workspaces()
+    .await
+    .unwrap()
+    .first()
+    .unwrap()
+    .workspace_id
+    .to_string();
+  let (bob_client, bob) = generate_unique_registered_user_client().await;
+  alice_client
+    .invite_workspace_members(
+      &workspace_id,
+      vec![WorkspaceMemberInvitation {
+        email: bob.email.clone(),
+        role: AFRole::Member,
+        skip_email_send: true,
+        ..Default::default()
+      }],
+    )
+    .await
+    .unwrap();
+
+  // the owner sees the time left before the invitation expires
+  let pending = alice_client
+    .list_workspace_pending_invitations(&workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(pending.len(), 1);
+  assert_eq!(pending[0].invitee_email, bob.email);
+  assert!(pending[0].expires_in_secs > 0);
+  assert!(pending[0].email_queued_at.is_none());
+  let invite_id = pending[0].invite_id.to_string();
+
+  let invitation = bob_client
+    .get_workspace_invitation(&invite_id)
+    .await
+    .unwrap();
+  assert_eq!(invitation.expires_at, Some(pending[0].expires_at));
+
+  // only the owners can list or extend the invitations
+  let err = bob_client
+    .list_workspace_pending_invitations(&workspace_id)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+
+  let params = ExtendWorkspaceInvitationParams { resend_email: true };
+  let extended = alice_client
+    .extend_workspace_invitation(&workspace_id, &invite_id, &params)
+    .await
+    .unwrap();
+  assert!(extended.expires_at >= pending[0].expires_at);
+  let queued_at = extended.email_queued_at.unwrap();
+
+  // re-sending again right away doesn't queue a second email
+  let extended = alice_client
+    .extend_workspace_invitation(&workspace_id, &invite_id, &params)
+    .await
+    .unwrap();
+  assert_eq!(extended.email_queued_at, Some(queued_at));
+
+  bob_client
+    .accept_workspace_invitation(&invite_id)
+    .await
+    .unwrap();
+  let err = alice_client
+    .extend_workspace_invitation(&workspace_id, &invite_id, &params)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+  assert!(alice_client
+    .list_workspace_pending_invitations(&workspace_id)
+    .await
+    .unwrap()
+    .is_empty());
+}
@@ -5,6 +5,7 @@ mod import_test;
 mod invitation_crud;
 mod member_crud;
 mod page_view;
+mod pagination;
 mod publish;
 mod published_data;
 mod quick_note;
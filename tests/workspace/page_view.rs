@@ -10,7 +10,7 @@ use collab_folder::{CollabOrigin, Folder};
 use serde_json::{json, Value};
 use shared_entity::dto::workspace_dto::{
   CreatePageParams, CreateSpaceParams, IconType, MovePageParams, PublishPageParams,
-  SpacePermission, UpdatePageParams, UpdateSpaceParams, ViewIcon, ViewLayout,
+  RepublishPageParams, SpacePermission, UpdatePageParams, UpdateSpaceParams, ViewIcon, ViewLayout,
 };
 use tokio::time::sleep;
 use uuid::Uuid;
@@ -908,3 +908,152 @@ async fn publish_page() {
     .unwrap();
   assert_eq!(published_view.children.len(), 0);
 }
+
+#[tokio::test]
+async fn publish_revision_is_pinned_until_republished() {
+  let registered_user = generate_unique_registered_user().await;
+  let mut web_client = TestClient::user_with_new_device(registered_user.clone()).await;
+  let workspace_id = web_client.workspace_id().await;
+  let workspace_uuid = Uuid::parse_str(&workspace_id).unwrap();
+  let folder_view = web_client
+    .api_client
+    .get_workspace_folder(&workspace_id, Some(2), None)
+    .await
+    .unwrap();
+  let view_id = folder_view
+    .children
+    .into_iter()
+    .find(|v| v.name == "General")
+    .unwrap()
+    .children
+    .into_iter()
+    .find(|v| v.name == "Getting started")
+    .unwrap()
+    .view_id;
+  web_client
+    .api_client
+    .publish_page(
+      workspace_uuid,
+      &view_id,
+      &PublishPageParams {
+        publish_name: Some("pinned-revision".to_string()),
+        visible_database_view_ids: None,
+        comments_enabled: None,
+        duplicate_enabled: None,
+      },
+    )
+    .await
+    .unwrap();
+  let publish_namespace = web_client
+    .api_client
+    .get_workspace_publish_namespace(&workspace_id)
+    .await
+    .unwrap();
+
+  let revision = web_client
+    .api_client
+    .get_publish_revision(workspace_uuid, &view_id)
+    .await
+    .unwrap();
+  assert_eq!(revision.has_unpublished_changes, Some(false));
+  let (etag, blob) = web_client
+    .api_client
+    .get_published_collab_blob_if_modified(&publish_namespace, "pinned-revision", None)
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(etag, format!("\"{}\"", revision.etag));
+
+  // editing the page doesn't change the published revision
+  web_client
+    .open_collab(&workspace_id, &view_id, CollabType::Document)
+    .await;
+  web_client
+    .insert_into(&view_id, "unpublished", "change")
+    .await;
+  web_client
+    .wait_object_sync_complete(&view_id)
+    .await
+    .unwrap();
+  let not_modified = web_client
+    .api_client
+    .get_published_collab_blob_if_modified(&publish_namespace, "pinned-revision", Some(&etag))
+    .await
+    .unwrap();
+  assert!(not_modified.is_none());
+  let revision = web_client
+    .api_client
+    .get_publish_revision(workspace_uuid, &view_id)
+    .await
+    .unwrap();
+  assert_eq!(revision.has_unpublished_changes, Some(true));
+
+  // a scheduled revision only replaces the published one once it's due
+  web_client
+    .api_client
+    .republish_page(
+      workspace_uuid,
+      &view_id,
+      &RepublishPageParams {
+        visible_database_view_ids: None,
+        publish_at: Some(chrono::Utc::now() + chrono::Duration::days(1)),
+      },
+    )
+    .await
+    .unwrap();
+  let scheduled = web_client
+    .api_client
+    .get_publish_revision(workspace_uuid, &view_id)
+    .await
+    .unwrap();
+  assert!(scheduled.scheduled_publish_at.is_some());
+  assert_eq!(scheduled.etag, revision.etag);
+
+  // republishing right away replaces the scheduled revision
+  web_client
+    .api_client
+    .republish_page(workspace_uuid, &view_id, &RepublishPageParams::default())
+    .await
+    .unwrap();
+  let republished = web_client
+    .api_client
+    .get_publish_revision(workspace_uuid, &view_id)
+    .await
+    .unwrap();
+  assert_ne!(republished.etag, revision.etag);
+  assert_eq!(republished.has_unpublished_changes, Some(false));
+  assert!(republished.scheduled_publish_at.is_none());
+  let (_, republished_blob) = web_client
+    .api_client
+    .get_published_collab_blob_if_modified(&publish_namespace, "pinned-revision", Some(&etag))
+    .await
+    .unwrap()
+    .unwrap();
+  assert_ne!(republished_blob, blob);
+
+  // the replaced revision stays servable from its revision-addressed URL
+  let replaced_blob = web_client
+    .api_client
+    .get_published_collab_blob_at_revision(&publish_namespace, "pinned-revision", &revision.etag)
+    .await
+    .unwrap();
+  assert_eq!(replaced_blob, blob);
+  let current_blob = web_client
+    .api_client
+    .get_published_collab_blob_at_revision(&publish_namespace, "pinned-revision", &republished.etag)
+    .await
+    .unwrap();
+  assert_eq!(current_blob, republished_blob);
+
+  // but not once the view is unpublished
+  web_client
+    .api_client
+    .unpublish_page(workspace_uuid, &view_id)
+    .await
+    .unwrap();
+  let result = web_client
+    .api_client
+    .get_published_collab_blob_at_revision(&publish_namespace, "pinned-revision", &revision.etag)
+    .await;
+  assert!(result.is_err());
+}
@@ -0,0 +1,229 @@
+use std::collections::HashSet;
+
+use app_error::{AppError, ErrorCode};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use client_api_test::TestClient;
+use database_entity::dto::{AFRole, WorkspaceMemberFilter, WorkspaceMemberSortBy};
+use rand::distributions::{Alphanumeric, DistString};
+use rand::{thread_rng, Rng};
+use shared_entity::dto::page_dto::Cursor;
+
+#[test]
+fn malformed_cursor_is_rejected_test() {
+  let mut rng = thread_rng();
+  let valid = Cursor::encode("workspace_member", &("2025-03-01T00:00:00Z", 42_i64));
+  let mut cursors = vec![
+    Cursor::from(String::new()),
+    Cursor::from("=".repeat(3)),
+    Cursor::from("a".repeat(4096)),
+    // the position of another list
+    Cursor::encode("blob", &("2025-03-01T00:00:00Z", "file")),
+    // the position of another type
+    Cursor::encode("workspace_member", &"42"),
+    Cursor::from(URL_SAFE_NO_PAD.encode(b"{\"k\":\"workspace_member\"}")),
+  ];
+  let mut altered_cursors = Vec::new();
+  for len in 0..valid.as_str().len() {
+    cursors.push(Cursor::from(valid.as_str()[..len].to_string()));
+  }
+  for _ in 0..1000 {
+    let len = rng.gen_range(0..64);
+    cursors.push(Cursor::from(Alphanumeric.sample_string(&mut rng, len)));
+    let bytes = (0..len).map(|_| rng.gen::<u8>()).collect::<Vec<_>>();
+    cursors.push(Cursor::from(URL_SAFE_NO_PAD.encode(bytes)));
+
+    let mut altered = valid.as_str().as_bytes().to_vec();
+    let at = rng.gen_range(0..altered.len());
+    altered[at] = rng.sample(Alphanumeric);
+    altered_cursors.push(Cursor::from(String::from_utf8(altered).unwrap()));
+  }
+
+  for cursor in cursors {
+    let result = cursor.decode::<(String, i64)>("workspace_member");
+    assert!(
+      matches!(result, Err(AppError::InvalidCursor(_))),
+      "cursor {} wasn't rejected as invalid",
+      cursor
+    );
+  }
+  // an altered cursor can still hold a position of the right shape, when it doesn't it's rejected
+  // like any other malformed cursor
+  for cursor in altered_cursors {
+    let result = cursor.decode::<(String, i64)>("workspace_member");
+    assert!(
+      matches!(result, Ok(_) | Err(AppError::InvalidCursor(_))),
+      "cursor {} wasn't rejected as invalid",
+      cursor
+    );
+  }
+  assert_eq!(
+    valid.decode::<(String, i64)>("workspace_member").unwrap(),
+    ("2025-03-01T00:00:00Z".to_string(), 42)
+  );
+}
+
+#[tokio::test]
+async fn list_endpoints_reject_malformed_cursor_test() {
+  let c = TestClient::new_user_without_ws_conn().await;
+  let workspace_id = c.workspace_id().await;
+  let cursor = Cursor::from("not-a-cursor".to_string());
+
+  let err = c
+    .api_client
+    .get_workspace_member_page(&workspace_id, Some(cursor.clone()), None)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidCursor);
+
+  let err = c
+    .api_client
+    .get_workspace_blob_metadata_page(&workspace_id, Some(cursor.clone()), None)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidCursor);
+
+  let err = c
+    .api_client
+    .list_user_notifications(false, Some(cursor), None)
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidCursor);
+
+  // a cursor of another list
+  let err = c
+    .api_client
+    .get_workspace_member_page(
+      &workspace_id,
+      Some(Cursor::encode("blob", &("2025-03-01T00:00:00Z", "file"))),
+      None,
+    )
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidCursor);
+}
+
+#[tokio::test]
+async fn workspace_member_pages_cover_every_member_once_test() {
+  let owner = TestClient::new_user_without_ws_conn().await;
+  let member_1 = TestClient::new_user_without_ws_conn().await;
+  let member_2 = TestClient::new_user_without_ws_conn().await;
+  let workspace_id = owner.workspace_id().await;
+  for member in [&member_1, &member_2] {
+    owner
+      .invite_and_accepted_workspace_member(&workspace_id, member, AFRole::Member)
+      .await
+      .unwrap();
+  }
+
+  let mut emails = Vec::new();
+  let mut cursor = None;
+  loop {
+    let page = owner
+      .api_client
+      .get_workspace_member_page(&workspace_id, cursor, Some(1))
+      .await
+      .unwrap();
+    assert!(page.items.len() <= 1);
+    assert_eq!(page.total, Some(3));
+    emails.extend(page.items.into_iter().map(|m| m.email));
+    cursor = page.next_cursor;
+    if cursor.is_none() {
+      break;
+    }
+  }
+  assert_eq!(emails.len(), 3);
+  let emails = emails.into_iter().collect::<HashSet<_>>();
+  assert_eq!(emails.len(), 3);
+  assert!(emails.contains(&owner.email().await));
+  assert!(emails.contains(&member_1.email().await));
+  assert!(emails.contains(&member_2.email().await));
+}
+
+#[tokio::test]
+async fn workspace_member_pages_filter_and_sort_test() {
+  let owner = TestClient::new_user_without_ws_conn().await;
+  let member_1 = TestClient::new_user_without_ws_conn().await;
+  let member_2 = TestClient::new_user_without_ws_conn().await;
+  let workspace_id = owner.workspace_id().await;
+  for member in [&member_1, &member_2] {
+    owner
+      .invite_and_accepted_workspace_member(&workspace_id, member, AFRole::Member)
+      .await
+      .unwrap();
+  }
+
+  let filter = WorkspaceMemberFilter {
+    role: Some(AFRole::Member),
+    ..Default::default()
+  };
+  let page = owner
+    .api_client
+    .get_workspace_members_paged(&workspace_id, &filter, None, None)
+    .await
+    .unwrap();
+  assert_eq!(page.total, Some(2));
+  assert!(page.items.iter().all(|m| m.role == AFRole::Member));
+
+  let member_1_email = member_1.email().await;
+  let filter = WorkspaceMemberFilter {
+    search: Some(member_1_email.to_uppercase()),
+    ..Default::default()
+  };
+  let page = owner
+    .api_client
+    .get_workspace_members_paged(&workspace_id, &filter, None, None)
+    .await
+    .unwrap();
+  assert_eq!(page.total, Some(1));
+  assert_eq!(page.items[0].email, member_1_email);
+
+  // the order depends on the collation of the database, the descending one is checked against
+  // the ascending one
+  let mut emails_by_order = Vec::new();
+  for descending in [false, true] {
+    let filter = WorkspaceMemberFilter {
+      sort_by: WorkspaceMemberSortBy::Email,
+      descending,
+      ..Default::default()
+    };
+    let mut emails = Vec::new();
+    let mut cursor = None;
+    loop {
+      let page = owner
+        .api_client
+        .get_workspace_members_paged(&workspace_id, &filter, cursor, Some(1))
+        .await
+        .unwrap();
+      assert_eq!(page.total, Some(3));
+      emails.extend(page.items.into_iter().map(|m| m.email));
+      cursor = page.next_cursor;
+      if cursor.is_none() {
+        break;
+      }
+    }
+    assert_eq!(emails.len(), 3);
+    emails_by_order.push(emails);
+  }
+  emails_by_order[1].reverse();
+  assert_eq!(emails_by_order[0], emails_by_order[1]);
+
+  let filter = WorkspaceMemberFilter {
+    sort_by: WorkspaceMemberSortBy::Email,
+    descending: true,
+    ..Default::default()
+  };
+
+  // the cursors of one order aren't accepted for another
+  let page = owner
+    .api_client
+    .get_workspace_members_paged(&workspace_id, &filter, None, Some(1))
+    .await
+    .unwrap();
+  let err = owner
+    .api_client
+    .get_workspace_member_page(&workspace_id, page.next_cursor, Some(1))
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::InvalidCursor);
+}
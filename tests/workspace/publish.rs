@@ -6,7 +6,9 @@ use client_api::entity::{
   PublishInfoMeta,
 };
 use client_api_test::TestClient;
-use client_api_test::{generate_unique_registered_user_client, localhost_client};
+use client_api_test::{
+  admin_user_client, generate_unique_registered_user_client, localhost_client,
+};
 use collab::util::MapExt;
 use collab_database::database::DatabaseBody;
 use collab_database::entity::FieldType;
@@ -18,7 +20,12 @@ use collab_entity::CollabType;
 use collab_folder::{CollabOrigin, Folder, UserId};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use shared_entity::dto::moderation_dto::{
+  ListPublishedViewReportsQuery, PublishedViewReportAction, PublishedViewReportReason,
+  PublishedViewReportStatus, ReportPublishedViewParams, ResolvePublishedViewReportParams,
+};
 use shared_entity::dto::publish_dto::PublishDatabaseData;
+use shared_entity::dto::workspace_dto::{FolderView, PublishedDuplicate};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::thread::sleep;
@@ -944,6 +951,114 @@ async fn workspace_member_publish_unpublish() {
     .unwrap();
 }
 
+#[tokio::test]
+async fn report_and_take_down_published_view() {
+  let publisher = TestClient::new_user_without_ws_conn().await;
+  let workspace_id = publisher.workspace_id().await;
+  let view_id = Uuid::new_v4();
+  let publish_item = || PublishCollabItem {
+    meta: PublishCollabMetadata {
+      view_id,
+      publish_name: "reported-view".to_string(),
+      metadata: MyCustomMetadata {
+        title: "reported".to_string(),
+      },
+    },
+    data: "yrs_encoded_data".as_bytes(),
+    comments_enabled: true,
+    duplicate_enabled: true,
+  };
+  publisher
+    .api_client
+    .publish_collabs::<MyCustomMetadata, &[u8]>(&workspace_id, vec![publish_item()])
+    .await
+    .unwrap();
+  let namespace = publisher
+    .api_client
+    .get_workspace_publish_namespace(&workspace_id)
+    .await
+    .unwrap();
+
+  // readers don't need to be signed in to report a view
+  let reader = localhost_client();
+  reader
+    .report_published_view(
+      &namespace,
+      "reported-view",
+      &ReportPublishedViewParams {
+        reason: PublishedViewReportReason::Phishing,
+        details: Some("asks for passwords".to_string()),
+        reporter_email: None,
+      },
+    )
+    .await
+    .unwrap();
+
+  // only admins can review the reports
+  let err = publisher
+    .api_client
+    .list_published_view_reports(&ListPublishedViewReportsQuery {
+      status: None,
+      offset: None,
+      limit: None,
+    })
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::NotEnoughPermissions);
+
+  let admin = admin_user_client().await;
+  let report = admin
+    .list_published_view_reports(&ListPublishedViewReportsQuery {
+      status: Some(PublishedViewReportStatus::Pending),
+      offset: None,
+      limit: Some(200),
+    })
+    .await
+    .unwrap()
+    .into_iter()
+    .find(|report| report.view_id == view_id)
+    .unwrap();
+  assert_eq!(report.reason, PublishedViewReportReason::Phishing);
+  assert_eq!(report.publish_namespace, namespace);
+
+  let resolved = admin
+    .resolve_published_view_report(
+      &report.report_id,
+      &ResolvePublishedViewReportParams {
+        action: PublishedViewReportAction::TakeDown,
+        note: Some("phishing".to_string()),
+      },
+    )
+    .await
+    .unwrap();
+  assert_eq!(resolved.status, PublishedViewReportStatus::TakenDown);
+  assert!(resolved.namespace_risk_score >= 10);
+
+  // the view is unpublished and can't be published again
+  let err = reader
+    .get_published_collab_blob(&namespace, "reported-view")
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::RecordNotFound);
+  let err = publisher
+    .api_client
+    .publish_collabs::<MyCustomMetadata, &[u8]>(&workspace_id, vec![publish_item()])
+    .await
+    .unwrap_err();
+  assert_eq!(err.code, ErrorCode::PublishTakenDown);
+
+  let risk = admin.get_publish_namespace_risk(&namespace).await.unwrap();
+  assert_eq!(risk.taken_down_reports, 1);
+
+  // until an admin lifts the takedown
+  admin.lift_published_view_takedown(&view_id).await.unwrap();
+  publisher
+    .api_client
+    .publish_collabs::<MyCustomMetadata, &[u8]>(&workspace_id, vec![publish_item()])
+    .await
+    .unwrap();
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct MyCustomMetadata {
   title: String,
@@ -1039,6 +1154,113 @@ async fn duplicate_to_workspace_references() {
   }
 }
 
+#[tokio::test]
+async fn retried_duplicate_to_workspace_does_not_create_duplicates() {
+  let client_1 = TestClient::new_user().await;
+  let workspace_id = client_1.workspace_id().await;
+  let doc_2_view_id = uuid::Uuid::new_v4();
+  let doc_1_view_id: uuid::Uuid = "e8c4f99a-50ea-4758-bca0-afa7df5c2434".parse().unwrap();
+  let grid_1_view_id: uuid::Uuid = "8e062f61-d7ae-4f4b-869c-f44c43149399".parse().unwrap();
+  client_1
+    .publish_collabs(
+      &workspace_id,
+      vec![
+        (
+          doc_2_view_id,
+          published_data::DOC_2_META,
+          published_data::DOC_2_DOC_STATE_HEX,
+        ),
+        (
+          doc_1_view_id,
+          published_data::DOC_1_META,
+          published_data::DOC_1_DOC_STATE_HEX,
+        ),
+        (
+          grid_1_view_id,
+          published_data::GRID_1_META,
+          published_data::GRID_1_DB_DATA,
+        ),
+      ],
+      true,
+      true,
+    )
+    .await;
+
+  let client_2 = TestClient::new_user().await;
+  let workspace_id_2 = client_2.workspace_id().await;
+  let fv = client_2
+    .api_client
+    .get_workspace_folder(&workspace_id_2, Some(5), None)
+    .await
+    .unwrap();
+  let space_id = fv.children[0].view_id.clone();
+  let duplicate = |nonce: &str| PublishedDuplicate {
+    published_view_id: doc_2_view_id.to_string(),
+    dest_view_id: space_id.clone(),
+    nonce: Some(nonce.to_string()),
+  };
+
+  // the same duplication is retried
+  let first = client_2
+    .api_client
+    .duplicate_published_to_workspace(&workspace_id_2, &duplicate("first"))
+    .await
+    .unwrap();
+  let retry = client_2
+    .api_client
+    .duplicate_published_to_workspace(&workspace_id_2, &duplicate("first"))
+    .await
+    .unwrap();
+  assert_eq!(first.view_id, retry.view_id);
+
+  let doc_2_copies = |fv: &FolderView| {
+    fv.children[0]
+      .children
+      .iter()
+      .filter(|v| v.name == "doc2")
+      .cloned()
+      .collect::<Vec<_>>()
+  };
+  tokio::time::sleep(Duration::from_secs(1)).await;
+  let fv = client_2
+    .api_client
+    .get_workspace_folder(&workspace_id_2, Some(5), None)
+    .await
+    .unwrap();
+  let copies = doc_2_copies(&fv);
+  assert_eq!(copies.len(), 1);
+  assert_eq!(copies[0].view_id, first.view_id);
+  let doc_1_copies = copies[0]
+    .children
+    .iter()
+    .filter(|v| v.name == "doc1")
+    .collect::<Vec<_>>();
+  assert_eq!(doc_1_copies.len(), 1);
+  assert_eq!(
+    doc_1_copies[0]
+      .children
+      .iter()
+      .filter(|v| v.name == "grid1")
+      .count(),
+    1
+  );
+
+  // a new duplication creates new pages
+  let second = client_2
+    .api_client
+    .duplicate_published_to_workspace(&workspace_id_2, &duplicate("second"))
+    .await
+    .unwrap();
+  assert_ne!(second.view_id, first.view_id);
+  tokio::time::sleep(Duration::from_secs(1)).await;
+  let fv = client_2
+    .api_client
+    .get_workspace_folder(&workspace_id_2, Some(5), None)
+    .await
+    .unwrap();
+  assert_eq!(doc_2_copies(&fv).len(), 2);
+}
+
 #[tokio::test]
 async fn duplicate_to_workspace_doc_inline_database() {
   let client_1 = TestClient::new_user().await;
@@ -1151,6 +1373,7 @@ async fn duplicate_to_workspace_doc_inline_database() {
       &folder,
       5,
       &HashSet::default(),
+      &HashSet::default(),
     )
     .unwrap();
     let doc_3_fv = folder_view.children[0]